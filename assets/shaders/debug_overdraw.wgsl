@@ -0,0 +1,27 @@
+struct Object {
+    world_matrix: mat4x4<f32>,
+    color: vec3<f32>,
+    joint_offset: u32,
+}
+
+@group(2) @binding(0)
+var<storage> objects: array<Object>;
+
+@group(2) @binding(1)
+var<storage> indirection: array<u32>;
+
+#import vertex::{VertexInput, VertexOutput, transform_vertex};
+
+@vertex
+fn vs_main(in: VertexInput) -> VertexOutput {
+    let object_index = indirection[in.instance];
+    let object = objects[object_index];
+
+    return transform_vertex(in, object.world_matrix, object.color);
+}
+
+// Blended additively, so each surface drawn to a pixel heats it up
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    return vec4(0.1, 0.04, 0.01, 1.0);
+}
@@ -0,0 +1,98 @@
+// Projects the captured faces of a light probe to the first two bands of spherical harmonics,
+// and stores the convolved irradiance in the probe volume
+
+struct ProjectionData {
+    inv_viewproj: array<mat4x4<f32>, 6>,
+    probe: vec3<u32>,
+    capture_resolution: u32,
+}
+
+@group(0) @binding(0)
+var<uniform> data: ProjectionData;
+
+@group(0) @binding(1)
+var capture: texture_2d_array<f32>;
+
+@group(0) @binding(2)
+var sh_r: texture_storage_3d<rgba16float, write>;
+
+@group(0) @binding(3)
+var sh_g: texture_storage_3d<rgba16float, write>;
+
+@group(0) @binding(4)
+var sh_b: texture_storage_3d<rgba16float, write>;
+
+const PI: f32 = 3.14159265359;
+const WORKGROUP_SIZE: u32 = 64u;
+
+const SH_Y0: f32 = 0.282095;
+const SH_Y1: f32 = 0.488603;
+
+var<workgroup> partial_r: array<vec4<f32>, WORKGROUP_SIZE>;
+var<workgroup> partial_g: array<vec4<f32>, WORKGROUP_SIZE>;
+var<workgroup> partial_b: array<vec4<f32>, WORKGROUP_SIZE>;
+var<workgroup> partial_weight: array<f32, WORKGROUP_SIZE>;
+
+@compute @workgroup_size(64)
+fn main(@builtin(local_invocation_index) index: u32) {
+    let size = data.capture_resolution;
+    let face_texels = size * size;
+
+    var r = vec4(0.0);
+    var g = vec4(0.0);
+    var b = vec4(0.0);
+    var weight = 0.0;
+
+    for (var i = index; i < face_texels * 6u; i += WORKGROUP_SIZE) {
+        let face = i / face_texels;
+        let texel = vec2(i % face_texels % size, i % face_texels / size);
+
+        let uv = (vec2<f32>(texel) + 0.5) / f32(size);
+        let ndc = vec2(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0);
+
+        let p = data.inv_viewproj[face] * vec4(ndc, 1.0, 1.0);
+        let dir = normalize(p.xyz / p.w);
+
+        // Texels towards the corners of a face cover a smaller solid angle
+        let solid_angle = 1.0 / pow(1.0 + dot(ndc, ndc), 1.5);
+
+        let radiance = textureLoad(capture, texel, face, 0).rgb;
+        let basis = vec4(SH_Y0, SH_Y1 * dir) * solid_angle;
+
+        r += radiance.r * basis;
+        g += radiance.g * basis;
+        b += radiance.b * basis;
+        weight += solid_angle;
+    }
+
+    partial_r[index] = r;
+    partial_g[index] = g;
+    partial_b[index] = b;
+    partial_weight[index] = weight;
+
+    workgroupBarrier();
+
+    for (var stride = WORKGROUP_SIZE / 2u; stride > 0u; stride /= 2u) {
+        if index < stride {
+            partial_r[index] += partial_r[index + stride];
+            partial_g[index] += partial_g[index + stride];
+            partial_b[index] += partial_b[index + stride];
+            partial_weight[index] += partial_weight[index + stride];
+        }
+
+        workgroupBarrier();
+    }
+
+    if index == 0u {
+        // The weights of all texels add up to the full sphere
+        let normalization = 4.0 * PI / partial_weight[0];
+
+        // Convolving with the clamped cosine lobe, divided by PI to match the irradiance map
+        let convolution = vec4(SH_Y0, vec3(SH_Y1 * 2.0 / 3.0)) * normalization;
+
+        let coords = vec3<i32>(data.probe);
+        textureStore(sh_r, coords, partial_r[0] * convolution);
+        textureStore(sh_g, coords, partial_g[0] * convolution);
+        textureStore(sh_b, coords, partial_b[0] * convolution);
+    }
+}
@@ -36,9 +36,12 @@ fn fragment_color(surface: SurfaceProperties, in: VertexOutput) -> vec4<f32> {
     in_lum.world_normal = world_normal;
     in_lum.tangent_normal = surface.tangent_normal;
 
-    in_lum.albedo = surface.albedo.rgb;
+    // Water pools on upward facing surfaces, darkening porous materials and making them glossy
+    let wetness = globals.wetness * smoothstep(0.0, 0.7, world_normal.y);
+
+    in_lum.albedo = surface.albedo.rgb * mix(1.0, 0.6, wetness * (1.0 - surface.metallic));
     in_lum.metallic = surface.metallic;
-    in_lum.roughness = surface.roughness;
+    in_lum.roughness = mix(surface.roughness, 0.1, wetness);
     in_lum.ao = surface.ao;
 
     in_lum.tbn = tbn;
@@ -47,11 +50,45 @@ fn fragment_color(surface: SurfaceProperties, in: VertexOutput) -> vec4<f32> {
     let luminance = brdf_forward(in_lum);
 
     let color = mix(luminance, in.fog.rgb, in.fog.a) + surface.emissive;
+#ifdef DEBUG_VIEW
+    return debug_color(surface.albedo, world_normal, surface.roughness, surface.metallic, in);
+#else
     return vec4(color, surface.albedo.a);
+#endif
 }
 
 fn fragment_color_unlit(albedo: vec4<f32>, in: VertexOutput) -> vec4<f32> {
     let base_color = albedo;
     let color = mix(base_color.rgb, in.fog.rgb, in.fog.a);
+#ifdef DEBUG_VIEW
+    return debug_color(albedo, normalize(in.normal), 1.0, 0.0, in);
+#else
+    return vec4(color, albedo.a);
+#endif
+}
+
+#ifdef DEBUG_VIEW
+// Distances at which meshes would switch to a lower detail level
+const LOD_DISTANCES = vec3(10.0, 30.0, 80.0);
+
+// Shader permutation for `DebugView`
+fn debug_color(albedo: vec4<f32>, normal: vec3<f32>, roughness: f32, metallic: f32, in: VertexOutput) -> vec4<f32> {
+    var color = vec3(1.0, 0.0, 1.0);
+#if DEBUG_VIEW == 1
+    color = normal * 0.5 + 0.5;
+#endif
+#if DEBUG_VIEW == 2
+    color = albedo.rgb;
+#endif
+#if DEBUG_VIEW == 3
+    color = vec3(roughness, metallic, 0.0);
+#endif
+#if DEBUG_VIEW == 4
+    var lod_colors = array(vec3(0.0, 1.0, 0.0), vec3(1.0, 1.0, 0.0), vec3(1.0, 0.5, 0.0), vec3(1.0, 0.0, 0.0));
+    let distance = length(in.world_pos - globals.camera_pos);
+    let lod = dot(step(LOD_DISTANCES, vec3(distance)), vec3(1.0));
+    color = lod_colors[u32(lod)];
+#endif
     return vec4(color, albedo.a);
 }
+#endif
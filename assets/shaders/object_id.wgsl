@@ -0,0 +1,52 @@
+struct VertexInput {
+    @location(0) pos: vec3<f32>,
+    @location(1) tex_coord: vec2<f32>,
+    @location(2) normal: vec3<f32>,
+    @location(3) tangent: vec4<f32>,
+    @location(4) joints: vec4<u32>,
+    @location(5) weights: vec4<f32>,
+    @builtin(instance_index) instance: u32,
+}
+
+struct VertexOutput {
+    @builtin(position) pos: vec4<f32>,
+    @location(0) @interpolate(flat) object_id: u32,
+}
+
+struct Object {
+    world_matrix: mat4x4<f32>,
+    color: vec3<f32>,
+    joint_offset: u32,
+}
+
+struct Globals {
+    viewproj: mat4x4<f32>,
+}
+
+@group(0) @binding(0)
+var<uniform> globals: Globals;
+
+@group(1) @binding(0)
+var<storage> objects: array<Object>;
+
+@group(1) @binding(1)
+var<storage> indirection: array<u32>;
+
+@vertex
+fn vs_main(in: VertexInput) -> VertexOutput {
+    var out: VertexOutput;
+
+    let object_index = indirection[in.instance];
+    let object = objects[object_index];
+
+    out.pos = globals.viewproj * object.world_matrix * vec4(in.pos, 1.0);
+    // 0 is reserved for the background
+    out.object_id = object_index + 1u;
+
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) u32 {
+    return in.object_id;
+}
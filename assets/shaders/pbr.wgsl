@@ -12,34 +12,72 @@ var<storage> indirection: array<u32>;
 
 #import vertex::{VertexInput, VertexOutput, transform_vertex, Globals, globals};
 
-#ifdef SKINNED
-    @group(2) @binding(2)
-    var<storage> joint_matrices: array<mat4x4<f32>>;
-#endif
-
 @vertex
 fn vs_main(in: VertexInput) -> VertexOutput {
     let object_index = indirection[in.instance];
     let object = objects[object_index];
 
-    var vertex = in;
+    var out = transform_vertex(in, object.world_matrix, object.color);
+#ifdef BINDLESS
+    out.material_index = object_materials[object_index];
+#endif
+    return out;
+}
+
+struct MaterialData {
+    roughness_factor: f32,
+    metallic_factor: f32,
+}
+
+#ifdef BINDLESS
+struct BindlessMaterial {
+    roughness_factor: f32,
+    metallic_factor: f32,
+    albedo: u32,
+    normal: u32,
+    metallic_roughness: u32,
+    ambient_occlusion: u32,
+    displacement: u32,
+    _padding: u32,
+}
+
+@group(3) @binding(0)
+var material_sampler: sampler;
 
-    #ifdef SKINNED
-    var pos = vec3(0f);
+@group(3) @binding(1)
+var textures: binding_array<texture_2d<f32>>;
 
-    for (var i = 0u; i < 4; i++) {
-        let joint: u32 = in.joints[i];
-        let weight: f32 = in.weights[i];
+@group(3) @binding(2)
+var<storage> materials: array<BindlessMaterial>;
 
-        pos += (joint_matrices[object.joint_offset + joint] * vec4(in.pos, 1.0)).xyz * weight;
-    }
+@group(3) @binding(3)
+var<storage> object_materials: array<u32>;
 
-    vertex.pos = pos;
-    #endif
+fn material_data(in: VertexOutput) -> MaterialData {
+    let material = materials[in.material_index];
+    return MaterialData(material.roughness_factor, material.metallic_factor);
+}
 
-    return transform_vertex(vertex, object.world_matrix, object.color);
+fn sample_albedo(in: VertexOutput) -> vec4<f32> {
+    return textureSample(textures[materials[in.material_index].albedo], material_sampler, in.tex_coord);
 }
 
+fn sample_normal(in: VertexOutput) -> vec4<f32> {
+    return textureSample(textures[materials[in.material_index].normal], material_sampler, in.tex_coord);
+}
+
+fn sample_metallic_roughness(in: VertexOutput) -> vec4<f32> {
+    return textureSample(textures[materials[in.material_index].metallic_roughness], material_sampler, in.tex_coord);
+}
+
+fn sample_ao(in: VertexOutput) -> vec4<f32> {
+    return textureSample(textures[materials[in.material_index].ambient_occlusion], material_sampler, in.tex_coord);
+}
+
+fn sample_displacement(in: VertexOutput) -> vec4<f32> {
+    return textureSample(textures[materials[in.material_index].displacement], material_sampler, in.tex_coord);
+}
+#else
 @group(3) @binding(0)
 var material_sampler: sampler;
 
@@ -59,26 +97,47 @@ var ao_texture: texture_2d<f32>;
 var displacement_texture: texture_2d<f32>;
 
 @group(3) @binding(6)
-var<uniform> material_data: MaterialData;
+var<uniform> material_uniforms: MaterialData;
 
-struct MaterialData {
-    roughness_factor: f32,
-    metallic_factor: f32,
+fn material_data(in: VertexOutput) -> MaterialData {
+    return material_uniforms;
+}
+
+fn sample_albedo(in: VertexOutput) -> vec4<f32> {
+    return textureSample(albedo_texture, material_sampler, in.tex_coord);
 }
 
+fn sample_normal(in: VertexOutput) -> vec4<f32> {
+    return textureSample(normal_texture, material_sampler, in.tex_coord);
+}
+
+fn sample_metallic_roughness(in: VertexOutput) -> vec4<f32> {
+    return textureSample(mr_texture, material_sampler, in.tex_coord);
+}
+
+fn sample_ao(in: VertexOutput) -> vec4<f32> {
+    return textureSample(ao_texture, material_sampler, in.tex_coord);
+}
+
+fn sample_displacement(in: VertexOutput) -> vec4<f32> {
+    return textureSample(displacement_texture, material_sampler, in.tex_coord);
+}
+#endif
+
 #import material_pbr::{fragment_color, fragment_color_unlit, SurfaceProperties};
 
 @fragment
 fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
-    let albedo = textureSample(albedo_texture, material_sampler, in.tex_coord);
+    let albedo = sample_albedo(in);
     #ifdef LIT 
-    let ao = textureSample(ao_texture, material_sampler, in.tex_coord).r;
-    let displacement = textureSample(displacement_texture, material_sampler, in.tex_coord).r;
-    let tangent_normal = textureSample(normal_texture, material_sampler, in.tex_coord).rgb * 2f - 1f;
-
-    let metallic_roughness = textureSample(mr_texture, material_sampler, in.tex_coord);
-    let metallic = material_data.metallic_factor * metallic_roughness.b;
-    let roughness = material_data.roughness_factor * metallic_roughness.g;
+    let ao = sample_ao(in).r;
+    let displacement = sample_displacement(in).r;
+    let tangent_normal = sample_normal(in).rgb * 2f - 1f;
+
+    let material = material_data(in);
+    let metallic_roughness = sample_metallic_roughness(in);
+    let metallic = material.metallic_factor * metallic_roughness.b;
+    let roughness = material.roughness_factor * metallic_roughness.g;
     var surface: SurfaceProperties;
 
     surface.albedo = albedo;
@@ -74,6 +74,28 @@ var integrated_brdf: texture_2d<f32>;
 @group(0) @binding(5)
 var environment_sampler: sampler;
 
+struct LightProbeVolume {
+    min: vec3<f32>,
+    enabled: u32,
+    max: vec3<f32>,
+    _padding: f32,
+    resolution: vec3<u32>,
+    _padding2: u32,
+}
+
+// Spherical harmonics of the irradiance, as (constant, x, y, z) for each color channel
+@group(0) @binding(6)
+var light_probe_sh_r: texture_3d<f32>;
+
+@group(0) @binding(7)
+var light_probe_sh_g: texture_3d<f32>;
+
+@group(0) @binding(8)
+var light_probe_sh_b: texture_3d<f32>;
+
+@group(0) @binding(9)
+var<uniform> light_probe_volume: LightProbeVolume;
+
 @group(1) @binding(0)
 var<storage> lights: array<Light>;
 
@@ -211,6 +233,33 @@ fn pbr_luminance(in: PbrLuminance, light: Light) -> vec3<f32> {
     return in_light * (kd * in.albedo / PI + specular) * radiance * ndotl;
 }
 
+/// Diffuse irradiance interpolated from the light probes, fading to the irradiance map outside of
+/// the probe volume
+fn sample_irradiance(world_pos: vec3<f32>, normal: vec3<f32>) -> vec3<f32> {
+    let global = textureSample(irradiance_map, environment_sampler, normal).rgb;
+    if light_probe_volume.enabled == 0u {
+        return global;
+    }
+
+    let cells = vec3<f32>(max(light_probe_volume.resolution, vec3(2u)) - 1u);
+    let t = (world_pos - light_probe_volume.min) / (light_probe_volume.max - light_probe_volume.min);
+
+    // Full weight inside the volume, falling off over one cell outside of it
+    let edge = min(t, 1.0 - t) * cells;
+    let weight = clamp(min(edge.x, min(edge.y, edge.z)) + 1.0, 0.0, 1.0);
+
+    let uvw = (clamp(t, vec3(0.0), vec3(1.0)) * cells + 0.5) / vec3<f32>(light_probe_volume.resolution);
+    let basis = vec4(1.0, normal);
+
+    let probe = vec3(
+        dot(textureSampleLevel(light_probe_sh_r, environment_sampler, uvw, 0.0), basis),
+        dot(textureSampleLevel(light_probe_sh_g, environment_sampler, uvw, 0.0), basis),
+        dot(textureSampleLevel(light_probe_sh_b, environment_sampler, uvw, 0.0), basis),
+    );
+
+    return mix(global, max(probe, vec3(0.0)), weight);
+}
+
 /// Calculate surface color from all incoming light
 fn brdf_forward(in: PbrLuminance) -> vec3<f32> {
     var luminance = vec3(0.0);
@@ -229,7 +278,7 @@ fn brdf_forward(in: PbrLuminance) -> vec3<f32> {
     let env_brdf = textureSample(integrated_brdf, environment_sampler, vec2(max(dot(in.world_normal, in.camera_dir), 0f), in.roughness)).rg;
     let specular = specular_color * (env_brdf.x + env_brdf.y);
 
-    let irradiance = textureSample(irradiance_map, environment_sampler, in.world_normal).rgb;
+    let irradiance = sample_irradiance(in.world_pos, in.world_normal);
     let diffuse = irradiance * in.albedo;
     let ambient_light = (ambient_kd * diffuse + ambient_ks * specular);
 
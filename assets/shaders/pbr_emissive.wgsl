@@ -12,32 +12,12 @@ var<storage> indirection: array<u32>;
 
 #import vertex::{VertexInput, VertexOutput, transform_vertex, Globals, globals};
 
-#ifdef SKINNED
-    @group(2) @binding(1)
-    var<storage> joint_matrices: array<mat4x4<f32>>;
-#endif
-
 @vertex
 fn vs_main(in: VertexInput) -> VertexOutput {
     let object_index = indirection[in.instance];
     let object = objects[object_index];
 
-    var vertex = in;
-
-    #ifdef SKINNED
-    var pos = vec3(0f);
-
-    for (var i = 0u; i < 4; i++) {
-        let joint: u32 = in.joints[i];
-        let weight: f32 = in.weights[i];
-
-        pos += (joint_matrices[joint] * vec4(in.pos, 1.0)).xyz * weight;
-    }
-
-    vertex.pos = pos;
-    #endif
-
-    return transform_vertex(vertex, object.world_matrix, object.color);
+    return transform_vertex(in, object.world_matrix, object.color);
 }
 
 @group(3) @binding(0)
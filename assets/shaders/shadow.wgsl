@@ -32,31 +32,14 @@ var<storage> objects: array<Object>;
 @group(1) @binding(1)
 var<storage> indirection: array<u32>;
 
-#ifdef SKINNED
-    @group(1) @binding(2)
-    var<storage> joint_matrices: array<mat4x4<f32>>;
-#endif
-
 @vertex
 fn vs_main(in: VertexInput) -> VertexOutput {
     var out: VertexOutput;
 
-    var pos = in.pos;
-
     let object_index = indirection[in.instance];
     let object = objects[object_index];
 
-    #ifdef SKINNED
-    pos = vec3(0f);
-    for (var i = 0u; i < 4; i++) {
-        let joint: u32 = in.joints[i];
-        let weight: f32 = in.weights[i];
-
-        pos += (joint_matrices[object.joint_offset + joint] * vec4(in.pos, 1.0)).xyz * weight;
-    }
-    #endif
-
-    let world_position = object.world_matrix * vec4(pos, 1.0);
+    let world_position = object.world_matrix * vec4(in.pos, 1.0);
     out.normal = (object.world_matrix * vec4(in.normal, 0.0)).xyz;
 
     out.pos = globals.viewproj * world_position;
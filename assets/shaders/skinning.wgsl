@@ -0,0 +1,95 @@
+// Applies the joint matrices of each skinned object to the bind pose of its mesh, writing vertices
+// which are drawn like any static mesh
+
+struct SkinJob {
+    source_offset: u32,
+    output_offset: u32,
+    joint_offset: u32,
+    vertex_count: u32,
+}
+
+// Vertices are accessed as words since the packed vertex layout does not follow the alignment of
+// wgsl structs.
+//
+// pos: 0..3, tex_coord: 3..5, normal: 5..8, tangent: 8..12, joints: 12..16, weights: 16..20
+const VERTEX_STRIDE: u32 = 20u;
+const MAX_INFLUENCES: u32 = 8u;
+// The source vertex is followed by the indices and weights of all influences
+const SOURCE_STRIDE: u32 = 36u;
+
+@group(0) @binding(0)
+var<storage> jobs: array<SkinJob>;
+
+@group(0) @binding(1)
+var<storage> joint_matrices: array<mat4x4<f32>>;
+
+@group(0) @binding(2)
+var<storage> source_vertices: array<u32>;
+
+@group(0) @binding(3)
+var<storage, read_write> output_vertices: array<u32>;
+
+fn read_vec3(index: u32) -> vec3<f32> {
+    return vec3(
+        bitcast<f32>(source_vertices[index]),
+        bitcast<f32>(source_vertices[index + 1u]),
+        bitcast<f32>(source_vertices[index + 2u]),
+    );
+}
+
+fn write_vec3(index: u32, value: vec3<f32>) {
+    output_vertices[index] = bitcast<u32>(value.x);
+    output_vertices[index + 1u] = bitcast<u32>(value.y);
+    output_vertices[index + 2u] = bitcast<u32>(value.z);
+}
+
+@compute @workgroup_size(64)
+fn main(@builtin(global_invocation_id) gid: vec3<u32>) {
+    let job = jobs[gid.y];
+    if gid.x >= job.vertex_count {
+        return;
+    }
+
+    let src = (job.source_offset + gid.x) * SOURCE_STRIDE;
+    let dst = (job.output_offset + gid.x) * VERTEX_STRIDE;
+
+    var skin = mat4x4<f32>(vec4(0f), vec4(0f), vec4(0f), vec4(0f));
+    var total_weight = 0f;
+
+    for (var i = 0u; i < MAX_INFLUENCES; i++) {
+        let joint = source_vertices[src + VERTEX_STRIDE + i];
+        let weight = bitcast<f32>(source_vertices[src + VERTEX_STRIDE + MAX_INFLUENCES + i]);
+
+        if weight > 0f {
+            skin += joint_matrices[job.joint_offset + joint] * weight;
+            total_weight += weight;
+        }
+    }
+
+    // Leave vertices without influences in the bind pose rather than collapsing them
+    if total_weight <= 0f {
+        skin = mat4x4<f32>(
+            vec4(1f, 0f, 0f, 0f),
+            vec4(0f, 1f, 0f, 0f),
+            vec4(0f, 0f, 1f, 0f),
+            vec4(0f, 0f, 0f, 1f),
+        );
+    }
+
+    let pos = skin * vec4(read_vec3(src), 1f);
+    let normal = skin * vec4(read_vec3(src + 5u), 0f);
+    let tangent = skin * vec4(read_vec3(src + 8u), 0f);
+
+    write_vec3(dst, pos.xyz);
+    write_vec3(dst + 5u, normalize(normal.xyz));
+    write_vec3(dst + 8u, normalize(tangent.xyz));
+
+    // Texture coordinates, tangent handedness, joints and weights are copied unchanged
+    output_vertices[dst + 3u] = source_vertices[src + 3u];
+    output_vertices[dst + 4u] = source_vertices[src + 4u];
+    output_vertices[dst + 11u] = source_vertices[src + 11u];
+
+    for (var i = 12u; i < VERTEX_STRIDE; i++) {
+        output_vertices[dst + i] = source_vertices[src + i];
+    }
+}
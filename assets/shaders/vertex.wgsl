@@ -23,6 +23,9 @@ struct VertexOutput {
     @location(7) bitangent: vec3<f32>,
     @location(8) fog: vec4<f32>,
     @location(9) color: vec3<f32>,
+#ifdef BINDLESS
+    @location(10) @interpolate(flat) material_index: u32,
+#endif
 }
 
 struct Globals {
@@ -32,6 +35,7 @@ struct Globals {
     camera_pos: vec3<f32>,
     fog_color: vec3<f32>,
     fog_density: f32,
+    wetness: f32,
 }
 
 @group(0) @binding(0)
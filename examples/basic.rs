@@ -2,8 +2,8 @@ use std::f32::consts::{PI, TAU};
 
 use anyhow::Context;
 use flax::{
-    components::child_of, BoxedSystem, Component, Entity, FetchExt, Query, QueryBorrow, System,
-    World,
+    components::child_of, BoxedSystem, CommandBuffer, Component, Entity, FetchExt, Query,
+    QueryBorrow, System, World,
 };
 use glam::{vec3, EulerRot, Mat4, Quat, Vec3};
 use image::{DynamicImage, Rgba};
@@ -13,18 +13,21 @@ use ivy_core::{
     app::PostInitEvent,
     gizmos,
     layer::events::EventRegisterContext,
+    memory::TrackingAllocator,
     palette::{Srgb, WithAlpha},
     profiling::ProfilingLayer,
     update_layer::{FixedTimeStep, Plugin, ScheduleSetBuilder, ScheduledLayer},
     App, EngineLayer, EntityBuilderExt, Layer,
 };
 use ivy_engine::{
-    async_commandbuffer, elapsed_time, engine, main_camera, rotation, world_transform,
-    RigidBodyBundle, TransformBundle,
+    elapsed_time, engine, rotation, tasks, world_transform, RigidBodyBundle, TransformBundle,
 };
 use ivy_game::{
-    free_camera::{setup_camera, FreeCameraPlugin},
+    camera::{update_perspective_projections, CameraPlugin},
+    config::{ConfigFile, ConfigOverrides},
+    free_camera::{setup_camera_with, FreeCameraBindings, FreeCameraPlugin},
     ray_picker::RayPickingPlugin,
+    replay::{ReplayConfig, ReplayLayer},
 };
 use ivy_gltf::{
     animation::{
@@ -37,14 +40,13 @@ use ivy_graphics::texture::{ColorChannel, MetallicRoughnessProcessor, TextureDat
 use ivy_input::layer::InputLayer;
 use ivy_physics::{ColliderBundle, GizmoSettings, PhysicsPlugin};
 use ivy_postprocessing::preconfigured::{
-    pbr::{PbrRenderGraphConfig, SkyboxConfig},
+    pbr::{AntiAliasing, PbrRenderGraphConfig, SkyboxConfig},
     SurfacePbrPipelineDesc, SurfacePbrRenderer,
 };
 use ivy_scene::{GltfNodeExt, NodeMountOptions};
 use ivy_wgpu::{
     components::{
-        environment_data, forward_pass, light_kind, light_params, projection_matrix, shadow_pass,
-        transparent_pass,
+        environment_data, forward_pass, light_kind, light_params, shadow_pass, transparent_pass,
     },
     driver::WinitDriver,
     events::ResizedEvent,
@@ -63,10 +65,12 @@ use tracing_subscriber::{layer::SubscriberExt, registry, util::SubscriberInitExt
 use tracing_tree::HierarchicalLayer;
 use violet::palette::{rgb::Rgb, Hsl, IntoColor};
 use wgpu::TextureFormat;
-use winit::{dpi::LogicalSize, window::WindowAttributes};
 
 const ENABLE_SKYBOX: bool = true;
 
+#[global_allocator]
+static ALLOCATOR: TrackingAllocator = TrackingAllocator;
+
 pub fn main() -> anyhow::Result<()> {
     registry()
         .with(EnvFilter::from_default_env())
@@ -78,52 +82,76 @@ pub fn main() -> anyhow::Result<()> {
         )
         .init();
 
+    let replay = ReplayConfig::from_env()?
+        .map(ReplayLayer::new)
+        .transpose()?;
+    let readback = replay.as_ref().map(|v| v.sender());
+
+    let config = ConfigFile::load("config.toml", ConfigOverrides::from_env()?)?;
+    let render_scale = config.graphics.render_scale;
+    let camera_bindings = FreeCameraBindings::from_config(&config)?;
+
     if let Err(err) = App::builder()
-        .with_driver(WinitDriver::new(
-            WindowAttributes::default()
-                .with_inner_size(LogicalSize::new(1920, 1080))
-                .with_title("Ivy"),
-        ))
+        .with_driver(
+            WinitDriver::new(config.window.window_attributes())
+                .with_frame_limit(config.window.frame_limit()),
+        )
         .with_layer(EngineLayer::new())
+        .with_optional_layer(replay)
         .with_layer(ProfilingLayer::new())
-        .with_layer(GraphicsLayer::new(|world, assets, store, gpu, surface| {
-            Ok(SurfacePbrRenderer::new(
-                world,
-                assets,
-                store,
-                gpu,
-                surface,
-                SurfacePbrPipelineDesc {
-                    pbr_config: PbrRenderGraphConfig {
-                        label: "basic".into(),
-                        shadow_map_config: Some(Default::default()),
-                        msaa: Some(Default::default()),
-                        bloom: Some(Default::default()),
-                        skybox: Some(SkyboxConfig {
-                            hdri: Box::new(AssetPath::new("hdris/EveningSkyHDRI035B_8K-HDR.exr")),
-                            format: TextureFormat::Rgba16Float,
-                        }),
-                        hdr_format: Some(wgpu::TextureFormat::Rgba16Float),
+        .with_layer(
+            GraphicsLayer::new(move |world, assets, store, gpu, surface| {
+                Ok(SurfacePbrRenderer::new(
+                    world,
+                    assets,
+                    store,
+                    gpu,
+                    surface,
+                    SurfacePbrPipelineDesc {
+                        readback: readback.clone(),
+                        pbr_config: PbrRenderGraphConfig {
+                            label: "basic".into(),
+                            shadow_map_config: Some(Default::default()),
+                            anti_aliasing: AntiAliasing::Msaa(Default::default()),
+                            bloom: Some(Default::default()),
+                            depth_of_field: None,
+                            auto_exposure: None,
+                            camera_effects: None,
+                            skybox: Some(SkyboxConfig::hdri(
+                                AssetPath::new("hdris/EveningSkyHDRI035B_8K-HDR.exr"),
+                                TextureFormat::Rgba16Float,
+                            )),
+                            light_probes: None,
+                            hdr_format: Some(wgpu::TextureFormat::Rgba16Float),
+                            camera_targets: Vec::new(),
+                            player_views: Vec::new(),
+                            texture_streaming: None,
+                            async_compute: true,
+                            picking: false,
+                            render_scale,
+                        },
+                        ..Default::default()
                     },
-                    ..Default::default()
-                },
-            ))
-        }))
+                ))
+            })
+            .with_vsync(config.window.vsync),
+        )
         .with_layer(InputLayer::new())
-        .with_layer(LogicLayer::new())
+        .with_layer(LogicLayer::new(camera_bindings))
         .with_layer(
             ScheduledLayer::new(FixedTimeStep::new(0.02))
                 .with_plugin(FreeCameraPlugin)
+                .with_plugin(CameraPlugin)
                 .with_plugin(GizmosPlugin)
                 .with_plugin(AnimationPlugin)
-                .with_plugin(
-                    PhysicsPlugin::new()
-                        .with_gravity(-Vec3::Y * 9.81)
-                        .with_gizmos(GizmoSettings { rigidbody: true }),
-                )
                 .with_plugin(RotateSpotlightPlugin)
                 .with_plugin(RayPickingPlugin),
         )
+        .with_plugin(
+            PhysicsPlugin::new()
+                .with_gravity(-Vec3::Y * 9.81)
+                .with_gizmos(GizmoSettings { rigidbody: true }),
+        )
         .run()
     {
         tracing::error!("{err:?}");
@@ -150,21 +178,23 @@ impl Plugin for GizmosPlugin {
     }
 }
 
-pub struct LogicLayer {}
+pub struct LogicLayer {
+    camera_bindings: FreeCameraBindings,
+}
 
 impl Default for LogicLayer {
     fn default() -> Self {
-        Self::new()
+        Self::new(Default::default())
     }
 }
 
 impl LogicLayer {
-    pub fn new() -> Self {
-        Self {}
+    pub fn new(camera_bindings: FreeCameraBindings) -> Self {
+        Self { camera_bindings }
     }
 
     fn setup_assets(&mut self, world: &mut World, assets: &AssetCache) -> anyhow::Result<()> {
-        let cmd = world.get(engine(), async_commandbuffer()).unwrap().clone();
+        let tasks = world.get(engine(), tasks())?.clone();
         let assets = assets.clone();
 
         const DENSITY: f32 = 10.0;
@@ -172,6 +202,8 @@ impl LogicLayer {
         const RESTITUTION: f32 = 0.1;
 
         let future = async move {
+            let mut cmd = CommandBuffer::new();
+
             let plane_mesh = MeshDesc::content(assets.insert(generate_plane(8.0, Vec3::Y)));
 
             let texture_group = "textures/BaseCollection/Sand";
@@ -202,7 +234,7 @@ impl LogicLayer {
             .load(&assets)
             .await?;
 
-            cmd.lock().spawn(
+            cmd.spawn(
                 Entity::builder()
                     .mount(TransformBundle::new(
                         Vec3::ZERO,
@@ -242,7 +274,7 @@ impl LogicLayer {
                         (shadow_pass(), MaterialData::ShadowMaterial),
                     ],
                 ))
-                .spawn_into(&mut cmd.lock());
+                .spawn_into(&mut cmd);
 
             let albedo = assets
                 .from_path("textures/BaseCollection/Porcelein/albedo.png")
@@ -287,7 +319,7 @@ impl LogicLayer {
                     kind: LightKind::Point,
                     cast_shadow: false,
                 })
-                .spawn_into(&mut cmd.lock());
+                .spawn_into(&mut cmd);
 
             let roughness_count = 16;
             for i in 0..roughness_count {
@@ -305,7 +337,7 @@ impl LogicLayer {
                         + j as f32 * PI / roughness_count as f32;
 
                     let radius = 8.0 + j as f32 * 3.0;
-                    cmd.lock().spawn(
+                    cmd.spawn(
                         Entity::builder()
                             .mount(TransformBundle::default().with_position(vec3(
                                 phi.cos() * radius,
@@ -358,12 +390,15 @@ impl LogicLayer {
                 Vec3::ONE,
             ))
             .set(ivy_gltf::components::animator(), animator)
-            .spawn_into(&mut cmd.lock());
+            .spawn_into(&mut cmd);
 
-            anyhow::Ok(())
+            anyhow::Ok(cmd)
         };
 
-        async_std::task::spawn(future.instrument(tracing::debug_span!("load_assets")));
+        tasks.spawn(
+            future.instrument(tracing::debug_span!("load_assets")),
+            |world, cmd| cmd?.apply(world),
+        );
 
         Ok(())
     }
@@ -453,21 +488,15 @@ impl Layer for LogicLayer {
         events.subscribe(|this, ctx, _: &PostInitEvent| this.setup_assets(ctx.world, ctx.assets));
 
         events.subscribe(|_, ctx, resized: &ResizedEvent| {
-            if let Some(main_camera) = Query::new(projection_matrix().as_mut())
-                .with(main_camera())
-                .borrow(ctx.world)
-                .first()
-            {
-                let aspect =
-                    resized.physical_size.width as f32 / resized.physical_size.height as f32;
-                tracing::info!(%aspect);
-                *main_camera = Mat4::perspective_rh(1.0, aspect, 0.1, 1000.0);
+            let size = resized.physical_size;
+            if !size.is_empty() {
+                update_perspective_projections(ctx.world, size.aspect());
             }
 
             Ok(())
         });
 
-        setup_camera()
+        setup_camera_with(&self.camera_bindings)
             .set(
                 environment_data(),
                 EnvironmentData::new(
@@ -1,5 +1,5 @@
-use flax::{Entity, Query, World};
-use glam::{Mat4, Quat, Vec3};
+use flax::{CommandBuffer, Entity, World};
+use glam::{Quat, Vec3};
 use ivy_assets::{fs::AssetPath, Asset, AssetCache, DynAsyncAssetDesc};
 use ivy_core::{
     app::PostInitEvent,
@@ -7,20 +7,21 @@ use ivy_core::{
     palette::Srgb,
     profiling::ProfilingLayer,
     update_layer::{FixedTimeStep, ScheduledLayer},
-    App, AsyncCommandBuffer, EngineLayer, EntityBuilderExt, Layer, DEG_90,
+    App, EngineLayer, EntityBuilderExt, Layer, DEG_90,
+};
+use ivy_engine::{engine, tasks, TransformBundle};
+use ivy_game::{
+    camera::{update_perspective_projections, CameraPlugin},
+    free_camera::{setup_camera, FreeCameraPlugin},
+    replay::{ReplayConfig, ReplayLayer},
 };
-use ivy_engine::{async_commandbuffer, engine, main_camera, TransformBundle};
-use ivy_game::free_camera::{setup_camera, FreeCameraPlugin};
 use ivy_gltf::Document;
 use ivy_input::layer::InputLayer;
 use ivy_physics::PhysicsPlugin;
 use ivy_postprocessing::preconfigured::{SurfacePbrPipelineDesc, SurfacePbrRenderer};
 use ivy_scene::{GltfNodeExt, NodeMountOptions};
 use ivy_wgpu::{
-    components::{environment_data, projection_matrix},
-    driver::WinitDriver,
-    events::ResizedEvent,
-    layer::GraphicsLayer,
+    components::environment_data, driver::WinitDriver, events::ResizedEvent, layer::GraphicsLayer,
     renderer::EnvironmentData,
 };
 use tracing_subscriber::{layer::SubscriberExt, registry, util::SubscriberInitExt, EnvFilter};
@@ -40,6 +41,11 @@ pub fn main() -> anyhow::Result<()> {
         )
         .init();
 
+    let replay = ReplayConfig::from_env()?
+        .map(ReplayLayer::new)
+        .transpose()?;
+    let readback = replay.as_ref().map(|v| v.sender());
+
     if let Err(err) = App::builder()
         .with_driver(WinitDriver::new(
             WindowAttributes::default()
@@ -48,29 +54,34 @@ pub fn main() -> anyhow::Result<()> {
                 .with_title("Ivy Physics"),
         ))
         .with_layer(EngineLayer::new())
+        .with_optional_layer(replay)
         .with_layer(ProfilingLayer::new())
-        .with_layer(GraphicsLayer::new(|world, assets, store, gpu, surface| {
-            Ok(SurfacePbrRenderer::new(
-                world,
-                assets,
-                store,
-                gpu,
-                surface,
-                SurfacePbrPipelineDesc {
-                    hdri: Some(Box::new(AssetPath::new(
-                        "hdris/HDR_artificial_planet_close.hdr",
-                    ))),
-                    ..Default::default()
-                },
-            ))
-        }))
+        .with_layer(GraphicsLayer::new(
+            move |world, assets, store, gpu, surface| {
+                Ok(SurfacePbrRenderer::new(
+                    world,
+                    assets,
+                    store,
+                    gpu,
+                    surface,
+                    SurfacePbrPipelineDesc {
+                        readback: readback.clone(),
+                        hdri: Some(Box::new(AssetPath::new(
+                            "hdris/HDR_artificial_planet_close.hdr",
+                        ))),
+                        ..Default::default()
+                    },
+                ))
+            },
+        ))
         .with_layer(InputLayer::new())
         .with_layer(LogicLayer)
         .with_layer(
             ScheduledLayer::new(FixedTimeStep::new(0.02))
                 .with_plugin(FreeCameraPlugin)
-                .with_plugin(PhysicsPlugin::new()),
+                .with_plugin(CameraPlugin),
         )
+        .with_plugin(PhysicsPlugin::new())
         .run()
     {
         tracing::error!("{err:?}");
@@ -80,7 +91,9 @@ pub fn main() -> anyhow::Result<()> {
     }
 }
 
-async fn setup_objects(cmd: AsyncCommandBuffer, assets: AssetCache) -> anyhow::Result<()> {
+async fn setup_objects(assets: AssetCache) -> anyhow::Result<CommandBuffer> {
+    let mut cmd = CommandBuffer::new();
+
     let document: Asset<Document> = AssetPath::new("models/droplet.glb")
         .load_async(&assets)
         .await?;
@@ -100,9 +113,9 @@ async fn setup_objects(cmd: AsyncCommandBuffer, assets: AssetCache) -> anyhow::R
                 .with_position(-Vec3::Z)
                 .with_rotation(Quat::from_axis_angle(Vec3::Y, -DEG_90)),
         )
-        .spawn_into(&mut cmd.lock());
+        .spawn_into(&mut cmd);
 
-    Ok(())
+    Ok(cmd)
 }
 
 struct LogicLayer;
@@ -115,27 +128,19 @@ impl Layer for LogicLayer {
         mut events: EventRegisterContext<Self>,
     ) -> anyhow::Result<()> {
         events.subscribe(|_, ctx, _: &PostInitEvent| {
-            async_std::task::spawn(setup_objects(
-                ctx.world
-                    .get(engine(), async_commandbuffer())
-                    .unwrap()
-                    .clone(),
-                ctx.assets.clone(),
-            ));
+            ctx.world
+                .get(engine(), tasks())?
+                .spawn(setup_objects(ctx.assets.clone()), |world, cmd| {
+                    cmd?.apply(world)
+                });
 
             Ok(())
         });
 
         events.subscribe(|_, ctx, resized: &ResizedEvent| {
-            if let Some(main_camera) = Query::new(projection_matrix().as_mut())
-                .with(main_camera())
-                .borrow(ctx.world)
-                .first()
-            {
-                let aspect =
-                    resized.physical_size.width as f32 / resized.physical_size.height as f32;
-                tracing::info!(%aspect);
-                *main_camera = Mat4::perspective_rh(1.0, aspect, 0.1, 1000.0);
+            let size = resized.physical_size;
+            if !size.is_empty() {
+                update_perspective_projections(ctx.world, size.aspect());
             }
 
             Ok(())
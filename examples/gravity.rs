@@ -1,5 +1,5 @@
-use flax::{Entity, Query, World};
-use glam::{vec3, EulerRot, Mat4, Quat, Vec3};
+use flax::{Entity, World};
+use glam::{vec3, EulerRot, Quat, Vec3};
 use ivy_assets::AssetCache;
 use ivy_core::{
     app::PostInitEvent,
@@ -9,8 +9,12 @@ use ivy_core::{
     update_layer::{FixedTimeStep, ScheduledLayer},
     App, Color, ColorExt, EngineLayer, EntityBuilderExt, Layer,
 };
-use ivy_engine::{is_static, main_camera, RigidBodyBundle, TransformBundle};
-use ivy_game::free_camera::{setup_camera, FreeCameraPlugin};
+use ivy_engine::{is_static, RigidBodyBundle, TransformBundle};
+use ivy_game::{
+    camera::{update_perspective_projections, CameraPlugin},
+    free_camera::{setup_camera, FreeCameraPlugin},
+    replay::{ReplayConfig, ReplayLayer},
+};
 use ivy_graphics::texture::TextureData;
 use ivy_input::layer::InputLayer;
 use ivy_physics::{
@@ -45,6 +49,11 @@ pub fn main() -> anyhow::Result<()> {
         )
         .init();
 
+    let replay = ReplayConfig::from_env()?
+        .map(ReplayLayer::new)
+        .transpose()?;
+    let readback = replay.as_ref().map(|v| v.sender());
+
     if let Err(err) = App::builder()
         .with_driver(WinitDriver::new(
             WindowAttributes::default()
@@ -52,31 +61,37 @@ pub fn main() -> anyhow::Result<()> {
                 .with_title("Ivy Physics"),
         ))
         .with_layer(EngineLayer::new())
+        .with_optional_layer(replay)
         .with_layer(ProfilingLayer::new())
-        .with_layer(GraphicsLayer::new(|world, assets, store, gpu, surface| {
-            Ok(SurfacePbrRenderer::new(
-                world,
-                assets,
-                store,
-                gpu,
-                surface,
-                SurfacePbrPipelineDesc {
-                    hdri: None,
-                    ui_instance: None,
-                    pbr_config: Default::default(),
-                },
-            ))
-        }))
+        .with_layer(GraphicsLayer::new(
+            move |world, assets, store, gpu, surface| {
+                Ok(SurfacePbrRenderer::new(
+                    world,
+                    assets,
+                    store,
+                    gpu,
+                    surface,
+                    SurfacePbrPipelineDesc {
+                        readback: readback.clone(),
+                        hdri: None,
+                        ui_instance: None,
+                        world_ui: None,
+                        pbr_config: Default::default(),
+                    },
+                ))
+            },
+        ))
         .with_layer(InputLayer::new())
         .with_layer(LogicLayer)
         .with_layer(
             ScheduledLayer::new(FixedTimeStep::new(0.02))
                 .with_plugin(FreeCameraPlugin)
-                .with_plugin(
-                    PhysicsPlugin::new()
-                        .with_gizmos(ivy_physics::GizmoSettings { rigidbody: true })
-                        .with_gravity(-Vec3::Y),
-                ),
+                .with_plugin(CameraPlugin),
+        )
+        .with_plugin(
+            PhysicsPlugin::new()
+                .with_gizmos(ivy_physics::GizmoSettings { rigidbody: true })
+                .with_gravity(-Vec3::Y),
         )
         .run()
     {
@@ -229,14 +244,9 @@ impl Layer for LogicLayer {
         });
 
         events.subscribe(|_, ctx, resized: &ResizedEvent| {
-            if let Some(main_camera) = Query::new(projection_matrix().as_mut())
-                .with(main_camera())
-                .borrow(ctx.world)
-                .first()
-            {
-                let aspect =
-                    resized.physical_size.width as f32 / resized.physical_size.height as f32;
-                *main_camera = Mat4::perspective_rh(1.0, aspect, 0.1, 1000.0);
+            let size = resized.physical_size;
+            if !size.is_empty() {
+                update_perspective_projections(ctx.world, size.aspect());
             }
 
             Ok(())
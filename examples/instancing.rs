@@ -1,7 +1,10 @@
 use std::iter::repeat;
 
-use flax::{component, BatchSpawn, FetchExt, Query, System, World};
-use glam::{vec3, Mat4, Quat, Vec3};
+use flax::{
+    component, fetch::Source, BatchSpawn, BoxedSystem, CommandBuffer, Component, Entity, FetchExt,
+    Query, QueryBorrow, System, World,
+};
+use glam::{vec3, Mat4, Quat, Vec2, Vec3};
 use itertools::iproduct;
 use ivy_assets::{fs::AssetPath, AssetCache};
 use ivy_core::{
@@ -9,30 +12,39 @@ use ivy_core::{
     layer::events::EventRegisterContext,
     palette::Srgb,
     profiling::ProfilingLayer,
-    update_layer::{FixedTimeStep, Plugin, ScheduledLayer},
-    App, Color, ColorExt, EngineLayer, Layer,
+    update_layer::{FixedTimeStep, Plugin, ScheduleSetBuilder, ScheduledLayer},
+    App, Color, ColorExt, EngineLayer, EntityBuilderExt, Layer,
 };
 use ivy_engine::{
-    color, elapsed_time, engine, main_camera, parent_transform, position, rotation, scale,
-    world_transform,
+    color, elapsed_time, engine, parent_transform, position, rotation, scale, world_transform,
+};
+use ivy_game::{
+    camera::{perspective_camera, update_perspective_projections, CameraPlugin, PerspectiveCamera},
+    free_camera::{setup_camera, FreeCameraPlugin},
+    replay::{ReplayConfig, ReplayLayer},
 };
-use ivy_game::free_camera::{setup_camera, FreeCameraPlugin};
 use ivy_gltf::animation::plugin::AnimationPlugin;
-use ivy_input::layer::InputLayer;
+use ivy_input::{
+    components::input_state, layer::InputLayer, types::MouseButton, Action, CursorPositionBinding,
+    InputState, MouseButtonBinding,
+};
 use ivy_physics::{GizmoSettings, PhysicsPlugin};
 use ivy_postprocessing::preconfigured::{
-    pbr::{PbrRenderGraphConfig, SkyboxConfig},
+    pbr::{AntiAliasing, PbrRenderGraphConfig, SkyboxConfig},
     SurfacePbrPipelineDesc, SurfacePbrRenderer,
 };
 use ivy_wgpu::{
-    components::{environment_data, forward_pass, projection_matrix, shadow_pass},
+    components::{environment_data, forward_pass, picker, shadow_pass},
     driver::WinitDriver,
     events::ResizedEvent,
     layer::GraphicsLayer,
     material_desc::{MaterialData, PbrMaterialData},
     mesh_desc::MeshDesc,
     primitives::{CubePrimitive, UvSpherePrimitive},
-    renderer::EnvironmentData,
+    renderer::{
+        picking::{PendingPick, Picker},
+        EnvironmentData,
+    },
 };
 use tracing_subscriber::{layer::SubscriberExt, registry, util::SubscriberInitExt, EnvFilter};
 use tracing_tree::HierarchicalLayer;
@@ -52,6 +64,11 @@ pub fn main() -> anyhow::Result<()> {
         )
         .init();
 
+    let replay = ReplayConfig::from_env()?
+        .map(ReplayLayer::new)
+        .transpose()?;
+    let readback = replay.as_ref().map(|v| v.sender());
+
     if let Err(err) = App::builder()
         .with_driver(WinitDriver::new(
             WindowAttributes::default()
@@ -59,42 +76,58 @@ pub fn main() -> anyhow::Result<()> {
                 .with_title("Ivy"),
         ))
         .with_layer(EngineLayer::new())
+        .with_optional_layer(replay)
         .with_layer(ProfilingLayer::new())
-        .with_layer(GraphicsLayer::new(|world, assets, store, gpu, surface| {
-            Ok(SurfacePbrRenderer::new(
-                world,
-                assets,
-                store,
-                gpu,
-                surface,
-                SurfacePbrPipelineDesc {
-                    pbr_config: PbrRenderGraphConfig {
-                        label: "basic".into(),
-                        shadow_map_config: Some(Default::default()),
-                        msaa: Some(Default::default()),
-                        bloom: Some(Default::default()),
-                        skybox: Some(SkyboxConfig {
-                            hdri: Box::new(AssetPath::new("hdris/EveningSkyHDRI035B_8K-HDR.exr")),
-                            format: TextureFormat::Rgba16Float,
-                        }),
-                        hdr_format: Some(wgpu::TextureFormat::Rgba16Float),
+        .with_layer(GraphicsLayer::new(
+            move |world, assets, store, gpu, surface| {
+                Ok(SurfacePbrRenderer::new(
+                    world,
+                    assets,
+                    store,
+                    gpu,
+                    surface,
+                    SurfacePbrPipelineDesc {
+                        readback: readback.clone(),
+                        pbr_config: PbrRenderGraphConfig {
+                            label: "basic".into(),
+                            shadow_map_config: Some(Default::default()),
+                            anti_aliasing: AntiAliasing::Msaa(Default::default()),
+                            bloom: Some(Default::default()),
+                            depth_of_field: None,
+                            auto_exposure: None,
+                            camera_effects: None,
+                            skybox: Some(SkyboxConfig::hdri(
+                                AssetPath::new("hdris/EveningSkyHDRI035B_8K-HDR.exr"),
+                                TextureFormat::Rgba16Float,
+                            )),
+                            light_probes: None,
+                            hdr_format: Some(wgpu::TextureFormat::Rgba16Float),
+                            camera_targets: Vec::new(),
+                            player_views: Vec::new(),
+                            texture_streaming: None,
+                            async_compute: true,
+                            picking: true,
+                            render_scale: 1.0,
+                        },
+                        ..Default::default()
                     },
-                    ..Default::default()
-                },
-            ))
-        }))
+                ))
+            },
+        ))
         .with_layer(InputLayer::new())
         .with_layer(LogicLayer::new())
         .with_layer(
             ScheduledLayer::new(FixedTimeStep::new(0.02))
                 .with_plugin(FreeCameraPlugin)
+                .with_plugin(CameraPlugin)
                 .with_plugin(AnimationPlugin)
                 .with_plugin(DynamicsPlugin)
-                .with_plugin(
-                    PhysicsPlugin::new()
-                        .with_gravity(Vec3::ZERO)
-                        .with_gizmos(GizmoSettings { rigidbody: true }),
-                ),
+                .with_plugin(SelectionPlugin),
+        )
+        .with_plugin(
+            PhysicsPlugin::new()
+                .with_gravity(Vec3::ZERO)
+                .with_gizmos(GizmoSettings { rigidbody: true }),
         )
         .run()
     {
@@ -170,21 +203,19 @@ impl Layer for LogicLayer {
         events.subscribe(|this, ctx, _: &PostInitEvent| this.setup_objects(ctx.world, ctx.assets));
 
         events.subscribe(|_, ctx, resized: &ResizedEvent| {
-            if let Some(main_camera) = Query::new(projection_matrix().as_mut())
-                .with(main_camera())
-                .borrow(ctx.world)
-                .first()
-            {
-                let aspect =
-                    resized.physical_size.width as f32 / resized.physical_size.height as f32;
-                tracing::info!(%aspect);
-                *main_camera = Mat4::perspective_rh(1.0, aspect, 0.1, 5000.0);
+            let size = resized.physical_size;
+            if !size.is_empty() {
+                update_perspective_projections(ctx.world, size.aspect());
             }
 
             Ok(())
         });
 
         setup_camera()
+            .set(
+                perspective_camera(),
+                PerspectiveCamera::default().with_clip_planes(0.1, 5000.0),
+            )
             .set(
                 environment_data(),
                 EnvironmentData::new(
@@ -233,3 +264,84 @@ impl Plugin for DynamicsPlugin {
         Ok(())
     }
 }
+
+component! {
+    select_action: bool,
+    select_cursor_action: Vec2,
+}
+
+/// Highlights the sphere under the cursor when clicked.
+///
+/// The spheres have no colliders, so they are picked from the id buffer rather than through a
+/// physics raycast.
+pub struct SelectionPlugin;
+
+impl Plugin for SelectionPlugin {
+    fn install(
+        &self,
+        world: &mut World,
+        _: &AssetCache,
+        schedules: &mut ScheduleSetBuilder,
+    ) -> anyhow::Result<()> {
+        let mut select = Action::new();
+        select.add(MouseButtonBinding::new(MouseButton::Left));
+
+        let mut cursor_position = Action::new();
+        cursor_position.add(CursorPositionBinding::new(true));
+
+        Entity::builder()
+            .set(
+                input_state(),
+                InputState::new()
+                    .with_action(select_action(), select)
+                    .with_action(select_cursor_action(), cursor_position),
+            )
+            .set_default(select_action())
+            .set_default(select_cursor_action())
+            .spawn(world);
+
+        schedules.per_tick_mut().with_system(select_system());
+
+        Ok(())
+    }
+}
+
+type SelectQuery = (
+    Source<Component<Picker>, Entity>,
+    Component<bool>,
+    Component<Vec2>,
+);
+
+fn select_system() -> BoxedSystem {
+    let mut was_pressed = false;
+    let mut pending: Option<PendingPick> = None;
+
+    System::builder()
+        .with_cmd_mut()
+        .with_query(Query::new((
+            picker().source(engine()),
+            select_action(),
+            select_cursor_action(),
+        )))
+        .build(
+            move |cmd: &mut CommandBuffer, mut query: QueryBorrow<'_, SelectQuery>| {
+                if let Some(picked) = pending.as_ref().and_then(|v| v.try_get()) {
+                    pending = None;
+
+                    if let Some(id) = picked {
+                        tracing::info!(%id, "selected");
+                        cmd.set(id, color(), Color::red());
+                    }
+                }
+
+                for (picker, &pressed, &cursor_pos) in query.iter() {
+                    if pressed && !was_pressed && pending.is_none() {
+                        pending = Some(picker.pick_normalized(cursor_pos));
+                    }
+
+                    was_pressed = pressed;
+                }
+            },
+        )
+        .boxed()
+}
@@ -1,5 +1,5 @@
-use flax::{Entity, Query, World};
-use glam::{vec3, EulerRot, Mat4, Quat, Vec3};
+use flax::{Entity, World};
+use glam::{vec3, EulerRot, Quat, Vec3};
 use ivy_assets::{fs::AssetPath, AssetCache};
 use ivy_core::{
     app::PostInitEvent,
@@ -9,16 +9,18 @@ use ivy_core::{
     update_layer::{FixedTimeStep, ScheduledLayer},
     App, EngineLayer, EntityBuilderExt, Layer, DEG_180, DEG_45,
 };
-use ivy_engine::{main_camera, RigidBodyBundle, TransformBundle};
-use ivy_game::free_camera::{setup_camera, FreeCameraPlugin};
+use ivy_engine::{RigidBodyBundle, TransformBundle};
+use ivy_game::{
+    camera::{update_perspective_projections, CameraPlugin},
+    free_camera::{setup_camera, FreeCameraPlugin},
+    replay::{ReplayConfig, ReplayLayer},
+};
 use ivy_graphics::texture::TextureData;
 use ivy_input::layer::InputLayer;
 use ivy_physics::{ColliderBundle, PhysicsPlugin};
 use ivy_postprocessing::preconfigured::{SurfacePbrPipelineDesc, SurfacePbrRenderer};
 use ivy_wgpu::{
-    components::{
-        cast_shadow, environment_data, forward_pass, light_kind, light_params, projection_matrix,
-    },
+    components::{cast_shadow, environment_data, forward_pass, light_kind, light_params},
     driver::WinitDriver,
     events::ResizedEvent,
     layer::GraphicsLayer,
@@ -45,6 +47,11 @@ pub fn main() -> anyhow::Result<()> {
         )
         .init();
 
+    let replay = ReplayConfig::from_env()?
+        .map(ReplayLayer::new)
+        .transpose()?;
+    let readback = replay.as_ref().map(|v| v.sender());
+
     if let Err(err) = App::builder()
         .with_driver(WinitDriver::new(
             WindowAttributes::default()
@@ -52,32 +59,37 @@ pub fn main() -> anyhow::Result<()> {
                 .with_title("Ivy Physics"),
         ))
         .with_layer(EngineLayer::new())
+        .with_optional_layer(replay)
         .with_layer(ProfilingLayer::new())
-        .with_layer(GraphicsLayer::new(|world, assets, store, gpu, surface| {
-            Ok(SurfacePbrRenderer::new(
-                world,
-                assets,
-                store,
-                gpu,
-                surface,
-                SurfacePbrPipelineDesc {
-                    hdri: Some(Box::new(AssetPath::new(
-                        "hdris/HDR_artificial_planet_close.hdr",
-                    ))),
-                    ..Default::default()
-                },
-            ))
-        }))
+        .with_layer(GraphicsLayer::new(
+            move |world, assets, store, gpu, surface| {
+                Ok(SurfacePbrRenderer::new(
+                    world,
+                    assets,
+                    store,
+                    gpu,
+                    surface,
+                    SurfacePbrPipelineDesc {
+                        readback: readback.clone(),
+                        hdri: Some(Box::new(AssetPath::new(
+                            "hdris/HDR_artificial_planet_close.hdr",
+                        ))),
+                        ..Default::default()
+                    },
+                ))
+            },
+        ))
         .with_layer(InputLayer::new())
         .with_layer(LogicLayer)
         .with_layer(
             ScheduledLayer::new(FixedTimeStep::new(0.02))
                 .with_plugin(FreeCameraPlugin)
-                .with_plugin(
-                    PhysicsPlugin::new()
-                        .with_gravity(Vec3::ZERO)
-                        .with_gizmos(ivy_physics::GizmoSettings { rigidbody: true }),
-                ),
+                .with_plugin(CameraPlugin),
+        )
+        .with_plugin(
+            PhysicsPlugin::new()
+                .with_gravity(Vec3::ZERO)
+                .with_gizmos(ivy_physics::GizmoSettings { rigidbody: true }),
         )
         .run()
     {
@@ -209,15 +221,9 @@ impl Layer for LogicLayer {
         });
 
         events.subscribe(|_, ctx, resized: &ResizedEvent| {
-            if let Some(main_camera) = Query::new(projection_matrix().as_mut())
-                .with(main_camera())
-                .borrow(ctx.world)
-                .first()
-            {
-                let aspect =
-                    resized.physical_size.width as f32 / resized.physical_size.height as f32;
-                tracing::info!(%aspect);
-                *main_camera = Mat4::perspective_rh(1.0, aspect, 0.1, 1000.0);
+            let size = resized.physical_size;
+            if !size.is_empty() {
+                update_perspective_projections(ctx.world, size.aspect());
             }
 
             Ok(())
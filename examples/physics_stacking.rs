@@ -1,5 +1,5 @@
-use flax::{Entity, Query, World};
-use glam::{vec3, EulerRot, Mat4, Quat, Vec3};
+use flax::{Entity, World};
+use glam::{vec3, EulerRot, Quat, Vec3};
 use ivy_assets::{fs::AssetPath, AssetCache};
 use ivy_core::{
     app::PostInitEvent,
@@ -9,10 +9,12 @@ use ivy_core::{
     update_layer::{FixedTimeStep, ScheduledLayer},
     App, Color, ColorExt, EngineLayer, EntityBuilderExt, Layer,
 };
-use ivy_engine::{is_static, main_camera, rotation, scale, RigidBodyBundle, TransformBundle};
+use ivy_engine::{is_static, rotation, scale, RigidBodyBundle, TransformBundle};
 use ivy_game::{
+    camera::{perspective_camera, update_perspective_projections, CameraPlugin, PerspectiveCamera},
     free_camera::{setup_camera, FreeCameraPlugin},
     ray_picker::RayPickingPlugin,
+    replay::{ReplayConfig, ReplayLayer},
 };
 use ivy_graphics::texture::TextureData;
 use ivy_input::layer::InputLayer;
@@ -51,6 +53,11 @@ pub fn main() -> anyhow::Result<()> {
         )
         .init();
 
+    let replay = ReplayConfig::from_env()?
+        .map(ReplayLayer::new)
+        .transpose()?;
+    let readback = replay.as_ref().map(|v| v.sender());
+
     if let Err(err) = App::builder()
         .with_driver(WinitDriver::new(
             WindowAttributes::default()
@@ -58,30 +65,35 @@ pub fn main() -> anyhow::Result<()> {
                 .with_title("Ivy Physics"),
         ))
         .with_layer(EngineLayer::new())
+        .with_optional_layer(replay)
         .with_layer(ProfilingLayer::new())
-        .with_layer(GraphicsLayer::new(|world, assets, store, gpu, surface| {
-            Ok(SurfacePbrRenderer::new(
-                world,
-                assets,
-                store,
-                gpu,
-                surface,
-                SurfacePbrPipelineDesc {
-                    hdri: Some(Box::new(AssetPath::new(
-                        "hdris/kloofendal_48d_partly_cloudy_puresky_2k.hdr",
-                    ))),
-                    ..Default::default()
-                },
-            ))
-        }))
+        .with_layer(GraphicsLayer::new(
+            move |world, assets, store, gpu, surface| {
+                Ok(SurfacePbrRenderer::new(
+                    world,
+                    assets,
+                    store,
+                    gpu,
+                    surface,
+                    SurfacePbrPipelineDesc {
+                        readback: readback.clone(),
+                        hdri: Some(Box::new(AssetPath::new(
+                            "hdris/kloofendal_48d_partly_cloudy_puresky_2k.hdr",
+                        ))),
+                        ..Default::default()
+                    },
+                ))
+            },
+        ))
         .with_layer(InputLayer::new())
         .with_layer(LogicLayer)
-        .with_layer(ScheduledLayer::new(FixedTimeStep::new(0.02)).with_plugin(FreeCameraPlugin))
         .with_layer(
             ScheduledLayer::new(FixedTimeStep::new(0.02))
-                .with_plugin(PhysicsPlugin::new().with_gravity(-Vec3::Y * 9.81))
+                .with_plugin(FreeCameraPlugin)
+                .with_plugin(CameraPlugin)
                 .with_plugin(RayPickingPlugin),
         )
+        .with_plugin(PhysicsPlugin::new().with_gravity(-Vec3::Y * 9.81))
         .run()
     {
         tracing::error!("{err:?}");
@@ -235,20 +247,19 @@ impl Layer for LogicLayer {
         });
 
         events.subscribe(|_, ctx, resized: &ResizedEvent| {
-            if let Some(main_camera) = Query::new(projection_matrix().as_mut())
-                .with(main_camera())
-                .borrow(ctx.world)
-                .first()
-            {
-                let aspect =
-                    resized.physical_size.width as f32 / resized.physical_size.height as f32;
-                *main_camera = Mat4::perspective_rh(1.0, aspect, 0.01, 1000.0);
+            let size = resized.physical_size;
+            if !size.is_empty() {
+                update_perspective_projections(ctx.world, size.aspect());
             }
 
             Ok(())
         });
 
         setup_camera()
+            .set(
+                perspective_camera(),
+                PerspectiveCamera::default().with_clip_planes(0.01, 1000.0),
+            )
             .mount(TransformBundle::new(
                 vec3(0.0, 20.0, 20.0),
                 Quat::IDENTITY,
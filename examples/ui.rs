@@ -1,7 +1,7 @@
 use flax::{
     fetch::Copied, BoxedSystem, Component, Entity, FetchExt, Query, QueryBorrow, System, World,
 };
-use glam::{vec3, EulerRot, Mat4, Quat, Vec2, Vec3};
+use glam::{vec3, EulerRot, Quat, Vec2, Vec3};
 use itertools::Itertools;
 use ivy_assets::{fs::AssetPath, AssetCache};
 use ivy_core::{
@@ -14,8 +14,10 @@ use ivy_core::{
 };
 use ivy_engine::{is_static, main_camera, rotation, scale, RigidBodyBundle, TransformBundle};
 use ivy_game::{
+    camera::{perspective_camera, update_perspective_projections, CameraPlugin, PerspectiveCamera},
     free_camera::{camera_speed, setup_camera, FreeCameraPlugin},
     ray_picker::RayPickingPlugin,
+    replay::{ReplayConfig, ReplayLayer},
 };
 use ivy_graphics::texture::TextureData;
 use ivy_input::layer::InputLayer;
@@ -24,7 +26,7 @@ use ivy_physics::{
     ColliderBundle, PhysicsPlugin,
 };
 use ivy_postprocessing::preconfigured::{SurfacePbrPipelineDesc, SurfacePbrRenderer};
-use ivy_ui::layer::{UiInputLayer, UiUpdateLayer};
+use ivy_ui::UiPlugin;
 use ivy_wgpu::{
     components::*,
     driver::WinitDriver,
@@ -67,10 +69,12 @@ pub fn main() -> anyhow::Result<()> {
         .init();
 
     let ui_state = Mutable::new(UiState::default());
-    let ui_input_layer = UiInputLayer::new(ui_app(ui_state.clone()));
+    let ui = UiPlugin::new(ui_app(ui_state.clone()));
 
-    let ui_layer = UiUpdateLayer::new(ui_input_layer.instance().clone());
-    let ui_instance = ui_layer.instance().clone();
+    let replay = ReplayConfig::from_env()?
+        .map(ReplayLayer::new)
+        .transpose()?;
+    let readback = replay.as_ref().map(|v| v.sender());
 
     if let Err(err) = App::builder()
         .with_driver(WinitDriver::new(
@@ -79,6 +83,7 @@ pub fn main() -> anyhow::Result<()> {
                 .with_title("Ivy UI"),
         ))
         .with_layer(EngineLayer::new())
+        .with_optional_layer(replay)
         .with_layer(ProfilingLayer::new())
         .with_layer(GraphicsLayer::new(
             move |world, assets, store, gpu, surface| {
@@ -89,28 +94,28 @@ pub fn main() -> anyhow::Result<()> {
                     gpu,
                     surface,
                     SurfacePbrPipelineDesc {
+                        readback: readback.clone(),
                         hdri: Some(Box::new(AssetPath::new(
                             "hdris/kloofendal_48d_partly_cloudy_puresky_2k.hdr",
                         ))),
-                        ui_instance: Some(ui_instance.clone()),
                         ..Default::default()
                     },
                 ))
             },
         ))
-        .with_layer(ui_input_layer)
         .with_layer(InputLayer::new())
         .with_layer(LogicLayer)
         .with_layer(
             ScheduledLayer::new(FixedTimeStep::new(0.02))
                 .with_plugin(FreeCameraPlugin)
+                .with_plugin(CameraPlugin)
                 .with_plugin(UiStatePlugin {
                     state: ui_state.clone(),
                 })
-                .with_plugin(PhysicsPlugin::new())
                 .with_plugin(RayPickingPlugin),
         )
-        .with_layer(ui_layer)
+        .with_plugin(PhysicsPlugin::new())
+        .with_plugin(ui)
         .run()
     {
         tracing::error!("{err:?}");
@@ -172,20 +177,19 @@ impl Layer for LogicLayer {
         });
 
         events.subscribe(|_, ctx, resized: &ResizedEvent| {
-            if let Some(main_camera) = Query::new(projection_matrix().as_mut())
-                .with(main_camera())
-                .borrow(ctx.world)
-                .first()
-            {
-                let aspect =
-                    resized.physical_size.width as f32 / resized.physical_size.height as f32;
-                *main_camera = Mat4::perspective_rh(1.0, aspect, 0.01, 1000.0);
+            let size = resized.physical_size;
+            if !size.is_empty() {
+                update_perspective_projections(ctx.world, size.aspect());
             }
 
             Ok(())
         });
 
         setup_camera()
+            .set(
+                perspective_camera(),
+                PerspectiveCamera::default().with_clip_planes(0.01, 1000.0),
+            )
             .mount(TransformBundle::new(
                 vec3(0.0, 20.0, 20.0),
                 Quat::IDENTITY,
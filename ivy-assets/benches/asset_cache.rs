@@ -0,0 +1,75 @@
+use std::convert::Infallible;
+
+use criterion::{black_box, criterion_group, criterion_main, BatchSize, Criterion};
+use ivy_assets::{Asset, AssetCache, AssetDesc};
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct ValueDesc(u32);
+
+impl AssetDesc<u64> for ValueDesc {
+    type Error = Infallible;
+
+    fn create(&self, assets: &AssetCache) -> Result<Asset<u64>, Self::Error> {
+        Ok(assets.insert(self.0 as u64 * 2))
+    }
+}
+
+const ASSET_COUNT: u32 = 1024;
+
+/// Returns a cache with [`ASSET_COUNT`] loaded assets, and the handles keeping them alive
+fn populated_cache() -> (AssetCache, Vec<Asset<u64>>) {
+    let assets = AssetCache::new();
+    let handles = (0..ASSET_COUNT)
+        .map(|i| assets.load(&ValueDesc(i)))
+        .collect();
+
+    (assets, handles)
+}
+
+fn asset_cache(c: &mut Criterion) {
+    let mut group = c.benchmark_group("asset_cache");
+
+    let (assets, _handles) = populated_cache();
+
+    group.bench_function("load_cached", |b| {
+        let mut i = 0;
+        b.iter(|| {
+            i = (i + 1) % ASSET_COUNT;
+            black_box(assets.load(&ValueDesc(i)))
+        })
+    });
+
+    group.bench_function("get_missing", |b| {
+        b.iter(|| black_box(assets.get(&ValueDesc(ASSET_COUNT + 1))))
+    });
+
+    group.bench_function("load_new", |b| {
+        b.iter_batched(
+            AssetCache::new,
+            |assets| {
+                for i in 0..64 {
+                    black_box(assets.load(&ValueDesc(i)));
+                }
+
+                assets
+            },
+            BatchSize::SmallInput,
+        )
+    });
+
+    group.bench_function("insert", |b| {
+        b.iter_batched(
+            AssetCache::new,
+            |assets| {
+                let handles = (0..64).map(|i| assets.insert(i as u64)).collect::<Vec<_>>();
+                (assets, handles)
+            },
+            BatchSize::SmallInput,
+        )
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, asset_cache);
+criterion_main!(benches);
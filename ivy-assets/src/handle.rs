@@ -7,7 +7,6 @@ use std::{
 
 use super::AssetId;
 
-#[derive(Debug)]
 pub struct WeakHandle<T: ?Sized> {
     pub(crate) id: AssetId,
     pub(crate) value: Weak<T>,
@@ -47,6 +46,33 @@ impl<T: ?Sized> PartialEq for WeakHandle<T> {
 
 impl<T: ?Sized> Eq for WeakHandle<T> {}
 
+impl<T: ?Sized> std::fmt::Debug for WeakHandle<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("WeakHandle").field(&self.id()).finish()
+    }
+}
+
+impl<T: ?Sized> Hash for WeakHandle<T> {
+    #[inline]
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.id.hash(state);
+    }
+}
+
+impl<T: ?Sized> PartialOrd for WeakHandle<T> {
+    #[inline]
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<T: ?Sized> Ord for WeakHandle<T> {
+    #[inline]
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.id.cmp(&other.id)
+    }
+}
+
 /// Keep-alive handle to an asset
 ///
 /// Works like an `Arc` with a unique identifier to allow it to be compared and sorted regardless of `T`.
@@ -26,12 +26,12 @@ use futures::{
     future::{BoxFuture, Shared, WeakShared},
     FutureExt, TryFutureExt,
 };
-pub use handle::Asset;
+pub use handle::{Asset, WeakHandle};
 use image::DynamicImage;
 use parking_lot::{RwLock, RwLockReadGuard};
 use service::Service;
 
-use self::{cell::AssetCell, handle::WeakHandle};
+use self::cell::AssetCell;
 
 slotmap::new_key_type! {
     pub struct AssetId;
@@ -111,6 +111,17 @@ impl AssetCache {
         }
 
         // Load the asset and insert it to get a handle
+        self.reload(desc)
+    }
+
+    /// Loads the asset again, replacing the asset associated with `desc`.
+    ///
+    /// Existing handles keep the previous asset, while subsequent loads return the new asset.
+    pub fn reload<K, V>(&self, desc: &K) -> Result<Asset<V>, K::Error>
+    where
+        K: ?Sized + AssetDesc<V>,
+        V: 'static + Send + Sync,
+    {
         let value = desc.create(self)?;
 
         self.inner
@@ -294,6 +305,14 @@ impl AssetCache {
                 .expect("Service type mismatch")
         })
     }
+
+    /// Returns the service if it has been registered
+    pub fn try_service<S: Service>(&self) -> Option<impl Deref<Target = S> + '_ + Send> {
+        RwLockReadGuard::try_map(self.inner.services.read(), |v| {
+            v.get(&TypeId::of::<S>())?.as_any().downcast_ref::<S>()
+        })
+        .ok()
+    }
 }
 
 impl Default for AssetCache {
@@ -484,6 +503,11 @@ mod tests {
         drop(bar);
 
         assert!(assets.get::<_, TestAsset>(&"Bar".to_string()).is_none());
+
+        let reloaded: Asset<TestAsset> = assets.reload(&"Foo").unwrap();
+        assert_ne!(reloaded, content);
+        let content5: Asset<TestAsset> = assets.load(&"Foo");
+        assert_eq!(content5, reloaded);
     }
 
     #[test]
@@ -0,0 +1,217 @@
+use std::time::Duration;
+
+use kira::{
+    effect::{
+        filter::{FilterBuilder, FilterHandle, FilterMode},
+        reverb::{ReverbBuilder, ReverbHandle},
+    },
+    manager::{backend::DefaultBackend, AudioManager},
+    track::{TrackBuilder, TrackHandle, TrackRoutes},
+    tween::Tween,
+    Volume,
+};
+
+use crate::{effects::MAX_CUTOFF, AudioEffects, ReverbZone};
+
+/// Time to transition between reverb zones
+const REVERB_FADE: Duration = Duration::from_millis(500);
+
+/// Cutoff of the low-pass filter of sources which are occluded from the listener
+const OCCLUSION_CUTOFF: f64 = 800.0;
+
+/// Volume group which a sound is mixed into
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AudioBus {
+    #[default]
+    Effects,
+    Music,
+    Voice,
+}
+
+/// Volumes of the buses, in the range `0..=1`.
+///
+/// Set on the engine entity using [`audio_volumes`](crate::components::audio_volumes) to change
+/// the volumes at runtime.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AudioVolumes {
+    pub master: f32,
+    pub music: f32,
+    pub effects: f32,
+    pub voice: f32,
+}
+
+impl AudioVolumes {
+    /// Returns the volume of `bus`, excluding the master volume
+    pub fn bus(&self, bus: AudioBus) -> f32 {
+        match bus {
+            AudioBus::Effects => self.effects,
+            AudioBus::Music => self.music,
+            AudioBus::Voice => self.voice,
+        }
+    }
+}
+
+impl Default for AudioVolumes {
+    fn default() -> Self {
+        Self {
+            master: 1.0,
+            music: 1.0,
+            effects: 1.0,
+            voice: 1.0,
+        }
+    }
+}
+
+/// Reverb parameters of the environment buses
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Reverb {
+    mix: f32,
+    feedback: f32,
+    damping: f32,
+}
+
+/// Effects of a bus affected by the environment
+struct EnvironmentEffects {
+    filter: FilterHandle,
+    reverb: ReverbHandle,
+}
+
+pub(crate) struct Buses {
+    effects: TrackHandle,
+    /// Muffles occluded spatial sounds before mixing them into the effects bus
+    occluded: TrackHandle,
+    music: TrackHandle,
+    voice: TrackHandle,
+    environment: Vec<EnvironmentEffects>,
+    volumes: Option<AudioVolumes>,
+    low_pass_cutoff: f32,
+    reverb: Option<Reverb>,
+}
+
+impl Buses {
+    pub(crate) fn new(manager: &mut AudioManager<DefaultBackend>) -> anyhow::Result<Self> {
+        let mut environment = Vec::new();
+        let mut environment_track = |manager: &mut AudioManager<DefaultBackend>| {
+            let mut builder = TrackBuilder::new();
+            let filter = builder.add_effect(
+                FilterBuilder::new()
+                    .mode(FilterMode::LowPass)
+                    .cutoff(MAX_CUTOFF as f64),
+            );
+            let reverb = builder.add_effect(ReverbBuilder::new().mix(0.0));
+
+            environment.push(EnvironmentEffects { filter, reverb });
+            manager.add_sub_track(builder)
+        };
+
+        let effects = environment_track(manager)?;
+        let voice = environment_track(manager)?;
+
+        let mut builder = TrackBuilder::new().routes(TrackRoutes::parent(effects.id()));
+        builder.add_effect(
+            FilterBuilder::new()
+                .mode(FilterMode::LowPass)
+                .cutoff(OCCLUSION_CUTOFF),
+        );
+        let occluded = manager.add_sub_track(builder)?;
+
+        Ok(Self {
+            effects,
+            occluded,
+            music: manager.add_sub_track(TrackBuilder::new())?,
+            voice,
+            environment,
+            volumes: None,
+            low_pass_cutoff: MAX_CUTOFF,
+            reverb: None,
+        })
+    }
+
+    pub(crate) fn occluded_track(&self) -> &TrackHandle {
+        &self.occluded
+    }
+
+    pub(crate) fn track(&self, bus: AudioBus) -> &TrackHandle {
+        match bus {
+            AudioBus::Effects => &self.effects,
+            AudioBus::Music => &self.music,
+            AudioBus::Voice => &self.voice,
+        }
+    }
+
+    /// Updates the volumes of the tracks if they have changed
+    pub(crate) fn set_volumes(
+        &mut self,
+        manager: &mut AudioManager<DefaultBackend>,
+        volumes: &AudioVolumes,
+    ) {
+        if self.volumes.as_ref() == Some(volumes) {
+            return;
+        }
+
+        let volume = |v: f32| Volume::Amplitude(v.clamp(0.0, 1.0) as f64);
+
+        manager
+            .main_track()
+            .set_volume(volume(volumes.master), Tween::default());
+
+        for bus in [AudioBus::Effects, AudioBus::Music, AudioBus::Voice] {
+            let track = match bus {
+                AudioBus::Effects => &mut self.effects,
+                AudioBus::Music => &mut self.music,
+                AudioBus::Voice => &mut self.voice,
+            };
+
+            track.set_volume(volume(volumes.bus(bus)), Tween::default());
+        }
+
+        self.volumes = Some(*volumes);
+    }
+
+    pub(crate) fn set_effects(&mut self, effects: &AudioEffects) {
+        if self.low_pass_cutoff == effects.low_pass_cutoff {
+            return;
+        }
+
+        for v in &mut self.environment {
+            v.filter.set_cutoff(
+                effects.low_pass_cutoff.clamp(20.0, MAX_CUTOFF) as f64,
+                Tween::default(),
+            );
+        }
+
+        self.low_pass_cutoff = effects.low_pass_cutoff;
+    }
+
+    /// Transitions to the reverb of `zone` scaled by `weight`, or disables the reverb if `None`
+    pub(crate) fn set_reverb(&mut self, zone: Option<(&ReverbZone, f32)>) {
+        let reverb = zone.map(|(zone, weight)| Reverb {
+            mix: zone.mix * weight,
+            feedback: zone.feedback,
+            damping: zone.damping,
+        });
+
+        if self.reverb == reverb {
+            return;
+        }
+
+        let tween = Tween {
+            duration: REVERB_FADE,
+            ..Default::default()
+        };
+
+        for v in &mut self.environment {
+            match reverb {
+                Some(reverb) => {
+                    v.reverb.set_feedback(reverb.feedback as f64, tween);
+                    v.reverb.set_damping(reverb.damping as f64, tween);
+                    v.reverb.set_mix(reverb.mix as f64, tween);
+                }
+                // Keep the room, so that the tail fades out naturally
+                None => v.reverb.set_mix(0.0, tween),
+            }
+        }
+
+        self.reverb = reverb;
+    }
+}
@@ -0,0 +1,16 @@
+use flax::{component, Debuggable};
+
+use crate::{AudioEffects, AudioSource, AudioVolumes, ReverbZone};
+
+component! {
+    /// Plays a sound positioned at the entity
+    pub audio_source: AudioSource => [ Debuggable ],
+    /// Sounds are heard from the world transform of this entity, usually the camera
+    pub audio_listener: () => [ Debuggable ],
+    /// Volumes of the audio buses, set on the engine entity
+    pub audio_volumes: AudioVolumes => [ Debuggable ],
+    /// Effects applied to the environment buses, set on the engine entity
+    pub audio_effects: AudioEffects => [ Debuggable ],
+    /// Reverb heard while the listener is within the radius of the entity
+    pub reverb_zone: ReverbZone => [ Debuggable ],
+}
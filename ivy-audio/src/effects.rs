@@ -0,0 +1,119 @@
+use glam::Vec3;
+
+/// Highest cutoff of the low-pass filter, at which it leaves the sound unchanged
+pub const MAX_CUTOFF: f32 = 20_000.0;
+
+/// Effects applied to the effects and voice buses.
+///
+/// Set on the engine entity using [`audio_effects`](crate::components::audio_effects).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AudioEffects {
+    /// Cutoff frequency of the low-pass filter, in Hz.
+    ///
+    /// Lower values muffle the sound, e.g; when underwater.
+    pub low_pass_cutoff: f32,
+}
+
+impl Default for AudioEffects {
+    fn default() -> Self {
+        Self {
+            low_pass_cutoff: MAX_CUTOFF,
+        }
+    }
+}
+
+/// Spherical region in which sounds reverberate, such as a cave or a hall.
+///
+/// The reverb is heard when the listener is inside the zone, and fades in over `blend` units from
+/// its edge.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ReverbZone {
+    pub radius: f32,
+    pub blend: f32,
+    /// Amount of reverberated sound, in the range `0..=1`
+    pub mix: f32,
+    /// Size of the room, in the range `0..=1`
+    pub feedback: f32,
+    /// Absorption of high frequencies, in the range `0..=1`
+    pub damping: f32,
+}
+
+impl ReverbZone {
+    pub fn new(radius: f32) -> Self {
+        Self {
+            radius,
+            blend: 2.0,
+            mix: 0.3,
+            feedback: 0.8,
+            damping: 0.4,
+        }
+    }
+
+    /// Set the distance over which the reverb fades in from the edge
+    pub fn with_blend(mut self, blend: f32) -> Self {
+        self.blend = blend;
+        self
+    }
+
+    /// Set the amount of reverberated sound
+    pub fn with_mix(mut self, mix: f32) -> Self {
+        self.mix = mix;
+        self
+    }
+
+    /// Set the size of the room
+    pub fn with_feedback(mut self, feedback: f32) -> Self {
+        self.feedback = feedback;
+        self
+    }
+
+    /// Set the absorption of high frequencies
+    pub fn with_damping(mut self, damping: f32) -> Self {
+        self.damping = damping;
+        self
+    }
+
+    /// Returns how strongly the zone is heard at `distance` from its center, from 0 to 1
+    pub fn weight(&self, distance: f32) -> f32 {
+        if self.blend <= 0.0 {
+            return if distance <= self.radius { 1.0 } else { 0.0 };
+        }
+
+        ((self.radius - distance) / self.blend).clamp(0.0, 1.0)
+    }
+}
+
+/// Returns the zone which is heard the strongest at `position`, and its weight
+pub(crate) fn strongest_zone<'a>(
+    zones: impl IntoIterator<Item = (Vec3, &'a ReverbZone)>,
+    position: Vec3,
+) -> Option<(&'a ReverbZone, f32)> {
+    zones
+        .into_iter()
+        .map(|(center, zone)| (zone, zone.weight(center.distance(position))))
+        .filter(|(_, weight)| *weight > 0.0)
+        .max_by(|a, b| a.1.total_cmp(&b.1))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn zone_weight() {
+        let hall = ReverbZone::new(10.0).with_blend(2.0);
+        let cave = ReverbZone::new(4.0).with_blend(0.0);
+
+        assert_eq!(hall.weight(0.0), 1.0);
+        assert_eq!(hall.weight(9.0), 0.5);
+        assert_eq!(hall.weight(12.0), 0.0);
+        assert_eq!(cave.weight(4.0), 1.0);
+        assert_eq!(cave.weight(4.5), 0.0);
+
+        let zones = [(Vec3::ZERO, &hall), (Vec3::X * 12.0, &cave)];
+
+        assert_eq!(strongest_zone(zones, Vec3::X * 9.0), Some((&cave, 1.0)));
+        assert_eq!(strongest_zone(zones, Vec3::X * 7.0), Some((&hall, 1.0)));
+        assert_eq!(strongest_zone(zones, Vec3::X * 20.0), None);
+    }
+}
@@ -0,0 +1,651 @@
+use std::{
+    collections::{btree_map::Entry, BTreeMap, BTreeSet},
+    time::Duration,
+};
+
+use flax::{components::child_of, entity_ids, Entity, Query, World};
+use glam::{Mat4, Quat, Vec3};
+use ivy_assets::{Asset, AssetCache, AssetLoadFuture, SharedError};
+use ivy_core::{
+    app::TickEvent,
+    components::{engine, world_transform},
+    layer::events::EventRegisterContext,
+    notifications::{notify, Notification},
+    pool::pooled,
+    Layer,
+};
+use ivy_physics::{
+    components::{physics_state, rb_handle},
+    rapier3d::prelude::{QueryFilter, Ray, RigidBodyHandle},
+    state::PhysicsState,
+};
+use kira::{
+    manager::{backend::DefaultBackend, AudioManager, AudioManagerSettings},
+    sound::{
+        static_sound::{StaticSoundData, StaticSoundHandle},
+        PlaybackState,
+    },
+    spatial::{
+        emitter::{EmitterHandle, EmitterSettings},
+        listener::{ListenerHandle, ListenerSettings},
+        scene::{SpatialSceneHandle, SpatialSceneSettings},
+    },
+    tween::Tween,
+    Volume,
+};
+
+use crate::{
+    bus::Buses,
+    components::{audio_effects, audio_listener, audio_source, audio_volumes, reverb_zone},
+    effects::strongest_zone,
+    music::MusicPlayer,
+    AudioBus, AudioSource, AudioVolumes, PlayMusic, PlaySound, Sound, SoundOrigin, StopMusic,
+};
+
+struct AudioBackend {
+    manager: AudioManager<DefaultBackend>,
+    buses: Buses,
+    scene: SpatialSceneHandle,
+    listener: ListenerHandle,
+    /// Heard through the low-pass filter of occluded sounds
+    occluded_scene: SpatialSceneHandle,
+    occluded_listener: ListenerHandle,
+    music: MusicPlayer,
+}
+
+impl AudioBackend {
+    fn new() -> anyhow::Result<Self> {
+        let mut manager = AudioManager::<DefaultBackend>::new(AudioManagerSettings::default())?;
+        let buses = Buses::new(&mut manager)?;
+        let mut scene = manager.add_spatial_scene(SpatialSceneSettings::default())?;
+        let mut occluded_scene = manager.add_spatial_scene(SpatialSceneSettings::default())?;
+
+        // Spatial sounds reach the output through the listener
+        let listener = scene.add_listener(
+            Vec3::ZERO,
+            Quat::IDENTITY,
+            ListenerSettings::new().track(buses.track(AudioBus::Effects).id()),
+        )?;
+
+        let occluded_listener = occluded_scene.add_listener(
+            Vec3::ZERO,
+            Quat::IDENTITY,
+            ListenerSettings::new().track(buses.occluded_track().id()),
+        )?;
+
+        Ok(Self {
+            manager,
+            buses,
+            scene,
+            listener,
+            occluded_scene,
+            occluded_listener,
+            music: MusicPlayer::new(),
+        })
+    }
+
+    fn set_listener(&mut self, position: Vec3, rotation: Quat) {
+        for listener in [&mut self.listener, &mut self.occluded_listener] {
+            listener.set_position(position, Tween::default());
+            listener.set_orientation(rotation, Tween::default());
+        }
+    }
+
+    /// Adds an emitter, heard through the occlusion filter if `occluded` is set
+    fn add_emitter(
+        &mut self,
+        position: Vec3,
+        min_distance: f32,
+        max_distance: f32,
+        occluded: bool,
+    ) -> anyhow::Result<EmitterHandle> {
+        let scene = if occluded {
+            &mut self.occluded_scene
+        } else {
+            &mut self.scene
+        };
+
+        let emitter = scene.add_emitter(
+            position,
+            EmitterSettings::new().distances((min_distance, max_distance)),
+        )?;
+
+        Ok(emitter)
+    }
+
+    fn play(
+        &mut self,
+        sound: &Sound,
+        volume: f32,
+        looping: bool,
+        output: Output,
+    ) -> anyhow::Result<StaticSoundHandle> {
+        let mut data: StaticSoundData = sound
+            .data()
+            .clone()
+            .volume(Volume::Amplitude(volume as f64));
+        if looping {
+            data = data.loop_region(..);
+        }
+
+        data = match output {
+            Output::Emitter(emitter) => data.output_destination(emitter),
+            Output::Bus(bus) => data.output_destination(self.buses.track(bus)),
+        };
+
+        self.manager
+            .play(data)
+            .map_err(|err| anyhow::anyhow!("Failed to play sound: {err:?}"))
+    }
+}
+
+enum Output<'a> {
+    Emitter(&'a EmitterHandle),
+    Bus(AudioBus),
+}
+
+/// Time for the volume to change when a source becomes occluded
+const OCCLUSION_FADE: Duration = Duration::from_millis(150);
+
+type SoundLoad = AssetLoadFuture<Sound, anyhow::Error>;
+
+/// A sound playing from an emitter, stopped when dropped
+struct Voice {
+    emitter: EmitterHandle,
+    sound: Option<StaticSoundHandle>,
+}
+
+impl Voice {
+    fn new(emitter: EmitterHandle) -> Self {
+        Self {
+            emitter,
+            sound: None,
+        }
+    }
+}
+
+impl Drop for Voice {
+    fn drop(&mut self) {
+        if let Some(sound) = &mut self.sound {
+            sound.stop(Tween::default());
+        }
+    }
+}
+
+/// Sound of an [`AudioSource`].
+///
+/// Occludable sources play the sound twice, unfiltered and through the occlusion filter, and
+/// crossfade between them when the line of sight to the listener changes.
+struct ActiveSource {
+    source: AudioSource,
+    /// The sound asset, until it has been decoded
+    loading: Option<SoundLoad>,
+    clear: Option<Voice>,
+    muffled: Option<Voice>,
+    occluded: bool,
+}
+
+impl ActiveSource {
+    /// Returns the volumes of the clear and muffled voices
+    fn volumes(&self, occluded_volume: f32) -> [f32; 2] {
+        if self.occluded {
+            [0.0, self.source.volume * occluded_volume]
+        } else {
+            [self.source.volume, 0.0]
+        }
+    }
+
+    fn voices(&mut self) -> impl Iterator<Item = &mut Voice> {
+        [&mut self.clear, &mut self.muffled].into_iter().flatten()
+    }
+
+    fn set_position(&mut self, position: Vec3) {
+        for voice in self.voices() {
+            voice.emitter.set_position(position, Tween::default());
+        }
+    }
+
+    /// Starts the sound once its asset has been decoded
+    fn start_loaded(
+        &mut self,
+        backend: &mut AudioBackend,
+        assets: &AssetCache,
+        occluded_volume: f32,
+    ) {
+        let Some(result) = self.loading.as_ref().and_then(|v| v.try_get()) else {
+            return;
+        };
+
+        self.loading = None;
+        let sound = match loaded(result) {
+            Ok(v) => v,
+            Err(err) => return report(assets, err),
+        };
+
+        let volumes = self.volumes(occluded_volume);
+        let looping = self.source.looping;
+        for (voice, volume) in [&mut self.clear, &mut self.muffled]
+            .into_iter()
+            .zip(volumes)
+        {
+            let Some(voice) = voice else {
+                continue;
+            };
+
+            voice.sound = backend
+                .play(&sound, volume, looping, Output::Emitter(&voice.emitter))
+                .map_err(|err| report(assets, err))
+                .ok();
+        }
+    }
+
+    fn set_occluded(&mut self, occluded: bool, occluded_volume: f32) {
+        if self.occluded == occluded {
+            return;
+        }
+
+        self.occluded = occluded;
+        let volumes = self.volumes(occluded_volume);
+
+        let tween = Tween {
+            duration: OCCLUSION_FADE,
+            ..Default::default()
+        };
+
+        for (voice, volume) in [&mut self.clear, &mut self.muffled]
+            .into_iter()
+            .zip(volumes)
+        {
+            if let Some(sound) = voice.as_mut().and_then(|v| v.sound.as_mut()) {
+                sound.set_volume(Volume::Amplitude(volume as f64), tween);
+            }
+        }
+    }
+}
+
+struct OneShot {
+    /// Kept alive until the sound has finished
+    _emitter: Option<EmitterHandle>,
+    sound: StaticSoundHandle,
+}
+
+/// A [`PlaySound`] whose sound is being decoded
+struct PendingOneShot {
+    sound: SoundLoad,
+    emitter: Option<EmitterHandle>,
+    bus: AudioBus,
+    volume: f32,
+}
+
+/// Plays the [`audio_source`]s, [`PlaySound`] and [`PlayMusic`] events, with spatial sounds
+/// heard from the [`audio_listener`].
+///
+/// Sources which are blocked from the listener by a collider are muffled, and the listener hears
+/// the reverb of the [`reverb_zone`] it is in.
+///
+/// If no audio device is available the layer does nothing.
+pub struct AudioLayer {
+    backend: Option<AudioBackend>,
+    sources: BTreeMap<Entity, ActiveSource>,
+    one_shots: Vec<OneShot>,
+    pending: Vec<PendingOneShot>,
+    occluded_volume: f32,
+    volumes: AudioVolumes,
+}
+
+impl AudioLayer {
+    pub fn new() -> Self {
+        Self {
+            backend: None,
+            sources: BTreeMap::new(),
+            one_shots: Vec::new(),
+            pending: Vec::new(),
+            occluded_volume: 0.3,
+            volumes: AudioVolumes::default(),
+        }
+    }
+
+    /// Set the initial volumes of the buses, e.g; from the settings of the player.
+    ///
+    /// Ignored if [`audio_volumes`] is already set.
+    pub fn with_volumes(mut self, volumes: AudioVolumes) -> Self {
+        self.volumes = volumes;
+        self
+    }
+
+    /// Set the volume of occluded sources, relative to their unoccluded volume
+    pub fn with_occluded_volume(mut self, occluded_volume: f32) -> Self {
+        self.occluded_volume = occluded_volume;
+        self
+    }
+
+    fn update(&mut self, world: &World, assets: &AssetCache) {
+        let Some(backend) = &mut self.backend else {
+            return;
+        };
+
+        if let Ok(volumes) = world.get(engine(), audio_volumes()) {
+            backend.buses.set_volumes(&mut backend.manager, &volumes);
+        }
+
+        if let Ok(effects) = world.get(engine(), audio_effects()) {
+            backend.buses.set_effects(&effects);
+        }
+
+        backend
+            .music
+            .update(assets, &mut backend.manager, &backend.buses);
+
+        let mut listener_query =
+            Query::new((entity_ids(), world_transform())).with(audio_listener());
+        let mut listener_query = listener_query.borrow(world);
+        let listener = listener_query.first();
+
+        if let Some((_, transform)) = listener {
+            let (_, rotation, position) = transform.to_scale_rotation_translation();
+            backend.set_listener(position, rotation);
+
+            let zones = Query::new((world_transform(), reverb_zone()))
+                .borrow(world)
+                .iter()
+                .map(|(transform, zone)| (translation(transform), *zone))
+                .collect::<Vec<_>>();
+
+            backend.buses.set_reverb(strongest_zone(
+                zones.iter().map(|(center, zone)| (*center, zone)),
+                position,
+            ));
+        }
+
+        let physics = world.get(engine(), physics_state()).ok();
+
+        let mut alive = BTreeSet::new();
+
+        for (id, source, transform) in Query::new((entity_ids(), audio_source(), world_transform()))
+            .without(pooled())
+            .borrow(world)
+            .iter()
+        {
+            alive.insert(id);
+            let position = translation(transform);
+
+            let active = match self.sources.entry(id) {
+                Entry::Occupied(entry) if entry.get().source == *source => {
+                    let active = entry.into_mut();
+                    active.set_position(position);
+                    active
+                }
+                Entry::Occupied(mut entry) => {
+                    entry.insert(start_source(backend, assets, source, position));
+                    entry.into_mut()
+                }
+                Entry::Vacant(entry) => {
+                    entry.insert(start_source(backend, assets, source, position))
+                }
+            };
+
+            active.start_loaded(backend, assets, self.occluded_volume);
+
+            if let (Some(physics), Some((listener, transform))) = (&physics, listener) {
+                let occluded = source.occlusion
+                    && is_occluded(
+                        world,
+                        physics,
+                        listener,
+                        translation(transform),
+                        id,
+                        position,
+                    );
+
+                active.set_occluded(occluded, self.occluded_volume);
+            }
+        }
+
+        self.sources.retain(|id, _| alive.contains(id));
+        self.start_one_shots(assets);
+        self.one_shots
+            .retain(|v| v.sound.state() != PlaybackState::Stopped);
+    }
+
+    /// Plays the one-shot sounds which have been decoded
+    fn start_one_shots(&mut self, assets: &AssetCache) {
+        let Some(backend) = &mut self.backend else {
+            return;
+        };
+
+        let one_shots = &mut self.one_shots;
+        self.pending.retain_mut(|pending| {
+            let Some(result) = pending.sound.try_get() else {
+                return true;
+            };
+
+            let output = match &pending.emitter {
+                Some(emitter) => Output::Emitter(emitter),
+                None => Output::Bus(pending.bus),
+            };
+
+            match loaded(result)
+                .and_then(|sound| backend.play(&sound, pending.volume, false, output))
+            {
+                Ok(sound) => one_shots.push(OneShot {
+                    _emitter: pending.emitter.take(),
+                    sound,
+                }),
+                Err(err) => report(assets, err),
+            }
+
+            false
+        });
+    }
+
+    fn play_one_shot(&mut self, world: &World, assets: &AssetCache, event: &PlaySound) {
+        let Some(backend) = &mut self.backend else {
+            return;
+        };
+
+        let position = match event.origin {
+            SoundOrigin::Global => None,
+            SoundOrigin::Position(v) => Some(v),
+            SoundOrigin::Entity(id) => match world.get(id, world_transform()) {
+                Ok(v) => Some(translation(&v)),
+                Err(err) => {
+                    tracing::warn!(?id, "Failed to play sound at entity: {err}");
+                    return;
+                }
+            },
+        };
+
+        let emitter = match position
+            .map(|v| backend.add_emitter(v, event.min_distance, event.max_distance, false))
+            .transpose()
+        {
+            Ok(v) => v,
+            Err(err) => return report(assets, err),
+        };
+
+        self.pending.push(PendingOneShot {
+            sound: assets.from_path(&event.sound),
+            emitter,
+            bus: event.bus,
+            volume: event.volume,
+        });
+
+        // Sounds which are already decoded start right away
+        self.start_one_shots(assets);
+    }
+}
+
+impl Default for AudioLayer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Layer for AudioLayer {
+    fn register(
+        &mut self,
+        world: &mut World,
+        assets: &AssetCache,
+        mut events: EventRegisterContext<Self>,
+    ) -> anyhow::Result<()>
+    where
+        Self: Sized,
+    {
+        match AudioBackend::new() {
+            Ok(v) => self.backend = Some(v),
+            Err(err) => {
+                tracing::warn!("Failed to initialize audio: {err:?}");
+                notify(
+                    assets,
+                    Notification::warning(format!("Audio is disabled: {err:#}")),
+                );
+            }
+        }
+
+        if !world.has(engine(), audio_volumes()) {
+            world.set(engine(), audio_volumes(), self.volumes)?;
+        }
+
+        if !world.has(engine(), audio_effects()) {
+            world.set(engine(), audio_effects(), Default::default())?;
+        }
+
+        events.subscribe(|this, ctx, _: &TickEvent| {
+            this.update(ctx.world, ctx.assets);
+            Ok(())
+        });
+
+        events.subscribe(|this, ctx, event: &PlaySound| {
+            this.play_one_shot(ctx.world, ctx.assets, event);
+            Ok(())
+        });
+
+        events.subscribe(|this, ctx, event: &PlayMusic| {
+            if let Some(backend) = &mut this.backend {
+                backend.music.play(ctx.world, ctx.assets, event)?;
+            }
+
+            Ok(())
+        });
+
+        events.subscribe(|this, _, event: &StopMusic| {
+            if let Some(backend) = &mut this.backend {
+                backend.music.stop(event.fade);
+            }
+
+            Ok(())
+        });
+
+        Ok(())
+    }
+}
+
+/// Creates the emitters and starts decoding the sound of a source.
+///
+/// Failures are reported rather than returned, so that they are not retried every tick.
+fn start_source(
+    backend: &mut AudioBackend,
+    assets: &AssetCache,
+    source: &AudioSource,
+    position: Vec3,
+) -> ActiveSource {
+    let mut voice = |occluded| {
+        backend
+            .add_emitter(position, source.min_distance, source.max_distance, occluded)
+            .map(Voice::new)
+            .map_err(|err| report(assets, err))
+            .ok()
+    };
+
+    let clear = voice(false);
+    let muffled = if source.occlusion { voice(true) } else { None };
+
+    let loading = clear.is_some().then(|| assets.from_path(&source.sound));
+
+    ActiveSource {
+        source: source.clone(),
+        loading,
+        clear,
+        muffled,
+        occluded: false,
+    }
+}
+
+/// Returns true if a collider blocks the line between the listener and a source
+fn is_occluded(
+    world: &World,
+    physics: &PhysicsState,
+    listener: Entity,
+    listener_pos: Vec3,
+    source: Entity,
+    source_pos: Vec3,
+) -> bool {
+    let Some(dir) = (source_pos - listener_pos).try_normalize() else {
+        return false;
+    };
+
+    // The listener is usually attached to a body, such as the camera of the player
+    let mut filter = QueryFilter::default().exclude_sensors();
+    for rb in [listener, source]
+        .into_iter()
+        .filter_map(|id| rigid_body(world, id))
+    {
+        filter = filter.exclude_rigid_body(rb);
+    }
+
+    let ray = Ray::new(listener_pos.into(), dir.into());
+    physics
+        .cast_ray(&ray, listener_pos.distance(source_pos), true, filter)
+        .is_some()
+}
+
+/// Returns the rigid body of an entity, or of its closest ancestor with one
+fn rigid_body(world: &World, mut id: Entity) -> Option<RigidBodyHandle> {
+    loop {
+        let entity = world.entity(id).ok()?;
+        if let Ok(rb) = entity.get(rb_handle()) {
+            return Some(*rb);
+        }
+
+        id = entity.relations(child_of).next()?.0;
+    }
+}
+
+fn translation(transform: &Mat4) -> Vec3 {
+    transform.w_axis.truncate()
+}
+
+fn loaded(
+    result: Result<Asset<Sound>, SharedError<anyhow::Error>>,
+) -> anyhow::Result<Asset<Sound>> {
+    // Keep the context of the shared error
+    result.map_err(|err| anyhow::anyhow!("{err:#}"))
+}
+
+pub(crate) fn report(assets: &AssetCache, err: anyhow::Error) {
+    tracing::error!("{err:?}");
+    notify(assets, Notification::error(format!("{err:#}")));
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn listener_rigid_body() {
+        let mut world = World::new();
+        let rb = RigidBodyHandle::from_raw_parts(1, 0);
+
+        let player = world.spawn();
+        world.set(player, rb_handle(), rb).unwrap();
+
+        let camera = world.spawn();
+        world.set(camera, child_of(player), ()).unwrap();
+
+        let lamp = world.spawn();
+
+        assert_eq!(rigid_body(&world, player), Some(rb));
+        assert_eq!(rigid_body(&world, camera), Some(rb));
+        assert_eq!(rigid_body(&world, lamp), None);
+    }
+}
@@ -0,0 +1,32 @@
+//! Spatial audio for the Ivy framework, added to the app through the [`AudioPlugin`].
+//!
+//! Sounds are played by entities with an [`audio_source`](components::audio_source), and heard
+//! from the [`audio_listener`](components::audio_listener). Panning and attenuation follow the
+//! world transforms of both.
+//!
+//! One-shot sounds are played by sending a [`PlaySound`] event through the
+//! [`event_queue`](ivy_core::components::event_queue), and music using [`PlayMusic`].
+//!
+//! Sounds are mixed into the [`AudioBus`]es, whose volumes are controlled by
+//! [`audio_volumes`](components::audio_volumes).
+//!
+//! Sources behind colliders are quieter and muffled by a low-pass filter. The effects and voice
+//! buses pass through a low-pass filter and the reverb of the
+//! [`reverb_zone`](components::reverb_zone) the listener is in.
+mod bus;
+pub mod components;
+mod effects;
+mod layer;
+mod music;
+mod plugin;
+mod sound;
+mod source;
+
+pub use bus::{AudioBus, AudioVolumes};
+pub use effects::{AudioEffects, ReverbZone, MAX_CUTOFF};
+pub use kira;
+pub use layer::*;
+pub use music::{MusicTrack, PlayMusic, StopMusic};
+pub use plugin::AudioPlugin;
+pub use sound::*;
+pub use source::*;
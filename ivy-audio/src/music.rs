@@ -0,0 +1,260 @@
+use std::time::Duration;
+
+use anyhow::Context;
+use flax::World;
+use ivy_assets::{service::FileSystemMapService, AssetCache};
+use ivy_core::{
+    components::{engine, tasks},
+    events::Event,
+};
+use kira::{
+    manager::{backend::DefaultBackend, AudioManager},
+    sound::{
+        streaming::{StreamingSoundData, StreamingSoundHandle},
+        FromFileError, PlaybackState,
+    },
+    tween::Tween,
+    Volume,
+};
+
+use crate::{bus::Buses, layer::report, AudioBus};
+
+const DEFAULT_FADE: Duration = Duration::from_secs(2);
+
+/// A music track, streamed from disk while it plays.
+///
+/// Tracks loop by default. A loop section which starts after the beginning of the track plays the
+/// part before it as an intro.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MusicTrack {
+    /// Path of the audio file, relative to the asset root
+    pub path: String,
+    pub volume: f32,
+    pub looping: bool,
+    /// Start of the repeated section, in seconds
+    pub loop_start: f64,
+    /// End of the repeated section, in seconds, or the end of the track if `None`
+    pub loop_end: Option<f64>,
+}
+
+impl MusicTrack {
+    pub fn new(path: impl Into<String>) -> Self {
+        Self {
+            path: path.into(),
+            volume: 1.0,
+            looping: true,
+            loop_start: 0.0,
+            loop_end: None,
+        }
+    }
+
+    /// Set the volume, in the range `0..=1`
+    pub fn with_volume(mut self, volume: f32) -> Self {
+        self.volume = volume;
+        self
+    }
+
+    /// Play the track once instead of repeating it
+    pub fn with_looping(mut self, looping: bool) -> Self {
+        self.looping = looping;
+        self
+    }
+
+    /// Set the section which is repeated after the intro, in seconds
+    pub fn with_loop_section(mut self, start: f64, end: Option<f64>) -> Self {
+        self.looping = true;
+        self.loop_start = start;
+        self.loop_end = end;
+        self
+    }
+}
+
+/// Starts a music track, crossfading from the current one.
+///
+/// Requesting the track which is already playing does nothing.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PlayMusic {
+    pub track: MusicTrack,
+    /// Duration of the crossfade
+    pub fade: Duration,
+}
+
+impl PlayMusic {
+    pub fn new(track: MusicTrack) -> Self {
+        Self {
+            track,
+            fade: DEFAULT_FADE,
+        }
+    }
+
+    /// Set the duration of the crossfade
+    pub fn with_fade(mut self, fade: Duration) -> Self {
+        self.fade = fade;
+        self
+    }
+}
+
+/// Fades out the current music
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct StopMusic {
+    pub fade: Duration,
+}
+
+impl Default for StopMusic {
+    fn default() -> Self {
+        Self { fade: DEFAULT_FADE }
+    }
+}
+
+impl Event for PlayMusic {}
+impl Event for StopMusic {}
+
+struct LoadedTrack {
+    id: u64,
+    track: MusicTrack,
+    fade: Duration,
+    data: anyhow::Result<StreamingSoundData<FromFileError>>,
+}
+
+/// Plays one music track at a time.
+///
+/// Tracks are opened on the blocking task pool, after which kira decodes them incrementally on
+/// its own thread.
+pub(crate) struct MusicPlayer {
+    current: Option<(MusicTrack, StreamingSoundHandle<FromFileError>)>,
+    /// The latest requested track, which replaces the current track once opened
+    pending: Option<(u64, MusicTrack)>,
+    next_id: u64,
+    tx: flume::Sender<LoadedTrack>,
+    rx: flume::Receiver<LoadedTrack>,
+}
+
+impl MusicPlayer {
+    pub(crate) fn new() -> Self {
+        let (tx, rx) = flume::unbounded();
+
+        Self {
+            current: None,
+            pending: None,
+            next_id: 0,
+            tx,
+            rx,
+        }
+    }
+
+    pub(crate) fn play(
+        &mut self,
+        world: &World,
+        assets: &AssetCache,
+        event: &PlayMusic,
+    ) -> anyhow::Result<()> {
+        let requested = match &self.pending {
+            Some((_, track)) => Some(track),
+            None => self.current.as_ref().map(|v| &v.0),
+        };
+
+        if requested == Some(&event.track) {
+            return Ok(());
+        }
+
+        let id = self.next_id;
+        self.next_id += 1;
+        self.pending = Some((id, event.track.clone()));
+
+        let path = assets
+            .service::<FileSystemMapService>()
+            .root
+            .join(&event.track.path);
+
+        let track = event.track.clone();
+        let fade = event.fade;
+        let tx = self.tx.clone();
+
+        world.get(engine(), tasks())?.spawn_blocking(
+            move || {
+                StreamingSoundData::from_file(&path)
+                    .with_context(|| format!("Failed to stream music {path:?}"))
+            },
+            move |_, data| {
+                tx.send(LoadedTrack {
+                    id,
+                    track,
+                    fade,
+                    data,
+                })
+                .ok();
+
+                Ok(())
+            },
+        );
+
+        Ok(())
+    }
+
+    pub(crate) fn stop(&mut self, fade: Duration) {
+        self.pending = None;
+
+        if let Some((_, mut handle)) = self.current.take() {
+            handle.stop(fade_tween(fade));
+        }
+    }
+
+    pub(crate) fn update(
+        &mut self,
+        assets: &AssetCache,
+        manager: &mut AudioManager<DefaultBackend>,
+        buses: &Buses,
+    ) {
+        if let Some((_, handle)) = &self.current {
+            if handle.state() == PlaybackState::Stopped {
+                self.current = None;
+            }
+        }
+
+        for loaded in self.rx.drain() {
+            // Superseded by a later request
+            if self.pending.as_ref().map(|v| v.0) != Some(loaded.id) {
+                continue;
+            }
+
+            self.pending = None;
+
+            let data = match loaded.data {
+                Ok(v) => v,
+                Err(err) => {
+                    report(assets, err);
+                    continue;
+                }
+            };
+
+            let track = loaded.track;
+            let mut data = data
+                .volume(Volume::Amplitude(track.volume as f64))
+                .output_destination(buses.track(AudioBus::Music))
+                .fade_in_tween(Some(fade_tween(loaded.fade)));
+
+            if track.looping {
+                data = match track.loop_end {
+                    Some(end) => data.loop_region(track.loop_start..end),
+                    None => data.loop_region(track.loop_start..),
+                };
+            }
+
+            match manager.play(data) {
+                Ok(handle) => {
+                    if let Some((_, mut prev)) = self.current.replace((track, handle)) {
+                        prev.stop(fade_tween(loaded.fade));
+                    }
+                }
+                Err(err) => report(assets, anyhow::anyhow!("Failed to play music: {err:?}")),
+            }
+        }
+    }
+}
+
+fn fade_tween(duration: Duration) -> Tween {
+    Tween {
+        duration,
+        ..Default::default()
+    }
+}
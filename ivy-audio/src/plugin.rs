@@ -0,0 +1,36 @@
+use ivy_core::{App, AppPlugin};
+
+use crate::{AudioLayer, AudioVolumes};
+
+/// Plays the sounds of the world through an [`AudioLayer`]
+#[derive(Default)]
+pub struct AudioPlugin {
+    layer: AudioLayer,
+}
+
+impl AudioPlugin {
+    pub fn new() -> Self {
+        Self {
+            layer: AudioLayer::new(),
+        }
+    }
+
+    /// Set the initial volumes of the buses, see [`AudioLayer::with_volumes`]
+    pub fn with_volumes(mut self, volumes: AudioVolumes) -> Self {
+        self.layer = self.layer.with_volumes(volumes);
+        self
+    }
+
+    /// Set the volume of occluded sources, relative to their unoccluded volume
+    pub fn with_occluded_volume(mut self, occluded_volume: f32) -> Self {
+        self.layer = self.layer.with_occluded_volume(occluded_volume);
+        self
+    }
+}
+
+impl AppPlugin for AudioPlugin {
+    fn build(self, app: &mut App) -> anyhow::Result<()> {
+        app.push_labeled_layer("audio", self.layer);
+        Ok(())
+    }
+}
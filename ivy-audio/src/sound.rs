@@ -0,0 +1,139 @@
+use std::{io::Cursor, mem, path::Path, sync::Arc};
+
+use anyhow::Context;
+use ivy_assets::{
+    fs::{AssetFromPath, AsyncAssetFromPath},
+    service::FileSystemMapService,
+    Asset, AssetCache,
+};
+use ivy_core::memory::TrackedMemory;
+use kira::sound::static_sound::StaticSoundData;
+
+/// Decoded audio, loaded from an `ogg` or `wav` file
+#[derive(Debug, Clone)]
+pub struct Sound {
+    data: StaticSoundData,
+    _memory: Arc<TrackedMemory>,
+}
+
+impl Sound {
+    /// Decodes the bytes of an audio file
+    pub fn from_bytes(bytes: Vec<u8>) -> anyhow::Result<Self> {
+        let data = StaticSoundData::from_cursor(Cursor::new(bytes))?;
+        let memory = TrackedMemory::new("assets", mem::size_of_val(&*data.frames) as u64);
+
+        Ok(Self {
+            data,
+            _memory: Arc::new(memory),
+        })
+    }
+
+    pub fn data(&self) -> &StaticSoundData {
+        &self.data
+    }
+
+    pub fn duration(&self) -> std::time::Duration {
+        self.data.duration()
+    }
+}
+
+impl AssetFromPath for Sound {
+    type Error = anyhow::Error;
+
+    fn load_from_path(path: &Path, assets: &AssetCache) -> anyhow::Result<Asset<Self>> {
+        let bytes = assets.service::<FileSystemMapService>().load_bytes(path)?;
+        let sound =
+            Sound::from_bytes(bytes).with_context(|| format!("Failed to load sound {path:?}"))?;
+
+        Ok(assets.insert(sound))
+    }
+}
+
+impl AsyncAssetFromPath for Sound {
+    type Error = anyhow::Error;
+
+    async fn load_from_path(path: &Path, assets: &AssetCache) -> anyhow::Result<Asset<Self>> {
+        let bytes = assets
+            .service::<FileSystemMapService>()
+            .load_bytes_async(path)
+            .await?;
+        let sound = async_std::task::spawn_blocking(move || Sound::from_bytes(bytes))
+            .await
+            .with_context(|| format!("Failed to load sound {path:?}"))?;
+
+        Ok(assets.insert(sound))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{fs, path::PathBuf, time::Duration};
+
+    use super::*;
+
+    fn setup(name: &str) -> (PathBuf, AssetCache) {
+        let root = std::env::temp_dir().join(format!("ivy-sound-{}-{name}", std::process::id()));
+        fs::create_dir_all(&root).unwrap();
+
+        let assets = AssetCache::new();
+        assets.register_service(FileSystemMapService::new(&root));
+        (root, assets)
+    }
+
+    /// Encodes mono 16 bit samples as a wav file
+    fn wav(samples: &[i16], sample_rate: u32) -> Vec<u8> {
+        let data_len = (samples.len() * 2) as u32;
+
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(b"RIFF");
+        bytes.extend_from_slice(&(36 + data_len).to_le_bytes());
+        bytes.extend_from_slice(b"WAVEfmt ");
+        bytes.extend_from_slice(&16u32.to_le_bytes());
+        // PCM, mono
+        bytes.extend_from_slice(&1u16.to_le_bytes());
+        bytes.extend_from_slice(&1u16.to_le_bytes());
+        bytes.extend_from_slice(&sample_rate.to_le_bytes());
+        bytes.extend_from_slice(&(sample_rate * 2).to_le_bytes());
+        bytes.extend_from_slice(&2u16.to_le_bytes());
+        bytes.extend_from_slice(&16u16.to_le_bytes());
+        bytes.extend_from_slice(b"data");
+        bytes.extend_from_slice(&data_len.to_le_bytes());
+        for sample in samples {
+            bytes.extend_from_slice(&sample.to_le_bytes());
+        }
+
+        bytes
+    }
+
+    #[test]
+    fn load_async() {
+        let (root, assets) = setup("load");
+        let samples = (0..4800)
+            .map(|i| (i % 100) as i16 * 100)
+            .collect::<Vec<_>>();
+        fs::write(root.join("beep.wav"), wav(&samples, 48000)).unwrap();
+
+        let sound = async_std::task::block_on(assets.from_path::<Sound>("beep.wav")).unwrap();
+        assert_eq!(sound.duration(), Duration::from_millis(100));
+
+        // Loading the same path again uses the decoded sound
+        let load = assets.from_path::<Sound>("beep.wav");
+        assert!(load.try_get().is_some_and(|v| v.is_ok()));
+
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn load_invalid() {
+        let (root, assets) = setup("invalid");
+        fs::write(root.join("broken.wav"), b"not a sound").unwrap();
+
+        let err = async_std::task::block_on(assets.from_path::<Sound>("broken.wav")).unwrap_err();
+        assert!(format!("{err:#}").contains("Failed to load sound"));
+
+        let err = async_std::task::block_on(assets.from_path::<Sound>("missing.wav")).unwrap_err();
+        assert!(!format!("{err:#}").is_empty());
+
+        fs::remove_dir_all(root).unwrap();
+    }
+}
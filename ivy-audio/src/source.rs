@@ -0,0 +1,136 @@
+use flax::Entity;
+use glam::Vec3;
+use ivy_core::events::Event;
+
+use crate::AudioBus;
+
+const DEFAULT_MIN_DISTANCE: f32 = 1.0;
+const DEFAULT_MAX_DISTANCE: f32 = 100.0;
+
+/// A sound positioned at an entity.
+///
+/// The sound starts playing when the component is added, and restarts when it is changed.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AudioSource {
+    /// Path of the [`Sound`](crate::Sound) asset
+    pub sound: String,
+    pub volume: f32,
+    pub looping: bool,
+    /// Distance at which the sound starts to attenuate
+    pub min_distance: f32,
+    /// Distance at which the sound is no longer audible
+    pub max_distance: f32,
+    /// Muffle the sound when a collider is between it and the listener
+    pub occlusion: bool,
+}
+
+impl AudioSource {
+    pub fn new(sound: impl Into<String>) -> Self {
+        Self {
+            sound: sound.into(),
+            volume: 1.0,
+            looping: false,
+            min_distance: DEFAULT_MIN_DISTANCE,
+            max_distance: DEFAULT_MAX_DISTANCE,
+            occlusion: true,
+        }
+    }
+
+    /// Set the volume, in the range `0..=1`
+    pub fn with_volume(mut self, volume: f32) -> Self {
+        self.volume = volume;
+        self
+    }
+
+    /// Enable or disable muffling the sound when it is occluded
+    pub fn with_occlusion(mut self, occlusion: bool) -> Self {
+        self.occlusion = occlusion;
+        self
+    }
+
+    /// Repeat the sound until the component is removed
+    pub fn with_looping(mut self, looping: bool) -> Self {
+        self.looping = looping;
+        self
+    }
+
+    /// Set the distances between which the sound attenuates
+    pub fn with_distances(mut self, min_distance: f32, max_distance: f32) -> Self {
+        self.min_distance = min_distance;
+        self.max_distance = max_distance;
+        self
+    }
+}
+
+/// Where a one-shot sound is played
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SoundOrigin {
+    /// Heard equally regardless of the listener, such as music or Ui sounds
+    Global,
+    /// At a fixed position in the world
+    Position(Vec3),
+    /// At the position of an entity when the sound starts
+    Entity(Entity),
+}
+
+/// Plays a sound once.
+///
+/// Send it through the [`event_queue`](ivy_core::components::event_queue) to play it from
+/// systems.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PlaySound {
+    /// Path of the [`Sound`](crate::Sound) asset
+    pub sound: String,
+    pub origin: SoundOrigin,
+    /// Bus of non-spatial sounds. Spatial sounds are mixed into [`AudioBus::Effects`]
+    pub bus: AudioBus,
+    pub volume: f32,
+    pub min_distance: f32,
+    pub max_distance: f32,
+}
+
+impl PlaySound {
+    pub fn new(sound: impl Into<String>) -> Self {
+        Self {
+            sound: sound.into(),
+            origin: SoundOrigin::Global,
+            bus: AudioBus::Effects,
+            volume: 1.0,
+            min_distance: DEFAULT_MIN_DISTANCE,
+            max_distance: DEFAULT_MAX_DISTANCE,
+        }
+    }
+
+    /// Play the sound at a position in the world
+    pub fn with_position(mut self, position: Vec3) -> Self {
+        self.origin = SoundOrigin::Position(position);
+        self
+    }
+
+    /// Play the sound at the position of an entity
+    pub fn with_entity(mut self, id: Entity) -> Self {
+        self.origin = SoundOrigin::Entity(id);
+        self
+    }
+
+    /// Set the bus of a non-spatial sound
+    pub fn with_bus(mut self, bus: AudioBus) -> Self {
+        self.bus = bus;
+        self
+    }
+
+    /// Set the volume, in the range `0..=1`
+    pub fn with_volume(mut self, volume: f32) -> Self {
+        self.volume = volume;
+        self
+    }
+
+    /// Set the distances between which the sound attenuates
+    pub fn with_distances(mut self, min_distance: f32, max_distance: f32) -> Self {
+        self.min_distance = min_distance;
+        self.max_distance = max_distance;
+        self
+    }
+}
+
+impl Event for PlaySound {}
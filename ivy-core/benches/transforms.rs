@@ -0,0 +1,78 @@
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use flax::{components::child_of, Entity, Query, Schedule, World};
+use glam::{Quat, Vec3};
+use ivy_core::{
+    components::{position, TransformBundle},
+    systems::update_transform_system,
+};
+
+/// Spawns `roots` trees with the given branching factor and depth, similar to imported models
+fn hierarchy_scene(roots: usize, branching: usize, depth: usize) -> World {
+    fn spawn_children(world: &mut World, parent: Entity, branching: usize, depth: usize) {
+        if depth == 0 {
+            return;
+        }
+
+        for i in 0..branching {
+            let child = Entity::builder()
+                .mount(
+                    TransformBundle::default()
+                        .with_position(Vec3::new(i as f32, 1.0, 0.0))
+                        .with_rotation(Quat::from_rotation_y(i as f32 * 0.5)),
+                )
+                .set(child_of(parent), ())
+                .spawn(world);
+
+            spawn_children(world, child, branching, depth - 1);
+        }
+    }
+
+    let mut world = World::new();
+    for i in 0..roots {
+        let root = Entity::builder()
+            .mount(TransformBundle::default().with_position(Vec3::new(0.0, 0.0, i as f32)))
+            .spawn(&mut world);
+
+        spawn_children(&mut world, root, branching, depth);
+    }
+
+    world
+}
+
+/// Moves every root to mark the whole hierarchy as modified
+fn move_roots(world: &World) {
+    for pos in &mut Query::new(position().as_mut())
+        .without_relation(child_of)
+        .borrow(world)
+    {
+        pos.x += 0.1;
+    }
+}
+
+fn transform_propagation(c: &mut Criterion) {
+    let mut group = c.benchmark_group("transform_propagation");
+
+    for (roots, branching, depth) in [(1000, 0, 0), (100, 4, 3), (10, 8, 3)] {
+        let mut world = hierarchy_scene(roots, branching, depth);
+        let entity_count = Query::new(position()).borrow(&world).count();
+
+        let mut schedule = Schedule::builder()
+            .with_system(update_transform_system())
+            .build();
+
+        group.bench_function(
+            BenchmarkId::new(format!("{roots}x{branching}^{depth}"), entity_count),
+            |b| {
+                b.iter(|| {
+                    move_roots(&world);
+                    schedule.execute_seq(&mut world).unwrap();
+                })
+            },
+        );
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, transform_propagation);
+criterion_main!(benches);
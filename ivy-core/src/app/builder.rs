@@ -1,3 +1,5 @@
+use ivy_random::service::Random;
+
 use self::driver::{DefaultDriver, Driver};
 use super::*;
 
@@ -10,7 +12,7 @@ impl AppBuilder {
     pub fn new() -> Self {
         Self {
             app: App::new(),
-            driver: Box::new(DefaultDriver {}),
+            driver: Box::new(DefaultDriver::new()),
         }
     }
 
@@ -20,6 +22,15 @@ impl AppBuilder {
         self
     }
 
+    /// Seeds the random number streams of the world, making the simulation deterministic
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.app
+            .world
+            .set(engine(), components::random(), Random::new(seed))
+            .unwrap();
+        self
+    }
+
     pub fn build(self) -> App {
         self.app
     }
@@ -39,6 +50,63 @@ impl AppBuilder {
         self.app.push_layer(layer);
         self
     }
+
+    /// Pushes a layer with a label, which other layers can be inserted relative to
+    pub fn with_labeled_layer<T: Layer>(mut self, label: impl Into<String>, layer: T) -> Self {
+        self.app.push_labeled_layer(label, layer);
+        self
+    }
+
+    /// Inserts a layer directly below the layer with `label`.
+    ///
+    /// # Panics
+    /// If no layer with `label` exists
+    pub fn insert_before<T: Layer>(mut self, label: &str, layer: T) -> Self {
+        self.app.insert_layer_before(label, layer).unwrap();
+        self
+    }
+
+    /// Inserts a layer directly above the layer with `label`.
+    ///
+    /// # Panics
+    /// If no layer with `label` exists
+    pub fn insert_after<T: Layer>(mut self, label: &str, layer: T) -> Self {
+        self.app.insert_layer_after(label, layer).unwrap();
+        self
+    }
+
+    /// Pushes a layer which only receives events while the app is in one of `states`
+    pub fn with_layer_in_states<S: AppState, T: Layer>(
+        mut self,
+        states: impl IntoIterator<Item = S>,
+        layer: T,
+    ) -> Self {
+        self.app.push_layer_in_states(states, layer);
+        self
+    }
+
+    /// Set the initial state of the app
+    pub fn with_state<S: AppState>(mut self, state: S) -> Self {
+        self.app.set_state(state).unwrap();
+        self
+    }
+
+    /// Adds a plugin, which can push its own layers and set up the world.
+    ///
+    /// # Panics
+    /// If the plugin fails to build
+    pub fn with_plugin(mut self, plugin: impl AppPlugin) -> Self {
+        self.app.add_plugin(plugin).unwrap();
+        self
+    }
+
+    /// Pushes the layer if present, e.g; for layers enabled through the environment
+    pub fn with_optional_layer<T: Layer>(self, layer: Option<T>) -> Self {
+        match layer {
+            Some(layer) => self.with_layer(layer),
+            None => self,
+        }
+    }
 }
 
 impl Default for AppBuilder {
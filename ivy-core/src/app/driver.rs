@@ -1,4 +1,4 @@
-use std::time::Instant;
+use std::time::{Duration, Instant};
 
 use crate::App;
 
@@ -7,19 +7,114 @@ pub trait Driver {
     fn enter(&mut self, app: &mut App) -> anyhow::Result<()>;
 }
 
-pub struct DefaultDriver {}
+/// Limits the rate at which the app is updated
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct FrameLimit {
+    /// Maximum frames per second while the app is active
+    pub max_fps: Option<f64>,
+    /// Maximum frames per second while the app is idle, such as when the window is unfocused or
+    /// minimized
+    pub idle_fps: Option<f64>,
+}
+
+impl FrameLimit {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the maximum frames per second while the app is active
+    pub fn with_max_fps(mut self, max_fps: f64) -> Self {
+        self.max_fps = Some(max_fps);
+        self
+    }
+
+    /// Set the maximum frames per second while the app is idle
+    pub fn with_idle_fps(mut self, idle_fps: f64) -> Self {
+        self.idle_fps = Some(idle_fps);
+        self
+    }
+}
+
+/// Paces the frames of a driver according to a [`FrameLimit`]
+#[derive(Debug, Clone)]
+pub struct FramePacer {
+    limit: FrameLimit,
+    last_frame: Option<Instant>,
+    idle: bool,
+}
+
+impl FramePacer {
+    pub fn new(limit: FrameLimit) -> Self {
+        Self {
+            limit,
+            last_frame: None,
+            idle: false,
+        }
+    }
+
+    pub fn set_idle(&mut self, idle: bool) {
+        self.idle = idle;
+    }
+
+    pub fn is_idle(&self) -> bool {
+        self.idle
+    }
+
+    fn frame_time(&self) -> Option<Duration> {
+        let fps = if self.idle {
+            self.limit.idle_fps.or(self.limit.max_fps)
+        } else {
+            self.limit.max_fps
+        };
+
+        fps.map(|fps| Duration::from_secs_f64(1.0 / fps))
+    }
+
+    /// Returns when the next frame is due, or `None` if frames are not limited
+    pub fn next_frame(&self) -> Option<Instant> {
+        Some(self.last_frame? + self.frame_time()?)
+    }
+
+    /// Marks the start of a new frame
+    pub fn begin_frame(&mut self, now: Instant) {
+        self.last_frame = Some(now);
+    }
+}
+
+#[derive(Default)]
+pub struct DefaultDriver {
+    frame_limit: FrameLimit,
+}
+
+impl DefaultDriver {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the frame limit
+    pub fn with_frame_limit(mut self, frame_limit: FrameLimit) -> Self {
+        self.frame_limit = frame_limit;
+        self
+    }
+}
 
 impl Driver for DefaultDriver {
     fn enter(&mut self, app: &mut App) -> anyhow::Result<()> {
         app.running = true;
 
         let mut current_time = Instant::now();
+        let mut pacer = FramePacer::new(self.frame_limit);
 
         // Update layers
         while app.running {
+            if let Some(next_frame) = pacer.next_frame() {
+                std::thread::sleep(next_frame.saturating_duration_since(Instant::now()));
+            }
+
             let new_time = Instant::now();
             let delta = new_time - current_time;
             current_time = new_time;
+            pacer.begin_frame(new_time);
 
             app.tick(delta)?;
         }
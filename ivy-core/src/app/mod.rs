@@ -1,34 +1,59 @@
 mod builder;
 pub mod driver;
 pub mod event;
+mod plugin;
+mod state;
 
-use std::time::Duration;
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use anyhow::Context;
 
 pub use builder::*;
 pub use event::*;
 use flax::World;
 use ivy_assets::{service::FileSystemMapService, stored::DynamicStore, AssetCache};
+pub use plugin::AppPlugin;
+pub(crate) use state::{in_states, LayerGate};
+pub use state::{AppState, CurrentState, StateEnter, StateExit, StateTransitions};
 
-use self::driver::Driver;
+use self::{driver::Driver, state::StateDyn};
 use crate::{
     components::{self, engine},
     events::EventContext,
-    layer::events::{Event, EventRegistry},
+    layer::events::{Event, EventQueue, EventRegistry},
+    memory::MemoryReport,
+    notifications::Notifications,
     Layer, LayerDyn,
 };
 
+struct LayerInfo {
+    /// Used to insert layers relative to each other
+    label: String,
+    /// Restricts the layer to specific states
+    gate: Option<LayerGate>,
+}
+
 pub struct App {
     name: String,
 
     store: DynamicStore,
     layers: Vec<Box<dyn LayerDyn>>,
+    layer_info: Vec<LayerInfo>,
     /// Event bus for layers
     pub event_registry: EventRegistry,
+    event_queue: EventQueue,
+
+    state: Option<Arc<dyn StateDyn>>,
+    state_transitions: StateTransitions,
 
     pub assets: AssetCache,
     pub world: World,
 
     running: bool,
+    initialized: bool,
 }
 
 impl App {
@@ -36,18 +61,59 @@ impl App {
         let asset_cache = AssetCache::new();
         asset_cache.register_service(FileSystemMapService::new("./assets"));
 
+        let event_queue = EventQueue::new();
+        let state_transitions = StateTransitions::new();
+
         let mut world = World::new();
         world
             .set(engine(), components::gizmos(), Default::default())
             .unwrap();
+        world
+            .set(engine(), components::time(), Default::default())
+            .unwrap();
+        world
+            .set(engine(), components::frame_stats(), Default::default())
+            .unwrap();
+        world
+            .set(engine(), components::scope_timings(), Default::default())
+            .unwrap();
+        world
+            .set(engine(), components::memory_report(), Default::default())
+            .unwrap();
+        let notifications = Notifications::new();
+        asset_cache.register_service(notifications.notifier());
+        world
+            .set(engine(), components::notifications(), notifications)
+            .unwrap();
+        world
+            .set(engine(), components::random(), Default::default())
+            .unwrap();
+        world
+            .set(engine(), components::event_channels(), Default::default())
+            .unwrap();
+        world
+            .set(engine(), components::event_queue(), event_queue.clone())
+            .unwrap();
+        world
+            .set(
+                engine(),
+                components::state_transitions(),
+                state_transitions.clone(),
+            )
+            .unwrap();
 
         Self {
             name: "Ivy".into(),
             layers: Default::default(),
+            layer_info: Default::default(),
             event_registry: Default::default(),
+            event_queue,
+            state: None,
+            state_transitions,
             world,
             assets: asset_cache,
             running: false,
+            initialized: false,
             store: DynamicStore::new(),
         }
     }
@@ -57,18 +123,60 @@ impl App {
     }
 
     pub fn tick(&mut self, delta: Duration) -> anyhow::Result<()> {
-        self.event_registry.emit(
-            &mut self.layers,
-            &mut EventContext {
-                world: &mut self.world,
-                assets: &self.assets,
-                store: &mut self.store,
-            },
-            &TickEvent(delta),
-        )
+        let start = Instant::now();
+
+        let mut ctx = EventContext {
+            world: &mut self.world,
+            assets: &self.assets,
+            store: &mut self.store,
+        };
+
+        self.event_registry
+            .emit(&mut self.layers, &mut ctx, &TickEvent(delta))?;
+
+        for event in self.event_queue.drain() {
+            self.event_registry
+                .emit_dyn(&mut self.layers, &mut ctx, &*event)?;
+        }
+
+        let transitions = self.state_transitions.drain().collect::<Vec<_>>();
+        for state in transitions {
+            self.transition(state)?;
+        }
+
+        if let Ok(mut channels) = self.world.get_mut(engine(), components::event_channels()) {
+            channels.update();
+        }
+
+        if let Ok(timings) = self
+            .world
+            .get(engine(), components::scope_timings())
+            .map(|v| v.clone())
+        {
+            timings.record("tick", start.elapsed());
+            if let Ok(mut stats) = self.world.get_mut(engine(), components::frame_stats()) {
+                stats.end_frame(delta, &timings);
+            }
+        }
+
+        let time = self
+            .world
+            .get(engine(), components::time())
+            .map(|v| *v)
+            .unwrap_or_default();
+        if let Ok(gizmos) = self.world.get(engine(), components::gizmos()) {
+            gizmos.advance(time.scaled(delta));
+        }
+
+        if let Ok(mut report) = self.world.get_mut(engine(), components::memory_report()) {
+            *report = MemoryReport::collect();
+        }
+
+        Ok(())
     }
 
     pub fn init(&mut self) -> anyhow::Result<()> {
+        self.initialized = true;
         for (index, layer) in &mut self.layers.iter_mut().enumerate() {
             layer.register_dyn(
                 &mut self.world,
@@ -78,15 +186,101 @@ impl App {
             )?;
         }
 
-        self.event_registry.emit(
+        let mut ctx = EventContext {
+            world: &mut self.world,
+            assets: &self.assets,
+            store: &mut self.store,
+        };
+
+        self.event_registry
+            .emit(&mut self.layers, &mut ctx, &PostInitEvent)?;
+
+        if let Some(state) = &self.state {
+            self.event_registry
+                .emit_dyn(&mut self.layers, &mut ctx, &*state.enter_event())?;
+        }
+
+        Ok(())
+    }
+
+    /// Adds a plugin to the app
+    pub fn add_plugin(&mut self, plugin: impl AppPlugin) -> anyhow::Result<()> {
+        plugin.build(self)
+    }
+
+    /// Returns the current state of the app, if it is of type `S`
+    pub fn state<S: AppState>(&self) -> Option<&S> {
+        self.state.as_ref()?.as_any().downcast_ref()
+    }
+
+    /// Transitions the app to `state`.
+    ///
+    /// Layers receive [`StateExit`] for the previous state, and [`StateEnter`] for the new state
+    /// after the layers restricted to it have been enabled.
+    ///
+    /// See [`StateTransitions`] for changing state from systems.
+    pub fn set_state<S: AppState>(&mut self, state: S) -> anyhow::Result<()> {
+        self.transition(Box::new(state))
+    }
+
+    fn transition(&mut self, state: Box<dyn StateDyn>) -> anyhow::Result<()> {
+        if self.state.as_ref().is_some_and(|v| v.eq_dyn(&*state)) {
+            return Ok(());
+        }
+
+        if !self.initialized {
+            self.set_current_state(state);
+            return Ok(());
+        }
+
+        tracing::info!(?state, "Entering state");
+
+        if let Some(old) = &self.state {
+            self.event_registry.emit_dyn(
+                &mut self.layers,
+                &mut EventContext {
+                    world: &mut self.world,
+                    assets: &self.assets,
+                    store: &mut self.store,
+                },
+                &*old.exit_event(),
+            )?;
+        }
+
+        let event = state.enter_event();
+        self.set_current_state(state);
+
+        self.event_registry.emit_dyn(
             &mut self.layers,
             &mut EventContext {
                 world: &mut self.world,
                 assets: &self.assets,
                 store: &mut self.store,
             },
-            &PostInitEvent,
-        )
+            &*event,
+        )?;
+
+        Ok(())
+    }
+
+    fn set_current_state(&mut self, state: Box<dyn StateDyn>) {
+        let state: Arc<dyn StateDyn> = state.into();
+        self.state = Some(state.clone());
+
+        // Lets systems check the state, see `ScheduledLayer::with_plugin_in_states`
+        self.world
+            .set(engine(), components::app_state(), CurrentState::new(state))
+            .unwrap();
+
+        self.update_layer_gates();
+    }
+
+    fn update_layer_gates(&mut self) {
+        let state = self.state.as_ref().map(|v| v.as_any());
+        for (index, info) in self.layer_info.iter().enumerate() {
+            let enabled = info.gate.as_ref().map_or(true, |gate| gate(state));
+            self.event_registry.set_layer_enabled(index, enabled);
+        }
     }
 
     pub fn run(&mut self, driver: &mut (impl Driver + ?Sized)) -> anyhow::Result<()> {
@@ -98,8 +292,132 @@ impl App {
         &self.name
     }
 
+    /// Pushes a layer to the top of the layer stack, labeled by its type name
     pub fn push_layer<T: Layer>(&mut self, layer: T) {
-        self.layers.push(Box::new(layer));
+        self.push_labeled_layer(std::any::type_name::<T>(), layer)
+    }
+
+    /// Pushes a layer to the top of the layer stack, which other layers can be inserted relative
+    /// to through `label`
+    pub fn push_labeled_layer<T: Layer>(&mut self, label: impl Into<String>, layer: T) {
+        self.insert_layer_at(self.layers.len(), label.into(), None, Box::new(layer))
+            .expect("Failed to register layer")
+    }
+
+    /// Pushes a layer which only receives events while the app is in one of `states`
+    pub fn push_layer_in_states<S: AppState, T: Layer>(
+        &mut self,
+        states: impl IntoIterator<Item = S>,
+        layer: T,
+    ) {
+        let gate = in_states(states.into_iter().collect());
+        self.insert_layer_at(
+            self.layers.len(),
+            std::any::type_name::<T>().into(),
+            Some(gate),
+            Box::new(layer),
+        )
+        .expect("Failed to register layer")
+    }
+
+    /// Returns the position of the first layer with `label` in the layer stack.
+    ///
+    /// Layers pushed without a label are labeled by their type name.
+    pub fn layer_index(&self, label: &str) -> Option<usize> {
+        self.layer_info.iter().position(|v| v.label == label)
+    }
+
+    fn find_layer(&self, label: &str) -> anyhow::Result<usize> {
+        self.layer_index(label)
+            .with_context(|| format!("No layer labeled {label:?}"))
+    }
+
+    /// Inserts a layer directly below the layer with `label`, receiving events before it
+    pub fn insert_layer_before<T: Layer>(&mut self, label: &str, layer: T) -> anyhow::Result<()> {
+        let index = self.find_layer(label)?;
+        self.insert_layer_at(
+            index,
+            std::any::type_name::<T>().into(),
+            None,
+            Box::new(layer),
+        )
+    }
+
+    /// Inserts a layer directly above the layer with `label`, receiving events after it
+    pub fn insert_layer_after<T: Layer>(&mut self, label: &str, layer: T) -> anyhow::Result<()> {
+        let index = self.find_layer(label)?;
+        self.insert_layer_at(
+            index + 1,
+            std::any::type_name::<T>().into(),
+            None,
+            Box::new(layer),
+        )
+    }
+
+    fn insert_layer_at(
+        &mut self,
+        index: usize,
+        label: String,
+        gate: Option<LayerGate>,
+        mut layer: Box<dyn LayerDyn>,
+    ) -> anyhow::Result<()> {
+        self.event_registry.insert_layer(index);
+
+        let state = self.state.as_ref().map(|v| v.as_any());
+        let enabled = gate.as_ref().map_or(true, |gate| gate(state));
+        self.event_registry.set_layer_enabled(index, enabled);
+
+        if self.initialized {
+            let result = layer.register_dyn(
+                &mut self.world,
+                &self.assets,
+                &mut self.event_registry,
+                index,
+            );
+
+            // Drop the slot and any callbacks registered before the failure
+            if let Err(err) = result {
+                self.event_registry.remove_layer(index);
+                return Err(err);
+            }
+        }
+
+        self.layers.insert(index, layer);
+        self.layer_info.insert(index, LayerInfo { label, gate });
+        Ok(())
+    }
+
+    /// Removes the layer with `label` from the layer stack, along with its event subscriptions.
+    ///
+    /// Entities and resources created by the layer are left in the world.
+    pub fn remove_layer(&mut self, label: &str) -> anyhow::Result<Box<dyn LayerDyn>> {
+        let index = self.find_layer(label)?;
+        self.event_registry.remove_layer(index);
+        self.layer_info.remove(index);
+        Ok(self.layers.remove(index))
+    }
+
+    /// Replaces the layer with `label` by `layer`, keeping its position, label and states in the
+    /// layer stack
+    pub fn replace_layer<T: Layer>(
+        &mut self,
+        label: &str,
+        layer: T,
+    ) -> anyhow::Result<Box<dyn LayerDyn>> {
+        let index = self.find_layer(label)?;
+        let mut layer: Box<dyn LayerDyn> = Box::new(layer);
+
+        if self.initialized {
+            self.event_registry.unregister_layer(index);
+            layer.register_dyn(
+                &mut self.world,
+                &self.assets,
+                &mut self.event_registry,
+                index,
+            )?;
+        }
+
+        Ok(std::mem::replace(&mut self.layers[index], layer))
     }
 
     /// Get a mutable reference to the app's world.
@@ -112,6 +430,11 @@ impl App {
         &mut self.assets
     }
 
+    /// Get a mutable reference to the store shared with the layers, e.g; for non-send values
+    pub fn store_mut(&mut self) -> &mut DynamicStore {
+        &mut self.store
+    }
+
     /// Get a reference to the app's world.
     pub fn world(&self) -> &World {
         &self.world
@@ -141,3 +464,71 @@ impl Default for App {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod test {
+    use std::{cell::RefCell, rc::Rc};
+
+    use super::*;
+    use crate::layer::events::EventRegisterContext;
+
+    #[derive(Debug)]
+    struct Ping;
+
+    impl Event for Ping {}
+
+    type Log = Rc<RefCell<Vec<&'static str>>>;
+
+    struct RecordLayer {
+        name: &'static str,
+        log: Log,
+        fail: bool,
+    }
+
+    impl Layer for RecordLayer {
+        fn register(
+            &mut self,
+            _: &mut World,
+            _: &AssetCache,
+            mut events: EventRegisterContext<Self>,
+        ) -> anyhow::Result<()> {
+            events.subscribe(|this, _, _: &Ping| {
+                this.log.borrow_mut().push(this.name);
+                Ok(())
+            });
+
+            if self.fail {
+                anyhow::bail!("Failed to register {}", self.name);
+            }
+
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn failed_layer_insert() {
+        let log = Log::default();
+        let layer = |name, fail| RecordLayer {
+            name,
+            log: log.clone(),
+            fail,
+        };
+
+        let mut app = App::new();
+        app.push_labeled_layer("a", layer("a", false));
+        app.push_labeled_layer("b", layer("b", false));
+        app.init().unwrap();
+
+        assert!(app.insert_layer_after("a", layer("failing", true)).is_err());
+        assert_eq!(app.layer_index("b"), Some(1));
+
+        app.emit_event(Ping).unwrap();
+        assert_eq!(log.take(), ["a", "b"]);
+
+        // The layer stack is still consistent for later inserts
+        app.insert_layer_after("a", layer("c", false)).unwrap();
+
+        app.emit_event(Ping).unwrap();
+        assert_eq!(log.take(), ["a", "c", "b"]);
+    }
+}
@@ -0,0 +1,20 @@
+use super::App;
+
+/// Adds a subsystem to the app, such as physics, UI or audio, with everything it needs in a single
+/// step.
+///
+/// A plugin can push layers, register asset services, set up the world and put non-send values for
+/// the renderer into the [`App::store_mut`]. For adding systems to an existing schedule, see
+/// [`crate::update_layer::Plugin`].
+pub trait AppPlugin {
+    fn build(self, app: &mut App) -> anyhow::Result<()>;
+}
+
+impl<F> AppPlugin for F
+where
+    F: FnOnce(&mut App) -> anyhow::Result<()>,
+{
+    fn build(self, app: &mut App) -> anyhow::Result<()> {
+        self(app)
+    }
+}
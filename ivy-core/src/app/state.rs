@@ -0,0 +1,110 @@
+use std::{any::Any, fmt::Debug, sync::Arc};
+
+use crate::layer::events::Event;
+
+/// A state of the application, such as loading, in a menu or in game.
+///
+/// Layers can be restricted to run only in specific states, and are notified through
+/// [`StateEnter`] and [`StateExit`] when the state changes.
+pub trait AppState: 'static + Send + Sync + Debug + Clone + PartialEq {}
+
+impl<T> AppState for T where T: 'static + Send + Sync + Debug + Clone + PartialEq {}
+
+/// Emitted after the app enters a new state
+#[derive(Debug, Clone)]
+pub struct StateEnter<S>(pub S);
+
+/// Emitted before the app leaves the current state
+#[derive(Debug, Clone)]
+pub struct StateExit<S>(pub S);
+
+impl<S: AppState> Event for StateEnter<S> {}
+impl<S: AppState> Event for StateExit<S> {}
+
+pub(crate) trait StateDyn: 'static + Send + Sync + Debug {
+    fn as_any(&self) -> &dyn Any;
+    fn eq_dyn(&self, other: &dyn StateDyn) -> bool;
+    fn enter_event(&self) -> Box<dyn Event>;
+    fn exit_event(&self) -> Box<dyn Event>;
+}
+
+impl<S: AppState> StateDyn for S {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn eq_dyn(&self, other: &dyn StateDyn) -> bool {
+        other.as_any().downcast_ref::<S>() == Some(self)
+    }
+
+    fn enter_event(&self) -> Box<dyn Event> {
+        Box::new(StateEnter(self.clone()))
+    }
+
+    fn exit_event(&self) -> Box<dyn Event> {
+        Box::new(StateExit(self.clone()))
+    }
+}
+
+/// The current state of the app, stored on the engine entity
+#[derive(Debug, Clone, Default)]
+pub struct CurrentState {
+    state: Option<Arc<dyn StateDyn>>,
+}
+
+impl CurrentState {
+    pub(crate) fn new(state: Arc<dyn StateDyn>) -> Self {
+        Self { state: Some(state) }
+    }
+
+    /// Returns the state, if it is of type `S`
+    pub fn get<S: AppState>(&self) -> Option<&S> {
+        self.as_any()?.downcast_ref()
+    }
+
+    pub(crate) fn as_any(&self) -> Option<&dyn Any> {
+        self.state.as_deref().map(|v| v.as_any())
+    }
+}
+
+/// Requests state transitions from outside of the app, such as from systems.
+///
+/// Stored on the engine entity. Transitions are applied in order after each tick.
+#[derive(Clone)]
+pub struct StateTransitions {
+    tx: flume::Sender<Box<dyn StateDyn>>,
+    rx: flume::Receiver<Box<dyn StateDyn>>,
+}
+
+impl StateTransitions {
+    pub fn new() -> Self {
+        let (tx, rx) = flume::unbounded();
+        Self { tx, rx }
+    }
+
+    /// Transitions the app to `state`
+    pub fn set<S: AppState>(&self, state: S) {
+        self.tx.send(Box::new(state)).ok();
+    }
+
+    pub(crate) fn drain(&self) -> flume::TryIter<'_, Box<dyn StateDyn>> {
+        self.rx.try_iter()
+    }
+}
+
+impl Default for StateTransitions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Decides if a layer is enabled for the current state
+pub(crate) type LayerGate = Box<dyn Fn(Option<&dyn Any>) -> bool>;
+
+pub(crate) fn in_states<S: AppState>(states: Vec<S>) -> LayerGate {
+    Box::new(move |current| {
+        current
+            .and_then(|v| v.downcast_ref::<S>())
+            .is_some_and(|v| states.contains(v))
+    })
+}
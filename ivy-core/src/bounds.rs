@@ -0,0 +1,260 @@
+//! Bounding volumes of entities, shared by culling, level of detail, and spatial queries.
+//!
+//! The bounds are stored in the local space of the entity, and are transformed by the
+//! [`world_transform`] when used. Meshes have their bounds computed by the graphics layer.
+use flax::{entity_ids, Entity, Query, World};
+use glam::{Mat3, Mat4, Vec3};
+
+use crate::components::world_transform;
+
+flax::component! {
+    /// Local space bounding box of the entity in its rest pose
+    pub aabb: Aabb,
+    /// Local space bounding sphere of the entity, which contains the current pose of animated
+    /// meshes
+    pub bounding_sphere: BoundingSphere,
+}
+
+/// Axis aligned bounding box
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Aabb {
+    pub min: Vec3,
+    pub max: Vec3,
+}
+
+impl Aabb {
+    pub fn new(min: Vec3, max: Vec3) -> Self {
+        Self { min, max }
+    }
+
+    pub fn from_center(center: Vec3, half_extents: Vec3) -> Self {
+        Self::new(center - half_extents, center + half_extents)
+    }
+
+    /// Returns the smallest box containing all points, or `None` if there are no points
+    pub fn from_points(points: impl IntoIterator<Item = Vec3>) -> Option<Self> {
+        points.into_iter().fold(None, |acc, p| match acc {
+            Some(v) => Some(Self::new(Vec3::min(v.min, p), Vec3::max(v.max, p))),
+            None => Some(Self::new(p, p)),
+        })
+    }
+
+    pub fn center(&self) -> Vec3 {
+        (self.min + self.max) * 0.5
+    }
+
+    pub fn half_extents(&self) -> Vec3 {
+        (self.max - self.min) * 0.5
+    }
+
+    pub fn size(&self) -> Vec3 {
+        self.max - self.min
+    }
+
+    pub fn union(&self, other: &Self) -> Self {
+        Self::new(self.min.min(other.min), self.max.max(other.max))
+    }
+
+    pub fn contains(&self, point: Vec3) -> bool {
+        point.cmpge(self.min).all() && point.cmple(self.max).all()
+    }
+
+    pub fn intersects(&self, other: &Self) -> bool {
+        self.min.cmple(other.max).all() && self.max.cmpge(other.min).all()
+    }
+
+    /// Returns the box containing this box after being transformed
+    pub fn transform(&self, transform: &Mat4) -> Self {
+        let center = transform.transform_point3(self.center());
+        let m = Mat3::from_mat4(*transform);
+        let abs = Mat3::from_cols(m.x_axis.abs(), m.y_axis.abs(), m.z_axis.abs());
+
+        Self::from_center(center, abs * self.half_extents())
+    }
+
+    /// Returns the sphere enclosing the box
+    pub fn bounding_sphere(&self) -> BoundingSphere {
+        BoundingSphere::new(self.center(), self.half_extents().length())
+    }
+
+    /// Returns the distance along the ray to where it enters the box, or zero if it starts inside
+    pub fn intersect_ray(&self, origin: Vec3, dir: Vec3) -> Option<f32> {
+        let inv = dir.recip();
+        let a = (self.min - origin) * inv;
+        let b = (self.max - origin) * inv;
+
+        let near = a.min(b).max_element();
+        let far = a.max(b).min_element();
+
+        (near <= far && far >= 0.0).then(|| near.max(0.0))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct BoundingSphere {
+    pub center: Vec3,
+    pub radius: f32,
+}
+
+impl BoundingSphere {
+    pub fn new(center: Vec3, radius: f32) -> Self {
+        Self { center, radius }
+    }
+
+    /// Returns a sphere containing all points, centered on their bounding box, or `None` if there
+    /// are no points
+    pub fn from_points(points: impl IntoIterator<Item = Vec3> + Clone) -> Option<Self> {
+        let center = Aabb::from_points(points.clone())?.center();
+        let radius = points
+            .into_iter()
+            .map(|v| v.distance_squared(center))
+            .fold(0.0, f32::max)
+            .sqrt();
+
+        Some(Self::new(center, radius))
+    }
+
+    /// Radius of the sphere centered at the origin which contains this sphere
+    pub fn origin_radius(&self) -> f32 {
+        self.center.length() + self.radius
+    }
+
+    pub fn union(&self, other: &Self) -> Self {
+        let offset = other.center - self.center;
+        let distance = offset.length();
+
+        if distance + other.radius <= self.radius {
+            *self
+        } else if distance + self.radius <= other.radius {
+            *other
+        } else {
+            let radius = (distance + self.radius + other.radius) * 0.5;
+            let center = self.center + offset * ((radius - self.radius) / distance);
+            Self::new(center, radius)
+        }
+    }
+
+    pub fn contains(&self, point: Vec3) -> bool {
+        self.center.distance_squared(point) <= self.radius * self.radius
+    }
+
+    pub fn intersects(&self, other: &Self) -> bool {
+        let radius = self.radius + other.radius;
+        self.center.distance_squared(other.center) <= radius * radius
+    }
+
+    /// Returns the sphere containing this sphere after being transformed
+    pub fn transform(&self, transform: &Mat4) -> Self {
+        let scale = transform
+            .x_axis
+            .truncate()
+            .length()
+            .max(transform.y_axis.truncate().length())
+            .max(transform.z_axis.truncate().length());
+
+        Self::new(transform.transform_point3(self.center), self.radius * scale)
+    }
+
+    /// Returns the distance along the ray to where it enters the sphere, or zero if it starts
+    /// inside.
+    ///
+    /// `dir` is expected to be normalized.
+    pub fn intersect_ray(&self, origin: Vec3, dir: Vec3) -> Option<f32> {
+        let to_center = self.center - origin;
+        let along = to_center.dot(dir);
+        let dist_sq = to_center.length_squared() - along * along;
+        let radius_sq = self.radius * self.radius;
+
+        if dist_sq > radius_sq {
+            return None;
+        }
+
+        let half_chord = (radius_sq - dist_sq).sqrt();
+        let far = along + half_chord;
+
+        (far >= 0.0).then(|| (along - half_chord).max(0.0))
+    }
+}
+
+/// Returns the world space bounding sphere of every entity
+fn world_spheres(world: &World) -> Vec<(Entity, BoundingSphere)> {
+    Query::new((entity_ids(), world_transform(), bounding_sphere()))
+        .borrow(world)
+        .iter()
+        .map(|(id, transform, sphere)| (id, sphere.transform(transform)))
+        .collect()
+}
+
+/// Returns all entities whose bounds overlap the sphere
+pub fn overlap_sphere(world: &World, sphere: BoundingSphere) -> Vec<Entity> {
+    world_spheres(world)
+        .into_iter()
+        .filter(|(_, v)| v.intersects(&sphere))
+        .map(|(id, _)| id)
+        .collect()
+}
+
+/// Returns the entity with the nearest bounds along the ray, and the distance to them.
+///
+/// This is a coarse test against the bounding spheres, and does not require any colliders.
+pub fn raycast_bounds(world: &World, origin: Vec3, dir: Vec3) -> Option<(Entity, f32)> {
+    let dir = dir.normalize_or_zero();
+
+    world_spheres(world)
+        .into_iter()
+        .filter_map(|(id, v)| Some((id, v.intersect_ray(origin, dir)?)))
+        .min_by(|a, b| a.1.total_cmp(&b.1))
+}
+
+#[cfg(test)]
+mod test {
+    use glam::{vec3, Quat};
+
+    use super::*;
+
+    #[test]
+    fn transform_aabb() {
+        let aabb = Aabb::new(vec3(-1.0, -2.0, -3.0), vec3(1.0, 2.0, 3.0));
+
+        let moved = aabb.transform(&Mat4::from_scale_rotation_translation(
+            Vec3::splat(2.0),
+            Quat::from_rotation_y(std::f32::consts::FRAC_PI_2),
+            vec3(10.0, 0.0, 0.0),
+        ));
+
+        assert!(moved.min.abs_diff_eq(vec3(4.0, -4.0, -2.0), 1e-4));
+        assert!(moved.max.abs_diff_eq(vec3(16.0, 4.0, 2.0), 1e-4));
+
+        let sphere = aabb
+            .bounding_sphere()
+            .transform(&Mat4::from_scale(vec3(1.0, 3.0, 1.0)));
+        assert!((sphere.radius - 14f32.sqrt() * 3.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn ray_intersection() {
+        let aabb = Aabb::new(Vec3::splat(-1.0), Vec3::ONE);
+        assert_eq!(aabb.intersect_ray(vec3(-5.0, 0.0, 0.0), Vec3::X), Some(4.0));
+        assert_eq!(aabb.intersect_ray(Vec3::ZERO, Vec3::X), Some(0.0));
+        assert_eq!(aabb.intersect_ray(vec3(-5.0, 2.0, 0.0), Vec3::X), None);
+        assert_eq!(aabb.intersect_ray(vec3(5.0, 0.0, 0.0), Vec3::X), None);
+
+        let sphere = BoundingSphere::new(vec3(0.0, 0.0, -10.0), 2.0);
+        assert_eq!(sphere.intersect_ray(Vec3::ZERO, Vec3::NEG_Z), Some(8.0));
+        assert_eq!(sphere.intersect_ray(Vec3::ZERO, Vec3::Z), None);
+    }
+
+    #[test]
+    fn sphere_union() {
+        let a = BoundingSphere::new(Vec3::ZERO, 1.0);
+        let b = BoundingSphere::new(vec3(4.0, 0.0, 0.0), 1.0);
+
+        let union = a.union(&b);
+        assert!(union.center.abs_diff_eq(vec3(2.0, 0.0, 0.0), 1e-5));
+        assert_eq!(union.radius, 3.0);
+
+        assert_eq!(a.union(&BoundingSphere::new(Vec3::X * 0.5, 0.2)), a);
+    }
+}
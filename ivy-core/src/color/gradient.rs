@@ -0,0 +1,159 @@
+use palette::{FromColor, LinSrgba, Mix, Oklaba};
+
+use super::Color;
+
+/// Color space which the colors of a gradient are blended in
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum GradientSpace {
+    /// Perceptually uniform, which avoids dark or muddy colors between saturated stops
+    #[default]
+    Oklab,
+    /// Linear light, matching how light sources add up
+    Linear,
+    /// Gamma encoded, matching the gradients of most image editors
+    Srgb,
+}
+
+impl GradientSpace {
+    pub fn mix(&self, a: Color, b: Color, t: f32) -> Color {
+        match self {
+            GradientSpace::Oklab => {
+                Color::from_color(Oklaba::from_color(a).mix(Oklaba::from_color(b), t))
+            }
+            GradientSpace::Linear => {
+                let a: LinSrgba = a.into_linear();
+                Color::from_linear(a.mix(b.into_linear(), t))
+            }
+            GradientSpace::Srgb => a.mix(b, t),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct GradientStop {
+    /// Position of the stop in `[0, 1]`
+    pub position: f32,
+    pub color: Color,
+}
+
+/// Blends between multiple colors, e.g; the color of a particle over its lifetime
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Gradient {
+    stops: Vec<GradientStop>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    space: GradientSpace,
+}
+
+impl Gradient {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a gradient with the colors evenly spaced
+    pub fn from_colors(colors: impl IntoIterator<Item = Color>) -> Self {
+        let colors = colors.into_iter().collect::<Vec<_>>();
+        let step = 1.0 / (colors.len().max(2) - 1) as f32;
+
+        Self {
+            stops: colors
+                .into_iter()
+                .enumerate()
+                .map(|(i, color)| GradientStop {
+                    position: i as f32 * step,
+                    color,
+                })
+                .collect(),
+            space: GradientSpace::default(),
+        }
+    }
+
+    pub fn with_stop(mut self, position: f32, color: Color) -> Self {
+        self.add_stop(position, color);
+        self
+    }
+
+    pub fn with_space(mut self, space: GradientSpace) -> Self {
+        self.space = space;
+        self
+    }
+
+    /// Add a stop, placed after any stops at the same position to allow hard edges
+    pub fn add_stop(&mut self, position: f32, color: Color) {
+        let position = position.clamp(0.0, 1.0);
+        let index = self.stops.partition_point(|v| v.position <= position);
+        self.stops.insert(index, GradientStop { position, color });
+    }
+
+    pub fn stops(&self) -> &[GradientStop] {
+        &self.stops
+    }
+
+    pub fn space(&self) -> GradientSpace {
+        self.space
+    }
+
+    /// Returns the color at `t` in `[0, 1]`.
+    ///
+    /// An empty gradient is transparent.
+    pub fn sample(&self, t: f32) -> Color {
+        let next = self.stops.partition_point(|v| v.position <= t);
+
+        match (
+            next.checked_sub(1).map(|i| &self.stops[i]),
+            self.stops.get(next),
+        ) {
+            (Some(a), Some(b)) => {
+                let t = (t - a.position) / (b.position - a.position).max(f32::EPSILON);
+                self.space.mix(a.color, b.color, t)
+            }
+            (Some(v), None) | (None, Some(v)) => v.color,
+            (None, None) => Color::new(0.0, 0.0, 0.0, 0.0),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::ColorExt;
+
+    #[test]
+    fn sample_stops() {
+        let gradient = Gradient::new()
+            .with_stop(0.5, Color::white())
+            .with_stop(0.0, Color::black())
+            .with_stop(1.0, Color::red())
+            .with_space(GradientSpace::Srgb);
+
+        assert_eq!(gradient.sample(-1.0), Color::black());
+        assert_eq!(gradient.sample(0.25), Color::new(0.5, 0.5, 0.5, 1.0));
+        assert_eq!(gradient.sample(0.5), Color::white());
+        assert_eq!(gradient.sample(2.0), Color::red());
+
+        assert_eq!(Gradient::new().sample(0.5), Color::transparent());
+    }
+
+    #[test]
+    fn blend_spaces() {
+        let gradient = Gradient::from_colors([Color::red(), Color::blue()]);
+        assert_eq!(gradient.stops()[1].position, 1.0);
+
+        // Endpoints survive the round trip through each space
+        for space in [GradientSpace::Oklab, GradientSpace::Linear] {
+            let gradient = gradient.clone().with_space(space);
+            let start = gradient.sample(0.0);
+            assert!((start.red - 1.0).abs() < 1e-3 && start.blue.abs() < 1e-3);
+        }
+
+        // Blending in linear light is brighter than the gamma encoded midpoint
+        let linear = gradient
+            .clone()
+            .with_space(GradientSpace::Linear)
+            .sample(0.5);
+        let srgb = gradient.with_space(GradientSpace::Srgb).sample(0.5);
+        assert!(linear.red > srgb.red);
+    }
+}
@@ -1,7 +1,15 @@
+mod gradient;
+mod named;
+mod temperature;
+
 use glam::{vec3, Vec3, Vec4};
 pub use palette;
 use palette::{FromColor, Hsla, Hsva, IntoColor, Srgb, Srgba};
 
+pub use gradient::*;
+pub use named::*;
+pub use temperature::*;
+
 pub type Color = Srgba;
 
 pub trait ColorExt {
@@ -100,3 +108,79 @@ pub fn to_linear_vec3(color: Srgb) -> Vec3 {
     let color = palette::rgb::LinSrgb::from_color(color);
     vec3(color.red, color.green, color.blue)
 }
+
+/// Parses a hex color of the form `#rgb`, `#rgba`, `#rrggbb` or `#rrggbbaa`.
+///
+/// The short forms repeat each digit, e.g; `#f80` is `#ff8800`.
+pub fn color_from_hex(hex: &str) -> Option<Color> {
+    let hex = hex.strip_prefix('#').unwrap_or(hex);
+    if !hex.bytes().all(|v| v.is_ascii_hexdigit()) {
+        return None;
+    }
+
+    let width = match hex.len() {
+        3 | 4 => 1,
+        6 | 8 => 2,
+        _ => return None,
+    };
+
+    let channel = |i: usize| {
+        let value = u8::from_str_radix(&hex[i * width..(i + 1) * width], 16).ok()?;
+        let value = if width == 1 { value * 17 } else { value };
+        Some(value as f32 / 255.0)
+    };
+
+    Some(Color::new(
+        channel(0)?,
+        channel(1)?,
+        channel(2)?,
+        if hex.len() == width * 4 {
+            channel(3)?
+        } else {
+            1.0
+        },
+    ))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn hex_forms() {
+        let g = 0x88 as f32 / 255.0;
+
+        assert_eq!(
+            color_from_hex("#ff8800"),
+            Some(Color::new(1.0, g, 0.0, 1.0))
+        );
+        assert_eq!(color_from_hex("ff8800"), Some(Color::new(1.0, g, 0.0, 1.0)));
+        assert_eq!(
+            color_from_hex("#FF8800"),
+            Some(Color::new(1.0, g, 0.0, 1.0))
+        );
+        assert_eq!(color_from_hex("#f80"), Some(Color::new(1.0, g, 0.0, 1.0)));
+        assert_eq!(
+            color_from_hex("#ff880080"),
+            Some(Color::new(1.0, g, 0.0, 0x80 as f32 / 255.0))
+        );
+        assert_eq!(color_from_hex("#f808"), Some(Color::new(1.0, g, 0.0, g)));
+    }
+
+    #[test]
+    fn invalid_hex() {
+        for hex in [
+            "",
+            "#",
+            "#ff",
+            "#ff880",
+            "#ff8800f",
+            "#ff8800ff0",
+            "#gg8800",
+            "#+f8800",
+            "#ff88é",
+        ] {
+            assert_eq!(color_from_hex(hex), None, "{hex:?}");
+        }
+    }
+}
@@ -0,0 +1,154 @@
+use std::collections::BTreeMap;
+
+use super::Color;
+
+/// A set of named colors, such as the theme of the UI.
+///
+/// Palette files are RON maps of names to hex colors, e.g; `{ "accent": "#ff8800" }`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ColorPalette {
+    colors: BTreeMap<String, Color>,
+}
+
+impl ColorPalette {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_color(mut self, name: impl Into<String>, color: Color) -> Self {
+        self.insert(name, color);
+        self
+    }
+
+    pub fn insert(&mut self, name: impl Into<String>, color: Color) {
+        self.colors.insert(name.into(), color);
+    }
+
+    pub fn get(&self, name: &str) -> Option<Color> {
+        self.colors.get(name).copied()
+    }
+
+    /// Returns the named color, or `fallback` if the palette does not contain it
+    pub fn get_or(&self, name: &str, fallback: Color) -> Color {
+        self.get(name).unwrap_or(fallback)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, Color)> {
+        self.colors.iter().map(|(k, &v)| (k.as_str(), v))
+    }
+
+    pub fn len(&self) -> usize {
+        self.colors.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.colors.is_empty()
+    }
+
+    #[cfg(feature = "serde")]
+    pub fn from_ron(source: &str) -> anyhow::Result<Self> {
+        use anyhow::Context;
+
+        let colors: BTreeMap<String, String> = ron::from_str(source)?;
+
+        colors
+            .into_iter()
+            .map(|(name, hex)| {
+                let color = super::color_from_hex(&hex)
+                    .with_context(|| format!("Invalid color {hex:?} for {name:?}"))?;
+                Ok((name, color))
+            })
+            .collect::<anyhow::Result<_>>()
+            .map(|colors| Self { colors })
+    }
+}
+
+impl FromIterator<(String, Color)> for ColorPalette {
+    fn from_iter<T: IntoIterator<Item = (String, Color)>>(iter: T) -> Self {
+        Self {
+            colors: iter.into_iter().collect(),
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+mod asset {
+    use std::path::Path;
+
+    use anyhow::Context;
+    use ivy_assets::{
+        fs::{AssetFromPath, AsyncAssetFromPath},
+        service::FileSystemMapService,
+        Asset, AssetCache,
+    };
+
+    use super::ColorPalette;
+
+    impl AssetFromPath for ColorPalette {
+        type Error = anyhow::Error;
+
+        fn load_from_path(path: &Path, assets: &AssetCache) -> anyhow::Result<Asset<Self>> {
+            let source = assets.service::<FileSystemMapService>().load_string(path)?;
+            let palette = ColorPalette::from_ron(&source)
+                .with_context(|| format!("Failed to load color palette {path:?}"))?;
+
+            Ok(assets.insert(palette))
+        }
+    }
+
+    impl AsyncAssetFromPath for ColorPalette {
+        type Error = anyhow::Error;
+
+        async fn load_from_path(path: &Path, assets: &AssetCache) -> anyhow::Result<Asset<Self>> {
+            let source = assets
+                .service::<FileSystemMapService>()
+                .load_string_async(path)
+                .await?;
+            let palette = ColorPalette::from_ron(&source)
+                .with_context(|| format!("Failed to load color palette {path:?}"))?;
+
+            Ok(assets.insert(palette))
+        }
+    }
+}
+
+#[cfg(all(test, feature = "serde"))]
+mod test {
+    use super::*;
+
+    #[test]
+    fn from_ron() {
+        let palette = ColorPalette::from_ron(
+            r##"{
+                "accent": "#ff8800",
+                "background": "#000",
+                "overlay": "#00000080",
+            }"##,
+        )
+        .unwrap();
+
+        assert_eq!(palette.len(), 3);
+        assert_eq!(
+            palette.get("accent"),
+            Some(Color::new(1.0, 0x88 as f32 / 255.0, 0.0, 1.0))
+        );
+        assert_eq!(
+            palette.get("background"),
+            Some(Color::new(0.0, 0.0, 0.0, 1.0))
+        );
+        assert_eq!(
+            palette.get("overlay"),
+            Some(Color::new(0.0, 0.0, 0.0, 0x80 as f32 / 255.0))
+        );
+        assert_eq!(palette.get("missing"), None);
+    }
+
+    #[test]
+    fn from_ron_invalid() {
+        let err = ColorPalette::from_ron(r##"{ "accent": "#ff88zz" }"##).unwrap_err();
+        assert!(format!("{err:#}").contains("accent"));
+
+        assert!(ColorPalette::from_ron(r#"{ "accent": 5 }"#).is_err());
+        assert!(ColorPalette::from_ron("not ron").is_err());
+    }
+}
@@ -0,0 +1,57 @@
+use palette::Srgb;
+
+/// Converts a color temperature in Kelvin to the color of a black body radiating at it, e.g;
+/// 1900K for candle light, 2700K for household bulbs, and 6500K for daylight.
+///
+/// Uses the fit by Tanner Helland, which is accurate enough for lighting between 1000K and
+/// 40000K.
+pub fn color_from_temperature(kelvin: f32) -> Srgb {
+    let temp = kelvin.clamp(1000.0, 40000.0) / 100.0;
+
+    let red = if temp <= 66.0 {
+        255.0
+    } else {
+        329.69873 * (temp - 60.0).powf(-0.13320476)
+    };
+
+    let green = if temp <= 66.0 {
+        99.4708 * temp.ln() - 161.11957
+    } else {
+        288.12216 * (temp - 60.0).powf(-0.075514846)
+    };
+
+    let blue = if temp >= 66.0 {
+        255.0
+    } else if temp <= 19.0 {
+        0.0
+    } else {
+        138.51773 * (temp - 10.0).ln() - 305.0448
+    };
+
+    Srgb::new(
+        red.clamp(0.0, 255.0) / 255.0,
+        green.clamp(0.0, 255.0) / 255.0,
+        blue.clamp(0.0, 255.0) / 255.0,
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn temperature() {
+        let daylight = color_from_temperature(6600.0);
+        assert!(daylight.red > 0.99 && daylight.green > 0.99 && daylight.blue > 0.99);
+
+        let candle = color_from_temperature(1900.0);
+        assert_eq!(candle.red, 1.0);
+        assert_eq!(candle.blue, 0.0);
+
+        let bulb = color_from_temperature(2700.0);
+        assert!(bulb.blue > candle.blue && bulb.blue < daylight.blue);
+
+        let sky = color_from_temperature(12000.0);
+        assert!(sky.blue > sky.red);
+    }
+}
@@ -2,8 +2,19 @@ use std::time::Duration;
 
 use flax::{Component, ComponentMut, Debuggable, EntityBuilder, Fetch};
 use glam::{Mat4, Quat, Vec2, Vec3};
-
-use crate::{gizmos::Gizmos, AsyncCommandBuffer, Bundle, Color};
+use ivy_random::service::Random;
+
+use crate::{
+    app::{CurrentState, StateTransitions},
+    channels::EventChannels,
+    events::EventQueue,
+    gizmos::Gizmos,
+    memory::MemoryReport,
+    notifications::Notifications,
+    profiling::{FrameStats, ScopeTimings},
+    time::Time,
+    AsyncCommandBuffer, Bundle, Color, Tasks,
+};
 
 flax::component! {
     pub position: Vec3 => [Debuggable],
@@ -12,6 +23,9 @@ flax::component! {
     pub scale: Vec3 => [ Debuggable ],
 
     pub parent_transform: Mat4,
+    /// Transform applied between the parent and the local transform of the entity, such as the
+    /// joint of a skinned mesh the entity is attached to
+    pub attachment_transform: Mat4 => [ Debuggable ],
 
     /// Computed world space transform based on [`position`], [`rotation`], and [`scale`].
     pub world_transform: Mat4 => [ Debuggable ],
@@ -26,11 +40,38 @@ flax::component! {
 
     pub gizmos: Gizmos,
     pub async_commandbuffer: AsyncCommandBuffer,
+    /// Spawns background jobs which apply their results to the world, see [`Tasks`]
+    pub tasks: Tasks,
+    /// Dispatches events to the layers, see [`EventQueue`]
+    pub event_queue: EventQueue,
+    /// Typed events for systems, see [`EventChannels`]
+    pub event_channels: EventChannels,
+    /// Changes the state of the app, see [`StateTransitions`]
+    pub state_transitions: StateTransitions,
+    /// The state the app is currently in, see [`AppState`](crate::app::AppState)
+    pub app_state: CurrentState,
     pub request_capture_mouse: bool,
 
     // Set by `ScheduleLayer`
     pub elapsed_time: Duration,
     pub delta_time: Duration,
+    /// Wall clock time of the current frame, unaffected by pausing and time scale
+    pub unscaled_delta_time: Duration,
+    /// Pause and time scale controls, see [`Time`]
+    pub time: Time,
+    /// CPU timings of the layers and systems, see [`FrameStats`]
+    pub frame_stats: FrameStats,
+    /// Timings recorded during the current frame, see [`ScopeTimings`]
+    pub scope_timings: ScopeTimings,
+    /// Memory used by the heap and subsystems, see [`MemoryReport`]
+    pub memory_report: MemoryReport,
+    /// Toasts shown on screen, see [`Notifications`]
+    pub notifications: Notifications,
+    /// Deterministic random number streams, see [`Random`]
+    pub random: Random,
+    /// When present on the engine entity, time advances by this amount each tick instead of the
+    /// measured wall clock time. Used for deterministic replays and offline rendering.
+    pub fixed_frame_delta: Duration,
 
     pub engine,
 }
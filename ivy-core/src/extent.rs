@@ -3,39 +3,153 @@ use std::{
     ops::{Add, Mul},
 };
 
-use glam::Vec2;
+use glam::{vec2, Vec2};
 
-/// Represents a width and height.
+/// Returns the largest size with the given aspect ratio which fits inside `size`
+fn fit_aspect(size: Vec2, aspect: f32) -> Vec2 {
+    if size.x <= 0.0 || size.y <= 0.0 || aspect <= 0.0 {
+        return Vec2::ZERO;
+    }
+
+    if size.x / size.y > aspect {
+        vec2(size.y * aspect, size.y)
+    } else {
+        vec2(size.x, size.x / aspect)
+    }
+}
+
+/// Returns the normalized `(min, max)` rectangle of content with the given aspect ratio centered
+/// in `size`, with bars on the sides which do not fit.
+pub fn letterbox(size: Vec2, aspect: f32) -> (Vec2, Vec2) {
+    if size.x <= 0.0 || size.y <= 0.0 {
+        return (Vec2::ZERO, Vec2::ONE);
+    }
+
+    let content = fit_aspect(size, aspect) / size;
+    let min = (Vec2::ONE - content) * 0.5;
+
+    (min, min + content)
+}
+
+/// Size in logical units, which are independent of the pixel density of the display.
+///
+/// Window sizes and cursor positions are in logical units, while framebuffers are sized in
+/// physical pixels.
+#[derive(Debug, Copy, Clone, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct LogicalExtent {
+    pub width: f32,
+    pub height: f32,
+}
+
+impl LogicalExtent {
+    pub fn new(width: f32, height: f32) -> Self {
+        Self { width, height }
+    }
+
+    /// Returns the aspect ratio of the extent, or `1.0` if it is empty
+    pub fn aspect(&self) -> f32 {
+        if self.is_empty() {
+            1.0
+        } else {
+            self.width / self.height
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.width <= 0.0 || self.height <= 0.0
+    }
+
+    pub fn as_vec(&self) -> Vec2 {
+        vec2(self.width, self.height)
+    }
+
+    /// Converts to physical pixels given the ratio of physical pixels to logical units
+    pub fn to_physical(&self, scale_factor: f64) -> PhysicalExtent {
+        PhysicalExtent::new(
+            (self.width as f64 * scale_factor).round() as u32,
+            (self.height as f64 * scale_factor).round() as u32,
+        )
+    }
+
+    /// Returns the largest extent with the given aspect ratio which fits inside this extent
+    pub fn fit_aspect(&self, aspect: f32) -> Self {
+        fit_aspect(self.as_vec(), aspect).into()
+    }
+}
+
+impl Display for LogicalExtent {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "({}, {})", self.width, self.height)
+    }
+}
+
+impl From<Vec2> for LogicalExtent {
+    fn from(v: Vec2) -> Self {
+        Self::new(v.x, v.y)
+    }
+}
+
+impl From<LogicalExtent> for Vec2 {
+    fn from(v: LogicalExtent) -> Self {
+        v.as_vec()
+    }
+}
+
+/// Size in physical pixels, such as the size of a framebuffer or texture
 #[derive(Debug, Copy, Clone, Hash, PartialEq, Eq, Default)]
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
-pub struct Extent {
+pub struct PhysicalExtent {
     pub width: u32,
     pub height: u32,
 }
 
-impl Extent {
-    pub fn new(width: u32, height: u32) -> Extent {
+impl PhysicalExtent {
+    pub fn new(width: u32, height: u32) -> PhysicalExtent {
         Self { width, height }
     }
 
-    /// Returns the aspect ratio of the extent
+    /// Returns the aspect ratio of the extent, or `1.0` if it is empty
     pub fn aspect(&self) -> f32 {
-        self.width as f32 / self.height as f32
+        if self.is_empty() {
+            1.0
+        } else {
+            self.width as f32 / self.height as f32
+        }
+    }
+
+    /// Returns true if either side is zero, such as for a minimized window
+    pub fn is_empty(&self) -> bool {
+        self.width == 0 || self.height == 0
     }
 
     // Convert the extent into a float vector
     pub fn as_vec(&self) -> Vec2 {
         (*self).into()
     }
+
+    /// Converts to logical units given the ratio of physical pixels to logical units
+    pub fn to_logical(&self, scale_factor: f64) -> LogicalExtent {
+        LogicalExtent::new(
+            (self.width as f64 / scale_factor) as f32,
+            (self.height as f64 / scale_factor) as f32,
+        )
+    }
+
+    /// Returns the largest extent with the given aspect ratio which fits inside this extent
+    pub fn fit_aspect(&self, aspect: f32) -> Self {
+        let size = fit_aspect(self.as_vec(), aspect).round();
+        Self::new(size.x as u32, size.y as u32)
+    }
 }
 
-impl Display for Extent {
+impl Display for PhysicalExtent {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "({}, {})", self.width, self.height)
     }
 }
 
-impl Mul<u32> for Extent {
+impl Mul<u32> for PhysicalExtent {
     type Output = Self;
 
     fn mul(self, rhs: u32) -> Self::Output {
@@ -46,7 +160,7 @@ impl Mul<u32> for Extent {
     }
 }
 
-impl Add<u32> for Extent {
+impl Add<u32> for PhysicalExtent {
     type Output = Self;
     fn add(self, rhs: u32) -> Self::Output {
         Self {
@@ -57,19 +171,19 @@ impl Add<u32> for Extent {
 }
 
 // Conversions
-impl From<Extent> for [u32; 2] {
-    fn from(val: Extent) -> Self {
+impl From<PhysicalExtent> for [u32; 2] {
+    fn from(val: PhysicalExtent) -> Self {
         [val.width, val.height]
     }
 }
 
-impl From<Extent> for (u32, u32) {
-    fn from(val: Extent) -> Self {
+impl From<PhysicalExtent> for (u32, u32) {
+    fn from(val: PhysicalExtent) -> Self {
         (val.width, val.height)
     }
 }
 
-impl From<[u32; 2]> for Extent {
+impl From<[u32; 2]> for PhysicalExtent {
     fn from(v: [u32; 2]) -> Self {
         Self {
             width: v[0],
@@ -78,7 +192,7 @@ impl From<[u32; 2]> for Extent {
     }
 }
 
-impl From<(u32, u32)> for Extent {
+impl From<(u32, u32)> for PhysicalExtent {
     fn from(v: (u32, u32)) -> Self {
         Self {
             width: v.0,
@@ -87,7 +201,7 @@ impl From<(u32, u32)> for Extent {
     }
 }
 
-impl From<[i32; 2]> for Extent {
+impl From<[i32; 2]> for PhysicalExtent {
     fn from(v: [i32; 2]) -> Self {
         Self {
             width: v[0] as u32,
@@ -96,7 +210,7 @@ impl From<[i32; 2]> for Extent {
     }
 }
 
-impl From<(i32, i32)> for Extent {
+impl From<(i32, i32)> for PhysicalExtent {
     fn from(v: (i32, i32)) -> Self {
         Self {
             width: v.0 as u32,
@@ -105,7 +219,7 @@ impl From<(i32, i32)> for Extent {
     }
 }
 
-impl From<[usize; 2]> for Extent {
+impl From<[usize; 2]> for PhysicalExtent {
     fn from(v: [usize; 2]) -> Self {
         Self {
             width: v[0] as u32,
@@ -114,7 +228,7 @@ impl From<[usize; 2]> for Extent {
     }
 }
 
-impl From<(usize, usize)> for Extent {
+impl From<(usize, usize)> for PhysicalExtent {
     fn from(v: (usize, usize)) -> Self {
         Self {
             width: v.0 as u32,
@@ -125,20 +239,57 @@ impl From<(usize, usize)> for Extent {
 
 // Float conversion
 
-impl From<Extent> for [f32; 2] {
-    fn from(val: Extent) -> Self {
+impl From<PhysicalExtent> for [f32; 2] {
+    fn from(val: PhysicalExtent) -> Self {
         [val.width as f32, val.height as f32]
     }
 }
 
-impl From<Extent> for (f32, f32) {
-    fn from(val: Extent) -> Self {
+impl From<PhysicalExtent> for (f32, f32) {
+    fn from(val: PhysicalExtent) -> Self {
         (val.width as f32, val.height as f32)
     }
 }
 
-impl From<Extent> for Vec2 {
-    fn from(extent: Extent) -> Vec2 {
+impl From<PhysicalExtent> for Vec2 {
+    fn from(extent: PhysicalExtent) -> Vec2 {
         Vec2::new(extent.width as f32, extent.height as f32)
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn scale_factor() {
+        let physical = PhysicalExtent::new(2560, 1440);
+        let logical = physical.to_logical(2.0);
+
+        assert_eq!(logical, LogicalExtent::new(1280.0, 720.0));
+        assert_eq!(logical.to_physical(2.0), physical);
+        assert_eq!(logical.to_physical(1.5), PhysicalExtent::new(1920, 1080));
+
+        assert_eq!(PhysicalExtent::new(800, 0).aspect(), 1.0);
+    }
+
+    #[test]
+    fn letterboxing() {
+        let window = PhysicalExtent::new(1920, 1080);
+        assert_eq!(
+            window.fit_aspect(4.0 / 3.0),
+            PhysicalExtent::new(1440, 1080)
+        );
+        assert_eq!(
+            window.fit_aspect(21.0 / 9.0),
+            PhysicalExtent::new(1920, 823)
+        );
+
+        let (min, max) = letterbox(window.as_vec(), 4.0 / 3.0);
+        assert!(min.abs_diff_eq(vec2(0.125, 0.0), 1e-5));
+        assert!(max.abs_diff_eq(vec2(0.875, 1.0), 1e-5));
+
+        let (min, max) = letterbox(window.as_vec(), 16.0 / 9.0);
+        assert!(min.abs_diff_eq(Vec2::ZERO, 1e-5) && max.abs_diff_eq(Vec2::ONE, 1e-5));
+    }
+}
@@ -1,15 +1,24 @@
+use std::time::Duration;
+
 use dashmap::DashMap;
 use glam::{Mat4, Vec3};
 use itertools::Itertools;
+use parking_lot::Mutex;
 
 use crate::{Color, ColorExt};
 
+mod screen;
+mod shapes;
 mod traits;
+pub use screen::*;
+pub use shapes::*;
 pub use traits::*;
 
 /// A default radius that looks good for small gizmos
 pub const DEFAULT_RADIUS: f32 = 0.04;
 pub const DEFAULT_THICKNESS: f32 = 0.02;
+/// Height of the letters of text gizmos, in world units
+pub const DEFAULT_TEXT_SIZE: f32 = 0.2;
 
 #[derive(Copy, Clone, Debug, PartialEq)]
 pub struct Sphere {
@@ -269,7 +278,105 @@ impl Default for Triangle {
         }
     }
 }
-#[derive(Debug, Copy, Clone, PartialEq)]
+/// RGB triad of the x, y and z axes of a transform
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Axes {
+    pub transform: Mat4,
+    pub length: f32,
+    pub radius: f32,
+}
+
+impl Axes {
+    pub fn new(transform: Mat4) -> Self {
+        Self {
+            transform,
+            ..Default::default()
+        }
+    }
+
+    /// Set the length of each axis
+    pub fn with_length(mut self, length: f32) -> Self {
+        self.length = length;
+        self
+    }
+
+    /// Set the radius
+    pub fn with_radius(mut self, radius: f32) -> Self {
+        self.radius = radius;
+        self
+    }
+}
+
+impl Default for Axes {
+    fn default() -> Self {
+        Self {
+            transform: Mat4::IDENTITY,
+            length: 1.0,
+            radius: DEFAULT_THICKNESS,
+        }
+    }
+}
+
+impl DrawGizmos for Axes {
+    fn draw_primitives(&self, gizmos: &mut GizmosSection) {
+        let origin = self.transform.transform_point3(Vec3::ZERO);
+
+        for (axis, color) in [
+            (Vec3::X, Color::red()),
+            (Vec3::Y, Color::green()),
+            (Vec3::Z, Color::blue()),
+        ] {
+            // Scale is ignored, so that the orientation is visible regardless of size
+            let dir = self.transform.transform_vector3(axis).normalize_or_zero() * self.length;
+            Line::new(origin, dir, self.radius, color).draw_primitives(gizmos);
+        }
+    }
+}
+
+/// Text label which always faces the camera
+#[derive(Clone, Debug, PartialEq)]
+pub struct Text {
+    pub origin: Vec3,
+    pub text: String,
+    pub size: f32,
+    pub color: Color,
+}
+
+impl Text {
+    pub fn new(origin: Vec3, text: impl Into<String>) -> Self {
+        Self {
+            origin,
+            text: text.into(),
+            size: DEFAULT_TEXT_SIZE,
+            color: Color::white(),
+        }
+    }
+
+    /// Set the color
+    pub fn with_color(mut self, color: Color) -> Self {
+        self.color = color;
+        self
+    }
+
+    /// Set the height of the letters
+    pub fn with_size(mut self, size: f32) -> Self {
+        self.size = size;
+        self
+    }
+}
+
+impl DrawGizmos for Text {
+    fn draw_primitives(&self, gizmos: &mut GizmosSection) {
+        gizmos.push(GizmoPrimitive::Text {
+            origin: self.origin,
+            text: self.text.clone(),
+            size: self.size,
+            color: self.color,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
 /// Represents a 3D world overlay for debugging purposes.
 pub enum GizmoPrimitive {
     Sphere {
@@ -286,6 +393,13 @@ pub enum GizmoPrimitive {
         // circle cap.
         corner_radius: f32,
     },
+    /// Text centered on `origin`, facing the camera
+    Text {
+        origin: Vec3,
+        text: String,
+        size: f32,
+        color: Color,
+    },
 }
 
 pub type Section = &'static str;
@@ -298,15 +412,52 @@ pub type Section = &'static str;
 ///
 /// The API works much like an immediate mode GUI, except different sections are
 /// transient at different durations.
+///
+/// Gizmos for one-shot events, such as impacts, can instead be drawn for a fixed duration using
+/// [`Gizmos::draw_for`]. The duration is measured in engine time, so timed gizmos remain while the
+/// game is paused.
 #[derive(Default)]
 pub struct Gizmos {
     sections: DashMap<&'static str, GizmosSection>,
+    screen_sections: DashMap<&'static str, ScreenGizmosSection>,
+    /// Remaining time of each timed gizmo
+    timed: Mutex<Vec<(Duration, GizmoPrimitive)>>,
 }
 
 impl Gizmos {
     pub fn new() -> Self {
         Self {
             sections: Default::default(),
+            screen_sections: Default::default(),
+            timed: Default::default(),
+        }
+    }
+
+    /// Draws a gizmo which remains visible for `duration` of engine time, independent of any
+    /// section
+    pub fn draw_for(&self, gizmo: impl DrawGizmos, duration: Duration) {
+        let mut section = GizmosSection::default();
+        gizmo.draw_primitives(&mut section);
+
+        self.timed
+            .lock()
+            .extend(section.primitives.into_iter().map(|v| (duration, v)));
+    }
+
+    /// Advances the timed gizmos by the scaled delta time, removing the expired ones.
+    ///
+    /// Called by the [`App`](crate::App) at the end of each tick.
+    pub fn advance(&self, dt: Duration) {
+        self.timed.lock().retain_mut(|(remaining, _)| {
+            *remaining = remaining.saturating_sub(dt);
+            !remaining.is_zero()
+        });
+    }
+
+    /// Visits the gizmos drawn with [`Self::draw_for`] which have not yet expired
+    pub fn for_each_timed(&self, mut f: impl FnMut(&GizmoPrimitive)) {
+        for (_, primitive) in self.timed.lock().iter() {
+            f(primitive)
         }
     }
 
@@ -341,6 +492,31 @@ impl Gizmos {
     > {
         self.sections.iter()
     }
+
+    /// Begins a new section of screen space gizmos, which are drawn on top of the final image.
+    ///
+    /// Works like [`Self::begin_section`].
+    pub fn begin_screen_section<'a>(
+        &'a self,
+        key: &'static str,
+    ) -> dashmap::mapref::one::RefMut<'a, &'static str, ScreenGizmosSection> {
+        self.screen_sections
+            .entry(key)
+            .and_modify(|v| v.primitives.clear())
+            .or_default()
+    }
+
+    pub fn screen_sections(
+        &self,
+    ) -> dashmap::iter::Iter<
+        '_,
+        &'static str,
+        ScreenGizmosSection,
+        std::hash::RandomState,
+        DashMap<&'static str, ScreenGizmosSection>,
+    > {
+        self.screen_sections.iter()
+    }
 }
 
 #[derive(Default, Debug, Clone)]
@@ -366,3 +542,35 @@ impl GizmosSection {
         &self.primitives
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn timed_count(gizmos: &Gizmos) -> usize {
+        let mut count = 0;
+        gizmos.for_each_timed(|_| count += 1);
+        count
+    }
+
+    #[test]
+    fn timed_engine_time() {
+        let gizmos = Gizmos::new();
+        gizmos.draw_for(
+            Sphere::new(Vec3::ZERO, 1.0, Color::red()),
+            Duration::from_secs(1),
+        );
+
+        // Paused, no time passes regardless of the wall clock
+        for _ in 0..10 {
+            gizmos.advance(Duration::ZERO);
+        }
+        assert_eq!(timed_count(&gizmos), 1);
+
+        gizmos.advance(Duration::from_millis(600));
+        assert_eq!(timed_count(&gizmos), 1);
+
+        gizmos.advance(Duration::from_millis(600));
+        assert_eq!(timed_count(&gizmos), 0);
+    }
+}
@@ -0,0 +1,368 @@
+use glam::Vec2;
+
+use crate::{Color, ColorExt};
+
+/// Default thickness of screen space lines, in pixels
+pub const DEFAULT_SCREEN_THICKNESS: f32 = 2.0;
+/// Default height of screen space text, in pixels
+pub const DEFAULT_SCREEN_TEXT_SIZE: f32 = 16.0;
+
+/// Coordinate space of the positions of screen space gizmos.
+///
+/// Sizes, such as thickness and radius, are always in pixels.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum ScreenUnits {
+    /// Pixels from the top left corner of the screen
+    #[default]
+    Pixels,
+    /// `0..1` from the top left to the bottom right corner of the screen
+    Normalized,
+}
+
+/// 2D overlay drawn on top of the final image, after post-processing
+#[derive(Debug, Clone, PartialEq)]
+pub enum ScreenGizmoPrimitive {
+    Line {
+        start: Vec2,
+        end: Vec2,
+        thickness: f32,
+        color: Color,
+        units: ScreenUnits,
+    },
+    Circle {
+        center: Vec2,
+        radius: f32,
+        thickness: f32,
+        color: Color,
+        units: ScreenUnits,
+    },
+    /// Text with its top left corner at `position`
+    Text {
+        position: Vec2,
+        text: String,
+        size: f32,
+        color: Color,
+        units: ScreenUnits,
+    },
+}
+
+pub trait DrawScreenGizmos {
+    /// Draw a set of screen space gizmos using the current section
+    fn draw_primitives(&self, gizmos: &mut ScreenGizmosSection);
+}
+
+impl<T: DrawScreenGizmos> DrawScreenGizmos for &T {
+    fn draw_primitives(&self, gizmos: &mut ScreenGizmosSection) {
+        (*self).draw_primitives(gizmos)
+    }
+}
+
+/// Screen space counterpart of [`GizmosSection`](super::GizmosSection)
+#[derive(Default, Debug, Clone)]
+pub struct ScreenGizmosSection {
+    pub(super) primitives: Vec<ScreenGizmoPrimitive>,
+}
+
+impl ScreenGizmosSection {
+    /// Adds a new gizmo to the current section
+    pub fn draw(&mut self, gizmo: impl DrawScreenGizmos) {
+        gizmo.draw_primitives(self)
+    }
+
+    pub fn push(&mut self, primitive: ScreenGizmoPrimitive) {
+        self.primitives.push(primitive)
+    }
+
+    pub fn primitives(&self) -> &[ScreenGizmoPrimitive] {
+        &self.primitives
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct ScreenLine {
+    pub start: Vec2,
+    pub end: Vec2,
+    pub thickness: f32,
+    pub color: Color,
+    pub units: ScreenUnits,
+}
+
+impl ScreenLine {
+    pub fn new(start: Vec2, end: Vec2, color: Color) -> Self {
+        Self {
+            start,
+            end,
+            thickness: DEFAULT_SCREEN_THICKNESS,
+            color,
+            units: ScreenUnits::Pixels,
+        }
+    }
+
+    /// Set the color
+    pub fn with_color(mut self, color: Color) -> Self {
+        self.color = color;
+        self
+    }
+
+    /// Set the thickness, in pixels
+    pub fn with_thickness(mut self, thickness: f32) -> Self {
+        self.thickness = thickness;
+        self
+    }
+
+    /// Set the coordinate space of the positions
+    pub fn with_units(mut self, units: ScreenUnits) -> Self {
+        self.units = units;
+        self
+    }
+}
+
+impl DrawScreenGizmos for ScreenLine {
+    fn draw_primitives(&self, gizmos: &mut ScreenGizmosSection) {
+        gizmos.push(ScreenGizmoPrimitive::Line {
+            start: self.start,
+            end: self.end,
+            thickness: self.thickness,
+            color: self.color,
+            units: self.units,
+        })
+    }
+}
+
+/// Outline of a rectangle
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct ScreenRect {
+    pub min: Vec2,
+    pub max: Vec2,
+    pub thickness: f32,
+    pub color: Color,
+    pub units: ScreenUnits,
+}
+
+impl ScreenRect {
+    pub fn new(min: Vec2, max: Vec2, color: Color) -> Self {
+        Self {
+            min,
+            max,
+            thickness: DEFAULT_SCREEN_THICKNESS,
+            color,
+            units: ScreenUnits::Pixels,
+        }
+    }
+
+    /// Set the color
+    pub fn with_color(mut self, color: Color) -> Self {
+        self.color = color;
+        self
+    }
+
+    /// Set the thickness, in pixels
+    pub fn with_thickness(mut self, thickness: f32) -> Self {
+        self.thickness = thickness;
+        self
+    }
+
+    /// Set the coordinate space of the corners
+    pub fn with_units(mut self, units: ScreenUnits) -> Self {
+        self.units = units;
+        self
+    }
+}
+
+impl DrawScreenGizmos for ScreenRect {
+    fn draw_primitives(&self, gizmos: &mut ScreenGizmosSection) {
+        let corners = [
+            self.min,
+            Vec2::new(self.max.x, self.min.y),
+            self.max,
+            Vec2::new(self.min.x, self.max.y),
+        ];
+
+        for i in 0..4 {
+            gizmos.draw(
+                ScreenLine::new(corners[i], corners[(i + 1) % 4], self.color)
+                    .with_thickness(self.thickness)
+                    .with_units(self.units),
+            );
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct ScreenCircle {
+    pub center: Vec2,
+    /// Radius in pixels
+    pub radius: f32,
+    pub thickness: f32,
+    pub color: Color,
+    pub units: ScreenUnits,
+}
+
+impl ScreenCircle {
+    pub fn new(center: Vec2, radius: f32, color: Color) -> Self {
+        Self {
+            center,
+            radius,
+            thickness: DEFAULT_SCREEN_THICKNESS,
+            color,
+            units: ScreenUnits::Pixels,
+        }
+    }
+
+    /// Set the color
+    pub fn with_color(mut self, color: Color) -> Self {
+        self.color = color;
+        self
+    }
+
+    /// Set the thickness, in pixels
+    pub fn with_thickness(mut self, thickness: f32) -> Self {
+        self.thickness = thickness;
+        self
+    }
+
+    /// Set the coordinate space of the center
+    pub fn with_units(mut self, units: ScreenUnits) -> Self {
+        self.units = units;
+        self
+    }
+}
+
+impl DrawScreenGizmos for ScreenCircle {
+    fn draw_primitives(&self, gizmos: &mut ScreenGizmosSection) {
+        gizmos.push(ScreenGizmoPrimitive::Circle {
+            center: self.center,
+            radius: self.radius,
+            thickness: self.thickness,
+            color: self.color,
+            units: self.units,
+        })
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ScreenText {
+    /// Top left corner of the text
+    pub position: Vec2,
+    pub text: String,
+    /// Height of the letters, in pixels
+    pub size: f32,
+    pub color: Color,
+    pub units: ScreenUnits,
+}
+
+impl ScreenText {
+    pub fn new(position: Vec2, text: impl Into<String>) -> Self {
+        Self {
+            position,
+            text: text.into(),
+            size: DEFAULT_SCREEN_TEXT_SIZE,
+            color: Color::white(),
+            units: ScreenUnits::Pixels,
+        }
+    }
+
+    /// Set the color
+    pub fn with_color(mut self, color: Color) -> Self {
+        self.color = color;
+        self
+    }
+
+    /// Set the height of the letters, in pixels
+    pub fn with_size(mut self, size: f32) -> Self {
+        self.size = size;
+        self
+    }
+
+    /// Set the coordinate space of the position
+    pub fn with_units(mut self, units: ScreenUnits) -> Self {
+        self.units = units;
+        self
+    }
+}
+
+impl DrawScreenGizmos for ScreenText {
+    fn draw_primitives(&self, gizmos: &mut ScreenGizmosSection) {
+        gizmos.push(ScreenGizmoPrimitive::Text {
+            position: self.position,
+            text: self.text.clone(),
+            size: self.size,
+            color: self.color,
+            units: self.units,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use glam::vec2;
+
+    use super::*;
+    use crate::gizmos::Gizmos;
+
+    #[test]
+    fn rect_outline() {
+        let mut section = ScreenGizmosSection::default();
+        section.draw(
+            ScreenRect::new(vec2(10.0, 20.0), vec2(30.0, 40.0), Color::red())
+                .with_thickness(4.0)
+                .with_units(ScreenUnits::Normalized),
+        );
+
+        let lines = section
+            .primitives()
+            .iter()
+            .map(|v| match v {
+                ScreenGizmoPrimitive::Line {
+                    start,
+                    end,
+                    thickness,
+                    units,
+                    ..
+                } => {
+                    assert_eq!(*thickness, 4.0);
+                    assert_eq!(*units, ScreenUnits::Normalized);
+                    (*start, *end)
+                }
+                v => panic!("Expected a line, found {v:?}"),
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            lines,
+            [
+                (vec2(10.0, 20.0), vec2(30.0, 20.0)),
+                (vec2(30.0, 20.0), vec2(30.0, 40.0)),
+                (vec2(30.0, 40.0), vec2(10.0, 40.0)),
+                (vec2(10.0, 40.0), vec2(10.0, 20.0)),
+            ]
+        );
+    }
+
+    #[test]
+    fn screen_sections() {
+        let gizmos = Gizmos::new();
+
+        gizmos
+            .begin_screen_section("hud")
+            .draw(ScreenText::new(Vec2::ZERO, "fps").with_size(8.0));
+        gizmos
+            .begin_screen_section("other")
+            .draw(ScreenCircle::new(Vec2::ONE, 2.0, Color::blue()));
+
+        let counts = |gizmos: &Gizmos| {
+            let mut counts = gizmos
+                .screen_sections()
+                .map(|v| (*v.key(), v.primitives().len()))
+                .collect::<Vec<_>>();
+            counts.sort();
+            counts
+        };
+
+        assert_eq!(counts(&gizmos), [("hud", 1), ("other", 1)]);
+
+        // Beginning a section clears it
+        drop(gizmos.begin_screen_section("hud"));
+        assert_eq!(counts(&gizmos), [("hud", 0), ("other", 1)]);
+    }
+}
@@ -0,0 +1,408 @@
+use std::f32::consts::{PI, TAU};
+
+use glam::{Mat4, Quat, Vec3};
+
+use super::{DrawGizmos, GizmosSection, Line, DEFAULT_THICKNESS};
+use crate::{Color, ColorExt};
+
+/// Number of line segments used for a full circle
+const CIRCLE_SEGMENTS: usize = 32;
+
+/// Returns `dir` normalized, or `fallback` if it has no length
+fn normalize_or(dir: Vec3, fallback: Vec3) -> Vec3 {
+    dir.try_normalize().unwrap_or(fallback)
+}
+
+/// Part of a circle, starting at `origin + start` and rotating `angle` radians around `normal`
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Arc {
+    pub origin: Vec3,
+    pub normal: Vec3,
+    pub start: Vec3,
+    pub angle: f32,
+    pub line_radius: f32,
+    pub color: Color,
+}
+
+impl Arc {
+    pub fn new(origin: Vec3, normal: Vec3, start: Vec3, angle: f32, color: Color) -> Self {
+        Self {
+            origin,
+            normal,
+            start,
+            angle,
+            line_radius: DEFAULT_THICKNESS,
+            color,
+        }
+    }
+
+    /// Set the color
+    pub fn with_color(mut self, color: Color) -> Self {
+        self.color = color;
+        self
+    }
+
+    /// Set the radius of the line
+    pub fn with_line_radius(mut self, line_radius: f32) -> Self {
+        self.line_radius = line_radius;
+        self
+    }
+}
+
+impl DrawGizmos for Arc {
+    fn draw_primitives(&self, gizmos: &mut GizmosSection) {
+        let normal = normalize_or(self.normal, Vec3::Y);
+        let segments = ((self.angle.abs() / TAU * CIRCLE_SEGMENTS as f32).ceil() as usize).max(1);
+
+        let point = |i: usize| {
+            let rotation = Quat::from_axis_angle(normal, self.angle * i as f32 / segments as f32);
+            self.origin + rotation * self.start
+        };
+
+        for i in 0..segments {
+            gizmos.draw(Line::from_points(
+                point(i),
+                point(i + 1),
+                self.line_radius,
+                self.color,
+            ));
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Circle {
+    pub origin: Vec3,
+    pub normal: Vec3,
+    pub radius: f32,
+    pub line_radius: f32,
+    pub color: Color,
+}
+
+impl Circle {
+    pub fn new(origin: Vec3, normal: Vec3, radius: f32, color: Color) -> Self {
+        Self {
+            origin,
+            normal,
+            radius,
+            line_radius: DEFAULT_THICKNESS,
+            color,
+        }
+    }
+
+    /// Set the color
+    pub fn with_color(mut self, color: Color) -> Self {
+        self.color = color;
+        self
+    }
+
+    /// Set the radius of the line
+    pub fn with_line_radius(mut self, line_radius: f32) -> Self {
+        self.line_radius = line_radius;
+        self
+    }
+}
+
+impl DrawGizmos for Circle {
+    fn draw_primitives(&self, gizmos: &mut GizmosSection) {
+        let normal = normalize_or(self.normal, Vec3::Y);
+
+        gizmos.draw(
+            Arc::new(
+                self.origin,
+                normal,
+                normal.any_orthonormal_vector() * self.radius,
+                TAU,
+                self.color,
+            )
+            .with_line_radius(self.line_radius),
+        );
+    }
+}
+
+/// Wireframe of a capsule with the segment from `start` to `end`
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Capsule {
+    pub start: Vec3,
+    pub end: Vec3,
+    pub radius: f32,
+    pub line_radius: f32,
+    pub color: Color,
+}
+
+impl Capsule {
+    pub fn new(start: Vec3, end: Vec3, radius: f32, color: Color) -> Self {
+        Self {
+            start,
+            end,
+            radius,
+            line_radius: DEFAULT_THICKNESS,
+            color,
+        }
+    }
+
+    /// Set the color
+    pub fn with_color(mut self, color: Color) -> Self {
+        self.color = color;
+        self
+    }
+
+    /// Set the radius of the line
+    pub fn with_line_radius(mut self, line_radius: f32) -> Self {
+        self.line_radius = line_radius;
+        self
+    }
+}
+
+impl DrawGizmos for Capsule {
+    fn draw_primitives(&self, gizmos: &mut GizmosSection) {
+        let dir = normalize_or(self.end - self.start, Vec3::Y);
+        let (u, v) = dir.any_orthonormal_pair();
+
+        for (center, dir) in [(self.start, -dir), (self.end, dir)] {
+            gizmos.draw(
+                Circle::new(center, dir, self.radius, self.color)
+                    .with_line_radius(self.line_radius),
+            );
+
+            // Half circles over the caps
+            for side in [u, v] {
+                gizmos.draw(
+                    Arc::new(center, side.cross(dir), side * self.radius, PI, self.color)
+                        .with_line_radius(self.line_radius),
+                );
+            }
+        }
+
+        for side in [u, -u, v, -v] {
+            let offset = side * self.radius;
+            gizmos.draw(Line::from_points(
+                self.start + offset,
+                self.end + offset,
+                self.line_radius,
+                self.color,
+            ));
+        }
+    }
+}
+
+/// Wireframe of a cone with its tip at `apex`, opening along `axis`
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Cone {
+    pub apex: Vec3,
+    /// Direction and height of the cone
+    pub axis: Vec3,
+    /// Radius of the base
+    pub radius: f32,
+    pub line_radius: f32,
+    pub color: Color,
+}
+
+impl Cone {
+    pub fn new(apex: Vec3, axis: Vec3, radius: f32, color: Color) -> Self {
+        Self {
+            apex,
+            axis,
+            radius,
+            line_radius: DEFAULT_THICKNESS,
+            color,
+        }
+    }
+
+    /// Creates a cone from the angle between its axis and side, such as the field of view of a
+    /// camera or the outer angle of a spotlight
+    pub fn from_angle(apex: Vec3, axis: Vec3, half_angle: f32, color: Color) -> Self {
+        Self::new(apex, axis, axis.length() * half_angle.tan(), color)
+    }
+
+    /// Set the color
+    pub fn with_color(mut self, color: Color) -> Self {
+        self.color = color;
+        self
+    }
+
+    /// Set the radius of the line
+    pub fn with_line_radius(mut self, line_radius: f32) -> Self {
+        self.line_radius = line_radius;
+        self
+    }
+}
+
+impl DrawGizmos for Cone {
+    fn draw_primitives(&self, gizmos: &mut GizmosSection) {
+        let base = self.apex + self.axis;
+        let (u, v) = normalize_or(self.axis, Vec3::Y).any_orthonormal_pair();
+
+        gizmos.draw(
+            Circle::new(base, self.axis, self.radius, self.color)
+                .with_line_radius(self.line_radius),
+        );
+
+        for side in [u, -u, v, -v] {
+            gizmos.draw(Line::from_points(
+                self.apex,
+                base + side * self.radius,
+                self.line_radius,
+                self.color,
+            ));
+        }
+    }
+}
+
+/// Line from `origin` along `dir` with a cone shaped head
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Arrow {
+    pub origin: Vec3,
+    pub dir: Vec3,
+    pub head_size: f32,
+    pub line_radius: f32,
+    pub color: Color,
+}
+
+impl Arrow {
+    pub fn new(origin: Vec3, dir: Vec3, color: Color) -> Self {
+        Self {
+            origin,
+            dir,
+            head_size: 0.1,
+            line_radius: DEFAULT_THICKNESS,
+            color,
+        }
+    }
+
+    /// Set the color
+    pub fn with_color(mut self, color: Color) -> Self {
+        self.color = color;
+        self
+    }
+
+    /// Set the length of the head
+    pub fn with_head_size(mut self, head_size: f32) -> Self {
+        self.head_size = head_size;
+        self
+    }
+
+    /// Set the radius of the line
+    pub fn with_line_radius(mut self, line_radius: f32) -> Self {
+        self.line_radius = line_radius;
+        self
+    }
+}
+
+impl DrawGizmos for Arrow {
+    fn draw_primitives(&self, gizmos: &mut GizmosSection) {
+        let end = self.origin + self.dir;
+        let head_size = self.head_size.min(self.dir.length() * 0.5);
+
+        gizmos.draw(Line::new(
+            self.origin,
+            self.dir,
+            self.line_radius,
+            self.color,
+        ));
+
+        gizmos.draw(
+            Cone::new(
+                end,
+                -self.dir.normalize_or_zero() * head_size,
+                head_size * 0.5,
+                self.color,
+            )
+            .with_line_radius(self.line_radius),
+        );
+    }
+}
+
+/// Wireframe of the volume visible to a camera
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Frustum {
+    /// Projection and view matrix of the camera
+    pub viewproj: Mat4,
+    pub line_radius: f32,
+    pub color: Color,
+}
+
+impl Frustum {
+    pub fn new(viewproj: Mat4) -> Self {
+        Self {
+            viewproj,
+            line_radius: DEFAULT_THICKNESS,
+            color: Color::yellow(),
+        }
+    }
+
+    /// Creates the frustum of a camera with the world `transform` and `projection`
+    pub fn from_camera(transform: Mat4, projection: Mat4) -> Self {
+        Self::new(projection * transform.inverse())
+    }
+
+    /// Set the color
+    pub fn with_color(mut self, color: Color) -> Self {
+        self.color = color;
+        self
+    }
+
+    /// Set the radius of the line
+    pub fn with_line_radius(mut self, line_radius: f32) -> Self {
+        self.line_radius = line_radius;
+        self
+    }
+
+    /// Returns the world space corners of the near and far plane, in counter-clockwise order
+    pub fn corners(&self) -> [[Vec3; 4]; 2] {
+        let inv = self.viewproj.inverse();
+        let ndc = [(-1.0, -1.0), (1.0, -1.0), (1.0, 1.0), (-1.0, 1.0)];
+
+        [0.0, 1.0].map(|z| ndc.map(|(x, y)| inv.project_point3(Vec3::new(x, y, z))))
+    }
+}
+
+impl DrawGizmos for Frustum {
+    fn draw_primitives(&self, gizmos: &mut GizmosSection) {
+        let [near, far] = self.corners();
+
+        for i in 0..4 {
+            let next = (i + 1) % 4;
+            for (start, end) in [
+                (near[i], near[next]),
+                (far[i], far[next]),
+                (near[i], far[i]),
+            ] {
+                gizmos.draw(Line::from_points(start, end, self.line_radius, self.color));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::gizmos::GizmoPrimitive;
+
+    #[test]
+    fn arc_ends_at_angle() {
+        let mut section = GizmosSection::default();
+        section.draw(Arc::new(
+            Vec3::ZERO,
+            Vec3::Z,
+            Vec3::X,
+            PI / 2.0,
+            Color::red(),
+        ));
+
+        let GizmoPrimitive::Line { origin, dir, .. } = section.primitives().last().unwrap() else {
+            panic!("Expected a line");
+        };
+
+        assert!((*origin + *dir).distance(Vec3::Y) < 1e-5);
+    }
+
+    #[test]
+    fn frustum_corners() {
+        let projection = Mat4::perspective_rh(PI / 2.0, 1.0, 1.0, 10.0);
+        let [near, far] = Frustum::from_camera(Mat4::IDENTITY, projection).corners();
+
+        assert!(near[0].distance(Vec3::new(-1.0, -1.0, -1.0)) < 1e-4);
+        assert!(far[2].distance(Vec3::new(10.0, 10.0, -10.0)) < 1e-3);
+    }
+}
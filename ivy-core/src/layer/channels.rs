@@ -0,0 +1,233 @@
+use std::{
+    any::{Any, TypeId},
+    collections::{HashMap, VecDeque},
+    marker::PhantomData,
+};
+
+/// Events of a single type which are kept for a number of updates, allowing any number of
+/// [`EventReader`]s to consume them in order.
+///
+/// By default events live for two updates, which ensures every reader which runs once per update
+/// sees each event exactly once, regardless of if it runs before or after the sender.
+pub struct Events<T> {
+    /// Events and the update they were sent in
+    events: VecDeque<(usize, T)>,
+    /// Id of the first event in `events`
+    start_id: usize,
+    update: usize,
+    lifetime: usize,
+}
+
+impl<T> Events<T> {
+    pub fn new() -> Self {
+        Self::with_lifetime(2)
+    }
+
+    /// Keep events for `lifetime` updates
+    pub fn with_lifetime(lifetime: usize) -> Self {
+        assert!(lifetime > 0, "Events must live for at least one update");
+        Self {
+            events: VecDeque::new(),
+            start_id: 0,
+            update: 0,
+            lifetime,
+        }
+    }
+
+    pub fn send(&mut self, event: T) {
+        self.events.push_back((self.update, event));
+    }
+
+    /// Advances to the next update, dropping the events which have outlived their lifetime
+    pub fn update(&mut self) {
+        self.update += 1;
+        while let Some(&(sent, _)) = self.events.front() {
+            if sent + self.lifetime > self.update {
+                break;
+            }
+
+            self.events.pop_front();
+            self.start_id += 1;
+        }
+    }
+
+    fn end_id(&self) -> usize {
+        self.start_id + self.events.len()
+    }
+
+    /// Returns a reader which only reads events sent after its creation
+    pub fn reader(&self) -> EventReader<T> {
+        EventReader {
+            cursor: self.end_id(),
+            _marker: PhantomData,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.events.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.events.is_empty()
+    }
+}
+
+impl<T> Default for Events<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Cursor into [`Events`] which tracks the events that have been read
+pub struct EventReader<T> {
+    cursor: usize,
+    _marker: PhantomData<fn() -> T>,
+}
+
+impl<T> EventReader<T> {
+    /// Creates a reader which reads all events still alive
+    pub fn new() -> Self {
+        Self {
+            cursor: 0,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Returns the events sent since the last read
+    pub fn read<'a>(&mut self, events: &'a Events<T>) -> impl Iterator<Item = &'a T> {
+        let skip = self.cursor.saturating_sub(events.start_id);
+        self.cursor = events.end_id();
+        events.events.range(skip..).map(|v| &v.1)
+    }
+
+    /// Returns the number of events which were dropped before being read
+    pub fn missed(&self, events: &Events<T>) -> usize {
+        events.start_id.saturating_sub(self.cursor)
+    }
+}
+
+impl<T> Default for EventReader<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> Clone for EventReader<T> {
+    fn clone(&self) -> Self {
+        Self {
+            cursor: self.cursor,
+            _marker: PhantomData,
+        }
+    }
+}
+
+trait EventsDyn: Send + Sync {
+    fn update(&mut self);
+    fn as_any(&self) -> &dyn Any;
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+}
+
+impl<T: 'static + Send + Sync> EventsDyn for Events<T> {
+    fn update(&mut self) {
+        Events::update(self)
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+/// Typed event channels, updated after each tick.
+///
+/// Stored on the engine entity, and allows systems to send and read events without subscribing to
+/// the layers.
+#[derive(Default)]
+pub struct EventChannels {
+    channels: HashMap<TypeId, Box<dyn EventsDyn>>,
+}
+
+impl EventChannels {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn get<T: 'static + Send + Sync>(&self) -> Option<&Events<T>> {
+        self.channels
+            .get(&TypeId::of::<T>())
+            .map(|v| v.as_any().downcast_ref().unwrap())
+    }
+
+    pub fn get_mut<T: 'static + Send + Sync>(&mut self) -> &mut Events<T> {
+        self.channels
+            .entry(TypeId::of::<T>())
+            .or_insert_with(|| Box::new(Events::<T>::new()))
+            .as_any_mut()
+            .downcast_mut()
+            .unwrap()
+    }
+
+    pub fn send<T: 'static + Send + Sync>(&mut self, event: T) {
+        self.get_mut().send(event)
+    }
+
+    /// Returns the events of type `T` sent since the last read of `reader`
+    pub fn read<'a, T: 'static + Send + Sync>(
+        &'a self,
+        reader: &'a mut EventReader<T>,
+    ) -> impl Iterator<Item = &'a T> {
+        self.get()
+            .into_iter()
+            .flat_map(|events| reader.read(events))
+    }
+
+    pub fn update(&mut self) {
+        for channel in self.channels.values_mut() {
+            channel.update();
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn double_buffered() {
+        let mut events = Events::new();
+        let mut before = events.reader();
+        let mut after = events.reader();
+
+        assert_eq!(before.read(&events).count(), 0);
+        events.send(1);
+        events.send(2);
+        assert_eq!(after.read(&events).copied().collect::<Vec<_>>(), [1, 2]);
+        events.update();
+
+        events.send(3);
+        assert_eq!(before.read(&events).copied().collect::<Vec<_>>(), [1, 2, 3]);
+        assert_eq!(after.read(&events).copied().collect::<Vec<_>>(), [3]);
+        events.update();
+        events.update();
+
+        assert_eq!(before.read(&events).count(), 0);
+        assert!(events.is_empty());
+    }
+
+    #[test]
+    fn missed() {
+        let mut events = Events::with_lifetime(1);
+        let mut reader = events.reader();
+
+        events.send(1);
+        events.update();
+        events.send(2);
+
+        assert_eq!(reader.missed(&events), 1);
+        assert_eq!(reader.read(&events).copied().collect::<Vec<_>>(), [2]);
+        assert_eq!(reader.missed(&events), 0);
+    }
+}
@@ -1,4 +1,8 @@
-use std::{any::TypeId, collections::HashMap};
+use std::{
+    any::TypeId,
+    collections::{BTreeSet, HashMap},
+    time::Instant,
+};
 
 use downcast_rs::{impl_downcast, Downcast};
 use flax::World;
@@ -6,7 +10,10 @@ use ivy_assets::{stored::DynamicStore, AssetCache};
 use ivy_profiling::{profile_function, profile_scope};
 use slab::Slab;
 
-use crate::{Layer, LayerDyn};
+use crate::{
+    components::{engine, scope_timings},
+    Layer, LayerDyn,
+};
 
 type EventCallbackDyn =
     Box<dyn FnMut(&mut dyn LayerDyn, &mut EventContext, &dyn Event) -> anyhow::Result<bool>>;
@@ -43,12 +50,21 @@ impl EventDispatcher {
         layers: &mut [Box<dyn LayerDyn>],
         ctx: &mut EventContext,
         registry: &mut Callbacks,
+        disabled_layers: &BTreeSet<usize>,
         event: &dyn Event,
     ) -> anyhow::Result<bool> {
         for (layer_index, func) in &self.listeners {
+            if disabled_layers.contains(layer_index) {
+                continue;
+            }
+
             let layer = &mut layers[*layer_index];
             profile_scope!("dispatch_layer", layer.label());
+            let start = Instant::now();
             let handled = registry.callbacks[*func](layer.as_mut(), ctx, event)?;
+            if let Ok(timings) = ctx.world.get(engine(), scope_timings()) {
+                timings.record(layer.label(), start.elapsed());
+            }
 
             if handled {
                 return Ok(handled);
@@ -62,6 +78,19 @@ impl EventDispatcher {
         self.listeners.push((layer_index, callback));
         self.listeners.sort_by_key(|v| v.0);
     }
+
+    fn unregister_layer(&mut self, layer_index: usize) {
+        self.listeners.retain(|v| v.0 != layer_index);
+    }
+
+    /// Moves the listeners of all layers at or after `from` by `offset`
+    fn shift_layers(&mut self, from: usize, offset: isize) {
+        for (layer, _) in &mut self.listeners {
+            if *layer >= from {
+                *layer = layer.checked_add_signed(offset).unwrap();
+            }
+        }
+    }
 }
 
 impl Default for EventDispatcher {
@@ -84,6 +113,8 @@ pub struct EventRegistry {
     callbacks: Callbacks,
     // layer, callback
     global_listeners: EventDispatcher,
+    /// Layers which currently do not receive events
+    disabled_layers: BTreeSet<usize>,
 }
 
 impl EventRegistry {
@@ -92,6 +123,7 @@ impl EventRegistry {
             dispatchers: HashMap::new(),
             callbacks: Callbacks::new(),
             global_listeners: EventDispatcher::new(),
+            disabled_layers: BTreeSet::new(),
         }
     }
 
@@ -112,6 +144,69 @@ impl EventRegistry {
             })
     }
 
+    fn dispatchers_mut(&mut self) -> impl Iterator<Item = &mut EventDispatcher> {
+        self.dispatchers
+            .values_mut()
+            .chain([&mut self.global_listeners])
+    }
+
+    /// Removes all callbacks registered by the layer at `layer_index`
+    pub(crate) fn unregister_layer(&mut self, layer_index: usize) {
+        let callbacks = self
+            .dispatchers
+            .values()
+            .chain([&self.global_listeners])
+            .flat_map(|v| &v.listeners)
+            .filter(|v| v.0 == layer_index)
+            .map(|v| v.1)
+            .collect::<BTreeSet<_>>();
+
+        for dispatcher in self.dispatchers_mut() {
+            dispatcher.unregister_layer(layer_index);
+        }
+
+        for callback in callbacks {
+            self.callbacks.callbacks.remove(callback);
+        }
+    }
+
+    /// Makes room for a layer inserted at `layer_index`
+    pub(crate) fn insert_layer(&mut self, layer_index: usize) {
+        for dispatcher in self.dispatchers_mut() {
+            dispatcher.shift_layers(layer_index, 1);
+        }
+
+        self.disabled_layers = self
+            .disabled_layers
+            .iter()
+            .map(|&v| if v >= layer_index { v + 1 } else { v })
+            .collect();
+    }
+
+    /// Removes the callbacks of the layer at `layer_index`, and moves the following layers down
+    pub(crate) fn remove_layer(&mut self, layer_index: usize) {
+        self.unregister_layer(layer_index);
+        for dispatcher in self.dispatchers_mut() {
+            dispatcher.shift_layers(layer_index + 1, -1);
+        }
+
+        self.disabled_layers = self
+            .disabled_layers
+            .iter()
+            .filter(|&&v| v != layer_index)
+            .map(|&v| if v > layer_index { v - 1 } else { v })
+            .collect();
+    }
+
+    /// Stops or resumes dispatching events to the layer at `layer_index`
+    pub fn set_layer_enabled(&mut self, layer_index: usize, enabled: bool) {
+        if enabled {
+            self.disabled_layers.remove(&layer_index);
+        } else {
+            self.disabled_layers.insert(layer_index);
+        }
+    }
+
     fn register_global(&mut self, layer_index: usize, callback: usize) {
         for dispatcher in self.dispatchers.values_mut() {
             dispatcher.register(layer_index, callback)
@@ -129,10 +224,21 @@ impl EventRegistry {
         profile_function!(std::any::type_name::<T>());
 
         if let Some(dispatcher) = self.dispatchers.get(&TypeId::of::<T>()) {
-            dispatcher.dispatch(layers, ctx, &mut self.callbacks, event)?;
+            dispatcher.dispatch(
+                layers,
+                ctx,
+                &mut self.callbacks,
+                &self.disabled_layers,
+                event,
+            )?;
         } else {
-            self.global_listeners
-                .dispatch(layers, ctx, &mut self.callbacks, event)?;
+            self.global_listeners.dispatch(
+                layers,
+                ctx,
+                &mut self.callbacks,
+                &self.disabled_layers,
+                event,
+            )?;
         }
 
         Ok(())
@@ -148,10 +254,21 @@ impl EventRegistry {
 
         let ty = event.type_id();
         if let Some(dispatcher) = self.dispatchers.get(&ty) {
-            dispatcher.dispatch(layers, ctx, &mut self.callbacks, event)
+            dispatcher.dispatch(
+                layers,
+                ctx,
+                &mut self.callbacks,
+                &self.disabled_layers,
+                event,
+            )
         } else {
-            self.global_listeners
-                .dispatch(layers, ctx, &mut self.callbacks, event)
+            self.global_listeners.dispatch(
+                layers,
+                ctx,
+                &mut self.callbacks,
+                &self.disabled_layers,
+                event,
+            )
         }
     }
 }
@@ -231,6 +348,37 @@ impl<'a, L: Layer> EventRegisterContext<'a, L> {
     }
 }
 
+/// Queues events from outside of the layers, such as from systems, which are dispatched to the
+/// layers after each tick.
+///
+/// Stored on the engine entity.
+#[derive(Clone)]
+pub struct EventQueue {
+    tx: flume::Sender<Box<dyn Event + Send>>,
+    rx: flume::Receiver<Box<dyn Event + Send>>,
+}
+
+impl EventQueue {
+    pub fn new() -> Self {
+        let (tx, rx) = flume::unbounded();
+        Self { tx, rx }
+    }
+
+    pub fn send<T: Event + Send>(&self, event: T) {
+        self.tx.send(Box::new(event)).ok();
+    }
+
+    pub(crate) fn drain(&self) -> flume::TryIter<'_, Box<dyn Event + Send>> {
+        self.rx.try_iter()
+    }
+}
+
+impl Default for EventQueue {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 pub trait Event: 'static + std::fmt::Debug + Downcast {
     fn type_name(&self) -> &'static str {
         std::any::type_name::<Self>()
@@ -238,3 +386,143 @@ pub trait Event: 'static + std::fmt::Debug + Downcast {
 }
 
 impl_downcast!(Event);
+
+#[cfg(test)]
+mod test {
+    use std::{cell::RefCell, rc::Rc};
+
+    use super::*;
+
+    #[derive(Debug)]
+    struct Ping;
+
+    impl Event for Ping {}
+
+    #[derive(Debug)]
+    struct Pong;
+
+    impl Event for Pong {}
+
+    type Log = Rc<RefCell<Vec<&'static str>>>;
+
+    struct RecordLayer {
+        name: &'static str,
+        log: Log,
+    }
+
+    impl Layer for RecordLayer {
+        fn register(
+            &mut self,
+            _: &mut World,
+            _: &AssetCache,
+            mut events: EventRegisterContext<Self>,
+        ) -> anyhow::Result<()> {
+            events.subscribe(|this, _, _: &Ping| {
+                this.log.borrow_mut().push(this.name);
+                Ok(())
+            });
+
+            events.subscribe_global(|this, _, event| {
+                if event.is::<Pong>() {
+                    this.log.borrow_mut().push(this.name);
+                }
+
+                Ok(false)
+            });
+
+            Ok(())
+        }
+    }
+
+    struct Harness {
+        world: World,
+        assets: AssetCache,
+        store: DynamicStore,
+        registry: EventRegistry,
+        layers: Vec<Box<dyn LayerDyn>>,
+        log: Log,
+    }
+
+    impl Harness {
+        fn new() -> Self {
+            Self {
+                world: World::new(),
+                assets: AssetCache::new(),
+                store: DynamicStore::default(),
+                registry: EventRegistry::new(),
+                layers: Vec::new(),
+                log: Default::default(),
+            }
+        }
+
+        fn insert(&mut self, index: usize, name: &'static str) {
+            self.registry.insert_layer(index);
+
+            let mut layer = RecordLayer {
+                name,
+                log: self.log.clone(),
+            };
+
+            layer
+                .register_dyn(&mut self.world, &self.assets, &mut self.registry, index)
+                .unwrap();
+
+            self.layers.insert(index, Box::new(layer));
+        }
+
+        fn remove(&mut self, index: usize) {
+            self.registry.remove_layer(index);
+            self.layers.remove(index);
+        }
+
+        fn emit(&mut self, event: &dyn Event) -> Vec<&'static str> {
+            let mut ctx = EventContext {
+                world: &mut self.world,
+                assets: &self.assets,
+                store: &mut self.store,
+            };
+
+            self.registry
+                .emit_dyn(&mut self.layers, &mut ctx, event)
+                .unwrap();
+
+            self.log.take()
+        }
+    }
+
+    #[test]
+    fn insert_and_remove_layers() {
+        let mut harness = Harness::new();
+        harness.insert(0, "a");
+        harness.insert(1, "b");
+        harness.insert(2, "c");
+
+        assert_eq!(harness.emit(&Ping), ["a", "b", "c"]);
+        assert_eq!(harness.emit(&Pong), ["a", "b", "c"]);
+
+        harness.registry.set_layer_enabled(2, false);
+
+        // Following layers move down, keeping their callbacks and enabled state
+        harness.remove(0);
+        assert_eq!(harness.emit(&Ping), ["b"]);
+        assert_eq!(harness.emit(&Pong), ["b"]);
+
+        harness.registry.set_layer_enabled(1, true);
+        assert_eq!(harness.emit(&Ping), ["b", "c"]);
+
+        // Following layers move up
+        harness.registry.set_layer_enabled(1, false);
+        harness.insert(0, "d");
+        assert_eq!(harness.emit(&Ping), ["d", "b"]);
+        assert_eq!(harness.emit(&Pong), ["d", "b"]);
+
+        harness.registry.set_layer_enabled(2, true);
+        harness.insert(3, "e");
+        assert_eq!(harness.emit(&Ping), ["d", "b", "c", "e"]);
+
+        harness.remove(1);
+        harness.remove(2);
+        assert_eq!(harness.emit(&Ping), ["d", "c"]);
+        assert_eq!(harness.emit(&Pong), ["d", "c"]);
+    }
+}
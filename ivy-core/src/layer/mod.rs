@@ -4,12 +4,14 @@ use ivy_assets::AssetCache;
 
 use crate::{
     app::TickEvent,
-    components::{async_commandbuffer, engine, gizmos, request_capture_mouse},
+    components::{async_commandbuffer, engine, gizmos, request_capture_mouse, tasks},
     gizmos::Gizmos,
-    systems::{apply_async_commandbuffers, update_root_transforms_system, update_transform_system},
-    AsyncCommandBuffer,
+    systems::{apply_async_commandbuffers, update_transform_system},
+    tasks::cancel_orphaned_tasks_system,
+    AsyncCommandBuffer, Tasks,
 };
 
+pub mod channels;
 pub mod events;
 
 use self::events::{EventRegisterContext, EventRegistry};
@@ -69,18 +71,24 @@ impl<T: Layer> LayerDyn for T {
 pub struct EngineLayer {
     schedule: Schedule,
     cmd: AsyncCommandBuffer,
+    tasks: Tasks,
 }
 
 impl EngineLayer {
     pub fn new() -> Self {
         let cmd = AsyncCommandBuffer::new();
+        let tasks = Tasks::new(cmd.clone());
         let schedule = Schedule::builder()
             .with_system(apply_async_commandbuffers(cmd.clone()))
-            .with_system(update_root_transforms_system())
+            .with_system(cancel_orphaned_tasks_system(tasks.clone()))
             .with_system(update_transform_system())
             .build();
 
-        Self { cmd, schedule }
+        Self {
+            cmd,
+            tasks,
+            schedule,
+        }
     }
 }
 
@@ -99,6 +107,7 @@ impl Layer for EngineLayer {
     ) -> anyhow::Result<()> {
         Entity::builder()
             .set(async_commandbuffer(), self.cmd.clone())
+            .set(tasks(), self.tasks.clone())
             .set(gizmos(), Gizmos::new())
             .set(request_capture_mouse(), false)
             .append_to(world, engine())?;
@@ -29,6 +29,7 @@
 pub mod profiling;
 
 pub mod app;
+pub mod bounds;
 mod color;
 pub mod components;
 mod dir;
@@ -37,19 +38,31 @@ mod extent;
 pub mod gizmos;
 pub mod layer;
 pub mod macros;
+pub mod memory;
+pub mod notifications;
+pub mod pool;
 pub mod subscribers;
+/// Exposed for benchmarking through the `bench` feature
+#[cfg(feature = "bench")]
+pub mod systems;
+#[cfg(not(feature = "bench"))]
 mod systems;
+pub mod tasks;
+pub mod time;
+pub mod tween;
 mod updatable;
 pub mod update_layer;
 
 use std::f32::consts::PI;
 
-pub use app::{driver, App, AppBuilder, AppEvent};
+pub use app::{driver, App, AppBuilder, AppEvent, AppPlugin};
 pub use color::*;
 pub use dir::*;
 pub use extensions::*;
 pub use extent::*;
 pub use layer::*;
+pub use tasks::Tasks;
+pub use time::Time;
 
 /// 45 degrees in radians
 pub const DEG_45: f32 = PI / 4.0;
@@ -0,0 +1,238 @@
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    collections::BTreeMap,
+    sync::{
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+        Arc,
+    },
+};
+
+use parking_lot::Mutex;
+
+static HEAP_CURRENT: AtomicUsize = AtomicUsize::new(0);
+static HEAP_PEAK: AtomicUsize = AtomicUsize::new(0);
+static HEAP_ALLOCATIONS: AtomicU64 = AtomicU64::new(0);
+
+/// Global allocator which counts the heap memory in use.
+///
+/// A library can not choose the allocator of the binary, so the binary opts in by installing it:
+///
+/// ```rust,ignore
+/// #[global_allocator]
+/// static ALLOCATOR: ivy_core::memory::TrackingAllocator = ivy_core::memory::TrackingAllocator;
+/// ```
+pub struct TrackingAllocator;
+
+unsafe impl GlobalAlloc for TrackingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let ptr = System.alloc(layout);
+        if !ptr.is_null() {
+            track_alloc(layout.size());
+        }
+        ptr
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        let ptr = System.alloc_zeroed(layout);
+        if !ptr.is_null() {
+            track_alloc(layout.size());
+        }
+        ptr
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout);
+        HEAP_CURRENT.fetch_sub(layout.size(), Ordering::Relaxed);
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        let new_ptr = System.realloc(ptr, layout, new_size);
+        if !new_ptr.is_null() {
+            HEAP_CURRENT.fetch_sub(layout.size(), Ordering::Relaxed);
+            track_alloc(new_size);
+        }
+        new_ptr
+    }
+}
+
+fn track_alloc(size: usize) {
+    let current = HEAP_CURRENT.fetch_add(size, Ordering::Relaxed) + size;
+    HEAP_PEAK.fetch_max(current, Ordering::Relaxed);
+    HEAP_ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+}
+
+/// Heap usage counted by the [`TrackingAllocator`].
+///
+/// All zero if the allocator is not installed.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct HeapUsage {
+    /// Bytes currently allocated
+    pub current: usize,
+    /// Highest number of bytes allocated at once
+    pub peak: usize,
+    /// Total number of allocations made
+    pub allocations: u64,
+}
+
+impl HeapUsage {
+    pub fn get() -> Self {
+        Self {
+            current: HEAP_CURRENT.load(Ordering::Relaxed),
+            peak: HEAP_PEAK.load(Ordering::Relaxed),
+            allocations: HEAP_ALLOCATIONS.load(Ordering::Relaxed),
+        }
+    }
+}
+
+static COUNTERS: Mutex<BTreeMap<&'static str, Arc<AtomicU64>>> = Mutex::new(BTreeMap::new());
+
+/// Bytes used by a subsystem, such as `gpu_buffers` or `physics`.
+///
+/// Counters with the same category are shared.
+#[derive(Debug, Clone)]
+pub struct MemoryCounter {
+    category: &'static str,
+    bytes: Arc<AtomicU64>,
+}
+
+impl MemoryCounter {
+    pub fn new(category: &'static str) -> Self {
+        let bytes = COUNTERS.lock().entry(category).or_default().clone();
+        Self { category, bytes }
+    }
+
+    pub fn add(&self, bytes: u64) {
+        self.bytes.fetch_add(bytes, Ordering::Relaxed);
+    }
+
+    pub fn sub(&self, bytes: u64) {
+        self.bytes.fetch_sub(bytes, Ordering::Relaxed);
+    }
+
+    pub fn get(&self) -> u64 {
+        self.bytes.load(Ordering::Relaxed)
+    }
+
+    pub fn category(&self) -> &'static str {
+        self.category
+    }
+}
+
+/// Memory accounted to a [`MemoryCounter`] for as long as it is alive
+#[derive(Debug)]
+pub struct TrackedMemory {
+    counter: MemoryCounter,
+    bytes: u64,
+}
+
+impl TrackedMemory {
+    pub fn new(category: &'static str, bytes: u64) -> Self {
+        let counter = MemoryCounter::new(category);
+        counter.add(bytes);
+        Self { counter, bytes }
+    }
+
+    /// Changes the accounted size, e.g; after reallocating
+    pub fn set(&mut self, bytes: u64) {
+        self.counter.sub(self.bytes);
+        self.counter.add(bytes);
+        self.bytes = bytes;
+    }
+
+    pub fn bytes(&self) -> u64 {
+        self.bytes
+    }
+}
+
+impl Drop for TrackedMemory {
+    fn drop(&mut self) {
+        self.counter.sub(self.bytes);
+    }
+}
+
+/// Snapshot of the memory used by the heap and each subsystem.
+///
+/// Stored on the engine entity and updated each tick.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct MemoryReport {
+    pub heap: HeapUsage,
+    /// Bytes used by each category of [`MemoryCounter`]
+    pub categories: BTreeMap<&'static str, u64>,
+}
+
+impl MemoryReport {
+    pub fn collect() -> Self {
+        Self {
+            heap: HeapUsage::get(),
+            categories: COUNTERS
+                .lock()
+                .iter()
+                .map(|(&category, bytes)| (category, bytes.load(Ordering::Relaxed)))
+                .collect(),
+        }
+    }
+
+    pub fn category(&self, category: &str) -> u64 {
+        self.categories.get(category).copied().unwrap_or_default()
+    }
+}
+
+/// Maximum number of bytes each category is expected to use
+#[derive(Debug, Default, Clone)]
+pub struct MemoryBudgets {
+    budgets: BTreeMap<&'static str, u64>,
+}
+
+impl MemoryBudgets {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the budget of `category`. Use `heap` for the total heap usage
+    pub fn with_budget(mut self, category: &'static str, bytes: u64) -> Self {
+        self.budgets.insert(category, bytes);
+        self
+    }
+
+    /// Returns the categories which exceed their budget, along with their usage and budget
+    pub fn exceeded<'a>(
+        &'a self,
+        report: &'a MemoryReport,
+    ) -> impl Iterator<Item = (&'static str, u64, u64)> + 'a {
+        self.budgets.iter().filter_map(|(&category, &budget)| {
+            let used = match category {
+                "heap" => report.heap.current as u64,
+                _ => report.category(category),
+            };
+
+            (used > budget).then_some((category, used, budget))
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn tracked_memory() {
+        let mut a = TrackedMemory::new("test_tracked", 64);
+        let b = TrackedMemory::new("test_tracked", 32);
+        assert_eq!(MemoryReport::collect().category("test_tracked"), 96);
+
+        a.set(16);
+        drop(b);
+
+        let report = MemoryReport::collect();
+        assert_eq!(report.category("test_tracked"), 16);
+
+        let budgets = MemoryBudgets::new()
+            .with_budget("test_tracked", 8)
+            .with_budget("test_untracked", 8);
+
+        assert_eq!(
+            budgets.exceeded(&report).collect::<Vec<_>>(),
+            [("test_tracked", 16, 8)]
+        );
+    }
+}
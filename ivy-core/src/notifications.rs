@@ -0,0 +1,300 @@
+//! On-screen notifications, used to surface non-fatal problems to the user rather than only
+//! logging them to the console.
+//!
+//! Anything with access to the asset cache can queue a notification using [`notify`], or through
+//! a [`Notifier`]. The [`NotificationLayer`] displays them as a stack of timed toasts.
+use std::{collections::VecDeque, time::Duration};
+
+use glam::Vec2;
+use ivy_assets::{service::Service, AssetCache};
+
+use crate::{
+    app::TickEvent,
+    components::{engine, gizmos, notifications},
+    gizmos::{DrawScreenGizmos, ScreenGizmosSection, ScreenLine, ScreenText},
+    layer::events::EventRegisterContext,
+    Color, ColorExt, Layer,
+};
+
+/// New notifications are dropped beyond this many pending, e.g; when no layer displays them
+const MAX_PENDING: usize = 64;
+
+const DEFAULT_DURATION: Duration = Duration::from_secs(5);
+const FADE_DURATION: Duration = Duration::from_millis(500);
+const TEXT_SIZE: f32 = 12.0;
+const SPACING: f32 = 24.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Severity {
+    Info,
+    Warning,
+    Error,
+}
+
+impl Severity {
+    pub fn color(&self) -> Color {
+        match self {
+            Severity::Info => Color::white(),
+            Severity::Warning => Color::yellow(),
+            Severity::Error => Color::red(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Notification {
+    pub severity: Severity,
+    pub message: String,
+    /// How long the notification is displayed
+    pub duration: Duration,
+}
+
+impl Notification {
+    pub fn new(severity: Severity, message: impl Into<String>) -> Self {
+        Self {
+            severity,
+            message: message.into(),
+            duration: DEFAULT_DURATION,
+        }
+    }
+
+    pub fn info(message: impl Into<String>) -> Self {
+        Self::new(Severity::Info, message)
+    }
+
+    pub fn warning(message: impl Into<String>) -> Self {
+        Self::new(Severity::Warning, message)
+    }
+
+    pub fn error(message: impl Into<String>) -> Self {
+        Self::new(Severity::Error, message)
+    }
+
+    /// Set how long the notification is displayed
+    pub fn with_duration(mut self, duration: Duration) -> Self {
+        self.duration = duration;
+        self
+    }
+}
+
+/// Queues notifications for the [`Notifications`] it was created from.
+///
+/// Registered as a service of the asset cache by the app, so that subsystems without access to
+/// the world can notify, including from other threads.
+#[derive(Debug, Clone)]
+pub struct Notifier {
+    tx: flume::Sender<Notification>,
+}
+
+impl Notifier {
+    pub fn notify(&self, notification: Notification) {
+        self.tx.try_send(notification).ok();
+    }
+}
+
+impl Service for Notifier {}
+
+/// Queues a notification through the [`Notifier`] registered with `assets`.
+///
+/// The notification is dropped if no notifier is registered, e.g; outside of an app.
+pub fn notify(assets: &AssetCache, notification: Notification) {
+    if let Some(notifier) = assets.try_service::<Notifier>() {
+        notifier.notify(notification);
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Toast {
+    notification: Notification,
+    remaining: Duration,
+}
+
+/// Currently displayed notifications, newest last
+#[derive(Debug, Clone)]
+pub struct Notifications {
+    toasts: VecDeque<Toast>,
+    max_visible: usize,
+    /// Top left corner of the stack, in pixels
+    position: Vec2,
+    tx: flume::Sender<Notification>,
+    pending: flume::Receiver<Notification>,
+}
+
+impl Notifications {
+    pub fn new() -> Self {
+        let (tx, pending) = flume::bounded(MAX_PENDING);
+        Self {
+            toasts: VecDeque::new(),
+            max_visible: 5,
+            position: Vec2::new(16.0, 16.0),
+            tx,
+            pending,
+        }
+    }
+
+    /// Returns a handle which queues notifications to be picked up by [`Self::update`]
+    pub fn notifier(&self) -> Notifier {
+        Notifier {
+            tx: self.tx.clone(),
+        }
+    }
+
+    /// Set the maximum number of toasts displayed at once.
+    ///
+    /// The oldest toasts are dismissed early to make room for new ones.
+    pub fn with_max_visible(mut self, max_visible: usize) -> Self {
+        self.max_visible = max_visible;
+        self
+    }
+
+    /// Set the top left corner of the stack, in pixels
+    pub fn with_position(mut self, position: Vec2) -> Self {
+        self.position = position;
+        self
+    }
+
+    pub fn push(&mut self, notification: Notification) {
+        self.toasts.push_back(Toast {
+            remaining: notification.duration,
+            notification,
+        });
+
+        while self.toasts.len() > self.max_visible {
+            self.toasts.pop_front();
+        }
+    }
+
+    /// Picks up the pending notifications and dismisses the expired ones
+    pub fn update(&mut self, dt: Duration) {
+        for toast in &mut self.toasts {
+            toast.remaining = toast.remaining.saturating_sub(dt);
+        }
+
+        self.toasts.retain(|v| !v.remaining.is_zero());
+
+        for notification in self.pending.try_iter().collect::<Vec<_>>() {
+            self.push(notification);
+        }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Notification> {
+        self.toasts.iter().map(|v| &v.notification)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.toasts.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.toasts.clear();
+    }
+}
+
+impl Default for Notifications {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl DrawScreenGizmos for Notifications {
+    fn draw_primitives(&self, gizmos: &mut ScreenGizmosSection) {
+        for (i, toast) in self.toasts.iter().enumerate() {
+            let mut color = toast.notification.severity.color();
+            color.alpha *= (toast.remaining.as_secs_f32() / FADE_DURATION.as_secs_f32()).min(1.0);
+
+            let pos = self.position + Vec2::Y * i as f32 * SPACING;
+
+            gizmos.draw(ScreenLine::new(pos, pos + Vec2::Y * TEXT_SIZE, color).with_thickness(4.0));
+
+            gizmos.draw(
+                ScreenText::new(pos + Vec2::X * 12.0, toast.notification.message.clone())
+                    .with_size(TEXT_SIZE)
+                    .with_color(color),
+            );
+        }
+    }
+}
+
+/// Displays the [`notifications`] of the engine as toasts using screen space gizmos
+#[derive(Default)]
+pub struct NotificationLayer {}
+
+impl NotificationLayer {
+    pub fn new() -> Self {
+        Self {}
+    }
+}
+
+impl Layer for NotificationLayer {
+    fn register(
+        &mut self,
+        _: &mut flax::World,
+        _: &ivy_assets::AssetCache,
+        mut events: EventRegisterContext<Self>,
+    ) -> anyhow::Result<()>
+    where
+        Self: Sized,
+    {
+        events.subscribe(|_, ctx, &TickEvent(dt): &TickEvent| {
+            let mut notifications = ctx.world.get_mut(engine(), notifications())?;
+            notifications.update(dt);
+
+            let gizmos = ctx.world.get(engine(), gizmos())?;
+            gizmos
+                .begin_screen_section("notifications")
+                .draw(&*notifications);
+
+            Ok(())
+        });
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn toasts_expire() {
+        let mut notifications = Notifications::new().with_max_visible(2);
+
+        notifications.push(Notification::info("a").with_duration(Duration::from_secs(1)));
+        notifications.push(Notification::warning("b").with_duration(Duration::from_secs(3)));
+        notifications.push(Notification::error("c").with_duration(Duration::from_secs(2)));
+
+        let messages = |n: &Notifications| n.iter().map(|v| v.message.clone()).collect::<Vec<_>>();
+        assert_eq!(messages(&notifications), ["b", "c"]);
+
+        notifications.update(Duration::from_secs(2));
+
+        assert_eq!(messages(&notifications), ["b"]);
+    }
+
+    #[test]
+    fn separate_notifiers() {
+        let mut a = Notifications::new();
+        let mut b = Notifications::new();
+
+        let assets = AssetCache::new();
+        assets.register_service(a.notifier());
+
+        notify(&assets, Notification::error("failed"));
+        b.notifier().notify(Notification::info("loaded"));
+
+        a.update(Duration::ZERO);
+        b.update(Duration::ZERO);
+
+        assert_eq!(
+            a.iter().map(|v| &*v.message).collect::<Vec<_>>(),
+            ["failed"]
+        );
+        assert_eq!(
+            b.iter().map(|v| &*v.message).collect::<Vec<_>>(),
+            ["loaded"]
+        );
+
+        // Dropped without a registered notifier
+        notify(&AssetCache::new(), Notification::error("lost"));
+    }
+}
@@ -0,0 +1,304 @@
+//! Reuses entities which are frequently spawned and despawned, such as bullets, particles, and
+//! pickups.
+use std::{collections::BTreeMap, fmt::Debug, sync::Arc};
+
+use flax::{components::child_of, Debuggable, Entity, EntityBuilder, World};
+
+/// Describes the components of an entity, which are applied each time it is spawned
+#[derive(Clone)]
+pub struct Template {
+    mount: Arc<dyn Fn(&mut EntityBuilder) + Send + Sync>,
+}
+
+impl Template {
+    pub fn new(mount: impl Fn(&mut EntityBuilder) + Send + Sync + 'static) -> Self {
+        Self {
+            mount: Arc::new(mount),
+        }
+    }
+
+    pub fn builder(&self) -> EntityBuilder {
+        let mut builder = Entity::builder();
+        (self.mount)(&mut builder);
+        builder
+    }
+
+    /// Returns true if both are clones of the same template
+    pub fn ptr_eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.mount, &other.mount)
+    }
+}
+
+impl Debug for Template {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Template").finish_non_exhaustive()
+    }
+}
+
+flax::component! {
+    /// Marks an entity returned to a [`Pool`], which keeps it in its archetype until it is spawned
+    /// again.
+    ///
+    /// Pooled meshes and sprites are not drawn, their rigid bodies and colliders are disabled, and
+    /// their audio sources are stopped. Other systems, such as gameplay, should skip them with
+    /// `.without(pooled())`.
+    pub pooled: () => [ Debuggable ],
+}
+
+/// Handle to an entity spawned from a [`Pool`], which is invalidated when the entity is returned
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct PoolHandle {
+    id: Entity,
+    generation: u32,
+}
+
+impl PoolHandle {
+    pub fn id(&self) -> Entity {
+        self.id
+    }
+}
+
+/// Keeps despawned entities alive to be spawned again, reusing the entity ids.
+///
+/// Returned entities keep their components and are marked as [`pooled`], which avoids moving them
+/// between archetypes. The values of the template are reset in place when spawned again.
+/// Components added after spawning are kept, and should be removed before returning the entity.
+///
+/// Children are despawned when returned, and spawned again by the template.
+///
+/// A pool can serve several templates. Free entities are only reused by the template they were
+/// spawned from, so that the components of one template do not leak into another.
+///
+/// As the ids are reused, use a [`PoolHandle`] rather than the entity id to refer to an entity
+/// which may be returned, such as from a background task.
+#[derive(Debug, Default)]
+pub struct Pool {
+    free: Vec<(Entity, Template)>,
+    active: BTreeMap<Entity, Template>,
+    /// Incremented each time an entity is returned, to invalidate its handles
+    generations: BTreeMap<Entity, u32>,
+    /// Maximum number of free entities kept, despawning any beyond it
+    capacity: Option<usize>,
+}
+
+impl Pool {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Limit the number of free entities kept around
+    pub fn with_capacity(mut self, capacity: usize) -> Self {
+        self.capacity = Some(capacity);
+        self
+    }
+
+    /// Spawns `count` free entities from `template` ahead of time
+    pub fn prewarm(&mut self, world: &mut World, template: &Template, count: usize) {
+        for _ in 0..count {
+            let id = template.builder().set(pooled(), ()).spawn(world);
+            self.generations.insert(id, 0);
+            self.free.push((id, template.clone()));
+        }
+    }
+
+    /// Spawns an entity with the components of `template`, reusing a free entity if available
+    pub fn spawn_from(&mut self, world: &mut World, template: &Template) -> anyhow::Result<Entity> {
+        let id = loop {
+            let Some(index) = self.free.iter().rposition(|(_, v)| v.ptr_eq(template)) else {
+                break template.builder().spawn(world);
+            };
+
+            let (id, _) = self.free.remove(index);
+
+            // Free entities may have been despawned by a parent
+            if !world.is_alive(id) {
+                self.generations.remove(&id);
+                continue;
+            }
+
+            // The template sets the components the entity already has, which keeps it in its
+            // archetype
+            template.builder().append_to(world, id)?;
+            world.remove(id, pooled())?;
+            break id;
+        };
+
+        self.generations.entry(id).or_default();
+        self.active.insert(id, template.clone());
+        Ok(id)
+    }
+
+    /// Returns an entity spawned from the pool to be reused.
+    ///
+    /// Entities not spawned from this pool are despawned normally.
+    pub fn despawn(&mut self, world: &mut World, id: Entity) -> anyhow::Result<()> {
+        let template = self
+            .active
+            .remove(&id)
+            .filter(|_| !self.capacity.is_some_and(|v| self.free.len() >= v));
+
+        let Some(template) = template else {
+            self.generations.remove(&id);
+            world.despawn_children(id, child_of)?;
+            world.despawn(id)?;
+            return Ok(());
+        };
+
+        world.despawn_children(id, child_of)?;
+        world.set(id, pooled(), ())?;
+
+        let generation = self.generations.entry(id).or_default();
+        *generation = generation.wrapping_add(1);
+
+        self.free.push((id, template));
+        Ok(())
+    }
+
+    /// Returns a handle to an active entity of the pool
+    pub fn handle(&self, id: Entity) -> Option<PoolHandle> {
+        if !self.active.contains_key(&id) {
+            return None;
+        }
+
+        Some(PoolHandle {
+            id,
+            generation: self.generations.get(&id).copied().unwrap_or_default(),
+        })
+    }
+
+    /// Returns the entity of the handle, or `None` if it has been returned to the pool since
+    pub fn get(&self, handle: PoolHandle) -> Option<Entity> {
+        (self.active.contains_key(&handle.id)
+            && self.generations.get(&handle.id) == Some(&handle.generation))
+        .then_some(handle.id)
+    }
+
+    /// Returns true if the entity was spawned from the pool and has not been returned
+    pub fn is_active(&self, id: Entity) -> bool {
+        self.active.contains_key(&id)
+    }
+
+    pub fn active_count(&self) -> usize {
+        self.active.len()
+    }
+
+    pub fn free_count(&self) -> usize {
+        self.free.len()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use glam::Vec3;
+
+    use super::*;
+    use crate::components::{position, rotation};
+
+    fn bullet() -> Template {
+        Template::new(|builder| {
+            builder.set(position(), Vec3::ZERO);
+        })
+    }
+
+    #[test]
+    fn reuse_entities() {
+        let mut world = World::new();
+        let mut pool = Pool::new().with_capacity(1);
+        let bullet = bullet();
+
+        let a = pool.spawn_from(&mut world, &bullet).unwrap();
+        let b = pool.spawn_from(&mut world, &bullet).unwrap();
+
+        *world.get_mut(a, position()).unwrap() = Vec3::X;
+
+        pool.despawn(&mut world, a).unwrap();
+        pool.despawn(&mut world, b).unwrap();
+
+        assert_eq!(pool.free_count(), 1);
+        assert!(world.is_alive(a));
+        assert!(world.has(a, pooled()));
+        assert_eq!(*world.get(a, position()).unwrap(), Vec3::X);
+        assert!(!world.is_alive(b));
+
+        let c = pool.spawn_from(&mut world, &bullet).unwrap();
+        assert_eq!(c, a);
+        assert_eq!(*world.get(c, position()).unwrap(), Vec3::ZERO);
+        assert!(!world.has(c, pooled()));
+        assert_eq!(pool.active_count(), 1);
+    }
+
+    #[test]
+    fn prewarmed() {
+        let mut world = World::new();
+        let mut pool = Pool::new();
+        let bullet = bullet();
+
+        pool.prewarm(&mut world, &bullet, 2);
+        assert_eq!(pool.free_count(), 2);
+
+        let id = pool.spawn_from(&mut world, &bullet).unwrap();
+        assert!(world.has(id, position()));
+        assert!(!world.has(id, pooled()));
+        assert_eq!(pool.free_count(), 1);
+    }
+
+    #[test]
+    fn templates_kept_apart() {
+        let mut world = World::new();
+        let mut pool = Pool::new();
+        let bullet = bullet();
+        let spark = Template::new(|builder| {
+            builder.set(rotation(), Default::default());
+        });
+
+        let a = pool.spawn_from(&mut world, &bullet).unwrap();
+        pool.despawn(&mut world, a).unwrap();
+
+        let b = pool.spawn_from(&mut world, &spark).unwrap();
+        assert_ne!(a, b);
+        assert!(!world.has(b, position()));
+
+        pool.despawn(&mut world, b).unwrap();
+        assert_eq!(pool.free_count(), 2);
+
+        assert_eq!(pool.spawn_from(&mut world, &bullet.clone()).unwrap(), a);
+        assert_eq!(pool.spawn_from(&mut world, &spark).unwrap(), b);
+    }
+
+    #[test]
+    fn stale_handles() {
+        let mut world = World::new();
+        let mut pool = Pool::new();
+        let bullet = bullet();
+
+        let id = pool.spawn_from(&mut world, &bullet).unwrap();
+        let handle = pool.handle(id).unwrap();
+        assert_eq!(pool.get(handle), Some(id));
+
+        pool.despawn(&mut world, id).unwrap();
+        assert_eq!(pool.get(handle), None);
+        assert_eq!(pool.handle(id), None);
+
+        let reused = pool.spawn_from(&mut world, &bullet).unwrap();
+        assert_eq!(reused, id);
+        assert_eq!(pool.get(handle), None);
+        assert_eq!(pool.get(pool.handle(reused).unwrap()), Some(reused));
+    }
+
+    #[test]
+    fn children_despawned() {
+        let mut world = World::new();
+        let mut pool = Pool::new();
+        let bullet = bullet();
+
+        let id = pool.spawn_from(&mut world, &bullet).unwrap();
+        let trail = Entity::builder()
+            .set(rotation(), Default::default())
+            .set(child_of(id), ())
+            .spawn(&mut world);
+
+        pool.despawn(&mut world, id).unwrap();
+        assert!(world.is_alive(id));
+        assert!(!world.is_alive(trail));
+    }
+}
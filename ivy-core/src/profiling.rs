@@ -1,7 +1,207 @@
+use std::{
+    borrow::Cow,
+    collections::{BTreeMap, VecDeque},
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
 pub use ivy_profiling::*;
+use parking_lot::Mutex;
 
 use crate::Layer;
 
+/// Timings recorded during the current frame, taken by [`FrameStats::end_frame`].
+///
+/// Stored on the engine entity. Cloning yields a handle to the same timings, which allows
+/// recording from systems and render nodes.
+#[derive(Default, Debug, Clone)]
+pub struct ScopeTimings {
+    current: Arc<Mutex<BTreeMap<String, Duration>>>,
+}
+
+impl ScopeTimings {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds `duration` to the CPU time of `name` for the current frame
+    pub fn record(&self, name: &str, duration: Duration) {
+        let mut current = self.current.lock();
+        match current.get_mut(name) {
+            Some(v) => *v += duration,
+            None => {
+                current.insert(name.to_string(), duration);
+            }
+        }
+    }
+
+    /// Starts measuring a scope which is recorded when dropped
+    pub fn scope(&self, name: impl Into<Cow<'static, str>>) -> TimedScope {
+        TimedScope {
+            timings: self.clone(),
+            name: name.into(),
+            start: Instant::now(),
+        }
+    }
+
+    /// Takes the timings recorded since the last call
+    pub fn take(&self) -> BTreeMap<String, Duration> {
+        std::mem::take(&mut *self.current.lock())
+    }
+}
+
+/// Measures the CPU time until dropped, and records it for the current frame.
+///
+/// Scopes with the same name are summed within a frame.
+///
+/// ```rust,ignore
+/// let _scope = world.get(engine(), scope_timings())?.scope("update_bodies");
+/// ```
+pub struct TimedScope {
+    timings: ScopeTimings,
+    name: Cow<'static, str>,
+    start: Instant,
+}
+
+impl Drop for TimedScope {
+    fn drop(&mut self) {
+        self.timings.record(&self.name, self.start.elapsed());
+    }
+}
+
+/// Summary of a timing over the window of a [`FrameStats`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TimingStats {
+    /// Timing of the latest frame
+    pub last: Duration,
+    pub mean: Duration,
+    pub min: Duration,
+    pub max: Duration,
+    pub p50: Duration,
+    pub p95: Duration,
+    pub p99: Duration,
+}
+
+impl TimingStats {
+    fn from_samples(samples: &VecDeque<Duration>) -> Option<Self> {
+        let last = *samples.back()?;
+
+        let mut sorted = samples.iter().copied().collect::<Vec<_>>();
+        sorted.sort();
+
+        let percentile = |p: f64| {
+            let index = ((sorted.len() - 1) as f64 * p).round() as usize;
+            sorted[index]
+        };
+
+        Some(Self {
+            last,
+            mean: sorted.iter().sum::<Duration>() / sorted.len() as u32,
+            min: sorted[0],
+            max: sorted[sorted.len() - 1],
+            p50: percentile(0.5),
+            p95: percentile(0.95),
+            p99: percentile(0.99),
+        })
+    }
+}
+
+/// CPU timings of the frame, the layers and the [`TimedScope`]s of the systems, kept over a
+/// sliding window of frames.
+///
+/// Stored on the engine entity and updated at the end of each tick.
+#[derive(Debug, Clone)]
+pub struct FrameStats {
+    window: usize,
+    frame_times: VecDeque<Duration>,
+    scopes: BTreeMap<String, VecDeque<Duration>>,
+}
+
+impl FrameStats {
+    /// Keep the timings of the last `window` frames
+    pub fn new(window: usize) -> Self {
+        assert!(window > 0, "Window must contain at least one frame");
+        Self {
+            window,
+            frame_times: VecDeque::with_capacity(window),
+            scopes: BTreeMap::new(),
+        }
+    }
+
+    /// Ends the frame, collecting the timings recorded since the previous frame
+    pub fn end_frame(&mut self, frame_time: Duration, timings: &ScopeTimings) {
+        self.push_frame(frame_time, timings.take());
+    }
+
+    fn push_frame(&mut self, frame_time: Duration, mut current: BTreeMap<String, Duration>) {
+        let window = self.window;
+        let push = |samples: &mut VecDeque<Duration>, value| {
+            if samples.len() == window {
+                samples.pop_front();
+            }
+            samples.push_back(value);
+        };
+
+        push(&mut self.frame_times, frame_time);
+
+        // Scopes which did not run this frame took no time
+        for (name, samples) in &mut self.scopes {
+            push(samples, current.remove(name).unwrap_or_default());
+        }
+
+        for (name, duration) in current {
+            let mut samples = VecDeque::with_capacity(window);
+            push(&mut samples, duration);
+            self.scopes.insert(name, samples);
+        }
+    }
+
+    /// Time between the start of consecutive frames
+    pub fn frame_time(&self) -> Option<TimingStats> {
+        TimingStats::from_samples(&self.frame_times)
+    }
+
+    pub fn scope(&self, name: &str) -> Option<TimingStats> {
+        TimingStats::from_samples(self.scopes.get(name)?)
+    }
+
+    /// Returns the stats of all recorded layers and scopes, by name
+    pub fn scopes(&self) -> impl Iterator<Item = (&str, TimingStats)> {
+        self.scopes.iter().filter_map(|(name, samples)| {
+            Some((name.as_str(), TimingStats::from_samples(samples)?))
+        })
+    }
+
+    /// Number of frames currently in the window
+    pub fn frame_count(&self) -> usize {
+        self.frame_times.len()
+    }
+
+    /// Frame times of the window, from oldest to newest
+    pub fn frame_times(&self) -> impl ExactSizeIterator<Item = Duration> + '_ {
+        self.frame_times.iter().copied()
+    }
+
+    /// Mean frame time of the slowest `fraction` of frames, such as `0.01` for the 1% lows
+    pub fn frame_time_low(&self, fraction: f64) -> Option<Duration> {
+        if self.frame_times.is_empty() {
+            return None;
+        }
+
+        let mut sorted = self.frame_times.iter().copied().collect::<Vec<_>>();
+        sorted.sort_by(|a, b| b.cmp(a));
+
+        let count = ((sorted.len() as f64 * fraction).ceil() as usize).clamp(1, sorted.len());
+        Some(sorted[..count].iter().sum::<Duration>() / count as u32)
+    }
+}
+
+impl Default for FrameStats {
+    fn default() -> Self {
+        Self::new(240)
+    }
+}
+
 pub struct ProfilingLayer {
     #[allow(dead_code)]
     #[cfg(feature = "profile")]
@@ -59,3 +259,73 @@ impl Default for ProfilingLayer {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn ms(v: u64) -> Duration {
+        Duration::from_millis(v)
+    }
+
+    #[test]
+    fn sliding_window() {
+        let mut stats = FrameStats::new(4);
+
+        for i in 1..=6 {
+            let mut current = BTreeMap::new();
+            current.insert("physics".to_string(), ms(i));
+            if i == 6 {
+                current.insert("late".to_string(), ms(10));
+            }
+
+            stats.push_frame(ms(16), current);
+        }
+
+        let physics = stats.scope("physics").unwrap();
+        assert_eq!(physics.last, ms(6));
+        assert_eq!(physics.min, ms(3));
+        assert_eq!(physics.max, ms(6));
+        assert_eq!(physics.p50, ms(5));
+
+        stats.push_frame(ms(16), BTreeMap::new());
+
+        let late = stats.scope("late").unwrap();
+        assert_eq!(late.last, Duration::ZERO);
+        assert_eq!(late.max, ms(10));
+        assert_eq!(stats.frame_count(), 4);
+        assert_eq!(stats.frame_time().unwrap().mean, ms(16));
+    }
+
+    #[test]
+    fn lows() {
+        let mut stats = FrameStats::new(200);
+        for i in 0..200 {
+            let frame_time = if i % 100 == 0 { ms(50) } else { ms(10) };
+            stats.push_frame(frame_time, BTreeMap::new());
+        }
+
+        assert_eq!(stats.frame_time_low(0.01), Some(ms(50)));
+        assert_eq!(stats.frame_time_low(0.001), Some(ms(50)));
+        assert_eq!(stats.frame_time_low(0.02), Some(ms(30)));
+        assert_eq!(FrameStats::new(4).frame_time_low(0.01), None);
+    }
+
+    #[test]
+    fn separate_timings() {
+        let a = ScopeTimings::new();
+        let b = ScopeTimings::new();
+
+        a.record("physics", ms(2));
+        a.clone().record("physics", ms(3));
+        drop(b.scope("render"));
+
+        let mut stats = FrameStats::new(4);
+        stats.end_frame(ms(16), &a);
+
+        assert_eq!(stats.scope("physics").unwrap().last, ms(5));
+        assert!(stats.scope("render").is_none());
+        assert!(a.take().is_empty());
+        assert!(b.take().contains_key("render"));
+    }
+}
@@ -1,61 +1,74 @@
 use anyhow::Context;
-use flax::{
-    components::child_of, system, BoxedSystem, Dfs, DfsBorrow, FetchExt, Query, RelationExt,
-    System, World,
-};
-use glam::{Mat4, Quat, Vec3};
+use flax::{components::child_of, entity_ids, BoxedSystem, Dfs, FetchExt, Query, System, World};
+use glam::Mat4;
 
 use crate::{
-    components::{position, rotation, scale, world_transform, TransformQuery},
+    components::{
+        attachment_transform, engine, parent_transform, scope_timings, world_transform,
+        TransformQuery,
+    },
     AsyncCommandBuffer,
 };
 
-// #[system(args(position=position(),rotation=rotation().modified(),scale=scale()), par)]
-// pub fn update_root_transforms(
-//     world_transform: &mut Mat4,
-//     position: &Vec3,
-//     rotation: &Quat,
-//     scale: &Vec3,
-// ) {
-//     *world_transform = Mat4::from_scale_rotation_translation(*scale, *rotation, *position)
-// }
-
-pub fn update_root_transforms_system() -> BoxedSystem {
-    System::builder()
-        .with_query(
-            Query::new((world_transform().as_mut(), TransformQuery::new().modified()))
-                .batch_size(1024),
-        )
-        .par_for_each(|(world_transform, item)| {
-            *world_transform =
-                Mat4::from_scale_rotation_translation(*item.scale, *item.rotation, *item.pos)
-        })
-        .boxed()
-}
-
+/// Propagates the local transforms of entities down the [`child_of`] hierarchy.
+///
+/// Only entities whose local transform was modified, or whose parent's world transform changed
+/// since the last run, are recomputed. The parent transform used is cached in
+/// [`parent_transform`], which also catches entities that were moved to a different parent.
+///
+/// The [`attachment_transform`] of an entity is applied between the parent and the local
+/// transform.
 pub fn update_transform_system() -> BoxedSystem {
+    let mut query = Query::new((
+        entity_ids(),
+        world_transform().copied(),
+        parent_transform().copied().opt(),
+        attachment_transform().copied().opt(),
+        TransformQuery::new(),
+        TransformQuery::new().modified().satisfied(),
+    ))
+    .with_strategy(Dfs::new(child_of));
+
+    let mut dirty = Vec::new();
+
     System::builder()
-        .with_query(
-            // TODO: be smarter about this, sleeping entities etc
-            Query::new((world_transform().as_mut(), position(), rotation(), scale()))
-                .with_strategy(Dfs::new(child_of)),
-        )
-        .build(|mut query: DfsBorrow<_, _>| {
-            query.traverse(
+        .with_world_mut()
+        .build(move |world: &mut World| {
+            let _scope = world
+                .get(engine(), scope_timings())
+                .ok()
+                .map(|v| v.scope("update_transforms"));
+
+            query.borrow(world).traverse(
                 &Mat4::IDENTITY,
-                |(world_transform, &position, &rotation, &scale): (
-                    &mut Mat4,
-                    &Vec3,
-                    &Quat,
-                    &Vec3,
-                ),
-                 _,
-                 parent| {
-                    *world_transform =
-                        *parent * Mat4::from_scale_rotation_translation(scale, rotation, position);
-                    *world_transform
+                |(id, current, cached_parent, attachment, item, modified), _, parent| {
+                    let parent = match attachment {
+                        Some(attachment) => *parent * attachment,
+                        None => *parent,
+                    };
+
+                    if !modified && cached_parent == Some(parent) {
+                        return current;
+                    }
+
+                    let transform = parent
+                        * Mat4::from_scale_rotation_translation(
+                            *item.scale,
+                            *item.rotation,
+                            *item.pos,
+                        );
+
+                    dirty.push((id, parent, transform));
+                    transform
                 },
             );
+
+            for (id, parent, transform) in dirty.drain(..) {
+                world.set(id, parent_transform(), parent)?;
+                world.set(id, world_transform(), transform)?;
+            }
+
+            anyhow::Ok(())
         })
         .boxed()
 }
@@ -70,3 +83,88 @@ pub fn apply_async_commandbuffers(cmd: AsyncCommandBuffer) -> BoxedSystem {
         })
         .boxed()
 }
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashSet;
+
+    use flax::{Entity, Schedule};
+    use glam::Vec3;
+
+    use super::*;
+    use crate::{
+        components::{position, TransformBundle},
+        Bundle,
+    };
+
+    #[test]
+    fn propagate_modified_subtrees() {
+        let mut world = World::new();
+
+        let spawn = |world: &mut World, pos: Vec3, parent: Option<Entity>| {
+            let mut builder = Entity::builder();
+            TransformBundle::default()
+                .with_position(pos)
+                .mount(&mut builder);
+            if let Some(parent) = parent {
+                builder.set(child_of(parent), ());
+            }
+            builder.spawn(world)
+        };
+
+        let a = spawn(&mut world, Vec3::X, None);
+        let a_child = spawn(&mut world, Vec3::Y, Some(a));
+        let b = spawn(&mut world, Vec3::Z, None);
+        let b_child = spawn(&mut world, Vec3::Y, Some(b));
+
+        let mut schedule = Schedule::builder()
+            .with_system(update_transform_system())
+            .build();
+
+        let mut changed = Query::new(entity_ids()).filter(world_transform().modified());
+
+        schedule.execute_seq(&mut world).unwrap();
+        assert_eq!(changed.borrow(&world).iter().count(), 4);
+
+        let child_pos = |world: &World, id| {
+            world
+                .get(id, world_transform())
+                .unwrap()
+                .transform_point3(Vec3::ZERO)
+        };
+
+        assert_eq!(child_pos(&world, a_child), Vec3::new(1.0, 1.0, 0.0));
+
+        *world.get_mut(a, position()).unwrap() = Vec3::NEG_X;
+        schedule.execute_seq(&mut world).unwrap();
+
+        assert_eq!(
+            changed.borrow(&world).iter().collect::<HashSet<_>>(),
+            HashSet::from([a, a_child])
+        );
+        assert_eq!(child_pos(&world, a_child), Vec3::new(-1.0, 1.0, 0.0));
+
+        // Moving to another parent recomputes the entity, even though it did not move locally
+        world.set(a_child, child_of(b), ()).unwrap();
+        world.remove(a_child, child_of(a)).unwrap();
+        schedule.execute_seq(&mut world).unwrap();
+
+        assert_eq!(child_pos(&world, a_child), Vec3::new(0.0, 1.0, 1.0));
+        assert_eq!(child_pos(&world, b_child), Vec3::new(0.0, 1.0, 1.0));
+
+        // Attachments apply between the parent and the local transform
+        world
+            .set(
+                b_child,
+                attachment_transform(),
+                Mat4::from_translation(Vec3::X),
+            )
+            .unwrap();
+        schedule.execute_seq(&mut world).unwrap();
+        assert_eq!(child_pos(&world, b_child), Vec3::new(1.0, 1.0, 1.0));
+
+        world.remove(b_child, attachment_transform()).unwrap();
+        schedule.execute_seq(&mut world).unwrap();
+        assert_eq!(child_pos(&world, b_child), Vec3::new(0.0, 1.0, 1.0));
+    }
+}
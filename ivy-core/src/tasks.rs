@@ -0,0 +1,177 @@
+use std::{
+    collections::HashMap,
+    future::Future,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+};
+
+use flax::{BoxedSystem, Entity, System, World};
+use futures::future::{AbortHandle, Abortable};
+use parking_lot::Mutex;
+
+use crate::AsyncCommandBuffer;
+
+struct TaskEntry {
+    owner: Option<Entity>,
+    abort: AbortHandle,
+}
+
+#[derive(Default)]
+struct TasksInner {
+    next_id: AtomicU64,
+    running: Mutex<HashMap<u64, TaskEntry>>,
+}
+
+/// Spawns background jobs whose results are applied to the world once they complete.
+///
+/// Stored on the engine entity. Results are applied through the [`AsyncCommandBuffer`] of the
+/// engine at the start of the next tick.
+///
+/// ```rust,ignore
+/// let tasks = world.get(engine(), tasks())?.clone();
+///
+/// tasks.spawn_owned(id, load_model(assets, path), |world, id, model| {
+///     model.mount(&mut world.entity_mut(id)?);
+///     Ok(())
+/// });
+/// ```
+#[derive(Clone)]
+pub struct Tasks {
+    cmd: AsyncCommandBuffer,
+    inner: Arc<TasksInner>,
+}
+
+impl Tasks {
+    pub fn new(cmd: AsyncCommandBuffer) -> Self {
+        Self {
+            cmd,
+            inner: Default::default(),
+        }
+    }
+
+    /// Spawns a job on the async executor, and applies its result to the world when it completes
+    pub fn spawn<T: 'static + Send>(
+        &self,
+        fut: impl 'static + Send + Future<Output = T>,
+        apply: impl 'static + Send + FnOnce(&mut World, T) -> anyhow::Result<()>,
+    ) -> TaskHandle {
+        self.spawn_inner(None, fut, apply)
+    }
+
+    /// Spawns a job which is cancelled if `owner` is despawned before the result is applied
+    pub fn spawn_owned<T: 'static + Send>(
+        &self,
+        owner: Entity,
+        fut: impl 'static + Send + Future<Output = T>,
+        apply: impl 'static + Send + FnOnce(&mut World, Entity, T) -> anyhow::Result<()>,
+    ) -> TaskHandle {
+        self.spawn_inner(Some(owner), fut, move |world, value| {
+            if world.is_alive(owner) {
+                apply(world, owner, value)
+            } else {
+                Ok(())
+            }
+        })
+    }
+
+    /// Runs blocking work, such as decoding or mesh generation, on a separate thread pool
+    pub fn spawn_blocking<T: 'static + Send>(
+        &self,
+        func: impl 'static + Send + FnOnce() -> T,
+        apply: impl 'static + Send + FnOnce(&mut World, T) -> anyhow::Result<()>,
+    ) -> TaskHandle {
+        self.spawn(async_std::task::spawn_blocking(func), apply)
+    }
+
+    fn spawn_inner<T: 'static + Send>(
+        &self,
+        owner: Option<Entity>,
+        fut: impl 'static + Send + Future<Output = T>,
+        apply: impl 'static + Send + FnOnce(&mut World, T) -> anyhow::Result<()>,
+    ) -> TaskHandle {
+        let id = self.inner.next_id.fetch_add(1, Ordering::Relaxed);
+        let (abort, registration) = AbortHandle::new_pair();
+
+        self.inner.running.lock().insert(
+            id,
+            TaskEntry {
+                owner,
+                abort: abort.clone(),
+            },
+        );
+
+        let fut = Abortable::new(fut, registration);
+        let cmd = self.cmd.clone();
+        let inner = self.inner.clone();
+        let handle = abort.clone();
+
+        async_std::task::spawn(async move {
+            let result = fut.await;
+            inner.running.lock().remove(&id);
+
+            if let Ok(value) = result {
+                cmd.lock().defer(move |world| {
+                    // Cancelled after completing, but before being applied
+                    if abort.is_aborted() {
+                        return Ok(());
+                    }
+
+                    apply(world, value)
+                });
+            }
+        });
+
+        TaskHandle { abort: handle }
+    }
+
+    /// Returns the number of jobs which have not yet completed
+    pub fn running(&self) -> usize {
+        self.inner.running.lock().len()
+    }
+
+    /// Cancels the jobs whose owner has been despawned
+    pub fn cancel_orphaned(&self, world: &World) {
+        self.inner
+            .running
+            .lock()
+            .retain(|_, task| match task.owner {
+                Some(owner) if !world.is_alive(owner) => {
+                    task.abort.abort();
+                    false
+                }
+                _ => true,
+            });
+    }
+
+    pub fn cancel_all(&self) {
+        for (_, task) in self.inner.running.lock().drain() {
+            task.abort.abort();
+        }
+    }
+}
+
+/// Handle to a job spawned through [`Tasks`]
+#[derive(Debug, Clone)]
+pub struct TaskHandle {
+    abort: AbortHandle,
+}
+
+impl TaskHandle {
+    /// Cancels the job. Its result will not be applied, even if it has already completed
+    pub fn cancel(&self) {
+        self.abort.abort()
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.abort.is_aborted()
+    }
+}
+
+pub fn cancel_orphaned_tasks_system(tasks: Tasks) -> BoxedSystem {
+    System::builder()
+        .with_world()
+        .build(move |world: &World| tasks.cancel_orphaned(world))
+        .boxed()
+}
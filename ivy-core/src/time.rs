@@ -0,0 +1,66 @@
+use std::time::Duration;
+
+/// Controls how fast time passes for the simulation.
+///
+/// Stored on the engine entity. Scaled time is used for [`delta_time`](crate::components::delta_time)
+/// and for advancing the fixed time step, so pausing freezes the world while schedules keep
+/// running. Systems that should ignore pausing and slow motion, such as UI, use
+/// [`unscaled_delta_time`](crate::components::unscaled_delta_time).
+///
+/// Systems which must not run at all while paused belong in an app state, see
+/// [`ScheduledLayer::with_plugin_in_states`](crate::update_layer::ScheduledLayer::with_plugin_in_states).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Time {
+    paused: bool,
+    scale: f64,
+}
+
+impl Time {
+    pub fn new() -> Self {
+        Self {
+            paused: false,
+            scale: 1.0,
+        }
+    }
+
+    pub fn pause(&mut self) {
+        self.paused = true;
+    }
+
+    pub fn resume(&mut self) {
+        self.paused = false;
+    }
+
+    pub fn set_paused(&mut self, paused: bool) {
+        self.paused = paused;
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    /// Set the rate at which time passes, e.g; `0.5` for slow motion
+    pub fn set_scale(&mut self, scale: f64) {
+        assert!(scale >= 0.0, "Time scale must not be negative");
+        self.scale = scale;
+    }
+
+    pub fn scale(&self) -> f64 {
+        self.scale
+    }
+
+    /// Returns the duration `dt` of wall clock time corresponds to in scaled time
+    pub fn scaled(&self, dt: Duration) -> Duration {
+        if self.paused {
+            Duration::ZERO
+        } else {
+            dt.mul_f64(self.scale)
+        }
+    }
+}
+
+impl Default for Time {
+    fn default() -> Self {
+        Self::new()
+    }
+}
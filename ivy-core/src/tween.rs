@@ -0,0 +1,595 @@
+//! Animates component values towards a target over time, such as fading UI, opening doors, or
+//! shaking the camera, without having to author an animation asset.
+//!
+//! Add a [`Tween`] to the [`tweens`] of an entity, and a [`TweenFinished`] event is sent through
+//! the [`EventChannels`](crate::channels::EventChannels) once it completes.
+//!
+//! Tweens are advanced by the [`TweenPlugin`].
+use std::f32::consts::PI;
+
+use flax::{
+    component::{ComponentKey, ComponentValue},
+    entity_ids, BoxedSystem, Component, Entity, Query, System, World,
+};
+use glam::{Quat, Vec2, Vec3, Vec4};
+use ivy_assets::AssetCache;
+
+use crate::{
+    components::{delta_time, engine, event_channels, unscaled_delta_time},
+    update_layer::{Plugin, ScheduleSetBuilder},
+    Color,
+};
+
+flax::component! {
+    /// Running tweens of an entity
+    pub tweens: Tweens,
+}
+
+/// Maps the linear progress of a tween to the interpolated progress
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Easing {
+    #[default]
+    Linear,
+    EaseInQuad,
+    EaseOutQuad,
+    EaseInOutQuad,
+    EaseInCubic,
+    EaseOutCubic,
+    EaseInOutCubic,
+    EaseInSine,
+    EaseOutSine,
+    EaseInOutSine,
+    /// Overshoots the target slightly before settling
+    EaseOutBack,
+    EaseOutElastic,
+    EaseOutBounce,
+}
+
+impl Easing {
+    pub fn apply(&self, t: f32) -> f32 {
+        let t = t.clamp(0.0, 1.0);
+        match self {
+            Easing::Linear => t,
+            Easing::EaseInQuad => t * t,
+            Easing::EaseOutQuad => 1.0 - (1.0 - t).powi(2),
+            Easing::EaseInOutQuad => {
+                if t < 0.5 {
+                    2.0 * t * t
+                } else {
+                    1.0 - (-2.0 * t + 2.0).powi(2) / 2.0
+                }
+            }
+            Easing::EaseInCubic => t * t * t,
+            Easing::EaseOutCubic => 1.0 - (1.0 - t).powi(3),
+            Easing::EaseInOutCubic => {
+                if t < 0.5 {
+                    4.0 * t * t * t
+                } else {
+                    1.0 - (-2.0 * t + 2.0).powi(3) / 2.0
+                }
+            }
+            Easing::EaseInSine => 1.0 - (t * PI / 2.0).cos(),
+            Easing::EaseOutSine => (t * PI / 2.0).sin(),
+            Easing::EaseInOutSine => -((t * PI).cos() - 1.0) / 2.0,
+            Easing::EaseOutBack => {
+                const C1: f32 = 1.70158;
+                const C3: f32 = C1 + 1.0;
+                1.0 + C3 * (t - 1.0).powi(3) + C1 * (t - 1.0).powi(2)
+            }
+            Easing::EaseOutElastic => {
+                if t == 0.0 || t == 1.0 {
+                    t
+                } else {
+                    2f32.powf(-10.0 * t) * ((t * 10.0 - 0.75) * (2.0 * PI / 3.0)).sin() + 1.0
+                }
+            }
+            Easing::EaseOutBounce => {
+                const N1: f32 = 7.5625;
+                const D1: f32 = 2.75;
+
+                if t < 1.0 / D1 {
+                    N1 * t * t
+                } else if t < 2.0 / D1 {
+                    let t = t - 1.5 / D1;
+                    N1 * t * t + 0.75
+                } else if t < 2.5 / D1 {
+                    let t = t - 2.25 / D1;
+                    N1 * t * t + 0.9375
+                } else {
+                    let t = t - 2.625 / D1;
+                    N1 * t * t + 0.984375
+                }
+            }
+        }
+    }
+}
+
+/// A value which can be interpolated by a [`Tween`]
+pub trait Tweenable: ComponentValue + Clone {
+    fn interpolate(&self, to: &Self, t: f32) -> Self;
+}
+
+impl Tweenable for f32 {
+    fn interpolate(&self, to: &Self, t: f32) -> Self {
+        self + (to - self) * t
+    }
+}
+
+impl Tweenable for Vec2 {
+    fn interpolate(&self, to: &Self, t: f32) -> Self {
+        self.lerp(*to, t)
+    }
+}
+
+impl Tweenable for Vec3 {
+    fn interpolate(&self, to: &Self, t: f32) -> Self {
+        self.lerp(*to, t)
+    }
+}
+
+impl Tweenable for Vec4 {
+    fn interpolate(&self, to: &Self, t: f32) -> Self {
+        self.lerp(*to, t)
+    }
+}
+
+impl Tweenable for Quat {
+    fn interpolate(&self, to: &Self, t: f32) -> Self {
+        self.slerp(*to, t)
+    }
+}
+
+impl Tweenable for Color {
+    fn interpolate(&self, to: &Self, t: f32) -> Self {
+        let lerp = |a: f32, b: f32| a + (b - a) * t;
+        Color::new(
+            lerp(self.red, to.red),
+            lerp(self.green, to.green),
+            lerp(self.blue, to.blue),
+            lerp(self.alpha, to.alpha),
+        )
+    }
+}
+
+/// How many times a [`Tween`] plays before it finishes
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum TweenRepeat {
+    #[default]
+    Once,
+    Count(u32),
+    Forever,
+}
+
+/// Interpolates a component from one value to another
+#[derive(Debug, Clone)]
+pub struct Tween<T> {
+    component: Component<T>,
+    from: T,
+    to: T,
+    duration: f32,
+    easing: Easing,
+    delay: f32,
+    repeat: TweenRepeat,
+    ping_pong: bool,
+    unscaled: bool,
+    label: Option<String>,
+    elapsed: f32,
+    completed: u32,
+    reversed: bool,
+}
+
+impl<T: Tweenable> Tween<T> {
+    /// Tween `component` from `from` to `to` over `duration` seconds
+    pub fn new(component: Component<T>, from: T, to: T, duration: f32, easing: Easing) -> Self {
+        Self {
+            component,
+            from,
+            to,
+            duration,
+            easing,
+            delay: 0.0,
+            repeat: TweenRepeat::Once,
+            ping_pong: false,
+            unscaled: false,
+            label: None,
+            elapsed: 0.0,
+            completed: 0,
+            reversed: false,
+        }
+    }
+
+    /// Wait for `delay` seconds before starting
+    pub fn with_delay(mut self, delay: f32) -> Self {
+        self.delay = delay;
+        self
+    }
+
+    pub fn with_repeat(mut self, repeat: TweenRepeat) -> Self {
+        self.repeat = repeat;
+        self
+    }
+
+    /// Play every other repetition backwards, rather than jumping back to the start
+    pub fn with_ping_pong(mut self, ping_pong: bool) -> Self {
+        self.ping_pong = ping_pong;
+        self
+    }
+
+    /// Advance using the wall clock time, which keeps the tween playing while the game is paused,
+    /// e.g; for menus
+    pub fn with_unscaled_time(mut self, unscaled: bool) -> Self {
+        self.unscaled = unscaled;
+        self
+    }
+
+    /// Set a label which is included in the [`TweenFinished`] event
+    pub fn with_label(mut self, label: impl Into<String>) -> Self {
+        self.label = Some(label.into());
+        self
+    }
+
+    /// Returns the current value
+    pub fn value(&self) -> T {
+        let t = if self.duration > 0.0 {
+            (self.elapsed / self.duration).clamp(0.0, 1.0)
+        } else {
+            1.0
+        };
+
+        let t = if self.reversed { 1.0 - t } else { t };
+        self.from.interpolate(&self.to, self.easing.apply(t))
+    }
+
+    /// Advances the tween, returning false while the delay has not passed
+    fn advance(&mut self, mut dt: f32) -> bool {
+        if self.delay > 0.0 {
+            self.delay -= dt;
+            if self.delay > 0.0 {
+                return false;
+            }
+
+            dt = -self.delay;
+            self.delay = 0.0;
+        }
+
+        self.elapsed += dt;
+        while !self.is_finished() && self.elapsed >= self.duration {
+            self.completed += 1;
+            if self.is_finished() || self.duration <= 0.0 {
+                self.elapsed = self.duration;
+                self.completed = self.completed.max(self.repeat_count());
+                break;
+            }
+
+            self.elapsed -= self.duration;
+            if self.ping_pong {
+                self.reversed = !self.reversed;
+            }
+        }
+
+        true
+    }
+
+    fn repeat_count(&self) -> u32 {
+        match self.repeat {
+            TweenRepeat::Once => 1,
+            TweenRepeat::Count(count) => count,
+            TweenRepeat::Forever => u32::MAX,
+        }
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.completed >= self.repeat_count()
+    }
+}
+
+trait TweenDyn: Send + Sync {
+    fn key(&self) -> ComponentKey;
+    fn label(&self) -> Option<&str>;
+    fn is_finished(&self) -> bool;
+    /// Advances the tween and writes the value to the entity
+    fn update(&mut self, world: &World, id: Entity, dt: f32, unscaled_dt: f32);
+}
+
+impl<T: Tweenable> TweenDyn for Tween<T> {
+    fn key(&self) -> ComponentKey {
+        self.component.key()
+    }
+
+    fn label(&self) -> Option<&str> {
+        self.label.as_deref()
+    }
+
+    fn is_finished(&self) -> bool {
+        Tween::is_finished(self)
+    }
+
+    fn update(&mut self, world: &World, id: Entity, dt: f32, unscaled_dt: f32) {
+        if !self.advance(if self.unscaled { unscaled_dt } else { dt }) {
+            return;
+        }
+
+        match world.get_mut(id, self.component) {
+            Ok(mut value) => *value = self.value(),
+            Err(_) => {
+                tracing::warn!(%id, component = ?self.component, "Tweened component is missing");
+                self.completed = self.repeat_count();
+            }
+        }
+    }
+}
+
+/// Tweens running on an entity.
+///
+/// Each component is animated by at most one tween at a time.
+#[derive(Default)]
+pub struct Tweens {
+    tweens: Vec<Box<dyn TweenDyn>>,
+}
+
+impl Tweens {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_tween<T: Tweenable>(mut self, tween: Tween<T>) -> Self {
+        self.push(tween);
+        self
+    }
+
+    /// Starts a tween, replacing any running tween of the same component
+    pub fn push<T: Tweenable>(&mut self, tween: Tween<T>) {
+        self.cancel(tween.component);
+        self.tweens.push(Box::new(tween));
+    }
+
+    /// Stops the tween of `component`, leaving the value where it is
+    pub fn cancel<T: ComponentValue>(&mut self, component: Component<T>) {
+        self.tweens.retain(|v| v.key() != component.key());
+    }
+
+    pub fn is_tweening<T: ComponentValue>(&self, component: Component<T>) -> bool {
+        self.tweens.iter().any(|v| v.key() == component.key())
+    }
+
+    pub fn len(&self) -> usize {
+        self.tweens.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tweens.is_empty()
+    }
+}
+
+impl std::fmt::Debug for Tweens {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Tweens")
+            .field("len", &self.tweens.len())
+            .finish()
+    }
+}
+
+/// Sent through the [`EventChannels`](crate::channels::EventChannels) when a tween completes
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TweenFinished {
+    pub id: Entity,
+    pub label: Option<String>,
+}
+
+/// Advances the [`tweens`] of all entities each tick
+pub struct TweenPlugin;
+
+impl Plugin for TweenPlugin {
+    fn install(
+        &self,
+        _: &mut World,
+        _: &AssetCache,
+        schedules: &mut ScheduleSetBuilder,
+    ) -> anyhow::Result<()> {
+        schedules.per_tick_mut().with_system(tween_system());
+
+        Ok(())
+    }
+}
+
+/// Advances the [`tweens`] of all entities
+pub fn tween_system() -> BoxedSystem {
+    let mut query = Query::new((entity_ids(), tweens().as_mut()));
+    let mut finished = Vec::new();
+
+    System::builder()
+        .with_world()
+        .build(move |world: &World| {
+            let dt = world
+                .get(engine(), delta_time())
+                .map(|v| v.as_secs_f32())
+                .unwrap_or_default();
+            let unscaled_dt = world
+                .get(engine(), unscaled_delta_time())
+                .map(|v| v.as_secs_f32())
+                .unwrap_or(dt);
+
+            for (id, tweens) in &mut query.borrow(world) {
+                for tween in &mut tweens.tweens {
+                    tween.update(world, id, dt, unscaled_dt);
+                }
+
+                tweens.tweens.retain(|tween| {
+                    if tween.is_finished() {
+                        finished.push(TweenFinished {
+                            id,
+                            label: tween.label().map(ToOwned::to_owned),
+                        });
+                    }
+
+                    !tween.is_finished()
+                });
+            }
+
+            if finished.is_empty() {
+                return;
+            }
+
+            if let Ok(mut channels) = world.get_mut(engine(), event_channels()) {
+                for event in finished.drain(..) {
+                    channels.send(event);
+                }
+            } else {
+                finished.clear();
+            }
+        })
+        .boxed()
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::{
+        channels::{EventChannels, EventReader},
+        components::{fixed_frame_delta, position, time},
+        time::Time,
+        update_layer::{FixedTimeStep, ScheduledLayer},
+    };
+
+    #[test]
+    fn tween_plugin() {
+        let mut world = World::new();
+        let assets = AssetCache::new();
+
+        world
+            .set(engine(), fixed_frame_delta(), Duration::from_millis(100))
+            .unwrap();
+
+        let scaled = Entity::builder()
+            .set(position(), Vec3::ZERO)
+            .set(
+                tweens(),
+                Tweens::new().with_tween(Tween::new(
+                    position(),
+                    Vec3::ZERO,
+                    Vec3::X,
+                    1.0,
+                    Easing::Linear,
+                )),
+            )
+            .spawn(&mut world);
+
+        let unscaled = Entity::builder()
+            .set(position(), Vec3::ZERO)
+            .set(
+                tweens(),
+                Tweens::new().with_tween(
+                    Tween::new(position(), Vec3::ZERO, Vec3::X, 1.0, Easing::Linear)
+                        .with_unscaled_time(true),
+                ),
+            )
+            .spawn(&mut world);
+
+        let mut layer = ScheduledLayer::new(FixedTimeStep::new(0.02)).with_plugin(TweenPlugin);
+        layer.register(&mut world, &assets).unwrap();
+
+        layer.tick(&mut world).unwrap();
+
+        let position_of = |id| world.get_copy(id, position()).unwrap();
+        assert!(position_of(scaled).abs_diff_eq(Vec3::X * 0.1, 1e-5));
+        assert!(position_of(unscaled).abs_diff_eq(Vec3::X * 0.1, 1e-5));
+
+        // Slow motion only affects scaled tweens
+        let mut controls = Time::new();
+        controls.set_scale(0.5);
+        world.set(engine(), time(), controls).unwrap();
+
+        layer.tick(&mut world).unwrap();
+
+        let position_of = |id| world.get_copy(id, position()).unwrap();
+        assert!(position_of(scaled).abs_diff_eq(Vec3::X * 0.15, 1e-5));
+        assert!(position_of(unscaled).abs_diff_eq(Vec3::X * 0.2, 1e-5));
+    }
+
+    #[test]
+    fn finished_events() {
+        let mut world = World::new();
+        let assets = AssetCache::new();
+
+        world
+            .set(engine(), fixed_frame_delta(), Duration::from_millis(100))
+            .unwrap();
+        world
+            .set(engine(), event_channels(), EventChannels::new())
+            .unwrap();
+
+        let id = Entity::builder()
+            .set(position(), Vec3::ZERO)
+            .set(
+                tweens(),
+                Tweens::new().with_tween(
+                    Tween::new(position(), Vec3::ZERO, Vec3::X, 0.05, Easing::Linear)
+                        .with_label("slide"),
+                ),
+            )
+            .spawn(&mut world);
+
+        let mut layer = ScheduledLayer::new(FixedTimeStep::new(0.02)).with_plugin(TweenPlugin);
+        layer.register(&mut world, &assets).unwrap();
+
+        let mut reader = EventReader::<TweenFinished>::new();
+        layer.tick(&mut world).unwrap();
+
+        let channels = world.get(engine(), event_channels()).unwrap();
+        assert_eq!(
+            channels.read(&mut reader).cloned().collect::<Vec<_>>(),
+            [TweenFinished {
+                id,
+                label: Some("slide".into()),
+            }]
+        );
+        assert_eq!(channels.read(&mut reader).count(), 0);
+    }
+
+    #[test]
+    fn easing_endpoints() {
+        let easings = [
+            Easing::Linear,
+            Easing::EaseInQuad,
+            Easing::EaseOutQuad,
+            Easing::EaseInOutQuad,
+            Easing::EaseInCubic,
+            Easing::EaseOutCubic,
+            Easing::EaseInOutCubic,
+            Easing::EaseInSine,
+            Easing::EaseOutSine,
+            Easing::EaseInOutSine,
+            Easing::EaseOutBack,
+            Easing::EaseOutElastic,
+            Easing::EaseOutBounce,
+        ];
+
+        for easing in easings {
+            assert!(easing.apply(0.0).abs() < 1e-5, "{easing:?}");
+            assert!((easing.apply(1.0) - 1.0).abs() < 1e-5, "{easing:?}");
+        }
+    }
+
+    #[test]
+    fn ping_pong() {
+        let mut tween = Tween::new(position(), Vec3::ZERO, Vec3::X, 1.0, Easing::Linear)
+            .with_delay(0.5)
+            .with_repeat(TweenRepeat::Count(2))
+            .with_ping_pong(true);
+
+        assert!(!tween.advance(0.25));
+        assert!(tween.advance(0.5));
+        assert!(tween.value().abs_diff_eq(Vec3::X * 0.25, 1e-5));
+
+        tween.advance(1.0);
+        assert!(tween.value().abs_diff_eq(Vec3::X * 0.75, 1e-5));
+        assert!(!tween.is_finished());
+
+        tween.advance(1.0);
+        assert!(tween.is_finished());
+        assert_eq!(tween.value(), Vec3::ZERO);
+    }
+}
@@ -9,9 +9,13 @@ use flax::{Schedule, ScheduleBuilder, World};
 use ivy_assets::AssetCache;
 
 use crate::{
-    app::{PostInitEvent, TickEvent},
-    components::{delta_time, elapsed_time, engine},
+    app::{in_states, AppState, LayerGate, PostInitEvent, TickEvent},
+    components::{
+        app_state, delta_time, elapsed_time, engine, fixed_frame_delta, scope_timings, time,
+        unscaled_delta_time,
+    },
     layer::events::EventRegisterContext,
+    time::Time,
     Layer,
 };
 
@@ -43,8 +47,21 @@ impl<U: Plugin> Plugin for Box<U> {
     }
 }
 
+/// Returns the fixed frame delta if the engine is stepping at a fixed rate
+fn frame_delta(world: &World) -> Option<Duration> {
+    world.get(engine(), fixed_frame_delta()).ok().map(|v| *v)
+}
+
+fn time_controls(world: &World) -> Time {
+    world.get(engine(), time()).map(|v| *v).unwrap_or_default()
+}
+
 pub trait TimeStep: 'static + Display + Copy {
     fn step(&mut self, world: &mut World, schedule: &mut Schedule) -> anyhow::Result<()>;
+
+    /// Called instead of [`Self::step`] while the schedule is disabled, so that the time which
+    /// passes meanwhile is not caught up on afterwards
+    fn skip(&mut self) {}
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -56,18 +73,26 @@ pub struct PerTick {
 impl TimeStep for PerTick {
     fn step(&mut self, world: &mut World, schedule: &mut Schedule) -> anyhow::Result<()> {
         let new_time = Instant::now();
-        let dt = new_time.duration_since(self.current_time);
+        let unscaled_dt =
+            frame_delta(world).unwrap_or_else(|| new_time.duration_since(self.current_time));
+        let dt = time_controls(world).scaled(unscaled_dt);
 
         self.current_time = new_time;
         self.elapsed += dt;
 
         world.set(engine(), delta_time(), dt)?;
+        world.set(engine(), unscaled_delta_time(), unscaled_dt)?;
         world.set(engine(), elapsed_time(), self.elapsed)?;
         schedule.execute_seq(world)?;
         world.set(engine(), delta_time(), Duration::ZERO)?;
+        world.set(engine(), unscaled_delta_time(), Duration::ZERO)?;
 
         Ok(())
     }
+
+    fn skip(&mut self) {
+        self.current_time = Instant::now();
+    }
 }
 
 impl Display for PerTick {
@@ -82,6 +107,7 @@ pub struct Startup;
 impl TimeStep for Startup {
     fn step(&mut self, world: &mut World, schedule: &mut Schedule) -> anyhow::Result<()> {
         world.set(engine(), delta_time(), Duration::ZERO)?;
+        world.set(engine(), unscaled_delta_time(), Duration::ZERO)?;
         world.set(engine(), elapsed_time(), Duration::ZERO)?;
         schedule.execute_seq(world)?;
 
@@ -122,7 +148,9 @@ impl TimeStep for FixedTimeStep {
     fn step(&mut self, world: &mut World, schedule: &mut Schedule) -> anyhow::Result<()> {
         let now = Instant::now();
 
-        let elapsed = now.duration_since(self.current_time);
+        let time = time_controls(world);
+        let elapsed = frame_delta(world).unwrap_or_else(|| now.duration_since(self.current_time));
+        let elapsed = time.scaled(elapsed);
         self.current_time = now;
 
         self.acc += elapsed.as_secs_f64();
@@ -133,6 +161,14 @@ impl TimeStep for FixedTimeStep {
             Duration::from_secs_f64(self.delta_time),
         )?;
 
+        // Wall clock time covered by a step, which is longer than the step in slow motion. Steps
+        // only execute while time passes, so the scale is never zero here.
+        world.set(
+            engine(),
+            unscaled_delta_time(),
+            Duration::from_secs_f64(self.delta_time / time.scale().max(f64::EPSILON)),
+        )?;
+
         if self.acc > self.delta_time {
             world.set(engine(), elapsed_time(), self.elapsed)?;
             // while self.acc > self.delta_time {
@@ -143,9 +179,14 @@ impl TimeStep for FixedTimeStep {
         }
 
         world.set(engine(), delta_time(), Duration::ZERO)?;
+        world.set(engine(), unscaled_delta_time(), Duration::ZERO)?;
 
         Ok(())
     }
+
+    fn skip(&mut self) {
+        self.current_time = Instant::now();
+    }
 }
 
 impl Display for FixedTimeStep {
@@ -165,6 +206,10 @@ impl<T: TimeStep> TimeStepSchedule<T> {
     fn step(&mut self, world: &mut World) -> anyhow::Result<()> {
         self.time_step.step(world, &mut self.schedule)
     }
+
+    fn skip(&mut self) {
+        self.time_step.skip()
+    }
 }
 
 pub struct TimeStepScheduleBuilder<T> {
@@ -263,6 +308,46 @@ impl ScheduleSet {
     pub fn startup_mut(&mut self) -> &mut Option<TimeStepSchedule<Startup>> {
         &mut self.startup
     }
+
+    fn step(&mut self, world: &mut World) -> anyhow::Result<()> {
+        if let Some(mut startup) = self.startup.take() {
+            startup
+                .step(world)
+                .context("Failed to execute startup schedule")?;
+        }
+
+        let timings = world.get(engine(), scope_timings()).ok().map(|v| v.clone());
+        let scope = |name| timings.as_ref().map(|v| v.scope(name));
+
+        let fixed_scope = scope("schedule.fixed");
+        self.fixed_timestep.step(world).with_context(|| {
+            format!(
+                "Failed to execute schedule {}",
+                self.fixed_timestep.time_step
+            )
+        })?;
+
+        drop(fixed_scope);
+
+        let _scope = scope("schedule.per_tick");
+        self.per_tick
+            .step(world)
+            .with_context(|| format!("Failed to execute schedule {}", self.per_tick.time_step))?;
+
+        Ok(())
+    }
+
+    fn skip(&mut self) {
+        self.fixed_timestep.skip();
+        self.per_tick.skip();
+    }
+}
+
+/// Plugin which only executes while the app is in specific states
+struct GatedPlugin {
+    gate: LayerGate,
+    plugin: Box<dyn Plugin>,
+    schedules: Option<ScheduleSet>,
 }
 
 /// Executes a schedule using the provided time step
@@ -270,6 +355,7 @@ pub struct ScheduledLayer {
     builder: ScheduleSetBuilder,
     schedules: Option<ScheduleSet>,
     plugins: Vec<Box<dyn Plugin>>,
+    gated_plugins: Vec<GatedPlugin>,
 }
 
 impl ScheduledLayer {
@@ -278,6 +364,7 @@ impl ScheduledLayer {
             builder: ScheduleSetBuilder::new(fixed_timestep),
             schedules: None,
             plugins: Vec::new(),
+            gated_plugins: Vec::new(),
         }
     }
 
@@ -286,6 +373,24 @@ impl ScheduledLayer {
         self
     }
 
+    /// Adds a plugin whose systems only execute while the app is in one of `states`, e.g; to
+    /// stop gameplay while paused.
+    ///
+    /// No time passes for the systems of the plugin while they are disabled. The systems execute
+    /// after those of the other plugins.
+    pub fn with_plugin_in_states<S: AppState>(
+        mut self,
+        states: impl IntoIterator<Item = S>,
+        plugin: impl 'static + Plugin,
+    ) -> Self {
+        self.gated_plugins.push(GatedPlugin {
+            gate: in_states(states.into_iter().collect()),
+            plugin: Box::new(plugin),
+            schedules: None,
+        });
+        self
+    }
+
     pub fn register(&mut self, world: &mut World, assets: &AssetCache) -> anyhow::Result<()> {
         assert!(self.schedules.is_none());
 
@@ -295,6 +400,13 @@ impl ScheduledLayer {
 
         self.schedules = Some(self.builder.build());
 
+        let fixed_timestep = *self.builder.fixed_mut().time_step();
+        for gated in &mut self.gated_plugins {
+            let mut builder = ScheduleSetBuilder::new(fixed_timestep);
+            gated.plugin.install(world, assets, &mut builder)?;
+            gated.schedules = Some(builder.build());
+        }
+
         Ok(())
     }
 
@@ -303,25 +415,28 @@ impl ScheduledLayer {
             return Ok(());
         };
 
-        if let Some(mut startup) = schedules.startup.take() {
-            startup
-                .step(world)
-                .context("Failed to execute startup schedule")?;
-        }
+        schedules.step(world)?;
 
-        schedules.fixed_timestep.step(world).with_context(|| {
-            format!(
-                "Failed to execute schedule {}",
-                schedules.fixed_timestep.time_step
-            )
-        })?;
+        if self.gated_plugins.is_empty() {
+            return Ok(());
+        }
 
-        schedules.per_tick.step(world).with_context(|| {
-            format!(
-                "Failed to execute schedule {}",
-                schedules.per_tick.time_step
-            )
-        })?;
+        let state = world
+            .get(engine(), app_state())
+            .map(|v| v.clone())
+            .unwrap_or_default();
+
+        for gated in &mut self.gated_plugins {
+            let Some(schedules) = &mut gated.schedules else {
+                continue;
+            };
+
+            if (gated.gate)(state.as_any()) {
+                schedules.step(world)?;
+            } else {
+                schedules.skip();
+            }
+        }
 
         Ok(())
     }
@@ -338,8 +453,157 @@ impl Layer for ScheduledLayer {
         Self: Sized,
     {
         events.subscribe(|this, ctx, _: &PostInitEvent| this.register(ctx.world, ctx.assets));
-        events.subscribe(|this, ctx, _: &TickEvent| this.tick(ctx.world));
+        events.subscribe(|this, ctx, _: &TickEvent| {
+            // Layers restricted to states other than the initial state miss the `PostInitEvent`
+            if this.schedules.is_none() {
+                this.register(ctx.world, ctx.assets)?;
+            }
+
+            this.tick(ctx.world)
+        });
 
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod test {
+    use std::sync::{Arc, Mutex};
+
+    use flax::{BoxedSystem, System};
+
+    use super::*;
+    use crate::app::CurrentState;
+
+    type Record = Arc<Mutex<Vec<(f64, f64)>>>;
+
+    fn record_system(record: Record) -> BoxedSystem {
+        System::builder()
+            .with_world()
+            .build(move |world: &World| {
+                let dt = world.get_copy(engine(), delta_time()).unwrap();
+                let unscaled_dt = world.get_copy(engine(), unscaled_delta_time()).unwrap();
+
+                record
+                    .lock()
+                    .unwrap()
+                    .push((dt.as_secs_f64(), unscaled_dt.as_secs_f64()));
+            })
+            .boxed()
+    }
+
+    fn assert_times(record: &Record, expected: &[(f64, f64)]) {
+        let record = std::mem::take(&mut *record.lock().unwrap());
+        assert_eq!(record.len(), expected.len(), "{record:?}");
+
+        for (&(dt, unscaled), &(expected_dt, expected_unscaled)) in record.iter().zip(expected) {
+            assert!((dt - expected_dt).abs() < 1e-6, "{record:?}");
+            assert!((unscaled - expected_unscaled).abs() < 1e-6, "{record:?}");
+        }
+    }
+
+    #[test]
+    fn time_steps() {
+        let mut world = World::new();
+
+        let mut controls = Time::new();
+        controls.set_scale(0.5);
+        world.set(engine(), time(), controls).unwrap();
+        world
+            .set(engine(), fixed_frame_delta(), Duration::from_millis(40))
+            .unwrap();
+
+        let record = Record::default();
+        let mut schedule = Schedule::builder()
+            .with_system(record_system(record.clone()))
+            .build();
+
+        let mut per_tick = PerTick {
+            current_time: Instant::now(),
+            elapsed: Duration::ZERO,
+        };
+
+        per_tick.step(&mut world, &mut schedule).unwrap();
+        assert_times(&record, &[(0.02, 0.04)]);
+
+        // Systems outside a time step see no time passing
+        assert_eq!(
+            world.get_copy(engine(), delta_time()).unwrap(),
+            Duration::ZERO
+        );
+        assert_eq!(
+            world.get_copy(engine(), unscaled_delta_time()).unwrap(),
+            Duration::ZERO
+        );
+
+        let mut fixed = FixedTimeStep::new(0.01);
+        fixed.step(&mut world, &mut schedule).unwrap();
+        assert_times(&record, &[(0.01, 0.02)]);
+
+        assert_eq!(
+            world.get_copy(engine(), unscaled_delta_time()).unwrap(),
+            Duration::ZERO
+        );
+    }
+
+    struct RecordPlugin(Record);
+
+    impl Plugin for RecordPlugin {
+        fn install(
+            &self,
+            _: &mut World,
+            _: &AssetCache,
+            schedules: &mut ScheduleSetBuilder,
+        ) -> anyhow::Result<()> {
+            schedules
+                .per_tick_mut()
+                .with_system(record_system(self.0.clone()));
+            Ok(())
+        }
+    }
+
+    #[derive(Debug, Clone, PartialEq)]
+    enum GameState {
+        Playing,
+        Paused,
+    }
+
+    #[test]
+    fn plugin_in_states() {
+        let mut world = World::new();
+        let assets = AssetCache::new();
+
+        world.set(engine(), time(), Time::new()).unwrap();
+        world
+            .set(engine(), fixed_frame_delta(), Duration::from_millis(100))
+            .unwrap();
+
+        let record = Record::default();
+        let mut layer = ScheduledLayer::new(FixedTimeStep::new(0.02))
+            .with_plugin_in_states([GameState::Playing], RecordPlugin(record.clone()));
+        layer.register(&mut world, &assets).unwrap();
+
+        let set_state = |world: &mut World, state: GameState| {
+            world
+                .set(engine(), app_state(), CurrentState::new(Arc::new(state)))
+                .unwrap();
+        };
+
+        // No state yet
+        layer.tick(&mut world).unwrap();
+        assert_times(&record, &[]);
+
+        set_state(&mut world, GameState::Playing);
+        layer.tick(&mut world).unwrap();
+        assert_times(&record, &[(0.1, 0.1)]);
+
+        set_state(&mut world, GameState::Paused);
+        layer.tick(&mut world).unwrap();
+        layer.tick(&mut world).unwrap();
+        assert_times(&record, &[]);
+
+        set_state(&mut world, GameState::Playing);
+        layer.tick(&mut world).unwrap();
+        assert_times(&record, &[(0.1, 0.1)]);
+    }
+}
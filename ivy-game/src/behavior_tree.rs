@@ -0,0 +1,395 @@
+use flax::{entity_ids, BoxedSystem, Entity, Query, System, World};
+use ivy_assets::AssetCache;
+use ivy_core::{
+    components::{delta_time, engine},
+    update_layer::{Plugin, ScheduleSetBuilder},
+};
+
+flax::component! {
+    pub behavior_tree: BehaviorTree,
+}
+
+/// Result of ticking a [`Node`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Status {
+    Success,
+    Failure,
+    /// The node has not finished, and will continue from where it left off in the next tick
+    Running,
+}
+
+impl From<bool> for Status {
+    fn from(value: bool) -> Self {
+        if value {
+            Status::Success
+        } else {
+            Status::Failure
+        }
+    }
+}
+
+pub struct BehaviorContext<'a> {
+    pub world: &'a mut World,
+    /// The entity the tree belongs to
+    pub id: Entity,
+    /// Time since the last tick in seconds
+    pub dt: f32,
+}
+
+pub trait Node: Send + Sync {
+    fn tick(&mut self, ctx: &mut BehaviorContext) -> Status;
+
+    /// Restarts the node, such as when a parent node is interrupted
+    fn reset(&mut self) {}
+}
+
+pub type BoxedNode = Box<dyn Node>;
+
+/// Runs the children in order until one fails
+pub struct Sequence {
+    children: Vec<BoxedNode>,
+    current: usize,
+}
+
+/// Runs the children in order until one succeeds
+pub struct Selector {
+    children: Vec<BoxedNode>,
+    current: usize,
+}
+
+impl Node for Sequence {
+    fn tick(&mut self, ctx: &mut BehaviorContext) -> Status {
+        tick_composite(&mut self.children, &mut self.current, ctx, Status::Success)
+    }
+
+    fn reset(&mut self) {
+        self.current = 0;
+        self.children.iter_mut().for_each(|v| v.reset());
+    }
+}
+
+impl Node for Selector {
+    fn tick(&mut self, ctx: &mut BehaviorContext) -> Status {
+        tick_composite(&mut self.children, &mut self.current, ctx, Status::Failure)
+    }
+
+    fn reset(&mut self) {
+        self.current = 0;
+        self.children.iter_mut().for_each(|v| v.reset());
+    }
+}
+
+/// Ticks the children from `current` while they return `next`
+fn tick_composite(
+    children: &mut [BoxedNode],
+    current: &mut usize,
+    ctx: &mut BehaviorContext,
+    next: Status,
+) -> Status {
+    while let Some(child) = children.get_mut(*current) {
+        match child.tick(ctx) {
+            Status::Running => return Status::Running,
+            status if status == next => *current += 1,
+            status => {
+                children.iter_mut().for_each(|v| v.reset());
+                *current = 0;
+                return status;
+            }
+        }
+    }
+
+    children.iter_mut().for_each(|v| v.reset());
+    *current = 0;
+    next
+}
+
+/// Swaps success and failure
+pub struct Invert(BoxedNode);
+
+impl Node for Invert {
+    fn tick(&mut self, ctx: &mut BehaviorContext) -> Status {
+        match self.0.tick(ctx) {
+            Status::Success => Status::Failure,
+            Status::Failure => Status::Success,
+            Status::Running => Status::Running,
+        }
+    }
+
+    fn reset(&mut self) {
+        self.0.reset()
+    }
+}
+
+/// Restarts the child when it finishes, until it fails or has succeeded `count` times
+pub struct Repeat {
+    child: BoxedNode,
+    count: Option<usize>,
+    completed: usize,
+}
+
+impl Node for Repeat {
+    fn tick(&mut self, ctx: &mut BehaviorContext) -> Status {
+        match self.child.tick(ctx) {
+            Status::Success => {
+                self.child.reset();
+                self.completed += 1;
+                if self.count.is_some_and(|v| self.completed >= v) {
+                    self.completed = 0;
+                    Status::Success
+                } else {
+                    Status::Running
+                }
+            }
+            Status::Failure => {
+                self.completed = 0;
+                Status::Failure
+            }
+            Status::Running => Status::Running,
+        }
+    }
+
+    fn reset(&mut self) {
+        self.completed = 0;
+        self.child.reset();
+    }
+}
+
+/// Runs for a duration, then succeeds
+pub struct Wait {
+    duration: f32,
+    elapsed: f32,
+}
+
+impl Node for Wait {
+    fn tick(&mut self, ctx: &mut BehaviorContext) -> Status {
+        self.elapsed += ctx.dt;
+        if self.elapsed >= self.duration {
+            self.elapsed = 0.0;
+            Status::Success
+        } else {
+            Status::Running
+        }
+    }
+
+    fn reset(&mut self) {
+        self.elapsed = 0.0;
+    }
+}
+
+/// Calls a function each tick
+pub struct Action<F>(F);
+
+impl<F> Node for Action<F>
+where
+    F: FnMut(&mut BehaviorContext) -> Status + Send + Sync,
+{
+    fn tick(&mut self, ctx: &mut BehaviorContext) -> Status {
+        (self.0)(ctx)
+    }
+}
+
+pub fn sequence(children: impl IntoIterator<Item = BoxedNode>) -> BoxedNode {
+    Box::new(Sequence {
+        children: children.into_iter().collect(),
+        current: 0,
+    })
+}
+
+pub fn selector(children: impl IntoIterator<Item = BoxedNode>) -> BoxedNode {
+    Box::new(Selector {
+        children: children.into_iter().collect(),
+        current: 0,
+    })
+}
+
+pub fn invert(child: BoxedNode) -> BoxedNode {
+    Box::new(Invert(child))
+}
+
+/// Repeats the child forever, or until it fails
+pub fn repeat(child: BoxedNode) -> BoxedNode {
+    Box::new(Repeat {
+        child,
+        count: None,
+        completed: 0,
+    })
+}
+
+pub fn repeat_n(child: BoxedNode, count: usize) -> BoxedNode {
+    Box::new(Repeat {
+        child,
+        count: Some(count),
+        completed: 0,
+    })
+}
+
+/// Waits for the given number of seconds
+pub fn wait(duration: f32) -> BoxedNode {
+    Box::new(Wait {
+        duration,
+        elapsed: 0.0,
+    })
+}
+
+pub fn action(f: impl FnMut(&mut BehaviorContext) -> Status + Send + Sync + 'static) -> BoxedNode {
+    Box::new(Action(f))
+}
+
+/// Succeeds if the predicate holds, and fails otherwise
+pub fn condition(
+    mut f: impl FnMut(&mut BehaviorContext) -> bool + Send + Sync + 'static,
+) -> BoxedNode {
+    action(move |ctx| f(ctx).into())
+}
+
+/// Runs a behavior tree on an entity each tick.
+///
+/// The root is restarted when it finishes.
+pub struct BehaviorTree {
+    root: Option<BoxedNode>,
+    status: Status,
+}
+
+impl BehaviorTree {
+    pub fn new(root: BoxedNode) -> Self {
+        Self {
+            root: Some(root),
+            status: Status::Running,
+        }
+    }
+
+    /// Returns the status of the last tick
+    pub fn status(&self) -> Status {
+        self.status
+    }
+
+    pub fn reset(&mut self) {
+        if let Some(root) = &mut self.root {
+            root.reset();
+        }
+    }
+}
+
+impl std::fmt::Debug for BehaviorTree {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("BehaviorTree")
+            .field("status", &self.status)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Ticks the [`BehaviorTree`]s
+pub struct BehaviorTreePlugin;
+
+impl Plugin for BehaviorTreePlugin {
+    fn install(
+        &self,
+        _: &mut World,
+        _: &AssetCache,
+        schedules: &mut ScheduleSetBuilder,
+    ) -> anyhow::Result<()> {
+        schedules.per_tick_mut().with_system(behavior_tree_system());
+        Ok(())
+    }
+}
+
+fn behavior_tree_system() -> BoxedSystem {
+    let mut ids = Vec::new();
+
+    System::builder()
+        .with_world_mut()
+        .build(move |world: &mut World| {
+            let dt = world
+                .get(engine(), delta_time())
+                .map(|v| v.as_secs_f32())
+                .unwrap_or_default();
+
+            ids.clear();
+            ids.extend(
+                Query::new(entity_ids())
+                    .with(behavior_tree())
+                    .borrow(world)
+                    .iter(),
+            );
+
+            for &id in &ids {
+                // The tree is taken out during the tick to give the nodes access to the world
+                let Some(mut root) = world
+                    .get_mut(id, behavior_tree())
+                    .ok()
+                    .and_then(|mut v| v.root.take())
+                else {
+                    continue;
+                };
+
+                let status = root.tick(&mut BehaviorContext {
+                    world: &mut *world,
+                    id,
+                    dt,
+                });
+
+                if let Ok(mut tree) = world.get_mut(id, behavior_tree()) {
+                    tree.root = Some(root);
+                    tree.status = status;
+                }
+            }
+        })
+        .boxed()
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    };
+
+    use super::*;
+
+    fn tick(node: &mut BoxedNode, world: &mut World) -> Status {
+        let id = Entity::builder().spawn(world);
+        node.tick(&mut BehaviorContext { world, id, dt: 0.5 })
+    }
+
+    #[test]
+    fn sequence_and_selector() {
+        let mut world = World::new();
+        let counter = Arc::new(AtomicUsize::new(0));
+
+        let count = |counter: &Arc<AtomicUsize>| {
+            let counter = counter.clone();
+            action(move |_| {
+                counter.fetch_add(1, Ordering::Relaxed);
+                Status::Success
+            })
+        };
+
+        let mut tree = sequence([count(&counter), wait(1.0), count(&counter)]);
+
+        assert_eq!(tick(&mut tree, &mut world), Status::Running);
+        assert_eq!(counter.load(Ordering::Relaxed), 1);
+        assert_eq!(tick(&mut tree, &mut world), Status::Success);
+        assert_eq!(counter.load(Ordering::Relaxed), 2);
+
+        let mut tree = sequence([condition(|_| false), count(&counter)]);
+        assert_eq!(tick(&mut tree, &mut world), Status::Failure);
+        assert_eq!(counter.load(Ordering::Relaxed), 2);
+
+        let mut tree = selector([condition(|_| false), count(&counter), count(&counter)]);
+        assert_eq!(tick(&mut tree, &mut world), Status::Success);
+        assert_eq!(counter.load(Ordering::Relaxed), 3);
+    }
+
+    #[test]
+    fn repeat_count() {
+        let mut world = World::new();
+        let mut tree = repeat_n(invert(condition(|_| false)), 3);
+
+        assert_eq!(tick(&mut tree, &mut world), Status::Running);
+        assert_eq!(tick(&mut tree, &mut world), Status::Running);
+        assert_eq!(tick(&mut tree, &mut world), Status::Success);
+
+        let mut tree = repeat(condition(|_| false));
+        assert_eq!(tick(&mut tree, &mut world), Status::Failure);
+    }
+}
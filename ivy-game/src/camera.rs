@@ -0,0 +1,537 @@
+use flax::{BoxedSystem, Entity, FetchExt, Query, System, World};
+use glam::{vec3, EulerRot, Mat4, Quat, Vec2, Vec3};
+use ivy_assets::AssetCache;
+use ivy_core::{
+    components::{
+        delta_time, engine, main_camera, position, rotation, world_transform, TransformBundle,
+    },
+    update_layer::{Plugin, ScheduleSetBuilder},
+};
+use ivy_input::{
+    components::input_state, types::MouseButton, Action, Axis2D, BindingExt, CursorMoveBinding,
+    InputState, MouseButtonBinding, ScrollBinding,
+};
+use ivy_physics::{
+    components::{physics_state, rb_handle},
+    rapier3d::{parry::shape::Ball, prelude::QueryFilter},
+};
+use ivy_wgpu::{
+    camera_target::view_aspect,
+    components::{
+        main_window, player_view, projection_matrix, split_screen_viewports, window_size,
+    },
+};
+
+flax::component! {
+    pub perspective_camera: PerspectiveCamera,
+    pub orbit_camera: OrbitCamera,
+    pub third_person_camera: ThirdPersonCamera,
+    /// The entity followed by an orbit or third-person camera
+    pub follow_target: Entity,
+
+    pub look_input: Vec2,
+    pub look_active: bool,
+    pub zoom_input: f32,
+}
+
+/// Limits the pitch to avoid flipping over the poles
+const MAX_PITCH: f32 = 1.5;
+
+/// Perspective projection which follows the aspect ratio of the main window
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PerspectiveCamera {
+    /// Vertical field of view in radians
+    pub fov: f32,
+    pub near: f32,
+    pub far: f32,
+}
+
+impl PerspectiveCamera {
+    pub fn new(fov: f32) -> Self {
+        Self {
+            fov,
+            ..Default::default()
+        }
+    }
+
+    /// Set the near and far planes
+    pub fn with_clip_planes(mut self, near: f32, far: f32) -> Self {
+        self.near = near;
+        self.far = far;
+        self
+    }
+
+    pub fn projection(&self, aspect: f32) -> Mat4 {
+        Mat4::perspective_rh(self.fov, aspect, self.near, self.far)
+    }
+}
+
+impl Default for PerspectiveCamera {
+    fn default() -> Self {
+        Self {
+            fov: 1.0,
+            near: 0.1,
+            far: 1000.0,
+        }
+    }
+}
+
+/// Orbits around a point, or the [`follow_target`] if set, rotated by dragging with the right
+/// mouse button and zoomed with the scroll wheel
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct OrbitCamera {
+    pub focus: Vec3,
+    pub distance: f32,
+    pub min_distance: f32,
+    pub max_distance: f32,
+    pub yaw: f32,
+    pub pitch: f32,
+}
+
+impl OrbitCamera {
+    pub fn new(focus: Vec3, distance: f32) -> Self {
+        Self {
+            focus,
+            distance,
+            ..Default::default()
+        }
+    }
+
+    /// Set the zoom range
+    pub fn with_distance_range(mut self, min: f32, max: f32) -> Self {
+        self.min_distance = min;
+        self.max_distance = max;
+        self
+    }
+
+    /// Set the initial orientation
+    pub fn with_angles(mut self, yaw: f32, pitch: f32) -> Self {
+        self.yaw = yaw;
+        self.pitch = pitch;
+        self
+    }
+}
+
+impl Default for OrbitCamera {
+    fn default() -> Self {
+        Self {
+            focus: Vec3::ZERO,
+            distance: 10.0,
+            min_distance: 1.0,
+            max_distance: 100.0,
+            yaw: 0.0,
+            pitch: -0.4,
+        }
+    }
+}
+
+/// Follows behind the [`follow_target`] on a boom, which is shortened to not clip through the
+/// colliders between the target and the camera
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ThirdPersonCamera {
+    /// Offset of the pivot from the target, such as the height of the head or shoulder
+    pub offset: Vec3,
+    pub distance: f32,
+    pub min_distance: f32,
+    pub max_distance: f32,
+    /// Distance kept between the camera and colliders
+    pub radius: f32,
+    /// Rate at which the boom extends again after being obstructed
+    pub recovery_speed: f32,
+    pub yaw: f32,
+    pub pitch: f32,
+    current_distance: f32,
+}
+
+impl ThirdPersonCamera {
+    pub fn new(offset: Vec3, distance: f32) -> Self {
+        Self {
+            offset,
+            distance,
+            current_distance: distance,
+            ..Default::default()
+        }
+    }
+
+    /// Set the zoom range
+    pub fn with_distance_range(mut self, min: f32, max: f32) -> Self {
+        self.min_distance = min;
+        self.max_distance = max;
+        self
+    }
+
+    pub fn with_radius(mut self, radius: f32) -> Self {
+        self.radius = radius;
+        self
+    }
+
+    pub fn with_recovery_speed(mut self, recovery_speed: f32) -> Self {
+        self.recovery_speed = recovery_speed;
+        self
+    }
+
+    /// Returns the forward direction of the camera on the horizontal plane, useful for moving the
+    /// target relative to the camera
+    pub fn forward(&self) -> Vec3 {
+        Quat::from_rotation_y(self.yaw) * -Vec3::Z
+    }
+}
+
+impl Default for ThirdPersonCamera {
+    fn default() -> Self {
+        Self {
+            offset: vec3(0.0, 1.5, 0.0),
+            distance: 5.0,
+            min_distance: 1.0,
+            max_distance: 15.0,
+            radius: 0.2,
+            recovery_speed: 5.0,
+            yaw: 0.0,
+            pitch: -0.2,
+            current_distance: 5.0,
+        }
+    }
+}
+
+/// Updates the [`PerspectiveCamera`] projections, and the orbit and third-person camera
+/// controllers
+pub struct CameraPlugin;
+
+impl Plugin for CameraPlugin {
+    fn install(
+        &self,
+        _: &mut World,
+        _: &AssetCache,
+        schedules: &mut ScheduleSetBuilder,
+    ) -> anyhow::Result<()> {
+        schedules
+            .per_tick_mut()
+            .with_system(perspective_projection_system())
+            .with_system(orbit_zoom_system())
+            .with_system(third_person_zoom_system())
+            .with_system(orbit_camera_system())
+            .with_system(third_person_camera_system());
+
+        Ok(())
+    }
+}
+
+fn controller_input(look_button: Option<MouseButton>) -> InputState {
+    let mut look_action = Action::new();
+    look_action.add(CursorMoveBinding::new().amplitude(Vec2::ONE * 0.003));
+
+    let mut zoom_action = Action::new();
+    zoom_action.add(ScrollBinding::new().decompose(Axis2D::Y));
+
+    let mut state = InputState::new()
+        .with_action(look_input(), look_action)
+        .with_action(zoom_input(), zoom_action);
+
+    if let Some(button) = look_button {
+        let mut active_action = Action::new();
+        active_action.add(MouseButtonBinding::new(button));
+        state = state.with_action(look_active(), active_action);
+    }
+
+    state
+}
+
+pub(crate) fn camera_builder(camera: PerspectiveCamera) -> flax::EntityBuilder {
+    let mut builder = Entity::builder();
+    builder
+        .mount(TransformBundle::default())
+        .set(main_camera(), ())
+        .set(projection_matrix(), camera.projection(1.0))
+        .set(perspective_camera(), camera)
+        .set_default(look_input())
+        .set_default(zoom_input());
+
+    builder
+}
+
+/// Creates a main camera orbiting around a point, or `target` if set.
+///
+/// Requires the [`CameraPlugin`].
+pub fn setup_orbit_camera(orbit: OrbitCamera, target: Option<Entity>) -> flax::EntityBuilder {
+    let mut builder = camera_builder(PerspectiveCamera::default());
+    builder
+        .set(orbit_camera(), orbit)
+        .set(input_state(), controller_input(Some(MouseButton::Right)))
+        .set_default(look_active());
+
+    if let Some(target) = target {
+        builder.set(follow_target(), target);
+    }
+
+    builder
+}
+
+/// Creates a main camera following behind `target`, rotated by moving the cursor.
+///
+/// Requires the [`CameraPlugin`].
+pub fn setup_third_person_camera(camera: ThirdPersonCamera, target: Entity) -> flax::EntityBuilder {
+    let mut builder = camera_builder(PerspectiveCamera::default());
+    builder
+        .set(third_person_camera(), camera)
+        .set(follow_target(), target)
+        .set(input_state(), controller_input(None))
+        .set(look_active(), true);
+
+    builder
+}
+
+fn boom_rotation(yaw: f32, pitch: f32) -> Quat {
+    Quat::from_euler(EulerRot::YXZ, yaw, pitch, 0.0)
+}
+
+/// Retracts the boom immediately to not clip, but extends it smoothly towards `target`
+fn boom_distance(current: f32, target: f32, recovery_speed: f32, dt: f32) -> f32 {
+    if target < current {
+        target
+    } else {
+        let t = 1.0 - (-recovery_speed * dt).exp();
+        current + (target - current) * t
+    }
+}
+
+fn target_position(world: &World, target: Option<&Entity>) -> Option<Vec3> {
+    let transform = world.get(*target?, world_transform()).ok()?;
+    Some(transform.w_axis.truncate())
+}
+
+/// Updates the projections of all [`PerspectiveCamera`]s for a main window of `window_aspect`.
+///
+/// The [`CameraPlugin`] does this each tick; call it from a [`ResizedEvent`] handler to apply a
+/// resize before the next tick.
+///
+/// [`ResizedEvent`]: ivy_wgpu::events::ResizedEvent
+pub fn update_perspective_projections(world: &World, window_aspect: f32) {
+    let mut query = Query::new((
+        perspective_camera(),
+        projection_matrix().as_mut(),
+        player_view().opt(),
+        split_screen_viewports().source(engine()).opt(),
+    ));
+
+    for (camera, projection, player_view, viewports) in &mut query.borrow(world) {
+        let aspect = view_aspect(
+            window_aspect,
+            player_view.copied(),
+            viewports.map(Vec::as_slice),
+        );
+        *projection = camera.projection(aspect);
+    }
+}
+
+fn perspective_projection_system() -> BoxedSystem {
+    let mut windows = Query::new(window_size()).with(main_window());
+
+    System::builder()
+        .with_world()
+        .build(move |world: &World| {
+            let Some(size) = windows.borrow(world).first().copied() else {
+                return;
+            };
+
+            if size.is_empty() {
+                return;
+            }
+
+            update_perspective_projections(world, size.aspect());
+        })
+        .boxed()
+}
+
+pub(crate) fn zoom(distance: &mut f32, delta: f32, min: f32, max: f32) {
+    *distance = (*distance * 2_f32.powf(-delta * 0.1)).clamp(min, max);
+}
+
+fn orbit_zoom_system() -> BoxedSystem {
+    System::builder()
+        .with_query(Query::new((
+            orbit_camera().as_mut(),
+            zoom_input().modified(),
+        )))
+        .for_each(|(camera, &delta)| {
+            zoom(
+                &mut camera.distance,
+                delta,
+                camera.min_distance,
+                camera.max_distance,
+            );
+        })
+        .boxed()
+}
+
+fn third_person_zoom_system() -> BoxedSystem {
+    System::builder()
+        .with_query(Query::new((
+            third_person_camera().as_mut(),
+            zoom_input().modified(),
+        )))
+        .for_each(|(camera, &delta)| {
+            zoom(
+                &mut camera.distance,
+                delta,
+                camera.min_distance,
+                camera.max_distance,
+            );
+        })
+        .boxed()
+}
+
+fn orbit_camera_system() -> BoxedSystem {
+    let mut query = Query::new((
+        orbit_camera().as_mut(),
+        look_input(),
+        look_active(),
+        follow_target().opt(),
+        position().as_mut(),
+        rotation().as_mut(),
+    ));
+
+    System::builder()
+        .with_world_mut()
+        .build(move |world: &mut World| {
+            for (camera, look, &active, target, position, rotation) in &mut query.borrow(world) {
+                if active {
+                    camera.yaw -= look.x;
+                    camera.pitch = (camera.pitch - look.y).clamp(-MAX_PITCH, MAX_PITCH);
+                }
+
+                if let Some(target) = target_position(world, target) {
+                    camera.focus = target;
+                }
+
+                *rotation = boom_rotation(camera.yaw, camera.pitch);
+                *position = camera.focus + *rotation * Vec3::Z * camera.distance;
+            }
+        })
+        .boxed()
+}
+
+fn third_person_camera_system() -> BoxedSystem {
+    let mut query = Query::new((
+        third_person_camera().as_mut(),
+        look_input(),
+        look_active(),
+        follow_target(),
+        position().as_mut(),
+        rotation().as_mut(),
+    ));
+
+    System::builder()
+        .with_world_mut()
+        .build(move |world: &mut World| {
+            let dt = world
+                .get(engine(), delta_time())
+                .map(|v| v.as_secs_f32())
+                .unwrap_or_default();
+
+            let physics = world.get(engine(), physics_state()).ok();
+
+            for (camera, look, &active, target, position, rotation) in &mut query.borrow(world) {
+                if active {
+                    camera.yaw -= look.x;
+                    camera.pitch = (camera.pitch - look.y).clamp(-MAX_PITCH, MAX_PITCH);
+                }
+
+                let Some(target_pos) = target_position(world, Some(target)) else {
+                    continue;
+                };
+
+                let pivot = target_pos + camera.offset;
+                *rotation = boom_rotation(camera.yaw, camera.pitch);
+                let dir = *rotation * Vec3::Z;
+
+                let mut distance = camera.distance;
+                if let Some(physics) = &physics {
+                    let mut filter = QueryFilter::default().exclude_sensors();
+                    if let Ok(rb) = world.get_copy(*target, rb_handle()) {
+                        filter = filter.exclude_rigid_body(rb);
+                    }
+
+                    let ball = Ball::new(camera.radius);
+                    if let Some(hit) = physics.cast_shape(
+                        pivot,
+                        Quat::IDENTITY,
+                        &ball,
+                        dir,
+                        camera.distance,
+                        filter,
+                    ) {
+                        distance = hit.hit.time_of_impact.min(camera.distance);
+                    }
+                }
+
+                camera.current_distance =
+                    boom_distance(camera.current_distance, distance, camera.recovery_speed, dt);
+
+                *position = pivot + dir * camera.current_distance;
+            }
+        })
+        .boxed()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn zoom_scales_and_clamps() {
+        let mut distance = 10.0;
+        zoom(&mut distance, 10.0, 1.0, 100.0);
+        assert!((distance - 5.0).abs() < 1e-5);
+
+        zoom(&mut distance, -10.0, 1.0, 100.0);
+        assert!((distance - 10.0).abs() < 1e-5);
+
+        zoom(&mut distance, 100.0, 1.0, 100.0);
+        assert_eq!(distance, 1.0);
+
+        zoom(&mut distance, -200.0, 1.0, 100.0);
+        assert_eq!(distance, 100.0);
+    }
+
+    #[test]
+    fn boom_retracts_immediately() {
+        assert_eq!(boom_distance(5.0, 2.0, 5.0, 0.02), 2.0);
+    }
+
+    #[test]
+    fn boom_extends_smoothly() {
+        let mut distance = 2.0;
+        distance = boom_distance(distance, 5.0, 5.0, 0.02);
+        assert!(distance > 2.0 && distance < 5.0);
+
+        for _ in 0..500 {
+            distance = boom_distance(distance, 5.0, 5.0, 0.02);
+        }
+
+        assert!((distance - 5.0).abs() < 1e-3);
+        assert_eq!(boom_distance(2.0, 5.0, 5.0, 0.0), 2.0);
+    }
+
+    #[test]
+    fn boom_behind_and_above() {
+        let dir = boom_rotation(0.0, -0.4) * Vec3::Z;
+        assert!(dir.z > 0.0);
+        assert!(dir.y > 0.0);
+        assert!((dir.length() - 1.0).abs() < 1e-5);
+
+        // Yawing a quarter turn swings the boom around to the side
+        let dir = boom_rotation(std::f32::consts::FRAC_PI_2, 0.0) * Vec3::Z;
+        assert!(dir.abs_diff_eq(Vec3::X, 1e-5));
+    }
+
+    #[test]
+    fn forward_opposes_boom() {
+        let camera = ThirdPersonCamera {
+            yaw: 0.7,
+            ..Default::default()
+        };
+
+        let boom = boom_rotation(camera.yaw, 0.0) * Vec3::Z;
+        assert!(camera.forward().abs_diff_eq(-boom, 1e-5));
+    }
+}
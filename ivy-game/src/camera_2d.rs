@@ -0,0 +1,188 @@
+use flax::{
+    BoxedSystem, Component, ComponentMut, Entity, FetchExt, Query, QueryBorrow, System, World,
+};
+use glam::{vec3, Mat4, Quat, Vec3};
+use ivy_assets::AssetCache;
+use ivy_core::{
+    components::{engine, main_camera, TransformBundle},
+    update_layer::{Plugin, ScheduleSetBuilder},
+    LogicalExtent,
+};
+use ivy_wgpu::{
+    camera_target::view_aspect,
+    components::{
+        main_window, player_view, projection_matrix, split_screen_viewports, window_size,
+    },
+};
+
+flax::component! {
+    pub orthographic_camera: OrthographicCamera,
+}
+
+/// Orthographic projection which keeps a fixed number of world units visible vertically,
+/// regardless of the window size
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct OrthographicCamera {
+    /// Visible world units from the bottom to the top of the window
+    pub height: f32,
+    pub near: f32,
+    pub far: f32,
+}
+
+impl OrthographicCamera {
+    pub fn new(height: f32) -> Self {
+        Self {
+            height,
+            ..Default::default()
+        }
+    }
+
+    /// Set the near and far planes
+    pub fn with_clip_planes(mut self, near: f32, far: f32) -> Self {
+        self.near = near;
+        self.far = far;
+        self
+    }
+
+    pub fn projection(&self, aspect: f32) -> Mat4 {
+        let half_height = self.height * 0.5;
+        let half_width = half_height * aspect;
+
+        Mat4::orthographic_rh(
+            -half_width,
+            half_width,
+            -half_height,
+            half_height,
+            self.near,
+            self.far,
+        )
+    }
+}
+
+impl Default for OrthographicCamera {
+    fn default() -> Self {
+        Self {
+            height: 10.0,
+            near: 0.1,
+            far: 1000.0,
+        }
+    }
+}
+
+/// Keeps the projection of orthographic cameras in sync with the aspect ratio of the main window
+pub struct Camera2dPlugin;
+
+impl Plugin for Camera2dPlugin {
+    fn install(
+        &self,
+        _: &mut World,
+        _: &AssetCache,
+        schedules: &mut ScheduleSetBuilder,
+    ) -> anyhow::Result<()> {
+        schedules
+            .per_tick_mut()
+            .with_system(orthographic_projection_system());
+
+        Ok(())
+    }
+}
+
+/// Creates a main camera looking down -Z at the XY plane, suitable for sprites.
+///
+/// Requires the [`Camera2dPlugin`] to update the projection.
+pub fn setup_camera_2d(camera: OrthographicCamera) -> flax::EntityBuilder {
+    let mut builder = Entity::builder();
+    builder
+        .mount(TransformBundle::new(
+            vec3(0.0, 0.0, 10.0),
+            Quat::IDENTITY,
+            Vec3::ONE,
+        ))
+        .set(main_camera(), ())
+        .set(projection_matrix(), camera.projection(1.0))
+        .set(orthographic_camera(), camera);
+
+    builder
+}
+
+fn orthographic_projection_system() -> BoxedSystem {
+    System::builder()
+        .with_query(Query::new(window_size()).with(main_window()))
+        .with_query(Query::new((
+            orthographic_camera(),
+            projection_matrix().as_mut(),
+            player_view().opt(),
+            split_screen_viewports().source(engine()).opt(),
+        )))
+        .build(
+            |mut windows: QueryBorrow<Component<LogicalExtent>, _>,
+             mut cameras: QueryBorrow<(
+                Component<OrthographicCamera>,
+                ComponentMut<Mat4>,
+                _,
+                _,
+            )>| {
+                let Some(size) = windows.first() else {
+                    return;
+                };
+
+                if size.is_empty() {
+                    return;
+                }
+
+                let window_aspect = size.aspect();
+                for (camera, projection, player_view, viewports) in &mut cameras {
+                    let aspect = view_aspect(
+                        window_aspect,
+                        player_view.copied(),
+                        viewports.map(Vec::as_slice),
+                    );
+                    *projection = camera.projection(aspect);
+                }
+            },
+        )
+        .boxed()
+}
+
+#[cfg(test)]
+mod tests {
+    use flax::Schedule;
+
+    use super::*;
+
+    #[test]
+    fn projection_keeps_height() {
+        let camera = OrthographicCamera::new(4.0);
+        let projection = camera.projection(2.0);
+
+        let top_right = projection.project_point3(vec3(4.0, 2.0, -1.0));
+        assert!(top_right.truncate().abs_diff_eq(glam::Vec2::ONE, 1e-5));
+
+        let bottom_left = projection.project_point3(vec3(-4.0, -2.0, -1.0));
+        assert!(bottom_left.truncate().abs_diff_eq(-glam::Vec2::ONE, 1e-5));
+    }
+
+    #[test]
+    fn follows_window_aspect() {
+        let mut world = World::new();
+
+        Entity::builder()
+            .set(main_window(), ())
+            .set(window_size(), LogicalExtent::new(800.0, 400.0))
+            .spawn(&mut world);
+
+        let camera = OrthographicCamera::new(4.0);
+        let id = setup_camera_2d(camera).spawn(&mut world);
+
+        let mut schedule = Schedule::builder()
+            .with_system(orthographic_projection_system())
+            .build();
+
+        schedule.execute_seq(&mut world).unwrap();
+
+        assert_eq!(
+            *world.get(id, projection_matrix()).unwrap(),
+            camera.projection(2.0)
+        );
+    }
+}
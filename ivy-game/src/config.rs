@@ -0,0 +1,546 @@
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::Context;
+use flax::World;
+use ivy_assets::AssetCache;
+use ivy_audio::{components::audio_volumes, AudioVolumes};
+use ivy_core::{
+    components::engine,
+    driver::FrameLimit,
+    update_layer::{Plugin, ScheduleSetBuilder},
+};
+use ivy_input::types::{Key, NamedKey};
+use serde::{
+    de::{DeserializeOwned, IntoDeserializer},
+    Deserialize, Serialize,
+};
+use winit::{
+    dpi::LogicalSize,
+    window::{Fullscreen, WindowAttributes},
+};
+
+/// Prefix of the environment variables which override config values.
+///
+/// Nested keys are separated by a double underscore, e.g; `IVY_WINDOW__VSYNC=false`. Variables
+/// without a double underscore are ignored.
+pub const ENV_PREFIX: &str = "IVY_";
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WindowMode {
+    #[default]
+    Windowed,
+    Maximized,
+    /// Borderless fullscreen on the current monitor
+    Fullscreen,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WindowConfig {
+    pub title: String,
+    pub mode: WindowMode,
+    /// Logical size of the window when windowed
+    pub width: u32,
+    pub height: u32,
+    pub vsync: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_fps: Option<f64>,
+    /// Maximum frames per second while the window is unfocused or minimized
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub idle_fps: Option<f64>,
+}
+
+impl WindowConfig {
+    /// Returns the attributes to create the window with
+    pub fn window_attributes(&self) -> WindowAttributes {
+        let attributes = WindowAttributes::default()
+            .with_title(&self.title)
+            .with_inner_size(LogicalSize::new(self.width, self.height));
+
+        match self.mode {
+            WindowMode::Windowed => attributes,
+            WindowMode::Maximized => attributes.with_maximized(true),
+            WindowMode::Fullscreen => {
+                attributes.with_fullscreen(Some(Fullscreen::Borderless(None)))
+            }
+        }
+    }
+
+    pub fn frame_limit(&self) -> FrameLimit {
+        FrameLimit {
+            max_fps: self.max_fps,
+            idle_fps: self.idle_fps,
+        }
+    }
+}
+
+impl Default for WindowConfig {
+    fn default() -> Self {
+        Self {
+            title: "Ivy".into(),
+            mode: WindowMode::Windowed,
+            width: 1280,
+            height: 720,
+            vsync: true,
+            max_fps: None,
+            idle_fps: Some(30.0),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GraphicsConfig {
+    /// Resolution of the rendered image relative to the window.
+    ///
+    /// Applied through
+    /// [`PbrRenderGraphConfig::render_scale`](ivy_postprocessing::preconfigured::pbr::PbrRenderGraphConfig::render_scale).
+    pub render_scale: f32,
+}
+
+impl Default for GraphicsConfig {
+    fn default() -> Self {
+        Self { render_scale: 1.0 }
+    }
+}
+
+/// Volumes in the range `0..=1`.
+///
+/// Applied at startup by the [`AudioConfigPlugin`], and at runtime by setting
+/// [`audio_volumes`](ivy_audio::components::audio_volumes).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AudioConfig {
+    pub master: f32,
+    pub music: f32,
+    pub effects: f32,
+    pub voice: f32,
+}
+
+impl AudioConfig {
+    /// Returns the volume of a channel, scaled by the master volume
+    pub fn volume(&self, channel: f32) -> f32 {
+        (self.master * channel).clamp(0.0, 1.0)
+    }
+
+    /// Returns the volumes of the audio buses
+    pub fn volumes(&self) -> AudioVolumes {
+        AudioVolumes {
+            master: self.master,
+            music: self.music,
+            effects: self.effects,
+            voice: self.voice,
+        }
+    }
+}
+
+impl Default for AudioConfig {
+    fn default() -> Self {
+        Self {
+            master: 1.0,
+            music: 1.0,
+            effects: 1.0,
+            voice: 1.0,
+        }
+    }
+}
+
+/// Applies the volumes of an [`AudioConfig`] to the [`audio_volumes`] of the engine
+pub struct AudioConfigPlugin {
+    config: AudioConfig,
+}
+
+impl AudioConfigPlugin {
+    pub fn new(config: AudioConfig) -> Self {
+        Self { config }
+    }
+}
+
+impl Plugin for AudioConfigPlugin {
+    fn install(
+        &self,
+        world: &mut World,
+        _: &AssetCache,
+        _: &mut ScheduleSetBuilder,
+    ) -> anyhow::Result<()> {
+        world.set(engine(), audio_volumes(), self.config.volumes())?;
+        Ok(())
+    }
+}
+
+/// Engine and game settings.
+///
+/// ```toml
+/// [window]
+/// mode = "fullscreen"
+/// vsync = false
+///
+/// [bindings]
+/// jump = ["Space"]
+/// forward = ["w", "ArrowUp"]
+///
+/// [game]
+/// difficulty = "hard"
+/// ```
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    pub window: WindowConfig,
+    pub graphics: GraphicsConfig,
+    pub audio: AudioConfig,
+    /// Names of the keys bound to each action, e.g; the actions of the
+    /// [`FreeCameraBindings`](crate::free_camera::FreeCameraBindings)
+    pub bindings: BTreeMap<String, Vec<String>>,
+    /// Arbitrary game defined settings
+    pub game: toml::Table,
+}
+
+impl Config {
+    /// Returns the keys bound to `action`.
+    ///
+    /// Single characters are parsed as character keys, and other names as [`NamedKey`]s, e.g;
+    /// `Space` or `ArrowUp`.
+    pub fn keys(&self, action: &str) -> anyhow::Result<Vec<Key>> {
+        self.bindings
+            .get(action)
+            .into_iter()
+            .flatten()
+            .map(|v| parse_key(v))
+            .collect()
+    }
+
+    pub fn set_keys(
+        &mut self,
+        action: impl Into<String>,
+        keys: impl IntoIterator<Item = impl Into<String>>,
+    ) {
+        self.bindings
+            .insert(action.into(), keys.into_iter().map(Into::into).collect());
+    }
+
+    /// Deserialize a game defined setting
+    pub fn game_value<T: DeserializeOwned>(&self, key: &str) -> anyhow::Result<Option<T>> {
+        self.game
+            .get(key)
+            .map(|v| v.clone().try_into())
+            .transpose()
+            .with_context(|| format!("Failed to deserialize game setting {key:?}"))
+    }
+
+    pub fn set_game_value(
+        &mut self,
+        key: impl Into<String>,
+        value: impl Serialize,
+    ) -> anyhow::Result<()> {
+        let value = toml::Value::try_from(value).context("Failed to serialize game setting")?;
+        self.game.insert(key.into(), value);
+        Ok(())
+    }
+}
+
+fn parse_key(name: &str) -> anyhow::Result<Key> {
+    let mut chars = name.chars();
+    if let (Some(_), None) = (chars.next(), chars.next()) {
+        return Ok(Key::Character(name.to_lowercase().into()));
+    }
+
+    let deserializer: serde::de::value::StrDeserializer<serde::de::value::Error> =
+        name.into_deserializer();
+
+    let key =
+        NamedKey::deserialize(deserializer).with_context(|| format!("Unknown key {name:?}"))?;
+
+    Ok(Key::Named(key))
+}
+
+/// Values which take precedence over the config file, without being persisted.
+#[derive(Debug, Default, Clone)]
+pub struct ConfigOverrides {
+    values: toml::Table,
+}
+
+impl ConfigOverrides {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Reads the overrides from the environment and the command line arguments of the process
+    pub fn from_env() -> anyhow::Result<Self> {
+        Self::new().with_env()?.with_args(std::env::args().skip(1))
+    }
+
+    /// Set the value of a `.` separated `key`.
+    ///
+    /// The value is parsed as toml, and used as a string if that fails.
+    pub fn set(&mut self, key: &str, value: &str) -> anyhow::Result<()> {
+        let path = key.split('.').map(|v| v.trim()).collect::<Vec<_>>();
+        insert_path(&mut self.values, &path, parse_value(value))
+            .with_context(|| format!("Invalid config override {key:?}"))
+    }
+
+    /// Read overrides from the environment variables prefixed with [`ENV_PREFIX`]
+    pub fn with_env(mut self) -> anyhow::Result<Self> {
+        for (key, value) in std::env::vars() {
+            let Some(key) = key.strip_prefix(ENV_PREFIX) else {
+                continue;
+            };
+
+            if !key.contains("__") {
+                continue;
+            }
+
+            self.set(&key.to_lowercase().replace("__", "."), &value)?;
+        }
+
+        Ok(self)
+    }
+
+    /// Read overrides passed as `--config key=value`, `--config=key=value` or `-c key=value`.
+    ///
+    /// Other arguments are ignored.
+    pub fn with_args(
+        mut self,
+        args: impl IntoIterator<Item = impl Into<String>>,
+    ) -> anyhow::Result<Self> {
+        let mut args = args.into_iter().map(Into::into);
+
+        while let Some(arg) = args.next() {
+            let value = if arg == "--config" || arg == "-c" {
+                args.next()
+                    .with_context(|| format!("Missing value for {arg}"))?
+            } else if let Some(value) = arg.strip_prefix("--config=") {
+                value.to_string()
+            } else {
+                continue;
+            };
+
+            let (key, value) = value.split_once('=').with_context(|| {
+                format!("Expected key=value for config override, got {value:?}")
+            })?;
+
+            self.set(key, value)?;
+        }
+
+        Ok(self)
+    }
+}
+
+fn parse_value(value: &str) -> toml::Value {
+    format!("value = {value}")
+        .parse::<toml::Table>()
+        .ok()
+        .and_then(|mut v| v.remove("value"))
+        .unwrap_or_else(|| toml::Value::String(value.into()))
+}
+
+fn insert_path(table: &mut toml::Table, path: &[&str], value: toml::Value) -> anyhow::Result<()> {
+    let (last, parents) = path.split_last().context("Empty config key")?;
+
+    let mut table = table;
+    for key in parents {
+        table = table
+            .entry(*key)
+            .or_insert_with(|| toml::Value::Table(Default::default()))
+            .as_table_mut()
+            .with_context(|| format!("Config key {key:?} is not a table"))?;
+    }
+
+    table.insert(last.to_string(), value);
+    Ok(())
+}
+
+/// Recursively merges `src` into `dst`, overwriting values which are not tables
+fn merge(dst: &mut toml::Table, src: &toml::Table) {
+    for (key, value) in src {
+        match (dst.get_mut(key), value) {
+            (Some(toml::Value::Table(dst)), toml::Value::Table(src)) => merge(dst, src),
+            _ => {
+                dst.insert(key.clone(), value.clone());
+            }
+        }
+    }
+}
+
+/// A [`Config`] loaded from a toml file.
+///
+/// Changes made through [`Self::update`] are written back to the file, while the overrides are
+/// only applied to the current run.
+///
+/// ```rust,ignore
+/// let config = ConfigFile::load("config.toml", ConfigOverrides::from_env()?)?;
+///
+/// App::builder()
+///     .with_driver(
+///         WinitDriver::new(config.window.window_attributes())
+///             .with_frame_limit(config.window.frame_limit()),
+///     )
+///     .with_layer(GraphicsLayer::new(init_renderer).with_vsync(config.window.vsync))
+///     .with_plugin(AudioPlugin::new())
+///     .with_layer(
+///         ScheduledLayer::new(FixedTimeStep::new(0.02))
+///             .with_plugin(AudioConfigPlugin::new(config.audio.clone())),
+///     )
+/// ```
+pub struct ConfigFile {
+    path: PathBuf,
+    /// Contents of the file, without overrides
+    user: toml::Table,
+    overrides: ConfigOverrides,
+    config: Config,
+}
+
+impl ConfigFile {
+    /// Loads the config at `path`, or the defaults if the file does not exist
+    pub fn load(path: impl Into<PathBuf>, overrides: ConfigOverrides) -> anyhow::Result<Self> {
+        let path = path.into();
+
+        let user = if path.exists() {
+            fs::read_to_string(&path)
+                .with_context(|| format!("Failed to read config {path:?}"))?
+                .parse::<toml::Table>()
+                .with_context(|| format!("Failed to parse config {path:?}"))?
+        } else {
+            tracing::info!(?path, "No config file found, using defaults");
+            toml::Table::new()
+        };
+
+        let config = resolve(&user, &overrides)?;
+
+        Ok(Self {
+            path,
+            user,
+            overrides,
+            config,
+        })
+    }
+
+    /// Applies a change made by the user, and persists it to the config file
+    pub fn update(&mut self, func: impl FnOnce(&mut Config)) -> anyhow::Result<()> {
+        let mut user: Config = toml::Value::Table(self.user.clone())
+            .try_into()
+            .context("Failed to deserialize config")?;
+
+        func(&mut user);
+
+        self.user = toml::Value::try_from(&user)
+            .context("Failed to serialize config")?
+            .try_into()?;
+
+        self.config = resolve(&self.user, &self.overrides)?;
+        self.save()
+    }
+
+    /// Writes the config to disk, excluding the overrides
+    pub fn save(&self) -> anyhow::Result<()> {
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        let data = toml::to_string_pretty(&self.user).context("Failed to serialize config")?;
+        fs::write(&self.path, data)
+            .with_context(|| format!("Failed to write config {:?}", self.path))?;
+
+        tracing::info!(path = ?self.path, "saved config");
+        Ok(())
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+}
+
+impl std::ops::Deref for ConfigFile {
+    type Target = Config;
+
+    fn deref(&self) -> &Self::Target {
+        &self.config
+    }
+}
+
+fn resolve(user: &toml::Table, overrides: &ConfigOverrides) -> anyhow::Result<Config> {
+    let mut values = user.clone();
+    merge(&mut values, &overrides.values);
+
+    toml::Value::Table(values)
+        .try_into()
+        .context("Invalid config")
+}
+
+#[cfg(test)]
+mod test {
+    use ivy_core::update_layer::FixedTimeStep;
+
+    use super::*;
+
+    #[test]
+    fn overrides() {
+        let user: toml::Table = "[window]\nvsync = true\nwidth = 800\n".parse().unwrap();
+
+        let overrides = ConfigOverrides::new()
+            .with_args([
+                "--level",
+                "2",
+                "--config",
+                "window.vsync=false",
+                "-c=ignored",
+                "--config=game.difficulty=hard",
+            ])
+            .unwrap();
+
+        let config = resolve(&user, &overrides).unwrap();
+
+        assert!(!config.window.vsync);
+        assert_eq!(config.window.width, 800);
+        assert_eq!(config.window.height, WindowConfig::default().height);
+        assert_eq!(
+            config
+                .game_value::<String>("difficulty")
+                .unwrap()
+                .as_deref(),
+            Some("hard")
+        );
+    }
+
+    #[test]
+    fn keys() {
+        let mut config = Config::default();
+        config.set_keys("jump", ["Space", "J"]);
+
+        assert_eq!(
+            config.keys("jump").unwrap(),
+            [Key::Named(NamedKey::Space), Key::Character("j".into())]
+        );
+        assert!(config.keys("missing").unwrap().is_empty());
+    }
+
+    #[test]
+    fn audio_volumes_applied() {
+        let user: toml::Table = "[audio]\nmaster = 0.5\nmusic = 0.25\n".parse().unwrap();
+        let config = resolve(&user, &ConfigOverrides::new()).unwrap();
+
+        let expected = AudioVolumes {
+            master: 0.5,
+            music: 0.25,
+            effects: 1.0,
+            voice: 1.0,
+        };
+        assert_eq!(config.audio.volumes(), expected);
+
+        let mut world = World::new();
+        let mut schedules = ScheduleSetBuilder::new(FixedTimeStep::new(0.02));
+        AudioConfigPlugin::new(config.audio)
+            .install(&mut world, &AssetCache::new(), &mut schedules)
+            .unwrap();
+
+        assert_eq!(world.get_copy(engine(), audio_volumes()).unwrap(), expected);
+    }
+}
@@ -0,0 +1,384 @@
+use std::f32::consts::TAU;
+
+use flax::{component, entity_ids, BoxedSystem, Query, System, World};
+use glam::{Quat, Vec3};
+use ivy_assets::AssetCache;
+use ivy_core::{
+    components::{delta_time, engine, rotation},
+    palette::Srgb,
+    update_layer::{Plugin, ScheduleSetBuilder},
+};
+use ivy_postprocessing::components::atmosphere;
+use ivy_wgpu::components::{environment_data, light_kind, light_params};
+
+const HOURS_PER_DAY: f32 = 24.0;
+
+/// Relative change of the sky intensity before the atmosphere is updated, as changing the sky
+/// regenerates the environment lighting
+const SKY_INTENSITY_THRESHOLD: f32 = 0.02;
+
+fn lerp(a: f32, b: f32, t: f32) -> f32 {
+    a + (b - a) * t
+}
+
+fn lerp_color(a: Srgb, b: Srgb, t: f32) -> Srgb {
+    Srgb::new(
+        lerp(a.red, b.red, t),
+        lerp(a.green, b.green, t),
+        lerp(a.blue, b.blue, t),
+    )
+}
+
+/// The lighting at a time of day, which is blended between over the day
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DayKey {
+    /// Hour of the day in `[0, 24)`
+    pub time: f32,
+    pub sun_color: Srgb,
+    pub sun_intensity: f32,
+    /// Brightness of the procedural sky, which provides the ambient lighting of the scene.
+    ///
+    /// Written to [`Atmosphere::sun_intensity`](ivy_postprocessing::atmosphere::Atmosphere)
+    pub ambient_intensity: f32,
+    pub fog_color: Srgb,
+    pub fog_density: f32,
+}
+
+impl DayKey {
+    pub fn lerp(&self, other: &Self, t: f32) -> Self {
+        Self {
+            time: lerp(self.time, other.time, t),
+            sun_color: lerp_color(self.sun_color, other.sun_color, t),
+            sun_intensity: lerp(self.sun_intensity, other.sun_intensity, t),
+            ambient_intensity: lerp(self.ambient_intensity, other.ambient_intensity, t),
+            fog_color: lerp_color(self.fog_color, other.fog_color, t),
+            fog_density: lerp(self.fog_density, other.fog_density, t),
+        }
+    }
+
+    pub fn midnight() -> Self {
+        Self {
+            time: 0.0,
+            sun_color: Srgb::new(0.5, 0.6, 1.0),
+            sun_intensity: 0.0,
+            ambient_intensity: 0.2,
+            fog_color: Srgb::new(0.02, 0.03, 0.06),
+            fog_density: 0.002,
+        }
+    }
+
+    pub fn dawn() -> Self {
+        Self {
+            time: 6.0,
+            sun_color: Srgb::new(1.0, 0.55, 0.3),
+            sun_intensity: 0.6,
+            ambient_intensity: 8.0,
+            fog_color: Srgb::new(0.75, 0.55, 0.45),
+            fog_density: 0.004,
+        }
+    }
+
+    pub fn noon() -> Self {
+        Self {
+            time: 12.0,
+            sun_color: Srgb::new(1.0, 0.97, 0.92),
+            sun_intensity: 2.0,
+            ambient_intensity: 20.0,
+            fog_color: Srgb::new(0.6, 0.7, 0.8),
+            fog_density: 0.001,
+        }
+    }
+
+    pub fn dusk() -> Self {
+        Self {
+            time: 18.0,
+            sun_color: Srgb::new(1.0, 0.45, 0.25),
+            sun_intensity: 0.6,
+            ambient_intensity: 8.0,
+            fog_color: Srgb::new(0.7, 0.45, 0.35),
+            fog_density: 0.003,
+        }
+    }
+}
+
+/// Moves the sun across the sky and blends the lighting between [`DayKey`]s.
+///
+/// The sun is the first directional light, and rises at 6 and sets at 18.
+#[derive(Debug, Clone)]
+pub struct DayCycle {
+    keys: Vec<DayKey>,
+    /// Hour of the day
+    time: f32,
+    /// Length of a full day in seconds
+    pub day_length: f32,
+    pub paused: bool,
+    /// Tilt of the path of the sun from passing straight overhead, in radians
+    pub tilt: f32,
+    /// Rotation of the sunrise around the up axis, in radians. Zero rises along +X.
+    pub azimuth: f32,
+    /// Write the fog parameters of the cameras.
+    ///
+    /// Disable when the fog is controlled by the weather.
+    pub fog: bool,
+}
+
+impl DayCycle {
+    pub fn new(time: f32) -> Self {
+        Self {
+            keys: vec![
+                DayKey::midnight(),
+                DayKey::dawn(),
+                DayKey::noon(),
+                DayKey::dusk(),
+            ],
+            time: time.rem_euclid(HOURS_PER_DAY),
+            day_length: 20.0 * 60.0,
+            paused: false,
+            tilt: 0.4,
+            azimuth: 0.0,
+            fog: true,
+        }
+    }
+
+    /// Set the length of a full day in seconds
+    pub fn with_day_length(mut self, day_length: f32) -> Self {
+        self.day_length = day_length;
+        self
+    }
+
+    pub fn with_tilt(mut self, tilt: f32) -> Self {
+        self.tilt = tilt;
+        self
+    }
+
+    pub fn with_azimuth(mut self, azimuth: f32) -> Self {
+        self.azimuth = azimuth;
+        self
+    }
+
+    pub fn with_fog(mut self, fog: bool) -> Self {
+        self.fog = fog;
+        self
+    }
+
+    /// Replace the keys of the day
+    pub fn with_keys(mut self, keys: impl IntoIterator<Item = DayKey>) -> Self {
+        self.keys.clear();
+        for key in keys {
+            self.add_key(key);
+        }
+
+        self
+    }
+
+    /// Add a key, replacing any key at the same time
+    pub fn add_key(&mut self, mut key: DayKey) {
+        key.time = key.time.rem_euclid(HOURS_PER_DAY);
+        let index = self.keys.partition_point(|v| v.time < key.time);
+
+        match self.keys.get_mut(index) {
+            Some(v) if v.time == key.time => *v = key,
+            _ => self.keys.insert(index, key),
+        }
+    }
+
+    pub fn keys(&self) -> &[DayKey] {
+        &self.keys
+    }
+
+    /// Returns the hour of the day
+    pub fn time(&self) -> f32 {
+        self.time
+    }
+
+    /// Jump to an hour of the day
+    pub fn set_time(&mut self, time: f32) {
+        self.time = time.rem_euclid(HOURS_PER_DAY);
+    }
+
+    pub fn update(&mut self, dt: f32) {
+        if self.paused || self.day_length <= 0.0 {
+            return;
+        }
+
+        self.set_time(self.time + dt / self.day_length * HOURS_PER_DAY);
+    }
+
+    /// Returns true if the sun is above the horizon
+    pub fn is_day(&self) -> bool {
+        self.sun_direction().y > 0.0
+    }
+
+    /// Direction towards the sun
+    pub fn sun_direction(&self) -> Vec3 {
+        let angle = (self.time - 6.0) / HOURS_PER_DAY * TAU;
+        let dir = Vec3::new(angle.cos(), angle.sin(), 0.0);
+
+        Quat::from_rotation_y(self.azimuth) * Quat::from_rotation_x(self.tilt) * dir
+    }
+
+    /// Returns the lighting at the current time, wrapping around midnight
+    pub fn current(&self) -> Option<DayKey> {
+        if self.keys.is_empty() {
+            return None;
+        }
+
+        let next = self.keys.partition_point(|v| v.time <= self.time);
+        let a = &self.keys[(next + self.keys.len() - 1) % self.keys.len()];
+        let b = &self.keys[next % self.keys.len()];
+
+        let span = (b.time - a.time).rem_euclid(HOURS_PER_DAY);
+        let t = if span > 0.0 {
+            (self.time - a.time).rem_euclid(HOURS_PER_DAY) / span
+        } else {
+            0.0
+        };
+
+        Some(DayKey {
+            time: self.time,
+            ..a.lerp(b, t)
+        })
+    }
+}
+
+component! {
+    /// Stored on the engine entity
+    pub day_cycle: DayCycle,
+}
+
+fn day_cycle_system() -> BoxedSystem {
+    System::builder()
+        .with_world_mut()
+        .build(|world: &mut World| {
+            let dt = world
+                .get(engine(), delta_time())
+                .map(|v| v.as_secs_f32())
+                .unwrap_or_default();
+
+            let Ok(mut cycle) = world.get_mut(engine(), day_cycle()) else {
+                return Ok(());
+            };
+
+            cycle.update(dt);
+
+            let sun_direction = cycle.sun_direction();
+            let fog = cycle.fog;
+            let Some(key) = cycle.current() else {
+                return Ok(());
+            };
+
+            drop(cycle);
+
+            let mut lights =
+                Query::new((light_kind(), light_params().as_mut(), rotation().as_mut()));
+            if let Some((_, params, rotation)) = lights
+                .borrow(world)
+                .iter()
+                .find(|(kind, _, _)| kind.is_directional())
+            {
+                params.color = key.sun_color;
+                params.intensity = key.sun_intensity;
+                // Lights shine along -Z
+                *rotation = Quat::from_rotation_arc(Vec3::Z, sun_direction);
+            }
+
+            if fog {
+                // Only write changes, as the renderer uploads modified environments
+                let changed = Query::new((entity_ids(), environment_data()))
+                    .borrow(world)
+                    .iter()
+                    .filter(|(_, env)| {
+                        env.fog_color != key.fog_color || env.fog_density != key.fog_density
+                    })
+                    .map(|(id, _)| id)
+                    .collect::<Vec<_>>();
+
+                for id in changed {
+                    let mut env = world.get_mut(id, environment_data())?;
+                    env.fog_color = key.fog_color;
+                    env.fog_density = key.fog_density;
+                }
+            }
+
+            for atmosphere in Query::new(atmosphere().as_mut()).borrow(world).iter() {
+                let diff = (atmosphere.sun_intensity - key.ambient_intensity).abs();
+                if diff > atmosphere.sun_intensity.abs() * SKY_INTENSITY_THRESHOLD {
+                    atmosphere.sun_intensity = key.ambient_intensity;
+                }
+            }
+
+            anyhow::Ok(())
+        })
+        .boxed()
+}
+
+/// Adds a [`DayCycle`] to the engine entity, which animates the sun, sky and fog over the day.
+///
+/// Cameras without an [`atmosphere`] keep the brightness of the configured sky.
+pub struct DayCyclePlugin {
+    cycle: DayCycle,
+}
+
+impl DayCyclePlugin {
+    pub fn new(cycle: DayCycle) -> Self {
+        Self { cycle }
+    }
+}
+
+impl Plugin for DayCyclePlugin {
+    fn install(
+        &self,
+        world: &mut World,
+        _: &AssetCache,
+        schedules: &mut ScheduleSetBuilder,
+    ) -> anyhow::Result<()> {
+        world.set(engine(), day_cycle(), self.cycle.clone())?;
+        schedules.per_tick_mut().with_system(day_cycle_system());
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn sun_path() {
+        let mut cycle = DayCycle::new(12.0).with_tilt(0.0);
+        assert!(cycle.sun_direction().abs_diff_eq(Vec3::Y, 1e-5));
+        assert!(cycle.is_day());
+
+        cycle.set_time(0.0);
+        assert!(cycle.sun_direction().abs_diff_eq(Vec3::NEG_Y, 1e-5));
+        assert!(!cycle.is_day());
+
+        cycle = cycle.with_day_length(24.0);
+        cycle.update(30.0);
+        assert!((cycle.time() - 6.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn wrap_around_midnight() {
+        let mut cycle = DayCycle::new(23.0).with_keys([
+            DayKey {
+                time: 22.0,
+                sun_intensity: 0.0,
+                ..DayKey::midnight()
+            },
+            DayKey {
+                time: 2.0,
+                sun_intensity: 4.0,
+                ..DayKey::midnight()
+            },
+        ]);
+
+        assert_eq!(cycle.current().unwrap().sun_intensity, 1.0);
+
+        cycle.set_time(1.0);
+        assert_eq!(cycle.current().unwrap().sun_intensity, 3.0);
+
+        cycle.set_time(12.0);
+        assert_eq!(cycle.current().unwrap().sun_intensity, 2.0);
+    }
+}
@@ -0,0 +1,286 @@
+use std::time::Duration;
+
+use flax::{component, entity_ids, BoxedSystem, Entity, Query, System, World};
+use glam::Vec2;
+use ivy_assets::AssetCache;
+use ivy_core::{
+    components::{engine, frame_stats, gizmos, memory_report},
+    gizmos::{DrawScreenGizmos, ScreenGizmosSection, ScreenLine, ScreenRect, ScreenText},
+    memory::MemoryReport,
+    update_layer::{Plugin, ScheduleSetBuilder},
+    Color, ColorExt,
+};
+use ivy_input::{
+    components::input_state,
+    types::{Key, NamedKey},
+    Action, BindingExt, InputState, KeyBinding,
+};
+use ivy_wgpu::{components::render_stats, renderer::render_stats::RenderStats};
+
+const GRAPH_SIZE: Vec2 = Vec2::new(240.0, 60.0);
+/// Frame time at the top of the graph
+const GRAPH_MAX: Duration = Duration::from_millis(50);
+const TEXT_SIZE: f32 = 10.0;
+const LINE_SPACING: f32 = 16.0;
+
+/// Compact performance overlay, drawn as screen space gizmos.
+///
+/// Shows a graph of the recent frame times, the 1% and 0.1% lows, the draw calls and triangles of
+/// the renderer, the number of entities, and the memory used by the heap and each subsystem.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PerformanceOverlay {
+    /// Top left corner, in pixels
+    pub position: Vec2,
+    /// Frame times, from oldest to newest
+    pub frame_times: Vec<Duration>,
+    pub mean: Duration,
+    pub low_1: Duration,
+    pub low_01: Duration,
+    pub render: RenderStats,
+    pub entities: usize,
+    pub memory: MemoryReport,
+}
+
+impl PerformanceOverlay {
+    /// Collects the current stats of the world
+    pub fn from_world(world: &World, position: Vec2) -> Self {
+        let stats = world.get(engine(), frame_stats()).ok();
+        let stats = stats.as_deref();
+
+        Self {
+            position,
+            frame_times: stats.map(|v| v.frame_times().collect()).unwrap_or_default(),
+            mean: stats
+                .and_then(|v| v.frame_time())
+                .map(|v| v.mean)
+                .unwrap_or_default(),
+            low_1: stats
+                .and_then(|v| v.frame_time_low(0.01))
+                .unwrap_or_default(),
+            low_01: stats
+                .and_then(|v| v.frame_time_low(0.001))
+                .unwrap_or_default(),
+            render: world.get_copy(engine(), render_stats()).unwrap_or_default(),
+            entities: Query::new(entity_ids()).borrow(world).iter().count(),
+            memory: world
+                .get(engine(), memory_report())
+                .map(|v| v.clone())
+                .unwrap_or_default(),
+        }
+    }
+}
+
+fn fps(frame_time: Duration) -> f32 {
+    if frame_time.is_zero() {
+        0.0
+    } else {
+        1.0 / frame_time.as_secs_f32()
+    }
+}
+
+fn frame_time_color(frame_time: Duration) -> Color {
+    if frame_time <= Duration::from_micros(16_667) {
+        Color::green()
+    } else if frame_time <= Duration::from_micros(33_334) {
+        Color::yellow()
+    } else {
+        Color::red()
+    }
+}
+
+fn si(value: u64) -> String {
+    if value < 1_000 {
+        value.to_string()
+    } else if value < 1_000_000 {
+        format!("{:.1}K", value as f32 / 1e3)
+    } else {
+        format!("{:.1}M", value as f32 / 1e6)
+    }
+}
+
+fn bytes(value: u64) -> String {
+    if value < 1 << 10 {
+        format!("{value}B")
+    } else if value < 1 << 20 {
+        format!("{:.1}KB", value as f32 / (1 << 10) as f32)
+    } else if value < 1 << 30 {
+        format!("{:.1}MB", value as f32 / (1 << 20) as f32)
+    } else {
+        format!("{:.1}GB", value as f32 / (1 << 30) as f32)
+    }
+}
+
+impl DrawScreenGizmos for PerformanceOverlay {
+    fn draw_primitives(&self, gizmos: &mut ScreenGizmosSection) {
+        let mut lines = vec![
+            format!(
+                "FPS {:.0} ({:.1}MS)",
+                fps(self.mean),
+                self.mean.as_secs_f32() * 1e3
+            ),
+            format!(
+                "1% LOW {:.0}  0.1% LOW {:.0}",
+                fps(self.low_1),
+                fps(self.low_01)
+            ),
+            format!(
+                "DRAWS {}  TRIS {}",
+                si(self.render.draw_calls),
+                si(self.render.triangles)
+            ),
+            format!("ENTITIES {}", si(self.entities as u64)),
+            format!(
+                "HEAP {}  PEAK {}",
+                bytes(self.memory.heap.current as u64),
+                bytes(self.memory.heap.peak as u64)
+            ),
+        ];
+
+        lines.extend(
+            self.memory
+                .categories
+                .iter()
+                .map(|(category, &used)| format!("{} {}", category.to_uppercase(), bytes(used))),
+        );
+
+        let line_count = lines.len();
+        for (i, line) in lines.into_iter().enumerate() {
+            gizmos.draw(
+                ScreenText::new(self.position + Vec2::Y * i as f32 * LINE_SPACING, line)
+                    .with_size(TEXT_SIZE),
+            );
+        }
+
+        let min = self.position + Vec2::Y * (line_count as f32 * LINE_SPACING + 4.0);
+        let max = min + GRAPH_SIZE;
+
+        gizmos.draw(ScreenRect::new(min, max, Color::white()).with_thickness(1.0));
+
+        let height = |frame_time: Duration| {
+            let t = (frame_time.as_secs_f32() / GRAPH_MAX.as_secs_f32()).min(1.0);
+            max.y - t * GRAPH_SIZE.y
+        };
+
+        // 60 and 30 fps
+        for target in [Duration::from_micros(16_667), Duration::from_micros(33_334)] {
+            let y = height(target);
+            gizmos.draw(
+                ScreenLine::new(Vec2::new(min.x, y), Vec2::new(max.x, y), Color::white())
+                    .with_thickness(1.0),
+            );
+        }
+
+        let count = self.frame_times.len().max(2);
+        let step = GRAPH_SIZE.x / (count - 1) as f32;
+
+        for (i, w) in self.frame_times.windows(2).enumerate() {
+            gizmos.draw(
+                ScreenLine::new(
+                    Vec2::new(min.x + i as f32 * step, height(w[0])),
+                    Vec2::new(min.x + (i + 1) as f32 * step, height(w[1])),
+                    frame_time_color(w[1]),
+                )
+                .with_thickness(1.5),
+            );
+        }
+    }
+}
+
+component! {
+    toggle_performance_overlay_action: bool,
+    performance_overlay_visible: bool,
+}
+
+fn performance_overlay_system(position: Vec2) -> BoxedSystem {
+    let mut input_query = Query::new(toggle_performance_overlay_action());
+
+    System::builder()
+        .with_world()
+        .build(move |world: &World| {
+            let toggle = input_query.borrow(world).first().copied().unwrap_or(false);
+
+            let mut visible = world.get_mut(engine(), performance_overlay_visible())?;
+            if toggle {
+                *visible = !*visible;
+            }
+
+            let gizmos = world.get(engine(), gizmos())?;
+            let mut section = gizmos.begin_screen_section("performance_overlay");
+
+            if *visible {
+                section.draw(PerformanceOverlay::from_world(world, position));
+            }
+
+            anyhow::Ok(())
+        })
+        .boxed()
+}
+
+/// Draws the [`PerformanceOverlay`], toggled with `F3` by default
+pub struct PerformanceOverlayPlugin {
+    key: Key,
+    position: Vec2,
+    visible: bool,
+}
+
+impl PerformanceOverlayPlugin {
+    pub fn new() -> Self {
+        Self {
+            key: Key::Named(NamedKey::F3),
+            position: Vec2::new(8.0, 8.0),
+            visible: false,
+        }
+    }
+
+    /// Set the key which toggles the overlay
+    pub fn with_toggle_key(mut self, key: Key) -> Self {
+        self.key = key;
+        self
+    }
+
+    /// Set the top left corner, in pixels
+    pub fn with_position(mut self, position: Vec2) -> Self {
+        self.position = position;
+        self
+    }
+
+    /// Show the overlay from the start
+    pub fn with_visible(mut self, visible: bool) -> Self {
+        self.visible = visible;
+        self
+    }
+}
+
+impl Default for PerformanceOverlayPlugin {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Plugin for PerformanceOverlayPlugin {
+    fn install(
+        &self,
+        world: &mut World,
+        _: &AssetCache,
+        schedules: &mut ScheduleSetBuilder,
+    ) -> anyhow::Result<()> {
+        world.set(engine(), performance_overlay_visible(), self.visible)?;
+
+        let mut toggle = Action::new();
+        toggle.add(KeyBinding::new(self.key.clone()).rising_edge());
+
+        Entity::builder()
+            .set(
+                input_state(),
+                InputState::new().with_action(toggle_performance_overlay_action(), toggle),
+            )
+            .set_default(toggle_performance_overlay_action())
+            .spawn(world);
+
+        schedules
+            .per_tick_mut()
+            .with_system(performance_overlay_system(self.position));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,633 @@
+use std::{collections::BTreeMap, path::Path};
+
+use anyhow::Context;
+use flax::{component, BoxedSystem, Entity, Query, System, World};
+use glam::Vec2;
+use ivy_assets::{
+    fs::{AssetFromPath, AsyncAssetFromPath},
+    service::FileSystemMapService,
+    Asset, AssetCache,
+};
+use ivy_core::{
+    components::{delta_time, engine},
+    update_layer::{Plugin, ScheduleSetBuilder},
+};
+use ivy_input::{
+    components::input_state,
+    types::{Key, NamedKey},
+    Action, BindingExt, InputState, KeyBinding,
+};
+use serde::{Deserialize, Serialize};
+use violet::{
+    core::{
+        layout::Align,
+        style::SizeExt,
+        widget::{card, col, label, SignalWidget, Stack},
+        Widget,
+    },
+    futures_signals::signal::Mutable,
+};
+
+/// Maximum number of choices selectable with the number keys
+const MAX_CHOICES: i32 = 9;
+
+/// An option presented to the player at the end of a line
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DialogueChoice {
+    /// Localization key or literal text
+    pub text: String,
+    /// Node to continue to, or end the dialogue if `None`
+    #[serde(default)]
+    pub next: Option<String>,
+    /// Gameplay event emitted when the choice is made
+    #[serde(default)]
+    pub event: Option<String>,
+}
+
+/// A line spoken in a dialogue
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DialogueNode {
+    #[serde(default)]
+    pub speaker: Option<String>,
+    /// Localization key or literal text
+    pub text: String,
+    /// Seconds until the dialogue automatically advances.
+    ///
+    /// If `None` the line is shown until the player advances.
+    #[serde(default)]
+    pub duration: Option<f32>,
+    /// Choices shown with the line, replacing `next`
+    #[serde(default)]
+    pub choices: Vec<DialogueChoice>,
+    /// Node to continue to, or end the dialogue if `None`
+    #[serde(default)]
+    pub next: Option<String>,
+    /// Gameplay event emitted when the line starts
+    #[serde(default)]
+    pub event: Option<String>,
+}
+
+/// An authored conversation, loaded from RON:
+///
+/// ```ron
+/// (
+///     start: "greeting",
+///     nodes: {
+///         "greeting": (
+///             speaker: Some("guard"),
+///             text: "guard.halt",
+///             choices: [
+///                 (text: "player.friend", next: Some("pass"), event: Some("open_gate")),
+///                 (text: "player.leave"),
+///             ],
+///         ),
+///         "pass": (speaker: Some("guard"), text: "guard.pass", duration: Some(2.0)),
+///     },
+/// )
+/// ```
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Dialogue {
+    pub start: String,
+    pub nodes: BTreeMap<String, DialogueNode>,
+}
+
+impl Dialogue {
+    pub fn node(&self, id: &str) -> Option<&DialogueNode> {
+        self.nodes.get(id)
+    }
+
+    /// Checks that all referenced nodes exist
+    pub fn validate(&self) -> anyhow::Result<()> {
+        let check = |id: &str| {
+            if !self.nodes.contains_key(id) {
+                anyhow::bail!("Reference to unknown dialogue node {id:?}");
+            }
+
+            Ok(())
+        };
+
+        check(self.start.as_str())?;
+
+        for node in self.nodes.values() {
+            for next in node
+                .next
+                .iter()
+                .chain(node.choices.iter().flat_map(|v| &v.next))
+            {
+                check(next.as_str())?;
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn from_ron(source: &str) -> anyhow::Result<Self> {
+        let dialogue: Self = ron::from_str(source)?;
+        dialogue.validate()?;
+        Ok(dialogue)
+    }
+}
+
+impl AssetFromPath for Dialogue {
+    type Error = anyhow::Error;
+
+    fn load_from_path(path: &Path, assets: &AssetCache) -> anyhow::Result<Asset<Self>> {
+        let source = assets.service::<FileSystemMapService>().load_string(path)?;
+        let dialogue = Dialogue::from_ron(&source)
+            .with_context(|| format!("Failed to load dialogue {path:?}"))?;
+
+        Ok(assets.insert(dialogue))
+    }
+}
+
+impl AsyncAssetFromPath for Dialogue {
+    type Error = anyhow::Error;
+
+    async fn load_from_path(path: &Path, assets: &AssetCache) -> anyhow::Result<Asset<Self>> {
+        let source = assets
+            .service::<FileSystemMapService>()
+            .load_string_async(path)
+            .await?;
+        let dialogue = Dialogue::from_ron(&source)
+            .with_context(|| format!("Failed to load dialogue {path:?}"))?;
+
+        Ok(assets.insert(dialogue))
+    }
+}
+
+/// Translated strings for a single language, loaded from a RON map of keys to text
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Localization {
+    strings: BTreeMap<String, String>,
+}
+
+impl Localization {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a translated string
+    pub fn with_string(mut self, key: impl Into<String>, text: impl Into<String>) -> Self {
+        self.strings.insert(key.into(), text.into());
+        self
+    }
+
+    /// Returns the translation of `key`, or `key` itself if missing
+    pub fn get<'a>(&'a self, key: &'a str) -> &'a str {
+        self.strings.get(key).map(|v| v.as_str()).unwrap_or(key)
+    }
+}
+
+impl AsyncAssetFromPath for Localization {
+    type Error = anyhow::Error;
+
+    async fn load_from_path(path: &Path, assets: &AssetCache) -> anyhow::Result<Asset<Self>> {
+        let source = assets
+            .service::<FileSystemMapService>()
+            .load_string_async(path)
+            .await?;
+        let localization = ron::from_str(&source)
+            .with_context(|| format!("Failed to parse localization {path:?}"))?;
+
+        Ok(assets.insert(localization))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum DialogueEvent {
+    Started,
+    /// A line started, identified by its node
+    Line(String),
+    /// A gameplay event of a line or choice
+    Event(String),
+    Ended,
+}
+
+struct ActiveDialogue {
+    dialogue: Asset<Dialogue>,
+    node: String,
+    elapsed: f32,
+}
+
+/// Plays a [`Dialogue`] one line at a time.
+///
+/// Stored on the engine entity. Gameplay code starts dialogues through [`Self::start`], and the
+/// [`DialoguePlugin`] advances them from player input.
+#[derive(Default)]
+pub struct DialogueRunner {
+    active: Option<ActiveDialogue>,
+    events: Vec<DialogueEvent>,
+}
+
+impl DialogueRunner {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.active.is_some()
+    }
+
+    /// Starts a dialogue, replacing the current one
+    pub fn start(&mut self, dialogue: Asset<Dialogue>) {
+        if self.active.is_some() {
+            self.events.push(DialogueEvent::Ended);
+        }
+
+        let start = dialogue.start.clone();
+        self.events.push(DialogueEvent::Started);
+        self.enter(dialogue, Some(start));
+    }
+
+    pub fn stop(&mut self) {
+        if self.active.take().is_some() {
+            self.events.push(DialogueEvent::Ended);
+        }
+    }
+
+    /// Returns the current line
+    pub fn current(&self) -> Option<&DialogueNode> {
+        let active = self.active.as_ref()?;
+        active.dialogue.node(&active.node)
+    }
+
+    fn enter(&mut self, dialogue: Asset<Dialogue>, node: Option<String>) {
+        let Some(node) = node.filter(|v| dialogue.nodes.contains_key(v)) else {
+            self.stop();
+            return;
+        };
+
+        let line = &dialogue.nodes[&node];
+        self.events.push(DialogueEvent::Line(node.clone()));
+        if let Some(event) = &line.event {
+            self.events.push(DialogueEvent::Event(event.clone()));
+        }
+
+        self.active = Some(ActiveDialogue {
+            dialogue,
+            node,
+            elapsed: 0.0,
+        });
+    }
+
+    /// Continues to the next line, unless the current line has choices
+    pub fn advance(&mut self) {
+        let Some(active) = &self.active else {
+            return;
+        };
+
+        let dialogue = active.dialogue.clone();
+        let Some(line) = dialogue.node(&active.node) else {
+            return;
+        };
+
+        if line.choices.is_empty() {
+            self.enter(dialogue.clone(), line.next.clone());
+        }
+    }
+
+    /// Selects a choice of the current line
+    pub fn choose(&mut self, index: usize) -> anyhow::Result<()> {
+        let active = self.active.as_ref().context("No active dialogue")?;
+
+        let dialogue = active.dialogue.clone();
+        let choice = dialogue
+            .node(&active.node)
+            .and_then(|v| v.choices.get(index))
+            .with_context(|| format!("No choice {index} in dialogue node {:?}", active.node))?;
+
+        if let Some(event) = &choice.event {
+            self.events.push(DialogueEvent::Event(event.clone()));
+        }
+
+        self.enter(dialogue.clone(), choice.next.clone());
+
+        Ok(())
+    }
+
+    /// Advances timed lines
+    pub fn update(&mut self, dt: f32) {
+        let Some(active) = &mut self.active else {
+            return;
+        };
+
+        active.elapsed += dt;
+
+        let duration = active.dialogue.node(&active.node).and_then(|v| v.duration);
+        if duration.is_some_and(|v| active.elapsed >= v) {
+            self.advance();
+        }
+    }
+
+    /// Returns the events since the last call
+    pub fn drain_events(&mut self) -> impl Iterator<Item = DialogueEvent> + '_ {
+        self.events.drain(..)
+    }
+
+    /// Returns the translated subtitle of the current line
+    pub fn subtitle(&self, localization: Option<&Localization>) -> Option<Subtitle> {
+        let line = self.current()?;
+        let translate = |v: &str| localization.map(|l| l.get(v)).unwrap_or(v).to_string();
+
+        Some(Subtitle {
+            speaker: line.speaker.as_deref().map(translate),
+            text: translate(&line.text),
+            choices: line.choices.iter().map(|v| translate(&v.text)).collect(),
+        })
+    }
+}
+
+/// The text of the current line, as shown by [`subtitles`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct Subtitle {
+    pub speaker: Option<String>,
+    pub text: String,
+    pub choices: Vec<String>,
+}
+
+component! {
+    /// Stored on the engine entity
+    pub dialogue_runner: DialogueRunner,
+    /// Active language used for subtitles, stored on the engine entity
+    pub localization: Asset<Localization>,
+
+    dialogue_advance_action: bool,
+    /// One based index of the pressed choice key
+    dialogue_choice_action: i32,
+}
+
+/// Displays the subtitle of the current line at the bottom of the screen
+pub fn subtitles(subtitle: Mutable<Option<Subtitle>>) -> impl Widget {
+    Stack::new(SignalWidget(subtitle.signal_ref(|subtitle| {
+        subtitle.as_ref().map(|subtitle| {
+            let speaker = subtitle.speaker.iter().map(|v| label(format!("{v}:")));
+            let choices = subtitle
+                .choices
+                .iter()
+                .enumerate()
+                .map(|(i, v)| label(format!("{}. {v}", i + 1)));
+
+            card(col(speaker
+                .chain([label(subtitle.text.clone())])
+                .chain(choices)
+                .collect::<Vec<_>>()))
+        })
+    })))
+    .with_maximize(Vec2::ONE)
+    .with_horizontal_alignment(Align::Center)
+    .with_vertical_alignment(Align::End)
+}
+
+fn dialogue_system(
+    listeners: Vec<flume::Sender<DialogueEvent>>,
+    subtitle: Option<Mutable<Option<Subtitle>>>,
+) -> BoxedSystem {
+    let mut input_query = Query::new((dialogue_advance_action(), dialogue_choice_action()));
+    let mut localization_query = Query::new(localization().modified());
+    let mut prev_choice = 0;
+
+    System::builder()
+        .with_world()
+        .build(move |world: &World| {
+            let dt = world
+                .get(engine(), delta_time())
+                .map(|v| v.as_secs_f32())
+                .unwrap_or_default();
+
+            let mut runner = world.get_mut(engine(), dialogue_runner())?;
+
+            let (advance, choice) = input_query
+                .borrow(world)
+                .first()
+                .map(|(&advance, &choice)| (advance, choice))
+                .unwrap_or_default();
+
+            if advance {
+                runner.advance();
+            }
+
+            if choice != prev_choice && (1..=MAX_CHOICES).contains(&choice) {
+                let has_choice = runner
+                    .current()
+                    .is_some_and(|v| v.choices.len() >= choice as usize);
+
+                if has_choice {
+                    runner.choose(choice as usize - 1)?;
+                }
+            }
+            prev_choice = choice;
+
+            runner.update(dt);
+
+            // Refresh the subtitle when the language changes as well
+            let mut changed = localization_query.borrow(world).iter().next().is_some();
+            for event in runner.drain_events() {
+                changed = true;
+                for tx in &listeners {
+                    let _ = tx.send(event.clone());
+                }
+            }
+
+            if let Some(subtitle) = subtitle.as_ref().filter(|_| changed) {
+                let localization = world.get(engine(), localization()).ok();
+                subtitle.set(runner.subtitle(localization.as_deref().map(|v| &**v)));
+            }
+
+            anyhow::Ok(())
+        })
+        .boxed()
+}
+
+/// Plays dialogues started through the [`dialogue_runner`].
+///
+/// Lines are advanced with `Space`, and choices selected with the number keys. The
+/// [`ManipulatorPlugin`](crate::manipulator::ManipulatorPlugin) ignores its number key modes while
+/// a dialogue is active.
+pub struct DialoguePlugin {
+    listeners: Vec<flume::Sender<DialogueEvent>>,
+    subtitle: Option<Mutable<Option<Subtitle>>>,
+    localization: Option<Asset<Localization>>,
+}
+
+impl DialoguePlugin {
+    pub fn new() -> Self {
+        Self {
+            listeners: Vec::new(),
+            subtitle: None,
+            localization: None,
+        }
+    }
+
+    /// Send dialogue events to the given channel, for gameplay branches
+    pub fn with_listener(mut self, tx: flume::Sender<DialogueEvent>) -> Self {
+        self.listeners.push(tx);
+        self
+    }
+
+    /// Write the current subtitle to the given state, for display with [`subtitles`]
+    pub fn with_subtitles(mut self, subtitle: Mutable<Option<Subtitle>>) -> Self {
+        self.subtitle = Some(subtitle);
+        self
+    }
+
+    /// Set the initial language
+    pub fn with_localization(mut self, localization: Asset<Localization>) -> Self {
+        self.localization = Some(localization);
+        self
+    }
+}
+
+impl Default for DialoguePlugin {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Plugin for DialoguePlugin {
+    fn install(
+        &self,
+        world: &mut World,
+        _: &AssetCache,
+        schedules: &mut ScheduleSetBuilder,
+    ) -> anyhow::Result<()> {
+        world.set(engine(), dialogue_runner(), DialogueRunner::new())?;
+        if let Some(value) = &self.localization {
+            world.set(engine(), localization(), value.clone())?;
+        }
+
+        let mut advance = Action::new();
+        advance.add(KeyBinding::new(Key::Named(NamedKey::Space)).rising_edge());
+
+        let mut choice = Action::new();
+        for i in 1..=MAX_CHOICES {
+            choice.add(
+                KeyBinding::new(Key::Character(i.to_string().into()))
+                    .integral()
+                    .amplitude(i),
+            );
+        }
+
+        Entity::builder()
+            .set(
+                input_state(),
+                InputState::new()
+                    .with_action(dialogue_advance_action(), advance)
+                    .with_action(dialogue_choice_action(), choice),
+            )
+            .set_default(dialogue_advance_action())
+            .set_default(dialogue_choice_action())
+            .spawn(world);
+
+        schedules.per_tick_mut().with_system(dialogue_system(
+            self.listeners.clone(),
+            self.subtitle.clone(),
+        ));
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use flax::Schedule;
+
+    use super::*;
+
+    fn dialogue(assets: &AssetCache) -> Asset<Dialogue> {
+        let dialogue = Dialogue::from_ron(
+            r#"(
+                start: "greeting",
+                nodes: {
+                    "greeting": (
+                        speaker: Some("guard"),
+                        text: "guard.halt",
+                        choices: [
+                            (text: "player.friend", next: Some("pass"), event: Some("open_gate")),
+                            (text: "player.leave"),
+                        ],
+                    ),
+                    "pass": (text: "guard.pass", duration: Some(2.0)),
+                },
+            )"#,
+        )
+        .unwrap();
+
+        assets.insert(dialogue)
+    }
+
+    #[test]
+    fn choices_and_timed_lines() {
+        let assets = AssetCache::new();
+        let mut runner = DialogueRunner::new();
+        runner.start(dialogue(&assets));
+
+        // Lines with choices wait for a choice
+        runner.advance();
+        assert_eq!(runner.current().unwrap().text, "guard.halt");
+        assert!(runner.choose(2).is_err());
+
+        runner.choose(0).unwrap();
+        assert_eq!(runner.current().unwrap().text, "guard.pass");
+
+        runner.update(1.0);
+        assert!(runner.is_active());
+        runner.update(1.0);
+        assert!(!runner.is_active());
+
+        assert_eq!(
+            runner.drain_events().collect::<Vec<_>>(),
+            [
+                DialogueEvent::Started,
+                DialogueEvent::Line("greeting".into()),
+                DialogueEvent::Event("open_gate".into()),
+                DialogueEvent::Line("pass".into()),
+                DialogueEvent::Ended,
+            ]
+        );
+    }
+
+    #[test]
+    fn invalid_reference() {
+        let result = Dialogue::from_ron(
+            r#"(start: "a", nodes: { "a": (text: "a", next: Some("missing")) })"#,
+        );
+
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn subtitles_follow_localization() {
+        let assets = AssetCache::new();
+        let subtitle = Mutable::new(None);
+
+        let mut world = World::new();
+        let mut runner = DialogueRunner::new();
+        runner.start(dialogue(&assets));
+        world.set(engine(), dialogue_runner(), runner).unwrap();
+
+        let mut schedule = Schedule::builder()
+            .with_system(dialogue_system(Vec::new(), Some(subtitle.clone())))
+            .build();
+
+        schedule.execute_seq(&mut world).unwrap();
+        assert_eq!(subtitle.get_cloned().unwrap().text, "guard.halt");
+
+        let english = Localization::new()
+            .with_string("guard", "Guard")
+            .with_string("guard.halt", "Halt!");
+        world
+            .set(engine(), localization(), assets.insert(english))
+            .unwrap();
+
+        schedule.execute_seq(&mut world).unwrap();
+        assert_eq!(
+            subtitle.get_cloned(),
+            Some(Subtitle {
+                speaker: Some("Guard".into()),
+                text: "Halt!".into(),
+                choices: vec!["player.friend".into(), "player.leave".into()],
+            })
+        );
+    }
+}
@@ -0,0 +1,417 @@
+use flax::{
+    components::child_of, entity_ids, BoxedSystem, ComponentMut, Debuggable, Entity, EntityBuilder,
+    FetchExt, Query, QueryBorrow, System, World,
+};
+use glam::{vec3, EulerRot, Quat, Vec2, Vec3};
+use ivy_assets::AssetCache;
+use ivy_core::{
+    components::{
+        delta_time, engine, main_camera, request_capture_mouse, rotation, world_transform,
+        TransformBundle,
+    },
+    update_layer::{Plugin, ScheduleSetBuilder},
+    Bundle, EntityBuilderExt,
+};
+use ivy_input::{
+    components::input_state,
+    types::{Key, NamedKey},
+    Action, Axis2D, BindingExt, CursorMoveBinding, InputState, KeyBinding,
+};
+use ivy_physics::{
+    components::{physics_state, rb_handle, velocity},
+    rapier3d::prelude::{LockedAxes, QueryFilter, Ray, SharedShape},
+    ColliderBundle, RigidBodyBundle,
+};
+use ivy_wgpu::components::projection_matrix;
+
+use crate::camera::{look_input, perspective_camera, PerspectiveCamera};
+
+flax::component! {
+    pub first_person_controller: FirstPersonController,
+    /// The camera attached to a first-person character
+    pub first_person_camera: (),
+
+    pub move_input: Vec2,
+    pub jump_input: bool,
+    pub sprint_input: bool,
+    /// Set if the character is standing on the ground
+    pub grounded: bool => [ Debuggable ],
+}
+
+/// Limits the pitch to avoid flipping over when looking straight up or down
+const MAX_PITCH: f32 = 1.55;
+/// Distance below the feet which still counts as standing on the ground
+const GROUND_TOLERANCE: f32 = 0.1;
+
+/// Movement settings and look direction of a first-person character
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FirstPersonController {
+    pub walk_speed: f32,
+    pub sprint_speed: f32,
+    /// Upwards velocity applied when jumping
+    pub jump_speed: f32,
+    pub acceleration: f32,
+    /// Acceleration while in the air, allowing limited control of the trajectory
+    pub air_acceleration: f32,
+    pub height: f32,
+    pub radius: f32,
+    /// Height of the camera above the feet
+    pub eye_height: f32,
+    pub yaw: f32,
+    pub pitch: f32,
+}
+
+impl FirstPersonController {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the walking and sprinting speeds
+    pub fn with_speed(mut self, walk_speed: f32, sprint_speed: f32) -> Self {
+        self.walk_speed = walk_speed;
+        self.sprint_speed = sprint_speed;
+        self
+    }
+
+    pub fn with_jump_speed(mut self, jump_speed: f32) -> Self {
+        self.jump_speed = jump_speed;
+        self
+    }
+
+    /// Set the ground and air acceleration
+    pub fn with_acceleration(mut self, acceleration: f32, air_acceleration: f32) -> Self {
+        self.acceleration = acceleration;
+        self.air_acceleration = air_acceleration;
+        self
+    }
+
+    /// Set the dimensions of the capsule collider and the height of the camera
+    pub fn with_dimensions(mut self, height: f32, radius: f32, eye_height: f32) -> Self {
+        self.height = height;
+        self.radius = radius;
+        self.eye_height = eye_height;
+        self
+    }
+
+    pub fn look_rotation(&self) -> Quat {
+        Quat::from_euler(EulerRot::YXZ, self.yaw, self.pitch, 0.0)
+    }
+}
+
+impl Default for FirstPersonController {
+    fn default() -> Self {
+        Self {
+            walk_speed: 4.0,
+            sprint_speed: 7.0,
+            jump_speed: 5.0,
+            acceleration: 40.0,
+            air_acceleration: 8.0,
+            height: 1.8,
+            radius: 0.35,
+            eye_height: 1.65,
+            yaw: 0.0,
+            pitch: 0.0,
+        }
+    }
+}
+
+/// A first-person character with a capsule collider, the default `WASD`, mouse look, `Space` to
+/// jump and `Shift` to sprint bindings, and an attached main camera.
+///
+/// Requires the [`FirstPersonPlugin`] and the [`CameraPlugin`](crate::camera::CameraPlugin).
+#[derive(Debug, Clone)]
+pub struct FirstPersonBundle {
+    /// Position of the center of the character
+    pub position: Vec3,
+    pub controller: FirstPersonController,
+    pub camera: PerspectiveCamera,
+}
+
+impl FirstPersonBundle {
+    pub fn new(position: Vec3) -> Self {
+        Self {
+            position,
+            controller: FirstPersonController::default(),
+            camera: PerspectiveCamera::default().with_clip_planes(0.05, 1000.0),
+        }
+    }
+
+    pub fn with_controller(mut self, controller: FirstPersonController) -> Self {
+        self.controller = controller;
+        self
+    }
+
+    pub fn with_camera(mut self, camera: PerspectiveCamera) -> Self {
+        self.camera = camera;
+        self
+    }
+}
+
+fn input() -> InputState {
+    let mut move_action = Action::new();
+    for (key, axis, amplitude) in [
+        ("w", Axis2D::Y, 1.0),
+        ("s", Axis2D::Y, -1.0),
+        ("d", Axis2D::X, 1.0),
+        ("a", Axis2D::X, -1.0),
+    ] {
+        move_action.add(
+            KeyBinding::new(Key::Character(key.into()))
+                .analog()
+                .compose(axis)
+                .amplitude(amplitude),
+        );
+    }
+
+    let mut look_action = Action::new();
+    look_action.add(CursorMoveBinding::new().amplitude(Vec2::ONE * 0.002));
+
+    let mut jump_action = Action::new();
+    jump_action.add(KeyBinding::new(Key::Named(NamedKey::Space)));
+
+    let mut sprint_action = Action::new();
+    sprint_action.add(KeyBinding::new(Key::Named(NamedKey::Shift)));
+
+    InputState::new()
+        .with_action(move_input(), move_action)
+        .with_action(look_input(), look_action)
+        .with_action(jump_input(), jump_action)
+        .with_action(sprint_input(), sprint_action)
+}
+
+impl Bundle for FirstPersonBundle {
+    fn mount(self, entity: &mut EntityBuilder) {
+        let controller = self.controller;
+        let half_height = (controller.height * 0.5 - controller.radius).max(0.0);
+
+        let mut camera = Entity::builder();
+        camera
+            .mount(TransformBundle::new(
+                Vec3::Y * (controller.eye_height - controller.height * 0.5),
+                controller.look_rotation(),
+                Vec3::ONE,
+            ))
+            .set(main_camera(), ())
+            .set(projection_matrix(), self.camera.projection(1.0))
+            .set(perspective_camera(), self.camera)
+            .set(first_person_camera(), ());
+
+        entity
+            .mount(TransformBundle::new(
+                self.position,
+                Quat::IDENTITY,
+                Vec3::ONE,
+            ))
+            .mount(
+                RigidBodyBundle::dynamic()
+                    .with_can_sleep(false)
+                    .with_locked_axes(LockedAxes::ROTATION_LOCKED),
+            )
+            .mount(ColliderBundle::new(SharedShape::capsule_y(
+                half_height,
+                controller.radius,
+            )))
+            .set(first_person_controller(), controller)
+            .set(input_state(), input())
+            .set_default(move_input())
+            .set_default(look_input())
+            .set_default(jump_input())
+            .set_default(sprint_input())
+            .set_default(grounded())
+            .attach(child_of, camera);
+    }
+}
+
+/// Moves the [`FirstPersonBundle`] characters and their cameras, and captures the cursor
+pub struct FirstPersonPlugin;
+
+impl Plugin for FirstPersonPlugin {
+    fn install(
+        &self,
+        _: &mut World,
+        _: &AssetCache,
+        schedules: &mut ScheduleSetBuilder,
+    ) -> anyhow::Result<()> {
+        schedules
+            .per_tick_mut()
+            .with_system(capture_cursor_system())
+            .with_system(look_system());
+
+        schedules.fixed_mut().with_system(movement_system());
+
+        Ok(())
+    }
+}
+
+/// Captures the cursor while a first-person character exists.
+///
+/// The request is only written when a character is spawned or the last one is despawned, which
+/// leaves it to other controllers, such as the free camera, in between.
+fn capture_cursor_system() -> BoxedSystem {
+    let mut active = false;
+
+    System::builder()
+        .with_query(Query::new(()).with(first_person_controller()))
+        .with_query(Query::new(request_capture_mouse().as_mut()))
+        .build(
+            move |mut characters: QueryBorrow<(), _>,
+                  mut capture: QueryBorrow<ComponentMut<bool>>| {
+                let has_character = characters.first().is_some();
+                if has_character == active {
+                    return;
+                }
+
+                if let Some(capture) = capture.first() {
+                    *capture = has_character;
+                    active = has_character;
+                }
+            },
+        )
+        .boxed()
+}
+
+fn look_system() -> BoxedSystem {
+    let mut query = Query::new((
+        entity_ids(),
+        first_person_controller().as_mut(),
+        look_input(),
+    ));
+    let mut looks = Vec::new();
+
+    System::builder()
+        .with_world_mut()
+        .build(move |world: &mut World| {
+            for (id, controller, look) in &mut query.borrow(world) {
+                controller.yaw -= look.x;
+                controller.pitch = (controller.pitch - look.y).clamp(-MAX_PITCH, MAX_PITCH);
+                looks.push((id, controller.look_rotation()));
+            }
+
+            for (id, look) in looks.drain(..) {
+                for rotation in &mut Query::new(rotation().as_mut())
+                    .with(first_person_camera())
+                    .with(child_of(id))
+                    .borrow(world)
+                {
+                    *rotation = look;
+                }
+            }
+        })
+        .boxed()
+}
+
+fn movement_system() -> BoxedSystem {
+    let mut query = Query::new((
+        entity_ids(),
+        first_person_controller(),
+        move_input().copied(),
+        jump_input().copied(),
+        sprint_input().copied(),
+        world_transform(),
+        velocity().as_mut(),
+        grounded().as_mut(),
+    ));
+
+    System::builder()
+        .with_world_mut()
+        .build(move |world: &mut World| {
+            let dt = world
+                .get(engine(), delta_time())
+                .map(|v| v.as_secs_f32())
+                .unwrap_or_default();
+
+            let physics = world.get(engine(), physics_state()).ok();
+
+            for (id, controller, movement, jump, sprint, transform, velocity, grounded) in
+                &mut query.borrow(world)
+            {
+                let position = transform.w_axis.truncate();
+
+                *grounded = physics.as_ref().is_some_and(|physics| {
+                    let mut filter = QueryFilter::default().exclude_sensors();
+                    if let Ok(rb) = world.get_copy(id, rb_handle()) {
+                        filter = filter.exclude_rigid_body(rb);
+                    }
+
+                    let ray = Ray::new(position.into(), (-Vec3::Y).into());
+                    physics
+                        .cast_ray(
+                            &ray,
+                            controller.height * 0.5 + GROUND_TOLERANCE,
+                            true,
+                            filter,
+                        )
+                        .is_some()
+                });
+
+                let speed = if sprint {
+                    controller.sprint_speed
+                } else {
+                    controller.walk_speed
+                };
+
+                let target = Quat::from_rotation_y(controller.yaw)
+                    * vec3(movement.x, 0.0, -movement.y).clamp_length_max(1.0)
+                    * speed;
+
+                let acceleration = if *grounded {
+                    controller.acceleration
+                } else {
+                    controller.air_acceleration
+                };
+
+                // Accelerate towards the target velocity on the horizontal plane, leaving gravity
+                // to the physics
+                let horizontal = vec3(velocity.x, 0.0, velocity.z);
+                let horizontal =
+                    horizontal + (target - horizontal).clamp_length_max(acceleration * dt);
+
+                velocity.x = horizontal.x;
+                velocity.z = horizontal.z;
+
+                if jump && *grounded {
+                    velocity.y = controller.jump_speed;
+                }
+            }
+        })
+        .boxed()
+}
+
+#[cfg(test)]
+mod tests {
+    use flax::Schedule;
+
+    use super::*;
+
+    #[test]
+    fn capture_only_on_change() {
+        let mut world = World::new();
+        let window = Entity::builder()
+            .set(request_capture_mouse(), false)
+            .spawn(&mut world);
+
+        let mut schedule = Schedule::builder()
+            .with_system(capture_cursor_system())
+            .build();
+
+        schedule.execute_seq(&mut world).unwrap();
+        assert!(!*world.get(window, request_capture_mouse()).unwrap());
+
+        let character = Entity::builder()
+            .set(first_person_controller(), FirstPersonController::default())
+            .spawn(&mut world);
+
+        schedule.execute_seq(&mut world).unwrap();
+        assert!(*world.get(window, request_capture_mouse()).unwrap());
+
+        // Released by another controller, such as the free camera
+        world.set(window, request_capture_mouse(), false).unwrap();
+        schedule.execute_seq(&mut world).unwrap();
+        assert!(!*world.get(window, request_capture_mouse()).unwrap());
+
+        world.set(window, request_capture_mouse(), true).unwrap();
+        world.despawn(character).unwrap();
+        schedule.execute_seq(&mut world).unwrap();
+        assert!(!*world.get(window, request_capture_mouse()).unwrap());
+    }
+}
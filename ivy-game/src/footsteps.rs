@@ -0,0 +1,330 @@
+use std::{borrow::Cow, collections::BTreeMap};
+
+use flax::{component, entity_ids, BoxedSystem, Entity, Query, System, World};
+use glam::Vec3;
+use ivy_assets::AssetCache;
+use ivy_core::{
+    components::{engine, world_transform},
+    update_layer::{Plugin, ScheduleSetBuilder},
+};
+use ivy_gltf::components::animator;
+use ivy_physics::{
+    components::{physics_state, rb_handle},
+    rapier3d::prelude::{QueryFilter, Ray},
+    state::PhysicsState,
+    surface::{query_surface, SurfaceHit, SurfaceType},
+};
+use ivy_wgpu::{components::forward_pass, material_desc::MaterialData};
+
+/// Sounds and particles played when stepping on a surface.
+///
+/// Both refer to assets, and are resolved by whoever handles [`FootstepEvent`]s.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SurfaceEffects {
+    /// Variations of the footstep sound, played in turn
+    pub sounds: Vec<String>,
+    pub particles: Option<String>,
+}
+
+impl SurfaceEffects {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a sound variation
+    pub fn with_sound(mut self, sound: impl Into<String>) -> Self {
+        self.sounds.push(sound.into());
+        self
+    }
+
+    /// Set the particles
+    pub fn with_particles(mut self, particles: impl Into<String>) -> Self {
+        self.particles = Some(particles.into());
+        self
+    }
+}
+
+/// Maps surfaces to their effects.
+///
+/// Colliders without a [`surface_type`](ivy_physics::components::surface_type) are resolved
+/// through the label of their material, which allows tagging the surfaces of imported levels.
+#[derive(Debug, Clone, Default)]
+pub struct SurfaceRegistry {
+    materials: BTreeMap<String, SurfaceType>,
+    effects: BTreeMap<SurfaceType, SurfaceEffects>,
+}
+
+impl SurfaceRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Tag all colliders using the material with the given label
+    pub fn with_material(
+        mut self,
+        label: impl Into<String>,
+        surface: impl Into<SurfaceType>,
+    ) -> Self {
+        self.materials.insert(label.into(), surface.into());
+        self
+    }
+
+    /// Set the effects of a surface.
+    ///
+    /// The effects of [`SurfaceType::DEFAULT`] are used for surfaces without effects.
+    pub fn with_effects(
+        mut self,
+        surface: impl Into<SurfaceType>,
+        effects: SurfaceEffects,
+    ) -> Self {
+        self.effects.insert(surface.into(), effects);
+        self
+    }
+
+    pub fn effects(&self, surface: &SurfaceType) -> Option<&SurfaceEffects> {
+        self.effects
+            .get(surface)
+            .or_else(|| self.effects.get(&SurfaceType::DEFAULT))
+    }
+
+    fn material_surface(&self, world: &World, id: Entity) -> Option<SurfaceType> {
+        let material = world.get(id, forward_pass()).ok()?;
+
+        let label = match &*material {
+            MaterialData::PbrMaterial(v) | MaterialData::UnlitMaterial(v) => v.label(),
+            _ => return None,
+        };
+
+        self.materials.get(label).cloned()
+    }
+
+    /// Resolves the surface of a hit from the collider tags, falling back to the material of the
+    /// collider or rigid body
+    pub fn resolve(&self, world: &World, hit: &SurfaceHit) -> SurfaceType {
+        hit.surface
+            .clone()
+            .or_else(|| self.material_surface(world, hit.hit.collider_id))
+            .or_else(|| self.material_surface(world, hit.hit.rigidbody_id))
+            .unwrap_or_default()
+    }
+
+    /// Returns the surface below `origin`, ignoring the rigid body of `exclude`
+    pub fn surface_below(
+        &self,
+        world: &World,
+        state: &PhysicsState,
+        origin: Vec3,
+        max_dist: f32,
+        exclude: Option<Entity>,
+    ) -> Option<(SurfaceType, SurfaceHit)> {
+        let mut filter = QueryFilter::default().exclude_sensors();
+        if let Some(rb) = exclude.and_then(|id| world.get(id, rb_handle()).ok().map(|v| *v)) {
+            filter = filter.exclude_rigid_body(rb);
+        }
+
+        let ray = Ray::new(origin.into(), (-Vec3::Y).into());
+        let hit = query_surface(world, state, &ray, max_dist, filter)?;
+
+        Some((self.resolve(world, &hit), hit))
+    }
+}
+
+/// Emits a [`FootstepEvent`] when the animation of the entity fires the footstep event
+#[derive(Debug, Clone, PartialEq)]
+pub struct FootstepEmitter {
+    /// Name of the animation event
+    pub event: Cow<'static, str>,
+    /// Distance above the entity origin to start probing for the ground
+    pub probe_offset: f32,
+    /// Distance below the entity origin to probe for the ground
+    pub probe_length: f32,
+    /// Number of steps taken, used to alternate between the sound variations
+    step: usize,
+}
+
+impl Default for FootstepEmitter {
+    fn default() -> Self {
+        Self {
+            event: "footstep".into(),
+            probe_offset: 0.5,
+            probe_length: 0.5,
+            step: 0,
+        }
+    }
+}
+
+impl FootstepEmitter {
+    pub fn new(event: impl Into<Cow<'static, str>>) -> Self {
+        Self {
+            event: event.into(),
+            ..Default::default()
+        }
+    }
+
+    /// Set the probe length
+    pub fn with_probe_length(mut self, probe_length: f32) -> Self {
+        self.probe_length = probe_length;
+        self
+    }
+
+    /// Returns the next sound variation of the surface, so that consecutive steps do not repeat
+    /// the same sound
+    fn next_sound(&mut self, effects: &SurfaceEffects) -> Option<String> {
+        if effects.sounds.is_empty() {
+            return None;
+        }
+
+        let sound = effects.sounds[self.step % effects.sounds.len()].clone();
+        self.step = self.step.wrapping_add(1);
+        Some(sound)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct FootstepEvent {
+    pub entity: Entity,
+    pub surface: SurfaceType,
+    pub position: Vec3,
+    pub normal: Vec3,
+    pub sound: Option<String>,
+    pub particles: Option<String>,
+}
+
+component! {
+    /// Stored on the engine entity
+    pub surface_registry: SurfaceRegistry,
+    pub footstep_emitter: FootstepEmitter,
+}
+
+fn footstep_system(listeners: Vec<flume::Sender<FootstepEvent>>) -> BoxedSystem {
+    System::builder()
+        .with_world()
+        .build(move |world: &World| {
+            let (Ok(state), Ok(registry)) = (
+                world.get(engine(), physics_state()),
+                world.get(engine(), surface_registry()),
+            ) else {
+                return Ok(());
+            };
+
+            let mut query = Query::new((
+                entity_ids(),
+                world_transform(),
+                animator(),
+                footstep_emitter().as_mut(),
+            ));
+
+            for (id, transform, animator, emitter) in &mut query.borrow(world) {
+                if !animator.events().iter().any(|v| v.name == emitter.event) {
+                    continue;
+                }
+
+                let origin =
+                    transform.transform_point3(Vec3::ZERO) + Vec3::Y * emitter.probe_offset;
+                let Some((surface, hit)) = registry.surface_below(
+                    world,
+                    &state,
+                    origin,
+                    emitter.probe_offset + emitter.probe_length,
+                    Some(id),
+                ) else {
+                    continue;
+                };
+
+                let effects = registry.effects(&surface);
+                let sound = effects.and_then(|v| emitter.next_sound(v));
+
+                let event = FootstepEvent {
+                    entity: id,
+                    surface,
+                    position: hit.point,
+                    normal: hit.normal,
+                    sound,
+                    particles: effects.and_then(|v| v.particles.clone()),
+                };
+
+                for tx in &listeners {
+                    let _ = tx.send(event.clone());
+                }
+            }
+
+            anyhow::Ok(())
+        })
+        .boxed()
+}
+
+/// Emits [`FootstepEvent`]s with the effects of the surface below each [`footstep_emitter`]
+pub struct FootstepPlugin {
+    registry: SurfaceRegistry,
+    listeners: Vec<flume::Sender<FootstepEvent>>,
+}
+
+impl FootstepPlugin {
+    pub fn new(registry: SurfaceRegistry) -> Self {
+        Self {
+            registry,
+            listeners: Vec::new(),
+        }
+    }
+
+    /// Send footstep events to the given channel, e.g; for audio playback
+    pub fn with_listener(mut self, tx: flume::Sender<FootstepEvent>) -> Self {
+        self.listeners.push(tx);
+        self
+    }
+}
+
+impl Plugin for FootstepPlugin {
+    fn install(
+        &self,
+        world: &mut World,
+        _: &AssetCache,
+        schedules: &mut ScheduleSetBuilder,
+    ) -> anyhow::Result<()> {
+        world.set(engine(), surface_registry(), self.registry.clone())?;
+
+        schedules
+            .per_tick_mut()
+            .with_system(footstep_system(self.listeners.clone()));
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn sound_variations() {
+        let effects = SurfaceEffects::new().with_sound("a").with_sound("b");
+
+        let mut first = FootstepEmitter::default();
+        let mut second = FootstepEmitter::default();
+
+        assert_eq!(first.next_sound(&effects).as_deref(), Some("a"));
+        assert_eq!(first.next_sound(&effects).as_deref(), Some("b"));
+
+        // Each emitter alternates on its own
+        assert_eq!(second.next_sound(&effects).as_deref(), Some("a"));
+        assert_eq!(first.next_sound(&effects).as_deref(), Some("a"));
+
+        assert_eq!(first.next_sound(&SurfaceEffects::new()), None);
+    }
+
+    #[test]
+    fn registry_fallback() {
+        let registry = SurfaceRegistry::new()
+            .with_effects(
+                SurfaceType::DEFAULT,
+                SurfaceEffects::new().with_sound("step"),
+            )
+            .with_effects("grass", SurfaceEffects::new().with_sound("rustle"));
+
+        let grass = registry.effects(&"grass".into()).unwrap();
+        assert_eq!(grass.sounds, ["rustle"]);
+
+        let stone = registry.effects(&"stone".into()).unwrap();
+        assert_eq!(stone.sounds, ["step"]);
+    }
+}
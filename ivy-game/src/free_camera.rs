@@ -21,6 +21,11 @@ use ivy_physics::{
 };
 use ivy_wgpu::components::projection_matrix;
 
+use crate::{
+    camera::{perspective_camera, PerspectiveCamera},
+    config::Config,
+};
+
 flax::component! {
     pub pan_active: bool,
     pub rotation_input: Vec2,
@@ -30,6 +35,9 @@ flax::component! {
     pub camera_speed_delta: f32,
 }
 
+/// Flies the camera created by [`setup_camera`], with the scroll wheel adjusting the speed.
+///
+/// The projection is updated by the [`CameraPlugin`](crate::camera::CameraPlugin).
 pub struct FreeCameraPlugin;
 
 impl Plugin for FreeCameraPlugin {
@@ -50,7 +58,63 @@ impl Plugin for FreeCameraPlugin {
     }
 }
 
+/// Keys of the free camera.
+///
+/// Each field can be rebound through the [`Config`] using an action of the same name.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FreeCameraBindings {
+    pub forward: Vec<Key>,
+    pub back: Vec<Key>,
+    pub left: Vec<Key>,
+    pub right: Vec<Key>,
+    pub up: Vec<Key>,
+    pub down: Vec<Key>,
+    pub pan: Vec<Key>,
+}
+
+impl FreeCameraBindings {
+    /// Returns the default bindings, replaced by the actions bound in `config`
+    pub fn from_config(config: &Config) -> anyhow::Result<Self> {
+        let mut bindings = Self::default();
+
+        for (action, keys) in [
+            ("forward", &mut bindings.forward),
+            ("back", &mut bindings.back),
+            ("left", &mut bindings.left),
+            ("right", &mut bindings.right),
+            ("up", &mut bindings.up),
+            ("down", &mut bindings.down),
+            ("pan", &mut bindings.pan),
+        ] {
+            if config.bindings.contains_key(action) {
+                *keys = config.keys(action)?;
+            }
+        }
+
+        Ok(bindings)
+    }
+}
+
+impl Default for FreeCameraBindings {
+    fn default() -> Self {
+        Self {
+            forward: vec![Key::Character("w".into())],
+            back: vec![Key::Character("s".into())],
+            left: vec![Key::Character("a".into())],
+            right: vec![Key::Character("d".into())],
+            up: vec![Key::Named(NamedKey::Space)],
+            down: vec![Key::Character("c".into())],
+            pan: vec![Key::Character("q".into())],
+        }
+    }
+}
+
 pub fn setup_camera() -> flax::EntityBuilder {
+    setup_camera_with(&FreeCameraBindings::default())
+}
+
+/// Creates the free camera with the given key bindings
+pub fn setup_camera_with(bindings: &FreeCameraBindings) -> flax::EntityBuilder {
     let mut speed_action = Action::new();
     speed_action.add(
         CompositeBinding::new(ScrollBinding::new(), [KeyBinding::new(NamedKey::Shift)])
@@ -58,56 +122,35 @@ pub fn setup_camera() -> flax::EntityBuilder {
     );
 
     let mut move_action = Action::<Vec3>::new();
-    move_action.add(
-        KeyBinding::new(Key::Character("w".into()))
-            .analog()
-            .compose(Axis3D::Z),
-    );
-    move_action.add(
-        KeyBinding::new(Key::Character("a".into()))
-            .analog()
-            .compose(Axis3D::X)
-            .amplitude(-1.0),
-    );
-    move_action.add(
-        KeyBinding::new(Key::Character("s".into()))
-            .analog()
-            .compose(Axis3D::Z)
-            .amplitude(-1.0),
-    );
-    move_action.add(
-        KeyBinding::new(Key::Character("d".into()))
-            .analog()
-            .compose(Axis3D::X),
-    );
-
-    move_action.add(
-        KeyBinding::new(Key::Character("c".into()))
-            .analog()
-            .compose(Axis3D::Y)
-            .amplitude(-1.0),
-    );
-    // move_action.add(
-    //     KeyBinding::new(Key::Named(NamedKey::Control))
-    //         .analog()
-    //         .compose(Axis3D::Y)
-    //         .amplitude(-1.0),
-    // );
-    move_action.add(
-        KeyBinding::new(Key::Named(NamedKey::Space))
-            .analog()
-            .compose(Axis3D::Y),
-    );
+    for (keys, axis, amplitude) in [
+        (&bindings.forward, Axis3D::Z, 1.0),
+        (&bindings.back, Axis3D::Z, -1.0),
+        (&bindings.left, Axis3D::X, -1.0),
+        (&bindings.right, Axis3D::X, 1.0),
+        (&bindings.up, Axis3D::Y, 1.0),
+        (&bindings.down, Axis3D::Y, -1.0),
+    ] {
+        for key in keys {
+            move_action.add(
+                KeyBinding::new(key.clone())
+                    .analog()
+                    .compose(axis)
+                    .amplitude(amplitude),
+            );
+        }
+    }
 
     let mut rotate_action = Action::new();
     rotate_action.add(CursorMoveBinding::new().amplitude(Vec2::ONE * 0.001));
 
     let mut pan_action = Action::new();
-    pan_action
-        .add(KeyBinding::new(Key::Character("q".into())))
-        .add(MouseButtonBinding::new(
-            ivy_input::types::MouseButton::Right,
-        ));
+    for key in &bindings.pan {
+        pan_action.add(KeyBinding::new(key.clone()));
+    }
+
+    pan_action.add(MouseButtonBinding::new(
+        ivy_input::types::MouseButton::Right,
+    ));
 
     let mut builder = Entity::builder();
     builder
@@ -119,6 +162,7 @@ pub fn setup_camera() -> flax::EntityBuilder {
         .mount(RigidBodyBundle::new(RigidBodyType::Dynamic).with_can_sleep(false))
         .set(main_camera(), ())
         .set_default(projection_matrix())
+        .set(perspective_camera(), PerspectiveCamera::default())
         .set_default(velocity())
         .set_default(angular_velocity())
         .set(
@@ -198,3 +242,27 @@ fn camera_movement_input_system() -> BoxedSystem {
         })
         .boxed()
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn bindings_from_config() {
+        let mut config = Config::default();
+        config.set_keys("forward", ["ArrowUp", "w"]);
+        config.set_keys("pan", Vec::<String>::new());
+
+        let bindings = FreeCameraBindings::from_config(&config).unwrap();
+
+        assert_eq!(
+            bindings.forward,
+            [Key::Named(NamedKey::ArrowUp), Key::Character("w".into())]
+        );
+        assert!(bindings.pan.is_empty());
+        assert_eq!(bindings.back, FreeCameraBindings::default().back);
+
+        config.set_keys("up", ["NotAKey"]);
+        assert!(FreeCameraBindings::from_config(&config).is_err());
+    }
+}
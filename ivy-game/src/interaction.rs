@@ -0,0 +1,341 @@
+use std::sync::Arc;
+
+use flax::{
+    component,
+    components::child_of,
+    entity_ids,
+    fetch::{entity_refs, EntityIds, EntityRefs, Source},
+    BoxedSystem, CommandBuffer, Component, ComponentMut, Entity, FetchExt, Query, QueryBorrow,
+    System, World,
+};
+use glam::{Mat4, Vec3};
+use ivy_assets::AssetCache;
+use ivy_core::{
+    components::{engine, main_camera, world_transform},
+    update_layer::{Plugin, ScheduleSetBuilder},
+};
+use ivy_input::{components::input_state, types::Key, Action, InputState, KeyBinding};
+use ivy_physics::{
+    components::{physics_state, rb_handle},
+    rapier3d::prelude::{QueryFilter, Ray, RigidBodyHandle},
+    state::PhysicsState,
+};
+
+use crate::camera::follow_target;
+
+/// Invoked with the interacted entity when the player interacts with it
+pub type InteractFn = Arc<dyn Send + Sync + Fn(&World, &mut CommandBuffer, Entity)>;
+
+/// Allows the player to interact with an entity by looking at it
+#[derive(Clone)]
+pub struct Interactable {
+    /// Shown to the player while the entity is focused, e.g; `"Open door"`
+    pub prompt: String,
+    /// Maximum distance from the camera
+    pub range: f32,
+    pub on_interact: Option<InteractFn>,
+}
+
+impl Interactable {
+    pub fn new(prompt: impl Into<String>) -> Self {
+        Self {
+            prompt: prompt.into(),
+            range: 2.0,
+            on_interact: None,
+        }
+    }
+
+    /// Set the range
+    pub fn with_range(mut self, range: f32) -> Self {
+        self.range = range;
+        self
+    }
+
+    /// Set the callback invoked on interaction
+    pub fn on_interact(
+        mut self,
+        on_interact: impl 'static + Send + Sync + Fn(&World, &mut CommandBuffer, Entity),
+    ) -> Self {
+        self.on_interact = Some(Arc::new(on_interact));
+        self
+    }
+}
+
+impl std::fmt::Debug for Interactable {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Interactable")
+            .field("prompt", &self.prompt)
+            .field("range", &self.range)
+            .finish_non_exhaustive()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InteractionEvent {
+    Focus(Entity),
+    Unfocus(Entity),
+    Interact(Entity),
+}
+
+pub struct InteractionState {
+    focused: Option<Entity>,
+    prev_interact: bool,
+    listeners: Vec<flume::Sender<InteractionEvent>>,
+}
+
+impl InteractionState {
+    pub fn new() -> Self {
+        Self {
+            focused: None,
+            prev_interact: false,
+            listeners: Vec::new(),
+        }
+    }
+
+    /// Returns the currently focused interactable
+    pub fn focused(&self) -> Option<Entity> {
+        self.focused
+    }
+
+    fn send(&self, event: InteractionEvent) {
+        for tx in &self.listeners {
+            let _ = tx.send(event);
+        }
+    }
+
+    /// Finds the interactable the camera is looking at.
+    ///
+    /// Colliders attached to an interactable rigid body also count as the interactable.
+    ///
+    /// The body of the player viewing through `camera` is ignored.
+    fn pick(
+        &self,
+        world: &World,
+        physics_state: &PhysicsState,
+        camera: Entity,
+        origin: Vec3,
+        dir: Vec3,
+        max_range: f32,
+    ) -> Option<Entity> {
+        let mut filter = QueryFilter::default().exclude_sensors();
+        if let Some(rb) = viewer_body(world, camera) {
+            filter = filter.exclude_rigid_body(rb);
+        }
+
+        let ray = Ray::new(origin.into(), dir.into());
+        let hit = physics_state.cast_ray(&ray, max_range, true, filter)?;
+
+        [hit.collider_id, hit.rigidbody_id]
+            .into_iter()
+            .find_map(|id| {
+                let interactable = world.get(id, interactable()).ok()?;
+                (hit.intersection.time_of_impact <= interactable.range).then_some(id)
+            })
+    }
+
+    fn set_focus(&mut self, focused: Option<Entity>) {
+        if self.focused == focused {
+            return;
+        }
+
+        if let Some(prev) = self.focused {
+            self.send(InteractionEvent::Unfocus(prev));
+        }
+
+        if let Some(id) = focused {
+            self.send(InteractionEvent::Focus(id));
+        }
+
+        self.focused = focused;
+    }
+}
+
+impl Default for InteractionState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Returns the rigid body of the player viewing through the camera.
+///
+/// This is the first rigid body among the camera and its parents, such as for a first-person
+/// camera attached to the character, or else the body of the [`follow_target`].
+fn viewer_body(world: &World, camera: Entity) -> Option<RigidBodyHandle> {
+    let mut current = Some(camera);
+    while let Some(id) = current {
+        let entity = world.entity(id).ok()?;
+        if let Ok(rb) = entity.get_copy(rb_handle()) {
+            return Some(rb);
+        }
+
+        current = entity.relations(child_of).next().map(|(parent, _)| parent);
+    }
+
+    let target = world.get_copy(camera, follow_target()).ok()?;
+    world.get_copy(target, rb_handle()).ok()
+}
+
+component! {
+    pub interactable: Interactable,
+    pub interaction_state: InteractionState,
+
+    interact_action: bool,
+}
+
+/// Returns the prompt of the focused interactable, for display in the UI
+pub fn focused_prompt(world: &World) -> Option<String> {
+    let id = Query::new(interaction_state())
+        .borrow(world)
+        .first()
+        .and_then(|v| v.focused())?;
+
+    world.get(id, interactable()).ok().map(|v| v.prompt.clone())
+}
+
+type InteractionQuery = (
+    Source<Component<PhysicsState>, Entity>,
+    Source<(Component<()>, EntityIds, Component<Mat4>), ()>,
+    (EntityRefs, Component<bool>, ComponentMut<InteractionState>),
+);
+
+fn interaction_system(max_range: f32) -> BoxedSystem {
+    System::builder()
+        .with_cmd_mut()
+        .with_query(Query::new((
+            physics_state().source(engine()),
+            (main_camera(), entity_ids(), world_transform()).source(()),
+            (
+                entity_refs(),
+                interact_action(),
+                interaction_state().as_mut(),
+            ),
+        )))
+        .build(
+            move |cmd: &mut CommandBuffer, mut query: QueryBorrow<'_, InteractionQuery>| {
+                for (physics_state, (_, camera, camera_transform), (entity, &interact, state)) in
+                    query.iter()
+                {
+                    let world = entity.world();
+
+                    let origin = camera_transform.transform_point3(Vec3::ZERO);
+                    let dir = camera_transform.transform_vector3(-Vec3::Z).normalize();
+
+                    let focused = state.pick(world, physics_state, camera, origin, dir, max_range);
+                    state.set_focus(focused);
+
+                    let pressed = interact && !state.prev_interact;
+                    state.prev_interact = interact;
+
+                    let Some(id) = state.focused.filter(|_| pressed) else {
+                        continue;
+                    };
+
+                    state.send(InteractionEvent::Interact(id));
+
+                    let callback = world
+                        .get(id, interactable())
+                        .ok()
+                        .and_then(|v| v.on_interact.clone());
+
+                    if let Some(callback) = callback {
+                        callback(world, cmd, id);
+                    }
+                }
+
+                anyhow::Ok(())
+            },
+        )
+        .boxed()
+}
+
+/// Focuses the [`interactable`] the main camera is looking at, and interacts with it when `E` is
+/// pressed
+pub struct InteractionPlugin {
+    max_range: f32,
+    listeners: Vec<flume::Sender<InteractionEvent>>,
+}
+
+impl InteractionPlugin {
+    pub fn new() -> Self {
+        Self {
+            max_range: 10.0,
+            listeners: Vec::new(),
+        }
+    }
+
+    /// Set the maximum range of any interactable
+    pub fn with_max_range(mut self, max_range: f32) -> Self {
+        self.max_range = max_range;
+        self
+    }
+
+    /// Send focus and interaction events to the given channel
+    pub fn with_listener(mut self, tx: flume::Sender<InteractionEvent>) -> Self {
+        self.listeners.push(tx);
+        self
+    }
+}
+
+impl Default for InteractionPlugin {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Plugin for InteractionPlugin {
+    fn install(
+        &self,
+        world: &mut World,
+        _: &AssetCache,
+        schedules: &mut ScheduleSetBuilder,
+    ) -> anyhow::Result<()> {
+        let mut interact = Action::new();
+        interact.add(KeyBinding::new(Key::Character("e".into())));
+
+        let mut state = InteractionState::new();
+        state.listeners = self.listeners.clone();
+
+        Entity::builder()
+            .set(
+                input_state(),
+                InputState::new().with_action(interact_action(), interact),
+            )
+            .set_default(interact_action())
+            .set(interaction_state(), state)
+            .spawn(world);
+
+        schedules
+            .per_tick_mut()
+            .with_system(interaction_system(self.max_range));
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn viewer_bodies() {
+        let mut world = World::new();
+        let rb = RigidBodyHandle::from_raw_parts(3, 0);
+
+        let player = Entity::builder().set(rb_handle(), rb).spawn(&mut world);
+        let head = Entity::builder()
+            .set(child_of(player), ())
+            .spawn(&mut world);
+        let first_person = Entity::builder().set(child_of(head), ()).spawn(&mut world);
+
+        assert_eq!(viewer_body(&world, first_person), Some(rb));
+
+        let third_person = Entity::builder()
+            .set(follow_target(), player)
+            .spawn(&mut world);
+
+        assert_eq!(viewer_body(&world, third_person), Some(rb));
+
+        let free = Entity::builder().spawn(&mut world);
+        assert_eq!(viewer_body(&world, free), None);
+    }
+}
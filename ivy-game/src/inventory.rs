@@ -0,0 +1,525 @@
+use std::{collections::BTreeMap, path::Path};
+
+use anyhow::Context;
+use flax::{component, entity_ids, BoxedSystem, Entity, Query, System, World};
+use ivy_assets::{
+    fs::{AssetFromPath, AsyncAssetFromPath},
+    service::FileSystemMapService,
+    Asset, AssetCache,
+};
+use ivy_core::{
+    components::engine,
+    update_layer::{Plugin, ScheduleSetBuilder},
+};
+use serde::{Deserialize, Serialize};
+
+fn default_max_stack() -> u32 {
+    1
+}
+
+/// Describes a type of item.
+///
+/// Loaded from RON, either on its own or as part of an [`ItemDatabase`]:
+///
+/// ```ron
+/// (
+///     id: "health_potion",
+///     name: "Health Potion",
+///     icon: Some("textures/icons/health_potion.png"),
+///     prefab: Some("models/health_potion.glb"),
+///     max_stack: 10,
+/// )
+/// ```
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ItemDef {
+    /// Unique identifier, used to refer to the item from inventories and saves
+    pub id: String,
+    /// User facing name
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    /// Path to the icon shown in the UI
+    #[serde(default)]
+    pub icon: Option<String>,
+    /// Path to the model spawned when the item is dropped in the world
+    #[serde(default)]
+    pub prefab: Option<String>,
+    /// Maximum number of items per inventory slot
+    #[serde(default = "default_max_stack")]
+    pub max_stack: u32,
+    /// Arbitrary game defined properties, such as damage or weight
+    #[serde(default)]
+    pub properties: BTreeMap<String, String>,
+}
+
+impl ItemDef {
+    pub fn new(id: impl Into<String>, name: impl Into<String>) -> Self {
+        Self {
+            id: id.into(),
+            name: name.into(),
+            description: String::new(),
+            icon: None,
+            prefab: None,
+            max_stack: default_max_stack(),
+            properties: BTreeMap::new(),
+        }
+    }
+
+    /// Set the description
+    pub fn with_description(mut self, description: impl Into<String>) -> Self {
+        self.description = description.into();
+        self
+    }
+
+    /// Set the icon
+    pub fn with_icon(mut self, icon: impl Into<String>) -> Self {
+        self.icon = Some(icon.into());
+        self
+    }
+
+    /// Set the prefab
+    pub fn with_prefab(mut self, prefab: impl Into<String>) -> Self {
+        self.prefab = Some(prefab.into());
+        self
+    }
+
+    /// Set the max stack
+    pub fn with_max_stack(mut self, max_stack: u32) -> Self {
+        self.max_stack = max_stack;
+        self
+    }
+
+    /// Set a game defined property
+    pub fn with_property(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.properties.insert(key.into(), value.into());
+        self
+    }
+}
+
+/// All item definitions of a game, keyed by id.
+///
+/// Loaded from a RON list of [`ItemDef`]s.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ItemDatabase {
+    items: BTreeMap<String, ItemDef>,
+}
+
+impl ItemDatabase {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add or replace an item
+    pub fn with_item(mut self, item: ItemDef) -> Self {
+        self.insert(item);
+        self
+    }
+
+    pub fn insert(&mut self, item: ItemDef) {
+        self.items.insert(item.id.clone(), item);
+    }
+
+    pub fn get(&self, id: &str) -> Option<&ItemDef> {
+        self.items.get(id)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &ItemDef> {
+        self.items.values()
+    }
+
+    pub fn from_ron(source: &str) -> anyhow::Result<Self> {
+        let items: Vec<ItemDef> = ron::from_str(source)?;
+
+        let mut database = Self::new();
+        for item in items {
+            if database.items.contains_key(&item.id) {
+                anyhow::bail!("Duplicate item id {:?}", item.id);
+            }
+
+            database.insert(item);
+        }
+
+        Ok(database)
+    }
+}
+
+impl AssetFromPath for ItemDef {
+    type Error = anyhow::Error;
+
+    fn load_from_path(path: &Path, assets: &AssetCache) -> anyhow::Result<Asset<Self>> {
+        let source = assets.service::<FileSystemMapService>().load_string(path)?;
+        let item = ron::from_str(&source)
+            .with_context(|| format!("Failed to parse item definition {path:?}"))?;
+
+        Ok(assets.insert(item))
+    }
+}
+
+impl AsyncAssetFromPath for ItemDef {
+    type Error = anyhow::Error;
+
+    async fn load_from_path(path: &Path, assets: &AssetCache) -> anyhow::Result<Asset<Self>> {
+        let source = assets
+            .service::<FileSystemMapService>()
+            .load_string_async(path)
+            .await?;
+        let item = ron::from_str(&source)
+            .with_context(|| format!("Failed to parse item definition {path:?}"))?;
+
+        Ok(assets.insert(item))
+    }
+}
+
+impl AssetFromPath for ItemDatabase {
+    type Error = anyhow::Error;
+
+    fn load_from_path(path: &Path, assets: &AssetCache) -> anyhow::Result<Asset<Self>> {
+        let source = assets.service::<FileSystemMapService>().load_string(path)?;
+        let database = ItemDatabase::from_ron(&source)
+            .with_context(|| format!("Failed to load item database {path:?}"))?;
+
+        Ok(assets.insert(database))
+    }
+}
+
+impl AsyncAssetFromPath for ItemDatabase {
+    type Error = anyhow::Error;
+
+    async fn load_from_path(path: &Path, assets: &AssetCache) -> anyhow::Result<Asset<Self>> {
+        let source = assets
+            .service::<FileSystemMapService>()
+            .load_string_async(path)
+            .await?;
+        let database = ItemDatabase::from_ron(&source)
+            .with_context(|| format!("Failed to load item database {path:?}"))?;
+
+        Ok(assets.insert(database))
+    }
+}
+
+/// A number of items of the same type
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ItemStack {
+    pub item: String,
+    pub count: u32,
+}
+
+impl ItemStack {
+    pub fn new(item: impl Into<String>, count: u32) -> Self {
+        Self {
+            item: item.into(),
+            count,
+        }
+    }
+}
+
+/// A change to an [`Inventory`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum InventoryChange {
+    Added { item: String, count: u32 },
+    Removed { item: String, count: u32 },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InventoryEvent {
+    pub entity: Entity,
+    pub change: InventoryChange,
+}
+
+/// A fixed number of slots each holding a stack of items.
+///
+/// Changes are recorded and sent as [`InventoryEvent`]s by the [`InventoryPlugin`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Inventory {
+    slots: Vec<Option<ItemStack>>,
+    #[serde(skip)]
+    changes: Vec<InventoryChange>,
+}
+
+impl Inventory {
+    pub fn new(slot_count: usize) -> Self {
+        Self {
+            slots: vec![None; slot_count],
+            changes: Vec::new(),
+        }
+    }
+
+    pub fn slots(&self) -> &[Option<ItemStack>] {
+        &self.slots
+    }
+
+    pub fn slot(&self, index: usize) -> Option<&ItemStack> {
+        self.slots.get(index)?.as_ref()
+    }
+
+    /// Returns the total number of the given item across all slots
+    pub fn count(&self, item: &str) -> u32 {
+        self.slots
+            .iter()
+            .flatten()
+            .filter(|v| v.item == item)
+            .map(|v| v.count)
+            .sum()
+    }
+
+    pub fn contains(&self, item: &str, count: u32) -> bool {
+        self.count(item) >= count
+    }
+
+    /// Adds items, filling existing stacks before empty slots.
+    ///
+    /// Returns the number of items which did not fit.
+    pub fn add(&mut self, item: &ItemDef, count: u32) -> u32 {
+        let max_stack = item.max_stack.max(1);
+        let mut remaining = count;
+
+        for stack in self.slots.iter_mut().flatten() {
+            if remaining == 0 {
+                break;
+            }
+
+            if stack.item == item.id && stack.count < max_stack {
+                let added = remaining.min(max_stack - stack.count);
+                stack.count += added;
+                remaining -= added;
+            }
+        }
+
+        for slot in &mut self.slots {
+            if remaining == 0 {
+                break;
+            }
+
+            if slot.is_none() {
+                let added = remaining.min(max_stack);
+                *slot = Some(ItemStack::new(item.id.clone(), added));
+                remaining -= added;
+            }
+        }
+
+        if remaining < count {
+            self.changes.push(InventoryChange::Added {
+                item: item.id.clone(),
+                count: count - remaining,
+            });
+        }
+
+        remaining
+    }
+
+    /// Removes up to `count` items, taking from the last slots first.
+    ///
+    /// Returns the number of items removed.
+    pub fn remove(&mut self, item: &str, count: u32) -> u32 {
+        let mut removed = 0;
+
+        for slot in self.slots.iter_mut().rev() {
+            if removed == count {
+                break;
+            }
+
+            let Some(stack) = slot.as_mut().filter(|v| v.item == item) else {
+                continue;
+            };
+
+            let taken = stack.count.min(count - removed);
+            stack.count -= taken;
+            removed += taken;
+
+            if stack.count == 0 {
+                *slot = None;
+            }
+        }
+
+        if removed > 0 {
+            self.changes.push(InventoryChange::Removed {
+                item: item.into(),
+                count: removed,
+            });
+        }
+
+        removed
+    }
+
+    /// Removes the whole stack in the given slot
+    pub fn take_slot(&mut self, index: usize) -> Option<ItemStack> {
+        let stack = self.slots.get_mut(index)?.take()?;
+
+        self.changes.push(InventoryChange::Removed {
+            item: stack.item.clone(),
+            count: stack.count,
+        });
+
+        Some(stack)
+    }
+
+    /// Swaps the contents of two slots.
+    ///
+    /// Fails if either slot is out of range.
+    pub fn swap(&mut self, a: usize, b: usize) -> anyhow::Result<()> {
+        let len = self.slots.len();
+        if a >= len || b >= len {
+            anyhow::bail!("Slot index out of range: {a} <-> {b} with {len} slots");
+        }
+
+        self.slots.swap(a, b);
+        Ok(())
+    }
+
+    /// Returns the changes since the last call
+    pub fn drain_changes(&mut self) -> impl Iterator<Item = InventoryChange> + '_ {
+        self.changes.drain(..)
+    }
+}
+
+component! {
+    pub inventory: Inventory,
+    /// Stored on the engine entity
+    pub item_database: Asset<ItemDatabase>,
+}
+
+fn inventory_events_system(listeners: Vec<flume::Sender<InventoryEvent>>) -> BoxedSystem {
+    let mut query = Query::new((entity_ids(), inventory()));
+
+    System::builder()
+        .with_world()
+        .build(move |world: &World| {
+            // Only borrow mutably when needed, to not mark every inventory as modified
+            let pending = query
+                .borrow(world)
+                .iter()
+                .filter(|(_, v)| !v.changes.is_empty())
+                .map(|(id, _)| id)
+                .collect::<Vec<_>>();
+
+            for id in pending {
+                let mut inventory = world.get_mut(id, inventory())?;
+
+                for change in inventory.drain_changes() {
+                    let event = InventoryEvent { entity: id, change };
+
+                    for tx in &listeners {
+                        let _ = tx.send(event.clone());
+                    }
+                }
+            }
+
+            anyhow::Ok(())
+        })
+        .boxed()
+}
+
+/// Makes the item database available on the engine entity and sends [`InventoryEvent`]s for
+/// changes to each [`inventory`]
+pub struct InventoryPlugin {
+    database: Asset<ItemDatabase>,
+    listeners: Vec<flume::Sender<InventoryEvent>>,
+}
+
+impl InventoryPlugin {
+    pub fn new(database: Asset<ItemDatabase>) -> Self {
+        Self {
+            database,
+            listeners: Vec::new(),
+        }
+    }
+
+    /// Send inventory events to the given channel, e.g; for updating the UI
+    pub fn with_listener(mut self, tx: flume::Sender<InventoryEvent>) -> Self {
+        self.listeners.push(tx);
+        self
+    }
+}
+
+impl Plugin for InventoryPlugin {
+    fn install(
+        &self,
+        world: &mut World,
+        _: &AssetCache,
+        schedules: &mut ScheduleSetBuilder,
+    ) -> anyhow::Result<()> {
+        world.set(engine(), item_database(), self.database.clone())?;
+
+        schedules
+            .per_tick_mut()
+            .with_system(inventory_events_system(self.listeners.clone()));
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stacks() {
+        let potion = ItemDef::new("potion", "Potion").with_max_stack(5);
+        let sword = ItemDef::new("sword", "Sword");
+
+        let mut inventory = Inventory::new(3);
+        assert_eq!(inventory.add(&potion, 7), 0);
+        assert_eq!(inventory.add(&sword, 2), 1);
+        assert_eq!(inventory.add(&potion, 1), 0);
+
+        assert_eq!(inventory.count("potion"), 8);
+        assert_eq!(inventory.slot(0), Some(&ItemStack::new("potion", 5)));
+        assert_eq!(inventory.slot(1), Some(&ItemStack::new("potion", 3)));
+
+        assert_eq!(inventory.remove("potion", 4), 4);
+        assert_eq!(inventory.slot(1), None);
+        assert_eq!(inventory.count("potion"), 4);
+
+        assert_eq!(
+            inventory.drain_changes().collect::<Vec<_>>(),
+            [
+                InventoryChange::Added {
+                    item: "potion".into(),
+                    count: 7
+                },
+                InventoryChange::Added {
+                    item: "sword".into(),
+                    count: 1
+                },
+                InventoryChange::Added {
+                    item: "potion".into(),
+                    count: 1
+                },
+                InventoryChange::Removed {
+                    item: "potion".into(),
+                    count: 4
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn swap_slots() {
+        let sword = ItemDef::new("sword", "Sword");
+
+        let mut inventory = Inventory::new(2);
+        inventory.add(&sword, 1);
+
+        inventory.swap(0, 1).unwrap();
+        assert_eq!(inventory.slot(0), None);
+        assert_eq!(inventory.slot(1), Some(&ItemStack::new("sword", 1)));
+
+        assert!(inventory.swap(1, 2).is_err());
+        assert_eq!(inventory.slot(1), Some(&ItemStack::new("sword", 1)));
+    }
+
+    #[test]
+    fn parse_database() {
+        let database = ItemDatabase::from_ron(
+            r#"[
+                (id: "potion", name: "Potion", max_stack: 10),
+                (id: "sword", name: "Sword", prefab: Some("models/sword.glb")),
+            ]"#,
+        )
+        .unwrap();
+
+        assert_eq!(database.get("potion").unwrap().max_stack, 10);
+        assert_eq!(database.get("sword").unwrap().max_stack, 1);
+        assert!(ItemDatabase::from_ron(r#"[(id: "a", name: "A"), (id: "a", name: "B")]"#).is_err());
+    }
+}
@@ -1,2 +1,25 @@
+pub mod behavior_tree;
+pub mod camera;
+pub mod camera_2d;
+pub mod config;
+pub mod day_night;
+pub mod debug;
+pub mod dialogue;
+pub mod first_person;
+pub mod footsteps;
 pub mod free_camera;
+pub mod interaction;
+pub mod inventory;
+pub mod manipulator;
+pub mod path_follower;
+pub mod placement;
 pub mod ray_picker;
+pub mod replay;
+pub mod save_game;
+pub mod spline_mesh;
+pub mod stats;
+pub mod steering;
+pub mod timeline;
+pub mod top_down_camera;
+pub mod undo;
+pub mod weather;
@@ -0,0 +1,606 @@
+use std::f32::consts::TAU;
+
+use flax::{
+    component,
+    fetch::{entity_refs, EntityRefs, Source},
+    system, BoxedSystem, Component, ComponentMut, Entity, EntityRef, Query, QueryBorrow, System,
+    World,
+};
+use glam::{Quat, Vec2, Vec3};
+use ivy_assets::AssetCache;
+use ivy_core::{
+    components::{engine, gizmos, main_camera, position, rotation, scale},
+    gizmos::{Gizmos, GizmosSection, Line, Sphere, DEFAULT_THICKNESS},
+    update_layer::{Plugin, ScheduleSetBuilder},
+    Color, ColorExt,
+};
+use ivy_input::{
+    components::input_state,
+    types::{Key, MouseButton},
+    Action, CursorPositionBinding, InputState, KeyBinding, MouseButtonBinding,
+};
+use ivy_physics::{
+    components::{collider_handle, physics_state, rb_handle},
+    rapier3d::prelude::{QueryFilter, Ray},
+    state::PhysicsState,
+};
+
+use crate::{
+    dialogue::{dialogue_runner, DialogueRunner},
+    placement::{placement_state, PlacementState},
+    ray_picker::{screen_ray, CameraQuery},
+    undo::{edit_history, SetComponent},
+};
+
+/// Size of the manipulator handles relative to the distance from the camera
+const HANDLE_SCALE: f32 = 0.15;
+/// How close the cursor ray needs to be to a handle to grab it, relative to the handle size
+const GRAB_THRESHOLD: f32 = 0.1;
+
+const RING_SEGMENTS: usize = 48;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ManipulatorMode {
+    #[default]
+    Translate,
+    Rotate,
+    Scale,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Drag {
+    axis: Vec3,
+    /// Parameter along the axis, or the angle in the axis plane where the drag started
+    start_value: f32,
+    start_position: Vec3,
+    start_rotation: Quat,
+    start_scale: Vec3,
+}
+
+/// Translate, rotate, and scale handles for editing the transform of the selected entity.
+///
+/// Entities are selected by clicking their collider. Dragging a handle edits the `position`,
+/// `rotation`, or `scale` component of the selected entity along the handle's axis.
+///
+/// Finished drags are recorded in the engine's [`EditHistory`](crate::undo::EditHistory) if
+/// present.
+#[derive(Debug, Default)]
+pub struct ManipulatorState {
+    mode: ManipulatorMode,
+    selected: Option<Entity>,
+    hovered: Option<Vec3>,
+    drag: Option<Drag>,
+    camera_position: Vec3,
+    was_pressed: bool,
+}
+
+impl ManipulatorState {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn mode(&self) -> ManipulatorMode {
+        self.mode
+    }
+
+    pub fn set_mode(&mut self, mode: ManipulatorMode) {
+        self.mode = mode;
+        self.drag = None;
+    }
+
+    pub fn selected(&self) -> Option<Entity> {
+        self.selected
+    }
+
+    pub fn select(&mut self, entity: Option<Entity>) {
+        self.selected = entity;
+        self.drag = None;
+    }
+
+    /// Returns true if a handle is currently being dragged
+    pub fn is_dragging(&self) -> bool {
+        self.drag.is_some()
+    }
+
+    fn axes(&self, entity: &EntityRef) -> [Vec3; 3] {
+        match self.mode {
+            ManipulatorMode::Translate | ManipulatorMode::Rotate => [Vec3::X, Vec3::Y, Vec3::Z],
+            // Scaling happens in local space
+            ManipulatorMode::Scale => {
+                let rotation = entity.get_copy(rotation()).unwrap_or_default();
+                [rotation * Vec3::X, rotation * Vec3::Y, rotation * Vec3::Z]
+            }
+        }
+    }
+
+    /// Returns the handle axis intersected by the ray, if any
+    fn hit_handle(&self, entity: &EntityRef, origin: Vec3, dir: Vec3) -> Option<(Vec3, f32)> {
+        let center = entity.get_copy(position()).ok()?;
+        let size = center.distance(origin) * HANDLE_SCALE;
+        let threshold = size * GRAB_THRESHOLD;
+
+        self.axes(entity)
+            .into_iter()
+            .filter_map(|axis| {
+                let (distance, value) = match self.mode {
+                    ManipulatorMode::Translate | ManipulatorMode::Scale => {
+                        let (ray_t, axis_t) = closest_ray_line(origin, dir, center, axis)?;
+                        if !(0.0..=size).contains(&axis_t) || ray_t < 0.0 {
+                            return None;
+                        }
+
+                        let distance = (origin + dir * ray_t).distance(center + axis * axis_t);
+                        (distance, axis_t)
+                    }
+                    ManipulatorMode::Rotate => {
+                        let point = ray_plane(origin, dir, center, axis)?;
+                        let distance = (point.distance(center) - size).abs();
+                        (distance, plane_angle(point - center, axis))
+                    }
+                };
+
+                (distance < threshold).then_some((axis, value, distance))
+            })
+            .min_by(|a, b| a.2.total_cmp(&b.2))
+            .map(|(axis, value, _)| (axis, value))
+    }
+
+    pub fn update(
+        &mut self,
+        world: &World,
+        physics_state: &PhysicsState,
+        pressed: bool,
+        origin: Vec3,
+        dir: Vec3,
+    ) -> anyhow::Result<()> {
+        self.camera_position = origin;
+        let just_pressed = pressed && !self.was_pressed;
+        self.was_pressed = pressed;
+
+        let selected = self.selected.and_then(|id| world.entity(id).ok());
+        if self.selected.is_some() && selected.is_none() {
+            self.select(None);
+        }
+
+        self.hovered = selected
+            .as_ref()
+            .and_then(|entity| self.hit_handle(entity, origin, dir))
+            .map(|v| v.0);
+
+        if !pressed {
+            if let (Some(drag), Some(entity)) = (self.drag.take(), &selected) {
+                self.record_drag(world, entity, &drag);
+            }
+
+            return Ok(());
+        }
+
+        if just_pressed {
+            if let Some((entity, (axis, start_value))) = selected
+                .as_ref()
+                .and_then(|entity| Some((entity, self.hit_handle(entity, origin, dir)?)))
+            {
+                self.drag = Some(Drag {
+                    axis,
+                    start_value,
+                    start_position: entity.get_copy(position()).unwrap_or_default(),
+                    start_rotation: entity.get_copy(rotation()).unwrap_or_default(),
+                    start_scale: entity.get_copy(scale()).unwrap_or(Vec3::ONE),
+                });
+            } else {
+                let ray = Ray::new(origin.into(), dir.into());
+                let hit = physics_state.cast_ray(&ray, 1e3, true, pick_filter(selected.as_ref()));
+                self.select(hit.map(|v| v.collider_id));
+            }
+
+            return Ok(());
+        }
+
+        if let (Some(drag), Some(entity)) = (self.drag, selected) {
+            self.apply_drag(&entity, &drag, origin, dir)?;
+        }
+
+        Ok(())
+    }
+
+    /// Adds the finished drag to the edit history, if any
+    fn record_drag(&self, world: &World, entity: &EntityRef, drag: &Drag) {
+        let Ok(mut history) = world.get_mut(engine(), edit_history()) else {
+            return;
+        };
+
+        let id = entity.id();
+        match self.mode {
+            ManipulatorMode::Translate => history.record(SetComponent::applied(
+                id,
+                position(),
+                Some(drag.start_position),
+                entity.get_copy(position()).unwrap_or_default(),
+            )),
+            ManipulatorMode::Rotate => history.record(SetComponent::applied(
+                id,
+                rotation(),
+                Some(drag.start_rotation),
+                entity.get_copy(rotation()).unwrap_or_default(),
+            )),
+            ManipulatorMode::Scale => history.record(SetComponent::applied(
+                id,
+                scale(),
+                entity.has(scale()).then_some(drag.start_scale),
+                entity.get_copy(scale()).unwrap_or(Vec3::ONE),
+            )),
+        }
+    }
+
+    fn apply_drag(
+        &self,
+        entity: &EntityRef,
+        drag: &Drag,
+        origin: Vec3,
+        dir: Vec3,
+    ) -> anyhow::Result<()> {
+        match self.mode {
+            ManipulatorMode::Translate => {
+                if let Some((_, t)) = closest_ray_line(origin, dir, drag.start_position, drag.axis)
+                {
+                    let new_pos = drag.start_position + drag.axis * (t - drag.start_value);
+                    entity.update_dedup(position(), new_pos);
+                }
+            }
+            ManipulatorMode::Rotate => {
+                if let Some(point) = ray_plane(origin, dir, drag.start_position, drag.axis) {
+                    let angle =
+                        plane_angle(point - drag.start_position, drag.axis) - drag.start_value;
+                    let new_rotation =
+                        Quat::from_axis_angle(drag.axis, angle) * drag.start_rotation;
+                    entity.update_dedup(rotation(), new_rotation.normalize());
+                }
+            }
+            ManipulatorMode::Scale => {
+                if let Some((_, t)) = closest_ray_line(origin, dir, drag.start_position, drag.axis)
+                {
+                    let factor = (t / drag.start_value.max(1e-3)).max(1e-3);
+                    let local_axis = (drag.start_rotation.inverse() * drag.axis).abs();
+                    let new_scale = drag.start_scale * (Vec3::ONE + local_axis * (factor - 1.0));
+                    entity.update_dedup(scale(), new_scale);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    #[system(with_world, with_query(Query::new(gizmos().as_mut())))]
+    pub fn draw_gizmos(
+        self: &mut ManipulatorState,
+        world: &World,
+        gizmos: &mut QueryBorrow<ComponentMut<Gizmos>>,
+    ) {
+        let gizmos = gizmos.first().unwrap();
+        let mut gizmos = gizmos.begin_section("ManipulatorState::draw_gizmos");
+
+        let Some(entity) = self.selected.and_then(|id| world.entity(id).ok()) else {
+            return;
+        };
+
+        let Ok(center) = entity.get_copy(position()) else {
+            return;
+        };
+
+        let size = center.distance(self.camera_position) * HANDLE_SCALE;
+        let active = self.drag.map(|v| v.axis).or(self.hovered);
+
+        for (axis, color) in
+            self.axes(&entity)
+                .into_iter()
+                .zip([Color::red(), Color::green(), Color::blue()])
+        {
+            let color = if active == Some(axis) {
+                Color::yellow()
+            } else {
+                color
+            };
+
+            self.draw_handle(&mut gizmos, center, axis, size, color);
+        }
+    }
+
+    fn draw_handle(
+        &self,
+        gizmos: &mut GizmosSection,
+        center: Vec3,
+        axis: Vec3,
+        size: f32,
+        color: Color,
+    ) {
+        let thickness = DEFAULT_THICKNESS * size;
+        match self.mode {
+            ManipulatorMode::Translate => {
+                gizmos.draw(Line::new(center, axis * size, thickness, color));
+            }
+            ManipulatorMode::Scale => {
+                gizmos.draw(Line::new(center, axis * size, thickness, color));
+                gizmos.draw(Sphere::new(center + axis * size, thickness * 3.0, color));
+            }
+            ManipulatorMode::Rotate => {
+                let tangent = axis.any_orthonormal_vector();
+                let points = (0..=RING_SEGMENTS).map(|i| {
+                    let angle = i as f32 / RING_SEGMENTS as f32 * TAU;
+                    center + Quat::from_axis_angle(axis, angle) * tangent * size
+                });
+
+                for (a, b) in points.clone().zip(points.skip(1)) {
+                    gizmos.draw(Line::from_points(a, b, thickness, color));
+                }
+            }
+        }
+    }
+}
+
+/// Ignores trigger volumes, and the selected entity so that clicking it selects what is behind
+fn pick_filter(selected: Option<&EntityRef>) -> QueryFilter<'static> {
+    let filter = QueryFilter::default().exclude_sensors();
+
+    let Some(entity) = selected else {
+        return filter;
+    };
+
+    if let Ok(rb) = entity.get_copy(rb_handle()) {
+        filter.exclude_rigid_body(rb)
+    } else if let Ok(collider) = entity.get_copy(collider_handle()) {
+        filter.exclude_collider(collider)
+    } else {
+        filter
+    }
+}
+
+/// Returns the parameters of the closest points between a ray and an infinite line
+fn closest_ray_line(origin: Vec3, dir: Vec3, point: Vec3, axis: Vec3) -> Option<(f32, f32)> {
+    let w = origin - point;
+    let b = dir.dot(axis);
+    let denom = 1.0 - b * b;
+
+    // Looking straight down the axis
+    if denom < 1e-4 {
+        return None;
+    }
+
+    let d = dir.dot(w);
+    let e = axis.dot(w);
+
+    Some(((b * e - d) / denom, (e - b * d) / denom))
+}
+
+fn ray_plane(origin: Vec3, dir: Vec3, point: Vec3, normal: Vec3) -> Option<Vec3> {
+    let denom = dir.dot(normal);
+    if denom.abs() < 1e-4 {
+        return None;
+    }
+
+    let t = (point - origin).dot(normal) / denom;
+    (t >= 0.0).then(|| origin + dir * t)
+}
+
+/// Angle of `v` around `axis`
+fn plane_angle(v: Vec3, axis: Vec3) -> f32 {
+    let tangent = axis.any_orthonormal_vector();
+    let bitangent = axis.cross(tangent);
+    v.dot(bitangent).atan2(v.dot(tangent))
+}
+
+component! {
+    pub manipulator_state: ManipulatorState,
+    manipulate_action: bool,
+    manipulator_cursor_action: Vec2,
+    translate_mode_action: bool,
+    rotate_mode_action: bool,
+    scale_mode_action: bool,
+}
+
+type ManipulatorQuery = (
+    Source<Component<PhysicsState>, Entity>,
+    Source<(Component<()>, CameraQuery), ()>,
+    (
+        EntityRefs,
+        Component<bool>,
+        Component<Vec2>,
+        Component<bool>,
+        Component<bool>,
+        Component<bool>,
+        ComponentMut<ManipulatorState>,
+    ),
+);
+
+fn manipulator_system() -> BoxedSystem {
+    System::builder()
+        .with_query(Query::new(placement_state()))
+        .with_query(Query::new(dialogue_runner()))
+        .with_query(Query::new((
+            physics_state().source(engine()),
+            (main_camera(), CameraQuery::new()).source(()),
+            (
+                entity_refs(),
+                manipulate_action(),
+                manipulator_cursor_action(),
+                translate_mode_action(),
+                rotate_mode_action(),
+                scale_mode_action(),
+                manipulator_state().as_mut(),
+            ),
+        )))
+        .build(
+            |mut placement: QueryBorrow<'_, Component<PlacementState>>,
+             mut dialogue: QueryBorrow<'_, Component<DialogueRunner>>,
+             mut query: QueryBorrow<'_, ManipulatorQuery>| {
+                // Left click places objects while in placement mode
+                let placing = placement.iter().any(|v| v.enabled());
+                // The number keys select dialogue choices
+                let in_dialogue = dialogue.iter().any(|v| v.is_active());
+
+                for (
+                    physics_state,
+                    (_, camera),
+                    (
+                        entity,
+                        &pressed,
+                        &cursor_pos,
+                        &translate_mode,
+                        &rotate_mode,
+                        &scale_mode,
+                        state,
+                    ),
+                ) in query.iter()
+                {
+                    if !state.is_dragging() && !in_dialogue {
+                        if translate_mode {
+                            state.set_mode(ManipulatorMode::Translate);
+                        } else if rotate_mode {
+                            state.set_mode(ManipulatorMode::Rotate);
+                        } else if scale_mode {
+                            state.set_mode(ManipulatorMode::Scale);
+                        }
+                    }
+
+                    let (origin, dir) = screen_ray(&camera, cursor_pos);
+                    state.update(
+                        entity.world(),
+                        physics_state,
+                        pressed && !placing,
+                        origin,
+                        dir,
+                    )?;
+                }
+
+                anyhow::Ok(())
+            },
+        )
+        .boxed()
+}
+
+/// Adds transform manipulation gizmos for editing entities in the scene.
+///
+/// Use `1`, `2`, and `3` to switch between translating, rotating, and scaling, except while a
+/// dialogue is active as the keys select its choices. Selection is suspended while the
+/// [`PlacementPlugin`](crate::placement::PlacementPlugin) is placing objects.
+pub struct ManipulatorPlugin;
+
+impl Plugin for ManipulatorPlugin {
+    fn install(
+        &self,
+        world: &mut World,
+        _: &AssetCache,
+        schedules: &mut ScheduleSetBuilder,
+    ) -> anyhow::Result<()> {
+        let mut manipulate = Action::new();
+        manipulate.add(MouseButtonBinding::new(MouseButton::Left));
+
+        let mut cursor_position = Action::new();
+        cursor_position.add(CursorPositionBinding::new(true));
+
+        let mode_action = |key: &str| {
+            let mut action = Action::new();
+            action.add(KeyBinding::new(Key::Character(key.into())));
+            action
+        };
+
+        Entity::builder()
+            .set(
+                input_state(),
+                InputState::new()
+                    .with_action(manipulate_action(), manipulate)
+                    .with_action(manipulator_cursor_action(), cursor_position)
+                    .with_action(translate_mode_action(), mode_action("1"))
+                    .with_action(rotate_mode_action(), mode_action("2"))
+                    .with_action(scale_mode_action(), mode_action("3")),
+            )
+            .set_default(manipulate_action())
+            .set_default(manipulator_cursor_action())
+            .set_default(translate_mode_action())
+            .set_default(rotate_mode_action())
+            .set_default(scale_mode_action())
+            .set(manipulator_state(), ManipulatorState::new())
+            .spawn(world);
+
+        schedules
+            .per_tick_mut()
+            .with_system(manipulator_system())
+            .with_system(ManipulatorState::draw_gizmos_system());
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use ivy_physics::state::PhysicsStateConfiguration;
+
+    use super::*;
+
+    #[test]
+    fn ray_helpers() {
+        let (ray_t, axis_t) =
+            closest_ray_line(Vec3::new(2.0, 0.0, 5.0), -Vec3::Z, Vec3::ZERO, Vec3::X).unwrap();
+        assert!((ray_t - 5.0).abs() < 1e-5);
+        assert!((axis_t - 2.0).abs() < 1e-5);
+
+        // Parallel to the axis
+        assert_eq!(
+            closest_ray_line(Vec3::Z, Vec3::X, Vec3::ZERO, Vec3::X),
+            None
+        );
+
+        assert_eq!(
+            ray_plane(Vec3::new(1.0, 4.0, 0.0), -Vec3::Y, Vec3::ZERO, Vec3::Y),
+            Some(Vec3::X)
+        );
+        assert_eq!(ray_plane(Vec3::Y, Vec3::Y, Vec3::ZERO, Vec3::Y), None);
+
+        let tangent = Vec3::Y.any_orthonormal_vector();
+        let quarter = Quat::from_axis_angle(Vec3::Y, 1.0) * tangent;
+        assert!(plane_angle(tangent, Vec3::Y).abs() < 1e-5);
+        assert!((plane_angle(quarter, Vec3::Y) - 1.0).abs() < 1e-5);
+    }
+
+    #[test]
+    fn translate_drag() {
+        let mut world = World::new();
+        let id = Entity::builder()
+            .set(position(), Vec3::ZERO)
+            .spawn(&mut world);
+
+        let physics_state = PhysicsState::new(&PhysicsStateConfiguration::default(), 1.0 / 60.0);
+
+        let mut state = ManipulatorState::new();
+        state.select(Some(id));
+
+        // The handles are 1.5 units long at this distance, so the ray grabs the X handle
+        let origin = Vec3::new(0.75, 0.0, 10.0);
+        state
+            .update(&world, &physics_state, true, origin, -Vec3::Z)
+            .unwrap();
+        assert!(state.is_dragging());
+
+        state
+            .update(&world, &physics_state, true, origin + Vec3::X, -Vec3::Z)
+            .unwrap();
+        assert_eq!(*world.get(id, position()).unwrap(), Vec3::X);
+
+        state
+            .update(&world, &physics_state, false, origin + Vec3::X, -Vec3::Z)
+            .unwrap();
+        assert!(!state.is_dragging());
+        assert_eq!(state.selected(), Some(id));
+
+        // Missing every handle deselects, as there are no colliders to hit
+        state
+            .update(
+                &world,
+                &physics_state,
+                true,
+                Vec3::new(5.0, 5.0, 10.0),
+                -Vec3::Z,
+            )
+            .unwrap();
+        assert_eq!(state.selected(), None);
+    }
+}
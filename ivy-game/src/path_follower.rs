@@ -0,0 +1,330 @@
+use flax::{component, BoxedSystem, Query, System, World};
+use glam::{Mat3, Mat4, Quat, Vec3};
+use ivy_assets::{Asset, AssetCache};
+use ivy_core::{
+    components::{delta_time, engine, gizmos, position, rotation},
+    gizmos::{Line, Sphere},
+    tween::Easing,
+    update_layer::{Plugin, ScheduleSetBuilder},
+    Color, ColorExt,
+};
+use ivy_graphics::spline::{ArcLengths, Spline};
+
+component! {
+    pub path_follower: PathFollower,
+}
+
+/// Number of lines per spline segment when drawing gizmos
+const GIZMO_STEPS: usize = 16;
+
+/// What happens when a [`PathFollower`] reaches the end of the path
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum PathLoop {
+    /// Stop at the end
+    #[default]
+    Once,
+    /// Start over from the beginning
+    Loop,
+    /// Turn around and travel back, such as a moving platform
+    PingPong,
+}
+
+/// Moves an entity along a spline at a constant speed, such as platforms, camera rails, and
+/// patrol routes
+#[derive(Debug, Clone)]
+pub struct PathFollower {
+    spline: Asset<Spline>,
+    lengths: ArcLengths,
+    /// Transform of the spline points
+    pub transform: Mat4,
+    pub speed: f32,
+    pub looping: PathLoop,
+    pub easing: Easing,
+    /// Rotate to face along the path
+    pub orient: bool,
+    /// Progress along the current traversal from 0 to 1
+    progress: f32,
+    reversed: bool,
+    playing: bool,
+}
+
+impl PathFollower {
+    pub fn new(spline: Asset<Spline>) -> Self {
+        Self {
+            lengths: spline.arc_lengths(),
+            spline,
+            transform: Mat4::IDENTITY,
+            speed: 1.0,
+            looping: PathLoop::Once,
+            easing: Easing::Linear,
+            orient: false,
+            progress: 0.0,
+            reversed: false,
+            playing: true,
+        }
+    }
+
+    pub fn with_transform(mut self, transform: Mat4) -> Self {
+        self.transform = transform;
+        self
+    }
+
+    /// Set the speed in units per second
+    pub fn with_speed(mut self, speed: f32) -> Self {
+        self.speed = speed;
+        self
+    }
+
+    pub fn with_looping(mut self, looping: PathLoop) -> Self {
+        self.looping = looping;
+        self
+    }
+
+    pub fn with_easing(mut self, easing: Easing) -> Self {
+        self.easing = easing;
+        self
+    }
+
+    pub fn with_orient(mut self, orient: bool) -> Self {
+        self.orient = orient;
+        self
+    }
+
+    /// Start at a fraction of the path, such as to spread out several platforms on the same path
+    pub fn with_progress(mut self, progress: f32) -> Self {
+        self.progress = progress.clamp(0.0, 1.0);
+        self
+    }
+
+    pub fn spline(&self) -> &Asset<Spline> {
+        &self.spline
+    }
+
+    pub fn progress(&self) -> f32 {
+        self.progress
+    }
+
+    /// Returns true if travelling back towards the start of a [`PathLoop::PingPong`] path
+    pub fn is_reversed(&self) -> bool {
+        self.reversed
+    }
+
+    pub fn is_playing(&self) -> bool {
+        self.playing
+    }
+
+    pub fn play(&mut self) {
+        if self.looping == PathLoop::Once && self.progress >= 1.0 {
+            self.progress = 0.0;
+        }
+
+        self.playing = true;
+    }
+
+    pub fn pause(&mut self) {
+        self.playing = false;
+    }
+
+    /// Advances along the path
+    pub fn update(&mut self, dt: f32) {
+        let length = self.lengths.length();
+        if !self.playing || length <= 0.0 {
+            return;
+        }
+
+        self.progress += self.speed * dt / length;
+
+        while self.progress >= 1.0 {
+            match self.looping {
+                PathLoop::Once => {
+                    self.progress = 1.0;
+                    self.playing = false;
+                    break;
+                }
+                PathLoop::Loop => self.progress -= 1.0,
+                PathLoop::PingPong => {
+                    self.progress -= 1.0;
+                    self.reversed = !self.reversed;
+                }
+            }
+        }
+    }
+
+    /// Distance along the path from the start
+    pub fn distance(&self) -> f32 {
+        let t = self.easing.apply(self.progress);
+        let t = if self.reversed { 1.0 - t } else { t };
+
+        t * self.lengths.length()
+    }
+
+    /// Returns the current position
+    pub fn position(&self) -> Vec3 {
+        if self.spline.segment_count() == 0 {
+            return self.transform.w_axis.truncate();
+        }
+
+        let t = self.lengths.parameter(self.distance());
+        self.transform.transform_point3(self.spline.sample(t))
+    }
+
+    /// Returns the rotation facing in the direction of travel
+    pub fn rotation(&self) -> Option<Quat> {
+        if self.spline.segment_count() == 0 {
+            return None;
+        }
+
+        let t = self.lengths.parameter(self.distance());
+        let mut forward = self
+            .transform
+            .transform_vector3(self.spline.derivative(t))
+            .try_normalize()?;
+
+        if self.reversed {
+            forward = -forward;
+        }
+
+        let right = forward.cross(Vec3::Y).try_normalize()?;
+        let up = right.cross(forward);
+
+        Some(Quat::from_mat3(&Mat3::from_cols(right, up, -forward)))
+    }
+}
+
+/// Moves the [`PathFollower`]s along their paths
+#[derive(Default)]
+pub struct PathFollowerPlugin {
+    gizmos: bool,
+}
+
+impl PathFollowerPlugin {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Draw the paths of the followers
+    pub fn with_gizmos(mut self, gizmos: bool) -> Self {
+        self.gizmos = gizmos;
+        self
+    }
+}
+
+impl Plugin for PathFollowerPlugin {
+    fn install(
+        &self,
+        _: &mut World,
+        _: &AssetCache,
+        schedules: &mut ScheduleSetBuilder,
+    ) -> anyhow::Result<()> {
+        schedules.per_tick_mut().with_system(path_follower_system());
+
+        if self.gizmos {
+            schedules.per_tick_mut().with_system(path_gizmo_system());
+        }
+
+        Ok(())
+    }
+}
+
+fn path_follower_system() -> BoxedSystem {
+    let mut query = Query::new((
+        path_follower().as_mut(),
+        position().as_mut(),
+        rotation().as_mut(),
+    ));
+
+    System::builder()
+        .with_world_mut()
+        .build(move |world: &mut World| {
+            let dt = world
+                .get(engine(), delta_time())
+                .map(|v| v.as_secs_f32())
+                .unwrap_or_default();
+
+            for (follower, position, rotation) in &mut query.borrow(world) {
+                follower.update(dt);
+                *position = follower.position();
+
+                if follower.orient {
+                    if let Some(v) = follower.rotation() {
+                        *rotation = v;
+                    }
+                }
+            }
+        })
+        .boxed()
+}
+
+fn path_gizmo_system() -> BoxedSystem {
+    System::builder()
+        .with_world()
+        .build(|world: &World| {
+            let gizmos = world.get(engine(), gizmos())?;
+            let mut section = gizmos.begin_section("path_gizmo_system");
+
+            for follower in &mut Query::new(path_follower()).borrow(world) {
+                let spline = follower.spline();
+                let steps = spline.segment_count() * GIZMO_STEPS;
+
+                let point = |i: usize| {
+                    follower
+                        .transform
+                        .transform_point3(spline.sample(i as f32 / GIZMO_STEPS as f32))
+                };
+
+                for i in 0..steps {
+                    section.draw(Line::from_points(
+                        point(i),
+                        point(i + 1),
+                        0.02,
+                        Color::cyan(),
+                    ));
+                }
+
+                for &v in &spline.points {
+                    section.draw(Sphere::new(
+                        follower.transform.transform_point3(v),
+                        0.08,
+                        Color::yellow(),
+                    ));
+                }
+            }
+
+            anyhow::Ok(())
+        })
+        .boxed()
+}
+
+#[cfg(test)]
+mod test {
+    use glam::vec3;
+
+    use super::*;
+
+    #[test]
+    fn ping_pong() {
+        let assets = AssetCache::new();
+        let spline = assets.insert(Spline::linear([Vec3::ZERO, vec3(4.0, 0.0, 0.0)]));
+
+        let mut follower = PathFollower::new(spline)
+            .with_speed(2.0)
+            .with_looping(PathLoop::PingPong);
+
+        follower.update(1.0);
+        assert!(follower.position().distance(vec3(2.0, 0.0, 0.0)) < 1e-3);
+
+        follower.update(1.5);
+        assert!(follower.is_reversed());
+        assert!(follower.position().distance(vec3(3.0, 0.0, 0.0)) < 1e-3);
+        assert!(follower
+            .rotation()
+            .unwrap()
+            .mul_vec3(-Vec3::Z)
+            .abs_diff_eq(-Vec3::X, 1e-4));
+
+        let mut follower = follower.with_looping(PathLoop::Once);
+        follower.update(10.0);
+        assert!(!follower.is_playing());
+        assert_eq!(follower.progress(), 1.0);
+    }
+}
@@ -0,0 +1,567 @@
+use std::{f32::consts::FRAC_PI_4, sync::Arc};
+
+use flax::{
+    component,
+    components::name,
+    entity_ids,
+    fetch::{entity_refs, EntityRefs, Source},
+    serialize::SerdeBuilder,
+    system, BoxedSystem, CommandBuffer, Component, ComponentMut, Debuggable, Entity, EntityBuilder,
+    FetchExt, Query, QueryBorrow, System, World,
+};
+use glam::{Mat4, Quat, Vec2, Vec3, Vec3Swizzles};
+use ivy_assets::AssetCache;
+use ivy_core::{
+    components::{engine, gizmos, main_camera, position, rotation, scale},
+    gizmos::{Cube, Gizmos},
+    update_layer::{Plugin, ScheduleSetBuilder},
+    Color, ColorExt,
+};
+use ivy_input::{
+    components::input_state,
+    types::{Key, MouseButton, NamedKey},
+    Action, CursorPositionBinding, InputState, KeyBinding, MouseButtonBinding,
+};
+use ivy_physics::{
+    components::physics_state,
+    rapier3d::prelude::{QueryFilter, Ray},
+    state::PhysicsState,
+};
+
+use crate::{
+    manipulator::manipulator_state,
+    ray_picker::{screen_ray, CameraQuery},
+};
+
+/// Mounts the components of a placeable object, excluding the transform
+pub type PrefabFn = Arc<dyn Send + Sync + Fn(&mut EntityBuilder)>;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PlacementSettings {
+    /// Size of a grid cell. Positions are snapped to the grid
+    pub grid_size: f32,
+    /// Rotations around the up axis are snapped to multiples of this angle
+    pub rotation_increment: f32,
+}
+
+impl Default for PlacementSettings {
+    fn default() -> Self {
+        Self {
+            grid_size: 1.0,
+            rotation_increment: FRAC_PI_4,
+        }
+    }
+}
+
+impl PlacementSettings {
+    pub fn snap_position(&self, pos: Vec3) -> Vec3 {
+        if self.grid_size <= 0.0 {
+            return pos;
+        }
+
+        (pos / self.grid_size).round() * self.grid_size
+    }
+
+    pub fn snap_angle(&self, angle: f32) -> f32 {
+        if self.rotation_increment <= 0.0 {
+            return angle;
+        }
+
+        (angle / self.rotation_increment).round() * self.rotation_increment
+    }
+}
+
+/// Editor mode for quickly blocking out levels by placing prefabs on surfaces.
+///
+/// Placed entities store the name of their prefab in [`placed_prefab`] along with their transform,
+/// which is all that needs to be serialized. The remaining components are mounted again from the
+/// prefab when the entity is loaded, see [`register_placement_serde`].
+pub struct PlacementState {
+    settings: PlacementSettings,
+    prefabs: Vec<(String, PrefabFn)>,
+    current: usize,
+    rotation_steps: i32,
+    enabled: bool,
+    preview: Option<(Vec3, Quat)>,
+    prev_actions: [bool; 5],
+}
+
+impl PlacementState {
+    pub fn new(settings: PlacementSettings) -> Self {
+        Self {
+            settings,
+            prefabs: Vec::new(),
+            current: 0,
+            rotation_steps: 0,
+            enabled: false,
+            preview: None,
+            prev_actions: [false; 5],
+        }
+    }
+
+    /// Add a placeable prefab
+    pub fn with_prefab(
+        mut self,
+        name: impl Into<String>,
+        prefab: impl 'static + Send + Sync + Fn(&mut EntityBuilder),
+    ) -> Self {
+        self.prefabs.push((name.into(), Arc::new(prefab)));
+        self
+    }
+
+    pub fn settings(&self) -> &PlacementSettings {
+        &self.settings
+    }
+
+    pub fn settings_mut(&mut self) -> &mut PlacementSettings {
+        &mut self.settings
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+        self.preview = None;
+    }
+
+    /// Name of the prefab currently being placed
+    pub fn current_prefab(&self) -> Option<&str> {
+        self.prefabs.get(self.current).map(|v| &*v.0)
+    }
+
+    pub fn select_prefab(&mut self, name: &str) -> bool {
+        match self.prefabs.iter().position(|v| v.0 == name) {
+            Some(index) => {
+                self.current = index;
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn prefab(&self, name: &str) -> Option<&PrefabFn> {
+        self.prefabs.iter().find(|v| v.0 == name).map(|v| &v.1)
+    }
+
+    fn rotation(&self) -> Quat {
+        Quat::from_rotation_y(self.rotation_steps as f32 * self.settings.rotation_increment)
+    }
+
+    /// Creates a new instance of a prefab at the given transform
+    pub fn instantiate(
+        &self,
+        name: &str,
+        position_: Vec3,
+        rotation_: Quat,
+    ) -> Option<EntityBuilder> {
+        let prefab = self.prefab(name)?;
+        let mut builder = Entity::builder();
+        prefab(&mut builder);
+
+        builder
+            .set(position(), position_)
+            .set(rotation(), rotation_)
+            .set(placed_prefab(), name.into())
+            .set_default(prefab_mounted());
+
+        Some(builder)
+    }
+
+    /// Updates the placement preview from the surface under the cursor
+    fn update_preview(&mut self, physics_state: &PhysicsState, origin: Vec3, dir: Vec3) {
+        let ray = Ray::new(origin.into(), dir.into());
+
+        self.preview = physics_state
+            .cast_ray(&ray, 1e3, true, QueryFilter::default())
+            .map(|hit| {
+                let point: Vec3 = ray.point_at(hit.intersection.time_of_impact).into();
+                let normal: Vec3 = hit.intersection.normal.into();
+
+                // Place the cell adjacent to the surface rather than inside it
+                let center = point + normal * self.settings.grid_size * 0.5;
+                (self.settings.snap_position(center), self.rotation())
+            });
+    }
+
+    /// Duplicates a placed entity one grid cell along the dominant horizontal direction of
+    /// `forward`
+    fn duplicate(&self, world: &World, cmd: &mut CommandBuffer, id: Entity, forward: Vec3) {
+        let Ok(entity) = world.entity(id) else {
+            return;
+        };
+
+        let Ok(prefab) = entity.get(placed_prefab()).map(|v| v.clone()) else {
+            tracing::warn!(%id, "Only placed entities can be duplicated");
+            return;
+        };
+
+        let forward = forward.xz();
+        let dir = if forward.x.abs() > forward.y.abs() {
+            Vec3::X * forward.x.signum()
+        } else {
+            Vec3::Z * forward.y.signum()
+        };
+
+        let pos = entity.get_copy(position()).unwrap_or_default();
+        let rot = entity.get_copy(rotation()).unwrap_or_default();
+
+        if let Some(mut builder) =
+            self.instantiate(&prefab, pos + dir * self.settings.grid_size, rot)
+        {
+            if let Ok(scale_) = entity.get_copy(scale()) {
+                builder.set(scale(), scale_);
+            }
+
+            cmd.spawn(builder);
+        }
+    }
+
+    #[system(with_query(Query::new(gizmos().as_mut())))]
+    pub fn draw_gizmos(self: &mut PlacementState, gizmos: &mut QueryBorrow<ComponentMut<Gizmos>>) {
+        let gizmos = gizmos.first().unwrap();
+        let mut gizmos = gizmos.begin_section("PlacementState::draw_gizmos");
+
+        if let Some((pos, rot)) = self.preview {
+            let half_extent = Vec3::splat(self.settings.grid_size * 0.5);
+            gizmos.draw(
+                Cube::new(-half_extent, half_extent, 0.02, Color::cyan())
+                    .with_transform(Mat4::from_rotation_translation(rot, pos)),
+            );
+        }
+    }
+}
+
+component! {
+    pub placement_state: PlacementState,
+    /// Name of the prefab the entity was placed from
+    pub placed_prefab: String => [ Debuggable ],
+    /// Set once the prefab components have been mounted on a placed entity
+    prefab_mounted: (),
+
+    toggle_placement_action: bool,
+    place_action: bool,
+    rotate_brush_action: bool,
+    cycle_prefab_action: bool,
+    duplicate_action: bool,
+    placement_cursor_action: Vec2,
+}
+
+/// Registers the components needed to persist placed entities with a serializer
+pub fn register_placement_serde(builder: &mut SerdeBuilder) -> &mut SerdeBuilder {
+    builder
+        .with(name())
+        .with(placed_prefab())
+        .with(position())
+        .with(rotation())
+        .with(scale())
+}
+
+type PlacementQuery = (
+    Source<Component<PhysicsState>, Entity>,
+    Source<(Component<()>, CameraQuery), ()>,
+    (
+        EntityRefs,
+        (
+            Component<bool>,
+            Component<bool>,
+            Component<bool>,
+            Component<bool>,
+            Component<bool>,
+        ),
+        Component<Vec2>,
+        ComponentMut<PlacementState>,
+    ),
+);
+
+fn placement_system() -> BoxedSystem {
+    System::builder()
+        .with_cmd_mut()
+        .with_query(Query::new((
+            physics_state().source(engine()),
+            (main_camera(), CameraQuery::new()).source(()),
+            (
+                entity_refs(),
+                (
+                    toggle_placement_action(),
+                    place_action(),
+                    rotate_brush_action(),
+                    cycle_prefab_action(),
+                    duplicate_action(),
+                ),
+                placement_cursor_action(),
+                placement_state().as_mut(),
+            ),
+        )))
+        .build(
+            |cmd: &mut CommandBuffer, mut query: QueryBorrow<'_, PlacementQuery>| {
+                for (
+                    physics_state,
+                    (_, camera),
+                    (entity, (&toggle, &place, &rotate, &cycle, &duplicate), &cursor_pos, state),
+                ) in query.iter()
+                {
+                    let world = entity.world();
+
+                    // Only react to the moment an action is pressed
+                    let actions = [toggle, place, rotate, cycle, duplicate];
+                    let [toggle, place, rotate, cycle, duplicate] =
+                        std::array::from_fn(|i| actions[i] && !state.prev_actions[i]);
+                    state.prev_actions = actions;
+
+                    if toggle {
+                        let enabled = !state.enabled;
+                        state.set_enabled(enabled);
+                    }
+
+                    if !state.enabled {
+                        continue;
+                    }
+
+                    if rotate {
+                        state.rotation_steps += 1;
+                    }
+
+                    if cycle && !state.prefabs.is_empty() {
+                        state.current = (state.current + 1) % state.prefabs.len();
+                        tracing::info!(prefab = state.current_prefab(), "selected prefab");
+                    }
+
+                    let (origin, dir) = screen_ray(&camera, cursor_pos);
+                    state.update_preview(physics_state, origin, dir);
+
+                    if place {
+                        if let (Some((pos, rot)), Some(prefab)) =
+                            (state.preview, state.current_prefab())
+                        {
+                            if let Some(builder) = state.instantiate(prefab, pos, rot) {
+                                cmd.spawn(builder);
+                            }
+                        }
+                    }
+
+                    if duplicate {
+                        let selected = Query::new(manipulator_state())
+                            .borrow(world)
+                            .first()
+                            .and_then(|v| v.selected());
+
+                        if let Some(id) = selected {
+                            state.duplicate(world, cmd, id, dir);
+                        }
+                    }
+                }
+
+                anyhow::Ok(())
+            },
+        )
+        .boxed()
+}
+
+/// Mounts the prefab components of placed entities which were loaded without them
+fn mount_prefabs_system() -> BoxedSystem {
+    System::builder()
+        .with_world_mut()
+        .build(|world: &mut World| {
+            let pending = Query::new((entity_ids(), placed_prefab().cloned()))
+                .without(prefab_mounted())
+                .borrow(world)
+                .iter()
+                .collect::<Vec<_>>();
+
+            if pending.is_empty() {
+                return Ok(());
+            }
+
+            let prefabs = Query::new(placement_state())
+                .borrow(world)
+                .first()
+                .map(|state| {
+                    pending
+                        .into_iter()
+                        .map(|(id, name)| (id, state.prefab(&name).cloned(), name))
+                        .collect::<Vec<_>>()
+                })
+                .unwrap_or_default();
+
+            for (id, prefab, name) in prefabs {
+                let mut builder = Entity::builder();
+                match prefab {
+                    Some(prefab) => prefab(&mut builder),
+                    None => tracing::warn!(%id, name, "Unknown prefab"),
+                }
+
+                builder.set_default(prefab_mounted()).append_to(world, id)?;
+            }
+
+            anyhow::Ok(())
+        })
+        .boxed()
+}
+
+/// Adds a placement mode for level blockouts.
+///
+/// Press `P` to toggle placement, `Left Click` to place the current prefab, `T` to rotate it, `Tab`
+/// to cycle between prefabs, and `V` to duplicate the entity selected by the
+/// [`ManipulatorPlugin`](crate::manipulator::ManipulatorPlugin).
+///
+/// The keys avoid the movement keys of the cameras, and the number keys used by the manipulator
+/// and dialogue. While placing, left click is not forwarded to the manipulator.
+pub struct PlacementPlugin {
+    prefabs: Vec<(String, PrefabFn)>,
+    settings: PlacementSettings,
+}
+
+impl PlacementPlugin {
+    pub fn new(settings: PlacementSettings) -> Self {
+        Self {
+            prefabs: Vec::new(),
+            settings,
+        }
+    }
+
+    /// Add a placeable prefab
+    pub fn with_prefab(
+        mut self,
+        name: impl Into<String>,
+        prefab: impl 'static + Send + Sync + Fn(&mut EntityBuilder),
+    ) -> Self {
+        self.prefabs.push((name.into(), Arc::new(prefab)));
+        self
+    }
+}
+
+impl Plugin for PlacementPlugin {
+    fn install(
+        &self,
+        world: &mut World,
+        _: &AssetCache,
+        schedules: &mut ScheduleSetBuilder,
+    ) -> anyhow::Result<()> {
+        let key_action = |key: Key| {
+            let mut action = Action::new();
+            action.add(KeyBinding::new(key));
+            action
+        };
+
+        let mut place = Action::new();
+        place.add(MouseButtonBinding::new(MouseButton::Left));
+
+        let mut cursor_position = Action::new();
+        cursor_position.add(CursorPositionBinding::new(true));
+
+        let mut state = PlacementState::new(self.settings);
+        state.prefabs = self.prefabs.clone();
+
+        Entity::builder()
+            .set(
+                input_state(),
+                InputState::new()
+                    .with_action(
+                        toggle_placement_action(),
+                        key_action(Key::Character("p".into())),
+                    )
+                    .with_action(place_action(), place)
+                    .with_action(
+                        rotate_brush_action(),
+                        key_action(Key::Character("t".into())),
+                    )
+                    .with_action(cycle_prefab_action(), key_action(Key::Named(NamedKey::Tab)))
+                    .with_action(duplicate_action(), key_action(Key::Character("v".into())))
+                    .with_action(placement_cursor_action(), cursor_position),
+            )
+            .set_default(toggle_placement_action())
+            .set_default(place_action())
+            .set_default(rotate_brush_action())
+            .set_default(cycle_prefab_action())
+            .set_default(duplicate_action())
+            .set_default(placement_cursor_action())
+            .set(placement_state(), state)
+            .spawn(world);
+
+        schedules
+            .per_tick_mut()
+            .with_system(placement_system())
+            .with_system(mount_prefabs_system())
+            .with_system(PlacementState::draw_gizmos_system());
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use glam::vec3;
+    use ivy_physics::state::PhysicsStateConfiguration;
+
+    use super::*;
+
+    #[test]
+    fn snapping() {
+        let settings = PlacementSettings {
+            grid_size: 0.5,
+            rotation_increment: FRAC_PI_4,
+        };
+
+        assert_eq!(
+            settings.snap_position(vec3(0.3, -0.2, 1.1)),
+            vec3(0.5, 0.0, 1.0)
+        );
+        assert_eq!(settings.snap_angle(0.7), FRAC_PI_4);
+
+        let free = PlacementSettings {
+            grid_size: 0.0,
+            rotation_increment: 0.0,
+        };
+
+        assert_eq!(free.snap_position(vec3(0.3, 0.2, 0.1)), vec3(0.3, 0.2, 0.1));
+        assert_eq!(free.snap_angle(0.7), 0.7);
+    }
+
+    #[test]
+    fn instantiate_and_duplicate() {
+        let state = PlacementState::new(PlacementSettings::default())
+            .with_prefab("crate", |builder| {
+                builder.set(scale(), Vec3::splat(2.0));
+            })
+            .with_prefab("wall", |_| {});
+
+        assert_eq!(state.current_prefab(), Some("crate"));
+        assert!(state
+            .instantiate("missing", Vec3::ZERO, Quat::IDENTITY)
+            .is_none());
+
+        let mut world = World::new();
+        let id = state
+            .instantiate("crate", vec3(1.0, 0.0, 1.0), Quat::IDENTITY)
+            .unwrap()
+            .spawn(&mut world);
+
+        assert_eq!(&*world.get(id, placed_prefab()).unwrap(), "crate");
+        assert_eq!(*world.get(id, scale()).unwrap(), Vec3::splat(2.0));
+
+        let mut cmd = CommandBuffer::new();
+        state.duplicate(&world, &mut cmd, id, vec3(-0.2, 0.0, -0.9));
+        cmd.apply(&mut world).unwrap();
+
+        let placed = Query::new(position().copied())
+            .with(placed_prefab())
+            .borrow(&world)
+            .iter()
+            .collect::<Vec<_>>();
+
+        assert_eq!(placed.len(), 2);
+        assert!(placed.contains(&vec3(1.0, 0.0, 0.0)));
+    }
+
+    #[test]
+    fn preview_without_surface() {
+        let physics_state = PhysicsState::new(&PhysicsStateConfiguration::default(), 1.0 / 60.0);
+        let mut state = PlacementState::new(PlacementSettings::default());
+
+        state.set_enabled(true);
+        state.update_preview(&physics_state, Vec3::Y, -Vec3::Y);
+        assert_eq!(state.preview, None);
+    }
+}
@@ -4,7 +4,7 @@ use flax::{
     system, BoxedSystem, CommandBuffer, Component, ComponentMut, Entity, Fetch, FetchExt, Query,
     QueryBorrow, System, World,
 };
-use glam::{vec2, vec4, Mat4, Vec2, Vec3, Vec4Swizzles};
+use glam::{Mat4, Vec2, Vec3};
 use ivy_assets::AssetCache;
 use ivy_core::{
     components::{
@@ -29,7 +29,10 @@ use ivy_physics::{
     state::PhysicsState,
     RigidBodyBundle,
 };
-use ivy_wgpu::components::projection_matrix;
+use ivy_wgpu::{
+    camera::{ndc_to_screen, screen_to_ndc, Camera},
+    components::projection_matrix,
+};
 
 pub struct PickingState {
     picked_object: Option<(Entity, Vec3, f32)>,
@@ -226,6 +229,51 @@ impl Default for CameraQuery {
     }
 }
 
+impl CameraQueryItem<'_> {
+    pub fn camera(&self) -> Camera {
+        Camera::new(*self.transform, *self.projection)
+    }
+}
+
+/// Returns the world space origin and direction of a ray through the normalized cursor position
+pub fn screen_ray(camera: &CameraQueryItem, cursor_pos: Vec2) -> (Vec3, Vec3) {
+    camera.camera().screen_to_ray(screen_to_ndc(cursor_pos))
+}
+
+/// Returns where a ray intersects the horizontal plane at `height`, if it points towards it
+pub fn ray_plane_intersection(origin: Vec3, dir: Vec3, height: f32) -> Option<Vec3> {
+    if dir.y.abs() < 1e-6 {
+        return None;
+    }
+
+    let t = (height - origin.y) / dir.y;
+    (t >= 0.0).then(|| origin + dir * t)
+}
+
+/// Returns the point on the horizontal ground plane at `height` under the normalized cursor
+/// position
+pub fn screen_to_ground(camera: &CameraQueryItem, cursor_pos: Vec2, height: f32) -> Option<Vec3> {
+    let (origin, dir) = screen_ray(camera, cursor_pos);
+    ray_plane_intersection(origin, dir, height)
+}
+
+/// Projects a world space point to a normalized screen position, or `None` if it is behind the
+/// camera
+pub fn world_to_screen(camera: &CameraQueryItem, point: Vec3) -> Option<Vec2> {
+    camera.camera().world_to_screen(point).map(ndc_to_screen)
+}
+
+/// Returns true if `point` is within the screen space rectangle spanned by two normalized cursor
+/// positions, such as for box selection
+pub fn in_screen_rect(camera: &CameraQueryItem, start: Vec2, end: Vec2, point: Vec3) -> bool {
+    let Some(pos) = world_to_screen(camera, point) else {
+        return false;
+    };
+
+    let (min, max) = (start.min(end), start.max(end));
+    pos.cmpge(min).all() && pos.cmple(max).all()
+}
+
 type PickingQuery = (
     EntityRefs,
     Component<bool>,
@@ -281,15 +329,7 @@ pub fn pick_ray_system() -> BoxedSystem {
                         return Ok(());
                     }
 
-                    let cursor_pos = vec2(cursor_pos.x * 2.0 - 1.0, -(cursor_pos.y * 2.0 - 1.0));
-
-                    let ray_eye =
-                        camera.projection.inverse() * vec4(cursor_pos.x, cursor_pos.y, 1.0, 1.0);
-                    let ray_eye = vec4(ray_eye.x, ray_eye.y, -1.0, 0.0);
-
-                    let world_ray = (*camera.transform * ray_eye).xyz().normalize();
-
-                    let origin = camera.transform.transform_point3(Vec3::ZERO);
+                    let (origin, world_ray) = screen_ray(&camera, *cursor_pos);
 
                     state.update(world, cmd, physics_state, origin, world_ray)?;
                 }
@@ -299,3 +339,17 @@ pub fn pick_ray_system() -> BoxedSystem {
         )
         .boxed()
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn plane_intersection() {
+        let hit = ray_plane_intersection(Vec3::new(1.0, 10.0, 0.0), Vec3::new(0.0, -1.0, 1.0), 2.0);
+        assert_eq!(hit, Some(Vec3::new(1.0, 2.0, 8.0)));
+
+        assert_eq!(ray_plane_intersection(Vec3::Y, Vec3::Y, 0.0), None);
+        assert_eq!(ray_plane_intersection(Vec3::Y, Vec3::X, 0.0), None);
+    }
+}
@@ -0,0 +1,339 @@
+use std::{
+    fs,
+    io::Write,
+    path::PathBuf,
+    process::{Child, ChildStdin, Command, Stdio},
+    str::FromStr,
+    thread::JoinHandle,
+    time::Duration,
+};
+
+use anyhow::Context;
+use flax::World;
+use ivy_assets::AssetCache;
+use ivy_core::{
+    components::{engine, fixed_frame_delta},
+    layer::events::EventRegisterContext,
+    Layer,
+};
+use ivy_wgpu::renderer::readback::ReadbackFrame;
+
+/// Where the frames of a replay are written
+#[derive(Debug, Clone)]
+pub enum ReplayOutput {
+    /// Writes each frame as a numbered png inside the directory
+    Images { dir: PathBuf },
+    /// Pipes raw frames into `ffmpeg`, which must be available in `PATH`
+    Ffmpeg {
+        path: PathBuf,
+        /// Additional encoder arguments, such as `-c:v libx264 -crf 18`
+        args: Vec<String>,
+    },
+}
+
+#[derive(Debug, Clone)]
+pub struct ReplayConfig {
+    /// Simulation and output frame rate
+    pub frame_rate: u32,
+    pub output: ReplayOutput,
+    /// Stop writing after this many frames
+    pub frame_count: Option<u64>,
+}
+
+impl ReplayConfig {
+    pub fn new(frame_rate: u32, output: ReplayOutput) -> Self {
+        Self {
+            frame_rate,
+            output,
+            frame_count: None,
+        }
+    }
+
+    /// Set the number of frames to record
+    pub fn with_frame_count(mut self, frame_count: u64) -> Self {
+        self.frame_count = Some(frame_count);
+        self
+    }
+
+    /// Reads the configuration from the environment, recording images into `IVY_REPLAY_DIR` if
+    /// set.
+    ///
+    /// `IVY_REPLAY_FRAMES` and `IVY_REPLAY_FRAME_RATE` optionally set the number of frames and
+    /// the frame rate, which defaults to 60.
+    pub fn from_env() -> anyhow::Result<Option<Self>> {
+        let Some(dir) = std::env::var_os("IVY_REPLAY_DIR") else {
+            return Ok(None);
+        };
+
+        let mut config = Self::new(
+            env_var("IVY_REPLAY_FRAME_RATE")?.unwrap_or(60),
+            ReplayOutput::Images { dir: dir.into() },
+        );
+
+        config.frame_count = env_var("IVY_REPLAY_FRAMES")?;
+
+        Ok(Some(config))
+    }
+
+    pub fn frame_delta(&self) -> Duration {
+        Duration::from_secs_f64(1.0 / self.frame_rate as f64)
+    }
+}
+
+fn env_var<T>(key: &str) -> anyhow::Result<Option<T>>
+where
+    T: FromStr,
+    T::Err: 'static + std::error::Error + Send + Sync,
+{
+    std::env::var(key)
+        .ok()
+        .map(|v| {
+            v.parse()
+                .with_context(|| format!("Invalid value for {key}: {v:?}"))
+        })
+        .transpose()
+}
+
+/// Steps the simulation at a fixed rate and writes each rendered frame to disk.
+///
+/// Frames are received from a [`ReadbackNode`](ivy_wgpu::renderer::readback::ReadbackNode) through
+/// [`Self::sender`], and are encoded on a background thread to not stall rendering.
+///
+/// Since time advances by a fixed amount per frame regardless of how long the frame took to
+/// render and encode, the output plays back at the configured frame rate.
+///
+/// Dropping the layer writes the frames which have already been received and finishes the
+/// output, even if the renderer still holds a sender.
+pub struct ReplayLayer {
+    config: ReplayConfig,
+    tx: flume::Sender<ReadbackFrame>,
+    /// Closed when the layer is dropped, as the senders held by the renderer may outlive it
+    stop: Option<flume::Sender<()>>,
+    thread: Option<JoinHandle<anyhow::Result<()>>>,
+}
+
+impl ReplayLayer {
+    pub fn new(config: ReplayConfig) -> anyhow::Result<Self> {
+        let (tx, rx) = flume::unbounded();
+        let (stop, stop_rx) = flume::bounded::<()>(0);
+
+        let mut sink = FrameSink::new(&config)?;
+        let frame_count = config.frame_count;
+
+        let thread = std::thread::Builder::new()
+            .name("replay_export".into())
+            .spawn(move || {
+                loop {
+                    let frame = flume::Selector::new()
+                        .recv(&rx, |frame| frame.ok())
+                        .recv(&stop_rx, |_| None)
+                        .wait();
+
+                    let Some(frame) = frame else {
+                        break;
+                    };
+
+                    if frame_count.is_some_and(|v| frame.index >= v) {
+                        return sink.finish();
+                    }
+
+                    sink.write(&frame)?;
+                }
+
+                for frame in rx.try_iter() {
+                    if frame_count.is_some_and(|v| frame.index >= v) {
+                        break;
+                    }
+
+                    sink.write(&frame)?;
+                }
+
+                sink.finish()
+            })?;
+
+        Ok(Self {
+            config,
+            tx,
+            stop: Some(stop),
+            thread: Some(thread),
+        })
+    }
+
+    /// Sender to pass to the readback node of the renderer
+    pub fn sender(&self) -> flume::Sender<ReadbackFrame> {
+        self.tx.clone()
+    }
+
+    pub fn config(&self) -> &ReplayConfig {
+        &self.config
+    }
+}
+
+impl Drop for ReplayLayer {
+    fn drop(&mut self) {
+        let Some(thread) = self.thread.take() else {
+            return;
+        };
+
+        // Wakes the export thread, which finishes writing the received frames
+        self.stop.take();
+
+        match thread.join() {
+            Ok(Ok(())) => {}
+            Ok(Err(err)) => tracing::error!("Failed to export replay: {err:?}"),
+            Err(_) => tracing::error!("Replay export thread panicked"),
+        }
+    }
+}
+
+impl Layer for ReplayLayer {
+    fn register(
+        &mut self,
+        world: &mut World,
+        _: &AssetCache,
+        _: EventRegisterContext<Self>,
+    ) -> anyhow::Result<()>
+    where
+        Self: Sized,
+    {
+        world.set(engine(), fixed_frame_delta(), self.config.frame_delta())?;
+        tracing::info!(
+            frame_rate = self.config.frame_rate,
+            output = ?self.config.output,
+            "recording replay"
+        );
+
+        Ok(())
+    }
+}
+
+enum FrameSink {
+    Images {
+        dir: PathBuf,
+    },
+    Ffmpeg {
+        frame_rate: u32,
+        path: PathBuf,
+        args: Vec<String>,
+        process: Option<(Child, ChildStdin)>,
+    },
+}
+
+impl FrameSink {
+    fn new(config: &ReplayConfig) -> anyhow::Result<Self> {
+        match &config.output {
+            ReplayOutput::Images { dir } => {
+                fs::create_dir_all(dir)
+                    .with_context(|| format!("Failed to create replay directory {dir:?}"))?;
+
+                Ok(Self::Images { dir: dir.clone() })
+            }
+            ReplayOutput::Ffmpeg { path, args } => Ok(Self::Ffmpeg {
+                frame_rate: config.frame_rate,
+                path: path.clone(),
+                args: args.clone(),
+                process: None,
+            }),
+        }
+    }
+
+    fn write(&mut self, frame: &ReadbackFrame) -> anyhow::Result<()> {
+        match self {
+            FrameSink::Images { dir } => {
+                let path = dir.join(format!("frame_{:06}.png", frame.index));
+                frame
+                    .to_image()
+                    .context("Invalid frame dimensions")?
+                    .save(&path)
+                    .with_context(|| format!("Failed to write frame {path:?}"))?;
+            }
+            FrameSink::Ffmpeg {
+                frame_rate,
+                path,
+                args,
+                process,
+            } => {
+                // The size is not known until the first frame arrives
+                if process.is_none() {
+                    let mut child = Command::new("ffmpeg")
+                        .args(["-y", "-f", "rawvideo", "-pix_fmt", "rgba", "-s"])
+                        .arg(format!("{}x{}", frame.width, frame.height))
+                        .arg("-r")
+                        .arg(frame_rate.to_string())
+                        .args(["-i", "-"])
+                        .args(args.iter())
+                        .arg(&*path)
+                        .stdin(Stdio::piped())
+                        .stdout(Stdio::null())
+                        .spawn()
+                        .context("Failed to spawn ffmpeg")?;
+
+                    let stdin = child.stdin.take().context("Missing ffmpeg stdin")?;
+                    *process = Some((child, stdin));
+                }
+
+                let (_, stdin) = process.as_mut().unwrap();
+                stdin
+                    .write_all(&frame.data)
+                    .context("Failed to write frame to ffmpeg")?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn finish(self) -> anyhow::Result<()> {
+        if let FrameSink::Ffmpeg {
+            process: Some((mut child, stdin)),
+            path,
+            ..
+        } = self
+        {
+            drop(stdin);
+            let status = child.wait()?;
+            anyhow::ensure!(status.success(), "ffmpeg exited with {status}");
+            tracing::info!(?path, "finished encoding replay");
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn frame(index: u64) -> ReadbackFrame {
+        ReadbackFrame {
+            index,
+            width: 2,
+            height: 2,
+            data: vec![255; 16],
+        }
+    }
+
+    #[test]
+    fn drop_with_live_sender() {
+        let dir = std::env::temp_dir().join(format!("ivy-replay-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+
+        let layer = ReplayLayer::new(
+            ReplayConfig::new(60, ReplayOutput::Images { dir: dir.clone() }).with_frame_count(2),
+        )
+        .unwrap();
+
+        // Held by the readback node of the renderer, which outlives the layer
+        let tx = layer.sender();
+        for i in 0..3 {
+            tx.send(frame(i)).unwrap();
+        }
+
+        drop(layer);
+
+        assert!(dir.join("frame_000000.png").exists());
+        assert!(dir.join("frame_000001.png").exists());
+        assert!(!dir.join("frame_000002.png").exists());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,701 @@
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::Context;
+use flax::{
+    component::ComponentValue, components::child_of, entity_ids, serialize::SerdeBuilder,
+    Component, Debuggable, Entity, Query, World,
+};
+use ivy_assets::AssetCache;
+use ivy_core::components::engine;
+use ivy_physics::{
+    components::physics_state,
+    snapshot::{restore_snapshot, PhysicsSnapshot},
+};
+use ivy_scene::serialize::{instantiate, SceneSerializer};
+use ivy_wgpu::renderer::readback::ReadbackFrame;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+flax::component! {
+    /// Marks entities which are stored by a [`WorldSection`], and replaced when a save is loaded
+    pub persistent: () => [ Debuggable ],
+}
+
+/// Current on-disk version of the save game envelope.
+///
+/// Bump this when the layout of the stored sections changes and register a [`SaveMigration`]
+/// to upgrade older saves.
+pub const SAVE_FORMAT_VERSION: u32 = 1;
+
+const SCREENSHOT_FILE: &str = "screenshot.png";
+
+/// Metadata stored alongside each save, cheap to read for save/load menus.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SaveMetadata {
+    /// User facing name of the save
+    pub name: String,
+    /// Seconds since the unix epoch when the save was written
+    pub timestamp: u64,
+    /// Total time played when the save was written
+    pub play_time: Duration,
+    /// File name of the screenshot, relative to the slot directory
+    pub screenshot: Option<String>,
+    /// Arbitrary game defined values, such as the current level or chapter
+    #[serde(default)]
+    pub extra: BTreeMap<String, String>,
+}
+
+impl SaveMetadata {
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            timestamp: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs(),
+            play_time: Duration::ZERO,
+            screenshot: None,
+            extra: BTreeMap::new(),
+        }
+    }
+
+    /// Set the play time
+    pub fn with_play_time(mut self, play_time: Duration) -> Self {
+        self.play_time = play_time;
+        self
+    }
+
+    /// Add a game defined metadata value
+    pub fn with_extra(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.extra.insert(key.into(), value.into());
+        self
+    }
+}
+
+/// The full contents of a save file.
+///
+/// Each registered [`SaveSection`] stores its data under its own key, which allows sections to be
+/// added or removed between game versions without invalidating the whole save.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SaveData {
+    pub version: u32,
+    pub metadata: SaveMetadata,
+    pub sections: BTreeMap<String, serde_json::Value>,
+}
+
+impl SaveData {
+    /// Deserialize a section into a typed value
+    pub fn section<T: DeserializeOwned>(&self, key: &str) -> anyhow::Result<Option<T>> {
+        self.sections
+            .get(key)
+            .map(|v| serde_json::from_value(v.clone()))
+            .transpose()
+            .with_context(|| format!("Failed to deserialize save section {key:?}"))
+    }
+}
+
+/// Shared state of the sections while a save is loaded
+#[derive(Debug, Default)]
+pub struct LoadContext {
+    entities: BTreeMap<Entity, Entity>,
+}
+
+impl LoadContext {
+    /// Returns the entity which was stored as `saved`, if it has been loaded
+    pub fn entity(&self, saved: Entity) -> Option<Entity> {
+        self.entities.get(&saved).copied()
+    }
+
+    /// Records the entity `saved` was loaded into, for the sections loaded after
+    pub fn map_entity(&mut self, saved: Entity, id: Entity) {
+        self.entities.insert(saved, id);
+    }
+}
+
+/// A part of the game state which is persisted in a save
+pub trait SaveSection: 'static + Send + Sync {
+    /// Unique key of the section inside the save file
+    fn key(&self) -> &str;
+
+    fn save(&self, world: &World) -> anyhow::Result<serde_json::Value>;
+
+    /// Sections are loaded in the order they were added
+    fn load(
+        &self,
+        world: &mut World,
+        data: serde_json::Value,
+        ctx: &mut LoadContext,
+    ) -> anyhow::Result<()>;
+}
+
+#[derive(Serialize, Deserialize)]
+struct WorldData {
+    entities: serde_json::Value,
+    #[serde(default)]
+    physics: Option<PhysicsSnapshot>,
+}
+
+/// Persists the [`persistent`] entities of the world, using the [`SceneSerializer`] with the
+/// registered components.
+///
+/// Loading replaces the persistent entities of the world, along with their children.
+///
+/// ```rust,ignore
+/// WorldSection::new("world", &assets, SerdeBuilder::new().with(position()).with(rotation()))
+///     .with_physics(true)
+/// ```
+pub struct WorldSection {
+    key: String,
+    serializer: SceneSerializer,
+    assets: AssetCache,
+    physics: bool,
+}
+
+impl WorldSection {
+    pub fn new(key: impl Into<String>, assets: &AssetCache, components: &mut SerdeBuilder) -> Self {
+        Self {
+            key: key.into(),
+            serializer: SceneSerializer::new(components.with(persistent())),
+            assets: assets.clone(),
+            physics: false,
+        }
+    }
+
+    /// Store the physics simulation, which restores the contacts, sleeping bodies and joints of
+    /// the persistent entities exactly
+    pub fn with_physics(mut self, physics: bool) -> Self {
+        self.physics = physics;
+        self
+    }
+}
+
+impl SaveSection for WorldSection {
+    fn key(&self) -> &str {
+        &self.key
+    }
+
+    fn save(&self, world: &World) -> anyhow::Result<serde_json::Value> {
+        // The serializer stores all entities with any of the components, so the entities which
+        // are not persistent are removed from a copy
+        let mut copy = self
+            .serializer
+            .deserialize(
+                self.serializer
+                    .serialize(world, serde_json::value::Serializer)?,
+            )
+            .context("Failed to copy world")?;
+
+        let transient = Query::new(entity_ids())
+            .without(persistent())
+            .borrow(&copy)
+            .iter()
+            .collect::<Vec<_>>();
+
+        for id in transient {
+            copy.despawn(id)?;
+        }
+
+        let physics = if self.physics {
+            Some(world.get(engine(), physics_state())?.snapshot())
+        } else {
+            None
+        };
+
+        let data = WorldData {
+            entities: self
+                .serializer
+                .serialize(&copy, serde_json::value::Serializer)?,
+            physics,
+        };
+
+        serde_json::to_value(data).context("Failed to serialize world")
+    }
+
+    fn load(
+        &self,
+        world: &mut World,
+        data: serde_json::Value,
+        ctx: &mut LoadContext,
+    ) -> anyhow::Result<()> {
+        let data: WorldData = serde_json::from_value(data).context("Malformed world section")?;
+        let loaded = self
+            .serializer
+            .deserialize(data.entities)
+            .context("Failed to deserialize world")?;
+
+        let current = Query::new(entity_ids())
+            .with(persistent())
+            .borrow(world)
+            .iter()
+            .collect::<Vec<_>>();
+
+        for id in current {
+            // Children may already have been despawned along with their parent
+            if world.is_alive(id) {
+                world.despawn_recursive(id, child_of)?;
+            }
+        }
+
+        for (saved, id) in instantiate(world, &self.assets, loaded)? {
+            ctx.map_entity(saved, id);
+        }
+
+        if let Some(mut snapshot) = data.physics {
+            snapshot.retain_entities(|id| ctx.entity(id).is_some());
+            snapshot.remap_entities(|id| ctx.entity(id));
+            restore_snapshot(world, snapshot)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Persists a component of the engine entity, such as the settings of the current playthrough
+///
+/// ```rust,ignore
+/// ComponentSection::new("settings", difficulty())
+/// ```
+pub struct ComponentSection<T> {
+    key: String,
+    component: Component<T>,
+}
+
+impl<T> ComponentSection<T> {
+    pub fn new(key: impl Into<String>, component: Component<T>) -> Self {
+        Self {
+            key: key.into(),
+            component,
+        }
+    }
+}
+
+impl<T> SaveSection for ComponentSection<T>
+where
+    T: ComponentValue + Serialize + DeserializeOwned + Send + Sync,
+{
+    fn key(&self) -> &str {
+        &self.key
+    }
+
+    fn save(&self, world: &World) -> anyhow::Result<serde_json::Value> {
+        let value = world.get(engine(), self.component)?;
+        serde_json::to_value(&*value)
+            .with_context(|| format!("Failed to serialize {}", self.component.name()))
+    }
+
+    fn load(
+        &self,
+        world: &mut World,
+        data: serde_json::Value,
+        _: &mut LoadContext,
+    ) -> anyhow::Result<()> {
+        let value = serde_json::from_value(data)
+            .with_context(|| format!("Failed to deserialize {}", self.component.name()))?;
+
+        world.set(engine(), self.component, value)?;
+        Ok(())
+    }
+}
+
+/// Upgrades a save from `from_version` to `from_version + 1`
+pub type SaveMigration = Box<dyn Send + Sync + Fn(&mut SaveData) -> anyhow::Result<()>>;
+
+/// Manages save slots on disk.
+///
+/// Each slot is stored as a directory containing `save.json` and optionally a screenshot of the
+/// frame the game was saved at.
+pub struct SaveGames {
+    root: PathBuf,
+    version: u32,
+    sections: Vec<Box<dyn SaveSection>>,
+    migrations: BTreeMap<u32, SaveMigration>,
+}
+
+impl SaveGames {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self {
+            root: root.into(),
+            version: SAVE_FORMAT_VERSION,
+            sections: Vec::new(),
+            migrations: BTreeMap::new(),
+        }
+    }
+
+    /// Set the current version of the game's save data
+    pub fn with_version(mut self, version: u32) -> Self {
+        self.version = version;
+        self
+    }
+
+    /// Add a section to persist in each save
+    pub fn with_section(mut self, section: impl SaveSection) -> Self {
+        self.sections.push(Box::new(section));
+        self
+    }
+
+    /// Register a migration upgrading saves of `from_version` to the next version
+    pub fn with_migration(
+        mut self,
+        from_version: u32,
+        migration: impl 'static + Send + Sync + Fn(&mut SaveData) -> anyhow::Result<()>,
+    ) -> Self {
+        self.migrations.insert(from_version, Box::new(migration));
+        self
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    fn slot_dir(&self, slot: &str) -> anyhow::Result<PathBuf> {
+        validate_slot(slot)?;
+        Ok(self.root.join(slot))
+    }
+
+    /// Path of the screenshot of the given slot
+    pub fn screenshot_path(&self, slot: &str) -> anyhow::Result<PathBuf> {
+        Ok(self.slot_dir(slot)?.join(SCREENSHOT_FILE))
+    }
+
+    /// Saves the world into the given slot, overwriting any previous save.
+    ///
+    /// The screenshot is usually the last frame read back from the renderer.
+    ///
+    /// The previous save in the slot is left untouched if any section fails to save.
+    pub fn save(
+        &self,
+        world: &World,
+        slot: &str,
+        mut metadata: SaveMetadata,
+        screenshot: Option<&ReadbackFrame>,
+    ) -> anyhow::Result<()> {
+        let dir = self.slot_dir(slot)?;
+
+        let sections = self
+            .sections
+            .iter()
+            .map(|section| {
+                let data = section
+                    .save(world)
+                    .with_context(|| format!("Failed to save section {:?}", section.key()))?;
+                anyhow::Ok((section.key().to_string(), data))
+            })
+            .collect::<anyhow::Result<_>>()?;
+
+        metadata.screenshot = screenshot.map(|_| SCREENSHOT_FILE.into());
+
+        let data = SaveData {
+            version: self.version,
+            metadata,
+            sections,
+        };
+
+        let bytes = serde_json::to_vec_pretty(&data)?;
+
+        fs::create_dir_all(&dir)
+            .with_context(|| format!("Failed to create save directory {dir:?}"))?;
+
+        // Write to temporary files first to not corrupt the slot if the game crashes mid-write.
+        //
+        // The temporary screenshot keeps the extension the image format is deduced from.
+        let screenshot_path = dir.join(SCREENSHOT_FILE);
+        let tmp_screenshot_path = dir.join(format!("tmp.{SCREENSHOT_FILE}"));
+        if let Some(frame) = screenshot {
+            frame
+                .to_image()
+                .context("Screenshot does not match its size")?
+                .save(&tmp_screenshot_path)
+                .with_context(|| format!("Failed to write screenshot {tmp_screenshot_path:?}"))?;
+        }
+
+        let path = dir.join("save.json");
+        let tmp_path = dir.join("save.json.tmp");
+        fs::write(&tmp_path, bytes)
+            .with_context(|| format!("Failed to write save file {tmp_path:?}"))?;
+
+        if screenshot.is_some() {
+            fs::rename(&tmp_screenshot_path, &screenshot_path)?;
+        } else if screenshot_path.exists() {
+            // Remove the screenshot of the previous save in the slot
+            fs::remove_file(&screenshot_path)?;
+        }
+
+        fs::rename(&tmp_path, &path)?;
+
+        tracing::info!(slot, ?path, "saved game");
+        Ok(())
+    }
+
+    /// Reads and migrates the save in the given slot without applying it to the world
+    pub fn read(&self, slot: &str) -> anyhow::Result<SaveData> {
+        let path = self.slot_dir(slot)?.join("save.json");
+        let bytes =
+            fs::read(&path).with_context(|| format!("Failed to read save file {path:?}"))?;
+
+        let mut data: SaveData = serde_json::from_slice(&bytes)
+            .with_context(|| format!("Malformed save file {path:?}"))?;
+
+        self.migrate(&mut data)?;
+        Ok(data)
+    }
+
+    fn migrate(&self, data: &mut SaveData) -> anyhow::Result<()> {
+        if data.version > self.version {
+            anyhow::bail!(
+                "Save version {} is newer than the supported version {}",
+                data.version,
+                self.version
+            );
+        }
+
+        while data.version < self.version {
+            let migration = self.migrations.get(&data.version).with_context(|| {
+                format!("No migration registered for save version {}", data.version)
+            })?;
+
+            migration(data)
+                .with_context(|| format!("Failed to migrate save version {}", data.version))?;
+
+            data.version += 1;
+        }
+
+        Ok(())
+    }
+
+    /// Loads the given slot into the world
+    pub fn load(&self, world: &mut World, slot: &str) -> anyhow::Result<SaveMetadata> {
+        let mut data = self.read(slot)?;
+        let mut ctx = LoadContext::default();
+
+        for section in &self.sections {
+            let Some(value) = data.sections.remove(section.key()) else {
+                tracing::warn!(slot, key = section.key(), "missing save section");
+                continue;
+            };
+
+            section
+                .load(world, value, &mut ctx)
+                .with_context(|| format!("Failed to load section {:?}", section.key()))?;
+        }
+
+        Ok(data.metadata)
+    }
+
+    /// Deletes the save in the given slot
+    pub fn delete(&self, slot: &str) -> anyhow::Result<()> {
+        let dir = self.slot_dir(slot)?;
+        if dir.exists() {
+            fs::remove_dir_all(&dir)
+                .with_context(|| format!("Failed to delete save directory {dir:?}"))?;
+        }
+
+        Ok(())
+    }
+
+    /// Returns the metadata of all existing saves, most recent first
+    pub fn list(&self) -> anyhow::Result<Vec<(String, SaveMetadata)>> {
+        if !self.root.exists() {
+            return Ok(Vec::new());
+        }
+
+        let mut saves = Vec::new();
+        for entry in fs::read_dir(&self.root)? {
+            let entry = entry?;
+            if !entry.file_type()?.is_dir() {
+                continue;
+            }
+
+            let slot = entry.file_name().to_string_lossy().into_owned();
+            match self.read(&slot) {
+                Ok(data) => saves.push((slot, data.metadata)),
+                Err(err) => tracing::warn!(slot, "Skipping unreadable save: {err:?}"),
+            }
+        }
+
+        saves.sort_by_key(|(_, metadata)| std::cmp::Reverse(metadata.timestamp));
+        Ok(saves)
+    }
+}
+
+/// Slot names are used as directory names, and are restricted to not escape the save directory
+fn validate_slot(slot: &str) -> anyhow::Result<()> {
+    let valid = !slot.is_empty()
+        && slot.len() <= 64
+        && !slot.starts_with(['.', ' '])
+        && slot
+            .chars()
+            .all(|c| c.is_alphanumeric() || matches!(c, '-' | '_' | ' ' | '.'));
+
+    anyhow::ensure!(valid, "Invalid save slot name {slot:?}");
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use flax::FetchExt;
+    use glam::Vec3;
+    use ivy_core::components::position;
+
+    use super::*;
+
+    fn temp_root(name: &str) -> PathBuf {
+        let root = std::env::temp_dir().join(format!("ivy-save-{}-{name}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        root
+    }
+
+    fn positions(world: &World) -> Vec<Vec3> {
+        let mut positions = Query::new(position().copied())
+            .with(persistent())
+            .borrow(world)
+            .iter()
+            .collect::<Vec<_>>();
+
+        positions.sort_by(|a, b| a.x.total_cmp(&b.x));
+        positions
+    }
+
+    #[test]
+    fn save_and_load() {
+        let root = temp_root("load");
+        let assets = AssetCache::new();
+        let saves = SaveGames::new(&root).with_section(WorldSection::new(
+            "world",
+            &assets,
+            SerdeBuilder::new().with(position()),
+        ));
+
+        let mut world = World::new();
+        for x in [1.0, 2.0] {
+            Entity::builder()
+                .set(position(), Vec3::X * x)
+                .set(persistent(), ())
+                .spawn(&mut world);
+        }
+
+        let camera = Entity::builder().set(position(), Vec3::Y).spawn(&mut world);
+
+        saves
+            .save(&world, "slot 1", SaveMetadata::new("First"), None)
+            .unwrap();
+
+        // Changes after saving are replaced, without duplicating the saved entities
+        Entity::builder()
+            .set(position(), Vec3::X * 3.0)
+            .set(persistent(), ())
+            .spawn(&mut world);
+
+        let metadata = saves.load(&mut world, "slot 1").unwrap();
+        assert_eq!(metadata.name, "First");
+        assert_eq!(positions(&world), [Vec3::X, Vec3::X * 2.0]);
+
+        saves.load(&mut world, "slot 1").unwrap();
+        assert_eq!(positions(&world), [Vec3::X, Vec3::X * 2.0]);
+
+        // Entities which are not persistent are left alone
+        assert_eq!(*world.get(camera, position()).unwrap(), Vec3::Y);
+
+        let list = saves.list().unwrap();
+        assert_eq!(list.len(), 1);
+        assert_eq!(list[0].0, "slot 1");
+        assert_eq!(list[0].1.screenshot, None);
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn migrate() {
+        let root = temp_root("migrate");
+        let saves = SaveGames::new(&root);
+        saves
+            .save(&World::new(), "slot", SaveMetadata::new("Old"), None)
+            .unwrap();
+
+        let saves = SaveGames::new(&root)
+            .with_version(SAVE_FORMAT_VERSION + 1)
+            .with_migration(SAVE_FORMAT_VERSION, |data| {
+                data.metadata.name.push_str(" (migrated)");
+                Ok(())
+            });
+
+        let data = saves.read("slot").unwrap();
+        assert_eq!(data.version, SAVE_FORMAT_VERSION + 1);
+        assert_eq!(data.metadata.name, "Old (migrated)");
+
+        // Saves from a newer version are rejected
+        assert!(SaveGames::new(&root).read("slot").is_ok());
+        assert!(SaveGames::new(&root).with_version(0).read("slot").is_err());
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    struct FailingSection;
+
+    impl SaveSection for FailingSection {
+        fn key(&self) -> &str {
+            "failing"
+        }
+
+        fn save(&self, _: &World) -> anyhow::Result<serde_json::Value> {
+            anyhow::bail!("section failed")
+        }
+
+        fn load(
+            &self,
+            _: &mut World,
+            _: serde_json::Value,
+            _: &mut LoadContext,
+        ) -> anyhow::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn failed_save_keeps_slot() {
+        let root = temp_root("failed");
+        let saves = SaveGames::new(&root);
+        saves
+            .save(&World::new(), "slot", SaveMetadata::new("Previous"), None)
+            .unwrap();
+
+        let screenshot_path = saves.screenshot_path("slot").unwrap();
+        fs::write(&screenshot_path, b"previous screenshot").unwrap();
+
+        let failing = SaveGames::new(&root).with_section(FailingSection);
+        assert!(failing
+            .save(&World::new(), "slot", SaveMetadata::new("Failed"), None)
+            .is_err());
+
+        assert_eq!(saves.read("slot").unwrap().metadata.name, "Previous");
+        assert_eq!(fs::read(&screenshot_path).unwrap(), b"previous screenshot");
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn slot_names() {
+        for slot in ["slot 1", "quick_save", "autosave-2", "v1.2"] {
+            assert!(validate_slot(slot).is_ok(), "{slot}");
+        }
+
+        for slot in [
+            "",
+            "..",
+            "../escape",
+            "a/b",
+            "a\\b",
+            ".hidden",
+            "/root",
+            " padded",
+        ] {
+            assert!(validate_slot(slot).is_err(), "{slot}");
+        }
+
+        let saves = SaveGames::new(temp_root("escape"));
+        assert!(saves.delete("../escape").is_err());
+    }
+}
@@ -0,0 +1,137 @@
+use flax::{
+    component, components::child_of, entity_ids, BoxedSystem, Component, Entity, FetchExt, Query,
+    System, World,
+};
+use ivy_assets::AssetCache;
+use ivy_core::{
+    components::TransformBundle,
+    update_layer::{Plugin, ScheduleSetBuilder},
+};
+use ivy_graphics::spline::{extrude, ExtrudeOptions, Spline, SplineProfile};
+use ivy_physics::{shapes::trimesh_from_mesh, ColliderBundle};
+use ivy_wgpu::{material_desc::MaterialData, mesh_desc::MeshDesc, renderer::RenderObjectBundle};
+
+/// Describes the mesh extruded along the [`spline`] of an entity
+#[derive(Debug, Clone)]
+pub struct SplineMesh {
+    pub profile: SplineProfile,
+    pub options: ExtrudeOptions,
+    pub materials: Vec<(Component<MaterialData>, MaterialData)>,
+    /// Generate a trimesh collider for the mesh.
+    ///
+    /// The collider is attached to the rigid body of the spline entity or its ancestors.
+    pub collider: bool,
+}
+
+impl SplineMesh {
+    pub fn new(profile: SplineProfile) -> Self {
+        Self {
+            profile,
+            options: ExtrudeOptions::default(),
+            materials: Vec::new(),
+            collider: false,
+        }
+    }
+
+    /// Set the extrusion options
+    pub fn with_options(mut self, options: ExtrudeOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Add a material for the given render pass
+    pub fn with_material(mut self, pass: Component<MaterialData>, material: MaterialData) -> Self {
+        self.materials.push((pass, material));
+        self
+    }
+
+    /// Set whether a collider is generated
+    pub fn with_collider(mut self, collider: bool) -> Self {
+        self.collider = collider;
+        self
+    }
+}
+
+component! {
+    pub spline: Spline,
+    pub spline_mesh: SplineMesh,
+
+    /// Child entity holding the mesh generated from the spline
+    pub spline_mesh_instance: Entity,
+}
+
+/// Regenerates the extruded mesh whenever the spline or its mesh description changes
+fn generate_spline_meshes_system(assets: AssetCache) -> BoxedSystem {
+    let mut query = Query::new((
+        entity_ids(),
+        (spline(), spline_mesh()).modified(),
+        spline_mesh_instance().copied().opt(),
+    ));
+
+    System::builder()
+        .with_world_mut()
+        .build(move |world: &mut World| {
+            let dirty = query
+                .borrow(world)
+                .iter()
+                .map(|(id, (spline, desc), instance)| (id, spline.clone(), desc.clone(), instance))
+                .collect::<Vec<_>>();
+
+            for (id, spline, desc, instance) in dirty {
+                // The renderer and physics do not track modified meshes and shapes, so the
+                // instance is replaced instead
+                if let Some(instance) = instance {
+                    if world.is_alive(instance) {
+                        world.despawn(instance)?;
+                    }
+
+                    world.remove(id, spline_mesh_instance())?;
+                }
+
+                if spline.segment_count() == 0 {
+                    continue;
+                }
+
+                let mesh = extrude(&spline, &desc.profile, &desc.options);
+
+                let mut builder = Entity::builder();
+                builder
+                    .mount(TransformBundle::default())
+                    .set(child_of(id), ());
+
+                if desc.collider {
+                    builder.mount(ColliderBundle::new(trimesh_from_mesh(&mesh)?));
+                }
+
+                builder.mount(RenderObjectBundle::new(
+                    MeshDesc::content(assets.insert(mesh)),
+                    &desc.materials,
+                ));
+
+                let instance = builder.spawn(world);
+                world.set(id, spline_mesh_instance(), instance)?;
+            }
+
+            anyhow::Ok(())
+        })
+        .boxed()
+}
+
+/// Extrudes meshes along entities with a [`spline`] and [`spline_mesh`], for roads, rivers and
+/// fences.
+pub struct SplineMeshPlugin;
+
+impl Plugin for SplineMeshPlugin {
+    fn install(
+        &self,
+        _: &mut World,
+        assets: &AssetCache,
+        schedules: &mut ScheduleSetBuilder,
+    ) -> anyhow::Result<()> {
+        schedules
+            .per_tick_mut()
+            .with_system(generate_spline_meshes_system(assets.clone()));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,450 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::Context;
+use flax::{component, entity_ids, BoxedSystem, FetchExt, Query, System, World};
+use glam::Vec3;
+use ivy_assets::AssetCache;
+use ivy_core::{
+    components::{delta_time, engine, world_transform},
+    update_layer::{Plugin, ScheduleSetBuilder},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::save_game::{LoadContext, SaveSection};
+
+/// Name of the stat accumulating the play time in seconds
+pub const PLAY_TIME: &str = "play_time";
+
+/// Unlocked when a stat reaches a threshold
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Achievement {
+    pub id: String,
+    pub stat: String,
+    pub threshold: f64,
+}
+
+impl Achievement {
+    pub fn new(id: impl Into<String>, stat: impl Into<String>, threshold: f64) -> Self {
+        Self {
+            id: id.into(),
+            stat: stat.into(),
+            threshold,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum StatsEvent {
+    /// A stat changed to the new value
+    Changed { stat: String, value: f64 },
+    /// An achievement was unlocked
+    Unlocked(String),
+}
+
+/// The persisted part of [`Stats`]
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct StatsData {
+    #[serde(default)]
+    pub values: BTreeMap<String, f64>,
+    #[serde(default)]
+    pub unlocked: BTreeSet<String>,
+}
+
+/// Counts named gameplay events, such as kills or distance travelled, and unlocks achievements
+/// when they pass their thresholds.
+///
+/// Changes are reported as [`StatsEvent`]s, which platform backends can subscribe to in order to
+/// mirror stats and achievements.
+#[derive(Debug, Clone, Default)]
+pub struct Stats {
+    data: StatsData,
+    achievements: Vec<Achievement>,
+    events: Vec<StatsEvent>,
+    dirty: bool,
+}
+
+impl Stats {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add an achievement
+    pub fn with_achievement(mut self, achievement: Achievement) -> Self {
+        self.achievements.push(achievement);
+        self
+    }
+
+    pub fn get(&self, stat: &str) -> f64 {
+        self.data.values.get(stat).copied().unwrap_or_default()
+    }
+
+    pub fn set(&mut self, stat: &str, value: f64) {
+        let prev = self.data.values.insert(stat.to_string(), value);
+        if prev == Some(value) {
+            return;
+        }
+
+        self.dirty = true;
+        self.events.push(StatsEvent::Changed {
+            stat: stat.to_string(),
+            value,
+        });
+
+        self.check_achievements(stat, value);
+    }
+
+    pub fn increment(&mut self, stat: &str, amount: f64) {
+        self.set(stat, self.get(stat) + amount);
+    }
+
+    /// Set the stat if `value` is greater than the current value, e.g; for high scores
+    pub fn set_max(&mut self, stat: &str, value: f64) {
+        if value > self.get(stat) {
+            self.set(stat, value);
+        }
+    }
+
+    pub fn is_unlocked(&self, achievement: &str) -> bool {
+        self.data.unlocked.contains(achievement)
+    }
+
+    pub fn achievements(&self) -> &[Achievement] {
+        &self.achievements
+    }
+
+    /// Unlock an achievement, regardless of its threshold
+    pub fn unlock(&mut self, achievement: &str) {
+        if self.data.unlocked.insert(achievement.to_string()) {
+            self.dirty = true;
+            self.events
+                .push(StatsEvent::Unlocked(achievement.to_string()));
+        }
+    }
+
+    fn check_achievements(&mut self, stat: &str, value: f64) {
+        let unlocked = self
+            .achievements
+            .iter()
+            .filter(|v| v.stat == stat && value >= v.threshold)
+            .map(|v| v.id.clone())
+            .collect::<Vec<_>>();
+
+        for id in unlocked {
+            self.unlock(&id);
+        }
+    }
+
+    pub fn data(&self) -> &StatsData {
+        &self.data
+    }
+
+    /// Replace the stored values, unlocking any achievements already past their threshold
+    pub fn set_data(&mut self, data: StatsData) {
+        self.data = data;
+
+        let values = self
+            .data
+            .values
+            .iter()
+            .map(|(k, &v)| (k.clone(), v))
+            .collect::<Vec<_>>();
+
+        for (stat, value) in values {
+            self.check_achievements(&stat, value);
+        }
+    }
+
+    pub fn drain_events(&mut self) -> impl Iterator<Item = StatsEvent> + '_ {
+        self.events.drain(..)
+    }
+
+    /// Read stats from a profile file, returning empty stats if the file does not exist
+    pub fn read(path: &Path) -> anyhow::Result<StatsData> {
+        if !path.exists() {
+            return Ok(StatsData::default());
+        }
+
+        let bytes = fs::read(path).with_context(|| format!("Failed to read stats {path:?}"))?;
+        serde_json::from_slice(&bytes).with_context(|| format!("Malformed stats file {path:?}"))
+    }
+
+    /// Write the stats to a profile file
+    pub fn write(&self, path: &Path) -> anyhow::Result<()> {
+        write_data(path, &self.data)
+    }
+}
+
+fn write_data(path: &Path, data: &StatsData) -> anyhow::Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)
+            .with_context(|| format!("Failed to create stats directory {dir:?}"))?;
+    }
+
+    let tmp_path = path.with_extension("tmp");
+    fs::write(&tmp_path, serde_json::to_vec_pretty(data)?)
+        .with_context(|| format!("Failed to write stats {tmp_path:?}"))?;
+    fs::rename(&tmp_path, path)?;
+
+    Ok(())
+}
+
+/// Writes the stats not yet written by the periodic save when dropped along with the schedule on
+/// shutdown
+struct StatsFlush {
+    path: PathBuf,
+    pending: Option<StatsData>,
+}
+
+impl Drop for StatsFlush {
+    fn drop(&mut self) {
+        if let Some(data) = self.pending.take() {
+            if let Err(err) = write_data(&self.path, &data) {
+                tracing::error!("Failed to write stats on shutdown\n{err:?}");
+            }
+        }
+    }
+}
+
+component! {
+    /// Stored on the engine entity
+    pub stats: Stats,
+    /// Accumulates the distance travelled by the entity into the named stat
+    pub distance_stat: String,
+
+    distance_stat_prev: Vec3,
+}
+
+/// Increments a stat stored on the engine entity
+pub fn increment_stat(world: &World, stat: &str, amount: f64) -> anyhow::Result<()> {
+    world.get_mut(engine(), stats())?.increment(stat, amount);
+    Ok(())
+}
+
+/// Persists the stats in a save game, for games which track stats per save rather than per
+/// profile
+pub struct StatsSection;
+
+impl SaveSection for StatsSection {
+    fn key(&self) -> &str {
+        "stats"
+    }
+
+    fn save(&self, world: &World) -> anyhow::Result<serde_json::Value> {
+        let stats = world.get(engine(), stats())?;
+        serde_json::to_value(stats.data()).context("Failed to serialize stats")
+    }
+
+    fn load(
+        &self,
+        world: &mut World,
+        data: serde_json::Value,
+        _: &mut LoadContext,
+    ) -> anyhow::Result<()> {
+        let data = serde_json::from_value(data).context("Failed to deserialize stats")?;
+        world.get_mut(engine(), stats())?.set_data(data);
+        Ok(())
+    }
+}
+
+fn stats_system(
+    listeners: Vec<flume::Sender<StatsEvent>>,
+    path: Option<PathBuf>,
+    save_interval: f32,
+) -> BoxedSystem {
+    let mut distance_query = Query::new((
+        entity_ids(),
+        world_transform(),
+        distance_stat(),
+        distance_stat_prev().opt(),
+    ));
+    let mut since_save = 0.0;
+    let mut flush = path.map(|path| StatsFlush {
+        path,
+        pending: None,
+    });
+
+    System::builder()
+        .with_world_mut()
+        .build(move |world: &mut World| {
+            let dt = world
+                .get(engine(), delta_time())
+                .map(|v| v.as_secs_f32())
+                .unwrap_or_default();
+
+            let mut travelled = Vec::new();
+            for (id, transform, stat, prev) in &mut distance_query.borrow(world) {
+                let pos = transform.transform_point3(Vec3::ZERO);
+                let distance = prev.map(|v| v.distance(pos)).unwrap_or_default();
+                travelled.push((id, stat.clone(), pos, distance));
+            }
+
+            let mut stats = world.get_mut(engine(), stats())?;
+            stats.increment(PLAY_TIME, dt as f64);
+
+            for (_, stat, _, distance) in &travelled {
+                if *distance > 0.0 {
+                    stats.increment(stat, *distance as f64);
+                }
+            }
+
+            // Only forward unlocks and non-play time changes to keep the listeners quiet
+            for event in stats.drain_events() {
+                if matches!(&event, StatsEvent::Changed { stat, .. } if stat == PLAY_TIME) {
+                    continue;
+                }
+
+                for tx in &listeners {
+                    let _ = tx.send(event.clone());
+                }
+            }
+
+            since_save += dt;
+            if let Some(flush) = &mut flush {
+                if stats.dirty && since_save >= save_interval {
+                    stats.write(&flush.path)?;
+                    stats.dirty = false;
+                    since_save = 0.0;
+                }
+
+                flush.pending = stats.dirty.then(|| stats.data.clone());
+            }
+
+            drop(stats);
+
+            for (id, _, pos, _) in travelled {
+                world.set(id, distance_stat_prev(), pos)?;
+            }
+
+            anyhow::Ok(())
+        })
+        .boxed()
+}
+
+/// Tracks play time and distance travelled into the [`stats`] of the engine entity.
+///
+/// Other stats are counted by the game through [`increment_stat`].
+pub struct StatsPlugin {
+    stats: Stats,
+    path: Option<PathBuf>,
+    save_interval: f32,
+    listeners: Vec<flume::Sender<StatsEvent>>,
+}
+
+impl StatsPlugin {
+    pub fn new(stats: Stats) -> Self {
+        Self {
+            stats,
+            path: None,
+            save_interval: 30.0,
+            listeners: Vec::new(),
+        }
+    }
+
+    /// Load the stats from a profile file on install, and periodically write them back.
+    ///
+    /// Changes since the last write are written when the app shuts down.
+    ///
+    /// Without a path the stats are only persisted through a [`StatsSection`].
+    pub fn with_path(mut self, path: impl Into<PathBuf>) -> Self {
+        self.path = Some(path.into());
+        self
+    }
+
+    /// Set the minimum number of seconds between writes of the profile file
+    pub fn with_save_interval(mut self, save_interval: f32) -> Self {
+        self.save_interval = save_interval;
+        self
+    }
+
+    /// Send stat changes and unlocked achievements to the given channel
+    pub fn with_listener(mut self, tx: flume::Sender<StatsEvent>) -> Self {
+        self.listeners.push(tx);
+        self
+    }
+}
+
+impl Plugin for StatsPlugin {
+    fn install(
+        &self,
+        world: &mut World,
+        _: &AssetCache,
+        schedules: &mut ScheduleSetBuilder,
+    ) -> anyhow::Result<()> {
+        let mut value = self.stats.clone();
+        if let Some(path) = &self.path {
+            value.set_data(Stats::read(path)?);
+        }
+
+        world.set(engine(), stats(), value)?;
+
+        schedules.per_tick_mut().with_system(stats_system(
+            self.listeners.clone(),
+            self.path.clone(),
+            self.save_interval,
+        ));
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use flax::Schedule;
+
+    use super::*;
+
+    #[test]
+    fn achievements() {
+        let mut stats = Stats::new()
+            .with_achievement(Achievement::new("first_blood", "kills", 1.0))
+            .with_achievement(Achievement::new("slayer", "kills", 100.0));
+
+        stats.increment("kills", 1.0);
+        assert!(stats.is_unlocked("first_blood"));
+        assert!(!stats.is_unlocked("slayer"));
+
+        stats.increment("kills", 99.0);
+        assert!(stats.is_unlocked("slayer"));
+
+        let unlocked = stats
+            .drain_events()
+            .filter(|v| matches!(v, StatsEvent::Unlocked(_)))
+            .count();
+        assert_eq!(unlocked, 2);
+
+        stats.increment("kills", 1.0);
+        assert!(stats
+            .drain_events()
+            .all(|v| !matches!(v, StatsEvent::Unlocked(_))));
+    }
+
+    #[test]
+    fn flush_on_shutdown() {
+        let path = std::env::temp_dir()
+            .join(format!("ivy-stats-{}", std::process::id()))
+            .join("stats.json");
+        let _ = fs::remove_file(&path);
+
+        let mut world = World::new();
+        world.set(engine(), stats(), Stats::new()).unwrap();
+        world
+            .set(engine(), delta_time(), Duration::from_secs(2))
+            .unwrap();
+
+        let mut schedule = Schedule::builder()
+            .with_system(stats_system(Vec::new(), Some(path.clone()), 30.0))
+            .build();
+
+        schedule.execute_seq(&mut world).unwrap();
+        assert!(!path.exists());
+
+        drop(schedule);
+        assert_eq!(Stats::read(&path).unwrap().values[PLAY_TIME], 2.0);
+    }
+}
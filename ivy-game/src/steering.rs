@@ -0,0 +1,640 @@
+use flax::{entity_ids, BoxedSystem, Entity, FetchExt, Query, System, World};
+use glam::{vec3, Quat, Vec3};
+use ivy_assets::AssetCache;
+use ivy_core::{
+    components::{delta_time, engine, position, rotation},
+    update_layer::{Plugin, ScheduleSetBuilder},
+};
+use ivy_random::rand::{thread_rng, Rng};
+
+flax::component! {
+    /// Movement limits and the current velocity of a steered entity.
+    ///
+    /// The velocity is the sum of the forces of the behavior components on the entity, such as
+    /// [`seek`] and [`flocking`].
+    pub steering: Steering,
+    /// Moves the position of the steered entity with its velocity, and turns it to face the
+    /// direction of movement
+    pub steering_movement: (),
+    /// Radius of an obstacle avoided by the [`avoid`] behavior
+    pub steering_obstacle: f32,
+
+    pub seek: Seek,
+    pub flee: Flee,
+    pub arrive: Arrive,
+    pub avoid: Avoid,
+    pub wander: Wander,
+    pub flocking: Flocking,
+}
+
+/// A position, or an entity to follow
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SteeringTarget {
+    Position(Vec3),
+    Entity(Entity),
+}
+
+impl SteeringTarget {
+    fn resolve(&self, world: &World) -> Option<Vec3> {
+        match *self {
+            SteeringTarget::Position(v) => Some(v),
+            SteeringTarget::Entity(id) => world.get_copy(id, position()).ok(),
+        }
+    }
+}
+
+impl From<Vec3> for SteeringTarget {
+    fn from(value: Vec3) -> Self {
+        Self::Position(value)
+    }
+}
+
+impl From<Entity> for SteeringTarget {
+    fn from(value: Entity) -> Self {
+        Self::Entity(value)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Steering {
+    pub max_speed: f32,
+    pub max_force: f32,
+    /// Restrict the movement to the horizontal plane
+    pub planar: bool,
+    pub velocity: Vec3,
+}
+
+impl Steering {
+    pub fn new(max_speed: f32, max_force: f32) -> Self {
+        Self {
+            max_speed,
+            max_force,
+            ..Default::default()
+        }
+    }
+
+    pub fn with_planar(mut self, planar: bool) -> Self {
+        self.planar = planar;
+        self
+    }
+
+    /// Returns the force needed to reach `desired` velocity
+    fn towards(&self, desired: Vec3) -> Vec3 {
+        desired - self.velocity
+    }
+
+    /// Returns the direction of movement, or forward if standing still
+    fn heading(&self) -> Vec3 {
+        self.velocity.try_normalize().unwrap_or(Vec3::NEG_Z)
+    }
+}
+
+impl Default for Steering {
+    fn default() -> Self {
+        Self {
+            max_speed: 4.0,
+            max_force: 8.0,
+            planar: true,
+            velocity: Vec3::ZERO,
+        }
+    }
+}
+
+/// Moves towards the target at full speed
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Seek {
+    pub target: SteeringTarget,
+    pub weight: f32,
+}
+
+impl Seek {
+    pub fn new(target: impl Into<SteeringTarget>) -> Self {
+        Self {
+            target: target.into(),
+            weight: 1.0,
+        }
+    }
+
+    pub fn with_weight(mut self, weight: f32) -> Self {
+        self.weight = weight;
+        self
+    }
+}
+
+/// Moves away from the target while it is within the panic distance
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Flee {
+    pub target: SteeringTarget,
+    pub panic_distance: f32,
+    pub weight: f32,
+}
+
+impl Flee {
+    pub fn new(target: impl Into<SteeringTarget>, panic_distance: f32) -> Self {
+        Self {
+            target: target.into(),
+            panic_distance,
+            weight: 1.0,
+        }
+    }
+
+    pub fn with_weight(mut self, weight: f32) -> Self {
+        self.weight = weight;
+        self
+    }
+}
+
+/// Moves towards the target, slowing down to stop at it
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Arrive {
+    pub target: SteeringTarget,
+    pub slowing_distance: f32,
+    pub weight: f32,
+}
+
+impl Arrive {
+    pub fn new(target: impl Into<SteeringTarget>, slowing_distance: f32) -> Self {
+        Self {
+            target: target.into(),
+            slowing_distance,
+            weight: 1.0,
+        }
+    }
+
+    pub fn with_weight(mut self, weight: f32) -> Self {
+        self.weight = weight;
+        self
+    }
+}
+
+/// Steers around the [`steering_obstacle`]s ahead
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Avoid {
+    /// Radius of the steered entity
+    pub radius: f32,
+    /// Distance to look ahead at full speed
+    pub look_ahead: f32,
+    pub weight: f32,
+}
+
+impl Avoid {
+    pub fn new(radius: f32, look_ahead: f32) -> Self {
+        Self {
+            radius,
+            look_ahead,
+            weight: 2.0,
+        }
+    }
+
+    pub fn with_weight(mut self, weight: f32) -> Self {
+        self.weight = weight;
+        self
+    }
+}
+
+/// Meanders by steering towards a point moving randomly along a circle ahead
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Wander {
+    pub radius: f32,
+    /// Distance of the circle ahead
+    pub distance: f32,
+    /// How fast the point moves along the circle, in radians per second
+    pub jitter: f32,
+    pub weight: f32,
+    angle: f32,
+}
+
+impl Wander {
+    pub fn new(radius: f32, distance: f32, jitter: f32) -> Self {
+        Self {
+            radius,
+            distance,
+            jitter,
+            weight: 1.0,
+            angle: 0.0,
+        }
+    }
+
+    pub fn with_weight(mut self, weight: f32) -> Self {
+        self.weight = weight;
+        self
+    }
+}
+
+impl Default for Wander {
+    fn default() -> Self {
+        Self::new(1.0, 2.0, 4.0)
+    }
+}
+
+/// Moves as a group with the other flocking entities within the radius
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Flocking {
+    pub radius: f32,
+    /// Weight of keeping distance from the neighbours
+    pub separation: f32,
+    /// Weight of matching the velocity of the neighbours
+    pub alignment: f32,
+    /// Weight of moving towards the center of the neighbours
+    pub cohesion: f32,
+}
+
+impl Flocking {
+    pub fn new(radius: f32) -> Self {
+        Self {
+            radius,
+            ..Default::default()
+        }
+    }
+
+    /// Set the separation, alignment, and cohesion weights
+    pub fn with_weights(mut self, separation: f32, alignment: f32, cohesion: f32) -> Self {
+        self.separation = separation;
+        self.alignment = alignment;
+        self.cohesion = cohesion;
+        self
+    }
+}
+
+impl Default for Flocking {
+    fn default() -> Self {
+        Self {
+            radius: 3.0,
+            separation: 1.5,
+            alignment: 1.0,
+            cohesion: 1.0,
+        }
+    }
+}
+
+fn arrive_velocity(offset: Vec3, slowing_distance: f32, max_speed: f32) -> Vec3 {
+    let distance = offset.length();
+    if distance < 1e-3 {
+        return Vec3::ZERO;
+    }
+
+    offset / distance * max_speed * (distance / slowing_distance.max(1e-3)).min(1.0)
+}
+
+/// Returns the force avoiding the closest obstacle in the path
+fn avoid_force(
+    avoid: &Avoid,
+    steering: &Steering,
+    position: Vec3,
+    obstacles: &[(Entity, Vec3, f32)],
+    id: Entity,
+) -> Vec3 {
+    let heading = steering.heading();
+    let look_ahead = avoid.look_ahead * (steering.velocity.length() / steering.max_speed).max(0.1);
+
+    let closest = obstacles
+        .iter()
+        .filter(|v| v.0 != id)
+        .filter_map(|&(_, obstacle, radius)| {
+            let local = obstacle - position;
+            let t = local.dot(heading);
+            let lateral = local - heading * t;
+
+            (t > 0.0 && t < look_ahead && lateral.length() < radius + avoid.radius)
+                .then_some((t, lateral))
+        })
+        .min_by(|a, b| a.0.total_cmp(&b.0));
+
+    let Some((t, lateral)) = closest else {
+        return Vec3::ZERO;
+    };
+
+    // Obstacles straight ahead are avoided to the right
+    let away = (-lateral)
+        .try_normalize()
+        .unwrap_or_else(|| heading.cross(Vec3::Y).normalize_or_zero());
+
+    away * steering.max_speed * (1.0 - t / look_ahead)
+}
+
+fn flocking_force(
+    flocking: &Flocking,
+    steering: &Steering,
+    position: Vec3,
+    flock: &[(Entity, Vec3, Vec3)],
+    id: Entity,
+) -> Vec3 {
+    let mut separation = Vec3::ZERO;
+    let mut velocity = Vec3::ZERO;
+    let mut center = Vec3::ZERO;
+    let mut count = 0;
+
+    for &(other, other_position, other_velocity) in flock {
+        let offset = position - other_position;
+        let distance = offset.length();
+        if other == id || distance > flocking.radius || distance < 1e-3 {
+            continue;
+        }
+
+        // Push harder the closer the neighbour is
+        separation += offset / (distance * distance);
+        velocity += other_velocity;
+        center += other_position;
+        count += 1;
+    }
+
+    if count == 0 {
+        return Vec3::ZERO;
+    }
+
+    let count = count as f32;
+    let separation = steering.towards(separation.normalize_or_zero() * steering.max_speed);
+    let alignment = steering.towards(velocity / count);
+    let cohesion = steering.towards(arrive_velocity(
+        center / count - position,
+        flocking.radius,
+        steering.max_speed,
+    ));
+
+    separation * flocking.separation + alignment * flocking.alignment + cohesion * flocking.cohesion
+}
+
+/// Updates the [`Steering`] velocities from the behaviors, and moves the entities with
+/// [`steering_movement`]
+pub struct SteeringPlugin;
+
+impl Plugin for SteeringPlugin {
+    fn install(
+        &self,
+        _: &mut World,
+        _: &AssetCache,
+        schedules: &mut ScheduleSetBuilder,
+    ) -> anyhow::Result<()> {
+        schedules
+            .per_tick_mut()
+            .with_system(steering_system())
+            .with_system(movement_system());
+
+        Ok(())
+    }
+}
+
+fn steering_system() -> BoxedSystem {
+    let mut query = Query::new((
+        entity_ids(),
+        steering().as_mut(),
+        position().copied(),
+        seek().opt(),
+        flee().opt(),
+        arrive().opt(),
+        avoid().opt(),
+        wander().as_mut().opt(),
+        flocking().opt(),
+    ));
+
+    let mut flock = Vec::new();
+    let mut obstacles = Vec::new();
+
+    System::builder()
+        .with_world_mut()
+        .build(move |world: &mut World| {
+            let dt = world
+                .get(engine(), delta_time())
+                .map(|v| v.as_secs_f32())
+                .unwrap_or_default();
+
+            flock.clear();
+            flock.extend(
+                Query::new((entity_ids(), position().copied(), steering()))
+                    .with(flocking())
+                    .borrow(world)
+                    .iter()
+                    .map(|(id, position, steering)| (id, position, steering.velocity)),
+            );
+
+            obstacles.clear();
+            obstacles.extend(
+                Query::new((
+                    entity_ids(),
+                    position().copied(),
+                    steering_obstacle().copied(),
+                ))
+                .borrow(world)
+                .iter(),
+            );
+
+            let mut rng = thread_rng();
+
+            for (id, steering, position, seek, flee, arrive, avoid, wander, flocking) in
+                &mut query.borrow(world)
+            {
+                let mut force = Vec3::ZERO;
+
+                if let Some(seek) = seek {
+                    if let Some(target) = seek.target.resolve(world) {
+                        let desired = (target - position).normalize_or_zero() * steering.max_speed;
+                        force += steering.towards(desired) * seek.weight;
+                    }
+                }
+
+                if let Some(flee) = flee {
+                    if let Some(target) = flee.target.resolve(world) {
+                        if target.distance(position) < flee.panic_distance {
+                            let desired =
+                                (position - target).normalize_or_zero() * steering.max_speed;
+                            force += steering.towards(desired) * flee.weight;
+                        }
+                    }
+                }
+
+                if let Some(arrive) = arrive {
+                    if let Some(target) = arrive.target.resolve(world) {
+                        let desired = arrive_velocity(
+                            target - position,
+                            arrive.slowing_distance,
+                            steering.max_speed,
+                        );
+                        force += steering.towards(desired) * arrive.weight;
+                    }
+                }
+
+                if let Some(wander) = wander {
+                    wander.angle += rng.gen_range(-1.0f32..=1.0) * wander.jitter * dt;
+
+                    let heading = steering.heading();
+                    let offset = vec3(wander.angle.cos(), 0.0, wander.angle.sin()) * wander.radius;
+                    let desired = (heading * wander.distance + offset).normalize_or_zero()
+                        * steering.max_speed;
+
+                    force += steering.towards(desired) * wander.weight;
+                }
+
+                if let Some(avoid) = avoid {
+                    force += avoid_force(avoid, steering, position, &obstacles, id) * avoid.weight;
+                }
+
+                if let Some(flocking) = flocking {
+                    force += flocking_force(flocking, steering, position, &flock, id);
+                }
+
+                if steering.planar {
+                    force.y = 0.0;
+                }
+
+                steering.velocity = (steering.velocity
+                    + force.clamp_length_max(steering.max_force) * dt)
+                    .clamp_length_max(steering.max_speed);
+            }
+        })
+        .boxed()
+}
+
+fn movement_system() -> BoxedSystem {
+    let mut query = Query::new((steering(), position().as_mut(), rotation().as_mut()))
+        .with(steering_movement());
+
+    System::builder()
+        .with_world_mut()
+        .build(move |world: &mut World| {
+            let dt = world
+                .get(engine(), delta_time())
+                .map(|v| v.as_secs_f32())
+                .unwrap_or_default();
+
+            for (steering, position, rotation) in &mut query.borrow(world) {
+                *position += steering.velocity * dt;
+
+                if let Some(dir) = steering.velocity.try_normalize() {
+                    *rotation = facing(dir, steering.planar);
+                }
+            }
+        })
+        .boxed()
+}
+
+/// Returns the rotation facing along `dir`, with forward being negative z
+fn facing(dir: Vec3, planar: bool) -> Quat {
+    if planar {
+        Quat::from_rotation_y(f32::atan2(-dir.x, -dir.z))
+    } else {
+        Quat::from_rotation_arc(Vec3::NEG_Z, dir)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn entities(count: usize) -> Vec<Entity> {
+        let mut world = World::new();
+        (0..count).map(|_| world.spawn()).collect()
+    }
+
+    #[test]
+    fn arrive_slows_down() {
+        let far = arrive_velocity(vec3(0.0, 0.0, -10.0), 2.0, 4.0);
+        assert!(far.abs_diff_eq(vec3(0.0, 0.0, -4.0), 1e-5));
+
+        let near = arrive_velocity(vec3(1.0, 0.0, 0.0), 2.0, 4.0);
+        assert!(near.abs_diff_eq(vec3(2.0, 0.0, 0.0), 1e-5));
+
+        assert_eq!(arrive_velocity(Vec3::ZERO, 2.0, 4.0), Vec3::ZERO);
+    }
+
+    #[test]
+    fn avoid_closest_obstacle() {
+        let ids = entities(3);
+        let avoid = Avoid::new(0.5, 5.0);
+        let steering = Steering {
+            velocity: vec3(0.0, 0.0, -4.0),
+            ..Steering::new(4.0, 8.0)
+        };
+
+        let obstacles = [
+            (ids[1], vec3(-0.5, 0.0, -4.0), 1.0),
+            (ids[2], vec3(0.5, 0.0, -3.0), 1.0),
+        ];
+
+        let force = avoid_force(&avoid, &steering, Vec3::ZERO, &obstacles, ids[0]);
+        assert!(force.abs_diff_eq(vec3(-1.6, 0.0, 0.0), 1e-5));
+    }
+
+    #[test]
+    fn avoid_ignores_out_of_path() {
+        let ids = entities(2);
+        let avoid = Avoid::new(0.5, 5.0);
+        let steering = Steering {
+            velocity: vec3(0.0, 0.0, -4.0),
+            ..Steering::new(4.0, 8.0)
+        };
+
+        for obstacle in [
+            // Behind
+            vec3(0.0, 0.0, 2.0),
+            // Beyond the look ahead
+            vec3(0.0, 0.0, -8.0),
+            // To the side
+            vec3(3.0, 0.0, -2.0),
+        ] {
+            let obstacles = [(ids[1], obstacle, 1.0)];
+            let force = avoid_force(&avoid, &steering, Vec3::ZERO, &obstacles, ids[0]);
+            assert_eq!(force, Vec3::ZERO, "{obstacle}");
+        }
+
+        // The entity itself is never an obstacle
+        let obstacles = [(ids[0], vec3(0.0, 0.0, -2.0), 1.0)];
+        let force = avoid_force(&avoid, &steering, Vec3::ZERO, &obstacles, ids[0]);
+        assert_eq!(force, Vec3::ZERO);
+    }
+
+    #[test]
+    fn avoid_straight_ahead_to_the_right() {
+        let ids = entities(2);
+        let avoid = Avoid::new(0.5, 5.0);
+        let steering = Steering {
+            velocity: vec3(0.0, 0.0, -4.0),
+            ..Steering::new(4.0, 8.0)
+        };
+
+        let obstacles = [(ids[1], vec3(0.0, 0.0, -1.0), 1.0)];
+        let force = avoid_force(&avoid, &steering, Vec3::ZERO, &obstacles, ids[0]);
+        assert!(force.x > 0.0);
+        assert!(force.abs_diff_eq(vec3(3.2, 0.0, 0.0), 1e-5));
+    }
+
+    #[test]
+    fn flocking_without_neighbours() {
+        let ids = entities(2);
+        let flocking = Flocking::new(3.0);
+        let steering = Steering::new(4.0, 8.0);
+
+        let flock = [
+            (ids[0], Vec3::ZERO, Vec3::X),
+            (ids[1], vec3(5.0, 0.0, 0.0), Vec3::X),
+        ];
+
+        let force = flocking_force(&flocking, &steering, Vec3::ZERO, &flock, ids[0]);
+        assert_eq!(force, Vec3::ZERO);
+    }
+
+    #[test]
+    fn flocking_separation() {
+        let ids = entities(2);
+        let flocking = Flocking::new(3.0).with_weights(1.0, 0.0, 0.0);
+        let steering = Steering::new(4.0, 8.0);
+
+        let flock = [(ids[1], vec3(1.0, 0.0, 0.0), Vec3::ZERO)];
+        let force = flocking_force(&flocking, &steering, Vec3::ZERO, &flock, ids[0]);
+        assert!(force.abs_diff_eq(vec3(-4.0, 0.0, 0.0), 1e-5));
+    }
+
+    #[test]
+    fn flocking_alignment_and_cohesion() {
+        let ids = entities(2);
+        let steering = Steering::new(4.0, 8.0);
+        let flock = [(ids[1], vec3(2.0, 0.0, 0.0), vec3(0.0, 0.0, -2.0))];
+
+        let alignment = Flocking::new(3.0).with_weights(0.0, 1.0, 0.0);
+        let force = flocking_force(&alignment, &steering, Vec3::ZERO, &flock, ids[0]);
+        assert!(force.abs_diff_eq(vec3(0.0, 0.0, -2.0), 1e-5));
+
+        let cohesion = Flocking::new(3.0).with_weights(0.0, 0.0, 1.0);
+        let force = flocking_force(&cohesion, &steering, Vec3::ZERO, &flock, ids[0]);
+        assert!(force.abs_diff_eq(vec3(4.0 * 2.0 / 3.0, 0.0, 0.0), 1e-5));
+    }
+}
@@ -0,0 +1,804 @@
+use std::{collections::BTreeMap, path::Path};
+
+use anyhow::Context;
+use flax::{component, components::name, entity_ids, BoxedSystem, Entity, Query, System, World};
+use glam::{Quat, Vec2, Vec3};
+use ivy_assets::{
+    fs::{AssetFromPath, AsyncAssetFromPath},
+    service::FileSystemMapService,
+    Asset, AssetCache,
+};
+use ivy_core::{
+    components::{delta_time, engine, main_camera, position, rotation, scale},
+    update_layer::{Plugin, ScheduleSetBuilder},
+};
+use ivy_gltf::{
+    animation::player::AnimationPlayer,
+    components::{animator, skin},
+};
+use serde::{Deserialize, Serialize};
+use violet::{
+    core::{
+        layout::Align,
+        style::SizeExt,
+        widget::{card, col, label, SignalWidget, Slider, Stack},
+        Widget,
+    },
+    futures_signals::signal::Mutable,
+};
+
+/// How a transform key blends towards the next key
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Interpolation {
+    #[default]
+    Linear,
+    /// Holds the value until the next key
+    Step,
+    /// Eases in and out of the key
+    Smooth,
+}
+
+impl Interpolation {
+    fn apply(&self, t: f32) -> f32 {
+        match self {
+            Interpolation::Linear => t,
+            Interpolation::Step => 0.0,
+            Interpolation::Smooth => t * t * (3.0 - 2.0 * t),
+        }
+    }
+}
+
+fn one() -> Vec3 {
+    Vec3::ONE
+}
+
+fn one_f32() -> f32 {
+    1.0
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TransformKey {
+    pub time: f32,
+    pub position: Vec3,
+    #[serde(default)]
+    pub rotation: Quat,
+    #[serde(default = "one")]
+    pub scale: Vec3,
+    #[serde(default)]
+    pub interpolation: Interpolation,
+}
+
+/// Switches the main camera to the named entity
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CameraCut {
+    pub time: f32,
+    pub camera: String,
+}
+
+/// Starts an animation clip of the target's skin, by label
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AnimationKey {
+    pub time: f32,
+    pub animation: String,
+    #[serde(default)]
+    pub looping: bool,
+    #[serde(default = "one_f32")]
+    pub speed: f32,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AudioCue {
+    pub time: f32,
+    /// Sound asset, resolved by whoever handles the [`TimelineEvent`]
+    pub sound: String,
+    /// Entity to play the sound at, or non-spatial if `None`
+    #[serde(default)]
+    pub target: Option<String>,
+    #[serde(default = "one_f32")]
+    pub volume: f32,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EventCue {
+    pub time: f32,
+    pub name: String,
+}
+
+/// Keys of a track must be sorted by time
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Track {
+    Transform {
+        target: String,
+        keys: Vec<TransformKey>,
+    },
+    Camera(Vec<CameraCut>),
+    Animation {
+        target: String,
+        keys: Vec<AnimationKey>,
+    },
+    Audio(Vec<AudioCue>),
+    Event(Vec<EventCue>),
+}
+
+impl Track {
+    fn key_times(&self) -> Vec<f32> {
+        match self {
+            Track::Transform { keys, .. } => keys.iter().map(|v| v.time).collect(),
+            Track::Camera(keys) => keys.iter().map(|v| v.time).collect(),
+            Track::Animation { keys, .. } => keys.iter().map(|v| v.time).collect(),
+            Track::Audio(keys) => keys.iter().map(|v| v.time).collect(),
+            Track::Event(keys) => keys.iter().map(|v| v.time).collect(),
+        }
+    }
+}
+
+/// Keyframed choreography of entities over time, loaded from RON.
+///
+/// Tracks refer to entities by name, which are resolved through the bindings of the
+/// [`TimelinePlayer`], or the flax [`name`] of the entity.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Timeline {
+    pub duration: f32,
+    #[serde(default)]
+    pub tracks: Vec<Track>,
+}
+
+impl Timeline {
+    pub fn new(duration: f32) -> Self {
+        Self {
+            duration,
+            tracks: Vec::new(),
+        }
+    }
+
+    /// Add a track
+    pub fn with_track(mut self, track: Track) -> Self {
+        self.tracks.push(track);
+        self
+    }
+
+    /// Ensures keys are sorted and within the duration
+    pub fn validate(&self) -> anyhow::Result<()> {
+        for (i, track) in self.tracks.iter().enumerate() {
+            let times = track.key_times();
+
+            if times.windows(2).any(|v| v[0] > v[1]) {
+                anyhow::bail!("Keys of track {i} are not sorted by time");
+            }
+
+            if let Some(time) = times.iter().find(|&&v| v < 0.0 || v > self.duration) {
+                anyhow::bail!(
+                    "Key at {time} of track {i} is outside the duration {}",
+                    self.duration
+                );
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn from_ron(source: &str) -> anyhow::Result<Self> {
+        let timeline: Self = ron::from_str(source)?;
+        timeline.validate()?;
+        Ok(timeline)
+    }
+}
+
+impl AssetFromPath for Timeline {
+    type Error = anyhow::Error;
+
+    fn load_from_path(path: &Path, assets: &AssetCache) -> anyhow::Result<Asset<Self>> {
+        let source = assets.service::<FileSystemMapService>().load_string(path)?;
+        let timeline = Timeline::from_ron(&source)
+            .with_context(|| format!("Failed to load timeline {path:?}"))?;
+
+        Ok(assets.insert(timeline))
+    }
+}
+
+impl AsyncAssetFromPath for Timeline {
+    type Error = anyhow::Error;
+
+    async fn load_from_path(path: &Path, assets: &AssetCache) -> anyhow::Result<Asset<Self>> {
+        let source = assets
+            .service::<FileSystemMapService>()
+            .load_string_async(path)
+            .await?;
+        let timeline = Timeline::from_ron(&source)
+            .with_context(|| format!("Failed to load timeline {path:?}"))?;
+
+        Ok(assets.insert(timeline))
+    }
+}
+
+/// Samples the transform keys at `time`, holding the first and last key outside their range
+fn sample_transform(keys: &[TransformKey], time: f32) -> Option<(Vec3, Quat, Vec3)> {
+    let next = keys.partition_point(|v| v.time <= time);
+
+    let (a, b) = match next {
+        0 => (keys.first()?, keys.first()?),
+        i if i == keys.len() => (keys.last()?, keys.last()?),
+        i => (&keys[i - 1], &keys[i]),
+    };
+
+    let span = b.time - a.time;
+    let t = if span > 0.0 {
+        a.interpolation
+            .apply(((time - a.time) / span).clamp(0.0, 1.0))
+    } else {
+        0.0
+    };
+
+    Some((
+        a.position.lerp(b.position, t),
+        a.rotation.slerp(b.rotation, t),
+        a.scale.lerp(b.scale, t),
+    ))
+}
+
+/// Returns true if a key at `time` was passed when stepping from `prev` to `now`.
+///
+/// The range is inclusive of the start so that keys at the start of the timeline fire, and
+/// inclusive of the end when the timeline `finished`.
+fn crossed(prev: f32, now: f32, wrapped: bool, finished: bool, time: f32) -> bool {
+    if wrapped {
+        time >= prev || time < now
+    } else if finished {
+        time >= prev && time <= now
+    } else {
+        time >= prev && time < now
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TimelineEvent {
+    Started,
+    Audio {
+        sound: String,
+        volume: f32,
+        target: Option<Entity>,
+    },
+    Event(String),
+    Finished,
+}
+
+/// Plays a [`Timeline`] on the entities of the world
+#[derive(Debug, Clone)]
+pub struct TimelinePlayer {
+    timeline: Asset<Timeline>,
+    bindings: BTreeMap<String, Entity>,
+    time: f32,
+    speed: f32,
+    looping: bool,
+    playing: bool,
+    /// The pose changed since it was last applied
+    dirty: bool,
+    /// Main camera before the first camera cut, restored when the timeline finishes
+    previous_camera: Option<Entity>,
+    events: Vec<TimelineEvent>,
+}
+
+impl TimelinePlayer {
+    pub fn new(timeline: Asset<Timeline>) -> Self {
+        Self {
+            timeline,
+            bindings: BTreeMap::new(),
+            time: 0.0,
+            speed: 1.0,
+            looping: false,
+            playing: false,
+            dirty: true,
+            previous_camera: None,
+            events: Vec::new(),
+        }
+    }
+
+    /// Bind a track target to an entity, taking precedence over entity names
+    pub fn with_binding(mut self, target: impl Into<String>, id: Entity) -> Self {
+        self.bindings.insert(target.into(), id);
+        self
+    }
+
+    /// Set the playback speed
+    pub fn with_speed(mut self, speed: f32) -> Self {
+        self.speed = speed;
+        self
+    }
+
+    /// Restart the timeline when reaching the end
+    pub fn with_looping(mut self, looping: bool) -> Self {
+        self.looping = looping;
+        self
+    }
+
+    pub fn timeline(&self) -> &Asset<Timeline> {
+        &self.timeline
+    }
+
+    pub fn time(&self) -> f32 {
+        self.time
+    }
+
+    pub fn duration(&self) -> f32 {
+        self.timeline.duration
+    }
+
+    pub fn is_playing(&self) -> bool {
+        self.playing
+    }
+
+    /// Play from the current time, restarting if the timeline has finished
+    pub fn play(&mut self) {
+        if self.time >= self.duration() {
+            self.time = 0.0;
+        }
+
+        self.playing = true;
+        self.events.push(TimelineEvent::Started);
+    }
+
+    pub fn pause(&mut self) {
+        self.playing = false;
+    }
+
+    /// Jump to `time` without firing the cues in between
+    pub fn seek(&mut self, time: f32) {
+        self.time = time.clamp(0.0, self.duration());
+        self.dirty = true;
+    }
+
+    pub fn drain_events(&mut self) -> impl Iterator<Item = TimelineEvent> + '_ {
+        self.events.drain(..)
+    }
+
+    fn resolve(&self, world: &World, target: &str) -> Option<Entity> {
+        if let Some(&id) = self.bindings.get(target) {
+            return Some(id);
+        }
+
+        Query::new((entity_ids(), name()))
+            .borrow(world)
+            .iter()
+            .find(|(_, v)| *v == target)
+            .map(|(id, _)| id)
+    }
+
+    /// Advances the playback and fires the passed cues
+    fn step(&mut self, world: &World, dt: f32) -> anyhow::Result<()> {
+        if !self.playing {
+            return Ok(());
+        }
+
+        // Includes the finishing step, so the final pose is applied
+        self.dirty = true;
+
+        let timeline = self.timeline.clone();
+        let prev = self.time;
+        let mut now = prev + dt * self.speed;
+        let mut wrapped = false;
+        let mut finished = false;
+
+        if now >= timeline.duration {
+            if self.looping && timeline.duration > 0.0 {
+                now %= timeline.duration;
+                wrapped = true;
+            } else {
+                now = timeline.duration;
+                finished = true;
+            }
+        }
+
+        self.time = now;
+        let passed = |time| crossed(prev, now, wrapped, finished, time);
+
+        for track in &timeline.tracks {
+            match track {
+                Track::Animation { target, keys } => {
+                    let keys = keys.iter().filter(|v| passed(v.time)).collect::<Vec<_>>();
+                    if keys.is_empty() {
+                        continue;
+                    }
+
+                    let Some(id) = self.resolve(world, target) else {
+                        tracing::warn!(%target, "Missing animation target for timeline");
+                        continue;
+                    };
+
+                    let (Ok(skin), Ok(mut animator)) =
+                        (world.get(id, skin()), world.get_mut(id, animator()))
+                    else {
+                        tracing::warn!(%target, "Animation target has no skin or animator");
+                        continue;
+                    };
+
+                    for key in keys {
+                        let Some(animation) = skin
+                            .animations()
+                            .iter()
+                            .find(|v| v.label() == key.animation)
+                        else {
+                            tracing::warn!(animation = %key.animation, "Missing animation for timeline");
+                            continue;
+                        };
+
+                        let mut player = AnimationPlayer::new(animation.clone());
+                        player.set_looping(key.looping);
+                        player.set_speed(key.speed);
+                        animator.start_animation(player);
+                    }
+                }
+                Track::Audio(keys) => {
+                    for key in keys.iter().filter(|v| passed(v.time)) {
+                        let target = key.target.as_ref().and_then(|v| self.resolve(world, v));
+
+                        self.events.push(TimelineEvent::Audio {
+                            sound: key.sound.clone(),
+                            volume: key.volume,
+                            target,
+                        });
+                    }
+                }
+                Track::Event(keys) => {
+                    self.events.extend(
+                        keys.iter()
+                            .filter(|v| passed(v.time))
+                            .map(|v| TimelineEvent::Event(v.name.clone())),
+                    );
+                }
+                Track::Transform { .. } | Track::Camera(_) => {}
+            }
+        }
+
+        if finished {
+            self.playing = false;
+            self.events.push(TimelineEvent::Finished);
+        }
+
+        Ok(())
+    }
+
+    /// Poses the targets and selects the camera for the current time.
+    ///
+    /// Unlike cues this is independent of the playback direction, which allows scrubbing, but is
+    /// only done when playing or seeking so that the targets can be moved by other systems
+    /// otherwise.
+    ///
+    /// Returns true if anything was applied.
+    fn apply(&mut self, world: &mut World) -> anyhow::Result<bool> {
+        if !self.dirty {
+            return Ok(false);
+        }
+
+        self.dirty = false;
+
+        let timeline = self.timeline.clone();
+        for track in &timeline.tracks {
+            match track {
+                Track::Transform { target, keys } => {
+                    let Some((pos, rot, scl)) = sample_transform(keys, self.time) else {
+                        continue;
+                    };
+
+                    let Some(id) = self.resolve(world, target) else {
+                        continue;
+                    };
+
+                    world.set(id, position(), pos)?;
+                    world.set(id, rotation(), rot)?;
+                    world.set(id, scale(), scl)?;
+                }
+                Track::Camera(cuts) => {
+                    let current = cuts.partition_point(|v| v.time <= self.time);
+                    let Some(cut) = cuts[..current].last() else {
+                        continue;
+                    };
+
+                    let Some(camera) = self.resolve(world, &cut.camera) else {
+                        tracing::warn!(camera = %cut.camera, "Missing camera for timeline");
+                        continue;
+                    };
+
+                    if world.has(camera, main_camera()) {
+                        continue;
+                    }
+
+                    let prev = Query::new(entity_ids())
+                        .with(main_camera())
+                        .borrow(world)
+                        .iter()
+                        .collect::<Vec<_>>();
+
+                    if self.previous_camera.is_none() {
+                        self.previous_camera = prev.first().copied();
+                    }
+
+                    for id in prev {
+                        world.remove(id, main_camera())?;
+                    }
+
+                    world.set(camera, main_camera(), ())?;
+                }
+                _ => {}
+            }
+        }
+
+        if !self.playing && self.time >= self.duration() {
+            self.release_camera(world)?;
+        }
+
+        Ok(true)
+    }
+
+    /// Returns the main camera to the camera which had it before the first cut
+    fn release_camera(&mut self, world: &mut World) -> anyhow::Result<()> {
+        let Some(camera) = self.previous_camera.take() else {
+            return Ok(());
+        };
+
+        if !world.is_alive(camera) {
+            return Ok(());
+        }
+
+        let current = Query::new(entity_ids())
+            .with(main_camera())
+            .borrow(world)
+            .iter()
+            .collect::<Vec<_>>();
+
+        for id in current {
+            world.remove(id, main_camera())?;
+        }
+
+        world.set(camera, main_camera(), ())?;
+
+        Ok(())
+    }
+}
+
+component! {
+    pub timeline_player: TimelinePlayer,
+}
+
+/// Progress of the first timeline, shared with the [`timeline_scrubber`] widget.
+///
+/// Dragging the scrubber pauses the timeline and seeks to the dragged time.
+#[derive(Debug, Clone, Default)]
+pub struct TimelineScrubber {
+    progress: Mutable<f32>,
+    time: Mutable<(f32, f32)>,
+}
+
+impl TimelineScrubber {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+/// Debug widget for inspecting and scrubbing through a playing timeline
+pub fn timeline_scrubber(scrubber: TimelineScrubber) -> impl Widget {
+    Stack::new(card(col((
+        SignalWidget(
+            scrubber
+                .time
+                .signal_ref(|(time, duration)| label(format!("{time:.2}s / {duration:.2}s"))),
+        ),
+        Slider::new(scrubber.progress, 0.0, 1.0),
+    ))))
+    .with_maximize(Vec2::ONE)
+    .with_horizontal_alignment(Align::Center)
+    .with_vertical_alignment(Align::Start)
+}
+
+fn timeline_system(
+    listeners: Vec<flume::Sender<TimelineEvent>>,
+    scrubber: Option<TimelineScrubber>,
+) -> BoxedSystem {
+    let mut query = Query::new(entity_ids()).with(timeline_player());
+    let mut last_progress = 0.0;
+
+    System::builder()
+        .with_world_mut()
+        .build(move |world: &mut World| {
+            let dt = world
+                .get(engine(), delta_time())
+                .map(|v| v.as_secs_f32())
+                .unwrap_or_default();
+
+            let ids = query.borrow(world).iter().collect::<Vec<_>>();
+
+            for (i, id) in ids.into_iter().enumerate() {
+                let mut player = {
+                    let mut player = world.get_mut(id, timeline_player())?;
+
+                    if let Some(scrubber) = scrubber.as_ref().filter(|_| i == 0) {
+                        let progress = scrubber.progress.get();
+                        if progress != last_progress {
+                            player.pause();
+                            player.seek(progress * player.duration());
+                        }
+                    }
+
+                    player.step(world, dt)?;
+
+                    for event in player.drain_events() {
+                        for tx in &listeners {
+                            let _ = tx.send(event.clone());
+                        }
+                    }
+
+                    player.clone()
+                };
+
+                // Write back the camera handover state
+                if player.apply(world)? {
+                    world.set(id, timeline_player(), player.clone())?;
+                }
+
+                if let Some(scrubber) = scrubber.as_ref().filter(|_| i == 0) {
+                    let duration = player.duration();
+                    last_progress = if duration > 0.0 {
+                        player.time() / duration
+                    } else {
+                        0.0
+                    };
+
+                    scrubber.progress.set(last_progress);
+                    scrubber.time.set((player.time(), duration));
+                }
+            }
+
+            anyhow::Ok(())
+        })
+        .boxed()
+}
+
+/// Plays every [`timeline_player`], animating the bound entities and firing cues
+pub struct TimelinePlugin {
+    listeners: Vec<flume::Sender<TimelineEvent>>,
+    scrubber: Option<TimelineScrubber>,
+}
+
+impl TimelinePlugin {
+    pub fn new() -> Self {
+        Self {
+            listeners: Vec::new(),
+            scrubber: None,
+        }
+    }
+
+    /// Send cues and playback events to the given channel, e.g; for audio playback
+    pub fn with_listener(mut self, tx: flume::Sender<TimelineEvent>) -> Self {
+        self.listeners.push(tx);
+        self
+    }
+
+    /// Synchronize the first timeline with a [`timeline_scrubber`]
+    pub fn with_scrubber(mut self, scrubber: TimelineScrubber) -> Self {
+        self.scrubber = Some(scrubber);
+        self
+    }
+}
+
+impl Default for TimelinePlugin {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Plugin for TimelinePlugin {
+    fn install(
+        &self,
+        _: &mut World,
+        _: &AssetCache,
+        schedules: &mut ScheduleSetBuilder,
+    ) -> anyhow::Result<()> {
+        schedules.per_tick_mut().with_system(timeline_system(
+            self.listeners.clone(),
+            self.scrubber.clone(),
+        ));
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use flax::Schedule;
+
+    use super::*;
+
+    fn key(time: f32, x: f32, interpolation: Interpolation) -> TransformKey {
+        TransformKey {
+            time,
+            position: Vec3::X * x,
+            rotation: Quat::IDENTITY,
+            scale: Vec3::ONE,
+            interpolation,
+        }
+    }
+
+    #[test]
+    fn sample() {
+        let keys = [
+            key(1.0, 0.0, Interpolation::Linear),
+            key(2.0, 4.0, Interpolation::Step),
+            key(3.0, 8.0, Interpolation::Linear),
+        ];
+
+        let x = |time| sample_transform(&keys, time).unwrap().0.x;
+
+        assert_eq!(x(0.0), 0.0);
+        assert_eq!(x(1.5), 2.0);
+        assert_eq!(x(2.5), 4.0);
+        assert_eq!(x(3.0), 8.0);
+        assert_eq!(x(10.0), 8.0);
+    }
+
+    #[test]
+    fn cues() {
+        assert!(crossed(0.0, 0.5, false, false, 0.0));
+        assert!(!crossed(0.0, 0.5, false, false, 0.5));
+        assert!(crossed(0.0, 0.5, false, true, 0.5));
+        assert!(crossed(0.9, 0.1, true, false, 0.95));
+        assert!(crossed(0.9, 0.1, true, false, 0.0));
+        assert!(!crossed(0.9, 0.1, true, false, 0.5));
+    }
+
+    #[test]
+    fn camera_handover() {
+        let assets = AssetCache::new();
+        let timeline = Timeline::new(1.0)
+            .with_track(Track::Camera(vec![CameraCut {
+                time: 0.0,
+                camera: "cutscene".into(),
+            }]))
+            .with_track(Track::Transform {
+                target: "actor".into(),
+                keys: vec![
+                    key(0.0, 0.0, Interpolation::Linear),
+                    key(1.0, 4.0, Interpolation::Linear),
+                ],
+            });
+
+        let mut world = World::new();
+        world
+            .set(engine(), delta_time(), Duration::from_millis(600))
+            .unwrap();
+
+        let gameplay = Entity::builder().set(main_camera(), ()).spawn(&mut world);
+        let cutscene = Entity::builder()
+            .set(name(), "cutscene".into())
+            .spawn(&mut world);
+        let actor = Entity::builder()
+            .set(name(), "actor".into())
+            .set_default(position())
+            .spawn(&mut world);
+
+        let mut player = TimelinePlayer::new(assets.insert(timeline));
+        player.play();
+        world.set(actor, timeline_player(), player).unwrap();
+
+        let mut schedule = Schedule::builder()
+            .with_system(timeline_system(Vec::new(), None))
+            .build();
+
+        schedule.execute_seq(&mut world).unwrap();
+        assert!(world.has(cutscene, main_camera()));
+        assert!(!world.has(gameplay, main_camera()));
+        assert_eq!(world.get(actor, position()).unwrap().x, 2.4);
+
+        // The final pose is applied on the finishing tick
+        schedule.execute_seq(&mut world).unwrap();
+        assert!(!world.get(actor, timeline_player()).unwrap().is_playing());
+        assert_eq!(world.get(actor, position()).unwrap().x, 4.0);
+        assert!(world.has(gameplay, main_camera()));
+        assert!(!world.has(cutscene, main_camera()));
+
+        // Finished timelines leave the targets alone
+        world.set(actor, position(), Vec3::ZERO).unwrap();
+        schedule.execute_seq(&mut world).unwrap();
+        assert_eq!(world.get(actor, position()).unwrap().x, 0.0);
+    }
+}
@@ -0,0 +1,312 @@
+use flax::{BoxedSystem, FetchExt, Query, System, World};
+use glam::{vec3, EulerRot, Quat, Vec2, Vec3};
+use ivy_assets::AssetCache;
+use ivy_core::{
+    components::{delta_time, engine, position, rotation},
+    update_layer::{Plugin, ScheduleSetBuilder},
+    DEG_90,
+};
+use ivy_input::{
+    components::input_state,
+    types::{Key, MouseButton, NamedKey},
+    Action, Axis2D, BindingExt, CursorPositionBinding, InputState, KeyBinding, MouseButtonBinding,
+    ScrollBinding,
+};
+
+use crate::{
+    camera::{camera_builder, zoom, zoom_input, PerspectiveCamera},
+    ray_picker::{screen_to_ground, CameraQuery},
+};
+
+flax::component! {
+    pub top_down_camera: TopDownCamera,
+
+    pub pan_input: Vec2,
+    pub rotate_input: f32,
+    pub drag_active: bool,
+    /// Normalized cursor position
+    pub cursor_position: Vec2,
+}
+
+/// Rate at which the yaw approaches the snapped rotation
+const ROTATION_SMOOTHING: f32 = 12.0;
+
+/// Looks down at the ground from an angle, for strategy and management games.
+///
+/// Pans with the keyboard, by moving the cursor to the edges of the window, or by dragging the
+/// ground with the middle mouse button. Scrolling zooms towards the cursor, and `Q` and `E`
+/// rotate in steps of [`Self::rotation_step`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TopDownCamera {
+    /// The point on the ground the camera looks at
+    pub focus: Vec3,
+    pub distance: f32,
+    pub min_distance: f32,
+    pub max_distance: f32,
+    /// Angle below the horizon
+    pub pitch: f32,
+    pub yaw: f32,
+    pub rotation_step: f32,
+    /// Pan speed per unit of distance, so that panning feels the same at all zoom levels
+    pub pan_speed: f32,
+    /// Normalized distance from the window edges in which the cursor pans the camera. Zero
+    /// disables edge scrolling.
+    pub edge_margin: f32,
+    /// Height of the ground plane used for dragging and zooming
+    pub ground_height: f32,
+    target_yaw: f32,
+    prev_rotate: f32,
+    drag_anchor: Option<Vec3>,
+    cursor_moved: bool,
+}
+
+impl TopDownCamera {
+    pub fn new(focus: Vec3, distance: f32) -> Self {
+        Self {
+            focus,
+            distance,
+            ..Default::default()
+        }
+    }
+
+    /// Set the zoom range
+    pub fn with_distance_range(mut self, min: f32, max: f32) -> Self {
+        self.min_distance = min;
+        self.max_distance = max;
+        self
+    }
+
+    pub fn with_pitch(mut self, pitch: f32) -> Self {
+        self.pitch = pitch;
+        self
+    }
+
+    pub fn with_yaw(mut self, yaw: f32) -> Self {
+        self.yaw = yaw;
+        self.target_yaw = yaw;
+        self
+    }
+
+    pub fn with_rotation_step(mut self, rotation_step: f32) -> Self {
+        self.rotation_step = rotation_step;
+        self
+    }
+
+    pub fn with_pan_speed(mut self, pan_speed: f32) -> Self {
+        self.pan_speed = pan_speed;
+        self
+    }
+
+    pub fn with_edge_margin(mut self, edge_margin: f32) -> Self {
+        self.edge_margin = edge_margin;
+        self
+    }
+
+    pub fn with_ground_height(mut self, ground_height: f32) -> Self {
+        self.ground_height = ground_height;
+        self
+    }
+
+    pub fn rotation(&self) -> Quat {
+        Quat::from_euler(EulerRot::YXZ, self.yaw, -self.pitch, 0.0)
+    }
+}
+
+impl Default for TopDownCamera {
+    fn default() -> Self {
+        Self {
+            focus: Vec3::ZERO,
+            distance: 30.0,
+            min_distance: 5.0,
+            max_distance: 100.0,
+            pitch: 0.9,
+            yaw: 0.0,
+            rotation_step: DEG_90,
+            pan_speed: 1.0,
+            edge_margin: 0.01,
+            ground_height: 0.0,
+            target_yaw: 0.0,
+            prev_rotate: 0.0,
+            drag_anchor: None,
+            cursor_moved: false,
+        }
+    }
+}
+
+/// Updates the [`TopDownCamera`] controllers.
+///
+/// The projection is updated by the [`CameraPlugin`](crate::camera::CameraPlugin).
+pub struct TopDownCameraPlugin;
+
+impl Plugin for TopDownCameraPlugin {
+    fn install(
+        &self,
+        _: &mut World,
+        _: &AssetCache,
+        schedules: &mut ScheduleSetBuilder,
+    ) -> anyhow::Result<()> {
+        schedules
+            .per_tick_mut()
+            .with_system(top_down_zoom_system())
+            .with_system(top_down_camera_system());
+
+        Ok(())
+    }
+}
+
+/// Creates a main camera with a [`TopDownCamera`] controller.
+///
+/// Requires the [`TopDownCameraPlugin`] and [`CameraPlugin`](crate::camera::CameraPlugin).
+pub fn setup_top_down_camera(camera: TopDownCamera) -> flax::EntityBuilder {
+    let mut pan_action = Action::new();
+    for (key, axis, amplitude) in [
+        (Key::Character("w".into()), Axis2D::Y, 1.0),
+        (Key::Character("s".into()), Axis2D::Y, -1.0),
+        (Key::Character("d".into()), Axis2D::X, 1.0),
+        (Key::Character("a".into()), Axis2D::X, -1.0),
+        (Key::Named(NamedKey::ArrowUp), Axis2D::Y, 1.0),
+        (Key::Named(NamedKey::ArrowDown), Axis2D::Y, -1.0),
+        (Key::Named(NamedKey::ArrowRight), Axis2D::X, 1.0),
+        (Key::Named(NamedKey::ArrowLeft), Axis2D::X, -1.0),
+    ] {
+        pan_action.add(
+            KeyBinding::new(key)
+                .analog()
+                .compose(axis)
+                .amplitude(amplitude),
+        );
+    }
+
+    let mut rotate_action = Action::new();
+    rotate_action.add(
+        KeyBinding::new(Key::Character("q".into()))
+            .analog()
+            .amplitude(-1.0),
+    );
+    rotate_action.add(KeyBinding::new(Key::Character("e".into())).analog());
+
+    let mut drag_action = Action::new();
+    drag_action.add(MouseButtonBinding::new(MouseButton::Middle));
+
+    let mut cursor_action = Action::new();
+    cursor_action.add(CursorPositionBinding::new(true));
+
+    let mut zoom_action = Action::new();
+    zoom_action.add(ScrollBinding::new().decompose(Axis2D::Y));
+
+    let mut builder = camera_builder(PerspectiveCamera::default());
+    builder
+        .set(top_down_camera(), camera)
+        .set(
+            input_state(),
+            InputState::new()
+                .with_action(pan_input(), pan_action)
+                .with_action(rotate_input(), rotate_action)
+                .with_action(drag_active(), drag_action)
+                .with_action(cursor_position(), cursor_action)
+                .with_action(zoom_input(), zoom_action),
+        )
+        .set_default(pan_input())
+        .set_default(rotate_input())
+        .set_default(drag_active())
+        .set_default(cursor_position());
+
+    builder
+}
+
+fn top_down_zoom_system() -> BoxedSystem {
+    System::builder()
+        .with_query(Query::new((
+            top_down_camera().as_mut(),
+            zoom_input().modified(),
+            cursor_position(),
+            CameraQuery::new(),
+        )))
+        .for_each(|(controller, &delta, &cursor, camera)| {
+            let prev_distance = controller.distance;
+            zoom(
+                &mut controller.distance,
+                delta,
+                controller.min_distance,
+                controller.max_distance,
+            );
+
+            // Move towards the point under the cursor to keep it in place
+            if let Some(point) = screen_to_ground(&camera, cursor, controller.ground_height) {
+                controller.focus +=
+                    (point - controller.focus) * (1.0 - controller.distance / prev_distance);
+            }
+        })
+        .boxed()
+}
+
+fn top_down_camera_system() -> BoxedSystem {
+    let mut query = Query::new((
+        top_down_camera().as_mut(),
+        pan_input().copied(),
+        rotate_input().copied(),
+        drag_active().copied(),
+        cursor_position().copied(),
+        CameraQuery::new(),
+        position().as_mut(),
+        rotation().as_mut(),
+    ));
+
+    System::builder()
+        .with_world_mut()
+        .build(move |world: &mut World| {
+            let dt = world
+                .get(engine(), delta_time())
+                .map(|v| v.as_secs_f32())
+                .unwrap_or_default();
+
+            for (controller, pan, rotate, dragging, cursor, camera, position, rotation) in
+                &mut query.borrow(world)
+            {
+                if rotate != 0.0 && controller.prev_rotate == 0.0 {
+                    controller.target_yaw += rotate.signum() * controller.rotation_step;
+                }
+
+                controller.prev_rotate = rotate;
+                controller.yaw += (controller.target_yaw - controller.yaw)
+                    * (1.0 - (-ROTATION_SMOOTHING * dt).exp());
+
+                // The cursor position is zero until the cursor enters the window
+                controller.cursor_moved |= cursor != Vec2::ZERO;
+
+                let mut movement = vec3(pan.x, 0.0, -pan.y);
+                if controller.edge_margin > 0.0 && controller.cursor_moved && !dragging {
+                    let edge = |v: f32| {
+                        if v < controller.edge_margin {
+                            -1.0
+                        } else if v > 1.0 - controller.edge_margin {
+                            1.0
+                        } else {
+                            0.0
+                        }
+                    };
+
+                    movement += vec3(edge(cursor.x), 0.0, edge(cursor.y));
+                }
+
+                controller.focus += Quat::from_rotation_y(controller.yaw)
+                    * movement.clamp_length_max(1.0)
+                    * controller.pan_speed
+                    * controller.distance
+                    * dt;
+
+                // Keep the grabbed point of the ground under the cursor
+                let ground = screen_to_ground(&camera, cursor, controller.ground_height);
+                match (dragging, controller.drag_anchor, ground) {
+                    (true, None, Some(point)) => controller.drag_anchor = Some(point),
+                    (true, Some(anchor), Some(point)) => controller.focus += anchor - point,
+                    (false, _, _) => controller.drag_anchor = None,
+                    _ => {}
+                }
+
+                *rotation = controller.rotation();
+                *position = controller.focus + *rotation * Vec3::Z * controller.distance;
+            }
+        })
+        .boxed()
+}
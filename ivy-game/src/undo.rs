@@ -0,0 +1,531 @@
+use std::{borrow::Cow, collections::VecDeque, sync::Arc};
+
+use anyhow::Context;
+use flax::{
+    component, component::ComponentValue, BoxedSystem, Component, Entity, EntityBuilder, EntityRef,
+    FetchExt, Query, System, World,
+};
+use ivy_assets::AssetCache;
+use ivy_core::{
+    components::engine,
+    update_layer::{Plugin, ScheduleSetBuilder},
+};
+use ivy_input::{
+    components::input_state,
+    types::{Key, NamedKey},
+    Action, CompositeBinding, InputState, KeyBinding,
+};
+
+/// A reversible mutation of the world
+pub trait EditCommand: 'static + Send + Sync {
+    fn label(&self) -> Cow<'static, str>;
+
+    fn apply(&mut self, world: &mut World) -> anyhow::Result<()>;
+
+    /// Undo the effects of a previous `apply`
+    fn revert(&mut self, world: &mut World) -> anyhow::Result<()>;
+}
+
+/// Sets the value of a component, restoring or removing the previous value when reverted
+pub struct SetComponent<T> {
+    id: Entity,
+    component: Component<T>,
+    value: T,
+    prev: Option<T>,
+}
+
+impl<T: ComponentValue + Clone> SetComponent<T> {
+    pub fn new(id: Entity, component: Component<T>, value: T) -> Self {
+        Self {
+            id,
+            component,
+            value,
+            prev: None,
+        }
+    }
+
+    /// Create a command for an edit which has already been applied, such as a gizmo drag
+    pub fn applied(id: Entity, component: Component<T>, prev: Option<T>, value: T) -> Self {
+        Self {
+            id,
+            component,
+            value,
+            prev,
+        }
+    }
+}
+
+impl<T: ComponentValue + Clone> EditCommand for SetComponent<T> {
+    fn label(&self) -> Cow<'static, str> {
+        format!("Set {}", self.component.name()).into()
+    }
+
+    fn apply(&mut self, world: &mut World) -> anyhow::Result<()> {
+        self.prev = world.get(self.id, self.component).ok().map(|v| v.clone());
+        world.set(self.id, self.component, self.value.clone())?;
+        Ok(())
+    }
+
+    fn revert(&mut self, world: &mut World) -> anyhow::Result<()> {
+        match &self.prev {
+            Some(prev) => {
+                world.set(self.id, self.component, prev.clone())?;
+            }
+            None => {
+                world.remove(self.id, self.component)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Copies a component from an entity into a builder
+pub type ComponentCapture = Arc<dyn Send + Sync + Fn(&EntityRef, &mut EntityBuilder)>;
+
+/// Returns a capture for the given component, used to restore despawned entities
+pub fn capture<T: ComponentValue + Clone>(component: Component<T>) -> ComponentCapture {
+    Arc::new(move |entity, builder| {
+        if let Ok(value) = entity.get(component) {
+            builder.set(component, value.clone());
+        }
+    })
+}
+
+/// Spawns a new entity.
+///
+/// The entity keeps the same id when redone, so later commands referring to it remain valid.
+pub struct SpawnEntity {
+    mount: Box<dyn Send + Sync + Fn(&mut EntityBuilder)>,
+    id: Option<Entity>,
+}
+
+impl SpawnEntity {
+    pub fn new(mount: impl 'static + Send + Sync + Fn(&mut EntityBuilder)) -> Self {
+        Self {
+            mount: Box::new(mount),
+            id: None,
+        }
+    }
+
+    /// Returns the id of the spawned entity, if applied
+    pub fn id(&self) -> Option<Entity> {
+        self.id
+    }
+}
+
+impl EditCommand for SpawnEntity {
+    fn label(&self) -> Cow<'static, str> {
+        "Spawn entity".into()
+    }
+
+    fn apply(&mut self, world: &mut World) -> anyhow::Result<()> {
+        let mut builder = Entity::builder();
+        (self.mount)(&mut builder);
+
+        match self.id {
+            Some(id) => {
+                builder.spawn_at(world, id)?;
+            }
+            None => self.id = Some(builder.spawn(world)),
+        }
+
+        Ok(())
+    }
+
+    fn revert(&mut self, world: &mut World) -> anyhow::Result<()> {
+        let id = self.id.context("Entity was never spawned")?;
+        world.despawn(id)?;
+        Ok(())
+    }
+}
+
+/// Despawns an entity, restoring the captured components when reverted
+pub struct DespawnEntity {
+    id: Entity,
+    components: Vec<ComponentCapture>,
+    snapshot: Option<EntityBuilder>,
+}
+
+impl DespawnEntity {
+    pub fn new(id: Entity, components: impl IntoIterator<Item = ComponentCapture>) -> Self {
+        Self {
+            id,
+            components: components.into_iter().collect(),
+            snapshot: None,
+        }
+    }
+}
+
+impl EditCommand for DespawnEntity {
+    fn label(&self) -> Cow<'static, str> {
+        "Despawn entity".into()
+    }
+
+    fn apply(&mut self, world: &mut World) -> anyhow::Result<()> {
+        let entity = world.entity(self.id)?;
+
+        let mut snapshot = Entity::builder();
+        for capture in &self.components {
+            capture(&entity, &mut snapshot);
+        }
+
+        self.snapshot = Some(snapshot);
+        world.despawn(self.id)?;
+        Ok(())
+    }
+
+    fn revert(&mut self, world: &mut World) -> anyhow::Result<()> {
+        let mut snapshot = self.snapshot.take().context("Entity was never despawned")?;
+        snapshot.spawn_at(world, self.id)?;
+        Ok(())
+    }
+}
+
+/// Several commands applied and reverted as one
+pub struct CommandGroup {
+    label: Cow<'static, str>,
+    commands: Vec<Box<dyn EditCommand>>,
+}
+
+impl CommandGroup {
+    pub fn new(label: impl Into<Cow<'static, str>>) -> Self {
+        Self {
+            label: label.into(),
+            commands: Vec::new(),
+        }
+    }
+
+    /// Add a command to the group
+    pub fn with_command(mut self, command: impl EditCommand) -> Self {
+        self.commands.push(Box::new(command));
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.commands.is_empty()
+    }
+}
+
+impl EditCommand for CommandGroup {
+    fn label(&self) -> Cow<'static, str> {
+        self.label.clone()
+    }
+
+    /// Reverts the commands which were applied if any command fails
+    fn apply(&mut self, world: &mut World) -> anyhow::Result<()> {
+        for i in 0..self.commands.len() {
+            if let Err(err) = self.commands[i].apply(world) {
+                for command in self.commands[..i].iter_mut().rev() {
+                    if let Err(err) = command.revert(world) {
+                        tracing::error!(label = %command.label(), "Failed to roll back: {err:?}");
+                    }
+                }
+
+                return Err(err);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Applies the commands which were reverted again if any command fails
+    fn revert(&mut self, world: &mut World) -> anyhow::Result<()> {
+        let len = self.commands.len();
+        for i in (0..len).rev() {
+            if let Err(err) = self.commands[i].revert(world) {
+                for command in &mut self.commands[i + 1..] {
+                    if let Err(err) = command.apply(world) {
+                        tracing::error!(label = %command.label(), "Failed to roll back: {err:?}");
+                    }
+                }
+
+                return Err(err);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Undo and redo stack of applied editor commands
+pub struct EditHistory {
+    undo: VecDeque<Box<dyn EditCommand>>,
+    redo: Vec<Box<dyn EditCommand>>,
+    max_len: usize,
+}
+
+impl EditHistory {
+    pub fn new(max_len: usize) -> Self {
+        Self {
+            undo: VecDeque::new(),
+            redo: Vec::new(),
+            max_len,
+        }
+    }
+
+    /// Applies a command and adds it to the history
+    pub fn execute(
+        &mut self,
+        world: &mut World,
+        mut command: impl EditCommand,
+    ) -> anyhow::Result<()> {
+        command
+            .apply(world)
+            .with_context(|| format!("Failed to apply {:?}", command.label()))?;
+
+        self.record(command);
+        Ok(())
+    }
+
+    /// Adds a command which has already been applied to the history
+    pub fn record(&mut self, command: impl EditCommand) {
+        self.redo.clear();
+        self.undo.push_back(Box::new(command));
+
+        if self.undo.len() > self.max_len {
+            self.undo.pop_front();
+        }
+    }
+
+    /// Reverts the most recent command. Returns false if there is nothing to undo.
+    ///
+    /// A command which fails to revert is kept in the history.
+    pub fn undo(&mut self, world: &mut World) -> anyhow::Result<bool> {
+        let Some(command) = self.undo.back_mut() else {
+            return Ok(false);
+        };
+
+        tracing::info!(label = %command.label(), "undo");
+        command
+            .revert(world)
+            .with_context(|| format!("Failed to undo {:?}", command.label()))?;
+
+        let command = self.undo.pop_back().unwrap();
+        self.redo.push(command);
+        Ok(true)
+    }
+
+    /// Applies the most recently undone command. Returns false if there is nothing to redo.
+    ///
+    /// A command which fails to apply is kept in the history.
+    pub fn redo(&mut self, world: &mut World) -> anyhow::Result<bool> {
+        let Some(command) = self.redo.last_mut() else {
+            return Ok(false);
+        };
+
+        tracing::info!(label = %command.label(), "redo");
+        command
+            .apply(world)
+            .with_context(|| format!("Failed to redo {:?}", command.label()))?;
+
+        let command = self.redo.pop().unwrap();
+        self.undo.push_back(command);
+        Ok(true)
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.undo.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.redo.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.undo.clear();
+        self.redo.clear();
+    }
+}
+
+impl Default for EditHistory {
+    fn default() -> Self {
+        Self::new(256)
+    }
+}
+
+component! {
+    /// Stored on the engine entity
+    pub edit_history: EditHistory,
+
+    undo_action: bool,
+    redo_action: bool,
+}
+
+/// Runs `f` with the engine's edit history temporarily detached from the world, allowing commands
+/// to mutate the world.
+pub fn with_history<R>(
+    world: &mut World,
+    f: impl FnOnce(&mut EditHistory, &mut World) -> anyhow::Result<R>,
+) -> anyhow::Result<R> {
+    let mut history = std::mem::take(&mut *world.get_mut(engine(), edit_history())?);
+    let result = f(&mut history, world);
+    *world.get_mut(engine(), edit_history())? = history;
+    result
+}
+
+fn undo_redo_system() -> BoxedSystem {
+    let mut prev = (false, false);
+
+    System::builder()
+        .with_world_mut()
+        .build(move |world: &mut World| {
+            let Some(actions) = Query::new((undo_action().copied(), redo_action().copied()))
+                .borrow(world)
+                .first()
+            else {
+                return Ok(());
+            };
+
+            let (undo, redo) = (actions.0 && !prev.0, actions.1 && !prev.1);
+            prev = actions;
+
+            if undo {
+                with_history(world, |history, world| history.undo(world))?;
+            } else if redo {
+                with_history(world, |history, world| history.redo(world))?;
+            }
+
+            anyhow::Ok(())
+        })
+        .boxed()
+}
+
+/// Adds an [`EditHistory`] to the engine entity with `Ctrl+Z` to undo and `Ctrl+Y` to redo
+pub struct EditHistoryPlugin;
+
+impl Plugin for EditHistoryPlugin {
+    fn install(
+        &self,
+        world: &mut World,
+        _: &AssetCache,
+        schedules: &mut ScheduleSetBuilder,
+    ) -> anyhow::Result<()> {
+        let shortcut = |key: &str| {
+            let mut action = Action::new();
+            action.add(CompositeBinding::new(
+                KeyBinding::new(Key::Character(key.into())),
+                [KeyBinding::new(NamedKey::Control)],
+            ));
+            action
+        };
+
+        world.set(engine(), edit_history(), EditHistory::default())?;
+
+        Entity::builder()
+            .set(
+                input_state(),
+                InputState::new()
+                    .with_action(undo_action(), shortcut("z"))
+                    .with_action(redo_action(), shortcut("y")),
+            )
+            .set_default(undo_action())
+            .set_default(redo_action())
+            .spawn(world);
+
+        schedules.per_tick_mut().with_system(undo_redo_system());
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    use glam::Vec3;
+    use ivy_core::components::position;
+
+    use super::*;
+
+    #[test]
+    fn undo_redo() {
+        let mut world = World::new();
+        let id = Entity::builder().spawn(&mut world);
+        let mut history = EditHistory::new(2);
+
+        for i in 1..=3 {
+            history
+                .execute(
+                    &mut world,
+                    SetComponent::new(id, position(), Vec3::X * i as f32),
+                )
+                .unwrap();
+        }
+
+        assert!(history.undo(&mut world).unwrap());
+        assert_eq!(*world.get(id, position()).unwrap(), Vec3::X * 2.0);
+
+        // The first edit was dropped as the history is full
+        assert!(history.undo(&mut world).unwrap());
+        assert_eq!(*world.get(id, position()).unwrap(), Vec3::X);
+        assert!(!history.undo(&mut world).unwrap());
+
+        assert!(history.redo(&mut world).unwrap());
+        assert!(history.redo(&mut world).unwrap());
+        assert_eq!(*world.get(id, position()).unwrap(), Vec3::X * 3.0);
+        assert!(!history.can_redo());
+    }
+
+    /// Fails to revert until `allow` is set
+    struct Flaky {
+        allow: Arc<AtomicBool>,
+    }
+
+    impl EditCommand for Flaky {
+        fn label(&self) -> Cow<'static, str> {
+            "Flaky".into()
+        }
+
+        fn apply(&mut self, _: &mut World) -> anyhow::Result<()> {
+            Ok(())
+        }
+
+        fn revert(&mut self, _: &mut World) -> anyhow::Result<()> {
+            anyhow::ensure!(self.allow.load(Ordering::Relaxed), "Not allowed");
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn failed_undo_keeps_history() {
+        let mut world = World::new();
+        let allow = Arc::new(AtomicBool::new(false));
+        let mut history = EditHistory::default();
+
+        history
+            .execute(
+                &mut world,
+                Flaky {
+                    allow: allow.clone(),
+                },
+            )
+            .unwrap();
+
+        assert!(history.undo(&mut world).is_err());
+        assert!(history.can_undo());
+        assert!(!history.can_redo());
+
+        allow.store(true, Ordering::Relaxed);
+        assert!(history.undo(&mut world).unwrap());
+        assert!(!history.can_undo());
+        assert!(history.can_redo());
+    }
+
+    #[test]
+    fn group_rolls_back() {
+        let mut world = World::new();
+        let id = Entity::builder().spawn(&mut world);
+        let missing = Entity::builder().spawn(&mut world);
+        world.despawn(missing).unwrap();
+
+        let mut history = EditHistory::default();
+        let group = CommandGroup::new("Move")
+            .with_command(SetComponent::new(id, position(), Vec3::X))
+            .with_command(SetComponent::new(missing, position(), Vec3::Y));
+
+        assert!(history.execute(&mut world, group).is_err());
+        assert!(world.get(id, position()).is_err());
+        assert!(!history.can_undo());
+    }
+}
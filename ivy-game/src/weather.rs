@@ -0,0 +1,433 @@
+use std::collections::BTreeMap;
+
+use anyhow::Context;
+use flax::{
+    component, entity_ids,
+    fetch::{Copied, EntityIds},
+    BoxedSystem, CommandBuffer, Component, ComponentMut, Debuggable, FetchExt, Query, QueryBorrow,
+    System, World,
+};
+use glam::{Mat4, Vec3};
+use ivy_assets::AssetCache;
+use ivy_core::{
+    components::{engine, position, world_transform},
+    palette::Srgb,
+    update_layer::{Plugin, ScheduleSetBuilder},
+};
+use ivy_physics::{
+    components::{effector, velocity},
+    Effector,
+};
+use ivy_wgpu::{components::environment_data, renderer::EnvironmentData};
+
+fn lerp(a: f32, b: f32, t: f32) -> f32 {
+    a + (b - a) * t
+}
+
+/// The parameters of a type of weather, which are blended between when the weather changes
+#[derive(Debug, Clone, PartialEq)]
+pub struct WeatherParams {
+    pub fog_color: Srgb,
+    pub fog_density: f32,
+    /// Wetness of surfaces, see [`EnvironmentData::wetness`]
+    pub wetness: f32,
+    /// Multiplier for the velocity of [`WindZone`]s
+    pub wind_strength: f32,
+    /// Intensity of rain emitters in `[0, 1]`
+    pub rain: f32,
+    /// Intensity of snow emitters in `[0, 1]`
+    pub snow: f32,
+    /// Volume of each named ambient sound, such as `"rain"` or `"wind"`.
+    ///
+    /// Sounds missing from either side of a transition fade in or out.
+    pub ambience: BTreeMap<String, f32>,
+}
+
+impl WeatherParams {
+    pub fn clear() -> Self {
+        Self {
+            fog_color: Srgb::new(0.6, 0.7, 0.8),
+            fog_density: 0.001,
+            wetness: 0.0,
+            wind_strength: 0.2,
+            rain: 0.0,
+            snow: 0.0,
+            ambience: BTreeMap::new(),
+        }
+    }
+
+    pub fn overcast() -> Self {
+        Self {
+            fog_color: Srgb::new(0.5, 0.5, 0.55),
+            fog_density: 0.004,
+            wind_strength: 0.5,
+            ..Self::clear()
+        }
+        .with_ambience("wind", 0.3)
+    }
+
+    pub fn rain() -> Self {
+        Self {
+            fog_color: Srgb::new(0.4, 0.42, 0.45),
+            fog_density: 0.01,
+            wetness: 0.8,
+            wind_strength: 0.6,
+            rain: 0.6,
+            ..Self::clear()
+        }
+        .with_ambience("rain", 0.6)
+        .with_ambience("wind", 0.3)
+    }
+
+    pub fn storm() -> Self {
+        Self {
+            fog_color: Srgb::new(0.25, 0.27, 0.3),
+            fog_density: 0.02,
+            wetness: 1.0,
+            wind_strength: 1.5,
+            rain: 1.0,
+            ..Self::clear()
+        }
+        .with_ambience("rain", 1.0)
+        .with_ambience("wind", 0.8)
+    }
+
+    pub fn snow() -> Self {
+        Self {
+            fog_color: Srgb::new(0.8, 0.82, 0.85),
+            fog_density: 0.015,
+            wetness: 0.2,
+            wind_strength: 0.4,
+            snow: 0.7,
+            ..Self::clear()
+        }
+        .with_ambience("wind", 0.4)
+    }
+
+    pub fn fog() -> Self {
+        Self {
+            fog_color: Srgb::new(0.7, 0.72, 0.75),
+            fog_density: 0.06,
+            wetness: 0.3,
+            wind_strength: 0.05,
+            ..Self::clear()
+        }
+    }
+
+    /// Set the volume of an ambient sound
+    pub fn with_ambience(mut self, sound: impl Into<String>, volume: f32) -> Self {
+        self.ambience.insert(sound.into(), volume);
+        self
+    }
+
+    pub fn lerp(&self, other: &Self, t: f32) -> Self {
+        let mut ambience = self.ambience.clone();
+        for sound in other.ambience.keys() {
+            ambience.entry(sound.clone()).or_insert(0.0);
+        }
+
+        for (sound, volume) in &mut ambience {
+            *volume = lerp(
+                *volume,
+                other.ambience.get(sound).copied().unwrap_or(0.0),
+                t,
+            );
+        }
+
+        Self {
+            fog_color: Srgb::new(
+                lerp(self.fog_color.red, other.fog_color.red, t),
+                lerp(self.fog_color.green, other.fog_color.green, t),
+                lerp(self.fog_color.blue, other.fog_color.blue, t),
+            ),
+            fog_density: lerp(self.fog_density, other.fog_density, t),
+            wetness: lerp(self.wetness, other.wetness, t),
+            wind_strength: lerp(self.wind_strength, other.wind_strength, t),
+            rain: lerp(self.rain, other.rain, t),
+            snow: lerp(self.snow, other.snow, t),
+            ambience,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum WeatherState {
+    Steady {
+        preset: String,
+    },
+    Transition {
+        /// The parameters when the transition started, which may be partway through another
+        /// transition
+        from: WeatherParams,
+        to: String,
+        elapsed: f32,
+        duration: f32,
+    },
+}
+
+/// Blends between named weather presets.
+///
+/// The current parameters are applied to the fog and wetness of each camera and the strength of
+/// [`WindZone`]s. Precipitation emitters and ambient audio read [`Self::current`].
+#[derive(Debug, Clone)]
+pub struct WeatherController {
+    presets: BTreeMap<String, WeatherParams>,
+    state: WeatherState,
+    current: WeatherParams,
+}
+
+impl WeatherController {
+    pub fn new(preset: impl Into<String>, params: WeatherParams) -> Self {
+        let preset = preset.into();
+        Self {
+            presets: [(preset.clone(), params.clone())].into(),
+            state: WeatherState::Steady { preset },
+            current: params,
+        }
+    }
+
+    /// Add or replace a preset
+    pub fn with_preset(mut self, name: impl Into<String>, params: WeatherParams) -> Self {
+        self.add_preset(name, params);
+        self
+    }
+
+    pub fn add_preset(&mut self, name: impl Into<String>, params: WeatherParams) {
+        self.presets.insert(name.into(), params);
+    }
+
+    pub fn preset(&self, name: &str) -> Option<&WeatherParams> {
+        self.presets.get(name)
+    }
+
+    /// Smoothly changes the weather to the named preset over `duration` seconds
+    pub fn transition_to(&mut self, preset: &str, duration: f32) -> anyhow::Result<()> {
+        if !self.presets.contains_key(preset) {
+            anyhow::bail!("Unknown weather preset {preset:?}");
+        }
+
+        self.state = WeatherState::Transition {
+            from: self.current.clone(),
+            to: preset.into(),
+            elapsed: 0.0,
+            duration,
+        };
+
+        Ok(())
+    }
+
+    pub fn update(&mut self, dt: f32) -> anyhow::Result<()> {
+        let WeatherState::Transition {
+            from,
+            to,
+            elapsed,
+            duration,
+        } = &mut self.state
+        else {
+            return Ok(());
+        };
+
+        let target = self
+            .presets
+            .get(to.as_str())
+            .with_context(|| format!("Unknown weather preset {to:?}"))?;
+
+        *elapsed += dt;
+        let t = (*elapsed / duration.max(f32::EPSILON)).clamp(0.0, 1.0);
+        let t = t * t * (3.0 - 2.0 * t);
+
+        self.current = from.lerp(target, t);
+
+        if t >= 1.0 {
+            tracing::info!(preset = %to, "weather changed");
+            self.state = WeatherState::Steady {
+                preset: std::mem::take(to),
+            };
+        }
+
+        Ok(())
+    }
+
+    pub fn state(&self) -> &WeatherState {
+        &self.state
+    }
+
+    /// Returns the blended parameters of the current weather
+    pub fn current(&self) -> &WeatherParams {
+        &self.current
+    }
+}
+
+/// Pushes rigid bodies inside the box towards the wind velocity
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct WindZone {
+    pub half_extents: Vec3,
+    /// Wind velocity at a weather wind strength of 1
+    pub velocity: Vec3,
+    /// Force per unit of relative velocity between the wind and a body
+    pub drag: f32,
+}
+
+impl WindZone {
+    pub fn new(half_extents: Vec3, velocity: Vec3) -> Self {
+        Self {
+            half_extents,
+            velocity,
+            drag: 0.5,
+        }
+    }
+
+    /// Set the drag
+    pub fn with_drag(mut self, drag: f32) -> Self {
+        self.drag = drag;
+        self
+    }
+}
+
+component! {
+    /// Stored on the engine entity
+    pub weather: WeatherController,
+    pub wind_zone: WindZone => [ Debuggable ],
+}
+
+#[allow(clippy::type_complexity)]
+fn update_weather_system(dt: f32) -> BoxedSystem {
+    System::builder()
+        .with_cmd_mut()
+        .with_query(Query::new(weather().as_mut()))
+        .with_query(Query::new((entity_ids(), environment_data().copied())))
+        .build(
+            move |cmd: &mut CommandBuffer,
+                  mut weather: QueryBorrow<ComponentMut<WeatherController>>,
+                  mut environments: QueryBorrow<(
+                EntityIds,
+                Copied<Component<EnvironmentData>>,
+            )>| {
+                let Some(weather) = weather.first() else {
+                    return Ok(());
+                };
+
+                weather.update(dt)?;
+                let params = weather.current();
+
+                // Only write changes, as the renderer uploads modified environments
+                for (id, mut env) in environments.iter() {
+                    if env.fog_color == params.fog_color
+                        && env.fog_density == params.fog_density
+                        && env.wetness == params.wetness
+                    {
+                        continue;
+                    }
+
+                    env.fog_color = params.fog_color;
+                    env.fog_density = params.fog_density;
+                    env.wetness = params.wetness;
+                    cmd.set(id, environment_data(), env);
+                }
+
+                anyhow::Ok(())
+            },
+        )
+        .boxed()
+}
+
+fn wind_zone_system() -> BoxedSystem {
+    System::builder()
+        .with_query(Query::new(weather()))
+        .with_query(Query::new((world_transform(), wind_zone())))
+        .with_query(Query::new((position(), velocity(), effector().as_mut())))
+        .build(
+            |mut weather: QueryBorrow<Component<WeatherController>>,
+             mut zones: QueryBorrow<(Component<Mat4>, Component<WindZone>)>,
+             mut bodies: QueryBorrow<(
+                Component<Vec3>,
+                Component<Vec3>,
+                ComponentMut<Effector>,
+            )>| {
+                let strength = weather
+                    .first()
+                    .map(|v| v.current().wind_strength)
+                    .unwrap_or(1.0);
+
+                for (transform, zone) in zones.iter() {
+                    let inv = transform.inverse();
+                    let wind = zone.velocity * strength;
+
+                    for (&pos, &vel, effector) in bodies.iter() {
+                        let local = inv.transform_point3(pos);
+                        if local.abs().cmpgt(zone.half_extents).any() {
+                            continue;
+                        }
+
+                        effector.apply_force(zone.drag * (wind - vel), true);
+                    }
+                }
+
+                anyhow::Ok(())
+            },
+        )
+        .boxed()
+}
+
+/// Adds a [`WeatherController`] to the engine entity, starting with the given preset.
+///
+/// The presets `"clear"`, `"overcast"`, `"rain"`, `"storm"`, `"snow"` and `"fog"` are available by
+/// default.
+pub struct WeatherPlugin {
+    initial: String,
+    presets: BTreeMap<String, WeatherParams>,
+}
+
+impl WeatherPlugin {
+    pub fn new(initial: impl Into<String>) -> Self {
+        Self {
+            initial: initial.into(),
+            presets: [
+                ("clear", WeatherParams::clear()),
+                ("overcast", WeatherParams::overcast()),
+                ("rain", WeatherParams::rain()),
+                ("storm", WeatherParams::storm()),
+                ("snow", WeatherParams::snow()),
+                ("fog", WeatherParams::fog()),
+            ]
+            .into_iter()
+            .map(|(k, v)| (k.into(), v))
+            .collect(),
+        }
+    }
+
+    /// Add or replace a preset
+    pub fn with_preset(mut self, name: impl Into<String>, params: WeatherParams) -> Self {
+        self.presets.insert(name.into(), params);
+        self
+    }
+}
+
+impl Plugin for WeatherPlugin {
+    fn install(
+        &self,
+        world: &mut World,
+        _: &AssetCache,
+        schedules: &mut ScheduleSetBuilder,
+    ) -> anyhow::Result<()> {
+        let initial = self
+            .presets
+            .get(&self.initial)
+            .with_context(|| format!("Unknown weather preset {:?}", self.initial))?;
+
+        let mut controller = WeatherController::new(self.initial.clone(), initial.clone());
+        for (name, params) in &self.presets {
+            controller.add_preset(name.clone(), params.clone());
+        }
+
+        world.set(engine(), weather(), controller)?;
+
+        let dt = schedules.fixed_mut().time_step().delta_time() as f32;
+        schedules
+            .fixed_mut()
+            .with_system(update_weather_system(dt))
+            .with_system(wind_zone_system());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,40 @@
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion};
+use ivy_assets::{service::FileSystemMapService, Asset, AssetCache};
+use ivy_gltf::Document;
+
+const MODELS: &[&str] = &["models/cube.glb", "models/Sphere.glb", "models/shapes.glb"];
+
+fn asset_cache() -> AssetCache {
+    let assets = AssetCache::new();
+    assets.register_service(FileSystemMapService::new(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/../assets"
+    )));
+
+    assets
+}
+
+fn gltf_import(c: &mut Criterion) {
+    let mut group = c.benchmark_group("gltf_import");
+
+    for &path in MODELS {
+        // Use a new cache for each import, as the document and its buffers would otherwise be
+        // reused
+        group.bench_function(path, |b| {
+            b.iter_batched(
+                asset_cache,
+                |assets| {
+                    let document: Asset<Document> =
+                        futures::executor::block_on(assets.from_path(path)).unwrap();
+                    (assets, document)
+                },
+                BatchSize::PerIteration,
+            )
+        });
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, gltf_import);
+criterion_main!(benches);
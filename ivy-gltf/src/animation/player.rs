@@ -1,4 +1,4 @@
-use std::collections::BTreeMap;
+use std::{borrow::Cow, collections::BTreeMap};
 
 use glam::{Mat4, Quat, Vec3};
 use itertools::Itertools;
@@ -7,21 +7,37 @@ use ivy_core::components::TransformBundle;
 
 use super::{skin::Skin, Animation, KeyFrameValues};
 
+/// An event reached by a playing animation during the last step
+#[derive(Debug, Clone)]
+pub struct AnimationEvent {
+    pub animation: Asset<Animation>,
+    pub name: Cow<'static, str>,
+}
+
 pub struct Animator {
     joint_targets: BTreeMap<usize, TransformBundle>,
+    /// Transforms relative to the skin root replacing the animated transform of a joint, such as
+    /// for ragdolls
+    pose_overrides: BTreeMap<usize, Mat4>,
+    override_weight: f32,
     players: AssetMap<Animation, AnimationPlayer>,
+    events: Vec<AnimationEvent>,
 }
 
 impl Animator {
     pub fn new() -> Self {
         Self {
             joint_targets: BTreeMap::new(),
+            pose_overrides: BTreeMap::new(),
+            override_weight: 1.0,
             players: Default::default(),
+            events: Vec::new(),
         }
     }
 
     pub fn step(&mut self, step_time: f32) {
         // self.joint_targets.clear();
+        self.events.clear();
 
         for (_, player) in &mut self.players {
             player.step(step_time, |joint, target_value| {
@@ -37,9 +53,20 @@ impl Animator {
                     AnimationTarget::Scale(v) => joint_target.scale = v,
                 }
             });
+
+            self.events
+                .extend(player.fired.iter().map(|name| AnimationEvent {
+                    animation: player.animation.clone(),
+                    name: name.clone(),
+                }));
         }
     }
 
+    /// Returns the events fired during the last step
+    pub fn events(&self) -> &[AnimationEvent] {
+        &self.events
+    }
+
     pub fn start_animation(&mut self, player: AnimationPlayer) {
         self.players.insert_with_id(player.animation.id(), player);
     }
@@ -59,19 +86,51 @@ impl Animator {
         self.players.remove(animation);
     }
 
+    /// Replaces the transform of a joint, relative to the skin root.
+    ///
+    /// Unaffected child joints follow the overridden transform.
+    pub fn set_pose_override(&mut self, joint_scene_index: usize, transform: Mat4) {
+        self.pose_overrides.insert(joint_scene_index, transform);
+    }
+
+    pub fn clear_pose_overrides(&mut self) {
+        self.pose_overrides.clear();
+    }
+
+    /// Set how much the pose overrides replace the animated pose, from 0 to 1
+    pub fn set_override_weight(&mut self, weight: f32) {
+        self.override_weight = weight.clamp(0.0, 1.0);
+    }
+
+    pub fn override_weight(&self) -> f32 {
+        self.override_weight
+    }
+
+    /// Fills `buffer` with the skinning matrix of each joint
     pub fn fill_buffer(&self, skin: &Asset<Skin>, buffer: &mut [Mat4]) {
+        self.visit_joints(skin, |index, transform| {
+            buffer[index] = transform * skin.joints()[index].inverse_bind_matrix;
+        });
+    }
+
+    /// Fills `buffer` with the transform of each joint relative to the skin root
+    pub fn fill_joint_transforms(&self, skin: &Asset<Skin>, buffer: &mut [Mat4]) {
+        self.visit_joints(skin, |index, transform| buffer[index] = transform);
+    }
+
+    fn visit_joints(&self, skin: &Asset<Skin>, mut visit: impl FnMut(usize, Mat4)) {
         for &root in skin.roots() {
             let index = skin.joint_to_index(root);
-            self.fill_buffer_recursive(skin, Mat4::IDENTITY, index, buffer);
+            self.visit_joints_recursive(skin, Mat4::IDENTITY, index, &mut visit);
         }
     }
 
-    pub fn fill_buffer_recursive(
+    fn visit_joints_recursive(
         &self,
         skin: &Asset<Skin>,
         parent_transform: Mat4,
         joint_index: usize,
-        buffer: &mut [Mat4],
+        visit: &mut impl FnMut(usize, Mat4),
     ) {
         let joint = &skin.joints()[joint_index];
         let target = self
@@ -79,11 +138,16 @@ impl Animator {
             .get(&joint.scene_index)
             .unwrap_or(&joint.local_bind_transform);
 
-        let transform = parent_transform * target.to_mat4();
-        buffer[joint_index] = transform * joint.inverse_bind_matrix;
+        let mut transform = parent_transform * target.to_mat4();
+
+        if let Some(pose) = self.pose_overrides.get(&joint.scene_index) {
+            transform = blend_transforms(transform, *pose, self.override_weight);
+        }
+
+        visit(joint_index, transform);
 
         for &child in &joint.children {
-            self.fill_buffer_recursive(skin, transform, skin.joint_to_index(child), buffer);
+            self.visit_joints_recursive(skin, transform, skin.joint_to_index(child), visit);
         }
     }
 
@@ -98,12 +162,31 @@ impl Default for Animator {
     }
 }
 
+fn blend_transforms(a: Mat4, b: Mat4, t: f32) -> Mat4 {
+    if t <= 0.0 {
+        return a;
+    } else if t >= 1.0 {
+        return b;
+    }
+
+    let (a_scale, a_rotation, a_pos) = a.to_scale_rotation_translation();
+    let (b_scale, b_rotation, b_pos) = b.to_scale_rotation_translation();
+
+    Mat4::from_scale_rotation_translation(
+        a_scale.lerp(b_scale, t),
+        a_rotation.slerp(b_rotation, t),
+        a_pos.lerp(b_pos, t),
+    )
+}
+
 pub struct AnimationPlayer {
     progress: f32,
     speed: f32,
     looping: bool,
     animation: Asset<Animation>,
     channels: Vec<ChannelState>,
+    events: Vec<(f32, Cow<'static, str>)>,
+    fired: Vec<Cow<'static, str>>,
 }
 
 impl AnimationPlayer {
@@ -118,9 +201,56 @@ impl AnimationPlayer {
             animation,
             speed: 1.0,
             looping: false,
+            events: Vec::new(),
+            fired: Vec::new(),
         }
     }
 
+    /// Fire a named event each time playback passes `time`, such as a foot touching the ground
+    pub fn with_event(mut self, time: f32, name: impl Into<Cow<'static, str>>) -> Self {
+        self.add_event(time, name);
+        self
+    }
+
+    pub fn add_event(&mut self, time: f32, name: impl Into<Cow<'static, str>>) {
+        self.events.push((time, name.into()));
+    }
+
+    /// Returns the events passed during the last step
+    pub fn fired_events(&self) -> &[Cow<'static, str>] {
+        &self.fired
+    }
+
+    fn collect_events(&mut self, prev: f32, wrapped: bool) {
+        let (start, end) = if self.speed >= 0.0 {
+            (prev, self.progress)
+        } else {
+            (self.progress, prev)
+        };
+
+        let duration = self.animation.duration();
+
+        // When wrapping the passed range is split across the end and start of the animation
+        let passed = |t: f32| {
+            if wrapped && self.speed >= 0.0 {
+                t > start || t <= end
+            } else if wrapped {
+                t >= start || t < end
+            } else if self.speed >= 0.0 {
+                t > start && t <= end
+            } else {
+                t >= start && t < end
+            }
+        };
+
+        self.fired.extend(
+            self.events
+                .iter()
+                .filter(|(t, _)| *t <= duration && passed(*t))
+                .map(|(_, name)| name.clone()),
+        );
+    }
+
     pub fn set_speed(&mut self, speed: f32) {
         self.speed = speed;
     }
@@ -130,6 +260,8 @@ impl AnimationPlayer {
     }
 
     pub fn step(&mut self, step_time: f32, mut writer: impl FnMut(usize, AnimationTarget)) {
+        self.fired.clear();
+
         let finished = (self.speed > 0.0 && self.progress > self.animation.duration())
             || (self.speed < 0.0 && self.progress < 0.0);
 
@@ -138,8 +270,12 @@ impl AnimationPlayer {
             return;
         }
 
+        let prev = self.progress;
         self.progress += step_time * self.speed;
 
+        let wrapped =
+            self.looping && (self.progress > self.animation.duration() || self.progress < 0.0);
+
         // Do this after stepping to not show past-end lerps when we could have wrapped
         if self.looping {
             if self.progress > self.animation.duration() {
@@ -157,6 +293,8 @@ impl AnimationPlayer {
             self.progress = self.progress.clamp(0.0, self.animation.duration());
         }
 
+        self.collect_events(prev, wrapped);
+
         for (i, state) in self.channels.iter_mut().enumerate() {
             let channel = &self.animation.channels()[i];
             if channel.times.len() == 1 {
@@ -244,3 +382,37 @@ pub enum AnimationTarget {
 struct ChannelState {
     left_keyframe: usize,
 }
+
+#[cfg(test)]
+mod test {
+    use std::f32::consts::FRAC_PI_2;
+
+    use super::*;
+
+    #[test]
+    fn blend_pose_override() {
+        let a = Mat4::from_translation(Vec3::X);
+        let b = Mat4::from_rotation_translation(Quat::from_rotation_y(FRAC_PI_2), Vec3::Z * 3.0);
+
+        assert_eq!(blend_transforms(a, b, 0.0), a);
+        assert_eq!(blend_transforms(a, b, -1.0), a);
+        assert_eq!(blend_transforms(a, b, 1.0), b);
+
+        let (scale, rot, pos) = blend_transforms(a, b, 0.5).to_scale_rotation_translation();
+        assert!(scale.abs_diff_eq(Vec3::ONE, 1e-5));
+        assert!(pos.abs_diff_eq(Vec3::new(0.5, 0.0, 1.5), 1e-5));
+        assert!((rot.angle_between(Quat::IDENTITY) - FRAC_PI_2 * 0.5).abs() < 1e-4);
+    }
+
+    #[test]
+    fn override_weight() {
+        let mut animator = Animator::new();
+        assert_eq!(animator.override_weight(), 1.0);
+
+        animator.set_override_weight(2.0);
+        assert_eq!(animator.override_weight(), 1.0);
+
+        animator.set_override_weight(-0.5);
+        assert_eq!(animator.override_weight(), 0.0);
+    }
+}
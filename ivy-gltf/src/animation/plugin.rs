@@ -1,14 +1,17 @@
-use std::time::Duration;
+use std::{collections::BTreeMap, time::Duration};
 
-use flax::{components::child_of, system, FetchExt, World};
-use glam::{Quat, Vec3};
+use flax::{
+    components::child_of, fetch::entity_refs, system, BoxedSystem, Entity, FetchExt, Query, System,
+    World,
+};
+use glam::{Mat4, Quat, Vec3};
 use ivy_assets::{Asset, AssetCache};
 use ivy_core::{
-    components::{delta_time, engine, position, rotation},
+    components::{attachment_transform, delta_time, engine, position, rotation},
     update_layer::{Plugin, ScheduleSetBuilder},
 };
 
-use crate::components::{animator, skin, track_bone};
+use crate::components::{animator, skin, socket, track_bone};
 
 use super::{player::Animator, skin::Skin};
 
@@ -24,7 +27,8 @@ impl Plugin for AnimationPlugin {
         schedules
             .per_tick_mut()
             .with_system(animation_step_system())
-            .with_system(follow_bone_plugin_system());
+            .with_system(follow_bone_plugin_system())
+            .with_system(socket_system());
 
         Ok(())
     }
@@ -54,3 +58,91 @@ fn follow_bone_plugin(
         }
     }
 }
+
+/// Places entities with a [`socket`] at the joint of the target's current pose.
+///
+/// The socket replaces the parent of the entity while attached, which is restored once the socket
+/// is removed.
+fn socket_system() -> BoxedSystem {
+    // Sockets attached last frame, to detach them when the relation is removed
+    let mut attached = BTreeMap::<Entity, Entity>::new();
+    // Parents replaced by a socket
+    let mut previous_parents = BTreeMap::<Entity, Entity>::new();
+    // Joint transforms of each skeleton, shared by all sockets attached to it
+    let mut palettes = BTreeMap::<Entity, Vec<Mat4>>::new();
+
+    let mut query = Query::new(entity_refs()).with_relation(socket);
+
+    System::builder()
+        .with_world_mut()
+        .build(move |world: &mut World| {
+            let mut sockets = Vec::new();
+            palettes.clear();
+
+            for entity in &mut query.borrow(world) {
+                for (target, bone) in entity.relations(socket) {
+                    let Ok(target_ref) = world.entity(target) else {
+                        continue;
+                    };
+
+                    let (Ok(skin), Ok(animator)) =
+                        (target_ref.get(skin()), target_ref.get(animator()))
+                    else {
+                        continue;
+                    };
+
+                    let Some(index) = skin.find_joint_by_name(bone) else {
+                        tracing::warn!(?target, %bone, "No joint found for socket");
+                        continue;
+                    };
+
+                    let palette = palettes.entry(target).or_insert_with(|| {
+                        let mut joints = vec![Mat4::IDENTITY; skin.joints().len()];
+                        animator.fill_joint_transforms(&skin, &mut joints);
+                        joints
+                    });
+
+                    let parent = entity
+                        .relations(child_of)
+                        .map(|(parent, _)| parent)
+                        .find(|&parent| parent != target);
+
+                    sockets.push((entity.id(), target, palette[index], parent));
+                }
+            }
+
+            let mut detached = std::mem::take(&mut attached);
+            for (id, target, transform, parent) in sockets {
+                if let Some(parent) = parent {
+                    world.remove(id, child_of(parent))?;
+                    previous_parents.insert(id, parent);
+                }
+
+                if !world.has(id, child_of(target)) {
+                    world.set(id, child_of(target), ())?;
+                }
+
+                world.set(id, attachment_transform(), transform)?;
+
+                detached.remove(&id);
+                attached.insert(id, target);
+            }
+
+            for (id, target) in detached {
+                let parent = previous_parents.remove(&id);
+                if !world.is_alive(id) {
+                    continue;
+                }
+
+                world.remove(id, attachment_transform()).ok();
+                world.remove(id, child_of(target)).ok();
+
+                if let Some(parent) = parent.filter(|&v| world.is_alive(v)) {
+                    world.set(id, child_of(parent), ())?;
+                }
+            }
+
+            anyhow::Ok(())
+        })
+        .boxed()
+}
@@ -195,6 +195,13 @@ impl Skin {
         self.joints.get(*self.joint_map.get(&index)?)
     }
 
+    /// Returns the index of the joint with the given name
+    pub fn find_joint_by_name(&self, name: &str) -> Option<usize> {
+        self.joints
+            .iter()
+            .position(|v| v.name.as_deref() == Some(name))
+    }
+
     pub fn joints(&self) -> &[Joint] {
         &self.joints
     }
@@ -7,4 +7,12 @@ component! {
     pub skin: Asset<Skin>,
     pub animator: Animator,
     pub track_bone: String,
+
+    /// Attaches the entity to the joint with the given name of the target's skin, e.g; a weapon
+    /// to `hand_r`.
+    ///
+    /// The entity is made a child of the target, replacing its parent until the socket is removed,
+    /// and its local transform is relative to the joint. Assumes the skinned mesh is not offset
+    /// from the entity holding the [`skin`] and [`animator`].
+    pub socket(target): String,
 }
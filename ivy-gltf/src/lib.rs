@@ -11,11 +11,15 @@ use gltf::{buffer, Gltf};
 use image::{DynamicImage, ImageFormat};
 use itertools::Itertools;
 use ivy_assets::{fs::AsyncAssetFromPath, Asset, AssetCache, AssetDesc};
-use ivy_core::components::TransformBundle;
-use ivy_graphics::mesh::{MeshData, TANGENT_ATTRIBUTE};
+use ivy_core::{components::TransformBundle, memory::TrackedMemory};
+use ivy_graphics::mesh::{
+    MeshData, JOINT_INDEX_1_ATTRIBUTE, TANGENT_ATTRIBUTE, WEIGHT_1_ATTRIBUTE,
+};
 use ivy_profiling::{profile_function, profile_scope};
 use rayon::iter::{ParallelBridge, ParallelIterator};
 
+const MEMORY_CATEGORY: &str = "assets";
+
 /// An in memory representation of a gltf document and binary buffer data
 pub struct DocumentData {
     gltf: Gltf,
@@ -30,6 +34,8 @@ pub struct DocumentData {
 
     skins: Vec<Asset<Skin>>,
     // buffer_data: Vec<gltf::buffer::Data>,
+    /// Accounts the buffer and decoded image data
+    _memory: TrackedMemory,
 }
 
 impl DocumentData {
@@ -171,6 +177,9 @@ impl Document {
 
         let skins = Skin::load_from_document(assets, &gltf.document, &buffer_data, path)?;
 
+        let bytes = buffer_data.iter().map(|v| v.0.len()).sum::<usize>()
+            + images.iter().map(|v| v.as_bytes().len()).sum::<usize>();
+
         let data = assets.insert(DocumentData {
             gltf,
             named_meshes,
@@ -180,6 +189,7 @@ impl Document {
             images,
             skins,
             mesh_data: meshes,
+            _memory: TrackedMemory::new(MEMORY_CATEGORY, bytes as u64),
         });
 
         Ok(Self { data })
@@ -554,6 +564,15 @@ pub(crate) fn mesh_from_gltf(
         .map(Vec4::from)
         .collect_vec();
 
+    // Second set of influences, for vertices affected by more than four joints
+    let joints_1 = reader
+        .read_joints(1)
+        .map(|v| v.into_u16().map(U16Vec4::from).collect_vec());
+
+    let weights_1 = reader
+        .read_weights(1)
+        .map(|v| v.into_f32().map(Vec4::from).collect_vec());
+
     let texcoord = reader
         .read_tex_coords(0)
         .into_iter()
@@ -568,6 +587,13 @@ pub(crate) fn mesh_from_gltf(
         this
     };
 
+    let this = if let (Some(joints), Some(weights)) = (joints_1, weights_1) {
+        this.with_attribute(JOINT_INDEX_1_ATTRIBUTE, joints)
+            .with_attribute(WEIGHT_1_ATTRIBUTE, weights)
+    } else {
+        this
+    };
+
     async move {
         let this = async_std::task::spawn_blocking(move || this.with_generated_tangents()).await?;
 
@@ -0,0 +1,623 @@
+//! Constructive solid geometry for blocking out levels.
+//!
+//! Brushes are combined using BSP trees, based on the approach used by `csg.js`.
+use std::f32::consts::TAU;
+
+use glam::{vec2, vec3, Mat3, Mat4, Vec2, Vec3};
+use itertools::Itertools;
+
+use crate::mesh::MeshData;
+
+const EPSILON: f32 = 1e-5;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Vertex {
+    pos: Vec3,
+    normal: Vec3,
+}
+
+impl Vertex {
+    fn new(pos: Vec3, normal: Vec3) -> Self {
+        Self { pos, normal }
+    }
+
+    fn flip(&mut self) {
+        self.normal = -self.normal;
+    }
+
+    fn lerp(&self, other: &Self, t: f32) -> Self {
+        Self {
+            pos: self.pos.lerp(other.pos, t),
+            normal: self.normal.lerp(other.normal, t).normalize_or_zero(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Plane {
+    normal: Vec3,
+    w: f32,
+}
+
+impl Plane {
+    fn from_points(a: Vec3, b: Vec3, c: Vec3) -> Self {
+        let normal = (b - a).cross(c - a).normalize_or_zero();
+        Self {
+            normal,
+            w: normal.dot(a),
+        }
+    }
+
+    fn flip(&mut self) {
+        self.normal = -self.normal;
+        self.w = -self.w;
+    }
+
+    /// Splits the polygon by this plane into the respective lists
+    fn split_polygon(
+        &self,
+        polygon: &Polygon,
+        coplanar_front: &mut Vec<Polygon>,
+        coplanar_back: &mut Vec<Polygon>,
+        front: &mut Vec<Polygon>,
+        back: &mut Vec<Polygon>,
+    ) {
+        const COPLANAR: u8 = 0;
+        const FRONT: u8 = 1;
+        const BACK: u8 = 2;
+        const SPANNING: u8 = 3;
+
+        let mut polygon_type = COPLANAR;
+        let types = polygon
+            .vertices
+            .iter()
+            .map(|v| {
+                let t = self.normal.dot(v.pos) - self.w;
+                let ty = if t < -EPSILON {
+                    BACK
+                } else if t > EPSILON {
+                    FRONT
+                } else {
+                    COPLANAR
+                };
+
+                polygon_type |= ty;
+                ty
+            })
+            .collect_vec();
+
+        match polygon_type {
+            COPLANAR => {
+                if self.normal.dot(polygon.plane.normal) > 0.0 {
+                    coplanar_front.push(polygon.clone());
+                } else {
+                    coplanar_back.push(polygon.clone());
+                }
+            }
+            FRONT => front.push(polygon.clone()),
+            BACK => back.push(polygon.clone()),
+            _ => {
+                let mut f = Vec::new();
+                let mut b = Vec::new();
+
+                for (i, j) in (0..polygon.vertices.len()).circular_tuple_windows() {
+                    let (ti, tj) = (types[i], types[j]);
+                    let (vi, vj) = (&polygon.vertices[i], &polygon.vertices[j]);
+
+                    if ti != BACK {
+                        f.push(*vi);
+                    }
+
+                    if ti != FRONT {
+                        b.push(*vi);
+                    }
+
+                    if (ti | tj) == SPANNING {
+                        let t =
+                            (self.w - self.normal.dot(vi.pos)) / self.normal.dot(vj.pos - vi.pos);
+                        let v = vi.lerp(vj, t);
+                        f.push(v);
+                        b.push(v);
+                    }
+                }
+
+                if f.len() >= 3 {
+                    front.push(Polygon::with_plane(f, polygon.plane));
+                }
+
+                if b.len() >= 3 {
+                    back.push(Polygon::with_plane(b, polygon.plane));
+                }
+            }
+        }
+    }
+}
+
+/// A convex polygon
+#[derive(Debug, Clone, PartialEq)]
+struct Polygon {
+    vertices: Vec<Vertex>,
+    plane: Plane,
+}
+
+impl Polygon {
+    fn new(vertices: Vec<Vertex>) -> Self {
+        let plane = Plane::from_points(vertices[0].pos, vertices[1].pos, vertices[2].pos);
+        Self { vertices, plane }
+    }
+
+    /// Creates a polygon with flat normals
+    fn flat(positions: impl IntoIterator<Item = Vec3>) -> Self {
+        let mut polygon = Self::new(
+            positions
+                .into_iter()
+                .map(|v| Vertex::new(v, Vec3::ZERO))
+                .collect(),
+        );
+
+        for v in &mut polygon.vertices {
+            v.normal = polygon.plane.normal;
+        }
+
+        polygon
+    }
+
+    fn with_plane(vertices: Vec<Vertex>, plane: Plane) -> Self {
+        Self { vertices, plane }
+    }
+
+    fn flip(&mut self) {
+        self.vertices.reverse();
+        self.vertices.iter_mut().for_each(Vertex::flip);
+        self.plane.flip();
+    }
+}
+
+/// A node in a BSP tree of polygons
+#[derive(Default)]
+struct Node {
+    plane: Option<Plane>,
+    front: Option<Box<Node>>,
+    back: Option<Box<Node>>,
+    polygons: Vec<Polygon>,
+}
+
+impl Node {
+    fn new(polygons: Vec<Polygon>) -> Self {
+        let mut node = Self::default();
+        node.build(polygons);
+        node
+    }
+
+    /// Converts solid space to empty space and vice versa
+    fn invert(&mut self) {
+        self.polygons.iter_mut().for_each(Polygon::flip);
+
+        if let Some(plane) = &mut self.plane {
+            plane.flip();
+        }
+
+        if let Some(front) = &mut self.front {
+            front.invert();
+        }
+
+        if let Some(back) = &mut self.back {
+            back.invert();
+        }
+
+        std::mem::swap(&mut self.front, &mut self.back);
+    }
+
+    /// Removes all polygons which are inside this BSP tree
+    fn clip_polygons(&self, polygons: Vec<Polygon>) -> Vec<Polygon> {
+        let Some(plane) = self.plane else {
+            return polygons;
+        };
+
+        let mut front = Vec::new();
+        let mut back = Vec::new();
+
+        for polygon in &polygons {
+            let mut coplanar_front = Vec::new();
+            let mut coplanar_back = Vec::new();
+            plane.split_polygon(
+                polygon,
+                &mut coplanar_front,
+                &mut coplanar_back,
+                &mut front,
+                &mut back,
+            );
+
+            front.append(&mut coplanar_front);
+            back.append(&mut coplanar_back);
+        }
+
+        let mut front = match &self.front {
+            Some(node) => node.clip_polygons(front),
+            None => front,
+        };
+
+        if let Some(node) = &self.back {
+            front.extend(node.clip_polygons(back));
+        }
+
+        front
+    }
+
+    /// Removes all polygons in this tree which are inside `other`
+    fn clip_to(&mut self, other: &Node) {
+        self.polygons = other.clip_polygons(std::mem::take(&mut self.polygons));
+
+        if let Some(front) = &mut self.front {
+            front.clip_to(other);
+        }
+
+        if let Some(back) = &mut self.back {
+            back.clip_to(other);
+        }
+    }
+
+    fn all_polygons(&self) -> Vec<Polygon> {
+        let mut polygons = self.polygons.clone();
+
+        if let Some(front) = &self.front {
+            polygons.extend(front.all_polygons());
+        }
+
+        if let Some(back) = &self.back {
+            polygons.extend(back.all_polygons());
+        }
+
+        polygons
+    }
+
+    fn build(&mut self, polygons: Vec<Polygon>) {
+        if polygons.is_empty() {
+            return;
+        }
+
+        let plane = *self.plane.get_or_insert(polygons[0].plane);
+
+        let mut front = Vec::new();
+        let mut back = Vec::new();
+
+        for polygon in &polygons {
+            let mut coplanar_front = Vec::new();
+            let mut coplanar_back = Vec::new();
+
+            plane.split_polygon(
+                polygon,
+                &mut coplanar_front,
+                &mut coplanar_back,
+                &mut front,
+                &mut back,
+            );
+
+            self.polygons.append(&mut coplanar_front);
+            self.polygons.append(&mut coplanar_back);
+        }
+
+        if !front.is_empty() {
+            self.front.get_or_insert_with(Default::default).build(front);
+        }
+
+        if !back.is_empty() {
+            self.back.get_or_insert_with(Default::default).build(back);
+        }
+    }
+}
+
+/// A solid made up of polygons which can be combined with boolean operations
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Csg {
+    polygons: Vec<Polygon>,
+}
+
+impl Csg {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// An axis aligned box
+    pub fn cuboid(half_extents: Vec3) -> Self {
+        const FACES: [[usize; 4]; 6] = [
+            [0, 4, 6, 2],
+            [1, 3, 7, 5],
+            [0, 1, 5, 4],
+            [2, 6, 7, 3],
+            [0, 2, 3, 1],
+            [4, 5, 7, 6],
+        ];
+
+        let corner = |i: usize| {
+            let sign = |bit: usize| if i & bit != 0 { 1.0 } else { -1.0 };
+            vec3(sign(1), sign(2), sign(4)) * half_extents
+        };
+
+        Self {
+            polygons: FACES
+                .iter()
+                .map(|face| Polygon::flat(face.map(corner)))
+                .collect(),
+        }
+    }
+
+    /// A cylinder along the Y axis
+    pub fn cylinder(radius: f32, half_height: f32, slices: u32) -> Self {
+        let slices = slices.max(3);
+        let dir = |i: u32| {
+            let angle = i as f32 / slices as f32 * TAU;
+            vec3(angle.cos(), 0.0, angle.sin())
+        };
+
+        let top = Vec3::Y * half_height;
+        let bottom = -top;
+
+        let mut polygons = Vec::new();
+        for i in 0..slices {
+            let (d0, d1) = (dir(i), dir(i + 1));
+
+            let (b0, b1) = (bottom + d0 * radius, bottom + d1 * radius);
+            let (t0, t1) = (top + d0 * radius, top + d1 * radius);
+
+            polygons.push(Polygon::flat([bottom, b0, b1]));
+            polygons.push(Polygon::new(vec![
+                Vertex::new(b0, d0),
+                Vertex::new(t0, d0),
+                Vertex::new(t1, d1),
+                Vertex::new(b1, d1),
+            ]));
+            polygons.push(Polygon::flat([top, t1, t0]));
+        }
+
+        Self { polygons }
+    }
+
+    /// A wedge with the slope rising towards +X
+    pub fn ramp(half_extents: Vec3) -> Self {
+        let Vec3 { x, y, z } = half_extents;
+
+        let b0 = vec3(-x, -y, -z);
+        let b1 = vec3(x, -y, -z);
+        let b2 = vec3(x, -y, z);
+        let b3 = vec3(-x, -y, z);
+        let t1 = vec3(x, y, -z);
+        let t2 = vec3(x, y, z);
+
+        Self {
+            polygons: vec![
+                Polygon::flat([b0, b1, b2, b3]),
+                Polygon::flat([b1, t1, t2, b2]),
+                Polygon::flat([b0, b3, t2, t1]),
+                Polygon::flat([b0, t1, b1]),
+                Polygon::flat([b3, b2, t2]),
+            ],
+        }
+    }
+
+    /// Returns the solid transformed by the given matrix
+    pub fn transformed(mut self, transform: Mat4) -> Self {
+        let normal_matrix = Mat3::from_mat4(transform).inverse().transpose();
+        let mirrored = transform.determinant() < 0.0;
+
+        for polygon in &mut self.polygons {
+            for v in &mut polygon.vertices {
+                v.pos = transform.transform_point3(v.pos);
+                v.normal = (normal_matrix * v.normal).normalize_or_zero();
+            }
+
+            if mirrored {
+                polygon.vertices.reverse();
+            }
+
+            let [a, b, c] = [0, 1, 2].map(|i| polygon.vertices[i].pos);
+            polygon.plane = Plane::from_points(a, b, c);
+        }
+
+        self
+    }
+
+    /// Returns the space occupied by either solid
+    pub fn union(&self, other: &Self) -> Self {
+        let mut a = Node::new(self.polygons.clone());
+        let mut b = Node::new(other.polygons.clone());
+
+        a.clip_to(&b);
+        b.clip_to(&a);
+        b.invert();
+        b.clip_to(&a);
+        b.invert();
+        a.build(b.all_polygons());
+
+        Self {
+            polygons: a.all_polygons(),
+        }
+    }
+
+    /// Returns the space occupied by this solid but not by `other`
+    pub fn subtract(&self, other: &Self) -> Self {
+        let mut a = Node::new(self.polygons.clone());
+        let mut b = Node::new(other.polygons.clone());
+
+        a.invert();
+        a.clip_to(&b);
+        b.clip_to(&a);
+        b.invert();
+        b.clip_to(&a);
+        b.invert();
+        a.build(b.all_polygons());
+        a.invert();
+
+        Self {
+            polygons: a.all_polygons(),
+        }
+    }
+
+    /// Returns the space occupied by both solids
+    pub fn intersect(&self, other: &Self) -> Self {
+        let mut a = Node::new(self.polygons.clone());
+        let mut b = Node::new(other.polygons.clone());
+
+        a.invert();
+        b.clip_to(&a);
+        b.invert();
+        a.clip_to(&b);
+        b.clip_to(&a);
+        a.build(b.all_polygons());
+        a.invert();
+
+        Self {
+            polygons: a.all_polygons(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.polygons.is_empty()
+    }
+
+    /// Triangulates the solid into a mesh.
+    ///
+    /// Texture coordinates are projected along the dominant axis of each face in world units
+    /// multiplied by `uv_scale`, which keeps grid textures aligned across brushes.
+    pub fn to_mesh_data(&self, uv_scale: f32) -> MeshData {
+        let mut positions = Vec::new();
+        let mut normals = Vec::new();
+        let mut tex_coords = Vec::new();
+        let mut indices = Vec::new();
+
+        for polygon in &self.polygons {
+            let base = positions.len() as u32;
+            let n = polygon.plane.normal.abs();
+
+            for v in &polygon.vertices {
+                let uv: Vec2 = if n.x >= n.y && n.x >= n.z {
+                    vec2(v.pos.z, -v.pos.y)
+                } else if n.y >= n.z {
+                    vec2(v.pos.x, v.pos.z)
+                } else {
+                    vec2(v.pos.x, -v.pos.y)
+                };
+
+                positions.push(v.pos);
+                normals.push(v.normal);
+                tex_coords.push(uv * uv_scale);
+            }
+
+            for i in 1..polygon.vertices.len() as u32 - 1 {
+                indices.extend([base, base + i, base + i + 1]);
+            }
+        }
+
+        let mut mesh = MeshData::unskinned(indices, positions, tex_coords, normals);
+        if let Err(err) = mesh.generate_tangents() {
+            tracing::warn!("Failed to generate tangents for csg mesh: {err:?}");
+        }
+
+        mesh
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BrushShape {
+    Box {
+        half_extents: Vec3,
+    },
+    Cylinder {
+        radius: f32,
+        half_height: f32,
+        slices: u32,
+    },
+    Ramp {
+        half_extents: Vec3,
+    },
+}
+
+impl BrushShape {
+    pub fn to_csg(&self) -> Csg {
+        match *self {
+            BrushShape::Box { half_extents } => Csg::cuboid(half_extents),
+            BrushShape::Cylinder {
+                radius,
+                half_height,
+                slices,
+            } => Csg::cylinder(radius, half_height, slices),
+            BrushShape::Ramp { half_extents } => Csg::ramp(half_extents),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CsgOperation {
+    #[default]
+    Union,
+    Subtract,
+    Intersect,
+}
+
+/// A shape placed in the level and combined with the preceding brushes
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CsgBrush {
+    pub shape: BrushShape,
+    pub transform: Mat4,
+    pub operation: CsgOperation,
+}
+
+impl CsgBrush {
+    pub fn new(shape: BrushShape, transform: Mat4) -> Self {
+        Self {
+            shape,
+            transform,
+            operation: CsgOperation::Union,
+        }
+    }
+
+    /// Set the operation
+    pub fn with_operation(mut self, operation: CsgOperation) -> Self {
+        self.operation = operation;
+        self
+    }
+}
+
+/// Combines the brushes in order into a single solid
+pub fn build_brushes<'a>(brushes: impl IntoIterator<Item = &'a CsgBrush>) -> Csg {
+    brushes.into_iter().fold(Csg::new(), |acc, brush| {
+        let solid = brush.shape.to_csg().transformed(brush.transform);
+
+        match brush.operation {
+            CsgOperation::Union if acc.is_empty() => solid,
+            CsgOperation::Union => acc.union(&solid),
+            CsgOperation::Subtract => acc.subtract(&solid),
+            CsgOperation::Intersect => acc.intersect(&solid),
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn volume(csg: &Csg) -> f32 {
+        // Divergence theorem over the triangulated faces
+        csg.polygons
+            .iter()
+            .flat_map(|p| {
+                (1..p.vertices.len() - 1).map(move |i| {
+                    let (a, b, c) = (p.vertices[0].pos, p.vertices[i].pos, p.vertices[i + 1].pos);
+                    a.dot(b.cross(c)) / 6.0
+                })
+            })
+            .sum()
+    }
+
+    #[test]
+    fn boolean_volumes() {
+        let a = Csg::cuboid(Vec3::ONE);
+        let b = Csg::cuboid(Vec3::ONE).transformed(Mat4::from_translation(Vec3::X));
+
+        assert!((volume(&a) - 8.0).abs() < 1e-3);
+        assert!((volume(&a.union(&b)) - 12.0).abs() < 1e-3);
+        assert!((volume(&a.subtract(&b)) - 4.0).abs() < 1e-3);
+        assert!((volume(&a.intersect(&b)) - 4.0).abs() < 1e-3);
+        assert!((volume(&Csg::ramp(Vec3::ONE)) - 4.0).abs() < 1e-3);
+    }
+}
@@ -1,2 +1,4 @@
+pub mod csg;
 pub mod mesh;
+pub mod spline;
 pub mod texture;
@@ -179,6 +179,11 @@ pub const WEIGHT_ATTRIBUTE: MeshAttribute =
     MeshAttribute::new("vertex_weight_attribute", AttributeType::Vec4);
 pub const TANGENT_ATTRIBUTE: MeshAttribute =
     MeshAttribute::new("vertex_tangent_attribute", AttributeType::Vec4);
+/// Additional joint influences for vertices affected by more than four joints
+pub const JOINT_INDEX_1_ATTRIBUTE: MeshAttribute =
+    MeshAttribute::new("vertex_joint_index_1_attribute", AttributeType::U16Vec4);
+pub const WEIGHT_1_ATTRIBUTE: MeshAttribute =
+    MeshAttribute::new("vertex_weight_1_attribute", AttributeType::Vec4);
 
 impl MeshData {
     pub fn new() -> Self {
@@ -0,0 +1,527 @@
+//! Splines and meshes extruded along them, used for roads, rivers and fences.
+use glam::{vec2, Vec2, Vec3};
+
+use crate::mesh::MeshData;
+
+/// Number of linear segments used to approximate the arc length of each spline segment
+const ARC_LENGTH_STEPS: usize = 16;
+
+/// How the curve is formed from the control points
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum SplineKind {
+    /// Smooth curve passing through each control point
+    #[default]
+    CatmullRom,
+    /// Cubic bezier segments, where each segment is formed by a point, two handles and the next
+    /// point
+    Bezier,
+    /// Straight lines between the points, such as waypoints
+    Linear,
+}
+
+/// A curve through a list of control points
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Spline {
+    pub points: Vec<Vec3>,
+    /// Connect the last point back to the first
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub closed: bool,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub kind: SplineKind,
+}
+
+impl Spline {
+    pub fn new(points: impl IntoIterator<Item = Vec3>) -> Self {
+        Self {
+            points: points.into_iter().collect(),
+            closed: false,
+            kind: SplineKind::CatmullRom,
+        }
+    }
+
+    /// Creates a spline of cubic bezier segments from the points `[p0, h0, h1, p1, h2, h3, p2..]`
+    pub fn bezier(points: impl IntoIterator<Item = Vec3>) -> Self {
+        Self::new(points).with_kind(SplineKind::Bezier)
+    }
+
+    /// Creates a path of straight lines between the points
+    pub fn linear(points: impl IntoIterator<Item = Vec3>) -> Self {
+        Self::new(points).with_kind(SplineKind::Linear)
+    }
+
+    /// Set whether the spline loops back to the first point
+    pub fn with_closed(mut self, closed: bool) -> Self {
+        self.closed = closed;
+        self
+    }
+
+    pub fn with_kind(mut self, kind: SplineKind) -> Self {
+        self.kind = kind;
+        self
+    }
+
+    /// Returns the number of curve segments between control points
+    pub fn segment_count(&self) -> usize {
+        match (self.kind, self.points.len()) {
+            (_, 0 | 1) => 0,
+            (SplineKind::Bezier, n) if self.closed => n / 3,
+            (SplineKind::Bezier, n) => (n - 1) / 3,
+            (_, n) if self.closed => n,
+            (_, n) => n - 1,
+        }
+    }
+
+    fn point(&self, index: isize) -> Vec3 {
+        let n = self.points.len() as isize;
+        if self.closed {
+            self.points[index.rem_euclid(n) as usize]
+        } else {
+            self.points[index.clamp(0, n - 1) as usize]
+        }
+    }
+
+    fn segment(&self, t: f32) -> (isize, f32) {
+        let count = self.segment_count();
+        let t = t.clamp(0.0, count as f32);
+        let index = (t.floor() as usize).min(count - 1);
+        (index as isize, t - index as f32)
+    }
+
+    /// Returns the control points of a segment in bezier form
+    fn control_points(&self, i: isize) -> [Vec3; 4] {
+        match self.kind {
+            SplineKind::CatmullRom => {
+                let [p0, p1, p2, p3] = [i - 1, i, i + 1, i + 2].map(|v| self.point(v));
+                [p1, p1 + (p2 - p0) / 6.0, p2 - (p3 - p1) / 6.0, p2]
+            }
+            SplineKind::Bezier => [3 * i, 3 * i + 1, 3 * i + 2, 3 * i + 3].map(|v| self.point(v)),
+            SplineKind::Linear => {
+                let (a, b) = (self.point(i), self.point(i + 1));
+                [a, a.lerp(b, 1.0 / 3.0), a.lerp(b, 2.0 / 3.0), b]
+            }
+        }
+    }
+
+    /// Samples the position at `t`, where each whole number is the start of a segment.
+    ///
+    /// Panics if the spline has no segments.
+    pub fn sample(&self, t: f32) -> Vec3 {
+        let (i, t) = self.segment(t);
+        let [p0, p1, p2, p3] = self.control_points(i);
+
+        let s = 1.0 - t;
+        p0 * (s * s * s) + p1 * (3.0 * s * s * t) + p2 * (3.0 * s * t * t) + p3 * (t * t * t)
+    }
+
+    /// Returns the derivative of the curve at `t`
+    pub fn derivative(&self, t: f32) -> Vec3 {
+        let (i, t) = self.segment(t);
+        let [p0, p1, p2, p3] = self.control_points(i);
+
+        let s = 1.0 - t;
+        3.0 * (s * s * (p1 - p0) + 2.0 * s * t * (p2 - p1) + t * t * (p3 - p2))
+    }
+
+    /// Returns the cumulative arc length at evenly spaced parameters along the curve
+    fn arc_length_table(&self) -> Vec<(f32, f32)> {
+        let steps = self.segment_count() * ARC_LENGTH_STEPS;
+
+        let mut distance = 0.0;
+        let mut prev = self.sample(0.0);
+        let mut table = vec![(0.0, 0.0)];
+
+        for i in 1..=steps {
+            let t = i as f32 / ARC_LENGTH_STEPS as f32;
+            let p = self.sample(t);
+            distance += p.distance(prev);
+            prev = p;
+            table.push((t, distance));
+        }
+
+        table
+    }
+
+    /// Approximate length of the curve
+    pub fn length(&self) -> f32 {
+        if self.segment_count() == 0 {
+            return 0.0;
+        }
+
+        self.arc_length_table().last().unwrap().1
+    }
+
+    /// Returns a table for moving along the curve at a constant speed
+    pub fn arc_lengths(&self) -> ArcLengths {
+        if self.segment_count() == 0 {
+            return ArcLengths::default();
+        }
+
+        ArcLengths {
+            table: self.arc_length_table(),
+        }
+    }
+
+    /// Places frames along the curve spaced approximately `spacing` apart.
+    ///
+    /// The frames are oriented around `up`, such that extruded roads stay level.
+    pub fn frames(&self, spacing: f32, up: Vec3) -> Vec<SplineFrame> {
+        if self.segment_count() == 0 {
+            return Vec::new();
+        }
+
+        let table = self.arc_length_table();
+        let length = table.last().unwrap().1;
+        let count = ((length / spacing.max(1e-3)).ceil() as usize).max(1);
+
+        let mut right = Vec3::X;
+        let mut cursor = 0;
+
+        (0..=count)
+            .map(|i| {
+                let distance = length * i as f32 / count as f32;
+
+                while cursor + 2 < table.len() && table[cursor + 1].1 < distance {
+                    cursor += 1;
+                }
+
+                let (t0, d0) = table[cursor];
+                let (t1, d1) = table[cursor + 1];
+                let t = t0 + (t1 - t0) * ((distance - d0) / (d1 - d0).max(1e-6)).clamp(0.0, 1.0);
+
+                let tangent = self.derivative(t).normalize_or(Vec3::Z);
+                // Keep the previous orientation when the curve is parallel to `up`
+                right = tangent.cross(up).try_normalize().unwrap_or(right);
+
+                SplineFrame {
+                    position: self.sample(t),
+                    tangent,
+                    right,
+                    up: right.cross(tangent),
+                    distance,
+                }
+            })
+            .collect()
+    }
+}
+
+#[cfg(feature = "serde")]
+mod asset {
+    use std::path::Path;
+
+    use anyhow::Context;
+    use ivy_assets::{
+        fs::{AssetFromPath, AsyncAssetFromPath},
+        service::FileSystemMapService,
+        Asset, AssetCache,
+    };
+
+    use super::Spline;
+
+    impl AssetFromPath for Spline {
+        type Error = anyhow::Error;
+
+        fn load_from_path(path: &Path, assets: &AssetCache) -> anyhow::Result<Asset<Self>> {
+            let source = assets.service::<FileSystemMapService>().load_string(path)?;
+            let spline = ron::from_str(&source)
+                .with_context(|| format!("Failed to parse spline {path:?}"))?;
+
+            Ok(assets.insert(spline))
+        }
+    }
+
+    impl AsyncAssetFromPath for Spline {
+        type Error = anyhow::Error;
+
+        async fn load_from_path(path: &Path, assets: &AssetCache) -> anyhow::Result<Asset<Self>> {
+            let source = assets
+                .service::<FileSystemMapService>()
+                .load_string_async(path)
+                .await?;
+            let spline = ron::from_str(&source)
+                .with_context(|| format!("Failed to parse spline {path:?}"))?;
+
+            Ok(assets.insert(spline))
+        }
+    }
+}
+
+/// Maps distances along a [`Spline`] to curve parameters
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ArcLengths {
+    table: Vec<(f32, f32)>,
+}
+
+impl ArcLengths {
+    pub fn length(&self) -> f32 {
+        self.table.last().map(|v| v.1).unwrap_or_default()
+    }
+
+    /// Returns the curve parameter at `distance` from the start
+    pub fn parameter(&self, distance: f32) -> f32 {
+        if self.table.len() < 2 {
+            return 0.0;
+        }
+
+        let i = self
+            .table
+            .partition_point(|v| v.1 < distance)
+            .clamp(1, self.table.len() - 1);
+
+        let (t0, d0) = self.table[i - 1];
+        let (t1, d1) = self.table[i];
+        t0 + (t1 - t0) * ((distance - d0) / (d1 - d0).max(1e-6)).clamp(0.0, 1.0)
+    }
+}
+
+/// A point along a spline
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SplineFrame {
+    pub position: Vec3,
+    pub tangent: Vec3,
+    pub right: Vec3,
+    pub up: Vec3,
+    /// Distance from the start of the spline
+    pub distance: f32,
+}
+
+impl SplineFrame {
+    /// Transforms a point in the cross-section plane into the frame
+    pub fn transform_point(&self, point: Vec2) -> Vec3 {
+        self.position + self.right * point.x + self.up * point.y
+    }
+
+    pub fn transform_vector(&self, vector: Vec2) -> Vec3 {
+        self.right * vector.x + self.up * vector.y
+    }
+}
+
+/// A cross-section swept along a spline.
+///
+/// Points are given with `x` to the right of the spline and `y` upwards. Each edge faces to the
+/// left of its direction, i.e; a clockwise outline faces outwards.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SplineProfile {
+    pub points: Vec<Vec2>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub closed: bool,
+}
+
+impl SplineProfile {
+    pub fn new(points: impl IntoIterator<Item = Vec2>) -> Self {
+        Self {
+            points: points.into_iter().collect(),
+            closed: false,
+        }
+    }
+
+    /// Set whether the last point connects back to the first
+    pub fn with_closed(mut self, closed: bool) -> Self {
+        self.closed = closed;
+        self
+    }
+
+    /// A flat upwards facing strip, such as a river surface
+    pub fn flat(width: f32) -> Self {
+        Self::new([vec2(-width * 0.5, 0.0), vec2(width * 0.5, 0.0)])
+    }
+
+    /// A road surface with sides and an underside, with the top at the spline
+    pub fn road(width: f32, thickness: f32) -> Self {
+        let x = width * 0.5;
+        Self::new([
+            vec2(-x, -thickness),
+            vec2(-x, 0.0),
+            vec2(x, 0.0),
+            vec2(x, -thickness),
+        ])
+        .with_closed(true)
+    }
+
+    /// A wall standing on the spline, such as a fence
+    pub fn wall(height: f32, thickness: f32) -> Self {
+        let x = thickness * 0.5;
+        Self::new([
+            vec2(-x, 0.0),
+            vec2(-x, height),
+            vec2(x, height),
+            vec2(x, 0.0),
+        ])
+    }
+
+    fn edges(&self) -> impl Iterator<Item = (Vec2, Vec2)> + '_ {
+        let wrap = self.closed && self.points.len() > 2;
+        self.points
+            .windows(2)
+            .map(|v| (v[0], v[1]))
+            .chain(wrap.then(|| (*self.points.last().unwrap(), self.points[0])))
+    }
+
+    /// Total length of the outline
+    pub fn perimeter(&self) -> f32 {
+        self.edges().map(|(a, b)| a.distance(b)).sum()
+    }
+}
+
+/// Options for [`extrude`]
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ExtrudeOptions {
+    /// Approximate distance between each ring of vertices
+    pub spacing: f32,
+    /// Length along the spline covered by one repetition of the texture
+    pub texture_length: f32,
+    pub up: Vec3,
+}
+
+impl Default for ExtrudeOptions {
+    fn default() -> Self {
+        Self {
+            spacing: 1.0,
+            texture_length: 1.0,
+            up: Vec3::Y,
+        }
+    }
+}
+
+/// Sweeps a profile along the spline.
+///
+/// Each profile edge gets its own vertices so that corners, such as the edge of a road, are
+/// shaded flat. The `u` texture coordinate runs across the profile from 0 to 1, and `v` runs along
+/// the spline.
+pub fn extrude(spline: &Spline, profile: &SplineProfile, options: &ExtrudeOptions) -> MeshData {
+    let frames = spline.frames(options.spacing, options.up);
+
+    let perimeter = profile.perimeter().max(1e-6);
+
+    let mut positions = Vec::new();
+    let mut normals = Vec::new();
+    let mut tex_coords = Vec::new();
+    let mut indices = Vec::new();
+
+    let mut u = 0.0;
+    for (a, b) in profile.edges() {
+        let dir = b - a;
+        let normal = vec2(-dir.y, dir.x).normalize_or_zero();
+        let (u0, u1) = (u / perimeter, (u + dir.length()) / perimeter);
+        u += dir.length();
+
+        for (i, frame) in frames.iter().enumerate() {
+            let base = positions.len() as u32;
+            let v = frame.distance / options.texture_length;
+            let normal = frame.transform_vector(normal);
+
+            positions.extend([frame.transform_point(a), frame.transform_point(b)]);
+            normals.extend([normal, normal]);
+            tex_coords.extend([vec2(u0, v), vec2(u1, v)]);
+
+            if i + 1 < frames.len() {
+                indices.extend([base, base + 1, base + 2, base + 1, base + 3, base + 2]);
+            }
+        }
+    }
+
+    let mut mesh = MeshData::unskinned(indices, positions, tex_coords, normals);
+    if let Err(err) = mesh.generate_tangents() {
+        tracing::warn!("Failed to generate tangents for extruded mesh: {err:?}");
+    }
+
+    mesh
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::vec3;
+
+    use super::*;
+    use crate::mesh::{NORMAL_ATTRIBUTE, POSITION_ATTRIBUTE};
+
+    #[test]
+    fn passes_through_points() {
+        let spline = Spline::new([Vec3::ZERO, vec3(1.0, 0.0, 2.0), vec3(3.0, 1.0, 2.0)]);
+
+        for (i, &p) in spline.points.iter().enumerate() {
+            assert!(spline.sample(i as f32).distance(p) < 1e-4);
+        }
+    }
+
+    #[test]
+    fn evenly_spaced_frames() {
+        let spline = Spline::new([Vec3::ZERO, vec3(0.0, 0.0, 4.0), vec3(0.0, 0.0, 10.0)]);
+        let frames = spline.frames(1.0, Vec3::Y);
+
+        assert_eq!(frames.len(), 11);
+        assert!((spline.length() - 10.0).abs() < 1e-3);
+
+        for (a, b) in frames.iter().zip(&frames[1..]) {
+            assert!((a.position.distance(b.position) - 1.0).abs() < 0.05);
+        }
+    }
+
+    #[test]
+    fn bezier_segments() {
+        let spline = Spline::bezier([
+            Vec3::ZERO,
+            vec3(0.0, 0.0, 1.0),
+            vec3(1.0, 0.0, 2.0),
+            vec3(2.0, 0.0, 2.0),
+            vec3(3.0, 0.0, 2.0),
+            vec3(4.0, 0.0, 1.0),
+            vec3(4.0, 0.0, 0.0),
+        ]);
+
+        assert_eq!(spline.segment_count(), 2);
+        assert!(spline.sample(0.0).distance(Vec3::ZERO) < 1e-4);
+        assert!(spline.sample(1.0).distance(vec3(2.0, 0.0, 2.0)) < 1e-4);
+        assert!(spline.sample(2.0).distance(vec3(4.0, 0.0, 0.0)) < 1e-4);
+        // The curve leaves the point towards the first handle
+        assert!(spline.derivative(0.0).normalize().distance(Vec3::Z) < 1e-4);
+    }
+
+    #[test]
+    fn constant_speed() {
+        let spline = Spline::linear([Vec3::ZERO, vec3(0.0, 0.0, 1.0), vec3(0.0, 0.0, 4.0)]);
+        let lengths = spline.arc_lengths();
+
+        assert!((lengths.length() - 4.0).abs() < 1e-4);
+        assert!(
+            spline
+                .sample(lengths.parameter(2.5))
+                .distance(vec3(0.0, 0.0, 2.5))
+                < 1e-3
+        );
+        assert!(
+            spline
+                .sample(lengths.parameter(10.0))
+                .distance(vec3(0.0, 0.0, 4.0))
+                < 1e-4
+        );
+    }
+
+    #[test]
+    fn extruded_faces_outward() {
+        let spline = Spline::new([Vec3::ZERO, vec3(0.0, 0.0, 5.0), vec3(5.0, 0.0, 10.0)]);
+        let mesh = extrude(
+            &spline,
+            &SplineProfile::flat(2.0),
+            &ExtrudeOptions::default(),
+        );
+
+        let positions = mesh.get_attribute(POSITION_ATTRIBUTE).unwrap();
+        let positions = positions.as_vec3().unwrap();
+        let normals = mesh.get_attribute(NORMAL_ATTRIBUTE).unwrap();
+
+        assert!(normals
+            .as_vec3()
+            .unwrap()
+            .iter()
+            .all(|n| n.distance(Vec3::Y) < 1e-4));
+
+        for tri in mesh.indices().chunks(3) {
+            let [a, b, c] = [0, 1, 2].map(|i| positions[tri[i] as usize]);
+            assert!((b - a).cross(c - a).dot(Vec3::Y) > 0.0);
+        }
+    }
+}
@@ -0,0 +1,188 @@
+use std::sync::Arc;
+
+use flax::{entity_ids, BoxedSystem, FetchExt, Query, System, World};
+use glam::{vec3, Vec3};
+use ivy_core::components::position;
+
+use crate::{
+    components::{desired_velocity, nav_agent, nav_target, navmesh},
+    NavMesh,
+};
+
+/// Follows a path on the [`NavMesh`] towards the agent's [`nav_target`], and outputs the
+/// [`desired_velocity`].
+///
+/// The path is found again when the target or the navigation mesh changes.
+#[derive(Debug, Clone, PartialEq)]
+pub struct NavAgent {
+    pub speed: f32,
+    /// Distance at which a corner of the path, or the target, is considered reached
+    pub arrival_distance: f32,
+    /// Distance from the target at which the agent starts slowing down
+    pub slowing_distance: f32,
+    /// Distance to keep from other agents. Zero disables separation
+    pub separation_radius: f32,
+    pub separation_weight: f32,
+    path: Vec<Vec3>,
+    corner: usize,
+    destination: Option<Vec3>,
+    arrived: bool,
+}
+
+impl NavAgent {
+    pub fn new(speed: f32) -> Self {
+        Self {
+            speed,
+            ..Default::default()
+        }
+    }
+
+    /// Set the distances at which the agent stops and starts slowing down
+    pub fn with_arrival(mut self, arrival_distance: f32, slowing_distance: f32) -> Self {
+        self.arrival_distance = arrival_distance;
+        self.slowing_distance = slowing_distance;
+        self
+    }
+
+    pub fn with_separation(mut self, radius: f32, weight: f32) -> Self {
+        self.separation_radius = radius;
+        self.separation_weight = weight;
+        self
+    }
+
+    /// Returns the remaining corners of the current path
+    pub fn path(&self) -> &[Vec3] {
+        self.path.get(self.corner..).unwrap_or_default()
+    }
+
+    pub fn has_arrived(&self) -> bool {
+        self.arrived
+    }
+
+    fn steer(&mut self, navmesh: &NavMesh, position: Vec3, target: Option<Vec3>) -> Vec3 {
+        let Some(target) = target else {
+            self.destination = None;
+            self.path.clear();
+            self.arrived = false;
+            return Vec3::ZERO;
+        };
+
+        if self.destination != Some(target) {
+            self.destination = Some(target);
+            self.arrived = false;
+            // The first point is the start of the path
+            self.corner = 1;
+            self.path = navmesh.find_path(position, target).unwrap_or_else(|| {
+                tracing::debug!(?position, ?target, "no path to target");
+                Vec::new()
+            });
+        }
+
+        while self.corner + 1 < self.path.len()
+            && horizontal(self.path[self.corner] - position).length() < self.arrival_distance
+        {
+            self.corner += 1;
+        }
+
+        let Some(&corner) = self.path.get(self.corner) else {
+            return Vec3::ZERO;
+        };
+
+        let offset = horizontal(corner - position);
+        let distance = offset.length();
+
+        if self.corner + 1 < self.path.len() {
+            return offset.normalize_or_zero() * self.speed;
+        }
+
+        self.arrived = distance <= self.arrival_distance;
+        if self.arrived {
+            return Vec3::ZERO;
+        }
+
+        let speed = self.speed * (distance / self.slowing_distance.max(f32::EPSILON)).min(1.0);
+        offset / distance * speed
+    }
+}
+
+impl Default for NavAgent {
+    fn default() -> Self {
+        Self {
+            speed: 3.5,
+            arrival_distance: 0.3,
+            slowing_distance: 1.0,
+            separation_radius: 0.8,
+            separation_weight: 1.0,
+            path: Vec::new(),
+            corner: 0,
+            destination: None,
+            arrived: false,
+        }
+    }
+}
+
+fn horizontal(v: Vec3) -> Vec3 {
+    vec3(v.x, 0.0, v.z)
+}
+
+pub(crate) fn steering_system() -> BoxedSystem {
+    let mut current: Option<Arc<NavMesh>> = None;
+    let mut neighbors = Vec::new();
+
+    let mut query = Query::new((
+        entity_ids(),
+        nav_agent().as_mut(),
+        nav_target().copied().opt(),
+        position().copied(),
+        desired_velocity().as_mut(),
+    ));
+
+    System::builder()
+        .with_world_mut()
+        .build(move |world: &mut World| {
+            let Some(navmesh) = Query::new(navmesh().cloned()).borrow(world).first() else {
+                return;
+            };
+
+            let changed = !current.as_ref().is_some_and(|v| Arc::ptr_eq(v, &navmesh));
+            current = Some(navmesh.clone());
+
+            neighbors.clear();
+            for (id, agent, target, position, desired_velocity) in &mut query.borrow(world) {
+                if changed {
+                    agent.destination = None;
+                }
+
+                *desired_velocity = agent.steer(&navmesh, position, target);
+                neighbors.push((id, position, agent.separation_radius));
+            }
+
+            // Steer away from the agents within the separation radius
+            for (id, agent, _, position, desired_velocity) in &mut query.borrow(world) {
+                if agent.separation_radius <= 0.0 {
+                    continue;
+                }
+
+                let separation: Vec3 = neighbors
+                    .iter()
+                    .filter(|&&(other, _, _)| other != id)
+                    .map(|&(_, other, radius)| {
+                        let offset = horizontal(position - other);
+                        let distance = offset.length();
+                        let radius = agent.separation_radius.max(radius);
+
+                        if distance < radius && distance > f32::EPSILON {
+                            offset / distance * (1.0 - distance / radius)
+                        } else {
+                            Vec3::ZERO
+                        }
+                    })
+                    .sum();
+
+                *desired_velocity = (*desired_velocity
+                    + separation * agent.separation_weight * agent.speed)
+                    .clamp_length_max(agent.speed);
+            }
+        })
+        .boxed()
+}
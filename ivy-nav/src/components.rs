@@ -0,0 +1,18 @@
+use std::sync::Arc;
+
+use flax::Debuggable;
+use glam::Vec3;
+
+use crate::{NavAgent, NavMesh};
+
+flax::component! {
+    /// The navigation mesh used by the agents
+    pub navmesh: Arc<NavMesh>,
+
+    pub nav_agent: NavAgent,
+    /// The position the agent is moving towards
+    pub nav_target: Vec3 => [ Debuggable ],
+    /// Horizontal velocity the agent wants to move with, to be followed by a character
+    /// controller
+    pub desired_velocity: Vec3 => [ Debuggable ],
+}
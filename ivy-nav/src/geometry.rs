@@ -0,0 +1,117 @@
+use flax::World;
+use glam::{Mat4, Vec3};
+use ivy_core::components::engine;
+use ivy_physics::{
+    components::physics_state,
+    rapier3d::{
+        math::Isometry,
+        parry::shape::{Shape, TypedShape},
+    },
+    state::PhysicsState,
+};
+
+/// Number of subdivisions used when converting curved shapes to triangles
+const SUBDIVISIONS: u32 = 8;
+
+/// Triangle soup of the level geometry, used as input when baking a [`NavMesh`](crate::NavMesh)
+#[derive(Debug, Clone, Default)]
+pub struct NavGeometry {
+    vertices: Vec<Vec3>,
+    triangles: Vec<[u32; 3]>,
+}
+
+impl NavGeometry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Collects the colliders of all fixed rigidbodies, excluding sensors.
+    ///
+    /// Dynamic and kinematic bodies are considered to be obstacles which move around, and are not
+    /// part of the level.
+    pub fn from_physics(state: &PhysicsState) -> Self {
+        let mut geometry = Self::new();
+
+        for (_, collider) in state.colliders() {
+            let is_fixed = collider
+                .parent()
+                .map(|rb| state.rigidbody(rb).is_fixed())
+                .unwrap_or(true);
+
+            if collider.is_sensor() || !is_fixed {
+                continue;
+            }
+
+            geometry.add_shape(collider.position(), collider.shape());
+        }
+
+        geometry
+    }
+
+    /// Collects the level colliders of the physics state on the engine entity
+    pub fn from_world(world: &World) -> anyhow::Result<Self> {
+        Ok(Self::from_physics(&*world.get(engine(), physics_state())?))
+    }
+
+    /// Adds an indexed triangle mesh, such as a render mesh
+    pub fn add_triangles(&mut self, transform: Mat4, vertices: &[Vec3], triangles: &[[u32; 3]]) {
+        let offset = self.vertices.len() as u32;
+        self.vertices
+            .extend(vertices.iter().map(|&v| transform.transform_point3(v)));
+        self.triangles
+            .extend(triangles.iter().map(|t| t.map(|i| i + offset)));
+    }
+
+    pub fn add_shape(&mut self, position: &Isometry<f32>, shape: &dyn Shape) {
+        let (vertices, indices) = match shape.as_typed_shape() {
+            TypedShape::Ball(v) => v.to_trimesh(SUBDIVISIONS, SUBDIVISIONS),
+            TypedShape::Cuboid(v) => v.to_trimesh(),
+            TypedShape::RoundCuboid(v) => v.inner_shape.to_trimesh(),
+            TypedShape::Capsule(v) => v.to_trimesh(SUBDIVISIONS, SUBDIVISIONS),
+            TypedShape::Cylinder(v) => v.to_trimesh(SUBDIVISIONS),
+            TypedShape::RoundCylinder(v) => v.inner_shape.to_trimesh(SUBDIVISIONS),
+            TypedShape::Cone(v) => v.to_trimesh(SUBDIVISIONS),
+            TypedShape::RoundCone(v) => v.inner_shape.to_trimesh(SUBDIVISIONS),
+            TypedShape::ConvexPolyhedron(v) => v.to_trimesh(),
+            TypedShape::RoundConvexPolyhedron(v) => v.inner_shape.to_trimesh(),
+            TypedShape::HeightField(v) => v.to_trimesh(),
+            TypedShape::TriMesh(v) => (v.vertices().to_vec(), v.indices().to_vec()),
+            TypedShape::Triangle(v) => (vec![v.a, v.b, v.c], vec![[0, 1, 2]]),
+            TypedShape::Compound(v) => {
+                for (local, shape) in v.shapes() {
+                    self.add_shape(&(position * local), &**shape);
+                }
+
+                return;
+            }
+            _ => {
+                tracing::warn!(shape = ?shape.shape_type(), "shape is not supported for navigation");
+                return;
+            }
+        };
+
+        let offset = self.vertices.len() as u32;
+        self.vertices
+            .extend(vertices.iter().map(|v| Vec3::from(position * v)));
+        self.triangles
+            .extend(indices.iter().map(|t| t.map(|i| i + offset)));
+    }
+
+    pub fn triangles(&self) -> impl Iterator<Item = [Vec3; 3]> + '_ {
+        self.triangles
+            .iter()
+            .map(|t| t.map(|i| self.vertices[i as usize]))
+    }
+
+    /// Returns the bounding box of the geometry, or `None` if it is empty
+    pub fn bounds(&self) -> Option<(Vec3, Vec3)> {
+        self.triangles().flatten().fold(None, |acc, v| match acc {
+            Some((min, max)) => Some((v.min(min), v.max(max))),
+            None => Some((v, v)),
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.triangles.is_empty()
+    }
+}
@@ -0,0 +1,401 @@
+use std::{collections::VecDeque, ops::Range};
+
+use glam::{vec3, Vec3};
+
+use crate::{NavGeometry, NavMeshSettings};
+
+/// Offsets of the neighbouring columns, in the order -x, -z, +x, +z
+pub(crate) const DIRECTIONS: [(isize, isize); 4] = [(-1, 0), (0, -1), (1, 0), (0, 1)];
+pub(crate) const NEG_X: usize = 0;
+pub(crate) const NEG_Z: usize = 1;
+pub(crate) const POS_X: usize = 2;
+pub(crate) const POS_Z: usize = 3;
+
+/// A solid vertical range of voxels
+#[derive(Debug, Clone, Copy)]
+struct Span {
+    min: i32,
+    max: i32,
+    walkable: bool,
+}
+
+/// Voxelized representation of the geometry, stored as columns of solid spans
+struct Heightfield {
+    origin: Vec3,
+    width: usize,
+    depth: usize,
+    cell_size: f32,
+    cell_height: f32,
+    columns: Vec<Vec<Span>>,
+}
+
+impl Heightfield {
+    fn new(min: Vec3, max: Vec3, cell_size: f32, cell_height: f32) -> Self {
+        let width = ((max.x - min.x) / cell_size).ceil().max(1.0) as usize;
+        let depth = ((max.z - min.z) / cell_size).ceil().max(1.0) as usize;
+
+        Self {
+            origin: min,
+            width,
+            depth,
+            cell_size,
+            cell_height,
+            columns: vec![Vec::new(); width * depth],
+        }
+    }
+
+    /// Adds a span, merging it with the overlapping spans of the column.
+    ///
+    /// The walkable flag of the top-most surface is kept, unless the surfaces are within
+    /// `merge_threshold` of each other.
+    fn add_span(&mut self, x: usize, z: usize, mut span: Span, merge_threshold: i32) {
+        let column = &mut self.columns[x + z * self.width];
+
+        let mut i = 0;
+        while i < column.len() {
+            let cur = column[i];
+            if cur.min > span.max {
+                break;
+            }
+
+            if cur.max < span.min {
+                i += 1;
+                continue;
+            }
+
+            if (cur.max - span.max).abs() <= merge_threshold {
+                span.walkable |= cur.walkable;
+            } else if cur.max > span.max {
+                span.walkable = cur.walkable;
+            }
+
+            span.min = span.min.min(cur.min);
+            span.max = span.max.max(cur.max);
+            column.remove(i);
+        }
+
+        column.insert(i, span);
+    }
+
+    fn rasterize_triangle(&mut self, triangle: [Vec3; 3], walkable: bool, merge_threshold: i32) {
+        let min = triangle[0].min(triangle[1]).min(triangle[2]) - self.origin;
+        let max = triangle[0].max(triangle[1]).max(triangle[2]) - self.origin;
+
+        let cell_size = self.cell_size;
+        let cell = |v: f32, len: usize| ((v / cell_size).floor().max(0.0) as usize).min(len - 1);
+
+        for z in cell(min.z, self.depth)..=cell(max.z, self.depth) {
+            let z_min = self.origin.z + z as f32 * self.cell_size;
+            let row = clip(&triangle, 2, z_min, true);
+            let row = clip(&row, 2, z_min + self.cell_size, false);
+            if row.is_empty() {
+                continue;
+            }
+
+            for x in cell(min.x, self.width)..=cell(max.x, self.width) {
+                let x_min = self.origin.x + x as f32 * self.cell_size;
+                let poly = clip(&row, 0, x_min, true);
+                let poly = clip(&poly, 0, x_min + self.cell_size, false);
+                if poly.is_empty() {
+                    continue;
+                }
+
+                let (y_min, y_max) = poly.iter().fold((f32::MAX, f32::MIN), |(lo, hi), v| {
+                    (lo.min(v.y), hi.max(v.y))
+                });
+
+                let span_min = ((y_min - self.origin.y) / self.cell_height)
+                    .floor()
+                    .max(0.0) as i32;
+                let span_max =
+                    (((y_max - self.origin.y) / self.cell_height).ceil() as i32).max(span_min + 1);
+
+                self.add_span(
+                    x,
+                    z,
+                    Span {
+                        min: span_min,
+                        max: span_max,
+                        walkable,
+                    },
+                    merge_threshold,
+                );
+            }
+        }
+    }
+}
+
+/// Clips a convex polygon against an axis aligned plane, keeping the part above or below
+fn clip(poly: &[Vec3], axis: usize, value: f32, keep_above: bool) -> Vec<Vec3> {
+    let side = |v: Vec3| {
+        if keep_above {
+            v[axis] - value
+        } else {
+            value - v[axis]
+        }
+    };
+
+    let mut result = Vec::with_capacity(poly.len() + 2);
+    for (i, &a) in poly.iter().enumerate() {
+        let b = poly[(i + 1) % poly.len()];
+        let (da, db) = (side(a), side(b));
+
+        if da >= 0.0 {
+            result.push(a);
+        }
+
+        if (da >= 0.0) != (db >= 0.0) {
+            result.push(a + (b - a) * (da / (da - db)));
+        }
+    }
+
+    result
+}
+
+/// A walkable surface and the free space above it
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct OpenSpan {
+    pub x: usize,
+    pub z: usize,
+    pub floor: i32,
+    pub ceiling: i32,
+    /// The connected spans in each of the [`DIRECTIONS`]
+    pub neighbors: [Option<usize>; 4],
+}
+
+/// The walkable surfaces of a heightfield, and how they connect.
+pub(crate) struct OpenHeightfield {
+    pub origin: Vec3,
+    pub width: usize,
+    pub depth: usize,
+    pub cell_size: f32,
+    pub cell_height: f32,
+    /// Range of spans in each column
+    pub columns: Vec<Range<usize>>,
+    pub spans: Vec<OpenSpan>,
+}
+
+impl OpenHeightfield {
+    /// Voxelizes the geometry and extracts the surfaces which the agent can stand on and move
+    /// between
+    pub fn build(geometry: &NavGeometry, settings: &NavMeshSettings) -> Option<Self> {
+        let (min, max) = geometry.bounds()?;
+        let padding = vec3(settings.cell_size, 0.0, settings.cell_size);
+
+        let mut heightfield = Heightfield::new(
+            min - padding,
+            max + padding,
+            settings.cell_size,
+            settings.cell_height,
+        );
+
+        let climb = (settings.max_climb / settings.cell_height).floor() as i32;
+        let height = (settings.agent_height / settings.cell_height).ceil() as i32;
+        let radius = (settings.agent_radius / settings.cell_size).ceil() as u32;
+        let min_normal_y = settings.max_slope.cos();
+
+        for triangle in geometry.triangles() {
+            let Some(normal) = (triangle[1] - triangle[0])
+                .cross(triangle[2] - triangle[0])
+                .try_normalize()
+            else {
+                continue;
+            };
+
+            // The winding of collider meshes is not consistent, so both sides are considered
+            let walkable = normal.y.abs() >= min_normal_y;
+            heightfield.rasterize_triangle(triangle, walkable, climb);
+        }
+
+        let mut result = Self::from_heightfield(&heightfield, height);
+        result.connect(climb, height);
+        result.erode(radius);
+
+        Some(result)
+    }
+
+    fn from_heightfield(heightfield: &Heightfield, height: i32) -> Self {
+        let mut columns = Vec::with_capacity(heightfield.columns.len());
+        let mut spans = Vec::new();
+
+        for z in 0..heightfield.depth {
+            for x in 0..heightfield.width {
+                let column = &heightfield.columns[x + z * heightfield.width];
+                let start = spans.len();
+
+                for (i, span) in column.iter().enumerate() {
+                    let ceiling = column.get(i + 1).map(|v| v.min).unwrap_or(i32::MAX);
+                    if span.walkable && ceiling - span.max >= height {
+                        spans.push(OpenSpan {
+                            x,
+                            z,
+                            floor: span.max,
+                            ceiling,
+                            neighbors: [None; 4],
+                        });
+                    }
+                }
+
+                columns.push(start..spans.len());
+            }
+        }
+
+        Self {
+            origin: heightfield.origin,
+            width: heightfield.width,
+            depth: heightfield.depth,
+            cell_size: heightfield.cell_size,
+            cell_height: heightfield.cell_height,
+            columns,
+            spans,
+        }
+    }
+
+    fn column(&self, x: usize, z: usize, (dx, dz): (isize, isize)) -> Option<Range<usize>> {
+        let x = x.checked_add_signed(dx).filter(|&v| v < self.width)?;
+        let z = z.checked_add_signed(dz).filter(|&v| v < self.depth)?;
+        Some(self.columns[x + z * self.width].clone())
+    }
+
+    /// Connects spans which are close enough in height to step between, and have enough room
+    /// for the agent at the transition
+    fn connect(&mut self, climb: i32, height: i32) {
+        for i in 0..self.spans.len() {
+            let span = self.spans[i];
+
+            for (dir, &offset) in DIRECTIONS.iter().enumerate() {
+                let Some(column) = self.column(span.x, span.z, offset) else {
+                    continue;
+                };
+
+                self.spans[i].neighbors[dir] = column.into_iter().find(|&j| {
+                    let other = &self.spans[j];
+                    (other.floor - span.floor).abs() <= climb
+                        && other.ceiling.min(span.ceiling) - other.floor.max(span.floor) >= height
+                });
+            }
+        }
+    }
+
+    /// Removes the spans closer than `radius` cells to an edge or obstacle, so that the agent
+    /// can stand anywhere on the remaining surface
+    fn erode(&mut self, radius: u32) {
+        if radius == 0 {
+            return;
+        }
+
+        let mut distance = vec![u32::MAX; self.spans.len()];
+        let mut queue = VecDeque::new();
+
+        for (i, span) in self.spans.iter().enumerate() {
+            if span.neighbors.iter().any(|v| v.is_none()) {
+                distance[i] = 0;
+                queue.push_back(i);
+            }
+        }
+
+        while let Some(i) = queue.pop_front() {
+            for j in self.spans[i].neighbors.into_iter().flatten() {
+                if distance[j] == u32::MAX {
+                    distance[j] = distance[i] + 1;
+                    queue.push_back(j);
+                }
+            }
+        }
+
+        self.retain(|i| distance[i] >= radius);
+    }
+
+    fn retain(&mut self, keep: impl Fn(usize) -> bool) {
+        let mut remap = vec![None; self.spans.len()];
+        let mut spans = Vec::with_capacity(self.spans.len());
+
+        for column in &mut self.columns {
+            let start = spans.len();
+            for i in column.clone() {
+                if keep(i) {
+                    remap[i] = Some(spans.len());
+                    spans.push(self.spans[i]);
+                }
+            }
+
+            *column = start..spans.len();
+        }
+
+        for span in &mut spans {
+            for neighbor in &mut span.neighbors {
+                *neighbor = neighbor.and_then(|v| remap[v]);
+            }
+        }
+
+        self.spans = spans;
+    }
+
+    /// World space position of the floor at the corner of a cell
+    pub fn corner(&self, x: usize, z: usize, floor: i32) -> Vec3 {
+        self.origin
+            + vec3(
+                x as f32 * self.cell_size,
+                floor as f32 * self.cell_height,
+                z as f32 * self.cell_size,
+            )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn clip_triangle() {
+        let triangle = [
+            vec3(0.0, 0.0, 0.0),
+            vec3(2.0, 0.0, 0.0),
+            vec3(0.0, 0.0, 2.0),
+        ];
+
+        let clipped = clip(&triangle, 0, 1.0, true);
+        assert_eq!(clipped.len(), 3);
+        assert!(clipped.iter().all(|v| v.x >= 1.0));
+
+        let clipped = clip(&triangle, 0, 1.0, false);
+        assert_eq!(clipped.len(), 4);
+        assert!(clipped.iter().all(|v| v.x <= 1.0));
+
+        assert!(clip(&triangle, 2, 3.0, true).is_empty());
+    }
+
+    #[test]
+    fn merge_spans() {
+        let mut heightfield = Heightfield::new(Vec3::ZERO, Vec3::ONE, 1.0, 0.1);
+        let walkable = |min, max| Span {
+            min,
+            max,
+            walkable: true,
+        };
+
+        heightfield.add_span(0, 0, walkable(0, 2), 1);
+        heightfield.add_span(0, 0, walkable(10, 12), 1);
+        assert_eq!(heightfield.columns[0].len(), 2);
+
+        // A wall covering the floor hides it
+        heightfield.add_span(
+            0,
+            0,
+            Span {
+                min: 0,
+                max: 8,
+                walkable: false,
+            },
+            1,
+        );
+
+        let column = &heightfield.columns[0];
+        assert_eq!(column.len(), 2);
+        assert_eq!((column[0].min, column[0].max), (0, 8));
+        assert!(!column[0].walkable);
+
+        heightfield.add_span(0, 0, walkable(7, 9), 1);
+        assert!(heightfield.columns[0][0].walkable);
+    }
+}
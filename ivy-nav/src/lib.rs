@@ -0,0 +1,28 @@
+//! Navigation meshes and path finding for the Ivy framework.
+//!
+//! A [`NavMesh`] is baked from the level's collision geometry, which is voxelized to find the
+//! surfaces an agent can stand on and move between.
+//!
+//! ```rust,ignore
+//! let geometry = NavGeometry::from_world(world)?;
+//! let navmesh = NavMesh::bake(&geometry, &NavMeshSettings::default().with_agent(1.8, 0.4));
+//!
+//! Entity::builder()
+//!     .set(components::navmesh(), Arc::new(navmesh))
+//!     .spawn(world);
+//! ```
+//!
+//! Entities with a [`NavAgent`](components::nav_agent) and a [`nav_target`](components::nav_target)
+//! are steered along the shortest path by the [`NavPlugin`], which writes the
+//! [`desired_velocity`](components::desired_velocity) for the character controller to follow.
+mod agent;
+pub mod components;
+mod geometry;
+mod heightfield;
+mod navmesh;
+mod plugin;
+
+pub use agent::NavAgent;
+pub use geometry::NavGeometry;
+pub use navmesh::{NavLink, NavMesh, NavMeshSettings, NavPolygon};
+pub use plugin::NavPlugin;
@@ -0,0 +1,612 @@
+use std::{cmp::Ordering, collections::BinaryHeap, ops::Range};
+
+use glam::{vec2, vec3, Vec2, Vec3, Vec3Swizzles};
+
+use crate::{
+    heightfield::{OpenHeightfield, OpenSpan, NEG_X, NEG_Z, POS_X, POS_Z},
+    NavGeometry,
+};
+
+/// Limits the size of the polygons, as the path costs are estimated between polygon edges
+const MAX_POLYGON_CELLS: usize = 32;
+
+/// Describes the agents which will use the navigation mesh, and the resolution of the
+/// voxelization.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct NavMeshSettings {
+    /// Horizontal size of the voxels
+    pub cell_size: f32,
+    /// Vertical size of the voxels
+    pub cell_height: f32,
+    pub agent_height: f32,
+    pub agent_radius: f32,
+    /// Maximum height of a ledge which can be stepped up or down
+    pub max_climb: f32,
+    /// Maximum walkable slope in radians
+    pub max_slope: f32,
+}
+
+impl NavMeshSettings {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the size of the voxels
+    pub fn with_cell_size(mut self, cell_size: f32, cell_height: f32) -> Self {
+        self.cell_size = cell_size;
+        self.cell_height = cell_height;
+        self
+    }
+
+    /// Set the dimensions of the agent
+    pub fn with_agent(mut self, height: f32, radius: f32) -> Self {
+        self.agent_height = height;
+        self.agent_radius = radius;
+        self
+    }
+
+    pub fn with_max_climb(mut self, max_climb: f32) -> Self {
+        self.max_climb = max_climb;
+        self
+    }
+
+    pub fn with_max_slope(mut self, max_slope: f32) -> Self {
+        self.max_slope = max_slope;
+        self
+    }
+}
+
+impl Default for NavMeshSettings {
+    fn default() -> Self {
+        Self {
+            cell_size: 0.25,
+            cell_height: 0.1,
+            agent_height: 1.8,
+            agent_radius: 0.4,
+            max_climb: 0.4,
+            max_slope: 45f32.to_radians(),
+        }
+    }
+}
+
+/// Connection to a neighbouring polygon through a shared edge.
+///
+/// `left` and `right` are as seen when leaving the polygon through the edge.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct NavLink {
+    pub polygon: usize,
+    pub left: Vec3,
+    pub right: Vec3,
+}
+
+impl NavLink {
+    pub fn midpoint(&self) -> Vec3 {
+        (self.left + self.right) * 0.5
+    }
+}
+
+/// Axis aligned walkable area of the [`NavMesh`], which may be sloped
+#[derive(Debug, Clone, PartialEq)]
+pub struct NavPolygon {
+    /// Minimum corner on the xz plane
+    pub min: Vec2,
+    /// Maximum corner on the xz plane
+    pub max: Vec2,
+    /// Height of the corners at `(min.x, min.y)`, `(max.x, min.y)`, `(min.x, max.y)` and
+    /// `(max.x, max.y)`
+    pub heights: [f32; 4],
+    pub links: Vec<NavLink>,
+}
+
+impl NavPolygon {
+    /// Returns the interpolated height of the surface
+    pub fn height_at(&self, point: Vec2) -> f32 {
+        let t = ((point - self.min) / (self.max - self.min)).clamp(Vec2::ZERO, Vec2::ONE);
+        let [a, b, c, d] = self.heights;
+
+        let near = a + (b - a) * t.x;
+        let far = c + (d - c) * t.x;
+        near + (far - near) * t.y
+    }
+
+    /// Returns the closest point on the polygon's surface
+    pub fn closest_point(&self, point: Vec3) -> Vec3 {
+        let p = point.xz().clamp(self.min, self.max);
+        vec3(p.x, self.height_at(p), p.y)
+    }
+
+    pub fn center(&self) -> Vec3 {
+        let center = (self.min + self.max) * 0.5;
+        vec3(center.x, self.height_at(center), center.y)
+    }
+
+    pub fn contains(&self, point: Vec2) -> bool {
+        point.cmpge(self.min).all() && point.cmple(self.max).all()
+    }
+
+    /// Returns the corners in counter-clockwise order as seen from above
+    pub fn vertices(&self) -> [Vec3; 4] {
+        let [a, b, c, d] = self.heights;
+        [
+            vec3(self.min.x, a, self.min.y),
+            vec3(self.min.x, c, self.max.y),
+            vec3(self.max.x, d, self.max.y),
+            vec3(self.max.x, b, self.min.y),
+        ]
+    }
+}
+
+/// Navigation mesh of the walkable surfaces of a level, used to find paths for agents.
+///
+/// The mesh is baked by voxelizing the level geometry, keeping the surfaces with enough clearance
+/// for the agent, and shrinking them by the agent's radius. The remaining surface is merged into
+/// rectangular polygons, which are connected by their shared edges.
+#[derive(Debug, Clone, Default)]
+pub struct NavMesh {
+    polygons: Vec<NavPolygon>,
+}
+
+impl NavMesh {
+    pub fn bake(geometry: &NavGeometry, settings: &NavMeshSettings) -> Self {
+        let Some(heightfield) = OpenHeightfield::build(geometry, settings) else {
+            return Self::default();
+        };
+
+        let climb = (settings.max_climb / settings.cell_height).floor() as i32;
+
+        let mut owner = vec![None; heightfield.spans.len()];
+        let mut rects = Vec::new();
+
+        for start in 0..heightfield.spans.len() {
+            if owner[start].is_some() {
+                continue;
+            }
+
+            let base = heightfield.spans[start].floor;
+            let fits = |owner: &[Option<usize>], i: usize| {
+                owner[i].is_none() && (heightfield.spans[i].floor - base).abs() <= climb
+            };
+
+            let mut row = vec![start];
+            while let Some(next) = heightfield.spans[*row.last().unwrap()].neighbors[POS_X] {
+                if row.len() >= MAX_POLYGON_CELLS || !fits(&owner, next) {
+                    break;
+                }
+
+                row.push(next);
+            }
+
+            let width = row.len();
+            let mut cells = row.clone();
+
+            while cells.len() / width < MAX_POLYGON_CELLS {
+                let Some(next) = row
+                    .iter()
+                    .map(|&i| heightfield.spans[i].neighbors[POS_Z].filter(|&v| fits(&owner, v)))
+                    .collect::<Option<Vec<_>>>()
+                else {
+                    break;
+                };
+
+                let connected = next
+                    .windows(2)
+                    .all(|v| heightfield.spans[v[0]].neighbors[POS_X] == Some(v[1]));
+
+                if !connected {
+                    break;
+                }
+
+                cells.extend_from_slice(&next);
+                row = next;
+            }
+
+            for &cell in &cells {
+                owner[cell] = Some(rects.len());
+            }
+
+            rects.push((width, cells));
+        }
+
+        let mut polygons: Vec<_> = rects
+            .iter()
+            .map(|(width, cells)| {
+                let corner = |i: usize, dx: usize, dz: usize| {
+                    let span = &heightfield.spans[cells[i]];
+                    heightfield.corner(span.x + dx, span.z + dz, span.floor)
+                };
+
+                let min = corner(0, 0, 0);
+                let max = corner(cells.len() - 1, 1, 1);
+
+                NavPolygon {
+                    min: min.xz(),
+                    max: max.xz(),
+                    heights: [
+                        min.y,
+                        corner(width - 1, 1, 0).y,
+                        corner(cells.len() - width, 0, 1).y,
+                        max.y,
+                    ],
+                    links: Vec::new(),
+                }
+            })
+            .collect();
+
+        for (index, (width, cells)) in rects.iter().enumerate() {
+            let width = *width;
+            let depth = cells.len() / width;
+
+            let sides = [
+                (
+                    NEG_X,
+                    (0..depth).map(|z| cells[z * width]).collect::<Vec<_>>(),
+                ),
+                (
+                    POS_X,
+                    (0..depth).map(|z| cells[z * width + width - 1]).collect(),
+                ),
+                (NEG_Z, cells[..width].to_vec()),
+                (POS_Z, cells[cells.len() - width..].to_vec()),
+            ];
+
+            let mut links = Vec::new();
+            for (dir, side) in sides {
+                let neighbors: Vec<_> = side
+                    .iter()
+                    .map(|&i| heightfield.spans[i].neighbors[dir].and_then(|v| owner[v]))
+                    .collect();
+
+                let mut start = 0;
+                while start < neighbors.len() {
+                    let end = (start..neighbors.len())
+                        .find(|&i| neighbors[i] != neighbors[start])
+                        .unwrap_or(neighbors.len());
+
+                    if let Some(other) = neighbors[start] {
+                        links.push(Self::link(
+                            &polygons[index],
+                            dir,
+                            &side,
+                            start..end,
+                            other,
+                            &heightfield,
+                        ));
+                    }
+
+                    start = end;
+                }
+            }
+
+            polygons[index].links = links;
+        }
+
+        Self { polygons }
+    }
+
+    /// Creates a link along the edge of the cells `range` of a polygon's side
+    fn link(
+        polygon: &NavPolygon,
+        dir: usize,
+        side: &[usize],
+        range: Range<usize>,
+        other: usize,
+        heightfield: &OpenHeightfield,
+    ) -> NavLink {
+        let first = &heightfield.spans[side[range.start]];
+        let last = &heightfield.spans[side[range.end - 1]];
+
+        let (a, b, normal) = match dir {
+            NEG_X => (vec2(polygon.min.x, 0.0), vec2(0.0, 1.0), vec2(-1.0, 0.0)),
+            POS_X => (vec2(polygon.max.x, 0.0), vec2(0.0, 1.0), vec2(1.0, 0.0)),
+            NEG_Z => (vec2(0.0, polygon.min.y), vec2(1.0, 0.0), vec2(0.0, -1.0)),
+            _ => (vec2(0.0, polygon.max.y), vec2(1.0, 0.0), vec2(0.0, 1.0)),
+        };
+
+        // Position along the edge, in cells
+        let along = |span: &OpenSpan| {
+            if b.x > 0.0 {
+                span.x as f32
+            } else {
+                span.z as f32
+            }
+        };
+
+        let offset = if b.x > 0.0 {
+            heightfield.origin.x
+        } else {
+            heightfield.origin.z
+        };
+
+        let point = |t: f32| {
+            let p = a + b * (offset + t * heightfield.cell_size);
+            vec3(p.x, polygon.height_at(p), p.y)
+        };
+
+        let p0 = point(along(first));
+        let p1 = point(along(last) + 1.0);
+
+        // The edge runs along `b`, so `p1` is to the left when it is counter-clockwise from the
+        // outwards normal
+        let (left, right) = if normal.perp_dot(b) > 0.0 {
+            (p1, p0)
+        } else {
+            (p0, p1)
+        };
+
+        NavLink {
+            polygon: other,
+            left,
+            right,
+        }
+    }
+
+    pub fn polygons(&self) -> &[NavPolygon] {
+        &self.polygons
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.polygons.is_empty()
+    }
+
+    /// Returns the polygon closest to `point`
+    pub fn find_polygon(&self, point: Vec3) -> Option<usize> {
+        self.polygons
+            .iter()
+            .map(|v| v.closest_point(point).distance_squared(point))
+            .enumerate()
+            .min_by(|a, b| a.1.total_cmp(&b.1))
+            .map(|(i, _)| i)
+    }
+
+    /// Returns the closest point on the navigation mesh
+    pub fn closest_point(&self, point: Vec3) -> Option<Vec3> {
+        let polygon = self.find_polygon(point)?;
+        Some(self.polygons[polygon].closest_point(point))
+    }
+
+    /// Finds the shortest path between two points, including the start and end.
+    ///
+    /// The points are moved to the closest point on the mesh. Returns `None` if the points are
+    /// not connected.
+    pub fn find_path(&self, start: Vec3, end: Vec3) -> Option<Vec<Vec3>> {
+        let start_polygon = self.find_polygon(start)?;
+        let end_polygon = self.find_polygon(end)?;
+
+        let start = self.polygons[start_polygon].closest_point(start);
+        let end = self.polygons[end_polygon].closest_point(end);
+
+        let portals = self.find_corridor(start_polygon, end_polygon, start, end)?;
+        Some(string_pull(start, end, &portals))
+    }
+
+    /// Finds the sequence of polygons between two points using A*, and returns the portals
+    /// between them
+    fn find_corridor(
+        &self,
+        start_polygon: usize,
+        end_polygon: usize,
+        start: Vec3,
+        end: Vec3,
+    ) -> Option<Vec<(Vec3, Vec3)>> {
+        let count = self.polygons.len();
+        let mut cost = vec![f32::INFINITY; count];
+        let mut entry = vec![Vec3::ZERO; count];
+        let mut came_from: Vec<Option<(usize, usize)>> = vec![None; count];
+        let mut closed = vec![false; count];
+        let mut open = BinaryHeap::new();
+
+        cost[start_polygon] = 0.0;
+        entry[start_polygon] = start;
+        open.push(Candidate {
+            estimate: start.distance(end),
+            polygon: start_polygon,
+        });
+
+        while let Some(Candidate { polygon, .. }) = open.pop() {
+            if polygon == end_polygon {
+                break;
+            }
+
+            if std::mem::replace(&mut closed[polygon], true) {
+                continue;
+            }
+
+            for (i, link) in self.polygons[polygon].links.iter().enumerate() {
+                let point = link.midpoint();
+                let g = cost[polygon] + entry[polygon].distance(point);
+
+                if g < cost[link.polygon] {
+                    cost[link.polygon] = g;
+                    entry[link.polygon] = point;
+                    came_from[link.polygon] = Some((polygon, i));
+                    open.push(Candidate {
+                        estimate: g + point.distance(end),
+                        polygon: link.polygon,
+                    });
+                }
+            }
+        }
+
+        if start_polygon != end_polygon && came_from[end_polygon].is_none() {
+            return None;
+        }
+
+        let mut portals = Vec::new();
+        let mut current = end_polygon;
+        while let Some((prev, link)) = came_from[current] {
+            let link = &self.polygons[prev].links[link];
+            portals.push((link.left, link.right));
+            current = prev;
+        }
+
+        portals.reverse();
+        Some(portals)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Candidate {
+    estimate: f32,
+    polygon: usize,
+}
+
+impl PartialEq for Candidate {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Candidate {}
+
+impl PartialOrd for Candidate {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Candidate {
+    // Reversed to pop the lowest estimate from the max-heap
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.estimate.total_cmp(&self.estimate)
+    }
+}
+
+/// Twice the signed area of the triangle on the xz plane. Positive if `c` is to the left of
+/// `a -> b`
+fn area(a: Vec3, b: Vec3, c: Vec3) -> f32 {
+    (b - a).xz().perp_dot((c - a).xz())
+}
+
+fn same_point(a: Vec3, b: Vec3) -> bool {
+    a.distance_squared(b) < 1e-6
+}
+
+/// Finds the shortest path through the portals using the simple stupid funnel algorithm
+fn string_pull(start: Vec3, end: Vec3, portals: &[(Vec3, Vec3)]) -> Vec<Vec3> {
+    let portals: Vec<_> = std::iter::once((start, start))
+        .chain(portals.iter().copied())
+        .chain(std::iter::once((end, end)))
+        .collect();
+
+    let mut path = vec![start];
+
+    let mut apex = start;
+    let (mut left, mut right) = (start, start);
+    let (mut apex_index, mut left_index, mut right_index) = (0, 0, 0);
+
+    let mut i = 1;
+    while i < portals.len() {
+        let (new_left, new_right) = portals[i];
+
+        // Tighten the right side of the funnel
+        if area(apex, right, new_right) >= 0.0 {
+            if same_point(apex, right) || area(apex, left, new_right) < 0.0 {
+                right = new_right;
+                right_index = i;
+            } else {
+                // The right side crossed the left, which becomes a corner of the path
+                path.push(left);
+                apex = left;
+                apex_index = left_index;
+                (left, right) = (apex, apex);
+                (left_index, right_index) = (apex_index, apex_index);
+                i = apex_index + 1;
+                continue;
+            }
+        }
+
+        // Tighten the left side of the funnel
+        if area(apex, left, new_left) <= 0.0 {
+            if same_point(apex, left) || area(apex, right, new_left) > 0.0 {
+                left = new_left;
+                left_index = i;
+            } else {
+                path.push(right);
+                apex = right;
+                apex_index = right_index;
+                (left, right) = (apex, apex);
+                (left_index, right_index) = (apex_index, apex_index);
+                i = apex_index + 1;
+                continue;
+            }
+        }
+
+        i += 1;
+    }
+
+    if !same_point(*path.last().unwrap(), end) {
+        path.push(end);
+    }
+
+    path
+}
+
+#[cfg(test)]
+mod test {
+    use ivy_physics::rapier3d::{math::Isometry, parry::shape::Cuboid, prelude::vector};
+
+    use super::*;
+
+    fn floor() -> NavGeometry {
+        let mut geometry = NavGeometry::new();
+        geometry.add_shape(
+            &Isometry::translation(0.0, -0.5, 0.0),
+            &Cuboid::new(vector![5.0, 0.5, 5.0]),
+        );
+
+        geometry
+    }
+
+    #[test]
+    fn straight_path() {
+        let navmesh = NavMesh::bake(&floor(), &NavMeshSettings::default());
+        assert!(!navmesh.is_empty());
+
+        let path = navmesh
+            .find_path(vec3(-3.0, 0.0, -3.0), vec3(3.0, 0.0, 3.0))
+            .unwrap();
+
+        assert_eq!(path.len(), 2);
+        assert!(path[0].xz().distance(vec2(-3.0, -3.0)) < 1e-3);
+        assert!(path[1].xz().distance(vec2(3.0, 3.0)) < 1e-3);
+    }
+
+    #[test]
+    fn path_around_wall() {
+        let mut geometry = floor();
+        // Wall from x = -5 to 2
+        geometry.add_shape(
+            &Isometry::translation(-1.5, 1.0, 0.0),
+            &Cuboid::new(vector![3.5, 1.0, 0.5]),
+        );
+
+        let settings = NavMeshSettings::default();
+        let navmesh = NavMesh::bake(&geometry, &settings);
+
+        let path = navmesh
+            .find_path(vec3(-3.0, 0.0, -3.0), vec3(-3.0, 0.0, 3.0))
+            .unwrap();
+
+        assert!(path.len() > 2);
+        // The path goes around the end of the wall, keeping the agent's distance
+        assert!(path
+            .iter()
+            .any(|v| v.x >= 2.0 + settings.agent_radius - 1e-3));
+        assert!(path.iter().all(|v| v.x < 3.5));
+    }
+
+    #[test]
+    fn unreachable() {
+        let mut geometry = floor();
+        // Wall splitting the floor in two
+        geometry.add_shape(
+            &Isometry::translation(0.0, 1.0, 0.0),
+            &Cuboid::new(vector![5.0, 1.0, 0.5]),
+        );
+
+        let navmesh = NavMesh::bake(&geometry, &NavMeshSettings::default());
+        assert!(navmesh
+            .find_path(vec3(0.0, 0.0, -3.0), vec3(0.0, 0.0, 3.0))
+            .is_none());
+    }
+}
@@ -0,0 +1,91 @@
+use flax::{BoxedSystem, Query, System, World};
+use glam::Vec3;
+use ivy_assets::AssetCache;
+use ivy_core::{
+    components::{engine, gizmos, position},
+    gizmos::Line,
+    update_layer::{Plugin, ScheduleSetBuilder},
+    Color, ColorExt,
+};
+
+use crate::{
+    agent::steering_system,
+    components::{nav_agent, navmesh},
+};
+
+/// Steers the [`NavAgent`](crate::NavAgent)s along their paths
+#[derive(Default)]
+pub struct NavPlugin {
+    gizmos: bool,
+}
+
+impl NavPlugin {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Draw the navigation mesh and the paths of the agents
+    pub fn with_gizmos(mut self, gizmos: bool) -> Self {
+        self.gizmos = gizmos;
+        self
+    }
+}
+
+impl Plugin for NavPlugin {
+    fn install(
+        &self,
+        _: &mut World,
+        _: &AssetCache,
+        schedules: &mut ScheduleSetBuilder,
+    ) -> anyhow::Result<()> {
+        schedules.fixed_mut().with_system(steering_system());
+
+        if self.gizmos {
+            schedules.per_tick_mut().with_system(gizmo_system());
+        }
+
+        Ok(())
+    }
+}
+
+fn gizmo_system() -> BoxedSystem {
+    System::builder()
+        .with_world()
+        .build(|world: &World| {
+            let gizmos = world.get(engine(), gizmos())?;
+            let mut section = gizmos.begin_section("nav_gizmo_system");
+
+            // Lift the lines slightly to avoid clipping into the ground
+            let offset = Vec3::Y * 0.05;
+
+            for navmesh in &mut Query::new(navmesh()).borrow(world) {
+                for polygon in navmesh.polygons() {
+                    let vertices = polygon.vertices();
+                    for (i, &v) in vertices.iter().enumerate() {
+                        section.draw(Line::from_points(
+                            v + offset,
+                            vertices[(i + 1) % vertices.len()] + offset,
+                            0.01,
+                            Color::cyan(),
+                        ));
+                    }
+                }
+            }
+
+            for (agent, &position) in &mut Query::new((nav_agent(), position())).borrow(world) {
+                let mut prev = position;
+                for &point in agent.path() {
+                    section.draw(Line::from_points(
+                        prev + offset,
+                        point + offset,
+                        0.02,
+                        Color::yellow(),
+                    ));
+                    prev = point;
+                }
+            }
+
+            anyhow::Ok(())
+        })
+        .boxed()
+}
@@ -0,0 +1,19 @@
+use flax::{component, Debuggable};
+
+use crate::{ConnectionId, Network};
+
+component! {
+    /// Sends messages to the connected peers, set on the engine entity by the
+    /// [`NetworkLayer`](crate::NetworkLayer)
+    pub network: Network,
+
+    /// Replicates the entity to the clients, see [`ReplicationLayer`](crate::ReplicationLayer)
+    pub replicated: () => [ Debuggable ],
+    /// The connection which controls the entity, such as a player's avatar.
+    ///
+    /// Used as the center of interest for the connection's replication.
+    pub owner_connection: ConnectionId => [ Debuggable ],
+    /// The entity is simulated by the predicted systems, see
+    /// [`PredictionPlugin`](crate::PredictionPlugin)
+    pub predicted: () => [ Debuggable ],
+}
@@ -0,0 +1,107 @@
+use std::collections::VecDeque;
+
+use glam::{Quat, Vec2, Vec3};
+
+/// Values which can be blended between snapshots
+pub trait Interpolate: Sized {
+    fn interpolate(&self, other: &Self, t: f32) -> Self;
+}
+
+impl Interpolate for f32 {
+    fn interpolate(&self, other: &Self, t: f32) -> Self {
+        self + (other - self) * t
+    }
+}
+
+impl Interpolate for Vec2 {
+    fn interpolate(&self, other: &Self, t: f32) -> Self {
+        self.lerp(*other, t)
+    }
+}
+
+impl Interpolate for Vec3 {
+    fn interpolate(&self, other: &Self, t: f32) -> Self {
+        self.lerp(*other, t)
+    }
+}
+
+impl Interpolate for Quat {
+    fn interpolate(&self, other: &Self, t: f32) -> Self {
+        self.slerp(*other, t)
+    }
+}
+
+/// Received values of a component, which are played back with a delay so that there is always a
+/// later value to blend towards.
+#[derive(Debug, Clone)]
+pub struct InterpolationBuffer<T> {
+    /// Server time and value, oldest first
+    values: VecDeque<(f64, T)>,
+}
+
+impl<T> Default for InterpolationBuffer<T> {
+    fn default() -> Self {
+        Self {
+            values: VecDeque::new(),
+        }
+    }
+}
+
+impl<T: Clone> InterpolationBuffer<T> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a value received at `time`. Values older than the latest are ignored
+    pub fn push(&mut self, time: f64, value: T) {
+        if self.values.back().is_some_and(|v| v.0 >= time) {
+            return;
+        }
+
+        self.values.push_back((time, value));
+    }
+
+    /// Returns the value at `time`, and discards the values before it which are no longer
+    /// needed.
+    ///
+    /// Times outside of the buffer are clamped rather than extrapolated.
+    pub fn sample(&mut self, time: f64, lerp: impl Fn(&T, &T, f32) -> T) -> Option<T> {
+        while self.values.len() > 1 && self.values[1].0 <= time {
+            self.values.pop_front();
+        }
+
+        let (start_time, start) = self.values.front()?;
+        let Some((end_time, end)) = self.values.get(1) else {
+            return Some(start.clone());
+        };
+
+        let t = ((time - start_time) / (end_time - start_time)).clamp(0.0, 1.0);
+        Some(lerp(start, end, t as f32))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn sample() {
+        let mut buffer = InterpolationBuffer::new();
+        assert_eq!(buffer.sample(0.0, f32::interpolate), None);
+
+        buffer.push(1.0, 10.0);
+        buffer.push(2.0, 20.0);
+        buffer.push(1.5, 0.0);
+        buffer.push(3.0, 40.0);
+
+        assert_eq!(buffer.sample(0.0, f32::interpolate), Some(10.0));
+        assert_eq!(buffer.sample(1.5, f32::interpolate), Some(15.0));
+        assert_eq!(buffer.sample(2.5, f32::interpolate), Some(30.0));
+        assert_eq!(buffer.sample(4.0, f32::interpolate), Some(40.0));
+        assert_eq!(buffer.values.len(), 1);
+    }
+}
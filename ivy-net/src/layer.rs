@@ -0,0 +1,192 @@
+use std::sync::Arc;
+
+use flax::World;
+use ivy_assets::AssetCache;
+use ivy_core::{
+    app::TickEvent,
+    components::{engine, event_queue},
+    layer::events::EventRegisterContext,
+    Layer,
+};
+use parking_lot::RwLock;
+
+use crate::{
+    components, message::MessageRegistry, ConnectionId, Delivery, Message, PeerConnected,
+    PeerDisconnected, Transport, TransportEvent,
+};
+
+enum Outgoing {
+    Send {
+        /// Sent to all connections if `None`
+        connection: Option<ConnectionId>,
+        delivery: Delivery,
+        payload: Vec<u8>,
+    },
+    Disconnect(ConnectionId),
+}
+
+/// Sends messages to the connected peers.
+///
+/// The messages are sent by the [`NetworkLayer`] during its next tick.
+#[derive(Clone)]
+pub struct Network {
+    registry: Arc<MessageRegistry>,
+    connections: Arc<RwLock<Vec<ConnectionId>>>,
+    tx: flume::Sender<Outgoing>,
+}
+
+impl Network {
+    pub fn send<T: Message>(&self, connection: ConnectionId, message: &T) -> anyhow::Result<()> {
+        let (delivery, payload) = self.registry.encode(message)?;
+        self.tx
+            .send(Outgoing::Send {
+                connection: Some(connection),
+                delivery,
+                payload,
+            })
+            .ok();
+
+        Ok(())
+    }
+
+    /// Sends a message to all connected peers
+    pub fn broadcast<T: Message>(&self, message: &T) -> anyhow::Result<()> {
+        let (delivery, payload) = self.registry.encode(message)?;
+        self.tx
+            .send(Outgoing::Send {
+                connection: None,
+                delivery,
+                payload,
+            })
+            .ok();
+
+        Ok(())
+    }
+
+    pub fn disconnect(&self, connection: ConnectionId) {
+        self.tx.send(Outgoing::Disconnect(connection)).ok();
+    }
+
+    /// Returns the established connections, as of the last tick
+    pub fn connections(&self) -> Vec<ConnectionId> {
+        self.connections.read().clone()
+    }
+}
+
+/// Manages the connections of a [`Transport`].
+///
+/// Received messages are emitted as [`Received`](crate::Received) events, alongside
+/// [`PeerConnected`] and [`PeerDisconnected`].
+pub struct NetworkLayer {
+    transport: Box<dyn Transport>,
+    registry: MessageRegistry,
+    network: Option<(Network, flume::Receiver<Outgoing>)>,
+    events: Vec<TransportEvent>,
+}
+
+impl NetworkLayer {
+    pub fn new(transport: impl Transport) -> Self {
+        Self {
+            transport: Box::new(transport),
+            registry: MessageRegistry::default(),
+            network: None,
+            events: Vec::new(),
+        }
+    }
+
+    /// Registers a message type.
+    ///
+    /// Messages must be registered in the same order by all peers.
+    pub fn with_message<T: Message>(mut self, delivery: Delivery) -> Self {
+        self.registry.register::<T>(delivery);
+        self
+    }
+
+    fn update(&mut self, world: &World) -> anyhow::Result<()> {
+        let Some((network, rx)) = &self.network else {
+            return Ok(());
+        };
+
+        let events = world.get(engine(), event_queue())?;
+
+        if let Err(err) = self.transport.poll(&mut self.events) {
+            tracing::error!("{err:?}");
+        }
+
+        for event in self.events.drain(..) {
+            match event {
+                TransportEvent::Connected(id) => {
+                    tracing::info!(%id, "Peer connected");
+                    events.send(PeerConnected(id));
+                }
+                TransportEvent::Disconnected(id) => {
+                    tracing::info!(%id, "Peer disconnected");
+                    events.send(PeerDisconnected(id));
+                }
+                TransportEvent::Received {
+                    connection,
+                    payload,
+                } => {
+                    if let Err(err) = network.registry.dispatch(connection, &payload, &events) {
+                        tracing::warn!(%connection, "{err:?}");
+                    }
+                }
+            }
+        }
+
+        for outgoing in rx.drain() {
+            match outgoing {
+                Outgoing::Send {
+                    connection,
+                    delivery,
+                    payload,
+                } => {
+                    let connections = match connection {
+                        Some(v) => vec![v],
+                        None => self.transport.connections(),
+                    };
+
+                    for connection in connections {
+                        if let Err(err) = self.transport.send(connection, delivery, &payload) {
+                            tracing::warn!(%connection, "{err:?}");
+                        }
+                    }
+                }
+                Outgoing::Disconnect(connection) => {
+                    self.transport.disconnect(connection);
+                    events.send(PeerDisconnected(connection));
+                }
+            }
+        }
+
+        *network.connections.write() = self.transport.connections();
+
+        Ok(())
+    }
+}
+
+impl Layer for NetworkLayer {
+    fn register(
+        &mut self,
+        world: &mut World,
+        _: &AssetCache,
+        mut events: EventRegisterContext<Self>,
+    ) -> anyhow::Result<()>
+    where
+        Self: Sized,
+    {
+        let (tx, rx) = flume::unbounded();
+        let network = Network {
+            registry: Arc::new(std::mem::take(&mut self.registry)),
+            connections: Default::default(),
+            tx,
+        };
+
+        world.set(engine(), components::network(), network.clone())?;
+        self.network = Some((network, rx));
+
+        events.subscribe(|this, ctx, _: &TickEvent| this.update(ctx.world));
+
+        Ok(())
+    }
+}
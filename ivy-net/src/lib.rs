@@ -0,0 +1,39 @@
+//! Client/server networking for the Ivy framework.
+//!
+//! Packets are sent through a [`Transport`]. The crate provides a [`UdpTransport`], and a
+//! [`MemoryTransport`] for peers within the same process.
+//!
+//! The [`NetworkLayer`] manages the connections of a [`Transport`], and dispatches the received
+//! messages to the layers as [`Received`] events. Messages are sent through the
+//! [`network`](components::network) on the engine entity.
+//!
+//! ```rust,ignore
+//! #[derive(Debug, Serialize, Deserialize)]
+//! struct Chat(String);
+//!
+//! App::builder()
+//!     .with_layer(
+//!         NetworkLayer::new(UdpTransport::server("0.0.0.0:7777")?)
+//!             .with_message::<Chat>(Delivery::Reliable),
+//!     )
+//! ```
+//!
+//! Entities marked as [`replicated`](components::replicated) are kept in sync with the clients by
+//! the [`ReplicationLayer`], which sends delta compressed snapshots of the registered components.
+//! Entities controlled by the local player are instead predicted by the [`PredictionPlugin`], and
+//! reconciled with the server's state.
+pub mod components;
+pub mod interpolation;
+mod layer;
+mod message;
+mod prediction;
+mod replication;
+mod transport;
+mod udp;
+
+pub use layer::*;
+pub use message::*;
+pub use prediction::{Prediction, PredictionLayer, PredictionPlugin, Reconciliation};
+pub use replication::{Replication, ReplicationLayer};
+pub use transport::*;
+pub use udp::UdpTransport;
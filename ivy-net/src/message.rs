@@ -0,0 +1,122 @@
+use std::{
+    any::{type_name, TypeId},
+    collections::HashMap,
+    fmt::Debug,
+};
+
+use anyhow::Context;
+use bincode::Options;
+use ivy_core::events::{Event, EventQueue};
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::{ConnectionId, Delivery};
+
+/// A message which can be sent between peers
+pub trait Message: 'static + Send + Sync + Debug + Serialize + DeserializeOwned {}
+
+impl<T> Message for T where T: 'static + Send + Sync + Debug + Serialize + DeserializeOwned {}
+
+/// A message received from a peer, dispatched to the layers as an event
+#[derive(Debug, Clone, PartialEq)]
+pub struct Received<T> {
+    pub connection: ConnectionId,
+    pub message: T,
+}
+
+impl<T: Message> Event for Received<T> {}
+
+/// Emitted when a connection to a peer is established
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PeerConnected(pub ConnectionId);
+
+/// Emitted when a peer disconnects or times out
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PeerDisconnected(pub ConnectionId);
+
+impl Event for PeerConnected {}
+impl Event for PeerDisconnected {}
+
+/// Largest allocation made while deserializing, so that a peer can not exhaust the memory with a
+/// forged length
+const MAX_DECODED_SIZE: u64 = 1 << 20;
+
+/// Encoding of messages and replicated components
+pub(crate) fn codec() -> impl Options + Copy {
+    bincode::DefaultOptions::new()
+        .with_fixint_encoding()
+        .allow_trailing_bytes()
+        .with_limit(MAX_DECODED_SIZE)
+}
+
+type DispatchFn = Box<dyn Fn(ConnectionId, &[u8], &EventQueue) -> anyhow::Result<()> + Send + Sync>;
+
+struct MessageType {
+    name: &'static str,
+    delivery: Delivery,
+    dispatch: DispatchFn,
+}
+
+/// The message types known to a peer.
+///
+/// Messages are identified by the order they are registered in, which must be the same for all
+/// peers.
+#[derive(Default)]
+pub(crate) struct MessageRegistry {
+    ids: HashMap<TypeId, u16>,
+    types: Vec<MessageType>,
+}
+
+impl MessageRegistry {
+    pub(crate) fn register<T: Message>(&mut self, delivery: Delivery) {
+        let id = u16::try_from(self.types.len()).expect("Too many message types");
+        if self.ids.insert(TypeId::of::<T>(), id).is_some() {
+            panic!("Message {} is already registered", type_name::<T>());
+        }
+
+        self.types.push(MessageType {
+            name: type_name::<T>(),
+            delivery,
+            dispatch: Box::new(|connection, payload, events| {
+                let message: T = codec().deserialize(payload)?;
+                events.send(Received {
+                    connection,
+                    message,
+                });
+                Ok(())
+            }),
+        });
+    }
+
+    /// Serializes a message, prefixed by its id
+    pub(crate) fn encode<T: Message>(&self, message: &T) -> anyhow::Result<(Delivery, Vec<u8>)> {
+        let id = *self
+            .ids
+            .get(&TypeId::of::<T>())
+            .with_context(|| format!("Message {} is not registered", type_name::<T>()))?;
+
+        let mut payload = id.to_le_bytes().to_vec();
+        codec().serialize_into(&mut payload, message)?;
+
+        Ok((self.types[id as usize].delivery, payload))
+    }
+
+    /// Deserializes a message and sends it to the layers
+    pub(crate) fn dispatch(
+        &self,
+        connection: ConnectionId,
+        payload: &[u8],
+        events: &EventQueue,
+    ) -> anyhow::Result<()> {
+        let (id, payload) = payload
+            .split_first_chunk::<2>()
+            .context("Message is missing its id")?;
+
+        let ty = self
+            .types
+            .get(u16::from_le_bytes(*id) as usize)
+            .context("Unknown message id")?;
+
+        (ty.dispatch)(connection, payload, events)
+            .with_context(|| format!("Failed to deserialize message {}", ty.name))
+    }
+}
@@ -0,0 +1,726 @@
+use std::collections::{BTreeMap, HashMap};
+
+use anyhow::Context;
+use bincode::Options;
+use flax::{
+    component::ComponentValue, entity_ids, BoxedSystem, Component, Entity, Query, Schedule,
+    ScheduleBuilder, System, World,
+};
+use ivy_assets::AssetCache;
+use ivy_core::{
+    components::engine,
+    layer::events::EventRegisterContext,
+    update_layer::{Plugin, ScheduleSetBuilder},
+    Layer,
+};
+use parking_lot::Mutex;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use crate::{
+    components::{network, owner_connection, predicted},
+    message::codec,
+    ConnectionId, Delivery, Message, NetworkLayer, PeerDisconnected, Received,
+};
+
+/// Number of ticks of inputs and predicted states kept by the client
+const HISTORY: usize = 128;
+/// Number of previous inputs resent with each input, to tolerate packet loss
+const REDUNDANCY: usize = 8;
+/// Inputs buffered by the server beyond this are dropped, to bound the input latency
+const MAX_BUFFERED_INPUTS: usize = 8;
+
+type ComponentState = Vec<Option<Vec<u8>>>;
+
+/// Inputs of the client for the latest ticks
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct InputFrame<I> {
+    inputs: Vec<(u32, I)>,
+}
+
+/// The authoritative state of a predicted entity after processing the input for `tick`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct PredictionState {
+    tick: u32,
+    state: ComponentState,
+}
+
+impl NetworkLayer {
+    /// Registers the messages used by the [`PredictionPlugin`] for the input type `I`
+    pub fn with_prediction<I: Message + Clone>(self) -> Self {
+        self.with_message::<InputFrame<I>>(Delivery::Unreliable)
+            .with_message::<PredictionState>(Delivery::Unreliable)
+    }
+}
+
+/// Information about a misprediction which was corrected
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Reconciliation {
+    pub entity: Entity,
+    /// The tick of the authoritative state
+    pub tick: u32,
+    /// Number of ticks which were simulated again after restoring the authoritative state
+    pub resimulated: usize,
+}
+
+trait PredictedComponent: Send + Sync {
+    fn save(&self, world: &World, id: Entity) -> anyhow::Result<Option<Vec<u8>>>;
+    fn restore(&self, world: &mut World, id: Entity, payload: Option<&[u8]>) -> anyhow::Result<()>;
+    fn matches(&self, predicted: &[u8], authoritative: &[u8]) -> anyhow::Result<bool>;
+}
+
+struct ComponentPredictor<T> {
+    component: Component<T>,
+    eq: fn(&T, &T) -> bool,
+}
+
+impl<T> PredictedComponent for ComponentPredictor<T>
+where
+    T: ComponentValue + Serialize + DeserializeOwned,
+{
+    fn save(&self, world: &World, id: Entity) -> anyhow::Result<Option<Vec<u8>>> {
+        match world.get(id, self.component) {
+            Ok(value) => Ok(Some(codec().serialize(&*value)?)),
+            Err(_) => Ok(None),
+        }
+    }
+
+    fn restore(&self, world: &mut World, id: Entity, payload: Option<&[u8]>) -> anyhow::Result<()> {
+        match payload {
+            Some(payload) => {
+                world.set(id, self.component, codec().deserialize(payload)?)?;
+            }
+            None => {
+                world.remove(id, self.component).ok();
+            }
+        }
+
+        Ok(())
+    }
+
+    fn matches(&self, predicted: &[u8], authoritative: &[u8]) -> anyhow::Result<bool> {
+        Ok((self.eq)(
+            &codec().deserialize(predicted)?,
+            &codec().deserialize(authoritative)?,
+        ))
+    }
+}
+
+type SampleFn<I> = Box<dyn FnMut(&World, Entity) -> I + Send + Sync>;
+type ApplyFn<I> = Box<dyn FnMut(&mut World, Entity, &I) -> anyhow::Result<()> + Send + Sync>;
+type ReconcileFn = Box<dyn FnMut(&mut World, &Reconciliation) -> anyhow::Result<()> + Send + Sync>;
+
+/// Declares how the [`predicted`](crate::components::predicted) entities are simulated.
+///
+/// The predicted systems are executed once per fixed tick by both the server and the client, and
+/// must be deterministic for the same input and state. When the authoritative state from the
+/// server differs from what was predicted, the client restores it and simulates the predicted
+/// systems again for the inputs the server has not yet processed.
+pub struct Prediction<I> {
+    components: Vec<Box<dyn PredictedComponent>>,
+    schedule: ScheduleBuilder,
+    effects: ScheduleBuilder,
+    sample: SampleFn<I>,
+    apply: ApplyFn<I>,
+    on_reconcile: Option<ReconcileFn>,
+}
+
+impl<I: Message + Clone> Prediction<I> {
+    /// `sample` reads the input of the local player on the client, and `apply` applies an input
+    /// to a predicted entity before the predicted systems run.
+    pub fn new(
+        sample: impl 'static + FnMut(&World, Entity) -> I + Send + Sync,
+        apply: impl 'static + FnMut(&mut World, Entity, &I) -> anyhow::Result<()> + Send + Sync,
+    ) -> Self {
+        Self {
+            components: Vec::new(),
+            schedule: Schedule::builder(),
+            effects: Schedule::builder(),
+            sample: Box::new(sample),
+            apply: Box::new(apply),
+            on_reconcile: None,
+        }
+    }
+
+    /// Adds a component to the predicted state, which is compared against the authoritative
+    /// state to detect mispredictions
+    pub fn with_component<T>(self, component: Component<T>) -> Self
+    where
+        T: ComponentValue + PartialEq + Serialize + DeserializeOwned,
+    {
+        self.with_component_eq(component, T::eq)
+    }
+
+    /// Adds a component to the predicted state with a custom comparison, such as to tolerate
+    /// small floating point differences
+    pub fn with_component_eq<T>(mut self, component: Component<T>, eq: fn(&T, &T) -> bool) -> Self
+    where
+        T: ComponentValue + Serialize + DeserializeOwned,
+    {
+        self.components
+            .push(Box::new(ComponentPredictor { component, eq }));
+        self
+    }
+
+    /// Adds a predicted system, which is simulated again on misprediction.
+    ///
+    /// The system should only modify the [`predicted`](crate::components::predicted) entities,
+    /// as it runs several times per tick while resimulating.
+    pub fn with_system(mut self, system: BoxedSystem) -> Self {
+        self.schedule.with_system(system);
+        self
+    }
+
+    /// Adds a system which runs once per tick after the predicted systems, and is not simulated
+    /// again on misprediction, such as for sounds or particles
+    pub fn with_effect_system(mut self, system: BoxedSystem) -> Self {
+        self.effects.with_system(system);
+        self
+    }
+
+    /// Invoked on the client after a misprediction has been corrected
+    pub fn on_reconcile(
+        mut self,
+        func: impl 'static + FnMut(&mut World, &Reconciliation) -> anyhow::Result<()> + Send + Sync,
+    ) -> Self {
+        self.on_reconcile = Some(Box::new(func));
+        self
+    }
+}
+
+enum Inbound<I> {
+    Inputs(ConnectionId, InputFrame<I>),
+    State(PredictionState),
+    Disconnected(ConnectionId),
+}
+
+struct Remote<I> {
+    inputs: BTreeMap<u32, I>,
+    processed: Option<u32>,
+    /// The input which was processed last
+    last: Option<I>,
+}
+
+impl<I: Clone> Remote<I> {
+    fn new() -> Self {
+        Self {
+            inputs: BTreeMap::new(),
+            processed: None,
+            last: None,
+        }
+    }
+
+    /// Returns the input for the next tick.
+    ///
+    /// The last input is repeated if the next one has not arrived in time, so that the entity
+    /// keeps moving. The client corrects the difference through reconciliation.
+    fn next_input(&mut self) -> Option<I> {
+        match self.inputs.pop_first() {
+            Some((tick, input)) => {
+                self.processed = Some(tick);
+                self.last = Some(input.clone());
+                Some(input)
+            }
+            None => {
+                let input = self.last.clone()?;
+                self.processed = self.processed.map(|v| v + 1);
+                Some(input)
+            }
+        }
+    }
+}
+
+struct Local<I> {
+    tick: u32,
+    inputs: BTreeMap<u32, I>,
+    states: BTreeMap<u32, ComponentState>,
+    latest: Option<PredictionState>,
+}
+
+enum Role<I> {
+    Server(HashMap<ConnectionId, Remote<I>>),
+    Client(Local<I>),
+}
+
+struct Simulation<I> {
+    components: Vec<Box<dyn PredictedComponent>>,
+    /// The predicted systems
+    schedule: Schedule,
+    effects: Schedule,
+    apply: ApplyFn<I>,
+}
+
+impl<I> Simulation<I> {
+    /// Applies the input and executes the predicted systems
+    fn step(&mut self, world: &mut World, id: Entity, input: &I) -> anyhow::Result<()> {
+        (self.apply)(world, id, input)?;
+        self.schedule.execute_seq(world)?;
+        Ok(())
+    }
+
+    fn save(&self, world: &World, id: Entity) -> anyhow::Result<ComponentState> {
+        self.components.iter().map(|v| v.save(world, id)).collect()
+    }
+
+    fn restore(&self, world: &mut World, id: Entity, state: &ComponentState) -> anyhow::Result<()> {
+        for (component, payload) in self.components.iter().zip(state) {
+            component.restore(world, id, payload.as_deref())?;
+        }
+
+        Ok(())
+    }
+
+    fn matches(&self, predicted: &ComponentState, authoritative: &ComponentState) -> bool {
+        self.components
+            .iter()
+            .zip(predicted.iter().zip(authoritative))
+            .all(|(component, states)| match states {
+                (Some(a), Some(b)) => component.matches(a, b).unwrap_or(false),
+                (None, None) => true,
+                _ => false,
+            })
+    }
+}
+
+struct Predictor<I> {
+    simulation: Simulation<I>,
+    sample: SampleFn<I>,
+    on_reconcile: Option<ReconcileFn>,
+    role: Role<I>,
+    rx: flume::Receiver<Inbound<I>>,
+}
+
+impl<I: Message + Clone> Predictor<I> {
+    fn step(&mut self, world: &mut World) -> anyhow::Result<()> {
+        match &mut self.role {
+            Role::Server(remotes) => {
+                Self::step_server(&mut self.simulation, remotes, &self.rx, world)
+            }
+            Role::Client(local) => {
+                for inbound in self.rx.drain() {
+                    if let Inbound::State(state) = inbound {
+                        if local.latest.as_ref().map_or(true, |v| state.tick > v.tick) {
+                            local.latest = Some(state);
+                        }
+                    }
+                }
+
+                let Some(id) = Query::new(entity_ids())
+                    .with(predicted())
+                    .borrow(world)
+                    .first()
+                else {
+                    return Ok(());
+                };
+
+                if let Some(state) = local.latest.take() {
+                    Self::reconcile(
+                        &mut self.simulation,
+                        local,
+                        &mut self.on_reconcile,
+                        world,
+                        id,
+                        state,
+                    )?;
+                }
+
+                local.tick += 1;
+                let input = (self.sample)(world, id);
+                self.simulation.step(world, id, &input)?;
+                self.simulation.effects.execute_seq(world)?;
+
+                local
+                    .states
+                    .insert(local.tick, self.simulation.save(world, id)?);
+                local.inputs.insert(local.tick, input);
+
+                while local.inputs.len() > HISTORY {
+                    local.inputs.pop_first();
+                }
+
+                while local.states.len() > HISTORY {
+                    local.states.pop_first();
+                }
+
+                let mut inputs = local
+                    .inputs
+                    .iter()
+                    .rev()
+                    .take(REDUNDANCY)
+                    .map(|(&tick, input)| (tick, input.clone()))
+                    .collect::<Vec<_>>();
+
+                inputs.reverse();
+
+                world
+                    .get(engine(), network())
+                    .context("Prediction requires a NetworkLayer")?
+                    .broadcast(&InputFrame { inputs })
+            }
+        }
+    }
+
+    fn step_server(
+        simulation: &mut Simulation<I>,
+        remotes: &mut HashMap<ConnectionId, Remote<I>>,
+        rx: &flume::Receiver<Inbound<I>>,
+        world: &mut World,
+    ) -> anyhow::Result<()> {
+        for inbound in rx.drain() {
+            match inbound {
+                Inbound::Inputs(connection, frame) => {
+                    let remote = remotes.entry(connection).or_insert_with(Remote::new);
+
+                    for (tick, input) in frame.inputs {
+                        if remote.processed.map_or(true, |v| tick > v) {
+                            remote.inputs.insert(tick, input);
+                        }
+                    }
+
+                    while remote.inputs.len() > MAX_BUFFERED_INPUTS {
+                        remote.inputs.pop_first();
+                    }
+                }
+                Inbound::Disconnected(connection) => {
+                    remotes.remove(&connection);
+                }
+                Inbound::State(_) => {}
+            }
+        }
+
+        let entities = Query::new((entity_ids(), owner_connection().copied()))
+            .with(predicted())
+            .borrow(world)
+            .iter()
+            .collect::<Vec<_>>();
+
+        for &(id, connection) in &entities {
+            let Some(remote) = remotes.get_mut(&connection) else {
+                continue;
+            };
+
+            if let Some(input) = remote.next_input() {
+                (simulation.apply)(world, id, &input)?;
+            }
+        }
+
+        simulation.schedule.execute_seq(world)?;
+        simulation.effects.execute_seq(world)?;
+
+        let network = world
+            .get(engine(), network())
+            .context("Prediction requires a NetworkLayer")?;
+
+        for (id, connection) in entities {
+            let Some(tick) = remotes.get(&connection).and_then(|v| v.processed) else {
+                continue;
+            };
+
+            let state = simulation.save(world, id)?;
+            network.send(connection, &PredictionState { tick, state })?;
+        }
+
+        Ok(())
+    }
+
+    fn reconcile(
+        simulation: &mut Simulation<I>,
+        local: &mut Local<I>,
+        on_reconcile: &mut Option<ReconcileFn>,
+        world: &mut World,
+        id: Entity,
+        authoritative: PredictionState,
+    ) -> anyhow::Result<()> {
+        let tick = authoritative.tick;
+        let mispredicted = local
+            .states
+            .get(&tick)
+            .is_some_and(|predicted| !simulation.matches(predicted, &authoritative.state));
+
+        local.inputs.retain(|&v, _| v > tick);
+        local.states.retain(|&v, _| v > tick);
+
+        if !mispredicted {
+            return Ok(());
+        }
+
+        tracing::debug!(tick, "Mispredicted, resimulating");
+        simulation.restore(world, id, &authoritative.state)?;
+
+        for (&tick, input) in &local.inputs {
+            simulation.step(world, id, input)?;
+            local.states.insert(tick, simulation.save(world, id)?);
+        }
+
+        if let Some(on_reconcile) = on_reconcile {
+            on_reconcile(
+                world,
+                &Reconciliation {
+                    entity: id,
+                    tick,
+                    resimulated: local.inputs.len(),
+                },
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Executes the predicted systems in the fixed timestep stage.
+///
+/// On the server, the inputs received from each connection are applied to the
+/// [`predicted`](crate::components::predicted) entity it owns through
+/// [`owner_connection`](crate::components::owner_connection). On the client, the inputs are
+/// sampled from the local predicted entity and simulated immediately, without waiting for the
+/// server.
+///
+/// Received messages are forwarded to the plugin by the layer returned from [`Self::layer`], and
+/// the [`NetworkLayer`] must be registered with [`NetworkLayer::with_prediction`].
+pub struct PredictionPlugin<I> {
+    predictor: Mutex<Option<Predictor<I>>>,
+    tx: flume::Sender<Inbound<I>>,
+}
+
+impl<I: Message + Clone> PredictionPlugin<I> {
+    pub fn server(prediction: Prediction<I>) -> Self {
+        Self::new(prediction, Role::Server(HashMap::new()))
+    }
+
+    pub fn client(prediction: Prediction<I>) -> Self {
+        Self::new(
+            prediction,
+            Role::Client(Local {
+                tick: 0,
+                inputs: BTreeMap::new(),
+                states: BTreeMap::new(),
+                latest: None,
+            }),
+        )
+    }
+
+    fn new(mut prediction: Prediction<I>, role: Role<I>) -> Self {
+        let (tx, rx) = flume::unbounded();
+
+        let predictor = Predictor {
+            simulation: Simulation {
+                components: prediction.components,
+                schedule: prediction.schedule.build(),
+                effects: prediction.effects.build(),
+                apply: prediction.apply,
+            },
+            sample: prediction.sample,
+            on_reconcile: prediction.on_reconcile,
+            role,
+            rx,
+        };
+
+        Self {
+            predictor: Mutex::new(Some(predictor)),
+            tx,
+        }
+    }
+
+    /// Returns the layer which forwards the received inputs and states to the plugin
+    pub fn layer(&self) -> PredictionLayer<I> {
+        PredictionLayer {
+            tx: self.tx.clone(),
+        }
+    }
+}
+
+impl<I: Message + Clone> Plugin for PredictionPlugin<I> {
+    fn install(
+        &self,
+        _: &mut World,
+        _: &AssetCache,
+        schedules: &mut ScheduleSetBuilder,
+    ) -> anyhow::Result<()> {
+        let mut predictor = self
+            .predictor
+            .lock()
+            .take()
+            .context("Prediction plugin is already installed")?;
+
+        schedules.fixed_mut().with_system(
+            System::builder()
+                .with_world_mut()
+                .build(move |world: &mut World| predictor.step(world))
+                .boxed(),
+        );
+
+        Ok(())
+    }
+}
+
+/// Forwards the network messages of a [`PredictionPlugin`]
+pub struct PredictionLayer<I> {
+    tx: flume::Sender<Inbound<I>>,
+}
+
+impl<I: Message + Clone> Layer for PredictionLayer<I> {
+    fn register(
+        &mut self,
+        _: &mut World,
+        _: &AssetCache,
+        mut events: EventRegisterContext<Self>,
+    ) -> anyhow::Result<()>
+    where
+        Self: Sized,
+    {
+        events.subscribe(|this, _, e: &Received<InputFrame<I>>| {
+            this.tx
+                .send(Inbound::Inputs(e.connection, e.message.clone()))
+                .ok();
+            Ok(())
+        });
+
+        events.subscribe(|this, _, e: &Received<PredictionState>| {
+            this.tx.send(Inbound::State(e.message.clone())).ok();
+            Ok(())
+        });
+
+        events.subscribe(|this, _, e: &PeerDisconnected| {
+            this.tx.send(Inbound::Disconnected(e.0)).ok();
+            Ok(())
+        });
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    flax::component! {
+        position: i32,
+    }
+
+    fn simulation() -> Simulation<i32> {
+        Simulation {
+            components: vec![Box::new(ComponentPredictor {
+                component: position(),
+                eq: i32::eq,
+            })],
+            schedule: Schedule::builder().build(),
+            effects: Schedule::builder().build(),
+            apply: Box::new(|world: &mut World, id: Entity, input: &i32| {
+                *world.get_mut(id, position())? += *input;
+                Ok(())
+            }),
+        }
+    }
+
+    fn state(value: i32) -> ComponentState {
+        vec![Some(codec().serialize(&value).unwrap())]
+    }
+
+    fn setup(inputs: &[i32]) -> (World, Entity, Simulation<i32>, Local<i32>) {
+        let mut world = World::new();
+        let id = Entity::builder().set(position(), 0).spawn(&mut world);
+        let mut simulation = simulation();
+
+        let mut local = Local {
+            tick: 0,
+            inputs: BTreeMap::new(),
+            states: BTreeMap::new(),
+            latest: None,
+        };
+
+        for &input in inputs {
+            local.tick += 1;
+            simulation.step(&mut world, id, &input).unwrap();
+            local
+                .states
+                .insert(local.tick, simulation.save(&world, id).unwrap());
+            local.inputs.insert(local.tick, input);
+        }
+
+        (world, id, simulation, local)
+    }
+
+    #[test]
+    fn reconcile_matching() {
+        let (mut world, id, mut simulation, mut local) = setup(&[1, 1, 1]);
+
+        let (tx, rx) = flume::unbounded();
+        let mut on_reconcile: Option<ReconcileFn> =
+            Some(Box::new(move |_: &mut World, v: &Reconciliation| {
+                tx.send(*v).unwrap();
+                Ok(())
+            }));
+
+        Predictor::reconcile(
+            &mut simulation,
+            &mut local,
+            &mut on_reconcile,
+            &mut world,
+            id,
+            PredictionState {
+                tick: 1,
+                state: state(1),
+            },
+        )
+        .unwrap();
+
+        assert_eq!(world.get_copy(id, position()).unwrap(), 3);
+        assert_eq!(local.inputs.keys().copied().collect::<Vec<_>>(), [2, 3]);
+        assert_eq!(local.states.keys().copied().collect::<Vec<_>>(), [2, 3]);
+        assert!(rx.try_recv().is_err());
+    }
+
+    #[test]
+    fn reconcile_mispredicted() {
+        let (mut world, id, mut simulation, mut local) = setup(&[1, 1, 1]);
+
+        let (tx, rx) = flume::unbounded();
+        let mut on_reconcile: Option<ReconcileFn> =
+            Some(Box::new(move |_: &mut World, v: &Reconciliation| {
+                tx.send(*v).unwrap();
+                Ok(())
+            }));
+
+        // The server was pushed ahead during the first tick
+        Predictor::reconcile(
+            &mut simulation,
+            &mut local,
+            &mut on_reconcile,
+            &mut world,
+            id,
+            PredictionState {
+                tick: 1,
+                state: state(5),
+            },
+        )
+        .unwrap();
+
+        assert_eq!(world.get_copy(id, position()).unwrap(), 7);
+        assert_eq!(local.states[&2], state(6));
+        assert_eq!(local.states[&3], state(7));
+        assert_eq!(
+            rx.try_recv().unwrap(),
+            Reconciliation {
+                entity: id,
+                tick: 1,
+                resimulated: 2,
+            }
+        );
+    }
+
+    #[test]
+    fn repeat_missing_input() {
+        let mut remote = Remote::new();
+        assert_eq!(remote.next_input(), None);
+
+        remote.inputs.insert(1, 10);
+        remote.inputs.insert(2, 20);
+
+        assert_eq!(remote.next_input(), Some(10));
+        assert_eq!(remote.next_input(), Some(20));
+        assert_eq!(remote.processed, Some(2));
+
+        // The input for tick 3 is late
+        assert_eq!(remote.next_input(), Some(20));
+        assert_eq!(remote.processed, Some(3));
+    }
+}
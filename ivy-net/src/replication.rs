@@ -0,0 +1,833 @@
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    mem,
+    time::Duration,
+};
+
+use bincode::Options;
+use flax::{component::ComponentValue, entity_ids, Component, Entity, Query, World};
+use glam::Vec3;
+use ivy_assets::AssetCache;
+use ivy_core::{
+    app::TickEvent,
+    components::{engine, world_transform},
+    layer::events::EventRegisterContext,
+    Layer,
+};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use crate::{
+    components::{network, owner_connection, replicated},
+    interpolation::{Interpolate, InterpolationBuffer},
+    message::codec,
+    ConnectionId, Delivery, NetworkLayer, PeerDisconnected, Received,
+};
+
+/// Number of snapshots which are kept as baselines for delta compression
+const HISTORY: u32 = 32;
+/// Approximate size of each snapshot message, to fit within a single packet
+const MAX_PART_SIZE: usize = 1000;
+/// Largest serialized component which fits in a snapshot part
+const MAX_COMPONENT_SIZE: usize = MAX_PART_SIZE - 32;
+
+type ComponentIndex = u16;
+type EntityState = BTreeMap<ComponentIndex, Vec<u8>>;
+type WorldState = BTreeMap<u64, EntityState>;
+
+/// Changes of the replicated state relative to a previous snapshot
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub(crate) struct Delta {
+    updated: Vec<(u64, Vec<(ComponentIndex, Vec<u8>)>)>,
+    removed: Vec<(u64, Vec<ComponentIndex>)>,
+    despawned: Vec<u64>,
+}
+
+impl Delta {
+    fn new(baseline: &WorldState, current: &WorldState) -> Self {
+        let mut delta = Self::default();
+
+        for (&id, state) in current {
+            let base = baseline.get(&id);
+
+            let updated = state
+                .iter()
+                .filter(|&(index, value)| base.and_then(|v| v.get(index)) != Some(value))
+                .map(|(&index, value)| (index, value.clone()))
+                .collect::<Vec<_>>();
+
+            let removed = base
+                .into_iter()
+                .flat_map(|v| v.keys())
+                .filter(|index| !state.contains_key(index))
+                .copied()
+                .collect::<Vec<_>>();
+
+            // New entities are always sent, even without any replicated components
+            if !updated.is_empty() || base.is_none() {
+                delta.updated.push((id, updated));
+            }
+
+            if !removed.is_empty() {
+                delta.removed.push((id, removed));
+            }
+        }
+
+        delta.despawned = baseline
+            .keys()
+            .filter(|id| !current.contains_key(id))
+            .copied()
+            .collect();
+
+        delta
+    }
+
+    fn apply(self, state: &mut WorldState) {
+        for id in self.despawned {
+            state.remove(&id);
+        }
+
+        for (id, removed) in self.removed {
+            if let Some(state) = state.get_mut(&id) {
+                removed.iter().for_each(|index| {
+                    state.remove(index);
+                });
+            }
+        }
+
+        for (id, updated) in self.updated {
+            state.entry(id).or_default().extend(updated);
+        }
+    }
+
+    /// Splits the delta into parts which each fit within a packet.
+    ///
+    /// The components of an entity are spread over several parts if they do not fit in one.
+    fn split(self) -> Vec<Delta> {
+        let mut size = self.despawned.len() * 8
+            + self
+                .removed
+                .iter()
+                .map(|(_, v)| 16 + v.len() * 2)
+                .sum::<usize>();
+
+        let mut parts = vec![Delta {
+            updated: Vec::new(),
+            removed: self.removed,
+            despawned: self.despawned,
+        }];
+
+        for (id, components) in self.updated {
+            let mut entry = (id, Vec::new());
+            size += 16;
+
+            for component in components {
+                let component_size = 10 + component.1.len();
+                let part = parts.last_mut().unwrap();
+
+                if size + component_size > MAX_PART_SIZE
+                    && !(part.updated.is_empty() && entry.1.is_empty())
+                {
+                    // Continue the entity in the next part
+                    if !entry.1.is_empty() {
+                        part.updated
+                            .push(mem::replace(&mut entry, (id, Vec::new())));
+                    }
+
+                    parts.push(Delta::default());
+                    size = 16;
+                }
+
+                size += component_size;
+                entry.1.push(component);
+            }
+
+            parts.last_mut().unwrap().updated.push(entry);
+        }
+
+        parts
+    }
+}
+
+/// A part of the snapshot for a server tick
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct SnapshotPart {
+    tick: u32,
+    /// The acknowledged snapshot which the delta is relative to
+    baseline: Option<u32>,
+    /// Server time in seconds
+    time: f64,
+    part: u16,
+    parts: u16,
+    delta: Delta,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub(crate) struct SnapshotAck(u32);
+
+impl NetworkLayer {
+    /// Registers the messages used by the [`ReplicationLayer`]
+    pub fn with_replication(self) -> Self {
+        self.with_message::<SnapshotPart>(Delivery::Unreliable)
+            .with_message::<SnapshotAck>(Delivery::Unreliable)
+    }
+}
+
+trait Replicator: Send {
+    fn serialize(&self, world: &World, id: Entity) -> anyhow::Result<Option<Vec<u8>>>;
+    fn apply(
+        &mut self,
+        world: &mut World,
+        id: Entity,
+        payload: &[u8],
+        time: f64,
+    ) -> anyhow::Result<()>;
+    fn remove(&mut self, world: &mut World, id: Entity);
+    fn interpolate(&mut self, world: &mut World, time: f64);
+    /// Returns true if every received value is buffered for interpolation
+    fn is_interpolated(&self) -> bool;
+}
+
+struct ComponentReplicator<T> {
+    component: Component<T>,
+    lerp: Option<fn(&T, &T, f32) -> T>,
+    buffers: HashMap<Entity, InterpolationBuffer<T>>,
+}
+
+impl<T> Replicator for ComponentReplicator<T>
+where
+    T: ComponentValue + Clone + Serialize + DeserializeOwned,
+{
+    fn serialize(&self, world: &World, id: Entity) -> anyhow::Result<Option<Vec<u8>>> {
+        match world.get(id, self.component) {
+            Ok(value) => Ok(Some(codec().serialize(&*value)?)),
+            Err(_) => Ok(None),
+        }
+    }
+
+    fn apply(
+        &mut self,
+        world: &mut World,
+        id: Entity,
+        payload: &[u8],
+        time: f64,
+    ) -> anyhow::Result<()> {
+        let value: T = codec().deserialize(payload)?;
+
+        if self.lerp.is_some() {
+            if !world.has(id, self.component) {
+                world.set(id, self.component, value.clone())?;
+            }
+
+            self.buffers.entry(id).or_default().push(time, value);
+        } else {
+            world.set(id, self.component, value)?;
+        }
+
+        Ok(())
+    }
+
+    fn remove(&mut self, world: &mut World, id: Entity) {
+        self.buffers.remove(&id);
+        world.remove(id, self.component).ok();
+    }
+
+    fn interpolate(&mut self, world: &mut World, time: f64) {
+        let Some(lerp) = self.lerp else {
+            return;
+        };
+
+        self.buffers.retain(|&id, buffer| {
+            let Some(value) = buffer.sample(time, lerp) else {
+                return true;
+            };
+
+            world.set(id, self.component, value).is_ok()
+        });
+    }
+
+    fn is_interpolated(&self) -> bool {
+        self.lerp.is_some()
+    }
+}
+
+/// Declares the components which are replicated from the server to the clients.
+///
+/// Components are identified by the order they are registered in, which must be the same for
+/// the server and its clients.
+pub struct Replication {
+    replicators: Vec<Box<dyn Replicator>>,
+    send_interval: Duration,
+    interest_radius: Option<f32>,
+    interpolation_delay: Duration,
+}
+
+impl Default for Replication {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Replication {
+    pub fn new() -> Self {
+        Self {
+            replicators: Vec::new(),
+            send_interval: Duration::from_millis(50),
+            interest_radius: None,
+            interpolation_delay: Duration::from_millis(100),
+        }
+    }
+
+    /// Replicates a component, which is applied as soon as it is received
+    pub fn with_component<T>(mut self, component: Component<T>) -> Self
+    where
+        T: ComponentValue + Clone + Serialize + DeserializeOwned,
+    {
+        self.replicators.push(Box::new(ComponentReplicator {
+            component,
+            lerp: None,
+            buffers: HashMap::new(),
+        }));
+        self
+    }
+
+    /// Replicates a component which is smoothly interpolated between the received snapshots on
+    /// the client, such as a position or rotation.
+    pub fn with_interpolated_component<T>(mut self, component: Component<T>) -> Self
+    where
+        T: ComponentValue + Clone + Serialize + DeserializeOwned + Interpolate,
+    {
+        self.replicators.push(Box::new(ComponentReplicator {
+            component,
+            lerp: Some(T::interpolate),
+            buffers: HashMap::new(),
+        }));
+        self
+    }
+
+    /// Set the number of snapshots sent per second
+    pub fn with_send_rate(mut self, rate: f32) -> Self {
+        self.send_interval = Duration::from_secs_f32(1.0 / rate);
+        self
+    }
+
+    /// Only replicate entities within `radius` of the entity owned by each connection.
+    ///
+    /// Entities without a transform, and connections without an
+    /// [`owner_connection`](crate::components::owner_connection) entity, are always replicated.
+    pub fn with_interest_radius(mut self, radius: f32) -> Self {
+        self.interest_radius = Some(radius);
+        self
+    }
+
+    /// Set how far behind the latest snapshot interpolated components are played back.
+    ///
+    /// Should be a few times the send interval to tolerate lost snapshots.
+    pub fn with_interpolation_delay(mut self, delay: Duration) -> Self {
+        self.interpolation_delay = delay;
+        self
+    }
+
+    /// Serializes the replicated entities.
+    ///
+    /// Components which do not fit in a snapshot part are left out, and added to `oversized` to
+    /// only warn once.
+    fn capture(
+        &self,
+        world: &World,
+        oversized: &mut BTreeSet<ComponentIndex>,
+    ) -> anyhow::Result<Vec<(u64, Option<Vec3>, EntityState)>> {
+        Query::new((entity_ids(), world_transform().opt()))
+            .with(replicated())
+            .borrow(world)
+            .iter()
+            .map(|(id, transform)| {
+                let mut state = EntityState::new();
+                for (index, replicator) in self.replicators.iter().enumerate() {
+                    let index = index as ComponentIndex;
+                    let Some(value) = replicator.serialize(world, id)? else {
+                        continue;
+                    };
+
+                    if value.len() > MAX_COMPONENT_SIZE {
+                        if oversized.insert(index) {
+                            tracing::warn!(
+                                index,
+                                size = value.len(),
+                                "Component is too large to be replicated"
+                            );
+                        }
+
+                        continue;
+                    }
+
+                    state.insert(index, value);
+                }
+
+                Ok((id.as_bits(), transform.map(|v| v.w_axis.truncate()), state))
+            })
+            .collect()
+    }
+}
+
+#[derive(Default)]
+struct ClientView {
+    /// The latest snapshot acknowledged by the client
+    baseline: Option<(u32, WorldState)>,
+    sent: BTreeMap<u32, WorldState>,
+}
+
+#[derive(Default)]
+struct Server {
+    tick: u32,
+    time: f64,
+    elapsed: Duration,
+    clients: HashMap<ConnectionId, ClientView>,
+    /// Components which have been too large to replicate
+    oversized: BTreeSet<ComponentIndex>,
+}
+
+impl Server {
+    fn update(
+        &mut self,
+        world: &World,
+        replication: &Replication,
+        dt: Duration,
+    ) -> anyhow::Result<()> {
+        self.time += dt.as_secs_f64();
+        self.elapsed += dt;
+
+        if self.elapsed < replication.send_interval {
+            return Ok(());
+        }
+
+        self.elapsed = Duration::ZERO;
+        self.tick += 1;
+
+        let network = world.get(engine(), network())?;
+        let entities = replication.capture(world, &mut self.oversized)?;
+
+        let centers = Query::new((owner_connection(), world_transform()))
+            .borrow(world)
+            .iter()
+            .map(|(&connection, transform)| (connection, transform.w_axis.truncate()))
+            .collect::<HashMap<_, _>>();
+
+        for connection in network.connections() {
+            let center = replication
+                .interest_radius
+                .zip(centers.get(&connection).copied());
+
+            let current = entities
+                .iter()
+                .filter(|(_, position, _)| match (center, position) {
+                    (Some((radius, center)), Some(position)) => position.distance(center) <= radius,
+                    _ => true,
+                })
+                .map(|(id, _, state)| (*id, state.clone()))
+                .collect::<WorldState>();
+
+            let view = self.clients.entry(connection).or_default();
+
+            // The client no longer keeps the baseline, so start over with a full snapshot
+            if view
+                .baseline
+                .as_ref()
+                .is_some_and(|(tick, _)| self.tick - tick >= HISTORY)
+            {
+                view.baseline = None;
+            }
+
+            let (baseline, delta) = match &view.baseline {
+                Some((tick, state)) => (Some(*tick), Delta::new(state, &current)),
+                None => (None, Delta::new(&WorldState::new(), &current)),
+            };
+
+            let parts = delta.split();
+            let count = u16::try_from(parts.len())?;
+
+            for (part, delta) in parts.into_iter().enumerate() {
+                network.send(
+                    connection,
+                    &SnapshotPart {
+                        tick: self.tick,
+                        baseline,
+                        time: self.time,
+                        part: part as u16,
+                        parts: count,
+                        delta,
+                    },
+                )?;
+            }
+
+            view.sent.insert(self.tick, current);
+            while view.sent.len() > HISTORY as usize {
+                view.sent.pop_first();
+            }
+        }
+
+        Ok(())
+    }
+
+    fn acknowledge(&mut self, connection: ConnectionId, tick: u32) {
+        let Some(view) = self.clients.get_mut(&connection) else {
+            return;
+        };
+
+        if let Some(state) = view.sent.remove(&tick) {
+            view.sent.retain(|&v, _| v > tick);
+            view.baseline = Some((tick, state));
+        }
+    }
+}
+
+struct Assembly {
+    baseline: Option<u32>,
+    time: f64,
+    parts: Vec<Option<Delta>>,
+}
+
+#[derive(Default)]
+struct Client {
+    /// Reconstructed snapshots, used as baselines
+    states: BTreeMap<u32, WorldState>,
+    assembling: BTreeMap<u32, Assembly>,
+    latest: Option<u32>,
+    /// Maps the server entities to the local entities
+    entities: HashMap<u64, Entity>,
+    latest_time: f64,
+    playback_time: Option<f64>,
+}
+
+impl Client {
+    fn receive(
+        &mut self,
+        world: &mut World,
+        replication: &mut Replication,
+        connection: ConnectionId,
+        part: SnapshotPart,
+    ) -> anyhow::Result<()> {
+        let tick = part.tick;
+        if self.latest.is_some_and(|v| tick <= v) {
+            return Ok(());
+        }
+
+        let assembly = self.assembling.entry(tick).or_insert_with(|| Assembly {
+            baseline: part.baseline,
+            time: part.time,
+            parts: vec![None; part.parts as usize],
+        });
+
+        let Some(slot) = assembly.parts.get_mut(part.part as usize) else {
+            anyhow::bail!("Invalid snapshot part {} of {}", part.part, part.parts);
+        };
+
+        *slot = Some(part.delta);
+
+        if assembly.parts.iter().any(Option::is_none) {
+            return Ok(());
+        }
+
+        let assembly = self.assembling.remove(&tick).unwrap();
+        self.assembling.retain(|&v, _| v > tick);
+
+        let mut state = match assembly.baseline {
+            Some(baseline) => match self.states.get(&baseline) {
+                Some(state) => state.clone(),
+                None => {
+                    tracing::debug!(tick, baseline, "Missing snapshot baseline");
+                    return Ok(());
+                }
+            },
+            None => WorldState::new(),
+        };
+
+        assembly
+            .parts
+            .into_iter()
+            .flatten()
+            .for_each(|delta| delta.apply(&mut state));
+
+        world
+            .get(engine(), network())?
+            .send(connection, &SnapshotAck(tick))?;
+
+        self.apply(world, replication, &state, assembly.time);
+
+        self.states.insert(tick, state);
+        self.states.retain(|&v, _| v + HISTORY > tick);
+        self.latest = Some(tick);
+        self.latest_time = assembly.time;
+        self.playback_time
+            .get_or_insert(assembly.time - replication.interpolation_delay.as_secs_f64());
+
+        Ok(())
+    }
+
+    fn apply(
+        &mut self,
+        world: &mut World,
+        replication: &mut Replication,
+        state: &WorldState,
+        time: f64,
+    ) {
+        // The state which was applied last, to only touch the components which changed
+        let previous = self.latest.and_then(|tick| self.states.get(&tick));
+
+        for (&key, components) in state {
+            let (id, previous) = match self.entities.get(&key) {
+                Some(&id) if world.is_alive(id) => (id, previous.and_then(|v| v.get(&key))),
+                _ => {
+                    let id = Entity::builder().spawn(world);
+                    self.entities.insert(key, id);
+                    (id, None)
+                }
+            };
+
+            for (index, replicator) in replication.replicators.iter_mut().enumerate() {
+                let index = index as ComponentIndex;
+                let before = previous.and_then(|v| v.get(&index));
+
+                match components.get(&index) {
+                    // Interpolated components are buffered even when unchanged, so that the
+                    // playback holds still
+                    Some(payload) if before == Some(payload) && !replicator.is_interpolated() => {}
+                    Some(payload) => {
+                        if let Err(err) = replicator.apply(world, id, payload, time) {
+                            tracing::warn!("Failed to apply replicated component: {err:?}");
+                        }
+                    }
+                    None if before.is_some() => replicator.remove(world, id),
+                    None => {}
+                }
+            }
+        }
+
+        self.entities.retain(|key, id| {
+            if state.contains_key(key) {
+                return true;
+            }
+
+            world.despawn(*id).ok();
+            false
+        });
+    }
+
+    fn update(&mut self, world: &mut World, replication: &mut Replication, dt: Duration) {
+        let Some(time) = &mut self.playback_time else {
+            return;
+        };
+
+        let delay = replication.interpolation_delay.as_secs_f64();
+        *time += dt.as_secs_f64();
+
+        // Catch up if the playback falls too far behind, such as after a stall
+        if *time < self.latest_time - delay * 3.0 {
+            *time = self.latest_time - delay;
+        }
+
+        *time = time.min(self.latest_time);
+
+        for replicator in &mut replication.replicators {
+            replicator.interpolate(world, *time);
+        }
+    }
+}
+
+enum Role {
+    Server(Server),
+    Client(Client),
+}
+
+/// Replicates the [`replicated`](crate::components::replicated) entities of a server to its
+/// clients.
+///
+/// Each snapshot only contains the changes since the last snapshot acknowledged by the client.
+/// Requires a [`NetworkLayer`] with [`NetworkLayer::with_replication`].
+pub struct ReplicationLayer {
+    replication: Replication,
+    role: Role,
+}
+
+impl ReplicationLayer {
+    pub fn server(replication: Replication) -> Self {
+        Self {
+            replication,
+            role: Role::Server(Server::default()),
+        }
+    }
+
+    pub fn client(replication: Replication) -> Self {
+        Self {
+            replication,
+            role: Role::Client(Client::default()),
+        }
+    }
+}
+
+impl Layer for ReplicationLayer {
+    fn register(
+        &mut self,
+        _: &mut World,
+        _: &AssetCache,
+        mut events: EventRegisterContext<Self>,
+    ) -> anyhow::Result<()>
+    where
+        Self: Sized,
+    {
+        events.subscribe(|this, ctx, e: &TickEvent| match &mut this.role {
+            Role::Server(server) => server.update(ctx.world, &this.replication, e.0),
+            Role::Client(client) => {
+                client.update(ctx.world, &mut this.replication, e.0);
+                Ok(())
+            }
+        });
+
+        events.subscribe(|this, ctx, e: &Received<SnapshotPart>| {
+            let Role::Client(client) = &mut this.role else {
+                return Ok(());
+            };
+
+            client.receive(
+                ctx.world,
+                &mut this.replication,
+                e.connection,
+                e.message.clone(),
+            )
+        });
+
+        events.subscribe(|this, _, e: &Received<SnapshotAck>| {
+            if let Role::Server(server) = &mut this.role {
+                server.acknowledge(e.connection, e.message.0);
+            }
+
+            Ok(())
+        });
+
+        events.subscribe(|this, _, e: &PeerDisconnected| {
+            if let Role::Server(server) = &mut this.role {
+                server.clients.remove(&e.0);
+            }
+
+            Ok(())
+        });
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn entity(components: &[(ComponentIndex, &[u8])]) -> EntityState {
+        components.iter().map(|(k, v)| (*k, v.to_vec())).collect()
+    }
+
+    #[test]
+    fn delta() {
+        let baseline = WorldState::from([
+            (1, entity(&[(0, &[1, 2]), (1, &[3])])),
+            (2, entity(&[(0, &[4])])),
+        ]);
+
+        let current = WorldState::from([
+            (1, entity(&[(0, &[1, 2])])),
+            (3, entity(&[(0, &[5]), (1, &[6])])),
+        ]);
+
+        let delta = Delta::new(&baseline, &current);
+        assert_eq!(delta.updated, [(3, vec![(0, vec![5]), (1, vec![6])])]);
+        assert_eq!(delta.removed, [(1, vec![1])]);
+        assert_eq!(delta.despawned, [2]);
+
+        let mut state = baseline.clone();
+        delta.apply(&mut state);
+        assert_eq!(state, current);
+    }
+
+    #[test]
+    fn split() {
+        let current = (0..16)
+            .map(|id| (id, entity(&[(0, &[0; 200])])))
+            .collect::<WorldState>();
+
+        let parts = Delta::new(&WorldState::new(), &current).split();
+        assert!(parts.len() > 1);
+
+        let mut state = WorldState::new();
+        parts.into_iter().for_each(|v| v.apply(&mut state));
+        assert_eq!(state, current);
+    }
+
+    #[test]
+    fn split_entity() {
+        let current = WorldState::from([(1, (0..4).map(|index| (index, vec![0; 400])).collect())]);
+
+        let parts = Delta::new(&WorldState::new(), &current).split();
+        assert_eq!(parts.len(), 2);
+        assert!(parts
+            .iter()
+            .all(|v| codec().serialize(v).unwrap().len() <= MAX_PART_SIZE));
+
+        let mut state = WorldState::new();
+        parts.into_iter().for_each(|v| v.apply(&mut state));
+        assert_eq!(state, current);
+    }
+
+    flax::component! {
+        health: u32,
+        blob: Vec<u8>,
+    }
+
+    #[test]
+    fn oversized() {
+        let mut world = World::new();
+        let id = Entity::builder()
+            .set(replicated(), ())
+            .set(health(), 5)
+            .set(blob(), vec![0; MAX_COMPONENT_SIZE + 1])
+            .spawn(&mut world);
+
+        let replication = Replication::new()
+            .with_component(health())
+            .with_component(blob());
+
+        let mut oversized = BTreeSet::new();
+        let entities = replication.capture(&world, &mut oversized).unwrap();
+
+        assert_eq!(
+            entities,
+            [(
+                id.as_bits(),
+                None,
+                entity(&[(0, &codec().serialize(&5u32).unwrap())])
+            )]
+        );
+        assert_eq!(oversized, BTreeSet::from([1]));
+    }
+
+    #[test]
+    fn apply_changed() {
+        let mut world = World::new();
+        let mut replication = Replication::new().with_component(health());
+        let mut client = Client::default();
+
+        let mut modified = Query::new(entity_ids()).filter(health().modified());
+
+        let snapshot = |value: u32| {
+            WorldState::from([(1, entity(&[(0, &codec().serialize(&value).unwrap())]))])
+        };
+
+        for (tick, value, changed) in [(1, 5, 1), (2, 5, 0), (3, 6, 1)] {
+            let state = snapshot(value);
+            client.apply(&mut world, &mut replication, &state, tick as f64);
+            client.states.insert(tick, state);
+            client.latest = Some(tick);
+
+            assert_eq!(modified.borrow(&world).iter().count(), changed);
+        }
+
+        let id = client.entities[&1];
+        assert_eq!(world.get_copy(id, health()).unwrap(), 6);
+    }
+}
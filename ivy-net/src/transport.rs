@@ -0,0 +1,141 @@
+use std::fmt::Display;
+
+use anyhow::Context;
+
+/// Identifies the connection to a peer
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ConnectionId(pub u64);
+
+impl Display for ConnectionId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "#{}", self.0)
+    }
+}
+
+/// How a packet is delivered
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Delivery {
+    /// Delivered exactly once and in order, resent until acknowledged
+    Reliable,
+    /// Sent once, and may be lost or arrive out of order. Used for frequently updated state
+    Unreliable,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TransportEvent {
+    Connected(ConnectionId),
+    Disconnected(ConnectionId),
+    Received {
+        connection: ConnectionId,
+        payload: Vec<u8>,
+    },
+}
+
+/// Sends and receives packets between peers.
+///
+/// Implemented for UDP by [`UdpTransport`](crate::UdpTransport), and within a process by
+/// [`MemoryTransport`]. QUIC and WebTransport are not provided; they can be supported by
+/// implementing this trait on top of a library such as `quinn`.
+pub trait Transport: 'static + Send {
+    fn send(
+        &mut self,
+        connection: ConnectionId,
+        delivery: Delivery,
+        payload: &[u8],
+    ) -> anyhow::Result<()>;
+
+    /// Receives pending packets, and maintains the connections
+    fn poll(&mut self, events: &mut Vec<TransportEvent>) -> anyhow::Result<()>;
+
+    fn disconnect(&mut self, connection: ConnectionId);
+
+    /// Returns the established connections
+    fn connections(&self) -> Vec<ConnectionId>;
+}
+
+/// Connects two peers within the same process, such as a local server and its host.
+///
+/// All packets are delivered reliably.
+pub struct MemoryTransport {
+    tx: flume::Sender<Vec<u8>>,
+    rx: flume::Receiver<Vec<u8>>,
+    connected: bool,
+    reported: bool,
+}
+
+impl MemoryTransport {
+    /// Returns two transports which are connected to each other
+    pub fn pair() -> (Self, Self) {
+        let (a_tx, a_rx) = flume::unbounded();
+        let (b_tx, b_rx) = flume::unbounded();
+
+        let new = |tx, rx| Self {
+            tx,
+            rx,
+            connected: true,
+            reported: false,
+        };
+
+        (new(a_tx, b_rx), new(b_tx, a_rx))
+    }
+
+    const CONNECTION: ConnectionId = ConnectionId(0);
+}
+
+impl Transport for MemoryTransport {
+    fn send(
+        &mut self,
+        connection: ConnectionId,
+        _: Delivery,
+        payload: &[u8],
+    ) -> anyhow::Result<()> {
+        anyhow::ensure!(
+            self.connected && connection == Self::CONNECTION,
+            "Not connected to {connection}"
+        );
+
+        self.tx
+            .send(payload.to_vec())
+            .ok()
+            .context("Peer disconnected")
+    }
+
+    fn poll(&mut self, events: &mut Vec<TransportEvent>) -> anyhow::Result<()> {
+        if !self.connected {
+            return Ok(());
+        }
+
+        if !self.reported {
+            events.push(TransportEvent::Connected(Self::CONNECTION));
+            self.reported = true;
+        }
+
+        events.extend(self.rx.drain().map(|payload| TransportEvent::Received {
+            connection: Self::CONNECTION,
+            payload,
+        }));
+
+        if self.rx.is_disconnected() {
+            self.connected = false;
+            events.push(TransportEvent::Disconnected(Self::CONNECTION));
+        }
+
+        Ok(())
+    }
+
+    fn disconnect(&mut self, connection: ConnectionId) {
+        if connection == Self::CONNECTION {
+            self.connected = false;
+            // Disconnects the peer
+            self.tx = flume::bounded(0).0;
+        }
+    }
+
+    fn connections(&self) -> Vec<ConnectionId> {
+        if self.connected {
+            vec![Self::CONNECTION]
+        } else {
+            vec![]
+        }
+    }
+}
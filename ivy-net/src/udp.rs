@@ -0,0 +1,568 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    io::ErrorKind,
+    net::{SocketAddr, ToSocketAddrs, UdpSocket},
+    time::{Duration, Instant},
+};
+
+use anyhow::Context;
+
+use crate::{ConnectionId, Delivery, Transport, TransportEvent};
+
+/// Identifies packets of this protocol, "IVYN"
+const PROTOCOL_ID: u32 = 0x4956_594e;
+/// Largest payload which fits in a single datagram without fragmentation
+pub const MAX_PAYLOAD: usize = 1200;
+
+const TIMEOUT: Duration = Duration::from_secs(10);
+const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(1);
+const RESEND_INTERVAL: Duration = Duration::from_millis(200);
+/// Number of reliable packets ahead of the next expected one which are buffered.
+///
+/// Packets further ahead are dropped without an ack, and resent by the peer later.
+const RECEIVE_WINDOW: u32 = 256;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Packet<'a> {
+    Connect,
+    Accept,
+    Disconnect,
+    Heartbeat,
+    Unreliable(&'a [u8]),
+    Reliable(u32, &'a [u8]),
+    Ack(u32),
+}
+
+impl<'a> Packet<'a> {
+    fn encode(&self, buf: &mut Vec<u8>) {
+        buf.clear();
+        buf.extend_from_slice(&PROTOCOL_ID.to_le_bytes());
+
+        match *self {
+            Packet::Connect => buf.push(0),
+            Packet::Accept => buf.push(1),
+            Packet::Disconnect => buf.push(2),
+            Packet::Heartbeat => buf.push(3),
+            Packet::Unreliable(payload) => {
+                buf.push(4);
+                buf.extend_from_slice(payload);
+            }
+            Packet::Reliable(seq, payload) => {
+                buf.push(5);
+                buf.extend_from_slice(&seq.to_le_bytes());
+                buf.extend_from_slice(payload);
+            }
+            Packet::Ack(seq) => {
+                buf.push(6);
+                buf.extend_from_slice(&seq.to_le_bytes());
+            }
+        }
+    }
+
+    fn decode(bytes: &'a [u8]) -> Option<Self> {
+        let (protocol, bytes) = bytes.split_first_chunk::<4>()?;
+        if u32::from_le_bytes(*protocol) != PROTOCOL_ID {
+            return None;
+        }
+
+        let (kind, body) = bytes.split_first()?;
+        let seq = || body.first_chunk::<4>().map(|v| u32::from_le_bytes(*v));
+
+        let packet = match kind {
+            0 => Packet::Connect,
+            1 => Packet::Accept,
+            2 => Packet::Disconnect,
+            3 => Packet::Heartbeat,
+            4 => Packet::Unreliable(body),
+            5 => Packet::Reliable(seq()?, &body[4..]),
+            6 => Packet::Ack(seq()?),
+            _ => return None,
+        };
+
+        Some(packet)
+    }
+}
+
+struct PendingPacket {
+    payload: Vec<u8>,
+    sent: Instant,
+}
+
+/// Resends reliable packets until acknowledged, and delivers them in order
+#[derive(Default)]
+struct ReliableChannel {
+    next_send: u32,
+    unacked: BTreeMap<u32, PendingPacket>,
+    next_receive: u32,
+    /// Packets received ahead of the next expected one
+    received: BTreeMap<u32, Vec<u8>>,
+}
+
+impl ReliableChannel {
+    /// Returns the sequence number of the packet
+    fn push(&mut self, payload: Vec<u8>, now: Instant) -> u32 {
+        let seq = self.next_send;
+        self.next_send = self.next_send.wrapping_add(1);
+        self.unacked
+            .insert(seq, PendingPacket { payload, sent: now });
+        seq
+    }
+
+    fn ack(&mut self, seq: u32) {
+        self.unacked.remove(&seq);
+    }
+
+    /// Returns the packets which have not been acknowledged in time
+    fn resends(&mut self, now: Instant) -> impl Iterator<Item = (u32, &[u8])> {
+        self.unacked
+            .iter_mut()
+            .filter(move |(_, v)| now.duration_since(v.sent) >= RESEND_INTERVAL)
+            .map(move |(&seq, v)| {
+                v.sent = now;
+                (seq, &*v.payload)
+            })
+    }
+
+    /// Returns the payloads which can be delivered after receiving `seq`, or `None` if the
+    /// packet is too far ahead to be buffered
+    fn receive(&mut self, seq: u32, payload: &[u8]) -> Option<Vec<Vec<u8>>> {
+        let ahead = seq.wrapping_sub(self.next_receive);
+
+        // Duplicate of an already delivered packet
+        if ahead > u32::MAX / 2 {
+            return Some(vec![]);
+        }
+
+        if ahead >= RECEIVE_WINDOW {
+            return None;
+        }
+
+        self.received.insert(seq, payload.to_vec());
+
+        let mut delivered = Vec::new();
+        while let Some(payload) = self.received.remove(&self.next_receive) {
+            delivered.push(payload);
+            self.next_receive = self.next_receive.wrapping_add(1);
+        }
+
+        Some(delivered)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ConnectionState {
+    /// Waiting for the server to accept the connection
+    Connecting,
+    Connected,
+}
+
+struct Connection {
+    addr: SocketAddr,
+    state: ConnectionState,
+    last_received: Instant,
+    last_sent: Instant,
+    reliable: ReliableChannel,
+}
+
+impl Connection {
+    fn new(addr: SocketAddr, state: ConnectionState, now: Instant) -> Self {
+        Self {
+            addr,
+            state,
+            last_received: now,
+            last_sent: now,
+            reliable: ReliableChannel::default(),
+        }
+    }
+}
+
+/// Transport over UDP, with its own reliability.
+///
+/// Payloads are limited to [`MAX_PAYLOAD`] bytes, as they are not fragmented.
+pub struct UdpTransport {
+    socket: UdpSocket,
+    /// Accepts new connections
+    is_server: bool,
+    max_connections: usize,
+    connections: BTreeMap<ConnectionId, Connection>,
+    addrs: HashMap<SocketAddr, ConnectionId>,
+    next_id: u64,
+    buf: Vec<u8>,
+}
+
+impl UdpTransport {
+    /// Listens for clients on `addr`
+    pub fn server(addr: impl ToSocketAddrs) -> anyhow::Result<Self> {
+        let socket = UdpSocket::bind(addr).context("Failed to bind server socket")?;
+        Self::new(socket, true)
+    }
+
+    /// Connects to the server at `addr`.
+    ///
+    /// The connection is established during the following polls, or disconnected if the server
+    /// does not respond.
+    pub fn client(addr: impl ToSocketAddrs) -> anyhow::Result<Self> {
+        let server = addr
+            .to_socket_addrs()?
+            .next()
+            .context("No address to connect to")?;
+
+        let local: SocketAddr = if server.is_ipv4() {
+            ([0, 0, 0, 0], 0).into()
+        } else {
+            ([0u16; 8], 0).into()
+        };
+
+        let socket = UdpSocket::bind(local).context("Failed to bind client socket")?;
+        let mut transport = Self::new(socket, false)?;
+
+        let id = transport.add_connection(server, ConnectionState::Connecting);
+        transport.send_packet(id, Packet::Connect)?;
+
+        Ok(transport)
+    }
+
+    fn new(socket: UdpSocket, is_server: bool) -> anyhow::Result<Self> {
+        socket.set_nonblocking(true)?;
+
+        Ok(Self {
+            socket,
+            is_server,
+            max_connections: 64,
+            connections: BTreeMap::new(),
+            addrs: HashMap::new(),
+            next_id: 0,
+            buf: Vec::with_capacity(MAX_PAYLOAD + 16),
+        })
+    }
+
+    /// Set the maximum number of clients a server accepts
+    pub fn with_max_connections(mut self, max_connections: usize) -> Self {
+        self.max_connections = max_connections;
+        self
+    }
+
+    pub fn local_addr(&self) -> anyhow::Result<SocketAddr> {
+        Ok(self.socket.local_addr()?)
+    }
+
+    fn add_connection(&mut self, addr: SocketAddr, state: ConnectionState) -> ConnectionId {
+        let id = ConnectionId(self.next_id);
+        self.next_id += 1;
+
+        self.connections
+            .insert(id, Connection::new(addr, state, Instant::now()));
+        self.addrs.insert(addr, id);
+        id
+    }
+
+    fn remove_connection(&mut self, id: ConnectionId) -> Option<Connection> {
+        let conn = self.connections.remove(&id)?;
+        self.addrs.remove(&conn.addr);
+        Some(conn)
+    }
+
+    fn send_packet(&mut self, id: ConnectionId, packet: Packet) -> anyhow::Result<()> {
+        let conn = self
+            .connections
+            .get_mut(&id)
+            .with_context(|| format!("Unknown connection {id}"))?;
+
+        packet.encode(&mut self.buf);
+        conn.last_sent = Instant::now();
+
+        match self.socket.send_to(&self.buf, conn.addr) {
+            Ok(_) => Ok(()),
+            // The packet is lost, which the protocol handles
+            Err(err) if err.kind() == ErrorKind::WouldBlock => Ok(()),
+            Err(err) => Err(err).with_context(|| format!("Failed to send to {}", conn.addr)),
+        }
+    }
+
+    fn handle_packet(
+        &mut self,
+        addr: SocketAddr,
+        packet: Packet,
+        events: &mut Vec<TransportEvent>,
+    ) -> anyhow::Result<()> {
+        let id = match (self.addrs.get(&addr), packet) {
+            (Some(&id), _) => id,
+            (None, Packet::Connect) if self.is_server => {
+                if self.connections.len() >= self.max_connections {
+                    tracing::warn!(%addr, "Refusing connection, server is full");
+                    return Ok(());
+                }
+
+                let id = self.add_connection(addr, ConnectionState::Connected);
+                events.push(TransportEvent::Connected(id));
+                id
+            }
+            // Not connected
+            _ => return Ok(()),
+        };
+
+        let now = Instant::now();
+        let conn = self.connections.get_mut(&id).unwrap();
+        conn.last_received = now;
+
+        match packet {
+            // Resent if the accept was lost
+            Packet::Connect => self.send_packet(id, Packet::Accept)?,
+            Packet::Accept => {
+                if conn.state == ConnectionState::Connecting {
+                    conn.state = ConnectionState::Connected;
+                    events.push(TransportEvent::Connected(id));
+                }
+            }
+            Packet::Disconnect => {
+                self.remove_connection(id);
+                events.push(TransportEvent::Disconnected(id));
+            }
+            Packet::Heartbeat => {}
+            Packet::Unreliable(payload) => {
+                events.push(TransportEvent::Received {
+                    connection: id,
+                    payload: payload.to_vec(),
+                });
+            }
+            Packet::Reliable(seq, payload) => {
+                let Some(delivered) = conn.reliable.receive(seq, payload) else {
+                    tracing::debug!(%id, seq, "Dropping reliable packet outside the window");
+                    return Ok(());
+                };
+
+                events.extend(
+                    delivered
+                        .into_iter()
+                        .map(|payload| TransportEvent::Received {
+                            connection: id,
+                            payload,
+                        }),
+                );
+
+                self.send_packet(id, Packet::Ack(seq))?;
+            }
+            Packet::Ack(seq) => conn.reliable.ack(seq),
+        }
+
+        Ok(())
+    }
+
+    /// Times out silent connections, and sends heartbeats and unacknowledged packets
+    fn maintain(&mut self, events: &mut Vec<TransportEvent>) -> anyhow::Result<()> {
+        let now = Instant::now();
+
+        let timed_out = self
+            .connections
+            .iter()
+            .filter(|(_, v)| now.duration_since(v.last_received) > TIMEOUT)
+            .map(|(&id, _)| id)
+            .collect::<Vec<_>>();
+
+        for id in timed_out {
+            tracing::info!(%id, "Connection timed out");
+            self.remove_connection(id);
+            events.push(TransportEvent::Disconnected(id));
+        }
+
+        let mut packets = Vec::new();
+        for (&id, conn) in &mut self.connections {
+            match conn.state {
+                ConnectionState::Connecting => {
+                    if now.duration_since(conn.last_sent) >= RESEND_INTERVAL {
+                        packets.push((id, Packet::Connect));
+                    }
+                }
+                ConnectionState::Connected => {
+                    let resends = conn
+                        .reliable
+                        .resends(now)
+                        .map(|(seq, payload)| (seq, payload.to_vec()))
+                        .collect::<Vec<_>>();
+
+                    for (seq, payload) in resends {
+                        Packet::Reliable(seq, &payload).encode(&mut self.buf);
+                        self.socket.send_to(&self.buf, conn.addr).ok();
+                        conn.last_sent = now;
+                    }
+
+                    if now.duration_since(conn.last_sent) >= HEARTBEAT_INTERVAL {
+                        packets.push((id, Packet::Heartbeat));
+                    }
+                }
+            }
+        }
+
+        for (id, packet) in packets {
+            self.send_packet(id, packet)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Transport for UdpTransport {
+    fn send(
+        &mut self,
+        connection: ConnectionId,
+        delivery: Delivery,
+        payload: &[u8],
+    ) -> anyhow::Result<()> {
+        anyhow::ensure!(
+            payload.len() <= MAX_PAYLOAD,
+            "Payload of {} bytes exceeds the maximum of {MAX_PAYLOAD}",
+            payload.len()
+        );
+
+        let conn = self
+            .connections
+            .get_mut(&connection)
+            .filter(|v| v.state == ConnectionState::Connected)
+            .with_context(|| format!("Not connected to {connection}"))?;
+
+        match delivery {
+            Delivery::Reliable => {
+                let seq = conn.reliable.push(payload.to_vec(), Instant::now());
+                self.send_packet(connection, Packet::Reliable(seq, payload))
+            }
+            Delivery::Unreliable => self.send_packet(connection, Packet::Unreliable(payload)),
+        }
+    }
+
+    fn poll(&mut self, events: &mut Vec<TransportEvent>) -> anyhow::Result<()> {
+        let mut buf = [0; MAX_PAYLOAD + 16];
+
+        loop {
+            let (len, addr) = match self.socket.recv_from(&mut buf) {
+                Ok(v) => v,
+                Err(err) if err.kind() == ErrorKind::WouldBlock => break,
+                // Reported on some platforms when a previous send was not delivered
+                Err(err) if err.kind() == ErrorKind::ConnectionReset => continue,
+                Err(err) => return Err(err).context("Failed to receive packet"),
+            };
+
+            if let Some(packet) = Packet::decode(&buf[..len]) {
+                self.handle_packet(addr, packet, events)?;
+            }
+        }
+
+        self.maintain(events)
+    }
+
+    fn disconnect(&mut self, connection: ConnectionId) {
+        if self.connections.contains_key(&connection) {
+            // Best effort, the peer times out otherwise
+            self.send_packet(connection, Packet::Disconnect).ok();
+            self.remove_connection(connection);
+        }
+    }
+
+    fn connections(&self) -> Vec<ConnectionId> {
+        self.connections
+            .iter()
+            .filter(|(_, v)| v.state == ConnectionState::Connected)
+            .map(|(&id, _)| id)
+            .collect()
+    }
+}
+
+impl Drop for UdpTransport {
+    fn drop(&mut self) {
+        for id in self.connections.keys().copied().collect::<Vec<_>>() {
+            self.disconnect(id);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn packets() {
+        let mut buf = Vec::new();
+
+        for packet in [
+            Packet::Connect,
+            Packet::Heartbeat,
+            Packet::Unreliable(b"state"),
+            Packet::Reliable(7, b"chat"),
+            Packet::Ack(7),
+        ] {
+            packet.encode(&mut buf);
+            assert_eq!(Packet::decode(&buf), Some(packet));
+        }
+
+        assert_eq!(Packet::decode(&[0, 0, 0, 0, 1]), None);
+        assert_eq!(Packet::decode(&buf[..6]), None);
+    }
+
+    #[test]
+    fn reliable_order() {
+        let mut sender = ReliableChannel::default();
+        let mut receiver = ReliableChannel::default();
+
+        let now = Instant::now();
+        let a = sender.push(b"a".to_vec(), now);
+        let b = sender.push(b"b".to_vec(), now);
+        let c = sender.push(b"c".to_vec(), now);
+
+        assert_eq!(receiver.receive(b, b"b"), Some(vec![]));
+        assert_eq!(
+            receiver.receive(a, b"a"),
+            Some(vec![b"a".to_vec(), b"b".to_vec()])
+        );
+        // Duplicate
+        assert_eq!(receiver.receive(a, b"a"), Some(vec![]));
+
+        sender.ack(a);
+        sender.ack(b);
+
+        let later = now + RESEND_INTERVAL;
+        assert_eq!(
+            sender.resends(later).collect::<Vec<_>>(),
+            [(c, b"c".as_slice())]
+        );
+        assert_eq!(sender.resends(later).count(), 0);
+
+        assert_eq!(receiver.receive(c, b"c"), Some(vec![b"c".to_vec()]));
+    }
+
+    #[test]
+    fn reliable_window() {
+        let mut receiver = ReliableChannel::default();
+
+        assert_eq!(receiver.receive(RECEIVE_WINDOW, b"far"), None);
+        assert_eq!(receiver.receive(u32::MAX / 2, b"far"), None);
+        assert_eq!(receiver.receive(RECEIVE_WINDOW - 1, b"last"), Some(vec![]));
+        assert_eq!(receiver.received.len(), 1);
+    }
+
+    #[test]
+    fn reliable_wrap() {
+        let mut sender = ReliableChannel {
+            next_send: u32::MAX - 1,
+            ..Default::default()
+        };
+        let mut receiver = ReliableChannel {
+            next_receive: u32::MAX - 1,
+            ..Default::default()
+        };
+
+        let now = Instant::now();
+        let seqs = [b"a", b"b", b"c"].map(|v| sender.push(v.to_vec(), now));
+        assert_eq!(seqs, [u32::MAX - 1, u32::MAX, 0]);
+
+        assert_eq!(receiver.receive(seqs[2], b"c"), Some(vec![]));
+        assert_eq!(receiver.receive(seqs[0], b"a"), Some(vec![b"a".to_vec()]));
+        assert_eq!(
+            receiver.receive(seqs[1], b"b"),
+            Some(vec![b"b".to_vec(), b"c".to_vec()])
+        );
+
+        // Duplicates from before the wrap
+        assert_eq!(receiver.receive(seqs[0], b"a"), Some(vec![]));
+        assert_eq!(receiver.next_receive, 1);
+        assert!(receiver.received.is_empty());
+    }
+}
@@ -0,0 +1,73 @@
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use flax::World;
+use glam::Vec3;
+use ivy_physics::{
+    rapier3d::prelude::{ColliderBuilder, RigidBodyBuilder},
+    state::{PhysicsState, PhysicsStateConfiguration},
+};
+use rand::{Rng, SeedableRng};
+use rand_pcg::Pcg32;
+
+/// A static floor with a pile of dynamic boxes and spheres dropped onto it
+fn pile_scene(world: &mut World, count: usize) -> PhysicsState {
+    let mut state = PhysicsState::new(&PhysicsStateConfiguration::default(), 0.02);
+    let mut rng = Pcg32::seed_from_u64(42);
+
+    let floor = world.spawn();
+    let floor_body = state.add_body(floor, RigidBodyBuilder::fixed().build());
+    state.attach_collider(
+        floor,
+        ColliderBuilder::cuboid(100.0, 0.5, 100.0).build(),
+        floor_body,
+    );
+
+    let extent = (count as f32).cbrt() * 1.5;
+
+    for i in 0..count {
+        let id = world.spawn();
+        let pos = Vec3::new(
+            rng.gen_range(-extent..extent),
+            rng.gen_range(1.0..extent * 2.0 + 1.0),
+            rng.gen_range(-extent..extent),
+        );
+
+        let body = state.add_body(
+            id,
+            RigidBodyBuilder::dynamic().translation(pos.into()).build(),
+        );
+
+        let collider = if i % 2 == 0 {
+            ColliderBuilder::cuboid(0.5, 0.5, 0.5)
+        } else {
+            ColliderBuilder::ball(0.5)
+        };
+
+        state.attach_collider(id, collider.build(), body);
+    }
+
+    state
+}
+
+fn broadphase(c: &mut Criterion) {
+    let mut group = c.benchmark_group("physics_step");
+    group.sample_size(20);
+
+    for count in [100, 1000, 5000] {
+        let mut world = World::new();
+        let mut state = pile_scene(&mut world, count);
+
+        // Settle the pile so that the benchmark measures resting contacts rather than free fall
+        for _ in 0..50 {
+            state.step();
+        }
+
+        group.bench_with_input(BenchmarkId::from_parameter(count), &count, |b, _| {
+            b.iter(|| state.step())
+        });
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, broadphase);
+criterion_main!(benches);
@@ -0,0 +1,246 @@
+//! Force fields which affect all bodies inside an area, such as wind volumes, bodies of water,
+//! and explosions.
+//!
+//! The effects are applied through the [`Effector`](crate::Effector) of each overlapping body.
+use flax::{entity_ids, BoxedSystem, CommandBuffer, Query, QueryBorrow, System};
+use glam::{Mat4, Vec3};
+use ivy_core::components::{position, world_transform};
+
+use crate::{
+    components::{area_effector, effector, explosion, mass, velocity},
+    Effector,
+};
+
+/// The volume of an area effector, in the local space of the entity
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum AreaShape {
+    Sphere { radius: f32 },
+    Cuboid { half_extents: Vec3 },
+}
+
+impl AreaShape {
+    pub fn contains(&self, point: Vec3) -> bool {
+        match *self {
+            AreaShape::Sphere { radius } => point.length_squared() <= radius * radius,
+            AreaShape::Cuboid { half_extents } => point.abs().cmple(half_extents).all(),
+        }
+    }
+}
+
+/// The effect applied to bodies inside an area
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum AreaEffect {
+    /// Accelerates bodies towards the center of the area, such as a planet or black hole.
+    ///
+    /// The acceleration falls off linearly to zero at `radius`.
+    RadialGravity { strength: f32, radius: f32 },
+    /// Drags bodies towards moving at `velocity` in world space
+    Wind { velocity: Vec3, drag: f32 },
+    /// Pushes bodies up proportional to how far they are below the surface at the top of the
+    /// area, and slows them down while submerged
+    Buoyancy { strength: f32, drag: f32 },
+}
+
+/// Applies an effect to all bodies inside of the area
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AreaEffector {
+    pub shape: AreaShape,
+    pub effect: AreaEffect,
+}
+
+impl AreaEffector {
+    pub fn new(shape: AreaShape, effect: AreaEffect) -> Self {
+        Self { shape, effect }
+    }
+
+    /// Returns the acceleration of a body at `local_pos` inside the area.
+    ///
+    /// `velocity` and the returned acceleration are in world space.
+    pub fn acceleration(&self, transform: &Mat4, local_pos: Vec3, velocity: Vec3) -> Vec3 {
+        match self.effect {
+            AreaEffect::RadialGravity { strength, radius } => {
+                let distance = local_pos.length();
+                let falloff = (1.0 - distance / radius).clamp(0.0, 1.0);
+                let dir = transform.transform_vector3(-local_pos).normalize_or_zero();
+
+                dir * strength * falloff
+            }
+            AreaEffect::Wind {
+                velocity: wind,
+                drag,
+            } => (wind - velocity) * drag,
+            AreaEffect::Buoyancy { strength, drag } => {
+                let surface = match self.shape {
+                    AreaShape::Sphere { radius } => radius,
+                    AreaShape::Cuboid { half_extents } => half_extents.y,
+                };
+
+                let depth = (surface - local_pos.y).max(0.0);
+                let up = transform.transform_vector3(Vec3::Y).normalize_or_zero();
+
+                up * depth * strength - velocity * drag
+            }
+        }
+    }
+}
+
+/// Applies a single impulse to all bodies within `radius`, after which the component is removed
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Explosion {
+    /// The impulse at the center, falling off linearly to zero at `radius`
+    pub impulse: f32,
+    pub radius: f32,
+}
+
+impl Explosion {
+    pub fn new(impulse: f32, radius: f32) -> Self {
+        Self { impulse, radius }
+    }
+
+    /// Returns the impulse applied to a body at `offset` from the center
+    pub fn impulse_at(&self, offset: Vec3) -> Vec3 {
+        let falloff = 1.0 - offset.length() / self.radius;
+        if falloff <= 0.0 {
+            return Vec3::ZERO;
+        }
+
+        offset.normalize_or(Vec3::Y) * self.impulse * falloff
+    }
+}
+
+#[allow(clippy::type_complexity)]
+pub fn area_effector_system() -> BoxedSystem {
+    System::builder()
+        .with_query(Query::new((world_transform(), area_effector())))
+        .with_query(Query::new((position(), velocity(), effector().as_mut())))
+        .build(|mut areas: QueryBorrow<_>, mut bodies: QueryBorrow<_>| {
+            let areas = areas
+                .iter()
+                .map(|(transform, area)| {
+                    let transform: Mat4 = *transform;
+                    let area: AreaEffector = *area;
+                    (transform, transform.inverse(), area)
+                })
+                .collect::<Vec<_>>();
+
+            if areas.is_empty() {
+                return;
+            }
+
+            for (pos, vel, body_effector) in &mut bodies {
+                let pos: Vec3 = *pos;
+                let vel: Vec3 = *vel;
+                let body_effector: &mut Effector = body_effector;
+
+                for (transform, inv_transform, area) in &areas {
+                    let local_pos = inv_transform.transform_point3(pos);
+                    if !area.shape.contains(local_pos) {
+                        continue;
+                    }
+
+                    body_effector
+                        .apply_acceleration(area.acceleration(transform, local_pos, vel), true);
+                }
+            }
+        })
+        .boxed()
+}
+
+#[allow(clippy::type_complexity)]
+pub fn explosion_system() -> BoxedSystem {
+    System::builder()
+        .with_cmd_mut()
+        .with_query(Query::new((entity_ids(), world_transform(), explosion())))
+        .with_query(Query::new((
+            position(),
+            mass().opt_or(1.0),
+            effector().as_mut(),
+        )))
+        .build(
+            |cmd: &mut CommandBuffer,
+             mut explosions: QueryBorrow<_>,
+             mut bodies: QueryBorrow<_>| {
+                for (id, transform, blast) in &mut explosions {
+                    let transform: &Mat4 = transform;
+                    let blast: &Explosion = blast;
+                    let center = transform.transform_point3(Vec3::ZERO);
+
+                    for (pos, mass, body_effector) in &mut bodies {
+                        let mass: f32 = *mass;
+                        let body_effector: &mut Effector = body_effector;
+
+                        let impulse = blast.impulse_at(*pos - center);
+                        if impulse != Vec3::ZERO {
+                            body_effector
+                                .apply_velocity_change(impulse / mass.max(f32::EPSILON), true);
+                        }
+                    }
+
+                    cmd.remove(id, explosion());
+                }
+            },
+        )
+        .boxed()
+}
+
+#[cfg(test)]
+mod test {
+    use glam::vec3;
+
+    use super::*;
+
+    #[test]
+    fn area_acceleration() {
+        let transform = Mat4::from_translation(vec3(0.0, 10.0, 0.0));
+
+        let planet = AreaEffector::new(
+            AreaShape::Sphere { radius: 4.0 },
+            AreaEffect::RadialGravity {
+                strength: 10.0,
+                radius: 4.0,
+            },
+        );
+
+        assert!(planet.shape.contains(vec3(2.0, 0.0, 0.0)));
+        assert!(!planet.shape.contains(vec3(3.0, 3.0, 0.0)));
+        assert_eq!(
+            planet.acceleration(&transform, vec3(2.0, 0.0, 0.0), Vec3::ZERO),
+            vec3(-5.0, 0.0, 0.0)
+        );
+
+        let water = AreaEffector::new(
+            AreaShape::Cuboid {
+                half_extents: Vec3::ONE,
+            },
+            AreaEffect::Buoyancy {
+                strength: 2.0,
+                drag: 0.5,
+            },
+        );
+
+        assert_eq!(
+            water.acceleration(&transform, vec3(0.0, -0.5, 0.0), vec3(0.0, -2.0, 0.0)),
+            vec3(0.0, 4.0, 0.0)
+        );
+        assert_eq!(
+            water.acceleration(&transform, vec3(0.0, 1.0, 0.0), Vec3::ZERO),
+            Vec3::ZERO
+        );
+    }
+
+    #[test]
+    fn explosion_falloff() {
+        let explosion = Explosion::new(10.0, 5.0);
+
+        assert_eq!(
+            explosion.impulse_at(vec3(0.0, 0.0, 2.5)),
+            vec3(0.0, 0.0, 5.0)
+        );
+        assert_eq!(explosion.impulse_at(vec3(6.0, 0.0, 0.0)), Vec3::ZERO);
+        assert_eq!(explosion.impulse_at(Vec3::ZERO), vec3(0.0, 10.0, 0.0));
+    }
+}
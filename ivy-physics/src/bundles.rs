@@ -1,17 +1,24 @@
 //! This module contains bundles and queries suitable for physics.
 use core::f32;
 
-use flax::EntityBuilder;
+use flax::{Entity, EntityBuilder};
 use glam::Vec3;
 use ivy_core::Bundle;
-use rapier3d::prelude::{LockedAxes, RigidBodyType, SharedShape};
+use nalgebra::{Point3, Unit, Vector3};
+use rapier3d::prelude::{
+    FixedJointBuilder, GenericJoint, JointAxis, LockedAxes, PrismaticJointBuilder,
+    RevoluteJointBuilder, RigidBodyType, RopeJointBuilder, SharedShape, SphericalJointBuilder,
+    SpringJointBuilder,
+};
 
 use crate::{
     components::{
-        angular_velocity, can_sleep, collider_shape, density, effector, friction, inertia_tensor,
-        locked_axes, mass, restitution, rigid_body_type, velocity,
+        angular_velocity, can_sleep, ccd_enabled, collider_shape, collision_layers, density,
+        effector, friction, impulse_joint, inertia_tensor, is_trigger, locked_axes, mass,
+        restitution, rigid_body_type, surface_type, velocity,
     },
-    Effector,
+    surface::SurfaceType,
+    CollisionLayers, Effector,
 };
 
 #[derive(Clone, Debug)]
@@ -20,6 +27,8 @@ use crate::{
 pub struct RigidBodyBundle {
     pub body_type: RigidBodyType,
     pub can_sleep: bool,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub ccd: bool,
     pub mass: f32,
     pub angular_mass: f32,
     pub locked_axes: Option<LockedAxes>,
@@ -37,6 +46,7 @@ impl RigidBodyBundle {
             angular_velocity: Vec3::ZERO,
             angular_mass: 0.0,
             can_sleep: true,
+            ccd: false,
             locked_axes: Default::default(),
         }
     }
@@ -57,6 +67,12 @@ impl RigidBodyBundle {
         Self::new(RigidBodyType::Fixed)
     }
 
+    /// A dynamic body using continuous collision detection, for fast and small objects such as
+    /// bullets
+    pub fn projectile() -> Self {
+        Self::dynamic().with_ccd(true).with_can_sleep(false)
+    }
+
     pub fn with_locked_axes(mut self, axes: LockedAxes) -> Self {
         self.locked_axes = Some(axes);
         self
@@ -91,6 +107,12 @@ impl RigidBodyBundle {
         self.can_sleep = can_sleep;
         self
     }
+
+    /// Set whether continuous collision detection is enabled
+    pub fn with_ccd(mut self, ccd: bool) -> Self {
+        self.ccd = ccd;
+        self
+    }
 }
 
 impl Bundle for RigidBodyBundle {
@@ -108,6 +130,10 @@ impl Bundle for RigidBodyBundle {
         if self.can_sleep {
             entity.set(can_sleep(), ());
         }
+
+        if self.ccd {
+            entity.set(ccd_enabled(), ());
+        }
     }
 }
 
@@ -118,6 +144,12 @@ pub struct ColliderBundle {
     density: f32,
     friction: f32,
     restitution: f32,
+    #[cfg_attr(feature = "serde", serde(default))]
+    surface: Option<SurfaceType>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    layers: Option<CollisionLayers>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    trigger: bool,
 }
 
 impl ColliderBundle {
@@ -127,9 +159,30 @@ impl ColliderBundle {
             density: 1.0,
             friction: 0.0,
             restitution: 0.0,
+            surface: None,
+            layers: None,
+            trigger: false,
         }
     }
 
+    /// Set the surface type
+    pub fn with_surface(mut self, surface: impl Into<SurfaceType>) -> Self {
+        self.surface = Some(surface.into());
+        self
+    }
+
+    /// Set the collision layers
+    pub fn with_layers(mut self, layers: CollisionLayers) -> Self {
+        self.layers = Some(layers);
+        self
+    }
+
+    /// Turns the collider into a trigger volume, which reports intersections instead of colliding
+    pub fn with_trigger(mut self, trigger: bool) -> Self {
+        self.trigger = trigger;
+        self
+    }
+
     /// Set the restitution
     pub fn with_restitution(mut self, restitution: f32) -> Self {
         self.restitution = restitution;
@@ -155,6 +208,162 @@ impl Bundle for ColliderBundle {
             .set(collider_shape(), self.shape)
             .set(density(), self.density)
             .set(restitution(), self.restitution)
-            .set(friction(), self.friction);
+            .set(friction(), self.friction)
+            .set_opt(surface_type(), self.surface)
+            .set_opt(collision_layers(), self.layers);
+
+        if self.trigger {
+            entity.set(is_trigger(), ());
+        }
+    }
+}
+
+/// Bundle for a joint connecting the rigidbody of the entity to the rigidbody of `target`.
+///
+/// The joint is created by the physics plugin once both rigidbodies exist, and removed along with
+/// the [`impulse_joint`] relation.
+#[derive(Clone, Debug)]
+pub struct JointBundle {
+    target: Entity,
+    joint: GenericJoint,
+}
+
+impl JointBundle {
+    pub fn new(target: Entity, joint: impl Into<GenericJoint>) -> Self {
+        Self {
+            target,
+            joint: joint.into(),
+        }
+    }
+
+    /// Locks all relative motion between the bodies
+    pub fn fixed(target: Entity) -> Self {
+        Self::new(target, FixedJointBuilder::new())
+    }
+
+    /// Allows rotation around `axis`, such as a hinge
+    pub fn revolute(target: Entity, axis: Vec3) -> Self {
+        Self::new(target, RevoluteJointBuilder::new(to_axis(axis)))
+    }
+
+    /// Allows translation along `axis`, such as a slider
+    pub fn prismatic(target: Entity, axis: Vec3) -> Self {
+        Self::new(target, PrismaticJointBuilder::new(to_axis(axis)))
+    }
+
+    /// Allows free rotation around the anchors, such as a ball and socket
+    pub fn spherical(target: Entity) -> Self {
+        Self::new(target, SphericalJointBuilder::new())
+    }
+
+    /// Keeps the anchors within `max_distance` of each other
+    pub fn rope(target: Entity, max_distance: f32) -> Self {
+        Self::new(target, RopeJointBuilder::new(max_distance))
+    }
+
+    /// Pulls the anchors towards `rest_length` from each other
+    pub fn spring(target: Entity, rest_length: f32, stiffness: f32, damping: f32) -> Self {
+        Self::new(
+            target,
+            SpringJointBuilder::new(rest_length, stiffness, damping),
+        )
+    }
+
+    /// Set the anchor in the local space of the entity
+    pub fn with_local_anchor(mut self, anchor: Vec3) -> Self {
+        self.joint
+            .set_local_anchor1(Point3::from(Vector3::from(anchor)));
+        self
+    }
+
+    /// Set the anchor in the local space of the target
+    pub fn with_target_anchor(mut self, anchor: Vec3) -> Self {
+        self.joint
+            .set_local_anchor2(Point3::from(Vector3::from(anchor)));
+        self
+    }
+
+    /// Set the limits of motion along a free axis of the joint
+    pub fn with_limits(mut self, axis: JointAxis, limits: [f32; 2]) -> Self {
+        self.joint.set_limits(axis, limits);
+        self
+    }
+
+    /// Set whether the connected bodies collide with each other
+    pub fn with_contacts_enabled(mut self, enabled: bool) -> Self {
+        self.joint.set_contacts_enabled(enabled);
+        self
+    }
+
+    pub fn joint(&self) -> &GenericJoint {
+        &self.joint
+    }
+}
+
+impl Bundle for JointBundle {
+    fn mount(self, entity: &mut EntityBuilder) {
+        entity.set(impulse_joint(self.target), self.joint);
+    }
+}
+
+fn to_axis(axis: Vec3) -> Unit<Vector3<f32>> {
+    Unit::new_normalize(axis.into())
+}
+
+#[cfg(test)]
+mod test {
+    use flax::World;
+    use rapier3d::prelude::JointAxesMask;
+
+    use super::*;
+
+    #[test]
+    fn joint_kinds() {
+        let target = Entity::builder().spawn(&mut World::new());
+
+        let masks = [
+            (JointBundle::fixed(target), JointAxesMask::LOCKED_FIXED_AXES),
+            (
+                JointBundle::revolute(target, Vec3::Y),
+                JointAxesMask::LOCKED_REVOLUTE_AXES,
+            ),
+            (
+                JointBundle::prismatic(target, Vec3::X),
+                JointAxesMask::LOCKED_PRISMATIC_AXES,
+            ),
+            (
+                JointBundle::spherical(target),
+                JointAxesMask::LOCKED_SPHERICAL_AXES,
+            ),
+        ];
+
+        for (joint, mask) in masks {
+            assert_eq!(joint.joint().locked_axes, mask);
+        }
+
+        let rope = JointBundle::rope(target, 2.0);
+        assert_eq!(rope.joint().limits(JointAxis::LinX).unwrap().max, 2.0);
+    }
+
+    #[test]
+    fn joint_settings() {
+        let mut world = World::new();
+        let target = Entity::builder().spawn(&mut world);
+
+        let bundle = JointBundle::revolute(target, Vec3::new(0.0, 0.0, 2.0))
+            .with_local_anchor(Vec3::X)
+            .with_target_anchor(-Vec3::Y)
+            .with_limits(JointAxis::AngX, [-1.0, 1.0])
+            .with_contacts_enabled(false);
+
+        let joint = bundle.joint();
+        assert_eq!(joint.local_anchor1(), Point3::new(1.0, 0.0, 0.0));
+        assert_eq!(joint.local_anchor2(), Point3::new(0.0, -1.0, 0.0));
+        assert_eq!(joint.local_axis1(), Vector3::z_axis());
+        assert_eq!(joint.limits(JointAxis::AngX).unwrap().min, -1.0);
+        assert!(!joint.contacts_enabled);
+
+        let id = Entity::builder().mount(bundle).spawn(&mut world);
+        assert!(world.has(id, impulse_joint(target)));
     }
 }
@@ -5,7 +5,13 @@ use rapier3d::prelude::{
     SharedShape,
 };
 
-use crate::{state::PhysicsState, Effector};
+use crate::{
+    area::{AreaEffector, Explosion},
+    ragdoll::Ragdoll,
+    state::PhysicsState,
+    surface::SurfaceType,
+    CollisionLayers, Effector,
+};
 
 component! {
     pub physics_state: PhysicsState,
@@ -23,10 +29,17 @@ component! {
     pub restitution: f32 => [ Debuggable ],
     /// Coefficient of friction
     pub friction: f32 => [ Debuggable ],
+    /// The kind of surface of a collider, inherited by child colliders
+    pub surface_type: SurfaceType => [ Debuggable ],
+    /// Selects which colliders a collider interacts with
+    pub collision_layers: CollisionLayers => [ Debuggable ],
 
     pub center_of_mass: Vec3 => [ Debuggable ],
 
     pub can_sleep: (),
+    /// Enables continuous collision detection, preventing fast bodies from passing through thin
+    /// colliders
+    pub ccd_enabled: (),
 
     pub velocity: Vec3 => [ Debuggable ],
     pub gravity: Vec3 => [ Debuggable ],
@@ -37,7 +50,14 @@ component! {
     pub gravity_influence: f32 => [ Debuggable ],
 
     pub sleeping: () => [ Debuggable ],
+    /// The collider is a sensor reporting [`TriggerEnter`](crate::TriggerEnter) and
+    /// [`TriggerExit`](crate::TriggerExit) events instead of colliding
     pub is_trigger: () => [ Debuggable ],
+
+    /// Applies a force field to all bodies inside the area
+    pub area_effector: AreaEffector => [ Debuggable ],
+    /// Pushes away nearby bodies once, and is then removed
+    pub explosion: Explosion => [ Debuggable ],
 }
 
 // Joints
@@ -45,4 +65,6 @@ component! {
     /// impulse based joint from the current entity to the target
     pub impulse_joint(target): GenericJoint,
     pub impulse_joint_handle(target): ImpulseJointHandle,
+
+    pub ragdoll: Ragdoll,
 }
@@ -1,9 +1,140 @@
 use anyhow::Context;
-use itertools::Itertools;
-use ivy_assets::{Asset, AssetDesc};
+use ivy_assets::{Asset, AssetCache, AssetDesc, AsyncAssetDesc, WeakHandle};
 use ivy_gltf::GltfPrimitive;
-use ivy_graphics::mesh::{MeshData, POSITION_ATTRIBUTE};
-use rapier3d::prelude::{SharedShape, TriMeshFlags};
+use ivy_graphics::mesh::MeshData;
+use rapier3d::prelude::{SharedShape, VHACDParameters};
+
+use crate::shapes::{convex_decomposition_from_mesh, convex_hull_from_mesh, trimesh_from_mesh};
+
+/// Mesh to generate a collider from
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum MeshSource {
+    Gltf(GltfPrimitive),
+    /// Weakly referenced, so that cached shapes do not keep the mesh alive
+    Mesh(WeakHandle<MeshData>),
+}
+
+impl MeshSource {
+    fn load(&self, assets: &AssetCache) -> anyhow::Result<Asset<MeshData>> {
+        match self {
+            MeshSource::Gltf(primitive) => assets.try_load(primitive),
+            MeshSource::Mesh(mesh) => mesh.upgrade().context("Mesh was dropped"),
+        }
+    }
+}
+
+impl From<GltfPrimitive> for MeshSource {
+    fn from(value: GltfPrimitive) -> Self {
+        Self::Gltf(value)
+    }
+}
+
+impl From<Asset<MeshData>> for MeshSource {
+    fn from(value: Asset<MeshData>) -> Self {
+        Self::Mesh(value.downgrade())
+    }
+}
+
+/// The kind of shape to generate from a mesh
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum MeshColliderKind {
+    /// The exact triangles of the mesh.
+    ///
+    /// Best suited for static geometry, as trimeshes have no volume.
+    TriMesh,
+    /// A single convex hull enclosing the mesh
+    ConvexHull,
+    /// Approximates a concave mesh with multiple convex hulls
+    ConvexDecomposition {
+        /// Voxel resolution used to split the mesh
+        resolution: u32,
+        max_convex_hulls: u32,
+    },
+}
+
+/// Creates a collider shape from a mesh.
+///
+/// The shape is cooked once per mesh and kind, and cached by the asset cache.
+///
+/// Convex decompositions are slow to cook, and should be loaded through
+/// [`AssetCache::try_load_async`], which cooks them on a blocking thread.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ColliderFromMesh {
+    pub source: MeshSource,
+    pub kind: MeshColliderKind,
+}
+
+impl ColliderFromMesh {
+    pub fn new(source: impl Into<MeshSource>, kind: MeshColliderKind) -> Self {
+        Self {
+            source: source.into(),
+            kind,
+        }
+    }
+
+    pub fn trimesh(source: impl Into<MeshSource>) -> Self {
+        Self::new(source, MeshColliderKind::TriMesh)
+    }
+
+    pub fn convex_hull(source: impl Into<MeshSource>) -> Self {
+        Self::new(source, MeshColliderKind::ConvexHull)
+    }
+
+    /// Decomposes the mesh with the default VHACD parameters
+    pub fn convex_decomposition(source: impl Into<MeshSource>) -> Self {
+        let params = VHACDParameters::default();
+        Self::new(
+            source,
+            MeshColliderKind::ConvexDecomposition {
+                resolution: params.resolution,
+                max_convex_hulls: params.max_convex_hulls,
+            },
+        )
+    }
+
+    fn cook(&self, assets: &AssetCache) -> anyhow::Result<SharedShape> {
+        let mesh = self.source.load(assets)?;
+
+        let shape = match self.kind {
+            MeshColliderKind::TriMesh => trimesh_from_mesh(&mesh)?,
+            MeshColliderKind::ConvexHull => convex_hull_from_mesh(&mesh)?,
+            MeshColliderKind::ConvexDecomposition {
+                resolution,
+                max_convex_hulls,
+            } => convex_decomposition_from_mesh(
+                &mesh,
+                &VHACDParameters {
+                    resolution,
+                    max_convex_hulls,
+                    ..Default::default()
+                },
+            )?,
+        };
+
+        Ok(shape)
+    }
+}
+
+impl AssetDesc<SharedShape> for ColliderFromMesh {
+    type Error = anyhow::Error;
+
+    fn create(&self, assets: &AssetCache) -> Result<Asset<SharedShape>, Self::Error> {
+        Ok(assets.insert(self.cook(assets)?))
+    }
+}
+
+impl AsyncAssetDesc for ColliderFromMesh {
+    type Output = SharedShape;
+    type Error = anyhow::Error;
+
+    async fn create(&self, assets: &AssetCache) -> Result<Asset<SharedShape>, Self::Error> {
+        let desc = self.clone();
+        let cook_assets = assets.clone();
+        let shape = async_std::task::spawn_blocking(move || desc.cook(&cook_assets)).await?;
+
+        Ok(assets.insert(shape))
+    }
+}
 
 /// Create a trimesh collider from provided primitive
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
@@ -20,33 +151,8 @@ impl GltfTriMeshDesc {
 impl AssetDesc<SharedShape> for GltfTriMeshDesc {
     type Error = anyhow::Error;
 
-    fn create(
-        &self,
-        assets: &ivy_assets::AssetCache,
-    ) -> Result<ivy_assets::Asset<SharedShape>, Self::Error> {
-        let mesh: Asset<MeshData> = assets.try_load(&self.primitive)?;
-
-        let positions = mesh
-            .get_attribute(POSITION_ATTRIBUTE)
-            .context("Missing attribute")?;
-
-        let vertices = positions
-            .as_vec3()
-            .context("Expected attribute of type vec3")?
-            .iter()
-            .map(|&v| v.into())
-            .collect_vec();
-
-        let shape = SharedShape::trimesh_with_flags(
-            vertices,
-            mesh.indices()
-                .chunks(3)
-                .map(|v| [v[0], v[1], v[2]])
-                .collect_vec(),
-            TriMeshFlags::FIX_INTERNAL_EDGES,
-        );
-
-        Ok(assets.insert(shape))
+    fn create(&self, assets: &AssetCache) -> Result<Asset<SharedShape>, Self::Error> {
+        assets.try_load(&ColliderFromMesh::trimesh(self.primitive.clone()))
     }
 }
 
@@ -65,25 +171,61 @@ impl GltfConvexMeshDesc {
 impl AssetDesc<SharedShape> for GltfConvexMeshDesc {
     type Error = anyhow::Error;
 
-    fn create(
-        &self,
-        assets: &ivy_assets::AssetCache,
-    ) -> Result<ivy_assets::Asset<SharedShape>, Self::Error> {
-        let mesh: Asset<MeshData> = assets.try_load(&self.primitive)?;
+    fn create(&self, assets: &AssetCache) -> Result<Asset<SharedShape>, Self::Error> {
+        assets.try_load(&ColliderFromMesh::convex_hull(self.primitive.clone()))
+    }
+}
 
-        let positions = mesh
-            .get_attribute(POSITION_ATTRIBUTE)
-            .context("Missing attribute")?;
+#[cfg(test)]
+mod test {
+    use glam::vec3;
+    use ivy_graphics::mesh::POSITION_ATTRIBUTE;
+
+    use super::*;
+
+    fn tetrahedron() -> MeshData {
+        MeshData::new()
+            .with_attribute(
+                POSITION_ATTRIBUTE,
+                [
+                    vec3(0.0, 0.0, 0.0),
+                    vec3(1.0, 0.0, 0.0),
+                    vec3(0.0, 1.0, 0.0),
+                    vec3(0.0, 0.0, 1.0),
+                ],
+            )
+            .with_indices([0, 2, 1, 0, 1, 3, 0, 3, 2, 1, 2, 3])
+    }
 
-        let vertices = positions
-            .as_vec3()
-            .context("Expected attribute of type vec3")?
-            .iter()
-            .map(|&v| v.into())
-            .collect_vec();
+    #[test]
+    fn cached_shape() {
+        let assets = AssetCache::new();
+        let mesh = assets.insert(tetrahedron());
 
-        let shape = SharedShape::convex_hull(&vertices).context("Malformed convex mesh")?;
+        let desc = ColliderFromMesh::convex_hull(mesh.clone());
+        let shape: Asset<SharedShape> = assets.try_load(&desc).unwrap();
+        assert!(shape.as_convex_polyhedron().is_some());
 
-        Ok(assets.insert(shape))
+        let cached: Asset<SharedShape> = assets.try_load(&desc).unwrap();
+        assert_eq!(shape, cached);
+
+        // Neither the descriptor nor the cached shape keep the mesh alive
+        let weak = mesh.downgrade();
+        drop(mesh);
+        assert!(weak.upgrade().is_none());
+        assert!(desc.source.load(&assets).is_err());
+    }
+
+    #[test]
+    fn cook_async() {
+        let assets = AssetCache::new();
+        let mesh = assets.insert(tetrahedron());
+
+        let shape = async_std::task::block_on(
+            assets.try_load_async(&ColliderFromMesh::trimesh(mesh.clone())),
+        )
+        .unwrap();
+
+        assert_eq!(shape.as_trimesh().unwrap().num_triangles(), 4);
     }
 }
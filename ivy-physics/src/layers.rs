@@ -0,0 +1,180 @@
+use std::ops::{BitOr, BitOrAssign, Not};
+
+use rapier3d::prelude::{Group, InteractionGroups, QueryFilter};
+
+/// A set of collision layers, numbered from 0 to 31
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct LayerMask(u32);
+
+impl LayerMask {
+    pub const NONE: Self = Self(0);
+    pub const ALL: Self = Self(u32::MAX);
+
+    /// Returns a mask containing only `layer`
+    pub const fn layer(layer: u32) -> Self {
+        assert!(layer < 32, "Collision layers range from 0 to 31");
+        Self(1 << layer)
+    }
+
+    pub const fn from_bits(bits: u32) -> Self {
+        Self(bits)
+    }
+
+    pub const fn bits(&self) -> u32 {
+        self.0
+    }
+
+    /// Adds `layer` to the mask
+    pub const fn with(self, layer: u32) -> Self {
+        Self(self.0 | Self::layer(layer).0)
+    }
+
+    /// Removes `layer` from the mask
+    pub const fn without(self, layer: u32) -> Self {
+        Self(self.0 & !Self::layer(layer).0)
+    }
+
+    pub const fn contains(&self, layer: u32) -> bool {
+        self.0 & Self::layer(layer).0 != 0
+    }
+
+    pub const fn intersects(&self, other: Self) -> bool {
+        self.0 & other.0 != 0
+    }
+}
+
+impl Default for LayerMask {
+    fn default() -> Self {
+        Self::ALL
+    }
+}
+
+impl BitOr for LayerMask {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self::Output {
+        Self(self.0 | rhs.0)
+    }
+}
+
+impl BitOrAssign for LayerMask {
+    fn bitor_assign(&mut self, rhs: Self) {
+        self.0 |= rhs.0;
+    }
+}
+
+impl Not for LayerMask {
+    type Output = Self;
+
+    fn not(self) -> Self::Output {
+        Self(!self.0)
+    }
+}
+
+impl From<LayerMask> for Group {
+    fn from(value: LayerMask) -> Self {
+        Group::from_bits_truncate(value.0)
+    }
+}
+
+/// Assigns a collider to a set of layers, and selects the layers it interacts with.
+///
+/// Two colliders interact only if each is a member of a layer in the filter of the other.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CollisionLayers {
+    memberships: LayerMask,
+    filter: LayerMask,
+    /// Layers to resolve contacts with. Interactions with other layers are only reported, such as
+    /// for triggers.
+    solver_filter: LayerMask,
+}
+
+impl CollisionLayers {
+    /// Member of, and interacts with, all layers
+    pub const ALL: Self = Self::new(LayerMask::ALL, LayerMask::ALL);
+
+    pub const fn new(memberships: LayerMask, filter: LayerMask) -> Self {
+        Self {
+            memberships,
+            filter,
+            solver_filter: LayerMask::ALL,
+        }
+    }
+
+    /// Set the layers to resolve contacts with
+    pub const fn with_solver_filter(mut self, solver_filter: LayerMask) -> Self {
+        self.solver_filter = solver_filter;
+        self
+    }
+
+    pub fn memberships(&self) -> LayerMask {
+        self.memberships
+    }
+
+    pub fn filter(&self) -> LayerMask {
+        self.filter
+    }
+
+    pub fn solver_filter(&self) -> LayerMask {
+        self.solver_filter
+    }
+
+    /// Returns true if colliders with these layers interact with colliders with `other`
+    pub fn interacts_with(&self, other: &Self) -> bool {
+        self.memberships.intersects(other.filter) && other.memberships.intersects(self.filter)
+    }
+
+    pub fn interaction_groups(&self) -> InteractionGroups {
+        InteractionGroups::new(self.memberships.into(), self.filter.into())
+    }
+
+    pub fn solver_groups(&self) -> InteractionGroups {
+        InteractionGroups::new(self.memberships.into(), self.solver_filter.into())
+    }
+
+    /// Returns a query filter hitting only the colliders interacting with these layers
+    pub fn query_filter(&self) -> QueryFilter<'static> {
+        QueryFilter::new().groups(self.interaction_groups())
+    }
+}
+
+impl Default for CollisionLayers {
+    fn default() -> Self {
+        Self::ALL
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn layer_mask() {
+        let mask = LayerMask::layer(1).with(4);
+        assert_eq!(mask.bits(), 0b10010);
+        assert!(mask.contains(4));
+        assert!(!mask.without(4).contains(4));
+        assert_eq!(!LayerMask::NONE, LayerMask::ALL);
+    }
+
+    #[test]
+    fn interactions() {
+        const PROJECTILE: u32 = 1;
+        const DEBRIS: u32 = 2;
+
+        let world = CollisionLayers::new(LayerMask::layer(0), LayerMask::ALL);
+        let projectile = CollisionLayers::new(
+            LayerMask::layer(PROJECTILE),
+            LayerMask::ALL.without(PROJECTILE),
+        );
+        let debris = CollisionLayers::new(LayerMask::layer(DEBRIS), LayerMask::layer(0));
+
+        assert!(projectile.interacts_with(&world));
+        assert!(!projectile.interacts_with(&projectile));
+        assert!(debris.interacts_with(&world));
+        assert!(!debris.interacts_with(&projectile));
+        assert!(!debris.interacts_with(&debris));
+    }
+}
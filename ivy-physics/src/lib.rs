@@ -1,17 +1,25 @@
+pub mod area;
 pub mod bundles;
 pub mod components;
 mod effector;
 mod error;
 mod gltf;
+mod layers;
 mod plugin;
+mod triggers;
+pub mod ragdoll;
+pub mod snapshot;
 pub mod state;
 pub mod systems;
 pub mod util;
 pub mod shapes;
+pub mod surface;
 
 pub use bundles::*;
 pub use effector::*;
 pub use error::*;
 pub use gltf::*;
+pub use layers::*;
 pub use plugin::*;
+pub use triggers::*;
 pub use rapier3d;
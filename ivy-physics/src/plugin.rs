@@ -3,17 +3,21 @@ use glam::Vec3;
 use ivy_assets::AssetCache;
 use ivy_core::{
     components::engine,
-    update_layer::{Plugin, ScheduleSetBuilder},
+    update_layer::{FixedTimeStep, Plugin, ScheduleSetBuilder, ScheduledLayer},
+    App, AppPlugin,
 };
 
 use crate::{
+    area::{area_effector_system, explosion_system},
     components::{gravity, physics_state},
+    ragdoll::ragdoll_system,
     state::{PhysicsState, PhysicsStateConfiguration},
     systems::{
-        apply_effectors_system, attach_joints_system, gizmo_system, physics_step_system,
-        register_bodies_system, register_colliders_system, sync_simulation_bodies_system,
-        unregister_bodies_system, unregister_colliders_system, update_bodies_system,
-        update_colliders_system,
+        apply_effectors_system, attach_joints_system, dispatch_trigger_events_system, gizmo_system,
+        physics_step_system, register_bodies_system, register_colliders_system,
+        sync_simulation_bodies_system, unregister_bodies_system, unregister_colliders_system,
+        update_bodies_system, update_colliders_system, update_collision_layers_system,
+        update_pooled_system, update_triggers_system,
     },
 };
 
@@ -22,10 +26,16 @@ pub struct GizmoSettings {
     pub rigidbody: bool,
 }
 
+/// Simulates rigid bodies, colliders and joints.
+///
+/// Either add it to an existing [`ScheduledLayer`], which steps physics at the fixed rate of that
+/// layer, or to the app as an [`AppPlugin`], which pushes its own layer stepping at
+/// [`Self::with_time_step`].
 pub struct PhysicsPlugin {
     gravity: Vec3,
     gizmos: GizmoSettings,
     configuration: PhysicsStateConfiguration,
+    time_step: f64,
 }
 
 impl PhysicsPlugin {
@@ -34,9 +44,16 @@ impl PhysicsPlugin {
             gravity: -Vec3::Y * 9.81,
             gizmos: Default::default(),
             configuration: PhysicsStateConfiguration::default(),
+            time_step: 0.02,
         }
     }
 
+    /// Set the fixed time step in seconds of the layer pushed when added as an [`AppPlugin`]
+    pub fn with_time_step(mut self, time_step: f64) -> Self {
+        self.time_step = time_step;
+        self
+    }
+
     /// Set the gravity
     pub fn with_gravity(mut self, gravity: Vec3) -> Self {
         self.gravity = gravity;
@@ -48,6 +65,18 @@ impl PhysicsPlugin {
         self.gizmos = gizmos;
         self
     }
+
+    /// Set the number of solver substeps per physics step
+    pub fn with_substeps(mut self, substeps: usize) -> Self {
+        self.configuration.substeps = substeps;
+        self
+    }
+
+    /// Set the maximum number of continuous collision detection substeps per physics step
+    pub fn with_max_ccd_substeps(mut self, max_ccd_substeps: usize) -> Self {
+        self.configuration.max_ccd_substeps = max_ccd_substeps;
+        self
+    }
 }
 
 impl Default for PhysicsPlugin {
@@ -81,19 +110,39 @@ impl Plugin for PhysicsPlugin {
             .with_system(register_colliders_system())
             .with_system(attach_joints_system(world))
             .flush()
+            .with_system(area_effector_system())
+            .with_system(explosion_system())
+            .flush()
             .with_system(apply_effectors_system(dt));
 
         // rapier barrier
         schedule
             .with_system(update_colliders_system())
+            .with_system(update_collision_layers_system(world))
+            .with_system(update_triggers_system(world))
             .with_system(update_bodies_system())
+            .with_system(update_pooled_system())
             .with_system(physics_step_system())
+            .with_system(dispatch_trigger_events_system())
             .with_system(sync_simulation_bodies_system());
 
         if self.gizmos.rigidbody {
             schedule.with_system(gizmo_system(dt));
         }
 
+        schedules.per_tick_mut().with_system(ragdoll_system());
+
+        Ok(())
+    }
+}
+
+impl AppPlugin for PhysicsPlugin {
+    fn build(self, app: &mut App) -> anyhow::Result<()> {
+        app.push_labeled_layer(
+            "physics",
+            ScheduledLayer::new(FixedTimeStep::new(self.time_step)).with_plugin(self),
+        );
+
         Ok(())
     }
 }
@@ -0,0 +1,481 @@
+use std::collections::BTreeMap;
+
+use anyhow::Context;
+use flax::{
+    entity_ids, BoxedSystem, CommandBuffer, Entity, EntityBuilder, FetchExt, Query, QueryBorrow,
+    System, World,
+};
+use glam::{Mat4, Quat, Vec3};
+use itertools::Itertools;
+use ivy_assets::Asset;
+use ivy_core::{
+    components::{delta_time, engine, position, rotation, world_transform},
+    EntityBuilderExt, TransformBundle,
+};
+use ivy_gltf::{
+    animation::{
+        player::{AnimationPlayer, Animator},
+        skin::Skin,
+        Animation,
+    },
+    components::{animator, skin},
+};
+use nalgebra::{Point3, Vector3};
+use rapier3d::prelude::{JointAxis, SharedShape};
+
+use crate::{components::ragdoll, ColliderBundle, CollisionLayers, JointBundle, RigidBodyBundle};
+
+/// Shape of the collider of a ragdoll bone, in the space of the joint
+#[derive(Debug, Clone)]
+pub enum BoneShape {
+    /// A capsule spanning from the joint to its first child joint, or a ball if the joint has no
+    /// children
+    Capsule {
+        radius: f32,
+    },
+    Ball {
+        radius: f32,
+    },
+    Shape(SharedShape),
+}
+
+/// Describes the body simulated for a joint of a skin
+#[derive(Debug, Clone)]
+pub struct RagdollBoneDesc {
+    pub joint: String,
+    pub shape: BoneShape,
+    pub density: f32,
+    /// Limits of the rotation around each axis relative to the parent bone, in radians
+    pub limits: Option<[f32; 2]>,
+}
+
+impl RagdollBoneDesc {
+    pub fn new(joint: impl Into<String>, shape: BoneShape) -> Self {
+        Self {
+            joint: joint.into(),
+            shape,
+            density: 1.0,
+            limits: None,
+        }
+    }
+
+    /// Set the density
+    pub fn with_density(mut self, density: f32) -> Self {
+        self.density = density;
+        self
+    }
+
+    /// Set the rotation limits
+    pub fn with_limits(mut self, limits: [f32; 2]) -> Self {
+        self.limits = Some(limits);
+        self
+    }
+}
+
+/// Describes a ragdoll built from the joints of a skin.
+///
+/// Joints without a bone are carried along by their nearest ancestor bone.
+#[derive(Debug, Clone)]
+pub struct RagdollDesc {
+    bones: Vec<RagdollBoneDesc>,
+    layers: Option<CollisionLayers>,
+    blend_time: f32,
+}
+
+impl RagdollDesc {
+    pub fn new() -> Self {
+        Self {
+            bones: Vec::new(),
+            layers: None,
+            blend_time: 0.1,
+        }
+    }
+
+    /// Add a bone
+    pub fn with_bone(mut self, bone: RagdollBoneDesc) -> Self {
+        self.bones.push(bone);
+        self
+    }
+
+    /// Set the collision layers of the bones
+    pub fn with_layers(mut self, layers: CollisionLayers) -> Self {
+        self.layers = Some(layers);
+        self
+    }
+
+    /// Set the time to blend from the animated pose to the simulated pose
+    pub fn with_blend_time(mut self, blend_time: f32) -> Self {
+        self.blend_time = blend_time;
+        self
+    }
+}
+
+impl Default for RagdollDesc {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum RagdollState {
+    Simulating,
+    Recovering,
+}
+
+#[derive(Debug, Clone)]
+struct RagdollBone {
+    joint_scene_index: usize,
+    body: Entity,
+    /// Scale of the joint in world space, which is not simulated
+    scale: Vec3,
+}
+
+/// A skinned entity posed by simulated bodies instead of its animation.
+///
+/// Created by [`spawn_ragdoll`].
+#[derive(Debug, Clone)]
+pub struct Ragdoll {
+    bones: Vec<RagdollBone>,
+    state: RagdollState,
+    weight: f32,
+    blend_time: f32,
+}
+
+impl Ragdoll {
+    /// Returns the rigidbody simulating each joint
+    pub fn bodies(&self) -> impl Iterator<Item = Entity> + '_ {
+        self.bones.iter().map(|v| v.body)
+    }
+
+    pub fn is_simulating(&self) -> bool {
+        self.state == RagdollState::Simulating
+    }
+}
+
+/// Replaces the animation of a skinned entity with a ragdoll.
+///
+/// A rigidbody is spawned for each bone, matching the current pose, and connected to the bone of
+/// the nearest ancestor joint.
+///
+/// The entity holds the skin and animator, and the joints are posed relative to its transform.
+pub fn spawn_ragdoll(world: &mut World, id: Entity, desc: &RagdollDesc) -> anyhow::Result<()> {
+    let entity = world.entity(id)?;
+    let skin = entity.get(skin()).context("Missing skin")?.clone();
+    let transform = entity.get_copy(world_transform()).unwrap_or_default();
+
+    let mut pose = vec![Mat4::IDENTITY; skin.joints().len()];
+    entity
+        .get(animator())
+        .context("Missing animator")?
+        .fill_joint_transforms(&skin, &mut pose);
+
+    let pose = pose.iter().map(|&v| transform * v).collect_vec();
+
+    let parents = skin
+        .joints()
+        .iter()
+        .flat_map(|joint| {
+            joint.children.iter().map(|&child| {
+                (
+                    skin.joint_to_index(child),
+                    skin.joint_to_index(joint.scene_index),
+                )
+            })
+        })
+        .collect::<BTreeMap<_, _>>();
+
+    let bones = desc
+        .bones
+        .iter()
+        .map(|bone| {
+            let index = skin
+                .joints()
+                .iter()
+                .position(|v| v.name.as_ref() == Some(&bone.joint))
+                .with_context(|| format!("No joint named {:?} in skin", bone.joint))?;
+
+            anyhow::Ok((index, bone))
+        })
+        .try_collect::<_, Vec<_>, _>()?
+        .into_iter()
+        .sorted_by_key(|&(index, _)| joint_depth(&parents, index))
+        .collect_vec();
+
+    let mut bodies = BTreeMap::<usize, (Entity, Vec3, Quat)>::new();
+    let mut ragdoll_bones = Vec::new();
+
+    for (index, bone) in bones {
+        let joint = &skin.joints()[index];
+        let (scale, rot, pos) = pose[index].to_scale_rotation_translation();
+
+        let shape = match &bone.shape {
+            BoneShape::Capsule { radius } => match joint.children.first() {
+                Some(&child) => {
+                    let end = pose[skin.joint_to_index(child)].transform_point3(Vec3::ZERO);
+                    let end = rot.inverse() * (end - pos);
+                    SharedShape::capsule(
+                        Point3::origin(),
+                        Point3::from(Vector3::from(end)),
+                        *radius,
+                    )
+                }
+                None => SharedShape::ball(*radius),
+            },
+            BoneShape::Ball { radius } => SharedShape::ball(*radius),
+            BoneShape::Shape(shape) => shape.clone(),
+        };
+
+        let mut collider = ColliderBundle::new(shape).with_density(bone.density);
+        if let Some(layers) = desc.layers {
+            collider = collider.with_layers(layers);
+        }
+
+        let mut builder = EntityBuilder::new();
+        builder
+            .mount(TransformBundle::new(pos, rot, Vec3::ONE))
+            .mount(RigidBodyBundle::dynamic())
+            .mount(collider);
+
+        if let Some(&(parent_body, parent_pos, parent_rot)) =
+            nearest_ancestor(&parents, index, |v| bodies.get(&v))
+        {
+            let mut joint_bundle = JointBundle::spherical(parent_body)
+                .with_target_anchor(parent_rot.inverse() * (pos - parent_pos))
+                .with_contacts_enabled(false);
+
+            if let Some(limits) = bone.limits {
+                for axis in [JointAxis::AngX, JointAxis::AngY, JointAxis::AngZ] {
+                    joint_bundle = joint_bundle.with_limits(axis, limits);
+                }
+            }
+
+            builder.mount(joint_bundle);
+        }
+
+        let body = builder.spawn(world);
+        bodies.insert(index, (body, pos, rot));
+        ragdoll_bones.push(RagdollBone {
+            joint_scene_index: joint.scene_index,
+            body,
+            scale,
+        });
+    }
+
+    world.set(
+        id,
+        ragdoll(),
+        Ragdoll {
+            bones: ragdoll_bones,
+            state: RagdollState::Simulating,
+            weight: 0.0,
+            blend_time: desc.blend_time,
+        },
+    )?;
+
+    Ok(())
+}
+
+/// Number of ancestors of the joint at `index`, given the parent index of each joint
+fn joint_depth(parents: &BTreeMap<usize, usize>, mut index: usize) -> usize {
+    let mut depth = 0;
+    while let Some(&parent) = parents.get(&index) {
+        index = parent;
+        depth += 1;
+    }
+
+    depth
+}
+
+/// Returns the first value of the ancestors of the joint at `index`, nearest first
+fn nearest_ancestor<T>(
+    parents: &BTreeMap<usize, usize>,
+    index: usize,
+    mut value: impl FnMut(usize) -> Option<T>,
+) -> Option<T> {
+    let mut parent = parents.get(&index);
+    while let Some(&index) = parent {
+        if let Some(value) = value(index) {
+            return Some(value);
+        }
+
+        parent = parents.get(&index);
+    }
+
+    None
+}
+
+/// Despawns the bodies of a ragdoll and blends from the last simulated pose back to the animation
+/// over `blend_time` seconds.
+///
+/// See [`best_matching_animation`] to select an animation to recover with, such as getting up.
+pub fn recover_ragdoll(world: &mut World, id: Entity, blend_time: f32) -> anyhow::Result<()> {
+    let bodies = {
+        let mut ragdoll = world.get_mut(id, ragdoll())?;
+        ragdoll.state = RagdollState::Recovering;
+        ragdoll.blend_time = blend_time;
+        ragdoll.bodies().collect_vec()
+    };
+
+    for body in bodies {
+        world.despawn(body)?;
+    }
+
+    Ok(())
+}
+
+/// Returns the animation starting in the pose closest to the current pose of a ragdoll, such as to
+/// get up from lying on the back or the front.
+pub fn best_matching_animation<'a>(
+    world: &World,
+    id: Entity,
+    animations: &'a [Asset<Animation>],
+) -> anyhow::Result<Option<&'a Asset<Animation>>> {
+    let entity = world.entity(id)?;
+    let skin = entity.get(skin()).context("Missing skin")?;
+    let ragdoll = entity.get(ragdoll()).context("Missing ragdoll")?;
+    let inv_transform = entity
+        .get_copy(world_transform())
+        .unwrap_or_default()
+        .inverse();
+
+    let current = ragdoll
+        .bones
+        .iter()
+        .filter_map(|bone| {
+            let rot = *world.get(bone.body, rotation()).ok()?;
+            let (_, rot, _) =
+                (inv_transform * Mat4::from_quat(rot)).to_scale_rotation_translation();
+            Some((skin.joint_to_index(bone.joint_scene_index), rot))
+        })
+        .collect_vec();
+
+    let mut pose = vec![Mat4::IDENTITY; skin.joints().len()];
+    let best = animations
+        .iter()
+        .map(|animation| {
+            let mut animator = Animator::new();
+            animator.start_animation(AnimationPlayer::new(animation.clone()));
+            animator.step(0.0);
+            animator.fill_joint_transforms(&skin, &mut pose);
+
+            let error: f32 = current
+                .iter()
+                .map(|&(index, rot)| {
+                    let (_, target, _) = pose[index].to_scale_rotation_translation();
+                    rot.angle_between(target)
+                })
+                .sum();
+
+            (error, animation)
+        })
+        .min_by(|a, b| a.0.total_cmp(&b.0))
+        .map(|(_, animation)| animation);
+
+    Ok(best)
+}
+
+fn step_weight(weight: f32, target: f32, dt: f32, blend_time: f32) -> f32 {
+    if blend_time <= 0.0 {
+        return target;
+    }
+
+    let step = dt / blend_time;
+    if target > weight {
+        (weight + step).min(target)
+    } else {
+        (weight - step).max(target)
+    }
+}
+
+/// Poses ragdolls after their bodies and blends between the simulated and animated pose
+pub fn ragdoll_system() -> BoxedSystem {
+    System::builder()
+        .with_world()
+        .with_cmd_mut()
+        .with_query(Query::new((
+            entity_ids(),
+            ragdoll().as_mut(),
+            animator().as_mut(),
+            world_transform(),
+            delta_time().source(engine()),
+        )))
+        .build(
+            |world: &World, cmd: &mut CommandBuffer, mut query: QueryBorrow<_>| {
+                for (id, state, animator, transform, dt) in &mut query {
+                    let state: &mut Ragdoll = state;
+                    let animator: &mut Animator = animator;
+                    let transform: &Mat4 = transform;
+                    let dt = dt.as_secs_f32();
+
+                    let target = match state.state {
+                        RagdollState::Simulating => {
+                            let inv_transform = transform.inverse();
+                            for bone in &state.bones {
+                                let (Ok(pos), Ok(rot)) = (
+                                    world.get(bone.body, position()),
+                                    world.get(bone.body, rotation()),
+                                ) else {
+                                    continue;
+                                };
+
+                                animator.set_pose_override(
+                                    bone.joint_scene_index,
+                                    inv_transform
+                                        * Mat4::from_scale_rotation_translation(
+                                            bone.scale, *rot, *pos,
+                                        ),
+                                );
+                            }
+
+                            1.0
+                        }
+                        RagdollState::Recovering => 0.0,
+                    };
+
+                    state.weight = step_weight(state.weight, target, dt, state.blend_time);
+                    animator.set_override_weight(state.weight);
+
+                    if state.state == RagdollState::Recovering && state.weight <= 0.0 {
+                        animator.clear_pose_overrides();
+                        animator.set_override_weight(1.0);
+                        cmd.remove(id, ragdoll());
+                    }
+                }
+
+                anyhow::Ok(())
+            },
+        )
+        .boxed()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn blend_weight() {
+        assert_eq!(step_weight(0.0, 1.0, 0.25, 0.5), 0.5);
+        assert_eq!(step_weight(0.5, 1.0, 0.1, 0.1), 1.0);
+        assert_eq!(step_weight(1.0, 0.0, 0.125, 0.5), 0.75);
+        assert_eq!(step_weight(0.1, 0.0, 1.0, 0.1), 0.0);
+        assert_eq!(step_weight(0.3, 1.0, 0.01, 0.0), 1.0);
+    }
+
+    #[test]
+    fn joint_hierarchy() {
+        // 0 -> 1 -> 2 -> 3, and 0 -> 4
+        let parents = BTreeMap::from([(1, 0), (2, 1), (3, 2), (4, 0)]);
+
+        assert_eq!(joint_depth(&parents, 0), 0);
+        assert_eq!(joint_depth(&parents, 3), 3);
+        assert_eq!(joint_depth(&parents, 4), 1);
+
+        let bodies = BTreeMap::from([(0, "hips"), (1, "spine")]);
+        let body = |index| bodies.get(&index).copied();
+
+        assert_eq!(nearest_ancestor(&parents, 3, body), Some("spine"));
+        assert_eq!(nearest_ancestor(&parents, 1, body), Some("hips"));
+        assert_eq!(nearest_ancestor(&parents, 4, body), Some("hips"));
+        assert_eq!(nearest_ancestor(&parents, 0, body), None);
+    }
+}
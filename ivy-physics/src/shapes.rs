@@ -1,7 +1,13 @@
 use std::option::Option;
 
+use anyhow::Context;
 use glam::Vec3;
-use rapier3d::math::DEFAULT_EPSILON;
+use itertools::Itertools;
+use ivy_graphics::mesh::{MeshData, POSITION_ATTRIBUTE};
+use rapier3d::{
+    math::{Point, DEFAULT_EPSILON},
+    prelude::{SharedShape, TriMeshFlags, VHACDParameters},
+};
 
 pub struct Plane {
     normal: Vec3,
@@ -32,3 +38,52 @@ impl Plane {
         None
     }
 }
+
+fn mesh_vertices(mesh: &MeshData) -> anyhow::Result<Vec<Point<f32>>> {
+    let positions = mesh
+        .get_attribute(POSITION_ATTRIBUTE)
+        .context("Missing attribute")?;
+
+    Ok(positions
+        .as_vec3()
+        .context("Expected attribute of type vec3")?
+        .iter()
+        .map(|&v| v.into())
+        .collect_vec())
+}
+
+fn mesh_triangles(mesh: &MeshData) -> Vec<[u32; 3]> {
+    mesh.indices()
+        .chunks_exact(3)
+        .map(|v| [v[0], v[1], v[2]])
+        .collect_vec()
+}
+
+/// Create a trimesh shape from the triangles of a mesh
+pub fn trimesh_from_mesh(mesh: &MeshData) -> anyhow::Result<SharedShape> {
+    Ok(SharedShape::trimesh_with_flags(
+        mesh_vertices(mesh)?,
+        mesh_triangles(mesh),
+        TriMeshFlags::FIX_INTERNAL_EDGES,
+    ))
+}
+
+/// Create a convex hull shape enclosing the vertices of a mesh
+pub fn convex_hull_from_mesh(mesh: &MeshData) -> anyhow::Result<SharedShape> {
+    SharedShape::convex_hull(&mesh_vertices(mesh)?).context("Malformed convex mesh")
+}
+
+/// Approximate a concave mesh with a compound of convex hulls using VHACD
+pub fn convex_decomposition_from_mesh(
+    mesh: &MeshData,
+    params: &VHACDParameters,
+) -> anyhow::Result<SharedShape> {
+    let triangles = mesh_triangles(mesh);
+    anyhow::ensure!(!triangles.is_empty(), "Mesh has no triangles");
+
+    Ok(SharedShape::convex_decomposition_with_params(
+        &mesh_vertices(mesh)?,
+        &triangles,
+        params,
+    ))
+}
@@ -0,0 +1,336 @@
+use flax::{entity_ids, Entity, FetchExt, Query, World};
+use glam::{Quat, Vec3};
+use ivy_core::components::{engine, position, rotation};
+use rapier3d::prelude::{
+    CCDSolver, ColliderSet, DefaultBroadPhase, ImpulseJointSet, IslandManager, MultibodyJointSet,
+    NarrowPhase, RigidBodySet,
+};
+
+use crate::components::{
+    angular_velocity, collider_handle, collider_shape, impulse_joint, impulse_joint_handle,
+    physics_state, rb_handle, rigid_body_type, velocity,
+};
+
+/// The complete state of the simulation, including contacts and sleeping islands.
+///
+/// Restoring a snapshot and stepping with the same inputs reproduces the same simulation, which
+/// allows rollback networking. Bodies, colliders and joints are keyed by the entities they were
+/// created for.
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PhysicsSnapshot {
+    pub(crate) bodies: RigidBodySet,
+    pub(crate) colliders: ColliderSet,
+    pub(crate) joints: ImpulseJointSet,
+    pub(crate) multibody_joints: MultibodyJointSet,
+    pub(crate) island_manager: IslandManager,
+    pub(crate) broad_phase: DefaultBroadPhase,
+    pub(crate) narrow_phase: NarrowPhase,
+    pub(crate) ccd_solver: CCDSolver,
+}
+
+impl PhysicsSnapshot {
+    /// Replaces the entities of the snapshot, such as when a save game is loaded into new
+    /// entities.
+    ///
+    /// Bodies and colliders mapped to `None` keep their previous entity.
+    pub fn remap_entities(&mut self, mut map: impl FnMut(Entity) -> Option<Entity>) {
+        let mut remap = |user_data: &mut u128| {
+            if let Some(id) = Entity::try_from_bits(*user_data as u64).and_then(&mut map) {
+                *user_data = id.as_bits() as u128;
+            }
+        };
+
+        for (_, body) in self.bodies.iter_mut() {
+            remap(&mut body.user_data);
+        }
+
+        for (_, collider) in self.colliders.iter_mut() {
+            remap(&mut collider.user_data);
+        }
+    }
+
+    /// Removes the bodies and colliders of the entities for which `f` returns false, such as
+    /// entities which are not stored in a save game.
+    ///
+    /// The contacts and joints of removed bodies are cleaned up on the next step.
+    pub fn retain_entities(&mut self, mut f: impl FnMut(Entity) -> bool) {
+        let mut keep =
+            |user_data: u128| Entity::try_from_bits(user_data as u64).is_some_and(&mut f);
+
+        let bodies = self
+            .bodies
+            .iter()
+            .filter(|(_, body)| !keep(body.user_data))
+            .map(|(handle, _)| handle)
+            .collect::<Vec<_>>();
+
+        for handle in bodies {
+            self.bodies.remove(
+                handle,
+                &mut self.island_manager,
+                &mut self.colliders,
+                &mut self.joints,
+                &mut self.multibody_joints,
+                true,
+            );
+        }
+
+        let colliders = self
+            .colliders
+            .iter()
+            .filter(|(_, collider)| !keep(collider.user_data))
+            .map(|(handle, _)| handle)
+            .collect::<Vec<_>>();
+
+        for handle in colliders {
+            self.colliders
+                .remove(handle, &mut self.island_manager, &mut self.bodies, true);
+        }
+    }
+
+    /// Returns the entities of all bodies in the snapshot
+    pub fn bodies(&self) -> impl Iterator<Item = Entity> + '_ {
+        self.bodies
+            .iter()
+            .filter_map(|(_, v)| Entity::try_from_bits(v.user_data as u64))
+    }
+}
+
+/// Restores the simulation to a snapshot, and writes the restored handles and body transforms to
+/// the entities.
+///
+/// Bodies of entities despawned since the snapshot are removed, and entities spawned since the
+/// snapshot have their bodies, colliders and joints registered again.
+pub fn restore_snapshot(world: &mut World, snapshot: PhysicsSnapshot) -> anyhow::Result<()> {
+    let mut state = world.get_mut(engine(), physics_state())?;
+    state.restore(snapshot);
+
+    let despawned = state
+        .bodies()
+        .filter(|(_, body)| {
+            Entity::try_from_bits(body.user_data as u64).is_some_and(|id| !world.is_alive(id))
+        })
+        .map(|(handle, _)| handle)
+        .collect::<Vec<_>>();
+
+    for handle in despawned {
+        state.remove_body(handle);
+    }
+
+    let bodies = state
+        .bodies()
+        .filter_map(|(handle, body)| {
+            let id = Entity::try_from_bits(body.user_data as u64)?;
+            let pos = body.position();
+            Some((
+                id,
+                handle,
+                Vec3::from(pos.translation),
+                Quat::from(pos.rotation),
+                Vec3::from(*body.linvel()),
+                Vec3::from(*body.angvel()),
+            ))
+        })
+        .collect::<Vec<_>>();
+
+    let colliders = state
+        .colliders()
+        .filter_map(|(handle, collider)| {
+            Some((Entity::try_from_bits(collider.user_data as u64)?, handle))
+        })
+        .collect::<Vec<_>>();
+
+    let joints = state
+        .joints()
+        .filter_map(|(handle, joint)| {
+            let body1 = state.body_entity(joint.body1)?;
+            let body2 = state.body_entity(joint.body2)?;
+            Some((body1, body2, handle))
+        })
+        .collect::<Vec<_>>();
+
+    // Handles of entities spawned after the snapshot refer to bodies which no longer exist, or
+    // to the bodies of other entities
+    let stale_bodies = Query::new((entity_ids(), rb_handle().copied()))
+        .borrow(world)
+        .iter()
+        .filter(|&(id, handle)| state.body_entity(handle) != Some(id))
+        .map(|(id, _)| id)
+        .collect::<Vec<_>>();
+
+    let stale_colliders = Query::new((entity_ids(), collider_handle().copied()))
+        .borrow(world)
+        .iter()
+        .filter(|&(id, handle)| state.collider_entity(handle) != Some(id))
+        .map(|(id, _)| id)
+        .collect::<Vec<_>>();
+
+    let stale_joints = Query::new(entity_ids())
+        .with_relation(impulse_joint_handle)
+        .borrow(world)
+        .iter()
+        .flat_map(|id| {
+            world
+                .entity(id)
+                .into_iter()
+                .flat_map(|entity| {
+                    entity
+                        .relations(impulse_joint_handle)
+                        .map(|(target, handle)| (target, *handle))
+                        .collect::<Vec<_>>()
+                })
+                .filter(|&(target, handle)| !joints.contains(&(id, target, handle)))
+                .map(move |(target, _)| (id, target))
+                .collect::<Vec<_>>()
+        })
+        .collect::<Vec<_>>();
+
+    drop(state);
+
+    for (id, handle, pos, rot, vel, ang_vel) in bodies {
+        world.set(id, rb_handle(), handle)?;
+        world.set(id, position(), pos)?;
+        world.set(id, rotation(), rot)?;
+        world.set(id, velocity(), vel)?;
+        world.set(id, angular_velocity(), ang_vel)?;
+    }
+
+    for (id, handle) in colliders {
+        if world.is_alive(id) {
+            world.set(id, collider_handle(), handle)?;
+        }
+    }
+
+    for &(id, target, handle) in &joints {
+        if world.is_alive(id) {
+            world.set(id, impulse_joint_handle(target), handle)?;
+        }
+    }
+
+    // Registration reacts to changes of the descriptions, so they are written again
+    for id in stale_bodies {
+        world.remove(id, rb_handle())?;
+        let body_type = *world.get(id, rigid_body_type())?;
+        world.set(id, rigid_body_type(), body_type)?;
+    }
+
+    for id in stale_colliders {
+        world.remove(id, collider_handle())?;
+        let shape = world.remove(id, collider_shape())?;
+        world.set(id, collider_shape(), shape)?;
+    }
+
+    for (id, target) in stale_joints {
+        world.remove(id, impulse_joint_handle(target))?;
+        let joint = *world.get(id, impulse_joint(target))?;
+        world.set(id, impulse_joint(target), joint)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use flax::Schedule;
+    use ivy_core::{components::TransformBundle, EntityBuilderExt};
+    use rapier3d::prelude::SharedShape;
+
+    use super::*;
+    use crate::{
+        components::gravity,
+        state::{PhysicsState, PhysicsStateConfiguration},
+        systems::*,
+        ColliderBundle, RigidBodyBundle,
+    };
+
+    fn spawn_ball(world: &mut World, pos: Vec3) -> Entity {
+        Entity::builder()
+            .mount(TransformBundle::default().with_position(pos))
+            .mount(RigidBodyBundle::dynamic())
+            .mount(ColliderBundle::new(SharedShape::ball(0.5)))
+            .spawn(world)
+    }
+
+    #[test]
+    fn restore_with_spawned_entities() {
+        let mut world = World::new();
+        let dt = 1.0 / 60.0;
+
+        world.set(engine(), gravity(), Vec3::Y * -9.81).unwrap();
+        world
+            .set(
+                engine(),
+                physics_state(),
+                PhysicsState::new(&PhysicsStateConfiguration::default(), dt),
+            )
+            .unwrap();
+
+        let mut schedule = Schedule::builder()
+            .with_system(unregister_bodies_system(&mut world))
+            .with_system(unregister_colliders_system(&mut world))
+            .with_system(register_bodies_system())
+            .flush()
+            .with_system(register_colliders_system())
+            .with_system(attach_joints_system(&mut world))
+            .flush()
+            .with_system(update_bodies_system())
+            .with_system(physics_step_system())
+            .with_system(sync_simulation_bodies_system())
+            .build();
+
+        let a = spawn_ball(&mut world, Vec3::ZERO);
+        schedule.execute_seq(&mut world).unwrap();
+
+        let snapshot = world.get(engine(), physics_state()).unwrap().snapshot();
+        let saved_pos = *world.get(a, position()).unwrap();
+
+        // Spawned after the snapshot, and despawned after the snapshot
+        let b = spawn_ball(&mut world, Vec3::X * 5.0);
+        let c = spawn_ball(&mut world, Vec3::X * 10.0);
+        for _ in 0..10 {
+            schedule.execute_seq(&mut world).unwrap();
+        }
+
+        world.despawn(a).unwrap();
+        restore_snapshot(&mut world, snapshot.clone()).unwrap();
+
+        assert!(!world.has(b, rb_handle()));
+        assert!(!world.has(c, collider_handle()));
+
+        // Registers `b` and `c` again
+        schedule.execute_seq(&mut world).unwrap();
+
+        let state = world.get(engine(), physics_state()).unwrap();
+        assert_eq!(state.bodies().count(), 2);
+        for id in [b, c] {
+            let handle = *world.get(id, rb_handle()).unwrap();
+            assert_eq!(state.body_entity(handle), Some(id));
+            let handle = *world.get(id, collider_handle()).unwrap();
+            assert_eq!(state.collider_entity(handle), Some(id));
+        }
+        drop(state);
+
+        // Restoring with the entity alive resumes from the saved position
+        let a = spawn_ball(&mut world, Vec3::ZERO);
+        schedule.execute_seq(&mut world).unwrap();
+
+        let mut snapshot = snapshot;
+        let saved = snapshot.bodies().next().unwrap();
+        snapshot.remap_entities(|id| (id == saved).then_some(a));
+
+        restore_snapshot(&mut world, snapshot).unwrap();
+        assert_eq!(*world.get(a, position()).unwrap(), saved_pos);
+
+        schedule.execute_seq(&mut world).unwrap();
+        assert!(world.get(a, position()).unwrap().y < saved_pos.y);
+        assert_eq!(
+            world
+                .get(engine(), physics_state())
+                .unwrap()
+                .bodies()
+                .count(),
+            3
+        );
+    }
+}
@@ -1,15 +1,31 @@
+use std::{collections::BTreeSet, mem, num::NonZeroUsize, sync::Arc};
+
 use flax::{Component, ComponentMut, Entity, Fetch, QueryBorrow};
 use glam::{Quat, Vec3};
-use ivy_core::components::{position, rotation};
+use ivy_core::{
+    components::{position, rotation},
+    memory::TrackedMemory,
+};
 use nalgebra::Isometry3;
-use rapier3d::prelude::{
-    CCDSolver, Collider, ColliderHandle, ColliderSet, DefaultBroadPhase, GenericJoint,
-    ImpulseJointHandle, ImpulseJointSet, IntegrationParameters, IslandManager, MultibodyJointSet,
-    NarrowPhase, PhysicsPipeline, QueryFilter, QueryPipeline, Ray, RayIntersection, RigidBody,
-    RigidBodyHandle, RigidBodySet,
+use rapier3d::{
+    parry::{
+        query::{PointProjection, PointQuery, ShapeCastHit, ShapeCastOptions},
+        shape::{Ball, TypedShape},
+    },
+    prelude::{
+        CCDSolver, Collider, ColliderHandle, ColliderSet, DefaultBroadPhase, GenericJoint,
+        ImpulseJoint, ImpulseJointHandle, ImpulseJointSet, IntegrationParameters, IslandManager,
+        MultibodyJointSet, NarrowPhase, PhysicsPipeline, QueryFilter, QueryPipeline, Ray,
+        RayIntersection, RigidBody, RigidBodyHandle, RigidBodySet, Shape,
+    },
 };
 
-use crate::components::{angular_velocity, velocity};
+use crate::{
+    components::{angular_velocity, velocity},
+    snapshot::PhysicsSnapshot,
+    triggers::{trigger_flags, TriggerEvent, TriggerTracker},
+    CollisionLayers,
+};
 
 #[derive(Debug, Clone)]
 pub struct RaycastHit {
@@ -35,8 +51,35 @@ impl RaycastHit {
     }
 }
 
-#[derive(Default)]
-pub struct PhysicsStateConfiguration {}
+#[derive(Debug, Clone)]
+pub struct ShapeCastResult {
+    pub rigidbody_id: Entity,
+    pub collider_id: Entity,
+    pub collider: ColliderHandle,
+    /// The distance travelled along the cast direction, and the contact at that point
+    pub hit: ShapeCastHit,
+}
+
+pub struct PhysicsStateConfiguration {
+    /// Number of solver substeps per step. More substeps improve the stability of stacks and
+    /// joints at the cost of performance.
+    pub substeps: usize,
+    /// Maximum number of continuous collision detection substeps per step for bodies with CCD
+    /// enabled.
+    ///
+    /// Each substep resolves the earliest time of impact, so more substeps allow fast bodies to
+    /// hit several colliders in a single step.
+    pub max_ccd_substeps: usize,
+}
+
+impl Default for PhysicsStateConfiguration {
+    fn default() -> Self {
+        Self {
+            substeps: 4,
+            max_ccd_substeps: 1,
+        }
+    }
+}
 
 pub struct PhysicsState {
     gravity: Vec3,
@@ -50,13 +93,19 @@ pub struct PhysicsState {
     multibody_joints: MultibodyJointSet,
     ccd_solder: CCDSolver,
     query_pipeline: QueryPipeline,
+    triggers: TriggerTracker,
     dt: f32,
+    substeps: NonZeroUsize,
+    max_ccd_substeps: usize,
+    memory: TrackedMemory,
 }
 
 impl PhysicsState {
-    pub fn new(_: &PhysicsStateConfiguration, dt: f32) -> Self {
+    pub fn new(configuration: &PhysicsStateConfiguration, dt: f32) -> Self {
         Self {
             dt,
+            substeps: NonZeroUsize::new(configuration.substeps).unwrap_or(NonZeroUsize::MIN),
+            max_ccd_substeps: configuration.max_ccd_substeps,
             bodies: RigidBodySet::new(),
             collider_set: ColliderSet::new(),
             physics_pipeline: PhysicsPipeline::new(),
@@ -67,7 +116,9 @@ impl PhysicsState {
             multibody_joints: MultibodyJointSet::new(),
             ccd_solder: CCDSolver::new(),
             query_pipeline: QueryPipeline::new(),
+            triggers: TriggerTracker::new(),
             gravity: -Vec3::Y * 9.81,
+            memory: TrackedMemory::new("physics", 0),
         }
     }
 
@@ -91,6 +142,16 @@ impl PhysicsState {
         );
     }
 
+    /// Returns the entity of a body, or `None` if the handle is not part of the simulation
+    pub fn body_entity(&self, handle: RigidBodyHandle) -> Option<Entity> {
+        Entity::try_from_bits(self.bodies.get(handle)?.user_data as u64)
+    }
+
+    /// Returns the entity of a collider, or `None` if the handle is not part of the simulation
+    pub fn collider_entity(&self, handle: ColliderHandle) -> Option<Entity> {
+        Entity::try_from_bits(self.collider_set.get(handle)?.user_data as u64)
+    }
+
     pub fn rigidbody(&self, handle: RigidBodyHandle) -> &RigidBody {
         &self.bodies[handle]
     }
@@ -99,6 +160,34 @@ impl PhysicsState {
         &mut self.bodies[handle]
     }
 
+    /// Excludes a body and its colliders from the simulation while disabled
+    pub fn set_body_enabled(&mut self, handle: RigidBodyHandle, enabled: bool) {
+        let body = &mut self.bodies[handle];
+        if body.is_enabled() != enabled {
+            body.set_enabled(enabled);
+        }
+    }
+
+    /// Excludes a collider from the simulation while disabled
+    pub fn set_collider_enabled(&mut self, handle: ColliderHandle, enabled: bool) {
+        let collider = &mut self.collider_set[handle];
+        if collider.is_enabled() != enabled {
+            collider.set_enabled(enabled);
+        }
+    }
+
+    pub fn bodies(&self) -> impl Iterator<Item = (RigidBodyHandle, &RigidBody)> {
+        self.bodies.iter()
+    }
+
+    pub fn colliders(&self) -> impl Iterator<Item = (ColliderHandle, &Collider)> {
+        self.collider_set.iter()
+    }
+
+    pub fn joints(&self) -> impl Iterator<Item = (ImpulseJointHandle, &ImpulseJoint)> {
+        self.joint_set.iter()
+    }
+
     pub fn collider(&self, handle: ColliderHandle) -> &Collider {
         &self.collider_set[handle]
     }
@@ -115,6 +204,32 @@ impl PhysicsState {
             .remove(handle, &mut self.island_manager, &mut self.bodies, true);
     }
 
+    pub fn set_collision_layers(&mut self, handle: ColliderHandle, layers: &CollisionLayers) {
+        let collider = &mut self.collider_set[handle];
+        collider.set_collision_groups(layers.interaction_groups());
+        collider.set_solver_groups(layers.solver_groups());
+    }
+
+    /// Makes a collider a trigger reporting intersections instead of colliding, or a solid
+    /// collider again.
+    ///
+    /// The intersections of a collider which stops being a trigger are exited.
+    pub fn set_trigger(&mut self, handle: ColliderHandle, trigger: bool) {
+        let collider = &mut self.collider_set[handle];
+        if collider.is_sensor() == trigger {
+            return;
+        }
+
+        let (events, collision_types) = trigger_flags(trigger);
+        collider.set_sensor(trigger);
+        collider.set_active_events(events);
+        collider.set_active_collision_types(collision_types);
+
+        if !trigger {
+            self.triggers.exit_collider(handle);
+        }
+    }
+
     pub fn attached_rigidbody(&self, collider: ColliderHandle) -> Option<Entity> {
         let handle = self.collider_set.get(collider)?.parent()?;
         Some(Entity::try_from_bits(self.rigidbody(handle).user_data as _).unwrap())
@@ -146,6 +261,13 @@ impl PhysicsState {
         self.joint_set.remove(joint, true);
     }
 
+    /// Replaces the data of an attached joint
+    pub fn update_joint(&mut self, joint: ImpulseJointHandle, data: impl Into<GenericJoint>) {
+        if let Some(joint) = self.joint_set.get_mut(joint) {
+            joint.data = data.into();
+        }
+    }
+
     pub fn cast_ray(
         &self,
         ray: &Ray,
@@ -198,10 +320,139 @@ impl PhysicsState {
         )
     }
 
+    /// Sweeps `shape` along `dir` and returns the first collider it hits within `max_dist`.
+    ///
+    /// Shapes already penetrating a collider hit it at distance zero.
+    pub fn cast_shape(
+        &self,
+        position: Vec3,
+        rotation: Quat,
+        shape: &dyn Shape,
+        dir: Vec3,
+        max_dist: f32,
+        filter: QueryFilter,
+    ) -> Option<ShapeCastResult> {
+        self.query_pipeline
+            .cast_shape(
+                &self.bodies,
+                &self.collider_set,
+                &Isometry3::new(position.into(), rotation.to_scaled_axis().into()),
+                &dir.normalize().into(),
+                shape,
+                ShapeCastOptions::with_max_time_of_impact(max_dist),
+                filter,
+            )
+            .map(|(handle, hit)| {
+                let collider = &self.collider_set[handle];
+                let root = collider.parent().unwrap();
+                let id = Entity::try_from_bits(collider.user_data as u64)
+                    .expect("user_data is valid entity");
+                let root_id = Entity::try_from_bits(self.bodies[root].user_data as u64).unwrap();
+                ShapeCastResult {
+                    rigidbody_id: root_id,
+                    collider_id: id,
+                    collider: handle,
+                    hit,
+                }
+            })
+    }
+
+    /// Invokes `callback` with each collider intersecting `shape`, until it returns false
+    pub fn intersections_with_shape(
+        &self,
+        position: Vec3,
+        rotation: Quat,
+        shape: &dyn Shape,
+        filter: QueryFilter,
+        mut callback: impl FnMut(Entity) -> bool,
+    ) {
+        self.query_pipeline.intersections_with_shape(
+            &self.bodies,
+            &self.collider_set,
+            &Isometry3::new(position.into(), rotation.to_scaled_axis().into()),
+            shape,
+            filter,
+            |handle| {
+                let id = Entity::try_from_bits(self.collider_set[handle].user_data as u64)
+                    .expect("user_data is valid entity");
+                callback(id)
+            },
+        );
+    }
+
+    /// Returns the collider closest to `point`, and the closest point on it
+    pub fn project_point(
+        &self,
+        point: Vec3,
+        solid: bool,
+        filter: QueryFilter,
+    ) -> Option<(Entity, PointProjection)> {
+        self.query_pipeline
+            .project_point(
+                &self.bodies,
+                &self.collider_set,
+                &point.into(),
+                solid,
+                filter,
+            )
+            .map(|(handle, projection)| {
+                let id = Entity::try_from_bits(self.collider_set[handle].user_data as u64)
+                    .expect("user_data is valid entity");
+                (id, projection)
+            })
+    }
+
+    /// Invokes `callback` with each collider within `radius` of `point`, until it returns false
+    pub fn within_radius(
+        &self,
+        point: Vec3,
+        radius: f32,
+        filter: QueryFilter,
+        callback: impl FnMut(Entity) -> bool,
+    ) {
+        self.intersections_with_shape(point, Quat::IDENTITY, &Ball::new(radius), filter, callback)
+    }
+
+    /// Returns up to `k` colliders within `max_dist` of `point`, ordered by distance
+    pub fn nearest(
+        &self,
+        point: Vec3,
+        k: usize,
+        max_dist: f32,
+        filter: QueryFilter,
+    ) -> Vec<(Entity, f32)> {
+        let mut result = Vec::new();
+        self.query_pipeline.intersections_with_shape(
+            &self.bodies,
+            &self.collider_set,
+            &Isometry3::translation(point.x, point.y, point.z),
+            &Ball::new(max_dist),
+            filter,
+            |handle| {
+                let collider = &self.collider_set[handle];
+                let dist =
+                    collider
+                        .shape()
+                        .distance_to_point(collider.position(), &point.into(), true);
+                let id = Entity::try_from_bits(collider.user_data as u64)
+                    .expect("user_data is valid entity");
+
+                result.push((id, dist));
+                true
+            },
+        );
+
+        result.sort_by(|a, b| a.1.total_cmp(&b.1));
+        result.truncate(k);
+        result
+    }
+
     pub fn step(&mut self) {
         let params = IntegrationParameters {
             dt: self.dt,
             min_ccd_dt: self.dt / 100.0,
+            num_solver_iterations: self.substeps,
+            max_ccd_substeps: self.max_ccd_substeps,
             ..Default::default()
         };
 
@@ -218,8 +469,84 @@ impl PhysicsState {
             &mut self.ccd_solder,
             Some(&mut self.query_pipeline),
             &(),
-            &(),
+            self.triggers.collector(),
         );
+
+        self.triggers.process(&self.collider_set);
+
+        self.memory.set(self.memory_usage() as u64);
+    }
+
+    /// Bytes used by the bodies, the geometry of the shapes and the contacts.
+    ///
+    /// Shapes shared between colliders are counted once. The acceleration structures of rapier
+    /// are not visible and are left out.
+    fn memory_usage(&self) -> usize {
+        let mut shapes = BTreeSet::new();
+        let shape_bytes = self
+            .collider_set
+            .iter()
+            .filter(|(_, collider)| {
+                shapes.insert(Arc::as_ptr(&collider.shared_shape().0).cast::<()>())
+            })
+            .map(|(_, collider)| shape_memory(collider.shape()))
+            .sum::<usize>();
+
+        let contact_bytes = self
+            .narrow_phase
+            .contact_pairs()
+            .map(|pair| {
+                mem::size_of_val(pair)
+                    + pair
+                        .manifolds
+                        .iter()
+                        .map(|v| mem::size_of_val(v) + mem::size_of_val(&v.points[..]))
+                        .sum::<usize>()
+            })
+            .sum::<usize>();
+
+        self.bodies.len() * mem::size_of::<RigidBody>()
+            + self.collider_set.len() * mem::size_of::<Collider>()
+            + self.joint_set.len() * mem::size_of::<ImpulseJoint>()
+            + shape_bytes
+            + contact_bytes
+    }
+
+    /// Captures the complete simulation state
+    pub fn snapshot(&self) -> PhysicsSnapshot {
+        PhysicsSnapshot {
+            bodies: self.bodies.clone(),
+            colliders: self.collider_set.clone(),
+            joints: self.joint_set.clone(),
+            multibody_joints: self.multibody_joints.clone(),
+            island_manager: self.island_manager.clone(),
+            broad_phase: self.broad_phase.clone(),
+            narrow_phase: self.narrow_phase.clone(),
+            ccd_solver: self.ccd_solder.clone(),
+        }
+    }
+
+    /// Replaces the simulation state with a snapshot.
+    ///
+    /// See [`restore_snapshot`](crate::snapshot::restore_snapshot) to also update the entities.
+    pub fn restore(&mut self, snapshot: PhysicsSnapshot) {
+        self.bodies = snapshot.bodies;
+        self.collider_set = snapshot.colliders;
+        self.joint_set = snapshot.joints;
+        self.multibody_joints = snapshot.multibody_joints;
+        self.island_manager = snapshot.island_manager;
+        self.broad_phase = snapshot.broad_phase;
+        self.narrow_phase = snapshot.narrow_phase;
+        self.ccd_solder = snapshot.ccd_solver;
+
+        self.query_pipeline.update(&self.collider_set);
+        self.triggers
+            .restore(&self.collider_set, &self.narrow_phase);
+    }
+
+    /// Returns the trigger events of the previous steps
+    pub fn drain_trigger_events(&mut self) -> impl Iterator<Item = TriggerEvent> + '_ {
+        self.triggers.drain()
     }
 
     pub fn update_bodies<'x, I>(&mut self, data: I)
@@ -337,3 +664,21 @@ impl Default for ColliderDynamicsQuery {
         Self::new()
     }
 }
+
+/// Heap memory of the geometry of a shape
+fn shape_memory(shape: &dyn Shape) -> usize {
+    match shape.as_typed_shape() {
+        TypedShape::TriMesh(v) => mem::size_of_val(v.vertices()) + mem::size_of_val(v.indices()),
+        TypedShape::Polyline(v) => mem::size_of_val(v.vertices()) + mem::size_of_val(v.indices()),
+        TypedShape::HeightField(v) => mem::size_of_val(v.heights().as_slice()),
+        TypedShape::ConvexPolyhedron(v) => {
+            mem::size_of_val(v.points()) + mem::size_of_val(v.faces()) + mem::size_of_val(v.edges())
+        }
+        TypedShape::Compound(v) => v
+            .shapes()
+            .iter()
+            .map(|(_, shape)| mem::size_of_val(&**shape) + shape_memory(&**shape))
+            .sum(),
+        _ => 0,
+    }
+}
@@ -0,0 +1,87 @@
+use std::{borrow::Cow, fmt::Display};
+
+use flax::{components::child_of, Entity, World};
+use glam::Vec3;
+use rapier3d::prelude::{QueryFilter, Ray};
+
+use crate::{
+    components::surface_type,
+    state::{PhysicsState, RaycastHit},
+};
+
+/// Tags a collider with the kind of surface it represents, such as `"grass"` or `"metal"`.
+///
+/// Used to select footstep sounds and impact effects.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SurfaceType(Cow<'static, str>);
+
+impl SurfaceType {
+    /// Used for surfaces without a type
+    pub const DEFAULT: Self = Self(Cow::Borrowed("default"));
+
+    pub fn new(name: impl Into<Cow<'static, str>>) -> Self {
+        Self(name.into())
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl Default for SurfaceType {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+impl Display for SurfaceType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl From<&'static str> for SurfaceType {
+    fn from(value: &'static str) -> Self {
+        Self::new(value)
+    }
+}
+
+/// Returns the surface type of a collider, inherited from its parents if not set
+pub fn get_surface_type(world: &World, mut id: Entity) -> Option<SurfaceType> {
+    loop {
+        let entity = world.entity(id).ok()?;
+        if let Ok(surface) = entity.get(surface_type()) {
+            return Some(surface.clone());
+        }
+
+        id = entity.relations(child_of).next()?.0;
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct SurfaceHit {
+    /// The tagged surface type, if any
+    pub surface: Option<SurfaceType>,
+    pub point: Vec3,
+    pub normal: Vec3,
+    pub hit: RaycastHit,
+}
+
+/// Casts a ray and returns the surface type of the first collider hit
+pub fn query_surface(
+    world: &World,
+    state: &PhysicsState,
+    ray: &Ray,
+    max_dist: f32,
+    filter: QueryFilter,
+) -> Option<SurfaceHit> {
+    let hit = state.cast_ray(ray, max_dist, true, filter)?;
+
+    Some(SurfaceHit {
+        surface: get_surface_type(world, hit.collider_id),
+        point: ray.point_at(hit.intersection.time_of_impact).coords.into(),
+        normal: hit.intersection.normal.into(),
+        hit,
+    })
+}
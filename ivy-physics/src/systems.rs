@@ -1,17 +1,22 @@
 use core::f32;
 
-use anyhow::Context;
 use flax::{
-    components::child_of, entity_ids, events::EventSubscriber, fetch::Copied, filter::ChangeFilter,
+    components::child_of,
+    entity_ids,
+    events::{EventKind, EventKindFilter, EventSubscriber},
+    fetch::Copied,
+    filter::ChangeFilter,
     BoxedSystem, CommandBuffer, Component, ComponentMut, EntityIds, FetchExt, Opt, Query,
     QueryBorrow, RelationExt, System, World,
 };
 use glam::{Mat4, Vec3};
 use ivy_core::{
     components::{
-        engine, main_camera, position, world_transform, TransformQuery, TransformQueryItem,
+        engine, event_channels, main_camera, position, scope_timings, world_transform,
+        TransformQuery, TransformQueryItem,
     },
-    gizmos::{Gizmos, Line, DEFAULT_THICKNESS},
+    gizmos::{Arrow, Axes, Gizmos},
+    pool::pooled,
     subscribers::{RemovedComponentSubscriber, RemovedRelationSubscriber},
     Color, ColorExt,
 };
@@ -26,6 +31,8 @@ use rapier3d::{
 use crate::{
     components::*,
     state::{BodyDynamicsQuery, BodyDynamicsQueryMut, ColliderDynamicsQuery, PhysicsState},
+    triggers::trigger_flags,
+    CollisionLayers, TriggerEvent,
 };
 
 #[allow(clippy::type_complexity)]
@@ -38,7 +45,9 @@ pub fn register_bodies_system() -> BoxedSystem {
             rigid_body_type().modified(),
             locked_axes().opt(),
             can_sleep().satisfied(),
+            ccd_enabled().satisfied(),
             gravity_influence().opt_or(1.0),
+            rb_handle().copied().opt(),
         )))
         .build(
             move |cmd: &mut CommandBuffer,
@@ -51,14 +60,25 @@ pub fn register_bodies_system() -> BoxedSystem {
                     Opt<Component<LockedAxes>>,
                     _,
                     _,
+                    _,
+                    _,
                 ),
             >| {
                 if let Some(state) = query.first() {
-                    for (id, &body_type, locked_axes, can_sleep, &gravity) in bodies.iter() {
+                    for (id, &body_type, locked_axes, can_sleep, ccd, &gravity, handle) in
+                        bodies.iter()
+                    {
+                        // Already registered, such as by a restored snapshot
+                        if let Some(handle) = handle.filter(|&v| state.body_entity(v) == Some(id)) {
+                            state.rigidbody_mut(handle).set_body_type(body_type, true);
+                            continue;
+                        }
+
                         let rb = state.add_body(
                             id,
                             RigidBodyBuilder::new(body_type)
                                 .can_sleep(can_sleep)
+                                .ccd_enabled(ccd)
                                 .locked_axes(locked_axes.copied().unwrap_or(LockedAxes::empty()))
                                 .gravity_scale(gravity)
                                 .build(),
@@ -80,8 +100,11 @@ pub fn register_colliders_system() -> BoxedSystem {
         .with_query(Query::new((
             entity_ids(),
             (collider_shape(), density(), restitution(), friction()).added(),
+            collision_layers().opt_or_default(),
+            is_trigger().satisfied(),
             TransformQuery::new(),
             (entity_ids(), rb_handle()).traverse(child_of),
+            collider_handle().copied().opt(),
         )))
         .build(
             move |cmd: &mut CommandBuffer,
@@ -91,10 +114,17 @@ pub fn register_colliders_system() -> BoxedSystem {
                     for (
                         id,
                         (shape, &density, &restitution, &friction),
+                        layers,
+                        trigger,
                         transform,
                         (parent_id, &parent),
+                        handle,
                     ) in bodies.iter()
                     {
+                        if handle.is_some_and(|v| state.collider_entity(v) == Some(id)) {
+                            continue;
+                        }
+
                         let local_position = if parent_id == id {
                             Isometry::identity()
                         } else {
@@ -105,12 +135,18 @@ pub fn register_colliders_system() -> BoxedSystem {
                             )
                         };
 
+                        let (events, collision_types) = trigger_flags(trigger);
                         let handle = state.attach_collider(
                             id,
                             ColliderBuilder::new(SharedShape::clone(shape))
                                 .density(density)
                                 .restitution(restitution)
                                 .friction(friction)
+                                .collision_groups(layers.interaction_groups())
+                                .solver_groups(layers.solver_groups())
+                                .sensor(trigger)
+                                .active_events(events)
+                                .active_collision_types(collision_types)
                                 .position(local_position)
                                 .build(),
                             parent,
@@ -143,8 +179,11 @@ pub fn unregister_bodies_system(world: &mut World) -> BoxedSystem {
                   _: &mut CommandBuffer,
                   mut query: QueryBorrow<ComponentMut<PhysicsState>>| {
                 if let Some(state) = query.first() {
-                    for (_, rb_handle) in rx.try_iter() {
-                        state.remove_body(rb_handle);
+                    for (id, rb_handle) in rx.try_iter() {
+                        // The handle may refer to another body after a snapshot was restored
+                        if state.body_entity(rb_handle) == Some(id) {
+                            state.remove_body(rb_handle);
+                        }
                     }
                 }
 
@@ -168,8 +207,10 @@ pub fn unregister_colliders_system(world: &mut World) -> BoxedSystem {
                   _: &mut CommandBuffer,
                   mut query: QueryBorrow<ComponentMut<PhysicsState>>| {
                 if let Some(state) = query.first() {
-                    for (_, handle) in rx.try_iter() {
-                        state.remvoe_collider(handle);
+                    for (id, handle) in rx.try_iter() {
+                        if state.collider_entity(handle) == Some(id) {
+                            state.remvoe_collider(handle);
+                        }
                     }
                 }
 
@@ -179,11 +220,14 @@ pub fn unregister_colliders_system(world: &mut World) -> BoxedSystem {
         .boxed()
 }
 
+/// Creates, updates and removes the rapier joints of [`impulse_joint`] relations.
+///
+/// Joints are attached once both rigidbodies have been registered.
 pub fn attach_joints_system(world: &mut World) -> BoxedSystem {
     let (tx, rx) = flume::unbounded();
 
     world.subscribe(
-        tx.filter_event_kind(flax::events::EventKindFilter::ADDED)
+        tx.filter_event_kind(EventKindFilter::ADDED | EventKindFilter::MODIFIED)
             .filter_relations([impulse_joint.as_relation().id()]),
     );
 
@@ -193,6 +237,9 @@ pub fn attach_joints_system(world: &mut World) -> BoxedSystem {
         impulse_joint.as_relation(),
     ));
 
+    // Joints waiting for the rigidbodies of either side
+    let mut pending = Vec::new();
+
     System::builder()
         .with_world()
         .with_cmd_mut()
@@ -204,30 +251,48 @@ pub fn attach_joints_system(world: &mut World) -> BoxedSystem {
                 if let Some(state) = state.first() {
                     for (id, component, _) in removed_rx.try_iter() {
                         let target = component.key().target().expect("joint target is present");
-                        let handle = *world.get(id, impulse_joint_handle(target))?;
+                        pending.retain(|&v| v != (id, target));
 
-                        state.detach_joint(handle);
-                        cmd.remove(id, impulse_joint_handle(target));
+                        if let Ok(handle) = world.get(id, impulse_joint_handle(target)) {
+                            state.detach_joint(*handle);
+                            cmd.remove(id, impulse_joint_handle(target));
+                        }
                     }
 
-                    for added in rx.try_iter() {
-                        let body1 = *world
-                            .get(added.id, rb_handle())
-                            .context("Missing rigidbody for joint source")?;
-
-                        let target = added.key.target().expect("joint target is present");
-                        let body2 = *world
-                            .get(target, rb_handle())
-                            .context("Missing rigidbody for joint target")?;
+                    for event in rx.try_iter() {
+                        let target = event.key.target().expect("joint target is present");
+
+                        let handle = world.get(event.id, impulse_joint_handle(target));
+
+                        match (event.kind, handle) {
+                            (EventKind::Modified, Ok(handle)) => {
+                                if let Ok(data) = world.get(event.id, impulse_joint(target)) {
+                                    state.update_joint(*handle, *data);
+                                }
+                            }
+                            _ => {
+                                if !pending.contains(&(event.id, target)) {
+                                    pending.push((event.id, target));
+                                }
+                            }
+                        }
+                    }
 
-                        let data = world
-                            .get(added.id, impulse_joint(target))
-                            .context("Missing joint data between entity pairs")?;
+                    pending.retain(|&(id, target)| {
+                        let Ok(data) = world.get(id, impulse_joint(target)) else {
+                            return false;
+                        };
 
-                        let handle = state.attach_joint(body1, body2, *data);
+                        let (Ok(body1), Ok(body2)) =
+                            (world.get(id, rb_handle()), world.get(target, rb_handle()))
+                        else {
+                            return true;
+                        };
 
-                        cmd.set(added.id, impulse_joint_handle(target), handle);
-                    }
+                        let handle = state.attach_joint(*body1, *body2, *data);
+                        cmd.set(id, impulse_joint_handle(target), handle);
+                        false
+                    });
                 }
 
                 anyhow::Ok(())
@@ -257,6 +322,38 @@ pub fn update_bodies_system() -> BoxedSystem {
         .boxed()
 }
 
+/// Disables the bodies and colliders of entities returned to a [`Pool`](ivy_core::pool::Pool),
+/// and enables them again once spawned
+#[allow(clippy::type_complexity)]
+pub fn update_pooled_system() -> BoxedSystem {
+    System::builder()
+        .with_query(Query::new(physics_state().as_mut()))
+        .with_query(Query::new((rb_handle().copied(), pooled().satisfied())))
+        .with_query(
+            Query::new((collider_handle().copied(), pooled().satisfied())).without(rb_handle()),
+        )
+        .build(
+            move |mut state: QueryBorrow<ComponentMut<PhysicsState>>,
+                  mut bodies: QueryBorrow<(Copied<Component<RigidBodyHandle>>, _)>,
+                  mut colliders: QueryBorrow<(Copied<Component<ColliderHandle>>, _), _>| {
+                let Some(state) = state.first() else {
+                    return anyhow::Ok(());
+                };
+
+                for (handle, pooled) in &mut bodies {
+                    state.set_body_enabled(handle, !pooled);
+                }
+
+                for (handle, pooled) in &mut colliders {
+                    state.set_collider_enabled(handle, !pooled);
+                }
+
+                anyhow::Ok(())
+            },
+        )
+        .boxed()
+}
+
 // writes collider position data into the physics state
 pub fn update_colliders_system() -> BoxedSystem {
     System::builder()
@@ -281,19 +378,132 @@ pub fn update_colliders_system() -> BoxedSystem {
         .boxed()
 }
 
+/// Applies changed collision layers to the colliders, and resets the colliders whose layers were
+/// removed
+pub fn update_collision_layers_system(world: &mut World) -> BoxedSystem {
+    let (tx, rx) = flume::unbounded();
+
+    world.subscribe(RemovedComponentSubscriber::new(tx, collision_layers()));
+
+    System::builder()
+        .with_world()
+        .with_query(Query::new(physics_state().as_mut()))
+        .with_query(Query::new((
+            collider_handle().copied(),
+            collision_layers().modified(),
+        )))
+        .build(
+            move |world: &World,
+                  mut state: QueryBorrow<ComponentMut<PhysicsState>>,
+                  mut query: QueryBorrow<(
+                Copied<Component<ColliderHandle>>,
+                ChangeFilter<CollisionLayers>,
+            )>| {
+                if let Some(state) = state.first() {
+                    for (handle, layers) in query.iter() {
+                        state.set_collision_layers(handle, layers);
+                    }
+
+                    for (id, _) in rx.try_iter() {
+                        // The collider is removed along with the entity
+                        let Ok(handle) = world.get_copy(id, collider_handle()) else {
+                            continue;
+                        };
+
+                        if state.collider_entity(handle) == Some(id) {
+                            state.set_collision_layers(handle, &CollisionLayers::default());
+                        }
+                    }
+                }
+
+                anyhow::Ok(())
+            },
+        )
+        .boxed()
+}
+
+/// Makes registered colliders triggers when `is_trigger` is added, and solid again when it is
+/// removed
+pub fn update_triggers_system(world: &mut World) -> BoxedSystem {
+    let (tx, rx) = flume::unbounded();
+
+    world.subscribe(RemovedComponentSubscriber::new(tx, is_trigger()));
+
+    System::builder()
+        .with_world()
+        .with_query(Query::new(physics_state().as_mut()))
+        .with_query(Query::new((
+            entity_ids(),
+            collider_handle().copied(),
+            is_trigger().added(),
+        )))
+        .build(
+            move |world: &World,
+                  mut state: QueryBorrow<ComponentMut<PhysicsState>>,
+                  mut query: QueryBorrow<(
+                EntityIds,
+                Copied<Component<ColliderHandle>>,
+                ChangeFilter<()>,
+            )>| {
+                if let Some(state) = state.first() {
+                    for (id, handle, _) in query.iter() {
+                        if state.collider_entity(handle) == Some(id) {
+                            state.set_trigger(handle, true);
+                        }
+                    }
+
+                    for (id, _) in rx.try_iter() {
+                        // The collider is removed along with the entity
+                        let Ok(handle) = world.get_copy(id, collider_handle()) else {
+                            continue;
+                        };
+
+                        if state.collider_entity(handle) == Some(id) {
+                            state.set_trigger(handle, false);
+                        }
+                    }
+                }
+
+                anyhow::Ok(())
+            },
+        )
+        .boxed()
+}
+
 pub fn physics_step_system() -> BoxedSystem {
     System::builder()
         .with_query(Query::new((
             physics_state().as_mut(),
             gravity().source(engine()),
+            scope_timings().source(engine()).opt(),
         )))
-        .for_each(|(v, gravity)| {
+        .for_each(|(v, gravity, timings)| {
+            let _scope = timings.map(|v| v.scope("physics_step"));
             v.set_gravity(*gravity);
             v.step();
         })
         .boxed()
 }
 
+/// Sends the trigger events of the physics steps through the
+/// [`EventChannels`](ivy_core::channels::EventChannels)
+pub fn dispatch_trigger_events_system() -> BoxedSystem {
+    System::builder()
+        .with_query(Query::new((
+            physics_state().as_mut(),
+            event_channels().as_mut().source(engine()),
+        )))
+        .for_each(|(state, channels)| {
+            for event in state.drain_trigger_events() {
+                match event {
+                    TriggerEvent::Enter(v) => channels.send(v),
+                    TriggerEvent::Exit(v) => channels.send(v),
+                }
+            }
+        })
+        .boxed()
+}
+
 pub fn sync_simulation_bodies_system() -> BoxedSystem {
     System::builder()
         .with_query(Query::new(physics_state().as_mut()))
@@ -343,32 +553,10 @@ pub fn gizmo_system(dt: f32) -> BoxedSystem {
                     let origin = transform.transform_point3(Vec3::ZERO);
 
                     let dv = effector.net_velocity_change(dt);
-                    gizmos.draw(Line::new(origin, dv, DEFAULT_THICKNESS, Color::red()));
-                    gizmos.draw(Line::new(
-                        origin,
-                        transform.transform_vector3(Vec3::Z),
-                        DEFAULT_THICKNESS,
-                        Color::blue(),
-                    ));
-                    gizmos.draw(Line::new(
-                        origin,
-                        transform.transform_vector3(Vec3::X),
-                        DEFAULT_THICKNESS,
-                        Color::red(),
-                    ));
-                    gizmos.draw(Line::new(
-                        origin,
-                        transform.transform_vector3(Vec3::Y),
-                        DEFAULT_THICKNESS,
-                        Color::green(),
-                    ));
-                    gizmos.draw(Line::new(
-                        origin,
-                        velocity,
-                        DEFAULT_THICKNESS,
-                        Color::cyan(),
-                    ));
-                    gizmos.draw(Line::new(origin, w, DEFAULT_THICKNESS, Color::purple()));
+                    gizmos.draw(Arrow::new(origin, dv, Color::red()));
+                    gizmos.draw(Axes::new(*transform));
+                    gizmos.draw(Arrow::new(origin, velocity, Color::cyan()));
+                    gizmos.draw(Arrow::new(origin, w, Color::purple()));
                 }
 
                 anyhow::Ok(())
@@ -433,3 +621,182 @@ pub fn apply_effectors_system(dt: f32) -> BoxedSystem {
         })
         .boxed()
 }
+
+#[cfg(test)]
+mod test {
+    use flax::{Entity, Schedule};
+    use ivy_core::{components::TransformBundle, EntityBuilderExt};
+
+    use super::*;
+    use crate::{
+        state::PhysicsStateConfiguration, ColliderBundle, JointBundle, LayerMask, RigidBodyBundle,
+    };
+
+    #[test]
+    fn joint_lifecycle() {
+        let mut world = World::new();
+
+        world
+            .set(
+                engine(),
+                physics_state(),
+                PhysicsState::new(&PhysicsStateConfiguration::default(), 1.0 / 60.0),
+            )
+            .unwrap();
+
+        let mut schedule = Schedule::builder()
+            .with_system(unregister_bodies_system(&mut world))
+            .with_system(register_bodies_system())
+            .flush()
+            .with_system(register_colliders_system())
+            .with_system(attach_joints_system(&mut world))
+            .flush()
+            .build();
+
+        let joint_count = |world: &World| {
+            world
+                .get(engine(), physics_state())
+                .unwrap()
+                .joints()
+                .count()
+        };
+
+        let target = Entity::builder()
+            .mount(TransformBundle::default())
+            .spawn(&mut world);
+
+        let id = Entity::builder()
+            .mount(TransformBundle::default())
+            .mount(RigidBodyBundle::dynamic())
+            .mount(ColliderBundle::new(SharedShape::ball(0.5)))
+            .mount(JointBundle::fixed(target))
+            .spawn(&mut world);
+
+        // Waits for the rigidbody of the target
+        schedule.execute_seq(&mut world).unwrap();
+        assert_eq!(joint_count(&world), 0);
+
+        Entity::builder()
+            .mount(RigidBodyBundle::fixed())
+            .append_to(&mut world, target)
+            .unwrap();
+        schedule.execute_seq(&mut world).unwrap();
+        schedule.execute_seq(&mut world).unwrap();
+        assert_eq!(joint_count(&world), 1);
+        let handle = *world.get(id, impulse_joint_handle(target)).unwrap();
+
+        // Modifying the relation updates the attached joint
+        let joint = *JointBundle::fixed(target)
+            .with_contacts_enabled(false)
+            .joint();
+        world.set(id, impulse_joint(target), joint).unwrap();
+        schedule.execute_seq(&mut world).unwrap();
+
+        {
+            let state = world.get(engine(), physics_state()).unwrap();
+            let (_, attached) = state.joints().find(|v| v.0 == handle).unwrap();
+            assert!(!attached.data.contacts_enabled);
+        }
+
+        world.remove(id, impulse_joint(target)).unwrap();
+        schedule.execute_seq(&mut world).unwrap();
+        assert_eq!(joint_count(&world), 0);
+        assert!(!world.has(id, impulse_joint_handle(target)));
+    }
+
+    #[test]
+    fn toggle_trigger() {
+        let mut world = World::new();
+
+        world
+            .set(
+                engine(),
+                physics_state(),
+                PhysicsState::new(&PhysicsStateConfiguration::default(), 1.0 / 60.0),
+            )
+            .unwrap();
+
+        let mut schedule = Schedule::builder()
+            .with_system(register_bodies_system())
+            .flush()
+            .with_system(register_colliders_system())
+            .flush()
+            .with_system(update_triggers_system(&mut world))
+            .build();
+
+        let id = Entity::builder()
+            .mount(TransformBundle::default())
+            .mount(RigidBodyBundle::fixed())
+            .mount(ColliderBundle::new(SharedShape::ball(0.5)))
+            .spawn(&mut world);
+
+        let is_sensor = |world: &World| {
+            let handle = world.get_copy(id, collider_handle()).unwrap();
+            let state = world.get(engine(), physics_state()).unwrap();
+            state.collider(handle).is_sensor()
+        };
+
+        schedule.execute_seq(&mut world).unwrap();
+        assert!(!is_sensor(&world));
+
+        world.set(id, is_trigger(), ()).unwrap();
+        schedule.execute_seq(&mut world).unwrap();
+        assert!(is_sensor(&world));
+
+        world.remove(id, is_trigger()).unwrap();
+        schedule.execute_seq(&mut world).unwrap();
+        assert!(!is_sensor(&world));
+    }
+
+    #[test]
+    fn collision_layers_lifecycle() {
+        let mut world = World::new();
+
+        world
+            .set(
+                engine(),
+                physics_state(),
+                PhysicsState::new(&PhysicsStateConfiguration::default(), 1.0 / 60.0),
+            )
+            .unwrap();
+
+        let mut schedule = Schedule::builder()
+            .with_system(register_bodies_system())
+            .flush()
+            .with_system(register_colliders_system())
+            .flush()
+            .with_system(update_collision_layers_system(&mut world))
+            .build();
+
+        let layers = CollisionLayers::new(LayerMask::layer(1), LayerMask::layer(2));
+
+        let id = Entity::builder()
+            .mount(TransformBundle::default())
+            .mount(RigidBodyBundle::dynamic())
+            .mount(ColliderBundle::new(SharedShape::ball(0.5)))
+            .spawn(&mut world);
+
+        let groups = |world: &World| {
+            let handle = world.get_copy(id, collider_handle()).unwrap();
+            let state = world.get(engine(), physics_state()).unwrap();
+            state.collider(handle).collision_groups()
+        };
+
+        schedule.execute_seq(&mut world).unwrap();
+        assert_eq!(
+            groups(&world),
+            CollisionLayers::default().interaction_groups()
+        );
+
+        world.set(id, collision_layers(), layers).unwrap();
+        schedule.execute_seq(&mut world).unwrap();
+        assert_eq!(groups(&world), layers.interaction_groups());
+
+        world.remove(id, collision_layers()).unwrap();
+        schedule.execute_seq(&mut world).unwrap();
+        assert_eq!(
+            groups(&world),
+            CollisionLayers::default().interaction_groups()
+        );
+    }
+}
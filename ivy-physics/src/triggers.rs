@@ -0,0 +1,235 @@
+use std::{
+    collections::{HashMap, HashSet},
+    mem,
+};
+
+use flax::Entity;
+use rapier3d::prelude::{
+    ActiveCollisionTypes, ActiveEvents, Collider, ColliderHandle, ColliderSet, CollisionEvent,
+    ContactPair, EventHandler, NarrowPhase, Real, RigidBodySet,
+};
+
+/// Sent through the [`EventChannels`](ivy_core::channels::EventChannels) when a collider starts
+/// intersecting a trigger collider
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TriggerEnter {
+    pub trigger: Entity,
+    pub other: Entity,
+}
+
+/// Sent through the [`EventChannels`](ivy_core::channels::EventChannels) when a collider stops
+/// intersecting a trigger collider, or either is removed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TriggerExit {
+    pub trigger: Entity,
+    pub other: Entity,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TriggerEvent {
+    Enter(TriggerEnter),
+    Exit(TriggerExit),
+}
+
+/// Receives the intersection events of sensor colliders during a physics step
+pub(crate) struct TriggerCollector {
+    tx: flume::Sender<CollisionEvent>,
+}
+
+impl EventHandler for TriggerCollector {
+    fn handle_collision_event(
+        &self,
+        _: &RigidBodySet,
+        _: &ColliderSet,
+        event: CollisionEvent,
+        _: Option<&ContactPair>,
+    ) {
+        if event.sensor() {
+            self.tx.send(event).ok();
+        }
+    }
+
+    fn handle_contact_force_event(
+        &self,
+        _: Real,
+        _: &RigidBodySet,
+        _: &ColliderSet,
+        _: &ContactPair,
+        _: Real,
+    ) {
+    }
+}
+
+/// Tracks the intersecting trigger pairs and translates collision events to entities
+pub(crate) struct TriggerTracker {
+    collector: TriggerCollector,
+    rx: flume::Receiver<CollisionEvent>,
+    /// Entities of intersecting pairs, as colliders are no longer available once removed
+    intersections: HashMap<(ColliderHandle, ColliderHandle), (Entity, Entity)>,
+    events: Vec<TriggerEvent>,
+}
+
+impl TriggerTracker {
+    pub fn new() -> Self {
+        let (tx, rx) = flume::unbounded();
+        Self {
+            collector: TriggerCollector { tx },
+            rx,
+            intersections: HashMap::new(),
+            events: Vec::new(),
+        }
+    }
+
+    pub fn collector(&self) -> &TriggerCollector {
+        &self.collector
+    }
+
+    pub fn process(&mut self, colliders: &ColliderSet) {
+        for event in self.rx.try_iter() {
+            match event {
+                CollisionEvent::Started(a, b, _) => {
+                    let (Some(collider_a), Some(collider_b)) = (colliders.get(a), colliders.get(b))
+                    else {
+                        continue;
+                    };
+
+                    let (trigger, other) = trigger_pair(collider_a, collider_b);
+
+                    self.intersections.insert((a, b), (trigger, other));
+                    self.events
+                        .push(TriggerEvent::Enter(TriggerEnter { trigger, other }));
+                }
+                CollisionEvent::Stopped(a, b, _) => {
+                    let intersection = self
+                        .intersections
+                        .remove(&(a, b))
+                        .or_else(|| self.intersections.remove(&(b, a)));
+
+                    if let Some((trigger, other)) = intersection {
+                        self.events
+                            .push(TriggerEvent::Exit(TriggerExit { trigger, other }));
+                    }
+                }
+            }
+        }
+    }
+
+    /// Replaces the intersecting pairs with those of a restored simulation.
+    ///
+    /// Pairs which no longer intersect emit exit events, and new pairs emit enter events.
+    pub fn restore(&mut self, colliders: &ColliderSet, narrow_phase: &NarrowPhase) {
+        self.rx.drain().for_each(drop);
+        let previous = mem::take(&mut self.intersections);
+
+        for (a, b, intersecting) in narrow_phase.intersection_pairs() {
+            let (true, Some(collider_a), Some(collider_b)) =
+                (intersecting, colliders.get(a), colliders.get(b))
+            else {
+                continue;
+            };
+
+            self.intersections
+                .insert((a, b), trigger_pair(collider_a, collider_b));
+        }
+
+        let current = self.intersections.values().copied().collect::<HashSet<_>>();
+        let previous = previous.into_values().collect::<HashSet<_>>();
+
+        for &(trigger, other) in previous.difference(&current) {
+            self.events
+                .push(TriggerEvent::Exit(TriggerExit { trigger, other }));
+        }
+
+        for &(trigger, other) in current.difference(&previous) {
+            self.events
+                .push(TriggerEvent::Enter(TriggerEnter { trigger, other }));
+        }
+    }
+
+    /// Exits the intersections of a collider which is no longer a trigger
+    pub fn exit_collider(&mut self, handle: ColliderHandle) {
+        let events = &mut self.events;
+        self.intersections.retain(|&(a, b), &mut (trigger, other)| {
+            if a != handle && b != handle {
+                return true;
+            }
+
+            events.push(TriggerEvent::Exit(TriggerExit { trigger, other }));
+            false
+        });
+    }
+
+    pub fn drain(&mut self) -> std::vec::Drain<'_, TriggerEvent> {
+        self.events.drain(..)
+    }
+}
+
+/// Returns the events and collision types of a trigger collider, or of a solid collider
+pub(crate) fn trigger_flags(trigger: bool) -> (ActiveEvents, ActiveCollisionTypes) {
+    if trigger {
+        // Detect kinematic and fixed bodies entering the trigger
+        (ActiveEvents::COLLISION_EVENTS, ActiveCollisionTypes::all())
+    } else {
+        (ActiveEvents::empty(), ActiveCollisionTypes::default())
+    }
+}
+
+/// Returns the entities of the trigger and the other collider of an intersecting pair
+fn trigger_pair(a: &Collider, b: &Collider) -> (Entity, Entity) {
+    let id_a = Entity::try_from_bits(a.user_data as u64).expect("user_data is valid entity");
+    let id_b = Entity::try_from_bits(b.user_data as u64).expect("user_data is valid entity");
+
+    if a.is_sensor() {
+        (id_a, id_b)
+    } else {
+        (id_b, id_a)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use flax::World;
+    use rapier3d::prelude::ColliderBuilder;
+
+    use super::*;
+
+    #[test]
+    fn exit_collider() {
+        let mut world = World::new();
+        let trigger = world.spawn();
+        let other = world.spawn();
+        let unrelated = world.spawn();
+
+        let mut colliders = ColliderSet::new();
+        let a = colliders.insert(ColliderBuilder::ball(1.0).sensor(true).build());
+        let b = colliders.insert(ColliderBuilder::ball(1.0).build());
+        let c = colliders.insert(ColliderBuilder::ball(1.0).build());
+
+        let mut tracker = TriggerTracker::new();
+        tracker.intersections.insert((a, b), (trigger, other));
+        tracker.intersections.insert((c, a), (trigger, unrelated));
+        tracker.intersections.insert((b, c), (other, unrelated));
+
+        tracker.exit_collider(a);
+
+        let events = tracker.drain().collect::<Vec<_>>();
+        assert_eq!(events.len(), 2);
+        assert!(events.contains(&TriggerEvent::Exit(TriggerExit { trigger, other })));
+        assert!(events.contains(&TriggerEvent::Exit(TriggerExit {
+            trigger,
+            other: unrelated
+        })));
+        assert_eq!(tracker.intersections.len(), 1);
+    }
+
+    #[test]
+    fn flags() {
+        let (events, types) = trigger_flags(true);
+        assert_eq!(events, ActiveEvents::COLLISION_EVENTS);
+        assert!(types.contains(ActiveCollisionTypes::DYNAMIC_FIXED));
+
+        let (events, types) = trigger_flags(false);
+        assert!(events.is_empty());
+        assert_eq!(types, ActiveCollisionTypes::default());
+    }
+}
@@ -0,0 +1,297 @@
+// Physically based sky, following "A Scalable and Production Ready Sky and Atmosphere Rendering
+// Technique" by Sébastien Hillaire.
+//
+// Distances are in kilometers.
+
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) clip_position: vec4<f32>,
+    @location(1) uv: vec2<f32>,
+};
+
+@vertex
+fn vs_main(@builtin(vertex_index) vertex_index: u32) -> VertexOutput {
+    var result: VertexOutput;
+    let x = i32(vertex_index) / 2;
+    let y = i32(vertex_index) & 1;
+    let uv = vec2<f32>(
+        f32(x) * 2.0,
+        f32(y) * 2.0
+    );
+    result.position = vec4<f32>(
+        uv.x * 2.0 - 1.0,
+        1.0 - uv.y * 2.0,
+        1.0, 1.0
+    );
+    result.clip_position = result.position;
+    result.uv = uv;
+    return result;
+}
+
+struct AtmosphereData {
+    rayleigh_scattering: vec3<f32>,
+    rayleigh_scale_height: f32,
+    mie_scattering: vec3<f32>,
+    mie_scale_height: f32,
+    mie_extinction: vec3<f32>,
+    mie_g: f32,
+    ozone_absorption: vec3<f32>,
+    ozone_center: f32,
+    ground_albedo: vec3<f32>,
+    ozone_width: f32,
+    sun_direction: vec3<f32>,
+    sun_intensity: f32,
+    ground_radius: f32,
+    top_radius: f32,
+    observer_altitude: f32,
+    sun_angular_radius: f32,
+}
+
+struct FaceData {
+    inv_proj: mat4x4<f32>,
+    inv_view: mat4x4<f32>,
+}
+
+@group(0) @binding(0)
+var<uniform> atmosphere: AtmosphereData;
+
+@group(1) @binding(0)
+var lut_sampler: sampler;
+
+@group(1) @binding(1)
+var transmittance_lut: texture_2d<f32>;
+
+@group(2) @binding(0)
+var<uniform> face: FaceData;
+
+@group(2) @binding(1)
+var multiscattering_lut: texture_2d<f32>;
+
+const PI: f32 = 3.14159265359;
+
+const TRANSMITTANCE_STEPS: i32 = 40;
+const MULTISCATTERING_STEPS: i32 = 20;
+const MULTISCATTERING_DIRECTIONS: i32 = 8;
+const SKY_STEPS: i32 = 32;
+
+// The sun disk is drawn brighter than the sky, but not so bright that it dominates the specular
+// environment map
+const SUN_DISK_SCALE: f32 = 32.0;
+
+// Distance along the ray to the sphere around the planet center, or -1 if it is missed
+fn ray_sphere(origin: vec3<f32>, dir: vec3<f32>, radius: f32) -> f32 {
+    let b = dot(origin, dir);
+    let c = dot(origin, origin) - radius * radius;
+    if c > 0.0 && b > 0.0 {
+        return -1.0;
+    }
+
+    let discriminant = b * b - c;
+    if discriminant < 0.0 {
+        return -1.0;
+    }
+
+    let s = sqrt(discriminant);
+    if c < 0.0 {
+        // Inside the sphere
+        return -b + s;
+    }
+
+    return -b - s;
+}
+
+struct Medium {
+    rayleigh: vec3<f32>,
+    mie: vec3<f32>,
+    extinction: vec3<f32>,
+}
+
+fn sample_medium(pos: vec3<f32>) -> Medium {
+    let altitude = length(pos) - atmosphere.ground_radius;
+
+    let rayleigh_density = exp(-altitude / atmosphere.rayleigh_scale_height);
+    let mie_density = exp(-altitude / atmosphere.mie_scale_height);
+    // Tent shaped ozone layer
+    let ozone_density = max(0.0, 1.0 - abs(altitude - atmosphere.ozone_center) / (atmosphere.ozone_width * 0.5));
+
+    var medium: Medium;
+    medium.rayleigh = atmosphere.rayleigh_scattering * rayleigh_density;
+    medium.mie = atmosphere.mie_scattering * mie_density;
+    medium.extinction = medium.rayleigh + atmosphere.mie_extinction * mie_density + atmosphere.ozone_absorption * ozone_density;
+    return medium;
+}
+
+fn rayleigh_phase(cos_theta: f32) -> f32 {
+    return 3.0 / (16.0 * PI) * (1.0 + cos_theta * cos_theta);
+}
+
+// Cornette-Shanks
+fn mie_phase(cos_theta: f32) -> f32 {
+    let g = atmosphere.mie_g;
+    let g2 = g * g;
+    let denom = (2.0 + g2) * pow(1.0 + g2 - 2.0 * g * cos_theta, 1.5);
+    return 3.0 / (8.0 * PI) * (1.0 - g2) * (1.0 + cos_theta * cos_theta) / denom;
+}
+
+fn lut_uv(pos: vec3<f32>, sun_dir: vec3<f32>) -> vec2<f32> {
+    let height = length(pos);
+    let cos_zenith = dot(sun_dir, pos / height);
+    let altitude = (height - atmosphere.ground_radius) / (atmosphere.top_radius - atmosphere.ground_radius);
+    return clamp(vec2(cos_zenith * 0.5 + 0.5, altitude), vec2(0.0), vec2(1.0));
+}
+
+fn sample_transmittance(pos: vec3<f32>, sun_dir: vec3<f32>) -> vec3<f32> {
+    return textureSampleLevel(transmittance_lut, lut_sampler, lut_uv(pos, sun_dir), 0.0).rgb;
+}
+
+fn sample_multiscattering(pos: vec3<f32>, sun_dir: vec3<f32>) -> vec3<f32> {
+    return textureSampleLevel(multiscattering_lut, lut_sampler, lut_uv(pos, sun_dir), 0.0).rgb;
+}
+
+// Position and direction described by a pixel of the transmittance and multiple scattering luts
+fn lut_position(uv: vec2<f32>) -> vec3<f32> {
+    return vec3(0.0, mix(atmosphere.ground_radius, atmosphere.top_radius, uv.y), 0.0);
+}
+
+fn lut_sun_direction(uv: vec2<f32>) -> vec3<f32> {
+    let cos_zenith = uv.x * 2.0 - 1.0;
+    return vec3(sqrt(max(0.0, 1.0 - cos_zenith * cos_zenith)), cos_zenith, 0.0);
+}
+
+// Integrates the energy lost over a segment, avoiding a division by zero in empty space
+fn segment_integral(value: vec3<f32>, segment_transmittance: vec3<f32>, extinction: vec3<f32>) -> vec3<f32> {
+    return (value - value * segment_transmittance) / max(extinction, vec3(1e-7));
+}
+
+@fragment
+fn fs_transmittance(in: VertexOutput) -> @location(0) vec4<f32> {
+    let pos = lut_position(in.uv);
+    let dir = lut_sun_direction(in.uv);
+
+    if ray_sphere(pos, dir, atmosphere.ground_radius) > 0.0 {
+        return vec4(0.0, 0.0, 0.0, 1.0);
+    }
+
+    let dist = ray_sphere(pos, dir, atmosphere.top_radius);
+    let dt = dist / f32(TRANSMITTANCE_STEPS);
+
+    var optical_depth = vec3(0.0);
+    for (var i = 0; i < TRANSMITTANCE_STEPS; i++) {
+        let t = (f32(i) + 0.3) * dt;
+        optical_depth += sample_medium(pos + t * dir).extinction * dt;
+    }
+
+    return vec4(exp(-optical_depth), 1.0);
+}
+
+// Approximates the infinite scattering orders as a geometric series of the second order,
+// assuming an isotropic phase
+@fragment
+fn fs_multiscattering(in: VertexOutput) -> @location(0) vec4<f32> {
+    let pos = lut_position(in.uv);
+    let sun_dir = lut_sun_direction(in.uv);
+
+    let direction_count = f32(MULTISCATTERING_DIRECTIONS * MULTISCATTERING_DIRECTIONS);
+    let isotropic_phase = 1.0 / (4.0 * PI);
+
+    var lum_total = vec3(0.0);
+    var transfer_total = vec3(0.0);
+
+    for (var i = 0; i < MULTISCATTERING_DIRECTIONS; i++) {
+        for (var j = 0; j < MULTISCATTERING_DIRECTIONS; j++) {
+            let theta = 2.0 * PI * (f32(i) + 0.5) / f32(MULTISCATTERING_DIRECTIONS);
+            let phi = acos(1.0 - 2.0 * (f32(j) + 0.5) / f32(MULTISCATTERING_DIRECTIONS));
+            let dir = vec3(sin(phi) * cos(theta), cos(phi), sin(phi) * sin(theta));
+
+            let ground_dist = ray_sphere(pos, dir, atmosphere.ground_radius);
+            var dist = ray_sphere(pos, dir, atmosphere.top_radius);
+            if ground_dist > 0.0 {
+                dist = ground_dist;
+            }
+
+            let dt = dist / f32(MULTISCATTERING_STEPS);
+
+            var lum = vec3(0.0);
+            var transfer = vec3(0.0);
+            var transmittance = vec3(1.0);
+
+            for (var s = 0; s < MULTISCATTERING_STEPS; s++) {
+                let sample_pos = pos + (f32(s) + 0.3) * dt * dir;
+                let medium = sample_medium(sample_pos);
+                let segment_transmittance = exp(-dt * medium.extinction);
+
+                let scattering = medium.rayleigh + medium.mie;
+                transfer += transmittance * segment_integral(scattering, segment_transmittance, medium.extinction);
+
+                let in_scattering = scattering * isotropic_phase * sample_transmittance(sample_pos, sun_dir);
+                lum += transmittance * segment_integral(in_scattering, segment_transmittance, medium.extinction);
+
+                transmittance *= segment_transmittance;
+            }
+
+            if ground_dist > 0.0 {
+                let hit = normalize(pos + ground_dist * dir) * atmosphere.ground_radius;
+                let irradiance = sample_transmittance(hit, sun_dir) * max(dot(normalize(hit), sun_dir), 0.0);
+                lum += transmittance * atmosphere.ground_albedo * irradiance / PI;
+            }
+
+            lum_total += lum / direction_count;
+            transfer_total += transfer / direction_count;
+        }
+    }
+
+    let psi = lum_total / max(vec3(1.0) - transfer_total, vec3(1e-4));
+    return vec4(psi, 1.0);
+}
+
+@fragment
+fn fs_sky(in: VertexOutput) -> @location(0) vec4<f32> {
+    let view_pos_homogeneous = face.inv_proj * in.clip_position;
+    let view_ray_direction = view_pos_homogeneous.xyz / view_pos_homogeneous.w;
+    let dir = normalize((face.inv_view * vec4(view_ray_direction, 0.0)).xyz);
+
+    let sun_dir = atmosphere.sun_direction;
+    let origin = vec3(0.0, atmosphere.ground_radius + atmosphere.observer_altitude, 0.0);
+
+    let ground_dist = ray_sphere(origin, dir, atmosphere.ground_radius);
+    var dist = ray_sphere(origin, dir, atmosphere.top_radius);
+    if ground_dist > 0.0 {
+        dist = ground_dist;
+    }
+
+    let cos_theta = dot(dir, sun_dir);
+    let rayleigh = rayleigh_phase(cos_theta);
+    let mie = mie_phase(cos_theta);
+
+    let dt = max(dist, 0.0) / f32(SKY_STEPS);
+
+    var lum = vec3(0.0);
+    var transmittance = vec3(1.0);
+
+    for (var i = 0; i < SKY_STEPS; i++) {
+        let sample_pos = origin + (f32(i) + 0.3) * dt * dir;
+        let medium = sample_medium(sample_pos);
+        let segment_transmittance = exp(-dt * medium.extinction);
+
+        let sun_transmittance = sample_transmittance(sample_pos, sun_dir);
+        let multiscattering = sample_multiscattering(sample_pos, sun_dir);
+
+        let in_scattering = medium.rayleigh * (rayleigh * sun_transmittance + multiscattering)
+            + medium.mie * (mie * sun_transmittance + multiscattering);
+
+        lum += transmittance * segment_integral(in_scattering, segment_transmittance, medium.extinction);
+        transmittance *= segment_transmittance;
+    }
+
+    if ground_dist > 0.0 {
+        let hit = normalize(origin + ground_dist * dir) * atmosphere.ground_radius;
+        let irradiance = sample_transmittance(hit, sun_dir) * max(dot(normalize(hit), sun_dir), 0.0);
+        lum += transmittance * atmosphere.ground_albedo * irradiance / PI;
+    } else {
+        let sun_cos = cos(atmosphere.sun_angular_radius);
+        let disk = smoothstep(sun_cos - 1e-4, sun_cos, cos_theta);
+        lum += transmittance * disk * SUN_DISK_SCALE;
+    }
+
+    return vec4(lum * atmosphere.sun_intensity, 1.0);
+}
@@ -0,0 +1,102 @@
+struct ExposureParams {
+    min_log_lum: f32,
+    log_lum_range: f32,
+    pixel_count: f32,
+    delta_time: f32,
+    min_ev: f32,
+    max_ev: f32,
+    compensation: f32,
+    speed_up: f32,
+    speed_down: f32,
+    history_valid: u32,
+}
+
+const BIN_COUNT: u32 = 256u;
+
+@group(0) @binding(0)
+var<uniform> params: ExposureParams;
+
+@group(0) @binding(1)
+var<storage, read_write> histogram: array<atomic<u32>, BIN_COUNT>;
+
+@group(0) @binding(2)
+var previous_exposure: texture_2d<f32>;
+
+@group(0) @binding(3)
+var source_texture: texture_2d<f32>;
+
+@group(0) @binding(4)
+var exposure_texture: texture_storage_2d<r32float, write>;
+
+var<workgroup> local_bins: array<atomic<u32>, BIN_COUNT>;
+var<workgroup> weighted_bins: array<f32, BIN_COUNT>;
+
+// Bin 0 is reserved for black pixels, which would otherwise drag the average down
+fn luminance_bin(color: vec3<f32>) -> u32 {
+    let lum = dot(color, vec3(0.2126, 0.7152, 0.0722));
+    if lum < 1e-5 {
+        return 0u;
+    }
+
+    let t = clamp((log2(lum) - params.min_log_lum) / params.log_lum_range, 0.0, 1.0);
+    return u32(t * f32(BIN_COUNT - 2u) + 1.0);
+}
+
+@compute @workgroup_size(16, 16)
+fn cs_histogram(
+    @builtin(global_invocation_id) id: vec3<u32>,
+    @builtin(local_invocation_index) index: u32,
+) {
+    atomicStore(&local_bins[index], 0u);
+    workgroupBarrier();
+
+    let size = textureDimensions(source_texture);
+    if id.x < size.x && id.y < size.y {
+        let color = textureLoad(source_texture, id.xy, 0).rgb;
+        atomicAdd(&local_bins[luminance_bin(color)], 1u);
+    }
+
+    workgroupBarrier();
+    atomicAdd(&histogram[index], atomicLoad(&local_bins[index]));
+}
+
+@compute @workgroup_size(256)
+fn cs_average(@builtin(local_invocation_index) index: u32) {
+    let count = atomicLoad(&histogram[index]);
+    weighted_bins[index] = f32(count) * f32(index);
+
+    // Clear for the next frame
+    atomicStore(&histogram[index], 0u);
+    workgroupBarrier();
+
+    for (var cutoff = BIN_COUNT / 2u; cutoff > 0u; cutoff >>= 1u) {
+        if index < cutoff {
+            weighted_bins[index] += weighted_bins[index + cutoff];
+        }
+
+        workgroupBarrier();
+    }
+
+    if index != 0u {
+        return;
+    }
+
+    // `count` is the number of black pixels for the first invocation
+    let lit_pixels = max(params.pixel_count - f32(count), 1.0);
+    let average_bin = weighted_bins[0] / lit_pixels;
+    let log_lum = (average_bin - 1.0) / f32(BIN_COUNT - 2u) * params.log_lum_range + params.min_log_lum;
+
+    // EV100 of the average luminance, using a reflected light meter calibration of 12.5
+    let target_ev = clamp(log_lum + log2(100.0 / 12.5), params.min_ev, params.max_ev) - params.compensation;
+
+    var ev = target_ev;
+    if params.history_valid != 0u {
+        let previous_ev = -log2(1.2 * textureLoad(previous_exposure, vec2<u32>(0u, 0u), 0).r);
+        let speed = select(params.speed_down, params.speed_up, target_ev > previous_ev);
+        ev = mix(previous_ev, target_ev, 1.0 - exp(-params.delta_time * speed));
+    }
+
+    // Maps the average luminance to middle gray
+    let exposure = 1.0 / (1.2 * exp2(ev));
+    textureStore(exposure_texture, vec2<u32>(0u, 0u), vec4(exposure, 0.0, 0.0, 1.0));
+}
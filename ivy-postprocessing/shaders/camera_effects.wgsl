@@ -0,0 +1,109 @@
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+};
+
+@vertex
+fn vs_main(@builtin(vertex_index) vertex_index: u32) -> VertexOutput {
+    var result: VertexOutput;
+    let x = i32(vertex_index) / 2;
+    let y = i32(vertex_index) & 1;
+    let uv = vec2<f32>(
+        f32(x) * 2.0,
+        f32(y) * 2.0
+    );
+    result.position = vec4<f32>(
+        uv.x * 2.0 - 1.0,
+        1.0 - uv.y * 2.0,
+        1.0, 1.0
+    );
+    result.uv = uv;
+    return result;
+}
+
+struct CameraEffectsData {
+    vignette_intensity: f32,
+    vignette_radius: f32,
+    vignette_smoothness: f32,
+    chromatic_aberration: f32,
+    grain: f32,
+    sharpen: f32,
+    frame: u32,
+    _padding: u32,
+};
+
+@group(0) @binding(0)
+var source_texture: texture_2d<f32>;
+
+@group(0) @binding(1)
+var linear_sampler: sampler;
+
+@group(0) @binding(2)
+var<uniform> effects: CameraEffectsData;
+
+fn luminance(color: vec3<f32>) -> f32 {
+    return dot(color, vec3(0.2126, 0.7152, 0.0722));
+}
+
+// Integer hash giving uncorrelated noise across pixels and frames
+fn hash(v: vec3<u32>) -> f32 {
+    var x = v.x * 1973u + v.y * 9277u + v.z * 26699u;
+    x = (x ^ (x >> 16u)) * 0x7feb352du;
+    x = (x ^ (x >> 15u)) * 0x846ca68bu;
+    x = x ^ (x >> 16u);
+    return f32(x) / 4294967295.0;
+}
+
+fn sample_color(uv: vec2<f32>, center: vec2<f32>) -> vec3<f32> {
+    if effects.chromatic_aberration <= 0.0 {
+        return textureSampleLevel(source_texture, linear_sampler, uv, 0.0).rgb;
+    }
+
+    // Lateral aberration grows towards the edges of the lens
+    let offset = (uv - center) * effects.chromatic_aberration;
+    let r = textureSampleLevel(source_texture, linear_sampler, uv - offset, 0.0).r;
+    let g = textureSampleLevel(source_texture, linear_sampler, uv, 0.0).g;
+    let b = textureSampleLevel(source_texture, linear_sampler, uv + offset, 0.0).b;
+
+    return vec3(r, g, b);
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let size = vec2<f32>(textureDimensions(source_texture));
+    let texel = 1.0 / size;
+    let center = vec2(0.5);
+
+    var color = sample_color(in.uv, center);
+
+    if effects.sharpen > 0.0 {
+        let n = textureSampleLevel(source_texture, linear_sampler, in.uv + vec2(0.0, -texel.y), 0.0).rgb;
+        let s = textureSampleLevel(source_texture, linear_sampler, in.uv + vec2(0.0, texel.y), 0.0).rgb;
+        let e = textureSampleLevel(source_texture, linear_sampler, in.uv + vec2(texel.x, 0.0), 0.0).rgb;
+        let w = textureSampleLevel(source_texture, linear_sampler, in.uv + vec2(-texel.x, 0.0), 0.0).rgb;
+
+        // Unsharp mask against the cross shaped neighbourhood, clamped to avoid ringing
+        let blurred = (n + s + e + w) * 0.25;
+        let lo = min(min(n, s), min(e, w));
+        let hi = max(max(n, s), max(e, w));
+        color = clamp(color + (color - blurred) * effects.sharpen, lo, hi);
+    }
+
+    if effects.vignette_intensity > 0.0 {
+        // Round regardless of the aspect ratio
+        let d = (in.uv - center) * vec2(size.x / size.y, 1.0) * 2.0;
+        let falloff = smoothstep(effects.vignette_radius, effects.vignette_radius + effects.vignette_smoothness, length(d));
+        color *= 1.0 - falloff * effects.vignette_intensity;
+    }
+
+    if effects.grain > 0.0 {
+        let noise = hash(vec3(vec2<u32>(in.position.xy), effects.frame)) - 0.5;
+
+        // Grain is most visible in the midtones, as with film
+        let lum = luminance(color);
+        let response = 1.0 - abs(lum * 2.0 - 1.0);
+        color += noise * effects.grain * (0.25 + 0.75 * response);
+    }
+
+    return vec4(max(color, vec3(0.0)), 1.0);
+}
@@ -0,0 +1,120 @@
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+};
+
+@vertex
+fn vs_main(@builtin(vertex_index) vertex_index: u32) -> VertexOutput {
+    var result: VertexOutput;
+    let x = i32(vertex_index) / 2;
+    let y = i32(vertex_index) & 1;
+    let uv = vec2<f32>(
+        f32(x) * 2.0,
+        f32(y) * 2.0
+    );
+    result.position = vec4<f32>(
+        uv.x * 2.0 - 1.0,
+        1.0 - uv.y * 2.0,
+        1.0, 1.0
+    );
+    result.uv = uv;
+    return result;
+}
+
+struct DepthOfFieldData {
+    inv_proj: mat4x4<f32>,
+    focal_distance: f32,
+    // Circle of confusion at infinity, as a fraction of the image height
+    coc_scale: f32,
+    // Maximum circle of confusion radius in pixels
+    max_radius: f32,
+    // Distance in pixels between each sampled ring
+    radius_step: f32,
+};
+
+@group(0) @binding(0)
+var<uniform> data: DepthOfFieldData;
+
+// Scene color for the coc pass, color and coc for the gather pass
+@group(0) @binding(1)
+var source_texture: texture_2d<f32>;
+
+@group(0) @binding(2)
+var depth_texture: texture_2d<f32>;
+
+@group(0) @binding(3)
+var linear_sampler: sampler;
+
+const GOLDEN_ANGLE: f32 = 2.39996323;
+
+fn view_distance(depth: f32, uv: vec2<f32>) -> f32 {
+    let clip = vec4<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, depth, 1.0);
+    let view = data.inv_proj * clip;
+    return -view.z / view.w;
+}
+
+// Signed circle of confusion radius in pixels, negative in front of the focal plane
+@fragment
+fn fs_coc(in: VertexOutput) -> @location(0) vec4<f32> {
+    let p = vec2<i32>(in.position.xy);
+    let color = textureLoad(source_texture, p, 0);
+    let depth = textureLoad(depth_texture, p, 0).r;
+
+    let distance = max(view_distance(depth, in.uv), 1e-4);
+    let height = f32(textureDimensions(source_texture).y);
+
+    let coc = data.coc_scale * height * (distance - data.focal_distance) / distance;
+
+    return vec4<f32>(color.rgb, clamp(coc, -data.max_radius, data.max_radius));
+}
+
+// Gathers samples in a spiral, where each sample contributes if its circle of confusion reaches
+// the center.
+//
+// Samples behind the center may not blur more than the center itself, which prevents the
+// background from bleeding over sharp foreground objects, while blurry foreground objects
+// spread out over sharp background.
+@fragment
+fn fs_gather(in: VertexOutput) -> @location(0) vec4<f32> {
+    let texel = 1.0 / vec2<f32>(textureDimensions(source_texture));
+
+    let center = textureSampleLevel(source_texture, linear_sampler, in.uv, 0.0);
+    let center_size = abs(center.a);
+
+    var color = center.rgb;
+    var total = 1.0;
+    // How much of the center is covered by blurry foreground
+    var near_coverage = 0.0;
+
+    var radius = data.radius_step;
+    var angle = 0.0;
+
+    while radius < data.max_radius {
+        let uv = in.uv + vec2<f32>(cos(angle), sin(angle)) * texel * radius;
+        let value = textureSampleLevel(source_texture, linear_sampler, uv, 0.0);
+
+        var size = abs(value.a);
+        let is_behind = value.a > center.a;
+        if is_behind {
+            size = min(size, center_size * 2.0);
+        }
+
+        let m = smoothstep(radius - 0.5, radius + 0.5, size);
+        color += mix(color / total, value.rgb, m);
+        total += 1.0;
+
+        if !is_behind && value.a < 0.0 {
+            near_coverage = max(near_coverage, m);
+        }
+
+        radius += data.radius_step / radius;
+        angle += GOLDEN_ANGLE;
+    }
+
+    let blurred = color / total;
+
+    // Keep in focus pixels sharp, unless covered by the foreground
+    let blend = max(smoothstep(0.5, 1.5, center_size), near_coverage);
+
+    return vec4<f32>(mix(center.rgb, blurred, blend), 1.0);
+}
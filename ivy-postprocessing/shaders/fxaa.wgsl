@@ -0,0 +1,184 @@
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+};
+
+@vertex
+fn vs_main(@builtin(vertex_index) vertex_index: u32) -> VertexOutput {
+    var result: VertexOutput;
+    let x = i32(vertex_index) / 2;
+    let y = i32(vertex_index) & 1;
+    let uv = vec2<f32>(
+        f32(x) * 2.0,
+        f32(y) * 2.0
+    );
+    result.position = vec4<f32>(
+        uv.x * 2.0 - 1.0,
+        1.0 - uv.y * 2.0,
+        1.0, 1.0
+    );
+    result.uv = uv;
+    return result;
+}
+
+@group(0) @binding(0)
+var source_texture: texture_2d<f32>;
+
+@group(0) @binding(1)
+var linear_sampler: sampler;
+
+// Minimum contrast required to consider a pixel an edge
+const EDGE_THRESHOLD_MIN: f32 = 0.0312;
+// Contrast relative to the brightest neighbour required to consider a pixel an edge
+const EDGE_THRESHOLD_MAX: f32 = 0.125;
+const SUBPIXEL_QUALITY: f32 = 0.75;
+const SEARCH_STEPS: i32 = 12;
+
+// Distance in pixels of each edge search step, growing to cover longer edges
+fn step_size(i: i32) -> f32 {
+    if i < 5 {
+        return 1.0;
+    } else if i == 5 {
+        return 1.5;
+    } else if i < 10 {
+        return 2.0;
+    } else if i == 10 {
+        return 4.0;
+    }
+
+    return 8.0;
+}
+
+// Perceptual luminance of a linear color
+fn luma(color: vec3<f32>) -> f32 {
+    return sqrt(dot(color, vec3<f32>(0.299, 0.587, 0.114)));
+}
+
+fn sample_color(uv: vec2<f32>) -> vec4<f32> {
+    return textureSampleLevel(source_texture, linear_sampler, uv, 0.0);
+}
+
+fn sample_luma(uv: vec2<f32>, offset: vec2<f32>, texel: vec2<f32>) -> f32 {
+    return luma(sample_color(uv + offset * texel).rgb);
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let texel = 1.0 / vec2<f32>(textureDimensions(source_texture));
+    let uv = in.uv;
+
+    let center = sample_color(uv);
+    let luma_c = luma(center.rgb);
+    let luma_u = sample_luma(uv, vec2<f32>(0.0, -1.0), texel);
+    let luma_d = sample_luma(uv, vec2<f32>(0.0, 1.0), texel);
+    let luma_l = sample_luma(uv, vec2<f32>(-1.0, 0.0), texel);
+    let luma_r = sample_luma(uv, vec2<f32>(1.0, 0.0), texel);
+
+    let luma_min = min(luma_c, min(min(luma_u, luma_d), min(luma_l, luma_r)));
+    let luma_max = max(luma_c, max(max(luma_u, luma_d), max(luma_l, luma_r)));
+    let luma_range = luma_max - luma_min;
+
+    if luma_range < max(EDGE_THRESHOLD_MIN, luma_max * EDGE_THRESHOLD_MAX) {
+        return center;
+    }
+
+    let luma_ul = sample_luma(uv, vec2<f32>(-1.0, -1.0), texel);
+    let luma_ur = sample_luma(uv, vec2<f32>(1.0, -1.0), texel);
+    let luma_dl = sample_luma(uv, vec2<f32>(-1.0, 1.0), texel);
+    let luma_dr = sample_luma(uv, vec2<f32>(1.0, 1.0), texel);
+
+    let luma_ud = luma_u + luma_d;
+    let luma_lr = luma_l + luma_r;
+    let luma_left_corners = luma_ul + luma_dl;
+    let luma_right_corners = luma_ur + luma_dr;
+    let luma_up_corners = luma_ul + luma_ur;
+    let luma_down_corners = luma_dl + luma_dr;
+
+    let edge_horizontal = abs(-2.0 * luma_l + luma_left_corners)
+        + abs(-2.0 * luma_c + luma_ud) * 2.0
+        + abs(-2.0 * luma_r + luma_right_corners);
+    let edge_vertical = abs(-2.0 * luma_u + luma_up_corners)
+        + abs(-2.0 * luma_c + luma_lr) * 2.0
+        + abs(-2.0 * luma_d + luma_down_corners);
+
+    let is_horizontal = edge_horizontal >= edge_vertical;
+
+    // Neighbours on either side of the edge
+    let luma_neg = select(luma_l, luma_u, is_horizontal);
+    let luma_pos = select(luma_r, luma_d, is_horizontal);
+    let gradient_neg = luma_neg - luma_c;
+    let gradient_pos = luma_pos - luma_c;
+
+    let is_neg_steepest = abs(gradient_neg) >= abs(gradient_pos);
+    let gradient_scaled = 0.25 * max(abs(gradient_neg), abs(gradient_pos));
+
+    var step_length = select(texel.x, texel.y, is_horizontal);
+    var luma_local_average: f32;
+    if is_neg_steepest {
+        step_length = -step_length;
+        luma_local_average = 0.5 * (luma_neg + luma_c);
+    } else {
+        luma_local_average = 0.5 * (luma_pos + luma_c);
+    }
+
+    // Move to the boundary between the pixels
+    var edge_uv = uv;
+    if is_horizontal {
+        edge_uv.y += step_length * 0.5;
+    } else {
+        edge_uv.x += step_length * 0.5;
+    }
+
+    // Search along the edge in both directions for its ends
+    let offset = select(vec2<f32>(0.0, texel.y), vec2<f32>(texel.x, 0.0), is_horizontal);
+    var uv1 = edge_uv - offset;
+    var uv2 = edge_uv + offset;
+
+    var luma_end1 = luma(sample_color(uv1).rgb) - luma_local_average;
+    var luma_end2 = luma(sample_color(uv2).rgb) - luma_local_average;
+    var reached1 = abs(luma_end1) >= gradient_scaled;
+    var reached2 = abs(luma_end2) >= gradient_scaled;
+
+    for (var i = 1; i < SEARCH_STEPS && !(reached1 && reached2); i += 1) {
+        if !reached1 {
+            uv1 -= offset * step_size(i);
+            luma_end1 = luma(sample_color(uv1).rgb) - luma_local_average;
+            reached1 = abs(luma_end1) >= gradient_scaled;
+        }
+
+        if !reached2 {
+            uv2 += offset * step_size(i);
+            luma_end2 = luma(sample_color(uv2).rgb) - luma_local_average;
+            reached2 = abs(luma_end2) >= gradient_scaled;
+        }
+    }
+
+    let distance1 = select(uv.y - uv1.y, uv.x - uv1.x, is_horizontal);
+    let distance2 = select(uv2.y - uv.y, uv2.x - uv.x, is_horizontal);
+
+    let is_direction1 = distance1 < distance2;
+    let distance_final = min(distance1, distance2);
+    let edge_length = distance1 + distance2;
+
+    // Only blend when the end of the edge varies in the same direction as the center
+    let is_luma_center_smaller = luma_c < luma_local_average;
+    let luma_end = select(luma_end2, luma_end1, is_direction1);
+    let correct_variation = (luma_end < 0.0) != is_luma_center_smaller;
+
+    var final_offset = select(0.0, 0.5 - distance_final / edge_length, correct_variation);
+
+    // Blend thin features smaller than a pixel
+    let luma_average = (1.0 / 12.0) * (2.0 * (luma_ud + luma_lr) + luma_left_corners + luma_right_corners);
+    let subpixel_a = clamp(abs(luma_average - luma_c) / luma_range, 0.0, 1.0);
+    let subpixel_b = (-2.0 * subpixel_a + 3.0) * subpixel_a * subpixel_a;
+    final_offset = max(final_offset, subpixel_b * subpixel_b * SUBPIXEL_QUALITY);
+
+    var final_uv = uv;
+    if is_horizontal {
+        final_uv.y += final_offset * step_length;
+    } else {
+        final_uv.x += final_offset * step_length;
+    }
+
+    return sample_color(final_uv);
+}
@@ -0,0 +1,258 @@
+// Subpixel morphological anti-aliasing, in three passes:
+//
+// 1. Detect luma edges to the left and top of each pixel
+// 2. Search along each edge for its ends and look up how much each pixel should blend with its
+//    neighbours from the precomputed area texture, keyed by the distances to and the crossing
+//    edges at both ends
+// 3. Blend each pixel with its neighbours
+//
+// Follows the reference SMAA 1x implementation, with diagonal and corner detection omitted.
+
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+};
+
+@vertex
+fn vs_main(@builtin(vertex_index) vertex_index: u32) -> VertexOutput {
+    var result: VertexOutput;
+    let x = i32(vertex_index) / 2;
+    let y = i32(vertex_index) & 1;
+    let uv = vec2<f32>(
+        f32(x) * 2.0,
+        f32(y) * 2.0
+    );
+    result.position = vec4<f32>(
+        uv.x * 2.0 - 1.0,
+        1.0 - uv.y * 2.0,
+        1.0, 1.0
+    );
+    result.uv = uv;
+    return result;
+}
+
+// Edge detection and blending
+@group(0) @binding(0)
+var color_texture: texture_2d<f32>;
+
+// Blending weights
+@group(0) @binding(0)
+var edges_texture: texture_2d<f32>;
+@group(0) @binding(1)
+var area_texture: texture_2d<f32>;
+@group(0) @binding(2)
+var search_texture: texture_2d<f32>;
+@group(0) @binding(3)
+var linear_sampler: sampler;
+@group(0) @binding(4)
+var point_sampler: sampler;
+
+// Blending
+@group(0) @binding(1)
+var weights_texture: texture_2d<f32>;
+@group(0) @binding(2)
+var blend_sampler: sampler;
+
+const EDGE_THRESHOLD: f32 = 0.1;
+// Edges are discarded if a neighbouring edge has this many times more contrast
+const LOCAL_CONTRAST_FACTOR: f32 = 2.0;
+// Maximum distance in pixels searched in each direction along an edge, two pixels per step
+const MAX_SEARCH_STEPS: f32 = 16.0;
+
+// Must match the lookup tables generated in `smaa.rs`
+const AREA_MAX_DISTANCE: f32 = 16.0;
+const AREA_SIZE: vec2<f32> = vec2<f32>(80.0, 80.0);
+const SEARCH_SIZE: vec2<f32> = vec2<f32>(66.0, 33.0);
+const SEARCH_PACKED_SIZE: vec2<f32> = vec2<f32>(64.0, 16.0);
+
+// Perceptual luma of a linear color
+fn luma(color: vec3<f32>) -> f32 {
+    return sqrt(dot(color, vec3<f32>(0.2126, 0.7152, 0.0722)));
+}
+
+fn load_luma(p: vec2<i32>) -> f32 {
+    let size = vec2<i32>(textureDimensions(color_texture));
+    return luma(textureLoad(color_texture, clamp(p, vec2<i32>(0), size - 1), 0).rgb);
+}
+
+@fragment
+fn fs_edges(in: VertexOutput) -> @location(0) vec4<f32> {
+    let p = vec2<i32>(in.position.xy);
+
+    let c = load_luma(p);
+    let l = load_luma(p + vec2<i32>(-1, 0));
+    let t = load_luma(p + vec2<i32>(0, -1));
+
+    let delta = abs(vec2<f32>(c - l, c - t));
+    var edges = step(vec2<f32>(EDGE_THRESHOLD), delta);
+
+    if edges.x == 0.0 && edges.y == 0.0 {
+        return vec4<f32>(0.0);
+    }
+
+    // Suppress edges next to much stronger edges, such as the inside of thick lines
+    let r = load_luma(p + vec2<i32>(1, 0));
+    let b = load_luma(p + vec2<i32>(0, 1));
+    let ll = load_luma(p + vec2<i32>(-2, 0));
+    let tt = load_luma(p + vec2<i32>(0, -2));
+
+    let max_delta = max(
+        max(max(delta.x, delta.y), max(abs(c - r), abs(c - b))),
+        max(abs(l - ll), abs(t - tt)),
+    );
+
+    edges *= step(vec2<f32>(max_delta), LOCAL_CONTRAST_FACTOR * delta);
+
+    return vec4<f32>(edges, 0.0, 0.0);
+}
+
+fn sample_edges(uv: vec2<f32>) -> vec2<f32> {
+    return textureSampleLevel(edges_texture, linear_sampler, uv, 0.0).rg;
+}
+
+// Looks up how many pixels the search went past the end of the edge from the bilinearly fetched
+// edges `e` where it stopped
+fn search_length(e: vec2<f32>, offset: f32) -> f32 {
+    let scale = (SEARCH_SIZE * vec2<f32>(0.5, -1.0) + vec2<f32>(-1.0, 1.0)) / SEARCH_PACKED_SIZE;
+    let bias = (SEARCH_SIZE * vec2<f32>(offset, 1.0) + vec2<f32>(0.5, -0.5)) / SEARCH_PACKED_SIZE;
+    return textureSampleLevel(search_texture, point_sampler, scale * e + bias, 0.0).r;
+}
+
+// The searches step two pixels at a time, fetching the edges of both with a single bilinear
+// sample between them. The search continues while both pixels have an edge and no crossing edge.
+
+fn search_x_left(start: vec2<f32>, end: f32, px: vec2<f32>) -> f32 {
+    var uv = start;
+    var e = vec2<f32>(0.0, 1.0);
+    while uv.x > end && e.g > 0.8281 && e.r == 0.0 {
+        e = sample_edges(uv);
+        uv.x -= 2.0 * px.x;
+    }
+
+    let offset = -(255.0 / 127.0) * search_length(e, 0.0) + 3.25;
+    return px.x * offset + uv.x;
+}
+
+fn search_x_right(start: vec2<f32>, end: f32, px: vec2<f32>) -> f32 {
+    var uv = start;
+    var e = vec2<f32>(0.0, 1.0);
+    while uv.x < end && e.g > 0.8281 && e.r == 0.0 {
+        e = sample_edges(uv);
+        uv.x += 2.0 * px.x;
+    }
+
+    let offset = -(255.0 / 127.0) * search_length(e, 0.5) + 3.25;
+    return -px.x * offset + uv.x;
+}
+
+fn search_y_up(start: vec2<f32>, end: f32, px: vec2<f32>) -> f32 {
+    var uv = start;
+    var e = vec2<f32>(1.0, 0.0);
+    while uv.y > end && e.r > 0.8281 && e.g == 0.0 {
+        e = sample_edges(uv);
+        uv.y -= 2.0 * px.y;
+    }
+
+    let offset = -(255.0 / 127.0) * search_length(e.gr, 0.0) + 3.25;
+    return px.y * offset + uv.y;
+}
+
+fn search_y_down(start: vec2<f32>, end: f32, px: vec2<f32>) -> f32 {
+    var uv = start;
+    var e = vec2<f32>(1.0, 0.0);
+    while uv.y < end && e.r > 0.8281 && e.g == 0.0 {
+        e = sample_edges(uv);
+        uv.y += 2.0 * px.y;
+    }
+
+    let offset = -(255.0 / 127.0) * search_length(e.gr, 0.5) + 3.25;
+    return -px.y * offset + uv.y;
+}
+
+// Blending weights of a pixel at the square root distances `dist` from both ends of an edge
+// with the crossing edges `e1` and `e2`
+fn area(dist: vec2<f32>, e1: f32, e2: f32) -> vec2<f32> {
+    let texel = AREA_MAX_DISTANCE * round(4.0 * vec2<f32>(e1, e2)) + dist;
+    return textureSampleLevel(area_texture, linear_sampler, (texel + 0.5) / AREA_SIZE, 0.0).rg;
+}
+
+// Weights of the top edge in `xy`, and of the left edge in `zw`
+@fragment
+fn fs_weights(in: VertexOutput) -> @location(0) vec4<f32> {
+    let size = vec2<f32>(textureDimensions(edges_texture));
+    let px = 1.0 / size;
+    let uv = in.uv;
+    let pixel = uv * size;
+
+    let offset0 = uv.xyxy + px.xyxy * vec4<f32>(-0.25, -0.125, 1.25, -0.125);
+    let offset1 = uv.xyxy + px.xyxy * vec4<f32>(-0.125, -0.25, -0.125, 1.25);
+    let bounds = vec4<f32>(offset0.xz, offset1.yw)
+        + vec4<f32>(-2.0, 2.0, -2.0, 2.0) * px.xxyy * MAX_SEARCH_STEPS;
+
+    var weights = vec4<f32>(0.0);
+    let e = sample_edges(uv);
+
+    if e.g > 0.0 {
+        let left = search_x_left(offset0.xy, bounds.x, px);
+        let right = search_x_right(offset0.zw, bounds.y, px);
+
+        let d = abs(round(vec2<f32>(left, right) * size.x - pixel.x));
+
+        let e1 = sample_edges(vec2<f32>(left, offset1.y)).r;
+        let e2 = sample_edges(vec2<f32>(right + px.x, offset1.y)).r;
+
+        weights = vec4<f32>(area(sqrt(d), e1, e2), weights.zw);
+    }
+
+    if e.r > 0.0 {
+        let top = search_y_up(offset1.xy, bounds.z, px);
+        let bottom = search_y_down(offset1.zw, bounds.w, px);
+
+        let d = abs(round(vec2<f32>(top, bottom) * size.y - pixel.y));
+
+        let e1 = sample_edges(vec2<f32>(offset0.x, top)).g;
+        let e2 = sample_edges(vec2<f32>(offset0.x, bottom + px.y)).g;
+
+        weights = vec4<f32>(weights.xy, area(sqrt(d), e1, e2));
+    }
+
+    return weights;
+}
+
+fn sample_color(uv: vec2<f32>) -> vec4<f32> {
+    return textureSampleLevel(color_texture, blend_sampler, uv, 0.0);
+}
+
+@fragment
+fn fs_blend(in: VertexOutput) -> @location(0) vec4<f32> {
+    let size = vec2<f32>(textureDimensions(color_texture));
+    let px = 1.0 / size;
+    let uv = in.uv;
+
+    let center = textureSampleLevel(weights_texture, blend_sampler, uv, 0.0);
+
+    // Right, bottom, left and top weights, stored by the neighbour sharing the edge
+    let a = vec4<f32>(
+        textureSampleLevel(weights_texture, blend_sampler, uv + vec2<f32>(px.x, 0.0), 0.0).a,
+        textureSampleLevel(weights_texture, blend_sampler, uv + vec2<f32>(0.0, px.y), 0.0).g,
+        center.z,
+        center.x,
+    );
+
+    if dot(a, vec4<f32>(1.0)) < 1e-5 {
+        return sample_color(uv);
+    }
+
+    var offset = vec4<f32>(0.0, a.y, 0.0, -a.w);
+    var weight = a.yw;
+
+    if max(a.x, a.z) > max(a.y, a.w) {
+        offset = vec4<f32>(a.x, 0.0, -a.z, 0.0);
+        weight = a.xz;
+    }
+
+    weight /= dot(weight, vec2<f32>(1.0));
+
+    let coords = uv.xyxy + offset * px.xyxy;
+    return weight.x * sample_color(coords.xy) + weight.y * sample_color(coords.zw);
+}
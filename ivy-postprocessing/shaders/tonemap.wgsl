@@ -29,6 +29,26 @@ var source_texture: texture_2d<f32>;
 @group(0) @binding(1)
 var default_sampler: sampler;
 
+@group(0) @binding(2)
+var exposure_texture: texture_2d<f32>;
+
+struct TonemapData {
+    // 0: reinhard, 1: aces, 2: agx, 3: neutral
+    operator: u32,
+    lut_size: f32,
+    lut_enabled: u32,
+    _padding: u32,
+};
+
+@group(0) @binding(3)
+var<uniform> tonemap_data: TonemapData;
+
+@group(0) @binding(4)
+var lut_texture: texture_3d<f32>;
+
+@group(0) @binding(5)
+var lut_sampler: sampler;
+
 fn reinhard(x: f32) -> f32 {
     return x / (1f + x);
 }
@@ -86,17 +106,142 @@ fn reinhard_2(x: f32) -> f32 {
     return (x * (1.0 + x / (L_white * L_white))) / (1.0 + x);
 }
 
-@fragment
-fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
-    var color = textureSample(source_texture, default_sampler, in.uv).rgb;
+fn tonemap_reinhard(color: vec3<f32>) -> vec3<f32> {
     var yxy = convert_rgb_yxy(color);
+    yxy.x = reinhard_2(yxy.x);
+    return convert_yxy_rgb(yxy);
+}
+
+// ACES filmic curve fitted by Stephen Hill, including the sRGB to ACES input and output transforms
+fn rrt_and_odt_fit(v: vec3<f32>) -> vec3<f32> {
+    let a = v * (v + 0.0245786) - 0.000090537;
+    let b = v * (0.983729 * v + 0.4329510) + 0.238081;
+    return a / b;
+}
+
+fn tonemap_aces(color: vec3<f32>) -> vec3<f32> {
+    let aces_input = mat3x3<f32>(
+        vec3(0.59719, 0.07600, 0.02840),
+        vec3(0.35458, 0.90834, 0.13383),
+        vec3(0.04823, 0.01566, 0.83777),
+    );
+
+    let aces_output = mat3x3<f32>(
+        vec3(1.60475, -0.10208, -0.00327),
+        vec3(-0.53108, 1.10813, -0.07276),
+        vec3(-0.07367, -0.00605, 1.07602),
+    );
+
+    return clamp(aces_output * rrt_and_odt_fit(aces_input * color), vec3(0.0), vec3(1.0));
+}
+
+// Polynomial fit of the AgX base contrast curve by Benjamin Wrensch
+fn agx_default_contrast(x: vec3<f32>) -> vec3<f32> {
+    let x2 = x * x;
+    let x4 = x2 * x2;
+
+    return 15.5 * x4 * x2
+        - 40.14 * x4 * x
+        + 31.96 * x4
+        - 6.868 * x2 * x
+        + 0.4298 * x2
+        + 0.1191 * x
+        - 0.00232;
+}
+
+fn tonemap_agx(color: vec3<f32>) -> vec3<f32> {
+    let agx_inset = mat3x3<f32>(
+        vec3(0.842479062253094, 0.0423282422610123, 0.0423756549057051),
+        vec3(0.0784335999999992, 0.878468636469772, 0.0784336),
+        vec3(0.0792237451477643, 0.0791661274605434, 0.879142973793104),
+    );
 
-    let lum = 0.05;
-    let lp = yxy.x / (9.6 * lum + 0.0001);
-    yxy.x = reinhard_2(lp);
+    let agx_outset = mat3x3<f32>(
+        vec3(1.19687900512017, -0.0528968517574562, -0.0529716355144438),
+        vec3(-0.0980208811401368, 1.15190312990417, -0.0980434501171241),
+        vec3(-0.0990297440797205, -0.0989611768448433, 1.15107367264116),
+    );
+
+    let min_ev = -12.47393;
+    let max_ev = 4.026069;
+
+    var v = agx_inset * color;
+    v = clamp(log2(max(v, vec3(1e-10))), vec3(min_ev), vec3(max_ev));
+    v = (v - min_ev) / (max_ev - min_ev);
+    v = agx_default_contrast(v);
+    v = agx_outset * v;
+
+    // The curve outputs display encoded values
+    return pow(max(v, vec3(0.0)), vec3(2.2));
+}
+
+// Khronos PBR Neutral, which preserves the hue and saturation of base colors
+fn tonemap_neutral(color: vec3<f32>) -> vec3<f32> {
+    let start_compression = 0.8 - 0.04;
+    let desaturation = 0.15;
+
+    let x = min(color.r, min(color.g, color.b));
+    let offset = select(0.04, x - 6.25 * x * x, x < 0.08);
+    var v = color - offset;
+
+    let peak = max(v.r, max(v.g, v.b));
+    if peak < start_compression {
+        return v;
+    }
+
+    let d = 1.0 - start_compression;
+    let new_peak = 1.0 - d * d / (peak + d - start_compression);
+    v *= new_peak / peak;
+
+    let g = 1.0 - 1.0 / (desaturation * (peak - new_peak) + 1.0);
+    return mix(v, vec3(new_peak), g);
+}
+
+fn linear_to_srgb(color: vec3<f32>) -> vec3<f32> {
+    let low = color * 12.92;
+    let high = 1.055 * pow(color, vec3(1.0 / 2.4)) - 0.055;
+    return select(high, low, color <= vec3(0.0031308));
+}
+
+fn srgb_to_linear(color: vec3<f32>) -> vec3<f32> {
+    let low = color / 12.92;
+    let high = pow((color + 0.055) / 1.055, vec3(2.4));
+    return select(high, low, color <= vec3(0.04045));
+}
 
-    color = convert_yxy_rgb(yxy);
+// The lookup table is indexed by, and stores, display encoded colors
+fn color_grade(color: vec3<f32>) -> vec3<f32> {
+    let size = tonemap_data.lut_size;
+    let encoded = clamp(linear_to_srgb(color), vec3(0.0), vec3(1.0));
+    let uvw = encoded * ((size - 1.0) / size) + 0.5 / size;
+
+    return srgb_to_linear(textureSampleLevel(lut_texture, lut_sampler, uvw, 0.0).rgb);
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let exposure = textureLoad(exposure_texture, vec2<i32>(0, 0), 0).r;
+    let hdr = textureSample(source_texture, default_sampler, in.uv).rgb * exposure;
+
+    var color: vec3<f32>;
+    switch tonemap_data.operator {
+        case 1u: {
+            color = tonemap_aces(hdr);
+        }
+        case 2u: {
+            color = tonemap_agx(hdr);
+        }
+        case 3u: {
+            color = tonemap_neutral(hdr);
+        }
+        default: {
+            color = tonemap_reinhard(hdr);
+        }
+    }
+
+    if tonemap_data.lut_enabled != 0u {
+        color = color_grade(color);
+    }
 
     return vec4(color, 1f);
 }
- 
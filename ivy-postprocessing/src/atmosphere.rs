@@ -0,0 +1,544 @@
+//! Procedural sky and environment lighting from a physically based atmosphere
+
+use std::{any::type_name, mem};
+
+use flax::{Query, World};
+use glam::{Mat4, Vec3};
+use ivy_core::components::world_transform;
+use ivy_wgpu::{
+    camera_target::CameraSelection,
+    components::light_kind,
+    renderer::SkyboxTextures,
+    rendergraph::{Dependency, Node, NodeExecutionContext, NodeUpdateContext, UpdateResult},
+    types::{
+        shader::{ShaderDesc, TargetDesc},
+        BindGroupBuilder, BindGroupLayoutBuilder, RenderShader, TypedBuffer,
+    },
+    Gpu,
+};
+use wgpu::{
+    BindGroup, BindGroupLayout, BufferUsages, Color, CommandEncoder, Extent3d, Operations,
+    RenderPassColorAttachment, SamplerDescriptor, ShaderModule, ShaderModuleDescriptor,
+    ShaderSource, ShaderStages, StoreOp, Texture, TextureDescriptor, TextureDimension,
+    TextureFormat, TextureUsages, TextureView, TextureViewDescriptor, TextureViewDimension,
+};
+
+use crate::{components::atmosphere, hdri::HdriProcessor};
+
+const LUT_FORMAT: TextureFormat = TextureFormat::Rgba16Float;
+const TRANSMITTANCE_LUT_SIZE: (u32, u32) = (256, 64);
+const MULTISCATTERING_LUT_SIZE: (u32, u32) = (32, 32);
+
+/// Used when the world contains no directional light
+const DEFAULT_SUN_DIRECTION: Vec3 = Vec3::new(0.0, 0.5, 0.866);
+
+/// Composition of a planet's atmosphere.
+///
+/// Distances are in kilometers and coefficients per kilometer. The defaults describe the earth.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Atmosphere {
+    pub ground_radius: f32,
+    pub top_radius: f32,
+    /// Height of the viewer above the ground
+    pub observer_altitude: f32,
+    pub ground_albedo: Vec3,
+
+    pub rayleigh_scattering: Vec3,
+    /// Altitude at which the density of air molecules has decreased by a factor of `e`
+    pub rayleigh_scale_height: f32,
+
+    pub mie_scattering: Vec3,
+    pub mie_absorption: Vec3,
+    /// Altitude at which the density of aerosols has decreased by a factor of `e`
+    pub mie_scale_height: f32,
+    /// Anisotropy of the scattering by aerosols, which gives the halo around the sun
+    pub mie_g: f32,
+
+    pub ozone_absorption: Vec3,
+    /// Altitude of the peak density of the ozone layer
+    pub ozone_center: f32,
+    /// Thickness of the ozone layer
+    pub ozone_width: f32,
+
+    /// Scales the radiance of the sky
+    pub sun_intensity: f32,
+    pub sun_angular_radius: f32,
+}
+
+impl Default for Atmosphere {
+    fn default() -> Self {
+        Self {
+            ground_radius: 6360.0,
+            top_radius: 6460.0,
+            observer_altitude: 0.2,
+            ground_albedo: Vec3::splat(0.3),
+            rayleigh_scattering: Vec3::new(5.802e-3, 13.558e-3, 33.1e-3),
+            rayleigh_scale_height: 8.0,
+            mie_scattering: Vec3::splat(3.996e-3),
+            mie_absorption: Vec3::splat(4.4e-3),
+            mie_scale_height: 1.2,
+            mie_g: 0.8,
+            ozone_absorption: Vec3::new(0.65e-3, 1.881e-3, 0.085e-3),
+            ozone_center: 25.0,
+            ozone_width: 30.0,
+            sun_intensity: 20.0,
+            sun_angular_radius: 0.0047,
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, bytemuck::Pod, bytemuck::Zeroable)]
+struct AtmosphereData {
+    rayleigh_scattering: Vec3,
+    rayleigh_scale_height: f32,
+    mie_scattering: Vec3,
+    mie_scale_height: f32,
+    mie_extinction: Vec3,
+    mie_g: f32,
+    ozone_absorption: Vec3,
+    ozone_center: f32,
+    ground_albedo: Vec3,
+    ozone_width: f32,
+    sun_direction: Vec3,
+    sun_intensity: f32,
+    ground_radius: f32,
+    top_radius: f32,
+    observer_altitude: f32,
+    sun_angular_radius: f32,
+}
+
+impl AtmosphereData {
+    fn new(atmosphere: &Atmosphere, sun_direction: Vec3) -> Self {
+        Self {
+            rayleigh_scattering: atmosphere.rayleigh_scattering,
+            rayleigh_scale_height: atmosphere.rayleigh_scale_height,
+            mie_scattering: atmosphere.mie_scattering,
+            mie_scale_height: atmosphere.mie_scale_height,
+            mie_extinction: atmosphere.mie_scattering + atmosphere.mie_absorption,
+            mie_g: atmosphere.mie_g,
+            ozone_absorption: atmosphere.ozone_absorption,
+            ozone_center: atmosphere.ozone_center,
+            ground_albedo: atmosphere.ground_albedo,
+            ozone_width: atmosphere.ozone_width,
+            sun_direction,
+            sun_intensity: atmosphere.sun_intensity,
+            ground_radius: atmosphere.ground_radius,
+            top_radius: atmosphere.top_radius,
+            observer_altitude: atmosphere.observer_altitude,
+            sun_angular_radius: atmosphere.sun_angular_radius,
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct FaceData {
+    inv_proj: Mat4,
+    inv_view: Mat4,
+}
+
+fn create_lut(gpu: &Gpu, label: &str, size: (u32, u32)) -> Texture {
+    gpu.device.create_texture(&TextureDescriptor {
+        label: Some(label),
+        size: Extent3d {
+            width: size.0,
+            height: size.1,
+            depth_or_array_layers: 1,
+        },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: TextureDimension::D2,
+        format: LUT_FORMAT,
+        usage: TextureUsages::RENDER_ATTACHMENT | TextureUsages::TEXTURE_BINDING,
+        view_formats: &[],
+    })
+}
+
+fn create_shader(
+    gpu: &Gpu,
+    label: &str,
+    module: &ShaderModule,
+    entry_point: &str,
+    format: TextureFormat,
+    layouts: &[&BindGroupLayout],
+) -> RenderShader {
+    let target = TargetDesc {
+        formats: &[format],
+        depth_format: None,
+        sample_count: 1,
+    };
+
+    let mut desc = ShaderDesc::new(label, module, &target)
+        .with_bind_group_layouts(layouts)
+        .with_blend(None);
+
+    desc.fragment_entry_point = entry_point;
+
+    RenderShader::new(gpu, &desc)
+}
+
+fn fullscreen_pass(
+    encoder: &mut CommandEncoder,
+    label: &str,
+    view: &TextureView,
+    shader: &RenderShader,
+    bind_groups: &[&BindGroup],
+) {
+    let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+        label: Some(label),
+        color_attachments: &[Some(RenderPassColorAttachment {
+            view,
+            resolve_target: None,
+            ops: Operations {
+                load: wgpu::LoadOp::Clear(Color::BLACK),
+                store: StoreOp::Store,
+            },
+        })],
+        depth_stencil_attachment: None,
+        ..Default::default()
+    });
+
+    render_pass.set_pipeline(shader.pipeline());
+    for (i, bind_group) in bind_groups.iter().enumerate() {
+        render_pass.set_bind_group(i as u32, bind_group, &[]);
+    }
+
+    render_pass.draw(0..3, 0..1);
+}
+
+/// Renders a physically based sky into the environment map of a [`SkyboxTextures`], and derives
+/// the irradiance and specular maps from it.
+///
+/// The sun follows the first directional light in the world, and the [`atmosphere`] of the camera
+/// overrides the configured atmosphere. The maps are only regenerated when the sun has
+/// moved by more than the update threshold, which keeps time of day systems cheap.
+pub struct AtmosphereNode {
+    processor: HdriProcessor,
+    skybox: SkyboxTextures,
+
+    default_atmosphere: Atmosphere,
+    update_threshold: f32,
+    current: Option<(Atmosphere, Vec3)>,
+
+    data: TypedBuffer<AtmosphereData>,
+
+    transmittance_lut: Texture,
+    multiscattering_lut: Texture,
+
+    atmosphere_bind_group: BindGroup,
+    transmittance_bind_group: BindGroup,
+    face_bind_groups: Vec<BindGroup>,
+
+    transmittance_shader: RenderShader,
+    multiscattering_shader: RenderShader,
+    sky_shader: RenderShader,
+
+    process_luts: bool,
+    process_sky: bool,
+    process_brdf_lookup: bool,
+
+    camera: CameraSelection,
+}
+
+impl AtmosphereNode {
+    pub fn new(
+        gpu: &Gpu,
+        processor: HdriProcessor,
+        skybox: SkyboxTextures,
+        atmosphere: Atmosphere,
+    ) -> Self {
+        let atmosphere_layout = BindGroupLayoutBuilder::new("Atmosphere")
+            .bind_uniform_buffer(ShaderStages::FRAGMENT)
+            .build(gpu);
+
+        let transmittance_layout = BindGroupLayoutBuilder::new("Atmosphere.transmittance")
+            .bind_sampler(ShaderStages::FRAGMENT)
+            .bind_texture(ShaderStages::FRAGMENT)
+            .build(gpu);
+
+        let face_layout = BindGroupLayoutBuilder::new("Atmosphere.face")
+            .bind_uniform_buffer(ShaderStages::FRAGMENT)
+            .bind_texture(ShaderStages::FRAGMENT)
+            .build(gpu);
+
+        let data = TypedBuffer::new(
+            gpu,
+            "Atmosphere",
+            BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+            &[Default::default()],
+        );
+
+        let proj_inv = processor.proj().inverse();
+        let face_buffers = processor.view_matrices().map(|view| {
+            TypedBuffer::new(
+                gpu,
+                "Atmosphere.face",
+                BufferUsages::UNIFORM,
+                &[FaceData {
+                    inv_proj: proj_inv,
+                    inv_view: view.inverse(),
+                }],
+            )
+        });
+
+        let transmittance_lut = create_lut(gpu, "transmittance_lut", TRANSMITTANCE_LUT_SIZE);
+        let multiscattering_lut = create_lut(gpu, "multiscattering_lut", MULTISCATTERING_LUT_SIZE);
+
+        let sampler = gpu.device.create_sampler(&SamplerDescriptor {
+            label: Some("Atmosphere"),
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Nearest,
+            ..Default::default()
+        });
+
+        let atmosphere_bind_group = BindGroupBuilder::new("Atmosphere")
+            .bind_buffer(&data)
+            .build(gpu, &atmosphere_layout);
+
+        let transmittance_bind_group = BindGroupBuilder::new("Atmosphere.transmittance")
+            .bind_sampler(&sampler)
+            .bind_texture(&transmittance_lut.create_view(&Default::default()))
+            .build(gpu, &transmittance_layout);
+
+        let multiscattering_view = multiscattering_lut.create_view(&Default::default());
+        let face_bind_groups = face_buffers
+            .iter()
+            .map(|buffer| {
+                BindGroupBuilder::new("Atmosphere.face")
+                    .bind_buffer(buffer)
+                    .bind_texture(&multiscattering_view)
+                    .build(gpu, &face_layout)
+            })
+            .collect();
+
+        let module = gpu.device.create_shader_module(ShaderModuleDescriptor {
+            label: Some("atmosphere"),
+            source: ShaderSource::Wgsl(include_str!("../shaders/atmosphere.wgsl").into()),
+        });
+
+        let transmittance_shader = create_shader(
+            gpu,
+            "Atmosphere.transmittance",
+            &module,
+            "fs_transmittance",
+            LUT_FORMAT,
+            &[&atmosphere_layout],
+        );
+
+        let multiscattering_shader = create_shader(
+            gpu,
+            "Atmosphere.multiscattering",
+            &module,
+            "fs_multiscattering",
+            LUT_FORMAT,
+            &[&atmosphere_layout, &transmittance_layout],
+        );
+
+        let sky_shader = create_shader(
+            gpu,
+            "Atmosphere.sky",
+            &module,
+            "fs_sky",
+            processor.format(),
+            &[&atmosphere_layout, &transmittance_layout, &face_layout],
+        );
+
+        Self {
+            processor,
+            skybox,
+            default_atmosphere: atmosphere,
+            update_threshold: 0.25_f32.to_radians(),
+            current: None,
+            data,
+            transmittance_lut,
+            multiscattering_lut,
+            atmosphere_bind_group,
+            transmittance_bind_group,
+            face_bind_groups,
+            transmittance_shader,
+            multiscattering_shader,
+            sky_shader,
+            process_luts: true,
+            process_sky: true,
+            process_brdf_lookup: true,
+            camera: CameraSelection::Main,
+        }
+    }
+
+    /// Use the atmosphere of another camera than the main camera
+    pub fn with_camera(mut self, camera: impl Into<CameraSelection>) -> Self {
+        self.camera = camera.into();
+        self
+    }
+
+    /// Set the angle in radians the sun must move before the environment is regenerated
+    pub fn with_update_threshold(mut self, update_threshold: f32) -> Self {
+        self.update_threshold = update_threshold;
+        self
+    }
+
+    fn sun_direction(world: &World) -> Vec3 {
+        Query::new((world_transform(), light_kind()))
+            .borrow(world)
+            .iter()
+            .find(|(_, kind)| kind.is_directional())
+            // Lights shine along -Z, so the sun is in the opposite direction
+            .map(|(transform, _)| transform.transform_vector3(Vec3::Z).normalize_or_zero())
+            .filter(|v| *v != Vec3::ZERO)
+            .unwrap_or(DEFAULT_SUN_DIRECTION.normalize())
+    }
+}
+
+impl Node for AtmosphereNode {
+    fn label(&self) -> &str {
+        type_name::<Self>()
+    }
+
+    fn update(&mut self, ctx: NodeUpdateContext) -> anyhow::Result<UpdateResult> {
+        let atmosphere = self
+            .camera
+            .find(ctx.world)
+            .and_then(|id| ctx.world.get_copy(id, atmosphere()).ok())
+            .unwrap_or(self.default_atmosphere);
+
+        let sun_direction = Self::sun_direction(ctx.world);
+
+        // The sun parameters only affect the sky, which allows animating the sky brightness without
+        // regenerating the luts
+        let without_sun = |v: &Atmosphere| Atmosphere {
+            sun_intensity: 0.0,
+            sun_angular_radius: 0.0,
+            ..*v
+        };
+
+        let (luts_changed, sky_changed) = match &self.current {
+            Some((current, current_sun)) => (
+                without_sun(current) != without_sun(&atmosphere),
+                *current != atmosphere
+                    || current_sun.angle_between(sun_direction) > self.update_threshold,
+            ),
+            None => (true, true),
+        };
+
+        if luts_changed || sky_changed {
+            self.current = Some((atmosphere, sun_direction));
+            self.data.write(
+                &ctx.gpu.queue,
+                0,
+                &[AtmosphereData::new(&atmosphere, sun_direction)],
+            );
+
+            self.process_luts |= luts_changed;
+            self.process_sky = true;
+        }
+
+        Ok(UpdateResult::Success)
+    }
+
+    fn draw(&mut self, ctx: NodeExecutionContext) -> anyhow::Result<()> {
+        if mem::take(&mut self.process_luts) {
+            fullscreen_pass(
+                ctx.encoder,
+                "Atmosphere.transmittance",
+                &self.transmittance_lut.create_view(&Default::default()),
+                &self.transmittance_shader,
+                &[&self.atmosphere_bind_group],
+            );
+
+            fullscreen_pass(
+                ctx.encoder,
+                "Atmosphere.multiscattering",
+                &self.multiscattering_lut.create_view(&Default::default()),
+                &self.multiscattering_shader,
+                &[&self.atmosphere_bind_group, &self.transmittance_bind_group],
+            );
+        }
+
+        if mem::take(&mut self.process_sky) {
+            let environment_map = ctx.get_texture(self.skybox.environment_map);
+
+            for (side, face_bind_group) in self.face_bind_groups.iter().enumerate() {
+                let view = environment_map.create_view(&TextureViewDescriptor {
+                    base_array_layer: side as u32,
+                    array_layer_count: Some(1),
+                    dimension: Some(TextureViewDimension::D2),
+                    mip_level_count: Some(1),
+                    ..Default::default()
+                });
+
+                fullscreen_pass(
+                    ctx.encoder,
+                    "Atmosphere.sky",
+                    &view,
+                    &self.sky_shader,
+                    &[
+                        &self.atmosphere_bind_group,
+                        &self.transmittance_bind_group,
+                        face_bind_group,
+                    ],
+                );
+
+                ivy_wgpu::types::mipmap::generate_mipmaps(
+                    ctx.gpu,
+                    ctx.encoder,
+                    environment_map,
+                    environment_map.mip_level_count(),
+                    side as u32,
+                );
+            }
+
+            self.processor.process_diffuse_irradiance(
+                ctx.gpu,
+                ctx.encoder,
+                environment_map,
+                ctx.get_texture(self.skybox.irradiance_map),
+            );
+
+            self.processor.process_specular_ibl(
+                ctx.gpu,
+                ctx.encoder,
+                environment_map,
+                ctx.get_texture(self.skybox.specular_map),
+            );
+        }
+
+        if mem::take(&mut self.process_brdf_lookup) {
+            self.processor.process_brdf_lookup(
+                ctx.gpu,
+                ctx.encoder,
+                ctx.get_texture(self.skybox.integrated_brdf),
+            );
+        }
+
+        Ok(())
+    }
+
+    fn read_dependencies(&self) -> Vec<Dependency> {
+        vec![]
+    }
+
+    fn write_dependencies(&self) -> Vec<Dependency> {
+        vec![
+            Dependency::texture(
+                self.skybox.environment_map,
+                TextureUsages::TEXTURE_BINDING | TextureUsages::RENDER_ATTACHMENT,
+            ),
+            Dependency::texture(self.skybox.irradiance_map, TextureUsages::RENDER_ATTACHMENT),
+            Dependency::texture(self.skybox.specular_map, TextureUsages::RENDER_ATTACHMENT),
+            Dependency::texture(
+                self.skybox.integrated_brdf,
+                TextureUsages::RENDER_ATTACHMENT,
+            ),
+        ]
+    }
+
+    fn on_resource_changed(&mut self, _resource: ivy_wgpu::rendergraph::ResourceHandle) {
+        self.process_sky = true;
+        self.process_brdf_lookup = true;
+    }
+}
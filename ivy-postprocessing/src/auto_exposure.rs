@@ -0,0 +1,280 @@
+use std::time::Instant;
+
+use flax::World;
+use ivy_wgpu::{
+    camera_target::CameraSelection,
+    components::environment_data,
+    renderer::EnvironmentData,
+    rendergraph::{
+        workgroups_for_extent, ComputeDispatch, Dependency, HistoryTexture, Node,
+        NodeExecutionContext, NodeUpdateContext, ResourceHandle, TextureHandle, UpdateResult,
+    },
+    types::{BindGroupBuilder, BindGroupLayoutBuilder, TypedBuffer},
+    Gpu,
+};
+use wgpu::{
+    BindGroup, BindGroupLayout, BufferUsages, ComputePipeline, ComputePipelineDescriptor,
+    PipelineLayoutDescriptor, ShaderModule, ShaderModuleDescriptor, ShaderSource, ShaderStages,
+    StorageTextureAccess, TextureFormat, TextureUsages,
+};
+
+/// Format of the 1x1 history texture holding the exposure, read by the
+/// [`TonemapNode`](crate::tonemap::TonemapNode)
+pub const EXPOSURE_FORMAT: TextureFormat = TextureFormat::R32Float;
+
+const BIN_COUNT: usize = 256;
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct ExposureParams {
+    min_log_lum: f32,
+    log_lum_range: f32,
+    pixel_count: f32,
+    delta_time: f32,
+    min_ev: f32,
+    max_ev: f32,
+    compensation: f32,
+    speed_up: f32,
+    speed_down: f32,
+    /// The previous exposure is adapted from, rather than the target exposure being used directly
+    history_valid: u32,
+    _padding: [f32; 2],
+}
+
+#[derive(Debug, Clone)]
+pub struct AutoExposureConfig {
+    /// Lowest log2 luminance in the histogram
+    pub min_log_luminance: f32,
+    /// Highest log2 luminance in the histogram
+    pub max_log_luminance: f32,
+    /// Rate of adaptation when the scene gets brighter
+    pub speed_up: f32,
+    /// Rate of adaptation when the scene gets darker.
+    ///
+    /// Adapting to darkness is slower than to brightness, as with the human eye.
+    pub speed_down: f32,
+}
+
+impl Default for AutoExposureConfig {
+    fn default() -> Self {
+        Self {
+            min_log_luminance: -10.0,
+            max_log_luminance: 10.0,
+            speed_up: 3.0,
+            speed_down: 1.0,
+        }
+    }
+}
+
+/// Measures the average luminance of the scene through a histogram and gradually adapts the
+/// exposure towards it.
+///
+/// The exposure is clamped and compensated by the [`EnvironmentData`] of the camera, and
+/// written to the `current` 1x1 texture of [`EXPOSURE_FORMAT`]. The exposure of the last frame
+/// is read back from `previous`.
+pub struct AutoExposureNode {
+    input: TextureHandle,
+    exposure: HistoryTexture,
+
+    config: AutoExposureConfig,
+    environment: EnvironmentData,
+    last_update: Option<Instant>,
+    history_valid: bool,
+
+    params: TypedBuffer<ExposureParams>,
+    histogram: TypedBuffer<[u32; BIN_COUNT]>,
+
+    layout: BindGroupLayout,
+    /// One for each parity of the exposure history
+    bind_groups: [Option<BindGroup>; 2],
+
+    histogram_pipeline: ComputePipeline,
+    average_pipeline: ComputePipeline,
+
+    camera: CameraSelection,
+}
+
+fn create_pipeline(
+    gpu: &Gpu,
+    label: &str,
+    module: &ShaderModule,
+    entry_point: &str,
+    layout: &BindGroupLayout,
+) -> ComputePipeline {
+    let pipeline_layout = gpu
+        .device
+        .create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: Some(label),
+            bind_group_layouts: &[layout],
+            push_constant_ranges: &[],
+        });
+
+    gpu.device
+        .create_compute_pipeline(&ComputePipelineDescriptor {
+            label: Some(label),
+            layout: Some(&pipeline_layout),
+            module,
+            entry_point,
+            compilation_options: Default::default(),
+            cache: None,
+        })
+}
+
+impl AutoExposureNode {
+    pub fn new(
+        gpu: &Gpu,
+        input: TextureHandle,
+        exposure: HistoryTexture,
+        config: AutoExposureConfig,
+    ) -> Self {
+        let layout = BindGroupLayoutBuilder::new("AutoExposure")
+            .bind_uniform_buffer(ShaderStages::COMPUTE)
+            .bind_storage_buffer_write(ShaderStages::COMPUTE)
+            .bind_texture_unfiltered(ShaderStages::COMPUTE)
+            .bind_texture_unfiltered(ShaderStages::COMPUTE)
+            .bind_storage_texture(
+                ShaderStages::COMPUTE,
+                StorageTextureAccess::WriteOnly,
+                EXPOSURE_FORMAT,
+            )
+            .build(gpu);
+
+        let params = TypedBuffer::new(
+            gpu,
+            "AutoExposure.params",
+            BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+            &[bytemuck::Zeroable::zeroed()],
+        );
+
+        let histogram = TypedBuffer::new(
+            gpu,
+            "AutoExposure.histogram",
+            BufferUsages::STORAGE,
+            &[[0; BIN_COUNT]],
+        );
+
+        let module = gpu.device.create_shader_module(ShaderModuleDescriptor {
+            label: Some("auto_exposure"),
+            source: ShaderSource::Wgsl(include_str!("../shaders/auto_exposure.wgsl").into()),
+        });
+
+        let histogram_pipeline = create_pipeline(
+            gpu,
+            "AutoExposure.histogram",
+            &module,
+            "cs_histogram",
+            &layout,
+        );
+        let average_pipeline =
+            create_pipeline(gpu, "AutoExposure.average", &module, "cs_average", &layout);
+
+        Self {
+            input,
+            exposure,
+            config,
+            environment: Default::default(),
+            last_update: None,
+            history_valid: false,
+            params,
+            histogram,
+            layout,
+            bind_groups: Default::default(),
+            histogram_pipeline,
+            average_pipeline,
+            camera: CameraSelection::Main,
+        }
+    }
+
+    /// Adapt within the exposure range of another camera than the main camera
+    pub fn with_camera(mut self, camera: impl Into<CameraSelection>) -> Self {
+        self.camera = camera.into();
+        self
+    }
+
+    fn camera_environment(&self, world: &World) -> EnvironmentData {
+        self.camera
+            .find(world)
+            .and_then(|id| world.get_copy(id, environment_data()).ok())
+            .unwrap_or_default()
+    }
+}
+
+impl Node for AutoExposureNode {
+    fn update(&mut self, ctx: NodeUpdateContext) -> anyhow::Result<UpdateResult> {
+        self.environment = self.camera_environment(ctx.world);
+        self.history_valid = ctx.resources.history_valid(self.exposure);
+        Ok(UpdateResult::Success)
+    }
+
+    fn draw(&mut self, ctx: NodeExecutionContext) -> anyhow::Result<()> {
+        let input = ctx.get_texture(self.input);
+        let current = ctx.get_texture(self.exposure.current);
+        let previous = ctx.get_texture(self.exposure.previous);
+
+        let now = Instant::now();
+        let delta_time = self
+            .last_update
+            .map(|v| now.duration_since(v).as_secs_f32())
+            .unwrap_or_default();
+        self.last_update = Some(now);
+
+        let size = input.size();
+        let env = &self.environment;
+
+        self.params.write(
+            ctx.queue,
+            0,
+            &[ExposureParams {
+                min_log_lum: self.config.min_log_luminance,
+                log_lum_range: (self.config.max_log_luminance - self.config.min_log_luminance)
+                    .max(1e-3),
+                pixel_count: (size.width * size.height) as f32,
+                delta_time,
+                min_ev: env.min_ev,
+                max_ev: env.max_ev,
+                compensation: env.exposure_compensation,
+                speed_up: self.config.speed_up,
+                speed_down: self.config.speed_down,
+                history_valid: self.history_valid as u32,
+                _padding: Default::default(),
+            }],
+        );
+
+        let parity = ctx.resources.history_parity(self.exposure);
+        let bind_group = self.bind_groups[parity].get_or_insert_with(|| {
+            BindGroupBuilder::new("AutoExposure")
+                .bind_buffer(&self.params)
+                .bind_buffer(&self.histogram)
+                .bind_texture(&previous.create_view(&Default::default()))
+                .bind_texture(&input.create_view(&Default::default()))
+                .bind_texture(&current.create_view(&Default::default()))
+                .build(ctx.gpu, &self.layout)
+        });
+
+        ComputeDispatch::new("AutoExposure.histogram", &self.histogram_pipeline)
+            .with_bind_groups(&[bind_group])
+            .with_workgroups(workgroups_for_extent(size, (16, 16)))
+            .dispatch(ctx.encoder);
+
+        ComputeDispatch::new("AutoExposure.average", &self.average_pipeline)
+            .with_bind_groups(&[bind_group])
+            .dispatch(ctx.encoder);
+
+        Ok(())
+    }
+
+    fn read_dependencies(&self) -> Vec<Dependency> {
+        vec![
+            Dependency::texture(self.input, TextureUsages::TEXTURE_BINDING),
+            Dependency::texture(self.exposure.previous, TextureUsages::TEXTURE_BINDING),
+        ]
+    }
+
+    fn write_dependencies(&self) -> Vec<Dependency> {
+        vec![Dependency::storage_texture(self.exposure.current)]
+    }
+
+    fn on_resource_changed(&mut self, _resource: ResourceHandle) {
+        self.bind_groups = Default::default();
+    }
+}
@@ -0,0 +1,312 @@
+use ivy_core::PhysicalExtent;
+use ivy_wgpu::{
+    camera_target::{CameraSelection, Viewport},
+    rendergraph::{
+        Dependency, Node, NodeExecutionContext, NodeUpdateContext, ResourceHandle, TextureHandle,
+        UpdateResult,
+    },
+    types::{
+        shader::{ShaderDesc, TargetDesc},
+        BindGroupBuilder, BindGroupLayoutBuilder, RenderShader, TypedBuffer,
+    },
+    Gpu,
+};
+use wgpu::{
+    BindGroup, BindGroupLayout, BufferUsages, Color, Operations, RenderPassColorAttachment,
+    SamplerDescriptor, ShaderModuleDescriptor, ShaderSource, ShaderStages, StoreOp, TextureUsages,
+};
+
+use crate::components::camera_effects;
+
+/// Darkens the edges of the screen
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Vignette {
+    /// Darkening at the corners, where `1.0` is black
+    pub intensity: f32,
+    /// Distance from the center where the darkening starts, relative to half the screen height
+    pub radius: f32,
+    /// Width of the transition from the radius to the full intensity
+    pub smoothness: f32,
+}
+
+impl Default for Vignette {
+    fn default() -> Self {
+        Self {
+            intensity: 0.4,
+            radius: 0.75,
+            smoothness: 0.5,
+        }
+    }
+}
+
+/// Lens and film effects applied to the final image of a camera.
+///
+/// Each effect is disabled when `None`.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct CameraEffects {
+    pub vignette: Option<Vignette>,
+    /// Separation of the red and blue channels at the screen corners, as a fraction of the screen
+    pub chromatic_aberration: Option<f32>,
+    /// Strength of the animated film grain
+    pub grain: Option<f32>,
+    /// Strength of the sharpening, restoring detail lost to anti-aliasing
+    pub sharpen: Option<f32>,
+}
+
+impl CameraEffects {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the vignette
+    pub fn with_vignette(mut self, vignette: Vignette) -> Self {
+        self.vignette = Some(vignette);
+        self
+    }
+
+    /// Set the chromatic aberration
+    pub fn with_chromatic_aberration(mut self, chromatic_aberration: f32) -> Self {
+        self.chromatic_aberration = Some(chromatic_aberration);
+        self
+    }
+
+    /// Set the film grain
+    pub fn with_grain(mut self, grain: f32) -> Self {
+        self.grain = Some(grain);
+        self
+    }
+
+    /// Set the sharpening
+    pub fn with_sharpen(mut self, sharpen: f32) -> Self {
+        self.sharpen = Some(sharpen);
+        self
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct CameraEffectsData {
+    vignette_intensity: f32,
+    vignette_radius: f32,
+    vignette_smoothness: f32,
+    chromatic_aberration: f32,
+    grain: f32,
+    sharpen: f32,
+    frame: u32,
+    _padding: u32,
+}
+
+/// Applies the [`CameraEffects`] of the camera to the tonemapped image in a single pass.
+///
+/// `default_effects` are used when the camera has no [`camera_effects`] component.
+pub struct CameraEffectsNode {
+    input: TextureHandle,
+    output: TextureHandle,
+    default_effects: CameraEffects,
+    /// Seeds the grain, counted in frames rather than time to keep replays deterministic
+    frame: u32,
+
+    shader: Option<RenderShader>,
+    layout: BindGroupLayout,
+    bind_group: Option<BindGroup>,
+    sampler: wgpu::Sampler,
+    data: TypedBuffer<CameraEffectsData>,
+
+    camera: CameraSelection,
+    viewport: Option<Viewport>,
+}
+
+impl CameraEffectsNode {
+    pub fn new(
+        gpu: &Gpu,
+        input: TextureHandle,
+        output: TextureHandle,
+        default_effects: CameraEffects,
+    ) -> Self {
+        let layout = BindGroupLayoutBuilder::new("CameraEffects")
+            .bind_texture(ShaderStages::FRAGMENT)
+            .bind_sampler(ShaderStages::FRAGMENT)
+            .bind_uniform_buffer(ShaderStages::FRAGMENT)
+            .build(gpu);
+
+        // Chromatic aberration samples between pixels
+        let sampler = gpu.device.create_sampler(&SamplerDescriptor {
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Nearest,
+            ..Default::default()
+        });
+
+        let data = TypedBuffer::new(
+            gpu,
+            "CameraEffects",
+            BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+            &[bytemuck::Zeroable::zeroed()],
+        );
+
+        Self {
+            input,
+            output,
+            default_effects,
+            frame: 0,
+            shader: None,
+            layout,
+            bind_group: None,
+            sampler,
+            data,
+            camera: CameraSelection::Main,
+            viewport: None,
+        }
+    }
+
+    /// Apply the effects of another camera than the main camera
+    pub fn with_camera(mut self, camera: impl Into<CameraSelection>) -> Self {
+        self.camera = camera.into();
+        self
+    }
+
+    /// Draw into a viewport of the output, keeping the rest of it, e.g; for the views of a split
+    /// screen.
+    ///
+    /// The output is expected to be cleared by the node writing it.
+    pub fn with_viewport(mut self, viewport: Viewport) -> Self {
+        self.viewport = Some(viewport);
+        self
+    }
+}
+
+impl Node for CameraEffectsNode {
+    fn update(&mut self, ctx: NodeUpdateContext) -> anyhow::Result<UpdateResult> {
+        let effects = self
+            .camera
+            .find(ctx.world)
+            .and_then(|id| ctx.world.get_copy(id, camera_effects()).ok())
+            .unwrap_or(self.default_effects);
+
+        let vignette = effects.vignette.unwrap_or(Vignette {
+            intensity: 0.0,
+            ..Default::default()
+        });
+
+        self.frame = self.frame.wrapping_add(1);
+
+        self.data.write(
+            &ctx.gpu.queue,
+            0,
+            &[CameraEffectsData {
+                vignette_intensity: vignette.intensity,
+                vignette_radius: vignette.radius,
+                vignette_smoothness: vignette.smoothness.max(1e-3),
+                chromatic_aberration: effects.chromatic_aberration.unwrap_or_default(),
+                grain: effects.grain.unwrap_or_default(),
+                sharpen: effects.sharpen.unwrap_or_default(),
+                frame: self.frame,
+                _padding: 0,
+            }],
+        );
+
+        Ok(UpdateResult::Success)
+    }
+
+    fn draw(&mut self, ctx: NodeExecutionContext) -> anyhow::Result<()> {
+        let input = ctx.get_texture(self.input);
+        let output = ctx.get_texture(self.output);
+
+        let bind_group = self.bind_group.get_or_insert_with(|| {
+            BindGroupBuilder::new("CameraEffects")
+                .bind_texture(&input.create_view(&Default::default()))
+                .bind_sampler(&self.sampler)
+                .bind_buffer(&self.data)
+                .build(ctx.gpu, &self.layout)
+        });
+
+        let shader = self.shader.get_or_insert_with(|| {
+            RenderShader::new(
+                ctx.gpu,
+                &ShaderDesc::new(
+                    "camera_effects",
+                    &ctx.gpu.device.create_shader_module(ShaderModuleDescriptor {
+                        label: Some("camera_effects"),
+                        source: ShaderSource::Wgsl(
+                            include_str!("../shaders/camera_effects.wgsl").into(),
+                        ),
+                    }),
+                    &TargetDesc {
+                        formats: &[output.format()],
+                        depth_format: None,
+                        sample_count: 1,
+                    },
+                )
+                .with_bind_group_layouts(&[&self.layout])
+                .with_blend(None),
+            )
+        });
+
+        let output_view = output.create_view(&Default::default());
+        let mut render_pass = ctx.encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: "CameraEffects".into(),
+            color_attachments: &[Some(RenderPassColorAttachment {
+                view: &output_view,
+                resolve_target: None,
+                ops: Operations {
+                    load: match self.viewport {
+                        Some(_) => wgpu::LoadOp::Load,
+                        None => wgpu::LoadOp::Clear(Color::BLACK),
+                    },
+                    store: StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            ..Default::default()
+        });
+
+        render_pass.set_pipeline(shader.pipeline());
+        render_pass.set_bind_group(0, bind_group, &[]);
+
+        if let Some(viewport) = self.viewport {
+            let (x, y, width, height) =
+                viewport.to_physical(PhysicalExtent::new(output.width(), output.height()));
+
+            render_pass.set_viewport(x as f32, y as f32, width as f32, height as f32, 0.0, 1.0);
+            render_pass.set_scissor_rect(x, y, width, height);
+        }
+
+        render_pass.draw(0..3, 0..1);
+
+        Ok(())
+    }
+
+    fn read_dependencies(&self) -> Vec<Dependency> {
+        let mut dependencies = vec![Dependency::texture(
+            self.input,
+            TextureUsages::TEXTURE_BINDING,
+        )];
+
+        // Drawn on top of the output, like an overlay
+        if self.viewport.is_some() {
+            dependencies.push(Dependency::texture(
+                self.output,
+                TextureUsages::RENDER_ATTACHMENT,
+            ));
+        }
+
+        dependencies
+    }
+
+    fn write_dependencies(&self) -> Vec<Dependency> {
+        match self.viewport {
+            Some(_) => vec![],
+            None => vec![Dependency::texture(
+                self.output,
+                TextureUsages::RENDER_ATTACHMENT,
+            )],
+        }
+    }
+
+    fn on_resource_changed(&mut self, _resource: ResourceHandle) {
+        self.bind_group = None;
+    }
+}
@@ -1,3 +1,37 @@
 use flax::component;
+use image::DynamicImage;
+use ivy_assets::Asset;
 
-component! {}
+use crate::{atmosphere::Atmosphere, camera_effects::CameraEffects, tonemap::Tonemapping};
+
+component! {
+    /// Distance from the camera which is in perfect focus.
+    ///
+    /// Enables depth of field for the camera together with [`aperture`].
+    pub focal_distance: f32,
+    /// Aperture of the camera lens as an f-number.
+    ///
+    /// Lower values, such as `1.4`, give a shallower depth of field and more blur.
+    pub aperture: f32,
+
+    /// Tonemapping operator of the camera, defaults to [`Tonemapping::Reinhard`]
+    pub tonemapping: Tonemapping,
+    /// Color grading lookup table applied after tonemapping.
+    ///
+    /// Stored as a horizontal strip of `N` slices of `NxN` pixels, such as a `1024x32` image for
+    /// a lut of size 32.
+    pub color_grading_lut: Asset<DynamicImage>,
+
+    /// Vignette, chromatic aberration, grain and sharpening applied to the final image.
+    ///
+    /// Overrides the effects configured for the render graph.
+    pub camera_effects: CameraEffects,
+
+    /// Atmosphere of the procedural sky, overriding the configured atmosphere
+    pub atmosphere: Atmosphere,
+    /// Equirectangular hdr image used as the environment of the camera, replacing the configured
+    /// skybox hdri.
+    ///
+    /// The image based lighting is re-baked when changed.
+    pub environment_hdri: Asset<DynamicImage>,
+}
@@ -0,0 +1,324 @@
+use flax::{Query, World};
+use glam::Mat4;
+use ivy_wgpu::{
+    camera_target::CameraSelection,
+    components::projection_matrix,
+    rendergraph::{
+        Dependency, Node, NodeExecutionContext, NodeUpdateContext, ResourceHandle, TextureHandle,
+        UpdateResult,
+    },
+    types::{
+        shader::{ShaderDesc, TargetDesc},
+        BindGroupBuilder, BindGroupLayoutBuilder, RenderShader, TypedBuffer,
+    },
+    Gpu,
+};
+use wgpu::{
+    BindGroup, BindGroupLayout, BufferUsages, Color, CommandEncoder, Operations,
+    RenderPassColorAttachment, Sampler, SamplerDescriptor, ShaderModule, ShaderModuleDescriptor,
+    ShaderSource, ShaderStages, StoreOp, TextureFormat, TextureUsages, TextureView,
+};
+
+use crate::components::{aperture, focal_distance};
+
+/// Height of a full frame camera sensor, used to derive the focal length from the field of view
+const SENSOR_HEIGHT: f32 = 0.024;
+
+/// Format of the intermediate color and circle of confusion texture
+pub const DOF_COC_FORMAT: TextureFormat = TextureFormat::Rgba16Float;
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct DepthOfFieldData {
+    inv_proj: Mat4,
+    focal_distance: f32,
+    coc_scale: f32,
+    max_radius: f32,
+    radius_step: f32,
+}
+
+#[derive(Debug, Clone)]
+pub struct DepthOfFieldConfig {
+    /// Maximum blur radius in pixels
+    pub max_radius: f32,
+    /// Distance in pixels between the sampled rings of the bokeh.
+    ///
+    /// Lower values give smoother bokeh at the cost of more samples.
+    pub radius_step: f32,
+}
+
+impl Default for DepthOfFieldConfig {
+    fn default() -> Self {
+        Self {
+            max_radius: 12.0,
+            radius_step: 1.0,
+        }
+    }
+}
+
+/// Blurs the image based on the distance from the focal plane of the camera.
+///
+/// Enabled by adding [`focal_distance`] and [`aperture`] to the camera. The strength of the
+/// blur follows a thin lens with the field of view of the camera.
+///
+/// The `coc` texture must be the size of the input, with the format [`DOF_COC_FORMAT`].
+pub struct DepthOfFieldNode {
+    input: TextureHandle,
+    depth: TextureHandle,
+    coc: TextureHandle,
+    output: TextureHandle,
+
+    config: DepthOfFieldConfig,
+    buffer: TypedBuffer<DepthOfFieldData>,
+
+    layout: BindGroupLayout,
+    bind_groups: Option<(BindGroup, BindGroup)>,
+    sampler: Sampler,
+
+    module: ShaderModule,
+    coc_shader: RenderShader,
+    gather_shader: Option<RenderShader>,
+
+    camera: CameraSelection,
+}
+
+fn create_shader(
+    gpu: &Gpu,
+    label: &str,
+    module: &ShaderModule,
+    entry_point: &str,
+    format: TextureFormat,
+    layout: &BindGroupLayout,
+) -> RenderShader {
+    let target = TargetDesc {
+        formats: &[format],
+        depth_format: None,
+        sample_count: 1,
+    };
+
+    let mut desc = ShaderDesc::new(label, module, &target)
+        .with_bind_group_layouts(&[layout])
+        .with_blend(None);
+
+    desc.fragment_entry_point = entry_point;
+
+    RenderShader::new(gpu, &desc)
+}
+
+fn fullscreen_pass(
+    encoder: &mut CommandEncoder,
+    label: &str,
+    view: &TextureView,
+    shader: &RenderShader,
+    bind_group: &BindGroup,
+) {
+    let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+        label: Some(label),
+        color_attachments: &[Some(RenderPassColorAttachment {
+            view,
+            resolve_target: None,
+            ops: Operations {
+                load: wgpu::LoadOp::Clear(Color::BLACK),
+                store: StoreOp::Store,
+            },
+        })],
+        depth_stencil_attachment: None,
+        ..Default::default()
+    });
+
+    render_pass.set_pipeline(shader.pipeline());
+    render_pass.set_bind_group(0, bind_group, &[]);
+
+    render_pass.draw(0..3, 0..1);
+}
+
+impl DepthOfFieldNode {
+    pub fn new(
+        gpu: &Gpu,
+        input: TextureHandle,
+        depth: TextureHandle,
+        coc: TextureHandle,
+        output: TextureHandle,
+        config: DepthOfFieldConfig,
+    ) -> Self {
+        let layout = BindGroupLayoutBuilder::new("DepthOfField")
+            .bind_uniform_buffer(ShaderStages::FRAGMENT)
+            .bind_texture(ShaderStages::FRAGMENT)
+            .bind_texture_unfiltered(ShaderStages::FRAGMENT)
+            .bind_sampler(ShaderStages::FRAGMENT)
+            .build(gpu);
+
+        let buffer = TypedBuffer::new(
+            gpu,
+            "DepthOfField",
+            BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+            &[bytemuck::Zeroable::zeroed()],
+        );
+
+        let sampler = gpu.device.create_sampler(&SamplerDescriptor {
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Nearest,
+            ..Default::default()
+        });
+
+        let module = gpu.device.create_shader_module(ShaderModuleDescriptor {
+            label: Some("depth_of_field"),
+            source: ShaderSource::Wgsl(include_str!("../shaders/depth_of_field.wgsl").into()),
+        });
+
+        let coc_shader = create_shader(
+            gpu,
+            "depth_of_field_coc",
+            &module,
+            "fs_coc",
+            DOF_COC_FORMAT,
+            &layout,
+        );
+
+        Self {
+            input,
+            depth,
+            coc,
+            output,
+            config,
+            buffer,
+            layout,
+            bind_groups: None,
+            sampler,
+            module,
+            coc_shader,
+            gather_shader: None,
+            camera: CameraSelection::Main,
+        }
+    }
+
+    /// Focus after another camera than the main camera
+    pub fn with_camera(mut self, camera: impl Into<CameraSelection>) -> Self {
+        self.camera = camera.into();
+        self
+    }
+
+    fn shader_data(&self, world: &World) -> DepthOfFieldData {
+        let mut query = Query::new((projection_matrix(), focal_distance(), aperture()));
+        let mut query = query.borrow(world);
+
+        let Some((&proj, &focal_distance, &aperture)) =
+            self.camera.find(world).and_then(|id| query.get(id).ok())
+        else {
+            // Disable the blur
+            return DepthOfFieldData {
+                inv_proj: Mat4::IDENTITY,
+                focal_distance: 1.0,
+                coc_scale: 0.0,
+                max_radius: 0.0,
+                radius_step: self.config.radius_step,
+            };
+        };
+
+        // Thin lens circle of confusion diameter at infinity, relative to the sensor height
+        let focal_length = SENSOR_HEIGHT * proj.y_axis.y * 0.5;
+        let coc_scale = focal_length * focal_length
+            / (aperture.max(0.1) * (focal_distance - focal_length).max(1e-4))
+            / SENSOR_HEIGHT;
+
+        DepthOfFieldData {
+            inv_proj: proj.inverse(),
+            focal_distance,
+            coc_scale: coc_scale * 0.5,
+            max_radius: self.config.max_radius,
+            radius_step: self.config.radius_step.max(0.1),
+        }
+    }
+}
+
+impl Node for DepthOfFieldNode {
+    fn update(&mut self, ctx: NodeUpdateContext) -> anyhow::Result<UpdateResult> {
+        let data = self.shader_data(ctx.world);
+        self.buffer.write(&ctx.gpu.queue, 0, &[data]);
+
+        Ok(UpdateResult::Success)
+    }
+
+    fn draw(&mut self, ctx: NodeExecutionContext) -> anyhow::Result<()> {
+        let input = ctx.get_texture(self.input);
+        let depth = ctx.get_texture(self.depth);
+        let coc = ctx.get_texture(self.coc);
+        let output = ctx.get_texture(self.output);
+
+        let coc_view = coc.create_view(&Default::default());
+        let output_view = output.create_view(&Default::default());
+
+        let (coc_bind_group, gather_bind_group) = self.bind_groups.get_or_insert_with(|| {
+            let input_view = input.create_view(&Default::default());
+            let depth_view = depth.create_view(&Default::default());
+
+            let create_bind_group = |label, source| {
+                BindGroupBuilder::new(label)
+                    .bind_buffer(&self.buffer)
+                    .bind_texture(source)
+                    .bind_texture(&depth_view)
+                    .bind_sampler(&self.sampler)
+                    .build(ctx.gpu, &self.layout)
+            };
+
+            (
+                create_bind_group("DepthOfField.coc", &input_view),
+                create_bind_group("DepthOfField.gather", &coc_view),
+            )
+        });
+
+        let gather_shader = self.gather_shader.get_or_insert_with(|| {
+            create_shader(
+                ctx.gpu,
+                "depth_of_field_gather",
+                &self.module,
+                "fs_gather",
+                output.format(),
+                &self.layout,
+            )
+        });
+
+        fullscreen_pass(
+            ctx.encoder,
+            "DepthOfField.coc",
+            &coc_view,
+            &self.coc_shader,
+            coc_bind_group,
+        );
+
+        fullscreen_pass(
+            ctx.encoder,
+            "DepthOfField.gather",
+            &output_view,
+            gather_shader,
+            gather_bind_group,
+        );
+
+        Ok(())
+    }
+
+    fn read_dependencies(&self) -> Vec<Dependency> {
+        vec![
+            Dependency::texture(self.input, TextureUsages::TEXTURE_BINDING),
+            Dependency::texture(self.depth, TextureUsages::TEXTURE_BINDING),
+        ]
+    }
+
+    fn write_dependencies(&self) -> Vec<Dependency> {
+        vec![
+            Dependency::texture(
+                self.coc,
+                TextureUsages::RENDER_ATTACHMENT | TextureUsages::TEXTURE_BINDING,
+            ),
+            Dependency::texture(self.output, TextureUsages::RENDER_ATTACHMENT),
+        ]
+    }
+
+    fn on_resource_changed(&mut self, _resource: ResourceHandle) {
+        self.bind_groups = None;
+    }
+}
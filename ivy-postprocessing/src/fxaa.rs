@@ -0,0 +1,127 @@
+use ivy_wgpu::{
+    rendergraph::{Dependency, Node, TextureHandle},
+    types::{
+        shader::{ShaderDesc, TargetDesc},
+        BindGroupBuilder, BindGroupLayoutBuilder, RenderShader,
+    },
+    Gpu,
+};
+use wgpu::{
+    BindGroup, BindGroupLayout, Color, Operations, RenderPassColorAttachment, SamplerDescriptor,
+    ShaderModuleDescriptor, ShaderSource, ShaderStages, StoreOp, TextureUsages,
+};
+
+/// Fast approximate anti-aliasing.
+///
+/// Single pass edge smoothing of a tonemapped image, for when MSAA is too costly.
+pub struct FxaaNode {
+    input: TextureHandle,
+    output: TextureHandle,
+    shader: Option<RenderShader>,
+    layout: BindGroupLayout,
+    bind_group: Option<BindGroup>,
+    sampler: wgpu::Sampler,
+}
+
+impl FxaaNode {
+    pub fn new(gpu: &Gpu, input: TextureHandle, output: TextureHandle) -> Self {
+        let layout = BindGroupLayoutBuilder::new("Fxaa")
+            .bind_texture(ShaderStages::FRAGMENT)
+            .bind_sampler(ShaderStages::FRAGMENT)
+            .build(gpu);
+
+        // The edge search relies on bilinear filtering to sample between pixels
+        let sampler = gpu.device.create_sampler(&SamplerDescriptor {
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Nearest,
+            ..Default::default()
+        });
+
+        Self {
+            input,
+            output,
+            shader: None,
+            bind_group: None,
+            layout,
+            sampler,
+        }
+    }
+}
+
+impl Node for FxaaNode {
+    fn draw(&mut self, ctx: ivy_wgpu::rendergraph::NodeExecutionContext) -> anyhow::Result<()> {
+        let input = ctx.get_texture(self.input);
+        let output = ctx.get_texture(self.output);
+
+        let bind_group = self.bind_group.get_or_insert_with(|| {
+            BindGroupBuilder::new("Fxaa")
+                .bind_texture(&input.create_view(&Default::default()))
+                .bind_sampler(&self.sampler)
+                .build(ctx.gpu, &self.layout)
+        });
+
+        let shader = self.shader.get_or_insert_with(|| {
+            RenderShader::new(
+                ctx.gpu,
+                &ShaderDesc::new(
+                    "fxaa",
+                    &ctx.gpu.device.create_shader_module(ShaderModuleDescriptor {
+                        label: Some("fxaa"),
+                        source: ShaderSource::Wgsl(include_str!("../shaders/fxaa.wgsl").into()),
+                    }),
+                    &TargetDesc {
+                        formats: &[output.format()],
+                        depth_format: None,
+                        sample_count: 1,
+                    },
+                )
+                .with_bind_group_layouts(&[&self.layout])
+                .with_blend(None),
+            )
+        });
+
+        let output_view = output.create_view(&Default::default());
+        let mut render_pass = ctx.encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: "Fxaa".into(),
+            color_attachments: &[Some(RenderPassColorAttachment {
+                view: &output_view,
+                resolve_target: None,
+                ops: Operations {
+                    load: wgpu::LoadOp::Clear(Color::BLACK),
+                    store: StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            ..Default::default()
+        });
+
+        render_pass.set_pipeline(shader.pipeline());
+        render_pass.set_bind_group(0, bind_group, &[]);
+
+        render_pass.draw(0..3, 0..1);
+
+        Ok(())
+    }
+
+    fn read_dependencies(&self) -> Vec<Dependency> {
+        vec![Dependency::texture(
+            self.input,
+            TextureUsages::TEXTURE_BINDING,
+        )]
+    }
+
+    fn write_dependencies(&self) -> Vec<Dependency> {
+        vec![Dependency::texture(
+            self.output,
+            TextureUsages::RENDER_ATTACHMENT,
+        )]
+    }
+
+    fn on_resource_changed(&mut self, _resource: ivy_wgpu::rendergraph::ResourceHandle) {
+        self.bind_group = None;
+    }
+}
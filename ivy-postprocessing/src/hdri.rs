@@ -9,6 +9,7 @@ use itertools::Itertools;
 use ivy_assets::Asset;
 use ivy_core::DEG_90;
 use ivy_wgpu::{
+    camera_target::CameraSelection,
     renderer::SkyboxTextures,
     rendergraph::{Dependency, Node, UpdateResult},
     types::{
@@ -25,6 +26,8 @@ use wgpu::{
     VertexBufferLayout, VertexStepMode,
 };
 
+use crate::components::environment_hdri;
+
 pub struct EnvironmentMapMode {}
 
 #[repr(C)]
@@ -468,15 +471,33 @@ impl HdriProcessor {
     pub fn format(&self) -> TextureFormat {
         self.format
     }
+
+    /// Projection used to render each side of the cubemap
+    pub fn proj(&self) -> Mat4 {
+        self.proj
+    }
+
+    /// View matrices of the cubemap sides, in layer order
+    pub fn view_matrices(&self) -> [Mat4; 6] {
+        self.view_matrices
+    }
 }
 
+/// Projects an hdri into the environment map of a [`SkyboxTextures`], and prefilters the
+/// irradiance and specular maps from it.
+///
+/// The [`environment_hdri`] of the camera overrides the configured hdri, and the maps are
+/// re-baked whenever it changes.
 pub struct HdriProcessorNode {
     processor: HdriProcessor,
     skybox: SkyboxTextures,
     incoming: futures::stream::Fuse<BoxStream<'static, Asset<DynamicImage>>>,
+    /// Last hdri received from `incoming`
+    configured: Option<Asset<DynamicImage>>,
     source: Option<Asset<DynamicImage>>,
     process_hdri: bool,
     process_brdf_lookup: bool,
+    camera: CameraSelection,
 }
 
 impl HdriProcessorNode {
@@ -490,10 +511,18 @@ impl HdriProcessorNode {
             incoming: source.fuse(),
             skybox,
             process_brdf_lookup: true,
+            configured: None,
             source: None,
             process_hdri: true,
+            camera: CameraSelection::Main,
         }
     }
+
+    /// Use the hdri of another camera than the main camera
+    pub fn with_camera(mut self, camera: impl Into<CameraSelection>) -> Self {
+        self.camera = camera.into();
+        self
+    }
 }
 
 impl Node for HdriProcessorNode {
@@ -503,11 +532,26 @@ impl Node for HdriProcessorNode {
 
     fn update(
         &mut self,
-        _ctx: ivy_wgpu::rendergraph::NodeUpdateContext,
+        ctx: ivy_wgpu::rendergraph::NodeUpdateContext,
     ) -> anyhow::Result<UpdateResult> {
         if let Some(source) = self.incoming.next().now_or_never().flatten() {
+            self.configured = Some(source);
+        }
+
+        let source = self
+            .camera
+            .find(ctx.world)
+            .and_then(|id| {
+                ctx.world
+                    .get(id, environment_hdri())
+                    .ok()
+                    .map(|v| v.clone())
+            })
+            .or_else(|| self.configured.clone());
+
+        if source.is_some() && source != self.source {
             self.process_hdri = true;
-            self.source = Some(source);
+            self.source = source;
         }
 
         Ok(UpdateResult::Success)
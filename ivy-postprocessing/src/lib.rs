@@ -1,8 +1,16 @@
+pub mod atmosphere;
+pub mod auto_exposure;
 pub mod bloom;
+pub mod camera_effects;
 pub mod components;
+pub mod depth_of_field;
 pub mod depth_resolve;
+pub mod fxaa;
 pub mod hdri;
 pub mod overlay;
 pub mod preconfigured;
 pub mod skybox;
+pub mod smaa;
+pub mod split_screen;
 pub mod tonemap;
+pub mod upscale;
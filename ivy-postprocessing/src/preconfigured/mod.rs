@@ -5,13 +5,18 @@ use std::sync::Arc;
 use anyhow::Context;
 use flax::World;
 use image::DynamicImage;
-use ivy_assets::{stored::DynamicStore, AssetCache, DynAsyncAssetDesc};
-use ivy_core::profiling::profile_scope;
-use ivy_ui::SharedUiInstance;
+use ivy_assets::{
+    service::FileSystemMapService, stored::DynamicStore, AssetCache, DynAsyncAssetDesc,
+};
+use ivy_core::{
+    components::engine, notifications::Notifier, profiling::profile_scope, PhysicalExtent,
+};
+use ivy_ui::{components::ui_instance, world_ui::SharedWorldUi, SharedUiInstance};
 use ivy_wgpu::{
+    renderer::readback::{ReadbackFrame, ReadbackNode},
     rendergraph::{self, ExternalResources, RenderGraph, RenderGraphResources, TextureHandle},
     shader_library::{ShaderLibrary, ShaderModuleDesc},
-    types::{PhysicalSize, Surface},
+    types::Surface,
     Gpu,
 };
 use pbr::{PbrRenderGraph, PbrRenderGraphConfig};
@@ -19,9 +24,13 @@ use pbr::{PbrRenderGraph, PbrRenderGraphConfig};
 #[derive(Default)]
 pub struct SurfacePbrPipelineDesc {
     pub hdri: Option<Box<dyn DynAsyncAssetDesc<DynamicImage>>>,
-    /// Render Ui if configured
+    /// Render Ui if configured, defaults to the Ui added by the [`UiPlugin`](ivy_ui::UiPlugin)
     pub ui_instance: Option<SharedUiInstance>,
+    /// Render the panels of a [`WorldUiLayer`](ivy_ui::world_ui::WorldUiLayer) if configured
+    pub world_ui: Option<SharedWorldUi>,
     pub pbr_config: PbrRenderGraphConfig,
+    /// Read back each presented frame, e.g; for recording replays
+    pub readback: Option<flume::Sender<ReadbackFrame>>,
 }
 
 /// Uses a rendergraph to render to a surface
@@ -41,20 +50,29 @@ impl SurfacePbrRenderer {
         surface: Surface,
         desc: SurfacePbrPipelineDesc,
     ) -> Self {
+        let mut shader_library = ShaderLibrary::new();
+        if let Some(fs) = assets.try_service::<FileSystemMapService>() {
+            shader_library = shader_library.with_root(fs.root.clone());
+        }
+
+        if let Some(notifier) = assets.try_service::<Notifier>() {
+            shader_library = shader_library.with_notifier(notifier.clone());
+        }
+
         // TODO; pass as param
-        let shader_library = ShaderLibrary::new()
+        let shader_library = shader_library
             .with_module(ShaderModuleDesc {
-                path: "./assets/shaders/pbr_base.wgsl",
+                path: "shaders/pbr_base.wgsl",
                 source: include_str!("../../../assets/shaders/pbr_base.wgsl"),
                 shader_defs: Default::default(),
             })
             .with_module(ShaderModuleDesc {
-                path: "./assets/shaders/vertex.wgsl",
+                path: "shaders/vertex.wgsl",
                 source: include_str!("../../../assets/shaders/vertex.wgsl"),
                 shader_defs: Default::default(),
             })
             .with_module(ShaderModuleDesc {
-                path: "./assets/shaders/material_pbr.wgsl",
+                path: "shaders/material_pbr.wgsl",
                 source: include_str!("../../../assets/shaders/material_pbr.wgsl"),
                 shader_defs: Default::default(),
             });
@@ -68,16 +86,26 @@ impl SurfacePbrRenderer {
             .resources
             .insert_texture(rendergraph::TextureDesc::External);
 
+        let ui_instance = desc.ui_instance.or_else(|| {
+            let handle = world.get(engine(), ui_instance()).ok()?;
+            Some(store.get(&*handle).clone())
+        });
+
         let pbr = desc.pbr_config.configure(
             world,
             gpu,
             assets,
             store,
             &mut render_graph,
-            desc.ui_instance,
+            ui_instance,
+            desc.world_ui,
             surface_texture,
         );
 
+        if let Some(tx) = desc.readback {
+            render_graph.add_node(ReadbackNode::new(surface_texture, tx));
+        }
+
         Self {
             render_graph,
             surface,
@@ -96,6 +124,8 @@ impl ivy_wgpu::layer::Renderer for SurfacePbrRenderer {
         gpu: &Gpu,
         queue: &wgpu::Queue,
     ) -> anyhow::Result<()> {
+        self.render_graph.resources.shader_library().poll_changes();
+
         let surface_texture = self.surface.get_current_texture()?;
 
         let mut external_resources = ExternalResources::new();
@@ -129,7 +159,7 @@ impl ivy_wgpu::layer::Renderer for SurfacePbrRenderer {
         Ok(())
     }
 
-    fn on_resize(&mut self, gpu: &Gpu, size: PhysicalSize<u32>) {
+    fn on_resize(&mut self, gpu: &Gpu, size: PhysicalExtent) {
         self.surface.resize(gpu, size);
 
         self.pbr.set_size(&mut self.render_graph, size);
@@ -179,17 +209,17 @@ impl SurfaceRenderer {
         // TODO; pass as param
         let shader_library = ShaderLibrary::new()
             .with_module(ShaderModuleDesc {
-                path: "./assets/shaders/pbr_base.wgsl",
+                path: "shaders/pbr_base.wgsl",
                 source: include_str!("../../../assets/shaders/pbr_base.wgsl"),
                 shader_defs: Default::default(),
             })
             .with_module(ShaderModuleDesc {
-                path: "./assets/shaders/vertex.wgsl",
+                path: "shaders/vertex.wgsl",
                 source: include_str!("../../../assets/shaders/vertex.wgsl"),
                 shader_defs: Default::default(),
             })
             .with_module(ShaderModuleDesc {
-                path: "./assets/shaders/material_pbr.wgsl",
+                path: "shaders/material_pbr.wgsl",
                 source: include_str!("../../../assets/shaders/material_pbr.wgsl"),
                 shader_defs: Default::default(),
             });
@@ -232,6 +262,8 @@ impl ivy_wgpu::layer::Renderer for SurfaceRenderer {
         gpu: &Gpu,
         queue: &wgpu::Queue,
     ) -> anyhow::Result<()> {
+        self.render_graph.resources.shader_library().poll_changes();
+
         let surface_texture = self.surface.get_current_texture()?;
 
         let mut external_resources = ExternalResources::new();
@@ -265,7 +297,7 @@ impl ivy_wgpu::layer::Renderer for SurfaceRenderer {
         Ok(())
     }
 
-    fn on_resize(&mut self, gpu: &Gpu, size: PhysicalSize<u32>) {
+    fn on_resize(&mut self, gpu: &Gpu, size: PhysicalExtent) {
         self.surface.resize(gpu, size);
     }
 
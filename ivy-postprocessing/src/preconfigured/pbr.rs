@@ -3,37 +3,98 @@ use std::{future::ready, mem::size_of};
 use flax::World;
 use futures::{stream, StreamExt};
 use image::DynamicImage;
-use ivy_assets::{stored::DynamicStore, AssetCache, DynAsyncAssetDesc};
-use ivy_ui::{node::UiRenderNode, SharedUiInstance};
+use itertools::Itertools;
+use ivy_assets::{
+    stored::{DynamicStore, Handle},
+    AssetCache, DynAsyncAssetDesc,
+};
+use ivy_core::{components::engine, PhysicalExtent};
+use ivy_ui::{
+    node::UiRenderNode,
+    world_ui::{SharedWorldUi, WorldUiRenderNode, WorldUiView},
+    SharedUiInstance,
+};
 use ivy_wgpu::{
-    components::{forward_pass, transparent_pass},
+    camera_target::{CameraSelection, CameraTarget, Viewport},
+    components::{forward_pass, picker, split_screen_viewports, transparent_pass},
+    material_desc::PbrMaterialData,
     renderer::{
         gizmos_renderer::GizmosRendererNode,
-        mesh_renderer::MeshRenderer,
+        light_probes::{
+            LightProbeBakeNode, LightProbeTextures, LightProbeVolume, LIGHT_PROBE_FORMAT,
+        },
+        mesh_renderer::{DrawOrder, MeshRenderer},
+        picking::{PickingNode, OBJECT_ID_FORMAT},
+        screen_gizmos_renderer::ScreenGizmosRendererNode,
         shadowmapping::{LightShadowCamera, ShadowMapNode},
-        CameraNode, LightManager, MsaaResolve, ObjectManager, SkyboxTextures,
+        sprite_renderer::SpriteRenderer,
+        CameraNode, CopyTexture, LightManager, MsaaResolve, ObjectManager, SkyboxTextures,
     },
-    rendergraph::{BufferDesc, ManagedTextureDesc, RenderGraph, TextureHandle},
-    types::{texture::max_mip_levels, PhysicalSize},
+    rendergraph::{
+        BufferDesc, BufferHandle, ManagedTextureDesc, RenderGraph, TextureDesc, TextureHandle,
+    },
+    texture_streaming::{TextureStreamer, TextureStreamingConfig, TextureStreamingNode},
+    types::texture::max_mip_levels,
     Gpu,
 };
 use wgpu::{BufferUsages, Extent3d, TextureDimension, TextureFormat};
 
 use crate::{
+    atmosphere::{Atmosphere, AtmosphereNode},
+    auto_exposure::{AutoExposureConfig, AutoExposureNode, EXPOSURE_FORMAT},
     bloom::BloomNode,
+    camera_effects::{CameraEffects, CameraEffectsNode},
+    depth_of_field::{DepthOfFieldConfig, DepthOfFieldNode, DOF_COC_FORMAT},
     depth_resolve::MsaaDepthResolve,
+    fxaa::FxaaNode,
     hdri::{HdriProcessor, HdriProcessorNode},
     skybox::SkyboxRenderer,
+    smaa::{SmaaNode, SMAA_EDGES_FORMAT, SMAA_WEIGHTS_FORMAT},
+    split_screen::SplitScreenNode,
     tonemap::TonemapNode,
+    upscale::UpscaleNode,
 };
 
 /// Pre-configured render graph suited for PBR render pipelines
 pub struct PbrRenderGraphConfig {
     pub shadow_map_config: Option<ShadowMapConfig>,
-    pub msaa: Option<MsaaConfig>,
+    pub anti_aliasing: AntiAliasing,
     pub bloom: Option<BloomConfig>,
+    /// Depth of field, controlled through the components on the main camera
+    pub depth_of_field: Option<DepthOfFieldConfig>,
+    /// Eye adaptation, clamped and compensated by the environment data of the main camera
+    pub auto_exposure: Option<AutoExposureConfig>,
+    /// Default lens and film effects, overridden by the camera effects of the main camera.
+    ///
+    /// The [`camera_effects`](crate::components::camera_effects) of the camera are applied
+    /// without this as well whenever the view is tonemapped or anti-aliased in post.
+    pub camera_effects: Option<CameraEffects>,
     pub skybox: Option<SkyboxConfig>,
+    /// Irradiance probes baked from the scene, giving bounce light inside the volume
+    pub light_probes: Option<LightProbeVolume>,
     pub hdr_format: Option<TextureFormat>,
+    /// Additional cameras rendered each frame into their own [`CameraTarget`]
+    pub camera_targets: Vec<CameraTargetConfig>,
+    /// Splits the window between views of the cameras with the matching
+    /// [`player_view`](ivy_wgpu::components::player_view) index, sharing the skybox and lighting.
+    ///
+    /// A single view of the main camera is rendered when empty.
+    pub player_views: Vec<Viewport>,
+    /// Streams material textures in and out by camera distance within a memory budget.
+    ///
+    /// Textures are fully loaded up front when disabled.
+    pub texture_streaming: Option<TextureStreamingConfig>,
+    /// Submit compute nodes which do not depend on the raster work ahead of it, allowing them to
+    /// overlap.
+    pub async_compute: bool,
+    /// Renders an id buffer of the main camera on request, answering
+    /// [`GraphicsLayer::pick`](ivy_wgpu::layer::GraphicsLayer::pick)
+    pub picking: bool,
+    /// Resolution of the camera views relative to the destination.
+    ///
+    /// Views are stretched over the destination when not `1.0`, while the ui is drawn at the full
+    /// resolution.
+    pub render_scale: f32,
     pub label: String,
 }
 
@@ -41,20 +102,142 @@ impl Default for PbrRenderGraphConfig {
     fn default() -> Self {
         Self {
             shadow_map_config: Some(Default::default()),
-            msaa: Some(Default::default()),
+            anti_aliasing: Default::default(),
             bloom: Some(Default::default()),
+            depth_of_field: None,
+            auto_exposure: None,
+            camera_effects: None,
             skybox: None,
+            light_probes: None,
             hdr_format: Some(TextureFormat::Rgba16Float),
+            camera_targets: Vec::new(),
+            player_views: Vec::new(),
+            texture_streaming: None,
+            async_compute: true,
+            picking: false,
+            render_scale: 1.0,
             label: "pbr".into(),
         }
     }
 }
 
+impl PbrRenderGraphConfig {
+    #[deprecated = "use `anti_aliasing` instead"]
+    pub fn msaa(&self) -> Option<&MsaaConfig> {
+        self.anti_aliasing.msaa()
+    }
+
+    /// Enables or disables multisampling, replacing any other anti-aliasing
+    #[deprecated = "use `anti_aliasing` instead"]
+    pub fn with_msaa(mut self, msaa: Option<MsaaConfig>) -> Self {
+        self.anti_aliasing = msaa.into();
+        self
+    }
+}
+
+/// Post processing of a single camera view
+#[derive(Debug, Clone)]
+pub struct PbrViewConfig {
+    pub anti_aliasing: AntiAliasing,
+    pub bloom: Option<BloomConfig>,
+    /// Controlled through the components on the main camera
+    pub depth_of_field: Option<DepthOfFieldConfig>,
+    /// Controlled through the environment data of the main camera
+    pub auto_exposure: Option<AutoExposureConfig>,
+    pub camera_effects: Option<CameraEffects>,
+    pub hdr_format: Option<TextureFormat>,
+}
+
+impl Default for PbrViewConfig {
+    fn default() -> Self {
+        Self {
+            anti_aliasing: Default::default(),
+            bloom: Some(Default::default()),
+            depth_of_field: None,
+            auto_exposure: None,
+            camera_effects: None,
+            hdr_format: Some(TextureFormat::Rgba16Float),
+        }
+    }
+}
+
+/// Camera rendering into a texture alongside the main view.
+///
+/// The camera is the entity with a matching [`camera_target`](ivy_wgpu::components::camera_target).
+/// Shadows and lighting are shared with the main view.
+pub struct CameraTargetConfig {
+    pub target: CameraTarget,
+    pub view: PbrViewConfig,
+}
+
+impl CameraTargetConfig {
+    pub fn new(target: CameraTarget, view: PbrViewConfig) -> Self {
+        Self { target, view }
+    }
+}
+
+/// Source of the environment map used for the skybox and image based lighting
+pub enum SkyboxSource {
+    /// Equirectangular hdr image
+    Hdri(Box<dyn DynAsyncAssetDesc<DynamicImage>>),
+    /// Procedural sky lit by the first directional light, which follows the sun as it moves
+    Atmosphere(Atmosphere),
+}
+
+impl From<Box<dyn DynAsyncAssetDesc<DynamicImage>>> for SkyboxSource {
+    fn from(value: Box<dyn DynAsyncAssetDesc<DynamicImage>>) -> Self {
+        Self::Hdri(value)
+    }
+}
+
+impl From<Atmosphere> for SkyboxSource {
+    fn from(value: Atmosphere) -> Self {
+        Self::Atmosphere(value)
+    }
+}
+
+/// Configures the skybox.
+///
+/// Previously the skybox was always an hdri, given through the `hdri` field. Configs written as
+/// `SkyboxConfig { hdri, format }` migrate to `SkyboxConfig::new(hdri, format)`, or
+/// [`SkyboxConfig::hdri`].
 pub struct SkyboxConfig {
-    pub hdri: Box<dyn DynAsyncAssetDesc<DynamicImage>>,
+    pub source: SkyboxSource,
     pub format: TextureFormat,
 }
 
+impl SkyboxConfig {
+    pub fn new(source: impl Into<SkyboxSource>, format: TextureFormat) -> Self {
+        Self {
+            source: source.into(),
+            format,
+        }
+    }
+
+    /// Returns the hdri of the skybox, if any
+    #[deprecated = "match on `source` instead"]
+    pub fn hdri_source(&self) -> Option<&dyn DynAsyncAssetDesc<DynamicImage>> {
+        match &self.source {
+            SkyboxSource::Hdri(v) => Some(&**v),
+            SkyboxSource::Atmosphere(_) => None,
+        }
+    }
+
+    pub fn hdri(hdri: impl DynAsyncAssetDesc<DynamicImage>, format: TextureFormat) -> Self {
+        Self {
+            source: SkyboxSource::Hdri(Box::new(hdri)),
+            format,
+        }
+    }
+
+    pub fn atmosphere(atmosphere: Atmosphere, format: TextureFormat) -> Self {
+        Self {
+            source: SkyboxSource::Atmosphere(atmosphere),
+            format,
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct ShadowMapConfig {
     pub resolution: u32,
@@ -83,6 +266,52 @@ impl Default for MsaaConfig {
     }
 }
 
+/// Technique used to smooth jagged edges
+#[derive(Debug, Clone)]
+pub enum AntiAliasing {
+    None,
+    /// Multisampled rendering and depth resolve
+    Msaa(MsaaConfig),
+    /// Fast approximate anti-aliasing of the final image.
+    ///
+    /// The cheapest option, but slightly blurs textures.
+    Fxaa,
+    /// Subpixel morphological anti-aliasing of the final image.
+    ///
+    /// Sharper than FXAA at the cost of two extra full screen passes.
+    Smaa,
+}
+
+impl AntiAliasing {
+    pub fn msaa(&self) -> Option<&MsaaConfig> {
+        match self {
+            AntiAliasing::Msaa(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    /// Returns true if anti-aliasing is applied after tonemapping
+    pub fn is_post_process(&self) -> bool {
+        matches!(self, AntiAliasing::Fxaa | AntiAliasing::Smaa)
+    }
+}
+
+impl Default for AntiAliasing {
+    fn default() -> Self {
+        Self::Msaa(Default::default())
+    }
+}
+
+/// Converts the previous `msaa` option of the render graph config
+impl From<Option<MsaaConfig>> for AntiAliasing {
+    fn from(value: Option<MsaaConfig>) -> Self {
+        match value {
+            Some(v) => Self::Msaa(v),
+            None => Self::None,
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct BloomConfig {
     pub filter_radius: f32,
@@ -99,7 +328,13 @@ impl Default for BloomConfig {
 }
 
 pub struct PbrRenderGraph {
+    /// Textures sized by the render scale
     screensized: Vec<TextureHandle>,
+    /// Textures of each split-screen view, sized by their viewport
+    player_views: Vec<(Viewport, Vec<TextureHandle>)>,
+    /// Textures which match the destination regardless of the render scale
+    unscaled: Vec<TextureHandle>,
+    render_scale: f32,
 }
 
 impl PbrRenderGraph {
@@ -108,6 +343,26 @@ impl PbrRenderGraph {
     }
 }
 
+/// Resources shared between all camera views of the render graph
+struct SharedPasses {
+    object_manager: Handle<ObjectManager>,
+    shadow_map_config: Option<ShadowMapConfig>,
+    /// Camera of the first view, which the shadow maps below are fitted to
+    primary_camera: CameraSelection,
+    shadow_maps: TextureHandle,
+    shadow_camera_buffer: BufferHandle,
+    skybox: Option<SkyboxTextures>,
+    light_probes: Option<LightProbeTextures>,
+}
+
+/// Textures of a configured camera view
+struct PbrView {
+    screensized: Vec<TextureHandle>,
+    resolved_depth_texture: TextureHandle,
+    /// Input of the final pass, which draws the view into the destination
+    final_input: TextureHandle,
+}
+
 impl PbrRenderGraphConfig {
     #[allow(clippy::too_many_arguments)]
     // TODO: fix arguments count
@@ -119,153 +374,59 @@ impl PbrRenderGraphConfig {
         store: &mut DynamicStore,
         render_graph: &mut RenderGraph,
         ui_instance: Option<SharedUiInstance>,
+        world_ui: Option<SharedWorldUi>,
         destination: TextureHandle,
     ) -> PbrRenderGraph {
         let object_manager = store.insert(ObjectManager::new(world, gpu));
+        render_graph.set_async_compute(self.async_compute);
 
-        let extent = Extent3d {
-            width: 0,
-            height: 0,
-            depth_or_array_layers: 1,
-        };
-
-        let target_format = self.hdr_format.unwrap_or(TextureFormat::Rgba8UnormSrgb);
-
-        // TODO: extend with generic effects
-        let needs_indirection_target = self.hdr_format.is_some() || self.bloom.is_some();
-
-        tracing::info!(?target_format);
-        let final_color = if needs_indirection_target {
-            render_graph.resources.insert_texture(ManagedTextureDesc {
-                label: format!("{}.final_color", self.label).into(),
-                extent,
-                dimension: wgpu::TextureDimension::D2,
-                format: target_format,
-                mip_level_count: 1,
-                sample_count: 1,
-                persistent: false,
-            })
-        } else {
-            destination
-        };
-
-        let sample_count = self.msaa.as_ref().map(|v| v.sample_count).unwrap_or(1);
-
-        let depth_texture = render_graph.resources.insert_texture(ManagedTextureDesc {
-            label: "depth_texture".into(),
-            extent,
-            dimension: wgpu::TextureDimension::D2,
-            format: wgpu::TextureFormat::Depth24Plus,
-            mip_level_count: 1,
-            sample_count,
-            persistent: false,
-        });
-
-        let resolved_depth_texture;
-        let sampled_target;
+        if let Some(config) = self.texture_streaming {
+            if assets.try_service::<TextureStreamer>().is_none() {
+                assets.register_service(TextureStreamer::new(config));
+            }
 
-        if self.msaa.is_some() {
-            sampled_target = render_graph.resources.insert_texture(ManagedTextureDesc {
-                label: "hrd_output".into(),
-                extent,
-                dimension: wgpu::TextureDimension::D2,
-                format: target_format,
-                mip_level_count: 1,
-                sample_count,
-                persistent: false,
-            });
+            render_graph.add_node(TextureStreamingNode);
+        }
 
-            resolved_depth_texture = render_graph.resources.insert_texture(ManagedTextureDesc {
-                label: "depth_texture".into(),
-                extent,
-                dimension: wgpu::TextureDimension::D2,
-                format: wgpu::TextureFormat::R32Float,
-                mip_level_count: 1,
-                sample_count: 1,
-                persistent: false,
-            })
+        // Shared passes, such as the skybox and light probes, follow the camera of the first view
+        let primary_camera = if self.player_views.is_empty() {
+            CameraSelection::Main
         } else {
-            sampled_target = final_color;
-            resolved_depth_texture = depth_texture;
+            CameraSelection::PlayerView(0)
         };
 
-        let (shadow_maps, shadow_camera_buffer) = match &self.shadow_map_config {
-            Some(v) => {
-                let shadow_maps = render_graph.resources.insert_texture(ManagedTextureDesc {
-                    label: "depth_texture".into(),
-                    extent: wgpu::Extent3d {
-                        width: v.resolution,
-                        height: v.resolution,
-                        depth_or_array_layers: v.max_shadows * v.max_cascades,
-                    },
-                    dimension: wgpu::TextureDimension::D2,
-                    format: wgpu::TextureFormat::Depth24Plus,
-                    mip_level_count: 1,
-                    sample_count: 1,
-                    persistent: false,
-                });
-
-                let shadow_camera_buffer = render_graph.resources.insert_buffer(BufferDesc {
-                    label: "shadow_camera_buffer".into(),
-                    size: size_of::<LightShadowCamera>() as u64
-                        * v.max_shadows as u64
-                        * v.max_cascades as u64,
-                    usage: BufferUsages::STORAGE,
-                });
-
-                (shadow_maps, shadow_camera_buffer)
-            }
-            None => {
-                let shadow_maps = render_graph.resources.insert_texture(ManagedTextureDesc {
-                    label: "depth_texture".into(),
-                    extent: wgpu::Extent3d {
-                        width: 1,
-                        height: 1,
-                        depth_or_array_layers: 1,
-                    },
-                    dimension: wgpu::TextureDimension::D2,
-                    format: wgpu::TextureFormat::Depth24Plus,
-                    mip_level_count: 1,
-                    sample_count: 1,
-                    persistent: false,
-                });
-
-                let shadow_camera_buffer = render_graph.resources.insert_buffer(BufferDesc {
-                    label: "shadow_camera_buffer".into(),
-                    size: size_of::<LightShadowCamera>() as u64,
-                    usage: BufferUsages::STORAGE,
-                });
-
-                (shadow_maps, shadow_camera_buffer)
-            }
-        };
+        let (shadow_maps, shadow_camera_buffer) = add_shadow_pass(
+            world,
+            gpu,
+            render_graph,
+            self.shadow_map_config.as_ref(),
+            &self.label,
+            primary_camera.clone(),
+            &object_manager,
+        );
 
-        if let Some(shadow_map_config) = self.shadow_map_config {
-            render_graph.add_node(ShadowMapNode::new(
-                world,
-                gpu,
-                shadow_maps,
-                shadow_camera_buffer,
-                shadow_map_config.max_shadows as _,
-                shadow_map_config.max_cascades as _,
-                render_graph.resources.shader_library().clone(),
-                object_manager.clone(),
-            ));
-        }
+        let target_format = self.hdr_format.unwrap_or(TextureFormat::Rgba8UnormSrgb);
 
         let skybox_textures = match self.skybox {
             Some(v) => {
                 const MAX_REFLECTION_LOD: u32 = 8;
                 let hdri_processor = HdriProcessor::new(gpu, v.format, MAX_REFLECTION_LOD);
 
+                // The procedural sky is smooth and regenerated as the sun moves, so smaller maps
+                // suffice
+                let (environment_size, irradiance_size, specular_size) = match &v.source {
+                    SkyboxSource::Hdri(_) => (4098, 512, 1024),
+                    SkyboxSource::Atmosphere(_) => (512, 64, 256),
+                };
+
                 let environment_map = render_graph.resources.insert_texture(ManagedTextureDesc {
                     label: "hdr_cubemap".into(),
                     extent: Extent3d {
-                        width: 4098,
-                        height: 4098,
+                        width: environment_size,
+                        height: environment_size,
                         depth_or_array_layers: 6,
                     },
-                    mip_level_count: max_mip_levels(4098, 4098),
+                    mip_level_count: max_mip_levels(environment_size, environment_size),
                     sample_count: 1,
                     dimension: TextureDimension::D2,
                     format: hdri_processor.format(),
@@ -275,8 +436,8 @@ impl PbrRenderGraphConfig {
                 let irradiance_map = render_graph.resources.insert_texture(ManagedTextureDesc {
                     label: "skybox_ir".into(),
                     extent: Extent3d {
-                        width: 512,
-                        height: 512,
+                        width: irradiance_size,
+                        height: irradiance_size,
                         depth_or_array_layers: 6,
                     },
                     mip_level_count: 1,
@@ -289,8 +450,8 @@ impl PbrRenderGraphConfig {
                 let specular_map = render_graph.resources.insert_texture(ManagedTextureDesc {
                     label: "hdr_cubemap".into(),
                     extent: Extent3d {
-                        width: 1024,
-                        height: 1024,
+                        width: specular_size,
+                        height: specular_size,
                         depth_or_array_layers: 6,
                     },
                     mip_level_count: MAX_REFLECTION_LOD,
@@ -321,31 +482,472 @@ impl PbrRenderGraphConfig {
                     integrated_brdf,
                 );
 
-                let hdri = v.hdri;
-                let assets = assets.clone();
-                render_graph.add_node(HdriProcessorNode::new(
-                    hdri_processor,
-                    stream::once(async move {
-                        match hdri.load_async(&assets).await {
-                            Ok(v) => Some(v),
-                            Err(err) => {
-                                tracing::error!(
-                                    "{:?}",
-                                    anyhow::Error::from(err).context("Failed to load hdri")
-                                );
-                                None
-                            }
-                        }
-                    })
-                    .filter_map(ready)
-                    .boxed(),
-                    skybox,
-                ));
+                match v.source {
+                    SkyboxSource::Hdri(hdri) => {
+                        let assets = assets.clone();
+                        render_graph.add_node(
+                            HdriProcessorNode::new(
+                                hdri_processor,
+                                stream::once(async move {
+                                    match hdri.load_async(&assets).await {
+                                        Ok(v) => Some(v),
+                                        Err(err) => {
+                                            tracing::error!(
+                                                "{:?}",
+                                                anyhow::Error::from(err)
+                                                    .context("Failed to load hdri")
+                                            );
+                                            None
+                                        }
+                                    }
+                                })
+                                .filter_map(ready)
+                                .boxed(),
+                                skybox,
+                            )
+                            .with_camera(primary_camera.clone()),
+                        );
+                    }
+                    SkyboxSource::Atmosphere(atmosphere) => {
+                        render_graph.add_node(
+                            AtmosphereNode::new(gpu, hdri_processor, skybox, atmosphere)
+                                .with_camera(primary_camera.clone()),
+                        );
+                    }
+                }
+
                 Some(skybox)
             }
             None => None,
         };
 
+        let light_probes = self.light_probes.map(|volume| {
+            let [sh_r, sh_g, sh_b] =
+                ["light_probes_r", "light_probes_g", "light_probes_b"].map(|label| {
+                    render_graph.resources.insert_texture(ManagedTextureDesc {
+                        label: label.into(),
+                        extent: Extent3d {
+                            width: volume.resolution.x,
+                            height: volume.resolution.y,
+                            depth_or_array_layers: volume.resolution.z,
+                        },
+                        dimension: TextureDimension::D3,
+                        format: LIGHT_PROBE_FORMAT,
+                        mip_level_count: 1,
+                        sample_count: 1,
+                        persistent: true,
+                    })
+                });
+
+            let textures = LightProbeTextures::new(sh_r, sh_g, sh_b, volume);
+
+            let renderers = std::array::from_fn(|_| {
+                (
+                    SkyboxRenderer::new(gpu),
+                    MeshRenderer::new(
+                        world,
+                        assets,
+                        gpu,
+                        forward_pass(),
+                        render_graph.resources.shader_library().clone(),
+                    ),
+                )
+            });
+
+            render_graph.add_node(LightProbeBakeNode::new(
+                gpu,
+                textures,
+                LightManager::new(gpu, shadow_maps, shadow_camera_buffer, 16),
+                object_manager.clone(),
+                skybox_textures,
+                renderers,
+            ));
+
+            textures
+        });
+
+        let shared = SharedPasses {
+            object_manager,
+            shadow_map_config: self.shadow_map_config,
+            primary_camera,
+            shadow_maps,
+            shadow_camera_buffer,
+            skybox: skybox_textures,
+            light_probes,
+        };
+
+        let main_view = PbrViewConfig {
+            anti_aliasing: self.anti_aliasing,
+            bloom: self.bloom,
+            depth_of_field: self.depth_of_field,
+            auto_exposure: self.auto_exposure,
+            camera_effects: self.camera_effects,
+            hdr_format: self.hdr_format,
+        };
+
+        let extent = Extent3d {
+            width: 0,
+            height: 0,
+            depth_or_array_layers: 1,
+        };
+
+        // Views render at the render scale, and are stretched over the destination afterwards
+        let scaled_output = (self.render_scale != 1.0).then(|| {
+            render_graph.resources.insert_texture(ManagedTextureDesc {
+                label: format!("{}.scaled_output", self.label).into(),
+                extent,
+                dimension: wgpu::TextureDimension::D2,
+                format: TextureFormat::Rgba8UnormSrgb,
+                mip_level_count: 1,
+                sample_count: 1,
+                persistent: false,
+            })
+        });
+
+        let view_output = scaled_output.unwrap_or(destination);
+
+        let mut player_views = Vec::new();
+
+        let mut screensized = if self.player_views.is_empty() {
+            let view = main_view.configure(
+                world,
+                gpu,
+                assets,
+                render_graph,
+                &shared,
+                &self.label,
+                CameraSelection::Main,
+                view_output,
+                None,
+                extent,
+            );
+
+            if let Some(world_ui) = world_ui {
+                render_graph.add_node(WorldUiRenderNode::new(
+                    gpu,
+                    world_ui,
+                    view_output,
+                    vec![WorldUiView::new(
+                        CameraSelection::Main,
+                        view.resolved_depth_texture,
+                    )],
+                ));
+            }
+
+            // working in non-hdr space
+            render_graph.add_node(GizmosRendererNode::new(
+                gpu,
+                view_output,
+                view.resolved_depth_texture,
+            ));
+
+            view.screensized
+        } else {
+            // Lets the projection of each camera follow the aspect ratio of its viewport
+            world
+                .set(
+                    engine(),
+                    split_screen_viewports(),
+                    self.player_views.clone(),
+                )
+                .unwrap();
+
+            // Each view draws into its viewport of the output after it is cleared
+            render_graph.add_node(SplitScreenNode::new(view_output));
+
+            let views = self
+                .player_views
+                .iter()
+                .enumerate()
+                .map(|(i, &viewport)| {
+                    let view = main_view.clone().configure(
+                        world,
+                        gpu,
+                        assets,
+                        render_graph,
+                        &shared,
+                        &format!("{}.player_{i}", self.label),
+                        CameraSelection::PlayerView(i),
+                        view_output,
+                        Some(viewport),
+                        extent,
+                    );
+
+                    (viewport, view)
+                })
+                .collect_vec();
+
+            if let Some(world_ui) = world_ui {
+                let world_ui_views = views
+                    .iter()
+                    .enumerate()
+                    .map(|(i, (viewport, view))| {
+                        WorldUiView::new(
+                            CameraSelection::PlayerView(i),
+                            view.resolved_depth_texture,
+                        )
+                        .with_viewport(*viewport, view.final_input)
+                    })
+                    .collect();
+
+                render_graph.add_node(WorldUiRenderNode::new(
+                    gpu,
+                    world_ui,
+                    view_output,
+                    world_ui_views,
+                ));
+            }
+
+            for (i, (viewport, view)) in views.into_iter().enumerate() {
+                render_graph.add_node(
+                    GizmosRendererNode::new(gpu, view_output, view.resolved_depth_texture)
+                        .with_camera(CameraSelection::PlayerView(i))
+                        .with_viewport(viewport, view.final_input),
+                );
+
+                player_views.push((viewport, view.screensized));
+            }
+
+            Vec::new()
+        };
+
+        for (i, camera_target) in self.camera_targets.into_iter().enumerate() {
+            let [render_target, target] = [
+                camera_target.target.render_texture(),
+                camera_target.target.texture(),
+            ]
+            .map(|v| {
+                render_graph
+                    .resources
+                    .insert_texture(TextureDesc::Imported(v.clone()))
+            });
+
+            // Targets keep their own resolution, independent of the window
+            camera_target.view.configure(
+                world,
+                gpu,
+                assets,
+                render_graph,
+                &shared,
+                &format!("{}.target_{i}", self.label),
+                CameraSelection::Target(camera_target.target.clone()),
+                render_target,
+                None,
+                camera_target.target.extent(),
+            );
+
+            // Materials sample a copy, so that the camera never draws into a texture it samples
+            render_graph.add_node(CopyTexture::new(render_target, target));
+        }
+
+        if let Some(scaled_output) = scaled_output {
+            render_graph.add_node(UpscaleNode::new(gpu, scaled_output, destination));
+            screensized.push(scaled_output);
+        }
+
+        if let Some(ui) = ui_instance {
+            render_graph.add_node(UiRenderNode::new(gpu, ui, destination));
+        }
+
+        // On top of the composited views and the ui
+        render_graph.add_node(ScreenGizmosRendererNode::new(gpu, destination));
+
+        let mut unscaled = Vec::new();
+
+        if self.picking {
+            let picker = world.get(engine(), picker()).map(|v| v.clone());
+
+            match picker {
+                Ok(picker) => {
+                    let object_ids = render_graph.resources.insert_texture(ManagedTextureDesc {
+                        label: format!("{}.object_ids", self.label).into(),
+                        extent,
+                        dimension: wgpu::TextureDimension::D2,
+                        format: OBJECT_ID_FORMAT,
+                        mip_level_count: 1,
+                        sample_count: 1,
+                        persistent: false,
+                    });
+
+                    let depth_texture = render_graph.resources.insert_texture(ManagedTextureDesc {
+                        label: format!("{}.object_ids_depth", self.label).into(),
+                        extent,
+                        dimension: wgpu::TextureDimension::D2,
+                        format: TextureFormat::Depth24Plus,
+                        mip_level_count: 1,
+                        sample_count: 1,
+                        persistent: false,
+                    });
+
+                    render_graph.add_node(PickingNode::new(
+                        world,
+                        assets,
+                        gpu,
+                        render_graph.resources.shader_library().clone(),
+                        shared.object_manager.clone(),
+                        &picker,
+                        object_ids,
+                        depth_texture,
+                    ));
+
+                    // Picks are made in window coordinates
+                    unscaled.extend([object_ids, depth_texture]);
+                }
+                Err(_) => tracing::warn!("Picking requires the picker set by the graphics layer"),
+            }
+        }
+
+        PbrRenderGraph {
+            screensized,
+            player_views,
+            unscaled,
+            render_scale: self.render_scale,
+        }
+    }
+}
+
+/// Adds the shadow maps of a camera, returning the shadow map array and the buffer of the shadow
+/// cameras.
+///
+/// Without a config, empty placeholders are returned for the light manager to bind.
+fn add_shadow_pass(
+    world: &mut World,
+    gpu: &Gpu,
+    render_graph: &mut RenderGraph,
+    config: Option<&ShadowMapConfig>,
+    label: &str,
+    camera: CameraSelection,
+    object_manager: &Handle<ObjectManager>,
+) -> (TextureHandle, BufferHandle) {
+    let (resolution, layers) = match config {
+        Some(v) => (v.resolution, v.max_shadows * v.max_cascades),
+        None => (1, 1),
+    };
+
+    let shadow_maps = render_graph.resources.insert_texture(ManagedTextureDesc {
+        label: format!("{label}.shadow_maps").into(),
+        extent: wgpu::Extent3d {
+            width: resolution,
+            height: resolution,
+            depth_or_array_layers: layers,
+        },
+        dimension: wgpu::TextureDimension::D2,
+        format: wgpu::TextureFormat::Depth24Plus,
+        mip_level_count: 1,
+        sample_count: 1,
+        persistent: false,
+    });
+
+    let shadow_camera_buffer = render_graph.resources.insert_buffer(BufferDesc {
+        label: format!("{label}.shadow_camera_buffer").into(),
+        size: size_of::<LightShadowCamera>() as u64 * layers as u64,
+        usage: BufferUsages::STORAGE,
+    });
+
+    if let Some(config) = config {
+        render_graph.add_node(
+            ShadowMapNode::new(
+                world,
+                gpu,
+                shadow_maps,
+                shadow_camera_buffer,
+                config.max_shadows as _,
+                config.max_cascades as _,
+                render_graph.resources.shader_library().clone(),
+                object_manager.clone(),
+            )
+            .with_camera(camera),
+        );
+    }
+
+    (shadow_maps, shadow_camera_buffer)
+}
+
+impl PbrViewConfig {
+    /// Adds the camera pass and post processing of a single view rendering into `destination`
+    #[allow(clippy::too_many_arguments)]
+    fn configure(
+        self,
+        world: &mut World,
+        gpu: &Gpu,
+        assets: &AssetCache,
+        render_graph: &mut RenderGraph,
+        shared: &SharedPasses,
+        label: &str,
+        camera: CameraSelection,
+        destination: TextureHandle,
+        viewport: Option<Viewport>,
+        extent: Extent3d,
+    ) -> PbrView {
+        let target_format = self.hdr_format.unwrap_or(TextureFormat::Rgba8UnormSrgb);
+
+        // TODO: extend with generic effects
+        let needs_tonemap = self.hdr_format.is_some()
+            || self.bloom.is_some()
+            || self.depth_of_field.is_some()
+            || self.auto_exposure.is_some();
+        // Views sharing the destination draw into their viewport in the final pass
+        let needs_indirection_target = needs_tonemap
+            || self.anti_aliasing.is_post_process()
+            || self.camera_effects.is_some()
+            || viewport.is_some();
+
+        tracing::info!(label, ?target_format);
+        let final_color = if needs_indirection_target {
+            render_graph.resources.insert_texture(ManagedTextureDesc {
+                label: format!("{label}.final_color").into(),
+                extent,
+                dimension: wgpu::TextureDimension::D2,
+                format: target_format,
+                mip_level_count: 1,
+                sample_count: 1,
+                persistent: false,
+            })
+        } else {
+            destination
+        };
+
+        let msaa = self.anti_aliasing.msaa();
+        let sample_count = msaa.map(|v| v.sample_count).unwrap_or(1);
+
+        let depth_texture = render_graph.resources.insert_texture(ManagedTextureDesc {
+            label: format!("{label}.depth_texture").into(),
+            extent,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Depth24Plus,
+            mip_level_count: 1,
+            sample_count,
+            persistent: false,
+        });
+
+        let resolved_depth_texture;
+        let sampled_target;
+
+        if msaa.is_some() {
+            sampled_target = render_graph.resources.insert_texture(ManagedTextureDesc {
+                label: format!("{label}.hrd_output").into(),
+                extent,
+                dimension: wgpu::TextureDimension::D2,
+                format: target_format,
+                mip_level_count: 1,
+                sample_count,
+                persistent: false,
+            });
+
+            resolved_depth_texture = render_graph.resources.insert_texture(ManagedTextureDesc {
+                label: format!("{label}.resolved_depth").into(),
+                extent,
+                dimension: wgpu::TextureDimension::D2,
+                format: wgpu::TextureFormat::R32Float,
+                mip_level_count: 1,
+                sample_count: 1,
+                persistent: false,
+            })
+        } else {
+            sampled_target = final_color;
+            resolved_depth_texture = depth_texture;
+        };
+
         let camera_renderers = (
             SkyboxRenderer::new(gpu),
             MeshRenderer::new(
@@ -354,27 +956,53 @@ impl PbrRenderGraphConfig {
                 gpu,
                 forward_pass(),
                 render_graph.resources.shader_library().clone(),
-            ),
+            )
+            .with_placeholder(PbrMaterialData::new())
+            .with_debug_views(true),
+            SpriteRenderer::new(gpu),
+            // Drawn after opaque geometry so that blending sees the final depth and color
             MeshRenderer::new(
                 world,
                 assets,
                 gpu,
                 transparent_pass(),
                 render_graph.resources.shader_library().clone(),
-            ),
+            )
+            .with_draw_order(DrawOrder::BackToFront)
+            .with_placeholder(PbrMaterialData::new())
+            .with_debug_views(true),
         );
 
+        // Each camera has shadows fitted to its own view
+        let (shadow_maps, shadow_camera_buffer) = if camera == shared.primary_camera {
+            (shared.shadow_maps, shared.shadow_camera_buffer)
+        } else {
+            add_shadow_pass(
+                world,
+                gpu,
+                render_graph,
+                shared.shadow_map_config.as_ref(),
+                label,
+                camera.clone(),
+                &shared.object_manager,
+            )
+        };
+
         let light_manager = LightManager::new(gpu, shadow_maps, shadow_camera_buffer, 16);
 
-        render_graph.add_node(CameraNode::new(
-            gpu,
-            depth_texture,
-            sampled_target,
-            camera_renderers,
-            light_manager,
-            object_manager,
-            skybox_textures,
-        ));
+        render_graph.add_node(
+            CameraNode::new(
+                gpu,
+                depth_texture,
+                sampled_target,
+                camera_renderers,
+                light_manager,
+                shared.object_manager.clone(),
+                shared.skybox,
+                shared.light_probes,
+            )
+            .with_camera(camera.clone()),
+        );
 
         let mut last_output = sampled_target;
 
@@ -384,12 +1012,12 @@ impl PbrRenderGraphConfig {
             screensized.push(final_color);
         }
 
-        if self.msaa.is_some() {
+        if msaa.is_some() {
             screensized.push(sampled_target);
             screensized.push(resolved_depth_texture);
         };
 
-        if self.msaa.is_some() {
+        if msaa.is_some() {
             render_graph.add_node(MsaaResolve::new(sampled_target, final_color));
             render_graph.add_node(MsaaDepthResolve::new(
                 gpu,
@@ -399,9 +1027,43 @@ impl PbrRenderGraphConfig {
             last_output = final_color;
         }
 
+        if let Some(depth_of_field) = self.depth_of_field {
+            let [coc, dof_result] = [
+                ("dof_coc", DOF_COC_FORMAT),
+                ("dof_result", TextureFormat::Rgba16Float),
+            ]
+            .map(|(name, format)| {
+                render_graph.resources.insert_texture(ManagedTextureDesc {
+                    label: format!("{label}.{name}").into(),
+                    extent,
+                    dimension: wgpu::TextureDimension::D2,
+                    format,
+                    mip_level_count: 1,
+                    sample_count: 1,
+                    persistent: false,
+                })
+            });
+
+            render_graph.add_node(
+                DepthOfFieldNode::new(
+                    gpu,
+                    last_output,
+                    resolved_depth_texture,
+                    coc,
+                    dof_result,
+                    depth_of_field,
+                )
+                .with_camera(camera.clone()),
+            );
+
+            last_output = dof_result;
+
+            screensized.extend([coc, dof_result]);
+        }
+
         if let Some(bloom) = self.bloom {
             let bloom_result = render_graph.resources.insert_texture(ManagedTextureDesc {
-                label: "bloom_result".into(),
+                label: format!("{label}.bloom_result").into(),
                 extent,
                 dimension: wgpu::TextureDimension::D2,
                 format: TextureFormat::Rgba16Float,
@@ -423,41 +1085,229 @@ impl PbrRenderGraphConfig {
             screensized.push(bloom_result);
         }
 
+        // Camera effects are applied last whenever there is an intermediate target, so that the
+        // effects of the camera work without configuring defaults
+        let camera_effects =
+            needs_indirection_target.then(|| self.camera_effects.unwrap_or_default());
+
+        // The previous passes write to an intermediate target for the effects
+        let ldr_destination = if camera_effects.is_some()
+            && (needs_tonemap || self.anti_aliasing.is_post_process())
+        {
+            let effects_input = render_graph.resources.insert_texture(ManagedTextureDesc {
+                label: format!("{label}.effects_input").into(),
+                extent,
+                dimension: wgpu::TextureDimension::D2,
+                format: TextureFormat::Rgba8UnormSrgb,
+                mip_level_count: 1,
+                sample_count: 1,
+                persistent: false,
+            });
+
+            screensized.push(effects_input);
+            effects_input
+        } else {
+            destination
+        };
+
         // Needs resolve to tonemap and write to non-hdr output
-        if needs_indirection_target {
-            render_graph.add_node(TonemapNode::new(gpu, last_output, destination));
+        if needs_tonemap {
+            let tonemap_output = if self.anti_aliasing.is_post_process() {
+                let ldr_color = render_graph.resources.insert_texture(ManagedTextureDesc {
+                    label: format!("{label}.ldr_color").into(),
+                    extent,
+                    dimension: wgpu::TextureDimension::D2,
+                    format: TextureFormat::Rgba8UnormSrgb,
+                    mip_level_count: 1,
+                    sample_count: 1,
+                    persistent: false,
+                });
+
+                screensized.push(ldr_color);
+                ldr_color
+            } else {
+                ldr_destination
+            };
+
+            let mut tonemap =
+                TonemapNode::new(gpu, last_output, tonemap_output).with_camera(camera.clone());
+
+            if let Some(auto_exposure) = self.auto_exposure {
+                let exposure = render_graph
+                    .resources
+                    .insert_history_texture(ManagedTextureDesc {
+                        label: format!("{label}.exposure").into(),
+                        extent: Extent3d {
+                            width: 1,
+                            height: 1,
+                            depth_or_array_layers: 1,
+                        },
+                        dimension: wgpu::TextureDimension::D2,
+                        format: EXPOSURE_FORMAT,
+                        mip_level_count: 1,
+                        sample_count: 1,
+                        persistent: false,
+                    });
+
+                render_graph.add_node(
+                    AutoExposureNode::new(gpu, last_output, exposure, auto_exposure)
+                        .with_camera(camera.clone()),
+                );
+
+                tonemap = tonemap.with_exposure(exposure);
+            }
+
+            render_graph.add_node(tonemap);
+            last_output = tonemap_output;
         }
 
-        // working in non-hdr space
-        render_graph.add_node(GizmosRendererNode::new(
-            gpu,
-            destination,
-            resolved_depth_texture,
-        ));
+        match self.anti_aliasing {
+            AntiAliasing::Fxaa => {
+                render_graph.add_node(FxaaNode::new(gpu, last_output, ldr_destination));
+                last_output = ldr_destination;
+            }
+            AntiAliasing::Smaa => {
+                let [edges, weights] = [
+                    ("smaa_edges", SMAA_EDGES_FORMAT),
+                    ("smaa_weights", SMAA_WEIGHTS_FORMAT),
+                ]
+                .map(|(name, format)| {
+                    render_graph.resources.insert_texture(ManagedTextureDesc {
+                        label: format!("{label}.{name}").into(),
+                        extent,
+                        dimension: wgpu::TextureDimension::D2,
+                        format,
+                        mip_level_count: 1,
+                        sample_count: 1,
+                        persistent: false,
+                    })
+                });
 
-        if let Some(ui) = ui_instance {
-            render_graph.add_node(UiRenderNode::new(gpu, ui, destination));
+                screensized.extend([edges, weights]);
+
+                render_graph.add_node(SmaaNode::new(
+                    gpu,
+                    last_output,
+                    edges,
+                    weights,
+                    ldr_destination,
+                ));
+                last_output = ldr_destination;
+            }
+            AntiAliasing::None | AntiAliasing::Msaa(_) => {}
+        }
+
+        let final_input = last_output;
+        if let Some(camera_effects) = camera_effects {
+            let mut node = CameraEffectsNode::new(gpu, last_output, destination, camera_effects)
+                .with_camera(camera);
+
+            if let Some(viewport) = viewport {
+                node = node.with_viewport(viewport);
+            }
+
+            render_graph.add_node(node);
         }
 
-        PbrRenderGraph { screensized }
+        PbrView {
+            screensized,
+            resolved_depth_texture,
+            final_input,
+        }
     }
 }
 
 impl PbrRenderGraph {
-    pub fn set_size(&self, render_graph: &mut RenderGraph, size: PhysicalSize<u32>) {
-        let new_extent = Extent3d {
-            width: size.width,
-            height: size.height,
-            depth_or_array_layers: 1,
-        };
+    pub fn set_size(&self, render_graph: &mut RenderGraph, size: PhysicalExtent) {
+        let render_size = scaled_size(size, self.render_scale);
 
-        for &handle in self.screensized() {
+        let mut set_extent = |handle, extent| {
             render_graph
                 .resources
                 .get_texture_mut(handle)
                 .as_managed_mut()
                 .unwrap()
-                .extent = new_extent;
+                .extent = extent;
+        };
+
+        let to_extent = |size: PhysicalExtent| Extent3d {
+            width: size.width,
+            height: size.height,
+            depth_or_array_layers: 1,
+        };
+
+        for &handle in self.screensized() {
+            set_extent(handle, to_extent(render_size));
+        }
+
+        for &handle in &self.unscaled {
+            set_extent(handle, to_extent(size));
         }
+
+        for (viewport, textures) in &self.player_views {
+            let extent = viewport.extent(render_size);
+
+            for &handle in textures {
+                set_extent(handle, extent);
+            }
+        }
+    }
+}
+
+/// Returns the resolution to render at for a destination of `size`
+fn scaled_size(size: PhysicalExtent, render_scale: f32) -> PhysicalExtent {
+    let scale = |v: u32| ((v as f32 * render_scale).round() as u32).max(1);
+    PhysicalExtent::new(scale(size.width), scale(size.height))
+}
+
+#[cfg(test)]
+mod tests {
+    use ivy_assets::fs::AssetPath;
+
+    use super::*;
+
+    #[test]
+    fn skybox_source() {
+        let hdri: Box<dyn DynAsyncAssetDesc<DynamicImage>> =
+            Box::new(AssetPath::<DynamicImage>::new("hdris/sky.exr"));
+
+        let config = SkyboxConfig::new(hdri, TextureFormat::Rgba16Float);
+        assert!(matches!(config.source, SkyboxSource::Hdri(_)));
+        #[allow(deprecated)]
+        let has_hdri = config.hdri_source().is_some();
+        assert!(has_hdri);
+
+        let config = SkyboxConfig::new(Atmosphere::default(), TextureFormat::Rgba16Float);
+        assert!(matches!(config.source, SkyboxSource::Atmosphere(_)));
+        #[allow(deprecated)]
+        let has_hdri = config.hdri_source().is_some();
+        assert!(!has_hdri);
+
+        let config = SkyboxConfig::hdri(
+            AssetPath::<DynamicImage>::new("hdris/sky.exr"),
+            TextureFormat::Rgba16Float,
+        );
+        assert!(matches!(config.source, SkyboxSource::Hdri(_)));
+    }
+
+    #[test]
+    fn msaa_migration() {
+        assert!(matches!(
+            AntiAliasing::from(Some(MsaaConfig::default())),
+            AntiAliasing::Msaa(MsaaConfig { sample_count: 4 })
+        ));
+        assert!(matches!(AntiAliasing::from(None), AntiAliasing::None));
+    }
+
+    #[test]
+    fn render_scale() {
+        let size = PhysicalExtent::new(1920, 1080);
+
+        assert_eq!(scaled_size(size, 1.0), size);
+        assert_eq!(scaled_size(size, 0.5), PhysicalExtent::new(960, 540));
+        assert_eq!(
+            scaled_size(PhysicalExtent::new(1, 1), 0.25),
+            PhysicalExtent::new(1, 1)
+        );
     }
 }
@@ -0,0 +1,582 @@
+use ivy_wgpu::{
+    rendergraph::{Dependency, Node, NodeExecutionContext, ResourceHandle, TextureHandle},
+    types::{
+        shader::{ShaderDesc, TargetDesc},
+        BindGroupBuilder, BindGroupLayoutBuilder, RenderShader,
+    },
+    Gpu,
+};
+use wgpu::{
+    util::{DeviceExt, TextureDataOrder},
+    AddressMode, BindGroup, BindGroupLayout, Color, CommandEncoder, Extent3d, FilterMode,
+    Operations, RenderPassColorAttachment, Sampler, SamplerDescriptor, ShaderModule,
+    ShaderModuleDescriptor, ShaderSource, ShaderStages, StoreOp, Texture, TextureDescriptor,
+    TextureDimension, TextureFormat, TextureUsages, TextureView,
+};
+
+/// Format of the intermediate edges texture
+pub const SMAA_EDGES_FORMAT: TextureFormat = TextureFormat::Rg8Unorm;
+/// Format of the intermediate blend weights texture
+pub const SMAA_WEIGHTS_FORMAT: TextureFormat = TextureFormat::Rgba8Unorm;
+
+/// Number of pixels along each axis of a single edge pattern in the area texture.
+///
+/// Must match `AREA_MAX_DISTANCE` in `smaa.wgsl`.
+const AREA_MAX_DISTANCE: usize = 16;
+/// The area texture holds 5x5 edge patterns, indexed by the rounded edge values at each end
+const AREA_TEXTURE_SIZE: usize = AREA_MAX_DISTANCE * 5;
+/// Distance at which the coverage of U shapes no longer is smoothed
+const AREA_SMOOTH_MAX_DISTANCE: f32 = 32.0;
+
+/// Width of the search texture before it is cropped
+const SEARCH_FULL_WIDTH: usize = 66;
+/// Size of the search texture, cropped to the rows which can be fetched
+const SEARCH_TEXTURE_SIZE: (usize, usize) = (64, 16);
+
+/// Edge pairs at both ends of an orthogonal pattern, as rounded edge values of `0`, `0.25`,
+/// `0.75` or `1` scaled by 4.
+const ORTHO_EDGES: [(usize, usize); 16] = [
+    (0, 0),
+    (3, 0),
+    (0, 3),
+    (3, 3),
+    (1, 0),
+    (4, 0),
+    (1, 3),
+    (4, 3),
+    (0, 1),
+    (3, 1),
+    (0, 4),
+    (3, 4),
+    (1, 1),
+    (4, 1),
+    (1, 4),
+    (4, 4),
+];
+
+/// Coverage of the pixel `[x, x + 1]` under the line from `p1` to `p2`.
+///
+/// Returns the area on the negative and positive side of the edge respectively.
+fn line_area(p1: (f32, f32), p2: (f32, f32), x: f32) -> [f32; 2] {
+    let d = (p2.0 - p1.0, p2.1 - p1.1);
+    let x1 = x;
+    let x2 = x + 1.0;
+    let y1 = p1.1 + d.1 * (x1 - p1.0) / d.0;
+    let y2 = p1.1 + d.1 * (x2 - p1.0) / d.0;
+
+    let inside = (x1 >= p1.0 && x1 < p2.0) || (x2 > p1.0 && x2 <= p2.0);
+    if !inside {
+        return [0.0, 0.0];
+    }
+
+    let is_trapezoid = y1.signum() == y2.signum() || y1.abs() < 1e-4 || y2.abs() < 1e-4;
+
+    if is_trapezoid {
+        let a = (y1 + y2) / 2.0;
+        if a < 0.0 {
+            [a.abs(), 0.0]
+        } else {
+            [0.0, a.abs()]
+        }
+    } else {
+        // The line crosses the edge inside the pixel, forming two triangles
+        let x = -p1.1 * d.0 / d.1 + p1.0;
+        let a1 = if x > p1.0 { y1 * x.fract() / 2.0 } else { 0.0 };
+        let a2 = if x < p2.0 {
+            y2 * (1.0 - x.fract()) / 2.0
+        } else {
+            0.0
+        };
+
+        let a = if a1.abs() > a2.abs() { a1 } else { -a2 };
+        if a < 0.0 {
+            [a1.abs(), a2.abs()]
+        } else {
+            [a2.abs(), a1.abs()]
+        }
+    }
+}
+
+/// Blends U shapes towards a rounder silhouette when the edge is short
+fn smooth_area(d: f32, a1: [f32; 2], a2: [f32; 2]) -> [f32; 2] {
+    let p = (d / AREA_SMOOTH_MAX_DISTANCE).clamp(0.0, 1.0);
+    let smooth = |a: f32| {
+        let b = (a * 2.0).sqrt() * 0.5;
+        b + (a - b) * p
+    };
+
+    [smooth(a1[0]) + smooth(a2[0]), smooth(a1[1]) + smooth(a2[1])]
+}
+
+/// Coverage of a pixel `left` pixels from the start and `right` pixels from the end of an
+/// orthogonal edge with the given crossing edge `pattern`.
+fn ortho_area(pattern: usize, left: f32, right: f32) -> [f32; 2] {
+    let d = left + right + 1.0;
+    let o1 = 0.5;
+    let o2 = -0.5;
+
+    match pattern {
+        1 if left <= right => line_area((0.0, o2), (d / 2.0, 0.0), left),
+        2 if left >= right => line_area((d / 2.0, 0.0), (d, o2), left),
+        3 => smooth_area(
+            d,
+            line_area((0.0, o2), (d / 2.0, 0.0), left),
+            line_area((d / 2.0, 0.0), (d, o2), left),
+        ),
+        4 if left <= right => line_area((0.0, o1), (d / 2.0, 0.0), left),
+        6 | 7 | 14 => line_area((0.0, o1), (d, o2), left),
+        8 if left >= right => line_area((d / 2.0, 0.0), (d, o1), left),
+        9 | 11 | 13 => line_area((0.0, o2), (d, o1), left),
+        12 => smooth_area(
+            d,
+            line_area((0.0, o1), (d / 2.0, 0.0), left),
+            line_area((d / 2.0, 0.0), (d, o1), left),
+        ),
+        _ => [0.0, 0.0],
+    }
+}
+
+/// Generates the two channel area texture used to look up the blending weights of a pixel from
+/// the distances to, and the crossing edges at, the ends of the edge it lies on.
+///
+/// Distances are stored squared, and looked up by their square root, to fit longer edges.
+fn area_texture_data() -> Vec<u8> {
+    let mut data = vec![0; AREA_TEXTURE_SIZE * AREA_TEXTURE_SIZE * 2];
+
+    for (pattern, &(e1, e2)) in ORTHO_EDGES.iter().enumerate() {
+        for left in 0..AREA_MAX_DISTANCE {
+            for right in 0..AREA_MAX_DISTANCE {
+                let area = ortho_area(pattern, (left * left) as f32, (right * right) as f32);
+
+                let x = AREA_MAX_DISTANCE * e1 + left;
+                let y = AREA_MAX_DISTANCE * e2 + right;
+                let i = (y * AREA_TEXTURE_SIZE + x) * 2;
+
+                data[i] = (area[0] * 255.0).round() as u8;
+                data[i + 1] = (area[1] * 255.0).round() as u8;
+            }
+        }
+    }
+
+    data
+}
+
+/// Edges fetched with bilinear filtering from four pixels, keyed by the filtered value in
+/// 1/32 units.
+fn search_edges() -> [Option<[bool; 4]>; 33] {
+    let mut edges = [None; 33];
+    for i in 0..16 {
+        let e = [i & 8 != 0, i & 4 != 0, i & 2 != 0, i & 1 != 0];
+        let key = e
+            .iter()
+            .zip([1, 3, 7, 21])
+            .map(|(&e, w)| e as usize * w)
+            .sum::<usize>();
+
+        edges[key] = Some(e);
+    }
+
+    edges
+}
+
+/// Pixels to step back when a search towards the left stops on the given edges
+fn search_delta_left(left: [bool; 4], top: [bool; 4]) -> u8 {
+    let mut d = 0;
+    if top[3] {
+        d += 1;
+    }
+
+    if d == 1 && top[2] && !left[1] && !left[3] {
+        d += 1;
+    }
+
+    d
+}
+
+/// Pixels to step back when a search towards the right stops on the given edges
+fn search_delta_right(left: [bool; 4], top: [bool; 4]) -> u8 {
+    let mut d = 0;
+    if top[3] && !left[1] && !left[3] {
+        d += 1;
+    }
+
+    if d == 1 && top[2] && !left[0] && !left[2] {
+        d += 1;
+    }
+
+    d
+}
+
+/// Generates the search texture used to find the exact end of an edge from a single bilinear
+/// fetch of two pixels, for searches towards the left in the first and towards the right in the
+/// second half.
+fn search_texture_data() -> Vec<u8> {
+    let edges = search_edges();
+    let mut image = vec![0; SEARCH_FULL_WIDTH * 33];
+
+    for (y, top) in edges.iter().enumerate() {
+        for (x, left) in edges.iter().enumerate() {
+            if let (Some(left), Some(top)) = (left, top) {
+                image[y * SEARCH_FULL_WIDTH + x] = 127 * search_delta_left(*left, *top);
+                image[y * SEARCH_FULL_WIDTH + x + 33] = 127 * search_delta_right(*left, *top);
+            }
+        }
+    }
+
+    // Only the bottom rows can be fetched, and are stored flipped
+    let (width, height) = SEARCH_TEXTURE_SIZE;
+    (0..height)
+        .flat_map(|row| {
+            let start = (32 - row) * SEARCH_FULL_WIDTH;
+            image[start..start + width].iter().copied()
+        })
+        .collect()
+}
+
+fn create_lut(
+    gpu: &Gpu,
+    label: &str,
+    (width, height): (usize, usize),
+    format: TextureFormat,
+    data: &[u8],
+) -> Texture {
+    gpu.device.create_texture_with_data(
+        &gpu.queue,
+        &TextureDescriptor {
+            label: Some(label),
+            size: Extent3d {
+                width: width as u32,
+                height: height as u32,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: TextureDimension::D2,
+            format,
+            usage: TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        },
+        TextureDataOrder::LayerMajor,
+        data,
+    )
+}
+
+fn create_sampler(gpu: &Gpu, filter: FilterMode) -> Sampler {
+    gpu.device.create_sampler(&SamplerDescriptor {
+        address_mode_u: AddressMode::ClampToEdge,
+        address_mode_v: AddressMode::ClampToEdge,
+        address_mode_w: AddressMode::ClampToEdge,
+        mag_filter: filter,
+        min_filter: filter,
+        mipmap_filter: FilterMode::Nearest,
+        ..Default::default()
+    })
+}
+
+struct BindGroups {
+    edges: BindGroup,
+    weights: BindGroup,
+    blend: BindGroup,
+}
+
+/// Subpixel morphological anti-aliasing.
+///
+/// Detects edges in a tonemapped image and blends along them according to their shape. Sharper
+/// than [`FxaaNode`](crate::fxaa::FxaaNode) at the cost of two extra passes.
+///
+/// The `edges` and `weights` textures must be the size of the input, with the formats
+/// [`SMAA_EDGES_FORMAT`] and [`SMAA_WEIGHTS_FORMAT`] respectively.
+pub struct SmaaNode {
+    input: TextureHandle,
+    edges: TextureHandle,
+    weights: TextureHandle,
+    output: TextureHandle,
+
+    area_view: TextureView,
+    search_view: TextureView,
+    linear_sampler: Sampler,
+    point_sampler: Sampler,
+
+    edges_layout: BindGroupLayout,
+    weights_layout: BindGroupLayout,
+    blend_layout: BindGroupLayout,
+    bind_groups: Option<BindGroups>,
+
+    module: ShaderModule,
+    edges_shader: RenderShader,
+    weights_shader: RenderShader,
+    blend_shader: Option<RenderShader>,
+}
+
+fn create_shader(
+    gpu: &Gpu,
+    label: &str,
+    module: &ShaderModule,
+    entry_point: &str,
+    format: TextureFormat,
+    layout: &BindGroupLayout,
+) -> RenderShader {
+    let target = TargetDesc {
+        formats: &[format],
+        depth_format: None,
+        sample_count: 1,
+    };
+
+    let mut desc = ShaderDesc::new(label, module, &target)
+        .with_bind_group_layouts(&[layout])
+        .with_blend(None);
+
+    desc.fragment_entry_point = entry_point;
+
+    RenderShader::new(gpu, &desc)
+}
+
+fn fullscreen_pass(
+    encoder: &mut CommandEncoder,
+    label: &str,
+    view: &TextureView,
+    shader: &RenderShader,
+    bind_group: &BindGroup,
+) {
+    let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+        label: Some(label),
+        color_attachments: &[Some(RenderPassColorAttachment {
+            view,
+            resolve_target: None,
+            ops: Operations {
+                load: wgpu::LoadOp::Clear(Color::TRANSPARENT),
+                store: StoreOp::Store,
+            },
+        })],
+        depth_stencil_attachment: None,
+        ..Default::default()
+    });
+
+    render_pass.set_pipeline(shader.pipeline());
+    render_pass.set_bind_group(0, bind_group, &[]);
+
+    render_pass.draw(0..3, 0..1);
+}
+
+impl SmaaNode {
+    pub fn new(
+        gpu: &Gpu,
+        input: TextureHandle,
+        edges: TextureHandle,
+        weights: TextureHandle,
+        output: TextureHandle,
+    ) -> Self {
+        let edges_layout = BindGroupLayoutBuilder::new("Smaa.edges")
+            .bind_texture(ShaderStages::FRAGMENT)
+            .build(gpu);
+
+        let weights_layout = BindGroupLayoutBuilder::new("Smaa.weights")
+            .bind_texture(ShaderStages::FRAGMENT)
+            .bind_texture(ShaderStages::FRAGMENT)
+            .bind_texture(ShaderStages::FRAGMENT)
+            .bind_sampler(ShaderStages::FRAGMENT)
+            .bind_sampler(ShaderStages::FRAGMENT)
+            .build(gpu);
+
+        let blend_layout = BindGroupLayoutBuilder::new("Smaa.blend")
+            .bind_texture(ShaderStages::FRAGMENT)
+            .bind_texture(ShaderStages::FRAGMENT)
+            .bind_sampler(ShaderStages::FRAGMENT)
+            .build(gpu);
+
+        let area = create_lut(
+            gpu,
+            "Smaa.area",
+            (AREA_TEXTURE_SIZE, AREA_TEXTURE_SIZE),
+            TextureFormat::Rg8Unorm,
+            &area_texture_data(),
+        );
+
+        let search = create_lut(
+            gpu,
+            "Smaa.search",
+            SEARCH_TEXTURE_SIZE,
+            TextureFormat::R8Unorm,
+            &search_texture_data(),
+        );
+
+        let module = gpu.device.create_shader_module(ShaderModuleDescriptor {
+            label: Some("Smaa"),
+            source: ShaderSource::Wgsl(include_str!("../shaders/smaa.wgsl").into()),
+        });
+
+        let edges_shader = create_shader(
+            gpu,
+            "Smaa.edges",
+            &module,
+            "fs_edges",
+            SMAA_EDGES_FORMAT,
+            &edges_layout,
+        );
+
+        let weights_shader = create_shader(
+            gpu,
+            "Smaa.weights",
+            &module,
+            "fs_weights",
+            SMAA_WEIGHTS_FORMAT,
+            &weights_layout,
+        );
+
+        Self {
+            input,
+            edges,
+            weights,
+            output,
+            area_view: area.create_view(&Default::default()),
+            search_view: search.create_view(&Default::default()),
+            linear_sampler: create_sampler(gpu, FilterMode::Linear),
+            point_sampler: create_sampler(gpu, FilterMode::Nearest),
+            edges_layout,
+            weights_layout,
+            blend_layout,
+            bind_groups: None,
+            module,
+            edges_shader,
+            weights_shader,
+            blend_shader: None,
+        }
+    }
+}
+
+impl Node for SmaaNode {
+    fn draw(&mut self, ctx: NodeExecutionContext) -> anyhow::Result<()> {
+        let input = ctx.get_texture(self.input);
+        let edges = ctx.get_texture(self.edges);
+        let weights = ctx.get_texture(self.weights);
+        let output = ctx.get_texture(self.output);
+
+        let input_view = input.create_view(&Default::default());
+        let edges_view = edges.create_view(&Default::default());
+        let weights_view = weights.create_view(&Default::default());
+        let output_view = output.create_view(&Default::default());
+
+        let bind_groups = self.bind_groups.get_or_insert_with(|| BindGroups {
+            edges: BindGroupBuilder::new("Smaa.edges")
+                .bind_texture(&input_view)
+                .build(ctx.gpu, &self.edges_layout),
+            weights: BindGroupBuilder::new("Smaa.weights")
+                .bind_texture(&edges_view)
+                .bind_texture(&self.area_view)
+                .bind_texture(&self.search_view)
+                .bind_sampler(&self.linear_sampler)
+                .bind_sampler(&self.point_sampler)
+                .build(ctx.gpu, &self.weights_layout),
+            blend: BindGroupBuilder::new("Smaa.blend")
+                .bind_texture(&input_view)
+                .bind_texture(&weights_view)
+                .bind_sampler(&self.linear_sampler)
+                .build(ctx.gpu, &self.blend_layout),
+        });
+
+        let blend_shader = self.blend_shader.get_or_insert_with(|| {
+            create_shader(
+                ctx.gpu,
+                "Smaa.blend",
+                &self.module,
+                "fs_blend",
+                output.format(),
+                &self.blend_layout,
+            )
+        });
+
+        fullscreen_pass(
+            ctx.encoder,
+            "Smaa.edges",
+            &edges_view,
+            &self.edges_shader,
+            &bind_groups.edges,
+        );
+
+        fullscreen_pass(
+            ctx.encoder,
+            "Smaa.weights",
+            &weights_view,
+            &self.weights_shader,
+            &bind_groups.weights,
+        );
+
+        fullscreen_pass(
+            ctx.encoder,
+            "Smaa.blend",
+            &output_view,
+            blend_shader,
+            &bind_groups.blend,
+        );
+
+        Ok(())
+    }
+
+    fn read_dependencies(&self) -> Vec<Dependency> {
+        vec![Dependency::texture(
+            self.input,
+            TextureUsages::TEXTURE_BINDING,
+        )]
+    }
+
+    fn write_dependencies(&self) -> Vec<Dependency> {
+        // The intermediate textures are written and then sampled by the following pass
+        let intermediate = TextureUsages::RENDER_ATTACHMENT | TextureUsages::TEXTURE_BINDING;
+
+        vec![
+            Dependency::texture(self.edges, intermediate),
+            Dependency::texture(self.weights, intermediate),
+            Dependency::texture(self.output, TextureUsages::RENDER_ATTACHMENT),
+        ]
+    }
+
+    fn on_resource_changed(&mut self, _resource: ResourceHandle) {
+        self.bind_groups = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn area_texture() {
+        let data = area_texture_data();
+        assert_eq!(data.len(), AREA_TEXTURE_SIZE * AREA_TEXTURE_SIZE * 2);
+
+        // Edges without crossing edges at either end do not blend
+        for y in 0..AREA_MAX_DISTANCE {
+            for x in 0..AREA_MAX_DISTANCE {
+                let i = (y * AREA_TEXTURE_SIZE + x) * 2;
+                assert_eq!(&data[i..i + 2], &[0, 0]);
+            }
+        }
+
+        assert!(data.iter().any(|&v| v != 0));
+    }
+
+    #[test]
+    fn ortho_area_coverage() {
+        assert_eq!(ortho_area(0, 4.0, 9.0), [0.0, 0.0]);
+        // An L shape only covers the half of the edge nearest its crossing edge
+        assert_eq!(ortho_area(1, 9.0, 4.0), [0.0, 0.0]);
+        assert_eq!(ortho_area(2, 4.0, 9.0), [0.0, 0.0]);
+
+        let [a, b] = ortho_area(1, 0.0, 16.0);
+        assert!(a > 0.0 && b == 0.0);
+    }
+
+    #[test]
+    fn search_texture() {
+        let (width, height) = SEARCH_TEXTURE_SIZE;
+        let data = search_texture_data();
+        assert_eq!(data.len(), width * height);
+
+        // The search stopped two pixels past the end of a continuous edge
+        assert_eq!(data[0], 254);
+        assert_eq!(data[33], 254);
+
+        // A crossing edge ends the search one pixel early towards the left
+        assert_eq!(data[32], 127);
+    }
+}
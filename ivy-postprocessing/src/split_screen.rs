@@ -0,0 +1,55 @@
+use ivy_wgpu::rendergraph::{
+    Dependency, Node, NodeExecutionContext, ResourceHandle, TextureHandle,
+};
+use wgpu::{Color, Operations, RenderPassColorAttachment, StoreOp, TextureUsages};
+
+/// Clears the target shared by the views of a split screen, e.g; for local multiplayer.
+///
+/// Each view draws its final pass into its own viewport of the target afterwards, which leaves
+/// the regions not covered by any view black.
+pub struct SplitScreenNode {
+    output: TextureHandle,
+}
+
+impl SplitScreenNode {
+    pub fn new(output: TextureHandle) -> Self {
+        Self { output }
+    }
+}
+
+impl Node for SplitScreenNode {
+    fn draw(&mut self, ctx: NodeExecutionContext) -> anyhow::Result<()> {
+        let output_view = ctx
+            .get_texture(self.output)
+            .create_view(&Default::default());
+
+        ctx.encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: "SplitScreen".into(),
+            color_attachments: &[Some(RenderPassColorAttachment {
+                view: &output_view,
+                resolve_target: None,
+                ops: Operations {
+                    load: wgpu::LoadOp::Clear(Color::BLACK),
+                    store: StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            ..Default::default()
+        });
+
+        Ok(())
+    }
+
+    fn read_dependencies(&self) -> Vec<Dependency> {
+        vec![]
+    }
+
+    fn write_dependencies(&self) -> Vec<Dependency> {
+        vec![Dependency::texture(
+            self.output,
+            TextureUsages::RENDER_ATTACHMENT,
+        )]
+    }
+
+    fn on_resource_changed(&mut self, _resource: ResourceHandle) {}
+}
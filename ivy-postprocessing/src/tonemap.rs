@@ -1,23 +1,131 @@
+use flax::{FetchExt, Query};
+use image::DynamicImage;
+use ivy_assets::Asset;
 use ivy_wgpu::{
-    rendergraph::{Dependency, Node, TextureHandle},
+    camera_target::CameraSelection,
+    rendergraph::{
+        Dependency, HistoryTexture, Node, NodeUpdateContext, TextureHandle, UpdateResult,
+    },
     types::{
         shader::{ShaderDesc, TargetDesc},
-        BindGroupBuilder, BindGroupLayoutBuilder, RenderShader,
+        BindGroupBuilder, BindGroupLayoutBuilder, RenderShader, TypedBuffer,
     },
     Gpu,
 };
 use wgpu::{
-    BindGroup, BindGroupLayout, Color, Operations, RenderPassColorAttachment, SamplerDescriptor,
-    ShaderModuleDescriptor, ShaderSource, ShaderStages, StoreOp, TextureUsages,
+    util::{DeviceExt, TextureDataOrder},
+    BindGroup, BindGroupLayout, BindingType, BufferUsages, Color, Extent3d, Operations,
+    RenderPassColorAttachment, SamplerDescriptor, ShaderModuleDescriptor, ShaderSource,
+    ShaderStages, StoreOp, Texture, TextureDescriptor, TextureDimension, TextureFormat,
+    TextureSampleType, TextureUsages, TextureViewDimension,
+};
+
+use crate::{
+    auto_exposure::EXPOSURE_FORMAT,
+    components::{color_grading_lut, tonemapping},
 };
 
+/// Exposure used without auto exposure, mapping a luminance of `0.05` to middle gray
+const DEFAULT_EXPOSURE: f32 = 1.0 / (9.6 * 0.05);
+
+/// Maps the hdr colors of a camera to the displayable range
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Tonemapping {
+    /// Reinhard applied to the luminance, preserving the chromaticity
+    #[default]
+    Reinhard,
+    /// Filmic curve of the ACES reference rendering transform, with a strong contrast
+    Aces,
+    /// Gracefully desaturates bright colors, avoiding hue shifts
+    AgX,
+    /// Khronos PBR Neutral, which reproduces base colors accurately under bright lighting
+    Neutral,
+}
+
+impl Tonemapping {
+    fn shader_index(&self) -> u32 {
+        match self {
+            Tonemapping::Reinhard => 0,
+            Tonemapping::Aces => 1,
+            Tonemapping::AgX => 2,
+            Tonemapping::Neutral => 3,
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct TonemapData {
+    operator: u32,
+    lut_size: f32,
+    lut_enabled: u32,
+    _padding: u32,
+}
+
+/// Creates a 3D lookup table from a horizontal strip of `N` slices of `NxN` pixels, where the
+/// slices are indexed by blue, and the pixels within a slice by red and green.
+fn create_lut(gpu: &Gpu, image: &DynamicImage) -> anyhow::Result<Texture> {
+    let image = image.to_rgba8();
+    let size = image.height();
+
+    anyhow::ensure!(
+        size > 0 && image.width() == size * size,
+        "Color grading lut must be {0} slices of {size}x{size} pixels, found {1}x{size}",
+        size,
+        image.width(),
+    );
+
+    let row_len = size as usize * 4;
+    let mut data = Vec::with_capacity(row_len * size as usize * size as usize);
+    for slice in 0..size as usize {
+        for row in image.as_raw().chunks_exact(row_len * size as usize) {
+            data.extend_from_slice(&row[slice * row_len..(slice + 1) * row_len]);
+        }
+    }
+
+    Ok(gpu.device.create_texture_with_data(
+        &gpu.queue,
+        &TextureDescriptor {
+            label: Some("color_grading_lut"),
+            size: Extent3d {
+                width: size,
+                height: size,
+                depth_or_array_layers: size,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: TextureDimension::D3,
+            format: TextureFormat::Rgba8Unorm,
+            usage: TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        },
+        TextureDataOrder::LayerMajor,
+        &data,
+    ))
+}
+
+/// Tonemaps and color grades the input using the [`tonemapping`] and [`color_grading_lut`] of the
+/// camera
 pub struct TonemapNode {
     input: TextureHandle,
     output: TextureHandle,
+    exposure: Option<HistoryTexture>,
+    default_exposure: Texture,
     shader: Option<RenderShader>,
     layout: BindGroupLayout,
-    bind_group: Option<BindGroup>,
+    /// One for each parity of the exposure history
+    bind_groups: [Option<BindGroup>; 2],
     default_sampler: wgpu::Sampler,
+
+    data: TypedBuffer<TonemapData>,
+    lut_sampler: wgpu::Sampler,
+    /// Bound when no lut is used
+    default_lut: Texture,
+    lut: Option<(Asset<DynamicImage>, Texture)>,
+    /// Lut which could not be used, graded without until changed
+    failed_lut: Option<Asset<DynamicImage>>,
+
+    camera: CameraSelection,
 }
 
 impl TonemapNode {
@@ -25,6 +133,17 @@ impl TonemapNode {
         let layout = BindGroupLayoutBuilder::new("Tonemap")
             .bind_texture(ShaderStages::FRAGMENT)
             .bind_sampler(ShaderStages::FRAGMENT)
+            .bind_texture_unfiltered(ShaderStages::FRAGMENT)
+            .bind_uniform_buffer(ShaderStages::FRAGMENT)
+            .bind(
+                ShaderStages::FRAGMENT,
+                BindingType::Texture {
+                    sample_type: TextureSampleType::Float { filterable: true },
+                    view_dimension: TextureViewDimension::D3,
+                    multisampled: false,
+                },
+            )
+            .bind_sampler(ShaderStages::FRAGMENT)
             .build(gpu);
 
         let default_sampler = gpu.device.create_sampler(&SamplerDescriptor {
@@ -37,26 +156,168 @@ impl TonemapNode {
             ..Default::default()
         });
 
+        let default_exposure = gpu.device.create_texture_with_data(
+            &gpu.queue,
+            &TextureDescriptor {
+                label: Some("default_exposure"),
+                size: Extent3d {
+                    width: 1,
+                    height: 1,
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: TextureDimension::D2,
+                format: EXPOSURE_FORMAT,
+                usage: TextureUsages::TEXTURE_BINDING,
+                view_formats: &[],
+            },
+            TextureDataOrder::LayerMajor,
+            bytemuck::bytes_of(&DEFAULT_EXPOSURE),
+        );
+
+        let lut_sampler = gpu.device.create_sampler(&SamplerDescriptor {
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Nearest,
+            ..Default::default()
+        });
+
+        let default_lut = gpu.device.create_texture_with_data(
+            &gpu.queue,
+            &TextureDescriptor {
+                label: Some("default_lut"),
+                size: Extent3d {
+                    width: 1,
+                    height: 1,
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: TextureDimension::D3,
+                format: TextureFormat::Rgba8Unorm,
+                usage: TextureUsages::TEXTURE_BINDING,
+                view_formats: &[],
+            },
+            TextureDataOrder::LayerMajor,
+            &[255; 4],
+        );
+
+        let data = TypedBuffer::new(
+            gpu,
+            "Tonemap",
+            BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+            &[bytemuck::Zeroable::zeroed()],
+        );
+
         Self {
             input,
             output,
+            exposure: None,
+            default_exposure,
             shader: None,
-            bind_group: None,
+            bind_groups: Default::default(),
             layout,
             default_sampler,
+            data,
+            lut_sampler,
+            default_lut,
+            lut: None,
+            failed_lut: None,
+            camera: CameraSelection::Main,
         }
     }
+
+    /// Tonemap with the settings of another camera than the main camera
+    pub fn with_camera(mut self, camera: impl Into<CameraSelection>) -> Self {
+        self.camera = camera.into();
+        self
+    }
+
+    /// Use the exposure written by an [`AutoExposureNode`](crate::auto_exposure::AutoExposureNode)
+    pub fn with_exposure(mut self, exposure: HistoryTexture) -> Self {
+        self.exposure = Some(exposure);
+        self
+    }
 }
 
 impl Node for TonemapNode {
+    fn update(&mut self, ctx: NodeUpdateContext) -> anyhow::Result<UpdateResult> {
+        let mut query = Query::new((tonemapping().opt_or_default(), color_grading_lut().opt()));
+        let (operator, lut) = self
+            .camera
+            .find(ctx.world)
+            .and_then(|id| {
+                let mut query = query.borrow(ctx.world);
+                let (&operator, lut) = query.get(id).ok()?;
+                Some((operator, lut.cloned()))
+            })
+            .unwrap_or_default();
+
+        let changed = self.lut.as_ref().map(|v| &v.0) != lut.as_ref()
+            && self.failed_lut.as_ref() != lut.as_ref();
+
+        if changed {
+            self.lut = None;
+            self.failed_lut = None;
+
+            if let Some(lut) = lut {
+                match create_lut(ctx.gpu, &lut) {
+                    Ok(texture) => self.lut = Some((lut, texture)),
+                    Err(err) => {
+                        tracing::error!("Failed to create color grading lut\n{err:?}");
+                        // Not retried until the lut changes
+                        self.failed_lut = Some(lut);
+                    }
+                }
+            }
+
+            self.bind_groups = Default::default();
+        }
+
+        self.data.write(
+            &ctx.gpu.queue,
+            0,
+            &[TonemapData {
+                operator: operator.shader_index(),
+                lut_size: self
+                    .lut
+                    .as_ref()
+                    .map(|v| v.1.depth_or_array_layers() as f32)
+                    .unwrap_or(1.0),
+                lut_enabled: self.lut.is_some() as u32,
+                _padding: 0,
+            }],
+        );
+
+        Ok(UpdateResult::Success)
+    }
+
     fn draw(&mut self, ctx: ivy_wgpu::rendergraph::NodeExecutionContext) -> anyhow::Result<()> {
         let input = ctx.get_texture(self.input);
         let output = ctx.get_texture(self.output);
+        let exposure = match self.exposure {
+            Some(exposure) => ctx.get_texture(exposure.current),
+            None => &self.default_exposure,
+        };
+        let lut = self.lut.as_ref().map_or(&self.default_lut, |v| &v.1);
 
-        let bind_group = self.bind_group.get_or_insert_with(|| {
+        let parity = self
+            .exposure
+            .map(|v| ctx.resources.history_parity(v))
+            .unwrap_or_default();
+
+        let bind_group = self.bind_groups[parity].get_or_insert_with(|| {
             BindGroupBuilder::new("Tonemap")
                 .bind_texture(&input.create_view(&Default::default()))
                 .bind_sampler(&self.default_sampler)
+                .bind_texture(&exposure.create_view(&Default::default()))
+                .bind_buffer(&self.data)
+                .bind_texture(&lut.create_view(&Default::default()))
+                .bind_sampler(&self.lut_sampler)
                 .build(ctx.gpu, &self.layout)
         });
 
@@ -103,10 +364,10 @@ impl Node for TonemapNode {
     }
 
     fn read_dependencies(&self) -> Vec<ivy_wgpu::rendergraph::Dependency> {
-        vec![Dependency::texture(
-            self.input,
-            TextureUsages::TEXTURE_BINDING,
-        )]
+        std::iter::once(self.input)
+            .chain(self.exposure.map(|v| v.current))
+            .map(|v| Dependency::texture(v, TextureUsages::TEXTURE_BINDING))
+            .collect()
     }
 
     fn write_dependencies(&self) -> Vec<ivy_wgpu::rendergraph::Dependency> {
@@ -117,6 +378,6 @@ impl Node for TonemapNode {
     }
 
     fn on_resource_changed(&mut self, _resource: ivy_wgpu::rendergraph::ResourceHandle) {
-        self.bind_group = None;
+        self.bind_groups = Default::default();
     }
 }
@@ -1,3 +1,4 @@
+pub mod service;
 mod traits;
 
 pub use rand;
@@ -1,3 +1,7 @@
+pub mod prefab;
+pub mod serialize;
+pub mod streaming;
+
 use std::collections::BTreeMap;
 
 use flax::{
@@ -0,0 +1,562 @@
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::{Duration, Instant, SystemTime},
+};
+
+use anyhow::Context;
+use flax::{
+    components::{child_of, name},
+    entity_ids, BoxedSystem, Entity, EntityRefMut, FetchExt, Query, System, World,
+};
+use ivy_assets::{service::FileSystemMapService, AssetCache};
+use ivy_core::{
+    components::{engine, tasks, TransformBundle},
+    notifications::{notify, Notification},
+    update_layer::{Plugin, ScheduleSetBuilder},
+    EntityBuilderExt,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::serialize::{resolve_models, scene_model, SceneFormat, SceneSerializer};
+
+/// Prefabs nested deeper than this are assumed to reference themselves
+const MAX_DEPTH: usize = 16;
+
+/// Marks an entity as an instance of the prefab file at `path`, relative to the asset root.
+///
+/// Entities of the prefab are spawned as children of the instance. Prefab files may contain
+/// instances of other prefabs, which are expanded recursively.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct PrefabInstance {
+    pub path: PathBuf,
+}
+
+type OverrideFn = Arc<dyn Fn(&mut EntityRefMut) -> anyhow::Result<()> + Send + Sync>;
+
+/// Per-instance changes applied to the entities of a prefab each time it is instantiated.
+///
+/// Entities of nested prefabs are addressed by the name of the nested instance followed by the
+/// entity name, separated by a `/`, such as `"lamp/light"`.
+#[derive(Clone, Default)]
+pub struct PrefabOverrides {
+    overrides: Vec<(String, OverrideFn)>,
+}
+
+impl PrefabOverrides {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Modify the entity named `entity` of the prefab
+    pub fn with_override(
+        mut self,
+        entity: impl Into<String>,
+        func: impl 'static + Fn(&mut EntityRefMut) -> anyhow::Result<()> + Send + Sync,
+    ) -> Self {
+        self.overrides.push((entity.into(), Arc::new(func)));
+        self
+    }
+}
+
+flax::component! {
+    pub prefab_instance: PrefabInstance,
+    pub prefab_overrides: PrefabOverrides,
+
+    pub prefab_library: PrefabLibrary,
+}
+
+struct PrefabSource {
+    /// Path of the file on disk
+    file: PathBuf,
+    data: String,
+    modified: Option<SystemTime>,
+}
+
+/// Loads prefab files and instantiates them into the world.
+///
+/// Prefab files use the scene format of the [`SceneSerializer`].
+pub struct PrefabLibrary {
+    serializer: SceneSerializer,
+    sources: HashMap<PathBuf, PrefabSource>,
+}
+
+impl PrefabLibrary {
+    pub fn new(serializer: SceneSerializer) -> Self {
+        Self {
+            serializer,
+            sources: HashMap::new(),
+        }
+    }
+
+    fn source(&mut self, assets: &AssetCache, path: &Path) -> anyhow::Result<&PrefabSource> {
+        if !self.sources.contains_key(path) {
+            let source = read_source(resolve_path(assets, path))?;
+            self.sources.insert(path.to_path_buf(), source);
+        }
+
+        Ok(&self.sources[path])
+    }
+
+    /// Spawns a new instance of the prefab at `path`
+    pub fn instantiate(
+        &mut self,
+        world: &mut World,
+        assets: &AssetCache,
+        path: impl Into<PathBuf>,
+        transform: TransformBundle,
+        overrides: PrefabOverrides,
+    ) -> anyhow::Result<Entity> {
+        let id = Entity::builder()
+            .mount(transform)
+            .set(prefab_instance(), PrefabInstance { path: path.into() })
+            .set(prefab_overrides(), overrides)
+            .spawn(world);
+
+        self.expand(world, assets, id, 0)?;
+        Ok(id)
+    }
+
+    /// Spawns the entities of the prefab instance `id` as its children
+    fn expand(
+        &mut self,
+        world: &mut World,
+        assets: &AssetCache,
+        id: Entity,
+        depth: usize,
+    ) -> anyhow::Result<()> {
+        if depth > MAX_DEPTH {
+            anyhow::bail!("Prefab nesting exceeds a depth of {MAX_DEPTH}");
+        }
+
+        let path = world.get(id, prefab_instance())?.path.clone();
+        let overrides = world.get(id, prefab_overrides()).ok().map(|v| v.clone());
+
+        let format = SceneFormat::from_path(&path)?;
+        let data = self.source(assets, &path)?.data.clone();
+        let mut loaded = self
+            .serializer
+            .from_str(&data, format)
+            .with_context(|| format!("Failed to load prefab {path:?}"))?;
+
+        let names = Query::new((entity_ids(), name().cloned()))
+            .borrow(&loaded)
+            .iter()
+            .collect::<HashMap<_, _>>();
+
+        let models = Query::new((entity_ids(), scene_model().cloned()))
+            .borrow(&loaded)
+            .iter()
+            .collect::<Vec<_>>();
+
+        let nested = Query::new(entity_ids())
+            .with(prefab_instance())
+            .borrow(&loaded)
+            .iter()
+            .collect::<Vec<_>>();
+
+        // Only the roots are attached, keeping the hierarchy within the prefab
+        let roots = Query::new(entity_ids())
+            .without_relation(child_of)
+            .borrow(&loaded)
+            .iter()
+            .collect::<Vec<_>>();
+
+        let ids = world.merge_with(&mut loaded);
+
+        for entity in roots {
+            world.set(ids.get(entity), child_of(id), ())?;
+        }
+
+        let find = |target: &str| {
+            names
+                .iter()
+                .find(|(_, name)| *name == target)
+                .map(|(&id, _)| id)
+        };
+
+        // Overrides of nested prefabs are forwarded to the nested instance
+        let mut nested_overrides: HashMap<Entity, PrefabOverrides> = HashMap::new();
+        for (target, func) in overrides.iter().flat_map(|v| &v.overrides) {
+            if let Some(target_id) = find(target) {
+                func(&mut world.entity_mut(ids.get(target_id))?)?;
+                continue;
+            }
+
+            let forwarded = target.split_once('/').and_then(|(instance, rest)| {
+                let instance = find(instance).filter(|v| nested.contains(v))?;
+                Some((instance, rest))
+            });
+
+            match forwarded {
+                Some((instance, rest)) => {
+                    let overrides = nested_overrides.entry(instance).or_default();
+                    overrides.overrides.push((rest.to_owned(), func.clone()));
+                }
+                None => tracing::warn!(?path, target, "Prefab override target not found"),
+            }
+        }
+
+        if !models.is_empty() {
+            let tasks = world.get(engine(), tasks())?.clone();
+            resolve_models(
+                &tasks,
+                assets.clone(),
+                models
+                    .into_iter()
+                    .map(|(entity, model)| (ids.get(entity), model)),
+            );
+        }
+
+        for entity in nested {
+            let nested_id = ids.get(entity);
+            if let Some(overrides) = nested_overrides.remove(&entity) {
+                world.set(nested_id, prefab_overrides(), overrides)?;
+            }
+
+            self.expand(world, assets, nested_id, depth + 1)
+                .with_context(|| format!("Failed to expand nested prefab of {path:?}"))?;
+        }
+
+        Ok(())
+    }
+
+    /// Re-reads the prefab files which were modified on disk, and re-instantiates their
+    /// instances with the same overrides.
+    ///
+    /// Returns `true` if any prefab was reloaded.
+    pub fn reload_changed(&mut self, world: &mut World, assets: &AssetCache) -> bool {
+        let mut changed = Vec::new();
+
+        for (path, source) in &mut self.sources {
+            let modified = modified_time(&source.file);
+            if modified.is_none() || modified == source.modified {
+                continue;
+            }
+
+            match read_source(source.file.clone()) {
+                Ok(v) => {
+                    tracing::info!(?path, "Reloading prefab");
+                    *source = v;
+                    changed.push(path.clone());
+                }
+                Err(err) => {
+                    tracing::error!(?path, "Failed to reload prefab: {err:?}");
+                    notify(
+                        assets,
+                        Notification::error(format!("Failed to reload prefab {path:?}: {err:#}")),
+                    );
+                }
+            }
+        }
+
+        if changed.is_empty() {
+            return false;
+        }
+
+        let instances = Query::new((entity_ids(), prefab_instance()))
+            .borrow(world)
+            .iter()
+            .filter(|(_, instance)| changed.contains(&instance.path))
+            .map(|(id, _)| id)
+            .collect::<Vec<_>>();
+
+        for id in instances {
+            if !world.is_alive(id) {
+                continue;
+            }
+
+            if let Err(err) = world.despawn_children(id, child_of) {
+                tracing::error!(?id, "Failed to despawn prefab instance: {err:?}");
+                continue;
+            }
+
+            if let Err(err) = self.expand(world, assets, id, 0) {
+                tracing::error!(?id, "Failed to re-instantiate prefab: {err:?}");
+                notify(
+                    assets,
+                    Notification::error(format!("Failed to re-instantiate prefab: {err:#}")),
+                );
+            }
+        }
+
+        true
+    }
+}
+
+/// Spawns a new instance of the prefab at `path` using the [`prefab_library`] of the engine
+pub fn instantiate_prefab(
+    world: &mut World,
+    assets: &AssetCache,
+    path: impl Into<PathBuf>,
+    transform: TransformBundle,
+    overrides: PrefabOverrides,
+) -> anyhow::Result<Entity> {
+    let mut library = world
+        .remove(engine(), prefab_library())
+        .context("Missing prefab library")?;
+
+    let result = library.instantiate(world, assets, path, transform, overrides);
+    world.set(engine(), prefab_library(), library)?;
+
+    result
+}
+
+/// Reloads the prefabs of the [`prefab_library`] which changed on disk
+pub fn prefab_reload_system(assets: AssetCache, interval: Duration) -> BoxedSystem {
+    let mut last_check = Instant::now();
+
+    System::builder()
+        .with_world_mut()
+        .build(move |world: &mut World| {
+            if last_check.elapsed() < interval {
+                return Ok(());
+            }
+
+            last_check = Instant::now();
+
+            let Ok(mut library) = world.remove(engine(), prefab_library()) else {
+                return Ok(());
+            };
+
+            library.reload_changed(world, &assets);
+            world.set(engine(), prefab_library(), library)?;
+
+            anyhow::Ok(())
+        })
+        .boxed()
+}
+
+/// Adds a [`PrefabLibrary`] to the engine and reloads the prefab files when they change
+pub struct PrefabPlugin {
+    serializer: Box<dyn Fn() -> SceneSerializer>,
+    reload_interval: Option<Duration>,
+}
+
+impl PrefabPlugin {
+    /// `serializer` creates the scene serializer used to read the prefabs
+    pub fn new(serializer: impl 'static + Fn() -> SceneSerializer) -> Self {
+        Self {
+            serializer: Box::new(serializer),
+            reload_interval: Some(Duration::from_secs(1)),
+        }
+    }
+
+    /// Set how often the prefab files are checked for changes, or `None` to disable reloading
+    pub fn with_reload_interval(mut self, reload_interval: Option<Duration>) -> Self {
+        self.reload_interval = reload_interval;
+        self
+    }
+}
+
+impl Plugin for PrefabPlugin {
+    fn install(
+        &self,
+        world: &mut World,
+        assets: &AssetCache,
+        schedules: &mut ScheduleSetBuilder,
+    ) -> anyhow::Result<()> {
+        world.set(
+            engine(),
+            prefab_library(),
+            PrefabLibrary::new((self.serializer)()),
+        )?;
+
+        if let Some(interval) = self.reload_interval {
+            schedules
+                .per_tick_mut()
+                .with_system(prefab_reload_system(assets.clone(), interval));
+        }
+
+        Ok(())
+    }
+}
+
+/// Resolves an asset path to the file on disk
+fn resolve_path(assets: &AssetCache, path: &Path) -> PathBuf {
+    match assets.try_service::<FileSystemMapService>() {
+        Some(fs) => fs.root.join(path),
+        None => path.to_path_buf(),
+    }
+}
+
+fn modified_time(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|v| v.modified()).ok()
+}
+
+fn read_source(file: PathBuf) -> anyhow::Result<PrefabSource> {
+    let data =
+        fs::read_to_string(&file).with_context(|| format!("Failed to read prefab {file:?}"))?;
+
+    Ok(PrefabSource {
+        modified: modified_time(&file),
+        file,
+        data,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs::File;
+
+    use flax::serialize::SerdeBuilder;
+    use glam::Vec3;
+    use ivy_core::components::position;
+
+    use super::*;
+
+    fn serializer() -> SceneSerializer {
+        SceneSerializer::new(SerdeBuilder::new().with(position()))
+    }
+
+    fn setup(name: &str) -> (PathBuf, AssetCache) {
+        let root = std::env::temp_dir().join(format!("ivy-prefab-{}-{name}", std::process::id()));
+        fs::create_dir_all(&root).unwrap();
+
+        let assets = AssetCache::new();
+        assets.register_service(FileSystemMapService::new(&root));
+        (root, assets)
+    }
+
+    fn write_prefab(root: &Path, path: &str, entities: &[(&str, Vec3, Option<&str>)]) {
+        let mut world = World::new();
+        for &(entity_name, pos, prefab) in entities {
+            let mut builder = Entity::builder();
+            builder.set(name(), entity_name.into()).set(position(), pos);
+
+            if let Some(prefab) = prefab {
+                builder.set(
+                    prefab_instance(),
+                    PrefabInstance {
+                        path: prefab.into(),
+                    },
+                );
+            }
+
+            builder.spawn(&mut world);
+        }
+
+        let data = serializer().to_string(&world, SceneFormat::Ron).unwrap();
+        fs::write(root.join(path), data).unwrap();
+    }
+
+    fn find(world: &World, entity_name: &str) -> Vec<Entity> {
+        Query::new((entity_ids(), name()))
+            .borrow(world)
+            .iter()
+            .filter(|(_, v)| *v == entity_name)
+            .map(|(id, _)| id)
+            .collect()
+    }
+
+    fn set_position(pos: Vec3) -> impl Fn(&mut EntityRefMut) -> anyhow::Result<()> {
+        move |entity| {
+            entity.set(position(), pos);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn nested_overrides() {
+        let (root, assets) = setup("nested");
+        write_prefab(&root, "light.ron", &[("light", Vec3::ZERO, None)]);
+        write_prefab(
+            &root,
+            "lamp.ron",
+            &[
+                ("base", Vec3::ZERO, None),
+                ("lamp", Vec3::ZERO, Some("light.ron")),
+            ],
+        );
+
+        let mut world = World::new();
+        let mut library = PrefabLibrary::new(serializer());
+
+        let overrides = PrefabOverrides::new()
+            .with_override("base", set_position(Vec3::X))
+            .with_override("lamp/light", set_position(Vec3::Y));
+
+        let instance = library
+            .instantiate(
+                &mut world,
+                &assets,
+                "lamp.ron",
+                TransformBundle::default(),
+                overrides,
+            )
+            .unwrap();
+
+        let [base] = find(&world, "base")[..] else {
+            panic!("Expected a single base");
+        };
+        let [lamp] = find(&world, "lamp")[..] else {
+            panic!("Expected a single lamp");
+        };
+        let [light] = find(&world, "light")[..] else {
+            panic!("Expected a single light");
+        };
+
+        assert!(world.has(base, child_of(instance)));
+        assert!(world.has(lamp, child_of(instance)));
+        assert!(world.has(light, child_of(lamp)));
+
+        assert_eq!(*world.get(base, position()).unwrap(), Vec3::X);
+        assert_eq!(*world.get(lamp, position()).unwrap(), Vec3::ZERO);
+        assert_eq!(*world.get(light, position()).unwrap(), Vec3::Y);
+
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn reload_changed() {
+        let (root, assets) = setup("reload");
+        write_prefab(&root, "chest.ron", &[("body", Vec3::ZERO, None)]);
+
+        let mut world = World::new();
+        let mut library = PrefabLibrary::new(serializer());
+
+        let overrides = PrefabOverrides::new().with_override("lid", set_position(Vec3::Z));
+        let instance = library
+            .instantiate(
+                &mut world,
+                &assets,
+                "chest.ron",
+                TransformBundle::default(),
+                overrides,
+            )
+            .unwrap();
+
+        assert!(!library.reload_changed(&mut world, &assets));
+
+        write_prefab(
+            &root,
+            "chest.ron",
+            &[("body", Vec3::X, None), ("lid", Vec3::ZERO, None)],
+        );
+
+        // Ensure the change is visible regardless of the timestamp resolution
+        File::options()
+            .write(true)
+            .open(root.join("chest.ron"))
+            .unwrap()
+            .set_modified(SystemTime::now() + Duration::from_secs(10))
+            .unwrap();
+
+        assert!(library.reload_changed(&mut world, &assets));
+
+        let [body] = find(&world, "body")[..] else {
+            panic!("Expected the previous entities to be despawned");
+        };
+        let [lid] = find(&world, "lid")[..] else {
+            panic!("Expected a single lid");
+        };
+
+        assert!(world.has(body, child_of(instance)));
+        assert_eq!(*world.get(body, position()).unwrap(), Vec3::X);
+        assert_eq!(*world.get(lid, position()).unwrap(), Vec3::Z);
+
+        fs::remove_dir_all(root).unwrap();
+    }
+}
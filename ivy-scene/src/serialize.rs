@@ -0,0 +1,431 @@
+use std::{fmt, fs, path::Path};
+
+use anyhow::Context;
+use flax::{
+    components::{child_of, name},
+    serialize::{SerdeBuilder, SerializeFormat, WorldDeserializer, WorldSerializer},
+    Entity, EntityBuilder, FetchExt, Query, World,
+};
+use ivy_assets::{fs::AssetPath, Asset, AssetCache, DynAsyncAssetDesc};
+use ivy_core::{
+    components::{engine, position, rotation, scale, tasks},
+    notifications::{notify, Notification},
+    Tasks,
+};
+use ivy_gltf::Document;
+use serde::{
+    de::{self, DeserializeSeed, MapAccess, SeqAccess, Visitor},
+    Deserialize, Deserializer, Serialize, Serializer,
+};
+
+use crate::{prefab::prefab_instance, GltfNodeExt, NodeMountOptions};
+
+/// Reference to a node of a gltf model, resolved when the scene is loaded.
+///
+/// Loaded assets can not be serialized, so entities store where their model was loaded from
+/// instead.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct ModelRef {
+    /// Asset path of the gltf document
+    pub path: String,
+    /// Name of the node to mount, or the first node if `None`
+    pub node: Option<String>,
+}
+
+impl ModelRef {
+    pub fn new(path: impl Into<String>) -> Self {
+        Self {
+            path: path.into(),
+            node: None,
+        }
+    }
+
+    /// Set the node
+    pub fn with_node(mut self, node: impl Into<String>) -> Self {
+        self.node = Some(node.into());
+        self
+    }
+}
+
+flax::component! {
+    /// The model an entity was created from. Resolved into render objects by
+    /// [`resolve_models`] when a scene is loaded.
+    pub scene_model: ModelRef,
+}
+
+/// Text format of a scene file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SceneFormat {
+    Ron,
+    Json,
+}
+
+impl SceneFormat {
+    /// Returns the format of a file from its extension
+    pub fn from_path(path: &Path) -> anyhow::Result<Self> {
+        match path.extension().and_then(|v| v.to_str()) {
+            Some("ron") => Ok(Self::Ron),
+            Some("json") => Ok(Self::Json),
+            _ => anyhow::bail!("Unknown scene format for {path:?}, expected .ron or .json"),
+        }
+    }
+}
+
+/// Saves and loads a subset of the world to a stable text format.
+///
+/// Only the whitelisted components are stored, along with the entity names, [`scene_model`],
+/// [`prefab_instance`], and the `child_of` hierarchy.
+///
+/// ```rust,ignore
+/// SceneSerializer::new(SerdeBuilder::new().with(position()).with(rotation()).with(scale()))
+/// ```
+pub struct SceneSerializer {
+    serializer: WorldSerializer,
+    deserializer: WorldDeserializer,
+}
+
+impl SceneSerializer {
+    pub fn new(components: &mut SerdeBuilder) -> Self {
+        let (serializer, deserializer) = components
+            .with(name())
+            .with(scene_model())
+            .with(prefab_instance())
+            .build();
+
+        Self {
+            serializer,
+            deserializer,
+        }
+    }
+
+    /// Serializes the world with any serde format
+    pub fn serialize<S: Serializer>(
+        &self,
+        world: &World,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        self.scene_data(world).serialize(serializer)
+    }
+
+    /// Deserializes a scene from any serde format into a new world
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        &self,
+        deserializer: D,
+    ) -> Result<World, D::Error> {
+        SceneSeed(&self.deserializer).deserialize(deserializer)
+    }
+
+    fn scene_data<'a>(&'a self, world: &'a World) -> SceneData<impl Serialize + 'a> {
+        SceneData {
+            entities: self.serializer.serialize(world, SerializeFormat::RowMajor),
+            parents: parents(world),
+        }
+    }
+
+    pub fn to_string(&self, world: &World, format: SceneFormat) -> anyhow::Result<String> {
+        let data = self.scene_data(world);
+
+        match format {
+            SceneFormat::Ron => ron::ser::to_string_pretty(&data, Default::default())
+                .context("Failed to serialize scene"),
+            SceneFormat::Json => {
+                serde_json::to_string_pretty(&data).context("Failed to serialize scene")
+            }
+        }
+    }
+
+    /// Deserializes a scene into a new world
+    pub fn from_str(&self, data: &str, format: SceneFormat) -> anyhow::Result<World> {
+        match format {
+            SceneFormat::Ron => {
+                let mut deserializer = ron::Deserializer::from_str(data)?;
+                self.deserialize(&mut deserializer)
+                    .context("Failed to deserialize scene")
+            }
+            SceneFormat::Json => {
+                let mut deserializer = serde_json::Deserializer::from_str(data);
+                self.deserialize(&mut deserializer)
+                    .context("Failed to deserialize scene")
+            }
+        }
+    }
+
+    /// Writes the world to a file, with the format chosen by the extension
+    pub fn save(&self, world: &World, path: impl AsRef<Path>) -> anyhow::Result<()> {
+        let path = path.as_ref();
+        let data = self.to_string(world, SceneFormat::from_path(path)?)?;
+
+        fs::write(path, data).with_context(|| format!("Failed to write scene {path:?}"))?;
+        tracing::info!(?path, "saved scene");
+        Ok(())
+    }
+
+    /// Loads a scene file into the world, and resolves the models of the loaded entities
+    pub fn load(
+        &self,
+        world: &mut World,
+        assets: &AssetCache,
+        path: impl AsRef<Path>,
+    ) -> anyhow::Result<()> {
+        let path = path.as_ref();
+        let data =
+            fs::read_to_string(path).with_context(|| format!("Failed to read scene {path:?}"))?;
+
+        let loaded = self
+            .from_str(&data, SceneFormat::from_path(path)?)
+            .with_context(|| format!("Failed to load scene {path:?}"))?;
+
+        instantiate(world, assets, loaded)?;
+
+        Ok(())
+    }
+}
+
+#[derive(Serialize)]
+struct SceneData<T> {
+    entities: T,
+    /// `(child, parent)` pairs of the hierarchy, which the world serializer does not store
+    parents: Vec<(Entity, Entity)>,
+}
+
+fn parents(world: &World) -> Vec<(Entity, Entity)> {
+    Query::new(flax::entity_ids())
+        .borrow(world)
+        .iter()
+        .filter_map(|id| {
+            let (parent, _) = world.entity(id).ok()?.relations(child_of).next()?;
+            Some((id, parent))
+        })
+        .collect()
+}
+
+#[derive(Deserialize)]
+#[serde(field_identifier, rename_all = "lowercase")]
+enum SceneField {
+    Entities,
+    Parents,
+}
+
+struct SceneSeed<'a>(&'a WorldDeserializer);
+
+impl<'de> DeserializeSeed<'de> for SceneSeed<'_> {
+    type Value = World;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<World, D::Error> {
+        deserializer.deserialize_struct("SceneData", &["entities", "parents"], self)
+    }
+}
+
+impl<'de> Visitor<'de> for SceneSeed<'_> {
+    type Value = World;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a scene")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<World, A::Error> {
+        let world = seq
+            .next_element_seed(WorldSeed(self.0))?
+            .ok_or_else(|| de::Error::invalid_length(0, &self))?;
+        let parents = seq
+            .next_element()?
+            .ok_or_else(|| de::Error::invalid_length(1, &self))?;
+
+        attach_parents(world, parents)
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<World, A::Error> {
+        let mut world = None;
+        let mut parents = None;
+
+        while let Some(key) = map.next_key()? {
+            match key {
+                SceneField::Entities => world = Some(map.next_value_seed(WorldSeed(self.0))?),
+                SceneField::Parents => parents = Some(map.next_value()?),
+            }
+        }
+
+        let world = world.ok_or_else(|| de::Error::missing_field("entities"))?;
+        attach_parents(world, parents.unwrap_or_default())
+    }
+}
+
+fn attach_parents<E: de::Error>(
+    mut world: World,
+    parents: Vec<(Entity, Entity)>,
+) -> Result<World, E> {
+    for (child, parent) in parents {
+        // Entities without any stored components are not part of the scene
+        if world.is_alive(child) && world.is_alive(parent) {
+            world.set(child, child_of(parent), ()).map_err(E::custom)?;
+        }
+    }
+
+    Ok(world)
+}
+
+struct WorldSeed<'a>(&'a WorldDeserializer);
+
+impl<'de> DeserializeSeed<'de> for WorldSeed<'_> {
+    type Value = World;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<World, D::Error> {
+        self.0.deserialize(deserializer)
+    }
+}
+
+/// Merges a deserialized scene into the world and resolves the models of the new entities.
+///
+/// Returns the ids of the entities in the scene and their new ids in `world`.
+pub fn instantiate(
+    world: &mut World,
+    assets: &AssetCache,
+    mut loaded: World,
+) -> anyhow::Result<Vec<(Entity, Entity)>> {
+    let models = Query::new((flax::entity_ids(), scene_model().cloned()))
+        .borrow(&loaded)
+        .iter()
+        .collect::<Vec<_>>();
+
+    let entities = Query::new(flax::entity_ids())
+        .borrow(&loaded)
+        .iter()
+        .collect::<Vec<_>>();
+
+    let ids = world.merge_with(&mut loaded);
+
+    if !models.is_empty() {
+        let tasks = world.get(engine(), tasks())?;
+        let models = models.into_iter().map(|(id, model)| (ids.get(id), model));
+        resolve_models(&tasks, assets.clone(), models);
+    }
+
+    Ok(entities.into_iter().map(|id| (id, ids.get(id))).collect())
+}
+
+/// Loads the models of the given entities in the background, and mounts them once loaded
+pub fn resolve_models(
+    tasks: &Tasks,
+    assets: AssetCache,
+    models: impl IntoIterator<Item = (Entity, ModelRef)>,
+) {
+    for (id, model) in models {
+        let notify_assets = assets.clone();
+        let assets = assets.clone();
+
+        tasks.spawn_owned(
+            id,
+            async move {
+                let result = load_model(&assets, &model).await;
+                (model, result)
+            },
+            move |world, id, (model, result)| {
+                match result {
+                    Ok(mut builder) => {
+                        builder.append_to(world, id)?;
+                    }
+                    Err(err) => {
+                        tracing::error!(?id, ?model, "Failed to load scene model: {err:?}");
+                        notify(
+                            &notify_assets,
+                            Notification::error(format!("Failed to load scene model: {err:#}")),
+                        );
+                    }
+                }
+
+                Ok(())
+            },
+        );
+    }
+}
+
+async fn load_model(assets: &AssetCache, model: &ModelRef) -> anyhow::Result<EntityBuilder> {
+    let document: Asset<Document> = AssetPath::new(&model.path).load_async(assets).await?;
+
+    let node = match &model.node {
+        Some(node) => document.find_node(node),
+        None => document.node(0),
+    }
+    .with_context(|| format!("No node {:?} in {:?}", model.node, model.path))?;
+
+    let mut builder = Entity::builder();
+    node.mount(
+        &mut builder,
+        &NodeMountOptions {
+            skip_empty_children: true,
+            material_overrides: &Default::default(),
+        },
+    );
+
+    // Keep the transform of the loaded entity
+    builder.remove(position());
+    builder.remove(rotation());
+    builder.remove(scale());
+
+    Ok(builder)
+}
+
+#[cfg(test)]
+mod test {
+    use flax::components::name;
+    use glam::{vec3, Vec3};
+    use ivy_core::components::position;
+
+    use super::*;
+
+    fn find(world: &World, entity_name: &str) -> Entity {
+        Query::new((flax::entity_ids(), name()))
+            .borrow(world)
+            .iter()
+            .find(|(_, v)| *v == entity_name)
+            .map(|(id, _)| id)
+            .unwrap()
+    }
+
+    #[test]
+    fn round_trip() {
+        let mut world = World::new();
+
+        let parent = Entity::builder()
+            .set(name(), "parent".into())
+            .set(position(), vec3(1.0, 2.0, 3.0))
+            .set(
+                scene_model(),
+                ModelRef::new("models/crate.glb").with_node("Crate"),
+            )
+            .spawn(&mut world);
+
+        Entity::builder()
+            .set(name(), "child".into())
+            .set(position(), Vec3::X)
+            .set(child_of(parent), ())
+            .spawn(&mut world);
+
+        let serializer = SceneSerializer::new(SerdeBuilder::new().with(position()));
+
+        for format in [SceneFormat::Ron, SceneFormat::Json] {
+            let data = serializer.to_string(&world, format).unwrap();
+            let mut loaded = serializer.from_str(&data, format).unwrap();
+
+            // Merge into a world with other entities to ensure the hierarchy is remapped
+            let mut target = World::new();
+            target.spawn();
+            target.merge_with(&mut loaded);
+
+            let parent = find(&target, "parent");
+            let child = find(&target, "child");
+
+            assert_eq!(
+                *target.get(parent, position()).unwrap(),
+                vec3(1.0, 2.0, 3.0)
+            );
+            assert_eq!(
+                *target.get(parent, scene_model()).unwrap(),
+                ModelRef::new("models/crate.glb").with_node("Crate")
+            );
+            assert_eq!(*target.get(child, position()).unwrap(), Vec3::X);
+            assert!(target.has(child, child_of(parent)));
+            assert!(!target.has(parent, child_of(child)));
+        }
+    }
+}
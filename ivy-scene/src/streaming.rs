@@ -0,0 +1,567 @@
+//! Streams the world in and out in cells around the player.
+//!
+//! The world is divided into a grid of square cells on the XZ plane, each with its own scene
+//! file. Cells within the load distance of a [`streaming_anchor`] are loaded in the background,
+//! and despawned again once every anchor has moved past the unload distance.
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    path::Path,
+    sync::Arc,
+};
+
+use anyhow::Context;
+use flax::{components::child_of, entity_ids, BoxedSystem, Entity, FetchExt, Query, System, World};
+use glam::{IVec2, Vec2, Vec3};
+use ivy_assets::{
+    fs::{AssetFromPath, AssetPath, AsyncAssetFromPath},
+    service::FileSystemMapService,
+    Asset, AssetCache, DynAsyncAssetDesc,
+};
+use ivy_core::{
+    components::{engine, tasks, world_transform},
+    notifications::{notify, Notification},
+    tasks::TaskHandle,
+    update_layer::{Plugin, ScheduleSetBuilder},
+};
+use ivy_gltf::Document;
+use serde::{Deserialize, Serialize};
+
+use crate::serialize::{instantiate, SceneFormat, SceneSerializer};
+
+/// A cell of the world
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WorldCell {
+    pub coord: IVec2,
+    /// Asset path of the scene file of the cell
+    pub scene: String,
+    /// Models loaded together with the cell, which are kept alive until the cell unloads
+    #[serde(default)]
+    pub preload: Vec<String>,
+}
+
+/// Layout of the cells of a streamed world, usually loaded from RON
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StreamingManifest {
+    /// Width of each cell in world units
+    pub cell_size: f32,
+    pub cells: Vec<WorldCell>,
+}
+
+impl StreamingManifest {
+    pub fn new(cell_size: f32) -> Self {
+        Self {
+            cell_size,
+            cells: Vec::new(),
+        }
+    }
+
+    /// Add a cell
+    pub fn with_cell(mut self, cell: WorldCell) -> Self {
+        self.cells.push(cell);
+        self
+    }
+
+    pub fn from_ron(source: &str) -> anyhow::Result<Self> {
+        let manifest: Self = ron::from_str(source)?;
+        if manifest.cell_size <= 0.0 {
+            anyhow::bail!("Cell size must be positive");
+        }
+
+        let mut coords = BTreeSet::new();
+        if let Some(cell) = manifest
+            .cells
+            .iter()
+            .find(|v| !coords.insert(v.coord.to_array()))
+        {
+            anyhow::bail!("Duplicate cell {}", cell.coord);
+        }
+
+        Ok(manifest)
+    }
+
+    /// Returns the cell containing `pos`
+    pub fn cell_at(&self, pos: Vec3) -> IVec2 {
+        (Vec2::new(pos.x, pos.z) / self.cell_size)
+            .floor()
+            .as_ivec2()
+    }
+
+    /// Returns the horizontal distance from `pos` to the closest point of the cell
+    pub fn distance_to_cell(&self, coord: IVec2, pos: Vec3) -> f32 {
+        let min = coord.as_vec2() * self.cell_size;
+        let max = min + self.cell_size;
+        let pos = Vec2::new(pos.x, pos.z);
+
+        pos.distance(pos.clamp(min, max))
+    }
+}
+
+impl AssetFromPath for StreamingManifest {
+    type Error = anyhow::Error;
+
+    fn load_from_path(path: &Path, assets: &AssetCache) -> anyhow::Result<Asset<Self>> {
+        let source = assets.service::<FileSystemMapService>().load_string(path)?;
+        let manifest = StreamingManifest::from_ron(&source)
+            .with_context(|| format!("Failed to load streaming manifest {path:?}"))?;
+
+        Ok(assets.insert(manifest))
+    }
+}
+
+impl AsyncAssetFromPath for StreamingManifest {
+    type Error = anyhow::Error;
+
+    async fn load_from_path(path: &Path, assets: &AssetCache) -> anyhow::Result<Asset<Self>> {
+        let source = assets
+            .service::<FileSystemMapService>()
+            .load_string_async(path)
+            .await?;
+        let manifest = StreamingManifest::from_ron(&source)
+            .with_context(|| format!("Failed to load streaming manifest {path:?}"))?;
+
+        Ok(assets.insert(manifest))
+    }
+}
+
+enum CellState {
+    Loading(TaskHandle),
+    Loaded {
+        /// Keeps the preloaded models alive while the cell is loaded
+        _preloaded: Vec<Asset<Document>>,
+    },
+    /// Not retried until the cell has been unloaded, such as by moving away from it
+    Failed,
+}
+
+/// Loads and unloads the cells of a [`StreamingManifest`].
+///
+/// Stored on the engine entity.
+pub struct LevelStreamer {
+    manifest: Asset<StreamingManifest>,
+    serializer: Arc<SceneSerializer>,
+    /// Cells closer than this to an anchor are loaded
+    pub load_distance: f32,
+    /// Cells further than this from all anchors are unloaded.
+    ///
+    /// Larger than the load distance to avoid reloading cells when moving back and forth along
+    /// a cell border.
+    pub unload_distance: f32,
+    cells: BTreeMap<[i32; 2], CellState>,
+}
+
+impl LevelStreamer {
+    pub fn new(manifest: Asset<StreamingManifest>, serializer: SceneSerializer) -> Self {
+        let load_distance = manifest.cell_size;
+
+        Self {
+            manifest,
+            serializer: Arc::new(serializer),
+            load_distance,
+            unload_distance: load_distance * 1.5,
+            cells: BTreeMap::new(),
+        }
+    }
+
+    /// Set the load and unload distance
+    pub fn with_distances(mut self, load_distance: f32, unload_distance: f32) -> Self {
+        self.load_distance = load_distance;
+        self.unload_distance = unload_distance.max(load_distance);
+        self
+    }
+
+    pub fn manifest(&self) -> &Asset<StreamingManifest> {
+        &self.manifest
+    }
+
+    pub fn is_loaded(&self, coord: IVec2) -> bool {
+        matches!(
+            self.cells.get(&coord.to_array()),
+            Some(CellState::Loaded { .. })
+        )
+    }
+
+    pub fn is_loading(&self, coord: IVec2) -> bool {
+        matches!(
+            self.cells.get(&coord.to_array()),
+            Some(CellState::Loading(_))
+        )
+    }
+
+    /// Returns true if the cell failed to load.
+    ///
+    /// The load is retried once the anchors have moved past the unload distance and back.
+    pub fn is_failed(&self, coord: IVec2) -> bool {
+        matches!(self.cells.get(&coord.to_array()), Some(CellState::Failed))
+    }
+
+    /// Returns the coordinates of the loaded cells
+    pub fn loaded(&self) -> impl Iterator<Item = IVec2> + '_ {
+        self.cells
+            .iter()
+            .filter(|(_, v)| matches!(v, CellState::Loaded { .. }))
+            .map(|(&k, _)| IVec2::from_array(k))
+    }
+
+    /// Returns the cells to load and unload for the given anchor positions
+    fn diff(&self, anchors: &[Vec3]) -> (Vec<WorldCell>, Vec<IVec2>) {
+        let manifest = &self.manifest;
+        let closest = |coord: IVec2| {
+            anchors
+                .iter()
+                .map(|&v| manifest.distance_to_cell(coord, v))
+                .fold(f32::MAX, f32::min)
+        };
+
+        let load = manifest
+            .cells
+            .iter()
+            .filter(|cell| !self.cells.contains_key(&cell.coord.to_array()))
+            .filter(|cell| closest(cell.coord) <= self.load_distance)
+            .cloned()
+            .collect();
+
+        let unload = self
+            .cells
+            .keys()
+            .map(|&v| IVec2::from_array(v))
+            .filter(|&coord| closest(coord) > self.unload_distance)
+            .collect();
+
+        (load, unload)
+    }
+
+    /// Forgets a cell, cancelling it if still loading.
+    ///
+    /// Returns true if the cell was loaded and its entities need to be despawned.
+    fn remove(&mut self, coord: IVec2) -> bool {
+        match self.cells.remove(&coord.to_array()) {
+            Some(CellState::Loading(handle)) => {
+                handle.cancel();
+                false
+            }
+            Some(CellState::Loaded { .. }) => true,
+            Some(CellState::Failed) | None => false,
+        }
+    }
+}
+
+flax::component! {
+    /// Cells are streamed in around entities with this component, such as the player or camera
+    pub streaming_anchor: (),
+    /// The cell an entity was streamed in with. The entity is despawned when the cell unloads.
+    pub streamed_cell: IVec2,
+
+    pub level_streamer: LevelStreamer,
+}
+
+/// Loads the scene of a cell in the background
+fn load_cell(
+    world: &World,
+    assets: &AssetCache,
+    serializer: Arc<SceneSerializer>,
+    cell: WorldCell,
+) -> anyhow::Result<TaskHandle> {
+    let tasks = world.get(engine(), tasks())?;
+    let notify_assets = assets.clone();
+    let assets = assets.clone();
+    let coord = cell.coord;
+
+    let load = async move {
+        let format = SceneFormat::from_path(cell.scene.as_ref())?;
+        let data = assets
+            .service::<FileSystemMapService>()
+            .load_string_async(&cell.scene)
+            .await?;
+
+        let loaded = serializer
+            .from_str(&data, format)
+            .with_context(|| format!("Failed to load scene {:?}", cell.scene))?;
+
+        let mut preloaded = Vec::new();
+        for path in &cell.preload {
+            preloaded.push(AssetPath::<Document>::new(path).load_async(&assets).await?);
+        }
+
+        anyhow::Ok((assets, loaded, preloaded))
+    };
+
+    Ok(tasks.spawn(load, move |world, result| {
+        let mut streamer = world.get_mut(engine(), level_streamer())?;
+
+        // Unloaded before the load completed
+        if !streamer.is_loading(coord) {
+            return Ok(());
+        }
+
+        let (assets, loaded, preloaded) = match result {
+            Ok(v) => v,
+            Err(err) => {
+                streamer.cells.insert(coord.to_array(), CellState::Failed);
+                tracing::error!(%coord, "Failed to load cell: {err:?}");
+                notify(
+                    &notify_assets,
+                    Notification::error(format!("Failed to load cell {coord}: {err:#}")),
+                );
+                return Ok(());
+            }
+        };
+
+        streamer.cells.insert(
+            coord.to_array(),
+            CellState::Loaded {
+                _preloaded: preloaded,
+            },
+        );
+
+        drop(streamer);
+
+        for (_, id) in instantiate(world, &assets, loaded)? {
+            world.set(id, streamed_cell(), coord)?;
+        }
+
+        tracing::info!(%coord, "Loaded cell");
+        Ok(())
+    }))
+}
+
+/// Despawns the entities of a cell, along with their children
+fn unload_cell(world: &mut World, coord: IVec2) -> anyhow::Result<()> {
+    let ids = Query::new((entity_ids(), streamed_cell().copied()))
+        .borrow(world)
+        .iter()
+        .filter(|&(_, v)| v == coord)
+        .map(|(id, _)| id)
+        .collect::<Vec<Entity>>();
+
+    for id in ids {
+        if !world.is_alive(id) {
+            continue;
+        }
+
+        world.despawn_children(id, child_of)?;
+        world.despawn(id)?;
+    }
+
+    tracing::info!(%coord, "Unloaded cell");
+    Ok(())
+}
+
+fn streaming_system(assets: AssetCache) -> BoxedSystem {
+    let mut anchors = Query::new(world_transform().copied()).with(streaming_anchor());
+    let mut positions = Vec::new();
+
+    System::builder()
+        .with_world_mut()
+        .build(move |world: &mut World| {
+            positions.clear();
+            positions.extend(
+                anchors
+                    .borrow(world)
+                    .iter()
+                    .map(|v| v.transform_point3(Vec3::ZERO)),
+            );
+
+            let Ok(streamer) = world.get(engine(), level_streamer()) else {
+                return Ok(());
+            };
+
+            let (load, unload) = streamer.diff(&positions);
+            let serializer = streamer.serializer.clone();
+            drop(streamer);
+
+            let mut handles = Vec::new();
+            for cell in load {
+                let coord = cell.coord;
+                handles.push((coord, load_cell(world, &assets, serializer.clone(), cell)?));
+            }
+
+            let mut unloaded = Vec::new();
+            {
+                let mut streamer = world.get_mut(engine(), level_streamer())?;
+                for (coord, handle) in handles {
+                    streamer
+                        .cells
+                        .insert(coord.to_array(), CellState::Loading(handle));
+                }
+
+                unloaded.extend(unload.into_iter().filter(|&coord| streamer.remove(coord)));
+            }
+
+            for coord in unloaded {
+                unload_cell(world, coord)?;
+            }
+
+            anyhow::Ok(())
+        })
+        .boxed()
+}
+
+/// Streams the cells of a world around the [`streaming_anchor`]s
+pub struct LevelStreamingPlugin {
+    manifest: StreamingManifest,
+    serializer: Box<dyn Fn() -> SceneSerializer>,
+    distances: Option<(f32, f32)>,
+}
+
+impl LevelStreamingPlugin {
+    /// `serializer` creates the scene serializer used to read the cells
+    pub fn new(
+        manifest: StreamingManifest,
+        serializer: impl 'static + Fn() -> SceneSerializer,
+    ) -> Self {
+        Self {
+            manifest,
+            serializer: Box::new(serializer),
+            distances: None,
+        }
+    }
+
+    /// Set the load and unload distance, which default to one and one and a half cells
+    pub fn with_distances(mut self, load_distance: f32, unload_distance: f32) -> Self {
+        self.distances = Some((load_distance, unload_distance));
+        self
+    }
+}
+
+impl Plugin for LevelStreamingPlugin {
+    fn install(
+        &self,
+        world: &mut World,
+        assets: &AssetCache,
+        schedules: &mut ScheduleSetBuilder,
+    ) -> anyhow::Result<()> {
+        let mut streamer =
+            LevelStreamer::new(assets.insert(self.manifest.clone()), (self.serializer)());
+
+        if let Some((load, unload)) = self.distances {
+            streamer = streamer.with_distances(load, unload);
+        }
+
+        world.set(engine(), level_streamer(), streamer)?;
+        schedules
+            .per_tick_mut()
+            .with_system(streaming_system(assets.clone()));
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use flax::serialize::SerdeBuilder;
+    use ivy_core::{AsyncCommandBuffer, Tasks};
+
+    use super::*;
+
+    #[test]
+    fn cell_distance() {
+        let manifest = StreamingManifest::new(10.0);
+
+        assert_eq!(
+            manifest.cell_at(Vec3::new(5.0, 100.0, -5.0)),
+            IVec2::new(0, -1)
+        );
+        assert_eq!(
+            manifest.distance_to_cell(IVec2::new(0, 0), Vec3::new(5.0, 0.0, 5.0)),
+            0.0
+        );
+        assert_eq!(
+            manifest.distance_to_cell(IVec2::new(1, 0), Vec3::new(5.0, 0.0, 5.0)),
+            5.0
+        );
+        assert_eq!(
+            manifest.distance_to_cell(IVec2::new(-1, -1), Vec3::new(3.0, 0.0, 4.0)),
+            5.0
+        );
+    }
+
+    #[test]
+    fn manifest_validation() {
+        let manifest = StreamingManifest::from_ron(
+            r#"(
+                cell_size: 32.0,
+                cells: [
+                    (coord: (0, 0), scene: "cells/0_0.ron"),
+                    (coord: (1, 0), scene: "cells/1_0.ron", preload: ["models/tree.glb"]),
+                ],
+            )"#,
+        )
+        .unwrap();
+
+        assert_eq!(manifest.cells[1].preload, ["models/tree.glb"]);
+
+        assert!(StreamingManifest::from_ron(
+            r#"(cell_size: 32.0, cells: [(coord: (0, 0), scene: "a.ron"), (coord: (0, 0), scene: "b.ron")])"#
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn cell_states() {
+        let assets = AssetCache::new();
+        let manifest = StreamingManifest::new(10.0)
+            .with_cell(WorldCell {
+                coord: IVec2::new(0, 0),
+                scene: "cells/0_0.ron".into(),
+                preload: Vec::new(),
+            })
+            .with_cell(WorldCell {
+                coord: IVec2::new(1, 0),
+                scene: "cells/1_0.ron".into(),
+                preload: Vec::new(),
+            })
+            .with_cell(WorldCell {
+                coord: IVec2::new(3, 0),
+                scene: "cells/3_0.ron".into(),
+                preload: Vec::new(),
+            });
+
+        let serializer = SceneSerializer::new(&mut SerdeBuilder::new());
+        let mut streamer = LevelStreamer::new(assets.insert(manifest), serializer);
+
+        let near = [Vec3::new(5.0, 0.0, 5.0)];
+        let far = [Vec3::new(100.0, 0.0, 5.0)];
+
+        let (load, unload) = streamer.diff(&near);
+        let load = load.iter().map(|v| v.coord).collect::<Vec<_>>();
+        assert_eq!(load, [IVec2::new(0, 0), IVec2::new(1, 0)]);
+        assert!(unload.is_empty());
+
+        let tasks = Tasks::new(AsyncCommandBuffer::new());
+        let handle = tasks.spawn(std::future::pending::<()>(), |_, _| Ok(()));
+
+        streamer
+            .cells
+            .insert([0, 0], CellState::Loading(handle.clone()));
+        streamer.cells.insert([1, 0], CellState::Failed);
+        assert!(streamer.is_loading(IVec2::new(0, 0)));
+        assert!(streamer.is_failed(IVec2::new(1, 0)));
+
+        // Neither loading nor failed cells are loaded again
+        let (load, unload) = streamer.diff(&near);
+        assert!(load.is_empty());
+        assert!(unload.is_empty());
+
+        // Unloading while loading cancels the load
+        let (_, unload) = streamer.diff(&far);
+        assert_eq!(unload, [IVec2::new(0, 0), IVec2::new(1, 0)]);
+        assert!(!streamer.remove(IVec2::new(0, 0)));
+        assert!(handle.is_cancelled());
+        assert!(!streamer.remove(IVec2::new(1, 0)));
+
+        // The failed cell is retried after moving away and back
+        let (load, _) = streamer.diff(&near);
+        assert_eq!(load.len(), 2);
+
+        streamer.cells.insert(
+            [0, 0],
+            CellState::Loaded {
+                _preloaded: Vec::new(),
+            },
+        );
+        assert!(streamer.is_loaded(IVec2::new(0, 0)));
+        assert_eq!(streamer.loaded().collect::<Vec<_>>(), [IVec2::new(0, 0)]);
+        assert!(streamer.remove(IVec2::new(0, 0)));
+        assert!(!streamer.is_loaded(IVec2::new(0, 0)));
+    }
+}
@@ -0,0 +1,55 @@
+// `depth_texture` and `load_depth` are prepended depending on the format of the depth buffer
+
+struct VertexOutput {
+    @builtin(position) pos: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+    @location(1) clip_pos: vec4<f32>,
+}
+
+@group(0) @binding(0)
+var<uniform> mvp: mat4x4<f32>;
+
+@group(0) @binding(1)
+var panel_texture: texture_2d<f32>;
+
+@group(0) @binding(2)
+var panel_sampler: sampler;
+
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> VertexOutput {
+    var corners = array(
+        vec2(0f, 0f),
+        vec2(0f, 1f),
+        vec2(1f, 1f),
+        vec2(0f, 0f),
+        vec2(1f, 1f),
+        vec2(1f, 0f),
+    );
+
+    let uv = corners[index];
+
+    var out: VertexOutput;
+    // Unit quad in the XY plane, with the top of the texture along +Y
+    out.pos = mvp * vec4(uv.x - 0.5, 0.5 - uv.y, 0f, 1f);
+    out.uv = uv;
+    out.clip_pos = out.pos;
+
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let color = textureSample(panel_texture, panel_sampler, in.uv);
+
+    // The depth buffer covers the viewport of the view rather than the whole output
+    let ndc = in.clip_pos.xy / in.clip_pos.w;
+    let depth_uv = vec2(ndc.x + 1f, 1f - ndc.y) * 0.5;
+    let depth_size = vec2<i32>(textureDimensions(depth_texture));
+    let depth_pos = clamp(vec2<i32>(depth_uv * vec2<f32>(depth_size)), vec2(0), depth_size - 1);
+
+    if in.pos.z > load_depth(depth_pos) {
+        discard;
+    }
+
+    return color;
+}
@@ -0,0 +1,223 @@
+use std::sync::Arc;
+
+use anyhow::Context;
+use flax::{
+    component::ComponentValue, entity_ids, filter::ChangeFilter, CommandBuffer, Component, Entity,
+    EntityIds, Query, World,
+};
+use ivy_core::components::engine;
+use parking_lot::Mutex;
+use violet::futures_signals::signal::{Mutable, Signal};
+
+use crate::components::ui_bindings;
+
+trait Binding: Send {
+    /// Returns false once all handles of the binding have been dropped
+    fn sync(&mut self, world: &World, cmd: &mut CommandBuffer) -> bool;
+}
+
+struct BindingState<T: ComponentValue> {
+    id: Entity,
+    component: Component<T>,
+    value: Mutable<Option<T>>,
+    edits: flume::Receiver<T>,
+    changed: Query<(EntityIds, ChangeFilter<T>)>,
+}
+
+impl<T: ComponentValue + Clone + PartialEq> Binding for BindingState<T> {
+    fn sync(&mut self, world: &World, cmd: &mut CommandBuffer) -> bool {
+        let alive = world.is_alive(self.id);
+
+        let mut edited = false;
+        for value in self.edits.drain() {
+            // Edits of a despawned entity are dropped, rather than failing the command buffer
+            if alive {
+                cmd.set(self.id, self.component, value);
+                edited = true;
+            }
+        }
+
+        if self.edits.is_disconnected() {
+            return false;
+        }
+
+        let modified = self
+            .changed
+            .borrow(world)
+            .iter()
+            .any(|(id, _)| id == self.id);
+
+        // Removing the component or despawning the entity is not a modification
+        let present = world.has(self.id, self.component);
+
+        // Keep the edited value until the command has been applied
+        if !edited && (modified || present != self.value.lock_ref().is_some()) {
+            let current = world.get(self.id, self.component).ok().map(|v| v.clone());
+            if *self.value.lock_ref() != current {
+                self.value.set(current);
+            }
+        }
+
+        true
+    }
+}
+
+/// Value of a component on an entity, exposed to the Ui as a signal.
+///
+/// The value is `None` while the entity or component is missing. The binding is removed once
+/// all clones of it have been dropped.
+pub struct UiBinding<T> {
+    value: Mutable<Option<T>>,
+    edits: flume::Sender<T>,
+}
+
+impl<T> Clone for UiBinding<T> {
+    fn clone(&self) -> Self {
+        Self {
+            value: self.value.clone(),
+            edits: self.edits.clone(),
+        }
+    }
+}
+
+impl<T: Clone> UiBinding<T> {
+    /// Returns a signal of the current value
+    pub fn signal(&self) -> impl 'static + Send + Signal<Item = Option<T>>
+    where
+        T: 'static + Send + Sync,
+    {
+        self.value.signal_cloned()
+    }
+
+    pub fn get(&self) -> Option<T> {
+        self.value.get_cloned()
+    }
+
+    /// Writes a new value to the component.
+    ///
+    /// The value is applied to the world through a command buffer during the next Ui update.
+    pub fn set(&self, value: T) {
+        self.value.set(Some(value.clone()));
+        self.edits.send(value).ok();
+    }
+}
+
+/// Bindings between components and the Ui, synchronized each tick by the
+/// [`UiUpdateLayer`](crate::layer::UiUpdateLayer)
+#[derive(Default, Clone)]
+pub struct UiBindings {
+    bindings: Arc<Mutex<Vec<Box<dyn Binding>>>>,
+}
+
+impl UiBindings {
+    /// Binds `component` on `id`, the value is available after the next update
+    pub fn bind<T: ComponentValue + Clone + PartialEq>(
+        &self,
+        id: Entity,
+        component: Component<T>,
+    ) -> UiBinding<T> {
+        let value = Mutable::new(None);
+        let (tx, rx) = flume::unbounded();
+
+        self.bindings.lock().push(Box::new(BindingState {
+            id,
+            component,
+            value: value.clone(),
+            edits: rx,
+            changed: Query::new((entity_ids(), component.modified())),
+        }));
+
+        UiBinding { value, edits: tx }
+    }
+
+    /// Publishes the values modified since the last sync to the Ui, and applies the edits made
+    /// by it
+    pub fn sync(&self, world: &mut World) -> anyhow::Result<()> {
+        let mut cmd = CommandBuffer::new();
+        self.bindings.lock().retain_mut(|v| v.sync(world, &mut cmd));
+
+        if let Err(err) = cmd.apply(world) {
+            tracing::error!("Failed to apply Ui binding edits: {err:?}");
+        }
+
+        Ok(())
+    }
+}
+
+/// Exposes the value of `component` on `id` as a signal, which is updated without rebuilding
+/// the widgets that depend on it.
+///
+/// Edits made through [`UiBinding::set`] are written back to the world.
+pub fn ui_bind<T: ComponentValue + Clone + PartialEq>(
+    world: &World,
+    id: Entity,
+    component: Component<T>,
+) -> anyhow::Result<UiBinding<T>> {
+    let bindings = world
+        .get(engine(), ui_bindings())
+        .context("Missing Ui bindings, add the UiUpdateLayer")?;
+
+    let binding = bindings.bind(id, component);
+    binding
+        .value
+        .set(world.get(id, component).ok().map(|v| v.clone()));
+
+    Ok(binding)
+}
+
+#[cfg(test)]
+mod test {
+    use flax::component;
+
+    use super::*;
+
+    component! {
+        health: i32,
+    }
+
+    #[test]
+    fn sync_modified() {
+        let mut world = World::new();
+        let id = Entity::builder().set(health(), 10).spawn(&mut world);
+
+        let bindings = UiBindings::default();
+        let binding = bindings.bind(id, health());
+        assert_eq!(binding.get(), None);
+
+        bindings.sync(&mut world).unwrap();
+        assert_eq!(binding.get(), Some(10));
+
+        *world.get_mut(id, health()).unwrap() = 5;
+        bindings.sync(&mut world).unwrap();
+        assert_eq!(binding.get(), Some(5));
+
+        world.remove(id, health()).unwrap();
+        bindings.sync(&mut world).unwrap();
+        assert_eq!(binding.get(), None);
+    }
+
+    #[test]
+    fn edits() {
+        let mut world = World::new();
+        let id = Entity::builder().set(health(), 10).spawn(&mut world);
+
+        let bindings = UiBindings::default();
+        let binding = bindings.bind(id, health());
+
+        binding.set(3);
+        assert_eq!(binding.get(), Some(3));
+
+        bindings.sync(&mut world).unwrap();
+        assert_eq!(world.get_copy(id, health()).unwrap(), 3);
+
+        // Editing a despawned entity does not fail the sync
+        world.despawn(id).unwrap();
+        binding.set(7);
+        bindings.sync(&mut world).unwrap();
+        assert_eq!(binding.get(), None);
+
+        drop(binding);
+        bindings.sync(&mut world).unwrap();
+        assert!(bindings.bindings.lock().is_empty());
+    }
+}
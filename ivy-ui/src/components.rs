@@ -1,10 +1,27 @@
 use flax::{component, World};
-use ivy_assets::AssetCache;
+use ivy_assets::{stored::Handle, AssetCache};
 use ivy_input::types::InputEvent;
 use ivy_wgpu::rendergraph::TextureHandle;
 use violet::core::ScopeRef;
 
+use crate::{bind::UiBindings, world_ui::WorldUi, SharedUiInstance};
+
 component! {
     pub texture_dependency: TextureHandle,
     pub on_input_event: Box<dyn Send + Sync + FnMut(&ScopeRef<'_>, &mut World, &AssetCache, &InputEvent) -> anyhow::Result<()>>,
+    /// Mounts a Ui onto a quad in the world, see [`WorldUi`]
+    pub world_ui: WorldUi,
+
+    /// Multiplier of the size of the Ui, on top of the scale factor of the window.
+    ///
+    /// Set on the engine entity.
+    pub ui_scale: f64,
+
+    /// Set on the engine entity by the [`UiUpdateLayer`](crate::layer::UiUpdateLayer)
+    pub ui_bindings: UiBindings,
+
+    /// The Ui drawn by the renderer, in the store of the app.
+    ///
+    /// Set on the engine entity by the [`UiPlugin`](crate::UiPlugin)
+    pub ui_instance: Handle<SharedUiInstance>,
 }
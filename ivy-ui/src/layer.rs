@@ -3,22 +3,29 @@ use std::{cell::RefCell, convert::identity, ops::Deref, rc::Rc};
 use flax::World;
 use ivy_assets::AssetCache;
 use ivy_core::{
-    app::TickEvent, components::request_capture_mouse, layer::events::EventRegisterContext,
-    profiling::profile_function, Layer, WorldExt,
+    app::TickEvent,
+    components::{engine, request_capture_mouse},
+    layer::events::EventRegisterContext,
+    profiling::profile_function,
+    Layer, PhysicalExtent, WorldExt,
 };
 use ivy_input::types::InputEvent;
 use ivy_wgpu::{
-    components::{main_window, window},
+    components::{main_window, window, window_physical_size},
     driver::WindowHandle,
     events::{ApplicationReady, ResizedEvent},
 };
 use violet::{
     core::{declare_atom, ScopeRef, Widget},
-    glam::vec2,
+    glam::{vec2, Vec2},
     wgpu::app::AppInstance,
 };
 
-use crate::{components::on_input_event, SharedUiInstance};
+use crate::{
+    bind::UiBindings,
+    components::{on_input_event, ui_bindings},
+    scaled_size, ui_scale_factor, SharedUiInstance,
+};
 
 pub type Action = Box<dyn Send + Sync + FnOnce(&mut World, &AssetCache) -> anyhow::Result<()>>;
 
@@ -44,6 +51,8 @@ declare_atom! {
 pub struct UiInputLayer {
     instance: Rc<RefCell<AppInstance>>,
     window: Option<WindowHandle>,
+    /// Size of the Ui root, in Ui units
+    size: Vec2,
 }
 
 impl UiInputLayer {
@@ -54,6 +63,7 @@ impl UiInputLayer {
         Self {
             instance,
             window: None,
+            size: Vec2::ONE,
         }
     }
 
@@ -62,6 +72,10 @@ impl UiInputLayer {
 
         if let Some(main_window) = main_window {
             self.window = Some(main_window.get(window())?.clone());
+
+            let size = main_window.get_copy(window_physical_size())?;
+            let size = scaled_size(size, ui_scale_factor(engine_world));
+            self.size = vec2(size.width as f32, size.height as f32);
         }
 
         Ok(())
@@ -98,13 +112,15 @@ impl UiInputLayer {
                 mouse_input.state,
                 mouse_input.button,
             ),
-            InputEvent::CursorMoved(cursor_moved) => instance.input_state.on_cursor_move(
-                &mut instance.frame,
-                vec2(
-                    cursor_moved.absolute_position.x,
-                    cursor_moved.absolute_position.y,
-                ),
-            ),
+            InputEvent::CursorMoved(cursor_moved) => {
+                // Map the normalized position onto the Ui root, which is independent of the units
+                // of the absolute position
+                let normalized = cursor_moved.normalized_position;
+                instance.input_state.on_cursor_move(
+                    &mut instance.frame,
+                    vec2(normalized.x, normalized.y) * self.size,
+                )
+            }
             InputEvent::CursorDelta(_) => false,
             InputEvent::CursorLeft => false,
             InputEvent::CursorEntered => false,
@@ -138,13 +154,15 @@ impl UiInputLayer {
 
     fn on_resized(
         &mut self,
-        _: &mut World,
+        world: &mut World,
         _: &AssetCache,
         event: &ResizedEvent,
     ) -> anyhow::Result<()> {
         let mut instance = self.instance.deref().borrow_mut();
 
-        instance.on_resize(event.physical_size);
+        let size = scaled_size(event.physical_size, ui_scale_factor(world));
+        self.size = vec2(size.width as f32, size.height as f32);
+        instance.on_resize(size);
         Ok(())
     }
 
@@ -181,6 +199,8 @@ impl Layer for UiInputLayer {
 pub struct UiUpdateLayer {
     instance: Rc<RefCell<AppInstance>>,
     pending_actions: flume::Receiver<Action>,
+    size: PhysicalExtent,
+    scale_factor: f64,
 }
 
 impl UiUpdateLayer {
@@ -194,6 +214,8 @@ impl UiUpdateLayer {
         Self {
             instance,
             pending_actions: rx,
+            size: PhysicalExtent::default(),
+            scale_factor: 1.0,
         }
     }
 
@@ -206,6 +228,17 @@ impl UiUpdateLayer {
 
         let mut instance = self.instance.deref().borrow_mut();
 
+        if let Ok(bindings) = world.get(engine(), ui_bindings()).map(|v| v.clone()) {
+            bindings.sync(world)?;
+        }
+
+        // Relayout when the window moves to another display, or the user scale changes
+        let scale_factor = ui_scale_factor(world);
+        if scale_factor != self.scale_factor && !self.size.is_empty() {
+            self.scale_factor = scale_factor;
+            instance.on_resize(scaled_size(self.size, scale_factor));
+        }
+
         instance.update();
 
         for action in self.pending_actions.drain() {
@@ -217,13 +250,15 @@ impl UiUpdateLayer {
 
     fn on_resized(
         &mut self,
-        _: &mut World,
+        world: &mut World,
         _: &AssetCache,
         event: &ResizedEvent,
     ) -> anyhow::Result<()> {
         let mut instance = self.instance.deref().borrow_mut();
 
-        instance.on_resize(event.physical_size);
+        self.size = event.physical_size;
+        self.scale_factor = ui_scale_factor(world);
+        instance.on_resize(scaled_size(self.size, self.scale_factor));
         Ok(())
     }
 
@@ -236,13 +271,15 @@ impl UiUpdateLayer {
 impl Layer for UiUpdateLayer {
     fn register(
         &mut self,
-        _: &mut World,
+        world: &mut World,
         _: &AssetCache,
         mut events: EventRegisterContext<Self>,
     ) -> anyhow::Result<()>
     where
         Self: Sized,
     {
+        world.set(engine(), ui_bindings(), UiBindings::default())?;
+
         events.subscribe(|this, ctx, _: &ApplicationReady| this.on_ready(ctx.world, ctx.assets));
 
         events.subscribe(|this, ctx, _: &TickEvent| this.on_tick(ctx.world, ctx.assets));
@@ -1,10 +1,47 @@
 use std::{cell::RefCell, rc::Rc};
 
+use flax::World;
+use ivy_core::{components::engine, PhysicalExtent, WorldExt};
+use ivy_wgpu::{
+    components::{main_window, window_scale_factor},
+    types::PhysicalSize,
+};
 use violet::wgpu::app::AppInstance;
 
+pub mod bind;
 pub mod components;
 pub mod image;
 pub mod layer;
 pub mod node;
+mod plugin;
+pub mod world_ui;
+
+pub use plugin::UiPlugin;
 
 pub type SharedUiInstance = Rc<RefCell<AppInstance>>;
+
+/// Returns the physical pixels per Ui unit, combining the scale factor of the main window with
+/// the user [`ui_scale`](components::ui_scale)
+pub fn ui_scale_factor(world: &World) -> f64 {
+    let window_scale = world
+        .by_tag(main_window())
+        .and_then(|v| v.get_copy(window_scale_factor()).ok())
+        .unwrap_or(1.0);
+
+    window_scale * user_ui_scale(world)
+}
+
+/// Returns the user [`ui_scale`](components::ui_scale), or `1.0` if unset
+pub fn user_ui_scale(world: &World) -> f64 {
+    world
+        .get_copy(engine(), components::ui_scale())
+        .unwrap_or(1.0)
+}
+
+/// Returns the size of the Ui root, in Ui units, for a surface of `size` physical pixels
+pub fn scaled_size(size: PhysicalExtent, scale_factor: f64) -> PhysicalSize<u32> {
+    PhysicalSize::new(
+        (size.width as f64 / scale_factor).round() as u32,
+        (size.height as f64 / scale_factor).round() as u32,
+    )
+}
@@ -17,7 +17,7 @@ use violet::{
 };
 use wgpu::{TextureUsages, TextureView};
 
-use crate::{components::texture_dependency, SharedUiInstance};
+use crate::{components::texture_dependency, ui_scale_factor, SharedUiInstance};
 
 type TextureDepFetch = (
     Component<TextureHandle>,
@@ -132,7 +132,7 @@ impl Node for UiRenderNode {
                 width: target.size().width,
                 height: target.size().height,
             },
-            1.0,
+            ui_scale_factor(ctx.world),
         );
 
         renderer.update(&mut self.ctx, &mut instance.frame)?;
@@ -0,0 +1,52 @@
+use ivy_core::{components::engine, App, AppPlugin};
+use ivy_input::layer::InputLayer;
+use violet::core::Widget;
+
+use crate::{
+    components::ui_instance,
+    layer::{UiInputLayer, UiUpdateLayer},
+    SharedUiInstance,
+};
+
+/// Adds a Ui to the app, which is rendered by the renderer on top of the scene.
+///
+/// Input reaches the Ui before the [`InputLayer`], and the Ui is updated after the layers added
+/// before the plugin.
+pub struct UiPlugin {
+    input: UiInputLayer,
+}
+
+impl UiPlugin {
+    pub fn new(root: impl Widget) -> Self {
+        Self {
+            input: UiInputLayer::new(root),
+        }
+    }
+
+    pub fn instance(&self) -> &SharedUiInstance {
+        self.input.instance()
+    }
+}
+
+impl AppPlugin for UiPlugin {
+    fn build(self, app: &mut App) -> anyhow::Result<()> {
+        let instance = self.input.instance().clone();
+
+        let handle = app
+            .store_mut()
+            .store_mut::<SharedUiInstance>()
+            .insert(instance.clone());
+        app.world_mut().set(engine(), ui_instance(), handle)?;
+
+        let input_layer = std::any::type_name::<InputLayer>();
+        if app.layer_index(input_layer).is_some() {
+            app.insert_layer_before(input_layer, self.input)?;
+        } else {
+            app.push_layer(self.input);
+        }
+
+        app.push_layer(UiUpdateLayer::new(instance));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,815 @@
+use std::{cell::RefCell, collections::BTreeMap, ops::Deref, rc::Rc};
+
+use flax::{entity_ids, Entity, Query, World};
+use glam::{Mat4, UVec2, Vec2, Vec3};
+use itertools::Itertools;
+use ivy_assets::AssetCache;
+use ivy_core::{
+    app::TickEvent, components::world_transform, layer::events::EventRegisterContext,
+    profiling::profile_function, Layer, PhysicalExtent,
+};
+use ivy_input::types::InputEvent;
+use ivy_wgpu::{
+    camera::{screen_to_ndc, Camera},
+    camera_target::{CameraSelection, Viewport},
+    renderer::get_camera_data,
+    rendergraph::{
+        Dependency, Node, NodeExecutionContext, NodeUpdateContext, TextureHandle, UpdateResult,
+    },
+    types::{
+        shader::{ShaderDesc, TargetDesc},
+        BindGroupBuilder, BindGroupLayoutBuilder, PhysicalSize, RenderShader, TypedBuffer,
+    },
+    Gpu,
+};
+use violet::{
+    core::Widget,
+    wgpu::{
+        app::AppInstance,
+        renderer::{MainRenderer, MainRendererConfig, RendererContext},
+    },
+};
+use wgpu::{
+    BufferUsages, RenderPassColorAttachment, RenderPassDescriptor, SamplerDescriptor, ShaderStages,
+    TextureFormat, TextureUsages,
+};
+
+use crate::components::world_ui;
+
+const PANEL_FORMAT: TextureFormat = TextureFormat::Rgba8UnormSrgb;
+
+/// Widget tree rendered onto a quad in the world, for in-world screens, holograms and menus.
+///
+/// The quad is centered on the entity and spans `size` along its local X and Y axes, facing +Z.
+pub struct WorldUi {
+    mount: Box<dyn Send + Sync + Fn() -> AppInstance>,
+    resolution: UVec2,
+    size: Vec2,
+    interactive: bool,
+}
+
+impl WorldUi {
+    /// Creates a panel of `resolution` pixels, mounting the widget returned by `widget`.
+    pub fn new<W: Widget>(
+        widget: impl 'static + Send + Sync + Fn() -> W,
+        resolution: UVec2,
+        size: Vec2,
+    ) -> Self {
+        Self {
+            mount: Box::new(move || AppInstance::new(widget(), false)),
+            resolution,
+            size,
+            interactive: false,
+        }
+    }
+
+    /// Receive cursor and keyboard input through rays cast from the main camera
+    pub fn with_interactive(mut self, interactive: bool) -> Self {
+        self.interactive = interactive;
+        self
+    }
+
+    pub fn resolution(&self) -> UVec2 {
+        self.resolution
+    }
+
+    pub fn size(&self) -> Vec2 {
+        self.size
+    }
+
+    pub fn interactive(&self) -> bool {
+        self.interactive
+    }
+}
+
+/// Ui instance mounted for an entity with [`world_ui`]
+pub struct WorldUiPanel {
+    pub instance: AppInstance,
+    resolution: UVec2,
+    size: Vec2,
+    interactive: bool,
+}
+
+impl WorldUiPanel {
+    fn new(desc: &WorldUi) -> Self {
+        let mut instance = (desc.mount)();
+        instance.on_resize(PhysicalSize::new(desc.resolution.x, desc.resolution.y));
+
+        Self {
+            instance,
+            resolution: desc.resolution,
+            size: desc.size,
+            interactive: desc.interactive,
+        }
+    }
+
+    /// Returns the distance along the ray and the pixel it hits, if any
+    fn hit(&self, transform: Mat4, origin: Vec3, dir: Vec3) -> Option<(f32, Vec2)> {
+        panel_hit(transform, self.size, self.resolution, origin, dir)
+    }
+}
+
+/// Intersects a ray with a panel of `size` and `resolution` placed by `transform`, returning the
+/// distance along the ray and the pixel it hits
+fn panel_hit(
+    transform: Mat4,
+    size: Vec2,
+    resolution: UVec2,
+    origin: Vec3,
+    dir: Vec3,
+) -> Option<(f32, Vec2)> {
+    let inv = transform.inverse();
+    let local_origin = inv.transform_point3(origin);
+    let local_dir = inv.transform_vector3(dir);
+
+    if local_dir.z.abs() < 1e-6 {
+        return None;
+    }
+
+    let t = -local_origin.z / local_dir.z;
+    if t < 0.0 {
+        return None;
+    }
+
+    let p = local_origin + local_dir * t;
+    let uv = Vec2::new(p.x / size.x + 0.5, 0.5 - p.y / size.y);
+
+    if uv.cmplt(Vec2::ZERO).any() || uv.cmpgt(Vec2::ONE).any() {
+        return None;
+    }
+
+    let distance = transform.transform_point3(p).distance(origin);
+    Some((distance, uv * resolution.as_vec2()))
+}
+
+/// Mounted world space panels, shared between [`WorldUiLayer`] and [`WorldUiRenderNode`]
+#[derive(Default)]
+pub struct WorldUiPanels {
+    panels: BTreeMap<Entity, WorldUiPanel>,
+}
+
+impl WorldUiPanels {
+    pub fn get(&self, id: Entity) -> Option<&WorldUiPanel> {
+        self.panels.get(&id)
+    }
+
+    pub fn get_mut(&mut self, id: Entity) -> Option<&mut WorldUiPanel> {
+        self.panels.get_mut(&id)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (Entity, &WorldUiPanel)> {
+        self.panels.iter().map(|(&k, v)| (k, v))
+    }
+}
+
+pub type SharedWorldUi = Rc<RefCell<WorldUiPanels>>;
+
+/// Mounts, updates and forwards input to the [`world_ui`] panels.
+///
+/// Add before [`UiInputLayer`](crate::layer::UiInputLayer), as it captures all input.
+pub struct WorldUiLayer {
+    panels: SharedWorldUi,
+    hovered: Option<Entity>,
+    focused: Option<Entity>,
+}
+
+impl WorldUiLayer {
+    pub fn new() -> Self {
+        Self {
+            panels: Default::default(),
+            hovered: None,
+            focused: None,
+        }
+    }
+
+    fn on_tick(&mut self, world: &mut World, _: &AssetCache) -> anyhow::Result<()> {
+        profile_function!();
+
+        let mut panels = self.panels.deref().borrow_mut();
+        let panels = &mut panels.panels;
+        panels.retain(|&id, _| world.has(id, world_ui()));
+
+        for (id, desc) in Query::new((entity_ids(), world_ui())).borrow(world).iter() {
+            let panel = panels.entry(id).or_insert_with(|| WorldUiPanel::new(desc));
+
+            if panel.resolution != desc.resolution {
+                panel.resolution = desc.resolution;
+                panel
+                    .instance
+                    .on_resize(PhysicalSize::new(desc.resolution.x, desc.resolution.y));
+            }
+
+            panel.size = desc.size;
+            panel.interactive = desc.interactive;
+        }
+
+        for panel in panels.values_mut() {
+            panel.instance.update();
+        }
+
+        Ok(())
+    }
+
+    fn on_input_event(
+        &mut self,
+        world: &mut World,
+        _: &AssetCache,
+        event: &InputEvent,
+    ) -> anyhow::Result<bool> {
+        profile_function!();
+
+        let panels = &mut *self.panels.deref().borrow_mut();
+
+        let captured = match event {
+            InputEvent::CursorMoved(cursor_moved) => {
+                let hit = cursor_hit(world, panels, cursor_moved.normalized_position);
+
+                // Move the cursor out of the previously hovered panel
+                if let Some(panel) = self
+                    .hovered
+                    .filter(|&id| hit.map(|v| v.0) != Some(id))
+                    .and_then(|id| panels.get_mut(id))
+                {
+                    let instance = &mut panel.instance;
+                    instance
+                        .input_state
+                        .on_cursor_move(&mut instance.frame, violet::glam::vec2(-1.0, -1.0));
+                }
+
+                self.hovered = hit.map(|v| v.0);
+
+                match hit.and_then(|(id, pos)| Some((panels.get_mut(id)?, pos))) {
+                    Some((panel, pos)) => {
+                        let instance = &mut panel.instance;
+                        instance
+                            .input_state
+                            .on_cursor_move(&mut instance.frame, violet::glam::vec2(pos.x, pos.y))
+                    }
+                    None => false,
+                }
+            }
+            InputEvent::MouseButton(mouse_input) => {
+                self.focused = self.hovered;
+
+                match self.hovered.and_then(|id| panels.get_mut(id)) {
+                    Some(panel) => {
+                        let instance = &mut panel.instance;
+                        instance.input_state.on_mouse_input(
+                            &mut instance.frame,
+                            mouse_input.state,
+                            mouse_input.button,
+                        )
+                    }
+                    None => false,
+                }
+            }
+            InputEvent::Scroll(scroll_motion) => {
+                match self.hovered.and_then(|id| panels.get_mut(id)) {
+                    Some(panel) => {
+                        let instance = &mut panel.instance;
+                        instance
+                            .input_state
+                            .on_scroll(&mut instance.frame, scroll_motion.delta)
+                    }
+                    None => false,
+                }
+            }
+            InputEvent::Keyboard(keyboard_input) => {
+                match self.focused.and_then(|id| panels.get_mut(id)) {
+                    Some(panel) => {
+                        let instance = &mut panel.instance;
+                        instance.input_state.on_keyboard_input(
+                            &mut instance.frame,
+                            keyboard_input.key.clone(),
+                            keyboard_input.state,
+                            keyboard_input.text.clone(),
+                        )
+                    }
+                    None => false,
+                }
+            }
+            _ => false,
+        };
+
+        Ok(captured)
+    }
+
+    /// Panels to draw with a [`WorldUiRenderNode`]
+    pub fn panels(&self) -> &SharedWorldUi {
+        &self.panels
+    }
+}
+
+impl Default for WorldUiLayer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Layer for WorldUiLayer {
+    fn register(
+        &mut self,
+        _: &mut World,
+        _: &AssetCache,
+        mut events: EventRegisterContext<Self>,
+    ) -> anyhow::Result<()>
+    where
+        Self: Sized,
+    {
+        events.subscribe(|this, ctx, _: &TickEvent| this.on_tick(ctx.world, ctx.assets));
+
+        events.intercept(|this, ctx, event: &InputEvent| {
+            this.on_input_event(ctx.world, ctx.assets, event)
+        });
+
+        Ok(())
+    }
+}
+
+/// Returns the closest interactive panel under the cursor and the pixel it hits
+fn cursor_hit(world: &World, panels: &WorldUiPanels, cursor_pos: Vec2) -> Option<(Entity, Vec2)> {
+    let camera = Camera::main(world)?;
+    let (origin, dir) = camera.screen_to_ray(screen_to_ndc(cursor_pos));
+
+    panels
+        .iter()
+        .filter(|(_, panel)| panel.interactive)
+        .filter_map(|(id, panel)| {
+            let transform = world.get_copy(id, world_transform()).ok()?;
+            let (distance, pos) = panel.hit(transform, origin, dir)?;
+            Some((id, distance, pos))
+        })
+        .min_by(|a, b| a.1.total_cmp(&b.1))
+        .map(|(id, _, pos)| (id, pos))
+}
+
+/// View of a camera which the panels are drawn into
+#[derive(Debug, Clone)]
+pub struct WorldUiView {
+    camera: CameraSelection,
+    depth_buffer: TextureHandle,
+    /// Viewport of the output and the input of the final pass of the view drawn into it
+    viewport: Option<(Viewport, TextureHandle)>,
+}
+
+impl WorldUiView {
+    /// Draws the panels as seen by `camera`, occluded by its `depth_buffer`.
+    ///
+    /// The depth buffer may either be a depth texture or a resolved `R32Float` depth.
+    pub fn new(camera: impl Into<CameraSelection>, depth_buffer: TextureHandle) -> Self {
+        Self {
+            camera: camera.into(),
+            depth_buffer,
+            viewport: None,
+        }
+    }
+
+    /// Draw into the viewport of a split-screen view of the output.
+    ///
+    /// `view_input` is the input of the final pass of the view, which draws the view into the
+    /// viewport. Reading it orders the panels after the view.
+    pub fn with_viewport(mut self, viewport: Viewport, view_input: TextureHandle) -> Self {
+        self.viewport = Some((viewport, view_input));
+        self
+    }
+}
+
+/// Transform of a panel for a single view
+struct PanelView {
+    buffer: TypedBuffer<Mat4>,
+    bind_group: wgpu::BindGroup,
+    visible: bool,
+}
+
+/// Texture and renderer of a single panel
+struct PanelTarget {
+    resolution: UVec2,
+    view: wgpu::TextureView,
+    ctx: RendererContext,
+    renderer: Option<MainRenderer>,
+    views: Vec<PanelView>,
+}
+
+impl PanelTarget {
+    fn new(
+        gpu: &Gpu,
+        layout: &wgpu::BindGroupLayout,
+        sampler: &wgpu::Sampler,
+        resolution: UVec2,
+        view_count: usize,
+    ) -> Self {
+        let texture = gpu.device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("world_ui"),
+            size: wgpu::Extent3d {
+                width: resolution.x.max(1),
+                height: resolution.y.max(1),
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: PANEL_FORMAT,
+            usage: TextureUsages::RENDER_ATTACHMENT | TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        });
+
+        let view = texture.create_view(&Default::default());
+
+        let views = (0..view_count)
+            .map(|_| {
+                let buffer = TypedBuffer::new(
+                    gpu,
+                    "world_ui",
+                    BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+                    &[Mat4::IDENTITY],
+                );
+
+                let bind_group = BindGroupBuilder::new("world_ui")
+                    .bind_buffer(&buffer)
+                    .bind_texture(&view)
+                    .bind_sampler(sampler)
+                    .build(gpu, layout);
+
+                PanelView {
+                    buffer,
+                    bind_group,
+                    visible: false,
+                }
+            })
+            .collect();
+
+        Self {
+            resolution,
+            view,
+            ctx: RendererContext::new(violet::wgpu::Gpu {
+                adapter: gpu.adapter.clone(),
+                device: gpu.device.clone(),
+                queue: gpu.queue.clone(),
+            }),
+            renderer: None,
+            views,
+        }
+    }
+}
+
+/// Renders each [`world_ui`] panel into a texture, and draws them as quads into `output` for each
+/// view.
+///
+/// The panels are only rendered once, regardless of the number of views.
+pub struct WorldUiRenderNode {
+    panels: SharedWorldUi,
+    targets: BTreeMap<Entity, PanelTarget>,
+    layout: wgpu::BindGroupLayout,
+    depth_layout: Option<wgpu::BindGroupLayout>,
+    shader: Option<RenderShader>,
+    sampler: wgpu::Sampler,
+    output: TextureHandle,
+    views: Vec<WorldUiView>,
+}
+
+impl WorldUiRenderNode {
+    pub fn new(
+        gpu: &Gpu,
+        panels: SharedWorldUi,
+        output: TextureHandle,
+        views: Vec<WorldUiView>,
+    ) -> Self {
+        let layout = BindGroupLayoutBuilder::new("world_ui")
+            .bind_uniform_buffer(ShaderStages::VERTEX)
+            .bind_texture(ShaderStages::FRAGMENT)
+            .bind_sampler(ShaderStages::FRAGMENT)
+            .build(gpu);
+
+        let sampler = gpu.device.create_sampler(&SamplerDescriptor {
+            label: Some("world_ui_sampler"),
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+
+        Self {
+            panels,
+            targets: BTreeMap::new(),
+            layout,
+            depth_layout: None,
+            shader: None,
+            sampler,
+            output,
+            views,
+        }
+    }
+}
+
+impl Node for WorldUiRenderNode {
+    fn update(&mut self, ctx: NodeUpdateContext) -> anyhow::Result<UpdateResult> {
+        let panels = self.panels.deref().borrow();
+
+        self.targets.retain(|&id, _| panels.get(id).is_some());
+
+        let viewprojs = self
+            .views
+            .iter()
+            .map(|view| {
+                let camera = ctx.world.entity(view.camera.find(ctx.world)?).ok()?;
+                Some(get_camera_data(&camera).viewproj)
+            })
+            .collect_vec();
+
+        for (id, panel) in panels.iter() {
+            let new_target = || {
+                PanelTarget::new(
+                    ctx.gpu,
+                    &self.layout,
+                    &self.sampler,
+                    panel.resolution,
+                    self.views.len(),
+                )
+            };
+
+            let target = self.targets.entry(id).or_insert_with(&new_target);
+            if target.resolution != panel.resolution {
+                *target = new_target();
+            }
+
+            let transform = ctx.world.get_copy(id, world_transform()).ok();
+
+            for (view, viewproj) in target.views.iter_mut().zip(&viewprojs) {
+                view.visible = false;
+                if let (Some(viewproj), Some(transform)) = (viewproj, transform) {
+                    let world = transform * Mat4::from_scale(panel.size.extend(1.0));
+                    view.buffer.write(&ctx.gpu.queue, 0, &[*viewproj * world]);
+                    view.visible = true;
+                }
+            }
+        }
+
+        Ok(UpdateResult::Success)
+    }
+
+    fn draw(&mut self, ctx: NodeExecutionContext) -> anyhow::Result<()> {
+        if self.targets.is_empty() {
+            return Ok(());
+        }
+
+        let panels = &mut *self.panels.deref().borrow_mut();
+
+        for (&id, target) in &mut self.targets {
+            let Some(panel) = panels.get_mut(id) else {
+                continue;
+            };
+
+            let instance = &mut panel.instance;
+
+            // Clear, as the Ui is drawn on top of the previous contents of the texture
+            ctx.encoder.begin_render_pass(&RenderPassDescriptor {
+                label: Some("world_ui_clear"),
+                color_attachments: &[Some(RenderPassColorAttachment {
+                    view: &target.view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: None,
+                ..Default::default()
+            });
+
+            let size = target.resolution.as_vec2();
+            target.ctx.globals.projview =
+                violet::glam::Mat4::orthographic_lh(0.0, size.x, size.y, 0.0, 0.0, 1000.0);
+            target
+                .ctx
+                .globals_buffer
+                .write(&target.ctx.gpu.queue, 0, &[target.ctx.globals]);
+
+            let renderer = target.renderer.get_or_insert_with(|| {
+                let text_system = instance.text_system().clone();
+                let layout_changes_rx = instance.layout_changes_rx().clone();
+                let root = instance.root();
+
+                MainRenderer::new(
+                    &mut instance.frame,
+                    &mut target.ctx,
+                    root,
+                    text_system,
+                    PANEL_FORMAT,
+                    layout_changes_rx,
+                    MainRendererConfig { debug_mode: false },
+                )
+            });
+
+            renderer.resize(
+                &target.ctx,
+                PhysicalSize {
+                    width: target.resolution.x,
+                    height: target.resolution.y,
+                },
+                1.0,
+            );
+
+            renderer.update(&mut target.ctx, &mut instance.frame)?;
+            renderer.draw(
+                &mut target.ctx,
+                &mut instance.frame,
+                ctx.encoder,
+                &target.view,
+                false,
+            )?;
+        }
+
+        let Some(first_view) = self.views.first() else {
+            return Ok(());
+        };
+
+        let output = ctx.get_texture(self.output);
+        let output_view = output.create_view(&Default::default());
+
+        // The views share the configuration, and hence the depth format
+        let is_depth = ctx
+            .get_texture(first_view.depth_buffer)
+            .format()
+            .is_depth_stencil_format();
+
+        let depth_layout = &*self.depth_layout.get_or_insert_with(|| {
+            let mut builder = BindGroupLayoutBuilder::new("world_ui_depth");
+            if is_depth {
+                builder.bind_texture_depth(ShaderStages::FRAGMENT);
+            } else {
+                builder.bind_texture_unfiltered(ShaderStages::FRAGMENT);
+            }
+
+            builder.build(ctx.gpu)
+        });
+
+        let target = TargetDesc {
+            formats: &[output.format()],
+            depth_format: None,
+            sample_count: output.sample_count(),
+        };
+
+        let shader = self.shader.get_or_insert_with(|| {
+            // Depth is either loaded from a depth texture or the first channel of a color texture
+            let depth_source = if is_depth {
+                "@group(1) @binding(0)\nvar depth_texture: texture_depth_2d;\n\
+                 fn load_depth(pos: vec2<i32>) -> f32 { return textureLoad(depth_texture, pos, 0); }\n"
+            } else {
+                "@group(1) @binding(0)\nvar depth_texture: texture_2d<f32>;\n\
+                 fn load_depth(pos: vec2<i32>) -> f32 { return textureLoad(depth_texture, pos, 0).r; }\n"
+            };
+
+            let shader_module = ctx
+                .gpu
+                .device
+                .create_shader_module(wgpu::ShaderModuleDescriptor {
+                    label: Some("world_ui"),
+                    source: wgpu::ShaderSource::Wgsl(
+                        format!("{depth_source}{}", include_str!("../shaders/world_ui.wgsl"))
+                            .into(),
+                    ),
+                });
+
+            RenderShader::new(
+                ctx.gpu,
+                &ShaderDesc::new("world_ui", &shader_module, &target)
+                    .with_bind_group_layouts(&[&self.layout, depth_layout]),
+            )
+        });
+
+        let mut render_pass = ctx.encoder.begin_render_pass(&RenderPassDescriptor {
+            label: Some("world_ui"),
+            color_attachments: &[Some(RenderPassColorAttachment {
+                view: &output_view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            ..Default::default()
+        });
+
+        render_pass.set_pipeline(shader.pipeline());
+
+        let output_size = PhysicalExtent::new(output.width(), output.height());
+        for (i, view) in self.views.iter().enumerate() {
+            let depth_view = ctx
+                .get_texture(view.depth_buffer)
+                .create_view(&Default::default());
+
+            let depth_bind_group = BindGroupBuilder::new("world_ui_depth")
+                .bind_texture(&depth_view)
+                .build(ctx.gpu, depth_layout);
+
+            let (x, y, width, height) = match view.viewport {
+                Some((viewport, _)) => viewport.to_physical(output_size),
+                None => (0, 0, output_size.width, output_size.height),
+            };
+
+            render_pass.set_viewport(x as f32, y as f32, width as f32, height as f32, 0.0, 1.0);
+            render_pass.set_scissor_rect(x, y, width, height);
+            render_pass.set_bind_group(1, &depth_bind_group, &[]);
+
+            for panel in self
+                .targets
+                .values()
+                .map(|v| &v.views[i])
+                .filter(|v| v.visible)
+            {
+                render_pass.set_bind_group(0, &panel.bind_group, &[]);
+                render_pass.draw(0..6, 0..1);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn read_dependencies(&self) -> Vec<Dependency> {
+        let mut dependencies = vec![Dependency::texture(
+            self.output,
+            TextureUsages::RENDER_ATTACHMENT,
+        )];
+
+        for view in &self.views {
+            dependencies.push(Dependency::texture(
+                view.depth_buffer,
+                TextureUsages::TEXTURE_BINDING,
+            ));
+
+            if let Some((_, view_input)) = view.viewport {
+                dependencies.push(Dependency::texture(
+                    view_input,
+                    TextureUsages::TEXTURE_BINDING,
+                ));
+            }
+        }
+
+        dependencies
+    }
+
+    fn write_dependencies(&self) -> Vec<Dependency> {
+        vec![]
+    }
+
+    fn on_resource_changed(&mut self, _resource: ivy_wgpu::rendergraph::ResourceHandle) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::{vec2, vec3, Quat};
+
+    use super::*;
+
+    #[test]
+    fn hit() {
+        let hit = |origin, dir| {
+            panel_hit(
+                Mat4::from_translation(vec3(0.0, 0.0, -5.0)),
+                vec2(2.0, 1.0),
+                UVec2::new(200, 100),
+                origin,
+                dir,
+            )
+        };
+
+        let (distance, pos) =
+            hit(Vec3::ZERO, -Vec3::Z).expect("ray through the center hits the panel");
+
+        assert!((distance - 5.0).abs() < 1e-4);
+        assert!(pos.abs_diff_eq(vec2(100.0, 50.0), 1e-3));
+
+        // The top left of the texture is at -X and +Y of the panel
+        let (_, pos) = hit(vec3(-0.5, 0.25, 0.0), -Vec3::Z).unwrap();
+        assert!(pos.abs_diff_eq(vec2(50.0, 25.0), 1e-3));
+
+        // Outside the panel
+        assert_eq!(hit(vec3(1.5, 0.0, 0.0), -Vec3::Z), None);
+        // Behind the ray
+        assert_eq!(hit(Vec3::ZERO, Vec3::Z), None);
+        // Parallel to the panel
+        assert_eq!(hit(Vec3::ZERO, Vec3::X), None);
+    }
+
+    #[test]
+    fn hit_transformed() {
+        // Panel facing +X, scaled up twice
+        let transform = Mat4::from_scale_rotation_translation(
+            Vec3::splat(2.0),
+            Quat::from_rotation_y(std::f32::consts::FRAC_PI_2),
+            vec3(5.0, 0.0, 0.0),
+        );
+
+        let (distance, pos) = panel_hit(
+            transform,
+            vec2(1.0, 1.0),
+            UVec2::new(100, 100),
+            vec3(10.0, 0.5, 0.0),
+            -Vec3::X,
+        )
+        .unwrap();
+
+        assert!((distance - 5.0).abs() < 1e-4);
+        assert!(pos.abs_diff_eq(vec2(50.0, 25.0), 1e-3));
+    }
+}
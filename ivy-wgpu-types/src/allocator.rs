@@ -112,6 +112,7 @@ impl BufferAllocator {
                 l.size += block.size;
             } else if block.continues_to(r) {
                 r.start -= block.size;
+                r.size += block.size;
             } else {
                 self.free.insert(idx, block);
             }
@@ -132,6 +133,16 @@ impl BufferAllocator {
     pub fn total_size(&self) -> usize {
         self.total_size
     }
+
+    /// Total unallocated size, which may be split across several blocks
+    pub fn free_size(&self) -> usize {
+        self.free.iter().map(|v| v.size).sum()
+    }
+
+    /// Size of the largest allocation which currently fits
+    pub fn largest_free(&self) -> usize {
+        self.free.iter().map(|v| v.size).max().unwrap_or_default()
+    }
 }
 
 #[cfg(test)]
@@ -243,4 +254,38 @@ mod tests {
             },]
         );
     }
+
+    #[test]
+    fn test_merge_right() {
+        let mut allocator = BufferAllocator::new(64);
+
+        let b0 = allocator.allocate(8).unwrap();
+        let _b1 = allocator.allocate(8).unwrap();
+        let b2 = allocator.allocate(8).unwrap();
+        let b3 = allocator.allocate(8).unwrap();
+        let _b4 = allocator.allocate(8).unwrap();
+
+        allocator.deallocate(b0);
+        allocator.deallocate(b3);
+        assert_eq!(allocator.free_size(), 40);
+        assert_eq!(allocator.largest_free(), 24);
+
+        // Merges into the following free block without losing space
+        allocator.deallocate(b2);
+        assert_eq!(
+            allocator.free,
+            [
+                Allocation { start: 0, size: 8 },
+                Allocation {
+                    start: 16,
+                    size: 16
+                },
+                Allocation {
+                    start: 40,
+                    size: 24
+                }
+            ]
+        );
+        assert_eq!(allocator.free_size(), 48);
+    }
 }
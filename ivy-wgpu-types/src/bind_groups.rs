@@ -1,4 +1,4 @@
-use std::borrow::Cow;
+use std::{borrow::Cow, num::NonZeroU32};
 
 use wgpu::{
     BindGroup, BindGroupEntry, BindGroupLayout, BindGroupLayoutDescriptor, BindGroupLayoutEntry,
@@ -47,6 +47,23 @@ impl BindGroupLayoutBuilder {
         )
     }
 
+    /// Binds an array of `count` textures, indexed in the shader through a `binding_array`.
+    ///
+    /// Requires `Features::TEXTURE_BINDING_ARRAY`
+    pub fn bind_texture_array(&mut self, visibility: ShaderStages, count: NonZeroU32) -> &mut Self {
+        self.bind(
+            visibility,
+            BindingType::Texture {
+                sample_type: TextureSampleType::Float { filterable: true },
+                view_dimension: TextureViewDimension::D2,
+                multisampled: false,
+            },
+        );
+
+        self.entries.last_mut().unwrap().count = Some(count);
+        self
+    }
+
     pub fn bind_texture_unfiltered(&mut self, visibility: ShaderStages) -> &mut Self {
         self.bind(
             visibility,
@@ -102,6 +119,17 @@ impl BindGroupLayoutBuilder {
         )
     }
 
+    pub fn bind_texture_3d(&mut self, visibility: ShaderStages) -> &mut Self {
+        self.bind(
+            visibility,
+            BindingType::Texture {
+                sample_type: TextureSampleType::Float { filterable: true },
+                view_dimension: TextureViewDimension::D3,
+                multisampled: false,
+            },
+        )
+    }
+
     pub fn bind_storage_texture(
         &mut self,
         visibility: ShaderStages,
@@ -118,6 +146,22 @@ impl BindGroupLayoutBuilder {
         )
     }
 
+    pub fn bind_storage_texture_3d(
+        &mut self,
+        visibility: ShaderStages,
+        access: StorageTextureAccess,
+        format: TextureFormat,
+    ) -> &mut Self {
+        self.bind(
+            visibility,
+            BindingType::StorageTexture {
+                access,
+                format,
+                view_dimension: TextureViewDimension::D3,
+            },
+        )
+    }
+
     pub fn bind_sampler(&mut self, visibility: ShaderStages) -> &mut Self {
         self.bind(
             visibility,
@@ -206,6 +250,10 @@ impl<'a> BindGroupBuilder<'a> {
         self.bind(BindingResource::TextureView(view))
     }
 
+    pub fn bind_texture_array(&mut self, views: &'a [&'a TextureView]) -> &mut Self {
+        self.bind(BindingResource::TextureViewArray(views))
+    }
+
     pub fn bind_sampler(&mut self, sampler: &'a Sampler) -> &mut Self {
         self.bind(BindingResource::Sampler(sampler))
     }
@@ -0,0 +1,104 @@
+type Job = Box<dyn FnOnce() + Send>;
+
+/// A fixed set of threads compiling pipelines in the background.
+///
+/// Compiling is CPU bound, so the number of concurrent compilations is bounded rather than
+/// spawning a thread for each pipeline.
+#[derive(Debug, Clone)]
+pub struct CompilePool {
+    tx: flume::Sender<Job>,
+}
+
+impl CompilePool {
+    /// Spawns `threads` worker threads.
+    ///
+    /// Jobs execute on the calling thread if no workers could be spawned, e.g; on the web.
+    pub fn new(threads: usize) -> Self {
+        let (tx, rx) = flume::unbounded::<Job>();
+
+        for i in 0..threads {
+            let rx = rx.clone();
+            let result = std::thread::Builder::new()
+                .name(format!("pipeline compiler {i}"))
+                .spawn(move || {
+                    for job in rx {
+                        job();
+                    }
+                });
+
+            if let Err(err) = result {
+                tracing::error!("Failed to spawn pipeline compilation thread: {err}");
+                break;
+            }
+        }
+
+        Self { tx }
+    }
+
+    /// Executes `job` on one of the worker threads
+    pub fn execute(&self, job: impl 'static + Send + FnOnce()) {
+        if let Err(flume::SendError(job)) = self.tx.send(Box::new(job)) {
+            job();
+        }
+    }
+}
+
+impl Default for CompilePool {
+    /// Leaves a core for the main thread, using at most four threads
+    fn default() -> Self {
+        let threads = std::thread::available_parallelism()
+            .map_or(1, |v| v.get().saturating_sub(1))
+            .clamp(1, 4);
+
+        Self::new(threads)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    };
+
+    use super::*;
+
+    #[test]
+    fn bounded() {
+        let pool = CompilePool::new(2);
+
+        let running = Arc::new(AtomicUsize::new(0));
+        let max_running = Arc::new(AtomicUsize::new(0));
+        let (tx, rx) = flume::unbounded();
+
+        for i in 0..16 {
+            let running = running.clone();
+            let max_running = max_running.clone();
+            let tx = tx.clone();
+            pool.execute(move || {
+                let count = running.fetch_add(1, Ordering::SeqCst) + 1;
+                max_running.fetch_max(count, Ordering::SeqCst);
+                std::thread::sleep(std::time::Duration::from_millis(5));
+                running.fetch_sub(1, Ordering::SeqCst);
+                tx.send(i).unwrap();
+            });
+        }
+
+        let mut finished = rx.iter().take(16).collect::<Vec<_>>();
+        finished.sort();
+
+        assert_eq!(finished, (0..16).collect::<Vec<_>>());
+        assert!(max_running.load(Ordering::SeqCst) <= 2);
+    }
+
+    #[test]
+    fn without_workers() {
+        let pool = CompilePool::new(0);
+
+        let (tx, rx) = flume::unbounded();
+        pool.execute(move || tx.send(()).unwrap());
+
+        // Executed on the calling thread
+        assert_eq!(rx.try_recv(), Ok(()));
+    }
+}
@@ -1,8 +1,14 @@
-use std::sync::Arc;
+use std::{path::Path, sync::Arc};
 
 use ivy_assets::service::Service;
-use wgpu::{Backends, Features, SurfaceConfiguration, SurfaceError, SurfaceTexture, TextureFormat};
-use winit::{dpi::PhysicalSize, window::Window};
+use ivy_core::PhysicalExtent;
+use wgpu::{
+    Backends, Features, PresentMode, SurfaceConfiguration, SurfaceError, SurfaceTexture,
+    TextureFormat,
+};
+use winit::window::Window;
+
+use crate::{compile_pool::CompilePool, pipeline_cache::PipelineCache};
 
 fn device_features() -> wgpu::Features {
     Features::TEXTURE_FORMAT_16BIT_NORM
@@ -10,25 +16,50 @@ fn device_features() -> wgpu::Features {
         | wgpu::Features::INDIRECT_FIRST_INSTANCE
 }
 
+/// Features which are enabled when supported by the adapter, but are not required.
+///
+/// Use `gpu.device.features()` to check if they are available.
+fn optional_features() -> wgpu::Features {
+    Features::MULTI_DRAW_INDIRECT
+        | Features::PIPELINE_CACHE
+        | Features::TEXTURE_BINDING_ARRAY
+        | Features::SAMPLED_TEXTURE_AND_STORAGE_BUFFER_ARRAY_NON_UNIFORM_INDEXING
+}
+
+/// Backends to create the instance with.
+///
+/// Restricted through `WGPU_BACKEND`, e.g; to select a software renderer together with
+/// `WGPU_ADAPTER_NAME`.
+fn backends() -> Backends {
+    #[cfg(not(target_arch = "wasm32"))]
+    let default = Backends::all();
+
+    #[cfg(target_arch = "wasm32")]
+    let default = Backends::GL;
+
+    wgpu::util::backend_bits_from_env().unwrap_or(default)
+}
+
 /// Represents the basic graphics state, such as the device and queue.
 #[derive(Debug, Clone)]
 pub struct Gpu {
     pub adapter: Arc<wgpu::Adapter>,
     pub device: Arc<wgpu::Device>,
     pub queue: Arc<wgpu::Queue>,
+    /// Used when creating pipelines, if enabled
+    pub pipeline_cache: Option<Arc<PipelineCache>>,
+    /// Compiles pipelines in the background, see [`RenderShader::spawn`](crate::RenderShader::spawn)
+    pub compile_pool: CompilePool,
 }
 
 impl Service for Gpu {}
 
 impl Gpu {
-    /// Creates a new Gpu instance with a surface.
+    /// Creates a new Gpu instance without a surface.
+    ///
+    /// The adapter is selected through `WGPU_ADAPTER_NAME` and `WGPU_POWER_PREF` if set.
     pub async fn headless() -> Self {
-        #[cfg(not(target_arch = "wasm32"))]
-        let backends = Backends::all();
-
-        #[cfg(target_arch = "wasm32")]
-        let backends = Backends::GL;
-
+        let backends = backends();
         tracing::info!(?backends);
 
         let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
@@ -37,25 +68,27 @@ impl Gpu {
             ..Default::default()
         });
 
-        let adapter = instance
-            .request_adapter(&wgpu::RequestAdapterOptions {
-                power_preference: wgpu::PowerPreference::default(),
-                compatible_surface: None,
-                force_fallback_adapter: false,
-            })
+        let adapter = wgpu::util::initialize_adapter_from_env_or_default(&instance, None)
             .await
             .expect("Failed to find an appropriate adapter");
 
         let (device, queue) = adapter
             .request_device(
                 &wgpu::DeviceDescriptor {
-                    required_features: device_features(),
+                    required_features: device_features()
+                        | (adapter.features() & optional_features()),
                     // WebGL doesn't support all of wgpu's features, so if
                     // we're building for the web we'll have to disable some.
                     required_limits: if cfg!(target_arch = "wasm32") {
                         wgpu::Limits::downlevel_webgl2_defaults()
                     } else {
-                        wgpu::Limits::default()
+                        wgpu::Limits {
+                            // Allow large texture arrays for bindless materials
+                            max_sampled_textures_per_shader_stage: adapter
+                                .limits()
+                                .max_sampled_textures_per_shader_stage,
+                            ..wgpu::Limits::default()
+                        }
                     },
                     label: None,
                     ..Default::default()
@@ -69,16 +102,24 @@ impl Gpu {
             adapter: Arc::new(adapter),
             device: Arc::new(device),
             queue: Arc::new(queue),
+            pipeline_cache: None,
+            compile_pool: Default::default(),
         }
     }
-    /// Creates a new Gpu instance with a surface.
-    pub async fn with_surface(window: Arc<Window>) -> (Self, Surface) {
-        #[cfg(not(target_arch = "wasm32"))]
-        let backends = Backends::all();
 
-        #[cfg(target_arch = "wasm32")]
-        let backends = Backends::GL;
+    /// Persist compiled pipelines in `dir`, if supported by the device.
+    ///
+    /// See [`PipelineCache`]
+    pub fn with_pipeline_cache(mut self, dir: impl AsRef<Path>) -> Self {
+        self.pipeline_cache = PipelineCache::load(&self, dir).map(Arc::new);
+        self
+    }
 
+    /// Creates a new Gpu instance with a surface.
+    ///
+    /// The adapter is selected through `WGPU_ADAPTER_NAME` and `WGPU_POWER_PREF` if set.
+    pub async fn with_surface(window: Arc<Window>) -> (Self, Surface) {
+        let backends = backends();
         tracing::info!(?backends);
 
         let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
@@ -90,19 +131,15 @@ impl Gpu {
         let window_size = window.inner_size();
         let surface = instance.create_surface(window).unwrap();
 
-        let adapter = instance
-            .request_adapter(&wgpu::RequestAdapterOptions {
-                power_preference: wgpu::PowerPreference::default(),
-                compatible_surface: Some(&surface),
-                force_fallback_adapter: false,
-            })
+        let adapter = wgpu::util::initialize_adapter_from_env_or_default(&instance, Some(&surface))
             .await
             .expect("Failed to find an appropriate adapter");
 
         let (device, queue) = adapter
             .request_device(
                 &wgpu::DeviceDescriptor {
-                    required_features: device_features(),
+                    required_features: device_features()
+                        | (adapter.features() & optional_features()),
                     // WebGL doesn't support all of wgpu's features, so if
                     // we're building for the web we'll have to disable some.
                     required_limits: if cfg!(target_arch = "wasm32") {
@@ -110,6 +147,10 @@ impl Gpu {
                     } else {
                         wgpu::Limits {
                             max_bind_groups: 6,
+                            // Allow large texture arrays for bindless materials
+                            max_sampled_textures_per_shader_stage: adapter
+                                .limits()
+                                .max_sampled_textures_per_shader_stage,
                             ..wgpu::Limits::default()
                         }
                     },
@@ -131,7 +172,9 @@ impl Gpu {
             .unwrap_or_else(|| surface_caps.formats[0]);
 
         let config = wgpu::SurfaceConfiguration {
-            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            // Allow reading back the presented frame for screenshots and recordings
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT
+                | (surface_caps.usages & wgpu::TextureUsages::COPY_SRC),
             format: surface_format,
             present_mode: wgpu::PresentMode::AutoNoVsync,
             alpha_mode: surface_caps.alpha_modes[0],
@@ -148,18 +191,20 @@ impl Gpu {
                 adapter: Arc::new(adapter),
                 device: Arc::new(device),
                 queue: Arc::new(queue),
+                pipeline_cache: None,
+                compile_pool: Default::default(),
             },
             Surface {
                 surface,
                 config,
-                size: window_size,
+                size: PhysicalExtent::new(window_size.width, window_size.height),
             },
         )
     }
 }
 
 pub struct Surface {
-    size: PhysicalSize<u32>,
+    size: PhysicalExtent,
     surface: wgpu::Surface<'static>,
     config: SurfaceConfiguration,
 }
@@ -173,14 +218,14 @@ impl Surface {
         &self.config
     }
 
-    pub fn resize(&mut self, gpu: &Gpu, new_size: PhysicalSize<u32>) {
+    pub fn resize(&mut self, gpu: &Gpu, new_size: PhysicalExtent) {
         tracing::debug_span!("resize", ?new_size);
         if new_size == self.size {
             tracing::info!(size=?new_size, "Duplicate resize message ignored");
             return;
         }
 
-        if new_size.width > 0 && new_size.height > 0 {
+        if !new_size.is_empty() {
             self.config.width = new_size.width;
             self.config.height = new_size.height;
 
@@ -196,11 +241,23 @@ impl Surface {
         self.surface.configure(&gpu.device, &self.config);
     }
 
+    /// Changes how frames are presented, e.g; to enable vsync
+    pub fn set_present_mode(&mut self, gpu: &Gpu, present_mode: PresentMode) {
+        if self.config.present_mode != present_mode {
+            self.config.present_mode = present_mode;
+            self.reconfigure(gpu);
+        }
+    }
+
+    pub fn present_mode(&self) -> PresentMode {
+        self.config.present_mode
+    }
+
     pub fn surface_format(&self) -> TextureFormat {
         self.config.format
     }
 
-    pub fn size(&self) -> PhysicalSize<u32> {
+    pub fn size(&self) -> PhysicalExtent {
         self.size
     }
 }
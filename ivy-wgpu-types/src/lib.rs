@@ -1,8 +1,10 @@
 pub mod allocator;
 mod bind_groups;
+pub mod compile_pool;
 mod gpu;
 pub mod mipmap;
 pub mod multi_buffer;
+pub mod pipeline_cache;
 pub mod shader;
 pub mod texture;
 pub mod typed_buffer;
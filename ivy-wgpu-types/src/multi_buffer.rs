@@ -1,7 +1,7 @@
-use std::{marker::PhantomData, ops::RangeBounds};
+use std::{marker::PhantomData, mem::size_of, ops::RangeBounds};
 
 use bytemuck::Pod;
-use wgpu::{Buffer, BufferSlice, BufferUsages, Queue};
+use wgpu::{Buffer, BufferSlice, BufferUsages, CommandEncoderDescriptor, Queue};
 
 use super::{
     allocator::{Allocation, BufferAllocator},
@@ -75,6 +75,11 @@ where
         }
     }
 
+    /// Accounts the memory of the buffer to `category`, see [`TypedBuffer::set_memory_category`]
+    pub fn set_memory_category(&mut self, category: &'static str) {
+        self.buffer.set_memory_category(category);
+    }
+
     pub fn grow(&mut self, gpu: &Gpu, additional: usize) {
         let size = (self.buffer.len() + additional.next_power_of_two()).next_power_of_two();
         tracing::debug!(?size, "grow");
@@ -83,6 +88,73 @@ where
         self.buffer.resize(gpu, self.allocator.total_size(), true);
     }
 
+    /// Moves the `live` allocations into a new tightly packed buffer with room for `additional`
+    /// elements, releasing all other allocations.
+    ///
+    /// The contents are copied into the new buffer on the GPU. The previous buffer is kept alive
+    /// by wgpu until the copy and any in-flight draws have completed, so this does not stall.
+    ///
+    /// Returns the new location of each allocation, in the same order.
+    pub fn compact(
+        &mut self,
+        gpu: &Gpu,
+        live: &[SubBuffer<T>],
+        additional: usize,
+    ) -> Vec<SubBuffer<T>> {
+        let used: usize = live.iter().map(|v| v.size()).sum();
+        let required = used + additional;
+        // Leave headroom to avoid compacting again on the next allocation
+        let size = (required + required / 2).next_power_of_two();
+        tracing::debug!(used, size, "compact");
+
+        let old_buffer = self.buffer.reallocate(gpu, size);
+        let mut allocator = BufferAllocator::new(size);
+
+        let mut encoder = gpu
+            .device
+            .create_command_encoder(&CommandEncoderDescriptor {
+                label: Some(&self.label),
+            });
+
+        let stride = size_of::<T>() as u64;
+        let moved = live
+            .iter()
+            .map(|sub_buffer| {
+                let block = allocator.allocate(sub_buffer.size()).unwrap();
+
+                if sub_buffer.size() > 0 {
+                    encoder.copy_buffer_to_buffer(
+                        &old_buffer,
+                        sub_buffer.offset() as u64 * stride,
+                        self.buffer.buffer(),
+                        block.start() as u64 * stride,
+                        sub_buffer.size() as u64 * stride,
+                    );
+                }
+
+                SubBuffer {
+                    block,
+                    _marker: PhantomData,
+                }
+            })
+            .collect();
+
+        gpu.queue.submit([encoder.finish()]);
+        self.allocator = allocator;
+
+        moved
+    }
+
+    /// Total unallocated space, which may be fragmented
+    pub fn free_size(&self) -> usize {
+        self.allocator.free_size()
+    }
+
+    /// Size of the largest allocation which currently fits without growing
+    pub fn largest_free(&self) -> usize {
+        self.allocator.largest_free()
+    }
+
     pub fn allocate(&mut self, len: usize) -> Option<SubBuffer<T>> {
         Some(SubBuffer {
             block: self.allocator.allocate(len)?,
@@ -0,0 +1,72 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+use wgpu::{Features, PipelineCacheDescriptor};
+
+use crate::Gpu;
+
+/// Driver pipeline cache persisted to disk between runs.
+///
+/// Compiled pipelines are reused from the previous session, avoiding hitches the first time a
+/// shader or material permutation is drawn. Requires [`Features::PIPELINE_CACHE`], which is
+/// currently only supported on Vulkan.
+#[derive(Debug)]
+pub struct PipelineCache {
+    cache: wgpu::PipelineCache,
+    path: PathBuf,
+}
+
+impl PipelineCache {
+    /// Loads the cache for the current adapter from `dir`.
+    ///
+    /// Returns `None` if pipeline caching is not supported by the device.
+    pub fn load(gpu: &Gpu, dir: impl AsRef<Path>) -> Option<Self> {
+        if !gpu.device.features().contains(Features::PIPELINE_CACHE) {
+            return None;
+        }
+
+        // Caches are only valid for the same adapter and driver version
+        let key = wgpu::util::pipeline_cache_key(&gpu.adapter.get_info())?;
+        let path = dir.as_ref().join(key);
+
+        let data = std::fs::read(&path).ok();
+        tracing::info!(?path, loaded = data.is_some(), "Loading pipeline cache");
+
+        // SAFETY: the data was produced by `get_data` of a cache with the same key, and
+        // `fallback` discards it if it is invalid
+        let cache = unsafe {
+            gpu.device.create_pipeline_cache(&PipelineCacheDescriptor {
+                label: Some("pipeline_cache"),
+                data: data.as_deref(),
+                fallback: true,
+            })
+        };
+
+        Some(Self { cache, path })
+    }
+
+    /// Writes the cache to disk
+    pub fn save(&self) -> anyhow::Result<()> {
+        let Some(data) = self.cache.get_data() else {
+            return Ok(());
+        };
+
+        if let Some(dir) = self.path.parent() {
+            std::fs::create_dir_all(dir)
+                .with_context(|| format!("Failed to create cache directory {dir:?}"))?;
+        }
+
+        // Write to a temporary file first to never leave a partially written cache behind
+        let tmp = self.path.with_extension("tmp");
+        std::fs::write(&tmp, data)
+            .with_context(|| format!("Failed to write pipeline cache {tmp:?}"))?;
+        std::fs::rename(&tmp, &self.path)
+            .with_context(|| format!("Failed to write pipeline cache {:?}", self.path))?;
+
+        Ok(())
+    }
+
+    pub fn cache(&self) -> &wgpu::PipelineCache {
+        &self.cache
+    }
+}
@@ -1,7 +1,10 @@
+use std::sync::Arc;
+
 use itertools::Itertools;
 use wgpu::{
-    BindGroupLayout, DepthBiasState, Face, FrontFace, PipelineLayoutDescriptor, RenderPipeline,
-    TextureFormat, VertexBufferLayout,
+    BindGroupLayout, BlendState, CompareFunction, DepthBiasState, Face, FrontFace, PipelineLayout,
+    PipelineLayoutDescriptor, PolygonMode, RenderPipeline, ShaderModule, TextureFormat,
+    VertexBufferLayout,
 };
 
 use crate::Gpu;
@@ -40,6 +43,11 @@ pub struct ShaderDesc<'a> {
     pub fragment_entry_point: &'a str,
     pub culling_mode: Culling,
     pub depth_bias: DepthBiasState,
+    /// Blend state of all color targets
+    pub blend: Option<BlendState>,
+    pub depth_write: bool,
+    pub depth_compare: CompareFunction,
+    pub polygon_mode: PolygonMode,
 }
 
 impl<'a> ShaderDesc<'a> {
@@ -54,6 +62,10 @@ impl<'a> ShaderDesc<'a> {
             fragment_entry_point: "fs_main",
             culling_mode: Default::default(),
             depth_bias: Default::default(),
+            blend: Some(BlendState::ALPHA_BLENDING),
+            depth_write: true,
+            depth_compare: CompareFunction::LessEqual,
+            polygon_mode: PolygonMode::Fill,
         }
     }
 
@@ -86,6 +98,30 @@ impl<'a> ShaderDesc<'a> {
         self.culling_mode = culling_mode;
         self
     }
+
+    /// Set the blend state
+    pub fn with_blend(mut self, blend: Option<BlendState>) -> Self {
+        self.blend = blend;
+        self
+    }
+
+    /// Set whether depth is written
+    pub fn with_depth_write(mut self, depth_write: bool) -> Self {
+        self.depth_write = depth_write;
+        self
+    }
+
+    /// Set the depth comparison function
+    pub fn with_depth_compare(mut self, depth_compare: CompareFunction) -> Self {
+        self.depth_compare = depth_compare;
+        self
+    }
+
+    /// Set the polygon mode, e.g; to draw wireframes
+    pub fn with_polygon_mode(mut self, polygon_mode: PolygonMode) -> Self {
+        self.polygon_mode = polygon_mode;
+        self
+    }
 }
 
 /// Represents a graphics shader
@@ -97,26 +133,67 @@ pub struct RenderShader {
 
 impl RenderShader {
     pub fn new(gpu: &Gpu, desc: &ShaderDesc) -> Self {
-        // let shader = gpu
-        //     .device
-        //     .create_shader_module(wgpu::ShaderModuleDescriptor {
-        //         label: Some(desc.label),
-        //         source: wgpu::ShaderSource::Wgsl(desc.source.into()),
-        //     });
-
-        let layout = gpu
-            .device
-            .create_pipeline_layout(&PipelineLayoutDescriptor {
-                label: Some(desc.label),
-                bind_group_layouts: desc.bind_group_layouts,
-                push_constant_ranges: &[],
-            });
+        let layout = create_pipeline_layout(gpu, desc);
+        Self::with_layout(gpu, desc, &layout)
+    }
+
+    /// Compiles the pipeline on a thread of the
+    /// [`CompilePool`](crate::compile_pool::CompilePool).
+    ///
+    /// `module` must be the module referenced by `desc`, and is kept alive until the pipeline has
+    /// been compiled.
+    pub fn spawn(gpu: &Gpu, desc: &ShaderDesc, module: Arc<ShaderModule>) -> PendingRenderShader {
+        let (tx, rx) = flume::bounded(1);
+        let label = desc.label.to_string();
+
+        let layout = create_pipeline_layout(gpu, desc);
+        let owned = OwnedShaderDesc::new(desc);
+
+        let compile = {
+            let gpu = gpu.clone();
+            move || {
+                let target = TargetDesc {
+                    formats: &owned.formats,
+                    depth_format: owned.depth_format,
+                    sample_count: owned.sample_count,
+                };
+
+                let desc = ShaderDesc {
+                    label: &owned.label,
+                    module: &module,
+                    target: &target,
+                    vertex_layouts: &owned.vertex_layouts,
+                    bind_group_layouts: &[],
+                    vertex_entry_point: &owned.vertex_entry_point,
+                    fragment_entry_point: &owned.fragment_entry_point,
+                    culling_mode: owned.culling_mode,
+                    depth_bias: owned.depth_bias,
+                    blend: owned.blend,
+                    depth_write: owned.depth_write,
+                    depth_compare: owned.depth_compare,
+                    polygon_mode: owned.polygon_mode,
+                };
+
+                tx.send(Self::with_layout(&gpu, &desc, &layout)).ok();
+            }
+        };
+
+        // Threads are not available on the web
+        if cfg!(target_arch = "wasm32") {
+            compile();
+        } else {
+            gpu.compile_pool.execute(compile);
+        }
 
+        PendingRenderShader { label, rx }
+    }
+
+    fn with_layout(gpu: &Gpu, desc: &ShaderDesc, layout: &PipelineLayout) -> Self {
         let pipeline = gpu
             .device
             .create_render_pipeline(&wgpu::RenderPipelineDescriptor {
                 label: Some(desc.label),
-                layout: Some(&layout),
+                layout: Some(layout),
                 vertex: wgpu::VertexState {
                     module: desc.module,
                     entry_point: desc.vertex_entry_point,
@@ -135,7 +212,7 @@ impl RenderShader {
                             Some(wgpu::ColorTargetState {
                                 // 4.
                                 format,
-                                blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                                blend: desc.blend,
                                 write_mask: wgpu::ColorWrites::ALL,
                             })
                         })
@@ -147,8 +224,8 @@ impl RenderShader {
                     strip_index_format: None,
                     front_face: desc.culling_mode.front_face,
                     cull_mode: desc.culling_mode.cull_mode,
-                    // Setting this to anything other than Fill requires Features::NON_FILL_POLYGON_MODE
-                    polygon_mode: wgpu::PolygonMode::Fill,
+                    // Line is enabled through Features::POLYGON_MODE_LINE
+                    polygon_mode: desc.polygon_mode,
                     // Requires Features::DEPTH_CLIP_CONTROL
                     unclipped_depth: false,
                     // Requires Features::CONSERVATIVE_RASTERIZATION
@@ -159,8 +236,8 @@ impl RenderShader {
                     .depth_format
                     .map(|format| wgpu::DepthStencilState {
                         format,
-                        depth_write_enabled: true,
-                        depth_compare: wgpu::CompareFunction::LessEqual,
+                        depth_write_enabled: desc.depth_write,
+                        depth_compare: desc.depth_compare,
                         stencil: Default::default(),
                         bias: desc.depth_bias,
                     }),
@@ -170,7 +247,7 @@ impl RenderShader {
                     alpha_to_coverage_enabled: false, // 4.
                 },
                 multiview: None,
-                cache: None,
+                cache: gpu.pipeline_cache.as_ref().map(|v| v.cache()),
             });
 
         Self {
@@ -187,3 +264,67 @@ impl RenderShader {
         &self.label
     }
 }
+
+fn create_pipeline_layout(gpu: &Gpu, desc: &ShaderDesc) -> PipelineLayout {
+    gpu.device
+        .create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: Some(desc.label),
+            bind_group_layouts: desc.bind_group_layouts,
+            push_constant_ranges: &[],
+        })
+}
+
+/// Owned copy of a [`ShaderDesc`] which can be sent to another thread
+struct OwnedShaderDesc {
+    label: String,
+    formats: Vec<TextureFormat>,
+    depth_format: Option<TextureFormat>,
+    sample_count: u32,
+    vertex_layouts: Vec<VertexBufferLayout<'static>>,
+    vertex_entry_point: String,
+    fragment_entry_point: String,
+    culling_mode: Culling,
+    depth_bias: DepthBiasState,
+    blend: Option<BlendState>,
+    depth_write: bool,
+    depth_compare: CompareFunction,
+    polygon_mode: PolygonMode,
+}
+
+impl OwnedShaderDesc {
+    fn new(desc: &ShaderDesc) -> Self {
+        Self {
+            label: desc.label.into(),
+            formats: desc.target.formats.to_vec(),
+            depth_format: desc.target.depth_format,
+            sample_count: desc.target.sample_count,
+            vertex_layouts: desc.vertex_layouts.to_vec(),
+            vertex_entry_point: desc.vertex_entry_point.into(),
+            fragment_entry_point: desc.fragment_entry_point.into(),
+            culling_mode: desc.culling_mode.clone(),
+            depth_bias: desc.depth_bias,
+            blend: desc.blend,
+            depth_write: desc.depth_write,
+            depth_compare: desc.depth_compare,
+            polygon_mode: desc.polygon_mode,
+        }
+    }
+}
+
+/// A [`RenderShader`] being compiled in the background
+#[derive(Debug)]
+pub struct PendingRenderShader {
+    label: String,
+    rx: flume::Receiver<RenderShader>,
+}
+
+impl PendingRenderShader {
+    /// Returns the shader if compilation has finished
+    pub fn try_take(&self) -> Option<RenderShader> {
+        self.rx.try_recv().ok()
+    }
+
+    pub fn label(&self) -> &str {
+        &self.label
+    }
+}
@@ -6,8 +6,8 @@ use itertools::Itertools;
 use ivy_assets::{Asset, AssetDesc};
 use ivy_core::profiling::{profile_function, profile_scope};
 use wgpu::{
-    BufferUsages, Extent3d, ImageCopyBuffer, ImageCopyTexture, ImageDataLayout, Origin3d, Texture,
-    TextureFormat, TextureUsages,
+    BufferUsages, CommandEncoder, Extent3d, ImageCopyBuffer, ImageCopyTexture, ImageDataLayout,
+    Origin3d, Texture, TextureDescriptor, TextureFormat, TextureUsages,
 };
 
 use super::Gpu;
@@ -109,6 +109,53 @@ pub fn texture_from_image(
     Ok(texture)
 }
 
+/// Creates a new texture from the mip levels of `texture` starting at `base_mip`, e.g; to release
+/// the most detailed levels of a streamed texture.
+///
+/// `texture` must have been created with `COPY_SRC`.
+pub fn texture_from_mips(
+    gpu: &Gpu,
+    encoder: &mut CommandEncoder,
+    texture: &Texture,
+    base_mip: u32,
+    label: &str,
+) -> Texture {
+    profile_function!();
+    let size = texture.size().mip_level_size(base_mip, texture.dimension());
+    let mip_level_count = texture.mip_level_count() - base_mip;
+
+    let output = gpu.device.create_texture(&TextureDescriptor {
+        label: Some(label),
+        size,
+        mip_level_count,
+        sample_count: 1,
+        dimension: texture.dimension(),
+        format: texture.format(),
+        usage: texture.usage(),
+        view_formats: &[],
+    });
+
+    for level in 0..mip_level_count {
+        encoder.copy_texture_to_texture(
+            ImageCopyTexture {
+                texture,
+                mip_level: base_mip + level,
+                origin: Origin3d::ZERO,
+                aspect: wgpu::TextureAspect::All,
+            },
+            ImageCopyTexture {
+                texture: &output,
+                mip_level: level,
+                origin: Origin3d::ZERO,
+                aspect: wgpu::TextureAspect::All,
+            },
+            size.mip_level_size(level, texture.dimension()),
+        );
+    }
+
+    output
+}
+
 fn normalize_image_format(
     image: &DynamicImage,
     format: TextureFormat,
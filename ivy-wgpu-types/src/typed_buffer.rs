@@ -5,6 +5,7 @@ use std::{
 };
 
 use bytemuck::NoUninit;
+use ivy_core::memory::TrackedMemory;
 use wgpu::{
     util::{BufferInitDescriptor, DeviceExt},
     Buffer, BufferAsyncError, BufferDescriptor, BufferSlice, BufferUsages, BufferView,
@@ -13,12 +14,15 @@ use wgpu::{
 
 use crate::Gpu;
 
+const MEMORY_CATEGORY: &str = "gpu_buffers";
+
 /// Type safe buffer
 pub struct TypedBuffer<T> {
     buffer: Buffer,
     len: usize,
     label: String,
     gen: u32,
+    memory: TrackedMemory,
     _marker: PhantomData<T>,
 }
 
@@ -44,6 +48,7 @@ where
         });
 
         Self {
+            memory: TrackedMemory::new(MEMORY_CATEGORY, buffer.size()),
             buffer,
             len: data.len(),
             label,
@@ -67,6 +72,7 @@ where
         });
 
         Self {
+            memory: TrackedMemory::new(MEMORY_CATEGORY, buffer.size()),
             buffer,
             len,
             label,
@@ -91,6 +97,7 @@ where
         });
 
         Self {
+            memory: TrackedMemory::new(MEMORY_CATEGORY, buffer.size()),
             buffer,
             len,
             label,
@@ -99,6 +106,12 @@ where
         }
     }
 
+    /// Accounts the memory of the buffer to `category` instead of `gpu_buffers`, see
+    /// [`MemoryReport`](ivy_core::memory::MemoryReport)
+    pub fn set_memory_category(&mut self, category: &'static str) {
+        self.memory = TrackedMemory::new(category, self.memory.bytes());
+    }
+
     #[inline]
     pub fn len(&self) -> usize {
         self.len
@@ -133,6 +146,23 @@ where
         queue.write_buffer(self.buffer(), offset, bytemuck::cast_slice(data));
     }
 
+    /// Replaces the buffer with a new uninitialized buffer of `new_len` elements.
+    ///
+    /// Returns the previous buffer, e.g; for copying the contents over.
+    pub fn reallocate(&mut self, gpu: &Gpu, new_len: usize) -> Buffer {
+        let buffer = gpu.device.create_buffer(&BufferDescriptor {
+            label: Some(&self.label),
+            usage: self.buffer.usage(),
+            size: (size_of::<T>() as u64 * new_len as u64),
+            mapped_at_creation: false,
+        });
+
+        self.len = new_len;
+        self.gen += 1;
+        self.memory.set(buffer.size());
+        mem::replace(&mut self.buffer, buffer)
+    }
+
     pub fn resize(&mut self, gpu: &Gpu, new_len: usize, preserve_contents: bool) {
         tracing::debug!(?new_len, "resize");
         let mut encoder = gpu
@@ -161,6 +191,7 @@ where
         }
 
         self.len = new_len;
+        self.memory.set(buffer.size());
         self.buffer = buffer;
         self.gen += 1;
     }
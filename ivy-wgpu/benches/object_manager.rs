@@ -0,0 +1,77 @@
+use criterion::{criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion};
+use flax::{Entity, Query, World};
+use glam::Vec3;
+use ivy_assets::AssetCache;
+use ivy_core::{
+    components::{color, world_transform, TransformBundle},
+    Color, ColorExt,
+};
+use ivy_graphics::mesh::MeshData;
+use ivy_wgpu::{components::mesh, mesh_desc::MeshDesc, renderer::ObjectManager, Gpu};
+
+/// A grid of `count` meshes, as with instanced scenery
+fn grid_scene(world: &mut World, assets: &AssetCache, count: usize) {
+    let mesh_data = assets.insert(MeshData::quad());
+    let width = (count as f32).sqrt().ceil() as usize;
+
+    for i in 0..count {
+        Entity::builder()
+            .mount(TransformBundle::default().with_position(Vec3::new(
+                (i % width) as f32,
+                0.0,
+                (i / width) as f32,
+            )))
+            .set(mesh(), MeshDesc::content(mesh_data.clone()))
+            .set(color(), Color::white())
+            .spawn(world);
+    }
+}
+
+fn object_manager(c: &mut Criterion) {
+    let gpu = futures::executor::block_on(Gpu::headless());
+    let assets = AssetCache::new();
+
+    let mut group = c.benchmark_group("object_manager");
+
+    for count in [1000, 10_000] {
+        group.bench_function(BenchmarkId::new("collect", count), |b| {
+            b.iter_batched(
+                || {
+                    let mut world = World::new();
+                    grid_scene(&mut world, &assets, count);
+                    let manager = ObjectManager::new(&mut world, &gpu);
+                    (world, manager)
+                },
+                |(mut world, mut manager)| {
+                    manager.update(&mut world, &assets, &gpu).unwrap();
+                    (world, manager)
+                },
+                BatchSize::LargeInput,
+            )
+        });
+
+        let mut world = World::new();
+        grid_scene(&mut world, &assets, count);
+        let mut manager = ObjectManager::new(&mut world, &gpu);
+        manager.update(&mut world, &assets, &gpu).unwrap();
+
+        group.bench_function(BenchmarkId::new("unchanged", count), |b| {
+            b.iter(|| manager.update(&mut world, &assets, &gpu).unwrap())
+        });
+
+        group.bench_function(BenchmarkId::new("all_moved", count), |b| {
+            b.iter(|| {
+                for transform in &mut Query::new(world_transform().as_mut()).borrow(&world) {
+                    transform.w_axis.y += 0.01;
+                }
+
+                manager.update(&mut world, &assets, &gpu).unwrap()
+            })
+        });
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, object_manager);
+criterion_main!(benches);
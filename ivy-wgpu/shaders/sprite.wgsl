@@ -0,0 +1,66 @@
+struct VertexOutput {
+    @builtin(position) pos: vec4<f32>,
+    @location(0) tex_coord: vec2<f32>,
+    @location(1) color: vec4<f32>,
+}
+
+struct Globals {
+    viewproj: mat4x4<f32>,
+    view: mat4x4<f32>,
+    proj: mat4x4<f32>,
+    camera_pos: vec3<f32>,
+}
+
+struct Sprite {
+    world: mat4x4<f32>,
+    color: vec4<f32>,
+    // Texture coordinates of the bottom left and top right corners
+    uv_min: vec2<f32>,
+    uv_max: vec2<f32>,
+}
+
+@group(0) @binding(0)
+var<uniform> globals: Globals;
+
+@group(1) @binding(0)
+var<storage, read> sprites: array<Sprite>;
+
+@group(2) @binding(0)
+var sprite_texture: texture_2d<f32>;
+
+@group(2) @binding(1)
+var sprite_sampler: sampler;
+
+// Two counter-clockwise triangles of a unit quad
+const CORNERS = array<vec2<f32>, 6>(
+    vec2(0.0, 0.0),
+    vec2(1.0, 0.0),
+    vec2(1.0, 1.0),
+    vec2(0.0, 0.0),
+    vec2(1.0, 1.0),
+    vec2(0.0, 1.0),
+);
+
+@vertex
+fn vs_main(@builtin(vertex_index) vertex_index: u32, @builtin(instance_index) instance: u32) -> VertexOutput {
+    let sprite = sprites[instance];
+    var corners = CORNERS;
+    let corner = corners[vertex_index];
+
+    var out: VertexOutput;
+    out.pos = globals.viewproj * sprite.world * vec4(corner - 0.5, 0.0, 1.0);
+    out.tex_coord = mix(sprite.uv_min, sprite.uv_max, corner);
+    out.color = sprite.color;
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let color = textureSample(sprite_texture, sprite_sampler, in.tex_coord) * in.color;
+
+    if color.a < 0.01 {
+        discard;
+    }
+
+    return color;
+}
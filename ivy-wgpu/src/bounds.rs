@@ -0,0 +1,74 @@
+use std::collections::HashMap;
+
+use anyhow::Context;
+use flax::{entity_ids, filter::ChangeFilter, EntityIds, FetchExt, Query, World};
+use ivy_assets::AssetCache;
+use ivy_core::{
+    bounds::{aabb, bounding_sphere, Aabb, BoundingSphere},
+    notifications::{notify, Notification},
+    profiling::profile_function,
+};
+use ivy_graphics::mesh::POSITION_ATTRIBUTE;
+
+use crate::{components::mesh, mesh_desc::MeshDesc};
+
+/// Computes the local space bounds of a mesh from its vertex positions
+pub fn mesh_bounds(assets: &AssetCache, mesh: &MeshDesc) -> anyhow::Result<(Aabb, BoundingSphere)> {
+    let data = mesh.load_data(assets)?;
+    let positions = data
+        .get_attribute(POSITION_ATTRIBUTE)
+        .and_then(|v| v.as_vec3())
+        .context("Mesh has no positions")?;
+
+    let aabb = Aabb::from_points(positions.iter().copied()).context("Mesh is empty")?;
+    let sphere = BoundingSphere::from_points(positions.iter().copied()).unwrap();
+
+    Ok((aabb, sphere))
+}
+
+/// Sets the [`aabb`] and [`bounding_sphere`] of entities when their [`mesh`] is added or changed.
+///
+/// The bounding sphere of skinned meshes is updated by the renderer to contain their current pose.
+pub(crate) struct MeshBounds {
+    query: Query<(EntityIds, ChangeFilter<MeshDesc>)>,
+}
+
+impl MeshBounds {
+    pub fn new() -> Self {
+        Self {
+            query: Query::new((entity_ids(), mesh().modified())),
+        }
+    }
+
+    pub fn update(&mut self, world: &mut World, assets: &AssetCache) -> anyhow::Result<()> {
+        profile_function!();
+
+        // Many entities share the same mesh when spawned together
+        let mut computed = HashMap::new();
+        let mut new_bounds = Vec::new();
+
+        for (id, mesh) in &mut self.query.borrow(world) {
+            let bounds = match computed.get(mesh) {
+                Some(&v) => v,
+                None => match mesh_bounds(assets, mesh) {
+                    Ok(v) => *computed.entry(mesh.clone()).or_insert(v),
+                    Err(err) => {
+                        let err = err.context("Failed to compute mesh bounds");
+                        tracing::error!("{err:?}");
+                        notify(assets, Notification::error(format!("{err:#}")));
+                        continue;
+                    }
+                },
+            };
+
+            new_bounds.push((id, bounds));
+        }
+
+        for (id, (local_aabb, sphere)) in new_bounds {
+            world.set(id, aabb(), local_aabb)?;
+            world.set(id, bounding_sphere(), sphere)?;
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,178 @@
+use flax::{EntityRef, FetchExt, Query, World};
+use glam::{vec2, Mat4, Vec2, Vec3, Vec4Swizzles};
+use ivy_core::components::{main_camera, world_transform};
+
+use crate::{camera_target::Viewport, components::projection_matrix};
+
+/// Converts a normalized window position, with the origin at the top left, to normalized device
+/// coordinates
+pub fn screen_to_ndc(pos: Vec2) -> Vec2 {
+    vec2(pos.x * 2.0 - 1.0, 1.0 - pos.y * 2.0)
+}
+
+/// Converts normalized device coordinates to a normalized window position
+pub fn ndc_to_screen(ndc: Vec2) -> Vec2 {
+    vec2(ndc.x * 0.5 + 0.5, 0.5 - ndc.y * 0.5)
+}
+
+/// The transform and projection of a camera entity, used to convert between screen and world
+/// space, such as for mouse picking or placing markers over objects.
+///
+/// Works for both perspective and orthographic projections.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Camera {
+    pub transform: Mat4,
+    pub projection: Mat4,
+}
+
+impl Camera {
+    pub fn new(transform: Mat4, projection: Mat4) -> Self {
+        Self {
+            transform,
+            projection,
+        }
+    }
+
+    pub fn from_entity(entity: &EntityRef) -> Option<Self> {
+        Some(Self::new(
+            entity.get_copy(world_transform()).ok()?,
+            entity.get_copy(projection_matrix()).ok()?,
+        ))
+    }
+
+    /// Returns the camera marked with `main_camera`
+    pub fn main(world: &World) -> Option<Self> {
+        Query::new((world_transform().copied(), projection_matrix().copied()))
+            .with(main_camera())
+            .borrow(world)
+            .first()
+            .map(|(transform, projection)| Self::new(transform, projection))
+    }
+
+    pub fn position(&self) -> Vec3 {
+        self.transform.transform_point3(Vec3::ZERO)
+    }
+
+    pub fn view(&self) -> Mat4 {
+        self.transform.inverse()
+    }
+
+    pub fn view_proj(&self) -> Mat4 {
+        self.projection * self.view()
+    }
+
+    /// Returns the world space origin and direction of a ray through normalized device
+    /// coordinates
+    pub fn screen_to_ray(&self, ndc: Vec2) -> (Vec3, Vec3) {
+        let inv_proj = self.projection.inverse();
+
+        // Two depths which are finite for both regular and reversed infinite depth
+        let a = inv_proj.project_point3(ndc.extend(0.25));
+        let b = inv_proj.project_point3(ndc.extend(0.75));
+
+        let mut dir = (b - a).normalize_or_zero();
+        // The camera looks along -Z
+        if dir.z > 0.0 {
+            dir = -dir;
+        }
+
+        // Start the ray at the plane of the camera, which is the eye for perspective projections
+        let origin = if dir.z != 0.0 {
+            a - dir * (a.z / dir.z)
+        } else {
+            a
+        };
+
+        (
+            self.transform.transform_point3(origin),
+            self.transform.transform_vector3(dir).normalize_or_zero(),
+        )
+    }
+
+    /// Projects a world space point to normalized device coordinates, or `None` if it is behind
+    /// the camera
+    pub fn world_to_screen(&self, point: Vec3) -> Option<Vec2> {
+        let view = self.view().transform_point3(point);
+        if view.z >= 0.0 {
+            return None;
+        }
+
+        let clip = self.projection * view.extend(1.0);
+        Some(clip.xy() / clip.w)
+    }
+
+    /// Returns a ray through a normalized window position, for a camera displayed in `viewport`
+    pub fn viewport_to_ray(&self, viewport: &Viewport, pos: Vec2) -> (Vec3, Vec3) {
+        self.screen_to_ray(screen_to_ndc(viewport.to_local(pos)))
+    }
+
+    /// Projects a world space point to a normalized window position, for a camera displayed in
+    /// `viewport`
+    pub fn world_to_viewport(&self, viewport: &Viewport, point: Vec3) -> Option<Vec2> {
+        let ndc = self.world_to_screen(point)?;
+        Some(viewport.to_window(ndc_to_screen(ndc)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::{vec3, Quat};
+
+    use super::*;
+
+    fn round_trip(camera: Camera, point: Vec3) {
+        let ndc = camera.world_to_screen(point).unwrap();
+        let (origin, dir) = camera.screen_to_ray(ndc);
+
+        let to_point = point - origin;
+        assert!(to_point.dot(dir) > 0.0);
+        assert!(
+            to_point.reject_from(dir).length() < 1e-3,
+            "{ndc} {origin} {dir}"
+        );
+    }
+
+    #[test]
+    fn perspective() {
+        let camera = Camera::new(
+            Mat4::from_rotation_translation(Quat::from_rotation_y(0.5), vec3(1.0, 2.0, 3.0)),
+            Mat4::perspective_rh(1.0, 1.5, 0.1, 100.0),
+        );
+
+        round_trip(camera, vec3(-2.0, 1.0, -5.0));
+        round_trip(camera, vec3(0.0, 3.0, -1.0));
+
+        let (origin, _) = camera.screen_to_ray(Vec2::ZERO);
+        assert!(origin.abs_diff_eq(camera.position(), 1e-4));
+
+        assert_eq!(camera.world_to_screen(vec3(5.0, 2.0, 10.0)), None);
+    }
+
+    #[test]
+    fn orthographic() {
+        let camera = Camera::new(
+            Mat4::from_translation(vec3(0.0, 0.0, 10.0)),
+            Mat4::orthographic_rh(-4.0, 4.0, -2.0, 2.0, 0.1, 100.0),
+        );
+
+        round_trip(camera, vec3(3.0, -1.0, 0.0));
+
+        let (origin, dir) = camera.screen_to_ray(vec2(0.5, 0.5));
+        assert!(origin.abs_diff_eq(vec3(2.0, 1.0, 10.0), 1e-4));
+        assert!(dir.abs_diff_eq(Vec3::NEG_Z, 1e-4));
+    }
+
+    #[test]
+    fn viewport() {
+        let camera = Camera::new(Mat4::IDENTITY, Mat4::perspective_rh(1.0, 1.0, 0.1, 100.0));
+        let viewport = Viewport::new(vec2(0.5, 0.0), vec2(1.0, 1.0));
+
+        let pos = camera
+            .world_to_viewport(&viewport, vec3(0.0, 0.0, -5.0))
+            .unwrap();
+        assert!(pos.abs_diff_eq(vec2(0.75, 0.5), 1e-5));
+
+        let (_, dir) = camera.viewport_to_ray(&viewport, pos);
+        assert!(dir.abs_diff_eq(Vec3::NEG_Z, 1e-5));
+    }
+}
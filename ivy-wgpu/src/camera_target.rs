@@ -0,0 +1,307 @@
+use flax::{entity_ids, Entity, Query, World};
+use glam::{vec2, Vec2};
+use ivy_assets::{Asset, AssetCache};
+use ivy_core::{components::main_camera, letterbox, PhysicalExtent};
+use wgpu::{Extent3d, Texture, TextureDescriptor, TextureDimension, TextureFormat, TextureUsages};
+
+use crate::{
+    components::{camera_target, player_view},
+    Gpu,
+};
+
+/// Texture a camera renders into instead of the window.
+///
+/// The texture can be sampled as a material input to show the view of the camera inside the
+/// scene, e.g; security monitors, mirrors, or portals.
+///
+/// The camera draws into a separate render texture which is copied to the sampled texture once the
+/// view is complete. A camera which sees a material showing its own target therefore samples the
+/// previous frame, rather than the texture it is drawing into.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct CameraTarget {
+    texture: Asset<Texture>,
+    render_texture: Asset<Texture>,
+}
+
+impl CameraTarget {
+    pub fn new(
+        gpu: &Gpu,
+        assets: &AssetCache,
+        label: &str,
+        width: u32,
+        height: u32,
+        format: TextureFormat,
+    ) -> Self {
+        let create_texture = |label: &str, usage| {
+            gpu.device.create_texture(&TextureDescriptor {
+                label: Some(label),
+                size: Extent3d {
+                    width,
+                    height,
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: TextureDimension::D2,
+                format,
+                usage,
+                view_formats: &[],
+            })
+        };
+
+        let texture = create_texture(
+            label,
+            TextureUsages::TEXTURE_BINDING | TextureUsages::COPY_DST | TextureUsages::COPY_SRC,
+        );
+
+        let render_texture = create_texture(
+            &format!("{label}.render"),
+            TextureUsages::RENDER_ATTACHMENT | TextureUsages::COPY_SRC,
+        );
+
+        Self {
+            texture: assets.insert(texture),
+            render_texture: assets.insert(render_texture),
+        }
+    }
+
+    /// Returns the texture sampled by materials
+    pub fn texture(&self) -> &Asset<Texture> {
+        &self.texture
+    }
+
+    /// Returns the texture the camera draws into, which is copied to [`Self::texture`] after
+    /// the view is drawn
+    pub fn render_texture(&self) -> &Asset<Texture> {
+        &self.render_texture
+    }
+
+    pub fn extent(&self) -> Extent3d {
+        self.texture.size()
+    }
+
+    pub fn format(&self) -> TextureFormat {
+        self.texture.format()
+    }
+}
+
+/// Selects the camera entity a view is rendered from
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum CameraSelection {
+    /// The camera marked with `main_camera`
+    #[default]
+    Main,
+    /// The camera rendering into the given target
+    Target(CameraTarget),
+    /// A specific camera entity
+    Entity(Entity),
+    /// The camera with the matching `player_view` index
+    PlayerView(usize),
+}
+
+impl CameraSelection {
+    pub fn find(&self, world: &World) -> Option<Entity> {
+        match self {
+            CameraSelection::Main => Query::new(entity_ids())
+                .with(main_camera())
+                .borrow(world)
+                .first(),
+            CameraSelection::Target(target) => Query::new((entity_ids(), camera_target()))
+                .borrow(world)
+                .iter()
+                .find(|(_, v)| *v == target)
+                .map(|(id, _)| id),
+            CameraSelection::Entity(id) => world.is_alive(*id).then_some(*id),
+            CameraSelection::PlayerView(index) => Query::new((entity_ids(), player_view()))
+                .borrow(world)
+                .iter()
+                .find(|(_, v)| *v == index)
+                .map(|(id, _)| id),
+        }
+    }
+}
+
+impl From<CameraTarget> for CameraSelection {
+    fn from(v: CameraTarget) -> Self {
+        Self::Target(v)
+    }
+}
+
+impl From<Entity> for CameraSelection {
+    fn from(v: Entity) -> Self {
+        Self::Entity(v)
+    }
+}
+
+/// Normalized region of the window a view is displayed in, with the origin at the top left
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Viewport {
+    pub min: Vec2,
+    pub max: Vec2,
+}
+
+impl Viewport {
+    pub const FULL: Self = Self {
+        min: Vec2::ZERO,
+        max: Vec2::ONE,
+    };
+
+    pub fn new(min: Vec2, max: Vec2) -> Self {
+        Self { min, max }
+    }
+
+    /// Evenly divides the window between `count` views.
+    ///
+    /// Two views are placed side by side, and more views in a grid filled row by row.
+    pub fn split(count: usize) -> Vec<Self> {
+        let columns = (count as f32).sqrt().ceil().max(1.0) as usize;
+        let rows = count.div_ceil(columns).max(1);
+
+        let size = vec2(1.0 / columns as f32, 1.0 / rows as f32);
+
+        (0..count)
+            .map(|i| {
+                let min = vec2((i % columns) as f32, (i / columns) as f32) * size;
+                Self::new(min, min + size)
+            })
+            .collect()
+    }
+
+    /// Returns the largest centered viewport with the given aspect ratio within a window of
+    /// `size`, leaving black bars on the sides which do not fit
+    pub fn letterbox(size: PhysicalExtent, aspect: f32) -> Self {
+        let (min, max) = letterbox(size.as_vec(), aspect);
+        Self::new(min, max)
+    }
+
+    /// Converts a normalized window position to a normalized position within the viewport
+    pub fn to_local(&self, pos: Vec2) -> Vec2 {
+        (pos - self.min) / (self.max - self.min).max(Vec2::splat(f32::EPSILON))
+    }
+
+    /// Converts a normalized position within the viewport to a normalized window position
+    pub fn to_window(&self, local: Vec2) -> Vec2 {
+        self.min + local * (self.max - self.min)
+    }
+
+    /// Returns the pixel rectangle `(x, y, width, height)` of the viewport within a target of the
+    /// given size
+    pub fn to_physical(&self, size: PhysicalExtent) -> (u32, u32, u32, u32) {
+        let size = size.as_vec();
+
+        let min = (self.min.clamp(Vec2::ZERO, Vec2::ONE) * size)
+            .round()
+            .min((size - Vec2::ONE).max(Vec2::ZERO));
+        let max = (self.max.clamp(Vec2::ZERO, Vec2::ONE) * size).round();
+
+        let extent = (max - min).max(Vec2::ONE);
+
+        (min.x as u32, min.y as u32, extent.x as u32, extent.y as u32)
+    }
+
+    /// Returns the aspect ratio of the viewport within a window of the given aspect ratio
+    pub fn aspect(&self, window_aspect: f32) -> f32 {
+        let size = (self.max - self.min).max(Vec2::splat(f32::EPSILON));
+        window_aspect * size.x / size.y
+    }
+
+    /// Size of the viewport in pixels within a target of the given size
+    pub fn extent(&self, size: PhysicalExtent) -> Extent3d {
+        let (_, _, width, height) = self.to_physical(size);
+
+        Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        }
+    }
+}
+
+/// Returns the aspect ratio of a camera's view within a window of the given aspect ratio.
+///
+/// Split-screen views follow the aspect ratio of their viewport.
+pub fn view_aspect(
+    window_aspect: f32,
+    player_view: Option<usize>,
+    viewports: Option<&[Viewport]>,
+) -> f32 {
+    player_view
+        .zip(viewports)
+        .and_then(|(index, viewports)| viewports.get(index))
+        .map_or(window_aspect, |v| v.aspect(window_aspect))
+}
+
+impl Default for Viewport {
+    fn default() -> Self {
+        Self::FULL
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn split_viewports() {
+        assert_eq!(Viewport::split(1), [Viewport::FULL]);
+
+        assert_eq!(
+            Viewport::split(2),
+            [
+                Viewport::new(vec2(0.0, 0.0), vec2(0.5, 1.0)),
+                Viewport::new(vec2(0.5, 0.0), vec2(1.0, 1.0)),
+            ]
+        );
+
+        assert_eq!(
+            Viewport::split(3),
+            [
+                Viewport::new(vec2(0.0, 0.0), vec2(0.5, 0.5)),
+                Viewport::new(vec2(0.5, 0.0), vec2(1.0, 0.5)),
+                Viewport::new(vec2(0.0, 0.5), vec2(0.5, 1.0)),
+            ]
+        );
+    }
+
+    #[test]
+    fn physical_viewport() {
+        let size = PhysicalExtent::new(1920, 1080);
+        let [left, right] = Viewport::split(2).try_into().unwrap();
+
+        assert_eq!(left.to_physical(size), (0, 0, 960, 1080));
+        assert_eq!(right.to_physical(size), (960, 0, 960, 1080));
+
+        // Never collapse to an empty rectangle
+        assert_eq!(
+            Viewport::new(vec2(0.5, 0.5), vec2(0.5, 0.5)).to_physical(size),
+            (960, 540, 1, 1)
+        );
+    }
+
+    #[test]
+    fn viewport_aspect() {
+        let window_aspect = 16.0 / 9.0;
+        let [left, _] = Viewport::split(2).try_into().unwrap();
+        let [top_left, ..] = Viewport::split(4).try_into().unwrap();
+
+        assert_eq!(Viewport::FULL.aspect(window_aspect), window_aspect);
+        assert_eq!(left.aspect(window_aspect), 8.0 / 9.0);
+        assert_eq!(top_left.aspect(window_aspect), window_aspect);
+
+        let viewports = [top_left, left];
+        assert_eq!(
+            view_aspect(window_aspect, None, Some(&viewports)),
+            window_aspect
+        );
+        assert_eq!(
+            view_aspect(window_aspect, Some(1), Some(&viewports)),
+            8.0 / 9.0
+        );
+        // Views without a viewport use the whole window
+        assert_eq!(
+            view_aspect(window_aspect, Some(2), Some(&viewports)),
+            window_aspect
+        );
+        assert_eq!(view_aspect(window_aspect, Some(1), None), window_aspect);
+    }
+}
@@ -1,19 +1,41 @@
 use flax::{component, Debuggable};
 use glam::Mat4;
-use winit::dpi::{LogicalPosition, LogicalSize};
+use ivy_core::{LogicalExtent, PhysicalExtent};
+use winit::dpi::LogicalPosition;
 
 use crate::{
+    camera_target::{CameraTarget, Viewport},
+    debug_view::DebugView,
     driver::WindowHandle,
     light::{LightKind, LightParams},
+    lod::MeshLods,
     material_desc::MaterialData,
     mesh_desc::MeshDesc,
-    renderer::{shadowmapping::LightShadowData, EnvironmentData},
+    renderer::{
+        gizmos_renderer::GizmoStats, picking::Picker, render_stats::RenderStats,
+        shadowmapping::LightShadowData, EnvironmentData,
+    },
+    sprite::Sprite,
 };
 
 component! {
     pub projection_matrix: Mat4 => [ Debuggable ],
 
+    /// Renders the camera into a texture instead of the window
+    pub camera_target: CameraTarget,
+
+    /// Index of the split-screen view rendered by the camera
+    pub player_view: usize,
+    /// Viewports of the split-screen views indexed by [`player_view`], set on the engine entity by
+    /// the renderer
+    pub split_screen_viewports: Vec<Viewport>,
+
     pub mesh: MeshDesc,
+    /// Replaces the [`mesh`] by the size of the entity on screen
+    pub mesh_lods: MeshLods,
+
+    /// Draws the entity as a textured quad using the sprite renderer
+    pub sprite: Sprite,
 
     pub forward_pass: MaterialData,
     pub transparent_pass: MaterialData,
@@ -24,7 +46,12 @@ component! {
     pub window: WindowHandle,
 
     pub window_cursor_position: LogicalPosition<f32>,
-    pub window_size: LogicalSize<f32>,
+    /// Size of the window in logical units, which cursor positions are relative to
+    pub window_size: LogicalExtent,
+    /// Size of the window framebuffer in physical pixels
+    pub window_physical_size: PhysicalExtent,
+    /// Ratio of physical to logical pixels of the window, e.g; `2.0` on HiDPI displays
+    pub window_scale_factor: f64,
 
 
     pub light_params: LightParams,
@@ -35,4 +62,16 @@ component! {
     pub light_shadow_data: LightShadowData,
 
     pub environment_data: EnvironmentData,
+
+    /// Visualization used by the renderer, set on the engine entity
+    pub debug_view: DebugView,
+
+    /// Picks entities from the screen, set on the engine entity
+    pub picker: Picker,
+
+    /// Gizmo instances of the last frame, set on the engine entity by the gizmos renderer
+    pub gizmo_stats: GizmoStats,
+
+    /// Draw calls and triangles of the last frame, set on the engine entity
+    pub render_stats: RenderStats,
 }
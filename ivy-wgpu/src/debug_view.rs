@@ -0,0 +1,64 @@
+use wgpu::PolygonMode;
+
+/// Renderer-wide visualization used to inspect the scene.
+///
+/// Set through the [`debug_view`](crate::components::debug_view) component of the engine entity.
+/// Only applies to mesh renderers created with
+/// [`with_debug_views`](crate::renderer::mesh_renderer::MeshRenderer::with_debug_views).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum DebugView {
+    /// Regular shading
+    #[default]
+    Lit,
+    /// Draws the triangle edges of each mesh
+    Wireframe,
+    /// World space normals, after normal mapping
+    Normals,
+    /// Base color of the material, without lighting
+    Albedo,
+    /// Roughness in the red channel and metallic in the green channel
+    RoughnessMetallic,
+    /// Brightens each pixel for every surface drawn to it, regardless of depth
+    Overdraw,
+    /// Colors surfaces by distance band from the camera, from green for the closest to red for the
+    /// farthest.
+    ///
+    /// Meshes are not switched to lower detail levels yet, so this shows where they would be.
+    LodColor,
+}
+
+impl DebugView {
+    pub const ALL: [Self; 7] = [
+        Self::Lit,
+        Self::Wireframe,
+        Self::Normals,
+        Self::Albedo,
+        Self::RoughnessMetallic,
+        Self::Overdraw,
+        Self::LodColor,
+    ];
+
+    /// Value of the `DEBUG_VIEW` shader def, for views implemented as shader permutations
+    pub fn shader_value(&self) -> Option<u32> {
+        match self {
+            DebugView::Normals => Some(1),
+            DebugView::Albedo => Some(2),
+            DebugView::RoughnessMetallic => Some(3),
+            DebugView::LodColor => Some(4),
+            DebugView::Lit | DebugView::Wireframe | DebugView::Overdraw => None,
+        }
+    }
+
+    pub fn polygon_mode(&self) -> PolygonMode {
+        match self {
+            DebugView::Wireframe => PolygonMode::Line,
+            _ => PolygonMode::Fill,
+        }
+    }
+
+    /// Returns the following view, e.g; to cycle through views with a key
+    pub fn next(&self) -> Self {
+        let index = Self::ALL.iter().position(|v| v == self).unwrap_or_default();
+        Self::ALL[(index + 1) % Self::ALL.len()]
+    }
+}
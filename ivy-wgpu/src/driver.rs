@@ -7,28 +7,44 @@ use std::{
 use atomic_refcell::AtomicRefCell;
 use flax::{components::name, Entity};
 use glam::{vec2, Vec2};
-use ivy_core::{driver::Driver, App};
+use ivy_core::{
+    driver::{Driver, FrameLimit, FramePacer},
+    App, PhysicalExtent,
+};
 use ivy_input::types::{CursorMoved, InputEvent, KeyboardInput, MouseInput, ScrollMotion};
 use winit::{
     application::ApplicationHandler,
     dpi::PhysicalPosition,
     event::WindowEvent,
-    event_loop::{ActiveEventLoop, EventLoop},
+    event_loop::{ActiveEventLoop, ControlFlow, EventLoop},
     window::{CursorGrabMode, Window, WindowAttributes, WindowId},
 };
 
 use crate::{
-    components::{main_window, window, window_cursor_position, window_size},
+    components::{
+        main_window, window, window_cursor_position, window_physical_size, window_scale_factor,
+        window_size,
+    },
     events::{ApplicationReady, RedrawEvent, ResizedEvent},
 };
 
 pub struct WinitDriver {
     window_attributes: WindowAttributes,
+    frame_limit: FrameLimit,
 }
 
 impl WinitDriver {
     pub fn new(window_attributes: WindowAttributes) -> Self {
-        Self { window_attributes }
+        Self {
+            window_attributes,
+            frame_limit: FrameLimit::default(),
+        }
+    }
+
+    /// Limits the frame rate, and the rate while the window is unfocused or minimized
+    pub fn with_frame_limit(mut self, frame_limit: FrameLimit) -> Self {
+        self.frame_limit = frame_limit;
+        self
     }
 }
 
@@ -52,6 +68,10 @@ impl Driver for WinitDriver {
             stats: AppStats::new(16),
             main_window: Default::default(),
             window_attributes: self.window_attributes.clone(),
+            pacer: FramePacer::new(self.frame_limit),
+            focused: true,
+            occluded: false,
+            minimized: false,
         })?;
 
         Ok(())
@@ -68,6 +88,10 @@ pub struct WinitEventHandler<'a> {
     stats: AppStats,
     main_window: Option<Entity>,
     window_attributes: WindowAttributes,
+    pacer: FramePacer,
+    focused: bool,
+    occluded: bool,
+    minimized: bool,
 }
 
 impl ApplicationHandler for WinitEventHandler<'_> {
@@ -80,6 +104,9 @@ impl ApplicationHandler for WinitEventHandler<'_> {
                 .unwrap(),
         );
 
+        let inner_size = window.inner_size();
+        let physical_size = PhysicalExtent::new(inner_size.width, inner_size.height);
+
         let entity = Entity::builder()
             .set(name(), "MainWindow".into())
             .set(
@@ -90,8 +117,13 @@ impl ApplicationHandler for WinitEventHandler<'_> {
                 },
             )
             .set_default(main_window())
-            .set_default(window_size())
+            .set(
+                window_size(),
+                physical_size.to_logical(window.scale_factor()),
+            )
+            .set(window_physical_size(), physical_size)
             .set_default(window_cursor_position())
+            .set(window_scale_factor(), window.scale_factor())
             .spawn(&mut self.app.world);
 
         self.scale_factor = window.scale_factor();
@@ -128,6 +160,17 @@ impl ApplicationHandler for WinitEventHandler<'_> {
 
     fn about_to_wait(&mut self, event_loop: &ActiveEventLoop) {
         let new_time = Instant::now();
+
+        if let Some(next_frame) = self.pacer.next_frame() {
+            if new_time < next_frame {
+                event_loop.set_control_flow(ControlFlow::WaitUntil(next_frame));
+                return;
+            }
+        }
+
+        event_loop.set_control_flow(ControlFlow::Poll);
+        self.pacer.begin_frame(new_time);
+
         let delta = new_time.duration_since(self.current_time);
         self.current_time = new_time;
         self.stats.record_frame(delta);
@@ -148,10 +191,20 @@ impl ApplicationHandler for WinitEventHandler<'_> {
             tracing::error!("{err:?}");
             event_loop.exit();
         }
+
+        if let Some(w) = self.main_window {
+            let handle = self.app.world.get(w, window()).unwrap();
+            handle.window.request_redraw();
+        }
     }
 }
 
 impl WinitEventHandler<'_> {
+    fn update_idle(&mut self) {
+        self.pacer
+            .set_idle(!self.focused || self.occluded || self.minimized);
+    }
+
     fn process_event(
         &mut self,
         event_loop: &ActiveEventLoop,
@@ -164,14 +217,17 @@ impl WinitEventHandler<'_> {
                 token: _,
             } => todo!(),
             WindowEvent::Resized(size) => {
-                let logical_size = size.to_logical(self.scale_factor);
+                self.minimized = size.width == 0 || size.height == 0;
+                self.update_idle();
+
+                let physical_size = PhysicalExtent::new(size.width, size.height);
 
                 let window = self.app.world().entity(window_id).unwrap();
-                *window.get_mut(window_size()).unwrap() = logical_size;
+                *window.get_mut(window_size()).unwrap() =
+                    physical_size.to_logical(self.scale_factor);
+                *window.get_mut(window_physical_size()).unwrap() = physical_size;
 
-                self.app.emit_event(ResizedEvent {
-                    physical_size: size,
-                })?;
+                self.app.emit_event(ResizedEvent { physical_size })?;
             }
             WindowEvent::Moved(_) => {}
             WindowEvent::CloseRequested => event_loop.exit(),
@@ -179,7 +235,10 @@ impl WinitEventHandler<'_> {
             WindowEvent::DroppedFile(_) => todo!(),
             WindowEvent::HoveredFile(_) => todo!(),
             WindowEvent::HoveredFileCancelled => todo!(),
-            WindowEvent::Focused(_focus) => {}
+            WindowEvent::Focused(focused) => {
+                self.focused = focused;
+                self.update_idle();
+            }
             WindowEvent::KeyboardInput { event, .. } => {
                 self.app.emit_event(InputEvent::Keyboard(KeyboardInput {
                     modifiers: self.modifiers,
@@ -197,7 +256,7 @@ impl WinitEventHandler<'_> {
                 device_id: _,
                 position,
             } => {
-                let logical_pos = position.to_logical(1.0);
+                let logical_pos = position.to_logical(self.scale_factor);
                 let window_entity = self.app.world().entity(window_id).unwrap();
 
                 let size;
@@ -214,7 +273,7 @@ impl WinitEventHandler<'_> {
                 self.app.emit_event(InputEvent::CursorMoved(CursorMoved {
                     absolute_position: logical_pos,
                     normalized_position: vec2(logical_pos.x, logical_pos.y)
-                        / vec2(size.width, size.height),
+                        / size.as_vec().max(Vec2::ONE),
                 }))?;
             }
             WindowEvent::CursorEntered { device_id: _ } => {
@@ -277,18 +336,22 @@ impl WinitEventHandler<'_> {
                 inner_size_writer: _,
             } => {
                 self.scale_factor = scale_factor;
+
+                let window = self.app.world().entity(window_id).unwrap();
+                *window.get_mut(window_scale_factor()).unwrap() = scale_factor;
+
+                // The physical size is kept until the following resize, while the logical size
+                // changes immediately
+                let physical_size = window.get_copy(window_physical_size()).unwrap();
+                *window.get_mut(window_size()).unwrap() = physical_size.to_logical(scale_factor);
             }
             WindowEvent::ThemeChanged(_) => {}
-            WindowEvent::Occluded(_) => {}
+            WindowEvent::Occluded(occluded) => {
+                self.occluded = occluded;
+                self.update_idle();
+            }
             WindowEvent::RedrawRequested => {
                 self.app.emit_event(RedrawEvent)?;
-                let window = self
-                    .app
-                    .world
-                    .get_mut(window_id, crate::components::window())
-                    .unwrap();
-
-                window.window.request_redraw();
             }
         }
 
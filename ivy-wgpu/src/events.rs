@@ -1,7 +1,7 @@
 use std::sync::Arc;
 
-use ivy_core::layer::events::Event;
-use winit::{dpi::PhysicalSize, window::Window};
+use ivy_core::{layer::events::Event, PhysicalExtent};
+use winit::window::Window;
 
 #[derive(Debug, Clone)]
 pub struct ApplicationReady(pub Arc<Window>);
@@ -11,9 +11,27 @@ pub struct RedrawEvent;
 
 #[derive(Debug, Clone)]
 pub struct ResizedEvent {
-    pub physical_size: PhysicalSize<u32>,
+    pub physical_size: PhysicalExtent,
 }
 
 impl Event for ApplicationReady {}
 impl Event for RedrawEvent {}
 impl Event for ResizedEvent {}
+
+/// Shaders of the [`ShaderLibrary`](crate::shader_library::ShaderLibrary) were reloaded from disk
+#[derive(Debug, Clone, Default)]
+pub struct ShaderReloaded {
+    /// Composable modules, which may be imported by any shader
+    pub modules: Vec<String>,
+    /// Paths of the reloaded shaders
+    pub shaders: Vec<String>,
+}
+
+impl ShaderReloaded {
+    /// Returns true if the shader at `path` needs to be processed again
+    pub fn affects(&self, path: &str) -> bool {
+        !self.modules.is_empty() || self.shaders.iter().any(|v| v == path)
+    }
+}
+
+impl Event for ShaderReloaded {}
@@ -1,15 +1,23 @@
-use std::sync::Arc;
+use std::{path::PathBuf, sync::Arc};
 
 use anyhow::Context;
 use flax::{component, World};
+use glam::UVec2;
 use ivy_assets::{stored::DynamicStore, AssetCache};
-use ivy_core::{components::engine, Layer};
+use ivy_core::{components::engine, Layer, PhysicalExtent};
 use ivy_wgpu_types::Surface;
-use wgpu::Queue;
-use winit::{dpi::PhysicalSize, window::Window};
+use wgpu::{PresentMode, Queue};
+use winit::window::Window;
 
 use crate::{
+    bounds::MeshBounds,
+    components::{picker, render_stats},
     events::{ApplicationReady, RedrawEvent, ResizedEvent},
+    lod::update_mesh_lods,
+    renderer::{
+        picking::{PendingPick, Picker},
+        render_stats::RenderStats,
+    },
     rendergraph::{ManagedTextureDesc, RenderGraph, TextureHandle},
     Gpu,
 };
@@ -86,7 +94,7 @@ pub trait Renderer {
         cmds: &mut flume::Receiver<RendererCommand>,
     ) -> anyhow::Result<()>;
 
-    fn on_resize(&mut self, gpu: &Gpu, physical_size: PhysicalSize<u32>);
+    fn on_resize(&mut self, gpu: &Gpu, physical_size: PhysicalExtent);
 }
 
 struct RenderingState {
@@ -94,15 +102,30 @@ struct RenderingState {
     renderer: Box<dyn Renderer>,
 }
 
+impl Drop for RenderingState {
+    fn drop(&mut self) {
+        if let Some(cache) = &self.gpu.pipeline_cache {
+            if let Err(err) = cache.save() {
+                tracing::error!("{err:?}");
+            }
+        }
+    }
+}
+
 /// Graphics layer
 ///
 /// Manages window and rendering
 pub struct GraphicsLayer {
     rendering_state: Option<RenderingState>,
     on_init: Option<OnInitFunc>,
+    pipeline_cache: Option<PathBuf>,
+    present_mode: Option<PresentMode>,
 
     commands_tx: flume::Sender<RendererCommand>,
     commands_rx: flume::Receiver<RendererCommand>,
+    picker: Picker,
+    /// Computes the bounds of meshes before they are drawn
+    bounds: MeshBounds,
 }
 
 impl GraphicsLayer {
@@ -118,11 +141,45 @@ impl GraphicsLayer {
             on_init: Some(Box::new(move |world, assets, store, gpu, surface| {
                 Ok(Box::new(on_init(world, assets, store, gpu, surface)?))
             })),
+            pipeline_cache: None,
+            present_mode: None,
             commands_tx,
             commands_rx,
+            picker: Picker::new(),
+            bounds: MeshBounds::new(),
         }
     }
 
+    /// Picks the entity drawn at `screen_pos`, in physical pixels.
+    ///
+    /// Requires the render graph to contain a
+    /// [`PickingNode`](crate::renderer::picking::PickingNode).
+    pub fn pick(&self, screen_pos: UVec2) -> PendingPick {
+        self.picker.pick(screen_pos)
+    }
+
+    /// Persist compiled pipelines to `dir`, reducing hitches on subsequent runs
+    pub fn with_pipeline_cache(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.pipeline_cache = Some(dir.into());
+        self
+    }
+
+    /// Set the present mode of the window surface
+    pub fn with_present_mode(mut self, present_mode: PresentMode) -> Self {
+        self.present_mode = Some(present_mode);
+        self
+    }
+
+    /// Wait for the vertical blank before presenting, limiting the frame rate to the refresh rate
+    /// of the display
+    pub fn with_vsync(self, vsync: bool) -> Self {
+        self.with_present_mode(if vsync {
+            PresentMode::AutoVsync
+        } else {
+            PresentMode::AutoNoVsync
+        })
+    }
+
     fn on_application_ready(
         &mut self,
         world: &mut World,
@@ -130,7 +187,15 @@ impl GraphicsLayer {
         store: &mut DynamicStore,
         window: Arc<Window>,
     ) -> Result<(), anyhow::Error> {
-        let (gpu, surface) = futures::executor::block_on(Gpu::with_surface(window));
+        let (mut gpu, mut surface) = futures::executor::block_on(Gpu::with_surface(window));
+
+        if let Some(present_mode) = self.present_mode {
+            surface.set_present_mode(&gpu, present_mode);
+        }
+
+        if let Some(dir) = &self.pipeline_cache {
+            gpu = gpu.with_pipeline_cache(dir);
+        }
 
         assets.register_service(gpu.clone());
 
@@ -147,12 +212,17 @@ impl GraphicsLayer {
         assets: &AssetCache,
         store: &mut DynamicStore,
     ) -> Result<(), anyhow::Error> {
+        update_mesh_lods(world)?;
+        self.bounds.update(world, assets)?;
+
         if let Some(state) = &mut self.rendering_state {
             state
                 .renderer
                 .process_commands(world, assets, store, &state.gpu, &mut self.commands_rx)
                 .context("Failed to process renderer commands before draw")?;
 
+            world.set(engine(), render_stats(), RenderStats::default())?;
+
             state
                 .renderer
                 .draw(world, assets, store, &state.gpu, &state.gpu.queue)?;
@@ -161,7 +231,7 @@ impl GraphicsLayer {
         Ok(())
     }
 
-    fn on_resize(&mut self, _: &mut World, physical_size: PhysicalSize<u32>) -> anyhow::Result<()> {
+    fn on_resize(&mut self, _: &mut World, physical_size: PhysicalExtent) -> anyhow::Result<()> {
         if let Some(state) = &mut self.rendering_state {
             state.renderer.on_resize(&state.gpu, physical_size);
         }
@@ -181,6 +251,7 @@ impl Layer for GraphicsLayer {
         Self: Sized,
     {
         world.set(engine(), renderer_commands(), self.commands_tx.clone())?;
+        world.set(engine(), picker(), self.picker.clone())?;
 
         events.subscribe(|this, ctx, ApplicationReady(window): &ApplicationReady| {
             this.on_application_ready(ctx.world, ctx.assets, ctx.store, window.clone())
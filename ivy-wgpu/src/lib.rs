@@ -1,8 +1,13 @@
+pub mod bounds;
+pub mod camera;
+pub mod camera_target;
 pub mod components;
+pub mod debug_view;
 pub mod driver;
 pub mod events;
 pub mod layer;
 pub mod light;
+pub mod lod;
 pub mod material;
 pub mod material_desc;
 pub mod mesh;
@@ -14,7 +19,9 @@ pub mod rendergraph;
 pub mod shader;
 pub mod shader_library;
 pub mod shaders;
+pub mod sprite;
 pub mod texture;
+pub mod texture_streaming;
 
 pub use ivy_wgpu_types as types;
 pub use ivy_wgpu_types::Gpu;
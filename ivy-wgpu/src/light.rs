@@ -1,4 +1,4 @@
-use ivy_core::{palette::Srgb, Bundle};
+use ivy_core::{color_from_temperature, palette::Srgb, Bundle};
 
 use crate::components::{cast_shadow, light_kind, light_params};
 
@@ -21,6 +21,11 @@ impl LightParams {
         }
     }
 
+    /// Creates a light with the color of the given temperature in Kelvin
+    pub fn from_temperature(kelvin: f32, intensity: f32) -> Self {
+        Self::new(color_from_temperature(kelvin), intensity)
+    }
+
     pub fn with_angular_cutoffs(mut self, inner_theta: f32, outer_theta: f32) -> Self {
         self.inner_theta = inner_theta;
         self.outer_theta = outer_theta;
@@ -0,0 +1,139 @@
+//! Selects the level of detail of meshes from the size of their bounds on screen.
+use flax::{entity_ids, FetchExt, Query, World};
+use glam::{Mat4, Vec3};
+use ivy_core::{
+    bounds::{bounding_sphere, BoundingSphere},
+    components::{main_camera, world_transform},
+    profiling::profile_function,
+};
+
+use crate::{
+    components::{mesh, mesh_lods, projection_matrix},
+    mesh_desc::MeshDesc,
+};
+
+/// Meshes of decreasing detail, which replace the [`mesh`] of the entity depending on how much of
+/// the screen its [`bounding_sphere`] covers
+#[derive(Debug, Clone, PartialEq)]
+pub struct MeshLods {
+    /// Sorted by decreasing coverage
+    levels: Vec<(MeshDesc, f32)>,
+}
+
+impl MeshLods {
+    /// Each level is used while the bounds cover at least the given fraction of the screen height.
+    ///
+    /// The level with the lowest coverage is used beyond that.
+    pub fn new(levels: impl IntoIterator<Item = (MeshDesc, f32)>) -> Self {
+        let mut levels = levels.into_iter().collect::<Vec<_>>();
+        levels.sort_by(|a, b| b.1.total_cmp(&a.1));
+
+        Self { levels }
+    }
+
+    /// Returns the mesh for the given screen coverage
+    pub fn select(&self, coverage: f32) -> Option<&MeshDesc> {
+        self.levels
+            .iter()
+            .find(|(_, min_coverage)| coverage >= *min_coverage)
+            .or(self.levels.last())
+            .map(|(mesh, _)| mesh)
+    }
+}
+
+/// Returns the fraction of the screen height covered by a world space sphere
+pub fn screen_coverage(sphere: &BoundingSphere, camera_pos: Vec3, projection: &Mat4) -> f32 {
+    let distance = sphere.center.distance(camera_pos);
+    if distance <= sphere.radius {
+        return f32::MAX;
+    }
+
+    // The focal length of the projection scales view space heights to normalized device
+    // coordinates
+    sphere.radius * projection.y_axis.y / distance
+}
+
+/// Replaces the [`mesh`] of entities with [`mesh_lods`] by their screen coverage from the main
+/// camera.
+///
+/// The bounds of the new mesh are computed by the graphics layer afterwards.
+pub(crate) fn update_mesh_lods(world: &mut World) -> anyhow::Result<()> {
+    profile_function!();
+
+    let Some((camera_pos, projection)) =
+        Query::new((world_transform(), projection_matrix().copied()))
+            .with(main_camera())
+            .borrow(world)
+            .first()
+            .map(|(transform, projection)| (transform.transform_point3(Vec3::ZERO), projection))
+    else {
+        return Ok(());
+    };
+
+    let changed = Query::new((
+        entity_ids(),
+        mesh(),
+        mesh_lods(),
+        bounding_sphere(),
+        world_transform(),
+    ))
+    .borrow(world)
+    .iter()
+    .filter_map(|(id, current, lods, bounds, transform)| {
+        let coverage = screen_coverage(&bounds.transform(transform), camera_pos, &projection);
+        let selected = lods.select(coverage)?;
+
+        (selected != current).then(|| (id, selected.clone()))
+    })
+    .collect::<Vec<_>>();
+
+    for (id, selected) in changed {
+        world.set(id, mesh(), selected)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use ivy_assets::AssetCache;
+    use ivy_graphics::mesh::MeshData;
+
+    use super::*;
+
+    #[test]
+    fn select_level() {
+        let assets = AssetCache::new();
+        let [high, medium, low] =
+            [(); 3].map(|_| MeshDesc::content(assets.insert(MeshData::new())));
+
+        let lods = MeshLods::new([
+            (low.clone(), 0.0),
+            (high.clone(), 0.5),
+            (medium.clone(), 0.1),
+        ]);
+
+        assert_eq!(lods.select(1.0), Some(&high));
+        assert_eq!(lods.select(0.5), Some(&high));
+        assert_eq!(lods.select(0.2), Some(&medium));
+        assert_eq!(lods.select(0.01), Some(&low));
+        assert_eq!(MeshLods::new([]).select(1.0), None);
+    }
+
+    #[test]
+    fn coverage_from_distance() {
+        let projection = Mat4::perspective_rh(std::f32::consts::FRAC_PI_2, 1.0, 0.1, 100.0);
+        let sphere = BoundingSphere::new(Vec3::new(0.0, 0.0, -10.0), 1.0);
+
+        // A field of view of 90 degrees spans twice the distance vertically
+        assert!((screen_coverage(&sphere, Vec3::ZERO, &projection) - 0.1).abs() < 1e-5);
+
+        let far = screen_coverage(&sphere, Vec3::new(0.0, 0.0, 10.0), &projection);
+        assert!((far - 0.05).abs() < 1e-5);
+
+        assert_eq!(
+            screen_coverage(&sphere, Vec3::new(0.0, 0.0, -10.5), &projection),
+            f32::MAX
+        );
+    }
+}
@@ -0,0 +1,443 @@
+use std::collections::BTreeMap;
+
+use anyhow::Context;
+use glam::{Vec2, Vec3, Vec4};
+use ivy_assets::{Asset, AssetCache};
+use ivy_graphics::texture::TextureData;
+use ivy_wgpu_types::{BindGroupBuilder, BindGroupLayoutBuilder, Gpu, TypedBuffer};
+use wgpu::{
+    naga::{self, AddressSpace, ImageClass, ImageDimension, Scalar, TypeInner, VectorSize},
+    BindGroup, BindGroupLayout, BufferUsages, SamplerDescriptor, ShaderStages, Texture,
+    TextureFormat,
+};
+
+use crate::{shader::ShaderPass, texture::TextureWithFormatDesc};
+
+/// Bind group of the material in mesh shaders.
+///
+/// Groups `0..=2` are reserved for the camera, lights, and objects.
+pub const MATERIAL_BIND_GROUP: u32 = 3;
+
+/// Value of a uniform in a custom material
+#[derive(Debug, Clone, Copy)]
+pub enum MaterialValue {
+    Float(f32),
+    Vec2(Vec2),
+    Vec3(Vec3),
+    Vec4(Vec4),
+    Int(i32),
+    UInt(u32),
+}
+
+impl MaterialValue {
+    fn to_bytes(self) -> Vec<u8> {
+        match self {
+            MaterialValue::Float(v) => bytemuck::bytes_of(&v).to_vec(),
+            MaterialValue::Vec2(v) => bytemuck::bytes_of(&v).to_vec(),
+            MaterialValue::Vec3(v) => bytemuck::bytes_of(&v).to_vec(),
+            MaterialValue::Vec4(v) => bytemuck::bytes_of(&v).to_vec(),
+            MaterialValue::Int(v) => bytemuck::bytes_of(&v).to_vec(),
+            MaterialValue::UInt(v) => bytemuck::bytes_of(&v).to_vec(),
+        }
+    }
+
+    /// Returns true if the value can be assigned to a member of type `ty`
+    fn matches(&self, ty: &TypeInner) -> bool {
+        let vector = |size, scalar| TypeInner::Vector { size, scalar };
+        *ty == match self {
+            MaterialValue::Float(_) => TypeInner::Scalar(Scalar::F32),
+            MaterialValue::Vec2(_) => vector(VectorSize::Bi, Scalar::F32),
+            MaterialValue::Vec3(_) => vector(VectorSize::Tri, Scalar::F32),
+            MaterialValue::Vec4(_) => vector(VectorSize::Quad, Scalar::F32),
+            MaterialValue::Int(_) => TypeInner::Scalar(Scalar::I32),
+            MaterialValue::UInt(_) => TypeInner::Scalar(Scalar::U32),
+        }
+    }
+
+    /// Alignment of the value in a std140 uniform buffer
+    fn alignment(&self) -> u32 {
+        match self {
+            MaterialValue::Float(_) | MaterialValue::Int(_) | MaterialValue::UInt(_) => 4,
+            MaterialValue::Vec2(_) => 8,
+            MaterialValue::Vec3(_) | MaterialValue::Vec4(_) => 16,
+        }
+    }
+}
+
+// Materials are used as keys, so values are compared by their bit patterns. This keeps `Eq`
+// and `Hash` consistent for NaN and signed zeros.
+impl PartialEq for MaterialValue {
+    fn eq(&self, other: &Self) -> bool {
+        std::mem::discriminant(self) == std::mem::discriminant(other)
+            && self.to_bytes() == other.to_bytes()
+    }
+}
+
+impl Eq for MaterialValue {}
+
+impl std::hash::Hash for MaterialValue {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        std::mem::discriminant(self).hash(state);
+        self.to_bytes().hash(state);
+    }
+}
+
+impl From<f32> for MaterialValue {
+    fn from(v: f32) -> Self {
+        Self::Float(v)
+    }
+}
+
+impl From<Vec2> for MaterialValue {
+    fn from(v: Vec2) -> Self {
+        Self::Vec2(v)
+    }
+}
+
+impl From<Vec3> for MaterialValue {
+    fn from(v: Vec3) -> Self {
+        Self::Vec3(v)
+    }
+}
+
+impl From<Vec4> for MaterialValue {
+    fn from(v: Vec4) -> Self {
+        Self::Vec4(v)
+    }
+}
+
+impl From<i32> for MaterialValue {
+    fn from(v: i32) -> Self {
+        Self::Int(v)
+    }
+}
+
+impl From<u32> for MaterialValue {
+    fn from(v: u32) -> Self {
+        Self::UInt(v)
+    }
+}
+
+/// Material drawn with a user authored shader.
+///
+/// The shader is processed through the [`ShaderLibrary`](crate::shader_library::ShaderLibrary)
+/// and may import its modules, e.g; `vertex` for the standard vertex transform. The bindings of
+/// [`MATERIAL_BIND_GROUP`] are read from the shader, and filled by name from the uniforms and
+/// textures of the material:
+///
+/// - `texture_2d<f32>`: the texture with the same name, or white
+/// - `sampler`: a linear repeating sampler
+/// - `var<uniform>` structs: each field is set to the uniform with the same name, or zero
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct CustomMaterialData {
+    label: String,
+    shader: ShaderPass,
+    uniforms: BTreeMap<String, MaterialValue>,
+    textures: BTreeMap<String, (TextureData, TextureFormat)>,
+}
+
+impl CustomMaterialData {
+    pub fn new(label: impl Into<String>, shader: ShaderPass) -> Self {
+        Self {
+            label: label.into(),
+            shader,
+            uniforms: Default::default(),
+            textures: Default::default(),
+        }
+    }
+
+    /// Set a uniform field
+    pub fn with_uniform(
+        mut self,
+        name: impl Into<String>,
+        value: impl Into<MaterialValue>,
+    ) -> Self {
+        self.uniforms.insert(name.into(), value.into());
+        self
+    }
+
+    /// Set a texture, uploaded with the given format
+    pub fn with_texture(
+        mut self,
+        name: impl Into<String>,
+        texture: impl Into<TextureData>,
+        format: TextureFormat,
+    ) -> Self {
+        self.textures.insert(name.into(), (texture.into(), format));
+        self
+    }
+
+    pub fn label(&self) -> &str {
+        &self.label
+    }
+
+    pub fn shader(&self) -> &ShaderPass {
+        &self.shader
+    }
+
+    /// Creates the material bind group from the bindings declared in the shader
+    pub(crate) fn create_bindings(
+        &self,
+        gpu: &Gpu,
+        assets: &AssetCache,
+        module: &naga::Module,
+    ) -> anyhow::Result<(BindGroupLayout, BindGroup)> {
+        let mut globals = module
+            .global_variables
+            .iter()
+            .filter_map(|(_, v)| Some((v.binding.as_ref()?, v)))
+            .filter(|(binding, _)| binding.group == MATERIAL_BIND_GROUP)
+            .collect::<Vec<_>>();
+
+        globals.sort_by_key(|(binding, _)| binding.binding);
+
+        let visibility = ShaderStages::VERTEX_FRAGMENT;
+        let mut layout = BindGroupLayoutBuilder::new(self.label.clone());
+        let mut resources = Vec::new();
+
+        for (i, (binding, global)) in globals.into_iter().enumerate() {
+            let name = global.name.as_deref().unwrap_or_default();
+
+            anyhow::ensure!(
+                binding.binding == i as u32,
+                "Material bindings must be contiguous, found {name:?} at binding {}",
+                binding.binding
+            );
+
+            let ty = &module.types[global.ty].inner;
+            match (global.space, ty) {
+                (
+                    AddressSpace::Handle,
+                    TypeInner::Image {
+                        dim: ImageDimension::D2,
+                        arrayed: false,
+                        class: ImageClass::Sampled { multi: false, .. },
+                    },
+                ) => {
+                    let (texture, format) = self
+                        .textures
+                        .get(name)
+                        .cloned()
+                        .unwrap_or((TextureData::white(), TextureFormat::Rgba8Unorm));
+
+                    let texture = assets
+                        .try_load(&TextureWithFormatDesc::new(texture, format))
+                        .with_context(|| format!("Failed to load material texture {name:?}"))?;
+
+                    layout.bind_texture(visibility);
+                    resources.push(MaterialResource::Texture(texture));
+                }
+                (AddressSpace::Handle, TypeInner::Sampler { comparison: false }) => {
+                    layout.bind_sampler(visibility);
+                    resources.push(MaterialResource::Sampler);
+                }
+                (AddressSpace::Uniform, TypeInner::Struct { .. }) => {
+                    let data = pack_uniforms(module, ty, &self.uniforms)
+                        .with_context(|| format!("Failed to pack the uniforms of {name:?}"))?;
+
+                    layout.bind_uniform_buffer(visibility);
+                    resources.push(MaterialResource::Uniform(TypedBuffer::new(
+                        gpu,
+                        "material_uniforms",
+                        BufferUsages::UNIFORM,
+                        &data,
+                    )));
+                }
+                _ => anyhow::bail!("Unsupported material binding {name:?}: {ty:?}"),
+            }
+        }
+
+        let layout = layout.build(gpu);
+
+        let sampler = gpu.device.create_sampler(&SamplerDescriptor {
+            label: "material_sampler".into(),
+            address_mode_u: wgpu::AddressMode::Repeat,
+            address_mode_v: wgpu::AddressMode::Repeat,
+            min_filter: wgpu::FilterMode::Linear,
+            mag_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Linear,
+            anisotropy_clamp: 16,
+            ..Default::default()
+        });
+
+        let views = resources
+            .iter()
+            .map(|v| match v {
+                MaterialResource::Texture(texture) => {
+                    Some(texture.create_view(&Default::default()))
+                }
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+
+        let mut bind_group = BindGroupBuilder::new(&self.label);
+        for (resource, view) in resources.iter().zip(&views) {
+            match (resource, view) {
+                (MaterialResource::Texture(_), Some(view)) => bind_group.bind_texture(view),
+                (MaterialResource::Sampler, _) => bind_group.bind_sampler(&sampler),
+                (MaterialResource::Uniform(buffer), _) => bind_group.bind_buffer(buffer),
+                (MaterialResource::Texture(_), None) => unreachable!(),
+            };
+        }
+
+        let bind_group = bind_group.build(gpu, &layout);
+
+        Ok((layout, bind_group))
+    }
+}
+
+/// Lays out the uniforms in a buffer matching the uniform struct `ty` of the shader.
+///
+/// Members without a uniform of the same name are zeroed.
+fn pack_uniforms(
+    module: &naga::Module,
+    ty: &TypeInner,
+    uniforms: &BTreeMap<String, MaterialValue>,
+) -> anyhow::Result<Vec<u8>> {
+    let TypeInner::Struct { members, span } = ty else {
+        anyhow::bail!("Uniforms must be a struct, found {ty:?}");
+    };
+
+    let mut data = vec![0u8; (*span as usize).next_multiple_of(16)];
+
+    for member in members {
+        let Some((name, value)) = member
+            .name
+            .as_ref()
+            .and_then(|v| Some((v, uniforms.get(v)?)))
+        else {
+            continue;
+        };
+
+        let member_ty = &module.types[member.ty].inner;
+        anyhow::ensure!(
+            value.matches(member_ty),
+            "Uniform {name:?} of type {member_ty:?} can not be set to {value:?}"
+        );
+
+        anyhow::ensure!(
+            member.offset % value.alignment() == 0,
+            "Uniform {name:?} at offset {} is not aligned to {} bytes",
+            member.offset,
+            value.alignment()
+        );
+
+        let bytes = value.to_bytes();
+        let offset = member.offset as usize;
+
+        anyhow::ensure!(
+            offset + bytes.len() <= *span as usize,
+            "Uniform {name:?} at offset {offset} exceeds the struct of {span} bytes"
+        );
+
+        data[offset..offset + bytes.len()].copy_from_slice(&bytes);
+    }
+
+    Ok(data)
+}
+
+enum MaterialResource {
+    Texture(Asset<Texture>),
+    Sampler,
+    Uniform(TypedBuffer<u8>),
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        collections::hash_map::DefaultHasher,
+        hash::{Hash, Hasher},
+    };
+
+    use super::*;
+
+    fn hash(value: &MaterialValue) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        value.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    #[test]
+    fn value_eq_hash() {
+        let values = [
+            MaterialValue::Float(f32::NAN),
+            MaterialValue::Float(0.0),
+            MaterialValue::Float(-0.0),
+            MaterialValue::Float(1.0),
+            MaterialValue::UInt(1.0f32.to_bits()),
+            MaterialValue::Vec2(Vec2::new(1.0, -0.0)),
+        ];
+
+        for (i, a) in values.iter().enumerate() {
+            for (j, b) in values.iter().enumerate() {
+                assert_eq!(a == b, i == j, "{a:?} {b:?}");
+            }
+
+            assert_eq!(hash(a), hash(&a.clone()));
+        }
+    }
+
+    fn uniform_struct(source: &str) -> (naga::Module, TypeInner) {
+        let module = naga::front::wgsl::parse_str(source).unwrap();
+        let (_, global) = module.global_variables.iter().next().unwrap();
+        let ty = module.types[global.ty].inner.clone();
+        (module, ty)
+    }
+
+    #[test]
+    fn pack() {
+        let (module, ty) = uniform_struct(
+            "
+            struct Params {
+                color: vec4<f32>,
+                scale: f32,
+                offset: vec3<f32>,
+                count: u32,
+            }
+
+            @group(3) @binding(0)
+            var<uniform> params: Params;
+            ",
+        );
+
+        let uniforms = BTreeMap::<String, MaterialValue>::from([
+            ("color".to_string(), Vec4::new(1.0, 2.0, 3.0, 4.0).into()),
+            ("offset".to_string(), Vec3::new(5.0, 6.0, 7.0).into()),
+            ("count".to_string(), 8u32.into()),
+            ("unused".to_string(), 9.0f32.into()),
+        ]);
+
+        let data = pack_uniforms(&module, &ty, &uniforms).unwrap();
+        assert_eq!(data.len(), 48);
+
+        let floats: &[f32] = bytemuck::cast_slice(&data);
+        assert_eq!(&floats[0..4], &[1.0, 2.0, 3.0, 4.0]);
+        // `scale` is not set, and `offset` is aligned to 16 bytes
+        assert_eq!(&floats[4..8], &[0.0; 4]);
+        assert_eq!(&floats[8..11], &[5.0, 6.0, 7.0]);
+        assert_eq!(bytemuck::cast_slice::<_, u32>(&data)[11], 8);
+    }
+
+    #[test]
+    fn pack_mismatched() {
+        let (module, ty) = uniform_struct(
+            "
+            struct Params {
+                scale: vec2<f32>,
+            }
+
+            @group(3) @binding(0)
+            var<uniform> params: Params;
+            ",
+        );
+
+        for value in [
+            MaterialValue::Float(1.0),
+            MaterialValue::Vec4(Vec4::ONE),
+            MaterialValue::UInt(1),
+        ] {
+            let uniforms = BTreeMap::from([("scale".to_string(), value)]);
+            assert!(pack_uniforms(&module, &ty, &uniforms).is_err(), "{value:?}");
+        }
+    }
+}
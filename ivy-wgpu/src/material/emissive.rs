@@ -58,6 +58,9 @@ impl PbrEmissiveMaterialParams {
             bind_group: Some(bind_group),
             layout: Some(layout),
             shader: self.pbr.shader,
+            custom: None,
+            streamed: Vec::new(),
+            bindless: None,
         }
     }
 }
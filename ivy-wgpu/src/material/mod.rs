@@ -1,10 +1,17 @@
+pub mod custom;
 pub mod emissive;
 
+use std::sync::Arc;
+
+use custom::CustomMaterialData;
 use ivy_assets::{Asset, AssetCache};
-use ivy_wgpu_types::{BindGroupBuilder, BindGroupLayoutBuilder};
-use wgpu::{BindGroup, BindGroupLayout, BufferUsages, SamplerDescriptor, ShaderStages, Texture};
+use ivy_wgpu_types::{BindGroupBuilder, BindGroupLayoutBuilder, Gpu};
+use parking_lot::Mutex;
+use wgpu::{
+    naga, BindGroup, BindGroupLayout, BufferUsages, SamplerDescriptor, ShaderStages, Texture,
+};
 
-use crate::{shader::ShaderPass, types::TypedBuffer};
+use crate::{shader::ShaderPass, texture_streaming::StreamedTexture, types::TypedBuffer};
 
 /// A material for a single pass of the renderer
 ///
@@ -14,21 +21,139 @@ pub struct RenderMaterial {
     bind_group: Option<BindGroup>,
     layout: Option<BindGroupLayout>,
     shader: Asset<ShaderPass>,
+    custom: Option<CustomBindings>,
+    /// Streamed textures and the generation they were bound at
+    streamed: Vec<(StreamedTexture, u32)>,
+    bindless: Option<BindlessMaterialParams>,
+}
+
+/// Textures and factors of a pbr material, for drawing through the bindless texture array
+/// instead of the material's own bind group
+#[derive(Clone)]
+pub(crate) struct BindlessMaterialParams {
+    /// Albedo, normal, metallic roughness, ambient occlusion and displacement
+    pub textures: [Asset<Texture>; 5],
+    pub roughness_factor: f32,
+    pub metallic_factor: f32,
+}
+
+/// Bindings of a custom material, created once the shader has been processed
+struct CustomBindings {
+    data: CustomMaterialData,
+    /// Replaced when the shader is reloaded, as the bindings may have changed
+    reflected: Mutex<Option<Arc<ReflectedBindings>>>,
+}
+
+/// Bindings of a custom material, as declared by its shader
+pub(crate) struct ReflectedBindings {
+    pub layout: BindGroupLayout,
+    pub bind_group: BindGroup,
 }
 
 impl RenderMaterial {
+    pub(crate) fn custom(data: CustomMaterialData, shader: Asset<ShaderPass>) -> Self {
+        Self {
+            label: data.label().into(),
+            bind_group: None,
+            layout: None,
+            shader,
+            custom: Some(CustomBindings {
+                data,
+                reflected: Mutex::new(None),
+            }),
+            streamed: Vec::new(),
+            bindless: None,
+        }
+    }
+
+    /// Material without bindings, e.g; for debug visualizations
+    pub(crate) fn unbound(label: impl Into<String>, shader: Asset<ShaderPass>) -> Self {
+        Self {
+            label: label.into(),
+            bind_group: None,
+            layout: None,
+            shader,
+            custom: None,
+            streamed: Vec::new(),
+            bindless: None,
+        }
+    }
+
+    /// Recreate the material when the resident level of any of the streamed textures changes
+    pub(crate) fn with_streamed(mut self, streamed: Vec<(StreamedTexture, u32)>) -> Self {
+        self.streamed = streamed;
+        self
+    }
+
+    /// Returns true if a streamed texture has changed since the material was created
+    pub(crate) fn is_stale(&self) -> bool {
+        self.streamed
+            .iter()
+            .any(|(texture, generation)| texture.generation() != *generation)
+    }
+
+    pub(crate) fn bindless(&self) -> Option<&BindlessMaterialParams> {
+        self.bindless.as_ref()
+    }
+
+    pub(crate) fn streamed_textures(&self) -> impl Iterator<Item = &StreamedTexture> {
+        self.streamed.iter().map(|v| &v.0)
+    }
+
     pub fn label(&self) -> &str {
         &self.label
     }
 
+    /// Returns the bind group of the material.
+    ///
+    /// Custom materials are bound through [`Self::reflected`] instead.
     pub fn bind_group(&self) -> Option<&BindGroup> {
         self.bind_group.as_ref()
     }
 
+    /// Returns the bind group layout of the material.
+    ///
+    /// Custom materials are bound through [`Self::reflected`] instead.
     pub fn layout(&self) -> Option<&BindGroupLayout> {
         self.layout.as_ref()
     }
 
+    pub(crate) fn is_custom(&self) -> bool {
+        self.custom.is_some()
+    }
+
+    /// Returns the current bindings of a custom material
+    pub(crate) fn reflected(&self) -> Option<Arc<ReflectedBindings>> {
+        self.custom.as_ref()?.reflected.lock().clone()
+    }
+
+    /// Returns true if the bindings must be reflected from the processed shader
+    pub(crate) fn needs_reflection(&self) -> bool {
+        self.custom
+            .as_ref()
+            .is_some_and(|v| v.reflected.lock().is_none())
+    }
+
+    /// Creates the bindings of a custom material from the processed shader module.
+    ///
+    /// Replaces the previous bindings, e.g; when the shader is reloaded. The previous bindings are
+    /// kept if the new ones can not be created.
+    pub(crate) fn reflect(
+        &self,
+        gpu: &Gpu,
+        assets: &AssetCache,
+        module: &naga::Module,
+    ) -> anyhow::Result<()> {
+        let Some(custom) = &self.custom else {
+            return Ok(());
+        };
+
+        let (layout, bind_group) = custom.data.create_bindings(gpu, assets, module)?;
+        *custom.reflected.lock() = Some(Arc::new(ReflectedBindings { layout, bind_group }));
+
+        Ok(())
+    }
+
     pub fn shader(&self) -> &Asset<ShaderPass> {
         &self.shader
     }
@@ -94,6 +219,19 @@ impl PbrMaterialParams {
             bind_group: Some(bind_group),
             layout: Some(layout),
             shader: self.shader,
+            custom: None,
+            streamed: Vec::new(),
+            bindless: Some(BindlessMaterialParams {
+                textures: [
+                    self.albedo,
+                    self.normal,
+                    self.metallic_roughness,
+                    self.ambient_occlusion,
+                    self.displacement,
+                ],
+                roughness_factor: self.roughness_factor,
+                metallic_factor: self.metallic_factor,
+            }),
         }
     }
 }
@@ -111,6 +249,9 @@ impl ShadowMaterialDesc {
             bind_group: None,
             layout: None,
             shader,
+            custom: None,
+            streamed: Vec::new(),
+            bindless: None,
         }
     }
 }
@@ -2,15 +2,17 @@ use ivy_assets::{loadable::Load, Asset, AssetCache, AssetDesc};
 use ivy_gltf::GltfMaterial;
 use ivy_graphics::texture::{TextureData, TextureDesc};
 use ordered_float::NotNan;
-use wgpu::TextureFormat;
+use wgpu::{Texture, TextureFormat};
 
 use crate::{
+    camera_target::CameraTarget,
     material::{
-        emissive::PbrEmissiveMaterialParams, PbrMaterialParams, RenderMaterial, ShadowMaterialDesc,
+        custom::CustomMaterialData, emissive::PbrEmissiveMaterialParams, PbrMaterialParams,
+        RenderMaterial, ShadowMaterialDesc,
     },
     shader::ShaderPass,
     shaders::{PbrEmissiveShaderDesc, PbrShaderDesc, ShadowShaderDesc},
-    texture::TextureWithFormatDesc,
+    texture_streaming::{load_material_texture, StreamedTexture},
 };
 
 /// Asynchronously loadable material, e.g; from json and texture file paths
@@ -178,6 +180,8 @@ pub enum MaterialData {
     UnlitMaterial(PbrMaterialData),
     EmissiveMaterial(PbrEmissiveMaterialData),
     ShadowMaterial,
+    /// Material using a user authored shader
+    CustomMaterial(CustomMaterialData),
 }
 
 impl From<PbrMaterialData> for MaterialData {
@@ -186,10 +190,18 @@ impl From<PbrMaterialData> for MaterialData {
     }
 }
 
+impl From<CustomMaterialData> for MaterialData {
+    fn from(v: CustomMaterialData) -> Self {
+        Self::CustomMaterial(v)
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct PbrMaterialData {
     label: String,
     albedo: TextureData,
+    /// Samples the albedo from what a camera renders instead
+    albedo_target: Option<CameraTarget>,
     normal: TextureData,
     metallic_roughness: TextureData,
     ambient_occlusion: TextureData,
@@ -202,6 +214,7 @@ impl PbrMaterialData {
     pub fn new() -> Self {
         Self {
             albedo: TextureData::white(),
+            albedo_target: None,
             normal: TextureData::default_normal(),
             metallic_roughness: TextureData::white(),
             ambient_occlusion: TextureData::white(),
@@ -247,30 +260,40 @@ impl PbrMaterialData {
         assets: &AssetCache,
         shader: Asset<ShaderPass>,
     ) -> anyhow::Result<Asset<RenderMaterial>> {
-        let albedo = assets.try_load(&TextureWithFormatDesc::new(
-            self.albedo.clone(),
-            TextureFormat::Rgba8UnormSrgb,
-        ))?;
+        let mut streamed = Vec::new();
+        let albedo = self.load_albedo(assets, &mut streamed)?;
 
-        let normal = assets.try_load(&TextureWithFormatDesc::new(
-            self.normal.clone(),
+        let normal = load_material_texture(
+            assets,
+            &self.normal,
             TextureFormat::Rgba8Unorm,
-        ))?;
+            TextureData::default_normal(),
+            &mut streamed,
+        )?;
 
-        let metallic_roughness = assets.try_load(&TextureWithFormatDesc::new(
-            self.metallic_roughness.clone(),
+        let metallic_roughness = load_material_texture(
+            assets,
+            &self.metallic_roughness,
             TextureFormat::Rgba8Unorm,
-        ))?;
+            TextureData::white(),
+            &mut streamed,
+        )?;
 
-        let ambient_occlusion = assets.try_load(&TextureWithFormatDesc::new(
-            self.ambient_occlusion.clone(),
+        let ambient_occlusion = load_material_texture(
+            assets,
+            &self.ambient_occlusion,
             TextureFormat::Rgba8Unorm,
-        ))?;
+            TextureData::white(),
+            &mut streamed,
+        )?;
 
-        let displacement = assets.try_load(&TextureWithFormatDesc::new(
-            self.displacement.clone(),
+        let displacement = load_material_texture(
+            assets,
+            &self.displacement,
             TextureFormat::Rgba8Unorm,
-        ))?;
+            TextureData::white(),
+            &mut streamed,
+        )?;
 
         Ok(assets.insert(
             PbrMaterialParams {
@@ -283,22 +306,50 @@ impl PbrMaterialData {
                 metallic_factor: *self.metallic_factor,
                 shader,
             }
-            .create_material(self.label.clone(), assets),
+            .create_material(self.label.clone(), assets)
+            .with_streamed(streamed),
         ))
     }
 
+    fn load_albedo(
+        &self,
+        assets: &AssetCache,
+        streamed: &mut Vec<(StreamedTexture, u32)>,
+    ) -> anyhow::Result<Asset<Texture>> {
+        match &self.albedo_target {
+            Some(target) => Ok(target.texture().clone()),
+            None => load_material_texture(
+                assets,
+                &self.albedo,
+                TextureFormat::Rgba8UnormSrgb,
+                TextureData::white(),
+                streamed,
+            ),
+        }
+    }
+
     /// Set the label
     pub fn with_label(mut self, label: impl Into<String>) -> Self {
         self.label = label.into();
         self
     }
 
+    pub fn label(&self) -> &str {
+        &self.label
+    }
+
     /// Set the albedo
     pub fn with_albedo(mut self, albedo: impl Into<TextureData>) -> Self {
         self.albedo = albedo.into();
         self
     }
 
+    /// Display the view of a camera rendering into `target` as the albedo
+    pub fn with_albedo_target(mut self, target: CameraTarget) -> Self {
+        self.albedo_target = Some(target);
+        self
+    }
+
     /// Set the normal
     pub fn with_normal(mut self, normal: impl Into<TextureData>) -> Self {
         self.normal = normal.into();
@@ -357,35 +408,48 @@ impl PbrEmissiveMaterialData {
         assets: &AssetCache,
         shader: Asset<ShaderPass>,
     ) -> anyhow::Result<Asset<RenderMaterial>> {
-        let albedo = assets.try_load(&TextureWithFormatDesc::new(
-            self.pbr.albedo.clone(),
-            TextureFormat::Rgba8UnormSrgb,
-        ))?;
+        let mut streamed = Vec::new();
+        let albedo = self.pbr.load_albedo(assets, &mut streamed)?;
 
-        let normal = assets.try_load(&TextureWithFormatDesc::new(
-            self.pbr.normal.clone(),
+        let normal = load_material_texture(
+            assets,
+            &self.pbr.normal,
             TextureFormat::Rgba8Unorm,
-        ))?;
+            TextureData::default_normal(),
+            &mut streamed,
+        )?;
 
-        let metallic_roughness = assets.try_load(&TextureWithFormatDesc::new(
-            self.pbr.metallic_roughness.clone(),
+        let metallic_roughness = load_material_texture(
+            assets,
+            &self.pbr.metallic_roughness,
             TextureFormat::Rgba8Unorm,
-        ))?;
+            TextureData::white(),
+            &mut streamed,
+        )?;
 
-        let ambient_occlusion = assets.try_load(&TextureWithFormatDesc::new(
-            self.pbr.ambient_occlusion.clone(),
+        let ambient_occlusion = load_material_texture(
+            assets,
+            &self.pbr.ambient_occlusion,
             TextureFormat::Rgba8Unorm,
-        ))?;
+            TextureData::white(),
+            &mut streamed,
+        )?;
 
-        let displacement = assets.try_load(&TextureWithFormatDesc::new(
-            self.pbr.displacement.clone(),
+        let displacement = load_material_texture(
+            assets,
+            &self.pbr.displacement,
             TextureFormat::Rgba8Unorm,
-        ))?;
+            TextureData::white(),
+            &mut streamed,
+        )?;
 
-        let emissive_color = assets.try_load(&TextureWithFormatDesc::new(
-            self.emissive_color.clone(),
+        let emissive_color = load_material_texture(
+            assets,
+            &self.emissive_color,
             TextureFormat::Rgba8Unorm,
-        ))?;
+            TextureData::white(),
+            &mut streamed,
+        )?;
 
         Ok(assets.insert(
             PbrEmissiveMaterialParams {
@@ -402,7 +466,8 @@ impl PbrEmissiveMaterialData {
                 emissive_color,
                 emissive_factor: *self.emissive_factor,
             }
-            .create_material(self.pbr.label.clone(), assets),
+            .create_material(self.pbr.label.clone(), assets)
+            .with_streamed(streamed),
         ))
     }
 }
@@ -416,7 +481,6 @@ impl Default for PbrMaterialData {
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub(crate) struct RenderMaterialDesc {
     pub material: MaterialData,
-    pub skinned: bool,
 }
 
 impl AssetDesc<RenderMaterial> for RenderMaterialDesc {
@@ -427,34 +491,21 @@ impl AssetDesc<RenderMaterial> for RenderMaterialDesc {
         assets: &ivy_assets::AssetCache,
     ) -> Result<Asset<RenderMaterial>, Self::Error> {
         match &self.material {
-            MaterialData::PbrMaterial(v) => v.create(
-                assets,
-                assets.load(&PbrShaderDesc {
-                    skinned: self.skinned,
-                    lit: true,
-                }),
-            ),
-            MaterialData::UnlitMaterial(v) => v.create(
-                assets,
-                assets.load(&PbrShaderDesc {
-                    skinned: self.skinned,
-                    lit: false,
-                }),
-            ),
-            MaterialData::EmissiveMaterial(v) => v.create(
-                assets,
-                assets.load(&PbrEmissiveShaderDesc {
-                    skinned: self.skinned,
-                    lit: true,
-                }),
-            ),
-            MaterialData::ShadowMaterial => {
-                Ok(assets.insert(ShadowMaterialDesc {}.create_material(
-                    "shadow".into(),
-                    assets.load(&ShadowShaderDesc {
-                        skinned: self.skinned,
-                    }),
-                )))
+            MaterialData::PbrMaterial(v) => {
+                v.create(assets, assets.load(&PbrShaderDesc { lit: true }))
+            }
+            MaterialData::UnlitMaterial(v) => {
+                v.create(assets, assets.load(&PbrShaderDesc { lit: false }))
+            }
+            MaterialData::EmissiveMaterial(v) => {
+                v.create(assets, assets.load(&PbrEmissiveShaderDesc { lit: true }))
+            }
+            MaterialData::ShadowMaterial => Ok(assets.insert(
+                ShadowMaterialDesc {}
+                    .create_material("shadow".into(), assets.load(&ShadowShaderDesc)),
+            )),
+            MaterialData::CustomMaterial(v) => {
+                Ok(assets.insert(RenderMaterial::custom(v.clone(), assets.load(v.shader()))))
             }
         }
     }
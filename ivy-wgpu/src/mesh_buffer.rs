@@ -1,7 +1,8 @@
-use std::sync::{Arc, Mutex};
+use std::sync::{Arc, Mutex, Weak};
 
 use bytemuck::Pod;
 use itertools::Itertools;
+use ivy_core::profiling::profile_function;
 use wgpu::{BufferUsages, RenderPass};
 
 use super::{
@@ -12,38 +13,78 @@ use crate::mesh::Vertex;
 
 pub struct MeshBufferInner {}
 
-type DroppedList<V> = Vec<(SubBuffer<V>, SubBuffer<u32>)>;
+type DroppedList<V> = Vec<MeshLocation<V>>;
 
+/// Location of a mesh within the buffers, which changes when compacted
+struct MeshLocation<V> {
+    vb: SubBuffer<V>,
+    ib: SubBuffer<u32>,
+    /// Compaction generation the location belongs to
+    gen: u32,
+}
+
+impl<V> Clone for MeshLocation<V> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<V> Copy for MeshLocation<V> {}
+
+/// Stores meshes in shared vertex and index buffers.
+///
+/// The buffers are grown and compacted as meshes are inserted and dropped, which moves the
+/// allocations. Renderers must re-read the location of their meshes whenever [`Self::gen`]
+/// changes.
 pub struct MeshBuffer<V = Vertex> {
     next_id: u64,
     pub vertex_buffers: MultiBuffer<V>,
     pub index_buffers: MultiBuffer<u32>,
+    live: Vec<Weak<MeshHandleInner<V>>>,
     dropped: Arc<Mutex<DroppedList<V>>>,
+    gen: u32,
 }
 
-/// Handle to an allocation within a mesh
-#[derive(Clone)]
-pub struct MeshHandle<V = Vertex> {
+struct MeshHandleInner<V> {
     id: u64,
-    vb: SubBuffer<V>,
-    ib: SubBuffer<u32>,
+    location: Mutex<MeshLocation<V>>,
     index_count: usize,
     on_drop: Arc<Mutex<DroppedList<V>>>,
 }
 
+impl<V> Drop for MeshHandleInner<V> {
+    fn drop(&mut self) {
+        let location = *self.location.lock().unwrap();
+        self.on_drop.lock().unwrap().push(location);
+    }
+}
+
+/// Handle to an allocation within a mesh
+pub struct MeshHandle<V = Vertex> {
+    inner: Arc<MeshHandleInner<V>>,
+}
+
+impl<V> Clone for MeshHandle<V> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
 impl<V> std::fmt::Debug for MeshHandle<V> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("MeshHandle")
-            .field("id", &self.id)
-            .field("vb", &self.vb)
-            .field("ib", &self.ib)
+            .field("id", &self.inner.id)
+            .field("vb", &self.vb())
+            .field("ib", &self.ib())
             .finish()
     }
 }
 
 impl<V> std::hash::Hash for MeshHandle<V> {
     fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
-        self.id.hash(state);
+        self.inner.id.hash(state);
     }
 }
 
@@ -51,27 +92,22 @@ impl<V> Eq for MeshHandle<V> {}
 
 impl<V> PartialEq for MeshHandle<V> {
     fn eq(&self, other: &Self) -> bool {
-        self.id == other.id
+        self.inner.id == other.inner.id
     }
 }
 
 impl<V> MeshHandle<V> {
     pub fn vb(&self) -> SubBuffer<V> {
-        self.vb
+        self.inner.location.lock().unwrap().vb
     }
 
+    /// Indices are relative to the start of [`Self::vb`], which is used as the base vertex
     pub fn ib(&self) -> SubBuffer<u32> {
-        self.ib
+        self.inner.location.lock().unwrap().ib
     }
 
     pub fn index_count(&self) -> usize {
-        self.index_count
-    }
-}
-
-impl<V> Drop for MeshHandle<V> {
-    fn drop(&mut self) {
-        self.on_drop.lock().unwrap().push((self.vb, self.ib));
+        self.inner.index_count
     }
 }
 
@@ -79,32 +115,54 @@ impl<V: Pod> MeshBuffer<V> {
     pub fn new(gpu: &Gpu, label: impl Into<String>, capacity: usize) -> Self {
         let label = label.into();
 
-        let vertex_buffer = MultiBuffer::new(
+        let mut vertex_buffer = MultiBuffer::new(
             gpu,
             format!("{}::vertex_buffer", label),
             BufferUsages::VERTEX | BufferUsages::COPY_DST | BufferUsages::COPY_SRC,
             capacity,
         );
-        let index_buffer = MultiBuffer::new(
+        let mut index_buffer = MultiBuffer::new(
             gpu,
             format!("{}::index_buffer", label),
             BufferUsages::INDEX | BufferUsages::COPY_DST | BufferUsages::COPY_SRC,
             capacity,
         );
 
+        vertex_buffer.set_memory_category("meshes");
+        index_buffer.set_memory_category("meshes");
+
         Self {
             vertex_buffers: vertex_buffer,
             index_buffers: index_buffer,
+            live: Vec::new(),
             dropped: Arc::default(),
             next_id: 0,
+            gen: 0,
         }
     }
 
+    /// Incremented whenever meshes are moved within the buffers
+    pub fn gen(&self) -> u32 {
+        self.gen
+    }
+
     fn reclaim(&mut self) {
-        for (vb, ib) in self.dropped.lock().unwrap().drain(..) {
-            tracing::debug!(?vb, ?ib, "reclaim");
-            self.vertex_buffers.deallocate(vb);
-            self.index_buffers.deallocate(ib);
+        let mut dropped = self.dropped.lock().unwrap();
+        if dropped.is_empty() {
+            return;
+        }
+
+        self.live.retain(|v| v.strong_count() > 0);
+
+        for location in dropped.drain(..) {
+            // Released by the compaction
+            if location.gen != self.gen {
+                continue;
+            }
+
+            tracing::debug!(?location.vb, ?location.ib, "reclaim");
+            self.vertex_buffers.deallocate(location.vb);
+            self.index_buffers.deallocate(location.ib);
         }
     }
 
@@ -126,32 +184,90 @@ impl<V: Pod> MeshBuffer<V> {
     ) -> MeshHandle<V> {
         self.reclaim();
         tracing::debug!("Allocating {vertex_count} {index_count}");
-        let vb = match self.vertex_buffers.allocate(vertex_count) {
-            Some(v) => v,
-            None => {
-                self.vertex_buffers.grow(gpu, vertex_count);
-                self.vertex_buffers.allocate(vertex_count).unwrap()
-            }
-        };
 
-        let ib = match self.index_buffers.allocate(index_count) {
-            Some(v) => v,
-            None => {
-                self.index_buffers.grow(gpu, index_count);
-                self.index_buffers.allocate(index_count).unwrap()
-            }
-        };
+        if self.vertex_buffers.largest_free() < vertex_count
+            || self.index_buffers.largest_free() < index_count
+        {
+            self.compact_with(gpu, vertex_count, index_count);
+        }
+
+        let vb = self.vertex_buffers.allocate(vertex_count).unwrap();
+        let ib = self.index_buffers.allocate(index_count).unwrap();
 
         let next_id = self.next_id;
         self.next_id += 1;
 
-        MeshHandle {
+        let inner = Arc::new(MeshHandleInner {
             id: next_id,
-            vb,
-            ib,
-            on_drop: self.dropped.clone(),
+            location: Mutex::new(MeshLocation {
+                vb,
+                ib,
+                gen: self.gen,
+            }),
             index_count,
+            on_drop: self.dropped.clone(),
+        });
+
+        self.live.push(Arc::downgrade(&inner));
+
+        MeshHandle { inner }
+    }
+
+    /// Moves all meshes into new tightly packed buffers, releasing fragmented free space.
+    ///
+    /// Done automatically when an allocation does not fit.
+    pub fn compact(&mut self, gpu: &Gpu) {
+        self.reclaim();
+        self.compact_with(gpu, 0, 0);
+    }
+
+    /// Compacts the buffers, growing them to fit the additional vertices and indices
+    fn compact_with(&mut self, gpu: &Gpu, additional_vertices: usize, additional_indices: usize) {
+        profile_function!();
+
+        let live = self.live.iter().filter_map(|v| v.upgrade()).collect_vec();
+
+        // Meshes dropped since the last reclaim are released by the compaction
+        self.gen += 1;
+
+        let mut locations = live
+            .iter()
+            .map(|v| v.location.lock().unwrap())
+            .collect_vec();
+
+        // Keep the relative order of the meshes
+        locations.sort_by_key(|v| v.vb.offset());
+        let vbs = self.vertex_buffers.compact(
+            gpu,
+            &locations.iter().map(|v| v.vb).collect_vec(),
+            additional_vertices,
+        );
+
+        for (location, vb) in locations.iter_mut().zip(vbs) {
+            location.vb = vb;
+        }
+
+        locations.sort_by_key(|v| v.ib.offset());
+        let ibs = self.index_buffers.compact(
+            gpu,
+            &locations.iter().map(|v| v.ib).collect_vec(),
+            additional_indices,
+        );
+
+        for (location, ib) in locations.iter_mut().zip(ibs) {
+            location.ib = ib;
+            location.gen = self.gen;
         }
+
+        tracing::debug!(
+            meshes = live.len(),
+            vertices = self.vertex_buffers.len(),
+            indices = self.index_buffers.len(),
+            "Compacted mesh buffer"
+        );
+
+        drop(locations);
+        self.live = live.iter().map(Arc::downgrade).collect();
     }
 
     pub fn insert(&mut self, gpu: &Gpu, vertices: &[V], indices: &[u32]) -> MeshHandle<V> {
@@ -166,14 +282,8 @@ impl<V: Pod> MeshBuffer<V> {
     }
 
     pub fn write(&mut self, gpu: &Gpu, handle: &MeshHandle<V>, vertices: &[V], indices: &[u32]) {
-        self.vertex_buffers.write(&gpu.queue, &handle.vb, vertices);
-        self.index_buffers.write(
-            &gpu.queue,
-            &handle.ib,
-            &indices
-                .iter()
-                .map(|v| v + handle.vb.offset() as u32)
-                .collect_vec(),
-        );
+        self.vertex_buffers
+            .write(&gpu.queue, &handle.vb(), vertices);
+        self.index_buffers.write(&gpu.queue, &handle.ib(), indices);
     }
 }
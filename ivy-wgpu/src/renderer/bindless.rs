@@ -0,0 +1,337 @@
+use std::{collections::HashMap, hash::Hash, num::NonZeroU32};
+
+use bytemuck::{Pod, Zeroable};
+use ivy_assets::{Asset, AssetCache};
+use ivy_core::profiling::profile_function;
+use ivy_graphics::texture::TextureData;
+use ivy_wgpu_types::{BindGroupBuilder, BindGroupLayoutBuilder, Gpu, TypedBuffer};
+use wgpu::{
+    BindGroup, BindGroupLayout, BufferUsages, Features, Sampler, SamplerDescriptor, ShaderStages,
+    Texture, TextureFormat,
+};
+
+use super::culling::CullDrawObject;
+use crate::{material::RenderMaterial, texture::TextureWithFormatDesc};
+
+/// Upper bound of textures in the bindless texture array
+const MAX_TEXTURES: u32 = 1024;
+/// Sampled textures left for the other bind groups of the pipeline
+const RESERVED_TEXTURES: u32 = 32;
+
+/// Returns the number of textures available for the bindless path, or `None` if unsupported by
+/// the device
+pub(crate) fn bindless_capacity(gpu: &Gpu) -> Option<u32> {
+    let required = Features::TEXTURE_BINDING_ARRAY
+        | Features::SAMPLED_TEXTURE_AND_STORAGE_BUFFER_ARRAY_NON_UNIFORM_INDEXING;
+
+    if !gpu.device.features().contains(required) {
+        return None;
+    }
+
+    capacity_for_limit(gpu.device.limits().max_sampled_textures_per_shader_stage)
+}
+
+fn capacity_for_limit(max_sampled_textures: u32) -> Option<u32> {
+    let capacity = max_sampled_textures
+        .saturating_sub(RESERVED_TEXTURES)
+        .min(MAX_TEXTURES);
+
+    (capacity >= 64).then_some(capacity)
+}
+
+#[repr(C)]
+#[derive(Pod, Zeroable, Clone, Copy, Debug, Default)]
+struct BindlessMaterialData {
+    roughness_factor: f32,
+    metallic_factor: f32,
+    textures: [u32; 5],
+    _padding: u32,
+}
+
+/// Assigns deduplicated slots to the textures and materials drawn in a frame
+struct SlotAllocator<M, T> {
+    capacity: u32,
+    /// The first slot holds the fallback texture
+    textures: Vec<T>,
+    texture_slots: HashMap<T, u32>,
+    material_slots: HashMap<M, u32>,
+    materials: Vec<BindlessMaterialData>,
+    exceeded: bool,
+}
+
+impl<M: Hash + Eq, T: Hash + Eq + Clone> SlotAllocator<M, T> {
+    fn new(capacity: u32, fallback: T) -> Self {
+        Self {
+            capacity,
+            textures: vec![fallback],
+            texture_slots: HashMap::new(),
+            material_slots: HashMap::new(),
+            materials: Vec::new(),
+            exceeded: false,
+        }
+    }
+
+    /// Textures exceeding the capacity are given the slot of the fallback
+    fn texture_slot(&mut self, texture: &T) -> u32 {
+        if let Some(&slot) = self.texture_slots.get(texture) {
+            return slot;
+        }
+
+        if self.textures.len() as u32 >= self.capacity {
+            self.exceeded = true;
+            return 0;
+        }
+
+        let slot = self.textures.len() as u32;
+        self.textures.push(texture.clone());
+        self.texture_slots.insert(texture.clone(), slot);
+        slot
+    }
+
+    fn material_slot(
+        &mut self,
+        material: M,
+        textures: &[T; 5],
+        roughness_factor: f32,
+        metallic_factor: f32,
+    ) -> u32 {
+        if let Some(&slot) = self.material_slots.get(&material) {
+            return slot;
+        }
+
+        let textures = textures
+            .each_ref()
+            .map(|texture| self.texture_slot(texture));
+
+        let slot = self.materials.len() as u32;
+        self.materials.push(BindlessMaterialData {
+            roughness_factor,
+            metallic_factor,
+            textures,
+            _padding: 0,
+        });
+        self.material_slots.insert(material, slot);
+        slot
+    }
+}
+
+/// Material slot of every object, indexed by the object index
+fn object_material_slots(draws: &[CullDrawObject], batch_slots: &[u32]) -> Vec<u32> {
+    let object_count = draws
+        .iter()
+        .map(|v| v.object_index as usize + 1)
+        .max()
+        .unwrap_or_default();
+
+    let mut object_materials = vec![0; object_count.max(1)];
+    for draw in draws {
+        object_materials[draw.object_index as usize] = batch_slots[draw.batch_id as usize];
+    }
+
+    object_materials
+}
+
+/// Binds the textures and factors of all pbr materials of a renderer at once, indexed per object
+/// in the shader.
+///
+/// This allows drawing objects with different materials without changing bind groups.
+pub(crate) struct BindlessMaterials {
+    capacity: u32,
+    layout: BindGroupLayout,
+    sampler: Sampler,
+    /// Bound to unused slots of the array, and to textures exceeding the capacity
+    fallback: Asset<Texture>,
+    materials: TypedBuffer<BindlessMaterialData>,
+    object_materials: TypedBuffer<u32>,
+    bind_group: Option<BindGroup>,
+}
+
+impl BindlessMaterials {
+    pub fn new(gpu: &Gpu, assets: &AssetCache, capacity: u32) -> Self {
+        let layout = BindGroupLayoutBuilder::new("BindlessMaterials")
+            .bind_sampler(ShaderStages::FRAGMENT)
+            .bind_texture_array(ShaderStages::FRAGMENT, NonZeroU32::new(capacity).unwrap())
+            .bind_storage_buffer(ShaderStages::FRAGMENT) // materials
+            .bind_storage_buffer(ShaderStages::VERTEX) // object_materials
+            .build(gpu);
+
+        let sampler = gpu.device.create_sampler(&SamplerDescriptor {
+            label: "bindless_material_sampler".into(),
+            address_mode_u: wgpu::AddressMode::Repeat,
+            address_mode_v: wgpu::AddressMode::Repeat,
+            min_filter: wgpu::FilterMode::Linear,
+            mag_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Linear,
+            anisotropy_clamp: 16,
+            ..Default::default()
+        });
+
+        let fallback = assets.load(&TextureWithFormatDesc::new(
+            TextureData::white(),
+            TextureFormat::Rgba8Unorm,
+        ));
+
+        Self {
+            capacity,
+            layout,
+            sampler,
+            fallback,
+            materials: TypedBuffer::new(
+                gpu,
+                "bindless_materials",
+                BufferUsages::STORAGE | BufferUsages::COPY_DST,
+                &[BindlessMaterialData::default()],
+            ),
+            object_materials: TypedBuffer::new(
+                gpu,
+                "bindless_object_materials",
+                BufferUsages::STORAGE | BufferUsages::COPY_DST,
+                &[0],
+            ),
+            bind_group: None,
+        }
+    }
+
+    pub fn layout(&self) -> &BindGroupLayout {
+        &self.layout
+    }
+
+    pub fn bind_group(&self) -> Option<&BindGroup> {
+        self.bind_group.as_ref()
+    }
+
+    /// Assigns a slot to the material of each batch, and writes the slot of every drawn object.
+    ///
+    /// `batch_materials` is indexed by the `batch_id` of the draws.
+    pub fn update<'a>(
+        &mut self,
+        gpu: &Gpu,
+        batch_materials: impl IntoIterator<Item = &'a Asset<RenderMaterial>>,
+        draws: &[CullDrawObject],
+    ) {
+        profile_function!();
+
+        let mut slots = SlotAllocator::new(self.capacity, self.fallback.clone());
+
+        let batch_slots = batch_materials
+            .into_iter()
+            .map(|material| {
+                let Some(params) = material.bindless() else {
+                    return 0;
+                };
+
+                slots.material_slot(
+                    material.clone(),
+                    &params.textures,
+                    params.roughness_factor,
+                    params.metallic_factor,
+                )
+            })
+            .collect::<Vec<_>>();
+
+        // Nothing is drawn through the bindless path
+        if slots.materials.is_empty() {
+            self.bind_group = None;
+            return;
+        }
+
+        if slots.exceeded {
+            tracing::warn!(
+                capacity = self.capacity,
+                "Bindless texture capacity exceeded, excess textures are replaced with a fallback"
+            );
+        }
+
+        let object_materials = object_material_slots(draws, &batch_slots);
+
+        write_buffer(gpu, &mut self.materials, &slots.materials);
+        write_buffer(gpu, &mut self.object_materials, &object_materials);
+
+        let views = slots
+            .textures
+            .iter()
+            .map(|v| v.create_view(&Default::default()))
+            .collect::<Vec<_>>();
+
+        let fallback = self.fallback.create_view(&Default::default());
+        let views = views
+            .iter()
+            .chain(std::iter::repeat(&fallback))
+            .take(self.capacity as usize)
+            .collect::<Vec<_>>();
+
+        self.bind_group = Some(
+            BindGroupBuilder::new("BindlessMaterials")
+                .bind_sampler(&self.sampler)
+                .bind_texture_array(&views)
+                .bind_buffer(self.materials.buffer())
+                .bind_buffer(self.object_materials.buffer())
+                .build(gpu, &self.layout),
+        );
+    }
+}
+
+fn write_buffer<T: Pod>(gpu: &Gpu, buffer: &mut TypedBuffer<T>, data: &[T]) {
+    if buffer.len() < data.len() {
+        buffer.resize(gpu, data.len().next_power_of_two(), false);
+    }
+
+    buffer.write(&gpu.queue, 0, data);
+}
+
+#[cfg(test)]
+mod tests {
+    use flax::Entity;
+
+    use super::*;
+
+    #[test]
+    fn capacity() {
+        assert_eq!(capacity_for_limit(16), None);
+        assert_eq!(capacity_for_limit(RESERVED_TEXTURES + 63), None);
+        assert_eq!(capacity_for_limit(RESERVED_TEXTURES + 64), Some(64));
+        assert_eq!(capacity_for_limit(500_000), Some(MAX_TEXTURES));
+    }
+
+    #[test]
+    fn shared_slots() {
+        let mut slots = SlotAllocator::new(64, "fallback");
+
+        let a = slots.material_slot(0, &["albedo", "normal", "mr", "ao", "disp"], 0.5, 1.0);
+        let b = slots.material_slot(1, &["albedo", "normal", "mr", "ao", "other"], 0.2, 0.0);
+        assert_eq!(slots.material_slot(0, &["albedo"; 5], 0.0, 0.0), a);
+
+        assert_eq!((a, b), (0, 1));
+        assert_eq!(slots.materials[0].textures, [1, 2, 3, 4, 5]);
+        assert_eq!(slots.materials[1].textures, [1, 2, 3, 4, 6]);
+        assert_eq!(slots.materials[1].roughness_factor, 0.2);
+        assert_eq!(slots.textures.len(), 7);
+        assert!(!slots.exceeded);
+    }
+
+    #[test]
+    fn exceeded_capacity() {
+        let mut slots = SlotAllocator::new(3, 0);
+
+        slots.material_slot((), &[1, 2, 3, 4, 2], 0.0, 0.0);
+
+        assert_eq!(slots.materials[0].textures, [1, 2, 0, 0, 2]);
+        assert_eq!(slots.textures, [0, 1, 2]);
+        assert!(slots.exceeded);
+    }
+
+    #[test]
+    fn object_slots() {
+        let draw = |object_index, batch_id| CullDrawObject {
+            object_index,
+            batch_id,
+            radius: 1.0,
+            id: Entity::builder().spawn(&mut flax::World::new()),
+        };
+
+        let draws = [draw(3, 1), draw(0, 0), draw(1, 1)];
+
+        assert_eq!(object_material_slots(&draws, &[2, 5]), [2, 5, 0, 5]);
+        assert_eq!(object_material_slots(&[], &[]), [0]);
+    }
+}
@@ -3,7 +3,8 @@ use bytemuck::Zeroable;
 use glam::{Mat4, Vec3, Vec4};
 use ivy_core::{
     components::{self, engine},
-    ColorExt,
+    gizmos::GizmoPrimitive,
+    ColorExt, PhysicalExtent,
 };
 use ivy_graphics::mesh::MeshData;
 use ivy_wgpu_types::{
@@ -15,14 +16,30 @@ use wgpu::{
     SamplerDescriptor, ShaderStages, TextureUsages,
 };
 
-use super::{get_main_camera_data, CameraData};
+use super::{get_camera_data, stroke_font, CameraData};
 use crate::{
+    camera_target::{CameraSelection, Viewport},
+    components::gizmo_stats,
     mesh::{Mesh, Vertex, VertexDesc},
     rendergraph::{
         Dependency, Node, NodeExecutionContext, NodeUpdateContext, TextureHandle, UpdateResult,
     },
 };
 
+/// Radius of the strokes of text, relative to its size
+const TEXT_THICKNESS: f32 = 0.06;
+/// Default maximum number of instances drawn each frame
+pub const DEFAULT_MAX_GIZMO_INSTANCES: usize = 65536;
+
+/// Number of gizmo instances of a frame
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct GizmoStats {
+    pub drawn: usize,
+    /// Instances exceeding the budget, which were not drawn
+    pub dropped: usize,
+}
+
+/// Draws the spheres, lines and text of all gizmo sections as instances of a single quad.
 pub struct GizmosRendererNode {
     mesh: Mesh,
     shader: Option<RenderShader>,
@@ -33,6 +50,11 @@ pub struct GizmosRendererNode {
     output: TextureHandle,
     depth_buffer: TextureHandle,
     sampler: wgpu::Sampler,
+    max_instances: usize,
+    stats: GizmoStats,
+    camera: CameraSelection,
+    /// Viewport of the output and the input of the final pass of the view drawn into it
+    viewport: Option<(Viewport, TextureHandle)>,
 }
 
 impl GizmosRendererNode {
@@ -86,8 +108,36 @@ impl GizmosRendererNode {
             data: Vec::new(),
             camera_buffer,
             output,
+            max_instances: DEFAULT_MAX_GIZMO_INSTANCES,
+            stats: GizmoStats::default(),
+            camera: CameraSelection::Main,
+            viewport: None,
         }
     }
+
+    /// Draw the gizmos from another camera than the main camera
+    pub fn with_camera(mut self, camera: impl Into<CameraSelection>) -> Self {
+        self.camera = camera.into();
+        self
+    }
+
+    /// Draw into the viewport of a split-screen view of the output.
+    ///
+    /// `view_input` is the input of the final pass of the view, which draws the view into the
+    /// viewport. Reading it orders the gizmos after the view.
+    pub fn with_viewport(mut self, viewport: Viewport, view_input: TextureHandle) -> Self {
+        self.viewport = Some((viewport, view_input));
+        self
+    }
+
+    /// Set the maximum number of instances drawn each frame.
+    ///
+    /// Gizmos beyond the budget are dropped, so that excessive debug drawing can not stall the
+    /// frame.
+    pub fn with_max_instances(mut self, max_instances: usize) -> Self {
+        self.max_instances = max_instances;
+        self
+    }
 }
 
 impl Node for GizmosRendererNode {
@@ -97,47 +147,54 @@ impl Node for GizmosRendererNode {
             .get(engine(), components::gizmos())
             .context("Missing gizmos")?;
 
-        if let Some(camera_data) = get_main_camera_data(ctx.world) {
+        let camera_data = self
+            .camera
+            .find(ctx.world)
+            .and_then(|id| ctx.world.entity(id).ok())
+            .map(|camera| get_camera_data(&camera));
+        if let Some(camera_data) = camera_data {
             self.camera_buffer.write(&ctx.gpu.queue, 0, &[camera_data]);
         }
 
+        // Text is laid out in the plane of the camera
+        let camera_transform = camera_data.map(|v| v.view.inverse());
+
         self.data.clear();
 
         for section in gizmos.sections() {
             for primitive in section.primitives() {
-                match primitive {
-                    ivy_core::gizmos::GizmoPrimitive::Sphere {
-                        origin,
-                        color,
-                        radius,
-                    } => {
-                        self.data.push(Data {
-                            world: Mat4::from_translation(*origin)
-                                * Mat4::from_scale(Vec3::splat(*radius)),
-                            color: color.to_vec4(),
-                            billboard_axis: Vec3::ZERO,
-                            corner_radius: 1.0,
-                        });
-                    }
-                    ivy_core::gizmos::GizmoPrimitive::Line {
-                        origin,
-                        color,
-                        dir,
-                        radius,
-                        corner_radius,
-                    } => {
-                        self.data.push(Data {
-                            world: Mat4::from_translation(*origin + *dir * 0.5)
-                                * Mat4::from_scale(Vec3::new(*radius, dir.length() * 0.5, *radius)),
-                            color: color.to_vec4(),
-                            billboard_axis: dir.normalize(),
-                            corner_radius: *corner_radius,
-                        });
-                    }
-                }
+                push_primitive(&mut self.data, camera_transform, primitive);
             }
         }
 
+        gizmos.for_each_timed(|primitive| {
+            push_primitive(&mut self.data, camera_transform, primitive)
+        });
+
+        let dropped = self.data.len().saturating_sub(self.max_instances);
+        if dropped > 0 && self.stats.dropped == 0 {
+            tracing::warn!(
+                instances = self.data.len(),
+                max_instances = self.max_instances,
+                "Gizmo instances exceed the budget"
+            );
+        }
+
+        self.data.truncate(self.max_instances);
+        self.stats = GizmoStats {
+            drawn: self.data.len(),
+            dropped,
+        };
+
+        drop(gizmos);
+        ctx.world.set(engine(), gizmo_stats(), self.stats)?;
+
+        // Grow geometrically, as text adds an instance for each stroke
+        if self.data.len() > self.buffer.len() {
+            self.buffer
+                .resize(ctx.gpu, self.data.len().next_power_of_two(), false);
+        }
+
         self.buffer.write(&ctx.gpu.queue, 0, &self.data);
 
         Ok(UpdateResult::Success)
@@ -203,6 +260,14 @@ impl Node for GizmosRendererNode {
             wgpu::IndexFormat::Uint32,
         );
 
+        if let Some((viewport, _)) = self.viewport {
+            let (x, y, width, height) =
+                viewport.to_physical(PhysicalExtent::new(output.width(), output.height()));
+
+            render_pass.set_viewport(x as f32, y as f32, width as f32, height as f32, 0.0, 1.0);
+            render_pass.set_scissor_rect(x, y, width, height);
+        }
+
         render_pass.set_bind_group(0, &bind_group, &[]);
         render_pass.draw_indexed(0..6, 0, 0..self.data.len() as _);
 
@@ -210,10 +275,19 @@ impl Node for GizmosRendererNode {
     }
 
     fn read_dependencies(&self) -> Vec<crate::rendergraph::Dependency> {
-        vec![
+        let mut dependencies = vec![
             Dependency::texture(self.output, TextureUsages::RENDER_ATTACHMENT),
             Dependency::texture(self.depth_buffer, TextureUsages::TEXTURE_BINDING),
-        ]
+        ];
+
+        if let Some((_, view_input)) = self.viewport {
+            dependencies.push(Dependency::texture(
+                view_input,
+                TextureUsages::TEXTURE_BINDING,
+            ));
+        }
+
+        dependencies
     }
 
     fn write_dependencies(&self) -> Vec<crate::rendergraph::Dependency> {
@@ -223,6 +297,68 @@ impl Node for GizmosRendererNode {
     fn on_resource_changed(&mut self, _resource: crate::rendergraph::ResourceHandle) {}
 }
 
+fn push_primitive(
+    data: &mut Vec<Data>,
+    camera_transform: Option<Mat4>,
+    primitive: &GizmoPrimitive,
+) {
+    match primitive {
+        GizmoPrimitive::Sphere {
+            origin,
+            color,
+            radius,
+        } => {
+            data.push(Data {
+                world: Mat4::from_translation(*origin) * Mat4::from_scale(Vec3::splat(*radius)),
+                color: color.to_vec4(),
+                billboard_axis: Vec3::ZERO,
+                corner_radius: 1.0,
+            });
+        }
+        GizmoPrimitive::Line {
+            origin,
+            color,
+            dir,
+            radius,
+            corner_radius,
+        } => {
+            data.push(Data {
+                world: Mat4::from_translation(*origin + *dir * 0.5)
+                    * Mat4::from_scale(Vec3::new(*radius, dir.length() * 0.5, *radius)),
+                color: color.to_vec4(),
+                billboard_axis: dir.normalize(),
+                corner_radius: *corner_radius,
+            });
+        }
+        GizmoPrimitive::Text {
+            origin,
+            text,
+            size,
+            color,
+        } => {
+            let Some(camera_transform) = camera_transform else {
+                return;
+            };
+
+            let right = camera_transform.x_axis.truncate().normalize() * *size;
+            let up = camera_transform.y_axis.truncate().normalize() * *size;
+            let radius = *size * TEXT_THICKNESS;
+
+            for (start, end) in stroke_font::layout(text) {
+                let pos = *origin + right * start.x + up * start.y;
+                let dir = right * (end.x - start.x) + up * (end.y - start.y);
+                data.push(Data {
+                    world: Mat4::from_translation(pos + dir * 0.5)
+                        * Mat4::from_scale(Vec3::new(radius, dir.length() * 0.5 + radius, radius)),
+                    color: color.to_vec4(),
+                    billboard_axis: dir.normalize(),
+                    corner_radius: 1.0,
+                });
+            }
+        }
+    }
+}
+
 #[repr(C)]
 #[derive(bytemuck::Pod, bytemuck::Zeroable, Clone, Copy, Debug)]
 struct Data {
@@ -0,0 +1,673 @@
+use std::f32::consts::FRAC_PI_2;
+
+use glam::{Mat4, UVec3, Vec3};
+use itertools::{izip, Itertools};
+use ivy_assets::{stored::Handle, AssetCache};
+use ivy_core::{
+    notifications::{notify, Notification},
+    profiling::{profile_function, profile_scope},
+};
+use wgpu::{
+    BindGroup, BindGroupLayout, BindingType, BufferUsages, ComputePassDescriptor, ComputePipeline,
+    ComputePipelineDescriptor, Extent3d, Operations, PipelineLayout, PipelineLayoutDescriptor,
+    RenderPassColorAttachment, ShaderStages, StorageTextureAccess, Texture, TextureDescriptor,
+    TextureFormat, TextureSampleType, TextureUsages, TextureView, TextureViewDescriptor,
+    TextureViewDimension,
+};
+
+use super::{
+    get_main_camera_data, render_stats::RenderStats, CameraData, CameraRenderer, CameraShaderData,
+    LightManager, ObjectManager, RenderContext, RendererStore, SkyboxTextures, UpdateContext,
+};
+use crate::{
+    events::ShaderReloaded,
+    rendergraph::{
+        Dependency, Node, NodeExecutionContext, NodeUpdateContext, ResourceHandle, TextureHandle,
+        UpdateResult,
+    },
+    shader_library::{ShaderLibrary, ShaderModuleDesc},
+    types::{shader::TargetDesc, BindGroupBuilder, BindGroupLayoutBuilder, TypedBuffer},
+    Gpu,
+};
+
+/// Format of the spherical harmonics volume textures
+pub const LIGHT_PROBE_FORMAT: TextureFormat = TextureFormat::Rgba16Float;
+
+const CAPTURE_FORMAT: TextureFormat = TextureFormat::Rgba16Float;
+const CAPTURE_DEPTH_FORMAT: TextureFormat = TextureFormat::Depth24Plus;
+const CAPTURE_NEAR: f32 = 0.05;
+const CAPTURE_FAR: f32 = 500.0;
+
+const PROJECTION_SHADER_PATH: &str = "shaders/light_probe_projection.wgsl";
+
+/// A grid of irradiance probes spanning an axis aligned box.
+///
+/// Each probe captures the lit scene around it, giving surfaces inside the volume bounce light
+/// from their surroundings rather than only from the environment map.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LightProbeVolume {
+    pub min: Vec3,
+    pub max: Vec3,
+    /// Number of probes along each axis, including the probes on the bounds
+    pub resolution: UVec3,
+    /// Size of the cubemap faces each probe captures the scene to
+    pub capture_resolution: u32,
+    /// Keep re-baking the probes after the first pass, one probe per frame, to follow moving
+    /// lights and objects
+    pub continuous: bool,
+}
+
+impl LightProbeVolume {
+    pub fn new(min: Vec3, max: Vec3, resolution: UVec3) -> Self {
+        Self {
+            min,
+            max,
+            resolution: resolution.max(UVec3::ONE),
+            capture_resolution: 32,
+            continuous: true,
+        }
+    }
+
+    /// Set the capture resolution
+    pub fn with_capture_resolution(mut self, capture_resolution: u32) -> Self {
+        self.capture_resolution = capture_resolution;
+        self
+    }
+
+    /// Set whether probes are re-baked continuously
+    pub fn with_continuous(mut self, continuous: bool) -> Self {
+        self.continuous = continuous;
+        self
+    }
+
+    pub fn probe_count(&self) -> u32 {
+        self.resolution.x * self.resolution.y * self.resolution.z
+    }
+
+    /// Grid coordinates of the probe at `index`
+    pub fn probe_coords(&self, index: u32) -> UVec3 {
+        let res = self.resolution;
+        UVec3::new(
+            index % res.x,
+            index / res.x % res.y,
+            index / (res.x * res.y),
+        )
+    }
+
+    pub fn probe_position(&self, coords: UVec3) -> Vec3 {
+        let cells = (self.resolution.max(UVec3::splat(2)) - 1).as_vec3();
+        self.min + (self.max - self.min) * coords.as_vec3() / cells
+    }
+
+    pub(crate) fn shader_data(&self) -> LightProbeVolumeData {
+        LightProbeVolumeData {
+            min: self.min,
+            enabled: 1,
+            max: self.max,
+            _padding: 0.0,
+            resolution: self.resolution,
+            _padding2: 0,
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, bytemuck::Pod, bytemuck::Zeroable)]
+pub(crate) struct LightProbeVolumeData {
+    min: Vec3,
+    enabled: u32,
+    max: Vec3,
+    _padding: f32,
+    resolution: UVec3,
+    _padding2: u32,
+}
+
+/// Volume textures holding the first two bands of spherical harmonics of each probe, one texture
+/// per color channel
+#[derive(Debug, Clone, Copy)]
+pub struct LightProbeTextures {
+    pub sh_r: TextureHandle,
+    pub sh_g: TextureHandle,
+    pub sh_b: TextureHandle,
+    pub volume: LightProbeVolume,
+}
+
+impl LightProbeTextures {
+    pub fn new(
+        sh_r: TextureHandle,
+        sh_g: TextureHandle,
+        sh_b: TextureHandle,
+        volume: LightProbeVolume,
+    ) -> Self {
+        Self {
+            sh_r,
+            sh_g,
+            sh_b,
+            volume,
+        }
+    }
+
+    fn handles(&self) -> [TextureHandle; 3] {
+        [self.sh_r, self.sh_g, self.sh_b]
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, bytemuck::Pod, bytemuck::Zeroable)]
+struct ProjectionData {
+    inv_viewproj: [Mat4; 6],
+    probe: UVec3,
+    capture_resolution: u32,
+}
+
+/// Looks along each axis, in cubemap layer order
+const FACES: [(Vec3, Vec3); 6] = [
+    (Vec3::X, Vec3::Y),
+    (Vec3::NEG_X, Vec3::Y),
+    (Vec3::Y, Vec3::Z),
+    (Vec3::NEG_Y, Vec3::NEG_Z),
+    (Vec3::Z, Vec3::Y),
+    (Vec3::NEG_Z, Vec3::Y),
+];
+
+/// Bakes the probes of a [`LightProbeVolume`], one probe per frame.
+///
+/// Each probe renders the scene in all six directions, which is then projected to spherical
+/// harmonics and stored in the [`LightProbeTextures`] sampled by the PBR shaders.
+///
+/// The probes are rendered without the probe volume itself, so they capture a single bounce.
+/// Probes baked before the scene has finished loading miss parts of it, unless
+/// [`LightProbeVolume::continuous`] is enabled.
+pub struct LightProbeBakeNode {
+    textures: LightProbeTextures,
+    skybox: Option<SkyboxTextures>,
+
+    renderers: Vec<Box<dyn CameraRenderer>>,
+    faces: Vec<CameraShaderData>,
+    bind_groups: Option<Vec<BindGroup>>,
+    light_manager: LightManager,
+    object_manager: Handle<ObjectManager>,
+    store: RendererStore,
+
+    capture: Texture,
+    /// Color and depth attachments of each face
+    capture_views: Vec<(TextureView, TextureView)>,
+
+    projection_layout: BindGroupLayout,
+    projection_pipeline_layout: PipelineLayout,
+    /// Created through the shader library, and recreated when the shader is reloaded
+    projection_pipeline: Option<ComputePipeline>,
+    projection_bind_group: Option<BindGroup>,
+    reload_rx: Option<flume::Receiver<ShaderReloaded>>,
+    projection_data: TypedBuffer<ProjectionData>,
+
+    next_probe: u32,
+    /// Probes left to bake in the current pass
+    remaining: u32,
+    baking: bool,
+}
+
+impl LightProbeBakeNode {
+    /// Creates a new bake node, rendering each of the cubemap faces using the corresponding
+    /// renderer
+    pub fn new<R: 'static + CameraRenderer>(
+        gpu: &Gpu,
+        textures: LightProbeTextures,
+        light_manager: LightManager,
+        object_manager: Handle<ObjectManager>,
+        skybox: Option<SkyboxTextures>,
+        renderers: [R; 6],
+    ) -> Self {
+        let volume = textures.volume;
+        let size = Extent3d {
+            width: volume.capture_resolution,
+            height: volume.capture_resolution,
+            depth_or_array_layers: 6,
+        };
+
+        let capture = gpu.device.create_texture(&TextureDescriptor {
+            label: Some("light_probe_capture"),
+            size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: CAPTURE_FORMAT,
+            usage: TextureUsages::RENDER_ATTACHMENT | TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        });
+
+        let capture_depth = gpu.device.create_texture(&TextureDescriptor {
+            label: Some("light_probe_capture_depth"),
+            size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: CAPTURE_DEPTH_FORMAT,
+            usage: TextureUsages::RENDER_ATTACHMENT,
+            view_formats: &[],
+        });
+
+        let capture_views = (0..6)
+            .map(|layer| {
+                let desc = TextureViewDescriptor {
+                    dimension: Some(TextureViewDimension::D2),
+                    base_array_layer: layer,
+                    array_layer_count: Some(1),
+                    ..Default::default()
+                };
+
+                (capture.create_view(&desc), capture_depth.create_view(&desc))
+            })
+            .collect_vec();
+
+        let projection_layout = BindGroupLayoutBuilder::new("LightProbeProjection")
+            .bind_uniform_buffer(ShaderStages::COMPUTE)
+            .bind(
+                ShaderStages::COMPUTE,
+                BindingType::Texture {
+                    sample_type: TextureSampleType::Float { filterable: true },
+                    view_dimension: TextureViewDimension::D2Array,
+                    multisampled: false,
+                },
+            )
+            .bind_storage_texture_3d(
+                ShaderStages::COMPUTE,
+                StorageTextureAccess::WriteOnly,
+                LIGHT_PROBE_FORMAT,
+            )
+            .bind_storage_texture_3d(
+                ShaderStages::COMPUTE,
+                StorageTextureAccess::WriteOnly,
+                LIGHT_PROBE_FORMAT,
+            )
+            .bind_storage_texture_3d(
+                ShaderStages::COMPUTE,
+                StorageTextureAccess::WriteOnly,
+                LIGHT_PROBE_FORMAT,
+            )
+            .build(gpu);
+
+        let projection_pipeline_layout =
+            gpu.device
+                .create_pipeline_layout(&PipelineLayoutDescriptor {
+                    label: Some("LightProbeProjection"),
+                    bind_group_layouts: &[&projection_layout],
+                    push_constant_ranges: &[],
+                });
+
+        let projection_data = TypedBuffer::new(
+            gpu,
+            "LightProbeProjection",
+            BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+            &[Default::default()],
+        );
+
+        Self {
+            textures,
+            skybox,
+            renderers: renderers
+                .into_iter()
+                .map(|v| Box::new(v) as Box<dyn CameraRenderer>)
+                .collect_vec(),
+            faces: (0..6).map(|_| CameraShaderData::new(gpu, None)).collect(),
+            bind_groups: None,
+            light_manager,
+            object_manager,
+            store: Default::default(),
+            capture,
+            capture_views,
+            projection_layout,
+            projection_pipeline_layout,
+            projection_pipeline: None,
+            projection_bind_group: None,
+            reload_rx: None,
+            projection_data,
+            next_probe: 0,
+            remaining: volume.probe_count(),
+            baking: false,
+        }
+    }
+
+    /// Starts a new bake of all probes, for when the scene has changed
+    pub fn rebake(&mut self) {
+        self.remaining = self.textures.volume.probe_count();
+    }
+}
+
+impl LightProbeBakeNode {
+    /// Creates the projection pipeline, or recreates it if the shader was reloaded.
+    ///
+    /// The previous pipeline is kept if the reloaded shader fails to compile.
+    fn update_projection_pipeline(
+        &mut self,
+        gpu: &Gpu,
+        assets: &AssetCache,
+        shader_library: &ShaderLibrary,
+    ) -> anyhow::Result<()> {
+        let reloaded = self
+            .reload_rx
+            .get_or_insert_with(|| shader_library.subscribe())
+            .drain()
+            .any(|v| v.affects(PROJECTION_SHADER_PATH));
+
+        if self.projection_pipeline.is_none() {
+            self.projection_pipeline = Some(self.create_projection_pipeline(gpu, shader_library)?);
+        } else if reloaded {
+            match self.create_projection_pipeline(gpu, shader_library) {
+                Ok(v) => self.projection_pipeline = Some(v),
+                Err(err) => {
+                    let err = err.context("Failed to reload the light probe projection shader");
+                    tracing::error!("{err:?}");
+                    notify(assets, Notification::error(format!("{err:#}")));
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn create_projection_pipeline(
+        &self,
+        gpu: &Gpu,
+        shader_library: &ShaderLibrary,
+    ) -> anyhow::Result<ComputePipeline> {
+        let module = shader_library.process(
+            gpu,
+            ShaderModuleDesc {
+                path: PROJECTION_SHADER_PATH,
+                source: include_str!("../../../assets/shaders/light_probe_projection.wgsl"),
+                shader_defs: Default::default(),
+            },
+        )?;
+
+        Ok(gpu
+            .device
+            .create_compute_pipeline(&ComputePipelineDescriptor {
+                label: Some("LightProbeProjection"),
+                layout: Some(&self.projection_pipeline_layout),
+                module: &module,
+                entry_point: "main",
+                compilation_options: Default::default(),
+                cache: None,
+            }))
+    }
+}
+
+impl Node for LightProbeBakeNode {
+    fn update(&mut self, ctx: NodeUpdateContext) -> anyhow::Result<UpdateResult> {
+        profile_function!();
+
+        self.update_projection_pipeline(ctx.gpu, ctx.assets, ctx.resources.shader_library())?;
+
+        let volume = self.textures.volume;
+        if self.remaining == 0 && volume.continuous {
+            self.rebake();
+        }
+
+        self.baking = self.remaining > 0;
+        if !self.baking {
+            return Ok(UpdateResult::Success);
+        }
+
+        let coords = volume.probe_coords(self.next_probe);
+        let position = volume.probe_position(coords);
+
+        self.next_probe = (self.next_probe + 1) % volume.probe_count();
+        self.remaining -= 1;
+
+        // Fog and wetness follow the main camera
+        let camera = get_main_camera_data(ctx.world).unwrap_or_default();
+        let proj = Mat4::perspective_rh(FRAC_PI_2, 1.0, CAPTURE_NEAR, CAPTURE_FAR);
+
+        let mut inv_viewproj = [Mat4::IDENTITY; 6];
+        for (face, (dir, up), inv_viewproj) in izip!(&mut self.faces, FACES, &mut inv_viewproj) {
+            let view = Mat4::look_at_rh(position, position + dir, up);
+
+            face.data = CameraData {
+                viewproj: proj * view,
+                view,
+                proj,
+                camera_pos: position,
+                ..camera
+            };
+
+            face.buffer.write(&ctx.gpu.queue, 0, &[face.data]);
+
+            // Directions are relative to the probe
+            *inv_viewproj = (proj * Mat4::look_at_rh(Vec3::ZERO, dir, up)).inverse();
+        }
+
+        self.projection_data.write(
+            &ctx.gpu.queue,
+            0,
+            &[ProjectionData {
+                inv_viewproj,
+                probe: coords,
+                capture_resolution: volume.capture_resolution,
+            }],
+        );
+
+        self.light_manager.update(&ctx)?;
+        let object_manager = ctx.store.get_mut(&self.object_manager);
+        object_manager.update(ctx.world, ctx.assets, ctx.gpu)?;
+
+        for (renderer, face) in izip!(&mut self.renderers, &self.faces) {
+            renderer.update(&mut UpdateContext {
+                world: ctx.world,
+                assets: ctx.assets,
+                gpu: ctx.gpu,
+                store: &mut self.store,
+                target_desc: TargetDesc {
+                    formats: &[CAPTURE_FORMAT],
+                    depth_format: Some(CAPTURE_DEPTH_FORMAT),
+                    sample_count: 1,
+                },
+                layouts: &[&face.layout, self.light_manager.layout()],
+                object_manager,
+            })?;
+        }
+
+        Ok(UpdateResult::Success)
+    }
+
+    fn draw(&mut self, ctx: NodeExecutionContext) -> anyhow::Result<()> {
+        profile_function!();
+
+        if !self.baking {
+            return Ok(());
+        }
+
+        let bind_groups = self.bind_groups.get_or_insert_with(|| {
+            self.faces
+                .iter()
+                .map(|face| face.create_bind_group(&ctx, self.skybox.as_ref(), None))
+                .collect_vec()
+        });
+
+        let iter = izip!(
+            &mut self.renderers,
+            &self.faces,
+            bind_groups.iter(),
+            &self.capture_views
+        );
+
+        for (renderer, face, bind_group, (view, depth_view)) in iter {
+            profile_scope!("capture_face");
+
+            let object_manager = ctx.store.get(&self.object_manager);
+            let render_context = RenderContext {
+                world: ctx.world,
+                assets: ctx.assets,
+                gpu: ctx.gpu,
+                queue: ctx.queue,
+                store: &self.store,
+                target_desc: TargetDesc {
+                    formats: &[CAPTURE_FORMAT],
+                    depth_format: Some(CAPTURE_DEPTH_FORMAT),
+                    sample_count: 1,
+                },
+                bind_groups: &[bind_group, self.light_manager.bind_group().unwrap()],
+                layouts: &[&face.layout, self.light_manager.layout()],
+                camera: face.data,
+                object_manager,
+            };
+
+            renderer.before_draw(&render_context, ctx.encoder)?;
+
+            let mut render_pass = ctx.encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: "light_probe_capture".into(),
+                color_attachments: &[Some(RenderPassColorAttachment {
+                    view,
+                    resolve_target: None,
+                    ops: Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                    view: depth_view,
+                    depth_ops: Some(Operations {
+                        load: wgpu::LoadOp::Clear(1.0),
+                        store: wgpu::StoreOp::Discard,
+                    }),
+                    stencil_ops: None,
+                }),
+                timestamp_writes: None,
+                occlusion_query_set: None,
+            });
+
+            renderer.draw(&render_context, &mut render_pass)?;
+        }
+
+        for renderer in &mut self.renderers {
+            RenderStats::collect(ctx.world, renderer);
+        }
+
+        let projection_bind_group = self.projection_bind_group.get_or_insert_with(|| {
+            let [sh_r, sh_g, sh_b] = self
+                .textures
+                .handles()
+                .map(|v| ctx.get_texture(v).create_view(&Default::default()));
+
+            BindGroupBuilder::new("LightProbeProjection")
+                .bind_buffer(&self.projection_data)
+                .bind_texture(&self.capture.create_view(&TextureViewDescriptor {
+                    dimension: Some(TextureViewDimension::D2Array),
+                    ..Default::default()
+                }))
+                .bind_texture(&sh_r)
+                .bind_texture(&sh_g)
+                .bind_texture(&sh_b)
+                .build(ctx.gpu, &self.projection_layout)
+        });
+
+        let mut compute_pass = ctx.encoder.begin_compute_pass(&ComputePassDescriptor {
+            label: Some("LightProbeProjection"),
+            timestamp_writes: None,
+        });
+
+        if let Some(pipeline) = &self.projection_pipeline {
+            compute_pass.set_pipeline(pipeline);
+        }
+        compute_pass.set_bind_group(0, projection_bind_group, &[]);
+        compute_pass.dispatch_workgroups(1, 1, 1);
+
+        Ok(())
+    }
+
+    fn read_dependencies(&self) -> Vec<Dependency> {
+        [
+            Dependency::texture(
+                self.light_manager.shadow_maps(),
+                TextureUsages::TEXTURE_BINDING,
+            ),
+            Dependency::buffer(
+                self.light_manager.shadow_camera_buffer(),
+                BufferUsages::STORAGE,
+            ),
+        ]
+        .into_iter()
+        .chain(
+            self.skybox
+                .iter()
+                .flat_map(|v| v.handles())
+                .map(|v| Dependency::texture(v, TextureUsages::TEXTURE_BINDING)),
+        )
+        .collect_vec()
+    }
+
+    fn write_dependencies(&self) -> Vec<Dependency> {
+        self.textures
+            .handles()
+            .map(|v| Dependency::texture(v, TextureUsages::STORAGE_BINDING))
+            .to_vec()
+    }
+
+    fn on_resource_changed(&mut self, _resource: ResourceHandle) {
+        self.bind_groups = None;
+        self.projection_bind_group = None;
+        self.light_manager.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn probe_grid() {
+        let volume =
+            LightProbeVolume::new(Vec3::splat(-2.0), Vec3::splat(2.0), UVec3::new(3, 2, 5));
+
+        assert_eq!(volume.probe_count(), 30);
+        assert_eq!(volume.probe_coords(0), UVec3::ZERO);
+        assert_eq!(volume.probe_coords(4), UVec3::new(1, 1, 0));
+        assert_eq!(volume.probe_coords(29), UVec3::new(2, 1, 4));
+
+        assert_eq!(volume.probe_position(UVec3::ZERO), Vec3::splat(-2.0));
+        assert_eq!(volume.probe_position(UVec3::new(2, 1, 4)), Vec3::splat(2.0));
+        assert_eq!(
+            volume.probe_position(UVec3::new(1, 0, 2)),
+            Vec3::new(0.0, -2.0, 0.0)
+        );
+    }
+
+    #[test]
+    fn degenerate_grid() {
+        let volume = LightProbeVolume::new(Vec3::ZERO, Vec3::ONE, UVec3::new(0, 1, 2));
+
+        assert_eq!(volume.resolution, UVec3::new(1, 1, 2));
+        assert_eq!(volume.probe_count(), 2);
+        assert_eq!(volume.probe_coords(1), UVec3::new(0, 0, 1));
+        assert_eq!(volume.probe_position(UVec3::ZERO), Vec3::ZERO);
+        assert_eq!(
+            volume.probe_position(UVec3::new(0, 0, 1)),
+            Vec3::new(0.0, 0.0, 1.0)
+        );
+    }
+
+    #[test]
+    fn shader_data() {
+        let volume = LightProbeVolume::new(Vec3::splat(-1.0), Vec3::splat(3.0), UVec3::splat(4));
+        let data = volume.shader_data();
+
+        assert_eq!(data.min, volume.min);
+        assert_eq!(data.max, volume.max);
+        assert_eq!(data.resolution, volume.resolution);
+        assert_eq!(data.enabled, 1);
+        assert_eq!(LightProbeVolumeData::default().enabled, 0);
+        assert_eq!(std::mem::size_of::<LightProbeVolumeData>() % 16, 0);
+    }
+
+    #[test]
+    fn capture_faces() {
+        for (i, (dir, up)) in FACES.iter().enumerate() {
+            assert_eq!(dir.dot(*up), 0.0, "face {i} is degenerate");
+            assert_eq!(dir.length(), 1.0);
+        }
+
+        for (a, b) in FACES.iter().zip(FACES.iter().skip(1)).step_by(2) {
+            assert_eq!(a.0, -b.0);
+        }
+    }
+}
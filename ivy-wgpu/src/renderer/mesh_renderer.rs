@@ -1,40 +1,61 @@
 use std::{
-    collections::{hash_map::Entry, BTreeMap, HashMap},
+    collections::{hash_map::Entry, BTreeMap, BTreeSet, HashMap, HashSet},
+    mem,
     sync::{Arc, Weak},
 };
 
 use bytemuck::{Pod, Zeroable};
 use flax::{
     entity_ids,
-    fetch::{entity_refs, EntityRefs, Satisfied},
+    fetch::{entity_refs, EntityRefs, Opt},
     filter::{All, ChangeFilter},
     Component, Entity, EntityIds, FetchExt, Query, World,
 };
 use glam::{vec4, Mat4, Vec3, Vec4, Vec4Swizzles};
 use itertools::Itertools;
 use ivy_assets::{map::AssetMap, stored::Handle, Asset, AssetCache};
-use ivy_core::{profiling::profile_function, subscribers::RemovedComponentSubscriber, WorldExt};
+use ivy_core::{
+    bounds::{bounding_sphere, BoundingSphere},
+    components::{engine, world_transform},
+    notifications::{notify, Notification},
+    pool::pooled,
+    profiling::profile_function,
+    subscribers::RemovedComponentSubscriber,
+    WorldExt,
+};
 use ivy_wgpu_types::{
     multi_buffer::SubBuffer, shader::Culling, BindGroupBuilder, BindGroupLayoutBuilder,
 };
-use wgpu::{BindGroup, BindGroupLayout, CommandEncoder, DepthBiasState, RenderPass, ShaderStages};
+use naga_oil::compose::ShaderDefValue;
+use wgpu::{
+    BindGroup, BindGroupLayout, CommandEncoder, CompareFunction, DepthBiasState, RenderPass,
+    ShaderModule, ShaderStages,
+};
 
 use super::{
+    bindless::{bindless_capacity, BindlessMaterials},
     culling::{CullDrawObject, ObjectCulling},
-    object_manager::{object_buffer_index, object_skinning_buffer},
+    object_manager::{object_buffer_index, object_skinned_vertices},
+    render_stats::RenderStats,
     CameraRenderer, TargetDesc,
 };
 use crate::{
-    components::mesh,
-    material::RenderMaterial,
+    components::{debug_view, mesh},
+    debug_view::DebugView,
+    events::ShaderReloaded,
+    material::{ReflectedBindings, RenderMaterial},
     material_desc::{MaterialData, PbrMaterialData, RenderMaterialDesc},
     mesh::{SkinnedVertex, VertexDesc},
     mesh_buffer::{MeshBuffer, MeshHandle},
     mesh_desc::MeshDesc,
     renderer::{culling::CullData, RendererStore},
     shader::ShaderPass,
-    shader_library::ShaderLibrary,
-    types::{shader::ShaderDesc, RenderShader},
+    shader_library::{ShaderLibrary, ShaderModuleDesc},
+    shaders::DebugOverdrawShaderDesc,
+    types::{
+        shader::{PendingRenderShader, ShaderDesc},
+        RenderShader,
+    },
     Gpu,
 };
 
@@ -42,6 +63,8 @@ use crate::{
 pub struct BatchKey {
     pub material: MaterialData,
     pub mesh: MeshDesc,
+    /// Skinned objects are drawn from their own animated vertices
+    pub skinned_vertices: Option<SubBuffer<SkinnedVertex>>,
 }
 
 /// A single rendering batch of similar objects
@@ -49,6 +72,11 @@ struct Batch {
     mesh: CachedMesh,
     material: Asset<RenderMaterial>,
     shader: Handle<RenderShader>,
+    skinned_vertices: Option<SubBuffer<SkinnedVertex>>,
+    /// Bindings of a custom material, which the pipeline was created for
+    reflected: Option<Arc<ReflectedBindings>>,
+    /// Drawn instead of `material` while its shader is compiling
+    placeholder: Option<Asset<RenderMaterial>>,
 }
 
 impl Batch {
@@ -56,21 +84,78 @@ impl Batch {
         mesh: CachedMesh,
         material: Asset<RenderMaterial>,
         shader: Handle<RenderShader>,
+        skinned_vertices: Option<SubBuffer<SkinnedVertex>>,
     ) -> Self {
         Self {
+            reflected: material.reflected(),
             mesh,
             material,
             shader,
+            skinned_vertices,
+            placeholder: None,
+        }
+    }
+
+    fn with_placeholder(mut self, placeholder: Asset<RenderMaterial>) -> Self {
+        self.placeholder = Some(placeholder);
+        self
+    }
+
+    /// The material currently bound when drawing the batch
+    fn draw_material(&self) -> &Asset<RenderMaterial> {
+        self.placeholder.as_ref().unwrap_or(&self.material)
+    }
+
+    /// The bind group of the material currently drawn
+    fn draw_bind_group(&self) -> Option<&BindGroup> {
+        match &self.placeholder {
+            Some(v) => v.bind_group(),
+            None => self
+                .reflected
+                .as_ref()
+                .map(|v| &v.bind_group)
+                .or(self.material.bind_group()),
         }
     }
+
+    /// Returns true if the drawn material supports the bindless path
+    fn is_bindless(&self) -> bool {
+        self.draw_material().bindless().is_some()
+    }
+
+    /// Indices are relative to the vertices of the mesh, or to the skinned vertices of the object
+    fn base_vertex(&self) -> i32 {
+        self.skinned_vertices
+            .unwrap_or_else(|| self.mesh.handle.vb())
+            .offset() as i32
+    }
 }
 
 pub type ShaderFactory = Box<dyn FnMut(ShaderDesc) -> ShaderDesc>;
 
+/// Determines the order in which a [`MeshRenderer`] issues its draws
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DrawOrder {
+    /// Group draws by pipeline and material to minimize state changes
+    #[default]
+    Batched,
+    /// Sort objects back-to-front relative to the camera each frame.
+    ///
+    /// Used for blended geometry, and disables depth writes.
+    BackToFront,
+}
+
+/// A run of consecutive indirect draws sharing the same pipeline and material.
+///
+/// When `MULTI_DRAW_INDIRECT` is supported the whole run is issued in a single draw call.
 #[derive(Copy, Clone)]
-struct IndirectBatch {
+struct DrawGroup {
+    /// Batch used to bind the pipeline and material
     batch_id: u32,
+    /// Offset of the first draw command in the indirect buffer
     offset: u32,
+    /// Number of consecutive draw commands
+    count: u32,
 }
 
 /// Argument buffer layout for draw_indexed_indirect commands.
@@ -93,12 +178,10 @@ pub struct DrawIndexedIndirectArgs {
 
 struct WeakCachedMesh {
     handle: Weak<MeshHandle<SkinnedVertex>>,
-    bounding_radius: f32,
 }
 
 struct CachedMesh {
     handle: Arc<MeshHandle<SkinnedVertex>>,
-    bounding_radius: f32,
 }
 
 type NewObjectQuery = (
@@ -106,14 +189,35 @@ type NewObjectQuery = (
     Component<MeshDesc>,
     Component<MaterialData>,
     Component<usize>,
-    Satisfied<Component<SubBuffer<Mat4>>>,
+    Opt<Component<BoundingSphere>>,
+    Opt<Component<SubBuffer<SkinnedVertex>>>,
 );
 
+/// Culling radius of objects without bounds, such as when they could not be computed, which keeps
+/// them from ever being culled
+const UNBOUNDED_RADIUS: f32 = 1e30;
+/// Culling radius of [`pooled`] objects, which fails every visibility test
+const HIDDEN_RADIUS: f32 = -1e30;
+
+fn new_object_query(
+    renderer: Entity,
+    shader_pass: Component<MaterialData>,
+) -> Query<NewObjectQuery, (All, flax::filter::Without)> {
+    Query::new((
+        entity_refs(),
+        mesh(),
+        shader_pass,
+        object_buffer_index(),
+        bounding_sphere().opt(),
+        object_skinned_vertices().opt(),
+    ))
+    .without(renderer_location(renderer))
+}
+
 pub struct MeshRenderer {
     id: Entity,
 
     object_buffer_gen: u32,
-    skin_buffer_gen: u32,
     bind_group: Option<BindGroup>,
     bind_group_layout: BindGroupLayout,
     meshes: HashMap<MeshDesc, WeakCachedMesh>,
@@ -127,17 +231,39 @@ pub struct MeshRenderer {
     draws: Vec<CullDrawObject>,
     sorted_draws: Vec<CullDrawObject>,
     entity_locations: BTreeMap<Entity, usize>,
+    /// Objects of pooled entities, which are kept but not drawn
+    hidden: BTreeSet<Entity>,
     batch_map: HashMap<BatchKey, BatchId>,
     indirect_draws: Vec<DrawIndexedIndirectArgs>,
-    indirect_batches: Vec<Option<IndirectBatch>>,
+    draw_groups: Vec<DrawGroup>,
+    /// Triangles of all draws, before culling
+    submitted_triangles: u64,
+    /// Draws submitted since the stats were last taken
+    stats: RenderStats,
+    multi_draw: bool,
+    draw_order: DrawOrder,
 
     mesh_buffer: MeshBuffer<SkinnedVertex>,
+    mesh_buffer_gen: u32,
     shader_library: Arc<ShaderLibrary>,
     shader_factory: ShaderFactory,
+    /// Shaders compiling in the background
+    pending_shaders: Vec<(Asset<ShaderPass>, PendingRenderShader)>,
+    placeholder_material: Option<MaterialData>,
+    placeholder: Option<(Asset<RenderMaterial>, Handle<RenderShader>)>,
+    debug_views: bool,
+    debug_view: DebugView,
+    /// Drawn instead of the material of every batch, such as for debug views or the id buffer
+    override_material: Option<Asset<RenderMaterial>>,
+    /// Binds all pbr materials at once, if supported by the device
+    bindless: Option<BindlessMaterials>,
     updated_object_indexes: Query<(EntityIds, Component<usize>, ChangeFilter<usize>)>,
+    updated_bounds: Query<(EntityIds, Component<usize>, ChangeFilter<BoundingSphere>)>,
     removed_rx: flume::Receiver<(Entity, usize)>,
+    reload_rx: flume::Receiver<ShaderReloaded>,
     cull: ObjectCulling,
-    new_object_query: Query<NewObjectQuery, (All, flax::filter::Without)>,
+    /// One for each drawn pass
+    new_object_queries: Vec<Query<NewObjectQuery, (All, flax::filter::Without)>>,
     needs_indirect_rebuild: bool,
 }
 
@@ -162,23 +288,14 @@ impl MeshRenderer {
         let bind_group_layout = BindGroupLayoutBuilder::new("ObjectBuffer")
             .bind_storage_buffer(ShaderStages::VERTEX) // object_data
             .bind_storage_buffer(ShaderStages::VERTEX) // indirection
-            .bind_storage_buffer(ShaderStages::VERTEX) // skin_data
             .build(gpu);
 
-        let new_object_query = Query::new((
-            entity_refs(),
-            mesh(),
-            shader_pass,
-            object_buffer_index(),
-            object_skinning_buffer().satisfied(),
-        ))
-        .without(renderer_location(id));
-
         Self {
             id,
             bind_group: None,
             bind_group_layout,
             cull,
+            reload_rx: shader_library.subscribe(),
             shader_library,
             meshes: Default::default(),
             shaders: Default::default(),
@@ -186,7 +303,15 @@ impl MeshRenderer {
             batches: Default::default(),
             batch_map: Default::default(),
             mesh_buffer: MeshBuffer::new(gpu, "mesh_buffer", 4),
+            mesh_buffer_gen: 0,
             shader_factory: Box::new(|v| v),
+            pending_shaders: Vec::new(),
+            placeholder_material: None,
+            placeholder: None,
+            debug_views: false,
+            debug_view: DebugView::Lit,
+            override_material: None,
+            bindless: bindless_capacity(gpu).map(|v| BindlessMaterials::new(gpu, assets, v)),
             removed_rx,
             draws: Vec::new(),
             updated_object_indexes: Query::new((
@@ -194,13 +319,25 @@ impl MeshRenderer {
                 renderer_location(id),
                 object_buffer_index().modified(),
             )),
-            new_object_query,
+            updated_bounds: Query::new((
+                entity_ids(),
+                renderer_location(id),
+                bounding_sphere().modified(),
+            )),
+            new_object_queries: vec![new_object_query(id, shader_pass)],
             indirect_draws: Vec::new(),
-            indirect_batches: Vec::new(),
+            draw_groups: Vec::new(),
+            submitted_triangles: 0,
+            stats: RenderStats::default(),
+            multi_draw: gpu
+                .device
+                .features()
+                .contains(wgpu::Features::MULTI_DRAW_INDIRECT),
+            draw_order: DrawOrder::Batched,
             object_buffer_gen: 0,
-            skin_buffer_gen: 0,
             needs_indirect_rebuild: true,
             entity_locations: BTreeMap::new(),
+            hidden: BTreeSet::new(),
             sorted_draws: Vec::new(),
         }
     }
@@ -214,6 +351,53 @@ impl MeshRenderer {
         self
     }
 
+    /// Set the draw order
+    pub fn with_draw_order(mut self, draw_order: DrawOrder) -> Self {
+        self.draw_order = draw_order;
+        self
+    }
+
+    /// Compile new shaders in the background, drawing their objects with `material` until ready.
+    ///
+    /// The shader of the placeholder itself is compiled immediately.
+    pub fn with_placeholder(mut self, material: impl Into<MaterialData>) -> Self {
+        self.placeholder_material = Some(material.into());
+        self
+    }
+
+    /// Follow the [`DebugView`] set on the engine entity
+    pub fn with_debug_views(mut self, debug_views: bool) -> Self {
+        self.debug_views = debug_views;
+        self
+    }
+
+    /// Draw every object with `material` rather than its own, e.g; to write ids or depth only
+    pub(crate) fn with_material_override(mut self, material: Asset<RenderMaterial>) -> Self {
+        self.override_material = Some(material);
+        self
+    }
+
+    /// Also draw the objects of another pass, e.g; to write ids for both opaque and transparent
+    /// objects without duplicating the meshes in another renderer.
+    ///
+    /// Only useful together with a material override, as the passes are otherwise drawn with
+    /// their own materials.
+    pub(crate) fn with_pass(mut self, shader_pass: Component<MaterialData>) -> Self {
+        self.new_object_queries
+            .push(new_object_query(self.id, shader_pass));
+        self
+    }
+
+    /// Draw pbr materials through a single texture array rather than per-material bind groups.
+    ///
+    /// Enabled by default where supported by the device.
+    pub fn with_bindless(mut self, bindless: bool) -> Self {
+        if !bindless {
+            self.bindless = None;
+        }
+        self
+    }
+
     #[allow(clippy::too_many_arguments)]
     pub fn process_new_objects(
         &mut self,
@@ -226,144 +410,191 @@ impl MeshRenderer {
     ) -> anyhow::Result<()> {
         let mut new_components = Vec::new();
 
-        for (entity, mesh, material, &object_index, skinned) in
-            self.new_object_query.borrow(world).iter()
-        {
-            let id = entity.id();
-            let key = BatchKey {
-                mesh: mesh.clone(),
-                material: material.clone(),
-            };
+        let params = PipelineParams {
+            gpu,
+            layouts,
+            object_layout: &self.bind_group_layout,
+            target,
+            draw_order: self.draw_order,
+            debug_view: self.debug_view,
+            bindless_layout: self.bindless.as_ref().map(|v| v.layout()),
+        };
+
+        if let (Some(placeholder), None) = (&self.placeholder_material, &self.placeholder) {
+            let material = assets.try_load(&RenderMaterialDesc {
+                material: placeholder.clone(),
+            })?;
+
+            let shader = build_shader(
+                &self.shader_library,
+                &mut self.shader_factory,
+                &params,
+                &material,
+                |desc, _| RenderShader::new(gpu, desc),
+            )?;
+
+            let shader = store.shaders.insert(shader);
+            self.shaders.insert(material.shader(), shader.clone());
+            self.placeholder = Some((material, shader));
+        }
 
-            let mut create_batch = |key: &BatchKey| {
-                let mut load_mesh = |v: &MeshDesc| {
-                    let mesh_data = v.load_data(assets).unwrap();
-                    let vertices = SkinnedVertex::compose_from_mesh(&mesh_data);
-
-                    let bounding_radius = vertices
-                        .iter()
-                        .map(|v| v.pos.length())
-                        .max_by_key(|&v| ordered_float::OrderedFloat(v))
-                        .unwrap_or_default();
-
-                    CachedMesh {
-                        handle: Arc::new(self.mesh_buffer.insert(
-                            gpu,
-                            &vertices,
-                            mesh_data.indices(),
-                        )),
-                        bounding_radius,
-                    }
+        for query in &mut self.new_object_queries {
+            for (entity, mesh, material, &object_index, bounds, skinned_vertices) in
+                query.borrow(world).iter()
+            {
+                let id = entity.id();
+                // Drawn through another pass already
+                if self.entity_locations.contains_key(&id) {
+                    continue;
+                }
+
+                let key = BatchKey {
+                    mesh: mesh.clone(),
+                    material: material.clone(),
+                    skinned_vertices: skinned_vertices.copied(),
                 };
 
-                let mesh = match self.meshes.entry(key.mesh.clone()) {
-                    Entry::Occupied(mut v) => {
-                        if let Some(mesh) = v.get().handle.upgrade() {
-                            CachedMesh {
-                                handle: mesh,
-                                bounding_radius: v.get().bounding_radius,
+                let mut create_batch = |key: &BatchKey| {
+                    let mut load_mesh = |v: &MeshDesc| {
+                        let mesh_data = v.load_data(assets).unwrap();
+                        let vertices = SkinnedVertex::compose_from_mesh(&mesh_data);
+
+                        CachedMesh {
+                            handle: Arc::new(self.mesh_buffer.insert(
+                                gpu,
+                                &vertices,
+                                mesh_data.indices(),
+                            )),
+                        }
+                    };
+
+                    let mesh = match self.meshes.entry(key.mesh.clone()) {
+                        Entry::Occupied(mut v) => {
+                            if let Some(mesh) = v.get().handle.upgrade() {
+                                CachedMesh { handle: mesh }
+                            } else {
+                                let mesh = load_mesh(v.key());
+                                v.insert(WeakCachedMesh {
+                                    handle: Arc::downgrade(&mesh.handle),
+                                });
+
+                                mesh
                             }
-                        } else {
+                        }
+                        Entry::Vacant(v) => {
                             let mesh = load_mesh(v.key());
                             v.insert(WeakCachedMesh {
                                 handle: Arc::downgrade(&mesh.handle),
-                                bounding_radius: mesh.bounding_radius,
                             });
 
                             mesh
                         }
-                    }
-                    Entry::Vacant(v) => {
-                        let mesh = load_mesh(v.key());
-                        v.insert(WeakCachedMesh {
-                            handle: Arc::downgrade(&mesh.handle),
-                            bounding_radius: mesh.bounding_radius,
-                        });
-
-                        mesh
-                    }
-                };
+                    };
 
-                let material = RenderMaterialDesc {
-                    material: key.material.clone(),
-                    skinned,
-                };
+                    let material = RenderMaterialDesc {
+                        material: key.material.clone(),
+                    };
 
-                let broken_material = |e: anyhow::Error| {
-                    tracing::error!(?key.material, "{:?}", e.context("Failed to load material"));
-                    assets.load(&RenderMaterialDesc {
-                        material: MaterialData::PbrMaterial(PbrMaterialData::new()),
-                        skinned: false,
-                    })
-                };
+                    let broken_material = |e: anyhow::Error| {
+                        let e = e.context("Failed to load material");
+                        tracing::error!(?key.material, "{e:?}");
+                        notify(assets, Notification::error(format!("{e:#}")));
+                        assets.load(&RenderMaterialDesc {
+                            material: MaterialData::PbrMaterial(PbrMaterialData::new()),
+                        })
+                    };
 
-                let material: Asset<RenderMaterial> =
-                    assets.try_load(&material).unwrap_or_else(broken_material);
+                    let material: Asset<RenderMaterial> = assets
+                        .try_load(&material)
+                        .and_then(|material: Asset<RenderMaterial>| {
+                            if material.needs_reflection() {
+                                let module =
+                                    self.shader_library.compose((&**material.shader()).into())?;
 
-                let shader = material.shader();
-                let shader =
-                    match self.shaders.entry(shader) {
-                        slotmap::secondary::Entry::Occupied(slot) => slot.get().clone(),
-                        slotmap::secondary::Entry::Vacant(slot) => {
-                            let module = self.shader_library.process(gpu, (&**shader).into())?;
-
-                            let vertex_layouts = &[SkinnedVertex::layout()];
-
-                            let bind_group_layouts = layouts
-                                .iter()
-                                .copied()
-                                .chain([&self.bind_group_layout])
-                                .chain(material.layout())
-                                .collect_vec();
-
-                            let shader_desc = ShaderDesc::new(shader.label(), &module, target)
-                                .with_vertex_layouts(vertex_layouts)
-                                .with_bind_group_layouts(&bind_group_layouts)
-                                .with_culling_mode(Culling {
-                                    cull_mode: shader.cull_mode,
-                                    front_face: wgpu::FrontFace::Ccw,
-                                })
-                                .with_depth_bias(DepthBiasState {
-                                    constant: -2,
-                                    slope_scale: 2.0,
-                                    clamp: 0.0,
-                                });
+                                material.reflect(gpu, assets, &module)?;
+                            }
 
-                            slot.insert(store.shaders.insert(RenderShader::new(
-                                gpu,
-                                &(self.shader_factory)(shader_desc),
-                            )))
-                            .clone()
-                        }
+                            Ok(material)
+                        })
+                        .unwrap_or_else(broken_material);
+
+                    let shader_material = self.override_material.as_ref().unwrap_or(&material);
+                    let shader = shader_material.shader();
+
+                    // The placeholder can not stand in for the debug material
+                    let placeholder = self
+                        .placeholder
+                        .as_ref()
+                        .filter(|_| self.override_material.is_none());
+
+                    let shader = match self.shaders.entry(shader) {
+                        slotmap::secondary::Entry::Occupied(slot) => slot.get().clone(),
+                        slotmap::secondary::Entry::Vacant(slot) => match placeholder {
+                            Some((placeholder, placeholder_shader)) => {
+                                if !self.pending_shaders.iter().any(|(v, _)| v == shader) {
+                                    let pending = build_shader(
+                                        &self.shader_library,
+                                        &mut self.shader_factory,
+                                        &params,
+                                        shader_material,
+                                        |desc, module| RenderShader::spawn(gpu, desc, module),
+                                    )?;
+
+                                    self.pending_shaders.push((shader.clone(), pending));
+                                }
+
+                                return anyhow::Ok(
+                                    Batch::new(
+                                        mesh,
+                                        material,
+                                        placeholder_shader.clone(),
+                                        key.skinned_vertices,
+                                    )
+                                    .with_placeholder(placeholder.clone()),
+                                );
+                            }
+                            None => {
+                                let shader = build_shader(
+                                    &self.shader_library,
+                                    &mut self.shader_factory,
+                                    &params,
+                                    shader_material,
+                                    |desc, _| RenderShader::new(gpu, desc),
+                                )?;
+
+                                slot.insert(store.shaders.insert(shader)).clone()
+                            }
+                        },
                     };
 
-                anyhow::Ok(Batch::new(mesh, material, shader))
-            };
+                    anyhow::Ok(Batch::new(mesh, material, shader, key.skinned_vertices))
+                };
 
-            let batch_id = match self.batch_map.entry(key) {
-                Entry::Occupied(slot) => *slot.get(),
-                Entry::Vacant(slot) => {
-                    let batch = create_batch(slot.key());
-                    let batch_index = self.batches.len();
-                    self.batches.push(batch?);
-                    slot.insert(batch_index);
-                    batch_index
-                }
-            };
+                let batch_id = match self.batch_map.entry(key) {
+                    Entry::Occupied(slot) => *slot.get(),
+                    Entry::Vacant(slot) => {
+                        let batch = create_batch(slot.key());
+                        let batch_index = self.batches.len();
+                        self.batches.push(batch?);
+                        slot.insert(batch_index);
+                        batch_index
+                    }
+                };
 
-            let draw = CullDrawObject {
-                object_index: object_index as u32,
-                batch_id: batch_id as u32,
-                radius: self.batches[batch_id].mesh.bounding_radius,
-                id,
-            };
+                let draw = CullDrawObject {
+                    object_index: object_index as u32,
+                    batch_id: batch_id as u32,
+                    radius: bounds.map_or(UNBOUNDED_RADIUS, BoundingSphere::origin_radius),
+                    id,
+                };
 
-            let new_index = self.draws.len();
-            new_components.push((id, new_index));
-            self.entity_locations.insert(id, new_index);
+                let new_index = self.draws.len();
+                new_components.push((id, new_index));
+                self.entity_locations.insert(id, new_index);
 
-            self.draws.push(draw);
-            self.needs_indirect_rebuild = true;
+                self.draws.push(draw);
+                self.needs_indirect_rebuild = true;
+            }
         }
 
         world
@@ -373,52 +604,414 @@ impl MeshRenderer {
         Ok(())
     }
 
+    /// Recreates the pipelines of batches using shaders which were reloaded from disk.
+    ///
+    /// Batches keep their previous pipeline if the reloaded shader fails to compile. The bindings
+    /// of custom materials are reflected again, as the shader may have changed them.
+    fn process_reloaded_shaders(
+        &mut self,
+        gpu: &Gpu,
+        assets: &AssetCache,
+        layouts: &[&BindGroupLayout],
+        store: &mut RendererStore,
+        target: &TargetDesc,
+    ) {
+        let reloaded = self.reload_rx.drain().collect_vec();
+        if reloaded.is_empty() {
+            return;
+        }
+
+        let mut reflected = HashSet::new();
+        for batch in &mut self.batches {
+            let material = &batch.material;
+            if !material.is_custom() || !reloaded.iter().any(|v| v.affects(&material.shader().path))
+            {
+                continue;
+            }
+
+            if reflected.insert(material.id()) {
+                let result = self
+                    .shader_library
+                    .compose((&**material.shader()).into())
+                    .and_then(|module| material.reflect(gpu, assets, &module));
+
+                if let Err(err) = result {
+                    let err = err.context(format!(
+                        "Failed to reflect the bindings of material {:?}",
+                        material.label()
+                    ));
+                    tracing::error!("{err:?}");
+                    notify(assets, Notification::error(format!("{err:#}")));
+                }
+            }
+
+            batch.reflected = material.reflected();
+        }
+
+        self.rebuild_shaders(gpu, assets, layouts, store, target, |shader| {
+            reloaded.iter().any(|v| v.affects(&shader.path))
+        });
+    }
+
+    /// Switches to a new debug view, recreating all pipelines as shader permutations of the view
+    fn set_debug_view(
+        &mut self,
+        gpu: &Gpu,
+        assets: &AssetCache,
+        layouts: &[&BindGroupLayout],
+        store: &mut RendererStore,
+        target: &TargetDesc,
+        debug_view: DebugView,
+    ) {
+        tracing::info!(?debug_view, "Changing debug view");
+        self.debug_view = debug_view;
+        self.override_material = (debug_view == DebugView::Overdraw).then(|| {
+            assets.insert(RenderMaterial::unbound(
+                "debug_overdraw",
+                assets.load(&DebugOverdrawShaderDesc),
+            ))
+        });
+
+        // Cached pipelines belong to the previous view
+        self.shaders = Default::default();
+        self.pending_shaders.clear();
+        self.placeholder = None;
+
+        self.rebuild_shaders(gpu, assets, layouts, store, target, |_| true);
+    }
+
+    /// Recreates the pipelines of the batches whose shader matches `filter`
+    fn rebuild_shaders(
+        &mut self,
+        gpu: &Gpu,
+        assets: &AssetCache,
+        layouts: &[&BindGroupLayout],
+        store: &mut RendererStore,
+        target: &TargetDesc,
+        filter: impl Fn(&ShaderPass) -> bool,
+    ) {
+        let params = PipelineParams {
+            gpu,
+            layouts,
+            object_layout: &self.bind_group_layout,
+            target,
+            draw_order: self.draw_order,
+            debug_view: self.debug_view,
+            bindless_layout: self.bindless.as_ref().map(|v| v.layout()),
+        };
+
+        let mut rebuilt = HashSet::new();
+        for batch in &mut self.batches {
+            let material = self.override_material.as_ref().unwrap_or(&batch.material);
+            let shader = material.shader();
+            if !filter(shader) {
+                continue;
+            }
+
+            if rebuilt.insert(shader.id()) {
+                match build_shader(
+                    &self.shader_library,
+                    &mut self.shader_factory,
+                    &params,
+                    material,
+                    |desc, _| RenderShader::new(gpu, desc),
+                ) {
+                    Ok(v) => self.shaders.insert(shader, store.shaders.insert(v)),
+                    Err(err) => {
+                        let err =
+                            err.context(format!("Failed to rebuild shader {:?}", shader.path));
+                        tracing::error!("{err:?}");
+                        notify(assets, Notification::error(format!("{err:#}")));
+                    }
+                }
+            }
+
+            if let Some(v) = self.shaders.get(shader) {
+                batch.shader = v.clone();
+                batch.placeholder = None;
+            }
+        }
+
+        // Draws are grouped by pipeline
+        self.needs_indirect_rebuild = true;
+    }
+
+    /// Replaces the placeholder of batches whose shader finished compiling
+    fn process_compiled_shaders(&mut self, store: &mut RendererStore) {
+        let mut compiled = false;
+        self.pending_shaders.retain(|(shader, pending)| {
+            let Some(v) = pending.try_take() else {
+                return true;
+            };
+
+            tracing::debug!(label = pending.label(), "Compiled shader");
+
+            // Keep the shader if it was already reloaded while compiling
+            if !self.shaders.contains(shader) {
+                self.shaders.insert(shader, store.shaders.insert(v));
+            }
+
+            compiled = true;
+            false
+        });
+
+        if !compiled {
+            return;
+        }
+
+        for batch in &mut self.batches {
+            if batch.placeholder.is_none() {
+                continue;
+            }
+
+            if let Some(v) = self.shaders.get(batch.material.shader()) {
+                batch.shader = v.clone();
+                batch.placeholder = None;
+            }
+        }
+
+        self.needs_indirect_rebuild = true;
+    }
+
     fn rebuild_indirect_batches(&mut self, gpu: &Gpu) {
-        let mut total_object_count = 0;
+        // Order the batches by pipeline, material and mesh so that batches sharing state occupy
+        // adjacent slots in the indirect buffer and can be collapsed into a single draw call.
+        let mut batch_order = (0..self.batches.len()).collect_vec();
+        batch_order.sort_by(|&a, &b| {
+            let a = &self.batches[a];
+            let b = &self.batches[b];
+            (&a.shader, a.draw_material(), a.mesh.handle.ib().offset()).cmp(&(
+                &b.shader,
+                b.draw_material(),
+                b.mesh.handle.ib().offset(),
+            ))
+        });
+
+        let mut batch_slots = vec![0; self.batches.len()];
+        for (slot, &batch_id) in batch_order.iter().enumerate() {
+            batch_slots[batch_id] = slot as u32;
+        }
+
         self.indirect_draws.clear();
-        self.indirect_batches.clear();
-        self.indirect_batches.resize(self.batches.len(), None);
-        self.indirect_draws.resize(
-            self.batches.len(),
-            DrawIndexedIndirectArgs {
-                index_count: 0,
-                instance_count: 0,
-                first_index: 0,
-                base_vertex: 0,
-                first_instance: 0,
-            },
-        );
+        self.indirect_draws
+            .resize(self.batches.len(), DrawIndexedIndirectArgs::default());
 
+        // The culling shader addresses the indirect draws through `batch_id`, so remap it to the
+        // sorted slot
         self.sorted_draws.clear();
-        self.sorted_draws.extend(self.draws.iter().copied());
+        self.sorted_draws
+            .extend(self.draws.iter().map(|&draw| CullDrawObject {
+                batch_id: batch_slots[draw.batch_id as usize],
+                ..draw
+            }));
         // sort same batches by id, to ensure stable rendering
         self.sorted_draws.sort_by_key(|v| (v.batch_id, v.id));
 
+        let mut total_object_count = 0;
+        let mut occupied_slots = Vec::new();
+        self.submitted_triangles = 0;
         let chunks = self.sorted_draws.iter().chunk_by(|v| v.batch_id);
-        for (batch_id, group) in &chunks {
+        for (slot, group) in &chunks {
             let instance_count = group.count() as u32;
-            let batch = &self.batches[batch_id as usize];
-            let cmd = DrawIndexedIndirectArgs {
+            let batch = &self.batches[batch_order[slot as usize]];
+            self.indirect_draws[slot as usize] = DrawIndexedIndirectArgs {
                 index_count: batch.mesh.handle.index_count() as u32,
                 instance_count: 0, // filled by culling
                 first_index: batch.mesh.handle.ib().offset() as u32,
-                base_vertex: 0,
+                base_vertex: batch.base_vertex(),
                 first_instance: total_object_count,
             };
 
-            let batch = IndirectBatch {
-                batch_id,
-                offset: batch_id,
-            };
-
-            self.indirect_draws[batch_id as usize] = cmd;
-            self.indirect_batches[batch_id as usize] = Some(batch);
+            occupied_slots.push(slot);
             total_object_count += instance_count;
+            self.submitted_triangles +=
+                batch.mesh.handle.index_count() as u64 / 3 * instance_count as u64;
         }
 
+        self.build_draw_groups(
+            occupied_slots
+                .into_iter()
+                .map(|slot| (slot, batch_order[slot as usize])),
+        );
+
         self.cull.update_objects(gpu, &self.sorted_draws);
     }
 
+    /// Gives each object its own indirect draw, ordered from farthest to nearest to the camera
+    fn sort_back_to_front(&mut self, gpu: &Gpu, world: &World, view: Mat4) {
+        profile_function!();
+
+        let mut depths = self
+            .draws
+            .iter()
+            .map(|draw| {
+                let pos = world
+                    .get(draw.id, world_transform())
+                    .map(|v| v.transform_point3(Vec3::ZERO))
+                    .unwrap_or_default();
+
+                // The camera looks down -Z, so the farthest objects have the lowest depth
+                (view.transform_point3(pos).z, *draw)
+            })
+            .collect_vec();
+
+        depths.sort_by(|a, b| a.0.total_cmp(&b.0).then(a.1.id.cmp(&b.1.id)));
+
+        self.sorted_draws.clear();
+        self.indirect_draws.clear();
+        self.submitted_triangles = 0;
+
+        for (slot, (_, draw)) in depths.iter().enumerate() {
+            let batch = &self.batches[draw.batch_id as usize];
+            self.submitted_triangles += batch.mesh.handle.index_count() as u64 / 3;
+            self.indirect_draws.push(DrawIndexedIndirectArgs {
+                index_count: batch.mesh.handle.index_count() as u32,
+                instance_count: 0, // filled by culling
+                first_index: batch.mesh.handle.ib().offset() as u32,
+                base_vertex: batch.base_vertex(),
+                first_instance: slot as u32,
+            });
+
+            self.sorted_draws.push(CullDrawObject {
+                batch_id: slot as u32,
+                ..*draw
+            });
+        }
+
+        self.build_draw_groups(
+            depths
+                .iter()
+                .enumerate()
+                .map(|(slot, (_, draw))| (slot as u32, draw.batch_id as usize)),
+        );
+
+        self.cull.update_objects(gpu, &self.sorted_draws);
+    }
+
+    /// Merges consecutive indirect draws sharing pipeline and material into draw groups
+    fn build_draw_groups(&mut self, slots: impl IntoIterator<Item = (u32, BatchId)>) {
+        let bindless = self.bindless_enabled();
+        self.draw_groups.clear();
+        for (slot, batch_id) in slots {
+            let batch = &self.batches[batch_id];
+
+            match self.draw_groups.last_mut() {
+                Some(group)
+                    if group.offset + group.count == slot
+                        && self.batches[group.batch_id as usize].shader == batch.shader
+                        && (self.batches[group.batch_id as usize].draw_material()
+                            == batch.draw_material()
+                            || (bindless
+                                && self.batches[group.batch_id as usize].is_bindless()
+                                && batch.is_bindless()))
+                        && self.batches[group.batch_id as usize]
+                            .skinned_vertices
+                            .is_some()
+                            == batch.skinned_vertices.is_some() =>
+                {
+                    group.count += 1;
+                }
+                _ => self.draw_groups.push(DrawGroup {
+                    batch_id: batch_id as u32,
+                    offset: slot,
+                    count: 1,
+                }),
+            }
+        }
+    }
+
+    /// Recreates the materials whose streamed textures changed their resident level.
+    ///
+    /// Batches sharing a material share the recreated material as well.
+    fn process_streamed_materials(&mut self, assets: &AssetCache) {
+        let mut recreated: HashMap<Asset<RenderMaterial>, Asset<RenderMaterial>> = HashMap::new();
+
+        for (key, &batch_id) in &self.batch_map {
+            let batch = &mut self.batches[batch_id];
+            if !batch.material.is_stale() {
+                continue;
+            }
+
+            let material = match recreated.entry(batch.material.clone()) {
+                Entry::Occupied(slot) => slot.get().clone(),
+                Entry::Vacant(slot) => {
+                    // Replace the stale material in the asset cache, so that batches created
+                    // later share the recreated material
+                    let material = assets.reload(&RenderMaterialDesc {
+                        material: key.material.clone(),
+                    });
+
+                    match material {
+                        Ok(v) => slot.insert(v).clone(),
+                        Err(err) => {
+                            let err = err.context("Failed to recreate streamed material");
+                            tracing::error!("{err:?}");
+                            notify(assets, Notification::error(format!("{err:#}")));
+                            continue;
+                        }
+                    }
+                }
+            };
+
+            batch.material = material;
+            self.needs_indirect_rebuild = true;
+        }
+    }
+
+    /// Requests the detail of the streamed textures of each object from its distance to the
+    /// camera
+    fn request_streamed_textures(&self, world: &World, camera_pos: Vec3) {
+        profile_function!();
+
+        for draw in &self.draws {
+            let batch = &self.batches[draw.batch_id as usize];
+            let mut textures = batch.material.streamed_textures().peekable();
+            if textures.peek().is_none() {
+                continue;
+            }
+
+            let Ok(transform) = world.get(draw.id, world_transform()) else {
+                continue;
+            };
+
+            let distance = (transform.transform_point3(Vec3::ZERO).distance(camera_pos)
+                - draw.radius)
+                .max(0.0);
+
+            for texture in textures {
+                texture.request(distance);
+            }
+        }
+    }
+
+    /// Returns true if batches supporting it are drawn through the bindless materials
+    fn bindless_enabled(&self) -> bool {
+        self.bindless.is_some() && self.override_material.is_none()
+    }
+
+    /// Updates the culling radius of objects whose bounds changed, such as animated meshes.
+    ///
+    /// Returns true if any radius changed.
+    fn update_bounds(&mut self, world: &World) -> bool {
+        let mut changed = false;
+        for (id, &loc, bounds) in self.updated_bounds.borrow(world).iter() {
+            if self.hidden.contains(&id) {
+                continue;
+            }
+
+            let draw = &mut self.draws[loc];
+            assert_eq!(draw.id, id);
+
+            let radius = bounds.origin_radius();
+            if radius != draw.radius {
+                draw.radius = radius;
+                changed = true;
+            }
+        }
+
+        changed
+    }
+
     pub fn process_moved_objects(&mut self, world: &World) {
         for (id, &loc, &new_index) in self.updated_object_indexes.borrow(world).iter() {
             assert_eq!(self.draws[loc].id, id);
@@ -427,6 +1020,43 @@ impl MeshRenderer {
         }
     }
 
+    /// Hides the objects of [`pooled`] entities, and restores them once spawned again.
+    ///
+    /// Returns true if any object was hidden or restored.
+    fn update_pooled(&mut self, world: &World) -> bool {
+        let pooled = Query::new((entity_ids(), renderer_location(self.id).copied()))
+            .with(pooled())
+            .borrow(world)
+            .iter()
+            .collect::<BTreeMap<_, _>>();
+
+        let mut changed = false;
+        for (&id, &loc) in &pooled {
+            if self.hidden.insert(id) {
+                self.draws[loc].radius = HIDDEN_RADIUS;
+                changed = true;
+            }
+        }
+
+        self.hidden.retain(|id| {
+            if pooled.contains_key(id) {
+                return true;
+            }
+
+            // Despawned entities no longer have an object
+            if let Some(&loc) = self.entity_locations.get(id) {
+                self.draws[loc].radius = world
+                    .get(*id, bounding_sphere())
+                    .map_or(UNBOUNDED_RADIUS, |v| v.origin_radius());
+                changed = true;
+            }
+
+            false
+        });
+
+        changed
+    }
+
     pub fn process_removed(&mut self, world: &World) {
         for (id, _) in self.removed_rx.try_iter() {
             self.needs_indirect_rebuild = true;
@@ -453,6 +1083,35 @@ impl MeshRenderer {
 impl CameraRenderer for MeshRenderer {
     fn update(&mut self, ctx: &mut super::UpdateContext) -> anyhow::Result<()> {
         profile_function!();
+        if self.debug_views {
+            let debug_view = ctx
+                .world
+                .get(engine(), debug_view())
+                .map(|v| *v)
+                .unwrap_or_default();
+
+            if debug_view != self.debug_view {
+                self.set_debug_view(
+                    ctx.gpu,
+                    ctx.assets,
+                    ctx.layouts,
+                    ctx.store,
+                    &ctx.target_desc,
+                    debug_view,
+                );
+            }
+        }
+
+        self.process_reloaded_shaders(
+            ctx.gpu,
+            ctx.assets,
+            ctx.layouts,
+            ctx.store,
+            &ctx.target_desc,
+        );
+        self.process_compiled_shaders(ctx.store);
+        self.process_streamed_materials(ctx.assets);
+
         self.process_new_objects(
             ctx.world,
             ctx.assets,
@@ -465,9 +1124,37 @@ impl CameraRenderer for MeshRenderer {
         self.process_moved_objects(ctx.world);
         self.process_removed(ctx.world);
 
+        // The indirect draws address the meshes by their location in the buffer
+        if self.mesh_buffer.gen() != self.mesh_buffer_gen {
+            self.mesh_buffer_gen = self.mesh_buffer.gen();
+            self.needs_indirect_rebuild = true;
+        }
+
+        let bounds_changed = self.update_bounds(ctx.world);
+        let pooled_changed = self.update_pooled(ctx.world);
+        let bounds_changed = bounds_changed || pooled_changed;
+
         if self.needs_indirect_rebuild {
             self.needs_indirect_rebuild = false;
-            self.rebuild_indirect_batches(ctx.gpu);
+
+            if let Some(bindless) = &mut self.bindless {
+                bindless.update(
+                    ctx.gpu,
+                    self.batches.iter().map(|v| v.draw_material()),
+                    &self.draws,
+                );
+            }
+
+            // Sorted draws are rebuilt each frame once the camera is known
+            if self.draw_order == DrawOrder::Batched {
+                self.rebuild_indirect_batches(ctx.gpu);
+            }
+        } else if bounds_changed && self.draw_order == DrawOrder::Batched {
+            for draw in &mut self.sorted_draws {
+                draw.radius = self.draws[self.entity_locations[&draw.id]].radius;
+            }
+
+            self.cull.update_objects(ctx.gpu, &self.sorted_draws);
         }
 
         Ok(())
@@ -480,18 +1167,20 @@ impl CameraRenderer for MeshRenderer {
     ) -> anyhow::Result<()> {
         profile_function!();
         let object_buffer = ctx.object_manager.object_buffer();
-        let skinning_buffer = ctx.object_manager.skinning_buffer();
 
-        if self.object_buffer_gen != object_buffer.gen()
-            || self.skin_buffer_gen != skinning_buffer.gen()
-        {
+        if self.object_buffer_gen != object_buffer.gen() {
             self.object_buffer_gen = object_buffer.gen();
-            self.skin_buffer_gen = skinning_buffer.gen();
 
             self.bind_group = None;
             self.cull.bind_group = None;
         }
 
+        if self.draw_order == DrawOrder::BackToFront {
+            self.sort_back_to_front(ctx.gpu, ctx.world, ctx.camera.view);
+        }
+
+        self.request_streamed_textures(ctx.world, ctx.camera.camera_pos);
+
         fn normalize_plane(plane: Vec4) -> Vec4 {
             plane / plane.xyz().length()
         }
@@ -534,13 +1223,11 @@ impl CameraRenderer for MeshRenderer {
         profile_function!();
 
         let object_buffer = ctx.object_manager.object_buffer();
-        let skinning_buffer = ctx.object_manager.skinning_buffer();
 
         let bind_group = self.bind_group.get_or_insert_with(|| {
             BindGroupBuilder::new("ObjectBuffer")
                 .bind_buffer(object_buffer.buffer())
                 .bind_buffer(self.cull.indirection_buffer())
-                .bind_buffer(skinning_buffer.buffer())
                 .build(ctx.gpu, &self.bind_group_layout)
         });
 
@@ -552,31 +1239,163 @@ impl CameraRenderer for MeshRenderer {
 
         self.mesh_buffer.bind(render_pass);
 
-        for draw in &self.indirect_batches {
-            let Some(draw) = draw else {
-                continue;
-            };
+        const STRIDE: u64 = size_of::<DrawIndexedIndirectArgs>() as u64;
+        let indirect_buffer = self.cull.indirect_draw_buffer();
+
+        let bindless_group = self
+            .bindless
+            .as_ref()
+            .and_then(|v| v.bind_group())
+            .filter(|_| self.override_material.is_none());
+
+        let mut bound_skinned_vertices = false;
+        let mut bound_bindless = false;
+        let mut draw_calls = 0;
+        for group in &self.draw_groups {
+            let batch = &self.batches[group.batch_id as usize];
+
+            let skinned = batch.skinned_vertices.is_some();
+            if skinned != bound_skinned_vertices {
+                bound_skinned_vertices = skinned;
+                if skinned {
+                    render_pass
+                        .set_vertex_buffer(0, ctx.object_manager.skinned_vertices().slice(..));
+                } else {
+                    render_pass.set_vertex_buffer(0, self.mesh_buffer.vertex_buffers.slice(..));
+                }
+            }
 
-            let batch = &self.batches[draw.batch_id as usize];
+            if let Some(bind_group) = bindless_group.filter(|_| batch.is_bindless()) {
+                // Shared by all bindless batches
+                if !bound_bindless {
+                    bound_bindless = true;
+                    render_pass.set_bind_group(ctx.bind_groups.len() as u32 + 1, bind_group, &[]);
+                }
+            } else {
+                bound_bindless = false;
+                let bind_group = match &self.override_material {
+                    Some(v) => v.bind_group(),
+                    None => batch.draw_bind_group(),
+                };
 
-            if let Some(bind_group) = batch.material.bind_group() {
-                render_pass.set_bind_group(ctx.bind_groups.len() as u32 + 1, bind_group, &[]);
+                if let Some(bind_group) = bind_group {
+                    render_pass.set_bind_group(ctx.bind_groups.len() as u32 + 1, bind_group, &[]);
+                }
             }
 
             render_pass.set_pipeline(ctx.store.shaders[&batch.shader].pipeline());
 
-            render_pass.draw_indexed_indirect(
-                self.cull.indirect_draw_buffer(),
-                draw.offset as u64 * size_of::<DrawIndexedIndirectArgs>() as u64,
-            );
+            let offset = group.offset as u64 * STRIDE;
+            if self.multi_draw && group.count > 1 {
+                render_pass.multi_draw_indexed_indirect(indirect_buffer, offset, group.count);
+                draw_calls += 1;
+            } else {
+                for i in 0..group.count as u64 {
+                    render_pass.draw_indexed_indirect(indirect_buffer, offset + i * STRIDE);
+                }
+                draw_calls += group.count as u64;
+            }
         }
 
+        self.stats += RenderStats::new(draw_calls, self.submitted_triangles);
+
         Ok(())
     }
+
+    fn take_stats(&mut self) -> RenderStats {
+        mem::take(&mut self.stats)
+    }
 }
 
 type BatchId = usize;
 
+/// State shared by the pipelines of a renderer
+struct PipelineParams<'a> {
+    gpu: &'a Gpu,
+    layouts: &'a [&'a BindGroupLayout],
+    object_layout: &'a BindGroupLayout,
+    target: &'a TargetDesc<'a>,
+    draw_order: DrawOrder,
+    debug_view: DebugView,
+    /// Used instead of the material layout for materials supporting the bindless path
+    bindless_layout: Option<&'a BindGroupLayout>,
+}
+
+/// Prepares the pipeline description of the material's shader, which is compiled by `compile`
+fn build_shader<R>(
+    shader_library: &ShaderLibrary,
+    shader_factory: &mut ShaderFactory,
+    params: &PipelineParams,
+    material: &RenderMaterial,
+    compile: impl FnOnce(&ShaderDesc, Arc<ShaderModule>) -> R,
+) -> anyhow::Result<R> {
+    let shader = material.shader();
+
+    let mut module_desc = ShaderModuleDesc::from(&**shader);
+    if let Some(value) = params.debug_view.shader_value() {
+        module_desc
+            .shader_defs
+            .insert("DEBUG_VIEW".into(), ShaderDefValue::UInt(value));
+    }
+
+    let bindless_layout = params
+        .bindless_layout
+        .filter(|_| material.bindless().is_some());
+
+    if bindless_layout.is_some() {
+        module_desc
+            .shader_defs
+            .insert("BINDLESS".into(), ShaderDefValue::Bool(true));
+    }
+
+    let module = Arc::new(shader_library.process(params.gpu, module_desc)?);
+    let reflected = material.reflected();
+
+    let vertex_layouts = &[SkinnedVertex::layout()];
+
+    let bind_group_layouts = params
+        .layouts
+        .iter()
+        .copied()
+        .chain([params.object_layout])
+        .chain(
+            bindless_layout
+                .or(reflected.as_ref().map(|v| &v.layout))
+                .or(material.layout()),
+        )
+        .collect_vec();
+
+    let shader_desc = ShaderDesc::new(shader.label(), &module, params.target)
+        .with_vertex_layouts(vertex_layouts)
+        .with_bind_group_layouts(&bind_group_layouts)
+        .with_culling_mode(Culling {
+            cull_mode: shader.cull_mode,
+            front_face: wgpu::FrontFace::Ccw,
+        })
+        .with_depth_bias(DepthBiasState {
+            constant: -2,
+            slope_scale: 2.0,
+            clamp: 0.0,
+        })
+        .with_polygon_mode(params.debug_view.polygon_mode());
+
+    // Every surface is counted, regardless of whether it is occluded
+    let shader_desc = if params.debug_view == DebugView::Overdraw {
+        shader_desc
+            .with_blend(shader.blend)
+            .with_depth_write(false)
+            .with_depth_compare(CompareFunction::Always)
+    } else {
+        match params.draw_order {
+            // Opaque geometry overwrites the target regardless of the material
+            DrawOrder::Batched => shader_desc.with_blend(None).with_depth_write(true),
+            DrawOrder::BackToFront => shader_desc.with_blend(shader.blend).with_depth_write(false),
+        }
+    };
+
+    Ok(compile(&shader_factory(shader_desc), module.clone()))
+}
+
 flax::component! {
     renderer_location(id): usize,
 }
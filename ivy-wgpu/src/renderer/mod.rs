@@ -1,9 +1,18 @@
+mod bindless;
 mod culling;
 pub mod gizmos_renderer;
 mod light_manager;
+pub mod light_probes;
 pub mod mesh_renderer;
 mod object_manager;
+pub mod picking;
+pub mod readback;
+pub mod render_stats;
+pub mod screen_gizmos_renderer;
 pub mod shadowmapping;
+mod skinning;
+pub mod sprite_renderer;
+mod stroke_font;
 
 use std::any::type_name;
 
@@ -22,7 +31,9 @@ use ivy_core::{
 };
 use ivy_wgpu_types::shader::TargetDesc;
 pub use light_manager::LightManager;
+use light_probes::{LightProbeTextures, LightProbeVolume, LightProbeVolumeData};
 pub use object_manager::ObjectManager;
+use render_stats::RenderStats;
 use wgpu::{
     AddressMode, BindGroup, BindGroupLayout, BufferUsages, CommandEncoder, Extent3d, FilterMode,
     Operations, Queue, RenderPass, RenderPassColorAttachment, RenderPassDescriptor, ShaderStages,
@@ -30,6 +41,7 @@ use wgpu::{
 };
 
 use crate::{
+    camera_target::CameraSelection,
     components::{environment_data, mesh, projection_matrix},
     material_desc::MaterialData,
     mesh_desc::MeshDesc,
@@ -100,6 +112,53 @@ impl Node for MsaaResolve {
     }
 }
 
+/// Copies the contents of a texture to another texture of the same size and format
+pub struct CopyTexture {
+    source: TextureHandle,
+    destination: TextureHandle,
+}
+
+impl CopyTexture {
+    pub fn new(source: TextureHandle, destination: TextureHandle) -> Self {
+        Self {
+            source,
+            destination,
+        }
+    }
+}
+
+impl Node for CopyTexture {
+    fn label(&self) -> &str {
+        type_name::<Self>()
+    }
+
+    fn draw(&mut self, ctx: crate::rendergraph::NodeExecutionContext) -> anyhow::Result<()> {
+        let source = ctx.get_texture(self.source);
+        let destination = ctx.get_texture(self.destination);
+
+        ctx.encoder.copy_texture_to_texture(
+            source.as_image_copy(),
+            destination.as_image_copy(),
+            source.size(),
+        );
+
+        Ok(())
+    }
+
+    fn on_resource_changed(&mut self, _resource: crate::rendergraph::ResourceHandle) {}
+
+    fn read_dependencies(&self) -> Vec<Dependency> {
+        vec![Dependency::texture(self.source, TextureUsages::COPY_SRC)]
+    }
+
+    fn write_dependencies(&self) -> Vec<Dependency> {
+        vec![Dependency::texture(
+            self.destination,
+            TextureUsages::COPY_DST,
+        )]
+    }
+}
+
 pub struct RenderContext<'a> {
     pub world: &'a mut World,
     pub assets: &'a AssetCache,
@@ -141,6 +200,11 @@ pub trait CameraRenderer {
         ctx: &'s RenderContext<'s>,
         render_pass: &mut RenderPass<'s>,
     ) -> anyhow::Result<()>;
+
+    /// Returns the draws submitted since the previous call
+    fn take_stats(&mut self) -> RenderStats {
+        RenderStats::default()
+    }
 }
 
 macro_rules! impl_for_tuples {
@@ -169,6 +233,12 @@ macro_rules! impl_for_tuples {
 
                 Ok(())
             }
+
+            fn take_stats(&mut self) -> RenderStats {
+                let mut stats = RenderStats::default();
+                $(stats += self.$idx.take_stats();)*
+                stats
+            }
         }
     };
 }
@@ -198,6 +268,10 @@ impl CameraRenderer for Box<dyn CameraRenderer> {
     ) -> anyhow::Result<()> {
         (**self).draw(ctx, render_pass)
     }
+
+    fn take_stats(&mut self) -> RenderStats {
+        (**self).take_stats()
+    }
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -222,13 +296,46 @@ impl SkyboxTextures {
             integrated_brdf,
         }
     }
+
+    pub fn handles(&self) -> [TextureHandle; 4] {
+        [
+            self.environment_map,
+            self.irradiance_map,
+            self.specular_map,
+            self.integrated_brdf,
+        ]
+    }
 }
 
-#[derive(Default, Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy)]
 pub struct EnvironmentData {
     pub fog_color: Srgb,
     pub fog_density: f32,
     pub fog_blend: f32,
+    /// How wet surfaces are, from dry at 0 to soaked at 1.
+    ///
+    /// Wet surfaces are darker and glossier.
+    pub wetness: f32,
+    /// Lowest exposure value (EV100) the auto exposure adapts to, i.e; the darkest scene
+    pub min_ev: f32,
+    /// Highest exposure value (EV100) the auto exposure adapts to, i.e; the brightest scene
+    pub max_ev: f32,
+    /// Exposure compensation in stops, brightening the image for positive values
+    pub exposure_compensation: f32,
+}
+
+impl Default for EnvironmentData {
+    fn default() -> Self {
+        Self {
+            fog_color: Default::default(),
+            fog_density: 0.0,
+            fog_blend: 0.0,
+            wetness: 0.0,
+            min_ev: -4.0,
+            max_ev: 16.0,
+            exposure_compensation: 0.0,
+        }
+    }
 }
 
 impl EnvironmentData {
@@ -237,8 +344,28 @@ impl EnvironmentData {
             fog_color,
             fog_density,
             fog_blend,
+            ..Default::default()
         }
     }
+
+    /// Set the wetness
+    pub fn with_wetness(mut self, wetness: f32) -> Self {
+        self.wetness = wetness;
+        self
+    }
+
+    /// Set the range of exposure values the auto exposure adapts within
+    pub fn with_exposure_range(mut self, min_ev: f32, max_ev: f32) -> Self {
+        self.min_ev = min_ev;
+        self.max_ev = max_ev;
+        self
+    }
+
+    /// Set the exposure compensation
+    pub fn with_exposure_compensation(mut self, exposure_compensation: f32) -> Self {
+        self.exposure_compensation = exposure_compensation;
+        self
+    }
 }
 
 pub fn get_main_camera_data(world: &World) -> Option<CameraData> {
@@ -264,6 +391,8 @@ pub fn get_camera_data(camera: &EntityRef) -> CameraData {
         fog_color: to_linear_vec3(env_data.fog_color),
         fog_density: env_data.fog_density,
         fog_blend: env_data.fog_blend,
+        wetness: env_data.wetness,
+        _padding: Default::default(),
     }
 }
 
@@ -278,13 +407,19 @@ pub struct CameraNode {
     /// 2: irradiance map
     /// 3: specular map
     /// 4: integrated brdf
+    /// 5: environment sampler
+    /// 6..=8: light probe volume
+    /// 9: light probe volume data
     pub bind_group: Option<BindGroup>,
     light_manager: LightManager,
     skybox: Option<SkyboxTextures>,
+    light_probes: Option<LightProbeTextures>,
     object_manager: Handle<ObjectManager>,
+    camera: CameraSelection,
 }
 
 impl CameraNode {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         gpu: &Gpu,
         depth_texture: TextureHandle,
@@ -293,19 +428,28 @@ impl CameraNode {
         light_manager: LightManager,
         object_manager: Handle<ObjectManager>,
         skybox: Option<SkyboxTextures>,
+        light_probes: Option<LightProbeTextures>,
     ) -> Self {
         Self {
             light_manager,
             object_manager,
             renderer: Box::new(renderer),
-            shader_data: CameraShaderData::new(gpu),
+            shader_data: CameraShaderData::new(gpu, light_probes.as_ref().map(|v| &v.volume)),
             depth_texture,
             store: Default::default(),
             output,
             skybox,
+            light_probes,
             bind_group: None,
+            camera: CameraSelection::Main,
         }
     }
+
+    /// Render from another camera than the main camera
+    pub fn with_camera(mut self, camera: impl Into<CameraSelection>) -> Self {
+        self.camera = camera.into();
+        self
+    }
 }
 
 impl Node for CameraNode {
@@ -318,10 +462,10 @@ impl Node for CameraNode {
 
         let depth = ctx.get_texture(self.depth_texture);
 
-        if let Some(camera) = Query::new(entity_refs())
-            .with(main_camera())
-            .borrow(ctx.world)
-            .first()
+        if let Some(camera) = self
+            .camera
+            .find(ctx.world)
+            .and_then(|id| ctx.world.entity(id).ok())
         {
             self.shader_data.data = get_camera_data(&camera);
 
@@ -333,7 +477,7 @@ impl Node for CameraNode {
         self.light_manager.update(&ctx)?;
         let object_manager = ctx.store.get_mut(&self.object_manager);
 
-        object_manager.update(ctx.world, ctx.gpu)?;
+        object_manager.update(ctx.world, ctx.assets, ctx.gpu)?;
 
         self.renderer.update(&mut UpdateContext {
             world: ctx.world,
@@ -358,82 +502,11 @@ impl Node for CameraNode {
         let depth_view = depth.create_view(&Default::default());
 
         let bind_group = self.bind_group.get_or_insert_with(|| {
-            let cubemap_view = TextureViewDescriptor {
-                dimension: Some(TextureViewDimension::Cube),
-                array_layer_count: Some(6),
-                ..Default::default()
-            };
-
-            let environment_sampler = ctx.gpu.device.create_sampler(&wgpu::SamplerDescriptor {
-                label: Some("environment_sampler"),
-                address_mode_u: AddressMode::ClampToEdge,
-                address_mode_v: AddressMode::ClampToEdge,
-                address_mode_w: AddressMode::ClampToEdge,
-                mag_filter: FilterMode::Linear,
-                min_filter: FilterMode::Linear,
-                mipmap_filter: FilterMode::Linear,
-                anisotropy_clamp: 16,
-                ..Default::default()
-            });
-
-            let (environment_map, irradiance_map, specular_map, integrated_brdf) =
-                match &self.skybox {
-                    Some(v) => (
-                        ctx.get_texture(v.environment_map)
-                            .create_view(&cubemap_view),
-                        ctx.get_texture(v.irradiance_map).create_view(&cubemap_view),
-                        ctx.get_texture(v.specular_map).create_view(&cubemap_view),
-                        ctx.get_texture(v.integrated_brdf)
-                            .create_view(&Default::default()),
-                    ),
-                    None => {
-                        let default_texture = ctx.gpu.device.create_texture(&TextureDescriptor {
-                            label: Some("default_skybox"),
-                            size: Extent3d {
-                                width: 64,
-                                height: 64,
-                                depth_or_array_layers: 6,
-                            },
-                            mip_level_count: 1,
-                            sample_count: 1,
-                            dimension: wgpu::TextureDimension::D2,
-                            format: wgpu::TextureFormat::R16Float,
-                            usage: TextureUsages::TEXTURE_BINDING,
-                            view_formats: &[],
-                        });
-
-                        let default_brdf = ctx.gpu.device.create_texture(&TextureDescriptor {
-                            label: Some("default_integrated_brdf"),
-                            size: Extent3d {
-                                width: 64,
-                                height: 64,
-                                depth_or_array_layers: 1,
-                            },
-                            mip_level_count: 1,
-                            sample_count: 1,
-                            dimension: wgpu::TextureDimension::D2,
-                            format: wgpu::TextureFormat::R16Float,
-                            usage: TextureUsages::TEXTURE_BINDING,
-                            view_formats: &[],
-                        });
-
-                        (
-                            default_texture.create_view(&cubemap_view),
-                            default_texture.create_view(&cubemap_view),
-                            default_texture.create_view(&cubemap_view),
-                            default_brdf.create_view(&Default::default()),
-                        )
-                    }
-                };
-
-            BindGroupBuilder::new("Globals")
-                .bind_buffer(&self.shader_data.buffer)
-                .bind_texture(&environment_map)
-                .bind_texture(&irradiance_map)
-                .bind_texture(&specular_map)
-                .bind_texture(&integrated_brdf)
-                .bind_sampler(&environment_sampler)
-                .build(ctx.gpu, &self.shader_data.layout)
+            self.shader_data.create_bind_group(
+                &ctx,
+                self.skybox.as_ref(),
+                self.light_probes.as_ref(),
+            )
         });
 
         let output = ctx.get_texture(self.output);
@@ -489,6 +562,9 @@ impl Node for CameraNode {
         });
 
         self.renderer.draw(&render_context, &mut render_pass)?;
+        drop(render_pass);
+
+        RenderStats::collect(ctx.world, &mut self.renderer);
 
         Ok(())
     }
@@ -507,17 +583,14 @@ impl Node for CameraNode {
         .into_iter()
         .chain(
             self.skybox
-                .as_ref()
-                .map(|v| {
-                    [
-                        Dependency::texture(v.environment_map, TextureUsages::TEXTURE_BINDING),
-                        Dependency::texture(v.irradiance_map, TextureUsages::TEXTURE_BINDING),
-                        Dependency::texture(v.specular_map, TextureUsages::TEXTURE_BINDING),
-                        Dependency::texture(v.integrated_brdf, TextureUsages::TEXTURE_BINDING),
-                    ]
-                })
-                .into_iter()
-                .flatten(),
+                .iter()
+                .flat_map(|v| v.handles())
+                .chain(
+                    self.light_probes
+                        .iter()
+                        .flat_map(|v| [v.sh_r, v.sh_g, v.sh_b]),
+                )
+                .map(|v| Dependency::texture(v, TextureUsages::TEXTURE_BINDING)),
         )
         .collect_vec()
     }
@@ -544,16 +617,19 @@ pub struct CameraData {
     pub fog_blend: f32,
     pub fog_color: Vec3,
     pub fog_density: f32,
+    pub wetness: f32,
+    pub _padding: [f32; 3],
 }
 
 pub struct CameraShaderData {
     pub data: CameraData,
     buffer: TypedBuffer<CameraData>,
+    light_probe_buffer: TypedBuffer<LightProbeVolumeData>,
     pub layout: BindGroupLayout,
 }
 
 impl CameraShaderData {
-    fn new(gpu: &Gpu) -> CameraShaderData {
+    fn new(gpu: &Gpu, light_probes: Option<&LightProbeVolume>) -> CameraShaderData {
         let layout = BindGroupLayoutBuilder::new("Globals")
             .bind_uniform_buffer(ShaderStages::VERTEX | ShaderStages::FRAGMENT)
             .bind_texture_cube(ShaderStages::FRAGMENT)
@@ -561,6 +637,10 @@ impl CameraShaderData {
             .bind_texture_cube(ShaderStages::FRAGMENT)
             .bind_texture(ShaderStages::FRAGMENT)
             .bind_sampler(ShaderStages::FRAGMENT)
+            .bind_texture_3d(ShaderStages::FRAGMENT)
+            .bind_texture_3d(ShaderStages::FRAGMENT)
+            .bind_texture_3d(ShaderStages::FRAGMENT)
+            .bind_uniform_buffer(ShaderStages::FRAGMENT)
             .build(gpu);
 
         let buffer = TypedBuffer::new(
@@ -570,12 +650,133 @@ impl CameraShaderData {
             &[Default::default()],
         );
 
+        // Disabled unless a volume is given
+        let light_probe_buffer = TypedBuffer::new(
+            gpu,
+            "Globals light probes",
+            BufferUsages::UNIFORM,
+            &[light_probes.map(|v| v.shader_data()).unwrap_or_default()],
+        );
+
         Self {
             buffer,
+            light_probe_buffer,
             layout,
             data: Default::default(),
         }
     }
+
+    /// Creates the bind group for [`Self::layout`], substituting empty textures for a missing
+    /// skybox or light probe volume
+    fn create_bind_group(
+        &self,
+        ctx: &crate::rendergraph::NodeExecutionContext,
+        skybox: Option<&SkyboxTextures>,
+        light_probes: Option<&LightProbeTextures>,
+    ) -> BindGroup {
+        let cubemap_view = TextureViewDescriptor {
+            dimension: Some(TextureViewDimension::Cube),
+            array_layer_count: Some(6),
+            ..Default::default()
+        };
+
+        let environment_sampler = ctx.gpu.device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("environment_sampler"),
+            address_mode_u: AddressMode::ClampToEdge,
+            address_mode_v: AddressMode::ClampToEdge,
+            address_mode_w: AddressMode::ClampToEdge,
+            mag_filter: FilterMode::Linear,
+            min_filter: FilterMode::Linear,
+            mipmap_filter: FilterMode::Linear,
+            anisotropy_clamp: 16,
+            ..Default::default()
+        });
+
+        let (environment_map, irradiance_map, specular_map, integrated_brdf) = match skybox {
+            Some(v) => (
+                ctx.get_texture(v.environment_map)
+                    .create_view(&cubemap_view),
+                ctx.get_texture(v.irradiance_map).create_view(&cubemap_view),
+                ctx.get_texture(v.specular_map).create_view(&cubemap_view),
+                ctx.get_texture(v.integrated_brdf)
+                    .create_view(&Default::default()),
+            ),
+            None => {
+                let default_texture = ctx.gpu.device.create_texture(&TextureDescriptor {
+                    label: Some("default_skybox"),
+                    size: Extent3d {
+                        width: 64,
+                        height: 64,
+                        depth_or_array_layers: 6,
+                    },
+                    mip_level_count: 1,
+                    sample_count: 1,
+                    dimension: wgpu::TextureDimension::D2,
+                    format: wgpu::TextureFormat::R16Float,
+                    usage: TextureUsages::TEXTURE_BINDING,
+                    view_formats: &[],
+                });
+
+                let default_brdf = ctx.gpu.device.create_texture(&TextureDescriptor {
+                    label: Some("default_integrated_brdf"),
+                    size: Extent3d {
+                        width: 64,
+                        height: 64,
+                        depth_or_array_layers: 1,
+                    },
+                    mip_level_count: 1,
+                    sample_count: 1,
+                    dimension: wgpu::TextureDimension::D2,
+                    format: wgpu::TextureFormat::R16Float,
+                    usage: TextureUsages::TEXTURE_BINDING,
+                    view_formats: &[],
+                });
+
+                (
+                    default_texture.create_view(&cubemap_view),
+                    default_texture.create_view(&cubemap_view),
+                    default_texture.create_view(&cubemap_view),
+                    default_brdf.create_view(&Default::default()),
+                )
+            }
+        };
+
+        let [sh_r, sh_g, sh_b] = match light_probes {
+            Some(v) => [v.sh_r, v.sh_g, v.sh_b]
+                .map(|v| ctx.get_texture(v).create_view(&Default::default())),
+            None => {
+                let default_volume = ctx.gpu.device.create_texture(&TextureDescriptor {
+                    label: Some("default_light_probes"),
+                    size: Extent3d {
+                        width: 1,
+                        height: 1,
+                        depth_or_array_layers: 1,
+                    },
+                    mip_level_count: 1,
+                    sample_count: 1,
+                    dimension: wgpu::TextureDimension::D3,
+                    format: light_probes::LIGHT_PROBE_FORMAT,
+                    usage: TextureUsages::TEXTURE_BINDING,
+                    view_formats: &[],
+                });
+
+                [(); 3].map(|_| default_volume.create_view(&Default::default()))
+            }
+        };
+
+        BindGroupBuilder::new("Globals")
+            .bind_buffer(&self.buffer)
+            .bind_texture(&environment_map)
+            .bind_texture(&irradiance_map)
+            .bind_texture(&specular_map)
+            .bind_texture(&integrated_brdf)
+            .bind_sampler(&environment_sampler)
+            .bind_texture(&sh_r)
+            .bind_texture(&sh_g)
+            .bind_texture(&sh_b)
+            .bind_buffer(&self.light_probe_buffer)
+            .build(ctx.gpu, &self.layout)
+    }
 }
 
 pub struct RenderObjectBundle<'a> {
@@ -9,9 +9,11 @@ use flax::{
     Component, Entity, Fetch, FetchExt, Query, World,
 };
 use glam::{Mat4, Vec3};
-use ivy_assets::Asset;
+use ivy_assets::{Asset, AssetCache};
 use ivy_core::{
+    bounds::{aabb, bounding_sphere, BoundingSphere},
     components::{color, world_transform},
+    notifications::{notify, Notification},
     palette::WithAlpha,
     profiling::{profile_function, profile_scope},
     subscribers::RemovedComponentSubscriber,
@@ -27,7 +29,8 @@ use ivy_wgpu_types::{
 };
 use wgpu::BufferUsages;
 
-use crate::components::mesh;
+use super::skinning::{MeshSkinning, SkinBounds};
+use crate::{components::mesh, mesh::SkinnedVertex, mesh_desc::MeshDesc};
 
 #[derive(Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
 #[repr(C)]
@@ -67,7 +70,9 @@ type UpdateFetch = (Component<usize>, Source<ObjectDataQuery, Traverse>);
 
 type SkinUpdateFetch = (
     Component<usize>,
+    Component<MeshDesc>,
     Component<SubBuffer<Mat4>>,
+    Component<SubBuffer<SkinnedVertex>>,
     Source<
         (
             Component<Asset<Skin>>,
@@ -76,6 +81,14 @@ type SkinUpdateFetch = (
         Traverse,
     >,
 );
+/// Buffers owned by a skinned object, released when it is removed
+struct SkinAllocation {
+    mesh: MeshDesc,
+    joints: SubBuffer<Mat4>,
+    vertices: SubBuffer<SkinnedVertex>,
+    bounds: SkinBounds,
+}
+
 pub struct ObjectManager {
     object_data: Vec<RenderObjectData>,
     object_map: Vec<Entity>,
@@ -85,6 +98,8 @@ pub struct ObjectManager {
 
     skinning_buffer: MultiBuffer<Mat4>,
     skinning_data: Vec<Mat4>,
+    skin_allocations: BTreeMap<Entity, SkinAllocation>,
+    mesh_skinning: MeshSkinning,
 
     removed_rx: flume::Receiver<(flax::Entity, usize)>,
     object_query: Query<UpdateFetch, (All, With)>,
@@ -125,12 +140,16 @@ impl ObjectManager {
             .with(mesh()),
             skin_query: Query::new((
                 object_buffer_index(),
+                mesh(),
                 object_skinning_buffer(),
+                object_skinned_vertices(),
                 (skin(), animator().modified()).traverse(child_of),
             ))
             .with(mesh()),
             skinning_data: vec![Mat4::IDENTITY; skinning_buffer.len()],
             skinning_buffer,
+            skin_allocations: BTreeMap::new(),
+            mesh_skinning: MeshSkinning::new(gpu),
             entity_locations: BTreeMap::new(),
         }
     }
@@ -144,10 +163,11 @@ impl ObjectManager {
             .resize(gpu, capacity.next_power_of_two(), false);
     }
 
-    pub fn collect_unbatched(&mut self, world: &mut World, gpu: &Gpu) {
+    pub fn collect_unbatched(&mut self, world: &mut World, assets: &AssetCache, gpu: &Gpu) {
         profile_function!();
         let mut query = Query::new((
             entity_refs(),
+            mesh(),
             (world_transform(), skin().opt()).traverse(child_of),
         ))
         .with(mesh())
@@ -155,12 +175,24 @@ impl ObjectManager {
 
         let mut new_components = Vec::new();
         let mut new_skin_components = Vec::new();
+        let mut new_skinned_vertices = Vec::new();
 
-        for (entity, (&transform, skin)) in &mut query.borrow(world) {
+        for (entity, mesh, (&transform, skin)) in &mut query.borrow(world) {
             let id = entity.id();
 
-            let skin_buffer_offset = match skin {
-                Some(skin) => {
+            let skinned_vertices = skin.and_then(|_| {
+                self.mesh_skinning
+                    .allocate(gpu, assets, mesh)
+                    .map_err(|e| {
+                        let e = e.context("Failed to skin mesh");
+                        tracing::error!(?mesh, "{e:?}");
+                        notify(assets, Notification::error(format!("{e:#}")));
+                    })
+                    .ok()
+            });
+
+            let skin_buffer_offset = match skin.zip(skinned_vertices) {
+                Some((skin, vertices)) => {
                     let joints = skin.joints().len();
                     let subbuffer = if let Some(handle) = self.skinning_buffer.allocate(joints) {
                         handle
@@ -173,6 +205,17 @@ impl ObjectManager {
                     };
 
                     new_skin_components.push((id, subbuffer));
+                    new_skinned_vertices.push((id, vertices));
+                    self.skin_allocations.insert(
+                        id,
+                        SkinAllocation {
+                            mesh: mesh.clone(),
+                            joints: subbuffer,
+                            vertices,
+                            bounds: SkinBounds::BIND_POSE,
+                        },
+                    );
+
                     Some(subbuffer.offset() as u32)
                 }
                 None => None,
@@ -199,6 +242,10 @@ impl ObjectManager {
             .append_all(object_skinning_buffer(), new_skin_components)
             .unwrap();
 
+        world
+            .append_all(object_skinned_vertices(), new_skinned_vertices)
+            .unwrap();
+
         if self.object_data.len() > self.object_buffer.len() {
             self.resize_object_buffer(gpu, self.object_data.len());
         }
@@ -210,6 +257,12 @@ impl ObjectManager {
     pub fn process_removed(&mut self, world: &World) {
         profile_function!();
         for (id, _) in self.removed_rx.try_iter() {
+            if let Some(allocation) = self.skin_allocations.remove(&id) {
+                self.skinning_buffer.deallocate(allocation.joints);
+                self.mesh_skinning
+                    .deallocate(&allocation.mesh, allocation.vertices);
+            }
+
             let loc = self.entity_locations.remove(&id).unwrap();
             if loc == self.object_data.len() - 1 {
                 self.object_map.pop();
@@ -247,7 +300,9 @@ impl ObjectManager {
 
     fn update_skin_data(&mut self, world: &World, gpu: &Gpu) {
         profile_function!();
-        for (&loc, skin_buffer, (skin, animator)) in &mut self.skin_query.borrow(world) {
+        for (&loc, mesh, skin_buffer, &vertices, (skin, animator)) in
+            &mut self.skin_query.borrow(world)
+        {
             assert_ne!(loc, usize::MAX);
             let object_data = &mut self.object_data[loc];
 
@@ -255,16 +310,50 @@ impl ObjectManager {
                 ..object_data.joint_offset as usize + skin.joints().len()];
             animator.fill_buffer(skin, data);
 
+            if let Some(allocation) = self.skin_allocations.get_mut(&self.object_map[loc]) {
+                allocation.bounds = SkinBounds::from_joints(data);
+            }
+
             self.skinning_buffer.write(&gpu.queue, skin_buffer, data);
+            self.mesh_skinning
+                .queue(mesh, vertices, object_data.joint_offset);
         }
+
+        self.mesh_skinning.run(gpu, &self.skinning_buffer);
     }
 
-    pub fn update(&mut self, world: &mut World, gpu: &Gpu) -> anyhow::Result<()> {
+    /// Grows the bounding sphere of skinned objects to contain their current pose
+    fn update_skinned_bounds(&self, world: &World) {
+        profile_function!();
+        for (&id, allocation) in &self.skin_allocations {
+            let Ok(entity) = world.entity(id) else {
+                continue;
+            };
+
+            let Ok(bind_pose) = entity.get_copy(aabb()) else {
+                continue;
+            };
+
+            let radius = allocation
+                .bounds
+                .radius(bind_pose.bounding_sphere().origin_radius());
+
+            entity.update_dedup(bounding_sphere(), BoundingSphere::new(Vec3::ZERO, radius));
+        }
+    }
+
+    pub fn update(
+        &mut self,
+        world: &mut World,
+        assets: &AssetCache,
+        gpu: &Gpu,
+    ) -> anyhow::Result<()> {
         profile_function!();
         self.process_removed(world);
-        self.collect_unbatched(world, gpu);
+        self.collect_unbatched(world, assets, gpu);
         self.update_object_data(world, gpu);
         self.update_skin_data(world, gpu);
+        self.update_skinned_bounds(world);
 
         Ok(())
     }
@@ -277,6 +366,16 @@ impl ObjectManager {
         &self.object_data
     }
 
+    /// Returns the entity stored at `index` of the object buffer
+    pub fn entity(&self, index: usize) -> Option<Entity> {
+        self.object_map.get(index).copied()
+    }
+
+    /// Returns the entity of each object in the object buffer
+    pub fn entities(&self) -> &[Entity] {
+        &self.object_map
+    }
+
     pub fn skinning_buffer(&self) -> &MultiBuffer<Mat4> {
         &self.skinning_buffer
     }
@@ -284,9 +383,15 @@ impl ObjectManager {
     pub fn skinning_data(&self) -> &[Mat4] {
         &self.skinning_data
     }
+
+    /// Animated vertices of skinned objects, laid out like the mesh vertices
+    pub fn skinned_vertices(&self) -> &MultiBuffer<SkinnedVertex> {
+        self.mesh_skinning.output_vertices()
+    }
 }
 
 component! {
     pub(crate) object_buffer_index: usize,
     pub(crate) object_skinning_buffer: SubBuffer<Mat4>,
+    pub(crate) object_skinned_vertices: SubBuffer<SkinnedVertex>,
 }
@@ -0,0 +1,512 @@
+use std::sync::Arc;
+
+use flax::{Entity, World};
+use glam::{Mat4, UVec2, Vec2};
+use ivy_assets::{stored::Handle, AssetCache};
+use ivy_core::profiling::profile_function;
+use ivy_wgpu_types::shader::TargetDesc;
+use wgpu::{
+    BindGroup, BindGroupLayout, Buffer, BufferDescriptor, BufferUsages, Extent3d, ImageCopyBuffer,
+    ImageCopyTexture, ImageDataLayout, Maintain, MapMode, Operations, Origin3d,
+    RenderPassColorAttachment, ShaderStages, TextureFormat, TextureUsages,
+};
+
+use super::{
+    get_camera_data, mesh_renderer::MeshRenderer, render_stats::RenderStats, CameraData,
+    CameraRenderer, ObjectManager, RenderContext, RendererStore, UpdateContext,
+};
+use crate::{
+    camera_target::CameraSelection,
+    components::{forward_pass, transparent_pass},
+    material::RenderMaterial,
+    rendergraph::{
+        Dependency, Node, NodeExecutionContext, NodeUpdateContext, ResourceHandle, TextureHandle,
+        UpdateResult,
+    },
+    shader_library::ShaderLibrary,
+    shaders::ObjectIdShaderDesc,
+    types::{BindGroupBuilder, BindGroupLayoutBuilder, TypedBuffer},
+    Gpu,
+};
+
+/// Format of the id buffer written by the [`PickingNode`]
+pub const OBJECT_ID_FORMAT: TextureFormat = TextureFormat::R32Uint;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum PickPosition {
+    Physical(UVec2),
+    /// From `0..1`, relative to the size of the id buffer
+    Normalized(Vec2),
+}
+
+impl PickPosition {
+    /// Returns the pixel of the id buffer, or `None` if outside of it
+    fn resolve(&self, size: UVec2) -> Option<UVec2> {
+        let pixel = match *self {
+            PickPosition::Physical(v) => v,
+            PickPosition::Normalized(v) => {
+                if v.cmplt(Vec2::ZERO).any() {
+                    return None;
+                }
+
+                (v * size.as_vec2()).as_uvec2()
+            }
+        };
+
+        pixel.cmplt(size).all().then_some(pixel)
+    }
+}
+
+struct PickRequest {
+    position: PickPosition,
+    tx: flume::Sender<Option<Entity>>,
+}
+
+/// Returns the entity of an id read back from the id buffer, where `0` is no object
+fn picked_entity(entities: &[Entity], id: u32) -> Option<Entity> {
+    let index = id.checked_sub(1)?;
+    entities.get(index as usize).copied()
+}
+
+/// Requests the entity visible at a position of the screen.
+///
+/// Requests are answered by the [`PickingNode`] of the render graph, and remain pending
+/// if there is none.
+#[derive(Clone)]
+pub struct Picker {
+    tx: flume::Sender<PickRequest>,
+    rx: flume::Receiver<PickRequest>,
+}
+
+impl Picker {
+    pub fn new() -> Self {
+        let (tx, rx) = flume::unbounded();
+        Self { tx, rx }
+    }
+
+    /// Picks the entity drawn at `screen_pos`, in physical pixels from the top left corner of the
+    /// window
+    pub fn pick(&self, screen_pos: UVec2) -> PendingPick {
+        self.request(PickPosition::Physical(screen_pos))
+    }
+
+    /// Picks the entity drawn at a normalized position from `0..1` of the screen, such as the
+    /// normalized cursor position
+    pub fn pick_normalized(&self, screen_pos: Vec2) -> PendingPick {
+        self.request(PickPosition::Normalized(screen_pos))
+    }
+
+    fn request(&self, position: PickPosition) -> PendingPick {
+        let (tx, rx) = flume::bounded(1);
+        self.tx.send(PickRequest { position, tx }).ok();
+
+        PendingPick { rx }
+    }
+}
+
+impl Default for Picker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Result of a [`Picker::pick`], available once the id buffer has been read back from the gpu.
+///
+/// This takes one or more frames.
+pub struct PendingPick {
+    rx: flume::Receiver<Option<Entity>>,
+}
+
+impl PendingPick {
+    /// Returns the picked entity, if any, or `None` while the pick is still pending
+    pub fn try_get(&self) -> Option<Option<Entity>> {
+        self.rx.try_recv().ok()
+    }
+
+    /// Waits for the picked entity
+    pub async fn get(self) -> Option<Entity> {
+        self.rx.recv_async().await.ok().flatten()
+    }
+}
+
+struct PendingReadback {
+    buffer: Buffer,
+    requests: Vec<QueuedPick>,
+    /// The entities of the object buffer when the id buffer was drawn, as objects may have moved
+    /// since
+    entities: Vec<Entity>,
+    /// Taken once the copy has been submitted and the buffer is being mapped
+    mapped_tx: Option<flume::Sender<bool>>,
+    mapped_rx: flume::Receiver<bool>,
+}
+
+struct QueuedPick {
+    pixel: UVec2,
+    tx: flume::Sender<Option<Entity>>,
+}
+
+/// Renders the object index of each pixel into an id buffer and reads back the pixels requested
+/// through a [`Picker`].
+///
+/// The id buffer is only drawn on frames with pending requests.
+pub struct PickingNode {
+    renderer: MeshRenderer,
+    store: RendererStore,
+    object_manager: Handle<ObjectManager>,
+    camera: CameraSelection,
+    camera_data: CameraData,
+
+    output: TextureHandle,
+    depth_texture: TextureHandle,
+
+    globals: TypedBuffer<Mat4>,
+    layout: BindGroupLayout,
+    bind_group: Option<BindGroup>,
+
+    requests: flume::Receiver<PickRequest>,
+    queued: Vec<QueuedPick>,
+    pending: Vec<PendingReadback>,
+}
+
+impl PickingNode {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        world: &mut World,
+        assets: &AssetCache,
+        gpu: &Gpu,
+        shader_library: Arc<ShaderLibrary>,
+        object_manager: Handle<ObjectManager>,
+        picker: &Picker,
+        output: TextureHandle,
+        depth_texture: TextureHandle,
+    ) -> Self {
+        let material = assets.insert(RenderMaterial::unbound(
+            "object_id",
+            assets.load(&ObjectIdShaderDesc),
+        ));
+
+        let renderer = MeshRenderer::new(world, assets, gpu, forward_pass(), shader_library)
+            .with_pass(transparent_pass())
+            .with_material_override(material)
+            .with_bindless(false);
+
+        let layout = BindGroupLayoutBuilder::new("ObjectId")
+            .bind_uniform_buffer(ShaderStages::VERTEX)
+            .build(gpu);
+
+        Self {
+            renderer,
+            store: Default::default(),
+            object_manager,
+            camera: CameraSelection::Main,
+            camera_data: Default::default(),
+            output,
+            depth_texture,
+            globals: TypedBuffer::new(
+                gpu,
+                "object_id_globals",
+                BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+                &[Mat4::IDENTITY],
+            ),
+            layout,
+            bind_group: None,
+            requests: picker.rx.clone(),
+            queued: Vec::new(),
+            pending: Vec::new(),
+        }
+    }
+
+    /// Pick from another camera than the main camera
+    pub fn with_camera(mut self, camera: impl Into<CameraSelection>) -> Self {
+        self.camera = camera.into();
+        self
+    }
+
+    fn receive_mapped(&mut self) {
+        self.pending.retain(|pending| {
+            let Ok(mapped) = pending.mapped_rx.try_recv() else {
+                return true;
+            };
+
+            if !mapped {
+                for request in &pending.requests {
+                    request.tx.send(None).ok();
+                }
+
+                return false;
+            }
+
+            {
+                let mapped = pending.buffer.slice(..).get_mapped_range();
+                let ids: &[u32] = bytemuck::cast_slice(&mapped);
+
+                for (request, &id) in pending.requests.iter().zip(ids) {
+                    request.tx.send(picked_entity(&pending.entities, id)).ok();
+                }
+            }
+
+            pending.buffer.unmap();
+            false
+        });
+    }
+}
+
+impl Node for PickingNode {
+    fn label(&self) -> &str {
+        "PickingNode"
+    }
+
+    fn update(&mut self, ctx: NodeUpdateContext) -> anyhow::Result<UpdateResult> {
+        profile_function!();
+
+        // All recorded copies have been submitted by now
+        for pending in &mut self.pending {
+            if let Some(mapped_tx) = pending.mapped_tx.take() {
+                pending
+                    .buffer
+                    .slice(..)
+                    .map_async(MapMode::Read, move |result| {
+                        if let Err(err) = &result {
+                            tracing::error!("Failed to map picking buffer: {err}");
+                        }
+
+                        mapped_tx.send(result.is_ok()).ok();
+                    });
+            }
+        }
+
+        ctx.gpu.device.poll(Maintain::Poll);
+
+        self.receive_mapped();
+
+        let size = ctx.get_texture(self.output).size();
+        let size = UVec2::new(size.width, size.height);
+        for request in self.requests.try_iter() {
+            match request.position.resolve(size) {
+                Some(pixel) => self.queued.push(QueuedPick {
+                    pixel,
+                    tx: request.tx,
+                }),
+                None => {
+                    request.tx.send(None).ok();
+                }
+            }
+        }
+
+        if let Some(camera) = self
+            .camera
+            .find(ctx.world)
+            .and_then(|id| ctx.world.entity(id).ok())
+        {
+            self.camera_data = get_camera_data(&camera);
+            self.globals
+                .write(&ctx.gpu.queue, 0, &[self.camera_data.viewproj]);
+        }
+
+        let output = ctx.get_texture(self.output);
+        let depth = ctx.get_texture(self.depth_texture);
+
+        self.renderer.update(&mut UpdateContext {
+            world: ctx.world,
+            assets: ctx.assets,
+            gpu: ctx.gpu,
+            store: &mut self.store,
+            object_manager: ctx.store.get(&self.object_manager),
+            layouts: &[&self.layout],
+            target_desc: TargetDesc {
+                formats: &[output.format()],
+                depth_format: depth.format().into(),
+                sample_count: output.sample_count(),
+            },
+        })?;
+
+        Ok(UpdateResult::Success)
+    }
+
+    fn draw(&mut self, ctx: NodeExecutionContext) -> anyhow::Result<()> {
+        if self.queued.is_empty() {
+            return Ok(());
+        }
+
+        let output = ctx.get_texture(self.output);
+        let depth = ctx.get_texture(self.depth_texture);
+        let output_view = output.create_view(&Default::default());
+        let depth_view = depth.create_view(&Default::default());
+
+        let bind_group = self.bind_group.get_or_insert_with(|| {
+            BindGroupBuilder::new("ObjectId")
+                .bind_buffer(&self.globals)
+                .build(ctx.gpu, &self.layout)
+        });
+
+        let render_context = RenderContext {
+            world: ctx.world,
+            assets: ctx.assets,
+            gpu: ctx.gpu,
+            queue: ctx.queue,
+            store: &self.store,
+            object_manager: ctx.store.get(&self.object_manager),
+            layouts: &[&self.layout],
+            bind_groups: &[bind_group],
+            target_desc: TargetDesc {
+                formats: &[output.format()],
+                depth_format: depth.format().into(),
+                sample_count: output.sample_count(),
+            },
+            camera: self.camera_data,
+        };
+
+        self.renderer.before_draw(&render_context, ctx.encoder)?;
+
+        {
+            let mut render_pass = ctx.encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: "object_id".into(),
+                color_attachments: &[Some(RenderPassColorAttachment {
+                    view: &output_view,
+                    resolve_target: None,
+                    ops: Operations {
+                        // 0 is no object
+                        load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                    view: &depth_view,
+                    depth_ops: Some(Operations {
+                        load: wgpu::LoadOp::Clear(1.0),
+                        store: wgpu::StoreOp::Discard,
+                    }),
+                    stencil_ops: None,
+                }),
+                timestamp_writes: None,
+                occlusion_query_set: None,
+            });
+
+            self.renderer.draw(&render_context, &mut render_pass)?;
+        }
+
+        RenderStats::collect(ctx.world, &mut self.renderer);
+
+        let requests = std::mem::take(&mut self.queued);
+        let buffer = ctx.gpu.device.create_buffer(&BufferDescriptor {
+            label: Some("picking_readback"),
+            size: (requests.len() * size_of::<u32>()) as u64,
+            usage: BufferUsages::COPY_DST | BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+
+        for (i, request) in requests.iter().enumerate() {
+            ctx.encoder.copy_texture_to_buffer(
+                ImageCopyTexture {
+                    texture: output,
+                    mip_level: 0,
+                    origin: Origin3d {
+                        x: request.pixel.x,
+                        y: request.pixel.y,
+                        z: 0,
+                    },
+                    aspect: wgpu::TextureAspect::All,
+                },
+                ImageCopyBuffer {
+                    buffer: &buffer,
+                    layout: ImageDataLayout {
+                        offset: (i * size_of::<u32>()) as u64,
+                        bytes_per_row: None,
+                        rows_per_image: None,
+                    },
+                },
+                Extent3d {
+                    width: 1,
+                    height: 1,
+                    depth_or_array_layers: 1,
+                },
+            );
+        }
+
+        let entities = ctx.store.get(&self.object_manager).entities().to_vec();
+
+        let (mapped_tx, mapped_rx) = flume::bounded(1);
+        self.pending.push(PendingReadback {
+            buffer,
+            requests,
+            entities,
+            mapped_tx: Some(mapped_tx),
+            mapped_rx,
+        });
+
+        Ok(())
+    }
+
+    fn on_resource_changed(&mut self, _resource: ResourceHandle) {}
+
+    fn read_dependencies(&self) -> Vec<Dependency> {
+        vec![]
+    }
+
+    fn write_dependencies(&self) -> Vec<Dependency> {
+        vec![
+            Dependency::texture(
+                self.output,
+                TextureUsages::RENDER_ATTACHMENT | TextureUsages::COPY_SRC,
+            ),
+            Dependency::texture(self.depth_texture, TextureUsages::RENDER_ATTACHMENT),
+        ]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::{uvec2, vec2};
+
+    use super::*;
+
+    #[test]
+    fn resolve_position() {
+        let size = uvec2(100, 50);
+
+        assert_eq!(
+            PickPosition::Physical(uvec2(10, 20)).resolve(size),
+            Some(uvec2(10, 20))
+        );
+        assert_eq!(PickPosition::Physical(uvec2(100, 20)).resolve(size), None);
+        assert_eq!(
+            PickPosition::Normalized(vec2(0.5, 0.5)).resolve(size),
+            Some(uvec2(50, 25))
+        );
+        assert_eq!(PickPosition::Normalized(vec2(1.0, 0.5)).resolve(size), None);
+        assert_eq!(
+            PickPosition::Normalized(vec2(-0.1, 0.5)).resolve(size),
+            None
+        );
+    }
+
+    #[test]
+    fn picked_entities() {
+        let mut world = World::new();
+        let a = world.spawn();
+        let b = world.spawn();
+
+        let entities = vec![a, b];
+
+        assert_eq!(picked_entity(&entities, 0), None);
+        assert_eq!(picked_entity(&entities, 1), Some(a));
+        assert_eq!(picked_entity(&entities, 2), Some(b));
+        assert_eq!(picked_entity(&entities, 3), None);
+    }
+
+    #[test]
+    fn pending_pick() {
+        let picker = Picker::new();
+        let pending = picker.pick_normalized(vec2(0.25, 0.75));
+        assert_eq!(pending.try_get(), None);
+
+        let request = picker.rx.try_recv().unwrap();
+        assert_eq!(request.position, PickPosition::Normalized(vec2(0.25, 0.75)));
+
+        let mut world = World::new();
+        let id = world.spawn();
+        request.tx.send(Some(id)).unwrap();
+
+        assert_eq!(pending.try_get(), Some(Some(id)));
+    }
+}
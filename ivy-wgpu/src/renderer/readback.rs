@@ -0,0 +1,241 @@
+use std::sync::Arc;
+
+use ivy_core::profiling::profile_function;
+use wgpu::{
+    Buffer, BufferDescriptor, BufferUsages, Extent3d, ImageCopyBuffer, ImageCopyTexture,
+    ImageDataLayout, Maintain, MapMode, TextureFormat, TextureUsages, COPY_BYTES_PER_ROW_ALIGNMENT,
+};
+
+use crate::rendergraph::{
+    Dependency, Node, NodeExecutionContext, NodeUpdateContext, TextureHandle, UpdateResult,
+};
+
+/// A frame read back from the gpu in tightly packed RGBA8 layout
+#[derive(Debug, Clone)]
+pub struct ReadbackFrame {
+    /// Monotonically increasing index of the frame
+    pub index: u64,
+    pub width: u32,
+    pub height: u32,
+    pub data: Vec<u8>,
+}
+
+impl ReadbackFrame {
+    pub fn to_image(&self) -> Option<image::RgbaImage> {
+        image::RgbaImage::from_raw(self.width, self.height, self.data.clone())
+    }
+}
+
+enum ReadbackState {
+    /// Copy has been recorded but not yet submitted
+    Recorded,
+    /// Waiting for the map to complete
+    Mapping,
+    Mapped,
+}
+
+struct PendingReadback {
+    index: u64,
+    buffer: Arc<Buffer>,
+    extent: Extent3d,
+    padded_bytes_per_row: u32,
+    state: ReadbackState,
+}
+
+/// Copies a texture into cpu memory each frame and sends the result through a channel.
+///
+/// The copy is mapped after the frame has been submitted, which means the frames are delivered
+/// with a latency of one or more frames. Supports 8-bit RGBA and BGRA textures.
+pub struct ReadbackNode {
+    input: TextureHandle,
+    tx: flume::Sender<ReadbackFrame>,
+    mapped_tx: flume::Sender<u64>,
+    mapped_rx: flume::Receiver<u64>,
+    pending: Vec<PendingReadback>,
+    free_buffers: Vec<(u64, Arc<Buffer>)>,
+    frame_index: u64,
+}
+
+impl ReadbackNode {
+    pub fn new(input: TextureHandle, tx: flume::Sender<ReadbackFrame>) -> Self {
+        let (mapped_tx, mapped_rx) = flume::unbounded();
+        Self {
+            input,
+            tx,
+            mapped_tx,
+            mapped_rx,
+            pending: Vec::new(),
+            free_buffers: Vec::new(),
+            frame_index: 0,
+        }
+    }
+
+    fn acquire_buffer(&mut self, gpu: &ivy_wgpu_types::Gpu, size: u64) -> Arc<Buffer> {
+        if let Some(index) = self.free_buffers.iter().position(|v| v.0 == size) {
+            return self.free_buffers.swap_remove(index).1;
+        }
+
+        Arc::new(gpu.device.create_buffer(&BufferDescriptor {
+            label: Some("readback"),
+            size,
+            usage: BufferUsages::COPY_DST | BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        }))
+    }
+
+    fn receive_mapped(&mut self, swap_channels: bool) {
+        for index in self.mapped_rx.try_iter() {
+            if let Some(pending) = self.pending.iter_mut().find(|v| v.index == index) {
+                pending.state = ReadbackState::Mapped;
+            }
+        }
+
+        let mut i = 0;
+        while i < self.pending.len() {
+            if !matches!(self.pending[i].state, ReadbackState::Mapped) {
+                i += 1;
+                continue;
+            }
+
+            let pending = self.pending.remove(i);
+            let width = pending.extent.width;
+            let height = pending.extent.height;
+            let mut data = Vec::with_capacity(width as usize * height as usize * 4);
+
+            {
+                let mapped = pending.buffer.slice(..).get_mapped_range();
+                for row in mapped.chunks_exact(pending.padded_bytes_per_row as usize) {
+                    data.extend_from_slice(&row[..width as usize * 4]);
+                }
+            }
+
+            pending.buffer.unmap();
+
+            if swap_channels {
+                data.chunks_exact_mut(4).for_each(|v| v.swap(0, 2));
+            }
+
+            self.free_buffers
+                .push((pending.buffer.size(), pending.buffer));
+
+            let frame = ReadbackFrame {
+                index: pending.index,
+                width,
+                height,
+                data,
+            };
+
+            if self.tx.send(frame).is_err() {
+                tracing::warn!("readback receiver dropped");
+            }
+        }
+    }
+}
+
+impl Node for ReadbackNode {
+    fn label(&self) -> &str {
+        "ReadbackNode"
+    }
+
+    fn update(&mut self, ctx: NodeUpdateContext) -> anyhow::Result<UpdateResult> {
+        profile_function!();
+
+        // All recorded copies have been submitted by now
+        for pending in &mut self.pending {
+            if let ReadbackState::Recorded = pending.state {
+                let mapped_tx = self.mapped_tx.clone();
+                let index = pending.index;
+                pending
+                    .buffer
+                    .slice(..)
+                    .map_async(MapMode::Read, move |result| match result {
+                        Ok(()) => {
+                            mapped_tx.send(index).ok();
+                        }
+                        Err(err) => tracing::error!("Failed to map readback buffer: {err}"),
+                    });
+
+                pending.state = ReadbackState::Mapping;
+            }
+        }
+
+        ctx.gpu.device.poll(Maintain::Poll);
+
+        let format = ctx.get_texture(self.input).format();
+        self.receive_mapped(matches!(
+            format,
+            TextureFormat::Bgra8Unorm | TextureFormat::Bgra8UnormSrgb
+        ));
+
+        Ok(UpdateResult::Success)
+    }
+
+    fn draw(&mut self, ctx: NodeExecutionContext) -> anyhow::Result<()> {
+        let texture = ctx.get_texture(self.input);
+
+        anyhow::ensure!(
+            matches!(
+                texture.format(),
+                TextureFormat::Rgba8Unorm
+                    | TextureFormat::Rgba8UnormSrgb
+                    | TextureFormat::Bgra8Unorm
+                    | TextureFormat::Bgra8UnormSrgb
+            ),
+            "Unsupported readback format {:?}",
+            texture.format()
+        );
+
+        let extent = Extent3d {
+            depth_or_array_layers: 1,
+            ..texture.size()
+        };
+
+        let padded_bytes_per_row =
+            (extent.width * 4).next_multiple_of(COPY_BYTES_PER_ROW_ALIGNMENT);
+        let buffer =
+            self.acquire_buffer(ctx.gpu, padded_bytes_per_row as u64 * extent.height as u64);
+
+        ctx.encoder.copy_texture_to_buffer(
+            ImageCopyTexture {
+                texture,
+                mip_level: 0,
+                origin: Default::default(),
+                aspect: wgpu::TextureAspect::All,
+            },
+            ImageCopyBuffer {
+                buffer: &buffer,
+                layout: ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(padded_bytes_per_row),
+                    rows_per_image: Some(extent.height),
+                },
+            },
+            extent,
+        );
+
+        self.pending.push(PendingReadback {
+            index: self.frame_index,
+            buffer,
+            extent,
+            padded_bytes_per_row,
+            state: ReadbackState::Recorded,
+        });
+
+        self.frame_index += 1;
+
+        Ok(())
+    }
+
+    fn on_resource_changed(&mut self, _resource: crate::rendergraph::ResourceHandle) {
+        // Buffers are sized by the texture each frame
+        self.free_buffers.clear();
+    }
+
+    fn read_dependencies(&self) -> Vec<Dependency> {
+        vec![Dependency::texture(self.input, TextureUsages::COPY_SRC)]
+    }
+
+    fn write_dependencies(&self) -> Vec<Dependency> {
+        vec![]
+    }
+}
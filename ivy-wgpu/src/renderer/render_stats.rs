@@ -0,0 +1,91 @@
+use std::ops::AddAssign;
+
+use flax::World;
+use ivy_core::components::engine;
+
+use super::CameraRenderer;
+use crate::components::render_stats;
+
+/// Draw calls and triangles submitted by the renderers during a frame.
+///
+/// Each renderer counts its own draws, which the render nodes add to
+/// [`render_stats`](crate::components::render_stats) on the engine entity after drawing.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct RenderStats {
+    pub draw_calls: u64,
+    /// Triangles submitted, before culling on the GPU
+    pub triangles: u64,
+}
+
+impl RenderStats {
+    pub fn new(draw_calls: u64, triangles: u64) -> Self {
+        Self {
+            draw_calls,
+            triangles,
+        }
+    }
+
+    /// Adds the draws of `renderer` since the previous call to the stats of the frame
+    pub fn collect(world: &World, renderer: &mut (impl CameraRenderer + ?Sized)) {
+        let stats = renderer.take_stats();
+        if let Ok(mut current) = world.get_mut(engine(), render_stats()) {
+            *current += stats;
+        }
+    }
+}
+
+impl AddAssign for RenderStats {
+    fn add_assign(&mut self, rhs: Self) {
+        self.draw_calls += rhs.draw_calls;
+        self.triangles += rhs.triangles;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use wgpu::RenderPass;
+
+    use super::*;
+    use crate::renderer::{RenderContext, UpdateContext};
+
+    struct Counted(RenderStats);
+
+    impl CameraRenderer for Counted {
+        fn update(&mut self, _: &mut UpdateContext) -> anyhow::Result<()> {
+            Ok(())
+        }
+
+        fn draw<'s>(
+            &'s mut self,
+            _: &'s RenderContext<'s>,
+            _: &mut RenderPass<'s>,
+        ) -> anyhow::Result<()> {
+            Ok(())
+        }
+
+        fn take_stats(&mut self) -> RenderStats {
+            std::mem::take(&mut self.0)
+        }
+    }
+
+    #[test]
+    fn collect_renderers() {
+        let mut world = World::new();
+        world
+            .set(engine(), render_stats(), RenderStats::default())
+            .unwrap();
+
+        let mut renderers = (
+            Counted(RenderStats::new(2, 100)),
+            Counted(RenderStats::new(1, 2)),
+        );
+
+        RenderStats::collect(&world, &mut renderers);
+        RenderStats::collect(&world, &mut renderers);
+
+        assert_eq!(
+            world.get_copy(engine(), render_stats()).unwrap(),
+            RenderStats::new(3, 102)
+        );
+    }
+}
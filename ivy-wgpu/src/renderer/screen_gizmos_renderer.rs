@@ -0,0 +1,373 @@
+use std::f32::consts::TAU;
+
+use anyhow::Context;
+use glam::Vec2;
+use ivy_core::{
+    components::{self, engine},
+    gizmos::{ScreenGizmoPrimitive, ScreenUnits},
+    ColorExt,
+};
+use ivy_wgpu_types::{
+    shader::{ShaderDesc, TargetDesc},
+    Gpu, RenderShader, TypedBuffer,
+};
+use wgpu::{
+    vertex_attr_array, BufferUsages, RenderPassColorAttachment, RenderPassDescriptor,
+    TextureUsages, VertexAttribute, VertexBufferLayout,
+};
+
+use super::stroke_font;
+use crate::rendergraph::{
+    Dependency, Node, NodeExecutionContext, NodeUpdateContext, TextureHandle, UpdateResult,
+};
+
+/// Number of line segments used for a circle
+const CIRCLE_SEGMENTS: usize = 32;
+/// Thickness of the strokes of text, relative to its size
+const TEXT_THICKNESS: f32 = 0.12;
+
+/// Draws the screen space gizmos on top of `output`.
+///
+/// Add this node after post-processing, so that the gizmos are unaffected by it.
+pub struct ScreenGizmosRendererNode {
+    shader: Option<RenderShader>,
+    buffer: TypedBuffer<ScreenVertex>,
+    vertices: Vec<ScreenVertex>,
+    output: TextureHandle,
+}
+
+impl ScreenGizmosRendererNode {
+    pub fn new(gpu: &Gpu, output: TextureHandle) -> Self {
+        let buffer = TypedBuffer::new_uninit(
+            gpu,
+            "screen_gizmos",
+            BufferUsages::VERTEX | BufferUsages::COPY_DST,
+            1024,
+        );
+
+        Self {
+            shader: None,
+            buffer,
+            vertices: Vec::new(),
+            output,
+        }
+    }
+}
+
+impl Node for ScreenGizmosRendererNode {
+    fn update(&mut self, ctx: NodeUpdateContext) -> anyhow::Result<UpdateResult> {
+        let gizmos = ctx
+            .world
+            .get(engine(), components::gizmos())
+            .context("Missing gizmos")?;
+
+        let size = ctx.get_texture(self.output).size();
+        let screen_size = Vec2::new(size.width as f32, size.height as f32);
+
+        self.vertices.clear();
+
+        for section in gizmos.screen_sections() {
+            for primitive in section.primitives() {
+                push_primitive(&mut self.vertices, screen_size, primitive);
+            }
+        }
+
+        if self.vertices.len() > self.buffer.len() {
+            self.buffer
+                .resize(ctx.gpu, self.vertices.len().next_power_of_two(), false);
+        }
+
+        self.buffer.write(&ctx.gpu.queue, 0, &self.vertices);
+
+        Ok(UpdateResult::Success)
+    }
+
+    fn draw(&mut self, ctx: NodeExecutionContext) -> anyhow::Result<()> {
+        if self.vertices.is_empty() {
+            return Ok(());
+        }
+
+        let output = ctx.get_texture(self.output);
+        let output_view = output.create_view(&Default::default());
+
+        let mut render_pass = ctx.encoder.begin_render_pass(&RenderPassDescriptor {
+            label: Some("screen_gizmos"),
+            color_attachments: &[Some(RenderPassColorAttachment {
+                view: &output_view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            ..Default::default()
+        });
+
+        let target = TargetDesc {
+            formats: &[output.format()],
+            depth_format: None,
+            sample_count: output.sample_count(),
+        };
+
+        let shader = self.shader.get_or_insert_with(|| {
+            let shader_module = ctx
+                .gpu
+                .device
+                .create_shader_module(wgpu::ShaderModuleDescriptor {
+                    label: Some("screen_gizmos"),
+                    source: wgpu::ShaderSource::Wgsl(
+                        include_str!("../../shaders/screen_gizmos.wgsl").into(),
+                    ),
+                });
+
+            RenderShader::new(
+                ctx.gpu,
+                &ShaderDesc::new("screen_gizmos", &shader_module, &target)
+                    .with_vertex_layouts(&[ScreenVertex::layout()]),
+            )
+        });
+
+        render_pass.set_pipeline(shader.pipeline());
+        render_pass.set_vertex_buffer(0, self.buffer.slice(..));
+        render_pass.draw(0..self.vertices.len() as u32, 0..1);
+
+        Ok(())
+    }
+
+    fn read_dependencies(&self) -> Vec<Dependency> {
+        vec![Dependency::texture(
+            self.output,
+            TextureUsages::RENDER_ATTACHMENT,
+        )]
+    }
+
+    fn write_dependencies(&self) -> Vec<Dependency> {
+        vec![]
+    }
+
+    fn on_resource_changed(&mut self, _resource: crate::rendergraph::ResourceHandle) {}
+}
+
+fn push_primitive(
+    vertices: &mut Vec<ScreenVertex>,
+    screen_size: Vec2,
+    primitive: &ScreenGizmoPrimitive,
+) {
+    let to_pixels = |pos: Vec2, units: ScreenUnits| match units {
+        ScreenUnits::Pixels => pos,
+        ScreenUnits::Normalized => pos * screen_size,
+    };
+
+    match primitive {
+        ScreenGizmoPrimitive::Line {
+            start,
+            end,
+            thickness,
+            color,
+            units,
+        } => push_line(
+            vertices,
+            screen_size,
+            to_pixels(*start, *units),
+            to_pixels(*end, *units),
+            *thickness,
+            color.to_vec4().to_array(),
+        ),
+        ScreenGizmoPrimitive::Circle {
+            center,
+            radius,
+            thickness,
+            color,
+            units,
+        } => {
+            let center = to_pixels(*center, *units);
+            let point = |i: usize| {
+                center + Vec2::from_angle(i as f32 / CIRCLE_SEGMENTS as f32 * TAU) * *radius
+            };
+
+            for i in 0..CIRCLE_SEGMENTS {
+                push_line(
+                    vertices,
+                    screen_size,
+                    point(i),
+                    point(i + 1),
+                    *thickness,
+                    color.to_vec4().to_array(),
+                );
+            }
+        }
+        ScreenGizmoPrimitive::Text {
+            position,
+            text,
+            size,
+            color,
+            units,
+        } => {
+            let center = to_pixels(*position, *units) + stroke_font::size(text) * *size * 0.5;
+
+            // The font is laid out with y up, while screen space is y down
+            for (start, end) in stroke_font::layout(text) {
+                push_line(
+                    vertices,
+                    screen_size,
+                    center + Vec2::new(start.x, -start.y) * *size,
+                    center + Vec2::new(end.x, -end.y) * *size,
+                    *size * TEXT_THICKNESS,
+                    color.to_vec4().to_array(),
+                );
+            }
+        }
+    }
+}
+
+/// Pushes a quad covering the line from `start` to `end`, in pixels
+fn push_line(
+    vertices: &mut Vec<ScreenVertex>,
+    screen_size: Vec2,
+    start: Vec2,
+    end: Vec2,
+    thickness: f32,
+    color: [f32; 4],
+) {
+    let Some(dir) = (end - start).try_normalize() else {
+        return;
+    };
+
+    // Extend the ends, so that connected lines meet at the corners
+    let dir = dir * thickness * 0.5;
+    let normal = dir.perp();
+
+    let corners = [
+        start - dir + normal,
+        start - dir - normal,
+        end + dir - normal,
+        end + dir + normal,
+    ];
+
+    let to_ndc = |p: Vec2| {
+        Vec2::new(
+            p.x / screen_size.x * 2.0 - 1.0,
+            1.0 - p.y / screen_size.y * 2.0,
+        )
+    };
+
+    vertices.extend([0, 1, 2, 0, 2, 3].map(|i| ScreenVertex {
+        pos: to_ndc(corners[i]),
+        color,
+    }));
+}
+
+#[repr(C)]
+#[derive(bytemuck::Pod, bytemuck::Zeroable, Clone, Copy, Debug)]
+struct ScreenVertex {
+    pos: Vec2,
+    color: [f32; 4],
+}
+
+impl ScreenVertex {
+    fn layout() -> VertexBufferLayout<'static> {
+        static ATTRIBUTES: &[VertexAttribute] = &vertex_attr_array![0 => Float32x2, 1 => Float32x4];
+
+        VertexBufferLayout {
+            array_stride: std::mem::size_of::<Self>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: ATTRIBUTES,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use ivy_core::{Color, ColorExt};
+
+    use super::*;
+
+    #[test]
+    fn line_quad() {
+        let mut vertices = Vec::new();
+        push_line(
+            &mut vertices,
+            Vec2::new(100.0, 50.0),
+            Vec2::new(10.0, 25.0),
+            Vec2::new(90.0, 25.0),
+            10.0,
+            [1.0; 4],
+        );
+
+        assert_eq!(vertices.len(), 6);
+
+        let (min, max) = vertices
+            .iter()
+            .fold((Vec2::MAX, Vec2::MIN), |(min, max), v| {
+                (min.min(v.pos), max.max(v.pos))
+            });
+
+        // Extended by half the thickness at the ends, and y flipped to point up
+        assert!(min.abs_diff_eq(Vec2::new(-0.9, -0.2), 1e-5), "{min}");
+        assert!(max.abs_diff_eq(Vec2::new(0.9, 0.2), 1e-5), "{max}");
+
+        // Degenerate lines are skipped
+        push_line(
+            &mut vertices,
+            Vec2::ONE,
+            Vec2::ONE,
+            Vec2::ONE,
+            1.0,
+            [1.0; 4],
+        );
+        assert_eq!(vertices.len(), 6);
+    }
+
+    #[test]
+    fn normalized_units() {
+        let line = |units| ScreenGizmoPrimitive::Line {
+            start: Vec2::new(0.0, 0.5),
+            end: Vec2::new(1.0, 0.5),
+            thickness: 2.0,
+            color: Color::white(),
+            units,
+        };
+
+        let screen_size = Vec2::new(200.0, 100.0);
+
+        let mut pixels = Vec::new();
+        push_primitive(
+            &mut pixels,
+            screen_size,
+            &ScreenGizmoPrimitive::Line {
+                start: Vec2::new(0.0, 50.0),
+                end: Vec2::new(200.0, 50.0),
+                thickness: 2.0,
+                color: Color::white(),
+                units: ScreenUnits::Pixels,
+            },
+        );
+
+        let mut normalized = Vec::new();
+        push_primitive(&mut normalized, screen_size, &line(ScreenUnits::Normalized));
+
+        assert_eq!(
+            pixels.iter().map(|v| v.pos).collect::<Vec<_>>(),
+            normalized.iter().map(|v| v.pos).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn circle_segments() {
+        let mut vertices = Vec::new();
+        push_primitive(
+            &mut vertices,
+            Vec2::splat(100.0),
+            &ScreenGizmoPrimitive::Circle {
+                center: Vec2::splat(50.0),
+                radius: 10.0,
+                thickness: 1.0,
+                color: Color::white(),
+                units: ScreenUnits::Pixels,
+            },
+        );
+
+        assert_eq!(vertices.len(), CIRCLE_SEGMENTS * 6);
+    }
+}
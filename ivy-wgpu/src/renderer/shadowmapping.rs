@@ -5,7 +5,7 @@ use glam::{vec2, vec3, Mat4, Vec2, Vec3, Vec4Swizzles};
 use itertools::{izip, Itertools};
 use ivy_assets::stored::Handle;
 use ivy_core::{
-    components::{main_camera, world_transform},
+    components::world_transform,
     profiling::{profile_function, profile_scope},
     WorldExt,
 };
@@ -19,13 +19,14 @@ use wgpu::{
 
 use super::ObjectManager;
 use crate::{
+    camera_target::CameraSelection,
     components::{
         cast_shadow, light_kind, light_params, light_shadow_data, projection_matrix, shadow_pass,
     },
     light::{LightKind, LightParams},
     renderer::{
-        mesh_renderer::MeshRenderer, CameraData, CameraRenderer, RenderContext, RendererStore,
-        UpdateContext,
+        mesh_renderer::MeshRenderer, render_stats::RenderStats, CameraData, CameraRenderer,
+        RenderContext, RendererStore, UpdateContext,
     },
     rendergraph::{
         BufferHandle, Dependency, Node, NodeExecutionContext, NodeUpdateContext, TextureHandle,
@@ -77,7 +78,9 @@ pub struct ShadowMapNode {
     query: Query<ShadowMapNodeQuery>,
     object_manager: Handle<ObjectManager>,
     shader_library: Arc<ShaderLibrary>,
-    main_camera_query: Query<(Component<()>, Component<Mat4>, Component<Mat4>)>,
+    camera_query: Query<(Component<Mat4>, Component<Mat4>)>,
+    /// The cascades are fitted to the view frustum of this camera
+    camera: CameraSelection,
 }
 
 fn shader_factory(desc: ShaderDesc) -> ShaderDesc {
@@ -125,7 +128,8 @@ impl ShadowMapNode {
             shadow_camera_buffer: light_camera_buffer,
             renderers: Vec::new(),
             store: RendererStore::new(),
-            main_camera_query: Query::new((main_camera(), world_transform(), projection_matrix())),
+            camera_query: Query::new((world_transform(), projection_matrix())),
+            camera: CameraSelection::Main,
             query: Query::new(ShadowMapNodeQuery {
                 id: entity_ids(),
                 world_transform: world_transform(),
@@ -137,6 +141,12 @@ impl ShadowMapNode {
             object_manager,
         }
     }
+
+    /// Fit the cascades to another camera than the main camera
+    pub fn with_camera(mut self, camera: impl Into<CameraSelection>) -> Self {
+        self.camera = camera.into();
+        self
+    }
 }
 
 impl Node for ShadowMapNode {
@@ -150,15 +160,19 @@ impl Node for ShadowMapNode {
 
         self.shadow_casters.clear();
 
-        let Some((_, &main_camera_transform, &main_camera_proj)) =
-            self.main_camera_query.borrow(ctx.world).first()
-        else {
-            tracing::warn!("no main camera");
+        let Some((camera_transform, camera_proj)) = self.camera.find(ctx.world).and_then(|id| {
+            let mut query = self.camera_query.borrow(ctx.world);
+            query
+                .get(id)
+                .ok()
+                .map(|(&transform, &proj)| (transform, proj))
+        }) else {
+            tracing::warn!(camera = ?self.camera, "no camera to fit the shadow cascades to");
             return Ok(UpdateResult::Success);
         };
 
-        let inv_proj = main_camera_proj.inverse();
-        let camera_inv_viewproj = main_camera_transform * inv_proj;
+        let inv_proj = camera_proj.inverse();
+        let camera_inv_viewproj = camera_transform * inv_proj;
 
         fn transform_perspective(inv_viewproj: Mat4, clip: Vec3) -> Vec3 {
             let p = inv_viewproj * clip.extend(1.0);
@@ -417,6 +431,8 @@ impl Node for ShadowMapNode {
                     fog_blend: Default::default(),
                     fog_color: Default::default(),
                     fog_density: Default::default(),
+                    wetness: Default::default(),
+                    _padding: Default::default(),
                 },
             };
 
@@ -442,6 +458,10 @@ impl Node for ShadowMapNode {
             renderer.draw(&draw_ctx, &mut render_pass)?;
         }
 
+        for renderer in &mut self.renderers {
+            RenderStats::collect(ctx.world, renderer);
+        }
+
         Ok(())
     }
 
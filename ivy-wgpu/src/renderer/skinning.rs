@@ -0,0 +1,424 @@
+use std::{collections::HashMap, iter::repeat};
+
+use bytemuck::{Pod, Zeroable};
+use glam::{Mat4, UVec4};
+use itertools::{izip, Itertools};
+use ivy_assets::AssetCache;
+use ivy_core::profiling::profile_function;
+use ivy_graphics::mesh::{MeshData, JOINT_INDEX_1_ATTRIBUTE, WEIGHT_1_ATTRIBUTE};
+use ivy_wgpu_types::{
+    multi_buffer::{MultiBuffer, SubBuffer},
+    BindGroupBuilder, BindGroupLayoutBuilder, Gpu, TypedBuffer,
+};
+use wgpu::{
+    BindGroup, BindGroupLayout, BufferUsages, CommandEncoderDescriptor, ComputePassDescriptor,
+    ComputePipeline, ComputePipelineDescriptor, PipelineLayoutDescriptor, ShaderStages,
+};
+
+use crate::{mesh::SkinnedVertex, mesh_desc::MeshDesc};
+
+/// Maximum number of joints affecting a single vertex
+pub const MAX_JOINT_INFLUENCES: usize = 8;
+
+/// Bind pose vertex with all joint influences
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Pod, Zeroable)]
+struct SkinningVertex {
+    vertex: SkinnedVertex,
+    joints: [u32; MAX_JOINT_INFLUENCES],
+    weights: [f32; MAX_JOINT_INFLUENCES],
+}
+
+impl SkinningVertex {
+    fn compose_from_mesh(mesh: &MeshData) -> Vec<Self> {
+        let joints_1 = mesh
+            .get_attribute(JOINT_INDEX_1_ATTRIBUTE)
+            .map(|v| v.as_u16_vec4())
+            .unwrap_or_default()
+            .into_iter()
+            .flatten()
+            .copied()
+            .chain(repeat(Default::default()));
+
+        let weights_1 = mesh
+            .get_attribute(WEIGHT_1_ATTRIBUTE)
+            .map(|v| v.as_vec4())
+            .unwrap_or_default()
+            .into_iter()
+            .flatten()
+            .copied()
+            .chain(repeat(Default::default()));
+
+        izip!(SkinnedVertex::compose_from_mesh(mesh), joints_1, weights_1)
+            .map(|(vertex, joints_1, weights_1)| {
+                let mut joints = [0; MAX_JOINT_INFLUENCES];
+                joints[..4].copy_from_slice(&vertex.joints.to_array());
+                joints[4..].copy_from_slice(&UVec4::from(joints_1).to_array());
+
+                let mut weights = [0.0; MAX_JOINT_INFLUENCES];
+                weights[..4].copy_from_slice(&vertex.weights.to_array());
+                weights[4..].copy_from_slice(&weights_1.to_array());
+
+                Self {
+                    vertex,
+                    joints,
+                    weights,
+                }
+            })
+            .collect_vec()
+    }
+}
+
+/// Conservative bounds of a skinned mesh in its current pose, relative to its bind pose bounds
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct SkinBounds {
+    /// Farthest any joint moves the origin
+    translation: f32,
+    /// Largest scale applied by any joint
+    scale: f32,
+}
+
+impl SkinBounds {
+    /// Bounds of an unanimated mesh
+    pub const BIND_POSE: Self = Self {
+        translation: 0.0,
+        scale: 1.0,
+    };
+
+    /// Bounds every vertex transformed by a weighted blend of the joint matrices.
+    ///
+    /// Assumes the joint matrices do not contain shear.
+    pub fn from_joints(joints: &[Mat4]) -> Self {
+        joints.iter().fold(
+            Self {
+                translation: 0.0,
+                scale: 0.0,
+            },
+            |acc, joint| {
+                let scale = joint
+                    .x_axis
+                    .truncate()
+                    .length()
+                    .max(joint.y_axis.truncate().length())
+                    .max(joint.z_axis.truncate().length());
+
+                Self {
+                    translation: acc.translation.max(joint.w_axis.truncate().length()),
+                    scale: acc.scale.max(scale),
+                }
+            },
+        )
+    }
+
+    /// Radius around the object origin containing the posed mesh, given the radius of its bind pose
+    pub fn radius(&self, bind_radius: f32) -> f32 {
+        self.translation + self.scale * bind_radius
+    }
+}
+
+/// Skins the vertices of a single object
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Pod, Zeroable)]
+struct SkinJob {
+    source_offset: u32,
+    output_offset: u32,
+    joint_offset: u32,
+    vertex_count: u32,
+}
+
+struct SourceMesh {
+    vertices: SubBuffer<SkinningVertex>,
+    vertex_count: usize,
+    users: usize,
+}
+
+/// Applies the joint matrices of skinned objects in a compute pass before rendering.
+///
+/// Each skinned object receives its own copy of the mesh vertices, which are drawn through the
+/// same pipelines as static meshes.
+pub(crate) struct MeshSkinning {
+    pipeline: ComputePipeline,
+    bind_group_layout: BindGroupLayout,
+    bind_group: Option<BindGroup>,
+    buffer_gens: [u32; 4],
+
+    meshes: HashMap<MeshDesc, SourceMesh>,
+    source_vertices: MultiBuffer<SkinningVertex>,
+    output_vertices: MultiBuffer<SkinnedVertex>,
+
+    jobs: Vec<SkinJob>,
+    job_buffer: TypedBuffer<SkinJob>,
+}
+
+impl MeshSkinning {
+    pub fn new(gpu: &Gpu) -> Self {
+        let bind_group_layout = BindGroupLayoutBuilder::new("Skinning")
+            .bind_storage_buffer(ShaderStages::COMPUTE) // jobs
+            .bind_storage_buffer(ShaderStages::COMPUTE) // joint_matrices
+            .bind_storage_buffer(ShaderStages::COMPUTE) // source_vertices
+            .bind_storage_buffer_write(ShaderStages::COMPUTE) // output_vertices
+            .build(gpu);
+
+        let pipeline_layout = gpu
+            .device
+            .create_pipeline_layout(&PipelineLayoutDescriptor {
+                label: Some("Skinning"),
+                bind_group_layouts: &[&bind_group_layout],
+                push_constant_ranges: &[],
+            });
+
+        let pipeline = gpu
+            .device
+            .create_compute_pipeline(&ComputePipelineDescriptor {
+                label: Some("Skinning"),
+                layout: Some(&pipeline_layout),
+                module: &gpu
+                    .device
+                    .create_shader_module(wgpu::ShaderModuleDescriptor {
+                        label: Some("Skinning"),
+                        source: wgpu::ShaderSource::Wgsl(
+                            include_str!("../../../assets/shaders/skinning.wgsl").into(),
+                        ),
+                    }),
+                entry_point: "main",
+                compilation_options: Default::default(),
+                cache: None,
+            });
+
+        Self {
+            pipeline,
+            bind_group_layout,
+            bind_group: None,
+            buffer_gens: Default::default(),
+            meshes: HashMap::new(),
+            source_vertices: MultiBuffer::new(
+                gpu,
+                "skinning_source_vertices",
+                BufferUsages::STORAGE | BufferUsages::COPY_DST | BufferUsages::COPY_SRC,
+                1024,
+            ),
+            output_vertices: MultiBuffer::new(
+                gpu,
+                "skinned_vertices",
+                BufferUsages::STORAGE
+                    | BufferUsages::VERTEX
+                    | BufferUsages::COPY_DST
+                    | BufferUsages::COPY_SRC,
+                1024,
+            ),
+            jobs: Vec::new(),
+            job_buffer: TypedBuffer::new_uninit(
+                gpu,
+                "skinning_jobs",
+                BufferUsages::STORAGE | BufferUsages::COPY_DST,
+                64,
+            ),
+        }
+    }
+
+    /// Allocates the skinned vertices of a new object.
+    ///
+    /// The bind pose of the mesh is uploaded once and shared between all objects using it.
+    pub fn allocate(
+        &mut self,
+        gpu: &Gpu,
+        assets: &AssetCache,
+        mesh: &MeshDesc,
+    ) -> anyhow::Result<SubBuffer<SkinnedVertex>> {
+        if !self.meshes.contains_key(mesh) {
+            let mesh_data = mesh.load_data(assets)?;
+            let vertices = SkinningVertex::compose_from_mesh(&mesh_data);
+
+            let allocation = match self.source_vertices.allocate(vertices.len()) {
+                Some(v) => v,
+                None => {
+                    self.source_vertices.grow(gpu, vertices.len());
+                    self.source_vertices.allocate(vertices.len()).unwrap()
+                }
+            };
+
+            self.source_vertices
+                .write(&gpu.queue, &allocation, &vertices);
+
+            self.meshes.insert(
+                mesh.clone(),
+                SourceMesh {
+                    vertices: allocation,
+                    vertex_count: vertices.len(),
+                    users: 0,
+                },
+            );
+        }
+
+        let source = self.meshes.get_mut(mesh).unwrap();
+        source.users += 1;
+        let vertex_count = source.vertex_count;
+
+        let output = match self.output_vertices.allocate(vertex_count) {
+            Some(v) => v,
+            None => {
+                self.output_vertices.grow(gpu, vertex_count);
+                self.output_vertices.allocate(vertex_count).unwrap()
+            }
+        };
+
+        Ok(output)
+    }
+
+    pub fn deallocate(&mut self, mesh: &MeshDesc, output: SubBuffer<SkinnedVertex>) {
+        self.output_vertices.deallocate(output);
+
+        if let Some(source) = self.meshes.get_mut(mesh) {
+            source.users -= 1;
+            if source.users == 0 {
+                self.source_vertices.deallocate(source.vertices);
+                self.meshes.remove(mesh);
+            }
+        }
+    }
+
+    /// Skin the object using the joint matrices at `joint_offset` during the next [`Self::run`]
+    pub fn queue(&mut self, mesh: &MeshDesc, output: SubBuffer<SkinnedVertex>, joint_offset: u32) {
+        let Some(source) = self.meshes.get(mesh) else {
+            return;
+        };
+
+        self.jobs.push(SkinJob {
+            source_offset: source.vertices.offset() as u32,
+            output_offset: output.offset() as u32,
+            joint_offset,
+            vertex_count: source.vertex_count as u32,
+        });
+    }
+
+    /// Dispatches the queued jobs.
+    ///
+    /// The work is submitted immediately to complete before any render pass of the frame.
+    pub fn run(&mut self, gpu: &Gpu, joint_matrices: &MultiBuffer<Mat4>) {
+        profile_function!();
+        if self.jobs.is_empty() {
+            return;
+        }
+
+        if self.job_buffer.len() < self.jobs.len() {
+            self.job_buffer
+                .resize(gpu, self.jobs.len().next_power_of_two(), false);
+        }
+
+        let buffer_gens = [
+            self.job_buffer.gen(),
+            joint_matrices.gen(),
+            self.source_vertices.gen(),
+            self.output_vertices.gen(),
+        ];
+
+        if self.buffer_gens != buffer_gens {
+            self.buffer_gens = buffer_gens;
+            self.bind_group = None;
+        }
+
+        self.job_buffer.write(&gpu.queue, 0, &self.jobs);
+
+        let bind_group = self.bind_group.get_or_insert_with(|| {
+            BindGroupBuilder::new("Skinning")
+                .bind_buffer(&self.job_buffer)
+                .bind_buffer(joint_matrices.buffer())
+                .bind_buffer(self.source_vertices.buffer())
+                .bind_buffer(self.output_vertices.buffer())
+                .build(gpu, &self.bind_group_layout)
+        });
+
+        let max_vertex_count = self.jobs.iter().map(|v| v.vertex_count).max().unwrap_or(0);
+
+        let mut encoder = gpu
+            .device
+            .create_command_encoder(&CommandEncoderDescriptor {
+                label: Some("skinning"),
+            });
+
+        {
+            let mut compute_pass = encoder.begin_compute_pass(&ComputePassDescriptor {
+                label: Some("skinning"),
+                timestamp_writes: None,
+            });
+
+            compute_pass.set_pipeline(&self.pipeline);
+            compute_pass.set_bind_group(0, bind_group, &[]);
+            compute_pass.dispatch_workgroups(
+                max_vertex_count.div_ceil(64),
+                self.jobs.len() as u32,
+                1,
+            );
+        }
+
+        gpu.queue.submit([encoder.finish()]);
+        self.jobs.clear();
+    }
+
+    pub fn output_vertices(&self) -> &MultiBuffer<SkinnedVertex> {
+        &self.output_vertices
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use glam::{Quat, U16Vec4, Vec3, Vec4};
+    use ivy_graphics::mesh::{JOINT_INDEX_ATTRIBUTE, WEIGHT_ATTRIBUTE};
+
+    use super::*;
+
+    fn skinned_quad() -> MeshData {
+        MeshData::quad()
+            .with_attribute(JOINT_INDEX_ATTRIBUTE, [U16Vec4::new(0, 1, 2, 3); 4])
+            .with_attribute(WEIGHT_ATTRIBUTE, [Vec4::splat(0.125); 4])
+    }
+
+    #[test]
+    fn compose_influences() {
+        let mesh = skinned_quad()
+            .with_attribute(JOINT_INDEX_1_ATTRIBUTE, [U16Vec4::new(4, 5, 6, 7); 4])
+            .with_attribute(WEIGHT_1_ATTRIBUTE, [Vec4::splat(0.125); 4]);
+
+        let vertices = SkinningVertex::compose_from_mesh(&mesh);
+        assert_eq!(vertices.len(), 4);
+
+        for vertex in vertices {
+            assert_eq!(vertex.joints, [0, 1, 2, 3, 4, 5, 6, 7]);
+            assert_eq!(vertex.weights, [0.125; MAX_JOINT_INFLUENCES]);
+        }
+    }
+
+    #[test]
+    fn compose_without_second_influences() {
+        let vertices = SkinningVertex::compose_from_mesh(&skinned_quad());
+        assert_eq!(vertices.len(), 4);
+
+        for vertex in vertices {
+            assert_eq!(vertex.joints, [0, 1, 2, 3, 0, 0, 0, 0]);
+            assert_eq!(vertex.weights[4..], [0.0; 4]);
+        }
+    }
+
+    #[test]
+    fn skin_bounds() {
+        assert_eq!(SkinBounds::BIND_POSE.radius(2.0), 2.0);
+
+        let bounds = SkinBounds::from_joints(&[Mat4::IDENTITY]);
+        assert_eq!(bounds.radius(2.0), 2.0);
+
+        let joints = [
+            Mat4::from_rotation_translation(Quat::from_rotation_y(1.0), Vec3::new(0.0, 3.0, 0.0)),
+            Mat4::from_scale(Vec3::new(1.0, 2.0, 1.0)),
+        ];
+
+        let bounds = SkinBounds::from_joints(&joints);
+        assert!((bounds.radius(1.0) - 5.0).abs() < 1e-5);
+
+        // Every posed vertex of the bind pose sphere is contained
+        for joint in joints {
+            for dir in [Vec3::X, Vec3::Y, Vec3::Z, -Vec3::Y, Vec3::ONE.normalize()] {
+                assert!(joint.transform_point3(dir).length() <= bounds.radius(1.0) + 1e-5);
+            }
+        }
+    }
+}
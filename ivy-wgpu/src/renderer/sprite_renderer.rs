@@ -0,0 +1,321 @@
+use std::{collections::HashMap, ops::Range};
+
+use bytemuck::{Pod, Zeroable};
+use flax::Query;
+use glam::{Mat4, Quat, Vec2, Vec3, Vec4};
+use ivy_assets::{Asset, AssetId};
+use ivy_core::{components::world_transform, pool::pooled, profiling::profile_function, ColorExt};
+use ivy_wgpu_types::{
+    shader::{Culling, ShaderDesc},
+    BindGroupBuilder, BindGroupLayoutBuilder, Gpu, RenderShader, TypedBuffer,
+};
+use ordered_float::OrderedFloat;
+use wgpu::{
+    BindGroup, BindGroupLayout, BufferUsages, CommandEncoder, FilterMode, RenderPass, Sampler,
+    SamplerDescriptor, ShaderStages, Texture,
+};
+
+use super::{render_stats::RenderStats, CameraRenderer, RenderContext, UpdateContext};
+use crate::{components::sprite, sprite::Sprite};
+
+/// Draws all [`Sprite`]s visible to the camera.
+///
+/// Sprites are sorted by layer and then back to front, and consecutive sprites sharing a texture
+/// are drawn in a single instanced draw call.
+pub struct SpriteRenderer {
+    shader: Option<RenderShader>,
+    instance_layout: BindGroupLayout,
+    texture_layout: BindGroupLayout,
+    instances: TypedBuffer<SpriteInstance>,
+    instance_bind_group: Option<BindGroup>,
+    instance_buffer_gen: u32,
+    texture_bind_groups: HashMap<(AssetId, bool), BindGroup>,
+    linear_sampler: Sampler,
+    nearest_sampler: Sampler,
+    batches: Vec<SpriteBatch>,
+    stats: RenderStats,
+}
+
+struct SpriteBatch {
+    key: (AssetId, bool),
+    instances: Range<u32>,
+}
+
+impl SpriteRenderer {
+    pub fn new(gpu: &Gpu) -> Self {
+        let instance_layout = BindGroupLayoutBuilder::new("sprite_instances")
+            .bind_storage_buffer(ShaderStages::VERTEX)
+            .build(gpu);
+
+        let texture_layout = BindGroupLayoutBuilder::new("sprite_texture")
+            .bind_texture(ShaderStages::FRAGMENT)
+            .bind_sampler(ShaderStages::FRAGMENT)
+            .build(gpu);
+
+        let instances = TypedBuffer::new_uninit(
+            gpu,
+            "sprite_instances",
+            BufferUsages::STORAGE | BufferUsages::COPY_DST,
+            256,
+        );
+
+        let create_sampler = |label, filter| {
+            gpu.device.create_sampler(&SamplerDescriptor {
+                label: Some(label),
+                address_mode_u: wgpu::AddressMode::ClampToEdge,
+                address_mode_v: wgpu::AddressMode::ClampToEdge,
+                address_mode_w: wgpu::AddressMode::ClampToEdge,
+                mag_filter: filter,
+                min_filter: filter,
+                mipmap_filter: filter,
+                ..Default::default()
+            })
+        };
+
+        Self {
+            shader: None,
+            instance_layout,
+            texture_layout,
+            instance_buffer_gen: instances.gen(),
+            instances,
+            instance_bind_group: None,
+            texture_bind_groups: HashMap::new(),
+            linear_sampler: create_sampler("sprite_linear", FilterMode::Linear),
+            nearest_sampler: create_sampler("sprite_nearest", FilterMode::Nearest),
+            batches: Vec::new(),
+            stats: RenderStats::default(),
+        }
+    }
+
+    fn texture_bind_group(&mut self, gpu: &Gpu, texture: &Asset<Texture>, pixelated: bool) {
+        let key = (texture.id(), pixelated);
+        if self.texture_bind_groups.contains_key(&key) {
+            return;
+        }
+
+        let view = texture.create_view(&Default::default());
+        let sampler = if pixelated {
+            &self.nearest_sampler
+        } else {
+            &self.linear_sampler
+        };
+
+        let bind_group = BindGroupBuilder::new("sprite_texture")
+            .bind_texture(&view)
+            .bind_sampler(sampler)
+            .build(gpu, &self.texture_layout);
+
+        self.texture_bind_groups.insert(key, bind_group);
+    }
+}
+
+/// World matrix of the sprite quad, which spans `-0.5..0.5` in the XY plane
+fn sprite_transform(sprite: &Sprite, transform: &Mat4, view: &Mat4) -> Mat4 {
+    let (scale, rotation, translation) = transform.to_scale_rotation_translation();
+
+    let rotation = if sprite.billboard {
+        Quat::from_mat4(&view.inverse())
+    } else {
+        rotation
+    };
+
+    Mat4::from_scale_rotation_translation(scale, rotation, translation)
+        * quad_transform(sprite.size, sprite.anchor, sprite.rotation)
+}
+
+/// Places the unit quad relative to the entity, so that `anchor` lies at the origin
+fn quad_transform(size: Vec2, anchor: Vec2, rotation: f32) -> Mat4 {
+    Mat4::from_rotation_z(rotation)
+        * Mat4::from_scale(size.extend(1.0))
+        * Mat4::from_translation((Vec2::splat(0.5) - anchor).extend(0.0))
+}
+
+impl CameraRenderer for SpriteRenderer {
+    fn update(&mut self, _: &mut UpdateContext) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    fn before_draw(&mut self, ctx: &RenderContext, _: &mut CommandEncoder) -> anyhow::Result<()> {
+        profile_function!();
+
+        let view = ctx.camera.view;
+
+        let mut query = Query::new((sprite(), world_transform())).without(pooled());
+        let mut query = query.borrow(ctx.world);
+
+        let mut sprites = query
+            .iter()
+            .map(|(sprite, transform)| {
+                let world = sprite_transform(sprite, transform, &view);
+                let depth = view.transform_point3(world.transform_point3(Vec3::ZERO)).z;
+                (sprite, world, depth)
+            })
+            .collect::<Vec<_>>();
+
+        // The camera looks down -Z, so the farthest sprites have the lowest depth
+        sprites.sort_by_key(|(sprite, _, depth)| {
+            (
+                sprite.layer,
+                OrderedFloat(*depth),
+                sprite.texture.id(),
+                sprite.pixelated,
+            )
+        });
+
+        self.batches.clear();
+        let mut instances = Vec::with_capacity(sprites.len());
+
+        for (i, (sprite, world, _)) in sprites.iter().enumerate() {
+            let key = (sprite.texture.id(), sprite.pixelated);
+            let (uv_min, uv_max) = sprite.tex_coords();
+
+            instances.push(SpriteInstance {
+                world: *world,
+                color: sprite.color.to_vec4(),
+                uv_min,
+                uv_max,
+            });
+
+            if let Some(batch) = self.batches.last_mut().filter(|v| v.key == key) {
+                batch.instances.end += 1;
+                continue;
+            }
+
+            self.texture_bind_group(ctx.gpu, &sprite.texture, sprite.pixelated);
+            self.batches.push(SpriteBatch {
+                key,
+                instances: i as u32..i as u32 + 1,
+            });
+        }
+
+        // Textures of sprites that were removed are no longer kept alive
+        let batches = &self.batches;
+        self.texture_bind_groups
+            .retain(|key, _| batches.iter().any(|v| v.key == *key));
+
+        if instances.len() > self.instances.len() {
+            self.instances
+                .resize(ctx.gpu, instances.len().next_power_of_two(), false);
+        }
+
+        if self.instance_buffer_gen != self.instances.gen() {
+            self.instance_buffer_gen = self.instances.gen();
+            self.instance_bind_group = None;
+        }
+
+        self.instances.write(ctx.queue, 0, &instances);
+
+        Ok(())
+    }
+
+    fn draw<'s>(
+        &'s mut self,
+        ctx: &'s RenderContext<'s>,
+        render_pass: &mut RenderPass<'s>,
+    ) -> anyhow::Result<()> {
+        profile_function!();
+
+        if self.batches.is_empty() {
+            return Ok(());
+        }
+
+        let shader = self.shader.get_or_insert_with(|| {
+            let shader_module = ctx
+                .gpu
+                .device
+                .create_shader_module(wgpu::ShaderModuleDescriptor {
+                    label: Some("sprite"),
+                    source: wgpu::ShaderSource::Wgsl(
+                        include_str!("../../shaders/sprite.wgsl").into(),
+                    ),
+                });
+
+            RenderShader::new(
+                ctx.gpu,
+                &ShaderDesc::new("sprite", &shader_module, &ctx.target_desc)
+                    .with_bind_group_layouts(&[
+                        ctx.layouts[0],
+                        &self.instance_layout,
+                        &self.texture_layout,
+                    ])
+                    .with_depth_write(false)
+                    .with_culling_mode(Culling {
+                        cull_mode: None,
+                        front_face: wgpu::FrontFace::Ccw,
+                    }),
+            )
+        });
+
+        let instance_bind_group = self.instance_bind_group.get_or_insert_with(|| {
+            BindGroupBuilder::new("sprite_instances")
+                .bind_buffer(&self.instances)
+                .build(ctx.gpu, &self.instance_layout)
+        });
+
+        render_pass.set_pipeline(shader.pipeline());
+        render_pass.set_bind_group(0, ctx.bind_groups[0], &[]);
+        render_pass.set_bind_group(1, instance_bind_group, &[]);
+
+        for batch in &self.batches {
+            render_pass.set_bind_group(2, &self.texture_bind_groups[&batch.key], &[]);
+            render_pass.draw(0..6, batch.instances.clone());
+            self.stats += RenderStats::new(1, batch.instances.len() as u64 * 2);
+        }
+
+        Ok(())
+    }
+
+    fn take_stats(&mut self) -> RenderStats {
+        std::mem::take(&mut self.stats)
+    }
+}
+
+#[repr(C)]
+#[derive(Pod, Zeroable, Clone, Copy, Debug)]
+struct SpriteInstance {
+    world: Mat4,
+    color: Vec4,
+    uv_min: Vec2,
+    uv_max: Vec2,
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::FRAC_PI_2;
+
+    use glam::vec2;
+
+    use super::*;
+
+    #[test]
+    fn quad_anchor() {
+        let centered = quad_transform(vec2(2.0, 4.0), Vec2::splat(0.5), 0.0);
+        assert_eq!(centered.transform_point3(Vec3::ZERO), Vec3::ZERO);
+        assert_eq!(
+            centered.transform_point3(Vec3::new(0.5, 0.5, 0.0)),
+            Vec3::new(1.0, 2.0, 0.0)
+        );
+
+        // The bottom left corner lies at the origin
+        let bottom_left = quad_transform(vec2(2.0, 4.0), Vec2::ZERO, 0.0);
+        assert_eq!(
+            bottom_left.transform_point3(Vec3::new(-0.5, -0.5, 0.0)),
+            Vec3::ZERO
+        );
+        assert_eq!(
+            bottom_left.transform_point3(Vec3::new(0.5, 0.5, 0.0)),
+            Vec3::new(2.0, 4.0, 0.0)
+        );
+    }
+
+    #[test]
+    fn quad_rotation() {
+        let rotated = quad_transform(Vec2::ONE, Vec2::ZERO, FRAC_PI_2);
+        let corner = rotated.transform_point3(Vec3::new(0.5, -0.5, 0.0));
+
+        assert!(
+            corner.abs_diff_eq(Vec3::new(0.0, 1.0, 0.0), 1e-5),
+            "{corner}"
+        );
+    }
+}
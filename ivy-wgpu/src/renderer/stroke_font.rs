@@ -0,0 +1,272 @@
+//! Minimal vector font for text gizmos.
+//!
+//! Glyphs are polylines on a grid 4 units wide and 6 units tall, with the origin at
+//! the bottom left. Lowercase letters are drawn as uppercase.
+
+use glam::Vec2;
+
+type Glyph = &'static [&'static [(i8, i8)]];
+
+const GLYPH_WIDTH: f32 = 4.0;
+const GLYPH_HEIGHT: f32 = 6.0;
+const ADVANCE: f32 = 6.0;
+const LINE_HEIGHT: f32 = 9.0;
+
+const O: &[(i8, i8)] = &[
+    (1, 0),
+    (0, 1),
+    (0, 5),
+    (1, 6),
+    (3, 6),
+    (4, 5),
+    (4, 1),
+    (3, 0),
+    (1, 0),
+];
+const P: &[(i8, i8)] = &[(0, 0), (0, 6), (3, 6), (4, 5), (4, 4), (3, 3), (0, 3)];
+const BOX: Glyph = &[&[(0, 0), (0, 6), (4, 6), (4, 0), (0, 0)]];
+
+fn glyph(c: char) -> Glyph {
+    match c.to_ascii_uppercase() {
+        ' ' => &[],
+        'A' => &[&[(0, 0), (0, 4), (2, 6), (4, 4), (4, 0)], &[(0, 3), (4, 3)]],
+        'B' => &[
+            &[(0, 0), (0, 6), (3, 6), (4, 5), (4, 4), (3, 3), (0, 3)],
+            &[(3, 3), (4, 2), (4, 1), (3, 0), (0, 0)],
+        ],
+        'C' => &[&[
+            (4, 5),
+            (3, 6),
+            (1, 6),
+            (0, 5),
+            (0, 1),
+            (1, 0),
+            (3, 0),
+            (4, 1),
+        ]],
+        'D' => &[&[(0, 0), (0, 6), (2, 6), (4, 4), (4, 2), (2, 0), (0, 0)]],
+        'E' => &[&[(4, 6), (0, 6), (0, 0), (4, 0)], &[(0, 3), (3, 3)]],
+        'F' => &[&[(4, 6), (0, 6), (0, 0)], &[(0, 3), (3, 3)]],
+        'G' => &[&[
+            (4, 5),
+            (3, 6),
+            (1, 6),
+            (0, 5),
+            (0, 1),
+            (1, 0),
+            (3, 0),
+            (4, 1),
+            (4, 3),
+            (2, 3),
+        ]],
+        'H' => &[&[(0, 0), (0, 6)], &[(4, 0), (4, 6)], &[(0, 3), (4, 3)]],
+        'I' => &[&[(1, 6), (3, 6)], &[(2, 6), (2, 0)], &[(1, 0), (3, 0)]],
+        'J' => &[&[(4, 6), (4, 1), (3, 0), (1, 0), (0, 1)]],
+        'K' => &[&[(0, 0), (0, 6)], &[(4, 6), (0, 2)], &[(1, 3), (4, 0)]],
+        'L' => &[&[(0, 6), (0, 0), (4, 0)]],
+        'M' => &[&[(0, 0), (0, 6), (2, 3), (4, 6), (4, 0)]],
+        'N' => &[&[(0, 0), (0, 6), (4, 0), (4, 6)]],
+        'O' => &[O],
+        'P' => &[P],
+        'Q' => &[O, &[(2, 2), (4, 0)]],
+        'R' => &[P, &[(2, 3), (4, 0)]],
+        'S' => &[&[
+            (4, 5),
+            (3, 6),
+            (1, 6),
+            (0, 5),
+            (0, 4),
+            (1, 3),
+            (3, 3),
+            (4, 2),
+            (4, 1),
+            (3, 0),
+            (1, 0),
+            (0, 1),
+        ]],
+        'T' => &[&[(0, 6), (4, 6)], &[(2, 6), (2, 0)]],
+        'U' => &[&[(0, 6), (0, 1), (1, 0), (3, 0), (4, 1), (4, 6)]],
+        'V' => &[&[(0, 6), (2, 0), (4, 6)]],
+        'W' => &[&[(0, 6), (1, 0), (2, 3), (3, 0), (4, 6)]],
+        'X' => &[&[(0, 0), (4, 6)], &[(0, 6), (4, 0)]],
+        'Y' => &[&[(0, 6), (2, 3), (4, 6)], &[(2, 3), (2, 0)]],
+        'Z' => &[&[(0, 6), (4, 6), (0, 0), (4, 0)]],
+        '0' => &[O, &[(0, 1), (4, 5)]],
+        '1' => &[&[(1, 5), (2, 6), (2, 0)], &[(1, 0), (3, 0)]],
+        '2' => &[&[(0, 5), (1, 6), (3, 6), (4, 5), (4, 4), (0, 0), (4, 0)]],
+        '3' => &[
+            &[(0, 5), (1, 6), (3, 6), (4, 5), (4, 4), (3, 3), (1, 3)],
+            &[(3, 3), (4, 2), (4, 1), (3, 0), (1, 0), (0, 1)],
+        ],
+        '4' => &[&[(3, 0), (3, 6), (0, 2), (4, 2)]],
+        '5' => &[&[
+            (4, 6),
+            (0, 6),
+            (0, 3),
+            (3, 3),
+            (4, 2),
+            (4, 1),
+            (3, 0),
+            (0, 0),
+        ]],
+        '6' => &[&[
+            (3, 6),
+            (1, 6),
+            (0, 5),
+            (0, 1),
+            (1, 0),
+            (3, 0),
+            (4, 1),
+            (4, 2),
+            (3, 3),
+            (0, 3),
+        ]],
+        '7' => &[&[(0, 6), (4, 6), (1, 0)]],
+        '8' => &[
+            &[
+                (1, 3),
+                (0, 4),
+                (0, 5),
+                (1, 6),
+                (3, 6),
+                (4, 5),
+                (4, 4),
+                (3, 3),
+            ],
+            &[
+                (1, 3),
+                (0, 2),
+                (0, 1),
+                (1, 0),
+                (3, 0),
+                (4, 1),
+                (4, 2),
+                (3, 3),
+                (1, 3),
+            ],
+        ],
+        '9' => &[&[
+            (4, 3),
+            (1, 3),
+            (0, 4),
+            (0, 5),
+            (1, 6),
+            (3, 6),
+            (4, 5),
+            (4, 1),
+            (3, 0),
+            (1, 0),
+        ]],
+        '.' => &[&[(2, 0), (2, 1)]],
+        ',' => &[&[(2, 1), (1, -1)]],
+        ':' => &[&[(2, 1), (2, 2)], &[(2, 4), (2, 5)]],
+        ';' => &[&[(2, 2), (1, 0)], &[(2, 4), (2, 5)]],
+        '-' => &[&[(1, 3), (3, 3)]],
+        '+' => &[&[(0, 3), (4, 3)], &[(2, 1), (2, 5)]],
+        '=' => &[&[(0, 2), (4, 2)], &[(0, 4), (4, 4)]],
+        '*' => &[&[(2, 1), (2, 5)], &[(0, 2), (4, 4)], &[(0, 4), (4, 2)]],
+        '_' => &[&[(0, 0), (4, 0)]],
+        '/' => &[&[(0, 0), (4, 6)]],
+        '|' => &[&[(2, 0), (2, 6)]],
+        '%' => &[&[(0, 0), (4, 6)], &[(0, 6), (0, 5)], &[(4, 0), (4, 1)]],
+        '#' => &[
+            &[(1, 0), (1, 6)],
+            &[(3, 0), (3, 6)],
+            &[(0, 2), (4, 2)],
+            &[(0, 4), (4, 4)],
+        ],
+        '(' => &[&[(3, 6), (1, 4), (1, 2), (3, 0)]],
+        ')' => &[&[(1, 6), (3, 4), (3, 2), (1, 0)]],
+        '[' => &[&[(3, 6), (1, 6), (1, 0), (3, 0)]],
+        ']' => &[&[(1, 6), (3, 6), (3, 0), (1, 0)]],
+        '<' => &[&[(4, 6), (0, 3), (4, 0)]],
+        '>' => &[&[(0, 6), (4, 3), (0, 0)]],
+        '!' => &[&[(2, 6), (2, 2)], &[(2, 0), (2, 1)]],
+        '?' => &[
+            &[(0, 5), (1, 6), (3, 6), (4, 5), (4, 4), (2, 3), (2, 2)],
+            &[(2, 0), (2, 1)],
+        ],
+        '\'' => &[&[(2, 6), (2, 4)]],
+        '"' => &[&[(1, 6), (1, 4)], &[(3, 6), (3, 4)]],
+        _ => BOX,
+    }
+}
+
+/// Returns the line segments of `text`, centered on the origin and scaled so that letters are
+/// one unit tall.
+pub fn layout(text: &str) -> impl Iterator<Item = (Vec2, Vec2)> + '_ {
+    let lines = text.lines().count().max(1);
+    let height = (lines - 1) as f32 * LINE_HEIGHT + GLYPH_HEIGHT;
+
+    text.lines().enumerate().flat_map(move |(row, line)| {
+        let chars = line.chars().count();
+        let width = chars.saturating_sub(1) as f32 * ADVANCE + GLYPH_WIDTH;
+
+        let offset = Vec2::new(
+            -width / 2.0,
+            height / 2.0 - GLYPH_HEIGHT - row as f32 * LINE_HEIGHT,
+        );
+
+        line.chars().enumerate().flat_map(move |(col, c)| {
+            let origin = offset + Vec2::X * col as f32 * ADVANCE;
+            glyph(c).iter().flat_map(move |stroke| {
+                stroke.windows(2).map(move |w| {
+                    let point =
+                        |(x, y): (i8, i8)| (origin + Vec2::new(x as f32, y as f32)) / GLYPH_HEIGHT;
+
+                    (point(w[0]), point(w[1]))
+                })
+            })
+        })
+    })
+}
+
+/// Returns the width and height of `text` laid out by [`layout`]
+pub fn size(text: &str) -> Vec2 {
+    let lines = text.lines().count().max(1);
+    let chars = text.lines().map(|v| v.chars().count()).max().unwrap_or(0);
+
+    Vec2::new(
+        chars.saturating_sub(1) as f32 * ADVANCE + GLYPH_WIDTH,
+        (lines - 1) as f32 * LINE_HEIGHT + GLYPH_HEIGHT,
+    ) / GLYPH_HEIGHT
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn layout_is_centered() {
+        let (min, max) = layout("AB\nCD")
+            .flat_map(|(a, b)| [a, b])
+            .fold((Vec2::MAX, Vec2::MIN), |(min, max), p| {
+                (min.min(p), max.max(p))
+            });
+
+        assert!((min + max).abs().max_element() < 1e-5, "{min} {max}");
+        assert_eq!(layout("  ").count(), 0);
+    }
+
+    #[test]
+    fn text_size() {
+        assert_eq!(size(""), Vec2::new(GLYPH_WIDTH / GLYPH_HEIGHT, 1.0));
+        assert_eq!(size("A"), Vec2::new(GLYPH_WIDTH / GLYPH_HEIGHT, 1.0));
+
+        let size = size("ABC\nD");
+        assert_eq!(
+            size * GLYPH_HEIGHT,
+            Vec2::new(2.0 * ADVANCE + GLYPH_WIDTH, LINE_HEIGHT + GLYPH_HEIGHT)
+        );
+
+        // The layout fits within the size
+        let (min, max) = layout("ABC\nD")
+            .flat_map(|(a, b)| [a, b])
+            .fold((Vec2::MAX, Vec2::MIN), |(min, max), p| {
+                (min.min(p), max.max(p))
+            });
+
+        assert!(min.cmpge(-size * 0.5 - 1e-5).all(), "{min} {size}");
+        assert!(max.cmple(size * 0.5 + 1e-5).all(), "{max} {size}");
+    }
+}
@@ -0,0 +1,89 @@
+use wgpu::{BindGroup, CommandEncoder, ComputePassDescriptor, ComputePipeline, Extent3d};
+
+/// Returns the number of workgroups required to cover `size` invocations
+pub fn workgroup_count(size: u32, workgroup_size: u32) -> u32 {
+    size.div_ceil(workgroup_size)
+}
+
+/// Returns the workgroups required to cover every texel of `extent`
+pub fn workgroups_for_extent(extent: Extent3d, workgroup_size: (u32, u32)) -> [u32; 3] {
+    [
+        workgroup_count(extent.width, workgroup_size.0),
+        workgroup_count(extent.height, workgroup_size.1),
+        extent.depth_or_array_layers,
+    ]
+}
+
+/// Describes a single compute dispatch.
+///
+/// Bind groups are bound in order, starting at group 0.
+pub struct ComputeDispatch<'a> {
+    label: &'a str,
+    pipeline: &'a ComputePipeline,
+    bind_groups: &'a [&'a BindGroup],
+    workgroups: [u32; 3],
+}
+
+impl<'a> ComputeDispatch<'a> {
+    pub fn new(label: &'a str, pipeline: &'a ComputePipeline) -> Self {
+        Self {
+            label,
+            pipeline,
+            bind_groups: &[],
+            workgroups: [1, 1, 1],
+        }
+    }
+
+    /// Set the bind groups
+    pub fn with_bind_groups(mut self, bind_groups: &'a [&'a BindGroup]) -> Self {
+        self.bind_groups = bind_groups;
+        self
+    }
+
+    /// Set the number of workgroups to dispatch
+    pub fn with_workgroups(mut self, workgroups: [u32; 3]) -> Self {
+        self.workgroups = workgroups;
+        self
+    }
+
+    /// Records the dispatch in its own compute pass
+    pub fn dispatch(&self, encoder: &mut CommandEncoder) {
+        let [x, y, z] = self.workgroups;
+        if x == 0 || y == 0 || z == 0 {
+            return;
+        }
+
+        let mut compute_pass = encoder.begin_compute_pass(&ComputePassDescriptor {
+            label: Some(self.label),
+            timestamp_writes: None,
+        });
+
+        compute_pass.set_pipeline(self.pipeline);
+        for (i, bind_group) in self.bind_groups.iter().enumerate() {
+            compute_pass.set_bind_group(i as u32, bind_group, &[]);
+        }
+
+        compute_pass.dispatch_workgroups(x, y, z);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn workgroups() {
+        assert_eq!(workgroup_count(0, 64), 0);
+        assert_eq!(workgroup_count(1, 64), 1);
+        assert_eq!(workgroup_count(64, 64), 1);
+        assert_eq!(workgroup_count(65, 64), 2);
+
+        let extent = Extent3d {
+            width: 1920,
+            height: 1080,
+            depth_or_array_layers: 1,
+        };
+
+        assert_eq!(workgroups_for_extent(extent, (16, 16)), [120, 68, 1]);
+    }
+}
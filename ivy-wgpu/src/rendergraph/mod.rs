@@ -1,17 +1,24 @@
+mod compute;
 mod resources;
 use std::{
-    collections::{BTreeSet, HashMap},
+    collections::{BTreeSet, HashMap, HashSet},
     mem,
 };
 
+pub use compute::*;
 use flax::World;
 use itertools::Itertools;
 use ivy_assets::{stored::DynamicStore, AssetCache};
-use ivy_core::profiling::{profile_function, profile_scope};
+use ivy_core::{
+    components::{engine, scope_timings},
+    profiling::{profile_function, profile_scope},
+};
 use ivy_wgpu_types::Gpu;
 pub use resources::*;
 use slotmap::{new_key_type, SecondaryMap, SlotMap};
-use wgpu::{Buffer, BufferUsages, CommandEncoder, Queue, Texture, TextureUsages};
+use wgpu::{
+    Buffer, BufferUsages, CommandEncoder, CommandEncoderDescriptor, Queue, Texture, TextureUsages,
+};
 
 pub struct NodeExecutionContext<'a> {
     pub gpu: &'a Gpu,
@@ -81,6 +88,14 @@ pub trait Node: 'static {
 
     fn read_dependencies(&self) -> Vec<Dependency>;
     fn write_dependencies(&self) -> Vec<Dependency>;
+
+    /// Returns true if the node only records compute work, and may be submitted ahead of the
+    /// graphics work of the frame.
+    ///
+    /// See [`RenderGraph::set_async_compute`].
+    fn is_async_compute(&self) -> bool {
+        false
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -140,6 +155,16 @@ impl Dependency {
         Self::Buffer { handle, usage }
     }
 
+    /// A buffer bound as a storage buffer in a shader
+    pub fn storage_buffer(handle: BufferHandle) -> Self {
+        Self::buffer(handle, BufferUsages::STORAGE)
+    }
+
+    /// A texture bound as a storage texture in a shader
+    pub fn storage_texture(handle: TextureHandle) -> Self {
+        Self::texture(handle, TextureUsages::STORAGE_BINDING)
+    }
+
     pub fn as_handle(&self) -> ResourceHandle {
         match self {
             Dependency::Texture { handle, .. } => (*handle).into(),
@@ -155,6 +180,9 @@ new_key_type! {
 pub struct RenderGraph {
     nodes: SlotMap<NodeId, Box<dyn Node>>,
     order: Option<Vec<NodeId>>,
+    /// Compute nodes which do not depend on any graphics work, and are submitted first
+    async_nodes: HashSet<NodeId>,
+    async_compute: bool,
     expected_lifetimes: HashMap<ResourceHandle, Lifetime>,
 
     resource_to_nodes: HashMap<ResourceHandle, BTreeSet<NodeId>>,
@@ -166,6 +194,8 @@ impl RenderGraph {
         Self {
             nodes: Default::default(),
             order: None,
+            async_nodes: Default::default(),
+            async_compute: false,
             expected_lifetimes: Default::default(),
             resource_to_nodes: Default::default(),
             resources,
@@ -182,6 +212,16 @@ impl RenderGraph {
         self.nodes.remove(node_id)
     }
 
+    /// Submit independent compute nodes separately, ahead of the graphics work of the frame.
+    ///
+    /// wgpu exposes a single queue, so this does not use a dedicated compute queue. Instead,
+    /// compute nodes which do not depend on graphics work are recorded into their own command
+    /// buffer, which allows drivers to overlap it with the raster work of the frame.
+    pub fn set_async_compute(&mut self, enabled: bool) {
+        self.order = None;
+        self.async_compute = enabled;
+    }
+
     fn allocate_resources(&mut self, gpu: &Gpu) -> anyhow::Result<()> {
         self.resources
             .allocate_textures(&self.nodes, gpu, &self.expected_lifetimes)?;
@@ -231,9 +271,14 @@ impl RenderGraph {
             .iter()
             .flat_map(|(node_id, node)| {
                 let writes = &writes;
+                let resources = &self.resources;
                 node.read_dependencies().into_iter().filter_map(move |v| {
                     let Some(&write_idx) = writes.get(&v.as_handle()) else {
-                        tracing::warn!("No corresponding write found for dependency: {v:?}");
+                        // Written during the previous frame
+                        if !resources.is_history(v.as_handle()) {
+                            tracing::warn!("No corresponding write found for dependency: {v:?}");
+                        }
+
                         return None;
                     };
 
@@ -247,12 +292,34 @@ impl RenderGraph {
             dependency_levels,
         } = topo_sort(&self.nodes, &dependencies);
 
+        self.async_nodes.clear();
+        if self.async_compute {
+            // Dependencies have lower levels, and are hence visited first
+            for &node in &order {
+                let independent = dependencies
+                    .get(&node)
+                    .into_iter()
+                    .flatten()
+                    .all(|dep| self.async_nodes.contains(dep));
+
+                if independent && self.nodes[node].is_async_compute() {
+                    self.async_nodes.insert(node);
+                }
+            }
+        }
+
         self.expected_lifetimes.clear();
 
         for (resource, node) in writes {
             let reads = reads.get(&resource).map(Vec::as_slice).unwrap_or_default();
 
-            let open = dependency_levels[node];
+            // Async compute executes before all graphics work, so its outputs may not be aliased
+            // with the resources of any earlier level
+            let open = if self.async_nodes.contains(&node) {
+                0
+            } else {
+                dependency_levels[node]
+            };
             let close = reads
                 .iter()
                 .map(|&node| {
@@ -276,8 +343,8 @@ impl RenderGraph {
     fn rebuild(&mut self, gpu: &Gpu) -> anyhow::Result<()> {
         self.build()?;
 
-        self.invoke_on_resource_modified();
         self.allocate_resources(gpu)?;
+        self.invoke_on_resource_modified();
 
         Ok(())
     }
@@ -297,10 +364,8 @@ impl RenderGraph {
         }
 
         if mem::take(&mut self.resources.dirty) {
-            self.invoke_on_resource_modified();
             self.allocate_resources(gpu)?;
-
-            self.resources.modified_resources.clear();
+            self.invoke_on_resource_modified();
         }
 
         let order = self.order.as_ref().unwrap();
@@ -350,9 +415,44 @@ impl RenderGraph {
             anyhow::bail!("update must be called before draw");
         };
 
-        for &idx in order {
+        let timings = world.get(engine(), scope_timings()).ok().map(|v| v.clone());
+
+        if !self.async_nodes.is_empty() {
+            profile_scope!("async_compute");
+            let mut compute_encoder =
+                gpu.device
+                    .create_command_encoder(&CommandEncoderDescriptor {
+                        label: Some("async_compute"),
+                    });
+
+            for &idx in order.iter().filter(|v| self.async_nodes.contains(v)) {
+                let node = &mut self.nodes[idx];
+                profile_scope!("compute_node", node.label());
+                let _scope = timings
+                    .as_ref()
+                    .map(|v| v.scope(format!("render.{}", node.label())));
+
+                node.draw(NodeExecutionContext {
+                    gpu,
+                    resources: &self.resources,
+                    queue,
+                    encoder: &mut compute_encoder,
+                    assets,
+                    world,
+                    store,
+                    external_resources,
+                })?;
+            }
+
+            queue.submit([compute_encoder.finish()]);
+        }
+
+        for &idx in order.iter().filter(|v| !self.async_nodes.contains(v)) {
             let node = &mut self.nodes[idx];
             profile_scope!("render_node", node.label());
+            let _scope = timings
+                .as_ref()
+                .map(|v| v.scope(format!("render.{}", node.label())));
 
             node.draw(NodeExecutionContext {
                 gpu,
@@ -366,11 +466,14 @@ impl RenderGraph {
             })?;
         }
 
+        self.resources.swap_history();
+        self.invoke_on_resource_modified();
+
         Ok(())
     }
 
     fn invoke_on_resource_modified(&mut self) {
-        for &modified in self.resources.modified_resources.iter() {
+        for modified in mem::take(&mut self.resources.modified_resources) {
             self.resource_to_nodes
                 .get(&modified)
                 .iter()
@@ -460,6 +563,10 @@ fn topo_sort(
         );
     }
 
+    // Resources are aliased by dependency level, so all nodes of a level must execute before the
+    // next
+    result.sort_by_key(|&v| dependency_levels[v]);
+
     TopoResult {
         order: result,
         dependency_levels,
@@ -480,8 +587,8 @@ mod test {
 
     use crate::{
         rendergraph::{
-            BufferDesc, BufferHandle, Dependency, ExternalResources, ManagedTextureDesc, Node,
-            NodeExecutionContext, RenderGraph, RenderGraphResources, TextureHandle,
+            BufferDesc, BufferHandle, Dependency, ExternalResources, Lifetime, ManagedTextureDesc,
+            Node, NodeExecutionContext, RenderGraph, RenderGraphResources, TextureHandle,
         },
         shader_library::ShaderLibrary,
     };
@@ -730,4 +837,178 @@ mod test {
 
         assert_eq!(mapped[5], 10u8);
     }
+
+    #[test]
+    fn async_compute() {
+        struct Simulate {
+            particles: BufferHandle,
+        }
+
+        impl Node for Simulate {
+            fn draw(&mut self, _: NodeExecutionContext) -> anyhow::Result<()> {
+                Ok(())
+            }
+
+            fn on_resource_changed(&mut self, _: super::ResourceHandle) {}
+
+            fn read_dependencies(&self) -> Vec<Dependency> {
+                vec![]
+            }
+
+            fn write_dependencies(&self) -> Vec<Dependency> {
+                vec![Dependency::storage_buffer(self.particles)]
+            }
+
+            fn is_async_compute(&self) -> bool {
+                true
+            }
+        }
+
+        struct DrawParticles {
+            particles: BufferHandle,
+            output: TextureHandle,
+        }
+
+        impl Node for DrawParticles {
+            fn draw(&mut self, _: NodeExecutionContext) -> anyhow::Result<()> {
+                Ok(())
+            }
+
+            fn on_resource_changed(&mut self, _: super::ResourceHandle) {}
+
+            fn read_dependencies(&self) -> Vec<Dependency> {
+                vec![Dependency::storage_buffer(self.particles)]
+            }
+
+            fn write_dependencies(&self) -> Vec<Dependency> {
+                vec![Dependency::texture(
+                    self.output,
+                    TextureUsages::RENDER_ATTACHMENT,
+                )]
+            }
+        }
+
+        /// Compute which depends on the raster work
+        struct Histogram {
+            input: TextureHandle,
+            output: BufferHandle,
+        }
+
+        impl Node for Histogram {
+            fn draw(&mut self, _: NodeExecutionContext) -> anyhow::Result<()> {
+                Ok(())
+            }
+
+            fn on_resource_changed(&mut self, _: super::ResourceHandle) {}
+
+            fn read_dependencies(&self) -> Vec<Dependency> {
+                vec![Dependency::texture(
+                    self.input,
+                    TextureUsages::TEXTURE_BINDING,
+                )]
+            }
+
+            fn write_dependencies(&self) -> Vec<Dependency> {
+                vec![Dependency::storage_buffer(self.output)]
+            }
+
+            fn is_async_compute(&self) -> bool {
+                true
+            }
+        }
+
+        let mut render_graph =
+            RenderGraph::new(RenderGraphResources::new(Arc::new(ShaderLibrary::new())));
+
+        let particles = render_graph.resources.insert_buffer(BufferDesc {
+            label: "particles".into(),
+            size: 1024,
+            usage: BufferUsages::STORAGE,
+        });
+
+        let histogram = render_graph.resources.insert_buffer(BufferDesc {
+            label: "histogram".into(),
+            size: 1024,
+            usage: BufferUsages::STORAGE,
+        });
+
+        let output = render_graph.resources.insert_texture(ManagedTextureDesc {
+            label: "output".into(),
+            extent: Extent3d {
+                width: 1,
+                height: 1,
+                depth_or_array_layers: 1,
+            },
+            dimension: TextureDimension::D2,
+            format: TextureFormat::Rgba8Unorm,
+            mip_level_count: 1,
+            sample_count: 1,
+            persistent: false,
+        });
+
+        let draw = render_graph.add_node(DrawParticles { particles, output });
+        let histogram_node = render_graph.add_node(Histogram {
+            input: output,
+            output: histogram,
+        });
+        let simulate = render_graph.add_node(Simulate { particles });
+
+        render_graph.build().unwrap();
+        assert!(render_graph.async_nodes.is_empty());
+
+        render_graph.set_async_compute(true);
+        render_graph.build().unwrap();
+
+        assert_eq!(
+            render_graph.async_nodes,
+            [simulate].into_iter().collect(),
+            "only compute independent of the raster work is submitted ahead"
+        );
+        assert_eq!(
+            render_graph.order.as_deref(),
+            Some(&[simulate, draw, histogram_node][..])
+        );
+
+        // Async outputs live from the start of the frame
+        assert_eq!(
+            render_graph.expected_lifetimes[&particles.into()],
+            Lifetime::new(0, 2)
+        );
+    }
+
+    #[test]
+    fn history_swap() {
+        let mut resources = RenderGraphResources::new(Arc::new(ShaderLibrary::new()));
+
+        let history = resources.insert_history_texture(ManagedTextureDesc {
+            label: "history".into(),
+            extent: Extent3d {
+                width: 1,
+                height: 1,
+                depth_or_array_layers: 1,
+            },
+            dimension: TextureDimension::D2,
+            format: TextureFormat::R32Float,
+            mip_level_count: 1,
+            sample_count: 1,
+            persistent: false,
+        });
+
+        assert!(resources.is_history(history.current.into()));
+        assert!(resources.is_history(history.previous.into()));
+        assert!(!resources.history_valid(history));
+        assert_eq!(resources.history_parity(history), 0);
+
+        resources.swap_history();
+        assert!(resources.history_valid(history));
+        assert_eq!(resources.history_parity(history), 1);
+        // Nodes cache a bind group per parity rather than being notified each frame
+        assert!(resources.modified_resources.is_empty());
+
+        resources.swap_history();
+        assert_eq!(resources.history_parity(history), 0);
+
+        resources.remove_history_texture(history);
+        assert!(!resources.is_history(history.current.into()));
+    }
 }
@@ -4,6 +4,7 @@ use std::{
     sync::Arc,
 };
 
+use ivy_assets::Asset;
 use ivy_wgpu_types::Gpu;
 use slotmap::{SecondaryMap, SlotMap};
 use wgpu::{
@@ -39,6 +40,9 @@ slotmap::new_key_type! {
 pub enum TextureDesc {
     External,
     Managed(ManagedTextureDesc),
+    /// Texture owned outside of the render graph which outlives the frame, such as a
+    /// [`CameraTarget`](crate::camera_target::CameraTarget) sampled by materials
+    Imported(Asset<Texture>),
 }
 
 impl From<ManagedTextureDesc> for TextureDesc {
@@ -81,6 +85,29 @@ pub struct ManagedTextureDesc {
     pub persistent: bool,
 }
 
+/// A texture which retains what was written to it during the previous frame.
+///
+/// Backed by two textures which are swapped at the end of each frame, so that `previous` holds
+/// the contents `current` had when the last frame finished. Nodes write to `current` and may
+/// read `previous` at any point of the frame.
+///
+/// The description of `current` is used for both textures. Swapping does not notify the nodes,
+/// as that would recreate their bind groups every frame. Nodes instead keep one bind group for
+/// each [`RenderGraphResources::history_parity`], and are notified through
+/// [`Node::on_resource_changed`] only when the textures are reallocated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct HistoryTexture {
+    pub current: TextureHandle,
+    pub previous: TextureHandle,
+}
+
+struct HistoryState {
+    texture: HistoryTexture,
+    /// `previous` holds a rendered frame
+    valid: bool,
+    parity: usize,
+}
+
 pub struct BufferDesc {
     pub label: Cow<'static, str>,
     pub size: u64,
@@ -90,9 +117,10 @@ pub struct BufferDesc {
 
 type BucketId = usize;
 
+/// A single allocation shared by resources with non-overlapping lifetimes
 struct Bucket<Handle, Data: SubResource> {
     desc: Data::Desc,
-    data: Data,
+    data: Option<Data>,
     handles: Vec<Handle>,
     lifetimes: Vec<Lifetime>,
 }
@@ -105,7 +133,12 @@ impl<Handle, T: SubResource> Bucket<Handle, T> {
 
 trait SubResource: std::fmt::Debug {
     type Desc: Clone;
+    /// Returns true if both resources can share an allocation once their descriptors are merged
     fn is_compatible(desc: &Self::Desc, other: &Self::Desc) -> bool;
+    /// Extends `desc` to also satisfy `other`, such as by combining usages.
+    ///
+    /// Returns true if `desc` was changed.
+    fn merge(desc: &mut Self::Desc, other: &Self::Desc) -> bool;
     fn is_persistent(desc: &Self::Desc) -> bool;
     fn create(gpu: &Gpu, desc: Self::Desc) -> Self;
 }
@@ -126,7 +159,13 @@ impl SubResource for Texture {
             && inner.format == other.desc.format
             && inner.mip_level_count == other.desc.mip_level_count
             && inner.sample_count == other.desc.sample_count
-            && desc.usage == other.usage
+    }
+
+    fn merge(desc: &mut Self::Desc, other: &Self::Desc) -> bool {
+        let usage = desc.usage | other.usage;
+        let changed = usage != desc.usage;
+        desc.usage = usage;
+        changed
     }
 
     fn is_persistent(desc: &Self::Desc) -> bool {
@@ -159,7 +198,18 @@ impl SubResource for Buffer {
     type Desc = AllocatedBufferDescriptor;
 
     fn is_compatible(desc: &Self::Desc, other: &Self::Desc) -> bool {
+        const MAP: BufferUsages = BufferUsages::MAP_READ.union(BufferUsages::MAP_WRITE);
+
+        // Mappable buffers only allow copy usages, so they can not be combined with others
         desc.size == other.size
+            && (desc.usage == other.usage || !(desc.usage | other.usage).intersects(MAP))
+    }
+
+    fn merge(desc: &mut Self::Desc, other: &Self::Desc) -> bool {
+        let usage = desc.usage | other.usage;
+        let changed = usage != desc.usage;
+        desc.usage = usage;
+        changed
     }
 
     fn is_persistent(_desc: &Self::Desc) -> bool {
@@ -195,15 +245,59 @@ impl<Handle: slotmap::Key, Data: SubResource> ResourceAllocator<Handle, Data> {
     }
 
     fn get(&self, handle: Handle) -> Option<&Data> {
-        Some(&self.buckets[*self.bucket_map.get(handle)?].data)
+        self.buckets[*self.bucket_map.get(handle)?].data.as_ref()
     }
 
+    /// Exchanges the allocations of two resources
+    fn swap(&mut self, a: Handle, b: Handle) {
+        let (Some(&a_bucket), Some(&b_bucket)) = (self.bucket_map.get(a), self.bucket_map.get(b))
+        else {
+            return;
+        };
+
+        self.bucket_map.insert(a, b_bucket);
+        self.bucket_map.insert(b, a_bucket);
+    }
+
+    /// Assigns the resources to buckets, aliasing transient resources with compatible
+    /// descriptors whose lifetimes do not overlap.
+    ///
+    /// wgpu does not expose placing resources in shared memory, so aliased resources share the
+    /// same allocation instead.
     fn allocate_resources<I: Iterator<Item = (Handle, Data::Desc, Option<Lifetime>)>>(
         &mut self,
         gpu: &Gpu,
         resources: I,
         modified: &mut BTreeSet<ResourceHandle>,
     ) -> anyhow::Result<()>
+    where
+        Handle: Into<ResourceHandle>,
+    {
+        self.assign_buckets(resources, modified)?;
+
+        for bucket in &mut self.buckets {
+            if bucket.data.is_none() {
+                bucket.data = Some(Data::create(gpu, bucket.desc.clone()));
+                modified.extend(bucket.handles.iter().map(|&v| v.into()));
+            }
+        }
+
+        tracing::debug!(
+            allocations = self.buckets.len(),
+            resources = self.buckets.iter().map(|v| v.handles.len()).sum::<usize>(),
+            "Allocated render graph resources"
+        );
+
+        Ok(())
+    }
+
+    /// Assigns the resources to buckets without creating the allocations of new or changed
+    /// buckets
+    fn assign_buckets<I: Iterator<Item = (Handle, Data::Desc, Option<Lifetime>)>>(
+        &mut self,
+        resources: I,
+        modified: &mut BTreeSet<ResourceHandle>,
+    ) -> anyhow::Result<()>
     where
         Handle: Into<ResourceHandle>,
     {
@@ -214,12 +308,18 @@ impl<Handle: slotmap::Key, Data: SubResource> ResourceAllocator<Handle, Data> {
 
         let mut missing_resources: BTreeSet<_> = self.bucket_map.keys().collect();
 
+        // Assigning in order of first use packs non-overlapping lifetimes into the fewest buckets
+        let mut resources = resources.collect::<Vec<_>>();
+        resources.sort_by_key(|(_, _, lifetime)| *lifetime);
+
         for (handle, desc, lifetime) in resources {
             missing_resources.remove(&handle);
 
             if Data::is_persistent(&desc) && self.bucket_map.contains_key(handle) {
+                let allocated = &self.allocated_desc[handle];
                 anyhow::ensure!(
-                    Data::is_compatible(&desc, &self.allocated_desc[handle]),
+                    Data::is_compatible(&desc, allocated)
+                        && !Data::merge(&mut allocated.clone(), &desc),
                     "persistent textures can not change allocation parameters"
                 );
 
@@ -238,20 +338,24 @@ impl<Handle: slotmap::Key, Data: SubResource> ResourceAllocator<Handle, Data> {
             let lifetime = lifetime.unwrap_or(Lifetime::new(0, u32::MAX));
 
             if let Some(bucket) = suitable_bucket {
+                // Recreated with the combined descriptor
+                if Data::merge(&mut bucket.desc, &desc) {
+                    bucket.data = None;
+                }
+
                 bucket.lifetimes.push(lifetime);
                 bucket.handles.push(handle);
             } else {
                 self.buckets.push(Bucket {
-                    desc: desc.clone(),
+                    desc,
                     lifetimes: vec![lifetime],
                     handles: vec![handle],
-                    data: Data::create(gpu, desc),
+                    data: None,
                 })
             }
         }
 
         self.buckets.retain(|v| !v.handles.is_empty());
-
         self.buckets.sort_by_key(|v| !Data::is_persistent(&v.desc));
 
         for missing in missing_resources {
@@ -295,6 +399,7 @@ pub struct RenderGraphResources {
 
     textures: SlotMap<TextureHandle, TextureDesc>,
     managed_texture_data: ResourceAllocator<TextureHandle, Texture>,
+    history: Vec<HistoryState>,
 
     buffers: SlotMap<BufferHandle, BufferDesc>,
     buffer_data: ResourceAllocator<BufferHandle, Buffer>,
@@ -309,6 +414,7 @@ impl RenderGraphResources {
             textures: Default::default(),
             buffers: Default::default(),
             managed_texture_data: ResourceAllocator::new(),
+            history: Vec::new(),
             buffer_data: ResourceAllocator::new(),
             modified_resources: Default::default(),
             shader_library,
@@ -325,6 +431,66 @@ impl RenderGraphResources {
         self.textures.remove(texture)
     }
 
+    /// Inserts a texture which keeps the contents of the previous frame.
+    ///
+    /// History textures are never aliased, but are cleared when reallocated, such as when resized.
+    pub fn insert_history_texture(&mut self, mut desc: ManagedTextureDesc) -> HistoryTexture {
+        desc.persistent = false;
+
+        let texture = HistoryTexture {
+            current: self.insert_texture(desc.clone()),
+            previous: self.insert_texture(desc),
+        };
+
+        self.history.push(HistoryState {
+            texture,
+            valid: false,
+            parity: 0,
+        });
+
+        texture
+    }
+
+    pub fn remove_history_texture(&mut self, texture: HistoryTexture) {
+        self.history.retain(|v| v.texture != texture);
+        self.remove_texture(texture.current);
+        self.remove_texture(texture.previous);
+    }
+
+    /// Returns true if `previous` holds the contents of the last frame.
+    ///
+    /// This is not the case for the first frame after the textures were (re)allocated.
+    pub fn history_valid(&self, texture: HistoryTexture) -> bool {
+        self.history.iter().any(|v| v.texture == texture && v.valid)
+    }
+
+    /// Alternates between `0` and `1` each frame, as the underlying textures of `current` and
+    /// `previous` are swapped.
+    pub fn history_parity(&self, texture: HistoryTexture) -> usize {
+        self.history
+            .iter()
+            .find(|v| v.texture == texture)
+            .map(|v| v.parity)
+            .unwrap_or_default()
+    }
+
+    pub(crate) fn is_history(&self, handle: ResourceHandle) -> bool {
+        self.history
+            .iter()
+            .any(|v| handle == v.texture.current.into() || handle == v.texture.previous.into())
+    }
+
+    /// Makes the current frame of each history texture the previous one
+    pub(crate) fn swap_history(&mut self) {
+        for state in &mut self.history {
+            let HistoryTexture { current, previous } = state.texture;
+            self.managed_texture_data.swap(current, previous);
+
+            state.valid = true;
+            state.parity ^= 1;
+        }
+    }
+
     pub fn get_texture_mut(&mut self, handle: TextureHandle) -> &mut TextureDesc {
         self.dirty = true;
         self.modified_resources.insert(handle.into());
@@ -339,6 +505,7 @@ impl RenderGraphResources {
     pub(super) fn get_texture_data(&self, key: TextureHandle) -> &Texture {
         match self.textures.get(key).unwrap() {
             TextureDesc::External => panic!("Must use external resources"),
+            TextureDesc::Imported(texture) => texture,
             TextureDesc::Managed(_) => match self.managed_texture_data.get(key) {
                 Some(v) => v,
                 None => {
@@ -388,10 +555,41 @@ impl RenderGraphResources {
                 }
             });
 
+        // Both textures of a history pair take turns being written and read
+        let mut history_source = SecondaryMap::new();
+        for state in &self.history {
+            let HistoryTexture { current, previous } = state.texture;
+            history_source.insert(previous, current);
+
+            let usage = usages
+                .get(current)
+                .copied()
+                .unwrap_or(TextureUsages::empty())
+                | usages
+                    .get(previous)
+                    .copied()
+                    .unwrap_or(TextureUsages::empty());
+
+            if !usage.is_empty() {
+                usages.insert(current, usage);
+                usages.insert(previous, usage);
+            }
+        }
+
+        let history_current = history_source.values().copied().collect::<BTreeSet<_>>();
+        let textures = &self.textures;
         let iter = self.textures.iter().filter_map(|(handle, desc)| {
-            let desc = desc.as_managed()?;
+            let desc = match history_source.get(handle) {
+                Some(&current) => textures[current].as_managed()?,
+                None => desc.as_managed()?,
+            };
 
-            let lf = lifetimes.get(&handle.into()).copied();
+            // History textures must retain their contents between frames
+            let lf = if history_source.contains_key(handle) || history_current.contains(&handle) {
+                None
+            } else {
+                lifetimes.get(&handle.into()).copied()
+            };
 
             let Some(&usage) = usages.get(handle) else {
                 tracing::warn!("no usages for {}", desc.label);
@@ -409,7 +607,18 @@ impl RenderGraphResources {
         });
 
         self.managed_texture_data
-            .allocate_resources(gpu, iter, &mut self.modified_resources)
+            .allocate_resources(gpu, iter, &mut self.modified_resources)?;
+
+        for state in &mut self.history {
+            let HistoryTexture { current, previous } = state.texture;
+            if self.modified_resources.contains(&current.into())
+                || self.modified_resources.contains(&previous.into())
+            {
+                state.valid = false;
+            }
+        }
+
+        Ok(())
     }
 
     pub(crate) fn allocate_buffers(
@@ -462,3 +671,179 @@ impl RenderGraphResources {
         &self.shader_library
     }
 }
+
+#[cfg(test)]
+mod test {
+    use std::collections::BTreeSet;
+
+    use slotmap::SlotMap;
+    use wgpu::{
+        Buffer, BufferUsages, Extent3d, Texture, TextureDimension, TextureFormat, TextureUsages,
+    };
+
+    use super::{
+        AllocatedBufferDescriptor, AllocatedTextureDescriptor, BufferHandle, Lifetime,
+        ManagedTextureDesc, ResourceAllocator, TextureHandle,
+    };
+
+    fn texture(format: TextureFormat, usage: TextureUsages) -> AllocatedTextureDescriptor {
+        AllocatedTextureDescriptor {
+            desc: ManagedTextureDesc {
+                label: "texture".into(),
+                extent: Extent3d {
+                    width: 64,
+                    height: 64,
+                    depth_or_array_layers: 1,
+                },
+                dimension: TextureDimension::D2,
+                format,
+                mip_level_count: 1,
+                sample_count: 1,
+                persistent: false,
+            },
+            usage,
+        }
+    }
+
+    fn buffer(usage: BufferUsages) -> AllocatedBufferDescriptor {
+        AllocatedBufferDescriptor {
+            label: "buffer".into(),
+            size: 256,
+            usage,
+            mapped_at_creation: false,
+        }
+    }
+
+    #[test]
+    fn alias_textures() {
+        let mut handles = SlotMap::<TextureHandle, ()>::with_key();
+        let [a, b, c, d] = [(); 4].map(|_| handles.insert(()));
+
+        let mut allocator = ResourceAllocator::<TextureHandle, Texture>::new();
+        let mut modified = BTreeSet::new();
+
+        let desc = texture(TextureFormat::Rgba8Unorm, TextureUsages::RENDER_ATTACHMENT);
+        allocator
+            .assign_buckets(
+                [
+                    (a, desc.clone(), Some(Lifetime::new(0, 2))),
+                    (b, desc.clone(), Some(Lifetime::new(2, 4))),
+                    // overlaps both `a` and `b`
+                    (c, desc.clone(), Some(Lifetime::new(1, 3))),
+                    // does not overlap `c`, but has a different format
+                    (
+                        d,
+                        texture(TextureFormat::Rgba16Float, TextureUsages::RENDER_ATTACHMENT),
+                        Some(Lifetime::new(3, 4)),
+                    ),
+                ]
+                .into_iter(),
+                &mut modified,
+            )
+            .unwrap();
+
+        assert_eq!(allocator.buckets.len(), 3);
+        assert_eq!(allocator.bucket_map[a], allocator.bucket_map[b]);
+        assert_ne!(allocator.bucket_map[a], allocator.bucket_map[c]);
+        assert_ne!(allocator.bucket_map[a], allocator.bucket_map[d]);
+        assert_ne!(allocator.bucket_map[c], allocator.bucket_map[d]);
+    }
+
+    #[test]
+    fn untracked_lifetimes_not_aliased() {
+        let mut handles = SlotMap::<TextureHandle, ()>::with_key();
+        let [a, b] = [(); 2].map(|_| handles.insert(()));
+
+        let mut allocator = ResourceAllocator::<TextureHandle, Texture>::new();
+
+        let desc = texture(TextureFormat::Rgba8Unorm, TextureUsages::RENDER_ATTACHMENT);
+        allocator
+            .assign_buckets(
+                [
+                    (a, desc.clone(), None),
+                    (b, desc, Some(Lifetime::new(0, 1))),
+                ]
+                .into_iter(),
+                &mut BTreeSet::new(),
+            )
+            .unwrap();
+
+        assert_ne!(allocator.bucket_map[a], allocator.bucket_map[b]);
+    }
+
+    #[test]
+    fn merged_usages() {
+        let mut handles = SlotMap::<TextureHandle, ()>::with_key();
+        let [a, b] = [(); 2].map(|_| handles.insert(()));
+
+        let mut allocator = ResourceAllocator::<TextureHandle, Texture>::new();
+
+        allocator
+            .assign_buckets(
+                [
+                    (
+                        a,
+                        texture(TextureFormat::Rgba8Unorm, TextureUsages::RENDER_ATTACHMENT),
+                        Some(Lifetime::new(0, 1)),
+                    ),
+                    (
+                        b,
+                        texture(TextureFormat::Rgba8Unorm, TextureUsages::TEXTURE_BINDING),
+                        Some(Lifetime::new(1, 2)),
+                    ),
+                ]
+                .into_iter(),
+                &mut BTreeSet::new(),
+            )
+            .unwrap();
+
+        let expected = TextureUsages::RENDER_ATTACHMENT | TextureUsages::TEXTURE_BINDING;
+
+        assert_eq!(allocator.buckets.len(), 1);
+        assert_eq!(allocator.buckets[0].desc.usage, expected);
+        assert_eq!(allocator.allocated_desc[a].usage, expected);
+        assert_eq!(allocator.allocated_desc[b].usage, expected);
+    }
+
+    #[test]
+    fn mappable_buffers_not_merged() {
+        let mut handles = SlotMap::<BufferHandle, ()>::with_key();
+        let [a, b, c] = [(); 3].map(|_| handles.insert(()));
+
+        let mut allocator = ResourceAllocator::<BufferHandle, Buffer>::new();
+
+        allocator
+            .assign_buckets(
+                [
+                    (
+                        a,
+                        buffer(BufferUsages::MAP_READ | BufferUsages::COPY_DST),
+                        Some(Lifetime::new(0, 1)),
+                    ),
+                    (
+                        b,
+                        buffer(BufferUsages::STORAGE | BufferUsages::COPY_SRC),
+                        Some(Lifetime::new(1, 2)),
+                    ),
+                    (
+                        c,
+                        buffer(BufferUsages::UNIFORM | BufferUsages::COPY_DST),
+                        Some(Lifetime::new(2, 3)),
+                    ),
+                ]
+                .into_iter(),
+                &mut BTreeSet::new(),
+            )
+            .unwrap();
+
+        assert_ne!(allocator.bucket_map[a], allocator.bucket_map[b]);
+        assert_eq!(allocator.bucket_map[b], allocator.bucket_map[c]);
+        assert_eq!(
+            allocator.allocated_desc[c].usage,
+            BufferUsages::STORAGE
+                | BufferUsages::UNIFORM
+                | BufferUsages::COPY_SRC
+                | BufferUsages::COPY_DST
+        );
+    }
+}
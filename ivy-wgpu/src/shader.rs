@@ -1,6 +1,6 @@
 use std::{borrow::Cow, collections::BTreeMap};
 
-use wgpu::Face;
+use wgpu::{BlendState, Face};
 
 /// Represents a shader
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
@@ -10,6 +10,11 @@ pub struct ShaderPass {
     pub label: Cow<'static, str>,
     pub source: Cow<'static, str>,
     pub cull_mode: Option<Face>,
+    /// Blend state used when the material is drawn in a sorted transparent pass, `None`
+    /// overwrites the target.
+    ///
+    /// Opaque passes always overwrite the target.
+    pub blend: Option<BlendState>,
     pub shader_defs: BTreeMap<String, ShaderValue>,
 }
 
@@ -43,6 +48,7 @@ impl ShaderPass {
             label: label.into(),
             source: source.into(),
             cull_mode: None,
+            blend: Some(BlendState::ALPHA_BLENDING),
             shader_defs: shader_defs.into_iter().collect(),
         }
     }
@@ -53,6 +59,12 @@ impl ShaderPass {
         self
     }
 
+    /// Set the blend state
+    pub fn with_blend(mut self, blend: Option<BlendState>) -> Self {
+        self.blend = blend;
+        self
+    }
+
     pub fn source(&self) -> &str {
         &self.source
     }
@@ -1,12 +1,21 @@
-use std::{borrow::Cow, collections::HashMap};
+use std::{
+    borrow::Cow,
+    collections::{BTreeMap, HashMap},
+    path::{Path, PathBuf},
+    time::{Duration, Instant, SystemTime},
+};
 
 use anyhow::Context;
+use ivy_core::notifications::{Notification, Notifier};
 use ivy_wgpu_types::Gpu;
 use naga_oil::compose::{Composer, ShaderDefValue};
 use parking_lot::Mutex;
-use wgpu::{ShaderModule, ShaderModuleDescriptor, ShaderSource};
+use wgpu::{naga, ShaderModule, ShaderModuleDescriptor, ShaderSource};
 
-use crate::shader::ShaderPass;
+use crate::{events::ShaderReloaded, shader::ShaderPass};
+
+/// Minimum interval between checking the watched shaders for changes
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
 
 pub struct ShaderModuleDesc<'a> {
     pub path: &'a str,
@@ -28,18 +37,97 @@ impl<'a> From<&'a ShaderPass> for ShaderModuleDesc<'a> {
     }
 }
 
+struct WatchedFile {
+    modified: Option<SystemTime>,
+    /// Shader defs of composable modules, which are re-added to the composer when changed
+    module_defs: Option<HashMap<String, ShaderDefValue>>,
+}
+
+impl WatchedFile {
+    fn new(path: &Path, module_defs: Option<HashMap<String, ShaderDefValue>>) -> Self {
+        Self {
+            modified: modified_time(path),
+            module_defs,
+        }
+    }
+}
+
+struct ShaderWatcher {
+    /// Shader paths are relative to the asset root
+    root: PathBuf,
+    files: BTreeMap<String, WatchedFile>,
+    /// Sources reloaded from disk, which take precedence over the embedded source
+    overrides: HashMap<String, String>,
+    subscribers: Vec<flume::Sender<ShaderReloaded>>,
+    last_poll: Option<Instant>,
+}
+
+impl Default for ShaderWatcher {
+    fn default() -> Self {
+        Self {
+            root: PathBuf::from("assets"),
+            files: Default::default(),
+            overrides: Default::default(),
+            subscribers: Default::default(),
+            last_poll: None,
+        }
+    }
+}
+
+impl ShaderWatcher {
+    fn watch(&mut self, path: &str, module_defs: Option<HashMap<String, ShaderDefValue>>) {
+        let file = WatchedFile::new(&self.root.join(path), module_defs);
+        self.files.insert(path.into(), file);
+    }
+}
+
 pub struct ShaderLibrary {
     composer: Mutex<Composer>,
+    watcher: Mutex<ShaderWatcher>,
+    notifier: Option<Notifier>,
 }
 
 impl ShaderLibrary {
     pub fn new() -> Self {
         Self {
             composer: Mutex::new(Composer::default()),
+            watcher: Default::default(),
+            notifier: None,
         }
     }
 
+    /// Show the shaders which fail to reload as notifications
+    pub fn with_notifier(mut self, notifier: Notifier) -> Self {
+        self.notifier = Some(notifier);
+        self
+    }
+
+    fn notify(&self, notification: Notification) {
+        if let Some(notifier) = &self.notifier {
+            notifier.notify(notification);
+        }
+    }
+
+    /// Set the asset root which the paths of the shaders are relative to.
+    ///
+    /// Defaults to `assets`, the same as the
+    /// [`FileSystemMapService`](ivy_assets::service::FileSystemMapService).
+    pub fn with_root(mut self, root: impl Into<PathBuf>) -> Self {
+        let watcher = self.watcher.get_mut();
+        watcher.root = root.into();
+
+        for (path, file) in &mut watcher.files {
+            file.modified = modified_time(&watcher.root.join(path));
+        }
+
+        self
+    }
+
     pub fn with_module(mut self, module: ShaderModuleDesc) -> Self {
+        self.watcher
+            .get_mut()
+            .watch(module.path, Some(module.shader_defs.clone()));
+
         match self.composer.get_mut().add_composable_module(
             naga_oil::compose::ComposableModuleDescriptor {
                 source: module.source,
@@ -60,29 +148,195 @@ impl ShaderLibrary {
     }
 
     pub fn process(&self, gpu: &Gpu, module: ShaderModuleDesc) -> anyhow::Result<ShaderModule> {
-        let naga_module = self
-            .composer
+        let path = module.path;
+        let naga_module = self.compose(module)?;
+
+        Ok(gpu.device.create_shader_module(ShaderModuleDescriptor {
+            source: ShaderSource::Naga(Cow::Owned(naga_module)),
+            label: Some(path),
+        }))
+    }
+
+    /// Resolves the imports of the module, e.g; to reflect on its bindings
+    pub fn compose(&self, module: ShaderModuleDesc) -> anyhow::Result<naga::Module> {
+        let source = {
+            let mut watcher = self.watcher.lock();
+            if !watcher.files.contains_key(module.path) {
+                watcher.watch(module.path, None);
+            }
+
+            watcher.overrides.get(module.path).cloned()
+        };
+
+        self.composer
             .lock()
             .make_naga_module(naga_oil::compose::NagaModuleDescriptor {
-                source: module.source,
+                source: source.as_deref().unwrap_or(module.source),
                 file_path: module.path,
                 shader_type: naga_oil::compose::ShaderType::Wgsl,
                 shader_defs: module.shader_defs,
                 ..Default::default()
             })
-            .with_context(|| {
-                anyhow::anyhow!("Failed to process shader module {:?}", module.path)
-            })?;
+            .with_context(|| anyhow::anyhow!("Failed to process shader module {:?}", module.path))
+    }
 
-        Ok(gpu.device.create_shader_module(ShaderModuleDescriptor {
-            source: ShaderSource::Naga(Cow::Owned(naga_module)),
-            label: Some(module.path),
-        }))
+    /// Receive a [`ShaderReloaded`] event each time watched shaders are reloaded from disk.
+    ///
+    /// Shaders created before the event should be processed again.
+    pub fn subscribe(&self) -> flume::Receiver<ShaderReloaded> {
+        let (tx, rx) = flume::unbounded();
+        self.watcher.lock().subscribers.push(tx);
+        rx
+    }
+
+    /// Reloads the changed shaders, checking at most once every [`POLL_INTERVAL`].
+    ///
+    /// Intended to be called each frame. Does nothing in release builds, which use the embedded
+    /// shaders.
+    pub fn poll_changes(&self) {
+        if !cfg!(debug_assertions) {
+            return;
+        }
+
+        {
+            let mut watcher = self.watcher.lock();
+            if watcher
+                .last_poll
+                .is_some_and(|v| v.elapsed() < POLL_INTERVAL)
+            {
+                return;
+            }
+
+            watcher.last_poll = Some(Instant::now());
+        }
+
+        self.reload_changed();
+    }
+
+    /// Re-reads the registered modules and processed shaders which were modified on disk.
+    ///
+    /// Returns `true` if any shader was reloaded.
+    pub fn reload_changed(&self) -> bool {
+        let mut watcher = self.watcher.lock();
+        let watcher = &mut *watcher;
+
+        let mut event = ShaderReloaded::default();
+
+        for (path, file) in &mut watcher.files {
+            let full_path = watcher.root.join(path);
+            let modified = modified_time(&full_path);
+            if modified.is_none() || modified == file.modified {
+                continue;
+            }
+
+            file.modified = modified;
+
+            let source = match std::fs::read_to_string(&full_path) {
+                Ok(v) => v,
+                Err(err) => {
+                    tracing::error!(path, "Failed to read shader: {err}");
+                    self.notify(Notification::error(format!(
+                        "Failed to read shader {path:?}: {err}"
+                    )));
+                    continue;
+                }
+            };
+
+            tracing::info!(path, "Reloading shader");
+
+            if let Some(shader_defs) = &file.module_defs {
+                if let Err(err) = self.composer.lock().add_composable_module(
+                    naga_oil::compose::ComposableModuleDescriptor {
+                        source: &source,
+                        file_path: path,
+                        shader_defs: shader_defs.clone(),
+                        ..Default::default()
+                    },
+                ) {
+                    tracing::error!(path, "Failed to reload module: {err:?}");
+                    self.notify(Notification::error(format!(
+                        "Failed to reload shader module {path:?}: {err}"
+                    )));
+                    continue;
+                }
+
+                event.modules.push(path.clone());
+            } else {
+                watcher.overrides.insert(path.clone(), source);
+                event.shaders.push(path.clone());
+            }
+        }
+
+        if event.modules.is_empty() && event.shaders.is_empty() {
+            return false;
+        }
+
+        watcher
+            .subscribers
+            .retain(|tx| tx.send(event.clone()).is_ok());
+
+        true
     }
 }
 
+fn modified_time(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|v| v.modified()).ok()
+}
+
 impl Default for ShaderLibrary {
     fn default() -> Self {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const SHADER: &str = "
+        @compute @workgroup_size(1)
+        fn main() {}
+    ";
+
+    #[test]
+    fn reload_changed() {
+        let root = std::env::temp_dir().join(format!("ivy-shaders-{}", std::process::id()));
+        std::fs::create_dir_all(root.join("shaders")).unwrap();
+        std::fs::write(root.join("shaders/test.wgsl"), SHADER).unwrap();
+
+        let library = ShaderLibrary::new().with_root(&root);
+        let reloaded = library.subscribe();
+
+        let desc = || ShaderModuleDesc {
+            path: "shaders/test.wgsl",
+            source: SHADER,
+            shader_defs: Default::default(),
+        };
+
+        let module = library.compose(desc()).unwrap();
+        assert_eq!(module.entry_points[0].name, "main");
+
+        assert!(!library.reload_changed());
+
+        // Ensure the modification time differs on file systems with a coarse resolution
+        std::thread::sleep(Duration::from_millis(20));
+        std::fs::write(
+            root.join("shaders/test.wgsl"),
+            SHADER.replace("fn main", "fn reloaded"),
+        )
+        .unwrap();
+
+        assert!(library.reload_changed());
+
+        let event = reloaded.try_recv().unwrap();
+        assert_eq!(event.shaders, ["shaders/test.wgsl"]);
+        assert!(event.affects("shaders/test.wgsl"));
+        assert!(!event.affects("shaders/other.wgsl"));
+
+        // The source on disk takes precedence over the embedded source
+        let module = library.compose(desc()).unwrap();
+        assert_eq!(module.entry_points[0].name, "reloaded");
+
+        std::fs::remove_dir_all(root).ok();
+    }
+}
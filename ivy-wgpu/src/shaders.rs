@@ -1,14 +1,22 @@
 use std::convert::Infallible;
 
 use ivy_assets::{Asset, AssetCache, AssetDesc};
-use wgpu::Face;
+use wgpu::{BlendComponent, BlendFactor, BlendOperation, BlendState, Face};
 
 use crate::shader::{ShaderPass, ShaderValue};
 
+/// Deduplicates user provided shaders, such as those of custom materials
+impl AssetDesc<ShaderPass> for ShaderPass {
+    type Error = Infallible;
+
+    fn create(&self, assets: &AssetCache) -> Result<Asset<ShaderPass>, Self::Error> {
+        Ok(assets.insert(self.clone()))
+    }
+}
+
 /// Loads the default PBR shader
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct PbrShaderDesc {
-    pub skinned: bool,
     pub lit: bool,
 }
 
@@ -18,25 +26,21 @@ impl AssetDesc<ShaderPass> for PbrShaderDesc {
     fn create(&self, assets: &ivy_assets::AssetCache) -> Result<Asset<ShaderPass>, Self::Error> {
         Ok(assets.insert(ShaderPass {
             label: "pbr_shader".into(),
-            path: "pbr.wgsl".into(),
+            path: "shaders/pbr.wgsl".into(),
             source: include_str!("../../assets/shaders/pbr.wgsl").into(),
             cull_mode: Some(Face::Back),
-            shader_defs: [
-                self.skinned
-                    .then(|| ("SKINNED".into(), ShaderValue::Bool(true))),
-                self.lit.then(|| ("LIT".into(), ShaderValue::Bool(true))),
-            ]
-            .into_iter()
-            .flatten()
-            .collect(),
+            blend: Some(BlendState::ALPHA_BLENDING),
+            shader_defs: self
+                .lit
+                .then(|| ("LIT".into(), ShaderValue::Bool(true)))
+                .into_iter()
+                .collect(),
         }))
     }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
-pub struct ShadowShaderDesc {
-    pub skinned: bool,
-}
+pub struct ShadowShaderDesc;
 
 impl AssetDesc<ShaderPass> for ShadowShaderDesc {
     type Error = Infallible;
@@ -44,15 +48,11 @@ impl AssetDesc<ShaderPass> for ShadowShaderDesc {
     fn create(&self, assets: &ivy_assets::AssetCache) -> Result<Asset<ShaderPass>, Self::Error> {
         Ok(assets.insert(ShaderPass {
             label: "shadow_shader".into(),
-            path: "../../assets/shaders/shadow.wgsl".into(),
+            path: "shaders/shadow.wgsl".into(),
             source: include_str!("../../assets/shaders/shadow.wgsl").into(),
             cull_mode: Some(Face::Back),
-            shader_defs: [self
-                .skinned
-                .then(|| ("SKINNED".into(), ShaderValue::Bool(true)))]
-            .into_iter()
-            .flatten()
-            .collect(),
+            blend: Some(BlendState::ALPHA_BLENDING),
+            shader_defs: Default::default(),
         }))
     }
 }
@@ -60,7 +60,6 @@ impl AssetDesc<ShaderPass> for ShadowShaderDesc {
 /// Emissive textured pbr material
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct PbrEmissiveShaderDesc {
-    pub skinned: bool,
     pub lit: bool,
 }
 
@@ -70,17 +69,62 @@ impl AssetDesc<ShaderPass> for PbrEmissiveShaderDesc {
     fn create(&self, assets: &AssetCache) -> Result<Asset<ShaderPass>, Self::Error> {
         Ok(assets.insert(ShaderPass {
             label: "pbr_emissive_shader".into(),
-            path: "pbr_emissive.wgsl".into(),
+            path: "shaders/pbr_emissive.wgsl".into(),
             source: include_str!("../../assets/shaders/pbr_emissive.wgsl").into(),
             cull_mode: Some(Face::Back),
-            shader_defs: [
-                self.skinned
-                    .then(|| ("SKINNED".into(), ShaderValue::Bool(true))),
-                self.lit.then(|| ("LIT".into(), ShaderValue::Bool(true))),
-            ]
-            .into_iter()
-            .flatten()
-            .collect(),
+            blend: Some(BlendState::ALPHA_BLENDING),
+            shader_defs: self
+                .lit
+                .then(|| ("LIT".into(), ShaderValue::Bool(true)))
+                .into_iter()
+                .collect(),
+        }))
+    }
+}
+
+/// Visualizes overdraw for [`DebugView::Overdraw`](crate::debug_view::DebugView::Overdraw)
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct DebugOverdrawShaderDesc;
+
+impl AssetDesc<ShaderPass> for DebugOverdrawShaderDesc {
+    type Error = Infallible;
+
+    fn create(&self, assets: &AssetCache) -> Result<Asset<ShaderPass>, Self::Error> {
+        let additive = BlendComponent {
+            src_factor: BlendFactor::One,
+            dst_factor: BlendFactor::One,
+            operation: BlendOperation::Add,
+        };
+
+        Ok(assets.insert(ShaderPass {
+            label: "debug_overdraw_shader".into(),
+            path: "shaders/debug_overdraw.wgsl".into(),
+            source: include_str!("../../assets/shaders/debug_overdraw.wgsl").into(),
+            cull_mode: None,
+            blend: Some(BlendState {
+                color: additive,
+                alpha: additive,
+            }),
+            shader_defs: Default::default(),
+        }))
+    }
+}
+
+/// Writes the object index of each surface, used for picking
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ObjectIdShaderDesc;
+
+impl AssetDesc<ShaderPass> for ObjectIdShaderDesc {
+    type Error = Infallible;
+
+    fn create(&self, assets: &AssetCache) -> Result<Asset<ShaderPass>, Self::Error> {
+        Ok(assets.insert(ShaderPass {
+            label: "object_id_shader".into(),
+            path: "shaders/object_id.wgsl".into(),
+            source: include_str!("../../assets/shaders/object_id.wgsl").into(),
+            cull_mode: Some(Face::Back),
+            blend: None,
+            shader_defs: Default::default(),
         }))
     }
 }
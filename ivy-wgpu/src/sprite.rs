@@ -0,0 +1,243 @@
+use glam::{vec2, Vec2};
+use ivy_assets::Asset;
+use ivy_core::{Color, ColorExt};
+use wgpu::Texture;
+
+/// Rectangle of a texture, in normalized texture coordinates
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SpriteRegion {
+    pub min: Vec2,
+    pub max: Vec2,
+}
+
+impl SpriteRegion {
+    /// The whole texture
+    pub const FULL: Self = Self {
+        min: Vec2::ZERO,
+        max: Vec2::ONE,
+    };
+
+    pub fn new(min: Vec2, max: Vec2) -> Self {
+        Self { min, max }
+    }
+
+    /// Region of the cell at `index` of a grid, counted row by row from the top left
+    pub fn grid_cell(columns: u32, rows: u32, index: u32) -> Self {
+        let columns = columns.max(1);
+        let rows = rows.max(1);
+
+        let index = index % (columns * rows);
+        let cell_size = vec2(1.0 / columns as f32, 1.0 / rows as f32);
+        let min = vec2((index % columns) as f32, (index / columns) as f32) * cell_size;
+
+        Self::new(min, min + cell_size)
+    }
+
+    /// Texture coordinates of the bottom left and top right corners of a quad showing the region
+    pub fn tex_coords(&self, flip_x: bool, flip_y: bool) -> (Vec2, Vec2) {
+        // Texture coordinates grow downwards
+        let mut bottom_left = vec2(self.min.x, self.max.y);
+        let mut top_right = vec2(self.max.x, self.min.y);
+
+        if flip_x {
+            std::mem::swap(&mut bottom_left.x, &mut top_right.x);
+        }
+
+        if flip_y {
+            std::mem::swap(&mut bottom_left.y, &mut top_right.y);
+        }
+
+        (bottom_left, top_right)
+    }
+}
+
+impl Default for SpriteRegion {
+    fn default() -> Self {
+        Self::FULL
+    }
+}
+
+/// A grid of equally sized sprites packed into a single texture.
+///
+/// Sprites sharing a texture are drawn in the same batch.
+#[derive(Debug, Clone)]
+pub struct TextureAtlas {
+    pub texture: Asset<Texture>,
+    pub columns: u32,
+    pub rows: u32,
+}
+
+impl TextureAtlas {
+    pub fn new(texture: Asset<Texture>, columns: u32, rows: u32) -> Self {
+        Self {
+            texture,
+            columns: columns.max(1),
+            rows: rows.max(1),
+        }
+    }
+
+    pub fn cell_count(&self) -> u32 {
+        self.columns * self.rows
+    }
+
+    /// Region of the cell at `index`, counted row by row from the top left
+    pub fn region(&self, index: u32) -> SpriteRegion {
+        SpriteRegion::grid_cell(self.columns, self.rows, index)
+    }
+
+    /// Creates a sprite showing the cell at `index`
+    pub fn sprite(&self, index: u32) -> Sprite {
+        Sprite::new(self.texture.clone()).with_region(self.region(index))
+    }
+}
+
+/// A textured quad drawn by the [`SpriteRenderer`](crate::renderer::sprite_renderer::SpriteRenderer).
+///
+/// The quad lies in the XY plane of the entity and faces +Z.
+#[derive(Debug, Clone)]
+pub struct Sprite {
+    pub texture: Asset<Texture>,
+    pub region: SpriteRegion,
+    /// Size of the quad in world units, before the scale of the transform
+    pub size: Vec2,
+    /// Point of the quad placed at the position of the entity, from `(0, 0)` at the bottom left
+    /// to `(1, 1)` at the top right
+    pub anchor: Vec2,
+    pub color: Color,
+    pub flip_x: bool,
+    pub flip_y: bool,
+    /// Rotation around the facing axis, in radians
+    pub rotation: f32,
+    /// Sprites on higher layers are drawn over lower layers regardless of their distance to the
+    /// camera. Opaque geometry still occludes sprites
+    pub layer: i32,
+    /// Turn the quad to face the camera, ignoring the rotation of the transform
+    pub billboard: bool,
+    /// Sample the texture without filtering, keeping pixel art crisp
+    pub pixelated: bool,
+}
+
+impl Sprite {
+    pub fn new(texture: Asset<Texture>) -> Self {
+        Self {
+            texture,
+            region: SpriteRegion::FULL,
+            size: Vec2::ONE,
+            anchor: Vec2::splat(0.5),
+            color: Color::white(),
+            flip_x: false,
+            flip_y: false,
+            rotation: 0.0,
+            layer: 0,
+            billboard: false,
+            pixelated: false,
+        }
+    }
+
+    /// Set the region
+    pub fn with_region(mut self, region: SpriteRegion) -> Self {
+        self.region = region;
+        self
+    }
+
+    /// Set the size
+    pub fn with_size(mut self, size: Vec2) -> Self {
+        self.size = size;
+        self
+    }
+
+    /// Set the anchor
+    pub fn with_anchor(mut self, anchor: Vec2) -> Self {
+        self.anchor = anchor;
+        self
+    }
+
+    /// Set the color
+    pub fn with_color(mut self, color: Color) -> Self {
+        self.color = color;
+        self
+    }
+
+    /// Set whether the sprite is mirrored horizontally and vertically
+    pub fn with_flip(mut self, flip_x: bool, flip_y: bool) -> Self {
+        self.flip_x = flip_x;
+        self.flip_y = flip_y;
+        self
+    }
+
+    /// Set the rotation
+    pub fn with_rotation(mut self, rotation: f32) -> Self {
+        self.rotation = rotation;
+        self
+    }
+
+    /// Set the sorting layer
+    pub fn with_layer(mut self, layer: i32) -> Self {
+        self.layer = layer;
+        self
+    }
+
+    /// Set whether the sprite faces the camera
+    pub fn with_billboard(mut self, billboard: bool) -> Self {
+        self.billboard = billboard;
+        self
+    }
+
+    /// Set whether the texture is sampled without filtering
+    pub fn with_pixelated(mut self, pixelated: bool) -> Self {
+        self.pixelated = pixelated;
+        self
+    }
+
+    /// Texture coordinates of the bottom left and top right corners of the quad
+    pub(crate) fn tex_coords(&self) -> (Vec2, Vec2) {
+        self.region.tex_coords(self.flip_x, self.flip_y)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn grid_cells() {
+        assert_eq!(
+            SpriteRegion::grid_cell(4, 2, 0),
+            SpriteRegion::new(Vec2::ZERO, vec2(0.25, 0.5))
+        );
+        assert_eq!(
+            SpriteRegion::grid_cell(4, 2, 5),
+            SpriteRegion::new(vec2(0.25, 0.5), vec2(0.5, 1.0))
+        );
+
+        // Indices wrap around the grid
+        assert_eq!(
+            SpriteRegion::grid_cell(4, 2, 9),
+            SpriteRegion::grid_cell(4, 2, 1)
+        );
+
+        assert_eq!(SpriteRegion::grid_cell(0, 0, 3), SpriteRegion::FULL);
+    }
+
+    #[test]
+    fn tex_coords() {
+        let region = SpriteRegion::new(vec2(0.25, 0.5), vec2(0.5, 1.0));
+
+        assert_eq!(
+            region.tex_coords(false, false),
+            (vec2(0.25, 1.0), vec2(0.5, 0.5))
+        );
+        assert_eq!(
+            region.tex_coords(true, false),
+            (vec2(0.5, 1.0), vec2(0.25, 0.5))
+        );
+        assert_eq!(
+            region.tex_coords(false, true),
+            (vec2(0.25, 0.5), vec2(0.5, 1.0))
+        );
+        assert_eq!(
+            region.tex_coords(true, true),
+            (vec2(0.5, 0.5), vec2(0.25, 1.0))
+        );
+    }
+}
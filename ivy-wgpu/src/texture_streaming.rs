@@ -0,0 +1,580 @@
+use std::{
+    collections::HashMap,
+    hash::Hash,
+    sync::{
+        atomic::{AtomicBool, AtomicU32, Ordering},
+        Arc, OnceLock,
+    },
+};
+
+use image::{DynamicImage, GenericImageView};
+use ivy_assets::{service::Service, Asset, AssetCache};
+use ivy_core::{
+    memory::TrackedMemory,
+    notifications::{notify, Notification},
+    profiling::profile_function,
+};
+use ivy_graphics::texture::TextureData;
+use ivy_wgpu_types::texture::{
+    max_mip_levels, texture_from_image, texture_from_mips, TextureFromImageDesc,
+};
+use parking_lot::Mutex;
+use wgpu::{Texture, TextureFormat, TextureUsages};
+
+use crate::{
+    rendergraph::{Dependency, Node, NodeExecutionContext, NodeUpdateContext, UpdateResult},
+    texture::TextureWithFormatDesc,
+    Gpu,
+};
+
+/// Configures the [`TextureStreamer`]
+#[derive(Debug, Clone)]
+pub struct TextureStreamingConfig {
+    /// Maximum GPU memory used by streamed textures, in bytes
+    pub budget: u64,
+    /// Objects closer than this use the full resolution of their textures.
+    ///
+    /// Each doubling of the distance drops one mip level.
+    pub full_detail_distance: f32,
+    /// Resolution of the lowest streamed level, which is loaded first
+    pub min_size: u32,
+}
+
+impl Default for TextureStreamingConfig {
+    fn default() -> Self {
+        Self {
+            budget: 512 * 1024 * 1024,
+            full_detail_distance: 8.0,
+            min_size: 64,
+        }
+    }
+}
+
+struct Resident {
+    texture: Asset<Texture>,
+    /// Number of mip levels dropped from the full resolution, `None` while only the fallback is
+    /// resident
+    lod: Option<u32>,
+}
+
+struct StreamedTextureInner {
+    label: String,
+    data: TextureData,
+    format: TextureFormat,
+    /// Full resolution of the texture, known once the first level has loaded.
+    ///
+    /// The image itself is only kept while a level is being loaded.
+    dimensions: OnceLock<(u32, u32)>,
+    resident: Mutex<Resident>,
+    /// Incremented each time the resident texture is replaced
+    generation: AtomicU32,
+    /// Closest distance requested since the last update, as `f32` bits.
+    ///
+    /// Positive floats order the same as their bits, which allows an atomic minimum.
+    requested_distance: AtomicU32,
+    loading: AtomicBool,
+}
+
+/// Texture whose resolution is adjusted by the [`TextureStreamer`] to the distance of the objects
+/// using it.
+///
+/// The resident texture is replaced when streaming, so bindings must be recreated once the
+/// [`generation`](Self::generation) changes.
+#[derive(Clone)]
+pub struct StreamedTexture {
+    inner: Arc<StreamedTextureInner>,
+}
+
+impl StreamedTexture {
+    /// The currently resident texture
+    pub fn texture(&self) -> Asset<Texture> {
+        self.inner.resident.lock().texture.clone()
+    }
+
+    pub fn generation(&self) -> u32 {
+        self.inner.generation.load(Ordering::Acquire)
+    }
+
+    /// Mip levels dropped from the full resolution
+    pub fn resident_lod(&self) -> Option<u32> {
+        self.inner.resident.lock().lod
+    }
+
+    /// Request detail for an object at `distance` from the camera until the next update
+    pub fn request(&self, distance: f32) {
+        self.inner
+            .requested_distance
+            .fetch_min(distance.max(0.0).to_bits(), Ordering::Relaxed);
+    }
+
+    pub fn label(&self) -> &str {
+        &self.inner.label
+    }
+
+    fn take_requested_distance(&self) -> f32 {
+        f32::from_bits(
+            self.inner
+                .requested_distance
+                .swap(f32::INFINITY.to_bits(), Ordering::Relaxed),
+        )
+    }
+
+    fn replace(&self, texture: Asset<Texture>, lod: u32) {
+        *self.inner.resident.lock() = Resident {
+            texture,
+            lod: Some(lod),
+        };
+
+        self.inner.generation.fetch_add(1, Ordering::Release);
+    }
+}
+
+impl std::fmt::Debug for StreamedTexture {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("StreamedTexture")
+            .field("label", &self.inner.label)
+            .field("format", &self.inner.format)
+            .field("lod", &self.resident_lod())
+            .finish()
+    }
+}
+
+impl PartialEq for StreamedTexture {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.inner, &other.inner)
+    }
+}
+
+impl Eq for StreamedTexture {}
+
+impl Hash for StreamedTexture {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        Arc::as_ptr(&self.inner).hash(state)
+    }
+}
+
+struct LoadedLevel {
+    texture: StreamedTexture,
+    lod: u32,
+    image: anyhow::Result<DynamicImage>,
+}
+
+/// Streams the mip levels of material textures in and out depending on camera distance, within a
+/// memory budget.
+///
+/// Textures start out as a small fallback while their lowest level is loaded in the background,
+/// after which more detailed levels are streamed in as objects approach the camera. Levels are
+/// released from the textures farthest away first when the budget is exceeded.
+///
+/// Registered as a service, and updated by the [`TextureStreamingNode`].
+pub struct TextureStreamer {
+    config: TextureStreamingConfig,
+    textures: Mutex<HashMap<(TextureData, TextureFormat), StreamedTexture>>,
+    loaded_tx: flume::Sender<LoadedLevel>,
+    loaded_rx: flume::Receiver<LoadedLevel>,
+    memory: Mutex<TrackedMemory>,
+}
+
+impl Service for TextureStreamer {}
+
+impl TextureStreamer {
+    pub fn new(config: TextureStreamingConfig) -> Self {
+        let (loaded_tx, loaded_rx) = flume::unbounded();
+
+        Self {
+            config,
+            textures: Default::default(),
+            loaded_tx,
+            loaded_rx,
+            memory: Mutex::new(TrackedMemory::new("streamed_textures", 0)),
+        }
+    }
+
+    /// Returns the streamed texture for `data`, which is `fallback` until the first level has
+    /// loaded
+    pub fn load(
+        &self,
+        assets: &AssetCache,
+        data: TextureData,
+        format: TextureFormat,
+        fallback: Asset<Texture>,
+    ) -> StreamedTexture {
+        let mut textures = self.textures.lock();
+        if let Some(texture) = textures.get(&(data.clone(), format)) {
+            return texture.clone();
+        }
+
+        let texture = StreamedTexture {
+            inner: Arc::new(StreamedTextureInner {
+                label: data.label(),
+                data: data.clone(),
+                format,
+                dimensions: OnceLock::new(),
+                resident: Mutex::new(Resident {
+                    texture: fallback,
+                    lod: None,
+                }),
+                generation: AtomicU32::new(0),
+                requested_distance: AtomicU32::new(f32::INFINITY.to_bits()),
+                loading: AtomicBool::new(false),
+            }),
+        };
+
+        self.stream_in(assets, &texture, None);
+        textures.insert((data, format), texture.clone());
+
+        texture
+    }
+
+    /// Memory used by the resident levels of all streamed textures
+    pub fn resident_bytes(&self) -> u64 {
+        self.textures
+            .lock()
+            .values()
+            .map(|v| {
+                let resident = v.inner.resident.lock();
+                mip_chain_bytes(&resident.texture)
+            })
+            .sum()
+    }
+
+    /// Uploads loaded levels and adjusts the resident level of each texture to the requested
+    /// distances
+    pub fn update(&self, gpu: &Gpu, assets: &AssetCache) {
+        profile_function!();
+
+        // Release the textures which are no longer used by any material
+        self.textures
+            .lock()
+            .retain(|_, v| Arc::strong_count(&v.inner) > 1);
+
+        for loaded in self.loaded_rx.drain() {
+            loaded.texture.inner.loading.store(false, Ordering::Release);
+
+            let image = match loaded.image {
+                Ok(v) => v,
+                Err(err) => {
+                    let err = err.context(format!(
+                        "Failed to stream texture {:?}",
+                        loaded.texture.label()
+                    ));
+                    tracing::error!("{err:?}");
+                    notify(assets, Notification::error(format!("{err:#}")));
+                    continue;
+                }
+            };
+
+            let texture = texture_from_image(
+                gpu,
+                &image,
+                TextureFromImageDesc {
+                    label: loaded.texture.label().to_string().into(),
+                    format: loaded.texture.inner.format,
+                    mip_level_count: None,
+                    usage: TextureUsages::TEXTURE_BINDING
+                        | TextureUsages::COPY_DST
+                        | TextureUsages::COPY_SRC,
+                    generate_mipmaps: true,
+                },
+            );
+
+            match texture {
+                Ok(texture) => loaded.texture.replace(assets.insert(texture), loaded.lod),
+                Err(err) => {
+                    tracing::error!("{err:?}");
+                    notify(assets, Notification::error(format!("{err:#}")));
+                }
+            }
+        }
+
+        let textures = self.textures.lock().values().cloned().collect::<Vec<_>>();
+
+        // Each request is kept with its texture, as loads finishing concurrently may add textures
+        // with known dimensions at any time
+        let (textures, mut requests): (Vec<_>, Vec<_>) = textures
+            .into_iter()
+            .filter_map(|texture| {
+                let distance = texture.take_requested_distance();
+                let &(width, height) = texture.inner.dimensions.get()?;
+                let max_lod = max_lod(width, height, self.config.min_size);
+
+                let request = LodRequest {
+                    distance,
+                    lod: distance_lod(distance, self.config.full_detail_distance, max_lod),
+                    max_lod,
+                    width,
+                    height,
+                    block_size: texture.inner.format.block_copy_size(None).unwrap_or(4),
+                };
+
+                Some((texture, request))
+            })
+            .unzip();
+
+        fit_budget(&mut requests, self.config.budget);
+
+        let resident_bytes = self.resident_bytes();
+        self.memory.lock().set(resident_bytes);
+        let over_budget = resident_bytes > self.config.budget;
+
+        let mut encoder = None;
+        for (texture, request) in textures.iter().zip(requests) {
+            let Some(resident_lod) = texture.resident_lod() else {
+                continue;
+            };
+
+            if request.lod < resident_lod && !texture.inner.loading.load(Ordering::Acquire) {
+                self.stream_in(assets, texture, Some(request.lod));
+            }
+            // Only release levels which are clearly no longer needed, to avoid streaming the same
+            // level in and out at the boundary
+            else if request.lod > resident_lod && (over_budget || request.lod > resident_lod + 1)
+            {
+                let encoder = encoder
+                    .get_or_insert_with(|| gpu.device.create_command_encoder(&Default::default()));
+
+                let current = texture.texture();
+                let output = texture_from_mips(
+                    gpu,
+                    encoder,
+                    &current,
+                    request.lod - resident_lod,
+                    texture.label(),
+                );
+
+                texture.replace(assets.insert(output), request.lod);
+            }
+        }
+
+        if let Some(encoder) = encoder {
+            gpu.queue.submit([encoder.finish()]);
+        }
+    }
+
+    /// Loads the level `lod` in the background, or the lowest level if `None`.
+    ///
+    /// The full resolution image is released once the level has been extracted from it.
+    fn stream_in(&self, assets: &AssetCache, texture: &StreamedTexture, lod: Option<u32>) {
+        texture.inner.loading.store(true, Ordering::Release);
+
+        let assets = assets.clone();
+        let texture = texture.clone();
+        let tx = self.loaded_tx.clone();
+        let min_size = self.config.min_size;
+
+        async_std::task::spawn_blocking(move || {
+            let image: anyhow::Result<Asset<DynamicImage>> = assets.try_load(&texture.inner.data);
+
+            let (lod, image) = match image {
+                Ok(image) => {
+                    let (width, height) = image.dimensions();
+                    texture.inner.dimensions.get_or_init(|| (width, height));
+
+                    let lod = lod.unwrap_or_else(|| max_lod(width, height, min_size));
+                    (lod, Ok(downsample(&image, lod)))
+                }
+                Err(err) => (0, Err(err)),
+            };
+
+            tx.send(LoadedLevel {
+                texture,
+                lod,
+                image,
+            })
+            .ok();
+        });
+    }
+}
+
+/// Streams the textures of the registered [`TextureStreamer`] each frame
+pub struct TextureStreamingNode;
+
+impl Node for TextureStreamingNode {
+    fn label(&self) -> &str {
+        "TextureStreamingNode"
+    }
+
+    fn update(&mut self, ctx: NodeUpdateContext) -> anyhow::Result<UpdateResult> {
+        if let Some(streamer) = ctx.assets.try_service::<TextureStreamer>() {
+            streamer.update(ctx.gpu, ctx.assets);
+        }
+
+        Ok(UpdateResult::Success)
+    }
+
+    fn draw(&mut self, _: NodeExecutionContext) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    fn on_resource_changed(&mut self, _: crate::rendergraph::ResourceHandle) {}
+
+    fn read_dependencies(&self) -> Vec<Dependency> {
+        vec![]
+    }
+
+    fn write_dependencies(&self) -> Vec<Dependency> {
+        vec![]
+    }
+}
+
+/// Loads a material texture, streamed if a [`TextureStreamer`] is registered.
+///
+/// Solid colors are never streamed. `fallback` is used until the first level is loaded.
+pub(crate) fn load_material_texture(
+    assets: &AssetCache,
+    data: &TextureData,
+    format: TextureFormat,
+    fallback: TextureData,
+    streamed: &mut Vec<(StreamedTexture, u32)>,
+) -> anyhow::Result<Asset<Texture>> {
+    let streamer = match data {
+        TextureData::Color(_) => None,
+        _ => assets.try_service::<TextureStreamer>(),
+    };
+
+    let Some(streamer) = streamer else {
+        return assets.try_load(&TextureWithFormatDesc::new(data.clone(), format));
+    };
+
+    let fallback = assets.try_load(&TextureWithFormatDesc::new(fallback, format))?;
+    let texture = streamer.load(assets, data.clone(), format, fallback);
+
+    // Read the generation first so that a concurrent replacement is detected as stale
+    let generation = texture.generation();
+    let resident = texture.texture();
+    streamed.push((texture, generation));
+    Ok(resident)
+}
+
+/// Extracts the level `lod` from the full resolution image.
+///
+/// Averages the covered pixels rather than filtering, as the low levels are far smaller than the
+/// source.
+fn downsample(image: &DynamicImage, lod: u32) -> DynamicImage {
+    if lod == 0 {
+        return image.clone();
+    }
+
+    let (width, height) = image.dimensions();
+    image.thumbnail_exact((width >> lod).max(1), (height >> lod).max(1))
+}
+
+fn max_lod(width: u32, height: u32, min_size: u32) -> u32 {
+    max_mip_levels(width, height).saturating_sub(max_mip_levels(min_size, min_size))
+}
+
+/// Mip level to use for an object at `distance`
+fn distance_lod(distance: f32, full_detail_distance: f32, max_lod: u32) -> u32 {
+    let lod = (distance / full_detail_distance).log2().floor().max(0.0);
+    (lod as u32).min(max_lod)
+}
+
+fn mip_chain_bytes(texture: &Texture) -> u64 {
+    let size = texture.size();
+    let block_size = texture.format().block_copy_size(None).unwrap_or(4);
+
+    (0..texture.mip_level_count())
+        .map(|level| {
+            let size = size.mip_level_size(level, texture.dimension());
+            size.width as u64 * size.height as u64 * block_size as u64
+        })
+        .sum()
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct LodRequest {
+    distance: f32,
+    lod: u32,
+    max_lod: u32,
+    width: u32,
+    height: u32,
+    block_size: u32,
+}
+
+impl LodRequest {
+    fn bytes(&self) -> u64 {
+        let width = (self.width >> self.lod).max(1) as u64;
+        let height = (self.height >> self.lod).max(1) as u64;
+
+        // The remaining mip levels add a third
+        width * height * self.block_size as u64 * 4 / 3
+    }
+}
+
+/// Drops levels from the farthest textures first until the requests fit within the budget
+fn fit_budget(requests: &mut [LodRequest], budget: u64) {
+    let mut total: u64 = requests.iter().map(|v| v.bytes()).sum();
+
+    let mut order = (0..requests.len()).collect::<Vec<_>>();
+    order.sort_by(|&a, &b| requests[b].distance.total_cmp(&requests[a].distance));
+
+    while total > budget {
+        let mut changed = false;
+        for &i in &order {
+            if total <= budget {
+                break;
+            }
+
+            let request = &mut requests[i];
+            if request.lod < request.max_lod {
+                total -= request.bytes();
+                request.lod += 1;
+                total += request.bytes();
+                changed = true;
+            }
+        }
+
+        if !changed {
+            break;
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn lod_from_distance() {
+        assert_eq!(distance_lod(0.0, 8.0, 4), 0);
+        assert_eq!(distance_lod(15.0, 8.0, 4), 0);
+        assert_eq!(distance_lod(16.0, 8.0, 4), 1);
+        assert_eq!(distance_lod(40.0, 8.0, 4), 2);
+        assert_eq!(distance_lod(f32::INFINITY, 8.0, 4), 4);
+
+        assert_eq!(max_lod(1024, 512, 64), 4);
+        assert_eq!(max_lod(32, 32, 64), 0);
+    }
+
+    #[test]
+    fn downsample_levels() {
+        let image = DynamicImage::new_rgba8(256, 64);
+        assert_eq!(downsample(&image, 0).dimensions(), (256, 64));
+        assert_eq!(downsample(&image, 2).dimensions(), (64, 16));
+        assert_eq!(downsample(&image, 8).dimensions(), (1, 1));
+    }
+
+    #[test]
+    fn budget_drops_farthest() {
+        let request = |distance| LodRequest {
+            distance,
+            lod: 0,
+            max_lod: 4,
+            width: 1024,
+            height: 1024,
+            block_size: 4,
+        };
+
+        let mut requests = [request(1.0), request(100.0)];
+        let full = requests[0].bytes();
+
+        fit_budget(&mut requests, full * 2);
+        assert_eq!((requests[0].lod, requests[1].lod), (0, 0));
+
+        fit_budget(&mut requests, full + full / 4);
+        assert_eq!((requests[0].lod, requests[1].lod), (0, 1));
+
+        fit_budget(&mut requests, 0);
+        assert_eq!((requests[0].lod, requests[1].lod), (4, 4));
+    }
+}
@@ -36,14 +36,17 @@
 
 pub use flax;
 pub use ivy_assets;
+pub use ivy_audio as audio;
 /// Rexports
 pub use ivy_core;
-pub use ivy_core::{components::*, App, Extent, Layer};
+pub use ivy_core::{components::*, App, Layer, LogicalExtent, PhysicalExtent};
 pub use ivy_game;
 pub use ivy_gltf;
 pub use ivy_graphics;
 pub use ivy_input as input;
 pub use ivy_input::InputState;
+pub use ivy_nav as nav;
+pub use ivy_net as net;
 pub use ivy_physics as physics;
 pub use ivy_physics::RigidBodyBundle;
 pub use ivy_postprocessing as postprocessing;
@@ -0,0 +1,392 @@
+//! Development tasks for the workspace, invoked through `cargo xtask`
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    process::{Child, Command, Stdio},
+    thread,
+    time::{Duration, Instant},
+};
+
+use anyhow::Context;
+use image::{Rgba, RgbaImage};
+
+const USAGE: &str = "\
+Usage: cargo xtask goldens [OPTIONS] [EXAMPLES]...
+
+Runs each example for a fixed number of frames and compares the last frame to the golden image in
+`tests/goldens`. Runs all examples if none are given.
+
+The examples are rendered headless on a virtual X display (Xvfb) using the software Vulkan driver
+of Mesa (lavapipe), so that the frames are the same regardless of the GPU of the machine.
+
+Options:
+    --bless              Overwrite the goldens with the captured frames
+    --hardware           Render on the current display and the default adapter instead
+    --release            Build and run the examples in release mode
+    --frames <N>         Number of frames to capture [default: 60]
+    --tolerance <N>      Maximum per channel difference of a matching pixel [default: 8]
+    --max-diff <RATIO>   Maximum ratio of mismatching pixels [default: 0.001]
+    --timeout <SECONDS>  Maximum time to wait for an example [default: 120]
+";
+
+struct GoldenArgs {
+    examples: Vec<String>,
+    bless: bool,
+    hardware: bool,
+    release: bool,
+    frames: u64,
+    tolerance: u8,
+    max_diff: f64,
+    timeout: Duration,
+}
+
+impl GoldenArgs {
+    fn parse(mut args: impl Iterator<Item = String>) -> anyhow::Result<Self> {
+        let mut result = Self {
+            examples: Vec::new(),
+            bless: false,
+            hardware: false,
+            release: false,
+            frames: 60,
+            tolerance: 8,
+            max_diff: 0.001,
+            timeout: Duration::from_secs(120),
+        };
+
+        while let Some(arg) = args.next() {
+            let mut value = || {
+                args.next()
+                    .with_context(|| format!("Missing value for {arg}"))
+            };
+
+            match arg.as_str() {
+                "--bless" => result.bless = true,
+                "--hardware" => result.hardware = true,
+                "--release" => result.release = true,
+                "--frames" => result.frames = value()?.parse()?,
+                "--tolerance" => result.tolerance = value()?.parse()?,
+                "--max-diff" => result.max_diff = value()?.parse()?,
+                "--timeout" => result.timeout = Duration::from_secs(value()?.parse()?),
+                v if v.starts_with('-') => anyhow::bail!("Unknown option {v}\n\n{USAGE}"),
+                v => result.examples.push(v.to_string()),
+            }
+        }
+
+        anyhow::ensure!(result.frames > 0, "At least one frame must be captured");
+
+        Ok(result)
+    }
+}
+
+fn workspace_root() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .parent()
+        .expect("xtask is inside the workspace")
+        .to_path_buf()
+}
+
+fn list_examples(root: &Path) -> anyhow::Result<Vec<String>> {
+    let mut examples = fs::read_dir(root.join("examples"))?
+        .map(|entry| {
+            let path = entry?.path();
+            let example = (path.extension().is_some_and(|v| v == "rs"))
+                .then(|| path.file_stem().map(|v| v.to_string_lossy().into_owned()))
+                .flatten();
+
+            anyhow::Ok(example)
+        })
+        .filter_map(Result::transpose)
+        .collect::<anyhow::Result<Vec<_>>>()?;
+
+    examples.sort();
+    Ok(examples)
+}
+
+/// Kills the process when dropped, so that it does not outlive a failed capture
+struct ChildProcess(Child);
+
+impl Drop for ChildProcess {
+    fn drop(&mut self) {
+        let _ = self.0.kill();
+        let _ = self.0.wait();
+    }
+}
+
+/// A virtual X display, shut down when dropped
+struct VirtualDisplay {
+    _process: ChildProcess,
+    display: String,
+}
+
+impl VirtualDisplay {
+    fn start() -> anyhow::Result<Self> {
+        let socket_path = |n: u32| PathBuf::from(format!("/tmp/.X11-unix/X{n}"));
+        let number = (99..)
+            .find(|&n| !socket_path(n).exists())
+            .context("No free display number")?;
+
+        let display = format!(":{number}");
+        let mut process = ChildProcess(
+            Command::new("Xvfb")
+                .args([&display, "-screen", "0", "1920x1080x24", "-nolisten", "tcp"])
+                .stdout(Stdio::null())
+                .stderr(Stdio::null())
+                .spawn()
+                .context("Failed to start Xvfb, install it or run with --hardware")?,
+        );
+
+        let start = Instant::now();
+        while !socket_path(number).exists() {
+            if let Some(status) = process.0.try_wait()? {
+                anyhow::bail!("Xvfb exited with {status}");
+            }
+
+            if start.elapsed() > Duration::from_secs(10) {
+                anyhow::bail!("Timed out waiting for Xvfb on {display}");
+            }
+
+            thread::sleep(Duration::from_millis(50));
+        }
+
+        Ok(Self {
+            _process: process,
+            display,
+        })
+    }
+}
+
+/// Runs the example until the last frame has been written, and returns it
+fn capture(
+    root: &Path,
+    args: &GoldenArgs,
+    display: Option<&VirtualDisplay>,
+    example: &str,
+    output_dir: &Path,
+) -> anyhow::Result<RgbaImage> {
+    if output_dir.exists() {
+        fs::remove_dir_all(output_dir)?;
+    }
+
+    let profile = if args.release { "release" } else { "debug" };
+    let binary = root
+        .join("target")
+        .join(profile)
+        .join("examples")
+        .join(example);
+
+    // Frames are written by the replay layer of the example, which steps the simulation by a
+    // fixed amount per frame to make the output independent of the frame rate
+    let mut command = Command::new(&binary);
+    command
+        .current_dir(root)
+        .env("IVY_REPLAY_DIR", output_dir)
+        .env("IVY_REPLAY_FRAMES", args.frames.to_string());
+
+    if let Some(display) = display {
+        command
+            .env("DISPLAY", &display.display)
+            .env_remove("WAYLAND_DISPLAY")
+            .env("WGPU_BACKEND", "vulkan")
+            .env("WGPU_ADAPTER_NAME", "llvmpipe");
+    }
+
+    let mut process = ChildProcess(
+        command
+            .spawn()
+            .with_context(|| format!("Failed to run {binary:?}"))?,
+    );
+
+    let last_frame = output_dir.join(format!("frame_{:06}.png", args.frames - 1));
+    let start = Instant::now();
+
+    loop {
+        // The frame may be partially written when first observed
+        if let Ok(image) = image::open(&last_frame) {
+            return Ok(image.to_rgba8());
+        }
+
+        if let Some(status) = process.0.try_wait()? {
+            anyhow::bail!("Example exited with {status} before capturing {last_frame:?}");
+        }
+
+        if start.elapsed() > args.timeout {
+            anyhow::bail!("Timed out waiting for {last_frame:?}");
+        }
+
+        thread::sleep(Duration::from_millis(100));
+    }
+}
+
+struct Comparison {
+    mismatched: usize,
+    ratio: f64,
+    diff: RgbaImage,
+}
+
+/// Compares the frames pixel by pixel, highlighting mismatching pixels in red over a dimmed copy
+/// of the golden
+fn compare(golden: &RgbaImage, frame: &RgbaImage, tolerance: u8) -> anyhow::Result<Comparison> {
+    anyhow::ensure!(
+        golden.dimensions() == frame.dimensions(),
+        "Frame size {:?} does not match the golden {:?}",
+        frame.dimensions(),
+        golden.dimensions()
+    );
+
+    let mut mismatched = 0;
+    let diff = RgbaImage::from_fn(golden.width(), golden.height(), |x, y| {
+        let a = golden.get_pixel(x, y);
+        let b = frame.get_pixel(x, y);
+
+        let max_diff = a.0.iter().zip(b.0).map(|(a, b)| a.abs_diff(b)).max();
+        if max_diff.unwrap_or_default() > tolerance {
+            mismatched += 1;
+            Rgba([255, 0, 0, 255])
+        } else {
+            let luma = (a[0] as u32 * 2 + a[1] as u32 * 5 + a[2] as u32) / 8;
+            let v = (luma / 3) as u8;
+            Rgba([v, v, v, 255])
+        }
+    });
+
+    let pixel_count = golden.width() as usize * golden.height() as usize;
+
+    Ok(Comparison {
+        mismatched,
+        ratio: mismatched as f64 / pixel_count.max(1) as f64,
+        diff,
+    })
+}
+
+fn check_example(
+    root: &Path,
+    args: &GoldenArgs,
+    display: Option<&VirtualDisplay>,
+    example: &str,
+) -> anyhow::Result<()> {
+    let output_dir = root.join("target").join("goldens").join(example);
+    let frame = capture(root, args, display, example, &output_dir)?;
+
+    let golden_path = root
+        .join("tests")
+        .join("goldens")
+        .join(format!("{example}.png"));
+
+    if args.bless {
+        fs::create_dir_all(golden_path.parent().unwrap())?;
+        frame
+            .save(&golden_path)
+            .with_context(|| format!("Failed to write golden {golden_path:?}"))?;
+
+        println!("{example}: blessed {golden_path:?}");
+        return Ok(());
+    }
+
+    if !golden_path.exists() {
+        let frame_path = output_dir.join("golden.png");
+        frame.save(&frame_path)?;
+
+        anyhow::bail!(
+            "Missing golden {golden_path:?}, run with --bless or copy the captured {frame_path:?}"
+        );
+    }
+
+    let golden = image::open(&golden_path)
+        .with_context(|| format!("Failed to read golden {golden_path:?}"))?
+        .to_rgba8();
+
+    let comparison = compare(&golden, &frame, args.tolerance)?;
+
+    if comparison.ratio > args.max_diff {
+        let diff_path = output_dir.join("diff.png");
+        comparison.diff.save(&diff_path)?;
+
+        anyhow::bail!(
+            "{} pixels ({:.3}%) differ from the golden, see {diff_path:?}",
+            comparison.mismatched,
+            comparison.ratio * 100.0,
+        );
+    }
+
+    println!(
+        "{example}: ok ({} mismatched pixels)",
+        comparison.mismatched
+    );
+
+    Ok(())
+}
+
+fn goldens(args: GoldenArgs) -> anyhow::Result<()> {
+    let root = workspace_root();
+
+    let examples = if args.examples.is_empty() {
+        list_examples(&root)?
+    } else {
+        args.examples.clone()
+    };
+
+    let mut build = Command::new(env!("CARGO"));
+    build.current_dir(&root).args(["build", "--examples"]);
+    if args.release {
+        build.arg("--release");
+    }
+
+    let status = build.status().context("Failed to build examples")?;
+    anyhow::ensure!(status.success(), "Building examples failed with {status}");
+
+    let display = if args.hardware {
+        None
+    } else {
+        Some(VirtualDisplay::start()?)
+    };
+
+    let mut failed = Vec::new();
+    for example in &examples {
+        if let Err(err) = check_example(&root, &args, display.as_ref(), example) {
+            eprintln!("{example}: {err:?}");
+            failed.push(example.as_str());
+        }
+    }
+
+    anyhow::ensure!(
+        failed.is_empty(),
+        "{} of {} examples failed: {}",
+        failed.len(),
+        examples.len(),
+        failed.join(", ")
+    );
+
+    Ok(())
+}
+
+fn main() -> anyhow::Result<()> {
+    let mut args = std::env::args().skip(1);
+
+    match args.next().as_deref() {
+        Some("goldens") => goldens(GoldenArgs::parse(args)?),
+        _ => {
+            eprintln!("{USAGE}");
+            std::process::exit(1);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn compare_tolerance() {
+        let golden = RgbaImage::from_pixel(4, 4, Rgba([100, 100, 100, 255]));
+        let mut frame = golden.clone();
+
+        frame.put_pixel(0, 0, Rgba([104, 100, 100, 255]));
+        frame.put_pixel(1, 0, Rgba([100, 120, 100, 255]));
+
+        let comparison = compare(&golden, &frame, 8).unwrap();
+        assert_eq!(comparison.mismatched, 1);
+        assert_eq!(comparison.ratio, 1.0 / 16.0);
+        assert_eq!(comparison.diff.get_pixel(1, 0), &Rgba([255, 0, 0, 255]));
+    }
+}